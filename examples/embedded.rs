@@ -0,0 +1,34 @@
+// Runs the tracker inside this process for a minute, printing what it
+// reports, then reads back today's numbers. Starting fails while the
+// focusdebt daemon runs, and `focusdebt stop` ends the minute early.
+
+use std::time::Duration;
+
+use focusdebt::api::{FocusTrackerHandle, StatsReader, TrackerOptions, TrackerUpdate};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = TrackerOptions::load().unwrap_or_default();
+    let tracker = FocusTrackerHandle::start(options)?;
+    let updates = tracker.subscribe();
+
+    let until = std::time::Instant::now() + Duration::from_secs(60);
+    while let Some(left) = until.checked_duration_since(std::time::Instant::now()) {
+        match updates.recv_timeout(left.min(Duration::from_secs(10))) {
+            Ok(TrackerUpdate::SessionStarted { app_name, window_title, .. }) => println!("now on {}: {}", app_name, window_title),
+            Ok(TrackerUpdate::SessionEnded(session)) => println!("saved {} ({}s)", session.app_name, session.duration.as_secs()),
+            Ok(TrackerUpdate::Switch(switch)) => println!("switched {} → {}", switch.from_app, switch.to_app),
+            Err(_) => {
+                let status = tracker.current_status();
+                println!("{} rows, {} switches so far", status.sessions, status.context_switches);
+            }
+        }
+    }
+
+    let summary = tracker.stop()?;
+    println!("{}: {} rows, {:.0}% focus", summary.session_name, summary.sessions, summary.efficiency());
+
+    let today = StatsReader::open()?.daily_stats(chrono::Utc::now())?;
+    println!("today: {}s in focus apps, {:.0}% efficiency, {} switches",
+        today.total_focus_time.as_secs(), today.focus_efficiency, today.context_switches);
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+/// Consecutive low-activity minutes before a stretch counts as passive (0 = off,
+/// until the config is loaded)
+static PASSIVE_AFTER_MINUTES: AtomicU32 = AtomicU32::new(0);
+/// Minute activity level (as f64 bits) below which a minute is low
+static PASSIVE_LEVEL_BITS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_passive_thresholds(minutes: u32, level: f64) {
+    PASSIVE_AFTER_MINUTES.store(minutes, Ordering::Relaxed);
+    PASSIVE_LEVEL_BITS.store(level.to_bits(), Ordering::Relaxed);
+}
+
+fn passive_thresholds() -> (u32, f64) {
+    (PASSIVE_AFTER_MINUTES.load(Ordering::Relaxed), f64::from_bits(PASSIVE_LEVEL_BITS.load(Ordering::Relaxed)))
+}
+
+/// Per-minute input activity for one focus session.
+///
+/// Each tracking tick records whether there was any keyboard or mouse input
+/// since the previous tick. A minute's level is the share of its ticks with
+/// input (0.0–1.0), and the session's activity is the mean of its minutes.
+/// Only the system idle time is ever read: no key codes, key contents or
+/// pointer positions are seen or stored.
+///
+/// A session stays in one window, so a run of low-activity minutes inside it
+/// is time spent watching rather than working (a CI run, a muted call). Runs
+/// of at least the passive threshold are counted as passive minutes.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityMeter {
+    minute: Option<i64>,
+    active_ticks: u32,
+    ticks: u32,
+    level_sum: f64,
+    minutes: u32,
+    low_run: u32,
+    passive_minutes: u32,
+    /// (minutes, level) to use instead of the configured thresholds
+    thresholds: Option<(u32, f64)>,
+}
+
+impl ActivityMeter {
+    /// A meter with its own passive thresholds rather than the config's
+    #[cfg(test)]
+    pub fn with_thresholds(minutes: u32, level: f64) -> Self {
+        ActivityMeter { thresholds: Some((minutes, level)), ..Self::default() }
+    }
+
+    pub fn record(&mut self, at: DateTime<Utc>, active: bool) {
+        let minute = at.timestamp().div_euclid(60);
+        if self.minute != Some(minute) {
+            self.close_minute();
+            self.minute = Some(minute);
+        }
+        self.ticks += 1;
+        if active {
+            self.active_ticks += 1;
+        }
+    }
+
+    fn close_minute(&mut self) {
+        if self.ticks > 0 {
+            let level = self.active_ticks as f64 / self.ticks as f64;
+            self.level_sum += level;
+            self.minutes += 1;
+
+            let (after, threshold) = self.thresholds.unwrap_or_else(passive_thresholds);
+            if after > 0 && level < threshold {
+                self.low_run += 1;
+                if self.low_run == after {
+                    self.passive_minutes += after;
+                } else if self.low_run > after {
+                    self.passive_minutes += 1;
+                }
+            } else {
+                self.low_run = 0;
+            }
+        }
+        self.active_ticks = 0;
+        self.ticks = 0;
+    }
+
+    /// Mean activity level over the sampled minutes, including the minute in
+    /// progress. None when nothing was sampled.
+    pub fn average(&self) -> Option<f64> {
+        let mut meter = self.clone();
+        meter.close_minute();
+        if meter.minutes == 0 {
+            return None;
+        }
+        Some(meter.level_sum / meter.minutes as f64)
+    }
+
+    /// Time spent in low-activity runs long enough to count as passive,
+    /// including a run still going. None when nothing was sampled.
+    pub fn passive(&self) -> Option<Duration> {
+        let mut meter = self.clone();
+        meter.close_minute();
+        if meter.minutes == 0 {
+            return None;
+        }
+        Some(Duration::from_secs(meter.passive_minutes as u64 * 60))
+    }
+
+    /// The average and passive time so far, leaving the meter empty for the
+    /// next session
+    pub fn take(&mut self) -> (Option<f64>, Option<Duration>) {
+        let meter = std::mem::replace(self, ActivityMeter { thresholds: self.thresholds, ..Self::default() });
+        (meter.average(), meter.passive())
+    }
+}
+
+/// Whether input idle time can be read here, for track_input_activity
+pub fn check_idle_time() -> Result<(), String> {
+    match idle_time() {
+        Some(_) => Ok(()),
+        None if cfg!(target_os = "linux") => Err("idle time unavailable (install xprintidle on X11)".to_string()),
+        None => Err("idle time unavailable".to_string()),
+    }
+}
+
+/// Whether the user gave any input during the last `interval`
+pub fn had_input_within(interval: Duration) -> Option<bool> {
+    idle_time().map(|idle| idle < interval)
+}
+
+fn command_stdout(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Time since the last keyboard or mouse input, from the session's idle
+/// counter (xprintidle on X11, Mutter's IdleMonitor on GNOME Wayland)
+#[cfg(target_os = "linux")]
+pub fn idle_time() -> Option<Duration> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let output = command_stdout(Command::new("gdbus").args([
+            "call", "--session",
+            "--dest", "org.gnome.Mutter.IdleMonitor",
+            "--object-path", "/org/gnome/Mutter/IdleMonitor/Core",
+            "--method", "org.gnome.Mutter.IdleMonitor.GetIdletime",
+        ]));
+        // Reply looks like "(uint64 1234,)"
+        if let Some(ms) = output.as_deref()
+            .and_then(|reply| reply.split_whitespace().nth(1))
+            .and_then(|value| value.trim_end_matches([',', ')']).parse::<u64>().ok())
+        {
+            return Some(Duration::from_millis(ms));
+        }
+    }
+
+    command_stdout(&mut Command::new("xprintidle"))
+        .and_then(|output| output.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+}
+
+/// Time since the last keyboard or mouse input, from IOHIDSystem's HIDIdleTime
+#[cfg(target_os = "macos")]
+pub fn idle_time() -> Option<Duration> {
+    let output = command_stdout(Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]))?;
+    output.lines()
+        .find(|line| line.contains("\"HIDIdleTime\""))
+        .and_then(|line| line.split('=').nth(1))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_nanos)
+}
+
+/// Time since the last keyboard or mouse input, from GetLastInputInfo
+#[cfg(target_os = "windows")]
+pub fn idle_time() -> Option<Duration> {
+    let script = r#"
+Add-Type @'
+using System;
+using System.Runtime.InteropServices;
+public static class FocusDebtIdle {
+    [StructLayout(LayoutKind.Sequential)]
+    struct LASTINPUTINFO { public uint cbSize; public uint dwTime; }
+    [DllImport("user32.dll")]
+    static extern bool GetLastInputInfo(ref LASTINPUTINFO info);
+    public static uint Get() {
+        var info = new LASTINPUTINFO();
+        info.cbSize = (uint)Marshal.SizeOf(info);
+        GetLastInputInfo(ref info);
+        return (uint)Environment.TickCount - info.dwTime;
+    }
+}
+'@
+[FocusDebtIdle]::Get()
+"#;
+    command_stdout(Command::new("powershell").args(["-NoProfile", "-Command", script]))
+        .and_then(|output| output.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn idle_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn second(n: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_716_282_000 + n, 0).unwrap()
+    }
+
+    #[test]
+    fn activity_is_the_mean_of_minute_levels() {
+        let mut meter = ActivityMeter::default();
+        assert_eq!(meter.average(), None);
+
+        // Minute one: 3 of 4 ticks had input; minute two: 1 of 4
+        for (tick, active) in [true, true, false, true].into_iter().enumerate() {
+            meter.record(second(tick as i64 * 15), active);
+        }
+        for (tick, active) in [false, false, true, false].into_iter().enumerate() {
+            meter.record(second(60 + tick as i64 * 15), active);
+        }
+        // The minute in progress counts without being closed
+        assert_eq!(meter.average(), Some(0.5));
+
+        // A minute with a single idle tick weighs as much as a full one
+        meter.record(second(120), false);
+        assert_eq!(meter.average(), Some((0.75 + 0.25) / 3.0));
+
+        // Reading doesn't change the meter; taking empties it
+        assert_eq!(meter.average(), Some((0.75 + 0.25) / 3.0));
+        let (average, _) = meter.take();
+        assert_eq!(average, Some((0.75 + 0.25) / 3.0));
+        assert_eq!(meter.average(), None);
+    }
+
+    #[test]
+    fn minutes_without_ticks_are_not_counted() {
+        let mut meter = ActivityMeter::default();
+        meter.record(second(0), true);
+        // Ten quiet minutes with no ticks at all (the machine slept)
+        meter.record(second(11 * 60), false);
+        assert_eq!(meter.average(), Some(0.5));
+    }
+
+    /// `levels` as one minute each, at four ticks a minute: 0 for none of
+    /// them with input, 4 for all
+    fn meter_over(mut meter: ActivityMeter, levels: &[u32]) -> ActivityMeter {
+        for (minute, active) in levels.iter().enumerate() {
+            for tick in 0..4 {
+                meter.record(second(minute as i64 * 60 + tick as i64 * 15), tick < *active);
+            }
+        }
+        meter
+    }
+
+    #[test]
+    fn long_quiet_runs_in_one_window_count_as_passive() {
+        // Twelve minutes without input: a muted call or a CI run to watch
+        let watching = meter_over(ActivityMeter::with_thresholds(10, 0.1), &[0; 12]);
+        assert_eq!(watching.passive(), Some(Duration::from_secs(12 * 60)));
+
+        // Typing now and then breaks each quiet run before ten minutes
+        let mut levels = vec![0; 9];
+        levels.push(2);
+        levels.extend([0; 9]);
+        let working = meter_over(ActivityMeter::with_thresholds(10, 0.1), &levels);
+        assert_eq!(working.passive(), Some(Duration::ZERO));
+
+        // Two minutes of work after ten quiet ones leave those ten passive
+        let mut levels = vec![0; 10];
+        levels.extend([4, 4]);
+        let back = meter_over(ActivityMeter::with_thresholds(10, 0.1), &levels);
+        assert_eq!(back.passive(), Some(Duration::from_secs(10 * 60)));
+        assert!(back.average().unwrap() > 0.0);
+
+        // Off, and without samples, nothing is passive
+        assert_eq!(meter_over(ActivityMeter::with_thresholds(0, 0.1), &[0; 12]).passive(), Some(Duration::ZERO));
+        assert_eq!(ActivityMeter::with_thresholds(10, 0.1).passive(), None);
+
+        // Taking keeps the meter's thresholds for the next session
+        let mut meter = meter_over(ActivityMeter::with_thresholds(10, 0.1), &[0; 12]);
+        assert_eq!(meter.take().1, Some(Duration::from_secs(12 * 60)));
+        assert_eq!(meter_over(meter, &[0; 10]).passive(), Some(Duration::from_secs(10 * 60)));
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BudgetPeriod, Config, SoundsConfig};
+use crate::sounds::{self, SoundEvent};
+use crate::stats::{BudgetUsage, Stats, StatsSettings, EFFICIENCY_TREND_DAYS};
+use crate::storage::Database;
+use crate::tracking::{EventKind, FocusSession, TrackerEvent};
+use crate::utils::{self, DurationStyle};
+
+const EFFICIENCY_ALERT_FILE: &str = "efficiency_alert";
+const BUDGET_ALERT_FILE: &str = "budget_alerts.json";
+
+/// Budget usage levels (percent) that trigger a notification
+const BUDGET_THRESHOLDS: [u32; 2] = [80, 100];
+
+/// Highest threshold already notified per budget and period, keyed like
+/// "slack@2024-05-21" by the local date the budget's current period began
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BudgetAlertState {
+    notified: BTreeMap<String, u32>,
+}
+
+fn budget_alert_key(budget: &BudgetUsage) -> String {
+    format!("{}@{}", budget.target, budget.period_start.with_timezone(&utils::timezone::zone()).format("%Y-%m-%d"))
+}
+
+/// The highest threshold `percent` has reached, if it wasn't notified yet
+fn budget_threshold_crossed(percent: f64, already: u32) -> Option<u32> {
+    BUDGET_THRESHOLDS.iter().rev().copied()
+        .find(|threshold| percent >= *threshold as f64)
+        .filter(|threshold| *threshold > already)
+}
+
+/// Check the trailing efficiency against the configured floor and send a
+/// desktop notification at most once per day. Meant to be called from the daemon.
+pub fn check_efficiency_floor(config: &Config, settings: &StatsSettings) {
+    if !config.efficiency_alerts {
+        return;
+    }
+
+    let today = utils::tracking_day(Utc::now(), config.day_start_hour);
+    if last_efficiency_alert() == Some(today) {
+        return;
+    }
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to open database for efficiency check: {}", e);
+            return;
+        }
+    };
+
+    let trend = match Stats::calculate_efficiency_trend(&db, settings, chrono::Utc::now(), config.day_start_hour) {
+        Ok(Some(trend)) => trend,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate efficiency trend: {}", e);
+            return;
+        }
+    };
+
+    if !trend.is_below(config.efficiency_floor) {
+        return;
+    }
+
+    println!("~=~ Efficiency {:.0}% is below floor {:.0}%, notifying", trend.average, config.efficiency_floor);
+    send_notification(
+        "FocusDebt: focus efficiency is slipping",
+        &format!(
+            "{}-day average {:.0}% is below your {:.0}% floor ({})",
+            EFFICIENCY_TREND_DAYS,
+            trend.average,
+            config.efficiency_floor,
+            trend.describe()
+        ),
+    );
+
+    let event = TrackerEvent::now(
+        EventKind::Alert,
+        serde_json::json!({
+            "alert": "efficiency_floor",
+            "average": trend.average,
+            "floor": config.efficiency_floor,
+        }),
+    );
+    if let Err(e) = db.save_event(&event) {
+        eprintln!("❌ Failed to record alert event: {}", e);
+    }
+
+    if let Err(e) = record_efficiency_alert(today) {
+        eprintln!("❌ Failed to save efficiency alert state: {}", e);
+    }
+}
+
+fn last_efficiency_alert() -> Option<NaiveDate> {
+    let path = utils::profile_data_directory(utils::active_profile())?.join(EFFICIENCY_ALERT_FILE);
+    let content = std::fs::read_to_string(path).ok()?;
+    NaiveDate::parse_from_str(content.trim(), "%Y-%m-%d").ok()
+}
+
+fn record_efficiency_alert(date: NaiveDate) -> std::io::Result<()> {
+    let data_dir = utils::ensure_profile_data_directory()?;
+    std::fs::write(data_dir.join(EFFICIENCY_ALERT_FILE), date.format("%Y-%m-%d").to_string())
+}
+
+/// Compare each configured budget's usage in its current period (day or
+/// week) against its limit and notify when it crosses 80% and 100%, once
+/// per threshold per period.
+pub fn check_budgets(config: &Config, settings: &StatsSettings, current: Option<&FocusSession>) {
+    let budgets = config.budget_rules();
+    if budgets.is_empty() {
+        return;
+    }
+
+    let now = chrono::Utc::now();
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to open database for budget check: {}", e);
+            return;
+        }
+    };
+
+    let usage = match Stats::calculate_budget_usage(&db, settings, &budgets, now, config.day_start_hour, current) {
+        Ok(usage) => usage,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate budget usage: {}", e);
+            return;
+        }
+    };
+
+    // Consumption resets at the period boundary, and so do the
+    // notifications: keys of periods that are over are dropped
+    let mut state = load_budget_state();
+    let current_keys: Vec<String> = usage.iter().map(budget_alert_key).collect();
+    let before = state.notified.len();
+    state.notified.retain(|key, _| current_keys.contains(key));
+
+    let mut changed = state.notified.len() != before;
+    for (budget, key) in usage.iter().zip(&current_keys) {
+        let percent = budget.percent();
+        let already = state.notified.get(key).copied().unwrap_or(0);
+        let Some(threshold) = budget_threshold_crossed(percent, already) else {
+            continue;
+        };
+
+        let title = if threshold >= 100 {
+            format!("FocusDebt: {} budget used up", budget.target)
+        } else {
+            format!("FocusDebt: {} budget at {}%", budget.target, threshold)
+        };
+        println!("~=~ Budget {} at {:.0}%, notifying", budget.target, percent);
+        send_notification(
+            &title,
+            &format!(
+                "{} of {} used {}",
+                utils::format_duration(budget.used, DurationStyle::Long),
+                utils::format_duration(budget.limit, DurationStyle::Long),
+                if budget.period == BudgetPeriod::Week { "this week" } else { "today" }
+            ),
+        );
+
+        let event = TrackerEvent::now(
+            EventKind::Alert,
+            serde_json::json!({
+                "alert": "budget",
+                "target": budget.target,
+                "threshold": threshold,
+                "period": budget.period.label(),
+                "used_seconds": budget.used.as_secs(),
+                "limit_seconds": budget.limit.as_secs(),
+            }),
+        );
+        if let Err(e) = db.save_event(&event) {
+            eprintln!("❌ Failed to record alert event: {}", e);
+        }
+
+        state.notified.insert(key.clone(), threshold);
+        changed = true;
+    }
+
+    if changed {
+        if let Err(e) = save_budget_state(&state) {
+            eprintln!("❌ Failed to save budget alert state: {}", e);
+        }
+    }
+}
+
+fn load_budget_state() -> BudgetAlertState {
+    utils::profile_data_directory(utils::active_profile())
+        .and_then(|dir| std::fs::read_to_string(dir.join(BUDGET_ALERT_FILE)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_budget_state(state: &BudgetAlertState) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = utils::ensure_profile_data_directory()?;
+    std::fs::write(data_dir.join(BUDGET_ALERT_FILE), serde_json::to_string(state)?)?;
+    Ok(())
+}
+
+/// Streaks the sound cues count: continuous focus (across focus windows)
+/// and time on the current distraction
+#[derive(Debug, Default)]
+pub struct Milestones {
+    focus_since: Option<DateTime<Utc>>,
+    /// Focus cues already played in this streak
+    focus_cues: u64,
+    distraction_since: Option<DateTime<Utc>>,
+    distraction_cued: bool,
+}
+
+impl Milestones {
+    /// Follow the daemon's current window and play a cue when a streak
+    /// crosses its mark. Meant to be called from the daemon's save loop.
+    pub fn check(&mut self, config: &SoundsConfig, current: Option<&FocusSession>, now: DateTime<Utc>) {
+        if !config.enabled {
+            return;
+        }
+        match current {
+            Some(session) if session.is_focus_app => {
+                self.distraction_since = None;
+                self.distraction_cued = false;
+                let since = *self.focus_since.get_or_insert(session.start_time);
+                let minutes = (now - since).num_minutes().max(0) as u64;
+                let cues = minutes.checked_div(config.focus_minutes).unwrap_or(0);
+                if cues > self.focus_cues {
+                    self.focus_cues = cues;
+                    println!("~=~ {}m of continuous focus, cueing", minutes);
+                    sounds::play(SoundEvent::FocusMilestone);
+                }
+            }
+            Some(session) => {
+                self.focus_since = None;
+                self.focus_cues = 0;
+                // A new distraction starts its own count
+                if self.distraction_since != Some(session.start_time) {
+                    self.distraction_since = Some(session.start_time);
+                    self.distraction_cued = false;
+                }
+                let minutes = (now - session.start_time).num_minutes().max(0) as u64;
+                if !self.distraction_cued && config.distraction_minutes > 0 && minutes >= config.distraction_minutes {
+                    self.distraction_cued = true;
+                    println!("~=~ {}m on {}, cueing", minutes, session.app_name);
+                    sounds::play(SoundEvent::Distraction);
+                }
+            }
+            None => *self = Milestones::default(),
+        }
+    }
+}
+
+static NOTIFICATIONS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn desktop notifications off for this run; they go to the log instead
+pub fn set_notifications_enabled(enabled: bool) {
+    NOTIFICATIONS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the notification command can be run here. None where there is
+/// no notification command, so nothing to check.
+pub fn check_notifications() -> Option<Result<(), String>> {
+    let command = notification_command("", "")?;
+    let program = command.get_program().to_string_lossy().into_owned();
+    Some(match utils::find_program(&program) {
+        Some(_) => Ok(()),
+        None => Err(format!("{} not found", program)),
+    })
+}
+
+/// Best-effort desktop notification; failures are only logged.
+pub fn send_notification(title: &str, body: &str) {
+    let command = NOTIFICATIONS_ENABLED.load(Ordering::Relaxed).then(|| notification_command(title, body)).flatten();
+    let Some(mut command) = command else {
+        println!("~=~ {}: {}", title, body);
+        return;
+    };
+
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("❌ Notification command exited with {}", status),
+        Err(e) => eprintln!("❌ Failed to send notification: {}", e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn notification_command(title: &str, body: &str) -> Option<Command> {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=focusdebt", title, body]);
+    Some(command)
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(title: &str, body: &str) -> Option<Command> {
+    let script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        body.replace('"', "'"),
+        title.replace('"', "'")
+    );
+    let mut command = Command::new("osascript");
+    command.args(["-e", &script]);
+    Some(command)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn notification_command(_title: &str, _body: &str) -> Option<Command> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn budget_thresholds_notify_once_each() {
+        let mut notified = 0;
+        let mut sent = Vec::new();
+        for percent in [10.0, 79.9, 80.0, 95.0, 99.9, 100.0, 140.0] {
+            if let Some(threshold) = budget_threshold_crossed(percent, notified) {
+                sent.push((percent, threshold));
+                notified = threshold;
+            }
+        }
+        assert_eq!(sent, [(80.0, 80), (100.0, 100)]);
+
+        // Jumping straight past both only sends the higher one
+        assert_eq!(budget_threshold_crossed(120.0, 0), Some(100));
+    }
+
+    #[test]
+    fn budget_alerts_are_keyed_by_period() {
+        let usage = |period_start: &str| BudgetUsage {
+            target: "fd-video".to_string(),
+            limit: Duration::from_secs(1800),
+            used: Duration::from_secs(1800),
+            period: BudgetPeriod::Day,
+            period_start: DateTime::parse_from_rfc3339(period_start).unwrap().with_timezone(&Utc),
+            carried: Duration::ZERO,
+        };
+        let today = budget_alert_key(&usage("2024-05-21T12:00:00Z"));
+        assert!(today.starts_with("fd-video@2024-05-2"), "{}", today);
+        assert_ne!(today, budget_alert_key(&usage("2024-05-22T12:00:00Z")));
+        assert_eq!(today, budget_alert_key(&usage("2024-05-21T12:00:00Z")));
+    }
+}
@@ -0,0 +1,510 @@
+//! The embedding API: run the tracker inside another app and read its
+//! reports without the CLI's daemon. The items here are kept stable
+//! (`tests/public_api.rs` guards them); everything else in the crate is
+//! the CLI's internals.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub use crate::config::{AssistantCountsAs, RuleKind, RuleMatch};
+pub use crate::power::PowerSource;
+pub use crate::stats::{AggregatedSession, AppTrend, BrowserSites, CoFocusTime, DailyStats, NotificationCounts, RoundTripPair, SuspendGap, SwitchCost, WorkspaceUsage};
+pub use crate::storage::{SessionIntent, SessionTask, TaskSource};
+pub use crate::tracking::{ContextSwitch, FocusSession, OpenEnd};
+
+use crate::config::Config;
+use crate::progress::Progress;
+use crate::stats::{Stats, StatsSettings};
+use crate::storage::Database;
+use crate::tracking::{self, BackendSelector, FocusTracker, WindowDebouncer, WindowInfo};
+use crate::utils::{self, LocalDays};
+use crate::utils::timezone::{self, Zone};
+
+/// What can go wrong starting the tracker or reading reports
+pub type Error = Box<dyn std::error::Error>;
+
+/// How often the tracking thread checks for `stop` while it waits for the
+/// next poll
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How the embedded tracker runs: the active profile's config, with the
+/// settings an embedder may want to choose itself on top. The config's
+/// other settings stay the CLI's own, so they can change without breaking
+/// embedders.
+#[derive(Debug, Clone, Default)]
+pub struct TrackerOptions {
+    config: Config,
+}
+
+impl TrackerOptions {
+    /// The active profile's config.toml; the defaults when there is none
+    pub fn load() -> Result<Self, Error> {
+        Ok(TrackerOptions { config: Config::load()? })
+    }
+
+    /// How often the focused window is checked
+    pub fn tracking_interval(mut self, interval: Duration) -> Self {
+        self.config.tracking_interval_ms = interval.as_millis().max(1) as u64;
+        self
+    }
+
+    /// Apps counted as focus, in place of the configured ones
+    pub fn focus_apps<S: Into<String>>(mut self, apps: impl IntoIterator<Item = S>) -> Self {
+        self.config.focus_apps = apps.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Apps never recorded, in place of the configured ones
+    pub fn ignored_apps<S: Into<String>>(mut self, apps: impl IntoIterator<Item = S>) -> Self {
+        self.config.ignored_apps = apps.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Local hour (0-23) at which days, and so the session's name, start
+    pub fn day_start_hour(mut self, hour: u32) -> Self {
+        self.config.day_start_hour = hour.min(23);
+        self
+    }
+
+    /// IANA zone (e.g. Europe/Berlin) reports cut days in; "system"
+    /// follows the system's
+    pub fn timezone(mut self, name: &str) -> Result<Self, Error> {
+        self.config.timezone = match name.trim() {
+            system if system.eq_ignore_ascii_case("system") => None,
+            name => Some(timezone::parse(name)?.name().to_string()),
+        };
+        Ok(self)
+    }
+
+    /// Days as the config cuts them. Unlike the CLI this never touches the
+    /// process-wide zone, so an embedder's own timezone is left alone.
+    fn local_days(&self) -> LocalDays {
+        let zone = self.config.timezone.as_deref()
+            .and_then(|name| timezone::parse(name).ok())
+            .map_or(Zone::System, Zone::Named);
+        LocalDays::in_zone(zone, self.config.day_start_hour)
+    }
+}
+
+/// What the tracker reports while it runs, in the order it happened
+#[derive(Debug, Clone)]
+pub enum TrackerUpdate {
+    /// A window became the tracked one
+    SessionStarted { app_name: String, window_title: String, at: DateTime<Utc> },
+    /// A row was completed and saved
+    SessionEnded(FocusSession),
+    /// Focus moved to another app
+    Switch(ContextSwitch),
+}
+
+/// The tracker as of its last poll
+#[derive(Debug, Clone, Default)]
+pub struct TrackerStatus {
+    pub session_name: String,
+    /// The window being tracked, its duration up to the last poll
+    pub current: Option<FocusSession>,
+    /// Rows completed since the tracker started
+    pub sessions: usize,
+    pub context_switches: usize,
+}
+
+/// Totals from `start` to `stop`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub session_name: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub sessions: usize,
+    pub context_switches: usize,
+    pub total_time: Duration,
+    pub focus_time: Duration,
+}
+
+impl SessionSummary {
+    /// Share of the tracked time spent in focus apps, 0–100
+    pub fn efficiency(&self) -> f64 {
+        if self.total_time.is_zero() {
+            return 0.0;
+        }
+        self.focus_time.as_secs_f64() / self.total_time.as_secs_f64() * 100.0
+    }
+}
+
+/// What the tracking thread shares with the handle
+#[derive(Default)]
+struct Shared {
+    status: TrackerStatus,
+    subscribers: Vec<mpsc::Sender<TrackerUpdate>>,
+}
+
+impl Shared {
+    /// Send `update` to every subscriber, forgetting those that hung up
+    fn publish(&mut self, update: TrackerUpdate) {
+        self.subscribers.retain(|subscriber| subscriber.send(update.clone()).is_ok());
+    }
+}
+
+/// The tracker running on a thread of its own, saving rows to the active
+/// profile's database as the daemon does. While it runs it holds the
+/// daemon's PID file, so `focusdebt start` won't track alongside it and
+/// `focusdebt stop` stops it. Dropping the handle stops it.
+pub struct FocusTrackerHandle {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<SessionSummary>>,
+    /// Whether this handle wrote the PID file and has to remove it
+    registered: bool,
+}
+
+impl FocusTrackerHandle {
+    /// Start tracking the focused window with `options`' rules, under the
+    /// session named after the day. Fails when the focusdebt daemon (or
+    /// another handle) is already tracking this profile, or the PID file
+    /// or database can't be written.
+    pub fn start(options: TrackerOptions) -> Result<Self, Error> {
+        let config = options.config;
+        if utils::is_daemon_running() {
+            return Err("the focusdebt daemon is already tracking this profile; stop it first".into());
+        }
+        utils::ensure_data_directory()?;
+        utils::write_pid_file(utils::get_current_pid())?;
+        let db = match Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                let _ = utils::remove_pid_file();
+                return Err(e.into());
+            }
+        };
+        let mut selector = BackendSelector::for_config(&config).quiet();
+        Ok(Self::start_with(config, db, true, move || tracking::detect_with(&mut selector).map(|(_, window)| window)))
+    }
+
+    /// `start` with the windows `detect` reports, saving to `db`. When
+    /// `registered` (the PID file is ours) the thread also stops once the
+    /// file no longer names this process, which is how `focusdebt stop`
+    /// asks the daemon to.
+    fn start_with(config: Config, db: Database, registered: bool, mut detect: impl FnMut() -> Option<WindowInfo> + Send + 'static) -> Self {
+        let session_name = utils::daily_session_name(Utc::now(), config.day_start_hour);
+        let mut tracker = FocusTracker::new();
+        tracker.set_debug_mode(false);
+        tracker.apply_config(&config, config.tracked_focus_apps(), &session_name);
+        tracker.start_tracking();
+
+        let shared = Arc::new(Mutex::new(Shared {
+            status: TrackerStatus { session_name: session_name.clone(), ..Default::default() },
+            subscribers: Vec::new(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let interval = Duration::from_millis(config.tracking_interval_ms.max(1));
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut run = Run::new(tracker, db, session_name, shared);
+                let mut debouncer = WindowDebouncer::for_interval(config.tracking_interval_ms);
+                let mut window: Option<WindowInfo> = None;
+                let mut stopped_by_cli = false;
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(detected) = detect() {
+                        if let Some((changed, since)) = debouncer.observe(window.as_ref(), detected, Utc::now()) {
+                            window = Some(changed.clone());
+                            run.tracker.update_active_window_at(changed, since);
+                        }
+                    }
+                    run.publish();
+                    if registered && !utils::pid_file_holds(utils::get_current_pid()) {
+                        stopped_by_cli = true;
+                        break;
+                    }
+                    let mut waited = Duration::ZERO;
+                    while waited < interval && !stop.load(Ordering::Relaxed) {
+                        let step = STOP_CHECK_INTERVAL.min(interval - waited);
+                        thread::sleep(step);
+                        waited += step;
+                    }
+                }
+                run.finish(stopped_by_cli)
+            })
+        };
+        FocusTrackerHandle { shared, stop, thread: Some(thread), registered }
+    }
+
+    /// Updates from now on, until the handle stops. Each call gets a
+    /// receiver of its own.
+    pub fn subscribe(&self) -> mpsc::Receiver<TrackerUpdate> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.lock().unwrap().subscribers.push(sender);
+        receiver
+    }
+
+    pub fn current_status(&self) -> TrackerStatus {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    /// End the open row, save what is left and stop the thread. Fails
+    /// when the thread died before it could.
+    pub fn stop(mut self) -> Result<SessionSummary, Error> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<SessionSummary, Error> {
+        self.stop.store(true, Ordering::Relaxed);
+        let summary = match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| "the tracking thread panicked".into()),
+            None => Err("the tracker was already stopped".into()),
+        };
+        // Subscribers see their channel close
+        self.shared.lock().unwrap().subscribers.clear();
+        // Unless `focusdebt stop` took it, or a daemon started since
+        if self.registered && utils::pid_file_holds(utils::get_current_pid()) {
+            let _ = utils::remove_pid_file();
+        }
+        self.registered = false;
+        summary
+    }
+}
+
+impl Drop for FocusTrackerHandle {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            let _ = self.join();
+        }
+    }
+}
+
+/// The tracking thread's state between polls
+struct Run {
+    tracker: FocusTracker,
+    db: Database,
+    shared: Arc<Mutex<Shared>>,
+    /// Start of the current row when it was last published
+    published_start: Option<(DateTime<Utc>, String)>,
+    /// When a row was last saved
+    last_committed: Option<DateTime<Utc>>,
+    summary: SessionSummary,
+}
+
+impl Run {
+    fn new(tracker: FocusTracker, db: Database, session_name: String, shared: Arc<Mutex<Shared>>) -> Self {
+        let now = Utc::now();
+        Run {
+            tracker,
+            db,
+            shared,
+            published_start: None,
+            last_committed: None,
+            summary: SessionSummary {
+                session_name,
+                started_at: now,
+                ended_at: now,
+                sessions: 0,
+                context_switches: 0,
+                total_time: Duration::ZERO,
+                focus_time: Duration::ZERO,
+            },
+        }
+    }
+
+    /// Save the rows the tracker completed and tell subscribers about them
+    /// and the window now tracked
+    fn publish(&mut self) {
+        let sessions = self.tracker.take_completed_sessions();
+        let switches = self.tracker.take_context_switches();
+        let current = self.tracker.get_current_session();
+        let mut shared = self.shared.lock().unwrap();
+        for session in sessions {
+            match self.db.save_focus_session(&session) {
+                Ok(()) => self.last_committed = Some(Utc::now()),
+                Err(e) => eprintln!("❌ Failed to save session: {}", e),
+            }
+            self.summary.sessions += 1;
+            self.summary.total_time += session.duration;
+            if session.is_focus_app {
+                self.summary.focus_time += session.duration;
+            }
+            shared.publish(TrackerUpdate::SessionEnded(session));
+        }
+        for switch in switches {
+            match self.db.save_context_switch(&switch) {
+                Ok(()) => self.last_committed = Some(Utc::now()),
+                Err(e) => eprintln!("❌ Failed to save context switch: {}", e),
+            }
+            self.summary.context_switches += 1;
+            shared.publish(TrackerUpdate::Switch(switch));
+        }
+        let start = current.as_ref().map(|session| (session.start_time, session.app_name.clone()));
+        if let Some(session) = current.as_ref().filter(|_| start != self.published_start) {
+            shared.publish(TrackerUpdate::SessionStarted {
+                app_name: session.app_name.clone(),
+                window_title: session.window_title.clone(),
+                at: session.start_time,
+            });
+        }
+        self.published_start = start;
+        shared.status.current = current;
+        shared.status.sessions = self.summary.sessions;
+        shared.status.context_switches = self.summary.context_switches;
+    }
+
+    /// Save the open row. When `focusdebt stop` asked for it, tell it that
+    /// everything is on disk as the daemon does.
+    fn finish(mut self, stopped_by_cli: bool) -> SessionSummary {
+        self.tracker.end_current_session();
+        self.publish();
+        self.summary.ended_at = Utc::now();
+        if stopped_by_cli {
+            if let Err(e) = utils::write_flush_marker(self.last_committed) {
+                eprintln!("❌ Failed to write flush marker: {}", e);
+            }
+        }
+        self.summary
+    }
+}
+
+/// Read-only access to the tracked data and the reports built from it.
+/// Reports follow the config as the CLI's do: where days start, the
+/// timezone, site lists, holidays and the rest.
+pub struct StatsReader {
+    db: Database,
+    settings: StatsSettings,
+    days: LocalDays,
+}
+
+impl StatsReader {
+    /// The active profile's database, read with its config
+    pub fn open() -> Result<Self, Error> {
+        Ok(Self::with_db(Database::new_read_only()?, &TrackerOptions::load()?))
+    }
+
+    /// The database at `path`, read with the active profile's config
+    pub fn open_path(path: &Path) -> Result<Self, Error> {
+        Self::open_with(path, &TrackerOptions::load()?)
+    }
+
+    /// The database at `path`, read with `options`' config
+    pub fn open_with(path: &Path, options: &TrackerOptions) -> Result<Self, Error> {
+        Ok(Self::with_db(Database::open_read_only(path)?, options))
+    }
+
+    fn with_db(db: Database, options: &TrackerOptions) -> Self {
+        StatsReader { db, settings: StatsSettings::from_config(&options.config), days: options.local_days() }
+    }
+
+    /// The day `date` falls on, as the config's day start and timezone
+    /// cut days
+    pub fn daily_stats(&self, date: DateTime<Utc>) -> Result<DailyStats, Error> {
+        Stats::calculate_daily_stats(&self.db, &self.settings, &self.days, self.days.day_of(date))
+    }
+
+    /// `days` days from `first_day` on, one entry each
+    pub fn daily_stats_range(&self, first_day: NaiveDate, days: usize) -> Result<Vec<DailyStats>, Error> {
+        Stats::calculate_daily_stats_range(&self.db, &self.settings, &self.days, first_day, days, &mut Progress::hidden())
+    }
+
+    /// Everything recorded under `session_name` in the last 30 days
+    pub fn session_stats(&self, session_name: &str) -> Result<AggregatedSession, Error> {
+        Stats::calculate_session_stats(&self.db, &self.settings, session_name)
+    }
+
+    /// Rows starting in `start..=end`
+    pub fn sessions_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<FocusSession>, Error> {
+        Ok(self.db.get_sessions_between(start, end)?)
+    }
+
+    pub fn context_switches_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<ContextSwitch>, Error> {
+        Ok(self.db.get_context_switches_between(start, end)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("focusdebt-api-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("focusdebt.db")
+    }
+
+    #[test]
+    fn handle_tracks_scripted_windows_and_summarizes_on_stop() {
+        let path = temp_db_path("scripted");
+        let TrackerOptions { config } = TrackerOptions::default()
+            .tracking_interval(Duration::from_millis(20))
+            .focus_apps(["code"]);
+        let config = Config { min_record_seconds: 0, ..config };
+        let window = Arc::new(Mutex::new(Some(WindowInfo::new("code", "main.rs"))));
+        let detect = {
+            let window = Arc::clone(&window);
+            move || window.lock().unwrap().clone()
+        };
+        let handle = FocusTrackerHandle::start_with(config, Database::open(&path).unwrap(), false, detect);
+        let updates = handle.subscribe();
+
+        // Past the settle time sub-second intervals hold changes back for
+        thread::sleep(tracking::WINDOW_SETTLE + Duration::from_millis(300));
+        let status = handle.current_status();
+        assert_eq!(status.current.map(|session| session.app_name).as_deref(), Some("code"));
+        assert_eq!(status.sessions, 0);
+
+        *window.lock().unwrap() = Some(WindowInfo::new("slack", "general"));
+        thread::sleep(tracking::WINDOW_SETTLE + Duration::from_millis(300));
+        let summary = handle.stop().unwrap();
+
+        let updates: Vec<TrackerUpdate> = updates.iter().collect();
+        let kinds: Vec<String> = updates.iter().map(|update| match update {
+            TrackerUpdate::SessionStarted { app_name, .. } => format!("start {}", app_name),
+            TrackerUpdate::SessionEnded(session) => format!("end {}", session.app_name),
+            TrackerUpdate::Switch(switch) => format!("switch {} {}", switch.from_app, switch.to_app),
+        }).collect();
+        assert_eq!(kinds, ["start code", "end code", "switch code slack", "start slack", "end slack"]);
+
+        assert_eq!((summary.sessions, summary.context_switches), (2, 1));
+        assert!(summary.focus_time > Duration::ZERO && summary.focus_time < summary.total_time);
+        assert!(summary.efficiency() > 0.0 && summary.efficiency() < 100.0);
+        assert!(summary.ended_at > summary.started_at);
+
+        let reader = StatsReader::open_with(&path, &TrackerOptions::default()).unwrap();
+        let sessions = reader.sessions_between(summary.started_at - chrono::Duration::minutes(1), summary.ended_at).unwrap();
+        let apps: Vec<&str> = sessions.iter().map(|session| session.app_name.as_str()).collect();
+        assert_eq!(apps, ["code", "slack"]);
+        let switches = reader.context_switches_between(summary.started_at - chrono::Duration::minutes(1), summary.ended_at).unwrap();
+        assert_eq!(switches.len(), 1);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn dropping_the_handle_stops_and_closes_subscriptions() {
+        let path = temp_db_path("drop");
+        let TrackerOptions { config } = TrackerOptions::default().tracking_interval(Duration::from_millis(20));
+        let handle = FocusTrackerHandle::start_with(config, Database::open(&path).unwrap(), false, || None);
+        let updates = handle.subscribe();
+        drop(handle);
+        assert!(updates.recv().is_err());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn summary_efficiency_is_a_percentage() {
+        let now = Utc::now();
+        let summary = |total: u64, focus: u64| SessionSummary {
+            session_name: String::new(),
+            started_at: now,
+            ended_at: now,
+            sessions: 0,
+            context_switches: 0,
+            total_time: Duration::from_secs(total),
+            focus_time: Duration::from_secs(focus),
+        };
+        assert_eq!(summary(0, 0).efficiency(), 0.0);
+        assert_eq!(summary(200, 50).efficiency(), 25.0);
+        assert_eq!(summary(60, 60).efficiency(), 100.0);
+    }
+}
@@ -0,0 +1,276 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+use crate::tracking::{EventKind, TrackerEvent};
+use crate::utils::{self, DurationStyle};
+
+/// Shortest and longest pause between two audit samples, in milliseconds;
+/// the cadence is randomized so it can't stay in step with the daemon's
+pub const SAMPLE_INTERVAL_MS: (u64, u64) = (200, 500);
+
+/// One look at the active window; `window` is None when detection failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub at: DateTime<Utc>,
+    pub window: Option<WindowKey>,
+}
+
+/// What tells two windows apart for the daemon: app and title
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowKey {
+    pub app: String,
+    pub title: String,
+}
+
+/// The window the daemon recorded from each window_change event, in time order
+pub fn recorded_changes(events: &[TrackerEvent]) -> Vec<Observation> {
+    events.iter()
+        .filter(|event| event.kind == EventKind::WindowChange)
+        .map(|event| Observation {
+            at: event.timestamp,
+            window: Some(WindowKey {
+                app: event.payload["app"].as_str().unwrap_or_default().to_string(),
+                title: event.payload["title"].as_str().unwrap_or_default().to_string(),
+            }),
+        })
+        .collect()
+}
+
+/// How the audit samples and the daemon's record of the same stretch differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub samples: usize,
+    /// Samples where the backend found no window
+    pub failed_samples: usize,
+    /// Window changes between consecutive successful samples
+    pub sampled_switches: usize,
+    /// Sampled changes the daemon recorded no change to within the tolerance
+    pub missed_switches: usize,
+    /// Changes the daemon recorded within the audited stretch
+    pub recorded_switches: usize,
+    /// Recorded changes no sample saw within the tolerance
+    pub unconfirmed_switches: usize,
+    /// Time covered by successful samples
+    pub compared: Duration,
+    /// Part of `compared` the daemon attributed to another window
+    pub attribution_error: Duration,
+}
+
+impl AuditReport {
+    pub fn failure_rate(&self) -> f64 {
+        percent(self.failed_samples as f64, self.samples as f64)
+    }
+
+    pub fn attribution_error_rate(&self) -> f64 {
+        percent(self.attribution_error.as_secs_f64(), self.compared.as_secs_f64())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "start": self.start.to_rfc3339(),
+            "end": self.end.to_rfc3339(),
+            "samples": self.samples,
+            "failed_samples": self.failed_samples,
+            "sampled_switches": self.sampled_switches,
+            "missed_switches": self.missed_switches,
+            "recorded_switches": self.recorded_switches,
+            "unconfirmed_switches": self.unconfirmed_switches,
+            "compared_seconds": self.compared.as_secs_f64(),
+            "attribution_error_seconds": self.attribution_error.as_secs_f64(),
+        })
+    }
+}
+
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 { part / whole * 100.0 } else { 0.0 }
+}
+
+/// Compare the audit's `samples` with the daemon's `recorded` changes over
+/// `start..end`. `recorded` may begin before `start`: the last change before
+/// it is the window the daemon had when the audit began. A change counts as
+/// seen by the other side when it names the same window within `tolerance`.
+pub fn compare(
+    samples: &[Observation],
+    recorded: &[Observation],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    tolerance: Duration,
+) -> AuditReport {
+    let tolerance = chrono::Duration::from_std(tolerance).unwrap_or_default();
+    let recorded_window_at = |at: DateTime<Utc>| {
+        recorded.iter().rev().find(|change| change.at <= at).and_then(|change| change.window.as_ref())
+    };
+    let changed_to = |changes: &[Observation], window: &WindowKey, at: DateTime<Utc>| {
+        changes.iter().any(|change| change.window.as_ref() == Some(window) && (change.at - at).abs() <= tolerance)
+    };
+
+    // Sampled switches: a successful sample naming another window than the last successful one
+    let mut sampled_changes = Vec::new();
+    let mut last_seen: Option<&WindowKey> = None;
+    for sample in samples {
+        let Some(window) = &sample.window else { continue };
+        if last_seen.is_some_and(|last| last != window) {
+            sampled_changes.push(sample.clone());
+        }
+        last_seen = Some(window);
+    }
+    let in_range: Vec<&Observation> = recorded.iter()
+        .filter(|change| change.window.is_some() && change.at >= start && change.at <= end)
+        .collect();
+
+    let missed_switches = sampled_changes.iter()
+        .filter(|change| change.window.as_ref().is_some_and(|window| !changed_to(recorded, window, change.at)))
+        .count();
+    // A sample showing the window is enough here, it needn't be a sampled switch
+    let unconfirmed_switches = in_range.iter()
+        .filter(|change| change.window.as_ref().is_some_and(|window| !changed_to(samples, window, change.at)))
+        .count();
+
+    // Each successful sample stands for the time until the next sample
+    let mut compared = Duration::ZERO;
+    let mut attribution_error = Duration::ZERO;
+    for (index, sample) in samples.iter().enumerate() {
+        let Some(window) = &sample.window else { continue };
+        let until = samples.get(index + 1).map(|next| next.at).unwrap_or(end).min(end);
+        let Ok(span) = (until - sample.at).to_std() else { continue };
+        compared += span;
+        if recorded_window_at(sample.at) != Some(window) {
+            attribution_error += span;
+        }
+    }
+
+    AuditReport {
+        start,
+        end,
+        samples: samples.len(),
+        failed_samples: samples.iter().filter(|sample| sample.window.is_none()).count(),
+        sampled_switches: sampled_changes.len(),
+        missed_switches,
+        recorded_switches: in_range.len(),
+        unconfirmed_switches,
+        compared,
+        attribution_error,
+    }
+}
+
+pub fn render(report: &AuditReport, tracking_interval_ms: u64) -> String {
+    let minutes = (report.end - report.start).num_seconds() as f64 / 60.0;
+    let mut text = format!("~=~ Tracking audit: {:.1} minutes, tracking_interval_ms {} ~=~\n\n", minutes, tracking_interval_ms);
+    text.push_str(&format!("  {:<28} {} ({} failed, {:.1}%)\n", "Samples", report.samples, report.failed_samples, report.failure_rate()));
+    text.push_str(&format!("  {:<28} {} sampled, {} recorded\n", "Window switches", report.sampled_switches, report.recorded_switches));
+    text.push_str(&format!("  {:<28} {}\n", "Missed by the daemon", report.missed_switches));
+    text.push_str(&format!("  {:<28} {}\n", "Not seen by the samples", report.unconfirmed_switches));
+    text.push_str(&format!("  {:<28} {:.1}s of {} ({:.1}%)\n", "Attributed to another window",
+        report.attribution_error.as_secs_f64(),
+        utils::format_duration(report.compared, DurationStyle::Long),
+        report.attribution_error_rate()));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        start() + chrono::Duration::milliseconds(millis)
+    }
+
+    fn seen(millis: i64, app: Option<&str>) -> Observation {
+        Observation {
+            at: at(millis),
+            window: app.map(|app| WindowKey { app: app.to_string(), title: format!("{} window", app) }),
+        }
+    }
+
+    /// One sample a second for ten seconds: a failed look at 2s, a switch
+    /// to fd-chat at 4s, and a second in fd-browser the daemon never saw
+    fn samples() -> Vec<Observation> {
+        let apps = [Some("fd-editor"), Some("fd-editor"), None, Some("fd-editor"), Some("fd-chat"),
+            Some("fd-chat"), Some("fd-browser"), Some("fd-chat"), Some("fd-chat"), Some("fd-chat")];
+        apps.iter().enumerate().map(|(second, app)| seen(second as i64 * 1000, *app)).collect()
+    }
+
+    /// fd-editor since before the audit, fd-chat half a second late, and
+    /// an fd-term change right at the end no sample saw
+    fn recorded() -> Vec<Observation> {
+        vec![seen(-60_000, Some("fd-editor")), seen(4500, Some("fd-chat")), seen(9500, Some("fd-term"))]
+    }
+
+    #[test]
+    fn compare_counts_missed_switches_and_misattributed_time() {
+        let report = compare(&samples(), &recorded(), start(), at(10_000), Duration::from_secs(1));
+        assert_eq!(report, AuditReport {
+            start: start(),
+            end: at(10_000),
+            samples: 10,
+            failed_samples: 1,
+            // Into fd-chat, into fd-browser, and back to fd-chat
+            sampled_switches: 3,
+            // fd-browser was never recorded; the change back is 2.5s from the recorded one
+            missed_switches: 2,
+            recorded_switches: 2,
+            unconfirmed_switches: 1,
+            // Nine successful samples of a second each; the failed one's second goes to the sample before
+            compared: Duration::from_secs(9),
+            // The second at 4s (fd-chat recorded at 4.5s) and the fd-browser second
+            attribution_error: Duration::from_secs(2),
+        });
+        assert_eq!(report.failure_rate(), 10.0);
+        assert!((report.attribution_error_rate() - 200.0 / 9.0).abs() < 1e-9);
+        assert_eq!(report.to_json()["attribution_error_seconds"], 2.0);
+
+        // A looser tolerance forgives the late change back
+        let loose = compare(&samples(), &recorded(), start(), at(10_000), Duration::from_secs(3));
+        assert_eq!(loose.missed_switches, 1);
+    }
+
+    #[test]
+    fn compare_agrees_with_a_perfect_record() {
+        let samples = vec![seen(0, Some("fd-editor")), seen(1000, Some("fd-chat")), seen(2000, Some("fd-chat"))];
+        let recorded = vec![seen(-500, Some("fd-editor")), seen(1000, Some("fd-chat"))];
+        let report = compare(&samples, &recorded, start(), at(3000), Duration::ZERO);
+        assert_eq!((report.sampled_switches, report.missed_switches, report.recorded_switches, report.unconfirmed_switches), (1, 0, 1, 0));
+        assert_eq!((report.compared, report.attribution_error), (Duration::from_secs(3), Duration::ZERO));
+
+        // Nothing sampled: no rates to divide by
+        let empty = compare(&[], &recorded, start(), at(3000), Duration::ZERO);
+        assert_eq!((empty.failure_rate(), empty.attribution_error_rate()), (0.0, 0.0));
+        assert_eq!(empty.unconfirmed_switches, 1);
+    }
+
+    #[test]
+    fn recorded_changes_come_from_window_change_events() {
+        let events = vec![
+            TrackerEvent { timestamp: at(0), kind: EventKind::DaemonStart, payload: serde_json::json!({}) },
+            TrackerEvent { timestamp: at(1000), kind: EventKind::WindowChange, payload: serde_json::json!({"app": "fd-chat", "title": "fd-chat window"}) },
+            TrackerEvent { timestamp: at(2000), kind: EventKind::WindowChange, payload: serde_json::json!({"app": "fd-term"}) },
+        ];
+        let changes = recorded_changes(&events);
+        assert_eq!(changes, [
+            seen(1000, Some("fd-chat")),
+            Observation { at: at(2000), window: Some(WindowKey { app: "fd-term".to_string(), title: String::new() }) },
+        ]);
+    }
+
+    #[test]
+    fn render_reports_every_metric() {
+        let report = compare(&samples(), &recorded(), start(), at(10_000), Duration::from_secs(1));
+        let text = render(&report, 1000);
+        assert!(text.starts_with("~=~ Tracking audit: 0.2 minutes, tracking_interval_ms 1000 ~=~\n\n"), "{}", text);
+        for line in [
+            format!("  {:<28} 10 (1 failed, 10.0%)", "Samples"),
+            format!("  {:<28} 3 sampled, 2 recorded", "Window switches"),
+            format!("  {:<28} 2", "Missed by the daemon"),
+            format!("  {:<28} 1", "Not seen by the samples"),
+            format!("  {:<28} 2.0s of {} (22.2%)", "Attributed to another window", utils::format_duration(Duration::from_secs(9), DurationStyle::Long)),
+        ] {
+            assert!(text.contains(&line), "{}\n{}", line, text);
+        }
+    }
+}
@@ -0,0 +1,971 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+use crate::glyphs::{self, BarStyle};
+use crate::stats::{AggregatedSession, SessionExport};
+use crate::utils::{self, DurationStyle};
+
+/// Lines above the list or pane: the title and the column header or
+/// session summary
+const LIST_HEADER_LINES: usize = 2;
+const DETAIL_HEADER_LINES: usize = 7;
+/// The status or prompt line at the bottom
+const FOOTER_LINES: usize = 1;
+
+/// What the list is sorted by, cycled with `s`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Newest first
+    Date,
+    /// Longest first
+    Duration,
+    /// Most focused first
+    Efficiency,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Date => SortKey::Duration,
+            SortKey::Duration => SortKey::Efficiency,
+            SortKey::Efficiency => SortKey::Date,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortKey::Date => "date",
+            SortKey::Duration => "duration",
+            SortKey::Efficiency => "efficiency",
+        }
+    }
+}
+
+/// Panes of the detail view, cycled with Tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Apps,
+    Domains,
+    Switches,
+}
+
+impl Pane {
+    const ALL: [Pane; 3] = [Pane::Apps, Pane::Domains, Pane::Switches];
+
+    fn next(self) -> Self {
+        match self {
+            Pane::Apps => Pane::Domains,
+            Pane::Domains => Pane::Switches,
+            Pane::Switches => Pane::Apps,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Pane::Apps => "Apps",
+            Pane::Domains => "Domains",
+            Pane::Switches => "Switches",
+        }
+    }
+}
+
+/// A session as the list shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub duration: Duration,
+    pub efficiency: f64,
+    pub tags: Vec<String>,
+}
+
+impl Entry {
+    pub fn new(session: &AggregatedSession, tags: Vec<String>) -> Self {
+        Self {
+            name: session.session_name.clone(),
+            start_time: session.start_time,
+            end_time: session.end_time,
+            duration: session.total_duration,
+            efficiency: session.focus_efficiency,
+            tags,
+        }
+    }
+
+    /// Whether every word of `filter` is in the name or a tag, ignoring case
+    fn matches(&self, filter: &str) -> bool {
+        let name = self.name.to_lowercase();
+        filter.to_lowercase().split_whitespace().all(|word| {
+            name.contains(word) || self.tags.iter().any(|tag| tag.contains(word))
+        })
+    }
+}
+
+/// A key press, decoded from the terminal's bytes by `parse_keys`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Tab,
+    Backspace,
+    Char(char),
+    /// Ctrl-C or Ctrl-D
+    Interrupt,
+}
+
+/// What a key press asks of the outside world. The browser never touches
+/// the database or the terminal; `run`'s caller carries these out and
+/// reports back through `opened`, `deleted`, `renamed`, `tagged` and
+/// `set_status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Quit,
+    Open(String),
+    Delete(String),
+    Rename { from: String, to: String },
+    SetTags { name: String, tags: Vec<String> },
+    Export(String),
+}
+
+/// A line at the bottom that takes the keys until it is answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prompt {
+    None,
+    /// Typing a filter after `/`
+    Filter,
+    ConfirmDelete,
+    Rename(String),
+    Tags(String),
+}
+
+/// State of `sessions browse`: the list, or one session's details when
+/// `detail` is set
+#[derive(Debug, Clone)]
+pub struct Browser {
+    entries: Vec<Entry>,
+    sort: SortKey,
+    filter: String,
+    /// Index into `visible()`
+    selected: usize,
+    /// First list row or pane line on screen
+    scroll: usize,
+    prompt: Prompt,
+    detail: Option<SessionExport>,
+    pane: Pane,
+    status: Option<String>,
+}
+
+impl Browser {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            sort: SortKey::Date,
+            filter: String::new(),
+            selected: 0,
+            scroll: 0,
+            prompt: Prompt::None,
+            detail: None,
+            pane: Pane::Apps,
+            status: None,
+        }
+    }
+
+    /// Entries passing the filter, in sort order
+    pub fn visible(&self) -> Vec<&Entry> {
+        let mut visible: Vec<&Entry> = self.entries.iter().filter(|entry| entry.matches(&self.filter)).collect();
+        match self.sort {
+            SortKey::Date => visible.sort_by(|a, b| b.start_time.cmp(&a.start_time).then_with(|| a.name.cmp(&b.name))),
+            SortKey::Duration => visible.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.name.cmp(&b.name))),
+            SortKey::Efficiency => visible.sort_by(|a, b| b.efficiency.total_cmp(&a.efficiency).then_with(|| a.name.cmp(&b.name))),
+        }
+        visible
+    }
+
+    /// The session keys act on: the open one, else the selected row
+    pub fn current(&self) -> Option<&Entry> {
+        match &self.detail {
+            Some(detail) => self.entries.iter().find(|entry| entry.name == detail.session.session_name),
+            None => self.visible().get(self.selected).copied(),
+        }
+    }
+
+    /// Apply one key press for a screen `height` lines tall
+    pub fn update(&mut self, key: Key, height: usize) -> Action {
+        self.status = None;
+        let action = match self.prompt.clone() {
+            Prompt::None => self.update_view(key, height),
+            Prompt::Filter => {
+                match key {
+                    Key::Char(c) => self.filter.push(c),
+                    Key::Backspace => {
+                        self.filter.pop();
+                    }
+                    Key::Esc => {
+                        self.filter.clear();
+                        self.prompt = Prompt::None;
+                    }
+                    Key::Enter => self.prompt = Prompt::None,
+                    Key::Interrupt => return Action::Quit,
+                    _ => {}
+                }
+                self.selected = 0;
+                self.scroll = 0;
+                Action::None
+            }
+            Prompt::ConfirmDelete => {
+                self.prompt = Prompt::None;
+                match (key, self.current()) {
+                    (Key::Char('y') | Key::Char('Y'), Some(entry)) => Action::Delete(entry.name.clone()),
+                    _ => {
+                        self.status = Some("Nothing deleted".to_string());
+                        Action::None
+                    }
+                }
+            }
+            Prompt::Rename(mut text) | Prompt::Tags(mut text) => {
+                let renaming = matches!(self.prompt, Prompt::Rename(_));
+                match key {
+                    Key::Char(c) => text.push(c),
+                    Key::Backspace => {
+                        text.pop();
+                    }
+                    Key::Esc => {
+                        self.prompt = Prompt::None;
+                        return Action::None;
+                    }
+                    Key::Enter => {
+                        self.prompt = Prompt::None;
+                        let Some(entry) = self.current() else { return Action::None };
+                        let name = entry.name.clone();
+                        if !renaming {
+                            return Action::SetTags { name, tags: crate::stats::parse_session_tags(&text) };
+                        }
+                        let to = text.trim().to_string();
+                        if to.is_empty() || to == name {
+                            self.status = Some("Name unchanged".to_string());
+                            return Action::None;
+                        }
+                        return Action::Rename { from: name, to };
+                    }
+                    Key::Interrupt => return Action::Quit,
+                    _ => {}
+                }
+                self.prompt = if renaming { Prompt::Rename(text) } else { Prompt::Tags(text) };
+                Action::None
+            }
+        };
+        self.keep_selection_visible(height);
+        action
+    }
+
+    fn update_view(&mut self, key: Key, height: usize) -> Action {
+        // Keys that act on a session work in both views
+        if let Some(entry) = self.current() {
+            let name = entry.name.clone();
+            match key {
+                Key::Char('d') => {
+                    self.prompt = Prompt::ConfirmDelete;
+                    return Action::None;
+                }
+                Key::Char('r') => {
+                    self.prompt = Prompt::Rename(name);
+                    return Action::None;
+                }
+                Key::Char('t') => {
+                    self.prompt = Prompt::Tags(entry.tags.join(", "));
+                    return Action::None;
+                }
+                Key::Char('e') => return Action::Export(name),
+                _ => {}
+            }
+        }
+
+        match key {
+            Key::Char('q') | Key::Interrupt => return Action::Quit,
+            _ => {}
+        }
+
+        if self.detail.is_some() {
+            let rows = height.saturating_sub(DETAIL_HEADER_LINES + FOOTER_LINES).max(1);
+            let last = self.pane_lines().len().saturating_sub(rows);
+            match key {
+                Key::Tab => {
+                    self.pane = self.pane.next();
+                    self.scroll = 0;
+                }
+                Key::Up | Key::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+                Key::Down | Key::Char('j') => self.scroll = (self.scroll + 1).min(last),
+                Key::PageUp => self.scroll = self.scroll.saturating_sub(rows),
+                Key::PageDown => self.scroll = (self.scroll + rows).min(last),
+                Key::Home => self.scroll = 0,
+                Key::End => self.scroll = last,
+                Key::Esc | Key::Backspace => {
+                    self.detail = None;
+                    self.scroll = 0;
+                }
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        let count = self.visible().len();
+        let rows = list_rows(height);
+        match key {
+            Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            Key::PageUp => self.selected = self.selected.saturating_sub(rows),
+            Key::PageDown => self.selected = (self.selected + rows).min(count.saturating_sub(1)),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = count.saturating_sub(1),
+            Key::Char('/') => self.prompt = Prompt::Filter,
+            Key::Char('s') => {
+                self.sort = self.sort.next();
+                self.selected = 0;
+            }
+            Key::Esc => {
+                self.filter.clear();
+                self.selected = 0;
+            }
+            Key::Enter => {
+                if let Some(entry) = self.current() {
+                    return Action::Open(entry.name.clone());
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Scroll the list so the selected row is on screen
+    fn keep_selection_visible(&mut self, height: usize) {
+        if self.detail.is_some() {
+            return;
+        }
+        let count = self.visible().len();
+        self.selected = self.selected.min(count.saturating_sub(1));
+        let rows = list_rows(height);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+    }
+
+    /// Show `export` in the detail view
+    pub fn opened(&mut self, export: SessionExport) {
+        self.detail = Some(export);
+        self.pane = Pane::Apps;
+        self.scroll = 0;
+    }
+
+    pub fn deleted(&mut self, name: &str) {
+        self.entries.retain(|entry| entry.name != name);
+        if self.detail.as_ref().is_some_and(|detail| detail.session.session_name == name) {
+            self.detail = None;
+            self.scroll = 0;
+        }
+    }
+
+    pub fn renamed(&mut self, from: &str, to: &str) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.name == from) {
+            entry.name = to.to_string();
+        }
+        if let Some(detail) = self.detail.as_mut().filter(|detail| detail.session.session_name == from) {
+            detail.session.session_name = to.to_string();
+        }
+    }
+
+    pub fn tagged(&mut self, name: &str, tags: Vec<String>) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.tags = tags;
+        }
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    /// Lines of the current detail pane
+    fn pane_lines(&self) -> Vec<String> {
+        let Some(detail) = &self.detail else {
+            return Vec::new();
+        };
+        let usage = |usage: &[(String, Duration, bool)]| -> Vec<String> {
+            usage.iter()
+                .map(|(name, duration, focus)| format!("{} {:<32} {:>9}",
+                    if *focus { "●" } else { "○" },
+                    utils::truncate_chars(name, 32),
+                    utils::format_duration(*duration, DurationStyle::Long)))
+                .collect()
+        };
+        let lines = match self.pane {
+            Pane::Apps => usage(&detail.session.app_usage),
+            Pane::Domains => usage(&detail.session.domain_usage),
+            Pane::Switches => detail.switches.iter()
+                .map(|switch| {
+                    let recovery = switch.recovery_time
+                        .map(|recovery| format!("  back in {}", utils::format_duration(recovery, DurationStyle::Long)))
+                        .unwrap_or_default();
+                    format!("{}  {} → {}{}", utils::format_timestamp_local(switch.timestamp), switch.from_app, switch.to_app, recovery)
+                })
+                .collect(),
+        };
+        if lines.is_empty() {
+            vec![format!("No {} recorded", self.pane.label().to_lowercase())]
+        } else {
+            lines
+        }
+    }
+
+    /// The screen as `height` lines at most `width` characters wide
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let mut lines = match &self.detail {
+            Some(detail) => self.render_detail(detail, width, height),
+            None => self.render_list(width, height),
+        };
+        lines.truncate(height.saturating_sub(FOOTER_LINES));
+        lines.resize(height.saturating_sub(FOOTER_LINES), String::new());
+        lines.push(self.footer());
+        // Names stored before they were sanitized may hold escape sequences
+        lines.into_iter().map(|line| utils::truncate_chars(&utils::escape_control_chars(&line), width)).collect()
+    }
+
+    fn render_list(&self, width: usize, height: usize) -> Vec<String> {
+        let visible = self.visible();
+        let mut title = format!("FocusDebt sessions · {} of {} · sorted by {}", visible.len(), self.entries.len(), self.sort.label());
+        if !self.filter.is_empty() {
+            title.push_str(&format!(" · filter: {}", self.filter));
+        }
+
+        // Name and tags share what the fixed columns leave
+        let fixed = 2 + 16 + 2 + 9 + 2 + 5 + 2;
+        let flexible = width.saturating_sub(fixed);
+        let tags_width = (flexible / 3).min(24);
+        let name_width = flexible.saturating_sub(tags_width + 2).max(8);
+        let mut lines = vec![
+            title,
+            format!("  {:<16}  {:<name_width$}  {:>9}  {:>5}  TAGS", "STARTED", "NAME", "DURATION", "FOCUS"),
+        ];
+        if visible.is_empty() {
+            lines.push(if self.entries.is_empty() {
+                "  No sessions in the last 30 days".to_string()
+            } else {
+                "  No session matches the filter (Esc clears it)".to_string()
+            });
+        }
+        for (index, entry) in visible.iter().enumerate().skip(self.scroll).take(list_rows(height)) {
+            lines.push(format!("{} {:<16}  {:<name_width$}  {:>9}  {:>4.0}%  {}",
+                if index == self.selected { ">" } else { " " },
+                utils::truncate_chars(&utils::format_datetime_local(entry.start_time), 16),
+                utils::truncate_chars(&entry.name, name_width),
+                utils::format_duration(entry.duration, DurationStyle::Long),
+                entry.efficiency,
+                utils::truncate_chars(&entry.tags.join(", "), tags_width)));
+        }
+        lines
+    }
+
+    fn render_detail(&self, detail: &SessionExport, width: usize, height: usize) -> Vec<String> {
+        let session = &detail.session;
+        let end = session.describe_end(utils::format_timestamp_local);
+        let tags = self.current().map(|entry| entry.tags.join(", ")).unwrap_or_default();
+        let cells = width.saturating_sub(2).max(10);
+        let end_label = session.end_time.map(utils::format_timestamp_local).unwrap_or_default();
+        let start_label = utils::format_timestamp_local(session.start_time);
+        let mut lines = vec![
+            format!("{} · {} → {} ({}) · {:.0}% focus",
+                session.session_name,
+                utils::format_datetime_local(session.start_time),
+                end,
+                utils::format_duration(session.total_duration, DurationStyle::Long),
+                session.focus_efficiency),
+            if tags.is_empty() { "No tags".to_string() } else { format!("Tags: {}", tags) },
+            String::new(),
+            format!("[{}]", timeline(detail, cells)),
+            format!(" {}{:>pad$}", start_label, end_label, pad = cells.saturating_sub(start_label.chars().count())),
+            String::new(),
+            Pane::ALL.iter()
+                .map(|pane| if *pane == self.pane { format!("[{}]", pane.label()) } else { format!(" {} ", pane.label()) })
+                .collect::<Vec<_>>()
+                .join(" "),
+        ];
+        let rows = height.saturating_sub(DETAIL_HEADER_LINES + FOOTER_LINES);
+        lines.extend(self.pane_lines().into_iter().skip(self.scroll).take(rows).map(|line| format!("  {}", line)));
+        lines
+    }
+
+    fn footer(&self) -> String {
+        let name = self.current().map(|entry| entry.name.as_str()).unwrap_or_default();
+        match &self.prompt {
+            Prompt::Filter => format!("Filter: {}█  (Enter keeps it, Esc clears it)", self.filter),
+            Prompt::ConfirmDelete => format!("Delete \"{}\" and all its rows? This can't be undone. (y/N)", name),
+            Prompt::Rename(text) => format!("Rename to: {}█  (Enter saves, Esc cancels)", text),
+            Prompt::Tags(text) => format!("Tags, comma-separated: {}█  (Enter saves, Esc cancels)", text),
+            Prompt::None => match &self.status {
+                Some(status) => status.clone(),
+                None if self.detail.is_some() => "Tab pane  ↑↓ scroll  d delete  r rename  t tags  e export  Esc back  q quit".to_string(),
+                None => "↑↓ move  Enter open  / filter  s sort  d delete  r rename  t tags  e export  q quit".to_string(),
+            },
+        }
+    }
+}
+
+/// List rows that fit on a screen `height` lines tall
+fn list_rows(height: usize) -> usize {
+    height.saturating_sub(LIST_HEADER_LINES + FOOTER_LINES).max(1)
+}
+
+/// The session across `cells` characters: █ focus, ░ anything else (# and
+/// - with ASCII glyphs), a space where nothing was tracked
+pub fn timeline(detail: &SessionExport, cells: usize) -> String {
+    let glyphs = glyphs::current();
+    let (_, other) = glyphs.bar_cells(BarStyle::Shade);
+    let start = detail.session.start_time;
+    let total = detail.session.total_duration;
+    if total.is_zero() {
+        return " ".repeat(cells);
+    }
+    (0..cells)
+        .map(|cell| {
+            let at = start + chrono::Duration::from_std(total.mul_f64((cell as f64 + 0.5) / cells as f64)).unwrap_or_default();
+            let row = detail.rows.iter().find(|row| {
+                row.start_time <= at && at < row.start_time + chrono::Duration::from_std(row.duration).unwrap_or_default()
+            });
+            match row {
+                Some(row) if row.is_focus_app => glyphs.solid(),
+                Some(_) => other,
+                None => ' ',
+            }
+        })
+        .collect()
+}
+
+/// Decode what the terminal sent: printable characters, control keys and
+/// the usual escape sequences for arrows, paging, Home and End
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if matches!(chars.peek(), Some('[') | Some('O')) => {
+                chars.next();
+                let mut code = String::new();
+                while let Some(&digit) = chars.peek().filter(|c| c.is_ascii_digit() || **c == ';') {
+                    code.push(digit);
+                    chars.next();
+                }
+                match (chars.next(), code.as_str()) {
+                    (Some('A'), _) => Key::Up,
+                    (Some('B'), _) => Key::Down,
+                    (Some('H'), _) => Key::Home,
+                    (Some('F'), _) => Key::End,
+                    (Some('Z'), _) => Key::Tab,
+                    (Some('~'), "5") => Key::PageUp,
+                    (Some('~'), "6") => Key::PageDown,
+                    (Some('~'), "1" | "7") => Key::Home,
+                    (Some('~'), "4" | "8") => Key::End,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Esc,
+            '\r' | '\n' => Key::Enter,
+            '\t' => Key::Tab,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' | '\x04' => Key::Interrupt,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// File name `e` exports a session to, e.g. "session-morning-coding.json"
+pub fn export_file_name(name: &str) -> String {
+    let slug: String = name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    format!("session-{}.json", if slug.is_empty() { "unnamed" } else { &slug })
+}
+
+/// The terminal in raw mode on the alternate screen, put back when dropped
+#[cfg(unix)]
+struct Screen {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        let original = unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(0, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = termios;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(0, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            termios
+        };
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        Ok(Self { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &self.original) };
+    }
+}
+
+/// Run `browser` on the terminal until it quits. `apply` carries out every
+/// other action and reports back to the browser.
+#[cfg(unix)]
+pub fn run(browser: &mut Browser, mut apply: impl FnMut(&mut Browser, Action)) -> io::Result<()> {
+    let _screen = Screen::enter()?;
+    let mut stdin = io::stdin();
+    let mut buffer = [0u8; 64];
+    loop {
+        let (width, height) = terminal_size::terminal_size()
+            .map(|(width, height)| (width.0 as usize, height.0 as usize))
+            .unwrap_or((utils::DEFAULT_REPORT_WIDTH, 24));
+        let mut frame = String::from("\x1b[H");
+        for line in browser.render(width, height) {
+            frame.push_str(&line);
+            frame.push_str("\x1b[K\r\n");
+        }
+        // The last line's newline would scroll the screen
+        frame.truncate(frame.len() - 2);
+        let mut stdout = io::stdout();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()?;
+
+        let read = stdin.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        for key in parse_keys(&buffer[..read]) {
+            match browser.update(key, height) {
+                Action::None => {}
+                Action::Quit => return Ok(()),
+                action => apply(browser, action),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run(_browser: &mut Browser, _apply: impl FnMut(&mut Browser, Action)) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the session browser needs a Unix terminal; use 'sessions list' and 'sessions show'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{Stats, StatsSettings};
+    use crate::storage::Database;
+    use crate::tracking::{ContextSwitch, FocusSession, OpenEnd};
+
+    /// Tall enough for seven list rows
+    const HEIGHT: usize = 10;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn entry(name: &str, start: &str, minutes: u64, efficiency: f64, tags: &[&str]) -> Entry {
+        Entry {
+            name: name.to_string(),
+            start_time: at(start),
+            end_time: Some(at(start) + chrono::Duration::minutes(minutes as i64)),
+            duration: Duration::from_secs(minutes * 60),
+            efficiency,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn browser() -> Browser {
+        Browser::new(vec![
+            entry("morning coding", "2024-05-20T08:00:00Z", 120, 81.0, &["api"]),
+            entry("Review", "2024-05-21T13:00:00Z", 45, 40.0, &["team", "api"]),
+            entry("planning", "2024-05-22T09:00:00Z", 30, 95.0, &[]),
+        ])
+    }
+
+    fn names(browser: &Browser) -> Vec<&str> {
+        browser.visible().iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn press(browser: &mut Browser, keys: &str) -> Action {
+        let mut action = Action::None;
+        for c in keys.chars() {
+            action = browser.update(Key::Char(c), HEIGHT);
+        }
+        action
+    }
+
+    #[test]
+    fn sorting_cycles_date_duration_and_efficiency() {
+        let mut browser = browser();
+        assert_eq!(names(&browser), ["planning", "Review", "morning coding"]);
+        press(&mut browser, "j");
+        press(&mut browser, "s");
+        assert_eq!(names(&browser), ["morning coding", "Review", "planning"]);
+        // Sorting again starts at the top
+        assert_eq!(browser.current().unwrap().name, "morning coding");
+        press(&mut browser, "s");
+        assert_eq!(names(&browser), ["planning", "morning coding", "Review"]);
+        press(&mut browser, "s");
+        assert_eq!(names(&browser), ["planning", "Review", "morning coding"]);
+
+        // Equal keys fall back to the name
+        let mut tied = Browser::new(vec![
+            entry("b", "2024-05-20T08:00:00Z", 30, 50.0, &[]),
+            entry("a", "2024-05-20T08:00:00Z", 30, 50.0, &[]),
+        ]);
+        for _ in 0..3 {
+            assert_eq!(names(&tied), ["a", "b"]);
+            press(&mut tied, "s");
+        }
+    }
+
+    #[test]
+    fn typing_filters_by_name_and_tag() {
+        let mut browser = browser();
+        press(&mut browser, "j");
+        assert_eq!(press(&mut browser, "/api"), Action::None);
+        assert_eq!(names(&browser), ["Review", "morning coding"]);
+        assert_eq!(browser.current().unwrap().name, "Review");
+        // Every word has to match, in any case; q is typed rather than quitting
+        press(&mut browser, " REV");
+        assert_eq!(names(&browser), ["Review"]);
+        browser.update(Key::Backspace, HEIGHT);
+        browser.update(Key::Backspace, HEIGHT);
+        browser.update(Key::Backspace, HEIGHT);
+        assert_eq!(names(&browser).len(), 2);
+        assert_eq!(press(&mut browser, " q"), Action::None);
+        assert!(names(&browser).is_empty());
+        assert!(browser.render(80, HEIGHT)[2].contains("No session matches the filter"));
+
+        // Enter keeps the filter, Esc in the list clears it
+        browser.update(Key::Backspace, HEIGHT);
+        browser.update(Key::Enter, HEIGHT);
+        assert_eq!(names(&browser).len(), 2);
+        assert_eq!(browser.render(80, HEIGHT)[0].trim_end(), "FocusDebt sessions · 2 of 3 · sorted by date · filter: api");
+        browser.update(Key::Esc, HEIGHT);
+        assert_eq!(names(&browser).len(), 3);
+
+        // Esc while typing drops it too
+        press(&mut browser, "/plan");
+        browser.update(Key::Esc, HEIGHT);
+        assert_eq!(names(&browser).len(), 3);
+        assert_eq!(browser.update(Key::Char('q'), HEIGHT), Action::Quit);
+    }
+
+    #[test]
+    fn the_selection_stays_in_the_list_and_on_screen() {
+        let entries = (0..20)
+            .map(|n| entry(&format!("session {:02}", n), "2024-05-20T08:00:00Z", 30 + n, 50.0, &[]))
+            .collect();
+        let mut browser = Browser::new(entries);
+        browser.update(Key::Up, HEIGHT);
+        assert_eq!(browser.selected, 0);
+        browser.update(Key::PageDown, HEIGHT);
+        assert_eq!((browser.selected, browser.scroll), (7, 1));
+        browser.update(Key::End, HEIGHT);
+        assert_eq!((browser.selected, browser.scroll), (19, 13));
+        browser.update(Key::Down, HEIGHT);
+        assert_eq!(browser.selected, 19);
+        browser.update(Key::PageUp, HEIGHT);
+        assert_eq!((browser.selected, browser.scroll), (12, 12));
+        browser.update(Key::Home, HEIGHT);
+        assert_eq!((browser.selected, browser.scroll), (0, 0));
+
+        let screen = browser.render(80, HEIGHT);
+        assert_eq!(screen.len(), HEIGHT);
+        assert!(screen[2].starts_with("> "), "{:?}", screen);
+        assert!(screen[3].starts_with("  "), "{:?}", screen);
+        assert!(screen.iter().all(|line| line.chars().count() <= 80));
+
+        // An empty list has nothing to act on
+        let mut empty = Browser::new(Vec::new());
+        assert_eq!(empty.update(Key::Enter, HEIGHT), Action::None);
+        assert_eq!(press(&mut empty, "drte"), Action::None);
+        assert!(empty.render(80, HEIGHT)[2].contains("No sessions in the last 30 days"));
+    }
+
+    #[test]
+    fn delete_asks_first() {
+        let mut browser = browser();
+        assert_eq!(press(&mut browser, "d"), Action::None);
+        assert!(browser.render(80, HEIGHT).last().unwrap().starts_with("Delete \"planning\" and all its rows?"));
+        assert_eq!(press(&mut browser, "n"), Action::None);
+        assert_eq!(browser.render(80, HEIGHT).last().unwrap(), "Nothing deleted");
+        // Any key clears the status
+        browser.update(Key::Down, HEIGHT);
+        assert!(browser.render(80, HEIGHT).last().unwrap().starts_with("↑↓ move"));
+
+        assert_eq!(press(&mut browser, "dY"), Action::Delete("Review".to_string()));
+        browser.deleted("Review");
+        assert_eq!(names(&browser), ["planning", "morning coding"]);
+        assert_eq!(browser.current().unwrap().name, "morning coding");
+        browser.deleted("morning coding");
+        assert_eq!(browser.update(Key::Down, HEIGHT), Action::None);
+        assert_eq!(browser.current().unwrap().name, "planning");
+    }
+
+    #[test]
+    fn rename_and_tags_are_edited_inline() {
+        let mut browser = browser();
+        press(&mut browser, "r");
+        assert_eq!(browser.prompt, Prompt::Rename("planning".to_string()));
+        for _ in 0..4 {
+            browser.update(Key::Backspace, HEIGHT);
+        }
+        press(&mut browser, "s and d");
+        assert_eq!(browser.update(Key::Enter, HEIGHT), Action::Rename { from: "planning".to_string(), to: "plans and d".to_string() });
+        browser.renamed("planning", "plans and d");
+        assert_eq!(names(&browser)[0], "plans and d");
+
+        // Unchanged or blank names go nowhere
+        press(&mut browser, "r");
+        assert_eq!(browser.update(Key::Enter, HEIGHT), Action::None);
+        assert_eq!(browser.render(80, HEIGHT).last().unwrap(), "Name unchanged");
+        press(&mut browser, "r");
+        browser.update(Key::Esc, HEIGHT);
+        assert_eq!(browser.prompt, Prompt::None);
+
+        // Tags start from the current ones and are tidied on the way out
+        browser.update(Key::Down, HEIGHT);
+        press(&mut browser, "t");
+        assert_eq!(browser.prompt, Prompt::Tags("team, api".to_string()));
+        press(&mut browser, ", Deep ,api");
+        let action = browser.update(Key::Enter, HEIGHT);
+        assert_eq!(action, Action::SetTags {
+            name: "Review".to_string(),
+            tags: vec!["api".to_string(), "deep".to_string(), "team".to_string()],
+        });
+        if let Action::SetTags { name, tags } = action {
+            browser.tagged(&name, tags);
+        }
+        press(&mut browser, "/deep");
+        assert_eq!(names(&browser), ["Review"]);
+        assert_eq!(browser.update(Key::Enter, HEIGHT), Action::None);
+        assert_eq!(press(&mut browser, "e"), Action::Export("Review".to_string()));
+    }
+
+    fn row(start: &str, app: &str, minutes: i64, is_focus_app: bool) -> FocusSession {
+        FocusSession {
+            start_time: at(start),
+            end_time: Some(at(start) + chrono::Duration::minutes(minutes)),
+            app_name: app.to_string(),
+            window_title: String::new(),
+            domain: None,
+            duration: Duration::from_secs(minutes as u64 * 60),
+            is_focus_app,
+            session_name: "planning".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    fn planning_export() -> SessionExport {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        db.save_focus_session(&row("2024-05-22T09:00:00Z", "fd-editor", 20, true)).unwrap();
+        db.save_focus_session(&row("2024-05-22T09:20:00Z", "fd-chat", 10, false)).unwrap();
+        db.save_context_switch(&ContextSwitch {
+            timestamp: at("2024-05-22T09:20:00Z"),
+            from_app: "fd-editor".to_string(),
+            to_app: "fd-chat".to_string(),
+            recovery_time: None,
+        }).unwrap();
+        Stats::load_session_export(&db, &StatsSettings::default(), "planning").unwrap()
+    }
+
+    #[test]
+    fn the_detail_view_has_panes_and_a_timeline() {
+        let mut browser = browser();
+        assert_eq!(browser.update(Key::Enter, HEIGHT), Action::Open("planning".to_string()));
+        browser.opened(planning_export());
+        let screen = browser.render(80, 12);
+        assert!(screen[0].starts_with("planning · "), "{:?}", screen);
+        assert_eq!(screen[1], "No tags");
+        assert_eq!(screen[6], "[Apps]  Domains   Switches ");
+        assert!(screen[7].contains("● fd-editor"), "{:?}", screen);
+        assert!(screen[8].contains("○ fd-chat"), "{:?}", screen);
+
+        // Two thirds focus, then the rest
+        let timeline = timeline(browser.detail.as_ref().unwrap(), 30);
+        let solid = glyphs::current().solid();
+        assert_eq!(timeline.chars().filter(|c| *c == solid).count(), 20);
+        assert!(timeline.starts_with(solid));
+
+        browser.update(Key::Tab, HEIGHT);
+        assert_eq!(browser.render(80, 12)[7], "  No domains recorded");
+        browser.update(Key::Tab, HEIGHT);
+        assert!(browser.render(80, 12)[7].ends_with("fd-editor → fd-chat"));
+        browser.update(Key::Tab, HEIGHT);
+        assert_eq!(browser.pane, Pane::Apps);
+
+        // Session keys act on the open session; Esc goes back to the list
+        assert_eq!(press(&mut browser, "e"), Action::Export("planning".to_string()));
+        browser.update(Key::Esc, HEIGHT);
+        assert!(browser.detail.is_none());
+        assert_eq!(browser.current().unwrap().name, "planning");
+
+        // Renaming and deleting the open session keep the view in step
+        browser.opened(planning_export());
+        browser.renamed("planning", "plans");
+        assert!(browser.render(80, 12)[0].starts_with("plans · "));
+        browser.deleted("plans");
+        assert!(browser.detail.is_none());
+        assert_eq!(names(&browser), ["Review", "morning coding"]);
+    }
+
+    #[test]
+    fn terminal_bytes_decode_to_keys() {
+        assert_eq!(parse_keys(b"\x1b[A\x1b[B\x1bOH\x1b[F"), [Key::Up, Key::Down, Key::Home, Key::End]);
+        assert_eq!(parse_keys(b"\x1b[5~\x1b[6~\x1b[1~\x1b[4~\x1b[Z"), [Key::PageUp, Key::PageDown, Key::Home, Key::End, Key::Tab]);
+        assert_eq!(parse_keys(b"\x1b"), [Key::Esc]);
+        assert_eq!(parse_keys(b"a\r\n\t\x7f\x03"), [Key::Char('a'), Key::Enter, Key::Enter, Key::Tab, Key::Backspace, Key::Interrupt]);
+        // Unknown sequences and other control characters are dropped
+        assert_eq!(parse_keys(b"\x1b[1;5C\x01x"), [Key::Char('x')]);
+        assert_eq!(parse_keys("é".as_bytes()), [Key::Char('é')]);
+    }
+
+    #[test]
+    fn exports_are_named_after_the_session() {
+        assert_eq!(export_file_name("Morning coding"), "session-morning-coding.json");
+        assert_eq!(export_file_name("  API / review #2 "), "session-api-review-2.json");
+        assert_eq!(export_file_name("Fokus über alles"), "session-fokus-über-alles.json");
+        assert_eq!(export_file_name("***"), "session-unnamed.json");
+    }
+}
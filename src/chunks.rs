@@ -0,0 +1,432 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::progress::Progress;
+use crate::stats::{self, DailyStats, Stats};
+use crate::team::{self, AnonymizedDay, AnonymizedExport};
+use crate::utils;
+
+/// Days each chunk of a chunked export covers
+pub const CHUNK_DAYS: usize = 7;
+
+/// Exports to a file covering more days than this are written in chunks
+pub const CHUNKED_EXPORT_MIN_DAYS: usize = 8 * CHUNK_DAYS;
+
+/// Bump when the manifest's fields change meaning
+const MANIFEST_VERSION: u32 = 1;
+
+/// What `export` can write. Each format renders a run of days on its own
+/// and puts such runs back together, so an export can be made in chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    DailyCsv,
+    AnonymizedJson,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "daily-csv" => Some(ExportFormat::DailyCsv),
+            "anonymized-json" => Some(ExportFormat::AnonymizedJson),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::DailyCsv => "csv",
+            ExportFormat::AnonymizedJson => "json",
+        }
+    }
+
+    /// One chunk's days: CSV rows without the header, or the anonymized days
+    pub fn render(&self, days: &[DailyStats]) -> Result<String, String> {
+        match self {
+            ExportFormat::DailyCsv => Ok(Stats::daily_summary_csv_rows(days)),
+            ExportFormat::AnonymizedJson => serde_json::to_string(&team::anonymize(days).days)
+                .map_err(|e| format!("couldn't encode the anonymized days: {}", e)),
+        }
+    }
+
+    /// The export from its chunks in order, the same as rendering all of
+    /// their days at once
+    pub fn assemble(&self, chunks: &[String]) -> Result<String, String> {
+        match self {
+            ExportFormat::DailyCsv => Ok(format!("{}{}", stats::DAILY_CSV_HEADER, chunks.concat())),
+            ExportFormat::AnonymizedJson => {
+                let mut export = team::anonymize(&[]);
+                for chunk in chunks {
+                    let days: Vec<AnonymizedDay> = serde_json::from_str(chunk)
+                        .map_err(|e| format!("a chunk isn't anonymized days: {}", e))?;
+                    export.days.extend(days);
+                }
+                encode_anonymized(&export)
+            }
+        }
+    }
+
+    /// `days` in one go, for exports too small to chunk
+    pub fn render_all(&self, days: &[DailyStats]) -> Result<String, String> {
+        match self {
+            ExportFormat::DailyCsv => Ok(Stats::daily_summary_csv(days)),
+            ExportFormat::AnonymizedJson => encode_anonymized(&team::anonymize(days)),
+        }
+    }
+}
+
+fn encode_anonymized(export: &AnonymizedExport) -> Result<String, String> {
+    serde_json::to_string_pretty(export)
+        .map(|json| json + "\n")
+        .map_err(|e| format!("couldn't encode the anonymized export: {}", e))
+}
+
+/// A chunk written to the parts directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkRecord {
+    pub start: NaiveDate,
+    pub days: usize,
+    /// File name in the parts directory
+    pub file: String,
+    pub bytes: u64,
+    /// `utils::stable_hash` of the file
+    pub checksum: String,
+}
+
+/// The sidecar of a chunked export, `<output>.manifest.json`: what is being
+/// exported and the chunks written so far, for `export --resume`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub version: u32,
+    pub format: ExportFormat,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub apply_current_rules: bool,
+    /// Whether the archives are read too (`--include-archives`)
+    #[serde(default)]
+    pub include_archives: bool,
+    pub output: PathBuf,
+    pub chunks: Vec<ChunkRecord>,
+}
+
+impl ChunkManifest {
+    pub fn new(format: ExportFormat, start: NaiveDate, end: NaiveDate, apply_current_rules: bool, include_archives: bool, output: PathBuf) -> Self {
+        Self { version: MANIFEST_VERSION, format, start, end, apply_current_rules, include_archives, output, chunks: Vec::new() }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let manifest: Self = serde_json::from_str(&text).map_err(|e| format!("{} is not an export manifest: {}", path.display(), e))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(format!("{} has manifest version {}, expected {}", path.display(), manifest.version, MANIFEST_VERSION));
+        }
+        Ok(manifest)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        utils::write_private_file(path, text).map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+    }
+
+    /// Days in the export
+    pub fn total_days(&self) -> usize {
+        (self.end - self.start).num_days() as usize + 1
+    }
+
+    /// Days no chunk covers yet
+    pub fn remaining_days(&self) -> usize {
+        ((self.end - self.next_day()).num_days() + 1).max(0) as usize
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.total_days().div_ceil(CHUNK_DAYS)
+    }
+
+    /// The first day no chunk covers yet
+    fn next_day(&self) -> NaiveDate {
+        self.chunks.last()
+            .map(|chunk| chunk.start + chrono::Duration::days(chunk.days as i64))
+            .unwrap_or(self.start)
+    }
+}
+
+/// `<output>.manifest.json`
+pub fn manifest_path(output: &Path) -> PathBuf {
+    with_suffix(output, ".manifest.json")
+}
+
+/// `<output>.parts`, where the chunks are written
+pub fn parts_directory(output: &Path) -> PathBuf {
+    with_suffix(output, ".parts")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Whether `chunk` is in the parts directory as it was written
+fn is_intact(parts: &Path, chunk: &ChunkRecord) -> bool {
+    fs::read(parts.join(&chunk.file))
+        .is_ok_and(|bytes| bytes.len() as u64 == chunk.bytes && utils::stable_hash(&bytes) == chunk.checksum)
+}
+
+/// Drop the chunks that are missing or changed since they were written,
+/// and every one after the first of them; returns how many were dropped
+pub fn verify(manifest: &mut ChunkManifest) -> usize {
+    let parts = parts_directory(&manifest.output);
+    let intact = manifest.chunks.iter().take_while(|chunk| is_intact(&parts, chunk)).count();
+    let dropped = manifest.chunks.len() - intact;
+    manifest.chunks.truncate(intact);
+    dropped
+}
+
+/// Write the chunks `manifest` doesn't have yet, saving it to
+/// `manifest_file` after each, then put them together into the output and
+/// remove the parts and the manifest. `days_of(start, days)` gives the
+/// stats of a chunk's days. Stopped by an error (or killed), the manifest
+/// keeps what was written for `--resume`.
+pub fn run(
+    manifest: &mut ChunkManifest,
+    manifest_file: &Path,
+    progress: &mut Progress,
+    mut days_of: impl FnMut(NaiveDate, usize) -> Result<Vec<DailyStats>, String>,
+) -> Result<(), String> {
+    let parts = parts_directory(&manifest.output);
+    fs::create_dir_all(&parts).map_err(|e| format!("couldn't create {}: {}", parts.display(), e))?;
+    manifest.save(manifest_file)?;
+
+    while manifest.next_day() <= manifest.end {
+        let start = manifest.next_day();
+        let days = ((manifest.end - start).num_days() as usize + 1).min(CHUNK_DAYS);
+        let text = manifest.format.render(&days_of(start, days)?)
+            .map_err(|e| format!("week of {}: {}", start, e))?;
+        let file = format!("{:04}.{}", manifest.chunks.len() + 1, manifest.format.extension());
+        utils::write_private_file(&parts.join(&file), &text)
+            .map_err(|e| format!("couldn't write chunk {}: {}", file, e))?;
+        manifest.chunks.push(ChunkRecord {
+            start,
+            days,
+            file,
+            bytes: text.len() as u64,
+            checksum: utils::stable_hash(text.as_bytes()),
+        });
+        manifest.save(manifest_file)?;
+        progress.advance(days);
+    }
+
+    let mut chunks = Vec::with_capacity(manifest.chunks.len());
+    for chunk in &manifest.chunks {
+        if !is_intact(&parts, chunk) {
+            return Err(format!("chunk {} changed while the export ran; resume to write it again", chunk.file));
+        }
+        chunks.push(fs::read_to_string(parts.join(&chunk.file)).map_err(|e| format!("couldn't read chunk {}: {}", chunk.file, e))?);
+    }
+    let content = manifest.format.assemble(&chunks)?;
+    // Like a one-shot export; the parts stay until it is written
+    fs::write(&manifest.output, content)
+        .map_err(|e| format!("couldn't write {}: {}", manifest.output.display(), e))?;
+    let _ = fs::remove_dir_all(&parts);
+    let _ = fs::remove_file(manifest_file);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use chrono::{DateTime, Utc};
+    use crate::stats::{DayRecords, StatsSettings};
+    use crate::tracking::{FocusSession, OpenEnd};
+    use crate::utils::LocalDays;
+    use crate::utils::timezone::Zone;
+
+    fn first_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+    }
+
+    /// 70 days: ten chunks
+    fn last_day() -> NaiveDate {
+        first_day() + chrono::Duration::days(69)
+    }
+
+    fn session(start: DateTime<Utc>, app: &str, seconds: u64, is_focus_app: bool) -> FocusSession {
+        FocusSession {
+            start_time: start,
+            end_time: Some(start + chrono::Duration::seconds(seconds as i64)),
+            app_name: app.to_string(),
+            window_title: format!("{} window", app),
+            domain: None,
+            duration: Duration::from_secs(seconds),
+            is_focus_app,
+            session_name: "work".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::default(),
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    /// Stats that differ from day to day, with every fourth day empty
+    fn stats_of(start: NaiveDate, days: usize) -> Vec<DailyStats> {
+        let now = stats::start_of_day(last_day() + chrono::Duration::days(1));
+        (0..days).map(|offset| {
+            let day = start + chrono::Duration::days(offset as i64);
+            let n = (day - first_day()).num_days() as u64;
+            let mut records = DayRecords::default();
+            if n % 4 != 3 {
+                let at = stats::start_of_day(day) + chrono::Duration::hours(9);
+                records.sessions.push(session(at, "code", 600 + 60 * n, true));
+                records.sessions.push(session(at + chrono::Duration::hours(3), "slack", 300 + 7 * n, false));
+            }
+            Stats::daily_stats_from_records(&StatsSettings::default(), &LocalDays::in_zone(Zone::Named(chrono_tz::UTC), 0), day, &records, now)
+        }).collect()
+    }
+
+    struct Export {
+        dir: PathBuf,
+        output: PathBuf,
+        manifest_file: PathBuf,
+    }
+
+    impl Export {
+        fn new(name: &str, format: ExportFormat) -> Self {
+            let dir = std::env::temp_dir().join(format!("focusdebt-chunks-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let output = dir.join(format!("export.{}", format.extension()));
+            let manifest_file = manifest_path(&output);
+            Self { dir, output, manifest_file }
+        }
+
+        fn manifest(&self, format: ExportFormat) -> ChunkManifest {
+            ChunkManifest::new(format, first_day(), last_day(), false, false, self.output.clone())
+        }
+
+        /// Run until `fail_at` chunks have been asked for, like an export
+        /// killed part way through
+        fn run_failing_at(&self, manifest: &mut ChunkManifest, fail_at: Option<usize>) -> Result<(), String> {
+            let mut calls = 0;
+            run(manifest, &self.manifest_file, &mut Progress::hidden(), |start, days| {
+                calls += 1;
+                if Some(calls) == fail_at {
+                    return Err("injected failure".to_string());
+                }
+                Ok(stats_of(start, days))
+            })
+        }
+
+        /// Resume from the manifest on disk, as `export --resume` does
+        fn resume(&self) -> (usize, Result<(), String>) {
+            let mut manifest = ChunkManifest::load(&self.manifest_file).unwrap();
+            let dropped = verify(&mut manifest);
+            (dropped, self.run_failing_at(&mut manifest, None))
+        }
+    }
+
+    impl Drop for Export {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn one_shot(format: ExportFormat) -> String {
+        let days = (last_day() - first_day()).num_days() as usize + 1;
+        format.render_all(&stats_of(first_day(), days)).unwrap()
+    }
+
+    const FORMATS: [ExportFormat; 2] = [ExportFormat::DailyCsv, ExportFormat::AnonymizedJson];
+
+    #[test]
+    fn chunked_export_matches_a_one_shot_export() {
+        for format in FORMATS {
+            let export = Export::new(&format!("whole-{}", format.extension()), format);
+            let mut manifest = export.manifest(format);
+            assert_eq!(manifest.total_chunks(), 10);
+            export.run_failing_at(&mut manifest, None).unwrap();
+
+            assert!(one_shot(format).len() > 2000);
+            assert_eq!(fs::read_to_string(&export.output).unwrap(), one_shot(format));
+            assert!(!export.manifest_file.exists());
+            assert!(!parts_directory(&export.output).exists());
+        }
+    }
+
+    #[test]
+    fn killed_export_resumes_to_the_same_bytes() {
+        for format in FORMATS {
+            let export = Export::new(&format!("resume-{}", format.extension()), format);
+            let mut manifest = export.manifest(format);
+            assert_eq!(export.run_failing_at(&mut manifest, Some(5)).unwrap_err(), "injected failure");
+
+            assert!(!export.output.exists());
+            let saved = ChunkManifest::load(&export.manifest_file).unwrap();
+            assert_eq!(saved.chunks.len(), 4);
+            assert_eq!(saved.remaining_days(), 70 - 4 * CHUNK_DAYS);
+
+            let (dropped, result) = export.resume();
+            result.unwrap();
+            assert_eq!(dropped, 0);
+            assert_eq!(fs::read(&export.output).unwrap(), one_shot(format).into_bytes());
+            assert!(!export.manifest_file.exists());
+            assert!(!parts_directory(&export.output).exists());
+        }
+    }
+
+    #[test]
+    fn resume_rewrites_a_corrupted_chunk_and_everything_after_it() {
+        for format in FORMATS {
+            let export = Export::new(&format!("corrupt-{}", format.extension()), format);
+            let mut manifest = export.manifest(format);
+            export.run_failing_at(&mut manifest, Some(7)).unwrap_err();
+            let parts = parts_directory(&export.output);
+            let mut changed = fs::read(parts.join(&manifest.chunks[2].file)).unwrap();
+            changed[0] ^= 1;
+            fs::write(parts.join(&manifest.chunks[2].file), changed).unwrap();
+            fs::remove_file(parts.join(&manifest.chunks[4].file)).unwrap();
+
+            let mut verified = ChunkManifest::load(&export.manifest_file).unwrap();
+            assert_eq!(verify(&mut verified), 4);
+            assert_eq!(verified.chunks.len(), 2);
+
+            let (dropped, result) = export.resume();
+            result.unwrap();
+            assert_eq!(dropped, 4);
+            assert_eq!(fs::read(&export.output).unwrap(), one_shot(format).into_bytes());
+        }
+    }
+
+    #[test]
+    fn resume_after_a_second_kill_still_matches() {
+        let format = ExportFormat::DailyCsv;
+        let export = Export::new("twice", format);
+        let mut manifest = export.manifest(format);
+        export.run_failing_at(&mut manifest, Some(3)).unwrap_err();
+
+        let mut manifest = ChunkManifest::load(&export.manifest_file).unwrap();
+        assert_eq!(verify(&mut manifest), 0);
+        export.run_failing_at(&mut manifest, Some(4)).unwrap_err();
+        assert_eq!(ChunkManifest::load(&export.manifest_file).unwrap().chunks.len(), 5);
+
+        let (_, result) = export.resume();
+        result.unwrap();
+        assert_eq!(fs::read_to_string(&export.output).unwrap(), one_shot(format));
+    }
+
+    #[test]
+    fn manifest_of_another_version_is_refused() {
+        let export = Export::new("version", ExportFormat::DailyCsv);
+        let mut manifest = export.manifest(ExportFormat::DailyCsv);
+        manifest.version = MANIFEST_VERSION + 1;
+        manifest.save(&export.manifest_file).unwrap();
+        let error = ChunkManifest::load(&export.manifest_file).unwrap_err();
+        assert!(error.contains("manifest version"), "{}", error);
+    }
+}
@@ -0,0 +1,9254 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use std::process::{self, ExitCode};
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    activity, desktop, tracking, storage, stats, utils, config, alerts, hooks, progress, import,
+    digest, spill, logs, team, completions, audit, sounds, wrapped, browse, service, power,
+    estimate, usage, reset, tmux, glyphs, disk, demo, timing, notifications, conflicts, tasks,
+    operation, chunks,
+};
+
+use tracking::{EventKind, FocusTracker, TrackerEvent};
+use storage::Database;
+use stats::{Stats, StatsSettings};
+use utils::fuzzy::{self, FuzzyMatch};
+use utils::{DurationStyle, check_dependencies, is_daemon_running, write_pid_file, remove_pid_file, sleep_ms, ensure_data_directory};
+use config::{Config, ConfigChange};
+
+/// How long `stop` waits for the daemon to confirm its final flush
+const FLUSH_TIMEOUT_MS: u64 = 10_000;
+
+/// How often the daemon re-evaluates the efficiency floor
+const EFFICIENCY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+#[derive(Debug)]
+enum DatabaseCommand {
+    SaveSession(tracking::FocusSession),
+    SaveContextSwitch(tracking::ContextSwitch),
+    RecordEvent(tracking::TrackerEvent),
+    /// The daily session changed at `at`; everything sent before belongs to `from`
+    RollOver { from: String, to: String, at: chrono::DateTime<chrono::Utc> },
+    /// The settings `session` is tracked with, sent once detection works
+    SaveSessionMeta { session: String, at: chrono::DateTime<chrono::Utc>, snapshot: config::SessionSnapshot },
+    /// Sent after each flush with retention_days set; prunes at most once a day
+    ApplyRetention { keep_session: String, at: chrono::DateTime<chrono::Utc> },
+    /// A notification from `source` arrived during `session`
+    CountNotification { session: String, source: String, at: chrono::DateTime<chrono::Utc> },
+}
+
+/// Sender to the database thread that counts commands not yet handled
+struct DbSender {
+    tx: mpsc::Sender<DatabaseCommand>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl DbSender {
+    fn send(&self, command: DatabaseCommand) -> Result<(), mpsc::SendError<()>> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(command).map_err(|_| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            mpsc::SendError(())
+        })
+    }
+}
+
+/// How often an idle daemon looks for a planned session to start
+const PLAN_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A planned session the daemon is tracking in place of the daily session
+#[derive(Clone)]
+struct RunningPlan {
+    id: i64,
+    name: String,
+    until: chrono::DateTime<chrono::Utc>,
+}
+
+/// How often the daemon rewrites the state shown by `debug state`
+const DEBUG_STATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Shortest gap between two checks of the tracking loop taken for a
+/// suspend; at least three tracking intervals
+const SUSPEND_GAP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The suspend between two checks of the tracking loop, when `min_gap` or
+/// more passed between them
+fn detect_suspend(previous: chrono::DateTime<chrono::Utc>, checked_at: chrono::DateTime<chrono::Utc>, min_gap: std::time::Duration) -> Option<stats::SuspendGap> {
+    let gap = stats::SuspendGap { start: previous, end: checked_at };
+    (checked_at > previous && gap.duration() >= min_gap).then_some(gap)
+}
+
+/// Most often the tracking loop logs a window change, and the "Still on"
+/// line for a window that stays
+const WINDOW_CHANGE_LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+const STILL_ON_LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(100);
+
+#[derive(Parser)]
+#[command(name = "focusdebt")]
+#[command(about = "A CLI tool to track focus")]
+#[command(version = "0.1.0")]
+#[command(disable_help_flag = true)]
+#[command(disable_help_subcommand = true)]
+struct Cli {
+    /// Use the database of this profile instead of the default one
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Don't draw progress bars for long operations
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Aggregate on a single thread, for comparing against the parallel path
+    #[arg(long, global = true, hide = true)]
+    serial: bool,
+
+    /// Print where the command's time went (also FOCUSDEBT_PROFILE=1)
+    #[arg(long, global = true, hide = true)]
+    profile_self: bool,
+
+    /// Keep the database and runtime files here (also FOCUSDEBT_DATA_DIR)
+    #[arg(long, global = true)]
+    data_dir: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum Commands {
+    /// Start background tracking daemon
+    Start {
+        /// Allow reusing the name of an earlier session
+        #[arg(long)]
+        allow_duplicate: bool,
+        /// Track this display instead of the inherited one (":1" for X11, "wayland-1" for Wayland)
+        #[arg(long)]
+        display: Option<String>,
+        /// What the session is for, e.g. "write the RFC draft"; asked about at stop
+        #[arg(long)]
+        intent: Option<String>,
+        /// How long it should take, e.g. 90m or 2h
+        #[arg(long, value_parser = utils::parse_duration_arg)]
+        expect: Option<std::time::Duration>,
+        /// The task the session is for: a taskwarrior id or uuid, or text of a todo.txt line
+        #[arg(long)]
+        task: Option<String>,
+    },
+    /// Track in the foreground until Ctrl-C or until the wrapped command exits
+    ///
+    /// Examples:
+    ///   focusdebt track --name "fix bug"
+    ///   focusdebt track --name "fix bug" -- cargo test
+    Track {
+        /// Session name (asked for when omitted)
+        #[arg(long)]
+        name: Option<String>,
+        /// Allow reusing the name of an earlier session
+        #[arg(long)]
+        allow_duplicate: bool,
+        /// Command to run while tracking; tracking stops when it exits
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Show whether the daemon is running and what it is bound to
+    Status {
+        /// Also the daemon's own CPU and memory use
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    /// Check the setup for common problems
+    Doctor {
+        /// Remove files older versions left in the shared temp directory
+        #[arg(long)]
+        clean: bool,
+    },
+    /// List runtime files and remove stale ones: PID files and locks of
+    /// processes that are gone, partial writes, old rotated spill journals
+    Maintenance {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Start over: back up, stop the daemon, delete the database, reset the
+    /// config and remove runtime files
+    Reset {
+        /// Everything; the only kind of reset there is, so it can't be typed by accident
+        #[arg(long, required = true)]
+        all: bool,
+        /// Skip the JSON backup in the export directory
+        #[arg(long)]
+        no_backup: bool,
+        /// Also delete every other profile and forget the configured one
+        #[arg(long)]
+        profiles: bool,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Stop daemon and show session summary
+    Stop {
+        /// How the session went, 1 (poor) to 5 (great); asked for when it had an intent
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        rating: Option<u8>,
+        /// Whether the session's intent was met (yes or no)
+        #[arg(long, value_name = "yes|no", value_parser = clap::builder::BoolishValueParser::new())]
+        met: Option<bool>,
+    },
+    /// Silence the daemon's sound cues for a while
+    ///
+    /// Examples:
+    ///   focusdebt mute 1h
+    ///   focusdebt mute off
+    Mute {
+        /// How long, e.g. 30m or 1h, or 'off' to unmute; shows the mute when omitted
+        duration: Option<String>,
+    },
+    /// Sample the active window independently and compare it with what the daemon records
+    ///
+    /// Examples:
+    ///   focusdebt audit --minutes 30
+    ///   focusdebt audit --minutes 10 --save
+    Audit {
+        /// How long to sample
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..=1440))]
+        minutes: u64,
+        /// Also record the result as an audit event
+        #[arg(long)]
+        save: bool,
+    },
+    /// Check stats for the previous session
+    #[command(args_conflicts_with_subcommands = true)]
+    Stats {
+        #[command(subcommand)]
+        view: Option<StatsCommands>,
+        /// Context switches by weekday and hour instead of today's summary
+        #[arg(long)]
+        switch_heatmap: bool,
+        /// Weeks of history the heatmap averages over
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=52), requires = "switch_heatmap")]
+        weeks: u32,
+        /// Print today's summary (or the heatmap's raw matrix) as JSON
+        #[arg(long)]
+        json: bool,
+        /// Classify sessions by today's focus and ignore lists, not their stored flags
+        #[arg(long)]
+        apply_current_rules: bool,
+        /// Show each of the configured app groups as one row of the top apps
+        #[arg(long, conflicts_with = "switch_heatmap")]
+        by_group: bool,
+        /// Count only time tracked on this power source (ac or battery)
+        #[arg(long, value_parser = power::PowerSource::parse, conflicts_with = "switch_heatmap")]
+        power: Option<power::PowerSource>,
+        /// The running session so far, with where its efficiency is heading
+        #[arg(long, alias = "since-start", conflicts_with_all = ["switch_heatmap", "json"])]
+        current: bool,
+        /// Leave out the hourglass (show_ascii_art = false for good)
+        #[arg(long)]
+        no_art: bool,
+        /// Also read the rows moved out by `database archive`
+        #[arg(long)]
+        include_archives: bool,
+    },
+    /// Today's focus score, how it was made up and the last 7 days
+    Score,
+    /// Where today's focus time ends up if the rest of the day goes as usual
+    Estimate {
+        /// Goal in minutes instead of daily_focus_goal_minutes
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        goal: Option<u64>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Nicer display of stats for sharing
+    Share {
+        /// Add 7/30-day totals, the current streak and how this session ranks
+        #[arg(long)]
+        with_history: bool,
+        /// ascii (the full report), oneline, tweet (≤280 chars) or badge (SVG of today's focus)
+        #[arg(long, default_value = "ascii")]
+        format: String,
+        /// File the badge is written to (default focusdebt-badge.svg)
+        #[arg(long)]
+        output: Option<String>,
+        /// Leave out the hourglass (show_ascii_art = false for good)
+        #[arg(long)]
+        no_art: bool,
+    },
+    /// Manage focus applications
+    Focusapp {
+        #[command(subcommand)]
+        action: FocusappCommands,
+    },
+    /// Manage focus websites
+    Focussite {
+        #[command(subcommand)]
+        action: FocussiteCommands,
+    },
+    /// Manage configuration
+    /// 
+    /// Examples:
+    ///   focusdebt config set tracking_interval_ms 2000
+    ///   focusdebt config set save_interval_ms 60000
+    ///   focusdebt config set deep_focus_threshold_minutes 45
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+    /// Manage sessions
+    Sessions {
+        #[command(subcommand)]
+        action: SessionCommands,
+    },
+    /// See which focus rules classify tracked time
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommands,
+    },
+    /// List raw tracker events (window changes, daemon start/stop, alerts)
+    Events {
+        /// Start of the range (YYYY-MM-DD or RFC3339, default: today)
+        #[arg(long)]
+        from: Option<String>,
+        /// End of the range (YYYY-MM-DD or RFC3339, default: now)
+        #[arg(long)]
+        to: Option<String>,
+        /// Only show events of this kind
+        #[arg(long)]
+        kind: Option<String>,
+    },
+    /// Show the daemon log
+    ///
+    /// Examples:
+    ///   focusdebt logs --tail 50 --level warn
+    ///   focusdebt logs --since 10m --follow
+    ///   focusdebt logs clear
+    Logs {
+        /// Lines to show
+        #[arg(long, default_value_t = logs::DEFAULT_TAIL_LINES)]
+        tail: usize,
+        /// Keep printing new lines until Ctrl-C
+        #[arg(long)]
+        follow: bool,
+        /// Only lines from this long ago on, e.g. 10m or 2h
+        #[arg(long, value_parser = utils::parse_duration_arg)]
+        since: Option<std::time::Duration>,
+        /// Only lines at this level or above (info, warn, error)
+        #[arg(long)]
+        level: Option<String>,
+        #[command(subcommand)]
+        action: Option<LogsCommands>,
+    },
+    /// Export data for use elsewhere
+    Export {
+        /// Output format (daily-csv: one row per day, for spreadsheets)
+        #[arg(long, default_value = "daily-csv")]
+        format: String,
+        /// First day to export (YYYY-MM-DD, default: 30 days before the end)
+        #[arg(long)]
+        start_date: Option<String>,
+        /// Last day to export (YYYY-MM-DD, default: today)
+        #[arg(long)]
+        end_date: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+        /// Open the exported file in the default application afterwards
+        #[arg(long)]
+        open: bool,
+        /// Classify sessions by today's focus and ignore lists, not their stored flags
+        #[arg(long)]
+        apply_current_rules: bool,
+        /// Write per-day numbers only (no dates, apps, titles or names) as
+        /// JSON, for `team aggregate`
+        #[arg(long, conflicts_with = "format")]
+        anonymize: bool,
+        /// Also read the rows moved out by `database archive`
+        #[arg(long)]
+        include_archives: bool,
+        /// Continue an interrupted export from its .manifest.json
+        #[arg(long, value_name = "MANIFEST",
+            conflicts_with_all = ["format", "start_date", "end_date", "output", "open", "apply_current_rules", "anonymize", "include_archives"])]
+        resume: Option<String>,
+    },
+    /// Combined stats from several people's anonymized exports
+    ///
+    /// Examples:
+    ///   focusdebt team aggregate exports/
+    Team {
+        #[command(subcommand)]
+        action: TeamCommands,
+    },
+    /// Plain-text digest of last week, written to the export directory or mailed
+    ///
+    /// Examples:
+    ///   focusdebt digest --week
+    ///   focusdebt digest --week --mail-to me@example.com
+    Digest {
+        /// Digest the last full week (Monday to Sunday)
+        #[arg(long)]
+        week: bool,
+        /// Mail the digest through mail_command instead of writing a file
+        #[arg(long)]
+        mail_to: Option<String>,
+        /// Also read the rows moved out by `database archive`
+        #[arg(long)]
+        include_archives: bool,
+    },
+    /// Year in review: totals, records, top apps and a month-by-month chart
+    ///
+    /// Examples:
+    ///   focusdebt wrapped 2024
+    ///   focusdebt wrapped 2024 --format markdown --output wrapped.md
+    ///   focusdebt wrapped 2024 --format svg
+    Wrapped {
+        /// Year to review (default: this year)
+        year: Option<i32>,
+        /// ascii (the full report), markdown or svg (an image card)
+        #[arg(long, default_value = "ascii")]
+        format: String,
+        /// File to write to; the image defaults to focusdebt-wrapped-<year>.svg
+        #[arg(long)]
+        output: Option<String>,
+        /// Also read the rows moved out by `database archive`
+        #[arg(long)]
+        include_archives: bool,
+    },
+    /// Run a read-only SELECT against the database
+    ///
+    /// Examples:
+    ///   focusdebt query "SELECT * FROM v_daily ORDER BY local_date DESC LIMIT 7"
+    ///   focusdebt query "SELECT app_name, SUM(total_seconds) FROM v_app_daily GROUP BY 1" --csv
+    Query {
+        /// One SELECT (or WITH ... SELECT) statement
+        sql: String,
+        /// Print CSV instead of an aligned table
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Debug window detection
+    Debug {
+        /// Print the report as JSON, e.g. for bug reports
+        #[arg(long)]
+        json: bool,
+        #[command(subcommand)]
+        action: Option<DebugCommands>,
+    },
+    /// Manage database
+    Database {
+        #[command(subcommand)]
+        action: DatabaseCommands,
+    },
+    /// Import history from other trackers
+    Import {
+        #[command(subcommand)]
+        source: ImportCommands,
+    },
+    /// Record time spent away from the computer
+    ///
+    /// Examples:
+    ///   focusdebt add-entry --app reading --start 14:00 --duration 45m --focus --session "deep work"
+    ///   focusdebt add-entry --app whiteboard --start "yesterday 10:30" --duration 1h --session planning
+    AddEntry {
+        /// What the time went to, shown like an app name
+        #[arg(long)]
+        app: String,
+        /// When it started: "14:00" (the latest one), "yesterday 14:00", "fri 10:00", "2024-05-21 14:00" or RFC3339
+        #[arg(long)]
+        start: String,
+        /// How long it took, e.g. 45m or 1h30m
+        #[arg(long, value_parser = utils::parse_duration_arg)]
+        duration: std::time::Duration,
+        /// Count it as focus time
+        #[arg(long)]
+        focus: bool,
+        /// Session the time belongs to
+        #[arg(long)]
+        session: String,
+        /// A note, shown where window titles are
+        #[arg(long)]
+        title: Option<String>,
+        /// Add it even where tracked rows cover the same time
+        #[arg(long)]
+        allow_overlap: bool,
+    },
+    /// Fill a database with generated sessions, for screenshots and trying things out
+    ///
+    /// Examples:
+    ///   focusdebt --data-dir /tmp/focusdebt-demo demo generate
+    ///   focusdebt --data-dir /tmp/focusdebt-demo demo generate --days 30 --seed 7
+    Demo {
+        #[command(subcommand)]
+        action: DemoCommands,
+    },
+    /// Schedule sessions ahead of time
+    ///
+    /// Examples:
+    ///   focusdebt plan add "deep work" --at "tomorrow 09:00" --duration 2h
+    ///   focusdebt plan list
+    Plan {
+        #[command(subcommand)]
+        action: PlanCommands,
+    },
+    /// Background helpers managed by the user's service manager
+    ///
+    /// Examples:
+    ///   focusdebt service install --with-watchdog
+    ///   focusdebt service uninstall
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommands,
+    },
+    /// Notify when the daemon's heartbeat has gone stale (run by the watchdog timer)
+    HeartbeatCheck {
+        /// Start the daemon again, with its last session, if it died
+        #[arg(long)]
+        restart: bool,
+    },
+    /// Shell completion scripts
+    ///
+    /// Examples:
+    ///   focusdebt completions bash > focusdebt.bash
+    ///   focusdebt completions install zsh
+    #[command(args_conflicts_with_subcommands = true)]
+    Completions {
+        /// bash, zsh, fish or powershell; its script is printed
+        shell: Option<String>,
+        #[command(subcommand)]
+        action: Option<CompletionsCommands>,
+    },
+    /// Show help for all commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum FocusappCommands {
+    /// Add application to focus list
+    Add {
+        name: String,
+        /// Add the name as typed when nothing matches, even if it doesn't look like a process name
+        #[arg(long)]
+        allow_literal: bool,
+    },
+    /// Remove application from focus list
+    Remove { name: String },
+    /// List focus apps
+    List,
+    /// Suggest running GUI applications to add as focus apps
+    Suggest,
+    /// Show tracked time and first/last sighting of each focus and ignored app
+    Audit {
+        /// Flag entries unseen for this many days
+        #[arg(long, default_value_t = stats::STALE_APP_DAYS)]
+        stale_days: i64,
+        /// Print the audit as JSON, without offering removals
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show help for focusapp commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum ConfigCommands {
+    /// Show current configuration
+    Show {
+        /// Print every key's effective value and its source as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Set configuration value
+    Set {
+        /// Configuration key (tracking_interval_ms, save_interval_ms, deep_focus_threshold_minutes, efficiency_floor, efficiency_alerts)
+        #[arg(required_unless_present = "json")]
+        key: Option<String>,
+        /// Configuration value
+        #[arg(required_unless_present = "json")]
+        value: Option<String>,
+        /// Set several keys from a JSON object, all or none of them
+        #[arg(long, conflicts_with_all = ["key", "value"])]
+        json: Option<String>,
+    },
+    /// Reset configuration to defaults
+    Reset,
+    /// Write the configuration, focus lists included, to a portable bundle
+    Export {
+        /// Bundle file to write, e.g. bundle.toml
+        path: String,
+    },
+    /// Apply a bundle written by `config export`
+    Import {
+        /// Bundle file to read
+        path: String,
+        /// Replace the configuration instead of merging into it
+        #[arg(long)]
+        replace: bool,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show how changed focus lists or thresholds would classify recent days, without saving anything
+    Preview {
+        /// Focus app to add (repeatable)
+        #[arg(long, value_name = "APP")]
+        add_focus_app: Vec<String>,
+        /// Focus app to remove (repeatable)
+        #[arg(long, value_name = "APP")]
+        remove_focus_app: Vec<String>,
+        /// Ignored app to add (repeatable)
+        #[arg(long, value_name = "APP")]
+        add_ignored_app: Vec<String>,
+        /// Ignored app to remove (repeatable)
+        #[arg(long, value_name = "APP")]
+        remove_ignored_app: Vec<String>,
+        /// Focus site to add (repeatable)
+        #[arg(long, value_name = "SITE")]
+        add_focus_site: Vec<String>,
+        /// Focus site to remove (repeatable)
+        #[arg(long, value_name = "SITE")]
+        remove_focus_site: Vec<String>,
+        /// Ignored site to add (repeatable)
+        #[arg(long, value_name = "SITE")]
+        add_ignored_site: Vec<String>,
+        /// Ignored site to remove (repeatable)
+        #[arg(long, value_name = "SITE")]
+        remove_ignored_site: Vec<String>,
+        /// Threshold to change, e.g. deep_focus_threshold_minutes=45 (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
+        /// Days of history to reclassify
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u32).range(1..=365))]
+        days: u32,
+    },
+    /// Check the focus rules for contradictions and rules that never apply
+    Doctor,
+    /// Show help for config commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum DatabaseCommands {
+    /// Clear all data from database
+    Clear,
+
+    /// Clean up invalid sessions
+    Cleanup {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+        /// Remove sessions of terminals showing focusdebt's own output instead
+        #[arg(long = "self")]
+        self_sessions: bool,
+    },
+    /// Optimize database
+    Optimize,
+    /// Delete raw events older than the given number of days
+    Prune {
+        /// Keep events from the last N days
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// Keep events newer than this instead, e.g. 36h or 2d12h
+        #[arg(long, value_parser = utils::parse_duration_arg, conflicts_with = "days")]
+        older_than: Option<std::time::Duration>,
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move old sessions, context switches and events into an archive database
+    ///
+    /// Examples:
+    ///   focusdebt database archive --older-than 180d
+    ///   focusdebt database archive --older-than 1y --to ~/archives/2023.db
+    Archive {
+        /// Archive rows older than this, e.g. 180d
+        #[arg(long, default_value = "180d", value_parser = utils::parse_duration_arg)]
+        older_than: std::time::Duration,
+        /// Archive file, created when missing (default: focusdebt-archive.db in the data directory)
+        #[arg(long, value_name = "FILE")]
+        to: Option<String>,
+        /// Only report what would be moved
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove rows saved more than once (same session, start, app, title and duration)
+    Dedupe {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Rewrite stored focus flags from the current focus and ignore lists
+    Reclassify {
+        /// Only report how many rows would flip
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Cut stored window titles to max_title_length, then VACUUM
+    TrimTitles {
+        /// Cut to this many characters instead of max_title_length
+        #[arg(long)]
+        max_length: Option<usize>,
+        /// Only report how many rows would be cut
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check the database and list rows quarantined for a wrong clock
+    Check,
+    /// Database size, growth per day and the disk space left
+    Stats,
+    /// Restore or drop rows quarantined for a wrong clock
+    Quarantine {
+        #[command(subcommand)]
+        action: QuarantineCommands,
+    },
+    /// SQL views for your own queries (v_sessions, v_daily, v_app_daily, v_switches)
+    Views {
+        #[command(subcommand)]
+        action: ViewsCommands,
+    },
+    /// Encrypt the database in place with SQLCipher (encryption builds only)
+    Encrypt,
+    /// Turn an encrypted database back into plain SQLite
+    Decrypt,
+    /// Show help for database commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum DemoCommands {
+    /// Write plausible sessions, switches and events for the last --days days
+    Generate {
+        /// Days of history to generate, today included
+        #[arg(long, default_value_t = demo::DEFAULT_DAYS, value_parser = clap::value_parser!(u32).range(1..=365))]
+        days: u32,
+        /// The same seed gives the same data
+        #[arg(long, default_value_t = demo::DEFAULT_SEED)]
+        seed: u64,
+        /// Write into a database that already has sessions
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show help for demo commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum RulesCommands {
+    /// Time by the rule that classified it, and focus entries without any
+    Stats {
+        /// Days of history to cover
+        #[arg(long, default_value_t = stats::RULE_STATS_DAYS, value_parser = clap::value_parser!(u32).range(1..=365))]
+        days: u32,
+        /// Print the totals as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show help for rules commands
+    Help,
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Time and focus efficiency per workspace (virtual desktop)
+    Workspaces {
+        /// Days of history to cover
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u32).range(1..=365))]
+        days: u32,
+        /// Print the breakdown as JSON
+        #[arg(long)]
+        json: bool,
+        /// Classify sessions by today's focus and ignore lists, not their stored flags
+        #[arg(long)]
+        apply_current_rules: bool,
+    },
+    /// Time and focus of every session linked to a task, across all days
+    Task {
+        /// A taskwarrior id or uuid, or text of the todo.txt line or task description
+        task: String,
+        /// Print the totals and sessions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CompletionsCommands {
+    /// Write the script to this user's completion directory for the shell
+    Install {
+        /// bash, zsh, fish or powershell
+        shell: String,
+        /// Only show where it would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Write the user units and enable them
+    Install {
+        /// A timer that runs 'heartbeat-check' every few minutes
+        #[arg(long)]
+        with_watchdog: bool,
+        /// Let the watchdog restart a daemon that died
+        #[arg(long, requires = "with_watchdog")]
+        restart: bool,
+        /// Only show what would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Disable the units and remove them
+    Uninstall {
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show service command help
+    Help,
+}
+
+#[derive(Subcommand)]
+enum TeamCommands {
+    /// Combine the `export --anonymize` files in a directory, with no per-person breakdown
+    Aggregate {
+        /// Directory holding one anonymized export (*.json) per person
+        dir: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum QuarantineCommands {
+    /// Move the quarantined rows into the history as recorded
+    Restore,
+    /// Delete the quarantined rows
+    Drop,
+}
+
+#[derive(Subcommand)]
+enum ViewsCommands {
+    /// Create the views and the view_docs table describing them
+    Install,
+    /// Drop the views and view_docs
+    Remove,
+    /// Describe each view and its columns
+    List,
+}
+
+#[derive(Subcommand)]
+enum LogsCommands {
+    /// Empty the log, even while the daemon is writing to it
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum DebugCommands {
+    /// Show the running daemon's in-memory state
+    State {
+        /// Print the state as JSON (one line per refresh with --watch)
+        #[arg(long)]
+        json: bool,
+        /// Refresh every second until Ctrl-C
+        #[arg(long)]
+        watch: bool,
+    },
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum ImportCommands {
+    /// Import a RescueTime CSV export
+    Rescuetime {
+        /// Path to the CSV file
+        file: String,
+    },
+    /// Add manual entries from a CSV with app, start, duration, focus and session columns
+    Manual {
+        /// Path to the CSV file
+        file: String,
+        /// Add entries even where tracked rows cover the same time
+        #[arg(long)]
+        allow_overlap: bool,
+    },
+    /// Rebuild approximate history from an old /tmp/focusdebt_daemon.log (the log is only read)
+    LegacyLog {
+        /// Path to the log file
+        file: String,
+        /// Tracking interval the old daemon checked at; defaults to tracking_interval_ms
+        #[arg(long)]
+        interval_ms: Option<u64>,
+    },
+    /// Show help for import commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum PlanCommands {
+    /// Plan a session; an idle daemon starts it on time and stops it after the duration
+    Add {
+        /// Session name
+        name: String,
+        /// Start: "tomorrow 09:00", "fri 14:00", "2024-05-21 09:00", "09:00" or RFC3339
+        #[arg(long)]
+        at: String,
+        /// How long it runs, e.g. 2h or 90m
+        #[arg(long, value_parser = utils::parse_duration_arg)]
+        duration: std::time::Duration,
+    },
+    /// List this week's and upcoming plans, with missed ones marked
+    List,
+    /// Show help for plan commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum SessionCommands {
+    /// List past sessions
+    List {
+        /// Merge sessions from every profile (read-only)
+        #[arg(long)]
+        all_profiles: bool,
+    },
+    /// Show individual session by name
+    Show {
+        /// Session name
+        name: String,
+        /// Print the session as JSON
+        #[arg(long)]
+        json: bool,
+        /// Include raw tracker events in the JSON output
+        #[arg(long, requires = "json")]
+        events: bool,
+        /// List every stored row and context switch behind the session
+        #[arg(long, conflicts_with = "events")]
+        raw: bool,
+        /// Page of --raw output to show
+        #[arg(long, requires = "raw", default_value_t = 1)]
+        page: usize,
+    },
+    /// Export one session with its raw rows, switches and summary
+    Export {
+        /// Session name
+        name: String,
+        /// Output format (json, csv, markdown, html)
+        #[arg(long, default_value = "json")]
+        format: String,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Name the rows of one day that were recorded without a session name
+    Adopt {
+        /// Session name to give them
+        name: String,
+        /// Local day of the rows (YYYY-MM-DD)
+        #[arg(long)]
+        date: String,
+        /// Allow a name already used by another session (the rows join it)
+        #[arg(long)]
+        allow_duplicate: bool,
+    },
+    /// Browse recent sessions interactively
+    Browse,
+    /// Delete a session's rows, name and tags
+    Delete {
+        /// Session name
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Give a session a new name
+    Rename {
+        /// Current session name
+        name: String,
+        /// New session name
+        new_name: String,
+    },
+    /// Show or replace a session's tags
+    Tag {
+        /// Session name
+        name: String,
+        /// Tags, comma-separated or as separate arguments; "off" clears them
+        tags: Vec<String>,
+    },
+    /// Rate how a session went, e.g. one stopped without a terminal
+    Rate {
+        /// Session name
+        name: String,
+        /// 1 (poor) to 5 (great)
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=5))]
+        rating: u8,
+        /// Whether the session's intent was met (yes or no)
+        #[arg(long, value_name = "yes|no", value_parser = clap::builder::BoolishValueParser::new())]
+        met: Option<bool>,
+    },
+    /// Show help for session commands
+    Help,
+}
+
+#[derive(Subcommand)]
+#[command(disable_help_flag = true)]
+enum FocussiteCommands {
+    /// Add website to focus list (tracked by tab names)
+    Add { domain: String },
+    /// Remove website from focus list
+    Remove { domain: String },
+    /// List focus vs distraction sites
+    List,
+    /// Suggest currently open browser tabs to add as focus sites
+    Suggest,
+    /// Show help for focussite commands
+    Help,
+}
+
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.profile_self || timing::requested_by_environment() {
+        timing::enable();
+    }
+    // Prints the breakdown, if asked for, once main returns
+    let _timing_report = timing::Report::start();
+
+    // Completion scripts go to stdout, so nothing else may print first
+    if let Commands::Completions { shell, action } = &cli.command {
+        match action {
+            Some(CompletionsCommands::Install { shell, dry_run }) => install_completions(shell, *dry_run),
+            None => print_completions(shell.as_deref()),
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    // The profile decides which database and runtime files everything below uses
+    let profile = cli.profile.clone()
+        .or_else(|| Config::load().ok().and_then(|config| config.profile))
+        .filter(|name| name != "default");
+    if let Some(name) = &profile {
+        if !utils::is_valid_profile_name(name) {
+            eprintln!("❌ Invalid profile name '{}'. Use letters, digits, '-' and '_' only.", name);
+            return ExitCode::FAILURE;
+        }
+    }
+    utils::set_active_profile(profile);
+    utils::set_data_dir_override(cli.data_dir.as_ref().map(|dir| {
+        std::env::current_dir().map(|cwd| cwd.join(dir)).unwrap_or_else(|_| dir.clone())
+    }));
+    progress::set_quiet(cli.quiet);
+    if !check_storage_access(&cli.command) {
+        return ExitCode::FAILURE;
+    }
+    // Released when main returns; process::exit would skip that and leave
+    // the operation recorded, so failures below return an exit code
+    let _operation_lock = match guard_operation(&cli.command) {
+        Ok(lock) => lock,
+        Err(code) => return code,
+    };
+    storage::set_include_archives(cli.command.include_archives());
+    // What reports are built with; the commands below add their own options
+    let mut settings = Config::load().map(|config| StatsSettings::from_config(&config)).unwrap_or_default();
+    settings.serial = cli.serial;
+    if let Ok(config) = Config::load() {
+        activity::set_passive_thresholds(config.passive_after_minutes, config.passive_activity_level);
+        utils::set_display_formats(config.time_format, &config.date_format, config.show_utc_offset);
+        utils::timezone::resolve(config.timezone.as_deref());
+        utils::set_report_width(config.report_width);
+        glyphs::set_display_glyphs(config.display_glyphs);
+        utils::fuzzy::set_thresholds(config.fuzzy_min_score, config.fuzzy_ambiguity_margin);
+        tracking::set_max_title_length(config.max_title_length);
+        tracking::set_max_session_hours(config.max_session_hours);
+        tracking::set_process_scan_fallback(config.allow_process_scan_fallback);
+    }
+
+    // Check for first run and show welcome message
+    if let Ok(mut config) = Config::load() {
+        if config.first_run {
+            show_welcome_message();
+            if let Err(e) = config.mark_first_run_complete() {
+                eprintln!("❌ Failed to save first run status: {}", e);
+            }
+        }
+    }
+
+    // Check dependencies
+    if !check_dependencies() {
+        eprintln!("~=~ Required dependencies not found.");
+        #[cfg(target_os = "linux")]
+        eprintln!("~=~ Please install xdotool: sudo pacman -S xdotool");
+        #[cfg(target_os = "macos")]
+        eprintln!("~=~ macOS should work out of the box");
+        #[cfg(target_os = "windows")]
+        eprintln!("~=~ Windows should work out of the box");
+        return ExitCode::FAILURE;
+    }
+
+    match cli.command {
+        Commands::Start { allow_duplicate, display, intent, expect, task } => {
+            if is_daemon_running() {
+                println!("~=~ Focus tracking daemon is already running");
+                return ExitCode::SUCCESS;
+            }
+            // Looked up before anything starts, so a typo doesn't leave a session without its task
+            let task = match task {
+                Some(task) => match tasks::resolve(&tasks::TaskCommand, &Config::load().unwrap_or_default(), &task) {
+                    Ok(task) => Some(task),
+                    Err(e) => {
+                        eprintln!("❌ Can't link the session to '{}': {}", task, e);
+                        return ExitCode::SUCCESS;
+                    }
+                },
+                None => None,
+            };
+
+            if let Some(display) = display {
+                bind_display(&display);
+            }
+
+            println!("~=~ Starting focus tracking daemon...");
+            let intent = intent.map(|intent| intent.trim().to_string()).filter(|intent| !intent.is_empty());
+            return start_daemon(allow_duplicate, intent, expect, task);
+        }
+        Commands::Track { name, allow_duplicate, command } => {
+            return track_foreground(&settings, name, allow_duplicate, &command);
+        }
+        Commands::Status { verbose } => {
+            show_status(verbose);
+        }
+        Commands::Audit { minutes, save } => {
+            run_audit(minutes, save);
+        }
+        Commands::Mute { duration } => {
+            mute_sounds(duration.as_deref());
+        }
+        Commands::Doctor { clean } => {
+            println!("~=~ Checking FocusDebt setup...");
+            run_doctor(clean);
+        }
+        Commands::Maintenance { dry_run } => {
+            run_maintenance(dry_run);
+        }
+        Commands::Reset { no_backup, profiles, yes, .. } => {
+            if !reset_all(no_backup, profiles, yes) {
+                return ExitCode::FAILURE;
+            }
+        }
+        Commands::Stop { rating, met } => {
+            if !is_daemon_running() {
+                println!("~=~ No focus tracking daemon is running");
+                return ExitCode::SUCCESS;
+            }
+
+            println!("~=~ Stopping daemon and showing session summary...");
+            stop_daemon(&settings, rating, met);
+        }
+        Commands::Stats { view: Some(StatsCommands::Workspaces { days, json, apply_current_rules }), .. } => {
+            if apply_current_rules {
+                settings.current_rules = Some(Config::load().unwrap_or_default());
+            }
+            show_workspace_usage(&settings, days, json);
+        }
+        Commands::Stats { view: Some(StatsCommands::Task { task, json }), .. } => {
+            show_task_stats(&settings, &task, json);
+        }
+        Commands::Stats { switch_heatmap: true, weeks, json, .. } => {
+            show_switch_heatmap(weeks, json);
+        }
+        Commands::Stats { current: true, apply_current_rules, no_art, .. } => {
+            if no_art {
+                settings.ascii_art = false;
+            }
+            if apply_current_rules {
+                settings.current_rules = Some(Config::load().unwrap_or_default());
+            }
+            settings.live_rows = live_rows();
+            show_current_session(&settings);
+        }
+        Commands::Stats { json, apply_current_rules, by_group, power, .. } => {
+            if apply_current_rules {
+                settings.current_rules = Some(Config::load().unwrap_or_default());
+            }
+            settings.power_filter = power;
+            if !json {
+                println!("~=~ Showing daily focus statistics...");
+            }
+            settings.live_rows = live_rows();
+            show_daily_stats(&settings, json, by_group);
+        }
+        Commands::Score => {
+            show_focus_score(&settings);
+        }
+        Commands::Estimate { goal, json } => {
+            settings.live_rows = live_rows();
+            show_estimate(&settings, goal, json);
+        }
+        Commands::Share { with_history, format, output, no_art } => {
+            if no_art {
+                settings.ascii_art = false;
+            }
+            match format.as_str() {
+                "ascii" => {
+                    println!("~=~ Generating shareable focus report...");
+                    generate_share_report(&settings, with_history);
+                }
+                "badge" => write_share_badge(&settings, output.as_deref().unwrap_or(stats::DEFAULT_BADGE_FILE)),
+                "oneline" | "tweet" => share_short(&settings, &format),
+                _ => eprintln!("❌ Unsupported share format '{}'. Supported formats: {}", format, stats::SHARE_FORMATS.join(", ")),
+            }
+        }
+        Commands::Focusapp { action } => match action {
+            FocusappCommands::Add { name, allow_literal } => {
+                println!("~=~ Adding '{}' to focus apps list (fuzzy match)...", name);
+                add_focus_app_fuzzy(&name, allow_literal);
+            }
+            FocusappCommands::Remove { name } => {
+                println!("~=~ Removing '{}' from focus apps list", name);
+                remove_focus_app(&name);
+            }
+            FocusappCommands::List => {
+                println!("~=~ Listing focus apps...");
+                list_focus_apps();
+            }
+            FocusappCommands::Suggest => {
+                println!("~=~ Suggesting running GUI applications...");
+                suggest_focus_apps();
+            }
+            FocusappCommands::Audit { stale_days, json } => {
+                audit_focus_apps(stale_days, json);
+            }
+            FocusappCommands::Help => {
+                println!("~=~ Showing help for focusapp commands...");
+                show_focusapp_help();
+            }
+        },
+        Commands::Rules { action } => match action {
+            RulesCommands::Stats { days, json } => {
+                show_rule_stats(days, json);
+            }
+            RulesCommands::Help => {
+                show_rules_help();
+            }
+        },
+        Commands::Focussite { action } => match action {
+            FocussiteCommands::Add { domain } => {
+                println!("~=~ Adding '{}' to focus sites (fuzzy match)...", domain);
+                add_focus_site_fuzzy(&domain);
+            }
+            FocussiteCommands::Remove { domain } => {
+                println!("~=~ Removing '{}' from focus sites", domain);
+                remove_focus_site(&domain);
+            }
+            FocussiteCommands::List => {
+                println!("~=~ Listing focus vs distraction sites...");
+                list_focus_sites();
+            }
+            FocussiteCommands::Suggest => {
+                println!("~=~ Suggesting currently open browser tabs...");
+                suggest_focus_sites();
+            }
+            FocussiteCommands::Help => {
+                println!("~=~ Showing help for focussite commands...");
+                show_focussite_help();
+            }
+        },
+        Commands::Config { action } => match action {
+            ConfigCommands::Show { json: true } => {
+                show_config_json(cli.profile.as_deref());
+            }
+            ConfigCommands::Show { json: false } => {
+                println!("~=~ Showing current configuration...");
+                show_config();
+            }
+            ConfigCommands::Set { json: Some(json), .. } => {
+                set_config_json(&json);
+            }
+            ConfigCommands::Set { key: Some(key), value: Some(value), .. } => {
+                println!("~=~ Setting {} = {}", key, value);
+                set_config(&key, &value);
+            }
+            ConfigCommands::Set { .. } => {
+                eprintln!("❌ config set takes a key and a value, or --json");
+            }
+            ConfigCommands::Reset => {
+                println!("~=~ Resetting configuration to defaults...");
+                reset_config();
+            }
+            ConfigCommands::Export { path } => {
+                println!("~=~ Exporting configuration to {}...", path);
+                export_config(&path);
+            }
+            ConfigCommands::Import { path, replace, dry_run } => {
+                println!("~=~ Importing configuration from {}...", path);
+                import_config(&path, replace, dry_run);
+            }
+            ConfigCommands::Preview {
+                add_focus_app, remove_focus_app, add_ignored_app, remove_ignored_app,
+                add_focus_site, remove_focus_site, add_ignored_site, remove_ignored_site,
+                set, days,
+            } => {
+                let changes = PreviewChanges {
+                    add_focus_app, remove_focus_app, add_ignored_app, remove_ignored_app,
+                    add_focus_site, remove_focus_site, add_ignored_site, remove_ignored_site,
+                    set,
+                };
+                preview_config(&changes, days);
+            }
+            ConfigCommands::Doctor => {
+                println!("~=~ Checking focus rules...");
+                config_doctor();
+            }
+            ConfigCommands::Help => {
+                println!("~=~ Showing help for config commands...");
+                show_config_help();
+            }
+        },
+        Commands::Events { from, to, kind } => {
+            list_events(from.as_deref(), to.as_deref(), kind.as_deref());
+        }
+        Commands::Logs { action: Some(LogsCommands::Clear), .. } => {
+            clear_daemon_log();
+        }
+        Commands::Logs { tail, follow, since, level, action: None } => {
+            show_daemon_log(tail, follow, since, level.as_deref());
+        }
+        Commands::Export { resume: Some(manifest), .. } => {
+            resume_export(&settings, &manifest);
+        }
+        Commands::Export { format, start_date, end_date, output, open, apply_current_rules, anonymize, include_archives, resume: None } => {
+            if apply_current_rules {
+                settings.current_rules = Some(Config::load().unwrap_or_default());
+            }
+            let format = if anonymize { "anonymized-json" } else { format.as_str() };
+            export_data(&settings, format, start_date.as_deref(), end_date.as_deref(), output.as_deref(), open, include_archives);
+        }
+        Commands::Team { action: TeamCommands::Aggregate { dir } } => {
+            aggregate_team(&dir);
+        }
+        Commands::Digest { week, mail_to, .. } => {
+            if !week {
+                eprintln!("❌ Choose the digest period: focusdebt digest --week");
+                return ExitCode::SUCCESS;
+            }
+            send_weekly_digest(&settings, mail_to.as_deref());
+        }
+        Commands::Wrapped { year, format, output, .. } => {
+            let year = year.unwrap_or_else(|| chrono::Datelike::year(&utils::timezone::to_zone(chrono::Utc::now())));
+            show_year_in_review(&settings, year, &format, output.as_deref());
+        }
+        Commands::Query { sql, csv } => {
+            run_query(&sql, csv);
+        }
+        Commands::Debug { action: Some(DebugCommands::State { json, watch }), .. } => {
+            show_debug_state(json, watch);
+        }
+        Commands::Debug { json, action: None } => {
+            if !json {
+                println!("~=~ Debugging window detection...");
+            }
+            debug_window_detection(json);
+        },
+        Commands::Import { source } => match source {
+            ImportCommands::Rescuetime { file } => {
+                println!("~=~ Importing RescueTime history from {}...", file);
+                import_rescuetime(&file);
+            }
+            ImportCommands::Manual { file, allow_overlap } => {
+                println!("~=~ Adding manual entries from {}...", file);
+                import_manual_entries(&file, allow_overlap);
+            }
+            ImportCommands::LegacyLog { file, interval_ms } => {
+                println!("~=~ Rebuilding history from the old daemon log {}...", file);
+                import_legacy_log(&file, interval_ms);
+            }
+            ImportCommands::Help => {
+                show_import_help();
+            }
+        },
+        Commands::AddEntry { app, start, duration, focus, session, title, allow_overlap } => {
+            add_manual_entry(&app, &start, duration, focus, &session, title.as_deref(), allow_overlap);
+        }
+        Commands::Demo { action } => match action {
+            DemoCommands::Generate { days, seed, force } => {
+                generate_demo_data(days, seed, force);
+            }
+            DemoCommands::Help => {
+                show_demo_help();
+            }
+        },
+        Commands::Plan { action } => match action {
+            PlanCommands::Add { name, at, duration } => {
+                add_planned_session(&name, &at, duration);
+            }
+            PlanCommands::List => {
+                println!("~=~ Listing planned sessions...");
+                list_planned_sessions();
+            }
+            PlanCommands::Help => {
+                show_plan_help();
+            }
+        },
+        Commands::Database { action } => match action {
+            DatabaseCommands::Clear => {
+                println!("~=~ Clearing all database data...");
+                clear_database();
+            }
+
+            DatabaseCommands::Cleanup { dry_run, self_sessions: true } => {
+                if dry_run {
+                    println!("~=~ Checking for focusdebt's own windows (dry run)...");
+                } else {
+                    println!("~=~ Removing focusdebt's own windows...");
+                }
+                cleanup_self_sessions(dry_run);
+            }
+            DatabaseCommands::Cleanup { dry_run, self_sessions: false } => {
+                if dry_run {
+                    println!("~=~ Checking for invalid sessions (dry run)...");
+                } else {
+                    println!("~=~ Cleaning up invalid sessions...");
+                }
+                cleanup_database(dry_run);
+            }
+            DatabaseCommands::Optimize => {
+                println!("~=~ Optimizing database...");
+                optimize_database();
+            }
+            DatabaseCommands::Prune { days, older_than, dry_run } => {
+                let keep = older_than.unwrap_or(std::time::Duration::from_secs(days as u64 * 86_400));
+                println!("~=~ Pruning events older than {}...", utils::format_duration(keep, DurationStyle::Long));
+                prune_events(keep, dry_run);
+            }
+            DatabaseCommands::Archive { older_than, to, dry_run } => {
+                println!("~=~ Archiving history older than {}...", utils::format_duration(older_than, DurationStyle::Long));
+                archive_history(older_than, to.as_deref(), dry_run);
+            }
+            DatabaseCommands::Dedupe { dry_run } => {
+                if dry_run {
+                    println!("~=~ Looking for duplicated session rows (dry run)...");
+                } else {
+                    println!("~=~ Removing duplicated session rows...");
+                }
+                dedupe_sessions(dry_run);
+            }
+            DatabaseCommands::Reclassify { dry_run } => {
+                if dry_run {
+                    println!("~=~ Checking stored focus flags against the current rules (dry run)...");
+                } else {
+                    println!("~=~ Reclassifying sessions with the current rules...");
+                }
+                reclassify_sessions(dry_run);
+            }
+            DatabaseCommands::TrimTitles { max_length, dry_run } => {
+                if dry_run {
+                    println!("~=~ Looking for overlong window titles (dry run)...");
+                } else {
+                    println!("~=~ Trimming overlong window titles...");
+                }
+                trim_titles(max_length, dry_run);
+            }
+            DatabaseCommands::Stats => {
+                println!("~=~ Database size and disk space...");
+                show_database_stats();
+            }
+            DatabaseCommands::Check => {
+                println!("~=~ Checking database...");
+                check_database();
+            }
+            DatabaseCommands::Views { action } => match action {
+                ViewsCommands::Install => install_views(),
+                ViewsCommands::Remove => remove_views(),
+                ViewsCommands::List => list_views(),
+            },
+            DatabaseCommands::Quarantine { action } => {
+                let restore = matches!(action, QuarantineCommands::Restore);
+                if restore {
+                    println!("~=~ Restoring quarantined sessions...");
+                } else {
+                    println!("~=~ Dropping quarantined sessions...");
+                }
+                settle_quarantine(restore);
+            }
+            DatabaseCommands::Encrypt => {
+                println!("~=~ Encrypting database...");
+                migrate_database_encryption(true);
+            }
+            DatabaseCommands::Decrypt => {
+                println!("~=~ Decrypting database...");
+                migrate_database_encryption(false);
+            }
+            DatabaseCommands::Help => {
+                println!("~=~ Showing help for database commands...");
+                show_database_help();
+            }
+        }
+        Commands::Sessions { action } => match action {
+            SessionCommands::List { all_profiles } => {
+                println!("~=~ Listing past sessions...");
+                if all_profiles {
+                    list_sessions_all_profiles(&settings);
+                } else {
+                    settings.live_rows = live_rows();
+                    list_sessions(&settings);
+                }
+            }
+            SessionCommands::Show { name, json, events, raw, page } => {
+                if raw {
+                    show_raw_session(&settings, &name, json, page);
+                    return ExitCode::SUCCESS;
+                }
+                if !json {
+                    println!("~=~ Showing session details for: {}", name);
+                }
+                settings.live_rows = live_rows();
+                show_session_details(&settings, &name, json, events);
+            }
+            SessionCommands::Export { name, format, output } => {
+                export_session(&settings, &name, &format, output.as_deref());
+            }
+            SessionCommands::Adopt { name, date, allow_duplicate } => {
+                adopt_unnamed_sessions(&name, &date, allow_duplicate);
+            }
+            SessionCommands::Browse => {
+                browse_sessions(&settings);
+            }
+            SessionCommands::Delete { name, yes } => {
+                delete_session(&name, yes);
+            }
+            SessionCommands::Rename { name, new_name } => {
+                rename_session(&name, &new_name);
+            }
+            SessionCommands::Tag { name, tags } => {
+                tag_session(&name, &tags);
+            }
+            SessionCommands::Rate { name, rating, met } => {
+                rate_session(&name, rating, met);
+            }
+            SessionCommands::Help => {
+                println!("~=~ Showing help for session commands...");
+                show_session_help();
+            }
+        }
+        Commands::Service { action } => match action {
+            ServiceCommands::Install { with_watchdog, restart, dry_run } => {
+                install_service(with_watchdog, restart, dry_run);
+            }
+            ServiceCommands::Uninstall { dry_run } => {
+                uninstall_service(dry_run);
+            }
+            ServiceCommands::Help => {
+                show_service_help();
+            }
+        },
+        Commands::HeartbeatCheck { restart } => {
+            return check_heartbeat(restart);
+        }
+        Commands::Help => {
+            show_main_help();
+        }
+        Commands::Completions { .. } => {}
+    }
+    ExitCode::SUCCESS
+}
+
+impl Commands {
+    /// Commands that never write the database, so they can still run against
+    /// an existing one when the data directory is read-only
+    fn is_read_only(&self) -> bool {
+        match self {
+            Commands::Stats { .. }
+            | Commands::Score
+            | Commands::Estimate { .. }
+            | Commands::Share { .. }
+            | Commands::Status { .. }
+            | Commands::Doctor { .. }
+            | Commands::Events { .. }
+            | Commands::Logs { .. }
+            | Commands::Export { .. }
+            | Commands::Digest { .. }
+            | Commands::Wrapped { .. }
+            | Commands::Query { .. }
+            | Commands::Team { .. }
+            | Commands::Debug { .. }
+            | Commands::Rules { .. }
+            | Commands::Help => true,
+            Commands::Sessions { action } => matches!(action, SessionCommands::List { .. } | SessionCommands::Show { .. } | SessionCommands::Export { .. } | SessionCommands::Help),
+            Commands::Config { action } => matches!(action, ConfigCommands::Show { .. } | ConfigCommands::Export { .. } | ConfigCommands::Preview { .. } | ConfigCommands::Doctor | ConfigCommands::Help),
+            Commands::Plan { action } => matches!(action, PlanCommands::List | PlanCommands::Help),
+            Commands::Database { action } => matches!(action, DatabaseCommands::Check | DatabaseCommands::Stats | DatabaseCommands::Views { action: ViewsCommands::List }),
+            Commands::Maintenance { dry_run } => *dry_run,
+            _ => false,
+        }
+    }
+
+    /// Commands that only talk to the daemon or change the config, which
+    /// can run while a long operation holds the database. focusapp and
+    /// focussite also keep the database's copy of the lists, so they wait.
+    fn writes_database(&self) -> bool {
+        !self.is_read_only() && !matches!(self,
+            Commands::Stop { .. }
+            | Commands::Mute { .. }
+            | Commands::Config { .. }
+            | Commands::Service { .. }
+            | Commands::HeartbeatCheck { .. }
+            | Commands::Completions { .. })
+    }
+
+    /// Whether the command asked to read the archives too
+    fn include_archives(&self) -> bool {
+        match self {
+            Commands::Stats { include_archives, .. }
+            | Commands::Export { include_archives, .. }
+            | Commands::Digest { include_archives, .. }
+            | Commands::Wrapped { include_archives, .. } => *include_archives,
+            _ => false,
+        }
+    }
+
+    /// What a long operation is called in the message other commands
+    /// print while it holds the operation lock; None for the rest
+    fn long_operation(&self) -> Option<&'static str> {
+        match self {
+            Commands::Import { source: ImportCommands::Help } => None,
+            Commands::Import { .. } => Some("an import"),
+            Commands::Demo { action: DemoCommands::Generate { .. } } => Some("demo data generation"),
+            Commands::Database { action } => match action {
+                DatabaseCommands::Dedupe { dry_run: false } => Some("a dedupe"),
+                DatabaseCommands::Reclassify { dry_run: false } => Some("a reclassify"),
+                DatabaseCommands::TrimTitles { dry_run: false, .. } => Some("a title trim"),
+                DatabaseCommands::Optimize => Some("an optimize"),
+                DatabaseCommands::Archive { dry_run: false, .. } => Some("an archive run"),
+                DatabaseCommands::Encrypt => Some("an encryption migration"),
+                DatabaseCommands::Decrypt => Some("a decryption migration"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Take the operation lock for a long operation, or check no long
+/// operation holds it before a command that writes the database. Either
+/// way a lock left by one that died is cleared, and a database from before
+/// epoch times is upgraded under the lock first. Fails, having said why,
+/// when it is taken.
+fn guard_operation(command: &Commands) -> Result<Option<operation::OperationLock>, ExitCode> {
+    if !matches!(command, Commands::Help | Commands::Completions { .. }) {
+        match operation::upgrade_database() {
+            Ok(()) => {}
+            Err(operation::LockError::Io(e)) => eprintln!("❌ Couldn't upgrade the database under the operation lock: {}", e),
+            Err(e) => {
+                eprintln!("❌ {}; try again once it has finished", e);
+                return Err(ExitCode::FAILURE);
+            }
+        }
+    }
+    if let Some(description) = command.long_operation() {
+        return match operation::OperationLock::acquire(description) {
+            Ok(lock) => Ok(Some(lock)),
+            Err(operation::LockError::Io(e)) => {
+                eprintln!("❌ Couldn't take the operation lock, so other commands won't wait for {}: {}", description, e);
+                Ok(None)
+            }
+            Err(e) => {
+                eprintln!("❌ {}; try again once it has finished", e);
+                Err(ExitCode::FAILURE)
+            }
+        };
+    }
+    if command.writes_database() {
+        match operation::check() {
+            Ok(()) => {}
+            Err(operation::LockError::Io(e)) => eprintln!("❌ Couldn't check for a running import or migration: {}", e),
+            Err(e) => {
+                eprintln!("❌ {}; try again once it has finished", e);
+                return Err(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Fail early, naming the directory, when the data directory can't be
+/// written, instead of surfacing a bare SQLite error later. Read-only
+/// commands carry on if a database is already there. False, having said
+/// why, when the command can't run.
+fn check_storage_access(command: &Commands) -> bool {
+    let Some(data_dir) = utils::profile_data_directory(utils::active_profile()) else {
+        eprintln!("❌ Could not determine a data directory. Pass --data-dir <path> or set FOCUSDEBT_DATA_DIR.");
+        return false;
+    };
+
+    if let Err(e) = utils::check_writable_directory(&data_dir) {
+        eprintln!("❌ Data directory {} is not writable: {}", data_dir.display(), e);
+        let has_database = data_dir.join("focusdebt.db").exists();
+        if command.is_read_only() && has_database {
+            eprintln!("~=~ Reading the existing database without saving anything");
+            utils::set_data_dir_read_only(true);
+        } else {
+            if command.is_read_only() {
+                eprintln!("~=~ There is no database to read there yet.");
+            }
+            eprintln!("~=~ Point focusdebt at a writable directory with --data-dir <path> or FOCUSDEBT_DATA_DIR=<path>.");
+            return false;
+        }
+    }
+
+    if command.is_read_only() {
+        return true;
+    }
+    if let Some(config_dir) = Config::directory() {
+        if let Err(e) = utils::check_writable_directory(&config_dir) {
+            eprintln!("❌ Config directory {} is not writable: {}", config_dir.display(), e);
+            eprintln!("~=~ Configuration changes won't be saved until it is.");
+        }
+    }
+    true
+}
+
+/// Point window detection (and everything it spawns) at the given display.
+/// X11 displays contain a colon (":1", "host:0"); anything else is a Wayland socket.
+fn bind_display(display: &str) {
+    if display.contains(':') {
+        std::env::set_var("DISPLAY", display);
+        std::env::remove_var("WAYLAND_DISPLAY");
+        std::env::set_var("XDG_SESSION_TYPE", "x11");
+    } else {
+        std::env::set_var("WAYLAND_DISPLAY", display);
+        std::env::set_var("XDG_SESSION_TYPE", "wayland");
+    }
+}
+
+/// In the `start` process, returns once the daemon reported whether it came
+/// up; in the daemon, once it stopped
+fn start_daemon(allow_duplicate: bool, intent: Option<String>, expected: Option<std::time::Duration>, task: Option<storage::SessionTask>) -> ExitCode {
+    // Interactive session name prompt
+    println!("\n~=~ Starting FocusDebt Session Tracker\n");
+    println!(
+        r#"
+      >>><<<>>><<<>>><<<>>> .--<12>--. <<<>>><<<>>><<<>>><<<
+     >>><<<>>><<<>>><<<>>> /   \      \ <<<>>><<<>>><<<>>><<<
+    >>><<<>>><<<>>><<<>>> |     I--    | <<<>>><<<>>><<<>>><<< 
+     >>><<<>>><<<>>><<<>>> \          / <<<>>><<<>>><<<>>><<<
+      >>><<<>>><<<>>><<<>>> *--<06>--* <<<>>><<<>>><<<>>><<<
+
+    "#
+    );
+    
+    // Load configuration
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config, using defaults: {}", e);
+            Config::default()
+        }
+    };
+    let allow_duplicate = allow_duplicate || config.allow_duplicate_session_names;
+    let config_fingerprint = Config::file_fingerprint();
+    print_config_warnings(&config);
+    if let Err(e) = tracking::check_interval(config.tracking_interval_ms, config.backend.as_deref()) {
+        eprintln!("❌ {}. Set it to {} or more with 'focusdebt config set tracking_interval_ms {}'.",
+            e, tracking::MIN_SPAWNING_INTERVAL_MS, tracking::MIN_SPAWNING_INTERVAL_MS);
+        return ExitCode::FAILURE;
+    }
+
+    // Ensure data directory exists
+    if let Err(e) = ensure_data_directory() {
+        eprintln!("❌ Failed to create data directory: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    // The daemon claims the name itself when it starts; if another session
+    // took it in the meantime it tells us and we ask again
+    // Automatic daily sessions pick up the day's session again after a restart
+    let (session_name, startup) = loop {
+        let session_name = if config.auto_daily_session {
+            utils::daily_session_name(chrono::Utc::now(), config.day_start_hour)
+        } else {
+            match prompt_session_name(allow_duplicate, config.max_session_name_length) {
+                Some(name) => name,
+                None => return ExitCode::FAILURE,
+            }
+        };
+        
+        println!("~=~ Starting session: \"{}\"", session_name);
+        println!("~=~ Tracking active windows and context switches...");
+        println!("~=~ Use 'focusdebt stop' to end session and view summary\n");
+        
+        match launch_daemon(&session_name, allow_duplicate || config.auto_daily_session) {
+            Launch::Running(startup) => break (session_name, startup),
+            Launch::NameTaken => {
+                println!("❌ Session name '{}' already exists. Please choose a different name.\n", session_name);
+            }
+            Launch::Started => return ExitCode::SUCCESS,
+            Launch::Failed => return ExitCode::FAILURE,
+        }
+    };
+
+    if intent.is_some() || expected.is_some() {
+        let saved = Database::new().and_then(|db| db.set_session_intent(&session_name, chrono::Utc::now(), intent.as_deref(), expected));
+        if let Err(e) = saved {
+            eprintln!("❌ Failed to save the session's intent: {}", e);
+        }
+    }
+    if let Some(task) = task {
+        link_session_task(&config, &session_name, task);
+    }
+    run_daemon(config, config_fingerprint, &session_name, startup)
+}
+
+/// Record the task `session_name` was started on and, with
+/// taskwarrior_integration, start it in taskwarrior
+fn link_session_task(config: &Config, session_name: &str, task: storage::SessionTask) {
+    if let Err(e) = Database::new().and_then(|db| db.set_session_task(session_name, &task)) {
+        eprintln!("❌ Failed to save the session's task: {}", e);
+        return;
+    }
+    println!("~=~ Session linked to {}", task.describe());
+    if config.taskwarrior_integration && task.source == storage::TaskSource::Taskwarrior {
+        match tasks::set_active(&tasks::TaskCommand, &task, true) {
+            Ok(()) => println!("~=~ Started task {} in taskwarrior", task.id),
+            Err(e) => eprintln!("❌ Failed to start task {} in taskwarrior: {}", task.id, e),
+        }
+    }
+}
+
+/// The daemon's life once `launch_daemon` has started it: check it can
+/// work and say so to whoever started it, track until stopped, then clean
+/// up its runtime files
+fn run_daemon(config: Config, config_fingerprint: Option<u64>, session_name: &str, startup: Startup) -> ExitCode {
+    if !startup.report(check_daemon_startup(&config)) {
+        finish_daemon_log();
+        return ExitCode::FAILURE;
+    }
+
+    // Remember which display we are bound to, for `status`, `doctor` and
+    // noticing when it goes away
+    let daemon_state = utils::DaemonState::current(session_name);
+    println!("~=~ Bound to display: {}", daemon_state.describe_display());
+    if let Err(e) = utils::write_daemon_state(&daemon_state) {
+        eprintln!("❌ Failed to write daemon state: {}", e);
+    }
+
+    run_tracking(config, config_fingerprint, session_name, daemon_state, true, || !is_daemon_running());
+    println!("~=~ Daemon exiting");
+
+    // Clean up
+    let _ = remove_pid_file();
+    let _ = utils::remove_daemon_state();
+    let _ = utils::remove_debug_state();
+    let _ = utils::remove_heartbeat();
+    let _ = tracking::remove_live_snapshot();
+    finish_daemon_log();
+    ExitCode::SUCCESS
+}
+
+/// Check every enabled optional integration and turn off the ones that
+/// can't work: hook commands whose program is missing, desktop
+/// notifications without a notification tool, input activity without a
+/// way to read idle time, tmux without tmux, and notification counting
+/// without a monitor
+fn check_integrations(config: &mut Config) -> Vec<utils::IntegrationStatus> {
+    let mut integrations = Vec::new();
+
+    let mut hook_problems = Vec::new();
+    for (key, hooks) in [("on_session_start", &mut config.on_session_start), ("on_session_end", &mut config.on_session_end)] {
+        hooks.retain(|command| match hooks::check_command(command) {
+            Ok(()) => true,
+            Err(problem) => {
+                hook_problems.push(format!("{}: {}", key, problem));
+                false
+            }
+        });
+    }
+    if !config.on_session_start.is_empty() || !config.on_session_end.is_empty() || !hook_problems.is_empty() {
+        let problem = (!hook_problems.is_empty()).then(|| hook_problems.join("; "));
+        integrations.push(utils::IntegrationStatus { name: "hooks".to_string(), problem });
+    }
+
+    if let Some(check) = alerts::check_notifications() {
+        let problem = check.err();
+        alerts::set_notifications_enabled(problem.is_none());
+        integrations.push(utils::IntegrationStatus { name: "notifications".to_string(), problem });
+    }
+
+    if config.track_input_activity {
+        let problem = activity::check_idle_time().err();
+        config.track_input_activity = problem.is_none();
+        integrations.push(utils::IntegrationStatus { name: "input activity".to_string(), problem });
+    }
+
+    if config.tmux_integration {
+        let problem = tmux::check().err();
+        config.tmux_integration = problem.is_none();
+        integrations.push(utils::IntegrationStatus { name: "tmux".to_string(), problem });
+    }
+
+    if config.count_notifications {
+        let problem = notifications::check(config.notification_command.as_deref()).err();
+        config.count_notifications = problem.is_none();
+        integrations.push(utils::IntegrationStatus { name: "notification counting".to_string(), problem });
+    }
+
+    integrations
+}
+
+/// Beat once for `heartbeat-check`; only the detached daemon is watched
+fn write_heartbeat(tracker: &FocusTracker, save_interval_ms: u64) {
+    if let Err(e) = utils::write_heartbeat(&utils::Heartbeat::now(tracker.get_session_name(), save_interval_ms)) {
+        eprintln!("❌ Failed to write heartbeat: {}", e);
+    }
+}
+
+/// Log entering (`Pause`) or leaving (`Resume`) the quiet hours `spec`, and
+/// show it in the daemon state for `status`
+fn record_quiet_hours(db_tx: &DbSender, kind: EventKind, spec: &str, detached: bool) {
+    let event = TrackerEvent::now(kind, serde_json::json!({ "reason": stats::QUIET_HOURS_REASON, "range": spec }));
+    if let Err(e) = db_tx.send(DatabaseCommand::RecordEvent(event)) {
+        eprintln!("❌ Failed to send event to database thread: {}", e);
+    }
+    if detached {
+        if let Some(mut state) = utils::read_daemon_state() {
+            state.quiet_hours = (kind == EventKind::Pause).then(|| spec.to_string());
+            if let Err(e) = utils::write_daemon_state(&state) {
+                eprintln!("❌ Failed to update daemon state: {}", e);
+            }
+        }
+    }
+}
+
+/// Show why window detection can't work, or that it works again, in the
+/// daemon state for `status`
+fn record_detection_problem(problem: Option<&str>, detached: bool) {
+    if detached {
+        if let Some(mut state) = utils::read_daemon_state() {
+            state.detection_problem = problem.map(str::to_string);
+            if let Err(e) = utils::write_daemon_state(&state) {
+                eprintln!("❌ Failed to update daemon state: {}", e);
+            }
+        }
+    }
+}
+
+fn log_replay(report: &spill::ReplayReport, journal: &std::path::Path) {
+    if report.replayed > 0 {
+        println!("~=~ Replayed {} spilled rows into the database", report.replayed);
+    }
+    if let Some(e) = &report.error {
+        eprintln!("❌ Database still unwritable ({}); {} rows stay in {}", e, report.remaining, journal.display());
+    }
+}
+
+/// Tell the user (once per run) that the disk filled up, and let `status`
+/// and `doctor` know through the daemon state
+fn record_degraded_mode(since: Option<chrono::DateTime<chrono::Utc>>, journal: &std::path::Path, detached: bool, notified: &mut bool) {
+    match since {
+        Some(_) if !*notified => {
+            alerts::send_notification(
+                "FocusDebt: disk full",
+                &format!("Tracking continues; rows are kept in {} until the database can be written again", journal.display()),
+            );
+            *notified = true;
+        }
+        Some(_) => {}
+        None => println!("~=~ Database writable again, degraded mode is over"),
+    }
+    if detached {
+        if let Some(mut state) = utils::read_daemon_state() {
+            state.degraded_since = since;
+            if let Err(e) = utils::write_daemon_state(&state) {
+                eprintln!("❌ Failed to update daemon state: {}", e);
+            }
+        }
+    }
+}
+
+/// The daemon's daily sample of the database size, for the growth rate in
+/// doctor and `database stats`. Notifies when the disk fills up within
+/// disk_warning_days at that rate.
+fn sample_database_size(db: &Database, at: chrono::DateTime<chrono::Utc>, warning_days: u32) {
+    let Ok(path) = Database::get_db_path() else {
+        return;
+    };
+    let day = at.with_timezone(&utils::timezone::zone()).date_naive();
+    let history = match disk::sample(db, &path, day) {
+        Ok(history) => history,
+        Err(e) => {
+            eprintln!("❌ Failed to record the database size: {}", e);
+            return;
+        }
+    };
+    let report = disk::DiskReport::collect(&path, &history);
+    if disk::should_warn(report.days_left, warning_days) {
+        alerts::send_notification(
+            "FocusDebt: disk filling up",
+            &format!("The database grows {}; {}", report.describe_growth(), report.describe_free_space()),
+        );
+    }
+}
+
+/// Why the clock check held a row back, e.g. "starts 3650d 2h after the
+/// last seen time (2026-10-16T10:53:22+00:00)"
+fn clock_skew_reason(skew: chrono::Duration, last_seen: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    let distance = utils::format_duration(skew.abs().to_std().unwrap_or_default(), DurationStyle::Compact);
+    let direction = if skew > chrono::Duration::zero() { "after" } else { "before" };
+    match last_seen {
+        Some(seen) => format!("starts {} {} the last seen time ({})", distance, direction, seen.to_rfc3339()),
+        None => format!("starts {} {} the last seen time", distance, direction),
+    }
+}
+
+/// Switch the tracker to session `to` at `at`. The old session's rows go
+/// ahead of the rollover, so its end hooks see all of them.
+fn roll_over_daemon_session(tracker: &mut FocusTracker, db_tx: &DbSender, to: String, at: chrono::DateTime<chrono::Utc>, detached: bool) {
+    let from = tracker.get_session_name().to_string();
+    tracker.roll_over_session(to.clone(), at);
+    let pending = tracker.take_completed_sessions().into_iter().map(DatabaseCommand::SaveSession)
+        .chain(tracker.take_context_switches().into_iter().map(DatabaseCommand::SaveContextSwitch))
+        .chain(std::iter::once(DatabaseCommand::RollOver { from, to: to.clone(), at }));
+    for command in pending {
+        if let Err(e) = db_tx.send(command) {
+            eprintln!("❌ Failed to send rollover to database thread: {}", e);
+        }
+    }
+    if detached {
+        if let Some(mut state) = utils::read_daemon_state() {
+            state.session_name = to;
+            if let Err(e) = utils::write_daemon_state(&state) {
+                eprintln!("❌ Failed to update daemon state: {}", e);
+            }
+        }
+    }
+}
+
+/// Start `due_plan` when the daemon is idle on the day's session `daily`,
+/// and go back to `daily` once the running plan's time is up. Returns the
+/// plan started and the plan finished.
+fn step_plans(
+    tracker: &mut FocusTracker,
+    db_tx: &DbSender,
+    running_plan: &mut Option<RunningPlan>,
+    due_plan: Option<storage::PlannedSession>,
+    daily: &str,
+    now: chrono::DateTime<chrono::Utc>,
+    detached: bool,
+) -> (Option<RunningPlan>, Option<RunningPlan>) {
+    let mut plan_started = None;
+    if let Some(plan) = due_plan {
+        if tracker.get_session_name() == daily {
+            println!("~=~ Starting planned session \"{}\"", plan.name);
+            roll_over_daemon_session(tracker, db_tx, plan.name.clone(), now, detached);
+            *running_plan = Some(RunningPlan { id: plan.id, until: plan.end_time(), name: plan.name });
+            plan_started = running_plan.clone();
+        }
+    }
+    let plan_finished = running_plan.take_if(|plan| now >= plan.until);
+    if let Some(plan) = &plan_finished {
+        println!("~=~ Planned session \"{}\" is over, back to \"{}\"", plan.name, daily);
+        roll_over_daemon_session(tracker, db_tx, daily.to_string(), plan.until, detached);
+    }
+    (plan_started, plan_finished)
+}
+
+/// Hand the current session plus anything completed since the last save to
+/// the database thread, followed by the daemon stop event
+fn drain_tracker(tracker: &mut FocusTracker, db_tx: &DbSender, session_name: &str) {
+    tracker.end_current_session();
+
+    for session in tracker.take_completed_sessions() {
+        if let Err(e) = db_tx.send(DatabaseCommand::SaveSession(session)) {
+            eprintln!("❌ Failed to send final session to database thread: {}", e);
+        }
+    }
+
+    for switch in tracker.take_context_switches() {
+        if let Err(e) = db_tx.send(DatabaseCommand::SaveContextSwitch(switch)) {
+            eprintln!("❌ Failed to send final context switch to database thread: {}", e);
+        }
+    }
+
+    let stop_event = TrackerEvent::now(
+        EventKind::DaemonStop,
+        serde_json::json!({ "session_name": session_name }),
+    );
+    if let Err(e) = db_tx.send(DatabaseCommand::RecordEvent(stop_event)) {
+        eprintln!("❌ Failed to send event to database thread: {}", e);
+    }
+}
+
+/// Store a row written at `written`, moving `last_committed` along once it
+/// is on disk (in the database or the spill journal)
+fn commit_row(
+    spill: &mut spill::Spill,
+    db: &Database,
+    record: spill::SpillRecord,
+    written: chrono::DateTime<chrono::Utc>,
+    last_committed: &mut Option<chrono::DateTime<chrono::Utc>>,
+) -> rusqlite::Result<spill::Stored> {
+    let stored = spill.store(db, record)?;
+    if stored != spill::Stored::Memory {
+        *last_committed = (*last_committed).max(Some(written));
+    }
+    Ok(stored)
+}
+
+/// Where `save_session` put a session row
+#[derive(Debug, PartialEq)]
+enum SessionSaved {
+    Stored(spill::Stored),
+    /// Held back by the clock check, with the reason it was given
+    Quarantined(String),
+}
+
+/// Store a session row, or quarantine it when it starts more than
+/// `max_clock_skew_days` from the last seen time
+fn save_session(
+    spill: &mut spill::Spill,
+    db: &Database,
+    session: tracking::FocusSession,
+    last_seen_clock: Option<chrono::DateTime<chrono::Utc>>,
+    max_clock_skew_days: u32,
+    last_committed: &mut Option<chrono::DateTime<chrono::Utc>>,
+) -> rusqlite::Result<SessionSaved> {
+    if let Some(skew) = utils::clock_skew(session.start_time, last_seen_clock, max_clock_skew_days) {
+        let reason = clock_skew_reason(skew, last_seen_clock);
+        spill.store(db, spill::SpillRecord::Quarantined { session, reason: reason.clone() })?;
+        return Ok(SessionSaved::Quarantined(reason));
+    }
+    let written = session.end_time.unwrap_or(session.start_time);
+    commit_row(spill, db, spill::SpillRecord::Session(session), written, last_committed).map(SessionSaved::Stored)
+}
+
+/// The tracking, save and database threads for one session, until
+/// `should_stop` says so. `detached` is the background daemon, which also
+/// owns the PID file and the flush marker `focusdebt stop` waits for.
+fn run_tracking(
+    mut config: Config,
+    config_fingerprint: Option<u64>,
+    session_name: &str,
+    daemon_state: utils::DaemonState,
+    detached: bool,
+    mut should_stop: impl FnMut() -> bool,
+) {
+    // A broken integration is turned off for this run; tracking goes on
+    let integrations = check_integrations(&mut config);
+    // Budgets, alerts and session end hooks read the config the run started with
+    let settings = StatsSettings::from_config(&config);
+    for integration in integrations.iter().filter(|integration| integration.problem.is_some()) {
+        eprintln!("~=~ Integration disabled for this run: {}", integration);
+    }
+    if detached {
+        if let Some(mut state) = utils::read_daemon_state() {
+            state.integrations = integrations;
+            if let Err(e) = utils::write_daemon_state(&state) {
+                eprintln!("❌ Failed to update daemon state: {}", e);
+            }
+        }
+    }
+
+    if config.focus_apps.is_empty() {
+        println!("~=~ No focus apps configured, using defaults: {:?}", config.tracked_focus_apps());
+    }
+    // The database's focus lists count as well, here and on every save
+    let listed = (config.tracked_focus_apps(), config.focus_sites.clone());
+    let (focus_apps, focus_sites) = match Database::new().and_then(|db| db.focus_lists(&listed.0, &listed.1)) {
+        Ok(lists) => lists,
+        Err(e) => {
+            eprintln!("❌ Failed to read the focus lists from the database, using the config's: {}", e);
+            listed.clone()
+        }
+    };
+    config.focus_sites = focus_sites;
+
+    let session_snapshot = config.session_snapshot(focus_apps.clone());
+
+    // Create shared tracker
+    let mut tracker = FocusTracker::new();
+    tracker.apply_config(&config, focus_apps, session_name);
+    tracker.start_tracking();
+    let tracker = Arc::new(Mutex::new(tracker));
+
+    // Create shutdown signal
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone1 = Arc::clone(&shutdown);
+    let shutdown_clone2 = Arc::clone(&shutdown);
+    // Set by the tracking thread when the tracker's buffers pass max_buffered_rows
+    let flush_requested = Arc::new(AtomicBool::new(false));
+    let flush_requested_save = Arc::clone(&flush_requested);
+    let early_saves = Arc::new(AtomicUsize::new(0));
+    let early_saves_debug = Arc::clone(&early_saves);
+
+    // Create channels for communication
+    let (tx, rx) = mpsc::channel();
+    let (db_tx_raw, db_rx) = mpsc::channel();
+    let db_queued = Arc::new(AtomicUsize::new(0));
+    let db_tx = Arc::new(Mutex::new(DbSender { tx: db_tx_raw, queued: Arc::clone(&db_queued) }));
+    let db_queued_debug = Arc::clone(&db_queued);
+    let tracker_clone1 = Arc::clone(&tracker);
+    let tracker_clone2 = Arc::clone(&tracker);
+    let db_tx_save = Arc::clone(&db_tx);
+    let db_tx_events = Arc::clone(&db_tx);
+    let tracking_config = config.clone();
+    let mut selector = tracking::BackendSelector::for_config(&config);
+    // Only a session named after the day rolls over; an explicit name stays
+    let mut daily_session = (config.auto_daily_session
+        && session_name == utils::daily_session_name(chrono::Utc::now(), config.day_start_hour))
+        .then(|| session_name.to_string());
+
+    // Spawn tracking thread
+    let tracking_thread = thread::spawn(move || {
+        let mut last_window = None;
+        let mut consecutive_failures = 0;
+        let mut same_window_count: u32 = 0;
+        let mut activity_unavailable = false;
+        let activity_window = std::time::Duration::from_millis(tracking_config.tracking_interval_ms);
+        let mut backend: Option<&'static str> = None;
+        let mut detection_problem: Option<&'static str> = None;
+        let mut last_detection_at = None;
+        // The session whose settings were last sent; a rollover sends again
+        let mut snapshot_sent_for: Option<String> = None;
+        let mut debug_written: Option<std::time::Instant> = None;
+        let mut power_checked = std::time::Instant::now();
+        let mut usage_monitor = usage::Monitor::new(tracking_config.cpu_warning_percent);
+        let mut usage_sampled = std::time::Instant::now();
+        let mut tmux_probe = tracking_config.tmux_integration.then(tmux::Probe::default);
+        let rule_terminals: Vec<String> = tracking_config.terminal_title_rules.iter().map(|rule| rule.terminal.clone()).collect();
+        let mut last_offset = (chrono::Utc::now(), utils::local_offset(chrono::Utc::now()));
+        let quiet_ranges = tracking_config.quiet_ranges();
+        let mut quiet_range: Option<String> = None;
+        let suspend_gap = SUSPEND_GAP.max(std::time::Duration::from_millis(tracking_config.tracking_interval_ms * 3));
+        let mut last_check: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut debouncer = tracking::WindowDebouncer::for_interval(tracking_config.tracking_interval_ms);
+        let mut log_limiter = tracking::LogLimiter::default();
+        let mut still_on_logged = std::time::Instant::now();
+        println!("~=~ Tracking thread started");
+        
+        while !shutdown_clone1.load(Ordering::Relaxed) {
+            // Check for stop signal
+            if rx.try_recv().is_ok() {
+                println!("~=~ Received stop signal");
+                break;
+            }
+
+            // The loop doesn't run while the machine sleeps, so a long gap
+            // since the last check is a suspend (or the clock jumped ahead).
+            // The window open before it ends there.
+            let checked_at = chrono::Utc::now();
+            if let Some(gap) = last_check.replace(checked_at).and_then(|previous| detect_suspend(previous, checked_at, suspend_gap)) {
+                println!("~=~ No check between {} and {}: the system was suspended, {} not counted",
+                    utils::format_timestamp_local(gap.start), utils::format_timestamp_local(gap.end),
+                    utils::format_duration(gap.duration(), DurationStyle::Long));
+                tracker_clone1.lock().unwrap().suspend(gap.start);
+                last_window = None;
+                if let Err(e) = db_tx_events.lock().unwrap().send(DatabaseCommand::RecordEvent(gap.to_event())) {
+                    eprintln!("❌ Failed to send event to database thread: {}", e);
+                }
+            }
+
+            // Quiet hours: nothing is detected or recorded inside a range
+            let local_now = utils::timezone::to_zone(chrono::Utc::now()).naive_local();
+            match (quiet_ranges.iter().find(|range| range.contains(local_now)), &quiet_range) {
+                (Some(range), None) => {
+                    println!("~=~ Quiet hours ({}): tracking paused", range.spec);
+                    tracker_clone1.lock().unwrap().pause();
+                    last_window = None;
+                    record_quiet_hours(&db_tx_events.lock().unwrap(), EventKind::Pause, &range.spec, detached);
+                    quiet_range = Some(range.spec.clone());
+                }
+                (None, Some(spec)) => {
+                    println!("~=~ Quiet hours ({}) are over: tracking resumed", spec);
+                    record_quiet_hours(&db_tx_events.lock().unwrap(), EventKind::Resume, spec, detached);
+                    quiet_range = None;
+                }
+                _ => {}
+            }
+
+            if quiet_range.is_none() {
+                // Get active window using platform-specific code
+                let detection = tracking::detect_with(&mut selector);
+                let advice = tracking::detection_advice(detection.as_ref().map(|(detected_by, _)| *detected_by));
+                if advice != detection_problem {
+                    match advice {
+                        Some(advice) => eprintln!("❌ {}", advice),
+                        None => println!("~=~ Window detection works again"),
+                    }
+                    record_detection_problem(advice, detached);
+                    detection_problem = advice;
+                }
+                match detection {
+                    Some((detected_by, mut current_window)) => {
+                        consecutive_failures = 0; // Reset failure counter
+                        backend = Some(detected_by);
+                        last_detection_at = Some(chrono::Utc::now());
+                        let session = tracker_clone1.lock().unwrap().get_session_name().to_string();
+                        if snapshot_sent_for.as_ref() != Some(&session) {
+                            let snapshot = config::SessionSnapshot { backend: Some(detected_by.to_string()), ..session_snapshot.clone() };
+                            let command = DatabaseCommand::SaveSessionMeta { session: session.clone(), at: chrono::Utc::now(), snapshot };
+                            if let Err(e) = db_tx_events.lock().unwrap().send(command) {
+                                eprintln!("❌ Failed to send session settings to database thread: {}", e);
+                            }
+                            snapshot_sent_for = Some(session);
+                        }
+                        let app_name = current_window.app_name.clone();
+                        let window_title = current_window.title.clone();
+                    
+                        if let Some(held) = log_limiter.allow("detection", tracking::LOG_INTERVAL) {
+                            println!("~=~ RAW DETECTION: {} - {}{}", app_name, window_title, tracking::held_back_note(held));
+                        }
+
+                        // Inside tmux the terminal's title stays put, so the
+                        // pane's command stands in for the terminal. A change
+                        // of command counts as a window change.
+                        let mut tmux_pane = None;
+                        if let Some(probe) = tmux_probe.as_mut().filter(|_| tmux::is_terminal(&app_name, &rule_terminals)) {
+                            match probe.pane() {
+                                Ok(Some(pane)) => {
+                                    current_window.app_name = pane.command.clone();
+                                    tmux_pane = Some(pane);
+                                }
+                                Ok(None) => {}
+                                Err(e) => println!("❌ {}", e),
+                            }
+                        }
+                        let app_name = current_window.app_name.clone();
+                        let same_window = last_window.as_ref() == Some(&current_window);
+
+                        // At sub-second intervals a change counts once it has settled
+                        match debouncer.observe(last_window.as_ref(), current_window, chrono::Utc::now()) {
+                            Some((current_window, since)) => {
+                                if let Some(held) = log_limiter.allow("window_change", WINDOW_CHANGE_LOG_INTERVAL) {
+                                    let pane_path = tmux_pane.as_ref()
+                                        .and_then(|pane| pane.path.as_ref())
+                                        .map(|path| format!(" (tmux pane in {})", path))
+                                        .unwrap_or_default();
+                                    println!("~=~ Window changed to: {} - {}{}{}", app_name, window_title, pane_path, tracking::held_back_note(held));
+                                }
+                                let event = TrackerEvent::window_change(&tracking_config, &current_window, since);
+                                if let Err(e) = db_tx_events.lock().unwrap().send(DatabaseCommand::RecordEvent(event)) {
+                                    eprintln!("❌ Failed to send event to database thread: {}", e);
+                                }
+                                let mut tracker = tracker_clone1.lock().unwrap();
+                                tracker.update_active_window_at(current_window.clone(), since);
+                                if tracker.needs_flush() && !flush_requested.swap(true, Ordering::Relaxed) {
+                                    println!("~=~ {} rows buffered, saving early", tracker.buffered_rows());
+                                }
+                                last_window = Some(current_window);
+                                same_window_count = 0;
+                                still_on_logged = std::time::Instant::now();
+                            }
+                            None if same_window => {
+                                // Same window, just log occasionally for debugging
+                                same_window_count += 1;
+                                if still_on_logged.elapsed() >= STILL_ON_LOG_INTERVAL {
+                                    println!("~=~ Still on: {} - {} ({} checks)", app_name, window_title, same_window_count);
+                                    still_on_logged = std::time::Instant::now();
+                                }
+                            }
+                            // A new window that hasn't settled yet
+                            None => {}
+                        }
+                    }
+                    None => {
+                        consecutive_failures += 1;
+
+                        // A display that went away (logout) won't come back: end the
+                        // session cleanly instead of logging failures forever
+                        if !daemon_state.display_available() {
+                            println!("~=~ Display {} is gone, ending session", daemon_state.describe_display());
+                            if detached {
+                                let _ = remove_pid_file();
+                            }
+                            break;
+                        }
+                        if log_limiter.allow("detection_failed", tracking::LOG_INTERVAL).is_some() {
+                            println!("❌ Could not get active window (consecutive failures: {})", consecutive_failures);
+                        }
+                    
+                        // If we've had too many consecutive failures, log more details
+                        if consecutive_failures == 5 {
+                            println!("~=~ Debugging window detection...");
+                            print_detection_report(&tracking::DetectionReport::collect());
+                        }
+                    }
+                }
+
+                // Only the idle time is sampled, never the keys themselves
+                if tracking_config.track_input_activity {
+                    match activity::had_input_within(activity_window) {
+                        Some(active) => tracker_clone1.lock().unwrap().record_activity(active),
+                        None if !activity_unavailable => {
+                            println!("~=~ Input idle time unavailable (install xprintidle on X11), engaged focus won't be scored");
+                            activity_unavailable = true;
+                        }
+                        None => {}
+                    }
+                }
+
+                if power_checked.elapsed() >= power::CHECK_INTERVAL {
+                    tracker_clone1.lock().unwrap().set_power_source(power::current());
+                    power_checked = std::time::Instant::now();
+                }
+            }
+
+            if usage_sampled.elapsed() >= usage::SAMPLE_INTERVAL {
+                if let Some(warning) = usage_monitor.sample(chrono::Utc::now()) {
+                    println!("~=~ {}", warning);
+                }
+                usage_sampled = std::time::Instant::now();
+            }
+
+            // A DST change keeps the zone's rules, so only a new zone (travel,
+            // manual change) also moves the offset of the last check
+            let now = chrono::Utc::now();
+            let offset = utils::local_offset(now);
+            let (checked_at, checked_offset) = last_offset;
+            if offset != checked_offset && utils::local_offset(checked_at) != checked_offset {
+                println!("~=~ Timezone changed: {} → {}",
+                    utils::describe_utc_offset(checked_offset), utils::describe_utc_offset(offset));
+                let event = TrackerEvent::now(
+                    EventKind::TimezoneChange,
+                    serde_json::json!({
+                        "from": utils::describe_utc_offset(checked_offset),
+                        "to": utils::describe_utc_offset(offset),
+                    }),
+                );
+                if let Err(e) = db_tx_events.lock().unwrap().send(DatabaseCommand::RecordEvent(event)) {
+                    eprintln!("❌ Failed to send event to database thread: {}", e);
+                }
+            }
+            last_offset = (now, offset);
+
+            if let Some(current) = &mut daily_session {
+                let now = chrono::Utc::now();
+                let name = utils::daily_session_name(now, tracking_config.day_start_hour);
+                if name != *current {
+                    let at = utils::day_start(now, tracking_config.day_start_hour);
+                    let mut tracker = tracker_clone1.lock().unwrap();
+                    // A planned session running over midnight hands back to
+                    // the new day's session when it ends
+                    if tracker.get_session_name() == current.as_str() {
+                        println!("~=~ New day: rolling session \"{}\" over to \"{}\"", current, name);
+                        roll_over_daemon_session(&mut tracker, &db_tx_events.lock().unwrap(), name.clone(), at, detached);
+                    }
+                    *current = name;
+                }
+            }
+
+            // The daemon's view for `focusdebt debug state`, and its unsaved
+            // rows for `stats` and `sessions`
+            if detached && debug_written.is_none_or(|written| written.elapsed() >= DEBUG_STATE_INTERVAL) {
+                let (mut state, live) = {
+                    let tracker = tracker_clone1.lock().unwrap();
+                    let current = tracker.get_current_session();
+                    let state = utils::DebugState {
+                        pid: utils::get_current_pid(),
+                        updated_at: chrono::Utc::now(),
+                        session_name: tracker.get_session_name().to_string(),
+                        current_app: current.as_ref().map(|session| session.app_name.clone()),
+                        current_title: current.as_ref().map(|session| session.window_title.clone()),
+                        current_started_at: current.as_ref().map(|session| session.start_time),
+                        current_is_focus: current.as_ref().map(|session| session.is_focus_app),
+                        buffered_sessions: tracker.get_completed_sessions().len(),
+                        buffered_switches: tracker.get_context_switches().len(),
+                        buffer_limit: tracking_config.max_buffered_rows,
+                        ..Default::default()
+                    };
+                    (state, tracking::LiveSnapshot::capture(&tracker))
+                };
+                state.db_queue_depth = db_queued_debug.load(Ordering::Relaxed);
+                state.last_detection_at = last_detection_at;
+                state.backend = backend.map(str::to_string);
+                state.consecutive_failures = consecutive_failures;
+                state.backend_failovers = selector.failovers();
+                state.early_saves = early_saves_debug.load(Ordering::Relaxed);
+                state.memory_bytes = utils::resident_memory();
+                state.resources = Some(usage_monitor.usage());
+                if let Err(e) = utils::write_debug_state(&state) {
+                    eprintln!("❌ Failed to write debug state: {}", e);
+                }
+                if let Err(e) = tracking::write_live_snapshot(&live) {
+                    eprintln!("❌ Failed to write live snapshot: {}", e);
+                }
+                debug_written = Some(std::time::Instant::now());
+            }
+
+            sleep_ms(tracking_config.tracking_interval_ms); // Use config interval
+        }
+        
+        println!("~=~ Tracking thread exiting");
+    });
+
+    let rollover_start_hooks = config.on_session_start.clone();
+    let rollover_end_hooks = config.on_session_end.clone();
+    let rollover_settings = settings.clone();
+    let mut retention = config.retention_days.map(|days| storage::DailyRetention::new(days, config.day_start_hour));
+    let day_start_hour = config.day_start_hour;
+    let max_clock_skew_days = config.max_clock_skew_days;
+    let disk_warning_days = config.disk_warning_days;
+    let mut spill = spill::Spill::new(config.spill_journal(), config.max_buffered_rows, config.max_spill_journal_bytes());
+
+    // Spawn database thread
+    let db_thread = thread::spawn(move || {
+        println!("~=~ Database thread started");
+        
+        // Create database connection in this thread
+        let db = match Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ Failed to initialize database in database thread: {}", e);
+                return None;
+            }
+        };
+        if let Err(e) = db.record_daemon_version(env!("CARGO_PKG_VERSION"), chrono::Utc::now()) {
+            eprintln!("❌ Failed to record the daemon version: {}", e);
+        }
+        // An older daemon running through an upgrade may have written text times
+        match db.convert_text_times() {
+            Ok(0) => {}
+            Ok(converted) => eprintln!("~=~ Converted the times of {} rows an older version wrote", converted),
+            Err(e) => eprintln!("❌ Failed to convert text times: {}", e),
+        }
+        
+        // Latest timestamp actually written, reported back for the stop handshake
+        let mut last_committed: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut size_sampled_for: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut degraded_since = None;
+        let mut disk_full_notified = false;
+        let mut last_seen_clock = utils::last_seen_clock();
+
+        // Rows an earlier run spilled go in ahead of this run's
+        if spill.has_pending() {
+            log_replay(&spill.replay(&db), spill.path());
+        }
+        
+        loop {
+            // Degraded mode wakes up on its own to retry the database
+            let command = match db_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+                Ok(command) => Some(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if spill.retry_due() {
+                log_replay(&spill.replay(&db), spill.path());
+            }
+            if spill.degraded_since() != degraded_since {
+                degraded_since = spill.degraded_since();
+                record_degraded_mode(degraded_since, spill.path(), detached, &mut disk_full_notified);
+            }
+            let now = chrono::Utc::now();
+            if spill.degraded_since().is_none() && utils::daily_run_due(size_sampled_for, now, day_start_hour) {
+                sample_database_size(&db, now, disk_warning_days);
+                size_sampled_for = Some(utils::day_start(now, day_start_hour));
+            }
+            let Some(command) = command else {
+                continue;
+            };
+
+            match command {
+                DatabaseCommand::SaveSession(session) => {
+                    let written = session.end_time.unwrap_or(session.start_time);
+                    let saved = format!("{} ({}s)", session.app_name, session.duration.as_secs());
+                    match save_session(&mut spill, &db, session, last_seen_clock, max_clock_skew_days, &mut last_committed) {
+                        Ok(SessionSaved::Quarantined(reason)) => {
+                            eprintln!("❌ Quarantined session {}: it {}. Is the system clock right? See 'focusdebt database check'", saved, reason);
+                        }
+                        Ok(SessionSaved::Stored(stored)) => {
+                            if last_seen_clock.is_none_or(|seen| written > seen) {
+                                last_seen_clock = Some(written);
+                                if let Err(e) = utils::record_seen_clock(written) {
+                                    eprintln!("❌ Failed to record the last seen time: {}", e);
+                                }
+                            }
+                            if stored == spill::Stored::Database {
+                                println!("~=~ Saved session: {}", saved);
+                            }
+                        }
+                        Err(e) => eprintln!("❌ Failed to save session: {}", e),
+                    }
+                }
+                DatabaseCommand::SaveContextSwitch(switch) => {
+                    let written = switch.timestamp;
+                    let saved = format!("{} → {}", switch.from_app, switch.to_app);
+                    match commit_row(&mut spill, &db, spill::SpillRecord::ContextSwitch(switch), written, &mut last_committed) {
+                        Ok(stored) => {
+                            if stored == spill::Stored::Database {
+                                println!("~=~ Saved context switch: {}", saved);
+                            }
+                        }
+                        Err(e) => eprintln!("❌ Failed to save context switch: {}", e),
+                    }
+                }
+                DatabaseCommand::RecordEvent(event) => {
+                    let kind = event.kind.as_str();
+                    if let Err(e) = spill.store(&db, spill::SpillRecord::event(event)) {
+                        eprintln!("❌ Failed to save {} event: {}", kind, e);
+                    }
+                }
+                DatabaseCommand::RollOver { from, to, at } => {
+                    if let Err(e) = spill.store(&db, spill::SpillRecord::SessionName { name: to.clone() }) {
+                        eprintln!("❌ Failed to record session {}: {}", to, e);
+                    }
+                    let event = TrackerEvent {
+                        timestamp: at,
+                        kind: EventKind::SessionRollover,
+                        payload: serde_json::json!({ "from": from, "to": to }),
+                    };
+                    if let Err(e) = spill.store(&db, spill::SpillRecord::event(event)) {
+                        eprintln!("❌ Failed to save {} event: {}", EventKind::SessionRollover.as_str(), e);
+                    }
+                    // Everything of `from` is written by now; neither hook is waited for
+                    let _ = hooks::run_hooks("session end", &rollover_end_hooks, &session_end_env(&db, &rollover_settings, &from));
+                    let _ = hooks::run_hooks(
+                        "session start",
+                        &rollover_start_hooks,
+                        &[("FOCUSDEBT_SESSION_NAME", to.clone())],
+                    );
+                }
+                DatabaseCommand::SaveSessionMeta { session, at, snapshot } => {
+                    let record = spill::SpillRecord::SessionMeta { name: session.clone(), recorded_at: at, snapshot };
+                    if let Err(e) = spill.store(&db, record) {
+                        eprintln!("❌ Failed to record the settings of session {}: {}", session, e);
+                    }
+                }
+                DatabaseCommand::ApplyRetention { keep_session, at } => {
+                    // Spilled rows aren't in the database yet; wait for the replay
+                    if let Some(retention) = retention.as_mut().filter(|_| spill.degraded_since().is_none()) {
+                        match retention.run(&db, &keep_session, at, utils::is_export_running()) {
+                            storage::RetentionRun::NotDue => {}
+                            storage::RetentionRun::ExportRunning => {
+                                println!("~=~ Retention: an export is running, trying again after the next save");
+                            }
+                            storage::RetentionRun::Pruned(report) => println!(
+                                "~=~ Retention: removed {} sessions, {} context switches and {} events older than {} days",
+                                report.sessions, report.context_switches, report.events, retention.days()
+                            ),
+                            storage::RetentionRun::Failed(e) => eprintln!("❌ Retention run failed: {}", e),
+                        }
+                    }
+                }
+                DatabaseCommand::CountNotification { session, source, at } => {
+                    let day = utils::timezone::to_zone(at).date_naive();
+                    if let Err(e) = db.add_notification(&session, day, &source) {
+                        eprintln!("❌ Failed to count a notification from {}: {}", source, e);
+                    }
+                }
+            }
+            db_queued.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        // One last try, then whatever is still spilled waits for the next start
+        if spill.degraded_since().is_some() {
+            let report = spill.replay(&db);
+            log_replay(&report, spill.path());
+            let lost = spill.persist();
+            if report.remaining > lost {
+                println!("~=~ {} rows wait in {}; the next start replays them", report.remaining - lost, spill.path().display());
+            }
+            if lost > 0 {
+                eprintln!("❌ {} rows could not be saved anywhere and are lost", lost);
+            }
+        }
+        
+        println!("~=~ Database thread exiting");
+        last_committed
+    });
+
+    let start_event = TrackerEvent::now(
+        EventKind::DaemonStart,
+        serde_json::json!({ "pid": utils::get_current_pid(), "session_name": session_name }),
+    );
+    if let Err(e) = db_tx.lock().unwrap().send(DatabaseCommand::RecordEvent(start_event)) {
+        eprintln!("❌ Failed to send event to database thread: {}", e);
+    }
+
+    // Start hooks run detached; the daemon does not wait for them
+    let _ = hooks::run_hooks(
+        "session start",
+        &config.on_session_start,
+        &[("FOCUSDEBT_SESSION_NAME", session_name.to_string())],
+    );
+
+    let end_hooks = config.on_session_end.clone();
+    let alert_settings = settings.clone();
+    sounds::start_player(&config.sounds);
+
+    // Notifications are counted from their own thread, towards whichever
+    // session is current when they arrive
+    let notification_monitor = config.count_notifications.then(|| {
+        let tracker = Arc::clone(&tracker);
+        let db_tx = Arc::clone(&db_tx);
+        notifications::Monitor::start(config.notification_command.as_deref(), move |source| {
+            let session = tracker.lock().unwrap().get_session_name().to_string();
+            let command = DatabaseCommand::CountNotification { session, source, at: chrono::Utc::now() };
+            if let Err(e) = db_tx.lock().unwrap().send(command) {
+                eprintln!("❌ Failed to send notification count to database thread: {}", e);
+            }
+        })
+        .map_err(|e| eprintln!("❌ Notification counting is off for this run: {}", e))
+        .ok()
+    }).flatten();
+
+    // Spawn save thread with proper shutdown
+    let save_thread = thread::spawn(move || {
+        let mut save_counter = 0;
+        let mut last_alert_check: Option<std::time::Instant> = None;
+        let mut milestones = alerts::Milestones::default();
+        let mut config_changed = false;
+        let mut seen_fingerprint = config_fingerprint;
+        let mut listed = listed;
+        let mut lists_db: Option<Database> = None;
+        let mut plan_db: Option<Database> = None;
+        let mut last_plan_check: Option<std::time::Instant> = None;
+        let mut running_plan: Option<RunningPlan> = None;
+        println!("~=~ Save thread started");
+        if detached {
+            write_heartbeat(&tracker_clone2.lock().unwrap(), config.save_interval_ms);
+        }
+        
+        while !shutdown_clone2.load(Ordering::Relaxed) {
+            // Sleep in short steps so shutdown isn't held up by a long save interval
+            let mut slept_ms = 0;
+            while slept_ms < config.save_interval_ms && !shutdown_clone2.load(Ordering::Relaxed) {
+                if flush_requested_save.swap(false, Ordering::Relaxed) {
+                    early_saves.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                let step = (config.save_interval_ms - slept_ms).min(200);
+                sleep_ms(step);
+                slept_ms += step;
+            }
+            
+            // Check shutdown again before processing
+            if shutdown_clone2.load(Ordering::Relaxed) {
+                break;
+            }
+            
+            save_counter += 1;
+
+            // Only a daemon tracking the automatic daily session is idle
+            // enough to start a plan; looked up before taking the tracker
+            let now = chrono::Utc::now();
+            let mut due_plan = None;
+            if running_plan.is_none() && config.auto_daily_session
+                && last_plan_check.is_none_or(|checked| checked.elapsed() >= PLAN_CHECK_INTERVAL) {
+                last_plan_check = Some(std::time::Instant::now());
+                if plan_db.is_none() {
+                    plan_db = Database::new().map_err(|e| eprintln!("❌ Failed to open database for plans: {}", e)).ok();
+                }
+                if let Some(db) = &plan_db {
+                    match db.due_planned_session(now) {
+                        Ok(plan) => due_plan = plan,
+                        Err(e) => eprintln!("❌ Failed to look up planned sessions: {}", e),
+                    }
+                }
+            }
+
+            // Focus lists apply without a restart (e.g. `focusapp add`);
+            // other config edits only after one, so say so once
+            let fingerprint = Config::file_fingerprint();
+            if fingerprint != seen_fingerprint {
+                seen_fingerprint = fingerprint;
+                if let Ok(fresh) = Config::load() {
+                    listed = (fresh.tracked_focus_apps(), fresh.focus_sites.clone());
+                    if !config_changed && fresh.needs_restart_from(&config) {
+                        config_changed = true;
+                        println!("~=~ Config changed on disk since the daemon started; restart it to apply the changes");
+                        if let Some(mut state) = utils::read_daemon_state() {
+                            state.config_changed_at = Some(chrono::Utc::now());
+                            if let Err(e) = utils::write_daemon_state(&state) {
+                                eprintln!("❌ Failed to update daemon state: {}", e);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // The database's lists are read on every save, the config's
+            // when the file changes
+            if lists_db.is_none() {
+                lists_db = Database::new().map_err(|e| eprintln!("❌ Failed to open database for focus lists: {}", e)).ok();
+            }
+            let lists = match &lists_db {
+                Some(db) => db.focus_lists(&listed.0, &listed.1)
+                    .map_err(|e| eprintln!("❌ Failed to read the focus lists from the database: {}", e))
+                    .ok(),
+                None => None,
+            };
+
+            let mut tracker = tracker_clone2.lock().unwrap();
+            for change in lists.map(|(apps, sites)| tracker.sync_focus_lists(&apps, &sites)).unwrap_or_default() {
+                println!("~=~ Focus list changed: {}", change);
+            }
+            
+            // Send completed sessions to database thread
+            let completed_sessions = tracker.take_completed_sessions();
+            for session in completed_sessions {
+                if let Err(e) = db_tx_save.lock().unwrap().send(DatabaseCommand::SaveSession(session)) {
+                    eprintln!("❌ Failed to send session to database thread: {}", e);
+                }
+            }
+            
+            // Send context switches to database thread
+            let context_switches = tracker.take_context_switches();
+            for switch in context_switches {
+                if let Err(e) = db_tx_save.lock().unwrap().send(DatabaseCommand::SaveContextSwitch(switch)) {
+                    eprintln!("❌ Failed to send context switch to database thread: {}", e);
+                }
+            }
+            if detached {
+                write_heartbeat(&tracker, config.save_interval_ms);
+            }
+            
+            let daily = utils::daily_session_name(now, config.day_start_hour);
+            let (plan_started, plan_finished) = step_plans(&mut tracker, &db_tx_save.lock().unwrap(), &mut running_plan, due_plan, &daily, now, detached);
+
+            // Queued after the rows above, so it sees them written
+            if config.retention_days.is_some() {
+                let command = DatabaseCommand::ApplyRetention {
+                    keep_session: tracker.get_session_name().to_string(),
+                    at: chrono::Utc::now(),
+                };
+                if let Err(e) = db_tx_save.lock().unwrap().send(command) {
+                    eprintln!("❌ Failed to send retention run to database thread: {}", e);
+                }
+            }
+            
+            // Log stats periodically
+            if save_counter % 10 == 0 {
+                let stats = tracker.get_stats();
+                println!("~=~ Tracker stats: {} sessions, {} switches, current: {}s", 
+                    stats.total_sessions, 
+                    stats.total_context_switches,
+                    stats.current_session_duration.as_secs()
+                );
+            }
+            
+            let current_session = tracker.get_current_session();
+            drop(tracker); // Release lock before sleeping
+
+            if let (Some(plan), Some(db)) = (plan_started, &plan_db) {
+                if let Err(e) = db.mark_plan_started(plan.id, now) {
+                    eprintln!("❌ Failed to record the start of plan {}: {}", plan.name, e);
+                }
+                alerts::send_notification(
+                    "FocusDebt: planned session started",
+                    &format!("\"{}\" until {}", plan.name, utils::format_timestamp_local(plan.until)),
+                );
+            }
+            if let (Some(plan), Some(db)) = (plan_finished, &plan_db) {
+                if let Err(e) = db.mark_plan_completed(plan.id, plan.until) {
+                    eprintln!("❌ Failed to record the end of plan {}: {}", plan.name, e);
+                }
+                alerts::send_notification(
+                    "FocusDebt: planned session done",
+                    &format!("\"{}\" is over; tracking the day again", plan.name),
+                );
+            }
+            
+            alerts::check_budgets(&config, &alert_settings, current_session.as_ref());
+            milestones.check(&config.sounds, current_session.as_ref(), now);
+            
+            // Efficiency floor check; the alert itself fires at most once a day
+            if last_alert_check.is_none_or(|checked| checked.elapsed() >= EFFICIENCY_CHECK_INTERVAL) {
+                alerts::check_efficiency_floor(&config, &alert_settings);
+                last_alert_check = Some(std::time::Instant::now());
+            }
+        }
+        
+        println!("~=~ Save thread exiting");
+    });
+
+    // Wait for stop signal (or for the tracking thread to give up on its own)
+    while !tracking_thread.is_finished() && !should_stop() {
+        sleep_ms(200);
+    }
+
+    // Signal shutdown and stop the producers first so nothing new arrives mid-flush
+    shutdown.store(true, Ordering::Relaxed);
+    let _ = tx.send(());
+    let _ = tracking_thread.join();
+    let _ = save_thread.join();
+    if let Some(monitor) = notification_monitor {
+        monitor.stop();
+    }
+    
+    // A daily session may have rolled over since we started
+    let session_name = tracker.lock().unwrap().get_session_name().to_string();
+    let session_name = session_name.as_str();
+
+    drain_tracker(&mut tracker.lock().unwrap(), &db_tx.lock().unwrap(), session_name);
+    
+    // Dropping the last sender lets the database thread drain its queue and exit
+    drop(db_tx);
+    let last_committed = db_thread.join().unwrap_or(None);
+    
+    // Tell `focusdebt stop` that everything is on disk
+    if detached {
+        if let Err(e) = utils::write_flush_marker(last_committed) {
+            eprintln!("❌ Failed to write flush marker: {}", e);
+        }
+    }
+    println!("~=~ Flushed pending data");
+    
+    // End hooks see the final numbers, so they run after the flush. Wait for
+    // them (each is bounded by a timeout) so exiting doesn't cut them short.
+    if !end_hooks.is_empty() {
+        let env = match Database::new() {
+            Ok(db) => session_end_env(&db, &settings, session_name),
+            Err(_) => vec![("FOCUSDEBT_SESSION_NAME", session_name.to_string())],
+        };
+        for hook in hooks::run_hooks("session end", &end_hooks, &env) {
+            let _ = hook.join();
+        }
+    }
+}
+
+/// Environment for session end hooks: the name plus its final numbers
+fn session_end_env(db: &Database, settings: &StatsSettings, session_name: &str) -> Vec<(&'static str, String)> {
+    let mut env = vec![("FOCUSDEBT_SESSION_NAME", session_name.to_string())];
+    if let Ok(stats) = Stats::calculate_session_stats(db, settings, session_name) {
+        let focus_seconds: u64 = stats.app_usage.iter()
+            .filter(|(_, _, is_focus)| *is_focus)
+            .map(|(_, duration, _)| duration.as_secs())
+            .sum();
+        env.push(("FOCUSDEBT_FOCUS_SECONDS", focus_seconds.to_string()));
+        env.push(("FOCUSDEBT_EFFICIENCY", format!("{:.0}", stats.focus_efficiency)));
+    }
+    env
+}
+
+/// Set by SIGINT/SIGTERM while `track` runs in the foreground
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Turn Ctrl-C into a clean stop instead of killing the process mid-write
+#[cfg(unix)]
+fn catch_interrupt() {
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {}
+
+/// The detached daemon's stdout and stderr going into the daemon log
+#[cfg(unix)]
+static DAEMON_LOG: Mutex<Option<logs::Forwarding>> = Mutex::new(None);
+
+/// Write out the daemon's last log lines before it exits
+#[cfg(unix)]
+fn finish_daemon_log() {
+    if let Some(forwarding) = DAEMON_LOG.lock().unwrap().take() {
+        forwarding.finish();
+    }
+}
+
+#[cfg(not(unix))]
+fn finish_daemon_log() {}
+
+/// Point stdout at the daemon log, keeping the original so it can be put back
+#[cfg(unix)]
+fn redirect_stdout_to_log() -> Option<logs::Forwarding> {
+    logs::Forwarding::start(&logs::daemon_log_path()?, false, true).ok()
+}
+
+#[cfg(unix)]
+fn restore_stdout(forwarding: Option<logs::Forwarding>) {
+    if let Some(forwarding) = forwarding {
+        forwarding.finish();
+    }
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout_to_log() -> Option<()> {
+    None
+}
+
+#[cfg(not(unix))]
+fn restore_stdout(_forwarding: Option<()>) {}
+
+/// `focusdebt track`: one session in this process, with no daemon, PID file
+/// or separate stop step. Ends on Ctrl-C or when the wrapped command exits,
+/// then prints the summary and passes on the command's exit code.
+fn track_foreground(settings: &StatsSettings, name: Option<String>, allow_duplicate: bool, command: &[String]) -> ExitCode {
+    // Both would record the same windows
+    if is_daemon_running() {
+        eprintln!("❌ The tracking daemon is already running. Stop it with 'focusdebt stop' first so time isn't counted twice.");
+        return ExitCode::FAILURE;
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config, using defaults: {}", e);
+            Config::default()
+        }
+    };
+    let allow_duplicate = allow_duplicate || config.allow_duplicate_session_names;
+    let config_fingerprint = Config::file_fingerprint();
+    print_config_warnings(&config);
+    if let Err(e) = tracking::check_interval(config.tracking_interval_ms, config.backend.as_deref()) {
+        eprintln!("❌ {}. Set it to {} or more with 'focusdebt config set tracking_interval_ms {}'.",
+            e, tracking::MIN_SPAWNING_INTERVAL_MS, tracking::MIN_SPAWNING_INTERVAL_MS);
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = ensure_data_directory() {
+        eprintln!("❌ Failed to create data directory: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let daily = name.is_none() && config.auto_daily_session;
+    let session_name = loop {
+        let session_name = match &name {
+            Some(name) => match clean_session_name(name, config.max_session_name_length) {
+                Ok(name) => name,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    return ExitCode::FAILURE;
+                }
+            },
+            None if daily => utils::daily_session_name(chrono::Utc::now(), config.day_start_hour),
+            None => match prompt_session_name(allow_duplicate, config.max_session_name_length) {
+                Some(name) => name,
+                None => return ExitCode::FAILURE,
+            },
+        };
+        match claim_session_name(&session_name, allow_duplicate || daily) {
+            Ok(true) => break session_name,
+            Ok(false) if name.is_some() => {
+                eprintln!("❌ Session name '{}' already exists. Pick another or pass --allow-duplicate.", session_name);
+                return ExitCode::FAILURE;
+            }
+            Ok(false) => println!("❌ Session name '{}' already exists. Please choose a different name.\n", session_name),
+            Err(e) => {
+                eprintln!("❌ Failed to claim session name: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+
+    catch_interrupt();
+    let mut child = match command.split_first() {
+        Some((program, args)) => match process::Command::new(program).args(args).spawn() {
+            Ok(child) => Some(child),
+            Err(e) => {
+                eprintln!("❌ Failed to run '{}': {}", program, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    match &child {
+        Some(_) => println!("~=~ Tracking \"{}\" until '{}' exits (Ctrl-C stops early)", session_name, command.join(" ")),
+        None => println!("~=~ Tracking \"{}\"; press Ctrl-C to stop and see the summary", session_name),
+    }
+
+    // The tracker's chatter goes to the daemon log, as it would for the daemon
+    let terminal = redirect_stdout_to_log();
+    let daemon_state = utils::DaemonState::current(&session_name);
+    run_tracking(config, config_fingerprint, &session_name, daemon_state, false, || {
+        INTERRUPTED.load(Ordering::SeqCst)
+            || child.as_mut().is_some_and(|child| !matches!(child.try_wait(), Ok(None)))
+    });
+    restore_stdout(terminal);
+
+    // On Ctrl-C the wrapped command got the signal too; let it finish exiting
+    let status = child.map(|mut child| child.wait());
+
+    match Database::new_read_only().map_err(|e| e.to_string())
+        .and_then(|db| Stats::calculate_session_stats(&db, settings, &session_name).map_err(|e| e.to_string()))
+    {
+        Ok(session_stats) => Stats::display_session_summary(&session_stats, settings),
+        Err(e) => eprintln!("❌ Failed to calculate session stats: {}", e),
+    }
+
+    match status {
+        Some(Ok(status)) => {
+            println!("~=~ '{}' exited with {}", command.join(" "), status);
+            // Killed by a signal, or a code an exit status can't carry
+            status.code().and_then(|code| u8::try_from(code).ok()).map_or(ExitCode::FAILURE, ExitCode::from)
+        }
+        Some(Err(e)) => {
+            eprintln!("❌ Failed to wait for '{}': {}", command.join(" "), e);
+            ExitCode::FAILURE
+        }
+        None => ExitCode::SUCCESS,
+    }
+}
+
+/// `raw` as a session name: see `utils::sanitize_session_name`, warning
+/// when something was removed or cut
+fn clean_session_name(raw: &str, max_length: usize) -> Result<String, String> {
+    let cleaned = utils::sanitize_session_name(raw, max_length)?;
+    if cleaned.stripped {
+        eprintln!("~=~ Removed control characters from the session name");
+    }
+    if cleaned.cut {
+        eprintln!("~=~ Session name cut to {} characters (max_session_name_length)", max_length);
+    }
+    Ok(cleaned.name)
+}
+
+/// None when stdin ends before a name is given
+fn prompt_session_name(allow_duplicate: bool, max_length: usize) -> Option<String> {
+    loop {
+        println!("~=~ Please name this focus session:");   
+        print!("~=~ Session name: ");
+        io::stdout().flush().unwrap();
+        let mut input_name = String::new();
+        if io::stdin().read_line(&mut input_name).unwrap_or(0) == 0 {
+            eprintln!("\n❌ No session name given");
+            return None;
+        }
+        let input_name = match clean_session_name(&input_name, max_length) {
+            Ok(name) => name,
+            Err(e) => {
+                println!("❌ {}. Please try again.\n", e);
+                continue;
+            }
+        };
+        
+        // Early, friendly check; the daemon makes the authoritative one
+        if !allow_duplicate {
+            if let Ok(db) = Database::new() {
+                match db.session_name_exists(&input_name) {
+                    Ok(true) => {
+                        println!("❌ Session name '{}' already exists. Please choose a different name.\n", input_name);
+                        continue;
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        eprintln!("❌ Failed to check session name: {}. The daemon will check it.", e);
+                    }
+                }
+            }
+        }
+        
+        return Some(input_name);
+    }
+}
+
+enum Launch {
+    /// We are the daemon and the session name is ours; it still has to
+    /// report whether it came up
+    Running(Startup),
+    /// The session name was claimed by someone else first
+    NameTaken,
+    /// We started the daemon and it came up; nothing is left to do here
+    Started,
+    /// The daemon couldn't be started, or we are a daemon that couldn't
+    /// claim the name; the reason was printed
+    Failed,
+}
+
+/// Claim the session name from the daemon's own database connection, so two
+/// concurrent starts can't both get it
+fn claim_session_name(session_name: &str, allow_duplicate: bool) -> Result<bool, String> {
+    Database::new()
+        .and_then(|db| db.claim_session_name(session_name, allow_duplicate))
+        .map_err(|e| e.to_string())
+}
+
+/// How long `start` waits for the daemon to report that it came up
+#[cfg(unix)]
+const STARTUP_TIMEOUT_MS: u64 = 15_000;
+
+/// What the daemon checks before it reports that it came up: that it can
+/// open the database and that a detection backend sees windows
+fn check_daemon_startup(config: &Config) -> Result<(), String> {
+    Database::new().map_err(|e| format!("failed to open the database: {}", e))?;
+    let backend = tracking::check_detection(config.backend.as_deref(), config.detection_fallback)?;
+    println!("~=~ Startup check: database opened, {} detection works", backend);
+    Ok(())
+}
+
+/// Where to look for what went wrong, after a failed start
+#[cfg(unix)]
+fn daemon_log_hint() -> String {
+    logs::daemon_log_path()
+        .map(|path| format!(" (daemon log: {})", path.display()))
+        .unwrap_or_default()
+}
+
+/// The daemon's half of the startup handshake, held until it has checked
+/// that it can work
+#[cfg(unix)]
+struct Startup {
+    /// The pipe to the `start` process waiting in `launch_daemon`
+    reply_fd: libc::c_int,
+}
+
+#[cfg(unix)]
+impl Startup {
+    /// Tell `start` whether the daemon came up. On success the daemon's PID
+    /// file is written first, so `start` never reports a daemon `status`
+    /// can't see; false when the daemon has to exit.
+    fn report(self, result: Result<(), String>) -> bool {
+        let reply = match result.and_then(|()| write_pid_file(utils::get_current_pid()).map_err(|e| format!("failed to write PID file: {}", e))) {
+            Ok(()) => "ok".to_string(),
+            Err(e) => {
+                eprintln!("❌ Daemon failed to start: {}", e);
+                format!("error:{}", e)
+            }
+        };
+        unsafe {
+            libc::write(self.reply_fd, reply.as_ptr() as *const libc::c_void, reply.len());
+            libc::close(self.reply_fd);
+        }
+        reply == "ok"
+    }
+}
+
+/// Wait up to STARTUP_TIMEOUT_MS for the daemon's reply on `read_fd`. None
+/// when it doesn't come in time; empty when the daemon closed the pipe
+/// without one (it died).
+#[cfg(unix)]
+fn read_startup_reply(read_fd: libc::c_int) -> Option<String> {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(STARTUP_TIMEOUT_MS);
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() {
+            return None;
+        }
+        let mut poll_fd = libc::pollfd { fd: read_fd, events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, left.as_millis() as libc::c_int) };
+        if ready < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        }
+        if ready <= 0 {
+            return (ready < 0).then(String::new);
+        }
+        let read = unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if read <= 0 {
+            return Some(String::from_utf8_lossy(&reply).to_string());
+        }
+        reply.extend_from_slice(&buf[..read as usize]);
+    }
+}
+
+/// Fork the daemon. The parent waits for the child to report over a pipe
+/// whether it could claim the session name and whether it came up, and
+/// is done once it has: Started only when the daemon is running.
+#[cfg(unix)]
+fn launch_daemon(session_name: &str, allow_duplicate: bool) -> Launch {
+    unsafe {
+        let mut fds = [0; 2];
+        if libc::pipe(fds.as_mut_ptr()) < 0 {
+            eprintln!("❌ Failed to create startup pipe");
+            return Launch::Failed;
+        }
+        let (read_fd, write_fd) = (fds[0], fds[1]);
+        // A program the startup check runs mustn't hold the pipe open
+        libc::fcntl(read_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(write_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+
+        let pid = libc::fork();
+        if pid < 0 {
+            eprintln!("❌ Failed to fork daemon process");
+            return Launch::Failed;
+        } else if pid > 0 {
+            // Parent process - wait for the child's verdict on the session
+            // name, then on whether it came up
+            libc::close(write_fd);
+            let reply = read_startup_reply(read_fd);
+            libc::close(read_fd);
+
+            match reply.as_deref() {
+                Some("ok") => {
+                    println!("~=~ Focus tracking daemon started successfully (PID: {})", pid);
+                    println!("~=~ Tracking active windows and context switches...");
+                    println!("~=~ Use 'focusdebt stop' to stop tracking and view summary");
+                    return Launch::Started;
+                }
+                Some("taken") => {
+                    libc::waitpid(pid, std::ptr::null_mut(), 0);
+                    return Launch::NameTaken;
+                }
+                Some(reply) => {
+                    libc::waitpid(pid, std::ptr::null_mut(), 0);
+                    let _ = remove_pid_file();
+                    let reason = reply.strip_prefix("error:").unwrap_or("the daemon exited without saying why");
+                    eprintln!("❌ Daemon failed to start: {}{}", reason, daemon_log_hint());
+                    return Launch::Failed;
+                }
+                None => {
+                    libc::kill(pid, libc::SIGKILL);
+                    libc::waitpid(pid, std::ptr::null_mut(), 0);
+                    let _ = remove_pid_file();
+                    eprintln!("❌ Daemon failed to start: no answer within {}s{}", STARTUP_TIMEOUT_MS / 1000, daemon_log_hint());
+                    return Launch::Failed;
+                }
+            }
+        }
+        // Child process continues here
+        libc::close(read_fd);
+        
+        // Create new session
+        if libc::setsid() < 0 {
+            eprintln!("❌ Failed to create new session");
+            return Launch::Failed;
+        }
+        
+        // Change to root directory to avoid keeping any directory in use
+        if libc::chdir(c"/".as_ptr()) < 0 {
+            eprintln!("❌ Failed to change directory");
+            return Launch::Failed;
+        }
+        
+        // Redirect stdout/stderr to the log file, one timestamped line per message
+        match logs::daemon_log_path().map(|path| logs::Forwarding::start(&path, true, false)) {
+            Some(Ok(forwarding)) => *DAEMON_LOG.lock().unwrap() = Some(forwarding),
+            _ => {
+                let null = libc::open(c"/dev/null".as_ptr(), libc::O_WRONLY);
+                if null >= 0 {
+                    libc::dup2(null, 1);
+                    libc::dup2(null, 2);
+                    libc::close(null);
+                }
+            }
+        }
+
+        
+        // Close stdin
+        libc::close(0);
+
+        // "ok" waits for the startup check in run_daemon
+        let reply = match claim_session_name(session_name, allow_duplicate) {
+            Ok(true) => return Launch::Running(Startup { reply_fd: write_fd }),
+            Ok(false) => "taken".to_string(),
+            Err(e) => format!("error:failed to claim the session name: {}", e),
+        };
+        libc::write(write_fd, reply.as_ptr() as *const libc::c_void, reply.len());
+        libc::close(write_fd);
+        eprintln!("❌ Could not claim session name '{}': {}", session_name, reply);
+        finish_daemon_log();
+        Launch::Failed
+    }
+}
+
+/// Without forking the daemon runs in this process, so the handshake is
+/// the startup check's result printed here
+#[cfg(windows)]
+struct Startup;
+
+#[cfg(windows)]
+impl Startup {
+    /// Print whether the daemon came up; false when it has to exit
+    fn report(self, result: Result<(), String>) -> bool {
+        let result = result.and_then(|()| write_pid_file(utils::get_current_pid()).map_err(|e| format!("failed to write PID file: {}", e)));
+        if let Err(e) = result {
+            let _ = remove_pid_file();
+            eprintln!("❌ Daemon failed to start: {}", e);
+            return false;
+        }
+        println!("~=~ Focus tracking daemon started successfully");
+        println!("~=~ Tracking active windows and context switches...");
+        println!("~=~ Use 'focusdebt stop' to stop tracking and view summary");
+        true
+    }
+}
+
+// On Windows, claim the name in this process (no proper daemonization)
+#[cfg(windows)]
+fn launch_daemon(session_name: &str, allow_duplicate: bool) -> Launch {
+    match claim_session_name(session_name, allow_duplicate) {
+        Ok(true) => Launch::Running(Startup),
+        Ok(false) => Launch::NameTaken,
+        Err(e) => {
+            eprintln!("❌ Daemon failed to start: failed to claim the session name: {}", e);
+            Launch::Failed
+        }
+    }
+}
+
+fn stop_daemon(settings: &StatsSettings, rating: Option<u8>, met: Option<bool>) {
+    let session_name = utils::read_daemon_state().map(|state| state.session_name);
+    match request_daemon_stop() {
+        Some(Some(last_committed)) => {
+            println!("~=~ Daemon flushed all data (last write at {})", utils::format_timestamp(last_committed));
+        }
+        Some(None) => println!("~=~ Daemon flushed all data"),
+        None => eprintln!("❌ Daemon did not confirm flush after {}s; summary may be incomplete", FLUSH_TIMEOUT_MS / 1000),
+    }
+
+    // Show session summary
+    show_session_summary(settings);
+    if let Some(session_name) = session_name {
+        stop_session_task(&session_name);
+        record_session_outcome(&session_name, rating, met);
+    }
+}
+
+/// With taskwarrior_integration, stop the taskwarrior task the session
+/// was started on
+fn stop_session_task(session_name: &str) {
+    if !Config::load().unwrap_or_default().taskwarrior_integration {
+        return;
+    }
+    let task = match Database::new_read_only().and_then(|db| db.get_session_task(session_name)) {
+        Ok(Some(task)) if task.source == storage::TaskSource::Taskwarrior => task,
+        Ok(_) => return,
+        Err(e) => {
+            eprintln!("❌ Failed to read the session's task: {}", e);
+            return;
+        }
+    };
+    match tasks::set_active(&tasks::TaskCommand, &task, false) {
+        Ok(()) => println!("~=~ Stopped task {} in taskwarrior", task.id),
+        Err(e) => eprintln!("❌ Failed to stop task {} in taskwarrior: {}", task.id, e),
+    }
+}
+
+/// Rate the session that just stopped: as --rating and --met say, or,
+/// for a session started with an intent, as answered at the terminal
+fn record_session_outcome(session_name: &str, rating: Option<u8>, met: Option<bool>) {
+    use std::io::IsTerminal;
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let has_intent = db.get_session_intent(session_name).ok().flatten().is_some_and(|intent| intent.intent.is_some());
+    let ask = has_intent && io::stdin().is_terminal();
+    match session_outcome(rating, met, ask, &mut io::stdin().lock()) {
+        Some((rating, met)) => save_session_outcome(&db, session_name, rating, met),
+        None if has_intent && !ask => {
+            println!("~=~ Rate how it went with 'focusdebt sessions rate \"{}\" <1-5> --met yes|no'", session_name);
+        }
+        None => {}
+    }
+}
+
+/// The rating and intent answer to save: the flags, with what they leave
+/// out asked for on `input` when `ask`; None when there's no rating
+fn session_outcome(rating: Option<u8>, met: Option<bool>, ask: bool, input: &mut impl BufRead) -> Option<(u8, Option<bool>)> {
+    let rating = match rating {
+        Some(rating) => rating,
+        None if ask => prompt_rating(input)?,
+        None => return None,
+    };
+    let met = match met {
+        Some(met) => Some(met),
+        None if ask => prompt_intent_met(input),
+        None => None,
+    };
+    Some((rating, met))
+}
+
+/// A 1-5 rating typed at the terminal; None when skipped with Enter
+fn prompt_rating(input: &mut impl BufRead) -> Option<u8> {
+    loop {
+        print!("~=~ How did it go, 1 (poor) to 5 (great)? Enter skips: ");
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        if input.read_line(&mut answer).unwrap_or(0) == 0 || answer.trim().is_empty() {
+            return None;
+        }
+        match answer.trim().parse::<u8>() {
+            Ok(rating @ 1..=5) => return Some(rating),
+            _ => println!("❌ Type a number from 1 to 5"),
+        }
+    }
+}
+
+/// Whether the intent was met, as answered at the terminal; None when skipped
+fn prompt_intent_met(input: &mut impl BufRead) -> Option<bool> {
+    print!("~=~ Did you get done what you set out to? [y/n] ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    input.read_line(&mut answer).ok()?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn save_session_outcome(db: &Database, session_name: &str, rating: u8, met: Option<bool>) {
+    match db.set_session_outcome(session_name, rating, met) {
+        Ok(()) => {
+            let outcome = storage::SessionIntent { rating: Some(rating), intent_met: met, ..Default::default() };
+            println!("~=~ Rated \"{}\" {}", session_name, outcome.describe_outcome().unwrap_or_default());
+        }
+        Err(e) => eprintln!("❌ Failed to save the rating of \"{}\": {}", session_name, e),
+    }
+}
+
+fn rate_session(name: &str, rating: u8, met: Option<bool>) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let name = match resolve_session_name(&db, name) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    save_session_outcome(&db, &name, rating, met);
+}
+
+/// Signal the daemon to stop and wait for its final flush. Returns the flush
+/// marker (the last row's time, if any), or None when the daemon didn't
+/// confirm within FLUSH_TIMEOUT_MS.
+fn request_daemon_stop() -> Option<Option<chrono::DateTime<chrono::Utc>>> {
+    // A marker left over from an earlier run must not be mistaken for this one
+    let _ = utils::remove_flush_marker();
+
+    // Remove PID file to signal stop
+    if let Err(e) = remove_pid_file() {
+        eprintln!("❌ Failed to remove PID file: {}", e);
+    }
+
+    // Wait for the daemon to confirm everything was written
+    let flushed = wait_for_flush(utils::read_flush_marker, FLUSH_TIMEOUT_MS);
+    // Stopped on purpose, so nothing for the watchdog to report
+    let _ = utils::remove_heartbeat();
+    flushed
+}
+
+/// Poll `read_marker` until the flush marker shows up, or None after `timeout_ms`
+fn wait_for_flush(
+    mut read_marker: impl FnMut() -> Option<Option<chrono::DateTime<chrono::Utc>>>,
+    timeout_ms: u64,
+) -> Option<Option<chrono::DateTime<chrono::Utc>>> {
+    let mut waited_ms = 0;
+    loop {
+        if let Some(last_committed) = read_marker() {
+            return Some(last_committed);
+        }
+        if waited_ms >= timeout_ms {
+            return None;
+        }
+        sleep_ms(100);
+        waited_ms += 100;
+    }
+}
+
+fn show_status(verbose: bool) {
+    if !is_daemon_running() {
+        println!("~=~ Daemon is not running");
+        return;
+    }
+
+    println!("~=~ Daemon is running");
+    let Some(state) = utils::read_daemon_state() else {
+        println!("  (started by an older version, no details available)");
+        return;
+    };
+
+    let uptime = chrono::Utc::now().signed_duration_since(state.started_at).to_std().unwrap_or_default();
+    println!("  Profile : {}", utils::active_profile().unwrap_or("default"));
+    println!("  PID     : {}", state.pid);
+    println!("  Session : {}", state.session_name);
+    println!("  Started : {} ({} ago)", utils::format_timestamp_local(state.started_at), utils::format_duration(uptime, DurationStyle::Long));
+    println!("  Display : {}", state.describe_display());
+    if let Some(range) = &state.quiet_hours {
+        println!("  Paused  : quiet hours ({})", range);
+    }
+    if verbose {
+        match utils::read_debug_state().and_then(|debug| debug.resources) {
+            Some(resources) => {
+                println!("  CPU     : {}", resources.describe_cpu());
+                if let Some(memory) = resources.describe_memory() {
+                    println!("  Memory  : {}", memory);
+                }
+            }
+            None => println!("  CPU     : not reported (the daemon runs in the foreground or was started by an older version)"),
+        }
+        if let Some(report) = Database::new_read_only().ok().as_ref().and_then(disk_report) {
+            println!("  Database: {}, {}", utils::format_bytes(report.database_bytes), report.describe_growth());
+            println!("  Disk    : {}", report.describe_free_space());
+        }
+    }
+    if !state.integrations.is_empty() {
+        println!("  Integrations: {}", utils::describe_integrations(&state.integrations));
+    }
+    if let Some(since) = state.degraded_since {
+        let journal = Config::load().unwrap_or_default().spill_journal();
+        println!("❌ Disk full since {}: degraded mode, rows go to {} until the database can be written again",
+            utils::format_timestamp_local(since), journal.display());
+    }
+    if let Some(changed_at) = state.config_changed_at {
+        println!("❌ Config changed on disk at {} since the daemon started; run 'focusdebt stop' and 'focusdebt start' to apply it",
+            utils::format_timestamp_local(changed_at));
+    }
+    if let Some(problem) = &state.detection_problem {
+        println!("❌ {}", problem);
+    }
+    if !state.display_available() {
+        println!("❌ That display is gone; the daemon will end the session");
+    } else if !state.matches_current_display() {
+        println!("❌ This shell is on display {}, not the one being tracked", utils::describe_current_display());
+    }
+}
+
+fn run_doctor(clean: bool) {
+    let mut problems = 0;
+
+    if utils::check_dependencies() {
+        println!("~=~ Window detection tools are installed");
+    } else {
+        println!("❌ xdotool not found; X11 window detection will not work");
+        problems += 1;
+    }
+
+    match utils::ensure_profile_data_directory() {
+        Ok(dir) => println!("~=~ Data directory: {}", dir.display()),
+        Err(e) => {
+            println!("❌ Data directory unavailable: {}", e);
+            problems += 1;
+        }
+    }
+
+    match Database::new_read_only() {
+        Ok(db) => {
+            println!("~=~ Database opens fine");
+            if let Some(report) = disk_report(&db) {
+                println!("~=~ Database size: {}, {}", utils::format_bytes(report.database_bytes), report.describe_growth());
+                println!("~=~ Disk: {}", report.describe_free_space());
+                let warning_days = Config::load().unwrap_or_default().disk_warning_days;
+                if disk::should_warn(report.days_left, warning_days) {
+                    println!("❌ At this rate the disk is full within {} days (disk_warning_days); free some space or set retention_days",
+                        warning_days);
+                    problems += 1;
+                }
+            }
+        }
+        Err(e) => {
+            println!("❌ Database unavailable: {}", e);
+            problems += 1;
+        }
+    }
+
+    let report = tracking::DetectionReport::collect();
+    match (report.chosen, report.advice) {
+        (_, Some(advice)) => {
+            println!("❌ {}", advice);
+            problems += 1;
+        }
+        (Some(backend), None) => println!("~=~ Window detection works here (backend: {})", backend),
+        (None, None) => {
+            println!("❌ No backend could detect the active window here; see 'focusdebt debug'");
+            problems += 1;
+        }
+    }
+
+    let current_display = utils::describe_current_display();
+    println!("~=~ This shell's display: {}", current_display);
+
+    // The running daemon's own check counts; without one, check now
+    let daemon_state = is_daemon_running().then(utils::read_daemon_state).flatten();
+    let integrations = match &daemon_state {
+        Some(state) => state.integrations.clone(),
+        None => check_integrations(&mut Config::load().unwrap_or_default()),
+    };
+    if !integrations.is_empty() {
+        println!("~=~ Integrations: {}", utils::describe_integrations(&integrations));
+    }
+    for integration in integrations.iter().filter(|integration| integration.problem.is_some()) {
+        match daemon_state {
+            Some(_) => println!("❌ {}: off in the running daemon until it restarts", integration),
+            None => println!("❌ {}: the daemon would start with it off", integration),
+        }
+        problems += 1;
+    }
+
+    if is_daemon_running() {
+        if let Some(state) = utils::read_daemon_state() {
+            println!("~=~ Daemon display: {}", state.describe_display());
+            if state.config_changed_at.is_some() {
+                println!("❌ The daemon is running with an outdated config; restart it to apply your changes");
+                problems += 1;
+            }
+            if let Some(since) = state.degraded_since {
+                println!("❌ The daemon is in degraded mode: the disk has been full since {}; free some space",
+                    utils::format_timestamp_local(since));
+                problems += 1;
+            }
+            if !state.display_available() {
+                println!("❌ The daemon's display is gone; it will end its session");
+                problems += 1;
+            } else if !state.matches_current_display() {
+                println!("❌ The daemon tracks {} but you are on {}; restart with 'focusdebt start --display ...'",
+                    state.describe_display(), current_display);
+                problems += 1;
+            }
+        }
+    } else {
+        println!("~=~ Daemon is not running");
+    }
+
+    let config = Config::load().unwrap_or_default();
+    for warning in config.lint() {
+        println!("❌ Settings conflict: {}", warning);
+        problems += 1;
+    }
+    let shadowed = conflicts::analyze(&config).iter().filter(|conflict| conflict.kind == conflicts::ConflictKind::Shadowed).count();
+    if shadowed > 0 {
+        println!("❌ {} focus rule{} can never apply; see 'focusdebt config doctor'", shadowed, if shadowed == 1 { "" } else { "s" });
+        problems += 1;
+    }
+
+    let journal = config.spill_journal();
+    let spilled = spill::pending_rows(&journal);
+    if spilled > 0 {
+        println!("❌ {} row{} spilled while the disk was full wait in {}; the daemon replays them once the database can be written",
+            spilled, if spilled == 1 { "" } else { "s" }, journal.display());
+        problems += 1;
+    }
+
+    // A spill_path pointing at the temp directory makes that journal the current one
+    let legacy: Vec<std::path::PathBuf> = utils::runtime_files::legacy_temp_files().into_iter().filter(|path| *path != journal).collect();
+    if clean {
+        problems += clean_legacy_files(&legacy, &journal);
+    } else if !legacy.is_empty() {
+        let names: Vec<String> = legacy.iter().map(|path| path.display().to_string()).collect();
+        println!("❌ Files from an older version are still in the shared temp directory: {}; remove them with 'focusdebt doctor --clean'",
+            names.join(", "));
+        problems += 1;
+    }
+
+    if problems == 0 {
+        println!("~=~ No problems found");
+    } else {
+        println!("❌ {} problem{} found", problems, if problems == 1 { "" } else { "s" });
+    }
+}
+
+/// Remove the legacy temp files, moving rows from an old spill journal into
+/// the current one first so nothing spilled is lost. Returns how many failed.
+fn clean_legacy_files(legacy: &[std::path::PathBuf], journal: &std::path::Path) -> usize {
+    if legacy.is_empty() {
+        println!("~=~ No files from older versions in the shared temp directory");
+        return 0;
+    }
+    let legacy_journal = spill::journal_path(&std::env::temp_dir());
+    let mut failed = 0;
+    for path in legacy {
+        if *path == legacy_journal {
+            match spill::move_journal(path, journal) {
+                Ok(0) => println!("~=~ Removed {}", path.display()),
+                Ok(rows) => println!("~=~ Moved {} spilled row{} from {} to {}",
+                    rows, if rows == 1 { "" } else { "s" }, path.display(), journal.display()),
+                Err(e) => {
+                    println!("❌ Failed to move {} to {}: {}", path.display(), journal.display(), e);
+                    failed += 1;
+                }
+            }
+            continue;
+        }
+        match std::fs::remove_file(path) {
+            Ok(()) => println!("~=~ Removed {}", path.display()),
+            Err(e) => {
+                println!("❌ Failed to remove {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+fn run_maintenance(dry_run: bool) {
+    use utils::runtime_files::{self, RuntimeFile, RuntimeKind};
+
+    let config = Config::load().unwrap_or_default();
+    let journal = config.spill_journal();
+    let files = runtime_files::scan(&journal, spill::JOURNAL_ROTATIONS);
+    if files.is_empty() {
+        println!("~=~ No runtime files found");
+        return;
+    }
+
+    println!("~=~ Runtime files:");
+    for file in &files {
+        let state = match file.stale_reason() {
+            Some(reason) => format!("stale: {}", reason),
+            None => "in use".to_string(),
+        };
+        println!("  {}  {}, {} ({})", file.path.display(), file.kind.label(), utils::format_bytes(file.size), state);
+    }
+    let journal_size: u64 = files.iter()
+        .filter(|file| matches!(file.kind, RuntimeKind::Journal | RuntimeKind::RotatedJournal))
+        .map(|file| file.size)
+        .sum();
+    if journal_size > 0 {
+        let cap = match config.max_spill_journal_mb {
+            0 => "no cap".to_string(),
+            mb => format!("rotated at {} MB", mb),
+        };
+        let rows = spill::pending_rows(&journal);
+        println!("~=~ Spill journal: {} row{} in {} ({})", rows, if rows == 1 { "" } else { "s" }, utils::format_bytes(journal_size), cap);
+    }
+
+    let stale: Vec<&RuntimeFile> = files.iter().filter(|file| file.stale_reason().is_some()).collect();
+    let stale_size: u64 = stale.iter().map(|file| file.size).sum();
+    if stale.is_empty() {
+        println!("~=~ No stale runtime files");
+        return;
+    }
+    if dry_run {
+        println!("~=~ Would remove {} stale file{} ({})", stale.len(), if stale.len() == 1 { "" } else { "s" }, utils::format_bytes(stale_size));
+        return;
+    }
+    let (mut removed, mut removed_size) = (0, 0);
+    for file in stale {
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                removed += 1;
+                removed_size += file.size;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("❌ Failed to remove {}: {}", file.path.display(), e),
+        }
+    }
+    println!("~=~ Removed {} stale file{} ({})", removed, if removed == 1 { "" } else { "s" }, utils::format_bytes(removed_size));
+}
+
+/// `reset --all`: stop the daemon, back up, delete the database(s), reset
+/// the config and remove runtime files, in that order. The daemon stops
+/// first so its last rows make it into the backup. Nothing is deleted
+/// unless both of those worked; after that every step runs even when an
+/// earlier one failed, so a rerun only has leftovers to deal with. False
+/// when anything failed.
+fn reset_all(no_backup: bool, all_profiles: bool, yes: bool) -> bool {
+    let config = Config::load().unwrap_or_default();
+    let profiles = reset::profiles(all_profiles);
+    let backup_dir = config.export_directory();
+    let profile_names: Vec<&str> = profiles.iter().map(reset::describe_profile).collect();
+    let running = reset::other_running_daemons(&profiles);
+    if !running.is_empty() {
+        eprintln!("❌ The daemon of profile {} is running; stop it first ('focusdebt --profile <name> stop'). Nothing changed.",
+            running.join(", "));
+        return false;
+    }
+
+    println!("~=~ This resets focusdebt to a fresh install:");
+    println!("  1. Stop the daemon, if it is running");
+    match (&backup_dir, no_backup) {
+        (_, true) => println!("  2. No backup (--no-backup)"),
+        (Some(dir), false) => println!("  2. Back up the config and the data of {} to {}", profile_names.join(", "), dir.display()),
+        (None, false) => println!("  2. Back up the config and the data (no export directory found, so this will fail)"),
+    }
+    if all_profiles {
+        println!("  3. Delete the database and every profile ({})", profile_names.join(", "));
+    } else {
+        println!("  3. Delete the database of profile {}", profile_names.join(", "));
+    }
+    match (&config.profile, all_profiles) {
+        (Some(profile), false) => println!("  4. Reset the config to defaults, keeping profile = \"{}\"", profile),
+        _ => println!("  4. Reset the config to defaults"),
+    }
+    println!("  5. Remove runtime files (PID, state, heartbeat, log, spill journal)");
+    let pending_rows = spill::pending_rows(&config.spill_journal());
+    if pending_rows > 0 {
+        println!("❌ {} row{} in the spill journal never reached the database and won't be in the backup",
+            pending_rows, if pending_rows == 1 { "" } else { "s" });
+    }
+
+    if !yes {
+        use std::io::IsTerminal;
+        if !io::stdin().is_terminal() {
+            eprintln!("❌ reset --all needs confirmation; pass --yes when not at a terminal");
+            return false;
+        }
+        print!("~=~ This can't be undone. Type 'reset' to go ahead: ");
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() || answer.trim() != "reset" {
+            println!("~=~ Nothing changed");
+            return true;
+        }
+    }
+
+    println!("~=~ [1/5] Stopping the daemon");
+    if is_daemon_running() {
+        match request_daemon_stop() {
+            Some(_) => println!("  Stopped after its final flush"),
+            None => {
+                eprintln!("❌ The daemon did not confirm its final flush after {}s. Nothing was deleted; \
+                    check 'focusdebt status' and run the reset again.", FLUSH_TIMEOUT_MS / 1000);
+                return false;
+            }
+        }
+    } else {
+        println!("  Not running");
+    }
+
+    println!("~=~ [2/5] Backing up");
+    let backup = if no_backup {
+        println!("  Skipped (--no-backup)");
+        None
+    } else {
+        let written = backup_dir
+            .ok_or_else(|| "no export directory; set export_dir".to_string())
+            .and_then(|dir| reset::write_backup(&dir, &config, &profiles, chrono::Utc::now()));
+        match written {
+            Ok(path) => {
+                println!("  Written to {}", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("❌ Backup failed ({}). Nothing was deleted; fix that or pass --no-backup.", e);
+                return false;
+            }
+        }
+    };
+
+    let mut failed_steps = 0;
+    println!("~=~ [3/5] Deleting the database{}", if all_profiles { "s and profiles" } else { "" });
+    let (removed, failed) = reset::remove_databases(&profiles, all_profiles);
+    for path in &removed {
+        println!("  Removed {}", path.display());
+    }
+    if removed.is_empty() && failed.is_empty() {
+        println!("  Nothing to delete");
+    }
+    for failure in &failed {
+        eprintln!("❌ Failed to remove {}", failure);
+    }
+    failed_steps += usize::from(!failed.is_empty());
+
+    println!("~=~ [4/5] Resetting the config");
+    let mut fresh = Config::default();
+    if !all_profiles {
+        fresh.profile = config.profile.clone();
+    }
+    match fresh.save() {
+        Ok(()) => println!("  Reset to defaults"),
+        Err(e) => {
+            eprintln!("❌ Failed to save the config: {}", e);
+            failed_steps += 1;
+        }
+    }
+
+    println!("~=~ [5/5] Removing runtime files");
+    let files = utils::runtime_files::scan(&config.spill_journal(), spill::JOURNAL_ROTATIONS);
+    let (remove, in_use) = reset::runtime_files_to_remove(files, all_profiles);
+    let mut runtime_failed = false;
+    for file in &remove {
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => println!("  Removed {} ({})", file.path.display(), file.kind.label()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                eprintln!("❌ Failed to remove {}: {}", file.path.display(), e);
+                runtime_failed = true;
+            }
+        }
+    }
+    for file in &in_use {
+        println!("  Kept {} ({}), its process is still running", file.path.display(), file.kind.label());
+    }
+    if remove.is_empty() && in_use.is_empty() {
+        println!("  Nothing to remove");
+    }
+    failed_steps += usize::from(runtime_failed);
+
+    let backup_note = match &backup {
+        Some(path) => format!("; the backup at {} has everything", path.display()),
+        None => String::new(),
+    };
+    if failed_steps == 0 {
+        println!("~=~ Reset complete{}", backup_note);
+    } else {
+        eprintln!("❌ {} step{} failed{}. Fix the problems above and run 'focusdebt reset --all --no-backup' to finish.",
+            failed_steps, if failed_steps == 1 { "" } else { "s" }, backup_note);
+    }
+    failed_steps == 0
+}
+
+fn show_session_summary(settings: &StatsSettings) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // Get the most recent session name
+    let session_name = match db.get_most_recent_session_name() {
+        Ok(Some(name)) => name,
+        Ok(None) => {
+            eprintln!("❌ No recent session found");
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to get session name: {}", e);
+            return;
+        }
+    };
+
+    // Calculate stats for the specific session
+    match Stats::calculate_session_stats(&db, settings, &session_name) {
+        Ok(session_stats) => Stats::display_session_summary(&session_stats, settings),
+        Err(e) => eprintln!("❌ Failed to calculate session stats: {}", e),
+    }
+}
+
+/// `stats --current`: the running session so far, including what the
+/// daemon hasn't saved yet
+fn show_current_session(settings: &StatsSettings) {
+    if !is_daemon_running() {
+        eprintln!("❌ No session is running. Start one with 'focusdebt start'");
+        return;
+    }
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // A daemon started by an older version leaves no state; its session is
+    // the most recent one
+    let session_name = match utils::read_daemon_state() {
+        Some(state) => state.session_name,
+        None => match db.get_most_recent_session_name() {
+            Ok(Some(name)) => name,
+            Ok(None) => {
+                eprintln!("❌ Nothing recorded for the running session yet");
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to get session name: {}", e);
+                return;
+            }
+        },
+    };
+
+    match Stats::calculate_session_progress(&db, settings, &session_name, chrono::Utc::now()) {
+        Ok(progress) => Stats::display_session_progress(&progress, settings),
+        Err(e) => eprintln!("❌ Failed to calculate session stats: {}", e),
+    }
+}
+
+/// Days as the config cuts them: from day_start_hour in the resolved zone
+fn local_days() -> utils::LocalDays {
+    utils::LocalDays::new(Config::load().unwrap_or_default().day_start_hour)
+}
+
+fn show_daily_stats(settings: &StatsSettings, json: bool, by_group: bool) {
+    if by_group && Config::load().unwrap_or_default().groups.is_empty() {
+        println!("~=~ No app groups configured, e.g. 'focusdebt config set groups.rust-toolchain code,alacritty,zellij'");
+    }
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            if json || !show_cached_daily_stats(settings, &e, by_group) {
+                eprintln!("❌ Failed to open database: {}", e);
+            }
+            return;
+        }
+    };
+
+    // Calculate stats for today
+    let config = Config::load().unwrap_or_default();
+    let today = chrono::Utc::now();
+    let days = utils::LocalDays::new(config.day_start_hour);
+    match stats::Stats::calculate_daily_stats(&db, settings, &days, days.day_of(today)) {
+        Ok(mut daily_stats) => {
+            daily_stats.focus_score_7d_avg = Stats::focus_score_trend(&db, settings, &days, days.day_of(today)).unwrap_or(None);
+            let shown = if by_group { daily_stats.by_group(settings) } else { daily_stats.clone() };
+            if json {
+                match Stats::daily_stats_json(&shown) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => eprintln!("❌ Failed to serialize daily stats: {}", e),
+                }
+                return;
+            }
+            stats::Stats::display_daily_stats(&shown, settings);
+            // The cache stands in for a full day, not a filtered one
+            if !utils::is_data_dir_read_only() && daily_stats.power_filter.is_none() {
+                if let Err(e) = Stats::save_daily_stats_cache(&daily_stats) {
+                    eprintln!("❌ Failed to cache daily stats: {}", e);
+                }
+            }
+        }
+        Err(e) => {
+            if json || !show_cached_daily_stats(settings, &e, by_group) {
+                eprintln!("❌ Failed to calculate daily stats: {}", e);
+            }
+            return;
+        }
+    }
+
+    let budgets = config.budget_rules();
+    if !budgets.is_empty() {
+        match Stats::calculate_budget_usage(&db, settings, &budgets, today, config.day_start_hour, None) {
+            Ok(usage) => Stats::display_budgets(&usage, today),
+            Err(e) => eprintln!("❌ Failed to calculate budget usage: {}", e),
+        }
+    }
+
+    if config.efficiency_alerts {
+        match Stats::calculate_efficiency_trend(&db, settings, today, config.day_start_hour) {
+            Ok(Some(trend)) if trend.is_below(config.efficiency_floor) => {
+                Stats::display_efficiency_warning(&trend, config.efficiency_floor);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("❌ Failed to calculate efficiency trend: {}", e),
+        }
+    }
+}
+
+/// Show the last cached daily stats when the database can't be read.
+/// Returns false if there is nothing cached.
+fn show_switch_heatmap(weeks: u32, json: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let heatmap = match Stats::calculate_switch_heatmap(&db, weeks, chrono::Utc::now()) {
+        Ok(heatmap) => heatmap,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate switch heatmap: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match Stats::switch_heatmap_json(&heatmap) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to serialize switch heatmap: {}", e),
+        }
+    } else {
+        print!("{}", Stats::render_switch_heatmap(&heatmap));
+    }
+}
+
+fn show_workspace_usage(settings: &StatsSettings, days: u32, json: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let report = match Stats::calculate_workspace_usage(&db, settings, days, chrono::Utc::now()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate workspace usage: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match Stats::workspace_usage_json(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to serialize workspace usage: {}", e),
+        }
+    } else {
+        print!("{}", Stats::render_workspace_usage(&report));
+    }
+}
+
+fn show_task_stats(settings: &StatsSettings, task: &str, json: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // Taskwarrior's ids move as tasks are done; its uuid for the id now
+    // tells sessions on an earlier task with the same id apart
+    let uuid = tasks::is_taskwarrior_id(task)
+        .then(|| tasks::lookup(&tasks::TaskCommand, task.trim()).ok())
+        .flatten()
+        .map(|(uuid, _)| uuid);
+    let report = match Stats::calculate_task_stats(&db, settings, task, uuid.as_deref()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate task stats: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match Stats::task_stats_json(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to serialize task stats: {}", e),
+        }
+    } else {
+        print!("{}", Stats::render_task_stats(&report));
+    }
+}
+
+fn show_focus_score(settings: &StatsSettings) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let local_days = local_days();
+    let today = local_days.day_of(chrono::Utc::now());
+    // Far enough back for the baseline of today's day type
+    let first_day = today - chrono::Duration::days(stats::BASELINE_LOOKBACK_DAYS as i64);
+    let days = match Stats::calculate_daily_stats_range(&db, settings, &local_days, first_day, stats::BASELINE_LOOKBACK_DAYS + 1, &mut progress::Progress::hidden()) {
+        Ok(days) => days,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate focus score: {}", e);
+            return;
+        }
+    };
+    let Some((today_stats, earlier)) = days.split_last() else { return };
+    let history = &earlier[earlier.len().saturating_sub(7)..];
+
+    let baseline = settings.baseline_days(today);
+    let scores: Vec<u32> = earlier.iter()
+        .filter(|day| baseline.contains(&day.date.date_naive()))
+        .filter_map(|day| day.focus_score)
+        .collect();
+    let average = (!scores.is_empty()).then(|| scores.iter().sum::<u32>() as f64 / scores.len() as f64);
+    let Some(score) = today_stats.focus_score else {
+        println!("~=~ Nothing tracked today yet, so there is no focus score");
+        return;
+    };
+
+    let config = Config::load().unwrap_or_default();
+    let tracked = today_stats.total_focus_time + today_stats.total_distraction_time;
+    let switches_per_hour = today_stats.context_switches as f64 / (tracked.as_secs_f64() / 3600.0).max(f64::EPSILON);
+    let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+    println!("\n{}\n", top_sep);
+    println!("FOCUS SCORE       : {}\n", stats::describe_focus_score(score, average, settings.day_type(today)));
+    println!("Efficiency        : {:.0}% (weight {})", today_stats.focus_efficiency, config.score.efficiency_weight);
+    println!("Switches          : {:.1}/h, half credit at {}/h (weight {})",
+        switches_per_hour, config.score.switch_rate_reference, config.score.switch_weight);
+    println!("Longest Block     : {} of {} target (weight {})\n",
+        utils::format_duration(today_stats.longest_focus_block, DurationStyle::Long),
+        utils::format_duration(std::time::Duration::from_secs(config.score.target_block_minutes * 60), DurationStyle::Long),
+        config.score.block_weight);
+    println!("LAST 7 DAYS\n");
+    for day in history {
+        let score = day.focus_score.map(|score| score.to_string()).unwrap_or_else(|| "--".to_string());
+        println!("  {} {:<12} {:>3}", day.date.format("%a"), utils::format_date_local(day.date.date_naive()), score);
+    }
+    println!("\n{}\n", top_sep);
+}
+
+fn show_estimate(settings: &StatsSettings, goal_minutes: Option<u64>, json: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let config = Config::load().unwrap_or_default();
+    let goal_minutes = goal_minutes.unwrap_or(config.daily_focus_goal_minutes);
+    let days = utils::LocalDays::new(config.day_start_hour);
+    let estimate = match estimate::collect(&db, settings, &days, chrono::Utc::now(), std::time::Duration::from_secs(goal_minutes * 60)) {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            eprintln!("❌ Failed to estimate today's focus: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match estimate::to_json(&estimate) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to serialize the estimate: {}", e),
+        }
+    } else {
+        print!("{}", estimate::render(&estimate));
+    }
+}
+
+/// What the running daemon hasn't saved yet, for `stats` and `sessions` to
+/// include. Without a daemon or a fresh snapshot they read the database only.
+fn live_rows() -> Option<(Vec<tracking::FocusSession>, Vec<tracking::ContextSwitch>)> {
+    tracking::read_live_snapshot().map(|snapshot| (snapshot.rows(), snapshot.switches))
+}
+
+fn show_cached_daily_stats(settings: &StatsSettings, error: &dyn std::fmt::Display, by_group: bool) -> bool {
+    if settings.power_filter.is_some() {
+        return false;
+    }
+    let Some(cached) = Stats::load_daily_stats_cache() else {
+        return false;
+    };
+    println!("~=~ Database unavailable ({})", error);
+    println!("~=~ Showing cached data from {}", cached.describe_saved_at());
+    Stats::display_daily_stats(&if by_group { cached.stats.by_group(settings) } else { cached.stats }, settings);
+    true
+}
+
+fn generate_share_report(settings: &StatsSettings, with_history: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // Get the most recent session name
+    let session_name = match db.get_most_recent_session_name() {
+        Ok(Some(name)) => name,
+        Ok(None) => {
+            eprintln!("❌ No recent session found");
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to get session name: {}", e);
+            return;
+        }
+    };
+
+    // Calculate stats for the specific session
+    match Stats::calculate_session_stats(&db, settings, &session_name) {
+        Ok(session_stats) => {
+            let mut report = Stats::generate_session_share_report(&session_stats, settings);
+            if with_history {
+                match Stats::calculate_session_history(&db, &local_days(), &session_name, chrono::Utc::now()) {
+                    Ok(history) => report.push_str(&Stats::generate_history_footer(&history)),
+                    Err(e) => eprintln!("❌ Failed to calculate session history: {}", e),
+                }
+            }
+            println!("{}", report);
+        }
+        Err(e) => eprintln!("❌ Failed to generate report: {}", e),
+    }
+}
+
+/// `share --format oneline|tweet`: the latest session in one line, for chat
+/// or a post
+fn share_short(settings: &StatsSettings, format: &str) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let session_name = match db.get_most_recent_session_name() {
+        Ok(Some(name)) => name,
+        Ok(None) => {
+            eprintln!("❌ No recent session found");
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to get session name: {}", e);
+            return;
+        }
+    };
+    match Stats::calculate_session_stats(&db, settings, &session_name) {
+        Ok(session) if format == "tweet" => println!("{}", Stats::share_tweet(&session)),
+        Ok(session) => println!("{}", Stats::share_oneline(&session)),
+        Err(e) => eprintln!("❌ Failed to generate report: {}", e),
+    }
+}
+
+/// `share --format badge`: today's focus time as an SVG badge for a README
+fn write_share_badge(settings: &StatsSettings, path: &str) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let days = local_days();
+    let today = match Stats::calculate_daily_stats(&db, settings, &days, days.day_of(chrono::Utc::now())) {
+        Ok(today) => today,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate daily stats: {}", e);
+            return;
+        }
+    };
+    match std::fs::write(path, Stats::share_badge_svg(&today)) {
+        Ok(()) => println!("~=~ Badge written to {}", path),
+        Err(e) => eprintln!("❌ Failed to write {}: {}", path, e),
+    }
+}
+
+/// Focus apps the daemon classifies with: the configured list, or a set of
+/// common editors and browsers when it is empty
+/// A running daemon re-reads the focus lists at its next save
+fn note_daemon_pickup(config: &Config) {
+    if is_daemon_running() {
+        println!("~=~ The running daemon will pick this up within {} seconds", config.save_interval_ms.div_ceil(1000));
+    }
+}
+
+fn add_focus_app_fuzzy(input: &str, allow_literal: bool) {
+    let mut config = Config::load().unwrap_or_default();
+    let running_apps = utils::get_running_apps();
+    if running_apps.is_empty() {
+        // Headless or over SSH there are no windows to match against
+        println!("~=~ No running GUI apps detected, so '{}' can't be matched against open windows", input);
+        let sightings = Database::new_read_only()
+            .and_then(|db| db.get_app_sightings())
+            .unwrap_or_default();
+        let Some(app_to_add) = match_tracked_app(input, allow_literal, sightings) else { return };
+        config.add_focus_app(app_to_add.clone());
+        config.save().ok();
+        println!("~=~ Added app: {}", app_to_add);
+        note_daemon_pickup(&config);
+        return;
+    }
+    // One candidate per process, known by its process and friendly names
+    let mut processes: Vec<String> = running_apps.iter().map(|(_, process)| process.clone()).collect();
+    processes.sort();
+    processes.dedup();
+    let names = |process: &String| {
+        let mut names: Vec<String> = running_apps.iter()
+            .filter(|(_, running)| running == process)
+            .map(|(friendly, _)| friendly.clone())
+            .collect();
+        names.push(process.clone());
+        names
+    };
+    let app_to_add = match fuzzy::best(input, &processes, names) {
+        FuzzyMatch::Unique(ranked) => ranked.item,
+        FuzzyMatch::Ambiguous(candidates) => {
+            eprintln!("❌ '{}' could be {}; use a more specific name", input,
+                fuzzy::describe_candidates(&candidates, |process| process.clone()));
+            return;
+        }
+        FuzzyMatch::None => {
+            println!("~=~ No running app matches '{}', adding it as typed", input);
+            input.to_string()
+        }
+    };
+    config.add_focus_app(app_to_add.clone());
+    config.save().ok();
+    println!("~=~ Added app: {}", app_to_add);
+    note_daemon_pickup(&config);
+}
+
+/// The app `input` means among those tracked before (`sightings`), most
+/// recently seen first; else `input` itself when it looks like a process
+/// name or `allow_literal` is set. None, having said why, when it can't be added.
+fn match_tracked_app(input: &str, allow_literal: bool, mut sightings: Vec<storage::AppSighting>) -> Option<String> {
+    sightings.sort_by_key(|sighting| std::cmp::Reverse(sighting.last_seen));
+    let tracked: Vec<String> = sightings.iter().map(|sighting| sighting.app_name.clone()).collect();
+    match fuzzy::best(input, &tracked, |app| vec![app.clone()]) {
+        FuzzyMatch::Unique(ranked) => {
+            if let Some(sighting) = sightings.iter().find(|sighting| sighting.app_name == ranked.item) {
+                println!("~=~ Matched '{}' from tracked history (last seen {})", ranked.item,
+                    utils::format_date_local(utils::timezone::to_zone(sighting.last_seen).date_naive()));
+            }
+            return Some(ranked.item);
+        }
+        FuzzyMatch::Ambiguous(candidates) => {
+            eprintln!("❌ '{}' could be {} from tracked history; use a more specific name", input,
+                fuzzy::describe_candidates(&candidates, |app| app.clone()));
+            return None;
+        }
+        FuzzyMatch::None => {}
+    }
+    if !allow_literal && !utils::looks_like_process_name(input) {
+        eprintln!("❌ '{}' matches no tracked app and doesn't look like a process name (lower case, no spaces)", input);
+        eprintln!("   Check the name with 'ps' or 'focusdebt focusapp audit', or pass --allow-literal to add it as typed");
+        return None;
+    }
+    println!("~=~ No tracked app matches '{}' either, adding it as typed", input);
+    Some(input.to_string())
+}
+
+fn suggest_focus_apps() {
+    let config = Config::load().unwrap_or_default();
+    let mut running_apps = utils::get_running_apps();
+    // Never suggest apps the user has explicitly ignored
+    running_apps.retain(|(friendly, process)| !config.is_ignored_app(process) && !config.is_ignored_app(friendly));
+    if running_apps.is_empty() {
+        println!("No running GUI applications detected.");
+        return;
+    }
+    // Sort and deduplicate by friendly name
+    running_apps.sort_by(|a, b| a.0.cmp(&b.0));
+    running_apps.dedup_by(|a, b| a.0 == b.0);
+    println!("Currently running GUI applications:");
+    for (i, (friendly, process)) in running_apps.iter().enumerate() {
+        let tracked = config.is_focus_app(process)
+            || desktop::resolve(process).is_some_and(|identity| config.is_focus_app(&identity.key));
+        if tracked {
+            println!("{}. {} ({}) - already tracked", i + 1, friendly, process);
+        } else {
+            println!("{}. {} ({})", i + 1, friendly, process);
+        }
+    }
+    println!("Use 'focusdebt focusapp add \"Your App Name\"' to add by fuzzy match.");
+}
+
+/// The rule a typed site stands for. Bare input gets the mode it most
+/// likely means; prefixes are kept as given.
+fn site_entry(input: &str) -> Result<utils::SiteRule, String> {
+    let entry = if utils::has_site_prefix(input) {
+        input.trim().to_string()
+    } else {
+        utils::migrate_bare_site(input).0
+    };
+    utils::SiteRule::parse(&entry).map_err(|e| format!("Invalid site entry: {}", e))
+}
+
+/// The entry of `sites` that `input` names, as listed or as it was
+/// originally typed; `input` itself when none does
+fn listed_site(sites: &[String], input: &str) -> String {
+    sites.iter()
+        .find(|site| site.as_str() == input.trim() || utils::SiteRule::parse(site).is_ok_and(|rule| rule.value.eq_ignore_ascii_case(input.trim())))
+        .cloned()
+        .unwrap_or_else(|| input.to_string())
+}
+
+fn add_focus_site_fuzzy(input: &str) {
+    let rule = match site_entry(input) {
+        Ok(rule) => rule,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let mut config = Config::load().unwrap_or_default();
+    config.add_focus_site(rule.to_entry());
+    config.save().ok();
+    println!("~=~ Added site: {} ({} match)", rule.value, rule.mode.label());
+    note_daemon_pickup(&config);
+}
+
+fn remove_focus_site(domain: &str) {
+    let mut config = Config::load().unwrap_or_default();
+    let entry = listed_site(&config.focus_sites, domain);
+    config.remove_focus_site(&entry);
+    config.save().ok();
+    // The database's list counts as well
+    if let Err(e) = Database::new().and_then(|db| db.remove_focus_site(&entry)) {
+        eprintln!("❌ Failed to remove from database: {}", e);
+    }
+    println!("~=~ Removed site: {}", entry);
+    note_daemon_pickup(&config);
+}
+
+fn list_focus_sites() {
+    let mut config = Config::load().unwrap_or_default();
+    // Including those only the database holds
+    match Database::new_read_only().and_then(|db| db.focus_lists(&[], &config.focus_sites)) {
+        Ok((_, sites)) => config.focus_sites = sites,
+        Err(e) => eprintln!("❌ Failed to read the focus lists from the database: {}", e),
+    }
+    let describe = |site: &str| match utils::SiteRule::parse(site) {
+        Ok(rule) => format!("{:<7} {}", rule.mode.label(), rule.value),
+        Err(e) => format!("invalid {} ({})", site, e),
+    };
+    if config.focus_sites.is_empty() && config.ignored_sites.is_empty() {
+        println!("No focus or distraction sites configured.");
+    } else {
+        println!("Focus Sites:");
+        for site in &config.focus_sites {
+            println!("  - {}", describe(site));
+        }
+        println!("Distraction Sites:");
+        for site in &config.ignored_sites {
+            println!("  - {}", describe(site));
+        }
+    }
+    if config.assistant_sites.is_empty() {
+        return;
+    }
+    println!("Assistant Sites (counted as {}):", config.assistant_counts_as.label());
+    for site in &config.assistant_sites {
+        println!("  - {}", describe(site));
+    }
+}
+
+fn remove_focus_app(app_name: &str) {
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    config.remove_focus_app(app_name);
+    
+    if let Err(e) = config.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+
+    // Also remove from database for backward compatibility
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = db.remove_focus_app(app_name) {
+        eprintln!("❌ Failed to remove from database: {}", e);
+    }
+
+    println!("~=~ Removed '{}' from focus apps", app_name);
+    note_daemon_pickup(&config);
+}
+
+fn list_focus_apps() {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    // Including those only the database holds
+    let apps = match Database::new_read_only().and_then(|db| db.focus_lists(&config.focus_apps, &[])) {
+        Ok((apps, _)) => apps,
+        Err(e) => {
+            eprintln!("❌ Failed to read the focus lists from the database: {}", e);
+            config.focus_apps
+        }
+    };
+    if apps.is_empty() {
+        println!("~=~ No focus apps configured");
+        println!("~=~ Use 'focusdebt focusapp add <app_name>' to add apps");
+    } else {
+        println!("~=~ Focus Apps:");
+        for (i, app) in apps.iter().enumerate() {
+            println!("  {}. {}", i + 1, app);
+        }
+    }
+}
+
+/// `focusapp audit`: each focus and ignored app entry against what was
+/// tracked, offering to remove the stale ones when run at a terminal
+/// `rules stats`: tracked time by the rule recorded on each row
+fn show_rule_stats(days: u32, json: bool) {
+    let config = Config::load().unwrap_or_default();
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // Focus apps added through the database only count as configured too
+    let focus_apps = match db.focus_lists(&config.focus_apps, &[]) {
+        Ok((apps, _)) => apps,
+        Err(e) => {
+            eprintln!("❌ Failed to read the focus lists from the database: {}", e);
+            return;
+        }
+    };
+    let report = match Stats::calculate_rule_stats(&db, &config, &focus_apps, days, chrono::Utc::now()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("❌ Failed to total time by rule: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to encode the totals: {}", e),
+        }
+    } else {
+        print!("{}", Stats::render_rule_stats(&report));
+    }
+}
+
+fn audit_focus_apps(stale_days: i64, json: bool) {
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // Focus apps added through the database only count as configured too
+    let focus_apps = match db.focus_lists(&config.focus_apps, &[]) {
+        Ok((apps, _)) => apps,
+        Err(e) => {
+            eprintln!("❌ Failed to read the focus lists from the database: {}", e);
+            return;
+        }
+    };
+    let sightings = match db.get_app_sightings() {
+        Ok(sightings) => sightings,
+        Err(e) => {
+            eprintln!("❌ Failed to read tracked apps: {}", e);
+            return;
+        }
+    };
+    let audit = stats::audit_apps(&focus_apps, &config.ignored_apps, &sightings, chrono::Utc::now(), stale_days);
+
+    if json {
+        match serde_json::to_string_pretty(&audit) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to encode the audit: {}", e),
+        }
+        return;
+    }
+    if audit.is_empty() {
+        println!("~=~ No focus or ignored apps configured");
+        return;
+    }
+
+    println!("~=~ App audit (flagged: never seen or unseen for {}+ days):", stale_days);
+    let width = audit.iter().map(|entry| entry.app.chars().count()).max().unwrap_or(0);
+    for entry in &audit {
+        let seen = match entry.first_seen {
+            Some(first) => format!("{}, first seen {}", entry.describe_seen(),
+                utils::format_date_local(utils::timezone::to_zone(first).date_naive())),
+            None => entry.describe_seen(),
+        };
+        println!("  {} {:<width$}  {:<7}  {:>9}  {}",
+            if entry.stale { "!" } else { " " },
+            entry.app,
+            entry.list.label(),
+            utils::format_duration(std::time::Duration::from_secs(entry.total_seconds), DurationStyle::Long),
+            seen);
+    }
+
+    let flagged: Vec<&stats::AppAudit> = audit.iter().filter(|entry| entry.stale).collect();
+    if flagged.is_empty() {
+        println!("~=~ Every entry was seen in the last {} days", stale_days);
+        return;
+    }
+    use std::io::IsTerminal;
+    if !io::stdin().is_terminal() {
+        println!("~=~ {} flagged; run this at a terminal to remove them", flagged.len());
+        return;
+    }
+
+    println!();
+    println!("~=~ Flagged entries:");
+    for (i, entry) in flagged.iter().enumerate() {
+        println!("  {}. {} ({}, {})", i + 1, entry.app, entry.list.label(), entry.describe_seen());
+    }
+    let picked = loop {
+        print!("~=~ Remove which? (e.g. 1,3 or 2-4, 'all', Enter for none): ");
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            println!();
+            return;
+        }
+        match utils::parse_selection(&input, flagged.len()) {
+            Ok(picked) => break picked,
+            Err(e) => println!("❌ {}", e),
+        }
+    };
+    if picked.is_empty() {
+        println!("~=~ Nothing removed");
+        return;
+    }
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    for entry in picked.iter().map(|&i| flagged[i]) {
+        match entry.list {
+            stats::AppList::Focus => {
+                config.remove_focus_app(&entry.app);
+                if let Err(e) = db.remove_focus_app(&entry.app) {
+                    eprintln!("❌ Failed to remove '{}' from the database: {}", entry.app, e);
+                }
+            }
+            stats::AppList::Ignored => config.remove_ignored_app(&entry.app),
+        }
+    }
+    if let Err(e) = config.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+    let names: Vec<&str> = picked.iter().map(|&i| flagged[i].app.as_str()).collect();
+    println!("~=~ Removed {}", names.join(", "));
+    note_daemon_pickup(&config);
+}
+
+fn show_config() {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    println!("~=~ Current Configuration:");
+    println!("  Profile: {}", utils::active_profile().unwrap_or("default"));
+    println!("  Tracking Interval: {}ms", config.tracking_interval_ms);
+    println!("  Save Interval: {}ms (early at {} buffered rows)", config.save_interval_ms, config.max_buffered_rows);
+    match &config.backend {
+        Some(backend) if config.detection_fallback => println!("  Backend: {} (fails over when it keeps failing)", backend),
+        Some(backend) => println!("  Backend: {} (pinned)", backend),
+        None => println!("  Backend: automatic"),
+    }
+    if config.allow_process_scan_fallback {
+        println!("  Process Scan Fallback: on (guesses the window from running apps)");
+    }
+    println!("  Deep Focus Threshold: {} minutes", config.deep_focus_threshold_minutes);
+    println!("  Noise Thresholds: record {}s, display {}s", config.min_record_seconds, config.min_display_seconds);
+    println!("  Round Trip Window: {}s", config.round_trip_window_seconds);
+    println!("  Co-focus Window: {}m", config.co_focus_window_minutes);
+    match config.merge_gap_seconds {
+        0 => println!("  Merge Gaps: off"),
+        seconds => println!("  Merge Gaps: under {}s between rows of one window", seconds),
+    }
+    println!("  Switch Cost: {} minutes when unmeasured ({})", config.switch_cost_minutes,
+        if config.switch_cost_unmeasured { "counted" } else { "left out" });
+    println!("  Efficiency Floor: {:.0}% (alerts {})", config.efficiency_floor,
+        if config.efficiency_alerts { "on" } else { "off" });
+    println!("  Daily Focus Goal: {} minutes", config.daily_focus_goal_minutes);
+    println!("  Weekends in Averages: {}", if config.exclude_weekends_from_averages { "excluded" } else { "included" });
+    if config.holidays.is_empty() {
+        println!("  Holidays: none");
+    } else {
+        println!("  Holidays: {}", config.holidays.join(", "));
+    }
+    println!("  Ignore Own Windows: {}", if config.self_ignore { "on" } else { "off" });
+    match config.max_title_length {
+        0 => println!("  Window Titles: kept whole"),
+        length => println!("  Window Titles: cut at {} characters", length),
+    }
+    match config.max_session_name_length {
+        0 => println!("  Session Names: kept whole"),
+        length => println!("  Session Names: cut at {} characters", length),
+    }
+    println!("  Browser Tab Tracking: {}", config.browser_tab_tracking.label());
+    if config.apply_sites_to_apps {
+        if config.site_apps.is_empty() {
+            println!("  Site Rules for Apps: on, but site_apps is empty");
+        } else {
+            println!("  Site Rules for Apps: {}", config.site_apps.join(", "));
+        }
+    }
+    if config.assistant_sites.is_empty() {
+        println!("  Assistant Sites: off");
+    } else {
+        println!("  Assistant Sites: {} (counted as {})", config.assistant_sites.join(", "), config.assistant_counts_as.label());
+    }
+    println!("  Input Activity Tracking: {}", if config.track_input_activity { "on" } else { "off" });
+    println!("  tmux Integration: {}", if config.tmux_integration { "on" } else { "off" });
+    match (&config.notification_command, config.count_notifications) {
+        (_, false) => println!("  Notification Counting: off"),
+        (Some(command), true) => println!("  Notification Counting: on (from '{}')", command),
+        (None, true) => println!("  Notification Counting: on (session D-Bus)"),
+    }
+    println!("  Taskwarrior Integration: {}", if config.taskwarrior_integration { "on" } else { "off" });
+    println!("  todo.txt File: {}", tasks::todo_txt_path(&config).map_or("none found".to_string(), |path| path.display().to_string()));
+    if config.track_input_activity {
+        if config.passive_after_minutes == 0 {
+            println!("  Passive Time: off");
+        } else {
+            println!("  Passive Time: after {} minutes below {:.0}% activity",
+                config.passive_after_minutes, config.passive_activity_level * 100.0);
+        }
+    }
+    println!("  Duplicate Session Names: {}",
+        if config.allow_duplicate_session_names { "allowed" } else { "rejected" });
+    println!("  Automatic Daily Session: {}", if config.auto_daily_session { "on" } else { "off" });
+    if !config.quiet_hours.is_empty() {
+        println!("  Quiet Hours: {}", config.quiet_hours.join(", "));
+    }
+    if let Some(path) = &config.database_key_file {
+        println!("  Database Key File: {}", path);
+    }
+    match config.retention_days {
+        Some(days) => println!("  Retention: {} days (pruned daily by the daemon)", days),
+        None => println!("  Retention: keep everything"),
+    }
+    match config.max_clock_skew_days {
+        0 => println!("  Clock Skew Check: off"),
+        days => println!("  Clock Skew Check: quarantine sessions over {} days from the last seen time", days),
+    }
+    match config.disk_warning_days {
+        0 => println!("  Disk Warning: off"),
+        days => println!("  Disk Warning: when the disk fills up within {} days", days),
+    }
+    println!("  Max Open Row: {} hours", config.max_session_hours);
+    if let Some(dir) = config.export_directory() {
+        println!("  Export Directory: {}", dir.display());
+    }
+    println!("  Spill Journal: {}", config.spill_journal().display());
+    match config.max_spill_journal_mb {
+        0 => println!("  Spill Journal Cap: none"),
+        mb => println!("  Spill Journal Cap: {} MB, {} rotated parts kept", mb, spill::JOURNAL_ROTATIONS),
+    }
+    match config.cpu_warning_percent {
+        0 => println!("  CPU Warning: off"),
+        percent => println!("  CPU Warning: above {}% for {} samples in a row", percent, usage::WARN_AFTER),
+    }
+    println!("  Mail Command: {}", config.mail_command);
+    println!("  Time Format: {}, dates as '{}' (UTC offset {})", config.time_format.label(), config.date_format,
+        if config.show_utc_offset { "shown" } else { "hidden" });
+    println!("  ASCII Art: {}", if config.show_ascii_art { "shown" } else { "hidden" });
+    match &config.timezone {
+        Some(timezone) => println!("  Timezone: {}", timezone),
+        None => println!("  Timezone: system"),
+    }
+    match config.report_width {
+        Some(width) => println!("  Report Width: {} columns", width),
+        None => println!("  Report Width: auto (terminal width)"),
+    }
+    match config.display_glyphs {
+        config::DisplayGlyphs::Auto => println!("  Display Glyphs: auto (using {})", glyphs::current().label()),
+        glyphs => println!("  Display Glyphs: {}", glyphs.label()),
+    }
+    println!("  Fuzzy Matching: score {} or more, ambiguous within {}", config.fuzzy_min_score, config.fuzzy_ambiguity_margin);
+
+    let warnings = config.lint();
+    if !warnings.is_empty() {
+        println!("~=~ Settings Warnings:");
+        for warning in warnings {
+            println!("  {}: {}", warning.rule, warning);
+        }
+    }
+    
+    if !config.focus_apps.is_empty() {
+        println!("~=~ Focus Apps: {}", config.focus_apps.join(", "));
+    }
+    
+    if !config.ignored_apps.is_empty() {
+        println!("~=~ Ignored Apps: {}", config.ignored_apps.join(", "));
+    }
+    
+    if !config.focus_sites.is_empty() {
+        println!("~=~ Focus Sites: {}", config.focus_sites.join(", "));
+    }
+    
+    if !config.ignored_sites.is_empty() {
+        println!("~=~ Ignored Sites: {}", config.ignored_sites.join(", "));
+    }
+    
+    if !config.private_title_patterns.is_empty() {
+        println!("~=~ Private Title Patterns: {}", config.private_title_patterns.join(", "));
+    }
+    
+    if !config.pip_titles.is_empty() {
+        println!("~=~ Picture-in-Picture Titles: {}", config.pip_titles.join(", "));
+    }
+    
+    if !config.title_noise_markers.is_empty() {
+        println!("~=~ Title Noise Markers: {}", config.title_noise_markers.join("  "));
+    }
+    
+    println!("  Focus Score: efficiency ×{}, switches ×{} (half at {}/h), longest block ×{} (full at {}m)",
+        config.score.efficiency_weight, config.score.switch_weight, config.score.switch_rate_reference,
+        config.score.block_weight, config.score.target_block_minutes);
+    
+    if config.sounds.enabled {
+        let sound = |file: &Option<String>| file.clone().unwrap_or_else(|| "beep".to_string());
+        println!("  Sounds: every {}m of focus ({}), after {}m on a distraction ({})",
+            config.sounds.focus_minutes, sound(&config.sounds.focus_sound),
+            config.sounds.distraction_minutes, sound(&config.sounds.distraction_sound));
+    } else {
+        println!("  Sounds: off");
+    }
+    
+    if !config.budgets.is_empty() {
+        println!("~=~ Budgets (days start at {:02}:00, weeks on Monday):", config.day_start_hour);
+        for (target, entry) in &config.budgets {
+            match entry.budget(target) {
+                Ok(_) => println!("  {}: {}", target, entry),
+                Err(e) => println!("  {}: {} ({}, ignored)", target, entry, e),
+            }
+        }
+    }
+    
+    if !config.groups.is_empty() {
+        println!("~=~ App Groups:");
+        for (group, apps) in &config.groups {
+            println!("  {}: {}", group, apps.join(", "));
+        }
+        if let Err(e) = config.app_groups() {
+            println!("  ({}, ignored)", e);
+        }
+    }
+
+    if !config.co_focus.is_empty() {
+        println!("~=~ Co-focus:");
+        for rule in &config.co_focus {
+            println!("  {} = {} ({}) + {} ({})", rule.label, rule.first, rule.first_apps.join(", "), rule.second, rule.second_apps.join(", "));
+        }
+        if let Err(e) = config.co_focus_rules() {
+            println!("  ({}, ignored)", e);
+        }
+    }
+    
+    for command in &config.on_session_start {
+        println!("~=~ On Session Start: {}", command);
+    }
+    
+    for command in &config.on_session_end {
+        println!("~=~ On Session End: {}", command);
+    }
+    
+    for rule in &config.terminal_title_rules {
+        println!("~=~ Terminal Title Rule: {} => {}", rule.terminal, rule.pattern);
+    }
+}
+
+/// `config show --json`: every key's effective value and whether it comes
+/// from the defaults, config.toml or the command line
+fn show_config_json(cli_profile: Option<&str>) {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+    let overrides: Vec<(&str, serde_json::Value)> = cli_profile
+        .map(|profile| ("profile", serde_json::Value::String(profile.to_string())))
+        .into_iter()
+        .collect();
+    let values = match config.sourced_values(&overrides) {
+        Ok(values) => values,
+        Err(e) => {
+            eprintln!("❌ Failed to read config: {}", e);
+            return;
+        }
+    };
+    let document = serde_json::json!({
+        "schema_version": config::CONFIG_JSON_SCHEMA_VERSION,
+        "config": values,
+    });
+    match serde_json::to_string_pretty(&document) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("❌ Failed to serialize config: {}", e),
+    }
+}
+
+/// `config set --json`: apply every key of a JSON object, or none of them
+/// when any is invalid
+fn set_config_json(json: &str) {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+    let (config, keys) = match apply_json_batch(&config, json) {
+        Ok(applied) => applied,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("❌ {}", error);
+            }
+            return;
+        }
+    };
+
+    if let Err(e) = config.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+    println!("~=~ Configuration updated successfully ({} key{})", keys, if keys == 1 { "" } else { "s" });
+    print_config_warnings(&config);
+}
+
+/// `config` with every key of the JSON object `json` applied, and how many
+/// there were; or every problem found, the last one saying nothing changed
+fn apply_json_batch(config: &Config, json: &str) -> Result<(Config, usize), Vec<String>> {
+    let entries = match serde_json::from_str::<serde_json::Value>(json) {
+        Ok(serde_json::Value::Object(entries)) => entries,
+        Ok(_) => return Err(vec!["--json takes an object of keys and values, e.g. '{\"tracking_interval_ms\": 2000}'".to_string()]),
+        Err(e) => return Err(vec![format!("Invalid JSON: {}", e)]),
+    };
+    let mut config = config.clone();
+    let mut errors: Vec<String> = entries.iter()
+        .filter_map(|(key, value)| apply_json_value(&mut config, key, value).err())
+        .collect();
+    if !errors.is_empty() {
+        errors.push(format!("Nothing was changed: {} of {} keys are invalid", errors.len(), entries.len()));
+        return Err(errors);
+    }
+    Ok((config, entries.len()))
+}
+
+/// `config set`'s text form of a JSON value: strings as they are, numbers
+/// and booleans written out, string lists joined by commas
+fn config_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Some(value.to_string()),
+        serde_json::Value::Array(items) if items.iter().all(serde_json::Value::is_string) => {
+            Some(items.iter().filter_map(serde_json::Value::as_str).collect::<Vec<_>>().join(","))
+        }
+        _ => None,
+    }
+}
+
+/// Apply one key of `config set --json`. Values `config set` takes go
+/// through its checks; the rest (focus lists, tables, null) are swapped in
+/// through serde, and tables like budgets then get checked field by field.
+fn apply_json_value(config: &mut Config, key: &str, value: &serde_json::Value) -> Result<(), String> {
+    if let Some(text) = config_text(value) {
+        match apply_config_value(config, key, &text) {
+            Ok(()) => return Ok(()),
+            Err(ConfigValueError::Invalid(message)) => return Err(message),
+            Err(ConfigValueError::UnknownKey) => {}
+        }
+    }
+    *config = config.with_json_value(key, value)?;
+    if key == "groups" {
+        config.app_groups()?;
+    }
+    if let serde_json::Value::Object(fields) = value {
+        for (field, field_value) in fields {
+            let Some(text) = config_text(field_value) else { continue };
+            if let Err(ConfigValueError::Invalid(message)) = apply_config_value(config, &format!("{}.{}", key, field), &text) {
+                return Err(message);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Options of a budget settable as budgets.<target>.<field>
+const BUDGET_FIELDS: [&str; 4] = ["limit", "period", "carry_over", "carry_over_cap"];
+
+/// Why `config set` rejected a key and value
+enum ConfigValueError {
+    UnknownKey,
+    /// What is wrong with the value, for the user
+    Invalid(String),
+}
+
+/// Validate `value` for `key` and apply it to `config`, the way `config set`
+/// takes it from the command line
+fn apply_config_value(config: &mut Config, key: &str, value: &str) -> Result<(), ConfigValueError> {
+    match key {
+        "tracking_interval_ms" => {
+            let Ok(val) = value.parse::<u64>() else {
+                return Err(ConfigValueError::Invalid("Invalid value for tracking_interval_ms. Must be a number.".to_string()));
+            };
+            if let Err(e) = tracking::check_interval(val, config.backend.as_deref()) {
+                return Err(ConfigValueError::Invalid(format!("{}. Use {} or more.", e, tracking::MIN_SPAWNING_INTERVAL_MS)));
+            }
+            config.tracking_interval_ms = val;
+        }
+        "backend" => {
+            if matches!(value.trim(), "" | "auto" | "off") {
+                config.backend = None;
+            } else {
+                let names: Vec<&str> = tracking::platform::Backend::ALL.iter().map(|backend| backend.name()).collect();
+                if !names.contains(&value.trim()) {
+                    return Err(ConfigValueError::Invalid(format!("Unknown backend '{}'. Use auto or one of: {}", value, names.join(", "))));
+                }
+                config.backend = Some(value.trim().to_string());
+            }
+        }
+        "detection_fallback" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.detection_fallback = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for detection_fallback. Must be true or false.".to_string()));
+            }
+        }
+        "allow_process_scan_fallback" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.allow_process_scan_fallback = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for allow_process_scan_fallback. Must be true or false.".to_string()));
+            }
+        }
+        "save_interval_ms" => {
+            if let Ok(val) = value.parse::<u64>() {
+                config.save_interval_ms = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for save_interval_ms. Must be a number.".to_string()));
+            }
+        }
+        "max_buffered_rows" => {
+            match value.parse::<usize>() {
+                Ok(val) if val > 0 => config.max_buffered_rows = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for max_buffered_rows. Must be a positive number.".to_string()));
+                }
+            }
+        }
+        "deep_focus_threshold_minutes" => {
+            if let Ok(val) = value.parse::<u64>() {
+                config.deep_focus_threshold_minutes = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for deep_focus_threshold_minutes. Must be a number.".to_string()));
+            }
+        }
+        "min_record_seconds" => {
+            if let Ok(val) = value.parse::<u64>() {
+                config.min_record_seconds = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for min_record_seconds. Must be a number.".to_string()));
+            }
+        }
+        "min_display_seconds" => {
+            if let Ok(val) = value.parse::<u64>() {
+                config.min_display_seconds = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for min_display_seconds. Must be a number.".to_string()));
+            }
+        }
+        "round_trip_window_seconds" => {
+            match value.parse::<u64>() {
+                Ok(val) if val > 0 => config.round_trip_window_seconds = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for round_trip_window_seconds. Must be a positive number.".to_string()));
+                }
+            }
+        }
+        "co_focus_window_minutes" => {
+            match value.parse::<u64>() {
+                Ok(val) if val > 0 => config.co_focus_window_minutes = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for co_focus_window_minutes. Must be a positive number.".to_string()));
+                }
+            }
+        }
+        "merge_gap_seconds" => {
+            match value.parse::<u64>() {
+                Ok(val) => config.merge_gap_seconds = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for merge_gap_seconds. Must be a number of seconds (0 turns it off).".to_string()));
+                }
+            }
+        }
+        "switch_cost_minutes" => {
+            match value.parse::<u64>() {
+                Ok(val) => config.switch_cost_minutes = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for switch_cost_minutes. Must be a number of minutes.".to_string()));
+                }
+            }
+        }
+        "switch_cost_unmeasured" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.switch_cost_unmeasured = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for switch_cost_unmeasured. Must be true or false.".to_string()));
+            }
+        }
+        "efficiency_floor" => {
+            match value.trim_end_matches('%').parse::<f64>() {
+                Ok(val) if (0.0..=100.0).contains(&val) => config.efficiency_floor = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for efficiency_floor. Must be a percentage between 0 and 100.".to_string()));
+                }
+            }
+        }
+        "profile" => {
+            if value == "default" || value == "none" {
+                config.profile = None;
+            } else if utils::is_valid_profile_name(value) {
+                config.profile = Some(value.to_string());
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid profile name. Use letters, digits, '-' and '_' only (or 'default').".to_string()));
+            }
+        }
+        "day_start_hour" => {
+            match value.parse::<u32>() {
+                Ok(val) if val < 24 => config.day_start_hour = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for day_start_hour. Must be an hour between 0 and 23.".to_string()));
+                }
+            }
+        }
+        "database_key_file" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.database_key_file = None;
+            } else if std::path::Path::new(value).is_absolute() {
+                config.database_key_file = Some(value.to_string());
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for database_key_file. Must be an absolute path (or 'off').".to_string()));
+            }
+        }
+        "export_dir" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.export_dir = None;
+            } else if std::path::Path::new(value).is_absolute() {
+                config.export_dir = Some(value.to_string());
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for export_dir. Must be an absolute path (or 'off').".to_string()));
+            }
+        }
+        "spill_path" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.spill_path = None;
+            } else if std::path::Path::new(value).is_absolute() {
+                config.spill_path = Some(value.to_string());
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for spill_path. Must be an absolute path (or 'off').".to_string()));
+            }
+        }
+        "max_spill_journal_mb" => {
+            match value.parse::<u64>() {
+                Ok(val) => config.max_spill_journal_mb = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for max_spill_journal_mb. Must be a number of MB (0 = no cap).".to_string()));
+                }
+            }
+        }
+        "cpu_warning_percent" => {
+            match value.parse::<u64>() {
+                Ok(val) => config.cpu_warning_percent = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for cpu_warning_percent. Must be a percentage of one core (0 = never warn).".to_string()));
+                }
+            }
+        }
+        "mail_command" => {
+            if value.trim().is_empty() {
+                return Err(ConfigValueError::Invalid("Invalid value for mail_command. Must be a command, e.g. 'sendmail -t'.".to_string()));
+            }
+            config.mail_command = value.to_string();
+        }
+        "retention_days" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.retention_days = None;
+            } else {
+                match value.parse::<u32>() {
+                    Ok(val) if val > 0 => config.retention_days = Some(val),
+                    _ => {
+                        return Err(ConfigValueError::Invalid("Invalid value for retention_days. Must be a positive number of days (or 'off').".to_string()));
+                    }
+                }
+            }
+        }
+        "max_clock_skew_days" => {
+            match value.parse::<u32>() {
+                Ok(val) => config.max_clock_skew_days = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for max_clock_skew_days. Must be a number of days (0 turns it off).".to_string()));
+                }
+            }
+        }
+        "disk_warning_days" => {
+            match value.parse::<u32>() {
+                Ok(val) => config.disk_warning_days = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for disk_warning_days. Must be a number of days (0 turns it off).".to_string()));
+                }
+            }
+        }
+        "max_session_hours" => {
+            match value.parse::<u64>() {
+                Ok(val) if val >= 1 => config.max_session_hours = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for max_session_hours. Must be a number of hours, at least 1.".to_string()));
+                }
+            }
+        }
+        "browser_tab_tracking" => {
+            match tracking::BrowserTabTracking::parse(value) {
+                Some(mode) => config.browser_tab_tracking = mode,
+                None => {
+                    return Err(ConfigValueError::Invalid("Invalid value for browser_tab_tracking. Must be full, domain or off.".to_string()));
+                }
+            }
+        }
+        "apply_sites_to_apps" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.apply_sites_to_apps = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for apply_sites_to_apps. Must be true or false.".to_string()));
+            }
+        }
+        "site_apps" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.site_apps.clear();
+            } else {
+                config.site_apps = value.split(',').map(|app| app.trim().to_string()).filter(|app| !app.is_empty()).collect();
+            }
+        }
+        "assistant_sites" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.assistant_sites.clear();
+            } else if value.eq_ignore_ascii_case("default") {
+                config.assistant_sites = config::DEFAULT_ASSISTANT_SITES.iter().map(|site| site.to_string()).collect();
+            } else {
+                config.assistant_sites = value.split(',')
+                    .map(str::trim)
+                    .filter(|site| !site.is_empty())
+                    .map(|site| site_entry(site).map(|rule| rule.to_entry()))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| ConfigValueError::Invalid(format!("Invalid value for assistant_sites. {}", e)))?;
+            }
+        }
+        "assistant_counts_as" => {
+            match config::AssistantCountsAs::parse(value) {
+                Some(counts_as) => config.assistant_counts_as = counts_as,
+                None => {
+                    return Err(ConfigValueError::Invalid("Invalid value for assistant_counts_as. Must be focus, distraction or neutral.".to_string()));
+                }
+            }
+        }
+        "time_format" => {
+            match utils::TimeFormat::parse(value) {
+                Some(format) => config.time_format = format,
+                None => {
+                    return Err(ConfigValueError::Invalid("Invalid value for time_format. Must be 12h or 24h.".to_string()));
+                }
+            }
+        }
+        "date_format" => {
+            if let Err(e) = utils::validate_strftime(value) {
+                return Err(ConfigValueError::Invalid(format!("Invalid value for date_format: {}. Use strftime codes like %b %d or %d.%m.", e)));
+            }
+            config.date_format = value.to_string();
+        }
+        "timezone" => {
+            if value == "off" || value == "system" {
+                config.timezone = None;
+            } else {
+                match utils::timezone::parse(value) {
+                    Ok(tz) => config.timezone = Some(tz.name().to_string()),
+                    Err(e) => return Err(ConfigValueError::Invalid(format!("Invalid value for timezone: {}", e))),
+                }
+            }
+        }
+        "show_utc_offset" => {
+            match value.parse::<bool>() {
+                Ok(val) => config.show_utc_offset = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for show_utc_offset. Must be true or false.".to_string()));
+                }
+            }
+        }
+        "show_ascii_art" => {
+            match value.parse::<bool>() {
+                Ok(val) => config.show_ascii_art = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for show_ascii_art. Must be true or false.".to_string()));
+                }
+            }
+        }
+        "report_width" => {
+            if value.eq_ignore_ascii_case("auto") {
+                config.report_width = None;
+            } else {
+                match value.parse::<usize>() {
+                    Ok(val) if val >= 20 => config.report_width = Some(val),
+                    _ => {
+                        return Err(ConfigValueError::Invalid("Invalid value for report_width. Must be a number of columns (at least 20) or 'auto'.".to_string()));
+                    }
+                }
+            }
+        }
+        "display_glyphs" => {
+            match config::DisplayGlyphs::parse(value) {
+                Some(glyphs) => config.display_glyphs = glyphs,
+                None => {
+                    return Err(ConfigValueError::Invalid("Invalid value for display_glyphs. Must be auto, unicode or ascii.".to_string()));
+                }
+            }
+        }
+        "fuzzy_min_score" => {
+            match value.parse::<i64>() {
+                Ok(val) if val >= 0 => config.fuzzy_min_score = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for fuzzy_min_score. Must be a non-negative number.".to_string()));
+                }
+            }
+        }
+        "fuzzy_ambiguity_margin" => {
+            match value.parse::<i64>() {
+                Ok(val) if val >= 0 => config.fuzzy_ambiguity_margin = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for fuzzy_ambiguity_margin. Must be a non-negative number.".to_string()));
+                }
+            }
+        }
+        key if key.starts_with("budgets.") => {
+            let target = &key["budgets.".len()..];
+            if target.is_empty() {
+                return Err(ConfigValueError::Invalid("Missing budget target. Use budgets.<app or site>, e.g. budgets.slack".to_string()));
+            }
+            // budgets.<target>.<field> sets one option; targets like
+            // youtube.com keep their dots
+            let field = BUDGET_FIELDS.iter()
+                .find_map(|field| target.strip_suffix(field).and_then(|target| target.strip_suffix('.')).map(|target| (target, *field)));
+            match field {
+                None if value.eq_ignore_ascii_case("off") => {
+                    config.budgets.remove(target);
+                }
+                None | Some((_, "limit")) => {
+                    let target = field.map_or(target, |(target, _)| target);
+                    if utils::parse_duration(value).is_none() {
+                        return Err(ConfigValueError::Invalid(format!("Invalid budget '{}'. Use a duration like 30m, 1h or 1h30m (or 'off').", value)));
+                    }
+                    let mut table = config.budgets.get(target).map(|entry| entry.table()).unwrap_or_default();
+                    table.limit = value.to_string();
+                    config.budgets.insert(target.to_string(), config::BudgetEntry::from_table(table));
+                }
+                Some((target, field)) => {
+                    let Some(mut table) = config.budgets.get(target).map(|entry| entry.table()) else {
+                        return Err(ConfigValueError::Invalid(format!("No budget for '{}' yet. Set its limit first, e.g. budgets.{} 3h", target, target)));
+                    };
+                    match field {
+                        "period" => match config::BudgetPeriod::parse(value) {
+                            Some(period) => table.period = period,
+                            None => return Err(ConfigValueError::Invalid("Invalid budget period. Use 'day' or 'week'.".to_string())),
+                        },
+                        "carry_over" => match value.parse::<bool>() {
+                            Ok(carry_over) => table.carry_over = carry_over,
+                            Err(_) => return Err(ConfigValueError::Invalid("Invalid value for carry_over. Must be true or false.".to_string())),
+                        },
+                        _ if value.eq_ignore_ascii_case("off") => table.carry_over_cap = None,
+                        _ if utils::parse_duration(value).is_some() => table.carry_over_cap = Some(value.to_string()),
+                        _ => return Err(ConfigValueError::Invalid(format!("Invalid carry_over_cap '{}'. Use a duration like 1h (or 'off' for one day's limit).", value))),
+                    }
+                    let entry = config::BudgetEntry::from_table(table);
+                    if let Err(e) = entry.budget(target) {
+                        return Err(ConfigValueError::Invalid(format!("Invalid budget for '{}': {}", target, e)));
+                    }
+                    config.budgets.insert(target.to_string(), entry);
+                }
+            }
+        }
+        key if key.starts_with("groups.") => {
+            let group = &key["groups.".len()..];
+            if group.is_empty() {
+                return Err(ConfigValueError::Invalid("Missing group name. Use groups.<name>, e.g. groups.rust-toolchain".to_string()));
+            }
+            if value.eq_ignore_ascii_case("off") {
+                config.groups.remove(group);
+                return Ok(());
+            }
+            let apps: Vec<String> = value.split(',').map(|app| app.trim().to_string()).filter(|app| !app.is_empty()).collect();
+            if apps.is_empty() {
+                return Err(ConfigValueError::Invalid("List the group's apps separated by commas, e.g. code,alacritty (or 'off').".to_string()));
+            }
+            config.groups.insert(group.to_string(), apps);
+            if let Err(e) = config.app_groups() {
+                return Err(ConfigValueError::Invalid(e));
+            }
+        }
+        key if key.starts_with("score.") => {
+            let field = &key["score.".len()..];
+            if field == "target_block_minutes" {
+                match value.parse::<u64>() {
+                    Ok(val) if val > 0 => config.score.target_block_minutes = val,
+                    _ => {
+                        return Err(ConfigValueError::Invalid("Invalid value for score.target_block_minutes. Must be a positive number of minutes.".to_string()));
+                    }
+                }
+            } else {
+                let (slot, minimum) = match field {
+                    "efficiency_weight" => (&mut config.score.efficiency_weight, 0.0),
+                    "switch_weight" => (&mut config.score.switch_weight, 0.0),
+                    "block_weight" => (&mut config.score.block_weight, 0.0),
+                    "switch_rate_reference" => (&mut config.score.switch_rate_reference, f64::MIN_POSITIVE),
+                    _ => {
+                        return Err(ConfigValueError::Invalid(format!("Unknown score key: {}. Use efficiency_weight, switch_weight, block_weight, switch_rate_reference or target_block_minutes.", field)));
+                    }
+                };
+                match value.parse::<f64>() {
+                    Ok(val) if val.is_finite() && val >= minimum => *slot = val,
+                    _ => {
+                        let requirement = if minimum > 0.0 { "a positive number" } else { "zero or more" };
+                        return Err(ConfigValueError::Invalid(format!("Invalid value for {}. Must be {}.", key, requirement)));
+                    }
+                }
+            }
+        }
+        key if key.starts_with("sounds.") => {
+            match &key["sounds.".len()..] {
+                "enabled" => match value.parse::<bool>() {
+                    Ok(val) => config.sounds.enabled = val,
+                    Err(_) => return Err(ConfigValueError::Invalid("Invalid value for sounds.enabled. Must be true or false.".to_string())),
+                },
+                field @ ("focus_minutes" | "distraction_minutes") => {
+                    let slot = if field == "focus_minutes" { &mut config.sounds.focus_minutes } else { &mut config.sounds.distraction_minutes };
+                    match value.parse::<u64>() {
+                        Ok(val) if val > 0 => *slot = val,
+                        _ => return Err(ConfigValueError::Invalid(format!("Invalid value for {}. Must be a positive number of minutes.", key))),
+                    }
+                }
+                field @ ("focus_sound" | "distraction_sound") => {
+                    let sound = match value.trim() {
+                        "" | "beep" => None,
+                        path if std::path::Path::new(path).is_file() => Some(path.to_string()),
+                        path => return Err(ConfigValueError::Invalid(format!("Sound file not found: {} (or 'beep' for the built-in one)", path))),
+                    };
+                    if field == "focus_sound" { config.sounds.focus_sound = sound } else { config.sounds.distraction_sound = sound }
+                }
+                field => {
+                    return Err(ConfigValueError::Invalid(format!("Unknown sounds key: {}. Use enabled, focus_minutes, distraction_minutes, focus_sound or distraction_sound.", field)));
+                }
+            }
+        }
+        "efficiency_alerts" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.efficiency_alerts = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for efficiency_alerts. Must be true or false.".to_string()));
+            }
+        }
+        "daily_focus_goal_minutes" => {
+            match value.parse::<u64>() {
+                Ok(val) if val > 0 => config.daily_focus_goal_minutes = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for daily_focus_goal_minutes. Must be a positive number of minutes.".to_string()));
+                }
+            }
+        }
+        "exclude_weekends_from_averages" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.exclude_weekends_from_averages = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for exclude_weekends_from_averages. Must be true or false.".to_string()));
+            }
+        }
+        "holidays" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.holidays.clear();
+            } else {
+                let mut holidays = Vec::new();
+                for day in value.split(',').map(str::trim).filter(|day| !day.is_empty()) {
+                    if chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").is_err() {
+                        return Err(ConfigValueError::Invalid(format!("Invalid holiday '{}'. Use YYYY-MM-DD dates, comma-separated.", day)));
+                    }
+                    holidays.push(day.to_string());
+                }
+                holidays.sort();
+                holidays.dedup();
+                config.holidays = holidays;
+            }
+        }
+
+        "self_ignore" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.self_ignore = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for self_ignore. Must be true or false.".to_string()));
+            }
+        }
+        "max_title_length" => {
+            match value.parse::<usize>() {
+                Ok(val) if val == 0 || val >= tracking::MIN_TITLE_LENGTH => config.max_title_length = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid(format!("Invalid value for max_title_length. Must be a number of characters, at least {} (0 keeps titles whole).", tracking::MIN_TITLE_LENGTH)));
+                }
+            }
+        }
+        "max_session_name_length" => {
+            match value.parse::<usize>() {
+                Ok(val) => config.max_session_name_length = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for max_session_name_length. Must be a number of characters (0 keeps names whole).".to_string()));
+                }
+            }
+        }
+        "track_input_activity" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.track_input_activity = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for track_input_activity. Must be true or false.".to_string()));
+            }
+        }
+        "tmux_integration" => {
+            match value.parse::<bool>() {
+                Ok(val) => config.tmux_integration = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for tmux_integration. Must be true or false.".to_string()));
+                }
+            }
+        }
+        "count_notifications" => {
+            match value.parse::<bool>() {
+                Ok(val) => config.count_notifications = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for count_notifications. Must be true or false.".to_string()));
+                }
+            }
+        }
+        "notification_command" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.notification_command = None;
+            } else if value.trim().is_empty() {
+                return Err(ConfigValueError::Invalid("Invalid value for notification_command. Must be a command (or 'off').".to_string()));
+            } else {
+                config.notification_command = Some(value.trim().to_string());
+            }
+        }
+        "taskwarrior_integration" => {
+            match value.parse::<bool>() {
+                Ok(val) => config.taskwarrior_integration = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for taskwarrior_integration. Must be true or false.".to_string()));
+                }
+            }
+        }
+        "todo_txt_file" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.todo_txt_file = None;
+            } else if value.trim().is_empty() {
+                return Err(ConfigValueError::Invalid("Invalid value for todo_txt_file. Must be a path (or 'off').".to_string()));
+            } else {
+                config.todo_txt_file = Some(value.trim().to_string());
+            }
+        }
+        "passive_after_minutes" => {
+            match value.parse::<u32>() {
+                Ok(val) => config.passive_after_minutes = val,
+                Err(_) => {
+                    return Err(ConfigValueError::Invalid("Invalid value for passive_after_minutes. Must be a number of minutes (0 turns it off).".to_string()));
+                }
+            }
+        }
+        "passive_activity_level" => {
+            match value.parse::<f64>() {
+                Ok(val) if (0.0..=1.0).contains(&val) => config.passive_activity_level = val,
+                _ => {
+                    return Err(ConfigValueError::Invalid("Invalid value for passive_activity_level. Must be between 0.0 and 1.0.".to_string()));
+                }
+            }
+        }
+        "allow_duplicate_session_names" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.allow_duplicate_session_names = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for allow_duplicate_session_names. Must be true or false.".to_string()));
+            }
+        }
+        "auto_daily_session" => {
+            if let Ok(val) = value.parse::<bool>() {
+                config.auto_daily_session = val;
+            } else {
+                return Err(ConfigValueError::Invalid("Invalid value for auto_daily_session. Must be true or false.".to_string()));
+            }
+        }
+        "quiet_hours" => {
+            if value.eq_ignore_ascii_case("off") {
+                config.quiet_hours.clear();
+            } else {
+                let specs: Vec<String> = value.split(',').map(|spec| spec.trim().to_string()).filter(|spec| !spec.is_empty()).collect();
+                if let Some(e) = specs.iter().find_map(|spec| config::QuietRange::parse(spec).err()) {
+                    return Err(ConfigValueError::Invalid(format!("Invalid quiet_hours: {}. Use ranges like 22:00-07:00 or mon-fri 12:00-13:00, separated by commas (or 'off').", e)));
+                }
+                config.quiet_hours = specs;
+            }
+        }
+
+        _ => return Err(ConfigValueError::UnknownKey),
+    }
+    Ok(())
+}
+
+fn set_config(key: &str, value: &str) {
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    match apply_config_value(&mut config, key, value) {
+        Ok(()) => {}
+        Err(ConfigValueError::Invalid(message)) => {
+            eprintln!("❌ {}", message);
+            return;
+        }
+        Err(ConfigValueError::UnknownKey) => {
+            eprintln!("❌ Unknown configuration key: {}", key);
+            match fuzzy::best(key, &Config::key_names(), |name| vec![name.clone()]) {
+                FuzzyMatch::Unique(ranked) => eprintln!("~=~ Did you mean '{}'?", ranked.item),
+                FuzzyMatch::Ambiguous(candidates) => eprintln!("~=~ Did you mean {}?", fuzzy::describe_candidates(&candidates, |name| name.clone())),
+                FuzzyMatch::None => {}
+            }
+                eprintln!("~=~ Available configuration keys:");
+                eprintln!("  tracking_interval_ms - How often to check active window (in milliseconds)");
+                eprintln!("  save_interval_ms - How often to save data to database (in milliseconds)");
+                eprintln!("  backend - Window detection backend to pin, as listed by 'focusdebt debug' ('auto' picks one)");
+                eprintln!("  detection_fallback - Let a pinned backend fail over when it keeps failing (true/false)");
+                eprintln!("  allow_process_scan_fallback - Guess the window from running apps when no backend can read it (true/false)");
+                eprintln!("  max_buffered_rows - Rows held in memory before saving early");
+                eprintln!("  deep_focus_threshold_minutes - Minimum duration for deep focus sessions");
+                eprintln!("  min_record_seconds - Window visits shorter than this are not saved");
+                eprintln!("  min_display_seconds - Reports roll apps and tabs below this into one 'other' line");
+                eprintln!("  round_trip_window_seconds - A→B→A switches back within this count as a round trip");
+                eprintln!("  co_focus_window_minutes - How recently the other side of a co_focus rule must have been used");
+                eprintln!("  merge_gap_seconds - Join rows of one window separated by less than this (0 = off)");
+                eprintln!("  switch_cost_minutes - Assumed cost of a switch away from focus with no measured recovery");
+                eprintln!("  switch_cost_unmeasured - Count switches with no measured recovery in the cost estimate (true/false)");
+                eprintln!("  efficiency_floor - Warn when the 3-day average efficiency drops below this (percent)");
+                eprintln!("  efficiency_alerts - Enable efficiency floor warnings and notifications (true/false)");
+                eprintln!("  daily_focus_goal_minutes - Focus time a day aims for, used by 'focusdebt estimate'");
+                eprintln!("  exclude_weekends_from_averages - Leave weekends and holidays out of averages and the floor (true/false)");
+                eprintln!("  holidays - Non-working days, e.g. \"2024-12-25, 2024-12-26\" ('off' clears)");
+                eprintln!("  day_start_hour - Local hour at which days (stats, budgets) start (0-23)");
+                eprintln!("  profile - Profile used when --profile is not given ('default' for none)");
+                eprintln!("  allow_duplicate_session_names - Let start reuse earlier session names (true/false)");
+                eprintln!("  auto_daily_session - Name sessions by date and roll over at day_start_hour (true/false)");
+                eprintln!("  quiet_hours - Local times tracking pauses, e.g. \"22:00-07:00, sat-sun 13:00-18:00\" ('off' clears)");
+                eprintln!("  self_ignore - Don't track terminals showing focusdebt's own output (true/false)");
+                eprintln!("  max_title_length - Characters kept of each window title (default 512, 0 = no limit)");
+                eprintln!("  max_session_name_length - Characters kept of a new session name (default 100, 0 = no limit)");
+                eprintln!("  track_input_activity - Score engaged focus from keyboard/mouse idle time (true/false)");
+                eprintln!("  tmux_integration - Attribute terminal time to the active tmux pane's command (true/false)");
+                eprintln!("  count_notifications - Count desktop notifications received per session, by app (true/false)");
+                eprintln!("  notification_command - Command printing one app name per notification ('off' watches D-Bus)");
+                eprintln!("  taskwarrior_integration - Run 'task <id> start/stop' around sessions started with --task (true/false)");
+                eprintln!("  todo_txt_file - todo.txt file 'start --task' looks lines up in ('off' for $TODO_FILE or ~/todo.txt)");
+                eprintln!("  passive_after_minutes - Low-activity minutes in one window before it counts as passive (0 = off)");
+                eprintln!("  passive_activity_level - Minute activity (0.0-1.0) below which a minute counts as low");
+                eprintln!("  retention_days - Daemon deletes history older than this once a day ('off' keeps all)");
+                eprintln!("  max_clock_skew_days - Quarantine sessions this far from the last seen time (0 = off)");
+                eprintln!("  disk_warning_days - Notify when the disk fills up within this many days (0 = off)");
+                eprintln!("  max_session_hours - Most a row saved without an end counts for, in hours");
+                eprintln!("  database_key_file - File holding the passphrase of an encrypted database ('off' to unset)");
+                eprintln!("  export_dir - Directory digest files are written to ('off' for the data directory)");
+                eprintln!("  spill_path - Directory rows are spilled to while the disk is full ('off' for the runtime directory)");
+                eprintln!("  max_spill_journal_mb - Size the spill journal is rotated at (0 = no cap)");
+                eprintln!("  cpu_warning_percent - CPU use the daemon warns about after 5 minutes above it (0 = never)");
+                eprintln!("  mail_command - Command digest --mail-to pipes the message to (default 'sendmail -t')");
+                eprintln!("  time_format - Clock used for times in reports (12h or 24h)");
+                eprintln!("  browser_tab_tracking - Browser sessions per tab, per site or not split (full, domain or off)");
+                eprintln!("  apply_sites_to_apps - Match focus and ignored sites against site_apps' window titles (true/false)");
+                eprintln!("  site_apps - Apps site rules apply to, e.g. \"notion, obsidian, slack\" ('off' clears)");
+                eprintln!("  assistant_sites - AI chat sites and titles shown as assistant time ('off' clears, 'default' restores)");
+                eprintln!("  assistant_counts_as - Whether assistant time is focus, distraction or neutral");
+                eprintln!("  date_format - strftime format for dates in reports, e.g. %d.%m.");
+                eprintln!("  show_utc_offset - Note the UTC offset in share reports and exports (true/false)");
+                eprintln!("  show_ascii_art - Show the hourglass in share reports and session summaries (true/false)");
+                eprintln!("  timezone - IANA timezone for times and days, e.g. Europe/Berlin ('system' follows the system)");
+                eprintln!("  report_width - Columns reports are laid out for ('auto' follows the terminal)");
+                eprintln!("  display_glyphs - Characters bars are drawn with: auto, unicode or ascii");
+                eprintln!("  fuzzy_min_score - Lowest score a fuzzy match needs (default 40)");
+                eprintln!("  fuzzy_ambiguity_margin - Runner-ups this close to the best make a match ambiguous (default 5)");
+                eprintln!("  budgets.<app or site> - Daily time budget, e.g. 30m or 1h ('off' removes it)");
+                eprintln!("  budgets.<app or site>.period - 'day' or 'week'");
+                eprintln!("  budgets.<app or site>.carry_over - Roll unused daily time into the next day (true/false)");
+                eprintln!("  budgets.<app or site>.carry_over_cap - Most time carried into a day ('off' = one day's limit)");
+                eprintln!("  groups.<name> - Apps shown as one row in 'stats --by-group', e.g. code,alacritty ('off' removes it)");
+                eprintln!("  score.<efficiency_weight|switch_weight|block_weight|switch_rate_reference|target_block_minutes> - Focus score tuning");
+                eprintln!("  sounds.<enabled|focus_minutes|distraction_minutes|focus_sound|distraction_sound> - Sound cues");
+                eprintln!("\n~=~ Examples:");
+                eprintln!("  focusdebt config set tracking_interval_ms 2000");
+                eprintln!("  focusdebt config set save_interval_ms 60000");
+                eprintln!("  focusdebt config set deep_focus_threshold_minutes 45");
+            return;
+        }
+    }
+
+    if let Err(e) = config.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+
+    println!("~=~ Configuration updated successfully");
+    print_config_warnings(&config);
+}
+
+/// One line per setting that works against another, with the fix
+fn print_config_warnings(config: &Config) {
+    for warning in config.lint() {
+        eprintln!("~=~ Check your settings: {}", warning);
+    }
+}
+
+fn reset_config() {
+    let config = Config::default();
+    
+    if let Err(e) = config.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+
+    println!("~=~ Configuration reset to defaults");
+}
+
+fn export_config(path: &str) {
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    // Focus apps/sites added to the database only would otherwise be left behind
+    match Database::new_read_only().and_then(|db| db.focus_lists(&config.focus_apps, &config.focus_sites)) {
+        Ok((apps, sites)) => (config.focus_apps, config.focus_sites) = (apps, sites),
+        Err(e) => eprintln!("❌ Failed to read focus lists from the database, exporting config only: {}", e),
+    }
+
+    match config.export_bundle(std::path::Path::new(path)) {
+        Ok(()) => println!("~=~ Exported configuration with {} focus apps and {} focus sites",
+            config.focus_apps.len(), config.focus_sites.len()),
+        Err(e) => eprintln!("❌ Failed to write {}: {}", path, e),
+    }
+}
+
+fn import_config(path: &str, replace: bool, dry_run: bool) {
+    let bundle = match Config::load_bundle(std::path::Path::new(path)) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("❌ Failed to read bundle {}: {}", path, e);
+            return;
+        }
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    let (imported, changes) = match config.import_bundle(&bundle, replace) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ Failed to apply bundle: {}", e);
+            return;
+        }
+    };
+
+    if changes.is_empty() {
+        println!("~=~ Configuration already matches the bundle, nothing to change");
+        return;
+    }
+
+    println!("~=~ {} ({} changes):", if replace { "Replacing" } else { "Merging" }, changes.len());
+    for change in &changes {
+        println!("  {}", change);
+    }
+
+    if dry_run {
+        println!("~=~ Dry run, nothing was written");
+        return;
+    }
+
+    if let Err(e) = imported.save() {
+        eprintln!("❌ Failed to save config: {}", e);
+        return;
+    }
+    println!("~=~ Configuration imported; restart the daemon to pick it up");
+}
+
+/// Keys `config preview --set` takes: the ones that change how rows are
+/// classified or counted
+const PREVIEW_KEYS: [&str; 4] = ["deep_focus_threshold_minutes", "min_record_seconds", "apply_sites_to_apps", "site_apps"];
+
+/// What `config preview` changes in a copy of the config
+struct PreviewChanges {
+    add_focus_app: Vec<String>,
+    remove_focus_app: Vec<String>,
+    add_ignored_app: Vec<String>,
+    remove_ignored_app: Vec<String>,
+    add_focus_site: Vec<String>,
+    remove_focus_site: Vec<String>,
+    add_ignored_site: Vec<String>,
+    remove_ignored_site: Vec<String>,
+    /// KEY=VALUE pairs
+    set: Vec<String>,
+}
+
+impl PreviewChanges {
+    /// Apply every change to `config`; the first one that can't be applied
+    /// is the error
+    fn apply(&self, config: &mut Config) -> Result<Vec<ConfigChange>, String> {
+        let mut changes = Vec::new();
+        for app in &self.add_focus_app {
+            add_list_entry(&mut config.focus_apps, "focus_apps", app.trim().to_string(), &mut changes)?;
+        }
+        for app in &self.remove_focus_app {
+            remove_list_entry(&mut config.focus_apps, "focus_apps", app.trim(), &mut changes)?;
+        }
+        for app in &self.add_ignored_app {
+            add_list_entry(&mut config.ignored_apps, "ignored_apps", app.trim().to_string(), &mut changes)?;
+        }
+        for app in &self.remove_ignored_app {
+            remove_list_entry(&mut config.ignored_apps, "ignored_apps", app.trim(), &mut changes)?;
+        }
+        for site in &self.add_focus_site {
+            add_list_entry(&mut config.focus_sites, "focus_sites", site_entry(site)?.to_entry(), &mut changes)?;
+        }
+        for site in &self.remove_focus_site {
+            let entry = listed_site(&config.focus_sites, site);
+            remove_list_entry(&mut config.focus_sites, "focus_sites", &entry, &mut changes)?;
+        }
+        for site in &self.add_ignored_site {
+            add_list_entry(&mut config.ignored_sites, "ignored_sites", site_entry(site)?.to_entry(), &mut changes)?;
+        }
+        for site in &self.remove_ignored_site {
+            let entry = listed_site(&config.ignored_sites, site);
+            remove_list_entry(&mut config.ignored_sites, "ignored_sites", &entry, &mut changes)?;
+        }
+        for pair in &self.set {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(format!("--set takes KEY=VALUE, got '{}'", pair));
+            };
+            let key = key.trim();
+            if !PREVIEW_KEYS.contains(&key) {
+                return Err(format!("--set doesn't take {}; it takes {}", key, PREVIEW_KEYS.join(", ")));
+            }
+            let from = config_key_text(config, key);
+            apply_config_value(config, key, value.trim()).map_err(|e| match e {
+                ConfigValueError::UnknownKey => format!("Unknown configuration key: {}", key),
+                ConfigValueError::Invalid(message) => message,
+            })?;
+            changes.push(ConfigChange::Changed { key: key.to_string(), from, to: config_key_text(config, key) });
+        }
+        Ok(changes)
+    }
+}
+
+fn add_list_entry(list: &mut Vec<String>, key: &str, entry: String, changes: &mut Vec<ConfigChange>) -> Result<(), String> {
+    if list.contains(&entry) {
+        return Err(format!("{} already lists {}", key, entry));
+    }
+    list.push(entry.clone());
+    changes.push(ConfigChange::Added { key: key.to_string(), value: entry });
+    Ok(())
+}
+
+fn remove_list_entry(list: &mut Vec<String>, key: &str, entry: &str, changes: &mut Vec<ConfigChange>) -> Result<(), String> {
+    let Some(index) = list.iter().position(|listed| listed == entry) else {
+        return Err(format!("{} doesn't list {}", key, entry));
+    };
+    list.remove(index);
+    changes.push(ConfigChange::Removed { key: key.to_string(), value: entry.to_string() });
+    Ok(())
+}
+
+/// `key`'s value in `config` as `config set` would take it
+fn config_key_text(config: &Config, key: &str) -> String {
+    serde_json::to_value(config).ok()
+        .and_then(|value| value.get(key).and_then(config_text))
+        .unwrap_or_default()
+}
+
+fn preview_config(changes: &PreviewChanges, days: u32) {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+    let mut proposed = config.clone();
+    let applied = match changes.apply(&mut proposed) {
+        Ok(applied) => applied,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    if applied.is_empty() {
+        eprintln!("❌ Nothing to preview; pass --add-focus-app, --remove-focus-app, --add-focus-site, ... or --set KEY=VALUE");
+        return;
+    }
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let preview = match Stats::calculate_classification_preview(&db, &config, &proposed, days, chrono::Utc::now()) {
+        Ok(preview) => preview,
+        Err(e) => {
+            eprintln!("❌ Failed to reclassify sessions: {}", e);
+            return;
+        }
+    };
+
+    println!("~=~ Previewing {} change{}:", applied.len(), if applied.len() == 1 { "" } else { "s" });
+    for change in &applied {
+        println!("  {}", change);
+    }
+    println!();
+    print!("{}", Stats::render_classification_preview(&preview));
+    println!("\n~=~ Preview only, nothing was written; apply with focusapp, focussite or config set");
+}
+
+fn config_doctor() {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            return;
+        }
+    };
+    println!("~=~ Precedence, first match wins:");
+    for (step, kind) in config::RuleKind::PRECEDENCE.iter().enumerate() {
+        println!("  {}. {}", step + 1, kind.explain());
+    }
+    println!("~=~ Budgets only limit time and never change the class; groups only change how reports list apps");
+
+    let conflicts = conflicts::analyze(&config);
+    if conflicts.is_empty() {
+        println!("~=~ No conflicts between focus rules");
+        return;
+    }
+    for conflict in &conflicts {
+        println!();
+        println!("❌ {}: {}", conflict.kind.label(), conflict.rules.join(", "));
+        println!("   {}", conflict.explanation);
+    }
+    println!();
+    println!("~=~ {} conflict{} found", conflicts.len(), if conflicts.len() == 1 { "" } else { "s" });
+}
+
+/// Next pause between audit samples, from a xorshift state
+fn next_sample_delay(state: &mut u64) -> std::time::Duration {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    let (low, high) = audit::SAMPLE_INTERVAL_MS;
+    std::time::Duration::from_millis(low + *state % (high - low + 1))
+}
+
+fn run_audit(minutes: u64, save: bool) {
+    if !is_daemon_running() {
+        eprintln!("❌ The daemon isn't running; the audit compares against what it records, so start it first");
+        return;
+    }
+    let Some(backend_name) = utils::read_debug_state().and_then(|state| state.backend) else {
+        eprintln!("❌ The daemon hasn't reported its detection backend yet; try again in a few seconds");
+        return;
+    };
+    let Some(backend) = tracking::platform::Backend::ALL.into_iter().find(|backend| backend.name() == backend_name) else {
+        eprintln!("❌ The daemon uses a detection backend this build doesn't know: {}", backend_name);
+        return;
+    };
+    let config = Config::load().unwrap_or_default();
+
+    catch_interrupt();
+    println!("~=~ Sampling the active window with {} every {}-{}ms for {} minute{} (Ctrl-C to stop early)...",
+        backend_name, audit::SAMPLE_INTERVAL_MS.0, audit::SAMPLE_INTERVAL_MS.1, minutes, if minutes == 1 { "" } else { "s" });
+    let start = chrono::Utc::now();
+    let deadline = start + chrono::Duration::minutes(minutes as i64);
+    let mut state = start.timestamp_subsec_nanos() as u64 | 1;
+    let mut samples = Vec::new();
+    while chrono::Utc::now() < deadline && !INTERRUPTED.load(Ordering::SeqCst) {
+        let at = chrono::Utc::now();
+        // Titles as the daemon stores them, so private ones compare equal
+        let window = backend.detect(false).map(|window| audit::WindowKey {
+            title: if config.is_private_title(&window.title) { "[private]".to_string() } else { window.title },
+            app: window.app_name,
+        });
+        samples.push(audit::Observation { at, window });
+        thread::sleep(next_sample_delay(&mut state));
+    }
+    let end = chrono::Utc::now();
+    // The daemon sends window changes to its database thread as it sees them
+    thread::sleep(std::time::Duration::from_millis(config.tracking_interval_ms + 500));
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let events = match db.get_events_between(start - chrono::Duration::days(1), end, Some(EventKind::WindowChange)) {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("❌ Failed to load window changes: {}", e);
+            return;
+        }
+    };
+    let tolerance = std::time::Duration::from_millis(config.tracking_interval_ms + audit::SAMPLE_INTERVAL_MS.1);
+    let report = audit::compare(&samples, &audit::recorded_changes(&events), start, end, tolerance);
+    println!();
+    print!("{}", audit::render(&report, config.tracking_interval_ms));
+
+    if save {
+        let mut payload = report.to_json();
+        payload["backend"] = serde_json::Value::String(backend_name);
+        payload["tracking_interval_ms"] = config.tracking_interval_ms.into();
+        match db.save_event(&TrackerEvent::now(EventKind::Audit, payload)) {
+            Ok(()) => println!("~=~ Saved as an audit event; compare runs with 'focusdebt events --kind audit'"),
+            Err(e) => eprintln!("❌ Failed to save the audit: {}", e),
+        }
+    }
+}
+
+fn debug_window_detection(json: bool) {
+    let report = tracking::DetectionReport::collect();
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to serialize detection report: {}", e),
+        }
+        return;
+    }
+
+    print_detection_report(&report);
+}
+
+/// A daemon that hasn't refreshed its state for this long is likely stuck
+const DEBUG_STATE_STALE_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+
+fn show_debug_state(json: bool, watch: bool) {
+    if watch {
+        catch_interrupt();
+    }
+    loop {
+        let state = if is_daemon_running() { utils::read_debug_state() } else { None };
+        match (&state, json) {
+            (Some(state), true) => match serde_json::to_string(state) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("❌ Failed to serialize debug state: {}", e),
+            },
+            (Some(state), false) => {
+                if watch {
+                    print!("\x1b[2J\x1b[H");
+                }
+                print_debug_state(state);
+            }
+            (None, _) if !is_daemon_running() => println!("~=~ Daemon is not running"),
+            (None, _) => println!("~=~ The daemon hasn't written its state yet (or was started by an older version)"),
+        }
+        let _ = io::stdout().flush();
+
+        if !watch || state.is_none() {
+            return;
+        }
+        for _ in 0..5 {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                return;
+            }
+            sleep_ms(200);
+        }
+    }
+}
+
+fn print_debug_state(state: &utils::DebugState) {
+    let now = chrono::Utc::now();
+    let ago = |at: chrono::DateTime<chrono::Utc>| {
+        utils::format_duration(now.signed_duration_since(at).to_std().unwrap_or_default(), DurationStyle::Compact)
+    };
+
+    println!("~=~ Daemon state (updated {} ago)", ago(state.updated_at));
+    println!("  PID                  : {}", state.pid);
+    println!("  Session              : {}", state.session_name);
+    match (&state.current_app, state.current_started_at) {
+        (Some(app), Some(started_at)) => {
+            let kind = if state.current_is_focus == Some(true) { "focus" } else { "distraction" };
+            println!("  Current window       : {} - {}", app, state.current_title.as_deref().unwrap_or(""));
+            println!("                         {}, for {}", kind, ago(started_at));
+        }
+        _ => println!("  Current window       : (none yet)"),
+    }
+    println!("  Buffered             : {} sessions, {} switches (early save at {} rows)",
+        state.buffered_sessions, state.buffered_switches, state.buffer_limit);
+    println!("  Early saves          : {}", state.early_saves);
+    println!("  Database queue       : {}", state.db_queue_depth);
+    match state.last_detection_at {
+        Some(at) => println!("  Last detection       : {} ({} ago) via {}",
+            utils::format_timestamp_local(at), ago(at), state.backend.as_deref().unwrap_or("?")),
+        None => println!("  Last detection       : never"),
+    }
+    println!("  Consecutive failures : {}", state.consecutive_failures);
+    println!("  Backend failovers    : {}", state.backend_failovers);
+    match state.memory_bytes {
+        Some(bytes) => println!("  Memory               : {}", utils::format_bytes(bytes)),
+        None => println!("  Memory               : unknown"),
+    }
+    if let Some(resources) = &state.resources {
+        println!("  CPU                  : {}", resources.describe_cpu());
+    }
+    if now.signed_duration_since(state.updated_at).to_std().unwrap_or_default() > DEBUG_STATE_STALE_AFTER {
+        println!("❌ The state is {} old; the tracking thread may be stuck", ago(state.updated_at));
+    }
+}
+
+fn print_detection_report(report: &tracking::DetectionReport) {
+    println!("~=~ Platform: {}", report.platform);
+    if !report.environment.is_empty() {
+        println!("~=~ Environment:");
+        for (var, value) in &report.environment {
+            println!("   {}={}", var, value.as_deref().unwrap_or("(unset)"));
+        }
+    }
+
+    println!("~=~ Backends (in the order the daemon tries them):");
+    for probe in &report.backends {
+        let status = match (&probe.app_name, &probe.window_title) {
+            (Some(app), Some(title)) => match &probe.window_id {
+                Some(id) => format!("detected {} - {} (window {})", app, title, id),
+                None => format!("detected {} - {}", app, title),
+            },
+            _ => "failed".to_string(),
+        };
+        let skipped = match probe.backend {
+            _ if probe.applicable => "",
+            tracking::PROCESS_SCAN_BACKEND if !tracking::process_scan_allowed() => " (off, see allow_process_scan_fallback)",
+            _ => " (skipped by daemon in this environment)",
+        };
+        println!("   {:<18} {:>8.1}ms  {}{}", probe.backend, probe.elapsed_ms, status, skipped);
+        for command in &probe.commands {
+            println!("   {:<18}   $ {}", "", command);
+        }
+    }
+
+    match report.chosen {
+        Some(backend) => println!("~=~ The daemon would use: {}", backend),
+        None => println!("❌ The daemon would not detect any window with these backends"),
+    }
+    if let Some(advice) = report.advice {
+        println!("❌ {}", advice);
+    }
+}
+
+fn clear_database() {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match db.clear_all_data() {
+        Ok(_) => println!("~=~ Database cleared successfully"),
+        Err(e) => eprintln!("❌ Failed to clear database: {}", e),
+    }
+}
+
+fn cleanup_database(dry_run: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match db.cleanup_sessions(dry_run) {
+        Ok(report) => {
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            for (category, count) in report.entries() {
+                println!("  {:<32}: {}", category.label(), count);
+            }
+            println!("~=~ {} {} invalid sessions", verb, report.total());
+            if dry_run && report.total() > 0 {
+                println!("~=~ Run 'focusdebt database cleanup' to delete them");
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to cleanup database: {}", e),
+    }
+}
+
+fn cleanup_self_sessions(dry_run: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let config = Config::load().unwrap_or_default();
+    let rule_terminals: Vec<String> = config.terminal_title_rules.iter().map(|rule| rule.terminal.clone()).collect();
+    match db.cleanup_self_sessions(&rule_terminals, dry_run) {
+        Ok(count) => {
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            println!("~=~ {} {} sessions of focusdebt's own windows", verb, count);
+            if dry_run && count > 0 {
+                println!("~=~ Run 'focusdebt database cleanup --self' to delete them");
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to cleanup database: {}", e),
+    }
+}
+
+fn dedupe_sessions(dry_run: bool) {
+    // Opening for writing upgrades the schema, which removes duplicates
+    // itself; a dry run has to look before that
+    let db = match if dry_run { Database::new_read_only() } else { Database::new() } {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match db.dedupe_sessions(dry_run) {
+        Ok(days) if days.is_empty() => println!("~=~ No duplicated session rows"),
+        Ok(days) => {
+            for day in &days {
+                println!("  {:<32}: {} rows, {}", day.day.format("%Y-%m-%d"), day.rows,
+                    utils::format_duration(day.duration, DurationStyle::Long));
+            }
+            let rows: usize = days.iter().map(|day| day.rows).sum();
+            let duration: std::time::Duration = days.iter().map(|day| day.duration).sum();
+            let verb = if dry_run { "would be removed" } else { "removed" };
+            println!("~=~ {} duplicated rows ({}) {}", rows, utils::format_duration(duration, DurationStyle::Long), verb);
+            if dry_run {
+                println!("~=~ Run 'focusdebt database dedupe' to remove them");
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to remove duplicated rows: {}", e),
+    }
+}
+
+/// Size, growth and free space for `db`, the active profile's database
+fn disk_report(db: &Database) -> Option<disk::DiskReport> {
+    let path = Database::get_db_path().ok()?;
+    Some(disk::DiskReport::collect(&path, &disk::load_history(db)))
+}
+
+fn show_database_stats() {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to open database: {}", e);
+            return;
+        }
+    };
+    let Some(report) = disk_report(&db) else {
+        eprintln!("❌ Could not find the database file");
+        return;
+    };
+    println!("  Size        : {}", utils::format_bytes(report.database_bytes));
+    println!("  Growth      : {}", report.describe_growth());
+    println!("  Disk        : {}", report.describe_free_space());
+    for path in db.archive_paths().unwrap_or_default() {
+        let size = std::fs::metadata(&path).map(|meta| utils::format_bytes(meta.len())).unwrap_or_else(|_| "missing".to_string());
+        println!("  Archive     : {} ({})", path.display(), size);
+    }
+    let warning_days = Config::load().unwrap_or_default().disk_warning_days;
+    if disk::should_warn(report.days_left, warning_days) {
+        println!("❌ The disk is full within {} days at this rate; free some space or set retention_days", warning_days);
+    }
+}
+
+fn check_database() {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to open database: {}", e);
+            return;
+        }
+    };
+
+    match db.quick_check() {
+        Ok(problems) if problems.iter().all(|line| line == "ok") => println!("~=~ Integrity: ok"),
+        Ok(problems) => {
+            eprintln!("❌ Integrity check found problems:");
+            for problem in problems {
+                eprintln!("  {}", problem);
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to run the integrity check: {}", e),
+    }
+
+    let now = chrono::Utc::now();
+    match utils::last_seen_clock() {
+        Some(seen) if seen > now => println!("~=~ Last seen time: {} (ahead of the clock now)", seen.with_timezone(&utils::timezone::zone()).format("%Y-%m-%d %H:%M")),
+        Some(seen) => println!("~=~ Last seen time: {}", seen.with_timezone(&utils::timezone::zone()).format("%Y-%m-%d %H:%M")),
+        None => println!("~=~ Last seen time: none recorded yet"),
+    }
+    // Rows saved before the clock check existed aren't quarantined
+    match db.count_sessions_after(now + chrono::Duration::days(1)) {
+        Ok(0) => {}
+        Ok(count) => println!("~=~ {} session rows start in the future; they were saved with a wrong clock", count),
+        Err(e) => eprintln!("❌ Failed to look for future rows: {}", e),
+    }
+
+    let quarantined = match db.get_quarantined_sessions() {
+        Ok(quarantined) => quarantined,
+        Err(e) => {
+            eprintln!("❌ Failed to read quarantined sessions: {}", e);
+            return;
+        }
+    };
+    if quarantined.is_empty() {
+        println!("~=~ No quarantined sessions");
+        return;
+    }
+    println!("~=~ {} sessions quarantined:", quarantined.len());
+    for entry in &quarantined {
+        let session = &entry.session;
+        println!("  {}  {:<20} {:>8}  {}", session.start_time.format("%Y-%m-%d %H:%M"),
+            session.app_name,
+            utils::format_duration(session.duration, DurationStyle::Compact),
+            if session.session_name.is_empty() { "(unnamed)" } else { &session.session_name });
+        println!("    {}", entry.reason);
+    }
+    println!("~=~ 'focusdebt database quarantine restore' keeps them as recorded (once the clock is right,");
+    println!("    for rows held back by their time) and 'focusdebt database quarantine drop' deletes them");
+}
+
+fn install_views() {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    match db.install_views() {
+        Ok(()) => {
+            let names: Vec<&str> = storage::VIEWS.iter().map(|view| view.name).collect();
+            println!("~=~ Installed {}; view_docs describes their columns", names.join(", "));
+            println!("~=~ Try: focusdebt query \"SELECT * FROM v_daily ORDER BY local_date DESC LIMIT 7\"");
+        }
+        Err(e) => eprintln!("❌ Failed to install the views: {}", e),
+    }
+}
+
+fn remove_views() {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    match db.remove_views() {
+        Ok(true) => println!("~=~ Removed the views"),
+        Ok(false) => println!("~=~ No views installed"),
+        Err(e) => eprintln!("❌ Failed to remove the views: {}", e),
+    }
+}
+
+fn list_views() {
+    for view in storage::VIEWS {
+        println!("{} - {}", view.name, view.description);
+        let width = view.columns.iter().map(|(column, _)| column.len()).max().unwrap_or(0);
+        for (column, description) in view.columns {
+            println!("  {:<width$}  {}", column, description);
+        }
+        println!();
+    }
+    println!("~=~ Local dates and times are in the system timezone of whoever runs the query, not the timezone setting");
+}
+
+fn run_query(sql: &str, csv: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let result = match db.query_read_only(sql) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ Query failed: {}", e);
+            if e.to_string().contains("no such table: v_") {
+                eprintln!("~=~ Install the views first with 'focusdebt database views install'");
+            }
+            return;
+        }
+    };
+
+    if csv {
+        println!("{}", result.columns.iter().map(|column| stats::csv_field(column)).collect::<Vec<_>>().join(","));
+        for row in &result.rows {
+            println!("{}", row.iter().map(|value| stats::csv_field(value)).collect::<Vec<_>>().join(","));
+        }
+        return;
+    }
+
+    let mut widths: Vec<usize> = result.columns.iter().map(|column| column.chars().count()).collect();
+    for row in &result.rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: &[String]| -> String {
+        values.iter().zip(&widths)
+            .map(|(value, &width)| format!("{:<width$}", value))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", line(&result.columns));
+    println!("{}", widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>().join("  "));
+    for row in &result.rows {
+        println!("{}", line(row));
+    }
+    println!("~=~ {} row{}", result.rows.len(), if result.rows.len() == 1 { "" } else { "s" });
+}
+
+fn settle_quarantine(restore: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let result = if restore { db.restore_quarantined_sessions() } else { db.drop_quarantined_sessions() };
+    // Settling the quarantine says the clock is right now, so a last seen
+    // time written while it was wrong mustn't hold back new rows
+    if result.is_ok() {
+        if let Err(e) = utils::reset_seen_clock(chrono::Utc::now()) {
+            eprintln!("❌ Failed to reset the last seen time: {}", e);
+        }
+    }
+    match result {
+        Ok(0) => println!("~=~ No quarantined sessions"),
+        Ok(count) if restore => println!("~=~ Restored {} sessions", count),
+        Ok(count) => println!("~=~ Dropped {} sessions", count),
+        Err(e) => eprintln!("❌ Failed to {} quarantined sessions: {}", if restore { "restore" } else { "drop" }, e),
+    }
+}
+
+fn reclassify_sessions(dry_run: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let config = Config::load().unwrap_or_default();
+
+    match db.reclassify_sessions(|session| config.classify_session(session), dry_run) {
+        Ok(report) => {
+            let verb = if dry_run { "would flip" } else { "flipped" };
+            println!("  {:<32}: {}", "distraction → focus", report.to_focus);
+            println!("  {:<32}: {}", "focus → distraction", report.to_distraction);
+            println!("  {:<32}: {}", "ignored, kept as distraction", report.ignored);
+            println!("  {:<32}: {}", "same category, rule recorded", report.rules_recorded);
+            println!("~=~ {} rows {}", report.to_focus + report.to_distraction, verb);
+            if report.ignored > 0 {
+                println!("~=~ Ignored rows stay in the database; --apply-current-rules leaves them out of stats and exports");
+            }
+            if dry_run && report.to_focus + report.to_distraction + report.rules_recorded > 0 {
+                println!("~=~ Run 'focusdebt database reclassify' to rewrite them");
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to reclassify sessions: {}", e),
+    }
+}
+
+fn trim_titles(max_length: Option<usize>, dry_run: bool) {
+    let config = Config::load().unwrap_or_default();
+    let rerun = match max_length {
+        Some(length) => format!("focusdebt database trim-titles --max-length {}", length),
+        None => "focusdebt database trim-titles".to_string(),
+    };
+    let max_length = match max_length.unwrap_or(config.max_title_length) {
+        0 => {
+            eprintln!("❌ max_title_length is 0 (titles kept whole); pass --max-length to choose a length");
+            return;
+        }
+        length if length < tracking::MIN_TITLE_LENGTH => {
+            eprintln!("❌ --max-length must be at least {} characters", tracking::MIN_TITLE_LENGTH);
+            return;
+        }
+        length => length,
+    };
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let report = match db.trim_titles(max_length, dry_run) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("❌ Failed to trim window titles: {}", e);
+            return;
+        }
+    };
+    let verb = if dry_run { "would be cut" } else { "cut" };
+    println!("  {:<20}: {}", "Session rows", report.sessions);
+    println!("  {:<20}: {}", "Window events", report.events);
+    println!("  {:<20}: {}", "Title text", utils::format_bytes(report.bytes_cut));
+    if report.sessions + report.events == 0 {
+        println!("~=~ No titles longer than {} characters", max_length);
+        return;
+    }
+    println!("~=~ {} titles {} to {} characters", report.sessions + report.events, verb, max_length);
+    if dry_run {
+        println!("~=~ Run '{}' to cut them", rerun);
+        return;
+    }
+
+    match db.vacuum_database() {
+        Ok(report) => {
+            let size = |bytes: Option<u64>| bytes.map(utils::format_bytes).unwrap_or_else(|| "unknown".to_string());
+            println!("  Size before VACUUM : {}", size(report.size_before));
+            println!("  Size after VACUUM  : {}", size(report.size_after));
+            if let (Some(before), Some(after)) = (report.size_before, report.size_after) {
+                println!("  Reclaimed          : {}", utils::format_bytes(before.saturating_sub(after)));
+            }
+        }
+        Err(e) => eprintln!("❌ Titles were cut, but VACUUM failed: {}", e),
+    }
+}
+
+fn optimize_database() {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match db.vacuum_database() {
+        Ok(report) => {
+            let size = |bytes: Option<u64>| bytes.map(utils::format_bytes).unwrap_or_else(|| "unknown".to_string());
+            println!("  Size before VACUUM : {}", size(report.size_before));
+            println!("  Size after VACUUM  : {}", size(report.size_after));
+            if let (Some(before), Some(after)) = (report.size_before, report.size_after) {
+                println!("  Reclaimed          : {}", utils::format_bytes(before.saturating_sub(after)));
+            }
+            println!("  ANALYZE            : {}", if report.analyzed { "run" } else { "skipped (failed)" });
+            println!("~=~ Database optimized successfully");
+        }
+        Err(e) => eprintln!("❌ Failed to optimize database: {}", e),
+    }
+}
+
+fn list_sessions(settings: &StatsSettings) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match Stats::list_sessions(&db, settings, None, None) {
+        Ok(sessions) => {
+            println!("~=~ Sessions:");
+            for session in sessions {
+                println!("  {}", session);
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to list sessions: {}", e),
+    }
+}
+
+fn adopt_unnamed_sessions(name: &str, date: &str, allow_duplicate: bool) {
+    let name = match clean_session_name(name, Config::load().unwrap_or_default().max_session_name_length) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let name = name.as_str();
+    let Ok(day) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        eprintln!("❌ Invalid --date '{}'. Use YYYY-MM-DD.", date);
+        return;
+    };
+    let next_day = (day + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+    let (Some(start), Some(end)) = (utils::parse_date_arg(date, false), utils::parse_date_arg(&next_day, false)) else {
+        eprintln!("❌ Invalid --date '{}'. Use YYYY-MM-DD.", date);
+        return;
+    };
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    match db.adopt_unnamed_rows(start, end, name, allow_duplicate) {
+        Ok(Some(0)) => println!("~=~ No unnamed rows on {}", utils::format_date_local(day)),
+        Ok(Some(rows)) => println!("~=~ Named {} unnamed rows from {} \"{}\"", rows, utils::format_date_local(day), name),
+        Ok(None) => eprintln!("❌ Session name '{}' is already used. Pass --allow-duplicate to add the rows to it.", name),
+        Err(e) => eprintln!("❌ Failed to name unnamed rows: {}", e),
+    }
+}
+
+/// Why the running daemon's session can't be deleted or renamed under it,
+/// if `name` is that session
+fn running_session_conflict(name: &str) -> Option<String> {
+    let state = utils::read_daemon_state().filter(|_| is_daemon_running())?;
+    state.session_name.eq_ignore_ascii_case(name)
+        .then(|| format!("\"{}\" is the session being tracked; stop it first with 'focusdebt stop'", state.session_name))
+}
+
+/// The stored spelling of session `name`, or an error naming close matches
+fn resolve_session_name(db: &Database, name: &str) -> Result<String, String> {
+    match db.get_sessions_by_name(name) {
+        Ok(rows) if !rows.is_empty() => Ok(rows[0].session_name.clone()),
+        // Only the name is wanted, which no report setting changes
+        Ok(_) => Stats::find_session(db, &StatsSettings::default(), name)
+            .map(|session| session.session_name)
+            .map_err(|e| e.to_string().trim_start_matches("❌ ").to_string()),
+        Err(e) => Err(format!("Failed to look up session: {}", e)),
+    }
+}
+
+fn delete_session(name: &str, yes: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let name = match resolve_session_name(&db, name) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    if let Some(conflict) = running_session_conflict(&name) {
+        eprintln!("❌ Can't delete {}", conflict);
+        return;
+    }
+
+    if !yes {
+        use std::io::IsTerminal;
+        if !io::stdin().is_terminal() {
+            eprintln!("❌ Deleting \"{}\" needs confirmation; pass --yes when not at a terminal", name);
+            return;
+        }
+        print!("~=~ Delete session \"{}\" and all its rows? This can't be undone. [y/N] ", name);
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+            println!("~=~ Nothing deleted");
+            return;
+        }
+    }
+
+    match db.delete_session(&name) {
+        Ok(rows) => println!("~=~ Deleted session \"{}\" ({} rows)", name, rows),
+        Err(e) => eprintln!("❌ Failed to delete session \"{}\": {}", name, e),
+    }
+}
+
+fn rename_session(name: &str, new_name: &str) {
+    let new_name = match clean_session_name(new_name, Config::load().unwrap_or_default().max_session_name_length) {
+        Ok(new_name) => new_name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let new_name = new_name.as_str();
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let name = match resolve_session_name(&db, name) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    if let Some(conflict) = running_session_conflict(&name) {
+        eprintln!("❌ Can't rename {}", conflict);
+        return;
+    }
+
+    match db.rename_session(&name, new_name) {
+        Ok(Some(rows)) => println!("~=~ Renamed \"{}\" to \"{}\" ({} rows)", name, new_name, rows),
+        Ok(None) => eprintln!("❌ Session name '{}' is already used", new_name),
+        Err(e) => eprintln!("❌ Failed to rename session \"{}\": {}", name, e),
+    }
+}
+
+/// With no `tags`, print the session's tags; "off" clears them
+fn tag_session(name: &str, tags: &[String]) {
+    let db = match if tags.is_empty() { Database::new_read_only() } else { Database::new() } {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let name = match resolve_session_name(&db, name) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+
+    if tags.is_empty() {
+        match db.get_session_tags() {
+            Ok(all) => match all.iter().find(|(session, _)| session.eq_ignore_ascii_case(&name)) {
+                Some((_, tags)) => println!("~=~ Tags of \"{}\": {}", name, tags.join(", ")),
+                None => println!("~=~ \"{}\" has no tags", name),
+            },
+            Err(e) => eprintln!("❌ Failed to read session tags: {}", e),
+        }
+        return;
+    }
+
+    let tags = if tags.len() == 1 && tags[0].eq_ignore_ascii_case("off") {
+        Vec::new()
+    } else {
+        stats::parse_session_tags(&tags.join(","))
+    };
+    match db.set_session_tags(&name, &tags) {
+        Ok(()) if tags.is_empty() => println!("~=~ Cleared the tags of \"{}\"", name),
+        Ok(()) => println!("~=~ Tagged \"{}\": {}", name, tags.join(", ")),
+        Err(e) => eprintln!("❌ Failed to tag session \"{}\": {}", name, e),
+    }
+}
+
+fn browse_sessions(settings: &StatsSettings) {
+    use std::io::IsTerminal;
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        eprintln!("❌ The session browser needs a terminal; use 'focusdebt sessions list' and 'sessions show' in scripts");
+        return;
+    }
+    let settings = StatsSettings { live_rows: live_rows(), ..settings.clone() };
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let sessions = match Stats::recent_sessions(&db, &settings) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            eprintln!("❌ Failed to list sessions: {}", e);
+            return;
+        }
+    };
+    let tags = match db.get_session_tags() {
+        Ok(tags) => tags,
+        Err(e) => {
+            eprintln!("❌ Failed to read session tags: {}", e);
+            return;
+        }
+    };
+    let entries = sessions.iter()
+        .map(|session| {
+            let tags = tags.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&session.session_name))
+                .map(|(_, tags)| tags.clone())
+                .unwrap_or_default();
+            browse::Entry::new(session, tags)
+        })
+        .collect();
+
+    let mut browser = browse::Browser::new(entries);
+    if let Err(e) = browse::run(&mut browser, |browser, action| apply_browser_action(&db, &settings, browser, action)) {
+        eprintln!("❌ Session browser failed: {}", e);
+    }
+}
+
+/// Carry out what the browser asked for with the same calls the session
+/// commands use, and report back to it
+fn apply_browser_action(db: &Database, settings: &StatsSettings, browser: &mut browse::Browser, action: browse::Action) {
+    let load = |name: &str| -> Result<stats::SessionExport, String> {
+        let mut export = Stats::load_session_export(db, settings, name).map_err(|e| e.to_string())?;
+        redact_private_titles(&mut export.rows);
+        Ok(export)
+    };
+    match action {
+        browse::Action::None | browse::Action::Quit => {}
+        browse::Action::Open(name) => match load(&name) {
+            Ok(export) => browser.opened(export),
+            Err(e) => browser.set_status(format!("❌ Failed to load \"{}\": {}", name, e)),
+        },
+        browse::Action::Delete(name) => {
+            if let Some(conflict) = running_session_conflict(&name) {
+                browser.set_status(format!("❌ Can't delete {}", conflict));
+                return;
+            }
+            match db.delete_session(&name) {
+                Ok(rows) => {
+                    browser.deleted(&name);
+                    browser.set_status(format!("Deleted \"{}\" ({} rows)", name, rows));
+                }
+                Err(e) => browser.set_status(format!("❌ Failed to delete \"{}\": {}", name, e)),
+            }
+        }
+        browse::Action::Rename { from, to } => {
+            if let Some(conflict) = running_session_conflict(&from) {
+                browser.set_status(format!("❌ Can't rename {}", conflict));
+                return;
+            }
+            let max_length = Config::load().unwrap_or_default().max_session_name_length;
+            let cleaned = match utils::sanitize_session_name(&to, max_length) {
+                Ok(cleaned) => cleaned,
+                Err(e) => {
+                    browser.set_status(format!("❌ {}", e));
+                    return;
+                }
+            };
+            let to = cleaned.name;
+            match db.rename_session(&from, &to) {
+                Ok(Some(_)) => {
+                    browser.renamed(&from, &to);
+                    let note = if cleaned.cut { format!(" (cut to {} characters)", max_length) } else { String::new() };
+                    browser.set_status(format!("Renamed \"{}\" to \"{}\"{}", from, to, note));
+                }
+                Ok(None) => browser.set_status(format!("❌ Session name '{}' is already used", to)),
+                Err(e) => browser.set_status(format!("❌ Failed to rename \"{}\": {}", from, e)),
+            }
+        }
+        browse::Action::SetTags { name, tags } => match db.set_session_tags(&name, &tags) {
+            Ok(()) => {
+                browser.tagged(&name, tags);
+                browser.set_status(format!("Saved the tags of \"{}\"", name));
+            }
+            Err(e) => browser.set_status(format!("❌ Failed to tag \"{}\": {}", name, e)),
+        },
+        browse::Action::Export(name) => {
+            let config = Config::load().unwrap_or_default();
+            let Some(dir) = config.export_directory() else {
+                browser.set_status("❌ Could not find data directory".to_string());
+                return;
+            };
+            let written = load(&name)
+                .and_then(|export| Stats::render_session_export(&export, "json").map_err(|e| e.to_string()))
+                .and_then(|document| {
+                    let path = dir.join(browse::export_file_name(&name));
+                    std::fs::create_dir_all(&dir)
+                        .and_then(|()| std::fs::write(&path, document))
+                        .map(|()| path)
+                        .map_err(|e| e.to_string())
+                });
+            match written {
+                Ok(path) => browser.set_status(format!("Exported \"{}\" to {}", name, path.display())),
+                Err(e) => browser.set_status(format!("❌ Failed to export \"{}\": {}", name, e)),
+            }
+        }
+    }
+}
+
+fn list_sessions_all_profiles(settings: &StatsSettings) {
+    let Some(data_dir) = utils::get_data_directory() else {
+        eprintln!("❌ Could not find data directory");
+        return;
+    };
+
+    let mut candidates = vec![("default".to_string(), data_dir.join("focusdebt.db"))];
+    for profile in utils::list_profiles() {
+        if let Some(dir) = utils::profile_data_directory(Some(&profile)) {
+            candidates.push((profile, dir.join("focusdebt.db")));
+        }
+    }
+
+    let mut dbs = Vec::new();
+    for (profile, path) in candidates {
+        if !path.exists() {
+            continue;
+        }
+        match Database::open_read_only(&path) {
+            Ok(db) => dbs.push((profile, db)),
+            Err(e) => eprintln!("❌ Failed to open database for profile {}: {}", profile, e),
+        }
+    }
+
+    match Stats::list_sessions_across_profiles(&dbs, settings) {
+        Ok(sessions) => {
+            println!("~=~ Sessions ({} profiles):", dbs.len());
+            for session in sessions {
+                println!("  {}", session);
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to list sessions: {}", e),
+    }
+}
+
+fn show_session_details(settings: &StatsSettings, query: &str, json: bool, include_events: bool) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let details = if json {
+        Stats::session_details_json(&db, settings, query, include_events)
+    } else {
+        Stats::show_session_details(&db, settings, query)
+    };
+
+    match details {
+        Ok(session) => println!("{}", session),
+        Err(e) => eprintln!("❌ Failed to show session details: {}", e),
+    }
+}
+
+/// Replace window titles matching private_title_patterns with "[private]"
+fn redact_private_titles(rows: &mut [tracking::FocusSession]) {
+    let config = Config::load().unwrap_or_default();
+    for row in rows {
+        if config.is_private_title(&row.window_title) {
+            row.window_title = "[private]".to_string();
+        }
+    }
+}
+
+fn show_raw_session(settings: &StatsSettings, name: &str, json: bool, page: usize) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let mut export = match Stats::load_session_export(&db, settings, name) {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("❌ Failed to load session rows: {}", e);
+            return;
+        }
+    };
+    redact_private_titles(&mut export.rows);
+
+    let rendered = if json {
+        Stats::render_session_export(&export, "json").map_err(|e| e.to_string())
+    } else {
+        Stats::render_raw_session(&export, page)
+    };
+    match rendered {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => eprintln!("❌ Failed to show session rows: {}", e),
+    }
+}
+
+fn export_session(settings: &StatsSettings, name: &str, format: &str, output: Option<&str>) {
+    if !stats::SESSION_EXPORT_FORMATS.contains(&format) {
+        eprintln!("❌ Unsupported export format '{}'. Supported formats: {}", format, stats::SESSION_EXPORT_FORMATS.join(", "));
+        return;
+    }
+
+    // Holds off the daemon's retention run until the export is done; a
+    // read-only data directory just goes without
+    let _export_marker = utils::ExportMarker::acquire().ok();
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let mut export = match Stats::load_session_export(&db, settings, name) {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("❌ Failed to export session: {}", e);
+            return;
+        }
+    };
+
+    // Raw rows carry window titles, so keep private ones out of the file
+    redact_private_titles(&mut export.rows);
+
+    let document = match Stats::render_session_export(&export, format) {
+        Ok(document) => document,
+        Err(e) => {
+            eprintln!("❌ Failed to export session: {}", e);
+            return;
+        }
+    };
+
+    match output {
+        Some(path) => match std::fs::write(path, document) {
+            Ok(()) => println!("~=~ Exported session \"{}\" ({} rows) to {}", export.session.session_name, export.rows.len(), path),
+            Err(e) => eprintln!("❌ Failed to write {}: {}", path, e),
+        },
+        None => print!("{}", document),
+    }
+}
+
+/// Days exported when --start-date is not given
+const DEFAULT_EXPORT_DAYS: i64 = 30;
+/// `format` is daily-csv, or anonymized-json for --anonymize; `settings` carries --apply-current-rules
+/// `format` is daily-csv, or anonymized-json for --anonymize
+fn export_data(
+    settings: &StatsSettings,
+    format: &str,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    output: Option<&str>,
+    open: bool,
+    include_archives: bool,
+) {
+    let Some(export_format) = chunks::ExportFormat::parse(format) else {
+        eprintln!("❌ Unsupported export format '{}'. Supported formats: daily-csv", format);
+        return;
+    };
+
+    let parse_day = |input: &str| chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d").ok();
+    let end = match end_date {
+        Some(input) => match parse_day(input) {
+            Some(day) => day,
+            None => {
+                eprintln!("❌ Invalid --end-date '{}'. Use YYYY-MM-DD.", input);
+                return;
+            }
+        },
+        None => chrono::Utc::now().date_naive(),
+    };
+    let start = match start_date {
+        Some(input) => match parse_day(input) {
+            Some(day) => day,
+            None => {
+                eprintln!("❌ Invalid --start-date '{}'. Use YYYY-MM-DD.", input);
+                return;
+            }
+        },
+        None => end - chrono::Duration::days(DEFAULT_EXPORT_DAYS - 1),
+    };
+    if start > end {
+        eprintln!("❌ --start-date must not be after --end-date");
+        return;
+    }
+
+    let path = match (output, open) {
+        (Some(output), _) => Some(std::path::PathBuf::from(output)),
+        (None, true) => match utils::runtime_directory() {
+            Some(dir) => Some(dir.join(format!("focusdebt-daily-{}-{}.{}", start, end, export_format.extension()))),
+            None => {
+                eprintln!("❌ Could not find data directory");
+                return;
+            }
+        },
+        (None, false) => None,
+    };
+    let days = (end - start).num_days() as usize + 1;
+
+    // Large exports to a file go a week at a time, so an interrupted one
+    // can be resumed
+    if let Some(path) = path.as_ref().filter(|_| days > chunks::CHUNKED_EXPORT_MIN_DAYS) {
+        // Resumed from anywhere, so the manifest keeps where the file goes
+        let path = match std::path::absolute(path) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("❌ Failed to resolve {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let manifest_file = chunks::manifest_path(&path);
+        if manifest_file.exists() {
+            eprintln!("❌ An interrupted export to {} exists; continue it with 'focusdebt export --resume {}', or delete that file to start over",
+                path.display(), manifest_file.display());
+            return;
+        }
+        let mut manifest = chunks::ChunkManifest::new(export_format, start, end, settings.current_rules.is_some(), include_archives, path);
+        run_chunked_export(settings, &mut manifest, &manifest_file, open);
+        return;
+    }
+
+    // Holds off the daemon's retention run until the export is done; a
+    // read-only data directory just goes without
+    let _export_marker = utils::ExportMarker::acquire().ok();
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    // When the CSV itself goes to stdout, stay out of its way
+    let mut progress = if path.is_some() {
+        progress::Progress::new("Aggregating days", days)
+    } else {
+        progress::Progress::hidden()
+    };
+    let rows = match Stats::calculate_daily_stats_range(&db, settings, &local_days(), start, days, &mut progress) {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("❌ Failed to calculate stats for {} to {}: {}", start, end, e);
+            return;
+        }
+    };
+    let content = match export_format.render_all(&rows) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("❌ Failed to encode the export: {}", e);
+            return;
+        }
+    };
+
+    let Some(path) = path else {
+        print!("{}", content);
+        return;
+    };
+    if let Err(e) = std::fs::write(&path, content) {
+        eprintln!("❌ Failed to write {}: {}", path.display(), e);
+        return;
+    }
+    progress.finish(&format!("Exported {} days to {}", rows.len(), path.display()));
+
+    if open {
+        if let Err(e) = utils::open_in_default_app(&path) {
+            eprintln!("❌ Failed to open {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// `export --resume`: carry on with the chunked export `manifest_file`
+/// describes, writing again any chunk that is missing or was changed
+fn resume_export(settings: &StatsSettings, manifest_file: &str) {
+    let manifest_file = std::path::PathBuf::from(manifest_file);
+    let mut manifest = match chunks::ChunkManifest::load(&manifest_file) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let dropped = chunks::verify(&mut manifest);
+    if dropped > 0 {
+        println!("~=~ {} chunk{} missing or changed; writing {} again",
+            dropped, if dropped == 1 { " is" } else { "s are" }, if dropped == 1 { "it" } else { "them" });
+    }
+    println!("~=~ Resuming the export of {} to {} at chunk {} of {}",
+        manifest.start, manifest.end, manifest.chunks.len() + 1, manifest.total_chunks());
+    let settings = StatsSettings {
+        current_rules: manifest.apply_current_rules.then(|| Config::load().unwrap_or_default()),
+        ..settings.clone()
+    };
+    storage::set_include_archives(manifest.include_archives);
+    run_chunked_export(&settings, &mut manifest, &manifest_file, false);
+}
+
+fn run_chunked_export(settings: &StatsSettings, manifest: &mut chunks::ChunkManifest, manifest_file: &std::path::Path, open: bool) {
+    let _export_marker = utils::ExportMarker::acquire().ok();
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let mut progress = progress::Progress::new("Exporting days", manifest.remaining_days());
+    let local_days = local_days();
+    let result = chunks::run(manifest, manifest_file, &mut progress, |start, days| {
+        Stats::calculate_daily_stats_range(&db, settings, &local_days, start, days, &mut progress::Progress::hidden())
+            .map_err(|e| format!("failed to calculate stats for the week of {}: {}", start, e))
+    });
+    if let Err(e) = result {
+        progress.clear();
+        eprintln!("❌ Export stopped: {}", e);
+        if manifest_file.exists() {
+            eprintln!("~=~ {} of {} chunks are saved; continue with 'focusdebt export --resume {}'",
+                manifest.chunks.len(), manifest.total_chunks(), manifest_file.display());
+        }
+        return;
+    }
+    progress.finish(&format!("Exported {} days to {}", manifest.total_days(), manifest.output.display()));
+
+    if open {
+        if let Err(e) = utils::open_in_default_app(&manifest.output) {
+            eprintln!("❌ Failed to open {}: {}", manifest.output.display(), e);
+        }
+    }
+}
+
+fn aggregate_team(dir: &str) {
+    let exports = match team::read_exports(std::path::Path::new(dir)) {
+        Ok(exports) => exports,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    match team::aggregate(&exports) {
+        Ok(aggregate) => print!("{}", team::render(&aggregate)),
+        Err(e) => eprintln!("❌ {}", e),
+    }
+}
+
+/// `wrapped`: the year in review as a report, markdown or an SVG card
+fn show_year_in_review(settings: &StatsSettings, year: i32, format: &str, output: Option<&str>) {
+    if !wrapped::WRAPPED_FORMATS.contains(&format) {
+        eprintln!("❌ Unsupported wrapped format '{}'. Supported formats: {}", format, wrapped::WRAPPED_FORMATS.join(", "));
+        return;
+    }
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let review = match wrapped::collect(&db, settings, &local_days(), year) {
+        Ok(review) => review,
+        Err(e) => {
+            eprintln!("❌ Failed to collect the year in review: {}", e);
+            return;
+        }
+    };
+    let (text, path) = match format {
+        "markdown" => (wrapped::render_markdown(&review), output.map(str::to_string)),
+        "svg" => (wrapped::render_svg(&review), Some(output.map(str::to_string).unwrap_or_else(|| wrapped::file_name(year)))),
+        _ => (wrapped::render_ascii(&review), output.map(str::to_string)),
+    };
+    match path {
+        Some(path) => match std::fs::write(&path, text) {
+            Ok(()) => println!("~=~ Year in review for {} written to {}", year, path),
+            Err(e) => eprintln!("❌ Failed to write {}: {}", path, e),
+        },
+        None => println!("{}", text),
+    }
+}
+
+fn send_weekly_digest(settings: &StatsSettings, mail_to: Option<&str>) {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let config = Config::load().unwrap_or_default();
+    let week_start = digest::last_complete_week(utils::timezone::to_zone(chrono::Utc::now()).date_naive());
+    let digest = match digest::collect_weekly(&db, &config, settings, &utils::LocalDays::new(config.day_start_hour), week_start) {
+        Ok(digest) => digest,
+        Err(e) => {
+            eprintln!("❌ Failed to collect the weekly digest: {}", e);
+            return;
+        }
+    };
+    let text = digest::render_weekly(&digest, settings);
+
+    match mail_to {
+        Some(to) => {
+            let subject = format!("FocusDebt weekly digest {}", digest::week_label(week_start));
+            let message = digest::mail_message(to, &subject, &text);
+            match digest::send_mail(&config.mail_command, to, &message) {
+                Ok(()) => println!("~=~ Weekly digest for {} sent to {}", digest::week_label(week_start), to),
+                Err(e) => eprintln!("❌ Failed to send the digest: {}", e),
+            }
+        }
+        None => {
+            let Some(dir) = config.export_directory() else {
+                eprintln!("❌ Could not determine an export directory. Set export_dir.");
+                return;
+            };
+            match digest::write_digest(&dir, week_start, &text) {
+                Ok(path) => println!("~=~ Weekly digest written to {}", path.display()),
+                Err(e) => eprintln!("❌ Failed to write the digest to {}: {}", dir.display(), e),
+            }
+        }
+    }
+}
+
+fn show_daemon_log(tail: usize, follow: bool, since: Option<std::time::Duration>, level: Option<&str>) {
+    let level = match level {
+        Some(input) => match logs::LogLevel::parse(input) {
+            Some(level) => Some(level),
+            None => {
+                eprintln!("❌ Invalid --level value: {}. Must be info, warn or error.", input);
+                return;
+            }
+        },
+        None => None,
+    };
+    let since = since.map(|since| chrono::Utc::now() - chrono::Duration::from_std(since).unwrap_or_default());
+    let Some(path) = logs::daemon_log_path() else {
+        eprintln!("❌ Could not find data directory");
+        return;
+    };
+
+    match logs::read_tail(&path, tail, since, level) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound && follow => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("~=~ No daemon log at {} yet", path.display());
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    }
+    if follow {
+        if let Err(e) = logs::follow(&path, since, level) {
+            eprintln!("❌ Stopped following {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn clear_daemon_log() {
+    let Some(path) = logs::daemon_log_path() else {
+        eprintln!("❌ Could not find data directory");
+        return;
+    };
+    match logs::clear(&path) {
+        Ok(()) => println!("~=~ Cleared {}", path.display()),
+        Err(e) => eprintln!("❌ Failed to clear {}: {}", path.display(), e),
+    }
+}
+
+fn list_events(from: Option<&str>, to: Option<&str>, kind: Option<&str>) {
+    let start = match from {
+        Some(from) => match utils::parse_date_arg(from, false) {
+            Some(start) => start,
+            None => {
+                eprintln!("❌ Invalid --from value: {} (expected YYYY-MM-DD or RFC3339)", from);
+                return;
+            }
+        },
+        None => utils::parse_date_arg(&utils::timezone::to_zone(chrono::Utc::now()).format("%Y-%m-%d").to_string(), false)
+            .unwrap_or_else(chrono::Utc::now),
+    };
+    let end = match to {
+        Some(to) => match utils::parse_date_arg(to, true) {
+            Some(end) => end,
+            None => {
+                eprintln!("❌ Invalid --to value: {} (expected YYYY-MM-DD or RFC3339)", to);
+                return;
+            }
+        },
+        None => chrono::Utc::now(),
+    };
+    let kind = match kind {
+        Some(kind) => match EventKind::parse(kind) {
+            Some(kind) => Some(kind),
+            None => {
+                let known: Vec<&str> = EventKind::ALL.iter().map(|k| k.as_str()).collect();
+                eprintln!("❌ Unknown event kind: {} (expected one of: {})", kind, known.join(", "));
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    match db.get_events_between(start, end, kind) {
+        Ok(events) if events.is_empty() => println!("~=~ No events found in this range"),
+        Ok(events) => {
+            for event in events {
+                println!("{} {}  {:<14} {}",
+                    event.timestamp.with_timezone(&utils::timezone::zone()).format("%Y-%m-%d"),
+                    utils::format_timestamp(event.timestamp),
+                    event.kind.as_str(),
+                    event.payload
+                );
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to load events: {}", e),
+    }
+}
+
+/// `database encrypt` / `database decrypt`: rewrite the database in place.
+/// The daemon must be stopped so nothing writes to the old file meanwhile.
+fn migrate_database_encryption(encrypt: bool) {
+    if !cfg!(feature = "encryption") {
+        eprintln!("❌ This build has no encryption support; rebuild with `cargo build --release --features encryption`");
+        return;
+    }
+    if is_daemon_running() {
+        eprintln!("❌ Stop the daemon first (focusdebt stop)");
+        return;
+    }
+    let path = match Database::get_db_path() {
+        Ok(path) if path.exists() => path,
+        Ok(_) => {
+            eprintln!("❌ No database yet; start tracking first");
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to find database: {}", e);
+            return;
+        }
+    };
+    if storage::is_encrypted(&path) == encrypt {
+        println!("~=~ The database is already {}", if encrypt { "encrypted" } else { "plain SQLite" });
+        return;
+    }
+
+    let configured = match Database::configured_database_key() {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let prompted = configured.is_none();
+    let key = match configured {
+        Some(key) => key,
+        None if encrypt => {
+            let first = utils::prompt_passphrase("New database passphrase: ");
+            let second = utils::prompt_passphrase("Repeat passphrase: ");
+            match (first, second) {
+                (Ok(first), Ok(second)) if !first.is_empty() && first == second => first,
+                (Ok(_), Ok(_)) => {
+                    eprintln!("❌ The passphrases were empty or didn't match");
+                    return;
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("❌ Set {} or database_key_file, or run this in a terminal ({})", storage::DB_KEY_ENV, e);
+                    return;
+                }
+            }
+        }
+        None => match utils::prompt_passphrase("Database passphrase: ") {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("❌ Set {} or database_key_file, or run this in a terminal ({})", storage::DB_KEY_ENV, e);
+                return;
+            }
+        },
+    };
+
+    let result = if encrypt {
+        Database::encrypt_file(&path, &key)
+    } else {
+        Database::decrypt_file(&path, &key)
+    };
+    match result {
+        Ok(()) if encrypt => {
+            println!("~=~ Database encrypted. Without the passphrase the data can't be recovered");
+            if prompted {
+                println!("~=~ Set {} or database_key_file so the daemon can open it", storage::DB_KEY_ENV);
+            }
+        }
+        Ok(()) => println!("~=~ Database decrypted"),
+        Err(e) => eprintln!("❌ Failed to {} the database: {}", if encrypt { "encrypt" } else { "decrypt" }, e),
+    }
+}
+
+fn prune_events(keep: std::time::Duration, dry_run: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::from_std(keep).unwrap_or(chrono::Duration::zero());
+    let total = match db.count_events_before(cutoff) {
+        Ok(total) => total,
+        Err(e) => {
+            eprintln!("❌ Failed to count events: {}", e);
+            return;
+        }
+    };
+
+    if dry_run {
+        println!("~=~ Would remove {} events", total);
+        return;
+    }
+
+    let mut progress = progress::Progress::new("Pruning events", total);
+    match db.prune_events(cutoff, &mut progress) {
+        Ok(count) => progress.finish(&format!("Removed {} events", count)),
+        Err(e) => eprintln!("❌ Failed to prune events after removing {}: {}", progress.processed(), e),
+    }
+}
+
+fn archive_history(keep: std::time::Duration, to: Option<&str>, dry_run: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let archive = match to {
+        Some(path) => std::path::PathBuf::from(path),
+        None => match Database::get_db_path() {
+            Ok(path) => path.with_file_name(storage::DEFAULT_ARCHIVE_FILE),
+            Err(e) => {
+                eprintln!("❌ Failed to find the data directory: {}", e);
+                return;
+            }
+        },
+    };
+    let live = Database::get_db_path().ok().and_then(|path| std::fs::canonicalize(path).ok());
+    if live.is_some() && live == std::fs::canonicalize(&archive).ok() {
+        eprintln!("❌ {} is the live database; choose another archive file", archive.display());
+        return;
+    }
+
+    // The running session stays whole, as with retention
+    let keep_session = utils::read_daemon_state()
+        .filter(|_| is_daemon_running())
+        .map(|state| state.session_name);
+    let before = chrono::Utc::now() - chrono::Duration::from_std(keep).unwrap_or(chrono::Duration::zero());
+    if dry_run {
+        match db.count_archivable(before, keep_session.as_deref()) {
+            Ok(report) => println!(
+                "~=~ Would move {} sessions, {} context switches and {} events to {}",
+                report.sessions, report.context_switches, report.events, archive.display()
+            ),
+            Err(e) => eprintln!("❌ Failed to count rows to archive: {}", e),
+        }
+        return;
+    }
+
+    match db.archive_history(before, &archive, keep_session.as_deref()) {
+        Ok(report) if report.moved.sessions + report.moved.context_switches + report.moved.events == 0 => {
+            println!("~=~ Nothing older than {} to archive", utils::format_duration(keep, DurationStyle::Long));
+        }
+        Ok(report) => {
+            let moved = report.moved;
+            println!(
+                "~=~ Moved {} sessions, {} context switches and {} events to {}",
+                moved.sessions, moved.context_switches, moved.events, archive.display()
+            );
+            if report.already_archived > 0 {
+                println!("~=~ {} of them were already in the archive and were only removed here", report.already_archived);
+            }
+            println!("~=~ Read them back with --include-archives (stats, export, digest, wrapped)");
+            println!("~=~ Run `focusdebt database optimize` to shrink the database file");
+        }
+        Err(e) => eprintln!("❌ Failed to archive history (nothing was moved): {}", e),
+    }
+}
+
+fn add_planned_session(name: &str, at: &str, duration: std::time::Duration) {
+    let name = match clean_session_name(name, Config::load().unwrap_or_default().max_session_name_length) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let name = name.as_str();
+    if duration.is_zero() {
+        eprintln!("❌ --duration must be longer than zero");
+        return;
+    }
+    let now = chrono::Utc::now();
+    let Some(start) = utils::parse_when(at, now) else {
+        eprintln!("❌ Invalid --at '{}'. Try \"tomorrow 09:00\", \"fri 14:00\", \"2024-05-21 09:00\" or \"09:00\".", at);
+        return;
+    };
+    if start + chrono::Duration::from_std(duration).unwrap_or_default() <= now {
+        eprintln!("❌ {} is already over; plans are for sessions still ahead", utils::format_datetime_local(start));
+        return;
+    }
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    match db.add_planned_session(name, start, duration) {
+        Ok(_) => {
+            println!("~=~ Planned \"{}\" for {} ({})",
+                name, utils::format_datetime_local(start), utils::format_duration(duration, DurationStyle::Long));
+            let config = Config::load().unwrap_or_default();
+            if !config.auto_daily_session {
+                println!("~=~ Plans start only while the daemon tracks the automatic daily session (auto_daily_session = true)");
+            }
+        }
+        Err(e) => eprintln!("❌ Failed to save plan: {}", e),
+    }
+}
+
+fn list_planned_sessions() {
+    let db = match Database::new_read_only() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let now = chrono::Utc::now();
+    let week_start = stats::start_of_day(digest::last_complete_week(now.date_naive()) + chrono::Duration::days(7));
+    let plans = match db.get_planned_sessions(week_start, None) {
+        Ok(plans) => plans,
+        Err(e) => {
+            eprintln!("❌ Failed to load plans: {}", e);
+            return;
+        }
+    };
+    if plans.is_empty() {
+        println!("~=~ Nothing planned this week. Add a plan with: focusdebt plan add <name> --at <when> --duration <d>");
+        return;
+    }
+
+    println!("~~+~~+*+~~+~~+*+~~+~~");
+    for plan in &plans {
+        let when = format!("{} {}", plan.start_time.with_timezone(&utils::timezone::zone()).format("%a"), utils::format_datetime_local(plan.start_time));
+        println!("  {:<20} {:>8}  {:<10} {}",
+            when,
+            utils::format_duration(plan.duration, DurationStyle::Compact),
+            plan.status(now).label(),
+            plan.name);
+    }
+    println!("~~+~~+*+~~+~~+*+~~+~~");
+    let past: Vec<storage::PlannedSession> = plans.into_iter().filter(|plan| plan.end_time() <= now).collect();
+    if !past.is_empty() {
+        println!("~=~ This week so far: {}", digest::describe_plans(&past, now));
+    }
+}
+
+fn import_rescuetime(path: &str) {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("❌ Failed to read {}: {}", path, e);
+            return;
+        }
+    };
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let summary = match import::import_rescuetime(&db, &content) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("❌ Not a RescueTime CSV export: {}", e);
+            return;
+        }
+    };
+    println!("~=~ {} imported, {} skipped (already imported or empty), {} failed",
+        summary.imported, summary.skipped, summary.failed);
+    for failure in &summary.failures {
+        eprintln!("❌ {}", failure);
+    }
+    if summary.failed > summary.failures.len() {
+        eprintln!("❌ ...and {} more", summary.failed - summary.failures.len());
+    }
+    if summary.imported > 0 {
+        println!("~=~ RescueTime only has daily totals, so each day's activities are laid end to end from {}; times of day are approximate",
+            import::packed_day_start().format("%H:%M"));
+    }
+}
+
+fn add_manual_entry(app: &str, start: &str, duration: std::time::Duration, focus: bool, session: &str, title: Option<&str>, allow_overlap: bool) {
+    let app = app.trim();
+    if app.is_empty() {
+        eprintln!("❌ --app cannot be empty");
+        return;
+    }
+    let now = chrono::Utc::now();
+    let Some(start_time) = utils::parse_past_when(start, now) else {
+        eprintln!("❌ Invalid --start '{}'. Try \"14:00\", \"yesterday 14:00\", \"fri 10:00\" or \"2024-05-21 14:00\".", start);
+        return;
+    };
+    let session = match clean_session_name(session, Config::load().unwrap_or_default().max_session_name_length) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+    let entry = import::ManualEntry {
+        app: app.to_string(),
+        title: title.unwrap_or_default().trim().to_string(),
+        start: start_time,
+        duration,
+        focus,
+        session,
+    };
+
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = entry.check(&db, now, allow_overlap) {
+        eprintln!("❌ Can't add the entry: {}", e);
+        return;
+    }
+    let span = format!("{}-{}", utils::format_datetime_local(entry.start), utils::format_timestamp_local(entry.end()));
+    match db.import_focus_sessions(storage::MANUAL_SOURCE, &[entry.to_row()]) {
+        Ok(0) => println!("~=~ {} {} is already entered", entry.app, span),
+        Ok(_) => println!("~=~ Added {} {} ({}, {}) to \"{}\"", entry.app, span,
+            utils::format_duration(entry.duration, DurationStyle::Long),
+            if entry.focus { "focus" } else { "not focus" },
+            entry.session),
+        Err(e) => eprintln!("❌ Failed to add the entry: {}", e),
+    }
+}
+
+/// `demo generate`: write `days` days of generated sessions into the
+/// active database, through the same calls the daemon and importers use
+fn generate_demo_data(days: u32, seed: u64, force: bool) {
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+    let path = Database::get_db_path().map(|path| path.display().to_string()).unwrap_or_default();
+    match db.count_sessions_after(chrono::DateTime::UNIX_EPOCH) {
+        Ok(0) => {}
+        Ok(rows) if !force => {
+            eprintln!("❌ {} already holds {} tracked rows; demo data would mix with them", path, rows);
+            eprintln!("~=~ Use an empty one with --data-dir <dir> (or --profile demo), or pass --force");
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("❌ Failed to read the database: {}", e);
+            return;
+        }
+    }
+
+    let mut config = Config::load().unwrap_or_default();
+    config.focus_sites = demo::focus_sites();
+    let snapshot = config.session_snapshot(demo::focus_apps());
+    // Today's sessions stop at now, as if the daemon were still running
+    let now = chrono::Utc::now();
+    let sessions = demo::generate(days, seed, utils::timezone::to_zone(now).date_naive());
+    let written = match demo::write(&db, &sessions, seed, &snapshot, now) {
+        Ok(written) => written,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+
+    if written.sessions == 0 {
+        println!("~=~ Seed {} was already written to {} for these {} days", seed, path, days);
+        return;
+    }
+    println!("~=~ Wrote {} sessions, {} rows and {} context switches over {} days (seed {}) to {}",
+        written.sessions, written.rows, written.switches, days, seed, path);
+    println!("~=~ The rows are classified as if focus_apps were {} and focus_sites {}",
+        demo::focus_apps().join(", "), demo::focus_sites().join(", "));
+    println!("~=~ Try 'focusdebt stats', 'focusdebt sessions list' or 'focusdebt rules stats'");
+}
+
+fn import_manual_entries(path: &str, allow_overlap: bool) {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("❌ Failed to read {}: {}", path, e);
+            return;
+        }
+    };
+    let now = chrono::Utc::now();
+    let parsed = match import::parse_manual_csv(&content, now, Config::load().unwrap_or_default().max_session_name_length) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("❌ Not a manual entries CSV: {}", e);
+            return;
+        }
+    };
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let summary = import::import_manual(&db, parsed, now, allow_overlap);
+    println!("~=~ {} added, {} skipped (already entered), {} failed",
+        summary.imported, summary.skipped, summary.failed);
+    for failure in &summary.failures {
+        eprintln!("❌ {}", failure);
+    }
+    if summary.failed > summary.failures.len() {
+        eprintln!("❌ ...and {} more", summary.failed - summary.failures.len());
+    }
+}
+
+fn import_legacy_log(path: &str, interval_ms: Option<u64>) {
+    let content = match std::fs::read(path) {
+        Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+        Err(e) => {
+            eprintln!("❌ Failed to read {}: {}", path, e);
+            return;
+        }
+    };
+    let modified = match std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => chrono::DateTime::<chrono::Utc>::from(modified),
+        Err(e) => {
+            eprintln!("❌ Failed to read the modification time of {}: {}", path, e);
+            return;
+        }
+    };
+    let config = Config::load().unwrap_or_default();
+    let interval_ms = interval_ms.unwrap_or(config.tracking_interval_ms);
+    if interval_ms == 0 {
+        eprintln!("❌ --interval-ms must be at least 1");
+        return;
+    }
+    let db = match Database::new() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            return;
+        }
+    };
+
+    let import = import::import_legacy_log(&db, &config, &content, modified, std::time::Duration::from_millis(interval_ms));
+    let summary = &import.summary;
+    println!("~=~ {} imported, {} skipped (already imported or ignored), {} failed",
+        summary.imported, summary.skipped, summary.failed);
+    for failure in &summary.failures {
+        eprintln!("❌ {}", failure);
+    }
+    if summary.failed > summary.failures.len() {
+        eprintln!("❌ ...and {} more", summary.failed - summary.failures.len());
+    }
+    if summary.imported == 0 {
+        if summary.skipped == 0 && summary.failed == 0 {
+            println!("~=~ No window changes found; is this a focusdebt daemon log?");
+        }
+        return;
+    }
+    println!("~=~ Recovered {} over {} day{} from {} daemon run{}",
+        utils::format_duration(import.recovered, DurationStyle::Long),
+        import.days, if import.days == 1 { "" } else { "s" },
+        import.runs, if import.runs == 1 { "" } else { "s" });
+    if import.estimated > 0 {
+        println!("~=~ {} of {} rows have estimated times: lines without a timestamp are counted back from the file's", import.estimated, summary.imported);
+        println!("    modification time, one {}ms check per detection, and earlier runs are placed back to back", interval_ms);
+        println!("    because the gaps between them weren't logged. Durations are close; dates and times of day may not be.");
+    }
+}
+
+fn show_import_help() {
+    println!("~=~ Import Commands:");
+    println!("  rescuetime <file>  - Import a RescueTime CSV export (date, time spent, activity, category, productivity)");
+    println!("  manual <file>      - Add time away from the computer from a CSV (app, start, duration, focus, session, title)");
+    println!("                       --allow-overlap adds entries that overlap tracked rows");
+    println!("  legacy-log <file>  - Rebuild approximate history from an old /tmp/focusdebt_daemon.log");
+    println!("                       --interval-ms sets the tracking interval it was written with");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Rows with productivity 1 or 2 count as focus. Running an import again skips rows it already added.");
+}
+
+fn show_service_help() {
+    println!("~=~ Service Commands:");
+    println!("  install --with-watchdog - Install a systemd user timer that runs 'heartbeat-check' every {} minutes", service::WATCHDOG_INTERVAL_MINUTES);
+    println!("                     --restart lets it start a daemon that died again, --dry-run only shows the files");
+    println!("  uninstall          - Disable the timer and remove its units (--dry-run)");
+    println!("  help               - Show this help message");
+    println!();
+    println!("The daemon writes a heartbeat at every save. When it stops beating for three save");
+    println!("intervals (at least two minutes) without 'focusdebt stop', the watchdog sends one");
+    println!("desktop notification. Each profile gets its own timer.");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt service install --with-watchdog --restart");
+    println!("  focusdebt --profile work service install --with-watchdog");
+}
+
+fn show_plan_help() {
+    println!("~=~ Plan Commands:");
+    println!("  add <name> --at <when> --duration <d> - Plan a session (when: \"tomorrow 09:00\", \"fri 14:00\", \"2024-05-21 09:00\", \"09:00\")");
+    println!("  list               - This week's and upcoming plans, with missed ones marked");
+    println!("  help               - Show this help message");
+    println!();
+    println!("A running daemon that is tracking the automatic daily session starts a plan when");
+    println!("its time comes and goes back to the daily session after the planned duration.");
+    println!("Plans whose time passes without a daemon to start them show as missed.");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt plan add \"deep work\" --at \"tomorrow 09:00\" --duration 2h");
+    println!("  focusdebt plan list");
+}
+
+fn show_welcome_message() {
+    println!(r#"
+Welcome to FocusDebt - CLI Focus Tracker!
+
+FocusDebt helps developers track focus time and context switching.
+
+Focus Apps:
+  focusdebt focusapp add code    # Add VS Code as focus app
+  focusdebt focusapp list        # View focus vs distraction apps
+
+Configuration:
+  focusdebt config show    # View current settings
+
+Get help anytime with --help on any command.
+Happy focusing! 🚀
+"#);
+}
+
+fn suggest_focus_sites() {
+    let config = Config::load().unwrap_or_default();
+    let mut open_tabs = utils::get_open_browser_tabs();
+    // Private and ignored titles are never echoed back to the terminal
+    open_tabs.retain(|tab| !config.is_private_title(tab) && !config.matches_ignored_site(tab));
+    if open_tabs.is_empty() {
+        println!("No open browser tabs detected.");
+        println!("Make sure you have browser windows open with tabs.");
+        return;
+    }
+    
+    println!("Currently open browser tab:");
+    for (i, tab) in open_tabs.iter().enumerate() {
+        if config.matches_focus_site(tab) {
+            println!("{}. {} - already tracked", i + 1, tab);
+        } else {
+            println!("{}. {}", i + 1, tab);
+        }
+    }
+    println!("\nUse 'focusdebt focussite add \"Tab Name\"' to add by fuzzy match.");
+    println!("Example: focusdebt focussite add \"ChatGPT\"");
+}
+
+fn show_focusapp_help() {
+    println!("~=~ FocusApp Commands:");
+    println!("  add <app_name>     - Add an application to the focus list; with no GUI apps running");
+    println!("                       it matches apps tracked before (--allow-literal adds any name)");
+    println!("  remove <app_name>  - Remove an application from the focus list");
+    println!("  list               - List all focus applications");
+    println!("  suggest            - Suggest running GUI applications");
+    println!("  audit              - Tracked time and first/last seen of focus and ignored apps,");
+    println!("                       flagging stale ones for removal (--stale-days N, --json)");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt focusapp add code");
+    println!("  focusdebt focusapp remove firefox");
+    println!("  focusdebt focusapp list");
+    println!("  focusdebt focusapp audit --stale-days 90");
+}
+
+fn show_demo_help() {
+    println!("~=~ Demo Commands:");
+    println!("  generate           - Write generated sessions for the last --days days (default {})", demo::DEFAULT_DAYS);
+    println!("                       --seed N picks the data (default {}), --force writes into a", demo::DEFAULT_SEED);
+    println!("                       database that already has sessions");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Generated rows are stored with source 'demo'. Point --data-dir or --profile");
+    println!("somewhere empty to keep them apart from real tracking.");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt --data-dir /tmp/focusdebt-demo demo generate");
+    println!("  focusdebt --profile demo demo generate --days 30 --seed 7");
+}
+
+fn show_rules_help() {
+    println!("~=~ Rules Commands:");
+    println!("  stats              - Time by the rule that classified it, and focus entries with none");
+    println!("                       (--days N, default {}; --json)", stats::RULE_STATS_DAYS);
+    println!("  help               - Show this help message");
+    println!();
+    println!("Rules are recorded on each row as it is tracked, e.g. focus_app:code or");
+    println!("focus_site:github.com, and 'default' when no list matched. Older rows get");
+    println!("one from 'focusdebt database reclassify'.");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt rules stats");
+    println!("  focusdebt rules stats --days 90 --json");
+}
+
+fn show_focussite_help() {
+    println!("~=~ Focussite Commands:");
+    println!("  add <entry>        - Add a website to the focus list");
+    println!("  remove <entry>     - Remove a website from the focus list");
+    println!("  list               - List all focus websites and how they match");
+    println!("  suggest            - Suggest currently open browser tabs");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt focussite add github.com             # domain (the default)");
+    println!("  focusdebt focussite add title:jira             # text in the tab title");
+    println!("  focusdebt focussite add 're:/\\bRFC\\b/'         # regex over the tab title");
+    println!("  focusdebt focussite remove youtube.com");
+    println!("  focusdebt focussite list");
+}
+
+fn show_config_help() {
+    println!("~=~ Config Commands:");
+    println!("  show               - Show current configuration (--json for values and their sources)");
+    println!("  set <key> <value>  - Set a configuration value");
+    println!("  set --json <obj>   - Set several keys from a JSON object, all or none of them");
+    println!("  reset              - Reset configuration to defaults");
+    println!("  export <file>      - Write config and focus lists to a portable bundle");
+    println!("  import <file>      - Merge a bundle in (--replace to overwrite, --dry-run to preview)");
+    println!("  preview            - Show how --add-focus-app/--remove-focus-site/--set ... would classify recent days");
+    println!("  doctor             - Check focus rules for contradictions and rules that never apply");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Available configuration keys:");
+    println!("  tracking_interval_ms           - How often to check active window (ms)");
+    println!("  save_interval_ms               - How often to save data to database (ms)");
+    println!("  backend                        - Pin a detection backend from 'focusdebt debug' (default auto)");
+    println!("  detection_fallback             - Let a pinned backend fail over (default false)");
+    println!("  allow_process_scan_fallback    - Guess the window from running apps as a last resort (default false)");
+    println!("  max_buffered_rows              - Rows held in memory before saving early (default 5000)");
+    println!("  deep_focus_threshold_minutes   - Minimum duration for deep focus sessions");
+    println!("  min_record_seconds             - Window visits shorter than this are not saved");
+    println!("  min_display_seconds            - Roll apps/tabs below this into one 'other' line");
+    println!("  round_trip_window_seconds      - A→B→A within this counts as a round trip (default 60)");
+    println!("  co_focus_window_minutes        - How recently the other side of a co_focus rule was used (default 5)");
+    println!("  merge_gap_seconds              - Join rows of one window split by a shorter gap (default 5, 0 = off)");
+    println!("  switch_cost_minutes            - Assumed cost of a switch away with no measured recovery (default 2)");
+    println!("  switch_cost_unmeasured         - Count unmeasured switches in the switching cost (true/false)");
+    println!("  efficiency_floor               - Warn when 3-day average efficiency drops below (%)");
+    println!("  efficiency_alerts              - Enable efficiency floor alerts (true/false)");
+    println!("  daily_focus_goal_minutes       - Focus time a day aims for, for estimate (default 240)");
+    println!("  exclude_weekends_from_averages - Leave weekends and holidays out of averages (true/false)");
+    println!("  holidays                       - Non-working days, YYYY-MM-DD, comma-separated ('off' clears)");
+    println!("  day_start_hour                 - Local hour at which days (stats, budgets) start (0-23)");
+    println!("  profile                        - Default profile ('default' for none)");
+    println!("  allow_duplicate_session_names  - Let start reuse session names (true/false)");
+    println!("  auto_daily_session             - One date-named session per day, no prompt (true/false)");
+    println!("  quiet_hours                    - Ranges tracking pauses in, e.g. \"22:00-07:00, sat-sun 13:00-18:00\" ('off' clears)");
+    println!("  self_ignore                    - Don't track focusdebt's own windows (true/false)");
+    println!("  max_title_length               - Characters kept of each window title (default 512, 0 = no limit)");
+    println!("  max_session_name_length        - Characters kept of a new session name (default 100, 0 = no limit)");
+    println!("  track_input_activity           - Score engaged focus from input idle time (true/false)");
+    println!("  tmux_integration               - Attribute terminal time to the tmux pane's command (true/false)");
+    println!("  count_notifications            - Count notifications received per session, by app (default false)");
+    println!("  notification_command           - Command printing one app name per notification (default: D-Bus)");
+    println!("  taskwarrior_integration        - Run 'task <id> start/stop' around sessions started with --task (default false)");
+    println!("  todo_txt_file                  - todo.txt file 'start --task' looks lines up in (default $TODO_FILE or ~/todo.txt)");
+    println!("  passive_after_minutes          - Low-activity minutes before time counts as passive (default 10, 0 = off)");
+    println!("  passive_activity_level         - Minute activity below which it counts as low (default 0.1)");
+    println!("  retention_days                 - Days of history the daemon keeps (default off)");
+    println!("  max_clock_skew_days            - Quarantine sessions this far from the last seen time (default 30, 0 = off)");
+    println!("  disk_warning_days              - Notify when the disk fills up within N days (default 30, 0 = off)");
+    println!("  max_session_hours              - Most a row saved without an end counts for (default 12)");
+    println!("  database_key_file              - Passphrase file for an encrypted database");
+    println!("  export_dir                     - Where digest files go (default <data dir>/exports)");
+    println!("  spill_path                     - Where rows go while the disk is full (default: runtime directory)");
+    println!("  max_spill_journal_mb           - Size the spill journal is rotated at (default 64, 0 = no cap)");
+    println!("  cpu_warning_percent            - CPU use the daemon warns about after 5 minutes above it (default 5, 0 = never)");
+    println!("  mail_command                   - Command mailed digests are piped to (default sendmail -t)");
+    println!("  time_format                    - 12h or 24h clock in reports (default 24h)");
+    println!("  browser_tab_tracking           - full (per tab), domain (per site) or off");
+    println!("  apply_sites_to_apps            - Match site rules against site_apps' window titles (default false)");
+    println!("  site_apps                      - Apps site rules apply to, comma-separated ('off' clears)");
+    println!("  assistant_sites                - AI chat sites shown as assistant time ('off' clears, 'default' restores)");
+    println!("  assistant_counts_as            - focus, distraction or neutral (default neutral)");
+    println!("  date_format                    - strftime format for dates (default %b %d)");
+    println!("  show_utc_offset                - Note the UTC offset in shares and exports (true/false)");
+    println!("  show_ascii_art                 - Show the hourglass in shares and summaries (true/false)");
+    println!("  timezone                       - IANA timezone, e.g. Europe/Berlin (default system)");
+    println!("  report_width                   - Report width in columns, compact below 50 (default auto)");
+    println!("  display_glyphs                 - Bar characters: auto, unicode or ascii (default auto)");
+    println!("  fuzzy_min_score                - Lowest score a fuzzy match needs (default 40)");
+    println!("  fuzzy_ambiguity_margin         - Runner-ups this close make a fuzzy match ambiguous (default 5)");
+    println!("  budgets.<app or site>          - Daily time budget like 30m or 1h ('off' removes)");
+    println!("  budgets.<target>.period        - Budget period: day or week (default day)");
+    println!("  budgets.<target>.carry_over    - Unused daily time rolls into the next day (true/false)");
+    println!("  budgets.<target>.carry_over_cap - Most time carried into a day (default one day's limit)");
+    println!("  groups.<name>                  - Apps shown as one row by 'stats --by-group' ('off' removes)");
+    println!("  score.efficiency_weight        - Focus score weight of efficiency (default 0.5)");
+    println!("  score.switch_weight            - Focus score weight of the switch rate (default 0.25)");
+    println!("  score.block_weight             - Focus score weight of the longest block (default 0.25)");
+    println!("  score.switch_rate_reference    - Switches per hour that earn half credit (default 6)");
+    println!("  score.target_block_minutes     - Longest block that earns full credit (default 90)");
+    println!("  sounds.enabled                 - Play sound cues from the daemon (default false)");
+    println!("  sounds.focus_minutes           - A cue every this many minutes of focus (default 25)");
+    println!("  sounds.distraction_minutes     - A cue after this long on a distraction (default 10)");
+    println!("  sounds.focus_sound             - wav or ogg file for the focus cue ('beep' = built-in)");
+    println!("  sounds.distraction_sound       - wav or ogg file for the distraction cue ('beep' = built-in)");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt config set tracking_interval_ms 2000");
+    println!("  focusdebt config set save_interval_ms 60000");
+    println!("  focusdebt config set deep_focus_threshold_minutes 45");
+    println!("  focusdebt config set efficiency_floor 60");
+    println!("  focusdebt config set budgets.youtube 30m");
+    println!("  focusdebt config set budgets.youtube.period week");
+    println!("  focusdebt config set groups.rust-toolchain code,alacritty,zellij");
+    println!("  focusdebt config set score.target_block_minutes 60");
+    println!("  focusdebt config set time_format 12h");
+}
+
+fn show_database_help() {
+    println!("~=~ Database Commands:");
+    println!("  clear              - Clear all data from the database");
+    println!("  cleanup            - Clean up invalid sessions (--dry-run to preview)");
+    println!("  optimize           - Optimize the database");
+    println!("  prune              - Delete raw events older than --days (default 30) or --older-than 36h");
+    println!("  archive            - Move rows older than --older-than (default 180d) into an archive file (--to, --dry-run)");
+    println!("  dedupe             - Remove rows saved more than once, with the time removed per day (--dry-run)");
+    println!("  reclassify         - Rewrite stored focus flags from the current focus/ignore lists (--dry-run)");
+    println!("  trim-titles        - Cut stored window titles to max_title_length and VACUUM (--dry-run)");
+    println!("  check              - Run an integrity check and list rows quarantined for a wrong clock");
+    println!("  stats              - Show the database size, its growth per day and the disk space left");
+    println!("  quarantine restore - Move quarantined rows into the history as recorded");
+    println!("  quarantine drop    - Delete quarantined rows");
+    println!("  views install      - Create SQL views for 'focusdebt query' or any SQLite client");
+    println!("  views remove       - Drop the views");
+    println!("  views list         - Describe each view and its columns");
+    println!("  encrypt            - Encrypt the database in place (builds with --features encryption)");
+    println!("  decrypt            - Turn an encrypted database back into plain SQLite");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt database clear");
+    println!("  focusdebt database cleanup");
+    println!("  focusdebt database cleanup --dry-run");
+    println!("  focusdebt database optimize");
+    println!("  focusdebt database prune --days 14");
+    println!("  focusdebt database prune --older-than 2d12h");
+    println!("  focusdebt database archive --older-than 180d --to archive.db");
+    println!("  focusdebt database dedupe --dry-run");
+    println!("  focusdebt database reclassify --dry-run");
+    println!("  focusdebt database trim-titles --dry-run");
+    println!("  focusdebt database check");
+    println!("  focusdebt database stats");
+    println!("  focusdebt database quarantine drop");
+    println!("  focusdebt database views install");
+}
+
+fn show_session_help() {
+    println!("~=~ Session Commands:");
+    println!("  list               - List all sessions (--all-profiles to merge every profile)");
+    println!("  show <session_name> - Show details for a specific session (--json for scripts)");
+    println!("                       --raw lists every stored row and switch (--page N)");
+    println!("  export <session_name> - Export one session with raw rows (--format json|csv|markdown|html, --output)");
+    println!("  adopt <name> --date <YYYY-MM-DD> - Name a day's rows recorded without a session name");
+    println!("  browse             - Browse recent sessions: sort, filter, open, delete, rename, tag, export");
+    println!("  delete <session_name> - Delete a session's rows and tags (--yes skips the confirmation)");
+    println!("  rename <session_name> <new_name> - Give a session a new name");
+    println!("  tag <session_name> [tags...] - Show a session's tags, or replace them (\"off\" clears them)");
+    println!("  rate <session_name> <1-5> - Rate how a session went (--met yes|no for its intent)");
+    println!("  help               - Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  focusdebt sessions list");
+    println!("  focusdebt sessions show \"Morning Coding Session\"");
+    println!("  focusdebt sessions show \"Morning Coding Session\" --json");
+    println!("  focusdebt sessions show \"Morning Coding Session\" --raw --page 2");
+    println!("  focusdebt sessions export \"Morning Coding Session\" --format markdown --output session.md");
+    println!("  focusdebt sessions adopt \"Old notes\" --date 2024-05-21");
+    println!("  focusdebt sessions browse");
+    println!("  focusdebt sessions rename \"Morning Coding Session\" \"Parser rewrite\"");
+    println!("  focusdebt sessions tag \"Parser rewrite\" work, deep");
+}
+
+fn parse_shell(input: &str) -> Option<completions::Shell> {
+    let shell = completions::Shell::parse(input);
+    if shell.is_none() {
+        eprintln!("❌ Unknown shell '{}'. Use bash, zsh, fish or powershell.", input);
+    }
+    shell
+}
+
+fn print_completions(shell: Option<&str>) {
+    let Some(shell) = shell else {
+        eprintln!("❌ Name a shell: focusdebt completions <bash|zsh|fish|powershell>, or 'completions install <shell>'");
+        return;
+    };
+    let Some(shell) = parse_shell(shell) else { return };
+    print!("{}", completions::script(shell, &Cli::command()));
+}
+
+fn install_completions(shell: &str, dry_run: bool) {
+    let Some(shell) = parse_shell(shell) else { return };
+    let Some(dirs) = completions::UserDirs::from_env() else {
+        eprintln!("❌ Could not determine your home directory");
+        return;
+    };
+    let target = match completions::install_target(shell, &dirs) {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    };
+
+    let script = completions::script(shell, &Cli::command());
+    match completions::install(&target.path, &script, dry_run) {
+        Ok(completions::InstallOutcome::Unchanged) => {
+            println!("~=~ {} already holds the current {} completions", target.path.display(), shell);
+        }
+        Ok(outcome) => {
+            let verb = if dry_run { "Would write" } else { "Wrote" };
+            println!("~=~ {} {} completions to {}", verb, shell, target.path.display());
+            if let completions::InstallOutcome::Replaced { backup } = &outcome {
+                let verb = if dry_run { "would be moved" } else { "was moved" };
+                println!("~=~ The existing file {} to {}", verb, backup.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return;
+        }
+    }
+    match &target.rc_hint {
+        Some(hint) => println!("~=~ Still needed, once: {}", hint),
+        None if shell == completions::Shell::Bash => println!("~=~ Loaded by bash-completion in new shells; install that package if completions don't appear"),
+        None => println!("~=~ Picked up by new {} shells", shell),
+    }
+    if dry_run {
+        println!("~=~ Dry run, nothing was written");
+    }
+}
+
+/// `service install --with-watchdog`: a systemd user timer running
+/// `heartbeat-check` for the active profile every few minutes
+fn install_service(with_watchdog: bool, restart: bool, dry_run: bool) {
+    if !with_watchdog {
+        println!("~=~ Nothing to install; pass --with-watchdog for the heartbeat watchdog");
+        return;
+    }
+    if !cfg!(target_os = "linux") {
+        eprintln!("❌ The watchdog is a systemd user timer, which needs Linux");
+        return;
+    }
+    let Some(dirs) = completions::UserDirs::from_env() else {
+        eprintln!("❌ Could not determine your home directory");
+        return;
+    };
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            eprintln!("❌ Could not find the focusdebt executable: {}", e);
+            return;
+        }
+    };
+
+    let profile = utils::active_profile();
+    for unit in service::watchdog_units(&dirs, &exe, profile, restart) {
+        match completions::install(&unit.path, &unit.content, dry_run) {
+            Ok(completions::InstallOutcome::Unchanged) => {
+                println!("~=~ {} is up to date", unit.path.display());
+            }
+            Ok(outcome) => {
+                println!("~=~ {} {}", if dry_run { "Would write" } else { "Wrote" }, unit.path.display());
+                if let completions::InstallOutcome::Replaced { backup } = &outcome {
+                    let verb = if dry_run { "would be moved" } else { "was moved" };
+                    println!("~=~ The existing file {} to {}", verb, backup.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                return;
+            }
+        }
+    }
+    if dry_run {
+        println!("~=~ Dry run, nothing was written");
+        return;
+    }
+
+    let timer = format!("{}.timer", service::watchdog_name(profile));
+    let steps = [vec!["daemon-reload"], vec!["enable", "--now", timer.as_str()]];
+    if utils::find_program("systemctl").is_none() {
+        println!("~=~ systemctl not found; enable the timer with:");
+        for step in &steps {
+            println!("  systemctl --user {}", step.join(" "));
+        }
+        return;
+    }
+    for step in &steps {
+        if let Err(e) = run_systemctl(step) {
+            eprintln!("❌ 'systemctl --user {}' failed: {}", step.join(" "), e);
+            return;
+        }
+    }
+    println!("~=~ Watchdog enabled: the heartbeat is checked every {} minutes", service::WATCHDOG_INTERVAL_MINUTES);
+    if restart {
+        println!("~=~ A daemon that died is started again with its last session");
+    }
+}
+
+/// `service uninstall`: disable the watchdog timer and remove its units
+fn uninstall_service(dry_run: bool) {
+    let Some(dirs) = completions::UserDirs::from_env() else {
+        eprintln!("❌ Could not determine your home directory");
+        return;
+    };
+    let profile = utils::active_profile();
+    let installed: Vec<service::Unit> = service::watchdog_units(&dirs, std::path::Path::new("focusdebt"), profile, false)
+        .into_iter()
+        .filter(|unit| unit.path.exists())
+        .collect();
+    if installed.is_empty() {
+        println!("~=~ No watchdog units are installed");
+        return;
+    }
+    if dry_run {
+        for unit in &installed {
+            println!("~=~ Would remove {}", unit.path.display());
+        }
+        println!("~=~ Dry run, nothing was removed");
+        return;
+    }
+
+    let timer = format!("{}.timer", service::watchdog_name(profile));
+    let systemctl = utils::find_program("systemctl").is_some();
+    if systemctl {
+        if let Err(e) = run_systemctl(&["disable", "--now", &timer]) {
+            eprintln!("❌ 'systemctl --user disable --now {}' failed: {}", timer, e);
+        }
+    }
+    for unit in &installed {
+        match std::fs::remove_file(&unit.path) {
+            Ok(()) => println!("~=~ Removed {}", unit.path.display()),
+            Err(e) => eprintln!("❌ Failed to remove {}: {}", unit.path.display(), e),
+        }
+    }
+    if systemctl {
+        if let Err(e) = run_systemctl(&["daemon-reload"]) {
+            eprintln!("❌ 'systemctl --user daemon-reload' failed: {}", e);
+        }
+    }
+}
+
+fn run_systemctl(args: &[&str]) -> Result<(), String> {
+    let status = process::Command::new("systemctl").arg("--user").args(args).status().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("exited with {}", status))
+    }
+}
+
+/// What `heartbeat-check` makes of a heartbeat as of `now`
+#[derive(Debug, Clone, PartialEq)]
+enum HeartbeatCheck {
+    /// Beating; how long ago the last beat was
+    Alive(std::time::Duration),
+    /// Stale: what to tell the user, whether this is the first report of
+    /// it, and whether to start the daemon again
+    Stale { message: String, notify: bool, restart: bool },
+}
+
+/// Judge `heartbeat` as of `now`. `daemon_alive` is whether the daemon's
+/// process is still there: a hung daemon is reported but never restarted.
+fn judge_heartbeat(heartbeat: &utils::Heartbeat, now: chrono::DateTime<chrono::Utc>, daemon_alive: bool, restart: bool) -> HeartbeatCheck {
+    let Some(silent) = heartbeat.silent_for(now) else {
+        return HeartbeatCheck::Alive((now - heartbeat.at).to_std().unwrap_or_default());
+    };
+    let ago = utils::format_duration(silent, DurationStyle::Long);
+    let message = if daemon_alive {
+        format!("The daemon (PID {}) hasn't saved for {} and may be stuck", heartbeat.pid, ago)
+    } else {
+        format!("focusdebt stopped tracking {} ago", ago)
+    };
+    HeartbeatCheck::Stale { message, notify: heartbeat.reported_at.is_none(), restart: restart && !daemon_alive }
+}
+
+/// `heartbeat-check`: notify once when the detached daemon's heartbeat has
+/// gone stale, and with `restart` start a daemon that died again
+fn check_heartbeat(restart: bool) -> ExitCode {
+    let Some(mut heartbeat) = utils::read_heartbeat() else {
+        println!("~=~ No daemon heartbeat: the daemon isn't running or was stopped with 'focusdebt stop'");
+        return ExitCode::SUCCESS;
+    };
+    let now = chrono::Utc::now();
+    // A hung daemon still holds its PID; one that died left the PID file behind
+    let alive = is_daemon_running();
+    let (message, notify, restart) = match judge_heartbeat(&heartbeat, now, alive, restart) {
+        HeartbeatCheck::Alive(age) => {
+            println!("~=~ Daemon is alive: last heartbeat {} ago (session \"{}\")", utils::format_duration(age, DurationStyle::Long), heartbeat.session_name);
+            return ExitCode::SUCCESS;
+        }
+        HeartbeatCheck::Stale { message, notify, restart } => (message, notify, restart),
+    };
+    println!("~=~ {}", message);
+    if notify {
+        if let Some(check) = alerts::check_notifications() {
+            alerts::set_notifications_enabled(check.is_ok());
+        }
+        alerts::send_notification("FocusDebt: tracking stopped", &message);
+        heartbeat.reported_at = Some(now);
+        if let Err(e) = utils::write_heartbeat(&heartbeat) {
+            eprintln!("❌ Failed to write heartbeat: {}", e);
+        }
+    }
+
+    if alive {
+        println!("~=~ Not restarting a daemon that is still running; use 'focusdebt stop' and start it again");
+    } else if restart {
+        return restart_daemon(&heartbeat);
+    } else {
+        println!("~=~ Run 'focusdebt start' to track again");
+    }
+    ExitCode::SUCCESS
+}
+
+/// The session a restarted daemon tracks: the one it died in, unless daily
+/// sessions have moved past the day boundary since
+fn restart_session_name(heartbeat: &utils::Heartbeat, config: &Config, now: chrono::DateTime<chrono::Utc>) -> String {
+    if config.auto_daily_session {
+        utils::daily_session_name(now, config.day_start_hour)
+    } else {
+        heartbeat.session_name.clone()
+    }
+}
+
+/// Start the daemon again after it died, on the display and session it last
+/// reported. Timers run outside the graphical session, so the display comes
+/// from the heartbeat unless the environment already has one.
+fn restart_daemon(heartbeat: &utils::Heartbeat) -> ExitCode {
+    let inherited = ["DISPLAY", "WAYLAND_DISPLAY"].iter().any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()));
+    if !inherited {
+        if let Some(display) = heartbeat.wayland_display.as_ref().or(heartbeat.display.as_ref()) {
+            bind_display(display);
+        }
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config, using defaults: {}", e);
+            Config::default()
+        }
+    };
+    let config_fingerprint = Config::file_fingerprint();
+    if let Err(e) = ensure_data_directory() {
+        eprintln!("❌ Failed to create data directory: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let session_name = restart_session_name(heartbeat, &config, chrono::Utc::now());
+    println!("~=~ Restarting the daemon with session \"{}\"", session_name);
+    // The session already exists: it is the one being resumed
+    match launch_daemon(&session_name, true) {
+        Launch::Running(startup) => run_daemon(config, config_fingerprint, &session_name, startup),
+        Launch::NameTaken => {
+            eprintln!("❌ Session name '{}' was taken; run 'focusdebt start'", session_name);
+            ExitCode::SUCCESS
+        }
+        Launch::Started => ExitCode::SUCCESS,
+        Launch::Failed => ExitCode::FAILURE,
+    }
+}
+
+/// `mute <duration>` silences the daemon's sound cues until then, `mute off`
+/// lifts it; without an argument the current mute is shown
+fn mute_sounds(duration: Option<&str>) {
+    match duration {
+        None => match sounds::muted_until() {
+            Some(until) => println!("~=~ Sound cues {}", sounds::describe_mute(until)),
+            None => println!("~=~ Sound cues are not muted"),
+        },
+        Some(value) if value.eq_ignore_ascii_case("off") => match sounds::unmute() {
+            Ok(()) => println!("~=~ Sound cues unmuted"),
+            Err(e) => eprintln!("❌ Failed to unmute sound cues: {}", e),
+        },
+        Some(value) => {
+            let duration = match utils::parse_duration_arg(value) {
+                Ok(duration) if !duration.is_zero() => duration,
+                Ok(_) => {
+                    eprintln!("❌ Mute for longer than 0, or use 'mute off'");
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    return;
+                }
+            };
+            match sounds::mute(duration) {
+                Ok(until) => println!("~=~ Sound cues {}", sounds::describe_mute(until)),
+                Err(e) => eprintln!("❌ Failed to mute sound cues: {}", e),
+            }
+        }
+    }
+}
+
+fn show_main_help() {
+    println!("~=~ FocusDebt - CLI Focus Tracker");
+    println!("~=~ A CLI tool to track focus time and context switching");
+    println!();
+    println!("~=~ Main Commands:");
+    println!("  start              - Start background tracking daemon (--allow-duplicate to reuse a name)");
+    println!("                       --intent \"...\" and --expect 2h say what the session is for");
+    println!("                       --task 123 (taskwarrior) or --task \"text\" (todo.txt line) links a task");
+    println!("  stop               - Stop daemon and show session summary (--rating 1-5, --met yes|no)");
+    println!("  mute <duration>    - Silence sound cues, e.g. 'mute 1h' ('mute off' unmutes)");
+    println!("  track              - Track in the foreground until Ctrl-C (--name, -- <command>)");
+    println!("  stats              - Check stats for the previous session (--json, --switch-heatmap [--weeks N], --apply-current-rules, --by-group, --power ac|battery)");
+    println!("  stats --current    - The running session so far, and its efficiency if the recent pace continues");
+    println!("  stats workspaces   - Time and focus per workspace over the last --days (default 7, --json)");
+    println!("  stats task <task>  - Time and focus of every session started with --task <task> (--json)");
+    println!("  score              - Today's 0-100 focus score, its parts and the last 7 days");
+    println!("  estimate           - Today's focus at the end of the day at the usual pace, against the goal (--goal MIN, --json)");
+    println!("  share              - Nicer display of stats for sharing (--with-history for a trend footer)");
+    println!("                       --format oneline|tweet|badge for a one-liner, a post or an SVG badge");
+    println!("  export             - Export a per-day CSV summary (--format daily-csv, --apply-current-rules)");
+    println!("                       --anonymize writes per-day numbers only, as JSON for team aggregate");
+    println!("                       Long ranges to a file go a week at a time; --resume <manifest> continues one");
+    println!("  team aggregate <dir> - Combined stats from anonymized exports of 3+ people");
+    println!("  digest --week      - Plain-text digest of last week to a file (--mail-to addr to mail it)");
+    println!("  wrapped [year]     - Year in review (--format ascii|markdown|svg, --output file)");
+    println!("  query \"<sql>\"      - Run a read-only SELECT, e.g. on the 'database views' (--csv)");
+    println!("  events             - List raw tracker events (--from, --to, --kind)");
+    println!("  logs               - Show the daemon log (--tail N, --follow, --since 10m, --level warn; 'logs clear')");
+    println!("  status             - Show whether the daemon is running and its display");
+    println!("  doctor             - Check the setup for common problems (--clean removes files older versions left in /tmp)");
+    println!("  maintenance        - List runtime files and remove stale ones (--dry-run to only list them)");
+    println!("  reset --all        - Back up everything, then delete the data and config (--no-backup, --profiles)");
+    println!("  audit              - Sample the active window for --minutes (default 30) and compare with the daemon (--save)");
+    println!("  debug              - Report every window detection backend (--json for bug reports)");
+    println!("  debug state        - Show the running daemon's internals (--json, --watch)");
+    println!("  help               - Show this help message");
+    println!();
+    println!("~=~ Management Commands:");
+    println!("  focusapp <action>  - Manage focus applications");
+    println!("  focussite <action> - Manage focus websites");
+    println!("  rules stats        - Time by the focus rule that classified it (--days N, --json)");
+    println!("  config <action>    - Manage configuration");
+    println!("  sessions <action>  - Manage sessions");
+    println!("  database <action>  - Manage database");
+    println!("  import <source>    - Import history from other trackers (rescuetime <file.csv>, manual <file.csv>, legacy-log <file>)");
+    println!("  add-entry          - Record time away from the computer (--app, --start, --duration, --focus, --session)");
+    println!("  plan <action>      - Schedule sessions ahead of time (add, list)");
+    println!("  demo generate      - Fill an empty database with generated sessions (--days, --seed, --force)");
+    println!("  completions <shell> - Print a bash, zsh, fish or powershell completion script");
+    println!("  completions install <shell> - Write it where the shell looks for it (--dry-run)");
+    println!("  service install --with-watchdog - Get notified when the daemon dies (--restart, --dry-run)");
+    println!("  heartbeat-check    - Report a daemon that stopped without 'stop' (--restart to start it again)");
+    println!();
+    println!("~=~ Focus Apps:");
+    println!("  focusdebt focusapp add code    # Add VS Code as focus app");
+    println!("  focusdebt focusapp list        # View focus vs distraction apps");
+    println!();
+    println!("~=~ Configuration:");
+    println!("  focusdebt config show    # View current settings");
+    println!("  focusdebt config help    # Show config command help");
+    println!();
+    println!("~=~ Profiles:");
+    println!("  focusdebt --profile work start   # Separate database per profile");
+    println!();
+    println!("~=~ Get help for specific commands:");
+    println!("  focusdebt focusapp help  # Focus app management help");
+    println!("  focusdebt focussite help # Focus site management help");
+    println!("  focusdebt rules help     # Focus rule statistics help");
+    println!("  focusdebt config help    # Configuration help");
+    println!("  focusdebt sessions help  # Session management help");
+    println!("  focusdebt database help  # Database management help");
+    println!("  focusdebt service help   # Watchdog service help");
+    println!();
+    println!("Happy focusing! 🚀");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+    use tracking::WindowInfo;
+
+    type Committed = (Database, Option<DateTime<Utc>>, Vec<&'static str>);
+
+    /// The database thread's side of the handshake: commit every command
+    /// until the last sender is dropped, then report what was committed
+    fn database_thread(db_rx: mpsc::Receiver<DatabaseCommand>, spill_path: std::path::PathBuf) -> thread::JoinHandle<Committed> {
+        thread::spawn(move || {
+            let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+            let mut spill = spill::Spill::new(spill_path, 100, 1 << 20);
+            let mut last_committed = None;
+            let mut received = Vec::new();
+            for command in db_rx {
+                match command {
+                    DatabaseCommand::SaveSession(session) => {
+                        save_session(&mut spill, &db, session, None, 0, &mut last_committed).unwrap();
+                        received.push("session");
+                    }
+                    DatabaseCommand::SaveContextSwitch(switch) => {
+                        let written = switch.timestamp;
+                        commit_row(&mut spill, &db, spill::SpillRecord::ContextSwitch(switch), written, &mut last_committed).unwrap();
+                        received.push("switch");
+                    }
+                    DatabaseCommand::RecordEvent(event) => {
+                        spill.store(&db, spill::SpillRecord::event(event)).unwrap();
+                        received.push("event");
+                    }
+                    _ => received.push("other"),
+                }
+            }
+            (db, last_committed, received)
+        })
+    }
+
+    #[test]
+    fn stop_confirms_the_flush_only_once_every_row_is_committed() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-flush-{}", std::process::id()));
+        let started = Utc::now() - Duration::minutes(30);
+        let mut tracker = FocusTracker::new();
+        tracker.set_session_name("flush".to_string());
+        tracker.start_tracking();
+        // Rows completed since the last save, and the window still open
+        tracker.update_active_window_at(WindowInfo::new("editor", "main.rs"), started);
+        tracker.update_active_window_at(WindowInfo::new("browser", "docs"), started + Duration::minutes(10));
+        tracker.update_active_window_at(WindowInfo::new("editor", "lib.rs"), started + Duration::minutes(20));
+
+        let (tx, rx) = mpsc::channel();
+        let db_tx = DbSender { tx, queued: Arc::new(AtomicUsize::new(0)) };
+        let db_thread = database_thread(rx, dir.join("spill.jsonl"));
+
+        // `stop` is already waiting while the daemon drains
+        let marker: Arc<Mutex<Option<Option<DateTime<Utc>>>>> = Arc::new(Mutex::new(None));
+        let stop = {
+            let marker = Arc::clone(&marker);
+            thread::spawn(move || wait_for_flush(|| *marker.lock().unwrap(), FLUSH_TIMEOUT_MS))
+        };
+
+        drain_tracker(&mut tracker, &db_tx, "flush");
+        assert!(tracker.get_completed_sessions().is_empty() && tracker.get_context_switches().is_empty());
+        drop(db_tx);
+        let (db, last_committed, received) = db_thread.join().unwrap();
+        assert_eq!(received, ["session", "session", "session", "switch", "switch", "event"]);
+
+        // Everything is in the database before the marker is written
+        let sessions = db.get_sessions_between(started - Duration::minutes(1), Utc::now()).unwrap();
+        let apps: Vec<&str> = sessions.iter().map(|session| session.app_name.as_str()).collect();
+        assert_eq!(apps, ["editor", "browser", "editor"]);
+        assert_eq!(db.get_context_switches_between(started, Utc::now()).unwrap().len(), 2);
+        let stops = db.get_events_between(started, Utc::now(), Some(EventKind::DaemonStop)).unwrap();
+        assert_eq!(stops.len(), 1);
+        // The database keeps whole seconds
+        assert_eq!(last_committed.map(|t| t.timestamp()), sessions[2].end_time.map(|t| t.timestamp()));
+        assert!(!stop.is_finished());
+
+        *marker.lock().unwrap() = Some(last_committed);
+        assert_eq!(stop.join().unwrap(), Some(last_committed));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stop_gives_up_when_the_flush_is_never_confirmed() {
+        let waited = std::time::Instant::now();
+        assert_eq!(wait_for_flush(|| None, 300), None);
+        assert!(waited.elapsed() >= std::time::Duration::from_millis(300));
+
+        // Nothing was written, but the daemon did confirm
+        assert_eq!(wait_for_flush(|| Some(None), 300), Some(None));
+    }
+
+    #[test]
+    fn end_hook_environment_has_the_session_numbers() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let started = Utc::now() - Duration::hours(2);
+        let mut tracker = FocusTracker::new();
+        tracker.set_session_name("deep work".to_string());
+        tracker.add_focus_app("fd-editor".to_string());
+        tracker.start_tracking();
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), started);
+        tracker.update_active_window_at(WindowInfo::new("fd-chat", "general"), started + Duration::minutes(30));
+        tracker.suspend(started + Duration::minutes(40));
+        for session in tracker.take_completed_sessions() {
+            db.save_focus_session(&session).unwrap();
+        }
+
+        assert_eq!(session_end_env(&db, &StatsSettings::default(), "deep work"), [
+            ("FOCUSDEBT_SESSION_NAME", "deep work".to_string()),
+            ("FOCUSDEBT_FOCUS_SECONDS", "1800".to_string()),
+            ("FOCUSDEBT_EFFICIENCY", "75".to_string()),
+        ]);
+        // A session with no rows still gets its name
+        assert_eq!(session_end_env(&db, &StatsSettings::default(), "empty"), [("FOCUSDEBT_SESSION_NAME", "empty".to_string())]);
+    }
+
+    #[test]
+    fn daily_rollover_splits_the_open_row_at_the_boundary() {
+        let boundary = DateTime::parse_from_rfc3339("2024-05-22T02:00:00Z").unwrap().with_timezone(&Utc);
+        let mut tracker = FocusTracker::new();
+        tracker.set_session_name("2024-05-21".to_string());
+        tracker.start_tracking();
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), boundary - Duration::minutes(50));
+        tracker.update_active_window_at(WindowInfo::new("fd-term", "cargo test"), boundary - Duration::minutes(20));
+
+        let (tx, rx) = mpsc::channel();
+        let db_tx = DbSender { tx, queued: Arc::new(AtomicUsize::new(0)) };
+        roll_over_daemon_session(&mut tracker, &db_tx, "2024-05-22".to_string(), boundary, false);
+        drop(db_tx);
+
+        let commands: Vec<String> = rx.iter().map(|command| match command {
+            DatabaseCommand::SaveSession(row) => format!("row {} {} {}-{}", row.session_name, row.app_name,
+                row.start_time.format("%H:%M"), row.end_time.unwrap().format("%H:%M")),
+            DatabaseCommand::SaveContextSwitch(switch) => format!("switch {} {}", switch.from_app, switch.to_app),
+            DatabaseCommand::RollOver { from, to, at } => format!("rollover {} {} {}", from, to, at.format("%H:%M")),
+            _ => "other".to_string(),
+        }).collect();
+        // Everything of the old day goes ahead of the rollover, so its end hooks see it
+        assert_eq!(commands, [
+            "row 2024-05-21 fd-editor 01:10-01:40",
+            "row 2024-05-21 fd-term 01:40-02:00",
+            "switch fd-editor fd-term",
+            "rollover 2024-05-21 2024-05-22 02:00",
+        ]);
+
+        // The window still open carries on in the new day's session
+        assert_eq!(tracker.get_session_name(), "2024-05-22");
+        let current = tracker.get_current_session().unwrap();
+        assert_eq!((current.app_name.as_str(), current.session_name.as_str(), current.start_time), ("fd-term", "2024-05-22", boundary));
+        tracker.suspend(boundary + Duration::minutes(15));
+        let rows = tracker.take_completed_sessions();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].session_name.as_str(), rows[0].duration.as_secs()), ("2024-05-22", 900));
+        assert!(tracker.take_context_switches().is_empty());
+    }
+
+    #[test]
+    fn plans_start_on_an_idle_daemon_and_end_on_time() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let nine = DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc);
+        let id = db.add_planned_session("deep work", nine, std::time::Duration::from_secs(2 * 3600)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let db_tx = DbSender { tx, queued: Arc::new(AtomicUsize::new(0)) };
+
+        let mut tracker = FocusTracker::new();
+        tracker.set_session_name("review".to_string());
+        tracker.start_tracking();
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), nine - Duration::minutes(30));
+        let mut running = None;
+
+        // Not before its time, and never over a session started by hand
+        assert!(db.due_planned_session(nine - Duration::minutes(1)).unwrap().is_none());
+        let due = db.due_planned_session(nine).unwrap();
+        let (started, finished) = step_plans(&mut tracker, &db_tx, &mut running, due, "2024-05-21", nine, false);
+        assert!(started.is_none() && finished.is_none());
+        assert_eq!(tracker.get_session_name(), "review");
+
+        tracker.set_session_name("2024-05-21".to_string());
+        let start = nine + Duration::minutes(5);
+        let due = db.due_planned_session(start).unwrap();
+        let (started, finished) = step_plans(&mut tracker, &db_tx, &mut running, due, "2024-05-21", start, false);
+        assert_eq!(started.map(|plan| (plan.id, plan.name, plan.until)), Some((id, "deep work".to_string(), nine + Duration::hours(2))));
+        assert!(finished.is_none());
+        assert_eq!(tracker.get_session_name(), "deep work");
+        db.mark_plan_started(id, start).unwrap();
+        assert!(db.due_planned_session(start).unwrap().is_none());
+
+        // Still running a minute before the end
+        let (started, finished) = step_plans(&mut tracker, &db_tx, &mut running, None, "2024-05-21", nine + Duration::minutes(119), false);
+        assert!(started.is_none() && finished.is_none());
+
+        // A late check still ends the plan at its planned end
+        let late = nine + Duration::minutes(121);
+        let (_, finished) = step_plans(&mut tracker, &db_tx, &mut running, None, "2024-05-21", late, false);
+        assert_eq!(finished.map(|plan| (plan.id, plan.until)), Some((id, nine + Duration::hours(2))));
+        assert!(running.is_none());
+        assert_eq!(tracker.get_session_name(), "2024-05-21");
+        drop(db_tx);
+
+        let rollovers: Vec<String> = rx.iter().filter_map(|command| match command {
+            DatabaseCommand::RollOver { from, to, at } => Some(format!("{} -> {} {}", from, to, at.format("%H:%M"))),
+            _ => None,
+        }).collect();
+        assert_eq!(rollovers, ["2024-05-21 -> deep work 09:05", "deep work -> 2024-05-21 11:00"]);
+    }
+
+    #[test]
+    fn json_batches_apply_whole_or_not_at_all() {
+        let config = Config::default();
+        let (applied, keys) = apply_json_batch(&config, r#"{"tracking_interval_ms": 2000, "focus_apps": ["fd-editor", "fd-term"], "quiet_hours": "22:00-07:00"}"#).unwrap();
+        assert_eq!(keys, 3);
+        assert_eq!(applied.tracking_interval_ms, 2000);
+        assert_eq!(applied.focus_apps, ["fd-editor", "fd-term"]);
+        assert_eq!(applied.quiet_hours, ["22:00-07:00"]);
+
+        // One bad key rejects the lot, and names every problem
+        let errors = apply_json_batch(&config, r#"{"tracking_interval_ms": 2000, "quiet_hours": "late", "no_such_key": 1, "focus_apps": "fd-editor"}"#).unwrap_err();
+        assert_eq!(errors.len(), 4, "{:#?}", errors);
+        assert!(errors.iter().any(|error| error.contains("quiet_hours")), "{:#?}", errors);
+        assert!(errors.iter().any(|error| error.contains("focus_apps")), "{:#?}", errors);
+        assert!(errors.iter().any(|error| error.contains("no_such_key")), "{:#?}", errors);
+        assert_eq!(errors.last().unwrap(), "Nothing was changed: 3 of 4 keys are invalid");
+        assert_eq!(config.tracking_interval_ms, Config::default().tracking_interval_ms);
+
+        assert!(apply_json_batch(&config, "[1, 2]").unwrap_err()[0].starts_with("--json takes an object"));
+        assert!(apply_json_batch(&config, "{").unwrap_err()[0].starts_with("Invalid JSON"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn broken_integrations_are_turned_off_and_the_rest_kept() {
+        let mut config = Config {
+            on_session_start: vec!["true".to_string(), "focusdebt-no-such-program --sync".to_string()],
+            on_session_end: vec!["/nonexistent/hook".to_string()],
+            count_notifications: true,
+            notification_command: Some("focusdebt-no-such-monitor".to_string()),
+            tmux_integration: false,
+            track_input_activity: false,
+            ..Config::default()
+        };
+        let tracking_interval_ms = config.tracking_interval_ms;
+
+        let integrations = check_integrations(&mut config);
+        let status = |name: &str| integrations.iter().find(|integration| integration.name == name).cloned();
+        assert_eq!(status("hooks").unwrap().problem.as_deref(),
+            Some("on_session_start: 'focusdebt-no-such-program' not found; on_session_end: '/nonexistent/hook' does not exist"));
+        assert_eq!(config.on_session_start, ["true"]);
+        assert!(config.on_session_end.is_empty());
+        assert_eq!(status("notification counting").unwrap().to_string(),
+            "notification counting ✗ ('focusdebt-no-such-monitor' not found)");
+        assert!(!config.count_notifications);
+        assert!(status("tmux").is_none() && status("input activity").is_none());
+        assert_eq!(config.tracking_interval_ms, tracking_interval_ms);
+
+        // Working ones stay on and show as such
+        config.count_notifications = true;
+        config.notification_command = Some("true".to_string());
+        let integrations = check_integrations(&mut config);
+        assert!(config.count_notifications);
+        let described = utils::describe_integrations(&integrations);
+        assert!(described.contains("hooks ✓") && described.contains("notification counting ✓"), "{}", described);
+    }
+
+    fn heartbeat(at: DateTime<Utc>) -> utils::Heartbeat {
+        utils::Heartbeat {
+            pid: 4242,
+            session_name: "fd-writing".to_string(),
+            at,
+            save_interval_ms: 30_000,
+            display: Some(":0".to_string()),
+            wayland_display: None,
+            reported_at: None,
+        }
+    }
+
+    #[test]
+    fn stale_heartbeats_are_reported_once_and_restarted_only_when_dead() {
+        let beat = DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc);
+        let dead = heartbeat(beat);
+
+        // Fresh up to the staleness window, whether or not the process is there
+        assert_eq!(judge_heartbeat(&dead, beat + Duration::seconds(90), false, true), HeartbeatCheck::Alive(std::time::Duration::from_secs(90)));
+        assert!(matches!(judge_heartbeat(&dead, beat + Duration::minutes(2), false, true), HeartbeatCheck::Alive(_)));
+
+        let later = beat + Duration::seconds(12 * 60 + 30);
+        assert_eq!(judge_heartbeat(&dead, later, false, true), HeartbeatCheck::Stale {
+            message: "focusdebt stopped tracking 12m 30s ago".to_string(),
+            notify: true,
+            restart: true,
+        });
+        assert_eq!(judge_heartbeat(&dead, later, false, false), HeartbeatCheck::Stale {
+            message: "focusdebt stopped tracking 12m 30s ago".to_string(),
+            notify: true,
+            restart: false,
+        });
+
+        // Reported already: the next check neither notifies nor forgets to restart
+        let reported = utils::Heartbeat { reported_at: Some(later), ..dead.clone() };
+        assert!(matches!(judge_heartbeat(&reported, later + Duration::minutes(5), false, true),
+            HeartbeatCheck::Stale { notify: false, restart: true, .. }));
+
+        // A hung daemon is only reported
+        assert_eq!(judge_heartbeat(&dead, later, true, true), HeartbeatCheck::Stale {
+            message: "The daemon (PID 4242) hasn't saved for 12m 30s and may be stuck".to_string(),
+            notify: true,
+            restart: false,
+        });
+    }
+
+    #[test]
+    fn a_restarted_daemon_resumes_its_session_unless_the_day_moved_on() {
+        let now = DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc);
+        let beat = heartbeat(now - Duration::hours(1));
+        assert_eq!(restart_session_name(&beat, &Config::default(), now), "fd-writing");
+        let daily = Config { auto_daily_session: true, day_start_hour: 4, ..Config::default() };
+        assert_eq!(restart_session_name(&beat, &daily, now), utils::daily_session_name(now, 4));
+    }
+
+    #[test]
+    fn a_clock_jump_between_checks_is_one_suspend() {
+        let start: DateTime<Utc> = "2024-05-21T09:00:00Z".parse().unwrap();
+        let min_gap = SUSPEND_GAP;
+        // Checks every two seconds, a two-hour jump, then two seconds again
+        let checks = [0, 2, 4, 2 * 3600 + 4, 2 * 3600 + 6].map(|seconds| start + Duration::seconds(seconds));
+        let gaps: Vec<stats::SuspendGap> = checks.windows(2).filter_map(|pair| detect_suspend(pair[0], pair[1], min_gap)).collect();
+        assert_eq!(gaps, [stats::SuspendGap { start: checks[2], end: checks[3] }]);
+        assert_eq!(gaps[0].duration(), std::time::Duration::from_secs(2 * 3600));
+
+        // Just under the threshold, or the clock going back, is no suspend
+        assert!(detect_suspend(start, start + Duration::seconds(59), min_gap).is_none());
+        assert!(detect_suspend(start, start + Duration::seconds(60), min_gap).is_some());
+        assert!(detect_suspend(start, start - Duration::hours(2), min_gap).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn startup_replies_are_read_until_the_daemon_closes_the_pipe() {
+        let reply_to = |written: &[u8]| unsafe {
+            let mut fds = [0 as libc::c_int; 2];
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+            libc::write(fds[1], written.as_ptr() as *const libc::c_void, written.len());
+            libc::close(fds[1]);
+            let reply = read_startup_reply(fds[0]);
+            libc::close(fds[0]);
+            reply
+        };
+        assert_eq!(reply_to(b"ok").as_deref(), Some("ok"));
+        assert_eq!(reply_to(b"error:failed to open the database: locked").as_deref(), Some("error:failed to open the database: locked"));
+        // Longer than one read
+        let long = format!("error:{}", "x".repeat(3000));
+        assert_eq!(reply_to(long.as_bytes()), Some(long));
+        // The daemon died without a word
+        assert_eq!(reply_to(b"").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn session_outcomes_come_from_flags_and_are_only_asked_for_at_a_terminal() {
+        let outcome = |rating, met, ask, typed: &str| session_outcome(rating, met, ask, &mut typed.as_bytes());
+        // Flags are enough without a terminal, and nothing is read
+        assert_eq!(outcome(Some(4), Some(true), false, "1\nn\n"), Some((4, Some(true))));
+        assert_eq!(outcome(Some(4), None, false, "n\n"), Some((4, None)));
+        // Without a terminal and without --rating there is nothing to save
+        assert_eq!(outcome(None, Some(true), false, "4\ny\n"), None);
+
+        assert_eq!(outcome(None, None, true, "4\nyes\n"), Some((4, Some(true))));
+        assert_eq!(outcome(Some(3), None, true, "N\n"), Some((3, Some(false))));
+        assert_eq!(outcome(None, Some(false), true, "2\n"), Some((2, Some(false))));
+        // Out of range and junk ask again; Enter or end of input skips
+        assert_eq!(outcome(None, None, true, "9\nfour\n0\n5\nmaybe\n"), Some((5, None)));
+        assert_eq!(outcome(None, None, true, "7\n\n4\n"), None);
+        assert_eq!(outcome(None, None, true, ""), None);
+        assert_eq!(outcome(None, None, true, "3\n"), Some((3, None)));
+    }
+
+    fn sighting(app_name: &str, days_ago: i64) -> storage::AppSighting {
+        let last_seen = DateTime::parse_from_rfc3339("2024-03-20T12:00:00Z").unwrap().with_timezone(&Utc) - Duration::days(days_ago);
+        storage::AppSighting { app_name: app_name.to_string(), first_seen: last_seen - Duration::days(30), last_seen, total: std::time::Duration::from_secs(3600) }
+    }
+
+    #[test]
+    fn headless_focusapp_add_matches_tracked_history_then_plausible_names() {
+        let history = || vec![sighting("fd-editor", 2), sighting("fd-chat-desktop", 10), sighting("fd-chat-web", 1), sighting("Fd-Term", 5)];
+        // A unique match in history is added by its tracked name
+        assert_eq!(match_tracked_app("editor", false, history()), Some("fd-editor".to_string()));
+        assert_eq!(match_tracked_app("fd-term", false, history()), Some("Fd-Term".to_string()));
+        // Two close matches add nothing
+        assert_eq!(match_tracked_app("fd-chat", false, history()), None);
+        // Nothing tracked matches: a process-like name is added as typed
+        assert_eq!(match_tracked_app("zed", false, history()), Some("zed".to_string()));
+        assert_eq!(match_tracked_app("zed", false, Vec::new()), Some("zed".to_string()));
+        // One that isn't needs --allow-literal
+        assert_eq!(match_tracked_app("Visual Studio Code", false, Vec::new()), None);
+        assert_eq!(match_tracked_app("Zed", false, Vec::new()), None);
+        assert_eq!(match_tracked_app("Visual Studio Code", true, Vec::new()), Some("Visual Studio Code".to_string()));
+    }
+
+    fn tracked(start: &str, app: &str) -> tracking::FocusSession {
+        let start_time = DateTime::parse_from_rfc3339(start).unwrap().with_timezone(&Utc);
+        tracking::FocusSession {
+            start_time,
+            end_time: Some(start_time + Duration::minutes(10)),
+            app_name: app.to_string(),
+            window_title: format!("{} window", app),
+            domain: None,
+            duration: std::time::Duration::from_secs(600),
+            is_focus_app: true,
+            session_name: "clock".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: tracking::OpenEnd::default(),
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    #[test]
+    fn sessions_far_from_the_last_seen_time_are_quarantined() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-clock-{}", std::process::id()));
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let mut spill = spill::Spill::new(dir.join("spill.jsonl"), 100, 1 << 20);
+        let mut last_committed = None;
+        let seen = Some(DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z").unwrap().with_timezone(&Utc));
+        let mut save = |start: &str, app: &str, max_days: u32| {
+            save_session(&mut spill, &db, tracked(start, app), seen, max_days, &mut last_committed).unwrap()
+        };
+
+        // A VM that booted ten years ahead, and one whose clock reset to 2016
+        let ahead = save("2036-03-01T12:00:00Z", "fd-ahead", 30);
+        let behind = save("2016-03-01T12:00:00Z", "fd-behind", 30);
+        let SessionSaved::Quarantined(reason) = &ahead else { panic!("{:?}", ahead) };
+        assert_eq!(reason, "starts 3653d0h after the last seen time (2026-03-01T12:00:00+00:00)");
+        let SessionSaved::Quarantined(reason) = &behind else { panic!("{:?}", behind) };
+        assert_eq!(reason, "starts 3652d0h before the last seen time (2026-03-01T12:00:00+00:00)");
+
+        // Within range, or with the check off, rows are saved as usual
+        assert_eq!(save("2026-03-29T12:00:00Z", "fd-editor", 30), SessionSaved::Stored(spill::Stored::Database));
+        assert_eq!(save("2016-03-01T12:00:00Z", "fd-unchecked", 0), SessionSaved::Stored(spill::Stored::Database));
+
+        // `database check` lists the held back rows; the history has the rest
+        let quarantined = db.get_quarantined_sessions().unwrap();
+        let held: Vec<&str> = quarantined.iter().map(|entry| entry.session.app_name.as_str()).collect();
+        assert_eq!(held, ["fd-behind", "fd-ahead"]);
+        assert!(quarantined[1].reason.contains("after the last seen time"));
+        let start = DateTime::parse_from_rfc3339("2000-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let saved: Vec<String> = db.get_sessions_between(start, start + Duration::days(365 * 50)).unwrap()
+            .into_iter().map(|session| session.app_name).collect();
+        assert_eq!(saved, ["fd-unchecked", "fd-editor"]);
+
+        assert_eq!(db.restore_quarantined_sessions().unwrap(), 2);
+        assert!(db.get_quarantined_sessions().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_set_rejects_a_date_format_chrono_cant_render() {
+        let mut config = Config::default();
+        match apply_config_value(&mut config, "date_format", "%d.%Q") {
+            Err(ConfigValueError::Invalid(message)) => assert_eq!(
+                message,
+                "Invalid value for date_format: '%d.%Q' is not a valid strftime format. Use strftime codes like %b %d or %d.%m.",
+            ),
+            _ => panic!("%d.%Q was accepted"),
+        }
+        assert_eq!(config.date_format, utils::DEFAULT_DATE_FORMAT);
+
+        assert!(apply_config_value(&mut config, "date_format", "%d.%m.").is_ok());
+        assert_eq!(config.date_format, "%d.%m.");
+    }
+}
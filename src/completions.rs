@@ -0,0 +1,341 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shells `focusdebt completions` writes scripts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Powershell => "powershell",
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Directories the install location is worked out from; the environment's
+/// unless overridden
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserDirs {
+    pub home: PathBuf,
+    /// $XDG_DATA_HOME, when set
+    pub data_home: Option<PathBuf>,
+    /// $XDG_CONFIG_HOME, when set
+    pub config_home: Option<PathBuf>,
+}
+
+impl UserDirs {
+    pub fn from_env() -> Option<Self> {
+        let xdg = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        Some(UserDirs {
+            home: dirs::home_dir()?,
+            data_home: xdg("XDG_DATA_HOME"),
+            config_home: xdg("XDG_CONFIG_HOME"),
+        })
+    }
+
+    fn data_home(&self) -> PathBuf {
+        self.data_home.clone().unwrap_or_else(|| self.home.join(".local").join("share"))
+    }
+
+    pub fn config_home(&self) -> PathBuf {
+        self.config_home.clone().unwrap_or_else(|| self.home.join(".config"))
+    }
+}
+
+/// Where a shell's script goes for one user, and what the user still has
+/// to add to their shell startup file, if anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallTarget {
+    pub path: PathBuf,
+    pub rc_hint: Option<String>,
+}
+
+/// The conventional per-user location for `shell`'s script. Anything that
+/// resolves outside the home directory (e.g. XDG_DATA_HOME=/usr/share) is
+/// refused; system-wide completion directories are left to packages.
+pub fn install_target(shell: Shell, dirs: &UserDirs) -> Result<InstallTarget, String> {
+    let target = match shell {
+        // bash-completion loads files from here on demand
+        Shell::Bash => InstallTarget {
+            path: dirs.data_home().join("bash-completion").join("completions").join("focusdebt"),
+            rc_hint: None,
+        },
+        Shell::Zsh => InstallTarget {
+            path: dirs.home.join(".zfunc").join("_focusdebt"),
+            rc_hint: Some("fpath=(~/.zfunc $fpath)  # in ~/.zshrc, before compinit".to_string()),
+        },
+        Shell::Fish => InstallTarget {
+            path: dirs.config_home().join("fish").join("completions").join("focusdebt.fish"),
+            rc_hint: None,
+        },
+        // Kept beside the profile rather than in it, so the profile itself is never rewritten
+        Shell::Powershell => {
+            let profile_dir = if cfg!(windows) {
+                dirs.home.join("Documents").join("PowerShell")
+            } else {
+                dirs.config_home().join("powershell")
+            };
+            let path = profile_dir.join("focusdebt-completions.ps1");
+            InstallTarget {
+                rc_hint: Some(format!(". \"{}\"  # in {}", path.display(), profile_dir.join("Microsoft.PowerShell_profile.ps1").display())),
+                path,
+            }
+        }
+    };
+    if !is_within(&target.path, &dirs.home) {
+        return Err(format!("{} is outside your home directory; refusing to write system-wide", target.path.display()));
+    }
+    Ok(target)
+}
+
+/// Whether `path` lies under `dir`, ignoring `..` tricks
+fn is_within(path: &Path, dir: &Path) -> bool {
+    let normalize = |path: &Path| {
+        let mut parts: Vec<std::path::Component> = Vec::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    parts.pop();
+                }
+                std::path::Component::CurDir => {}
+                component => parts.push(component),
+            }
+        }
+        parts.into_iter().collect::<PathBuf>()
+    };
+    path.is_absolute() && normalize(path).starts_with(normalize(dir))
+}
+
+/// What `install` did, or with `dry_run` would do
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallOutcome {
+    Written,
+    /// The existing file was moved here first
+    Replaced { backup: PathBuf },
+    /// The file already holds this script
+    Unchanged,
+}
+
+/// Write `script` to `path`, moving a different existing file to
+/// `<path>.bak` first. With `dry_run` nothing is touched.
+pub fn install(path: &Path, script: &str, dry_run: bool) -> Result<InstallOutcome, String> {
+    let outcome = match fs::read(path) {
+        Ok(existing) if existing == script.as_bytes() => return Ok(InstallOutcome::Unchanged),
+        Ok(_) => {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            InstallOutcome::Replaced { backup: PathBuf::from(backup) }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => InstallOutcome::Written,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if dry_run {
+        return Ok(outcome);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    if let InstallOutcome::Replaced { backup } = &outcome {
+        fs::rename(path, backup).map_err(|e| format!("Failed to back up {} to {}: {}", path.display(), backup.display(), e))?;
+    }
+    fs::write(path, script).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(outcome)
+}
+
+/// One command of the CLI, e.g. "focusdebt config", with what can follow it
+struct CompletionNode {
+    path: String,
+    words: Vec<String>,
+}
+
+/// Every command path with its subcommands and long flags, walked from
+/// the clap definition so the scripts can't drift from it
+fn completion_nodes(command: &clap::Command) -> Vec<CompletionNode> {
+    fn walk(command: &clap::Command, path: String, nodes: &mut Vec<CompletionNode>) {
+        let mut words: Vec<String> = command.get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| sub.get_name().to_string())
+            .collect();
+        words.extend(command.get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long)));
+        nodes.push(CompletionNode { path: path.clone(), words });
+        for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+            walk(sub, format!("{} {}", path, sub.get_name()), nodes);
+        }
+    }
+    let mut nodes = Vec::new();
+    walk(command, command.get_name().to_string(), &mut nodes);
+    nodes
+}
+
+/// The completion script for `shell`, covering subcommands and long flags
+pub fn script(shell: Shell, command: &clap::Command) -> String {
+    let name = command.get_name();
+    let nodes = completion_nodes(command);
+    // Paths a word can extend, i.e. every command but the root
+    let subcommands: Vec<&str> = nodes.iter().skip(1).map(|node| node.path.as_str()).collect();
+    match shell {
+        Shell::Bash => {
+            let mut text = format!("# {} completions for bash\n_{}() {{\n", name, name);
+            text.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" cmd_path=\"");
+            text.push_str(name);
+            text.push_str("\" word\n    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n        case \"$cmd_path $word\" in\n");
+            text.push_str(&format!("            {}) cmd_path=\"$cmd_path $word\" ;;\n", quoted_alternatives(&subcommands, "|")));
+            text.push_str("        esac\n    done\n    case \"$cmd_path\" in\n");
+            for node in &nodes {
+                text.push_str(&format!("        \"{}\") COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n", node.path, node.words.join(" ")));
+            }
+            text.push_str(&format!("    esac\n}}\ncomplete -F _{} {}\n", name, name));
+            text
+        }
+        Shell::Zsh => {
+            let mut text = format!("#compdef {}\n# {} completions for zsh\n", name, name);
+            text.push_str(&format!("local cmd_path=\"{}\" word\n", name));
+            text.push_str("for word in ${words[2,CURRENT-1]}; do\n    case \"$cmd_path $word\" in\n");
+            text.push_str(&format!("        ({}) cmd_path=\"$cmd_path $word\" ;;\n", quoted_alternatives(&subcommands, "|")));
+            text.push_str("    esac\ndone\ncase \"$cmd_path\" in\n");
+            for node in &nodes {
+                text.push_str(&format!("    (\"{}\") compadd -- {} ;;\n", node.path, node.words.join(" ")));
+            }
+            text.push_str("esac\n");
+            text
+        }
+        Shell::Fish => {
+            let mut text = format!("# {} completions for fish\nfunction __{}_path\n", name, name);
+            text.push_str(&format!("    set -l cmd_path {}\n", name));
+            text.push_str("    for word in (commandline -opc)[2..-1]\n        switch \"$cmd_path $word\"\n");
+            text.push_str(&format!("            case {}\n", quoted_alternatives(&subcommands, " ")));
+            text.push_str("                set cmd_path \"$cmd_path $word\"\n        end\n    end\n    echo $cmd_path\nend\n");
+            text.push_str(&format!("complete -c {} -f\n", name));
+            for node in nodes.iter().filter(|node| !node.words.is_empty()) {
+                text.push_str(&format!("complete -c {} -n 'test (__{}_path) = \"{}\"' -a '{}'\n", name, name, node.path, node.words.join(" ")));
+            }
+            text
+        }
+        Shell::Powershell => {
+            let mut text = format!("# {} completions for PowerShell\n", name);
+            text.push_str(&format!("Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{\n", name));
+            text.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n    $candidates = @{\n");
+            for node in &nodes {
+                let words: Vec<String> = node.words.iter().map(|word| format!("'{}'", word)).collect();
+                text.push_str(&format!("        '{}' = @({})\n", node.path, words.join(", ")));
+            }
+            text.push_str("    }\n");
+            text.push_str(&format!("    $path = '{}'\n", name));
+            text.push_str("    foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {\n");
+            // The word under the cursor is the one being completed
+            text.push_str("        if ($element.Extent.EndOffset -ge $cursorPosition) { break }\n");
+            text.push_str("        $word = $element.ToString()\n");
+            text.push_str("        if ($candidates.ContainsKey(\"$path $word\")) { $path = \"$path $word\" }\n    }\n");
+            text.push_str("    $candidates[$path] | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n");
+            text.push_str("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n    }\n}\n");
+            text
+        }
+    }
+}
+
+fn quoted_alternatives(paths: &[&str], separator: &str) -> String {
+    paths.iter().map(|path| format!("\"{}\"", path)).collect::<Vec<_>>().join(separator)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn home(data_home: Option<&str>, config_home: Option<&str>) -> UserDirs {
+        UserDirs {
+            home: PathBuf::from("/home/fd-user"),
+            data_home: data_home.map(PathBuf::from),
+            config_home: config_home.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn install_targets_follow_each_shell_and_xdg() {
+        let dirs = home(None, None);
+        let target = |shell: Shell, dirs: &UserDirs| install_target(shell, dirs).map(|target| (target.path.display().to_string(), target.rc_hint));
+
+        assert_eq!(target(Shell::Bash, &dirs), Ok(("/home/fd-user/.local/share/bash-completion/completions/focusdebt".to_string(), None)));
+        assert_eq!(target(Shell::Zsh, &dirs), Ok(("/home/fd-user/.zfunc/_focusdebt".to_string(),
+            Some("fpath=(~/.zfunc $fpath)  # in ~/.zshrc, before compinit".to_string()))));
+        assert_eq!(target(Shell::Fish, &dirs), Ok(("/home/fd-user/.config/fish/completions/focusdebt.fish".to_string(), None)));
+        assert_eq!(target(Shell::Powershell, &dirs), Ok(("/home/fd-user/.config/powershell/focusdebt-completions.ps1".to_string(),
+            Some(". \"/home/fd-user/.config/powershell/focusdebt-completions.ps1\"  # in /home/fd-user/.config/powershell/Microsoft.PowerShell_profile.ps1".to_string()))));
+
+        // XDG directories inside the home are followed
+        let xdg = home(Some("/home/fd-user/xdg/data"), Some("/home/fd-user/xdg/config"));
+        assert_eq!(target(Shell::Bash, &xdg).unwrap().0, "/home/fd-user/xdg/data/bash-completion/completions/focusdebt");
+        assert_eq!(target(Shell::Fish, &xdg).unwrap().0, "/home/fd-user/xdg/config/fish/completions/focusdebt.fish");
+        assert_eq!(target(Shell::Zsh, &xdg).unwrap().0, "/home/fd-user/.zfunc/_focusdebt");
+    }
+
+    #[test]
+    fn system_wide_targets_are_refused() {
+        let system = home(Some("/usr/share"), Some("/home/fd-user/../../etc"));
+        assert_eq!(install_target(Shell::Bash, &system).unwrap_err(),
+            "/usr/share/bash-completion/completions/focusdebt is outside your home directory; refusing to write system-wide");
+        assert!(install_target(Shell::Fish, &system).is_err());
+        assert!(install_target(Shell::Zsh, &system).is_ok());
+        assert!(install_target(Shell::Bash, &home(Some("relative/data"), None)).is_err());
+
+        assert!(is_within(Path::new("/home/fd-user/./a/../b"), Path::new("/home/fd-user")));
+        assert!(!is_within(Path::new("/home/fd-user-other/b"), Path::new("/home/fd-user")));
+    }
+
+    #[test]
+    fn install_backs_up_a_different_file_and_honours_dry_run() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-completions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("completions").join("focusdebt");
+        let backup = dir.join("completions").join("focusdebt.bak");
+
+        assert_eq!(install(&path, "new", true), Ok(InstallOutcome::Written));
+        assert!(!dir.exists());
+        assert_eq!(install(&path, "new", false), Ok(InstallOutcome::Written));
+        assert_eq!(install(&path, "new", false), Ok(InstallOutcome::Unchanged));
+
+        assert_eq!(install(&path, "newer", true), Ok(InstallOutcome::Replaced { backup: backup.clone() }));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!backup.exists());
+        assert_eq!(install(&path, "newer", false), Ok(InstallOutcome::Replaced { backup: backup.clone() }));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newer");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "new");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shells_parse_by_name() {
+        assert_eq!(Shell::parse(" Pwsh"), Some(Shell::Powershell));
+        assert_eq!(Shell::parse("zsh").map(|shell| shell.to_string()).as_deref(), Some("zsh"));
+        assert_eq!(Shell::parse("tcsh"), None);
+    }
+}
@@ -1,7 +1,17 @@
 use serde::{Deserialize, Serialize};
+use chrono::{Datelike, Timelike};
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::fs;
-use std::path::PathBuf;
-use dirs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::desktop;
+use crate::spill;
+use crate::tracking::{self, FocusSession};
+use crate::utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -11,9 +21,57 @@ pub struct Config {
     #[serde(default = "default_save_interval")]
     pub save_interval_ms: u64,
     
+    /// Window detection backend to use, as named by `focusdebt debug`;
+    /// unset picks one automatically and fails over when it stops working
+    #[serde(default)]
+    pub backend: Option<String>,
+    
+    /// Let a pinned backend fail over to another when it keeps failing
+    #[serde(default)]
+    pub detection_fallback: bool,
+    
+    /// Let the daemon fall back to scanning for running GUI apps when no
+    /// backend can read the focused window. The app it finds is a guess,
+    /// not the focused one, so this is off by default.
+    #[serde(default)]
+    pub allow_process_scan_fallback: bool,
+    
+    /// Completed sessions plus switches the tracker holds before it saves
+    /// early instead of waiting for the save interval
+    #[serde(default = "default_max_buffered_rows")]
+    pub max_buffered_rows: usize,
+    
     #[serde(default = "default_deep_focus_threshold")]
     pub deep_focus_threshold_minutes: u64,
     
+    /// Window visits shorter than this are not saved at all
+    #[serde(default = "default_min_record_seconds")]
+    pub min_record_seconds: u64,
+    
+    /// Reports fold apps and tabs below this into one "other" line
+    #[serde(default = "default_min_display_seconds")]
+    pub min_display_seconds: u64,
+    
+    /// A switch A→B→A that returns within this many seconds counts as a
+    /// round trip in the reports
+    #[serde(default = "default_round_trip_window_seconds")]
+    pub round_trip_window_seconds: u64,
+    
+    /// Reports and exports join rows of the same window separated by less
+    /// than this many seconds (0 turns it off)
+    #[serde(default = "default_merge_gap_seconds")]
+    pub merge_gap_seconds: u64,
+    
+    /// Minutes a switch away from focus is assumed to cost when no recovery
+    /// time was measured for it
+    #[serde(default = "default_switch_cost_minutes")]
+    pub switch_cost_minutes: u64,
+    
+    /// Count switches without a measured recovery at switch_cost_minutes in
+    /// the switching cost estimate; off leaves them out
+    #[serde(default = "default_switch_cost_unmeasured")]
+    pub switch_cost_unmeasured: bool,
+    
     #[serde(default)]
     pub focus_apps: Vec<String>,
     
@@ -26,9 +84,274 @@ pub struct Config {
     #[serde(default)]
     pub ignored_sites: Vec<String>,
     
+    /// Regex patterns for window titles that should never be shown or suggested
+    #[serde(default)]
+    pub private_title_patterns: Vec<String>,
+    
+    /// Browser window titles that mark a picture-in-picture window, which is
+    /// counted as the tab it was popped out of
+    #[serde(default = "default_pip_titles")]
+    pub pip_titles: Vec<String>,
+
+    /// Title parts that change without the window showing something else
+    /// (an unsaved mark, "Loading…", "(3)"), ignored when titles are
+    /// compared; `#` stands for a number
+    #[serde(default = "default_title_noise_markers")]
+    pub title_noise_markers: Vec<String>,
+    
+    /// Browser sessions per tab ("full"), per site ("domain") or one for the
+    /// whole browser ("off")
+    #[serde(default)]
+    pub browser_tab_tracking: tracking::BrowserTabTracking,
+    
+    /// Also match focus_sites and ignored_sites against the window titles of
+    /// the apps in site_apps, so "Notion — Engineering Wiki" can be focus
+    #[serde(default)]
+    pub apply_sites_to_apps: bool,
+    
+    /// Non-browser apps whose window titles site rules apply to, by app key
+    /// or display name (e.g. "notion", "obsidian", "slack")
+    #[serde(default)]
+    pub site_apps: Vec<String>,
+    
+    /// Site entries for AI chat tools, matched against a browser row's tab
+    /// or any other app's window title; time there is shown on its own line
+    #[serde(default = "default_assistant_sites")]
+    pub assistant_sites: Vec<String>,
+    
+    /// Whether assistant time adds to focus, to distraction or to neither
+    #[serde(default)]
+    pub assistant_counts_as: AssistantCountsAs,
+    
+    /// Don't track terminals showing focusdebt's own output (e.g. "focusdebt stats")
+    #[serde(default = "default_self_ignore")]
+    pub self_ignore: bool,
+    
+    /// Window titles longer than this many characters are cut (ending in
+    /// "…") before they are stored or logged; 0 keeps them whole
+    #[serde(default = "default_max_title_length")]
+    pub max_title_length: usize,
+    
+    /// Session names longer than this many characters are cut, with a
+    /// warning, when they are given; 0 keeps them whole
+    #[serde(default = "default_max_session_name_length")]
+    pub max_session_name_length: usize,
+    
+    /// Sample keyboard/mouse idle time to score how engaged focus sessions
+    /// were. Only idle time is read, never which keys were pressed.
+    #[serde(default)]
+    pub track_input_activity: bool,
+    
+    /// Minutes of low input activity in one window before the stretch counts
+    /// as passive time (0 turns it off). Needs track_input_activity.
+    #[serde(default = "default_passive_after_minutes")]
+    pub passive_after_minutes: u32,
+    
+    /// Share of ticks with input (0.0–1.0) below which a minute counts as low
+    #[serde(default = "default_passive_activity_level")]
+    pub passive_activity_level: f64,
+    
+    /// Warn when the trailing 3-day focus efficiency drops below this percentage
+    #[serde(default = "default_efficiency_floor")]
+    pub efficiency_floor: f64,
+    
+    /// Notify when efficiency_floor is crossed; off unless turned on
+    #[serde(default = "default_efficiency_alerts")]
+    pub efficiency_alerts: bool,
+    
+    /// Focus time a day aims for, which `estimate` projects against
+    #[serde(default = "default_daily_focus_goal_minutes")]
+    pub daily_focus_goal_minutes: u64,
+    
+    /// Leave weekends and holidays out of averages and the efficiency
+    /// floor; they still get a baseline of their own
+    #[serde(default)]
+    pub exclude_weekends_from_averages: bool,
+    
+    /// Non-working days (YYYY-MM-DD), measured against the weekend baseline
+    #[serde(default)]
+    pub holidays: Vec<String>,
+    
+    /// Let `start` reuse the name of an earlier session
+    #[serde(default)]
+    pub allow_duplicate_session_names: bool,
+    
+    /// Name sessions after the day instead of asking, and roll over to a new
+    /// one at day_start_hour
+    #[serde(default)]
+    pub auto_daily_session: bool,
+    
+    /// Profile whose database is used when --profile is not given
+    #[serde(default)]
+    pub profile: Option<String>,
+    
+    /// Hour (local time) at which days start, for stats and budgets alike
+    #[serde(default)]
+    pub day_start_hour: u32,
+    
+    /// Time budgets per app or site, e.g. slack = "1h", or a table like
+    /// youtube = { limit = "3h", period = "week" }; see `BudgetEntry`
+    #[serde(default)]
+    pub budgets: BTreeMap<String, BudgetEntry>,
+
+    /// Apps shown together in reports, e.g. rust-toolchain = ["code",
+    /// "alacritty", "zellij"]. Only reports are grouped; the database keeps
+    /// every app. An app may be in one group at most, see `app_groups`.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+    
+    /// Pairs of app sets used at the same time, e.g. a meeting app and an
+    /// editor while pair programming; see `CoFocusRule`
+    #[serde(default)]
+    pub co_focus: Vec<CoFocusRule>,
+    
+    /// Minutes the other side of a co_focus rule may have been out of
+    /// front for a row to still be co-focused
+    #[serde(default = "default_co_focus_window_minutes")]
+    pub co_focus_window_minutes: u64,
+    
+    /// Local times the daemon pauses tracking, e.g. "22:00-07:00" or
+    /// "sat-sun 13:00-18:00"; see `QuietRange`
+    #[serde(default)]
+    pub quiet_hours: Vec<String>,
+    
+    /// 12- or 24-hour clock for times in reports
+    #[serde(default)]
+    pub time_format: utils::TimeFormat,
+    
+    /// strftime format for dates in reports, e.g. "%d.%m." or "%m/%d"
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    
+    /// Note the UTC offset next to times in share reports and exports
+    #[serde(default)]
+    pub show_utc_offset: bool,
+    
+    /// Put the hourglass in share reports and session summaries
+    #[serde(default = "default_show_ascii_art")]
+    pub show_ascii_art: bool,
+    
+    /// IANA timezone times are shown in and days are cut by, e.g.
+    /// "Europe/Berlin"; None follows the system's
+    #[serde(default)]
+    pub timezone: Option<String>,
+    
+    /// Columns reports are laid out for; None follows the terminal's width
+    #[serde(default)]
+    pub report_width: Option<usize>,
+    
+    /// Characters report bars are drawn with: unicode, ascii, or auto to
+    /// check the terminal
+    #[serde(default)]
+    pub display_glyphs: DisplayGlyphs,
+    
+    /// Lowest fuzzy match score that counts (focusapp add, sessions show,
+    /// config key suggestions)
+    #[serde(default = "default_fuzzy_min_score")]
+    pub fuzzy_min_score: i64,
+    
+    /// A fuzzy match is ambiguous when the runner-up scores within this of
+    /// the best
+    #[serde(default = "default_fuzzy_ambiguity_margin")]
+    pub fuzzy_ambiguity_margin: i64,
+    
+    /// The daemon deletes sessions, switches and events older than this many
+    /// days once a day; None keeps everything
+    #[serde(default)]
+    pub retention_days: Option<u32>,
+    
+    /// Sessions starting more than this many days away from the latest
+    /// time focusdebt has seen are quarantined instead of saved, in case
+    /// the clock is wrong (0 turns it off)
+    #[serde(default = "default_max_clock_skew_days")]
+    pub max_clock_skew_days: u32,
+
+    /// Notify when the disk holding the database fills up within this many
+    /// days at the database's growth rate (0 turns it off)
+    #[serde(default = "default_disk_warning_days")]
+    pub disk_warning_days: u32,
+
+    /// Most a row saved without an end_time (the daemon crashed) counts
+    /// for, when no later row or the present ends it sooner
+    #[serde(default = "default_max_session_hours")]
+    pub max_session_hours: u64,
+    
+    /// Where `digest` writes its files; None means "exports" in the data directory
+    #[serde(default)]
+    pub export_dir: Option<String>,
+    
+    /// Directory the daemon spills rows to while the database's disk is
+    /// full; best on another filesystem. None means the runtime directory
+    /// ($XDG_RUNTIME_DIR/focusdebt, or the data directory without one).
+    #[serde(default)]
+    pub spill_path: Option<String>,
+
+    /// Size the spill journal grows to before it is rotated (two older
+    /// parts are kept); 0 for no cap
+    #[serde(default = "default_max_spill_journal_mb")]
+    pub max_spill_journal_mb: u64,
+    
+    /// CPU use (% of one core) the daemon warns about in its log once it
+    /// stays above it for five samples in a row; 0 never warns
+    #[serde(default = "default_cpu_warning_percent")]
+    pub cpu_warning_percent: u64,
+    
+    /// Shell command `digest --mail-to` pipes the message (with headers) to
+    #[serde(default = "default_mail_command")]
+    pub mail_command: String,
+    
+    /// How the 0–100 focus score weighs efficiency, switches and block length
+    #[serde(default)]
+    pub score: ScoreConfig,
+    
+    /// Audio cues the daemon plays for focus milestones and distraction
+    #[serde(default)]
+    pub sounds: SoundsConfig,
+    
+    /// Shell commands run by the daemon when a session starts
+    #[serde(default)]
+    pub on_session_start: Vec<String>,
+    
+    /// Shell commands run by the daemon when a session ends
+    #[serde(default)]
+    pub on_session_end: Vec<String>,
+    
+    /// Per-terminal regexes used to pull the running program out of the window title
+    #[serde(default)]
+    pub terminal_title_rules: Vec<TerminalTitleRule>,
+    
+    /// In a terminal running tmux, attribute time to the active pane's
+    /// command (asked from tmux every few seconds) instead of the terminal
+    #[serde(default)]
+    pub tmux_integration: bool,
+    
+    /// Count the desktop notifications received during a session, by the
+    /// app that sent them (never their contents)
+    #[serde(default)]
+    pub count_notifications: bool,
+    
+    /// Command printing one line per notification received, naming its
+    /// app, for count_notifications; unset watches the session D-Bus on Linux
+    #[serde(default)]
+    pub notification_command: Option<String>,
+    
+    /// Run `task <id> start` and `task <id> stop` around sessions started
+    /// with `--task <id>`
+    #[serde(default)]
+    pub taskwarrior_integration: bool,
+    
+    /// The todo.txt file `start --task` looks lines up in; unset uses
+    /// $TODO_FILE, else ~/todo.txt
+    #[serde(default)]
+    pub todo_txt_file: Option<String>,
+    
     #[serde(default = "default_database_path")]
     pub database_path: Option<String>,
     
+    /// File holding the passphrase of an encrypted database
+    #[serde(default)]
+    pub database_key_file: Option<String>,
+    
 
     
     #[serde(default = "default_first_run")]
@@ -36,18 +359,399 @@ pub struct Config {
 }
 
 
+/// When the active app is `terminal`, `pattern` is matched against the window
+/// title and its named groups (program, path, host) refine what is tracked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalTitleRule {
+    pub terminal: String,
+    pub pattern: String,
+}
+
+/// One `quiet_hours` entry: a local time range, optionally only on some
+/// weekdays. A range that ends before it starts runs past midnight and
+/// belongs to the day it starts on, so "fri 22:00-07:00" covers Saturday
+/// morning too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuietRange {
+    pub spec: String,
+    /// Days the range starts on, Monday first
+    pub days: [bool; 7],
+    /// Minutes after midnight; `end` may be 24:00
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietRange {
+    /// "22:00-07:00", "mon 12:00-13:00" or "mon-fri 18:00-24:00"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (days, times) = match spec.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (Self::parse_days(days.trim())?, times),
+            None => ([true; 7], spec),
+        };
+        let (start, end) = times.split_once('-')
+            .ok_or_else(|| format!("'{}' has no time range; use HH:MM-HH:MM", spec))?;
+        let (start, end) = (Self::parse_minutes(start)?, Self::parse_minutes(end)?);
+        if start == end {
+            return Err(format!("'{}' starts and ends at the same time", spec));
+        }
+        if start == 24 * 60 {
+            return Err(format!("'{}' can't start at 24:00", spec));
+        }
+        Ok(Self { spec: spec.to_string(), days, start, end })
+    }
+
+    fn parse_minutes(time: &str) -> Result<u32, String> {
+        let time = time.trim();
+        if time == "24:00" {
+            return Ok(24 * 60);
+        }
+        chrono::NaiveTime::parse_from_str(time, "%H:%M")
+            .map(|time| time.hour() * 60 + time.minute())
+            .map_err(|_| format!("'{}' is not a time (HH:MM)", time))
+    }
+
+    fn parse_days(days: &str) -> Result<[bool; 7], String> {
+        let weekday = |day: &str| day.trim().parse::<chrono::Weekday>()
+            .map(|day| day.num_days_from_monday() as usize)
+            .map_err(|_| format!("'{}' is not a weekday (mon, tue, ...)", day.trim()));
+        let (from, to) = match days.split_once('-') {
+            Some((from, to)) => (weekday(from)?, weekday(to)?),
+            None => (weekday(days)?, weekday(days)?),
+        };
+        // Ranges may wrap: "fri-mon"
+        let mut selected = [false; 7];
+        let mut day = from;
+        selected[day] = true;
+        while day != to {
+            day = (day + 1) % 7;
+            selected[day] = true;
+        }
+        Ok(selected)
+    }
+
+    /// Whether the local time `at` falls in this range
+    pub fn contains(&self, at: chrono::NaiveDateTime) -> bool {
+        let minute = at.hour() * 60 + at.minute();
+        let today = at.weekday().num_days_from_monday() as usize;
+        if self.start < self.end {
+            return self.days[today] && minute >= self.start && minute < self.end;
+        }
+        (self.days[today] && minute >= self.start) || (self.days[(today + 6) % 7] && minute < self.end)
+    }
+}
+
+/// How long a budget lasts before it starts over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    /// From day_start_hour to day_start_hour
+    #[default]
+    Day,
+    /// Monday to Monday at day_start_hour, like the weekly digest
+    Week,
+}
+
+impl BudgetPeriod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "day" | "daily" => Some(BudgetPeriod::Day),
+            "week" | "weekly" => Some(BudgetPeriod::Week),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Day => "day",
+            BudgetPeriod::Week => "week",
+        }
+    }
+}
+
+/// The `groups` table once checked: which group each app is in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppGroups {
+    /// Lowercased app name to its group
+    members: BTreeMap<String, String>,
+}
+
+impl AppGroups {
+    pub fn group_of(&self, app: &str) -> Option<&str> {
+        self.members.get(&app.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+/// One `co_focus` entry: two named app sets used together, like "meeting"
+/// (zoom, teams) and "coding" (code, nvim) while pairing. A row of either
+/// side is tagged with `label` when the other side was in front within
+/// co_focus_window_minutes before it started. Reports show that time once,
+/// crediting each side with CO_FOCUS_SHARE of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoFocusRule {
+    pub label: String,
+    pub first: String,
+    pub first_apps: Vec<String>,
+    pub second: String,
+    pub second_apps: Vec<String>,
+}
+
+/// The part of co-focused time each side of a rule is credited with, so
+/// the sides add up to the time once
+pub const CO_FOCUS_SHARE: f64 = 0.5;
+
+impl CoFocusRule {
+    /// 0 for the first side, 1 for the second; app names match ignoring case
+    pub fn side_of(&self, app: &str) -> Option<usize> {
+        let listed = |apps: &[String]| apps.iter().any(|listed| listed.trim().eq_ignore_ascii_case(app));
+        if listed(&self.first_apps) {
+            Some(0)
+        } else if listed(&self.second_apps) {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    pub fn side_name(&self, side: usize) -> &str {
+        if side == 0 { &self.first } else { &self.second }
+    }
+}
+
+/// One `budgets` entry: just the daily limit ("30m"), or a table with the
+/// period and carry-over
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BudgetEntry {
+    Limit(String),
+    Table(BudgetTable),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BudgetTable {
+    pub limit: String,
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Daily budgets only: time left unused rolls into the next day
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub carry_over: bool,
+    /// Most time carried into a day; None allows one more day's limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carry_over_cap: Option<String>,
+}
+
+impl BudgetEntry {
+    pub fn table(&self) -> BudgetTable {
+        match self {
+            BudgetEntry::Limit(limit) => BudgetTable { limit: limit.clone(), ..Default::default() },
+            BudgetEntry::Table(table) => table.clone(),
+        }
+    }
+
+    /// The entry for `table`, written as a plain limit when nothing else is set
+    pub fn from_table(table: BudgetTable) -> Self {
+        if table.period == BudgetPeriod::Day && !table.carry_over && table.carry_over_cap.is_none() {
+            BudgetEntry::Limit(table.limit)
+        } else {
+            BudgetEntry::Table(table)
+        }
+    }
+
+    /// The entry parsed, or what is wrong with it
+    pub fn budget(&self, target: &str) -> Result<Budget, String> {
+        let table = self.table();
+        let limit = utils::parse_duration(&table.limit)
+            .ok_or_else(|| format!("invalid limit '{}'", table.limit))?;
+        let carry_over_cap = match &table.carry_over_cap {
+            Some(cap) => utils::parse_duration(cap).ok_or_else(|| format!("invalid carry_over_cap '{}'", cap))?,
+            None => limit,
+        };
+        if table.carry_over && table.period != BudgetPeriod::Day {
+            return Err("carry_over only works with period = \"day\"".to_string());
+        }
+        Ok(Budget {
+            target: target.to_string(),
+            limit,
+            period: table.period,
+            carry_over: table.carry_over,
+            carry_over_cap,
+        })
+    }
+}
+
+impl fmt::Display for BudgetEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = self.table();
+        write!(f, "{} per {}", table.limit, table.period.label())?;
+        if table.carry_over {
+            write!(f, ", unused time carries over (up to {})", table.carry_over_cap.as_deref().unwrap_or(&table.limit))?;
+        }
+        Ok(())
+    }
+}
+
+/// A parsed budget, see `Config::budget_rules`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    pub target: String,
+    pub limit: Duration,
+    pub period: BudgetPeriod,
+    pub carry_over: bool,
+    pub carry_over_cap: Duration,
+}
+
+/// `[score]`: weights and reference points of the focus score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreConfig {
+    #[serde(default = "default_efficiency_weight")]
+    pub efficiency_weight: f64,
+    
+    #[serde(default = "default_switch_weight")]
+    pub switch_weight: f64,
+    
+    #[serde(default = "default_block_weight")]
+    pub block_weight: f64,
+    
+    /// Context switches per hour that earn half of the switch part
+    #[serde(default = "default_switch_rate_reference")]
+    pub switch_rate_reference: f64,
+    
+    /// Longest focus block that earns all of the block part
+    #[serde(default = "default_target_block_minutes")]
+    pub target_block_minutes: u64,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self {
+            efficiency_weight: default_efficiency_weight(),
+            switch_weight: default_switch_weight(),
+            block_weight: default_block_weight(),
+            switch_rate_reference: default_switch_rate_reference(),
+            target_block_minutes: default_target_block_minutes(),
+        }
+    }
+}
+
+/// `[sounds]`: audio cues for long focus stretches and for lingering on a
+/// distraction. A sound left unset plays a built-in beep.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// A cue every this many minutes of continuous focus
+    #[serde(default = "default_focus_cue_minutes")]
+    pub focus_minutes: u64,
+    
+    /// A cue after this many minutes on a distraction
+    #[serde(default = "default_distraction_cue_minutes")]
+    pub distraction_minutes: u64,
+    
+    /// wav or ogg file played for the focus cue
+    #[serde(default)]
+    pub focus_sound: Option<String>,
+    
+    /// wav or ogg file played for the distraction cue
+    #[serde(default)]
+    pub distraction_sound: Option<String>,
+}
+
+impl Default for SoundsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focus_minutes: default_focus_cue_minutes(),
+            distraction_minutes: default_distraction_cue_minutes(),
+            focus_sound: None,
+            distraction_sound: None,
+        }
+    }
+}
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             tracking_interval_ms: default_tracking_interval(),
             save_interval_ms: default_save_interval(),
+            backend: None,
+            detection_fallback: false,
+            allow_process_scan_fallback: false,
+            max_buffered_rows: default_max_buffered_rows(),
             deep_focus_threshold_minutes: default_deep_focus_threshold(),
+            min_record_seconds: default_min_record_seconds(),
+            min_display_seconds: default_min_display_seconds(),
+            round_trip_window_seconds: default_round_trip_window_seconds(),
+            merge_gap_seconds: default_merge_gap_seconds(),
+            switch_cost_minutes: default_switch_cost_minutes(),
+            switch_cost_unmeasured: default_switch_cost_unmeasured(),
             focus_apps: Vec::new(),
             ignored_apps: Vec::new(),
             focus_sites: Vec::new(),
             ignored_sites: Vec::new(),
+            private_title_patterns: Vec::new(),
+            pip_titles: default_pip_titles(),
+            title_noise_markers: default_title_noise_markers(),
+            browser_tab_tracking: tracking::BrowserTabTracking::default(),
+            apply_sites_to_apps: false,
+            site_apps: Vec::new(),
+            assistant_sites: default_assistant_sites(),
+            assistant_counts_as: AssistantCountsAs::default(),
+            self_ignore: default_self_ignore(),
+            max_title_length: default_max_title_length(),
+            max_session_name_length: default_max_session_name_length(),
+            track_input_activity: false,
+            passive_after_minutes: default_passive_after_minutes(),
+            passive_activity_level: default_passive_activity_level(),
+            efficiency_floor: default_efficiency_floor(),
+            efficiency_alerts: default_efficiency_alerts(),
+            daily_focus_goal_minutes: default_daily_focus_goal_minutes(),
+            exclude_weekends_from_averages: false,
+            holidays: Vec::new(),
+            allow_duplicate_session_names: false,
+            auto_daily_session: false,
+            profile: None,
+            day_start_hour: 0,
+            budgets: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            co_focus: Vec::new(),
+            co_focus_window_minutes: default_co_focus_window_minutes(),
+            quiet_hours: Vec::new(),
+            time_format: utils::TimeFormat::default(),
+            date_format: default_date_format(),
+            show_utc_offset: false,
+            show_ascii_art: default_show_ascii_art(),
+            timezone: None,
+            report_width: None,
+            display_glyphs: DisplayGlyphs::default(),
+            fuzzy_min_score: default_fuzzy_min_score(),
+            fuzzy_ambiguity_margin: default_fuzzy_ambiguity_margin(),
+            retention_days: None,
+            max_clock_skew_days: default_max_clock_skew_days(),
+            disk_warning_days: default_disk_warning_days(),
+            max_session_hours: default_max_session_hours(),
+            export_dir: None,
+            spill_path: None,
+            max_spill_journal_mb: default_max_spill_journal_mb(),
+            cpu_warning_percent: default_cpu_warning_percent(),
+            mail_command: default_mail_command(),
+            score: ScoreConfig::default(),
+            sounds: SoundsConfig::default(),
+            on_session_start: Vec::new(),
+            on_session_end: Vec::new(),
+            terminal_title_rules: Vec::new(),
+            tmux_integration: false,
+            count_notifications: false,
+            notification_command: None,
+            taskwarrior_integration: false,
+            todo_txt_file: None,
             database_path: default_database_path(),
+            database_key_file: None,
 
             first_run: default_first_run(),
         }
@@ -56,11 +760,456 @@ impl Default for Config {
 
 
 
+/// Keys that describe this machine rather than preferences; bundles never change them
+/// How the current config sees a recorded session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionClass {
+    Focus,
+    Distraction,
+    /// Matches ignored_apps or ignored_sites; left out of reports
+    Ignored,
+}
+
+/// Default assistant_sites: the chat sites of the common AI assistants,
+/// their desktop apps (whose titles are the site's name) and editor chat
+/// panels
+pub const DEFAULT_ASSISTANT_SITES: [&str; 8] = [
+    "domain:chatgpt.com", "domain:chat.openai.com", "domain:claude.ai", "domain:gemini.google.com",
+    "domain:copilot.microsoft.com", "domain:perplexity.ai", "title:Copilot Chat", "title:GitHub Copilot",
+];
+
+/// Where assistant time goes in the totals (`assistant_counts_as`).
+/// Neutral time, like passive time, leaves efficiency and the score alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum AssistantCountsAs {
+    Focus,
+    Distraction,
+    #[default]
+    Neutral,
+}
+
+impl AssistantCountsAs {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "focus" => Some(AssistantCountsAs::Focus),
+            "distraction" => Some(AssistantCountsAs::Distraction),
+            "neutral" => Some(AssistantCountsAs::Neutral),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            AssistantCountsAs::Focus => "focus",
+            AssistantCountsAs::Distraction => "distraction",
+            AssistantCountsAs::Neutral => "neutral",
+        }
+    }
+}
+
+/// How report bars are drawn (`display_glyphs`). Auto uses block
+/// characters when the locale is UTF-8 and the terminal draws them one
+/// column wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayGlyphs {
+    #[default]
+    Auto,
+    Unicode,
+    Ascii,
+}
+
+impl DisplayGlyphs {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Some(DisplayGlyphs::Auto),
+            "unicode" => Some(DisplayGlyphs::Unicode),
+            "ascii" => Some(DisplayGlyphs::Ascii),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DisplayGlyphs::Auto => "auto",
+            DisplayGlyphs::Unicode => "unicode",
+            DisplayGlyphs::Ascii => "ascii",
+        }
+    }
+}
+
+impl SessionClass {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionClass::Focus => "focus",
+            SessionClass::Distraction => "distraction",
+            SessionClass::Ignored => "ignored",
+        }
+    }
+}
+
+/// The list a classification came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum RuleKind {
+    IgnoredApp,
+    IgnoredSite,
+    FocusApp,
+    FocusSite,
+    /// No list matched
+    Default,
+}
+
+impl RuleKind {
+    /// The order `classify_window` tries the lists in; the first that
+    /// matches settles the class
+    pub const PRECEDENCE: [RuleKind; 5] = [RuleKind::IgnoredApp, RuleKind::IgnoredSite, RuleKind::FocusApp, RuleKind::FocusSite, RuleKind::Default];
+
+    /// The class a window gets when this list settles it
+    pub(crate) fn class(&self) -> SessionClass {
+        match self {
+            RuleKind::IgnoredApp | RuleKind::IgnoredSite => SessionClass::Ignored,
+            RuleKind::FocusApp | RuleKind::FocusSite => SessionClass::Focus,
+            RuleKind::Default => SessionClass::Distraction,
+        }
+    }
+
+    /// What reaching this step means, for `config doctor`
+    pub(crate) fn explain(&self) -> &'static str {
+        match self {
+            RuleKind::IgnoredApp => "the app is in ignored_apps: ignored",
+            RuleKind::IgnoredSite => "the tab (or a site_apps title) matches ignored_sites: ignored",
+            RuleKind::FocusApp => "the app is in focus_apps: focus",
+            RuleKind::FocusSite => "the tab (or a site_apps title) matches focus_sites: focus",
+            RuleKind::Default => "nothing matched: distraction",
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            RuleKind::IgnoredApp => "ignored_app",
+            RuleKind::IgnoredSite => "ignored_site",
+            RuleKind::FocusApp => "focus_app",
+            RuleKind::FocusSite => "focus_site",
+            RuleKind::Default => "default",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "ignored_app" => Ok(RuleKind::IgnoredApp),
+            "ignored_site" => Ok(RuleKind::IgnoredSite),
+            "focus_app" => Ok(RuleKind::FocusApp),
+            "focus_site" => Ok(RuleKind::FocusSite),
+            "default" => Ok(RuleKind::Default),
+            other => Err(format!("unknown rule kind '{}'", other)),
+        }
+    }
+}
+
+/// Which rule classified a window: the list and the entry of it that
+/// matched. Stored on the row as "focus_site:github.com", or "default".
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RuleMatch {
+    pub kind: RuleKind,
+    pub pattern: Option<String>,
+}
+
+impl RuleMatch {
+    pub(crate) fn new(kind: RuleKind, pattern: &str) -> Self {
+        Self { kind, pattern: Some(pattern.to_string()) }
+    }
+
+    pub(crate) fn default_rule() -> Self {
+        Self { kind: RuleKind::Default, pattern: None }
+    }
+
+    /// The focus_rule column's text
+    pub(crate) fn to_column(&self) -> String {
+        match &self.pattern {
+            Some(pattern) => format!("{}:{}", self.kind.label(), pattern),
+            None => self.kind.label().to_string(),
+        }
+    }
+
+    /// A focus_rule column; the pattern may hold colons itself
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value.split_once(':') {
+            Some((kind, pattern)) => Ok(Self::new(RuleKind::parse(kind)?, pattern)),
+            None => Ok(Self { kind: RuleKind::parse(value)?, pattern: None }),
+        }
+    }
+
+    /// e.g. "focus_sites entry \"github.com\"", or "no rule (default)"
+    pub(crate) fn describe(&self) -> String {
+        let list = match self.kind {
+            RuleKind::IgnoredApp => "ignored_apps",
+            RuleKind::IgnoredSite => "ignored_sites",
+            RuleKind::FocusApp => "focus_apps",
+            RuleKind::FocusSite => "focus_sites",
+            RuleKind::Default => return "no rule (default)".to_string(),
+        };
+        format!("{} entry \"{}\"", list, self.pattern.as_deref().unwrap_or_default())
+    }
+}
+
+/// How a window is classified, for the tracker and for `classify_session`
+/// alike, and the rule that settled it. The lists are tried in
+/// `RuleKind::PRECEDENCE` order and the first match wins:
+/// 1. an ignored app, or site text matching ignored_sites: Ignored
+/// 2. a focus app, or site text matching focus_sites: Focus
+/// 3. anything else: Distraction
+///
+/// `ignored_app` and `focus_app` are the list entries the app matched, if
+/// any. `site_text` is what site rules see: a browser's tab, or the window
+/// title of an app in site_apps; None for every other app.
+pub fn classify_window(ignored_app: Option<&str>, focus_app: Option<&str>, site_text: Option<&str>, focus_sites: &[String], ignored_sites: &[String]) -> (SessionClass, RuleMatch) {
+    fn matching<'a>(site_text: Option<&str>, sites: &'a [String]) -> Option<&'a str> {
+        let text = site_text?;
+        sites.iter().find(|site| utils::title_matches_site(text, site)).map(String::as_str)
+    }
+    let rule = RuleKind::PRECEDENCE.into_iter()
+        .find_map(|kind| {
+            let pattern = match kind {
+                RuleKind::IgnoredApp => ignored_app,
+                RuleKind::IgnoredSite => matching(site_text, ignored_sites),
+                RuleKind::FocusApp => focus_app,
+                RuleKind::FocusSite => matching(site_text, focus_sites),
+                RuleKind::Default => return Some(RuleMatch::default_rule()),
+            };
+            pattern.map(|pattern| RuleMatch::new(kind, pattern))
+        })
+        .unwrap_or_else(RuleMatch::default_rule);
+    (rule.kind.class(), rule)
+}
+
+/// Whether `app_name` is in a site_apps list, by key or display name
+pub fn is_site_app(site_apps: &[String], app_name: &str) -> bool {
+    site_apps.iter().any(|app| {
+        app.eq_ignore_ascii_case(app_name) || desktop::display_name(app_name).eq_ignore_ascii_case(app)
+    })
+}
+
+const LOCAL_ONLY_KEYS: [&str; 5] = ["first_run", "database_path", "database_key_file", "export_dir", "spill_path"];
+
+/// Version of the `config show --json` layout
+pub const CONFIG_JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Where the effective value of a config key comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    /// Written in config.toml
+    File,
+    /// A command-line flag, like --profile for `profile`
+    Cli,
+}
+
+/// One key of `config show --json`
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedValue {
+    pub value: serde_json::Value,
+    pub source: ConfigSource,
+}
+
+/// The settings a session was tracked with, stored once per session so a
+/// report read later can say what produced it. Fields missing from an
+/// older snapshot read as their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSnapshot {
+    pub version: String,
+    /// The window detection backend, once one has answered
+    pub backend: Option<String>,
+    /// Focus apps as tracked: the defaults when none are configured
+    pub focus_apps: Vec<String>,
+    pub focus_sites: Vec<String>,
+    pub ignored_apps: Vec<String>,
+    pub ignored_sites: Vec<String>,
+    pub site_apps: Vec<String>,
+    pub terminal_title_rules: Vec<TerminalTitleRule>,
+    pub tmux_integration: bool,
+    pub browser_tab_tracking: Option<tracking::BrowserTabTracking>,
+    pub self_ignore: bool,
+    /// 0 for sessions recorded before titles were capped
+    pub max_title_length: usize,
+    pub track_input_activity: bool,
+    pub tracking_interval_ms: u64,
+    pub min_record_seconds: u64,
+    pub merge_gap_seconds: u64,
+    pub deep_focus_threshold_minutes: u64,
+    pub passive_after_minutes: u32,
+    pub passive_activity_level: f64,
+}
+
+/// One difference between the current config and an imported bundle
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChange {
+    Changed { key: String, from: String, to: String },
+    Added { key: String, value: String },
+    Removed { key: String, value: String },
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigChange::Changed { key, from, to } => write!(f, "~ {}: {} → {}", key, from, to),
+            ConfigChange::Added { key, value } => write!(f, "+ {}: {}", key, value),
+            ConfigChange::Removed { key, value } => write!(f, "- {}: {}", key, value),
+        }
+    }
+}
+
+/// A combination of settings that works against itself, from `Config::lint`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    /// The rule that found it, e.g. "tracking-vs-min-record"
+    pub rule: &'static str,
+    pub message: String,
+    /// The `config set` that fixes it
+    pub suggestion: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (try 'focusdebt config set {}')", self.message, self.suggestion)
+    }
+}
+
+/// A cross-key check over the typed config: the message and suggested
+/// `config set` when the keys conflict
+struct LintRule {
+    name: &'static str,
+    check: fn(&Config) -> Option<(String, String)>,
+}
+
+/// Window visits this short are ordinary (a glance at chat), so a tracking
+/// interval above it hides them whatever min_record_seconds says
+const SHORT_VISIT_MS: u64 = 2000;
+/// Rows since the last save are lost if the daemon crashes; more than this
+/// is more than most people expect to lose
+const MAX_UNSAVED_MS: u64 = 2 * 60 * 1000;
+/// Activity levels are per minute; fewer than four ticks a minute leave
+/// each minute at a handful of possible levels, and under one a minute
+/// leaves minutes unsampled and breaks passive runs
+const MAX_ACTIVITY_TICK_MS: u64 = 15_000;
+
+const LINT_RULES: &[LintRule] = &[
+    LintRule {
+        name: "tracking-vs-min-record",
+        check: |config| {
+            let shortest = (config.min_record_seconds * 1000).max(SHORT_VISIT_MS);
+            (config.tracking_interval_ms > shortest).then(|| (
+                format!("tracking_interval_ms is {}, so window visits shorter than {} can be missed entirely even though min_record_seconds is {}",
+                    config.tracking_interval_ms, lint_duration(config.tracking_interval_ms), config.min_record_seconds),
+                format!("tracking_interval_ms {}", shortest),
+            ))
+        },
+    },
+    LintRule {
+        name: "subsecond-vs-backend",
+        check: |config| {
+            tracking::check_interval(config.tracking_interval_ms, config.backend.as_deref()).err().map(|problem| (
+                format!("{}, so the daemon refuses to start", problem),
+                format!("tracking_interval_ms {}", tracking::MIN_SPAWNING_INTERVAL_MS),
+            ))
+        },
+    },
+    LintRule {
+        name: "pinned-process-scan",
+        check: |config| {
+            (config.backend.as_deref() == Some("process_scan") && !config.allow_process_scan_fallback).then(|| (
+                "backend is process_scan but allow_process_scan_fallback is off, so no window is ever detected".to_string(),
+                "allow_process_scan_fallback true".to_string(),
+            ))
+        },
+    },
+    LintRule {
+        name: "save-vs-crash-loss",
+        check: |config| {
+            (config.save_interval_ms > MAX_UNSAVED_MS).then(|| (
+                format!("save_interval_ms is {}, so a crash or power loss can lose up to {} of tracking",
+                    config.save_interval_ms, lint_duration(config.save_interval_ms)),
+                format!("save_interval_ms {}", default_save_interval()),
+            ))
+        },
+    },
+    LintRule {
+        name: "save-vs-tracking",
+        check: |config| {
+            (config.save_interval_ms < config.tracking_interval_ms).then(|| (
+                format!("save_interval_ms ({}) is shorter than tracking_interval_ms ({}), so most saves find nothing new",
+                    config.save_interval_ms, config.tracking_interval_ms),
+                format!("save_interval_ms {}", config.tracking_interval_ms.max(default_save_interval())),
+            ))
+        },
+    },
+    LintRule {
+        name: "activity-vs-tracking",
+        check: |config| {
+            (config.track_input_activity && config.passive_after_minutes > 0 && config.tracking_interval_ms > MAX_ACTIVITY_TICK_MS).then(|| (
+                format!("tracking_interval_ms is {}, too coarse to measure input activity per minute; passive stretches of {} minutes won't be told apart",
+                    config.tracking_interval_ms, config.passive_after_minutes),
+                format!("tracking_interval_ms {}", MAX_ACTIVITY_TICK_MS),
+            ))
+        },
+    },
+];
+
+fn lint_duration(ms: u64) -> String {
+    utils::format_duration(Duration::from_millis(ms), utils::DurationStyle::Long)
+}
+
 fn default_tracking_interval() -> u64 { 1000 }
 fn default_save_interval() -> u64 { 30000 }
+fn default_max_buffered_rows() -> usize { 5000 }
+
+fn default_max_spill_journal_mb() -> u64 { 64 }
+fn default_cpu_warning_percent() -> u64 { 5 }
+fn default_date_format() -> String { utils::DEFAULT_DATE_FORMAT.to_string() }
+fn default_fuzzy_min_score() -> i64 { utils::fuzzy::DEFAULT_MIN_SCORE }
+fn default_fuzzy_ambiguity_margin() -> i64 { utils::fuzzy::DEFAULT_AMBIGUITY_MARGIN }
 fn default_deep_focus_threshold() -> u64 { 30 }
+fn default_efficiency_floor() -> f64 { 50.0 }
+fn default_self_ignore() -> bool { true }
+fn default_max_title_length() -> usize { 512 }
+fn default_max_session_name_length() -> usize { 100 }
+fn default_passive_after_minutes() -> u32 { 10 }
+fn default_passive_activity_level() -> f64 { 0.1 }
+fn default_pip_titles() -> Vec<String> { tracking::DEFAULT_PIP_TITLES.iter().map(|title| title.to_string()).collect() }
+
+fn default_title_noise_markers() -> Vec<String> { utils::DEFAULT_TITLE_NOISE_MARKERS.iter().map(|marker| marker.to_string()).collect() }
+fn default_assistant_sites() -> Vec<String> { DEFAULT_ASSISTANT_SITES.iter().map(|site| site.to_string()).collect() }
+fn default_min_record_seconds() -> u64 { 1 }
+fn default_min_display_seconds() -> u64 { 10 }
+
+fn default_round_trip_window_seconds() -> u64 { 60 }
+fn default_co_focus_window_minutes() -> u64 { 5 }
+fn default_merge_gap_seconds() -> u64 { 5 }
+fn default_switch_cost_minutes() -> u64 { 2 }
+fn default_switch_cost_unmeasured() -> bool { true }
+
+fn default_max_clock_skew_days() -> u32 { 30 }
+fn default_disk_warning_days() -> u32 { 30 }
+fn default_max_session_hours() -> u64 { 12 }
+fn default_efficiency_alerts() -> bool { false }
+fn default_daily_focus_goal_minutes() -> u64 { 240 }
+fn default_efficiency_weight() -> f64 { 0.5 }
+fn default_switch_weight() -> f64 { 0.25 }
+fn default_block_weight() -> f64 { 0.25 }
+fn default_switch_rate_reference() -> f64 { 6.0 }
+fn default_target_block_minutes() -> u64 { 90 }
+fn default_focus_cue_minutes() -> u64 { 25 }
+fn default_distraction_cue_minutes() -> u64 { 10 }
 
 fn default_first_run() -> bool { true }
+fn default_show_ascii_art() -> bool { true }
+fn default_mail_command() -> String { "sendmail -t".to_string() }
 
 fn default_database_path() -> Option<String> {
     Some("focusdebt.db".to_string())
@@ -72,7 +1221,8 @@ impl Config {
         
         if config_path.exists() {
             let content = fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&content)?;
+            let mut config: Config = toml::from_str(&content)?;
+            config.migrate_site_entries();
             Ok(config)
         } else {
             // Create default config
@@ -95,6 +1245,32 @@ impl Config {
         Ok(())
     }
 
+    /// Whether this config differs from `running` in anything the daemon only
+    /// reads at start. Focus apps and sites are picked up while it runs.
+    pub fn needs_restart_from(&self, running: &Config) -> bool {
+        let without_focus_lists = |config: &Config| {
+            let mut config = config.clone();
+            config.focus_apps.clear();
+            config.focus_sites.clear();
+            toml::to_string(&config).ok()
+        };
+        without_focus_lists(self) != without_focus_lists(running)
+    }
+
+    /// Hash of the config file as it is on disk, to notice edits made while
+    /// the daemon is running. None if there is no readable file.
+    pub fn file_fingerprint() -> Option<u64> {
+        let content = fs::read(Self::get_config_path().ok()?).ok()?;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Directory holding config.toml
+    pub fn directory() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("focusdebt"))
+    }
+
     fn get_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
         let config_dir = dirs::config_dir()
             .ok_or("Could not find config directory")?;
@@ -112,12 +1288,6 @@ impl Config {
         self.focus_apps.retain(|app| app != app_name);
     }
 
-    pub fn add_ignored_app(&mut self, app_name: String) {
-        if !self.ignored_apps.contains(&app_name) {
-            self.ignored_apps.push(app_name);
-        }
-    }
-
     pub fn remove_ignored_app(&mut self, app_name: &str) {
         self.ignored_apps.retain(|app| app != app_name);
     }
@@ -127,25 +1297,74 @@ impl Config {
     }
 
     pub fn is_ignored_app(&self, app_name: &str) -> bool {
-        self.ignored_apps.contains(&app_name.to_string())
+        self.matching_ignored_app(app_name).is_some()
     }
 
-    pub fn get_database_path(&self) -> PathBuf {
-        if let Some(ref path) = self.database_path {
-            PathBuf::from(path)
-        } else {
-            // Fallback to default location
-            dirs::data_dir()
-                .map(|dir| dir.join("focusdebt").join("focusdebt.db"))
-                .unwrap_or_else(|| PathBuf::from("focusdebt.db"))
+    /// The ignored_apps entry `app_name` matches
+    pub fn matching_ignored_app(&self, app_name: &str) -> Option<&str> {
+        self.ignored_apps.iter().find(|app| *app == app_name).map(String::as_str)
+    }
+
+    pub fn export_directory(&self) -> Option<PathBuf> {
+        match &self.export_dir {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => utils::get_data_directory().map(|dir| dir.join("exports")),
         }
     }
 
+    /// The spill journal used when the disk is full
+    pub fn spill_journal(&self) -> PathBuf {
+        let dir = self.spill_path.as_ref().map(PathBuf::from)
+            .or_else(utils::runtime_directory)
+            .unwrap_or_default();
+        spill::journal_path(&dir)
+    }
+
+    /// max_spill_journal_mb in bytes
+    pub fn max_spill_journal_bytes(&self) -> u64 {
+        self.max_spill_journal_mb.saturating_mul(1024 * 1024)
+    }
+
     pub fn mark_first_run_complete(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         self.first_run = false;
         self.save()
     }
 
+    /// Give site entries from before match modes existed an explicit mode,
+    /// saving the result and warning once about entries that could have
+    /// meant either a site or a title word
+    fn migrate_site_entries(&mut self) {
+        let mut migrated = 0;
+        let mut ambiguous = Vec::new();
+        for entry in self.focus_sites.iter_mut().chain(self.ignored_sites.iter_mut()) {
+            if utils::has_site_prefix(entry) {
+                continue;
+            }
+            let (rewritten, is_ambiguous) = utils::migrate_bare_site(entry);
+            if is_ambiguous {
+                ambiguous.push(rewritten.clone());
+            }
+            *entry = rewritten;
+            migrated += 1;
+        }
+        if migrated == 0 {
+            return;
+        }
+
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| {
+            eprintln!("~=~ Site entries now declare how they match (domain:, title:, re:); migrated {} entr{}", 
+                migrated, if migrated == 1 { "y" } else { "ies" });
+            if !ambiguous.is_empty() {
+                eprintln!("~=~ These could be a site or a word in the title and now match as title text: {}", ambiguous.join(", "));
+                eprintln!("~=~ Change them to domain:<site> with 'focussite remove' and 'focussite add' if you meant the site");
+            }
+        });
+        if let Err(e) = self.save() {
+            eprintln!("❌ Failed to save migrated site entries: {}", e);
+        }
+    }
+
     pub fn add_focus_site(&mut self, site: String) {
         if !self.focus_sites.contains(&site) {
             self.focus_sites.push(site);
@@ -156,21 +1375,896 @@ impl Config {
         self.focus_sites.retain(|s| s != site);
     }
 
-    pub fn add_ignored_site(&mut self, site: String) {
-        if !self.ignored_sites.contains(&site) {
-            self.ignored_sites.push(site);
+    /// Whether a tab/window title matches one of the focus sites
+    pub fn matches_focus_site(&self, title: &str) -> bool {
+        self.focus_sites.iter().any(|site| utils::title_matches_site(title, site))
+    }
+
+    /// Whether a tab/window title matches one of the ignored sites
+    pub fn matches_ignored_site(&self, title: &str) -> bool {
+        self.ignored_sites.iter().any(|site| utils::title_matches_site(title, site))
+    }
+
+    /// What site rules see for a session: its browser tab, or with
+    /// apply_sites_to_apps the window title of an app in site_apps
+    pub fn site_text<'a>(&self, app_name: &str, domain: Option<&'a str>, window_title: &'a str) -> Option<&'a str> {
+        domain.or_else(|| (self.apply_sites_to_apps && is_site_app(&self.site_apps, app_name)).then_some(window_title))
+    }
+
+    /// The focus apps the tracker watches: the configured ones, or a
+    /// default set of editors and browsers while there are none
+    pub fn tracked_focus_apps(&self) -> Vec<String> {
+        if !self.focus_apps.is_empty() {
+            return self.focus_apps.clone();
         }
+        vec![
+            "code".to_string(),      // VS Code
+            "code-oss".to_string(),  // VS Code OSS
+            "vim".to_string(),       // Vim
+            "nvim".to_string(),      // Neovim
+            "emacs".to_string(),     // Emacs
+            "subl".to_string(),      // Sublime Text
+            "gedit".to_string(),     // Gedit
+            "kate".to_string(),      // Kate
+            "firefox".to_string(),   // Firefox (for documentation)
+            "chromium".to_string(),  // Chromium (for documentation)
+        ]
     }
 
-    pub fn remove_ignored_site(&mut self, site: &str) {
-        self.ignored_sites.retain(|s| s != site);
+    /// What a session tracked with this config and `focus_apps` records
+    /// about itself; the backend is filled in once detection works
+    pub fn session_snapshot(&self, focus_apps: Vec<String>) -> SessionSnapshot {
+        SessionSnapshot {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            backend: None,
+            focus_apps,
+            focus_sites: self.focus_sites.clone(),
+            ignored_apps: self.ignored_apps.clone(),
+            ignored_sites: self.ignored_sites.clone(),
+            site_apps: if self.apply_sites_to_apps { self.site_apps.clone() } else { Vec::new() },
+            terminal_title_rules: self.terminal_title_rules.clone(),
+            tmux_integration: self.tmux_integration,
+            browser_tab_tracking: Some(self.browser_tab_tracking),
+            self_ignore: self.self_ignore,
+            max_title_length: self.max_title_length,
+            track_input_activity: self.track_input_activity,
+            tracking_interval_ms: self.tracking_interval_ms,
+            min_record_seconds: self.min_record_seconds,
+            merge_gap_seconds: self.merge_gap_seconds,
+            deep_focus_threshold_minutes: self.deep_focus_threshold_minutes,
+            passive_after_minutes: self.passive_after_minutes,
+            passive_activity_level: self.passive_activity_level,
+        }
     }
 
-    pub fn is_focus_site(&self, site: &str) -> bool {
-        self.focus_sites.contains(&site.to_string())
+    /// Classify a recorded session by the current focus and ignore lists,
+    /// whatever is_focus_app it was stored with (see `classify_window` for
+    /// the precedence), with the rule that matched. Budgets only limit time
+    /// and don't change the class.
+    pub fn classify_session(&self, session: &FocusSession) -> (SessionClass, RuleMatch) {
+        let site_text = self.site_text(&session.app_name, session.domain.as_deref(), &session.window_title);
+        classify_window(
+            self.matching_ignored_app(&session.app_name),
+            self.is_focus_app(&session.app_name).then_some(session.app_name.as_str()),
+            site_text,
+            &self.focus_sites,
+            &self.ignored_sites,
+        )
     }
 
-    pub fn is_ignored_site(&self, site: &str) -> bool {
-        self.ignored_sites.contains(&site.to_string())
+    /// Budgets that parse; malformed entries are skipped
+    pub fn budget_rules(&self) -> Vec<Budget> {
+        self.budgets.iter()
+            .filter_map(|(target, entry)| entry.budget(target).ok())
+            .collect()
     }
-} 
\ No newline at end of file
+
+    /// `co_focus` checked: every rule has a label of its own and two named
+    /// sides with apps, and no app is on both sides of a rule
+    pub fn co_focus_rules(&self) -> Result<Vec<CoFocusRule>, String> {
+        let mut labels = BTreeSet::new();
+        for rule in &self.co_focus {
+            let label = rule.label.trim();
+            if label.is_empty() {
+                return Err("A co_focus rule needs a label".to_string());
+            }
+            if !labels.insert(label.to_lowercase()) {
+                return Err(format!("Two co_focus rules are labelled '{}'", label));
+            }
+            if rule.first.trim().is_empty() || rule.second.trim().is_empty() {
+                return Err(format!("co_focus '{}' needs a name for both sides (first and second)", label));
+            }
+            if rule.first_apps.is_empty() || rule.second_apps.is_empty() {
+                return Err(format!("co_focus '{}' needs apps on both sides (first_apps and second_apps)", label));
+            }
+            if let Some(app) = rule.second_apps.iter().find(|app| rule.side_of(app.trim()) == Some(0)) {
+                return Err(format!("'{}' is on both sides of co_focus '{}'", app.trim(), label));
+            }
+        }
+        Ok(self.co_focus.clone())
+    }
+
+    /// `groups` checked so that no app is in two of them; app names match
+    /// ignoring case
+    pub fn app_groups(&self) -> Result<AppGroups, String> {
+        let mut members: BTreeMap<String, String> = BTreeMap::new();
+        for (group, apps) in &self.groups {
+            if group.trim().is_empty() {
+                return Err("A group needs a name".to_string());
+            }
+            for app in apps.iter().map(|app| app.trim()).filter(|app| !app.is_empty()) {
+                match members.get(&app.to_lowercase()) {
+                    Some(other) if other != group => {
+                        return Err(format!("'{}' is in both groups '{}' and '{}'; an app can be in one group at most", app, other, group));
+                    }
+                    Some(_) => {}
+                    None => {
+                        members.insert(app.to_lowercase(), group.clone());
+                    }
+                }
+            }
+        }
+        Ok(AppGroups { members })
+    }
+
+    /// Quiet hours that parse; malformed entries are skipped
+    pub fn quiet_ranges(&self) -> Vec<QuietRange> {
+        self.quiet_hours.iter().filter_map(|spec| QuietRange::parse(spec).ok()).collect()
+    }
+
+    /// Holidays that parse; malformed entries are skipped
+    pub fn holiday_dates(&self) -> Vec<chrono::NaiveDate> {
+        self.holidays.iter()
+            .filter_map(|day| chrono::NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d").ok())
+            .collect()
+    }
+
+    /// Settings that work against each other, each with a suggested fix.
+    /// Run on `config set`, daemon start, `config show` and `doctor`.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        LINT_RULES.iter()
+            .filter_map(|rule| (rule.check)(self).map(|(message, suggestion)| LintWarning { rule: rule.name, message, suggestion }))
+            .collect()
+    }
+
+    pub fn is_private_title(&self, title: &str) -> bool {
+        utils::matches_any_pattern(title, &self.private_title_patterns)
+    }
+
+    /// Write this config as a portable bundle. A bundle is a plain config.toml,
+    /// so it can also be dropped into place by hand.
+    pub fn export_bundle(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load_bundle(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// The config that importing `bundle` would produce, and what changes.
+    /// Merging unions lists (deduplicated, existing order first), merges
+    /// tables key by key and lets the bundle win scalar conflicts. Replacing
+    /// takes the bundle as is. Local-only keys are kept either way.
+    pub fn import_bundle(&self, bundle: &Config, replace: bool) -> Result<(Config, Vec<ConfigChange>), Box<dyn std::error::Error>> {
+        let mut current = to_table(self)?;
+        let incoming = to_table(bundle)?;
+        let mut changes = Vec::new();
+
+        if replace {
+            let mut replaced = incoming;
+            for key in LOCAL_ONLY_KEYS {
+                match current.get(key) {
+                    Some(value) => replaced.insert(key.to_string(), value.clone()),
+                    None => replaced.remove(key),
+                };
+            }
+            diff_tables(&current, &replaced, "", &mut changes);
+            current = replaced;
+        } else {
+            let mut incoming = incoming;
+            for key in LOCAL_ONLY_KEYS {
+                incoming.remove(key);
+            }
+            merge_tables(&mut current, &incoming, "", &mut changes);
+        }
+
+        let imported: Config = toml::Value::Table(current).try_into()?;
+        Ok((imported, changes))
+    }
+
+    /// Top-level keys written in config.toml; empty without a readable file
+    pub fn file_keys() -> BTreeSet<String> {
+        Self::get_config_path().ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str::<toml::Table>(&content).ok())
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every top-level key with its effective value and where it came from.
+    /// `overrides` are values given on the command line, which win over the
+    /// file; keys not in the file are defaults.
+    pub fn sourced_values(&self, overrides: &[(&str, serde_json::Value)]) -> Result<BTreeMap<String, SourcedValue>, Box<dyn std::error::Error>> {
+        let serde_json::Value::Object(values) = serde_json::to_value(self)? else {
+            return Err("config did not serialize to an object".into());
+        };
+        let file_keys = Self::file_keys();
+        Ok(values.into_iter()
+            .map(|(key, value)| {
+                let sourced = match overrides.iter().find(|(name, _)| *name == key) {
+                    Some((_, value)) => SourcedValue { value: value.clone(), source: ConfigSource::Cli },
+                    None if file_keys.contains(&key) => SourcedValue { value, source: ConfigSource::File },
+                    None => SourcedValue { value, source: ConfigSource::Default },
+                };
+                (key, sourced)
+            })
+            .collect())
+    }
+
+    /// Every key of the config, tables flattened like "score.switch_weight",
+    /// for suggesting one in place of a mistyped key
+    pub fn key_names() -> Vec<String> {
+        let Ok(serde_json::Value::Object(values)) = serde_json::to_value(Config::default()) else {
+            return Vec::new();
+        };
+        let mut names = Vec::new();
+        for (key, value) in values {
+            match value {
+                serde_json::Value::Object(table) if !table.is_empty() => {
+                    names.extend(table.keys().map(|field| format!("{}.{}", key, field)));
+                }
+                _ => names.push(key),
+            }
+        }
+        names
+    }
+
+    /// This config with `key` set to a JSON value through serde, for values
+    /// `config set` has no text form for (lists, tables, null). Fails on
+    /// unknown keys and on values of the wrong type.
+    pub fn with_json_value(&self, key: &str, value: &serde_json::Value) -> Result<Config, String> {
+        let mut values = match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(values)) => values,
+            _ => return Err("config did not serialize to an object".to_string()),
+        };
+        if !values.contains_key(key) {
+            return Err(format!("Unknown configuration key: {}", key));
+        }
+        values.insert(key.to_string(), value.clone());
+        serde_json::from_value(serde_json::Value::Object(values))
+            .map_err(|e| format!("Invalid value for {}: {}", key, e))
+    }
+}
+
+fn to_table(config: &Config) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    match toml::Value::try_from(config)? {
+        toml::Value::Table(table) => Ok(table),
+        _ => Err("config did not serialize to a table".into()),
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn merge_tables(current: &mut toml::Table, incoming: &toml::Table, prefix: &str, changes: &mut Vec<ConfigChange>) {
+    for (key, value) in incoming {
+        let full_key = join_key(prefix, key);
+        match (current.get_mut(key), value) {
+            (None, _) => {
+                changes.push(ConfigChange::Added { key: full_key, value: value.to_string() });
+                current.insert(key.clone(), value.clone());
+            }
+            (Some(toml::Value::Table(existing)), toml::Value::Table(incoming)) => {
+                merge_tables(existing, incoming, &full_key, changes);
+            }
+            (Some(toml::Value::Array(existing)), toml::Value::Array(incoming)) => {
+                for item in incoming {
+                    if !existing.contains(item) {
+                        changes.push(ConfigChange::Added { key: full_key.clone(), value: item.to_string() });
+                        existing.push(item.clone());
+                    }
+                }
+            }
+            (Some(existing), value) => {
+                if existing != value {
+                    changes.push(ConfigChange::Changed {
+                        key: full_key,
+                        from: existing.to_string(),
+                        to: value.to_string(),
+                    });
+                    *existing = value.clone();
+                }
+            }
+        }
+    }
+}
+
+fn diff_tables(current: &toml::Table, replaced: &toml::Table, prefix: &str, changes: &mut Vec<ConfigChange>) {
+    for (key, old) in current {
+        if !replaced.contains_key(key) {
+            changes.push(ConfigChange::Removed { key: join_key(prefix, key), value: old.to_string() });
+        }
+    }
+
+    for (key, new) in replaced {
+        let full_key = join_key(prefix, key);
+        match (current.get(key), new) {
+            (None, _) => changes.push(ConfigChange::Added { key: full_key, value: new.to_string() }),
+            (Some(toml::Value::Table(old)), toml::Value::Table(new)) => diff_tables(old, new, &full_key, changes),
+            (Some(toml::Value::Array(old)), toml::Value::Array(new)) => {
+                for item in old.iter().filter(|item| !new.contains(item)) {
+                    changes.push(ConfigChange::Removed { key: full_key.clone(), value: item.to_string() });
+                }
+                for item in new.iter().filter(|item| !old.contains(item)) {
+                    changes.push(ConfigChange::Added { key: full_key.clone(), value: item.to_string() });
+                }
+            }
+            (Some(old), new) => {
+                if old != new {
+                    changes.push(ConfigChange::Changed { key: full_key, from: old.to_string(), to: new.to_string() });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn budgets_parse_plain_limits_and_tables() {
+        let config = parse(r#"
+            [budgets]
+            "youtube.com" = "30m"
+            slack = "1h"
+            chat = { limit = "1h30m", period = "week" }
+            news = { limit = "20m", carry_over = true, carry_over_cap = "45m" }
+        "#);
+        let rules = config.budget_rules();
+        let budgets: Vec<(&str, u64, BudgetPeriod, bool, u64)> = rules.iter()
+            .map(|budget| (budget.target.as_str(), budget.limit.as_secs(), budget.period, budget.carry_over, budget.carry_over_cap.as_secs()))
+            .collect();
+        assert_eq!(budgets, [
+            ("chat", 5400, BudgetPeriod::Week, false, 5400),
+            ("news", 1200, BudgetPeriod::Day, true, 2700),
+            ("slack", 3600, BudgetPeriod::Day, false, 3600),
+            ("youtube.com", 1800, BudgetPeriod::Day, false, 1800),
+        ]);
+    }
+
+    #[test]
+    fn malformed_budgets_are_reported_and_skipped() {
+        let config = parse(r#"
+            [budgets]
+            good = "45"
+            words = "half an hour"
+            trailing = "1h30"
+            cap = { limit = "1h", carry_over = true, carry_over_cap = "lots" }
+            weekly = { limit = "1h", period = "week", carry_over = true }
+        "#);
+        let rules: Vec<String> = config.budget_rules().into_iter().map(|budget| budget.target).collect();
+        assert_eq!(rules, ["good"]);
+        assert_eq!(config.budgets["good"].budget("good").unwrap().limit, Duration::from_secs(2700));
+
+        let error = |target: &str| config.budgets[target].budget(target).unwrap_err();
+        assert_eq!(error("words"), "invalid limit 'half an hour'");
+        assert_eq!(error("trailing"), "invalid limit '1h30'");
+        assert_eq!(error("cap"), "invalid carry_over_cap 'lots'");
+        assert!(error("weekly").contains("carry_over only works"));
+    }
+
+    #[test]
+    fn budget_entries_round_trip_in_their_shortest_form() {
+        let plain = BudgetTable { limit: "30m".to_string(), ..Default::default() };
+        assert_eq!(BudgetEntry::from_table(plain.clone()), BudgetEntry::Limit("30m".to_string()));
+        let weekly = BudgetTable { period: BudgetPeriod::Week, ..plain };
+        assert_eq!(BudgetEntry::from_table(weekly.clone()), BudgetEntry::Table(weekly.clone()));
+        assert_eq!(BudgetEntry::Table(weekly).to_string(), "30m per week");
+    }
+
+    fn changes(changes: &[ConfigChange]) -> Vec<String> {
+        changes.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn merging_a_bundle_dedupes_lists_and_lets_the_bundle_win_scalars() {
+        let current = parse(r#"
+            focus_apps = ["code", "kitty"]
+            focus_sites = ["github.com"]
+            tracking_interval_ms = 1000
+            daily_focus_goal_minutes = 300
+            export_dir = "/home/me/exports"
+            [budgets]
+            slack = "1h"
+            [score]
+            switch_weight = 0.3
+        "#);
+        let bundle = parse(r#"
+            focus_apps = ["obsidian", "code", "obsidian"]
+            focus_sites = ["github.com"]
+            tracking_interval_ms = 500
+            export_dir = "/mnt/other/exports"
+            [budgets]
+            slack = "30m"
+            "youtube.com" = "20m"
+            [score]
+            switch_weight = 0.3
+        "#);
+
+        let (merged, changed) = current.import_bundle(&bundle, false).unwrap();
+        // Existing order first; the duplicate inside the bundle goes too
+        assert_eq!(merged.focus_apps, ["code", "kitty", "obsidian"]);
+        assert_eq!(merged.focus_sites, ["github.com"]);
+        assert_eq!(merged.tracking_interval_ms, 500);
+        // Keys the bundle only has as defaults still overwrite
+        assert_eq!(merged.daily_focus_goal_minutes, 240);
+        assert_eq!(merged.budgets["slack"], BudgetEntry::Limit("30m".to_string()));
+        assert_eq!(merged.budgets["youtube.com"], BudgetEntry::Limit("20m".to_string()));
+        // Paths on this machine are never taken from a bundle
+        assert_eq!(merged.export_dir.as_deref(), Some("/home/me/exports"));
+
+        let changed = changes(&changed);
+        for expected in [
+            "+ focus_apps: \"obsidian\"",
+            "~ tracking_interval_ms: 1000 → 500",
+            "~ daily_focus_goal_minutes: 300 → 240",
+            "~ budgets.slack: \"1h\" → \"30m\"",
+            "+ budgets.youtube.com: \"20m\"",
+        ] {
+            assert!(changed.contains(&expected.to_string()), "{} not in {:#?}", expected, changed);
+        }
+        assert_eq!(changed.iter().filter(|change| change.starts_with("+ focus_apps")).count(), 1);
+        assert!(!changed.iter().any(|change| change.contains("export_dir") || change.contains("score.")), "{:#?}", changed);
+
+        // Importing the same bundle again changes nothing
+        let (again, changed) = merged.import_bundle(&bundle, false).unwrap();
+        assert!(changed.is_empty(), "{:#?}", changes(&changed));
+        assert_eq!(to_table(&again).unwrap(), to_table(&merged).unwrap());
+    }
+
+    #[test]
+    fn replacing_with_a_bundle_reports_what_goes_and_keeps_local_keys() {
+        let current = parse(r#"
+            focus_apps = ["code", "kitty"]
+            database_path = "/data/focusdebt.db"
+            [budgets]
+            slack = "1h"
+        "#);
+        let bundle = parse(r#"
+            focus_apps = ["code", "obsidian"]
+            database_path = "/elsewhere.db"
+        "#);
+
+        let (replaced, changed) = current.import_bundle(&bundle, true).unwrap();
+        assert_eq!(replaced.focus_apps, ["code", "obsidian"]);
+        assert!(replaced.budgets.is_empty());
+        assert_eq!(replaced.database_path.as_deref(), Some("/data/focusdebt.db"));
+        assert_eq!(changes(&changed), [
+            "- budgets.slack: \"1h\"",
+            "- focus_apps: \"kitty\"",
+            "+ focus_apps: \"obsidian\"",
+        ]);
+
+        let (_, changed) = replaced.import_bundle(&bundle, true).unwrap();
+        assert!(changed.is_empty(), "{:#?}", changes(&changed));
+    }
+
+    #[test]
+    fn exported_bundles_load_back_unchanged() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-config-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.toml");
+        let config = parse(r#"
+            focus_apps = ["code"]
+            focus_sites = ["docs.rs"]
+            holidays = ["2024-12-25"]
+            [budgets]
+            chat = { limit = "2h", period = "week" }
+            [groups]
+            writing = ["obsidian", "code"]
+        "#);
+
+        config.export_bundle(&path).unwrap();
+        let loaded = Config::load_bundle(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(to_table(&loaded).unwrap(), to_table(&config).unwrap());
+        let (imported, _) = Config::default().import_bundle(&loaded, true).unwrap();
+        assert_eq!(to_table(&imported).unwrap(), to_table(&config).unwrap());
+    }
+
+    #[test]
+    fn site_rules_classify_tabs_by_mode() {
+        let focus = ["domain:github.com".to_string(), "title:jira".to_string()];
+        let ignored = [r"re:/\bnotifications\b/".to_string()];
+        let classify = |title: &str| {
+            let (class, rule) = classify_window(None, None, Some(title), &focus, &ignored);
+            (class, rule.pattern)
+        };
+        assert_eq!(classify("Pull requests · GitHub"), (SessionClass::Focus, Some("domain:github.com".to_string())));
+        assert_eq!(classify("PROJ-7 - Jira"), (SessionClass::Focus, Some("title:jira".to_string())));
+        // Ignored sites win over focus ones
+        assert_eq!(classify("Notifications · GitHub").0, SessionClass::Ignored);
+        assert_eq!(classify("Funny memes - Google Docs"), (SessionClass::Distraction, None));
+    }
+
+    /// A stored row of `app`, flagged `is_focus_app` by whatever rules it was tracked under
+    fn stored(app: &str, domain: Option<&str>, is_focus_app: bool) -> FocusSession {
+        let start = chrono::Utc::now();
+        FocusSession {
+            start_time: start,
+            end_time: Some(start + chrono::Duration::minutes(5)),
+            app_name: app.to_string(),
+            window_title: domain.unwrap_or("window").to_string(),
+            domain: domain.map(str::to_string),
+            duration: Duration::from_secs(300),
+            is_focus_app,
+            session_name: "test".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: tracking::OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    #[test]
+    fn stored_sessions_are_reclassified_by_the_current_rules() {
+        let config = parse(r#"
+            focus_apps = ["fd-editor", "fd-steam"]
+            ignored_apps = ["fd-steam"]
+            focus_sites = ["domain:github.com"]
+            ignored_sites = ["title:reddit"]
+
+            [budgets]
+            fd-chat = "30m"
+        "#);
+        let classify = |session: FocusSession| {
+            let (class, rule) = config.classify_session(&session);
+            (class, rule.to_column())
+        };
+        // The stored flag plays no part
+        assert_eq!(classify(stored("fd-editor", None, false)), (SessionClass::Focus, "focus_app:fd-editor".to_string()));
+        assert_eq!(classify(stored("fd-chat", None, true)), (SessionClass::Distraction, "default".to_string()));
+        // Ignored apps win over focus_apps
+        assert_eq!(classify(stored("fd-steam", None, true)), (SessionClass::Ignored, "ignored_app:fd-steam".to_string()));
+        assert_eq!(classify(stored("FD-Steam", None, true)).0, SessionClass::Distraction);
+        assert_eq!(classify(stored("fd-firefox", Some("Issue #4 · GitHub"), false)), (SessionClass::Focus, "focus_site:domain:github.com".to_string()));
+        assert_eq!(classify(stored("fd-firefox", Some("r/rust - Reddit"), true)), (SessionClass::Ignored, "ignored_site:title:reddit".to_string()));
+        // Site rules only see a tab, not an app's window title
+        assert_eq!(classify(stored("fd-term", None, true)).0, SessionClass::Distraction);
+    }
+
+    /// Local time on the week of Monday 2024-05-20
+    fn local(day: &str, time: &str) -> chrono::NaiveDateTime {
+        let offset = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"].iter().position(|name| *name == day).unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 20).unwrap() + chrono::Duration::days(offset as i64);
+        date.and_time(chrono::NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn quiet_ranges_parse_times_and_weekdays() {
+        let every_day = QuietRange::parse(" 22:00-07:00 ").unwrap();
+        assert_eq!((every_day.spec.as_str(), every_day.days, every_day.start, every_day.end), ("22:00-07:00", [true; 7], 22 * 60, 7 * 60));
+
+        let lunch = QuietRange::parse("mon 12:00-13:00").unwrap();
+        assert_eq!(lunch.days, [true, false, false, false, false, false, false]);
+        assert_eq!(QuietRange::parse("mon-fri 18:00-24:00").unwrap().days, [true, true, true, true, true, false, false]);
+        // Day ranges wrap around the week
+        assert_eq!(QuietRange::parse("Fri-Mon 00:00-09:00").unwrap().days, [true, false, false, false, true, true, true]);
+        assert_eq!(QuietRange::parse("sat-sun 13:00-24:00").unwrap().end, 24 * 60);
+
+        for bad in ["22:00", "22:00-", "25:00-07:00", "22:00-07:60", "noon-13:00", "moon 12:00-13:00", "mon-xyz 12:00-13:00", "12:00-12:00", "24:00-07:00", ""] {
+            assert!(QuietRange::parse(bad).is_err(), "{:?} parsed", bad);
+        }
+        let config = parse(r#"quiet_hours = ["22:00-07:00", "someday 12:00-13:00", "sat 09:00-10:00"]"#);
+        let specs: Vec<String> = config.quiet_ranges().into_iter().map(|range| range.spec).collect();
+        assert_eq!(specs, ["22:00-07:00", "sat 09:00-10:00"]);
+    }
+
+    #[test]
+    fn quiet_ranges_contain_times_across_midnight() {
+        let night = QuietRange::parse("22:00-07:00").unwrap();
+        assert!(night.contains(local("tue", "22:00")));
+        assert!(night.contains(local("tue", "23:59")));
+        assert!(night.contains(local("wed", "00:00")));
+        assert!(night.contains(local("wed", "06:59")));
+        assert!(!night.contains(local("wed", "07:00")));
+        assert!(!night.contains(local("wed", "21:59")));
+        assert!(!night.contains(local("wed", "12:00")));
+
+        let evening = QuietRange::parse("mon-fri 18:00-24:00").unwrap();
+        assert!(evening.contains(local("fri", "23:59")));
+        assert!(!evening.contains(local("fri", "17:59")));
+        assert!(!evening.contains(local("sat", "00:00")));
+        assert!(!evening.contains(local("sat", "19:00")));
+
+        // A range past midnight belongs to the day it starts on
+        let friday_night = QuietRange::parse("fri 22:00-07:00").unwrap();
+        assert!(friday_night.contains(local("fri", "23:00")));
+        assert!(friday_night.contains(local("sat", "06:30")));
+        assert!(!friday_night.contains(local("fri", "06:30")));
+        assert!(!friday_night.contains(local("sat", "23:00")));
+        // Sunday night runs into Monday
+        let sunday_night = QuietRange::parse("sun 23:00-01:00").unwrap();
+        assert!(sunday_night.contains(local("mon", "00:30")));
+        assert!(!sunday_night.contains(local("tue", "00:30")));
+    }
+
+    #[test]
+    fn classification_precedence_is_ignored_then_focus_then_default() {
+        let focus = ["title:wiki".to_string()];
+        let ignored = ["title:meal".to_string()];
+        let kind = |ignored_app, focus_app, title| classify_window(ignored_app, focus_app, title, &focus, &ignored).1.kind;
+        // Every rule matches: the ignored app wins
+        assert_eq!(kind(Some("fd-notes"), Some("fd-notes"), Some("Meal wiki")), RuleKind::IgnoredApp);
+        // Ignored sites beat the app being a focus app
+        assert_eq!(kind(None, Some("fd-notes"), Some("Meal wiki")), RuleKind::IgnoredSite);
+        // Focus apps beat focus sites
+        assert_eq!(kind(None, Some("fd-notes"), Some("Engineering Wiki")), RuleKind::FocusApp);
+        assert_eq!(kind(None, None, Some("Engineering Wiki")), RuleKind::FocusSite);
+        assert_eq!(kind(None, None, Some("Inbox")), RuleKind::Default);
+        // Without site text, site rules never match
+        assert_eq!(kind(None, None, None), RuleKind::Default);
+        assert_eq!(RuleKind::PRECEDENCE.map(|kind| kind.class()), [
+            SessionClass::Ignored, SessionClass::Ignored, SessionClass::Focus, SessionClass::Focus, SessionClass::Distraction,
+        ]);
+    }
+
+    #[test]
+    fn site_rules_reach_app_titles_only_when_enabled() {
+        let toml = r#"
+            focus_sites = ["title:engineering wiki"]
+            ignored_sites = ["title:meal planning"]
+            site_apps = ["fd-notes"]
+        "#;
+        let off = parse(toml);
+        let on = parse(&format!("apply_sites_to_apps = true\n{}", toml));
+        let page = |title: &str| FocusSession { window_title: title.to_string(), ..stored("fd-notes", None, false) };
+
+        assert_eq!(on.classify_session(&page("Notion — Engineering Wiki")).0, SessionClass::Focus);
+        assert_eq!(on.classify_session(&page("Notion — Meal planning")).0, SessionClass::Ignored);
+        assert_eq!(on.classify_session(&page("Notion — Inbox")).0, SessionClass::Distraction);
+        assert_eq!(off.classify_session(&page("Notion — Engineering Wiki")).0, SessionClass::Distraction);
+        // Apps off the list keep app-level rules only
+        let other = FocusSession { window_title: "Engineering Wiki".to_string(), ..stored("fd-chat", None, false) };
+        assert_eq!(on.classify_session(&other).0, SessionClass::Distraction);
+        assert_eq!(on.site_text("FD-Notes", None, "Engineering Wiki"), Some("Engineering Wiki"));
+    }
+
+    #[test]
+    fn json_values_set_keys_through_serde() {
+        let config = Config::default();
+        let set = config.with_json_value("focus_apps", &serde_json::json!(["fd-editor"])).unwrap();
+        assert_eq!(set.focus_apps, ["fd-editor"]);
+        let set = set.with_json_value("profile", &serde_json::Value::Null).unwrap();
+        assert_eq!(set.profile, None);
+        assert_eq!(set.with_json_value("score", &serde_json::json!({"switch_weight": 0.5})).unwrap().score.switch_weight, 0.5);
+
+        assert_eq!(config.with_json_value("focus_app", &serde_json::json!([])).unwrap_err(), "Unknown configuration key: focus_app");
+        let error = config.with_json_value("tracking_interval_ms", &serde_json::json!("fast")).unwrap_err();
+        assert!(error.starts_with("Invalid value for tracking_interval_ms:"), "{}", error);
+
+        // `config show --json` output reads back as the same config
+        let shown = serde_json::to_string(&config).unwrap();
+        let read: Config = serde_json::from_str(&shown).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+
+    #[test]
+    fn sourced_values_mark_command_line_overrides() {
+        let config = Config { profile: Some("fd-writing".to_string()), ..Config::default() };
+        let values = config.sourced_values(&[("profile", serde_json::json!("fd-writing"))]).unwrap();
+        assert_eq!(values["profile"].source, ConfigSource::Cli);
+        assert_eq!(values["profile"].value, "fd-writing");
+        assert_eq!(values["tracking_interval_ms"].value, config.tracking_interval_ms);
+        assert!(values.contains_key("score"));
+
+        let names = Config::key_names();
+        assert!(names.contains(&"score.switch_weight".to_string()), "{:?}", names);
+        assert!(names.contains(&"focus_apps".to_string()));
+        assert!(!names.contains(&"score".to_string()));
+    }
+
+    fn rules(config: &Config) -> Vec<&'static str> {
+        config.lint().iter().map(|warning| warning.rule).collect()
+    }
+
+    /// `config` with the warning's suggested `config set` applied
+    fn follow(config: &Config, warning: &LintWarning) -> Config {
+        let (key, value) = warning.suggestion.split_once(' ').unwrap();
+        config.with_json_value(key, &serde_json::from_str(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn the_defaults_lint_clean() {
+        assert!(Config::default().lint().is_empty());
+    }
+
+    #[test]
+    fn coarse_tracking_misses_short_visits() {
+        let at = |tracking_interval_ms, min_record_seconds| Config { tracking_interval_ms, min_record_seconds, ..Config::default() };
+        assert!(rules(&at(2000, 1)).is_empty());
+        assert_eq!(rules(&at(2001, 1)), ["tracking-vs-min-record"]);
+        // A longer minimum stretches what counts as short
+        assert!(rules(&at(5000, 5)).is_empty());
+
+        let config = at(5000, 3);
+        let warning = &config.lint()[0];
+        assert_eq!(warning.suggestion, "tracking_interval_ms 3000");
+        assert!(warning.message.starts_with("tracking_interval_ms is 5000, so window visits shorter than "), "{}", warning.message);
+        assert!(warning.message.ends_with("even though min_record_seconds is 3"), "{}", warning.message);
+        assert_eq!(warning.to_string(), format!("{} (try 'focusdebt config set tracking_interval_ms 3000')", warning.message));
+        assert!(follow(&config, warning).lint().is_empty());
+    }
+
+    #[test]
+    fn long_save_intervals_risk_losing_rows() {
+        let config = Config { save_interval_ms: 5 * 60 * 1000, ..Config::default() };
+        assert_eq!(rules(&config), ["save-vs-crash-loss"]);
+        let warning = &config.lint()[0];
+        assert_eq!(warning.suggestion, format!("save_interval_ms {}", default_save_interval()));
+        assert!(warning.message.contains(&lint_duration(5 * 60 * 1000)), "{}", warning.message);
+        assert!(follow(&config, warning).lint().is_empty());
+        assert!(rules(&Config { save_interval_ms: MAX_UNSAVED_MS, ..Config::default() }).is_empty());
+    }
+
+    #[test]
+    fn saving_faster_than_ticking_finds_nothing_new() {
+        let config = Config { save_interval_ms: 500, ..Config::default() };
+        assert_eq!(rules(&config), ["save-vs-tracking"]);
+        assert_eq!(config.lint()[0].message, "save_interval_ms (500) is shorter than tracking_interval_ms (1000), so most saves find nothing new");
+        assert!(follow(&config, &config.lint()[0]).lint().is_empty());
+        assert!(rules(&Config { save_interval_ms: 1000, ..Config::default() }).is_empty());
+
+        // Raised to the tracking interval when that is the longer one
+        let config = Config { tracking_interval_ms: 60_000, save_interval_ms: 40_000, min_record_seconds: 60, ..Config::default() };
+        assert_eq!(rules(&config), ["save-vs-tracking"]);
+        assert_eq!(config.lint()[0].suggestion, "save_interval_ms 60000");
+    }
+
+    #[test]
+    fn activity_needs_several_ticks_a_minute() {
+        let config = Config {
+            tracking_interval_ms: 20_000,
+            min_record_seconds: 30,
+            track_input_activity: true,
+            ..Config::default()
+        };
+        assert_eq!(rules(&config), ["activity-vs-tracking"]);
+        let warning = &config.lint()[0];
+        assert_eq!(warning.suggestion, "tracking_interval_ms 15000");
+        assert!(warning.message.ends_with("passive stretches of 10 minutes won't be told apart"), "{}", warning.message);
+        assert!(follow(&config, warning).lint().is_empty());
+
+        // Only while activity is tracked and passive runs are on
+        assert!(rules(&Config { track_input_activity: false, ..config.clone() }).is_empty());
+        assert!(rules(&Config { passive_after_minutes: 0, ..config.clone() }).is_empty());
+    }
+
+    #[test]
+    fn every_broken_rule_is_reported_in_order() {
+        let config = Config {
+            tracking_interval_ms: 60_000,
+            save_interval_ms: 10_000,
+            track_input_activity: true,
+            ..Config::default()
+        };
+        assert_eq!(rules(&config), ["tracking-vs-min-record", "save-vs-tracking", "activity-vs-tracking"]);
+        // Following the suggestions one at a time settles
+        let mut fixed = config;
+        for _ in 0..LINT_RULES.len() {
+            let Some(warning) = fixed.lint().into_iter().next() else { break };
+            fixed = follow(&fixed, &warning);
+        }
+        assert!(fixed.lint().is_empty(), "{:#?}", fixed.lint());
+    }
+
+    #[test]
+    fn an_app_may_be_in_one_group_at_most() {
+        let groups = parse(r#"
+            [groups]
+            rust-toolchain = ["code", "Alacritty", " zellij ", "code", ""]
+            chat = ["slack"]
+        "#).app_groups().unwrap();
+        assert_eq!(groups.group_of("alacritty"), Some("rust-toolchain"));
+        assert_eq!(groups.group_of("Code"), Some("rust-toolchain"));
+        assert_eq!(groups.group_of("zellij"), Some("rust-toolchain"));
+        assert_eq!(groups.group_of("slack"), Some("chat"));
+        assert_eq!(groups.group_of("firefox"), None);
+        assert!(Config::default().app_groups().unwrap().is_empty());
+
+        let error = parse(r#"
+            [groups]
+            editors = ["code"]
+            rust-toolchain = ["CODE", "alacritty"]
+        "#).app_groups().unwrap_err();
+        assert!(error.contains("'CODE' is in both groups 'editors' and 'rust-toolchain'"), "{}", error);
+        assert!(parse("[groups]\n\" \" = [\"code\"]\n").app_groups().is_err());
+    }
+
+    #[test]
+    fn assistant_sites_default_on_and_counts_as_parses() {
+        let config = parse("");
+        assert_eq!(config.assistant_sites.len(), DEFAULT_ASSISTANT_SITES.len());
+        assert_eq!(config.assistant_counts_as, AssistantCountsAs::Neutral);
+        for site in DEFAULT_ASSISTANT_SITES {
+            assert!(utils::has_site_prefix(site) && utils::SiteRule::parse(site).is_ok(), "{}", site);
+        }
+
+        let config = parse("assistant_sites = []\nassistant_counts_as = \"focus\"\n");
+        assert!(config.assistant_sites.is_empty());
+        assert_eq!(config.assistant_counts_as, AssistantCountsAs::Focus);
+        for counts_as in [AssistantCountsAs::Focus, AssistantCountsAs::Distraction, AssistantCountsAs::Neutral] {
+            assert_eq!(AssistantCountsAs::parse(&counts_as.label().to_uppercase()), Some(counts_as));
+        }
+        assert_eq!(AssistantCountsAs::parse("sometimes"), None);
+        assert!(toml::from_str::<Config>("assistant_counts_as = \"sometimes\"\n").is_err());
+    }
+
+    #[test]
+    fn display_glyphs_defaults_to_auto_and_parses() {
+        assert_eq!(parse("").display_glyphs, DisplayGlyphs::Auto);
+        assert_eq!(parse("display_glyphs = \"ascii\"\n").display_glyphs, DisplayGlyphs::Ascii);
+        for glyphs in [DisplayGlyphs::Auto, DisplayGlyphs::Unicode, DisplayGlyphs::Ascii] {
+            assert_eq!(DisplayGlyphs::parse(&format!(" {} ", glyphs.label().to_uppercase())), Some(glyphs));
+        }
+        assert_eq!(DisplayGlyphs::parse("emoji"), None);
+        assert!(toml::from_str::<Config>("display_glyphs = \"emoji\"\n").is_err());
+    }
+
+    #[test]
+    fn disk_warning_days_defaults_to_thirty() {
+        assert_eq!(parse("").disk_warning_days, 30);
+        assert_eq!(parse("disk_warning_days = 0\n").disk_warning_days, 0);
+        assert!(toml::from_str::<Config>("disk_warning_days = -1\n").is_err());
+    }
+
+    #[test]
+    fn sub_second_ticks_need_a_native_backend() {
+        let backend = crate::tracking::platform::Backend::ALL[0].name().to_string();
+        let config = Config { tracking_interval_ms: 250, backend: Some(backend), ..Config::default() };
+        assert_eq!(rules(&config), ["subsecond-vs-backend"]);
+        let warning = &config.lint()[0];
+        assert!(warning.message.ends_with(", so the daemon refuses to start"), "{}", warning.message);
+        assert_eq!(warning.suggestion, "tracking_interval_ms 1000");
+        assert!(follow(&config, warning).lint().is_empty());
+    }
+
+    #[test]
+    fn notifications_are_only_counted_when_asked() {
+        let config = parse("");
+        assert!(!config.count_notifications);
+        assert_eq!(config.notification_command, None);
+        let config = parse("count_notifications = true\nnotification_command = \"my-notify-feed\"\n");
+        assert!(config.count_notifications);
+        assert_eq!(config.notification_command.as_deref(), Some("my-notify-feed"));
+    }
+
+    #[test]
+    fn task_links_are_off_until_configured() {
+        let config = parse("");
+        assert!(!config.taskwarrior_integration);
+        assert_eq!(config.todo_txt_file, None);
+        let config = parse(r#"
+            taskwarrior_integration = true
+            todo_txt_file = "~/notes/todo.txt"
+        "#);
+        assert!(config.taskwarrior_integration);
+        assert_eq!(config.todo_txt_file.as_deref(), Some("~/notes/todo.txt"));
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+
+use crate::config::{is_site_app, Config};
+use crate::tracking::{BrowserTabTracking, FocusTracker};
+use crate::utils::{self, DurationStyle, SiteMode, SiteRule};
+
+/// What is wrong between two or more focus rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConflictKind {
+    /// The rules say different things about the same windows; the
+    /// explanation says which one wins
+    Contradiction,
+    /// A rule that can never take effect
+    Shadowed,
+}
+
+impl ConflictKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConflictKind::Contradiction => "contradiction",
+            ConflictKind::Shadowed => "shadowed",
+        }
+    }
+}
+
+/// One finding of `config doctor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleConflict {
+    pub kind: ConflictKind,
+    /// The entries involved, e.g. `focus_apps "chrome"`
+    pub rules: Vec<String>,
+    pub explanation: String,
+}
+
+impl RuleConflict {
+    fn new(kind: ConflictKind, rules: Vec<String>, explanation: String) -> Self {
+        Self { kind, rules, explanation }
+    }
+}
+
+/// `list "entry"`, how conflicts name a rule
+fn entry(list: &str, value: &str) -> String {
+    format!("{} \"{}\"", list, value)
+}
+
+/// Every contradiction and shadowed rule among the focus and ignore lists,
+/// site entries, terminal title rules, budgets and groups, shadowed ones first
+pub fn analyze(config: &Config) -> Vec<RuleConflict> {
+    let mut conflicts = Vec::new();
+    check_duplicates(config, &mut conflicts);
+    check_apps(config, &mut conflicts);
+    check_sites(config, &mut conflicts);
+    check_title_rules(config, &mut conflicts);
+    check_budgets(config, &mut conflicts);
+    check_groups(config, &mut conflicts);
+    conflicts.sort_by_key(|conflict| std::cmp::Reverse(conflict.kind));
+    conflicts
+}
+
+/// Whether site rules see `app`'s windows in newly tracked time: a
+/// browser's tabs, or the titles of a site app
+fn sees_sites(config: &Config, app: &str) -> bool {
+    (FocusTracker::is_browser_app(app) && config.browser_tab_tracking != BrowserTabTracking::Off)
+        || (config.apply_sites_to_apps && is_site_app(&config.site_apps, app))
+}
+
+/// Apps compare ignoring case
+fn app_key(app: &str) -> String {
+    app.trim().to_lowercase()
+}
+
+/// Sites compare by what they match
+fn site_key(site: &str) -> String {
+    SiteRule::parse(site).map(|rule| rule.to_entry()).unwrap_or_else(|_| site.to_string())
+}
+
+fn check_duplicates(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    find_duplicates("focus_apps", &config.focus_apps, app_key, conflicts);
+    find_duplicates("ignored_apps", &config.ignored_apps, app_key, conflicts);
+    find_duplicates("focus_sites", &config.focus_sites, site_key, conflicts);
+    find_duplicates("ignored_sites", &config.ignored_sites, site_key, conflicts);
+}
+
+/// Entries `list` holds twice, by `key`
+fn find_duplicates(list: &str, entries: &[String], key: fn(&str) -> String, conflicts: &mut Vec<RuleConflict>) {
+    let mut seen: BTreeMap<String, &String> = BTreeMap::new();
+    for value in entries {
+        match seen.get(&key(value)) {
+            Some(first) => conflicts.push(RuleConflict::new(
+                ConflictKind::Shadowed,
+                vec![entry(list, first), entry(list, value)],
+                format!("{} holds the same rule twice; the second never matches anything the first doesn't", list),
+            )),
+            None => {
+                seen.insert(key(value), value);
+            }
+        }
+    }
+}
+
+/// Focus apps that are also ignored, and focus apps whose tabs ignored_sites
+/// take away
+fn check_apps(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    for app in &config.focus_apps {
+        if let Some(ignored) = config.matching_ignored_app(app) {
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Shadowed,
+                vec![entry("focus_apps", app), entry("ignored_apps", ignored)],
+                format!("ignored_apps comes first, so {}'s time is left out of reports and the focus_apps entry never applies", app),
+            ));
+            continue;
+        }
+        if !config.ignored_sites.is_empty() && sees_sites(config, app) {
+            let mut rules = vec![entry("focus_apps", app)];
+            rules.extend(config.ignored_sites.iter().map(|site| entry("ignored_sites", site)));
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Contradiction,
+                rules,
+                format!("ignored_sites comes before focus_apps: {}'s windows matching an ignored site are ignored, the rest count as focus", app),
+            ));
+        }
+    }
+}
+
+/// Whether every title `inner` matches is also matched by `outer`, as far
+/// as that can be told without trying titles: the same rule, or title text
+/// contained in the other's
+fn site_covers(outer: &SiteRule, inner: &SiteRule) -> bool {
+    match (outer.mode, inner.mode) {
+        _ if outer.to_entry() == inner.to_entry() => true,
+        (SiteMode::Title, SiteMode::Title) => inner.value.to_lowercase().contains(&outer.value.to_lowercase()),
+        _ => false,
+    }
+}
+
+/// Site entries that can't match: invalid ones, focus sites an ignored
+/// site always matches first, and any when nothing records sites
+fn check_sites(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    let parse = |list: &str, sites: &[String], conflicts: &mut Vec<RuleConflict>| -> Vec<(String, SiteRule)> {
+        sites.iter()
+            .filter_map(|site| match SiteRule::parse(site) {
+                Ok(rule) => Some((site.clone(), rule)),
+                Err(e) => {
+                    conflicts.push(RuleConflict::new(ConflictKind::Shadowed, vec![entry(list, site)], format!("never matches: {}", e)));
+                    None
+                }
+            })
+            .collect()
+    };
+    let focus_sites = parse("focus_sites", &config.focus_sites, conflicts);
+    let ignored_sites = parse("ignored_sites", &config.ignored_sites, conflicts);
+
+    for (focus, focus_rule) in &focus_sites {
+        // Duplicates within one list were reported already; this is across lists
+        if let Some((ignored, _)) = ignored_sites.iter().find(|(_, ignored_rule)| site_covers(ignored_rule, focus_rule)) {
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Shadowed,
+                vec![entry("focus_sites", focus), entry("ignored_sites", ignored)],
+                "every title the focus_sites entry matches also matches the ignored_sites entry, which comes first, so it never applies".to_string(),
+            ));
+        }
+    }
+
+    let site_apps_on = config.apply_sites_to_apps && !config.site_apps.is_empty();
+    if config.browser_tab_tracking == BrowserTabTracking::Off && !site_apps_on && !(focus_sites.is_empty() && ignored_sites.is_empty()) {
+        let rules = focus_sites.iter().map(|(site, _)| entry("focus_sites", site))
+            .chain(ignored_sites.iter().map(|(site, _)| entry("ignored_sites", site)))
+            .collect();
+        conflicts.push(RuleConflict::new(
+            ConflictKind::Shadowed,
+            rules,
+            "browser_tab_tracking is off and no site_apps are in use, so newly tracked time has no tabs or titles for site entries to match".to_string(),
+        ));
+    }
+}
+
+/// Title rules that can't run, and rules that rename an ignored terminal's
+/// rows out from under its ignore
+fn check_title_rules(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    let mut seen: Vec<(String, &str)> = Vec::new();
+    for rule in &config.terminal_title_rules {
+        let name = entry("terminal_title_rules", &format!("{}: {}", rule.terminal, rule.pattern));
+        if let Err(e) = regex::Regex::new(&rule.pattern) {
+            conflicts.push(RuleConflict::new(ConflictKind::Shadowed, vec![name], format!("the pattern doesn't compile, so the rule never runs: {}", e)));
+            continue;
+        }
+        let key = (rule.terminal.to_lowercase(), rule.pattern.as_str());
+        if seen.contains(&key) {
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Shadowed,
+                vec![name],
+                format!("{} has this rule twice; the first always parses whatever the second would", rule.terminal),
+            ));
+            continue;
+        }
+        seen.push(key);
+        if let Some(ignored) = config.matching_ignored_app(&rule.terminal) {
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Contradiction,
+                vec![name, entry("ignored_apps", ignored)],
+                format!("the rule records {}'s windows under the program in their title, so the ignored_apps entry only catches windows whose title it can't parse; ignore the programs too, or drop the rule",
+                    rule.terminal),
+            ));
+        }
+    }
+}
+
+/// Budgets that don't parse, and budgets on ignored or focus time, which
+/// keep counting whatever the class
+fn check_budgets(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    for (target, budget_entry) in &config.budgets {
+        let name = entry("budgets", target);
+        let budget = match budget_entry.budget(target) {
+            Ok(budget) => budget,
+            Err(e) => {
+                conflicts.push(RuleConflict::new(ConflictKind::Shadowed, vec![name], format!("never counts anything: {}", e)));
+                continue;
+            }
+        };
+        let limit = utils::format_duration(budget.limit, DurationStyle::Long);
+        let ignored = config.matching_ignored_app(target).map(|app| entry("ignored_apps", app))
+            .or_else(|| config.ignored_sites.iter().find(|site| utils::title_matches_site(target, site)).map(|site| entry("ignored_sites", site)));
+        if let Some(ignored) = ignored {
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Contradiction,
+                vec![name, ignored],
+                format!("reports leave {}'s time out, but budgets count all time whatever its class, so the {} budget still fills up and alerts",
+                    target, limit),
+            ));
+            continue;
+        }
+        let focus = config.focus_apps.iter().find(|app| app.eq_ignore_ascii_case(target)).map(|app| entry("focus_apps", app))
+            .or_else(|| config.focus_sites.iter().find(|site| utils::title_matches_site(target, site)).map(|site| entry("focus_sites", site)));
+        if let Some(focus) = focus {
+            let ignored_tabs = if !config.ignored_sites.is_empty() && sees_sites(config, target) {
+                "; its ignored tabs count toward the budget as well"
+            } else {
+                ""
+            };
+            conflicts.push(RuleConflict::new(
+                ConflictKind::Contradiction,
+                vec![name, focus],
+                format!("budgets limit time and never change the class: {}'s time stays focus time, and the budget alerts once {} of it is used{}",
+                    target, limit, ignored_tabs),
+            ));
+        }
+    }
+}
+
+/// Apps in two groups, and ignored apps that no report will show in their group
+fn check_groups(config: &Config, conflicts: &mut Vec<RuleConflict>) {
+    if let Err(e) = config.app_groups() {
+        conflicts.push(RuleConflict::new(ConflictKind::Contradiction, vec!["groups".to_string()], e));
+    }
+    for (group, apps) in &config.groups {
+        for app in apps {
+            if let Some(ignored) = config.matching_ignored_app(app) {
+                conflicts.push(RuleConflict::new(
+                    ConflictKind::Shadowed,
+                    vec![entry(&format!("groups.{}", group), app), entry("ignored_apps", ignored)],
+                    format!("reports leave ignored apps out, so {} never adds to the group", app),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze_toml(toml: &str) -> Vec<RuleConflict> {
+        analyze(&toml::from_str(toml).unwrap())
+    }
+
+    /// The entries of each finding, with its kind
+    fn findings(conflicts: &[RuleConflict]) -> Vec<(ConflictKind, Vec<&str>)> {
+        conflicts.iter().map(|c| (c.kind, c.rules.iter().map(String::as_str).collect())).collect()
+    }
+
+    #[test]
+    fn a_config_without_overlaps_has_no_conflicts() {
+        assert_eq!(analyze_toml(r#"
+            focus_apps = ["fd-editor"]
+            ignored_apps = ["fd-music"]
+            focus_sites = ["title:engineering wiki"]
+            ignored_sites = ["title:meal planning"]
+        "#), Vec::new());
+    }
+
+    #[test]
+    fn duplicates_compare_apps_by_case_and_sites_by_what_they_match() {
+        let conflicts = analyze_toml(r#"
+            focus_apps = ["fd-editor", "FD-Editor "]
+            focus_sites = ["www.example.com", "domain:example.com"]
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["focus_apps \"fd-editor\"", "focus_apps \"FD-Editor \""]),
+            (ConflictKind::Shadowed, vec!["focus_sites \"www.example.com\"", "focus_sites \"domain:example.com\""]),
+        ]);
+    }
+
+    #[test]
+    fn an_ignored_app_shadows_the_same_focus_app() {
+        let conflicts = analyze_toml(r#"
+            focus_apps = ["fd-editor", "FD-Chat"]
+            ignored_apps = ["fd-editor", "fd-chat"]
+        "#);
+        // Names match exactly, as when classifying
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["focus_apps \"fd-editor\"", "ignored_apps \"fd-editor\""]),
+        ]);
+        assert!(conflicts[0].explanation.starts_with("ignored_apps comes first"), "{}", conflicts[0].explanation);
+    }
+
+    #[test]
+    fn ignored_sites_take_tabs_from_a_focus_browser() {
+        let toml = r#"
+            focus_apps = ["firefox", "fd-editor"]
+            ignored_sites = ["title:meal planning"]
+        "#;
+        assert_eq!(findings(&analyze_toml(toml)), vec![
+            (ConflictKind::Contradiction, vec!["focus_apps \"firefox\"", "ignored_sites \"title:meal planning\""]),
+        ]);
+        // A site app only when its titles go through site rules
+        let with_site_app = format!("{}\nsite_apps = [\"fd-editor\"]\n", toml);
+        assert_eq!(analyze_toml(&with_site_app).len(), 1);
+        let applied = format!("{}\napply_sites_to_apps = true\n", with_site_app);
+        assert_eq!(findings(&analyze_toml(&applied))[1],
+            (ConflictKind::Contradiction, vec!["focus_apps \"fd-editor\"", "ignored_sites \"title:meal planning\""]));
+    }
+
+    #[test]
+    fn an_ignored_site_covering_a_focus_site_shadows_it() {
+        let conflicts = analyze_toml(r#"
+            focus_sites = ["title:Team Wiki Recipes", "title:engineering wiki", "example.com"]
+            ignored_sites = ["title:wiki recipes", "domain:example.com"]
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["focus_sites \"title:Team Wiki Recipes\"", "ignored_sites \"title:wiki recipes\""]),
+            (ConflictKind::Shadowed, vec!["focus_sites \"example.com\"", "ignored_sites \"domain:example.com\""]),
+        ]);
+    }
+
+    #[test]
+    fn invalid_sites_and_sites_nothing_records_never_match() {
+        let conflicts = analyze_toml(r#"
+            focus_sites = ["re:/(unclosed/", "title:engineering wiki"]
+            ignored_sites = ["title:"]
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["focus_sites \"re:/(unclosed/\""]),
+            (ConflictKind::Shadowed, vec!["ignored_sites \"title:\""]),
+        ]);
+        assert!(conflicts[0].explanation.starts_with("never matches: invalid regex"), "{}", conflicts[0].explanation);
+
+        let off = analyze_toml(r#"
+            browser_tab_tracking = "off"
+            focus_sites = ["title:engineering wiki"]
+        "#);
+        assert_eq!(findings(&off), vec![(ConflictKind::Shadowed, vec!["focus_sites \"title:engineering wiki\""])]);
+        assert!(off[0].explanation.contains("browser_tab_tracking is off"));
+        // Site apps still give site entries titles to match
+        assert_eq!(analyze_toml(r#"
+            browser_tab_tracking = "off"
+            focus_sites = ["title:engineering wiki"]
+            site_apps = ["fd-editor"]
+            apply_sites_to_apps = true
+        "#), Vec::new());
+    }
+
+    #[test]
+    fn title_rules_that_never_run_or_rename_an_ignored_terminal() {
+        let conflicts = analyze_toml(r#"
+            ignored_apps = ["fd-term"]
+            [[terminal_title_rules]]
+            terminal = "fd-shell"
+            pattern = "(unclosed"
+            [[terminal_title_rules]]
+            terminal = "fd-shell"
+            pattern = "^(\\w+)"
+            [[terminal_title_rules]]
+            terminal = "FD-Shell"
+            pattern = "^(\\w+)"
+            [[terminal_title_rules]]
+            terminal = "fd-term"
+            pattern = "^(\\w+)"
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["terminal_title_rules \"fd-shell: (unclosed\""]),
+            (ConflictKind::Shadowed, vec!["terminal_title_rules \"FD-Shell: ^(\\w+)\""]),
+            (ConflictKind::Contradiction, vec!["terminal_title_rules \"fd-term: ^(\\w+)\"", "ignored_apps \"fd-term\""]),
+        ]);
+    }
+
+    #[test]
+    fn budgets_on_ignored_or_focus_time_and_budgets_that_dont_parse() {
+        let conflicts = analyze_toml(r#"
+            focus_apps = ["fd-editor"]
+            ignored_apps = ["fd-music"]
+            ignored_sites = ["title:meal planning"]
+            [budgets]
+            fd-editor = "2h"
+            fd-music = "30m"
+            "Meal Planning" = "15m"
+            fd-chat = "half an hour"
+            fd-mail = "20m"
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["budgets \"fd-chat\""]),
+            (ConflictKind::Contradiction, vec!["budgets \"Meal Planning\"", "ignored_sites \"title:meal planning\""]),
+            (ConflictKind::Contradiction, vec!["budgets \"fd-editor\"", "focus_apps \"fd-editor\""]),
+            (ConflictKind::Contradiction, vec!["budgets \"fd-music\"", "ignored_apps \"fd-music\""]),
+        ]);
+        assert!(conflicts[2].explanation.contains("once 2h 0m of it is used"), "{}", conflicts[2].explanation);
+    }
+
+    #[test]
+    fn a_focus_browser_budget_notes_its_ignored_tabs() {
+        let conflicts = analyze_toml(r#"
+            focus_apps = ["firefox"]
+            ignored_sites = ["title:meal planning"]
+            [budgets]
+            firefox = "1h"
+        "#);
+        let budget = conflicts.iter().find(|c| c.rules[0] == "budgets \"firefox\"").unwrap();
+        assert!(budget.explanation.ends_with("its ignored tabs count toward the budget as well"), "{}", budget.explanation);
+    }
+
+    #[test]
+    fn groups_with_shared_or_ignored_apps() {
+        let conflicts = analyze_toml(r#"
+            ignored_apps = ["fd-music"]
+            [groups]
+            chat = ["fd-chat", "fd-music"]
+            writing = ["fd-editor", "FD-Chat"]
+        "#);
+        assert_eq!(findings(&conflicts), vec![
+            (ConflictKind::Shadowed, vec!["groups.chat \"fd-music\"", "ignored_apps \"fd-music\""]),
+            (ConflictKind::Contradiction, vec!["groups"]),
+        ]);
+        assert!(conflicts[1].explanation.contains("'FD-Chat' is in both groups 'chat' and 'writing'"), "{}", conflicts[1].explanation);
+    }
+
+    #[test]
+    fn shadowed_rules_come_before_contradictions() {
+        let conflicts = analyze_toml(r#"
+            focus_apps = ["firefox", "fd-editor", "fd-editor"]
+            ignored_sites = ["title:meal planning"]
+        "#);
+        let kinds: Vec<_> = conflicts.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ConflictKind::Shadowed, ConflictKind::Contradiction]);
+        assert_eq!(ConflictKind::Shadowed.label(), "shadowed");
+        assert_eq!(ConflictKind::Contradiction.label(), "contradiction");
+    }
+}
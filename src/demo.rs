@@ -0,0 +1,428 @@
+use std::time::Duration;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+use crate::config::{RuleKind, RuleMatch, SessionSnapshot};
+use crate::storage::Database;
+use crate::tracking::{ContextSwitch, EventKind, FocusSession, OpenEnd, TrackerEvent};
+use crate::utils;
+
+/// The import source demo rows are stored under, so they can be told
+/// apart from tracked ones (`query "SELECT ... WHERE source = 'demo'"`)
+pub const DEMO_SOURCE: &str = "demo";
+
+pub const DEFAULT_DAYS: u32 = 14;
+pub const DEFAULT_SEED: u64 = 42;
+
+/// An app the generated day switches between
+struct DemoApp {
+    name: &'static str,
+    titles: &'static [&'static str],
+    /// Relative chance of being switched to
+    weight: u32,
+    focus: bool,
+    /// Shortest and longest row, in seconds
+    seconds: (u64, u64),
+}
+
+/// A browser tab: its title and the focus_sites entry it matches, if any
+struct DemoSite {
+    title: &'static str,
+    focus_site: Option<&'static str>,
+    weight: u32,
+    seconds: (u64, u64),
+}
+
+const BROWSER: &str = "firefox";
+/// Chance a switch goes to the browser rather than one of APPS
+const BROWSER_WEIGHT: u32 = 25;
+
+const APPS: &[DemoApp] = &[
+    DemoApp { name: "code", titles: &["main.rs - focusdebt", "storage.rs - focusdebt", "stats.rs - focusdebt", "README.md - focusdebt"], weight: 30, focus: true, seconds: (240, 2100) },
+    DemoApp { name: "kitty", titles: &["cargo test", "git log --oneline", "nvim notes.md", "htop"], weight: 15, focus: true, seconds: (60, 900) },
+    DemoApp { name: "obsidian", titles: &["Design notes - vault", "Weekly plan - vault"], weight: 6, focus: true, seconds: (180, 1200) },
+    DemoApp { name: "slack", titles: &["#general", "#team-backend", "Direct message"], weight: 12, focus: false, seconds: (20, 420) },
+    DemoApp { name: "thunderbird", titles: &["Inbox (3)", "Re: release notes", "Calendar"], weight: 5, focus: false, seconds: (30, 300) },
+    DemoApp { name: "spotify", titles: &["Spotify Premium"], weight: 3, focus: false, seconds: (15, 90) },
+    DemoApp { name: "zoom", titles: &["Zoom Meeting", "Standup"], weight: 2, focus: false, seconds: (600, 1800) },
+];
+
+const SITES: &[DemoSite] = &[
+    DemoSite { title: "Pull requests · focusdebt · GitHub", focus_site: Some("github.com"), weight: 10, seconds: (60, 900) },
+    DemoSite { title: "rusqlite - Rust - Docs.rs", focus_site: Some("docs.rs"), weight: 8, seconds: (45, 600) },
+    DemoSite { title: "sqlite - How to upsert a row - Stack Overflow", focus_site: Some("stackoverflow.com"), weight: 6, seconds: (30, 420) },
+    DemoSite { title: "YouTube", focus_site: None, weight: 6, seconds: (60, 900) },
+    DemoSite { title: "Hacker News", focus_site: None, weight: 5, seconds: (30, 480) },
+    DemoSite { title: "reddit: the front page of the internet", focus_site: None, weight: 4, seconds: (30, 360) },
+    DemoSite { title: "Inbox - Gmail", focus_site: None, weight: 4, seconds: (20, 240) },
+];
+
+/// What the day's named sessions are called, with the date appended
+const TASKS: &[&str] = &[
+    "api refactor", "bug triage", "write docs", "code review", "release prep", "flaky tests", "design spike", "planning",
+];
+
+/// Chance a row is preceded by time away from the computer
+const IDLE_CHANCE: f64 = 0.08;
+/// Chance of an evening session on a weekday, and of any work on a weekend day
+const EVENING_CHANCE: f64 = 0.3;
+const WEEKEND_CHANCE: f64 = 0.25;
+
+/// SplitMix64: tiny, and the same sequence for a seed on every platform
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `low..=high`
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next_u64() % (high - low + 1)
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() as u64 - 1) as usize]
+    }
+
+    fn weighted<'a, T>(&mut self, items: &'a [T], weight: impl Fn(&T) -> u32) -> &'a T {
+        let mut left = self.range(1, items.iter().map(|item| weight(item) as u64).sum());
+        for item in items {
+            if left <= weight(item) as u64 {
+                return item;
+            }
+            left -= weight(item) as u64;
+        }
+        &items[items.len() - 1]
+    }
+}
+
+/// One named stretch of work, as a daemon run would have recorded it
+#[derive(Debug, Clone)]
+pub struct DemoSession {
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub rows: Vec<FocusSession>,
+    pub switches: Vec<ContextSwitch>,
+    pub events: Vec<TrackerEvent>,
+}
+
+/// The focus_apps entries the generated rows were classified by
+pub fn focus_apps() -> Vec<String> {
+    APPS.iter().filter(|app| app.focus).map(|app| app.name.to_string()).collect()
+}
+
+/// The focus_sites entries the generated rows were classified by
+pub fn focus_sites() -> Vec<String> {
+    SITES.iter().filter_map(|site| site.focus_site).map(str::to_string).collect()
+}
+
+/// `days` days of sessions ending with `last_day`, the same for the same
+/// seed. Weekdays get a morning and an afternoon session and sometimes an
+/// evening one; weekend days now and then a short one.
+pub fn generate(days: u32, seed: u64, last_day: NaiveDate) -> Vec<DemoSession> {
+    let mut rng = Rng::new(seed);
+    let mut sessions = Vec::new();
+    for offset in (0..days as i64).rev() {
+        let day = last_day - chrono::Duration::days(offset);
+        let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+        // (earliest start in minutes after midnight, latest start, shortest, longest)
+        let mut blocks: Vec<(u64, u64, u64, u64)> = Vec::new();
+        if weekend {
+            if rng.chance(WEEKEND_CHANCE) {
+                blocks.push((10 * 60, 14 * 60, 30, 90));
+            }
+        } else {
+            blocks.push((8 * 60 + 30, 9 * 60 + 45, 90, 180));
+            blocks.push((13 * 60, 14 * 60, 120, 210));
+            if rng.chance(EVENING_CHANCE) {
+                blocks.push((20 * 60, 21 * 60 + 30, 45, 90));
+            }
+        }
+        for (earliest, latest, shortest, longest) in blocks {
+            let minutes = rng.range(earliest, latest);
+            let Some(start) = local_time(day, minutes) else { continue };
+            let length = Duration::from_secs(rng.range(shortest, longest) * 60);
+            let name = format!("{} {}", rng.pick(TASKS), day.format("%m-%d"));
+            sessions.push(generate_session(&mut rng, name, start, length));
+        }
+    }
+    sessions
+}
+
+/// `day` at `minutes` past midnight in the report timezone
+fn local_time(day: NaiveDate, minutes: u64) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::from_hms_opt((minutes / 60) as u32, (minutes % 60) as u32, 0)?;
+    utils::timezone::zone().from_local_datetime(&day.and_time(time)).earliest().map(|at| at.with_timezone(&Utc))
+}
+
+fn generate_session(rng: &mut Rng, name: String, start: DateTime<Utc>, length: Duration) -> DemoSession {
+    let end = start + chrono::Duration::from_std(length).unwrap_or_default();
+    let mut rows: Vec<FocusSession> = Vec::new();
+    let mut switches = Vec::new();
+    let mut at = start;
+    let mut last_switch = start;
+    while at < end {
+        if !rows.is_empty() && rng.chance(IDLE_CHANCE) {
+            at += chrono::Duration::seconds(rng.range(120, 720) as i64);
+        }
+        let mut row = generate_row(rng, &name, at);
+        // Switching to the same app is no switch; pick again, then give up
+        for _ in 0..3 {
+            if rows.last().is_none_or(|last| last.app_name != row.app_name) {
+                break;
+            }
+            row = generate_row(rng, &name, at);
+        }
+        let row_end = (at + chrono::Duration::from_std(row.duration).unwrap_or_default()).min(end);
+        if row_end <= at {
+            break;
+        }
+        row.end_time = Some(row_end);
+        row.duration = (row_end - at).to_std().unwrap_or_default();
+        if let Some(last) = rows.last() {
+            // As the tracker counts it: time since the last switch, when coming back to focus
+            switches.push(ContextSwitch {
+                timestamp: at,
+                from_app: last.app_name.clone(),
+                to_app: row.app_name.clone(),
+                recovery_time: row.is_focus_app.then(|| (at - last_switch).to_std().unwrap_or_default()),
+            });
+            last_switch = at;
+        }
+        at = row_end;
+        rows.push(row);
+    }
+
+    let events = vec![
+        TrackerEvent { timestamp: start, kind: EventKind::DaemonStart, payload: serde_json::json!({ "session_name": name }) },
+        TrackerEvent { timestamp: at, kind: EventKind::DaemonStop, payload: serde_json::json!({ "session_name": name }) },
+    ];
+    DemoSession { name, start, rows, switches, events }
+}
+
+fn generate_row(rng: &mut Rng, name: &str, at: DateTime<Utc>) -> FocusSession {
+    let total: u32 = APPS.iter().map(|app| app.weight).sum();
+    let (app_name, window_title, domain, focus, rule, seconds) = if rng.range(1, (total + BROWSER_WEIGHT) as u64) <= BROWSER_WEIGHT as u64 {
+        let site = rng.weighted(SITES, |site| site.weight);
+        let rule = match site.focus_site {
+            Some(entry) => RuleMatch::new(RuleKind::FocusSite, entry),
+            None => RuleMatch::default_rule(),
+        };
+        (BROWSER, format!("{} — Mozilla Firefox", site.title), Some(site.title.to_string()), site.focus_site.is_some(), rule, site.seconds)
+    } else {
+        let app = rng.weighted(APPS, |app| app.weight);
+        let rule = if app.focus { RuleMatch::new(RuleKind::FocusApp, app.name) } else { RuleMatch::default_rule() };
+        (app.name, rng.pick(app.titles).to_string(), None, app.focus, rule, app.seconds)
+    };
+    FocusSession {
+        start_time: at,
+        end_time: None,
+        app_name: app_name.to_string(),
+        window_title,
+        domain,
+        duration: Duration::from_secs(rng.range(seconds.0, seconds.1)),
+        is_focus_app: focus,
+        session_name: name.to_string(),
+        activity: None,
+        passive: None,
+        workspace: None,
+        power: None,
+        open_end: OpenEnd::Closed,
+        manual: false,
+        focus_rule: Some(rule),
+        co_focus: None,
+    }
+}
+
+/// The key that makes writing the same demo rows twice a no-op
+pub fn row_key(seed: u64, row: &FocusSession) -> String {
+    format!("{}|{}|{}", seed, row.start_time.timestamp(), row.app_name)
+}
+
+/// What `write` added to the database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Written {
+    pub sessions: usize,
+    pub rows: usize,
+    pub switches: usize,
+}
+
+/// Write `sessions` into `db` through the same calls the daemon and the
+/// importers use, leaving out whatever is after `now`. Sessions an earlier
+/// run with `seed` wrote are skipped, their switches with them.
+pub fn write(db: &Database, sessions: &[DemoSession], seed: u64, snapshot: &SessionSnapshot, now: DateTime<Utc>) -> Result<Written, String> {
+    let mut written = Written::default();
+    let mut switches = Vec::new();
+    let mut events = Vec::new();
+    for session in sessions.iter().filter(|session| session.start < now) {
+        let keyed: Vec<(String, FocusSession)> = session.rows.iter()
+            .filter(|row| row.end_time.is_some_and(|end| end <= now))
+            .map(|row| (row_key(seed, row), row.clone()))
+            .collect();
+        let result = db.import_focus_sessions(DEMO_SOURCE, &keyed).and_then(|inserted| {
+            // Written by an earlier run with this seed; its switches are there too
+            if inserted == 0 {
+                return Ok(());
+            }
+            db.claim_session_name(&session.name, true)?;
+            db.save_session_meta(&session.name, session.start, snapshot)?;
+            written.sessions += 1;
+            written.rows += inserted;
+            switches.extend(session.switches.iter().filter(|switch| switch.timestamp <= now).cloned());
+            events.extend(session.events.iter().filter(|event| event.timestamp <= now).cloned());
+            Ok(())
+        });
+        if let Err(e) = result {
+            return Err(format!("Failed to write session \"{}\": {}", session.name, e));
+        }
+    }
+    db.save_switches_and_events(&switches, &events)
+        .map_err(|e| format!("Failed to write context switches: {}", e))?;
+    written.switches = switches.len();
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::stats::{Stats, StatsSettings};
+    use crate::progress::Progress;
+    use crate::utils::LocalDays;
+    use crate::utils::timezone::Zone;
+
+    /// Sunday, so the two weeks end on a weekend
+    fn last_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 17).unwrap()
+    }
+
+    fn snapshot() -> SessionSnapshot {
+        let config = Config { focus_sites: focus_sites(), ..Config::default() };
+        config.session_snapshot(focus_apps())
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_data() {
+        let data = |seed| format!("{:?}", generate(DEFAULT_DAYS, seed, last_day()));
+        assert_eq!(data(DEFAULT_SEED), data(DEFAULT_SEED));
+        assert_ne!(data(DEFAULT_SEED), data(DEFAULT_SEED + 1));
+
+        let mut rng = Rng::new(7);
+        let first: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        let mut rng = Rng::new(7);
+        assert_eq!((0..3).map(|_| rng.next_u64()).collect::<Vec<_>>(), first);
+        for _ in 0..1000 {
+            assert!((3..=5).contains(&rng.range(3, 5)));
+        }
+    }
+
+    #[test]
+    fn sessions_look_like_a_working_fortnight() {
+        let sessions = generate(DEFAULT_DAYS, DEFAULT_SEED, last_day());
+        let day_of = |at: DateTime<Utc>| utils::timezone::to_zone(at).date_naive();
+        for offset in 0..DEFAULT_DAYS as i64 {
+            let day = last_day() - chrono::Duration::days(offset);
+            let count = sessions.iter().filter(|session| day_of(session.start) == day).count();
+            match day.weekday() {
+                Weekday::Sat | Weekday::Sun => assert!(count <= 1, "{} has {} sessions", day, count),
+                _ => assert!((2..=3).contains(&count), "{} has {} sessions", day, count),
+            }
+        }
+
+        let focus_apps = focus_apps();
+        for session in &sessions {
+            let local = utils::timezone::to_zone(session.start).time();
+            assert!(local >= NaiveTime::from_hms_opt(8, 30, 0).unwrap() && local <= NaiveTime::from_hms_opt(21, 30, 0).unwrap(), "{}", session.name);
+            assert!(!session.rows.is_empty(), "{}", session.name);
+            assert_eq!(session.rows[0].start_time, session.start);
+            // Rows follow each other, with idle gaps at most, and are closed
+            for pair in session.rows.windows(2) {
+                assert!(pair[1].start_time >= pair[0].end_time.unwrap(), "{:?}", pair);
+            }
+            for row in &session.rows {
+                assert_eq!(row.duration, (row.end_time.unwrap() - row.start_time).to_std().unwrap());
+                assert_eq!(row.session_name, session.name);
+                if row.app_name != BROWSER {
+                    assert_eq!(row.is_focus_app, focus_apps.contains(&row.app_name), "{}", row.app_name);
+                } else {
+                    assert!(row.domain.is_some());
+                }
+            }
+            // A switch between every two rows, at the start of the second
+            assert_eq!(session.switches.len(), session.rows.len() - 1);
+            for (switch, pair) in session.switches.iter().zip(session.rows.windows(2)) {
+                assert_eq!((switch.timestamp, switch.from_app.as_str(), switch.to_app.as_str()),
+                    (pair[1].start_time, pair[0].app_name.as_str(), pair[1].app_name.as_str()));
+                assert_eq!(switch.recovery_time.is_some(), pair[1].is_focus_app);
+            }
+            let kinds: Vec<EventKind> = session.events.iter().map(|event| event.kind).collect();
+            assert_eq!(kinds, [EventKind::DaemonStart, EventKind::DaemonStop]);
+            assert_eq!(session.events[1].timestamp, session.rows.last().unwrap().end_time.unwrap());
+        }
+    }
+
+    #[test]
+    fn written_data_runs_through_the_stats_pipeline() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let sessions = generate(DEFAULT_DAYS, DEFAULT_SEED, last_day());
+        let now = utils::timezone::zone().from_local_datetime(&(last_day() + chrono::Duration::days(1)).and_hms_opt(12, 0, 0).unwrap())
+            .unwrap()
+            .with_timezone(&Utc);
+        let rows: Vec<&FocusSession> = sessions.iter().flat_map(|session| &session.rows).collect();
+        let switches: usize = sessions.iter().map(|session| session.switches.len()).sum();
+
+        let written = write(&db, &sessions, DEFAULT_SEED, &snapshot(), now).unwrap();
+        assert_eq!(written, Written { sessions: sessions.len(), rows: rows.len(), switches });
+        // A second run with the seed adds nothing
+        assert_eq!(write(&db, &sessions, DEFAULT_SEED, &snapshot(), now).unwrap(), Written::default());
+
+        let first_day = last_day() - chrono::Duration::days(DEFAULT_DAYS as i64 - 1);
+        let days = Stats::calculate_daily_stats_range(&db, &StatsSettings::default(), &LocalDays::in_zone(Zone::Named(chrono_tz::UTC), 0), first_day, DEFAULT_DAYS as usize, &mut Progress::hidden()).unwrap();
+        assert_eq!(days.len(), DEFAULT_DAYS as usize);
+        let tracked: Duration = days.iter().map(|day| day.total_focus_time + day.total_distraction_time).sum();
+        assert_eq!(tracked, rows.iter().map(|row| row.duration).sum::<Duration>());
+        let focus: Duration = rows.iter().filter(|row| row.is_focus_app).map(|row| row.duration).sum();
+        assert_eq!(days.iter().map(|day| day.total_focus_time).sum::<Duration>(), focus);
+        assert_eq!(days.iter().map(|day| day.context_switches).sum::<usize>(), switches);
+        for day in &days {
+            let weekday = !matches!(day.date.weekday(), Weekday::Sat | Weekday::Sun);
+            if weekday {
+                assert!(day.focus_efficiency > 0.0 && day.focus_efficiency < 100.0, "{:?}", day.focus_efficiency);
+                assert!(day.most_used_apps.len() > 1);
+            }
+            assert!(Stats::daily_stats_json(day).is_ok());
+        }
+
+        // Sessions are listed under their names, with the demo focus rules
+        let session = &sessions[0];
+        let export = Stats::load_session_export(&db, &StatsSettings::default(), &session.name).unwrap();
+        assert!(format!("{:?}", export).contains(&session.name));
+        assert_eq!(db.count_sessions_after(DateTime::UNIX_EPOCH).unwrap(), rows.len());
+    }
+
+    #[test]
+    fn nothing_after_now_is_written() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let sessions = generate(3, DEFAULT_SEED, last_day());
+        let last = sessions.last().unwrap();
+        let now = last.rows[0].end_time.unwrap();
+        let written = write(&db, &sessions, DEFAULT_SEED, &snapshot(), now).unwrap();
+        let rows = sessions.iter().flat_map(|session| &session.rows).filter(|row| row.end_time.unwrap() <= now).count();
+        assert_eq!((written.sessions, written.rows), (sessions.len(), rows));
+        let stored = db.get_sessions_between(sessions[0].start, last.start + chrono::Duration::days(1)).unwrap();
+        assert_eq!(stored.len(), rows);
+        assert!(stored.iter().all(|row| row.end_time.unwrap() <= now));
+    }
+}
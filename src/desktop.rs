@@ -0,0 +1,349 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+const IDENTITY_CACHE_FILE: &str = "app_identity_cache.json";
+
+/// One application, however it was installed: `key` is the desktop file ID
+/// (e.g. "org.gnome.Builder") and `name` the desktop file's Name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppIdentity {
+    pub key: String,
+    pub name: String,
+}
+
+/// The parts of a .desktop file used for matching
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DesktopEntry {
+    pub id: String,
+    pub name: String,
+    pub program: Option<String>,
+    pub wm_class: Option<String>,
+}
+
+/// Resolved identities keyed by the raw class/app_id/process name. Hits are
+/// persisted in the data directory; misses are only remembered in memory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IdentityCache {
+    entries: BTreeMap<String, AppIdentity>,
+    #[serde(skip)]
+    misses: HashSet<String>,
+}
+
+static CACHE: OnceLock<Mutex<IdentityCache>> = OnceLock::new();
+static ENTRIES: OnceLock<Vec<DesktopEntry>> = OnceLock::new();
+
+/// Map a window class, Wayland app_id or process name to the .desktop entry
+/// it belongs to, so Flatpak, AppImage and distro installs of one app share
+/// an identity. None when no entry matches or off Linux.
+pub fn resolve(raw: &str) -> Option<AppIdentity> {
+    if !cfg!(target_os = "linux") || raw.trim().is_empty() {
+        return None;
+    }
+
+    let cache = CACHE.get_or_init(|| Mutex::new(load_cache()));
+    let mut cache = cache.lock().unwrap();
+    if let Some(identity) = cache.entries.get(raw) {
+        return Some(identity.clone());
+    }
+    if cache.misses.contains(raw) {
+        return None;
+    }
+
+    let entries = ENTRIES.get_or_init(|| load_entries(&application_dirs()));
+    match find_entry(entries, raw) {
+        Some(entry) => {
+            let identity = AppIdentity { key: entry.id.clone(), name: entry.name.clone() };
+            cache.entries.insert(raw.to_string(), identity.clone());
+            if let Err(e) = save_cache(&cache) {
+                eprintln!("❌ Failed to save app identity cache: {}", e);
+            }
+            Some(identity)
+        }
+        None => {
+            cache.misses.insert(raw.to_string());
+            None
+        }
+    }
+}
+
+/// Human-readable name for a stored app key, falling back to the key itself
+pub fn display_name(app: &str) -> String {
+    let cache = CACHE.get_or_init(|| Mutex::new(load_cache()));
+    let cache = cache.lock().unwrap();
+    cache.entries.values()
+        .find(|identity| identity.key == app)
+        .map(|identity| identity.name.clone())
+        .unwrap_or_else(|| app.to_string())
+}
+
+fn load_cache() -> IdentityCache {
+    utils::get_data_directory()
+        .and_then(|dir| fs::read_to_string(dir.join(IDENTITY_CACHE_FILE)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &IdentityCache) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = utils::ensure_data_directory()?;
+    fs::write(data_dir.join(IDENTITY_CACHE_FILE), serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
+
+/// `applications` directories in XDG lookup order: the user's data home
+/// first, then XDG_DATA_DIRS, then Flatpak's exports
+pub fn application_dirs() -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+    let mut data_dirs = Vec::new();
+
+    match utils::non_empty_env("XDG_DATA_HOME") {
+        Some(data_home) => data_dirs.push(PathBuf::from(data_home)),
+        None => data_dirs.extend(home.as_ref().map(|home| home.join(".local/share"))),
+    }
+    let system_dirs = utils::non_empty_env("XDG_DATA_DIRS")
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    data_dirs.extend(system_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    data_dirs.extend(home.as_ref().map(|home| home.join(".local/share/flatpak/exports/share")));
+    data_dirs.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+
+    let mut seen = HashSet::new();
+    data_dirs.into_iter()
+        .map(|dir| dir.join("applications"))
+        .filter(|dir| seen.insert(dir.clone()))
+        .collect()
+}
+
+/// Every entry under `dirs`. When two directories hold the same desktop file
+/// ID, the earlier directory wins, as in the XDG spec.
+pub fn load_entries(dirs: &[PathBuf]) -> Vec<DesktopEntry> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for dir in dirs {
+        let mut files = Vec::new();
+        collect_desktop_files(dir, &mut files);
+        files.sort();
+        for path in files {
+            let Some(id) = desktop_file_id(dir, &path) else { continue };
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            if let Some(entry) = fs::read_to_string(&path).ok().and_then(|content| parse_desktop_entry(&id, &content)) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else { return };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "desktop") {
+            files.push(path);
+        }
+    }
+}
+
+/// "applications/kde/org.kde.kate.desktop" has the ID "kde-org.kde.kate"
+fn desktop_file_id(dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?.with_extension("");
+    let parts: Vec<String> = relative.components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("-"))
+}
+
+/// Parse the [Desktop Entry] group of a .desktop file. Hidden entries and
+/// anything that isn't an application are skipped.
+pub fn parse_desktop_entry(id: &str, content: &str) -> Option<DesktopEntry> {
+    let mut in_group = false;
+    let mut entry = DesktopEntry { id: id.to_string(), ..DesktopEntry::default() };
+    let mut exec = None;
+    let mut entry_type = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_group || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim().to_string();
+        // Localized keys like Name[de] are ignored
+        match key.trim() {
+            "Name" => entry.name = value,
+            "Exec" => exec = Some(value),
+            "StartupWMClass" => entry.wm_class = Some(value),
+            "Type" => entry_type = Some(value),
+            "Hidden" if value == "true" => return None,
+            _ => {}
+        }
+    }
+
+    if entry.name.is_empty() || entry_type.as_deref().is_some_and(|t| t != "Application") {
+        return None;
+    }
+    entry.program = exec.as_deref().and_then(exec_program);
+    Some(entry)
+}
+
+/// The program an Exec line starts, normalized. `flatpak run` lines yield the
+/// --command (or the app ID) and `env VAR=x prog` yields prog.
+fn exec_program(exec: &str) -> Option<String> {
+    let mut tokens = exec.split_whitespace()
+        .map(|token| token.trim_matches('"'))
+        .filter(|token| !token.starts_with('%'))
+        .skip_while(|token| *token == "env" || token.ends_with("/env") || token.contains('='));
+    let program = tokens.next()?;
+
+    if normalize_program(program) == "flatpak" {
+        let args: Vec<&str> = tokens.collect();
+        if let Some(command) = args.iter().find_map(|arg| arg.strip_prefix("--command=")) {
+            return Some(normalize_program(command));
+        }
+        return args.iter().rev().find(|arg| !arg.starts_with('-')).map(|id| normalize_program(id));
+    }
+    Some(normalize_program(program))
+}
+
+/// Lowercased basename with AppImage packaging stripped, so
+/// "/opt/Obsidian-1.4.16.AppImage", "/tmp/.mount_Obsid3x/obsidian" and
+/// "obsidian" all become "obsidian"
+pub fn normalize_program(program: &str) -> String {
+    let base = program.rsplit('/').next().unwrap_or(program).to_lowercase();
+    let base = base.strip_suffix(".appimage").unwrap_or(&base);
+    // Drop a trailing version: "obsidian-1.4.16" → "obsidian"
+    let bytes = base.as_bytes();
+    let cut = (1..bytes.len())
+        .find(|&i| (bytes[i - 1] == b'-' || bytes[i - 1] == b'_') && bytes[i].is_ascii_digit())
+        .map(|i| i - 1)
+        .unwrap_or(base.len());
+    base[..cut].to_string()
+}
+
+/// Match order: desktop file ID (Wayland app_id, Flatpak ID), then
+/// StartupWMClass (X11 class), then the program from Exec
+pub fn find_entry<'a>(entries: &'a [DesktopEntry], raw: &str) -> Option<&'a DesktopEntry> {
+    let program = normalize_program(raw);
+    entries.iter().find(|entry| entry.id.eq_ignore_ascii_case(raw))
+        .or_else(|| entries.iter().find(|entry| entry.wm_class.as_deref().is_some_and(|class| class.eq_ignore_ascii_case(raw))))
+        .or_else(|| entries.iter().find(|entry| entry.program.as_deref() == Some(program.as_str())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// tests/fixtures/desktop: a user data dir that overrides one system entry
+    fn fixture_dirs() -> Vec<PathBuf> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/desktop");
+        vec![root.join("user/applications"), root.join("system/applications")]
+    }
+
+    fn entry(id: &str, program: Option<&str>, wm_class: Option<&str>) -> DesktopEntry {
+        DesktopEntry {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            program: program.map(String::from),
+            wm_class: wm_class.map(String::from),
+        }
+    }
+
+    #[test]
+    fn fixtures_load_with_ids_and_earlier_dirs_winning() {
+        let entries = load_entries(&fixture_dirs());
+        let mut ids: Vec<&str> = entries.iter().map(|entry| entry.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["com.slack.Slack", "firefox", "kde-org.kde.kate", "obsidian", "org.gnome.Builder"]);
+
+        let builder = entries.iter().find(|entry| entry.id == "org.gnome.Builder").unwrap();
+        assert_eq!(builder.name, "Builder (local build)");
+        assert_eq!(builder.program.as_deref(), Some("gnome-builder"));
+    }
+
+    #[test]
+    fn desktop_entry_fields_come_from_the_main_group() {
+        let content = fs::read_to_string(fixture_dirs()[0].join("firefox.desktop")).unwrap();
+        assert_eq!(parse_desktop_entry("firefox", &content), Some(DesktopEntry {
+            id: "firefox".to_string(),
+            name: "Firefox".to_string(),
+            program: Some("firefox".to_string()),
+            wm_class: Some("Navigator".to_string()),
+        }));
+
+        let system = &fixture_dirs()[1];
+        let read = |name: &str| fs::read_to_string(system.join(name)).unwrap();
+        assert_eq!(parse_desktop_entry("hidden", &read("hidden.desktop")), None);
+        assert_eq!(parse_desktop_entry("docs-link", &read("docs-link.desktop")), None);
+        assert_eq!(parse_desktop_entry("empty", "[Desktop Entry]\nType=Application\n"), None);
+        assert_eq!(parse_desktop_entry("other", "[Other Group]\nName=Other\n"), None);
+    }
+
+    #[test]
+    fn exec_lines_yield_the_program() {
+        let cases = [
+            ("kate -b %U", Some("kate")),
+            ("\"/opt/Obsidian-1.4.16.AppImage\" --no-sandbox %U", Some("obsidian")),
+            ("env MOZ_ENABLE_WAYLAND=1 firefox %u", Some("firefox")),
+            ("/usr/bin/env GDK_BACKEND=x11 /usr/bin/code --unity-launch %F", Some("code")),
+            ("/usr/bin/flatpak run --branch=stable --command=slack com.slack.Slack %U", Some("slack")),
+            ("flatpak run --branch=stable org.gnome.Builder", Some("org.gnome.builder")),
+            ("%U", None),
+            ("", None),
+        ];
+        for (exec, program) in cases {
+            assert_eq!(exec_program(exec).as_deref(), program, "{}", exec);
+        }
+    }
+
+    #[test]
+    fn programs_normalize_across_packaging() {
+        for raw in ["obsidian", "Obsidian", "/opt/Obsidian-1.4.16.AppImage", "/tmp/.mount_Obsid3x/obsidian", "obsidian_2"] {
+            assert_eq!(normalize_program(raw), "obsidian", "{}", raw);
+        }
+        assert_eq!(normalize_program("gnome-builder"), "gnome-builder");
+    }
+
+    #[test]
+    fn lookup_resolves_every_install_method_from_the_fixtures() {
+        let entries = load_entries(&fixture_dirs());
+        let key = |raw: &str| find_entry(&entries, raw).map(|entry| entry.id.as_str());
+        // Wayland app_id / Flatpak ID, matched on the desktop file ID
+        assert_eq!(key("org.gnome.Builder"), Some("org.gnome.Builder"));
+        assert_eq!(key("com.slack.slack"), Some("com.slack.Slack"));
+        assert_eq!(key("kde-org.kde.kate"), Some("kde-org.kde.kate"));
+        // X11 class, matched on StartupWMClass
+        assert_eq!(key("Navigator"), Some("firefox"));
+        // Process names and AppImage mounts, matched on Exec
+        assert_eq!(key("/tmp/.mount_Obsid3x/obsidian"), Some("obsidian"));
+        assert_eq!(key("slack"), Some("com.slack.Slack"));
+        assert_eq!(key("kate"), Some("kde-org.kde.kate"));
+        assert_eq!(key("gnome-builder"), Some("org.gnome.Builder"));
+
+        assert_eq!(key("removed"), None);
+        assert_eq!(key("unknown"), None);
+    }
+
+    #[test]
+    fn lookup_prefers_id_then_class_then_program() {
+        let by_program = entry("by-program", Some("code"), None);
+        let by_class = entry("by-class", None, Some("Code"));
+        let by_id = entry("code", None, None);
+
+        let entries = [by_program.clone(), by_class.clone(), by_id.clone()];
+        assert_eq!(find_entry(&entries, "code"), Some(&by_id));
+        let entries = [by_program.clone(), by_class.clone()];
+        assert_eq!(find_entry(&entries, "code"), Some(&by_class));
+        let entries = [by_program.clone()];
+        assert_eq!(find_entry(&entries, "code"), Some(&by_program));
+    }
+}
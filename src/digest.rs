@@ -0,0 +1,793 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use chrono::{Datelike, NaiveDate, Utc};
+
+use crate::config::{BudgetPeriod, Config};
+use crate::hooks;
+use crate::progress::Progress;
+use crate::stats::{self, DailyStats, DayType, NotificationCounts, RoundTripPair, Stats, StatsSettings};
+use crate::storage::{self, Database, PlanStatus, PlannedSession};
+use crate::utils::{self, DurationStyle, LocalDays};
+
+/// Interruption targets listed in the digest
+const TOP_INTERRUPTIONS: usize = 5;
+
+/// Tracked days needed before notifications and switches are correlated
+const MIN_CORRELATED_DAYS: usize = 3;
+
+/// Rated sessions needed before ratings are correlated with the numbers
+const MIN_CORRELATED_SESSIONS: usize = 3;
+
+/// A session of the week rated at `stop` or with `sessions rate`, with
+/// what was measured
+#[derive(Debug, Clone)]
+pub struct RatedSession {
+    pub rating: u8,
+    /// None when it had no intent or the question was skipped
+    pub intent_met: Option<bool>,
+    pub focus_efficiency: f64,
+    pub context_switches: usize,
+}
+
+/// One budget over a week
+#[derive(Debug, Clone)]
+pub struct BudgetWeek {
+    pub target: String,
+    pub limit: Duration,
+    pub period: BudgetPeriod,
+    /// Daily budgets: days over the limit plus what was carried in
+    pub days_over: usize,
+    pub total: Duration,
+}
+
+/// Everything a weekly digest shows, collected up front so the text can be
+/// composed without the database
+#[derive(Debug, Clone)]
+pub struct WeeklyDigest {
+    /// Monday of the ISO week
+    pub week_start: NaiveDate,
+    pub days: Vec<DailyStats>,
+    /// Mean focus score of the week before, for the trend
+    pub previous_score: Option<f64>,
+    /// Apps switched to from a focus app, most frequent first
+    pub interruptions: Vec<(String, usize)>,
+    /// App pairs bounced between most, over the whole week
+    pub round_trips: Vec<RoundTripPair>,
+    pub efficiency_floor: f64,
+    pub budgets: Vec<BudgetWeek>,
+    /// Sessions planned to start this week
+    pub plans: Vec<PlannedSession>,
+    /// Notifications received over the week, by source
+    pub notifications: NotificationCounts,
+    /// Notifications received on each day of the week, Monday first
+    pub daily_notifications: Vec<usize>,
+    /// Sessions started this week and rated, oldest first
+    pub rated_sessions: Vec<RatedSession>,
+}
+
+/// Monday of the last full ISO week before `today`
+pub fn last_complete_week(today: NaiveDate) -> NaiveDate {
+    today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64 + 7)
+}
+
+/// "2024-W07", by ISO week numbering
+pub fn week_label(week_start: NaiveDate) -> String {
+    let week = week_start.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// digest-YYYY-WW.txt
+pub fn file_name(week_start: NaiveDate) -> String {
+    let week = week_start.iso_week();
+    format!("digest-{}-{:02}.txt", week.year(), week.week())
+}
+
+/// The digest of the week from `week_start`, with its days cut by `local_days`
+pub fn collect_weekly(
+    db: &Database,
+    config: &Config,
+    settings: &StatsSettings,
+    local_days: &LocalDays,
+    week_start: NaiveDate,
+) -> Result<WeeklyDigest, Box<dyn std::error::Error>> {
+    let days = Stats::calculate_daily_stats_range(db, settings, local_days, week_start, 7, &mut Progress::hidden())?;
+    let previous = Stats::calculate_daily_stats_range(db, settings, local_days, week_start - chrono::Duration::days(7), 7, &mut Progress::hidden())?;
+    let start = local_days.start(week_start);
+    let end = local_days.start(week_start + chrono::Duration::days(7));
+
+    let switches = db.get_context_switches_between(start, end - chrono::Duration::seconds(1))?;
+    let mut round_trips = stats::round_trip_pairs(&switches, settings.round_trip_window);
+    round_trips.truncate(stats::ROUND_TRIPS_SHOWN);
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for switch in switches {
+        let leaves_focus = config.focus_apps.is_empty() || config.is_focus_app(&switch.from_app);
+        if leaves_focus && switch.from_app != switch.to_app && !config.is_focus_app(&switch.to_app) {
+            *counts.entry(switch.to_app).or_default() += 1;
+        }
+    }
+    let mut interruptions: Vec<(String, usize)> = counts.into_iter().collect();
+    interruptions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    interruptions.truncate(TOP_INTERRUPTIONS);
+
+    let rules = config.budget_rules();
+    let mut budgets: Vec<BudgetWeek> = rules.iter()
+        .map(|rule| BudgetWeek { target: rule.target.clone(), limit: rule.limit, period: rule.period, days_over: 0, total: Duration::ZERO })
+        .collect();
+    if !rules.is_empty() {
+        // Carry-over is followed from the start of the week, between days
+        // of the same type
+        let mut used_so_far: HashMap<DayType, Vec<Vec<Duration>>> = HashMap::new();
+        for day in &days {
+            let (day_start, day_end) = local_days.range(day.date.date_naive());
+            let used = Stats::calculate_budget_used_between(db, &rules, day_start, day_end)?;
+            let same_type = used_so_far.entry(settings.day_type(day.date.date_naive())).or_insert_with(|| vec![Vec::new(); rules.len()]);
+            for (((budget, rule), used), previous) in budgets.iter_mut().zip(&rules).zip(used).zip(same_type) {
+                budget.total += used;
+                let carried = if rule.carry_over {
+                    stats::carried_over(rule.limit, rule.carry_over_cap, previous)
+                } else {
+                    Duration::ZERO
+                };
+                if rule.period == BudgetPeriod::Day && used > rule.limit + carried {
+                    budget.days_over += 1;
+                }
+                previous.push(used);
+            }
+        }
+    }
+
+    // A database no newer build has opened for writing has no plans table yet
+    let plans = db.get_planned_sessions(start, Some(end)).unwrap_or_default();
+
+    let by_day = db.get_notifications_by_day(week_start, week_start + chrono::Duration::days(7))?;
+    let mut sources: HashMap<String, usize> = HashMap::new();
+    for (source, count) in by_day.values().flatten() {
+        *sources.entry(source.clone()).or_default() += count;
+    }
+    let daily_notifications = (0..7)
+        .map(|offset| by_day.get(&(week_start + chrono::Duration::days(offset)))
+            .map_or(0, |sources| sources.iter().map(|(_, count)| count).sum()))
+        .collect();
+
+    let mut intents: Vec<(String, storage::SessionIntent)> = db.get_session_intents()?.into_iter()
+        .filter(|(_, intent)| intent.rating.is_some() && intent.started_at >= start && intent.started_at < end)
+        .collect();
+    intents.sort_by_key(|(_, intent)| intent.started_at);
+    let rated_sessions = intents.into_iter()
+        .filter_map(|(name, intent)| {
+            // A rated session whose rows are gone has nothing to compare with
+            let (session, _) = Stats::aggregate_named_session(db, settings, &name).ok()?;
+            Some(RatedSession {
+                rating: intent.rating?,
+                intent_met: intent.intent_met,
+                focus_efficiency: session.focus_efficiency,
+                context_switches: session.context_switches,
+            })
+        })
+        .collect();
+
+    Ok(WeeklyDigest {
+        week_start,
+        days,
+        previous_score: mean_score(&previous, settings),
+        interruptions,
+        round_trips,
+        efficiency_floor: config.efficiency_floor,
+        budgets,
+        plans,
+        notifications: NotificationCounts::new(sources.into_iter().collect()),
+        daily_notifications,
+        rated_sessions,
+    })
+}
+
+/// Pearson correlation of `pairs`; None without variation on either side
+fn correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+    let n = pairs.len() as f64;
+    let (mean_x, mean_y) = (pairs.iter().map(|(x, _)| x).sum::<f64>() / n, pairs.iter().map(|(_, y)| y).sum::<f64>() / n);
+    let covariance: f64 = pairs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let spread_x: f64 = pairs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let spread_y: f64 = pairs.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    (spread_x > 0.0 && spread_y > 0.0).then(|| covariance / (spread_x * spread_y).sqrt())
+}
+
+/// How notifications went along with switches on the tracked days
+fn describe_notification_correlation(digest: &WeeklyDigest) -> Option<String> {
+    let pairs: Vec<(f64, f64)> = digest.days.iter()
+        .zip(&digest.daily_notifications)
+        .filter(|(day, _)| is_tracked(day))
+        .map(|(day, notifications)| (*notifications as f64, day.context_switches as f64))
+        .collect();
+    if pairs.len() < MIN_CORRELATED_DAYS {
+        return None;
+    }
+    let r = correlation(&pairs)?;
+    let reading = if r >= 0.3 {
+        "Days with more notifications had more switches"
+    } else if r <= -0.3 {
+        "Days with more notifications had fewer switches"
+    } else {
+        "Little connection between notifications and switches"
+    };
+    Some(format!("{} (r = {:.2} over {} days)", reading, r, pairs.len()))
+}
+
+/// How ratings went along with one measured number, e.g. "Higher-rated
+/// sessions had higher efficiency (r = 0.71 over 5 sessions)"
+fn describe_rating_correlation(pairs: &[(f64, f64)], measure: &str, more: &str, less: &str) -> Option<String> {
+    if pairs.len() < MIN_CORRELATED_SESSIONS {
+        return None;
+    }
+    let r = correlation(pairs)?;
+    let reading = if r >= 0.3 {
+        format!("Higher-rated sessions had {} {}", more, measure)
+    } else if r <= -0.3 {
+        format!("Higher-rated sessions had {} {}", less, measure)
+    } else {
+        format!("Little connection between rating and {}", measure)
+    };
+    Some(format!("{} (r = {:.2} over {} sessions)", reading, r, pairs.len()))
+}
+
+/// Average rating, how often intents were met, and whether the ratings
+/// agree with measured efficiency and switches
+fn describe_outcomes(sessions: &[RatedSession]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let average = sessions.iter().map(|session| session.rating as f64).sum::<f64>() / sessions.len() as f64;
+    lines.push(format!("Average rating {:.1}/5 over {} session{}", average, sessions.len(), if sessions.len() == 1 { "" } else { "s" }));
+    let answered: Vec<bool> = sessions.iter().filter_map(|session| session.intent_met).collect();
+    if !answered.is_empty() {
+        let met = answered.iter().filter(|met| **met).count();
+        lines.push(format!("Intent met in {} of {} ({:.0}%)", met, answered.len(), met as f64 / answered.len() as f64 * 100.0));
+    }
+    let efficiency: Vec<(f64, f64)> = sessions.iter().map(|session| (session.rating as f64, session.focus_efficiency)).collect();
+    lines.extend(describe_rating_correlation(&efficiency, "efficiency", "higher", "lower"));
+    let switches: Vec<(f64, f64)> = sessions.iter().map(|session| (session.rating as f64, session.context_switches as f64)).collect();
+    lines.extend(describe_rating_correlation(&switches, "switches", "more", "fewer"));
+    lines
+}
+
+/// Mean score of the days that count toward averages
+fn mean_score(days: &[DailyStats], settings: &StatsSettings) -> Option<f64> {
+    let scores: Vec<u32> = days.iter()
+        .filter(|day| settings.counts_toward_averages(settings.day_type(day.date.date_naive())))
+        .filter_map(|day| day.focus_score)
+        .collect();
+    (!scores.is_empty()).then(|| scores.iter().sum::<u32>() as f64 / scores.len() as f64)
+}
+
+/// Mean of a day's `time` over the days that have it
+fn mean_time(days: &[DailyStats], time: fn(&DailyStats) -> Option<Duration>) -> Option<Duration> {
+    let times: Vec<Duration> = days.iter().filter_map(time).collect();
+    (!times.is_empty()).then(|| times.iter().sum::<Duration>() / times.len() as u32)
+}
+
+fn is_tracked(day: &DailyStats) -> bool {
+    !(day.total_focus_time + day.total_distraction_time).is_zero()
+}
+
+fn describe_day(day: &DailyStats) -> String {
+    let score = day.focus_score.map(|score| format!("score {}", score)).unwrap_or_else(|| "no score".to_string());
+    format!("{} {}: {}, {} focus, {:.0}% efficiency",
+        day.date.format("%a"),
+        utils::format_date_local(day.date.date_naive()),
+        score,
+        utils::format_duration(day.total_focus_time, DurationStyle::Long),
+        day.focus_efficiency)
+}
+
+/// Plain-text digest, kept to ASCII layout so it reads the same in any mail client
+pub fn render_weekly(digest: &WeeklyDigest, settings: &StatsSettings) -> String {
+    let mut out = String::new();
+    let week_end = digest.week_start + chrono::Duration::days(6);
+    out.push_str(&format!("FocusDebt weekly digest, {} ({} - {})\n\n",
+        week_label(digest.week_start),
+        utils::format_date_local(digest.week_start),
+        utils::format_date_local(week_end)));
+
+    let tracked: Vec<&DailyStats> = digest.days.iter().filter(|day| is_tracked(day)).collect();
+    if tracked.is_empty() {
+        out.push_str("Nothing was tracked this week.\n");
+        return out;
+    }
+
+    let focus: Duration = digest.days.iter().map(|day| day.total_focus_time).sum();
+    let distraction: Duration = digest.days.iter().map(|day| day.total_distraction_time).sum();
+    let switches: usize = digest.days.iter().map(|day| day.context_switches).sum();
+    let deep: usize = digest.days.iter().map(|day| day.deep_focus_sessions).sum();
+    let assistant: Duration = digest.days.iter().map(|day| day.assistant_time).sum();
+    let total = focus + distraction;
+    let efficiency = focus.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0;
+
+    out.push_str("TOTALS\n");
+    out.push_str(&format!("  Focus time         {}\n", utils::format_duration(focus, DurationStyle::Long)));
+    out.push_str(&format!("  Distraction time   {}\n", utils::format_duration(distraction, DurationStyle::Long)));
+    if let Some(day) = digest.days.iter().find(|day| !day.assistant_time.is_zero()) {
+        out.push_str(&format!("  Assistant time     {}\n", stats::describe_assistant_time(assistant, day.assistant_counts_as)));
+    }
+    out.push_str(&format!("  Efficiency         {:.0}%\n", efficiency));
+    out.push_str(&format!("  Context switches   {}\n", switches));
+    out.push_str(&format!("  Deep focus blocks  {}\n", deep));
+    if let Some(to_first) = mean_time(&digest.days, |day| day.time_to_first_focus) {
+        out.push_str(&format!("  First focus        {} after start on average\n", utils::format_duration(to_first, DurationStyle::Long)));
+    }
+    if let Some(wind_down) = mean_time(&digest.days, |day| day.focus_wind_down) {
+        out.push_str(&format!("  Focus wind-down    {} before sign-off on average\n", utils::format_duration(wind_down, DurationStyle::Long)));
+    }
+    out.push_str(&format!("  Days tracked       {} of 7\n\n", tracked.len()));
+
+    out.push_str("FOCUS SCORE\n");
+    match (mean_score(&digest.days, settings), digest.previous_score) {
+        (Some(score), Some(previous)) => {
+            let delta = score.round() as i64 - previous.round() as i64;
+            let trend = match delta {
+                0 => "same as the week before".to_string(),
+                delta if delta > 0 => format!("up {} from {:.0} the week before", delta, previous),
+                delta => format!("down {} from {:.0} the week before", -delta, previous),
+            };
+            out.push_str(&format!("  Average {:.0}, {}\n", score, trend));
+        }
+        (Some(score), None) => out.push_str(&format!("  Average {:.0} (no scores the week before)\n", score)),
+        (None, _) => out.push_str("  No focus score this week\n"),
+    }
+    for day in &digest.days {
+        let score = day.focus_score.map(|score| score.to_string()).unwrap_or_else(|| "--".to_string());
+        out.push_str(&format!("  {} {:<12} {:>3}\n", day.date.format("%a"), utils::format_date_local(day.date.date_naive()), score));
+    }
+    out.push('\n');
+
+    out.push_str("BASELINES\n");
+    for baseline in settings.daily_baselines(&digest.days) {
+        let excluded = if settings.counts_toward_averages(baseline.day_type) { "" } else { ", left out of averages" };
+        out.push_str(&format!("  {:<20} {}{}\n", baseline.day_type.label(), baseline.describe(), excluded));
+    }
+    out.push('\n');
+
+    let by_score = |day: &&&DailyStats| (day.focus_score.unwrap_or(0), day.total_focus_time);
+    if let (Some(best), Some(worst)) = (tracked.iter().max_by_key(by_score), tracked.iter().min_by_key(by_score)) {
+        out.push_str(&format!("Best day   {}\n", describe_day(best)));
+        if tracked.len() > 1 {
+            out.push_str(&format!("Worst day  {}\n", describe_day(worst)));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("TOP INTERRUPTIONS\n");
+    if digest.interruptions.is_empty() {
+        out.push_str("  None - no switches away from focus apps\n");
+    }
+    for (app, count) in &digest.interruptions {
+        out.push_str(&format!("  {:<24} {} switch{}\n", app, count, if *count == 1 { "" } else { "es" }));
+    }
+    out.push('\n');
+
+    if !digest.notifications.is_empty() {
+        out.push_str("NOTIFICATIONS\n");
+        out.push_str(&format!("  Received {}\n", digest.notifications.describe()));
+        if let Some(correlation) = describe_notification_correlation(digest) {
+            out.push_str(&format!("  {}\n", correlation));
+        }
+        out.push('\n');
+    }
+
+    if !digest.rated_sessions.is_empty() {
+        out.push_str("OUTCOMES\n");
+        for line in describe_outcomes(&digest.rated_sessions) {
+            out.push_str(&format!("  {}\n", line));
+        }
+        out.push('\n');
+    }
+
+    if !digest.round_trips.is_empty() {
+        out.push_str("ROUND TRIPS\n");
+        for pair in &digest.round_trips {
+            out.push_str(&format!("  {}\n", pair.describe()));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("GOALS\n");
+    let counted: Vec<&&DailyStats> = tracked.iter().filter(|day| settings.counts_toward_averages(settings.day_type(day.date.date_naive()))).collect();
+    let met = counted.iter().filter(|day| day.focus_efficiency >= digest.efficiency_floor).count();
+    let which = if counted.len() == tracked.len() { "tracked days" } else { "tracked workdays" };
+    out.push_str(&format!("  Efficiency floor {:.0}%: met on {} of {} {}\n", digest.efficiency_floor, met, counted.len(), which));
+    for budget in &digest.budgets {
+        let status = match (budget.period, budget.days_over) {
+            (BudgetPeriod::Week, _) if budget.total > budget.limit => format!("over budget by {}",
+                utils::format_duration(budget.total - budget.limit, DurationStyle::Long)),
+            (BudgetPeriod::Week, _) => "within budget".to_string(),
+            (BudgetPeriod::Day, 0) => "within budget every day".to_string(),
+            (BudgetPeriod::Day, 1) => "over budget on 1 day".to_string(),
+            (BudgetPeriod::Day, days) => format!("over budget on {} days", days),
+        };
+        out.push_str(&format!("  Budget {} ({}/{}): {}, {} in total\n",
+            budget.target,
+            utils::format_duration(budget.limit, DurationStyle::Long),
+            budget.period.label(),
+            status,
+            utils::format_duration(budget.total, DurationStyle::Long)));
+    }
+    if !digest.plans.is_empty() {
+        out.push_str(&format!("  {}\n", describe_plans(&digest.plans, Utc::now())));
+    }
+    out
+}
+
+/// "2 of 5 planned sessions completed"
+pub fn describe_plans(plans: &[PlannedSession], now: chrono::DateTime<Utc>) -> String {
+    let completed = plans.iter().filter(|plan| plan.status(now) == PlanStatus::Completed).count();
+    format!("{} of {} planned session{} completed", completed, plans.len(), if plans.len() == 1 { "" } else { "s" })
+}
+
+/// The digest as a mail message with headers, ready for `sendmail -t`.
+/// Lines end in LF, as local mail submission expects.
+pub fn mail_message(to: &str, subject: &str, body: &str) -> String {
+    format!("To: {}\nSubject: {}\nDate: {}\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n{}",
+        to, subject, Utc::now().to_rfc2822(), body)
+}
+
+/// Pipe `message` to `command` through the shell. The recipient is also
+/// passed as FOCUSDEBT_MAIL_TO for commands that don't read the headers.
+pub fn send_mail(command: &str, to: &str, message: &str) -> Result<(), String> {
+    let mut child = hooks::shell_command(command)
+        .env("FOCUSDEBT_MAIL_TO", to)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run '{}': {}", command, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())
+            .map_err(|e| format!("could not write to '{}': {}", command, e))?;
+    }
+    let status = child.wait().map_err(|e| format!("'{}' failed: {}", command, e))?;
+    if !status.success() {
+        return Err(format!("'{}' exited with {}", command, status));
+    }
+    Ok(())
+}
+
+pub fn write_digest(dir: &Path, week_start: NaiveDate, text: &str) -> std::io::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(file_name(week_start));
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use crate::stats::DayRecords;
+    use crate::tracking::ContextSwitch;
+    use crate::utils::timezone::Zone;
+
+    fn week() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+    }
+
+    fn utc_days() -> LocalDays {
+        LocalDays::in_zone(Zone::Named(chrono_tz::UTC), 0)
+    }
+
+    /// Day `offset` of the week with the given minutes and score
+    fn day(offset: i64, focus: u64, distraction: u64, score: Option<u32>) -> DailyStats {
+        let date = stats::start_of_day(week() + chrono::Duration::days(offset));
+        let mut day = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), date.date_naive(), &DayRecords::default(), date + chrono::Duration::days(1));
+        day.total_focus_time = Duration::from_secs(focus * 60);
+        day.total_distraction_time = Duration::from_secs(distraction * 60);
+        day.focus_efficiency = if focus + distraction == 0 { 0.0 } else { focus as f64 / (focus + distraction) as f64 * 100.0 };
+        day.focus_score = score;
+        day.context_switches = 4;
+        day
+    }
+
+    fn digest(days: Vec<DailyStats>) -> WeeklyDigest {
+        WeeklyDigest {
+            week_start: week(),
+            days,
+            previous_score: Some(60.0),
+            interruptions: vec![("fd-chat".to_string(), 7), ("fd-browser".to_string(), 1)],
+            round_trips: Vec::new(),
+            efficiency_floor: 60.0,
+            budgets: vec![BudgetWeek {
+                target: "fd-chat".to_string(),
+                limit: Duration::from_secs(30 * 60),
+                period: BudgetPeriod::Day,
+                days_over: 2,
+                total: Duration::from_secs(100 * 60),
+            }],
+            plans: Vec::new(),
+            notifications: NotificationCounts::new(Vec::new()),
+            daily_notifications: vec![0; 7],
+            rated_sessions: Vec::new(),
+        }
+    }
+
+    fn fixture_week() -> Vec<DailyStats> {
+        vec![
+            day(0, 240, 60, Some(80)),
+            day(1, 120, 120, Some(50)),
+            day(2, 300, 30, Some(90)),
+            day(3, 0, 0, None),
+            day(4, 180, 60, Some(70)),
+            day(5, 0, 0, None),
+            day(6, 0, 0, None),
+        ]
+    }
+
+    #[test]
+    fn weeks_are_named_by_iso_numbering() {
+        assert_eq!(week_label(week()), "2024-W10");
+        assert_eq!(file_name(week()), "digest-2024-10.txt");
+        // 2021-01-04 is the Monday of week 1; the days before belong to 2020-W53
+        assert_eq!(week_label(NaiveDate::from_ymd_opt(2020, 12, 28).unwrap()), "2020-W53");
+        assert_eq!(file_name(NaiveDate::from_ymd_opt(2021, 1, 4).unwrap()), "digest-2021-01.txt");
+    }
+
+    #[test]
+    fn last_complete_week_is_the_monday_before_this_weeks() {
+        for offset in 0..7 {
+            let today = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap() + chrono::Duration::days(offset);
+            assert_eq!(last_complete_week(today), week(), "{}", today);
+        }
+        assert_eq!(last_complete_week(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap()), NaiveDate::from_ymd_opt(2024, 2, 26).unwrap());
+    }
+
+    #[test]
+    fn digest_has_totals_trend_days_interruptions_and_goals() {
+        let text = render_weekly(&digest(fixture_week()), &StatsSettings::default());
+        assert!(text.starts_with("FocusDebt weekly digest, 2024-W10 ("), "{}", text);
+
+        // 840m focus, 270m distraction
+        assert!(text.contains("  Focus time         14h 0m\n"), "{}", text);
+        assert!(text.contains("  Distraction time   4h 30m\n"), "{}", text);
+        assert!(text.contains("  Efficiency         76%\n"), "{}", text);
+        assert!(text.contains("  Context switches   28\n"), "{}", text);
+        assert!(text.contains("  Days tracked       4 of 7\n"), "{}", text);
+
+        // (80 + 50 + 90 + 70) / 4 = 72.5; the trend compares rounded scores
+        assert!(text.contains("  Average 72, up 13 from 60 the week before\n"), "{}", text);
+        assert!(text.contains("Best day   Wed "), "{}", text);
+        assert!(text.contains(": score 90, 5h 0m focus, 91% efficiency"), "{}", text);
+        assert!(text.contains("Worst day  Tue "), "{}", text);
+        assert!(text.contains(": score 50, 2h 0m focus, 50% efficiency"), "{}", text);
+
+        let interruptions = text.split("TOP INTERRUPTIONS\n").nth(1).unwrap();
+        assert!(interruptions.starts_with("  fd-chat                  7 switches\n  fd-browser               1 switch\n"), "{}", text);
+
+        assert!(text.contains("  Efficiency floor 60%: met on 3 of 4 tracked days\n"), "{}", text);
+        assert!(text.contains("  Budget fd-chat (30m 0s/day): over budget on 2 days, 1h 40m in total\n"), "{}", text);
+    }
+
+    #[test]
+    fn digest_trend_reads_down_flat_and_missing() {
+        let mut down = digest(fixture_week());
+        down.previous_score = Some(80.0);
+        let text = render_weekly(&down, &StatsSettings::default());
+        assert!(text.contains("  Average 72, down 7 from 80 the week before\n"), "{}", text);
+
+        let mut none = digest(vec![day(0, 60, 0, Some(75))]);
+        none.previous_score = None;
+        let text = render_weekly(&none, &StatsSettings::default());
+        assert!(text.contains("  Average 75 (no scores the week before)\n"), "{}", text);
+        // One tracked day is best and worst at once
+        assert!(text.contains("Best day   Mon "), "{}", text);
+        assert!(!text.contains("Worst day"), "{}", text);
+
+        let mut same = digest(vec![day(0, 60, 0, Some(60))]);
+        same.interruptions.clear();
+        let text = render_weekly(&same, &StatsSettings::default());
+        assert!(text.contains("  Average 60, same as the week before\n"), "{}", text);
+        assert!(text.contains("  None - no switches away from focus apps\n"), "{}", text);
+    }
+
+    #[test]
+    fn empty_week_says_so() {
+        let text = render_weekly(&digest((0..7).map(|offset| day(offset, 0, 0, None)).collect()), &StatsSettings::default());
+        assert!(text.ends_with("\n\nNothing was tracked this week.\n"), "{}", text);
+        assert!(!text.contains("TOTALS"), "{}", text);
+    }
+
+    #[test]
+    fn mail_message_has_headers_then_the_body() {
+        let message = mail_message("me@example.com", "FocusDebt digest 2024-W10", "body\n");
+        let (headers, body) = message.split_once("\n\n").unwrap();
+        assert_eq!(body, "body\n");
+        let headers: Vec<&str> = headers.lines().collect();
+        assert_eq!(headers[0], "To: me@example.com");
+        assert_eq!(headers[1], "Subject: FocusDebt digest 2024-W10");
+        assert!(DateTime::parse_from_rfc2822(headers[2].trim_start_matches("Date: ")).is_ok(), "{:?}", headers);
+        assert!(headers.contains(&"Content-Type: text/plain; charset=utf-8"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn send_mail_pipes_the_message_and_reports_failures() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-digest-mail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("sent.txt");
+        let command = format!("cat > '{}'; echo \"$FOCUSDEBT_MAIL_TO\" >> '{}'", out.display(), out.display());
+        send_mail(&command, "me@example.com", "To: me@example.com\n\nhello\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "To: me@example.com\n\nhello\nme@example.com\n");
+
+        let error = send_mail("exit 3", "me@example.com", "hello").unwrap_err();
+        assert!(error.contains("exited with"), "{}", error);
+
+        let path = write_digest(&dir.join("exports"), week(), "digest\n").unwrap();
+        assert_eq!(path.file_name().unwrap(), "digest-2024-10.txt");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "digest\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interruptions_count_switches_away_from_focus_apps() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let config = Config { focus_apps: vec!["fd-editor".to_string()], ..Config::default() };
+        let start = stats::start_of_day(week()) + chrono::Duration::hours(9);
+        let switches = [
+            ("fd-editor", "fd-chat"), ("fd-chat", "fd-editor"),
+            ("fd-editor", "fd-chat"), ("fd-chat", "fd-browser"),
+            ("fd-editor", "fd-browser"), ("fd-editor", "fd-term"),
+        ];
+        for (minute, (from, to)) in switches.iter().enumerate() {
+            db.save_context_switch(&ContextSwitch {
+                timestamp: start + chrono::Duration::minutes(minute as i64),
+                from_app: from.to_string(),
+                to_app: to.to_string(),
+                recovery_time: None,
+            }).unwrap();
+        }
+        // Outside the week
+        db.save_context_switch(&ContextSwitch {
+            timestamp: start + chrono::Duration::days(7),
+            from_app: "fd-editor".to_string(),
+            to_app: "fd-term".to_string(),
+            recovery_time: None,
+        }).unwrap();
+
+        let digest = collect_weekly(&db, &config, &StatsSettings::default(), &utc_days(), week()).unwrap();
+        assert_eq!(digest.days.len(), 7);
+        assert_eq!(digest.interruptions, [
+            ("fd-chat".to_string(), 2),
+            ("fd-browser".to_string(), 1),
+            ("fd-term".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn notifications_are_totalled_and_set_against_switches() {
+        let mut days = fixture_week();
+        for (day, switches) in days.iter_mut().zip([10, 20, 5, 0, 15, 0, 0]) {
+            day.context_switches = switches;
+        }
+        let mut digest = digest(days);
+        digest.notifications = NotificationCounts::new(vec![("slack".to_string(), 24), ("email".to_string(), 9), ("other".to_string(), 5)]);
+        // The untracked Thursday's 4 don't count towards the correlation
+        digest.daily_notifications = vec![8, 14, 2, 4, 10, 0, 0];
+        let text = render_weekly(&digest, &StatsSettings::default());
+        assert!(text.contains("NOTIFICATIONS\n  Received 38 (slack 24, email 9, other 5)\n  Days with more notifications had more switches (r = 0.98 over 4 days)\n"), "{}", text);
+
+        digest.daily_notifications = vec![14, 8, 10, 0, 2, 0, 0];
+        assert!(render_weekly(&digest, &StatsSettings::default()).contains("  Days with more notifications had fewer switches (r = "), "{}", render_weekly(&digest, &StatsSettings::default()));
+        // Without variation there is nothing to correlate
+        digest.daily_notifications = vec![3; 7];
+        let text = render_weekly(&digest, &StatsSettings::default());
+        assert!(text.contains("  Received 38 (slack 24, email 9, other 5)\n\n"), "{}", text);
+
+        assert!(!render_weekly(&self::digest(fixture_week()), &StatsSettings::default()).contains("NOTIFICATIONS"));
+        assert_eq!(correlation(&[(1.0, 1.0), (2.0, 2.0)]), Some(1.0));
+        assert_eq!(correlation(&[(1.0, 1.0), (1.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn weekly_notifications_are_read_by_day() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        for (offset, source) in [(0, "slack"), (0, "slack"), (2, "email"), (7, "slack")] {
+            db.add_notification("week", week() + chrono::Duration::days(offset), source).unwrap();
+        }
+        let digest = collect_weekly(&db, &Config::default(), &StatsSettings::default(), &utc_days(), week()).unwrap();
+        assert_eq!(digest.notifications.describe(), "3 (slack 2, email 1)");
+        assert_eq!(digest.daily_notifications, [2, 0, 1, 0, 0, 0, 0]);
+    }
+
+    fn rated(rating: u8, intent_met: Option<bool>, focus_efficiency: f64, context_switches: usize) -> RatedSession {
+        RatedSession { rating, intent_met, focus_efficiency, context_switches }
+    }
+
+    #[test]
+    fn outcomes_average_the_ratings_and_set_them_against_the_numbers() {
+        let mut digest = digest(fixture_week());
+        assert!(!render_weekly(&digest, &StatsSettings::default()).contains("OUTCOMES"));
+
+        digest.rated_sessions = vec![
+            rated(2, Some(false), 40.0, 30),
+            rated(4, Some(true), 80.0, 10),
+            rated(5, None, 90.0, 6),
+            rated(3, Some(true), 60.0, 20),
+        ];
+        let text = render_weekly(&digest, &StatsSettings::default());
+        assert!(text.contains(concat!(
+            "OUTCOMES\n",
+            "  Average rating 3.5/5 over 4 sessions\n",
+            "  Intent met in 2 of 3 (67%)\n",
+            "  Higher-rated sessions had higher efficiency (r = 0.99 over 4 sessions)\n",
+            "  Higher-rated sessions had fewer switches (r = -0.98 over 4 sessions)\n",
+            "\n",
+        )), "{}", text);
+
+        // Ratings that don't follow the numbers say so
+        digest.rated_sessions = vec![rated(2, None, 60.0, 10), rated(3, None, 40.0, 12), rated(4, None, 40.0, 12), rated(5, None, 60.0, 10)];
+        let lines = describe_outcomes(&digest.rated_sessions);
+        assert_eq!(lines[0], "Average rating 3.5/5 over 4 sessions");
+        assert!(lines[1].starts_with("Little connection between rating and efficiency (r = "), "{:?}", lines);
+        assert!(lines[2].starts_with("Little connection between rating and switches (r = "), "{:?}", lines);
+
+        // Too few to correlate, and one isn't plural
+        assert_eq!(describe_outcomes(&[rated(4, None, 80.0, 10), rated(2, None, 40.0, 30)]), ["Average rating 3.0/5 over 2 sessions"]);
+        assert_eq!(describe_outcomes(&[rated(4, Some(false), 80.0, 10)]), ["Average rating 4.0/5 over 1 session", "Intent met in 0 of 1 (0%)"]);
+    }
+
+    #[test]
+    fn weekly_outcomes_are_the_weeks_rated_sessions_with_their_rows() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let monday = stats::start_of_day(week()) + chrono::Duration::hours(9);
+        let save = |name: &str, start: DateTime<Utc>, focus: bool| {
+            db.save_focus_session(&crate::tracking::FocusSession {
+                start_time: start,
+                end_time: Some(start + chrono::Duration::minutes(30)),
+                app_name: "fd-editor".to_string(),
+                window_title: "notes".to_string(),
+                domain: None,
+                duration: Duration::from_secs(30 * 60),
+                is_focus_app: focus,
+                session_name: name.to_string(),
+                activity: None,
+                passive: None,
+                workspace: None,
+                power: None,
+                open_end: crate::tracking::OpenEnd::Closed,
+                manual: false,
+                focus_rule: None,
+                co_focus: None,
+            }).unwrap();
+        };
+        // Rated out of order; read back oldest first
+        let wednesday = monday + chrono::Duration::days(2);
+        save("wednesday", wednesday, false);
+        db.set_session_intent("wednesday", wednesday, Some("inbox zero"), None).unwrap();
+        db.set_session_outcome("wednesday", 2, Some(false)).unwrap();
+        save("monday", monday, true);
+        db.set_session_outcome("monday", 5, None).unwrap();
+        // Unrated, rated without rows, and rated the week after
+        save("tuesday", monday + chrono::Duration::days(1), true);
+        db.set_session_intent("tuesday", monday + chrono::Duration::days(1), Some("review"), None).unwrap();
+        db.set_session_intent("gone", monday, Some("lost"), None).unwrap();
+        db.set_session_outcome("gone", 3, None).unwrap();
+        let next_week = monday + chrono::Duration::days(7);
+        save("next", next_week, true);
+        db.set_session_outcome("next", 4, None).unwrap();
+
+        let digest = collect_weekly(&db, &Config::default(), &StatsSettings::default(), &utc_days(), week()).unwrap();
+        let sessions: Vec<_> = digest.rated_sessions.iter()
+            .map(|session| (session.rating, session.intent_met, session.focus_efficiency.round()))
+            .collect();
+        assert_eq!(sessions, [(5, None, 100.0), (2, Some(false), 0.0)]);
+    }
+
+    #[test]
+    fn digest_averages_the_bookends_over_the_days_that_have_them() {
+        let mut days = fixture_week();
+        for (day, (to_first, wind_down)) in days.iter_mut().zip([(Some(30), Some(60)), (Some(90), None), (None, None), (None, None), (Some(60), Some(20))]) {
+            day.time_to_first_focus = to_first.map(|minutes| Duration::from_secs(minutes * 60));
+            day.focus_wind_down = wind_down.map(|minutes| Duration::from_secs(minutes * 60));
+        }
+        let text = render_weekly(&digest(days), &StatsSettings::default());
+        assert!(text.contains("  First focus        1h 0m after start on average\n  Focus wind-down    40m 0s before sign-off on average\n"), "{}", text);
+
+        let text = render_weekly(&digest(fixture_week()), &StatsSettings::default());
+        assert!(!text.contains("First focus") && !text.contains("Focus wind-down"), "{}", text);
+    }
+}
@@ -0,0 +1,280 @@
+use std::io;
+use std::path::Path;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::Database;
+use crate::utils;
+
+/// meta key the size history is kept under, as a JSON array
+const HISTORY_KEY: &str = "size_history";
+
+/// Days of database sizes kept for the growth rate
+pub const HISTORY_DAYS: i64 = 14;
+
+/// The database's size on one day, as the daemon's daily sample saw it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeSample {
+    pub day: NaiveDate,
+    pub bytes: u64,
+}
+
+/// `history` with `sample` added: a sample for the same day is replaced,
+/// and samples more than HISTORY_DAYS before it are dropped
+pub fn record_sample(history: &[SizeSample], sample: SizeSample) -> Vec<SizeSample> {
+    let mut history: Vec<SizeSample> = history.iter()
+        .copied()
+        .filter(|earlier| earlier.day != sample.day && (sample.day - earlier.day).num_days() <= HISTORY_DAYS)
+        .collect();
+    history.push(sample);
+    history.sort_by_key(|sample| sample.day);
+    history
+}
+
+/// The samples kept in the database; none when missing or unreadable
+pub fn load_history(db: &Database) -> Vec<SizeSample> {
+    db.get_meta(HISTORY_KEY)
+        .ok()
+        .flatten()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+/// Record today's size in `db`'s history and return the new history
+pub fn sample(db: &Database, path: &Path, day: NaiveDate) -> Result<Vec<SizeSample>, Box<dyn std::error::Error>> {
+    let history = record_sample(&load_history(db), SizeSample { day, bytes: database_size(path) });
+    db.set_meta(HISTORY_KEY, &serde_json::to_string(&history)?)?;
+    Ok(history)
+}
+
+/// Mean bytes per day between the oldest and the newest sample; negative
+/// when retention or pruning shrank the database. None with less than a
+/// day between them.
+pub fn growth_per_day(history: &[SizeSample]) -> Option<f64> {
+    let (first, last) = (history.first()?, history.last()?);
+    let days = (last.day - first.day).num_days();
+    (days > 0).then(|| (last.bytes as f64 - first.bytes as f64) / days as f64)
+}
+
+/// Days until `free_bytes` are used up at `growth` bytes a day; None when
+/// the database isn't growing
+pub fn days_until_full(free_bytes: u64, growth: Option<f64>) -> Option<f64> {
+    growth.filter(|growth| *growth > 0.0).map(|growth| free_bytes as f64 / growth)
+}
+
+/// Whether `days_left` is under disk_warning_days (0 never warns)
+pub fn should_warn(days_left: Option<f64>, warning_days: u32) -> bool {
+    warning_days > 0 && days_left.is_some_and(|days| days < warning_days as f64)
+}
+
+/// The database file and SQLite's write-ahead log beside it
+pub fn database_size(path: &Path) -> u64 {
+    let wal = path.with_extension("db-wal");
+    [path, wal.as_path()].iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Bytes an unprivileged process can still write on the filesystem holding `path`
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Bytes an unprivileged process can still write on the volume holding `path`
+#[cfg(windows)]
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available = 0u64;
+    let ok = unsafe { GetDiskFreeSpaceExW(PCWSTR(wide.as_ptr()), Some(&mut available as *mut u64), None, None) };
+    if ok.as_bool() {
+        Ok(available)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn free_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "free space can't be read on this platform"))
+}
+
+/// What doctor, status and `database stats` show about the database's size
+#[derive(Debug, Clone)]
+pub struct DiskReport {
+    pub database_bytes: u64,
+    pub growth_per_day: Option<f64>,
+    /// Days the growth rate was measured over
+    pub history_days: i64,
+    pub free_bytes: Option<u64>,
+    pub days_left: Option<f64>,
+}
+
+impl DiskReport {
+    pub fn collect(database: &Path, history: &[SizeSample]) -> Self {
+        let free_bytes = free_space(database.parent().unwrap_or(database)).ok();
+        let growth = growth_per_day(history);
+        Self {
+            database_bytes: database_size(database),
+            growth_per_day: growth,
+            history_days: match (history.first(), history.last()) {
+                (Some(first), Some(last)) => (last.day - first.day).num_days(),
+                _ => 0,
+            },
+            free_bytes,
+            days_left: free_bytes.and_then(|free| days_until_full(free, growth)),
+        }
+    }
+
+    /// e.g. "+1.2 MB/day over 14 days", or why there is no rate yet
+    pub fn describe_growth(&self) -> String {
+        match self.growth_per_day {
+            Some(growth) if growth < 0.0 => format!("-{}/day over {} days",
+                utils::format_bytes(growth.abs() as u64), self.history_days),
+            Some(growth) => format!("+{}/day over {} days", utils::format_bytes(growth as u64), self.history_days),
+            None => "not known yet (the daemon samples the size once a day)".to_string(),
+        }
+    }
+
+    /// e.g. "41.3 GB free, full in about 812 days at this rate"
+    pub fn describe_free_space(&self) -> String {
+        match (self.free_bytes, self.days_left) {
+            (Some(free), Some(days)) => format!("{} free, full in about {:.0} days at this rate", utils::format_bytes(free), days),
+            (Some(free), None) => format!("{} free", utils::format_bytes(free)),
+            (None, _) => "unknown".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn day(n: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap() + chrono::Duration::days(n as i64)
+    }
+
+    /// A sample a day for `days` days, starting at 10 MB and growing `per_day` bytes a day
+    fn history(days: u32, per_day: u64) -> Vec<SizeSample> {
+        (0..days).fold(Vec::new(), |history, n| {
+            record_sample(&history, SizeSample { day: day(n), bytes: 10 * MB + n as u64 * per_day })
+        })
+    }
+
+    #[test]
+    fn samples_replace_the_same_day_and_keep_fourteen_days() {
+        let history = history(30, MB);
+        assert_eq!(history.len(), HISTORY_DAYS as usize + 1);
+        assert_eq!(history.first().unwrap().day, day(30 - 1 - HISTORY_DAYS as u32));
+        assert_eq!(history.last().unwrap().day, day(29));
+
+        let history = record_sample(&history, SizeSample { day: day(29), bytes: 5 });
+        assert_eq!(history.len(), HISTORY_DAYS as usize + 1);
+        assert_eq!(history.last().unwrap().bytes, 5);
+
+        // A sample for an earlier day (clock set back) is sorted in
+        let history = record_sample(&[SizeSample { day: day(5), bytes: 2 }], SizeSample { day: day(3), bytes: 1 });
+        assert_eq!(history.iter().map(|sample| sample.day).collect::<Vec<_>>(), [day(3), day(5)]);
+    }
+
+    #[test]
+    fn growth_is_the_mean_over_the_history() {
+        assert_eq!(growth_per_day(&history(15, MB)), Some(MB as f64));
+        assert_eq!(growth_per_day(&[]), None);
+        assert_eq!(growth_per_day(&history(1, MB)), None);
+
+        // Uneven days and gaps average out between the ends
+        let uneven = [
+            SizeSample { day: day(0), bytes: 100 },
+            SizeSample { day: day(1), bytes: 1000 },
+            SizeSample { day: day(4), bytes: 500 },
+        ];
+        assert_eq!(growth_per_day(&uneven), Some(100.0));
+
+        // Retention shrank the database
+        let shrinking = [SizeSample { day: day(0), bytes: 10 * MB }, SizeSample { day: day(2), bytes: 8 * MB }];
+        assert_eq!(growth_per_day(&shrinking), Some(-(MB as f64)));
+    }
+
+    #[test]
+    fn days_until_full_and_the_warning_threshold() {
+        assert_eq!(days_until_full(100 * MB, Some(MB as f64)), Some(100.0));
+        assert_eq!(days_until_full(100 * MB, Some(0.0)), None);
+        assert_eq!(days_until_full(100 * MB, Some(-1.0)), None);
+        assert_eq!(days_until_full(100 * MB, None), None);
+
+        assert!(should_warn(Some(29.5), 30));
+        assert!(!should_warn(Some(30.0), 30));
+        assert!(!should_warn(None, 30));
+        assert!(!should_warn(Some(1.0), 0));
+
+        let growth = growth_per_day(&history(15, 2 * MB));
+        assert!(should_warn(days_until_full(50 * MB, growth), 30));
+        assert!(!should_warn(days_until_full(500 * MB, growth), 30));
+    }
+
+    #[test]
+    fn reports_describe_growth_and_free_space() {
+        let report = DiskReport {
+            database_bytes: 12 * MB,
+            growth_per_day: Some(1.5 * MB as f64),
+            history_days: 14,
+            free_bytes: Some(300 * MB),
+            days_left: Some(200.0),
+        };
+        assert_eq!(report.describe_growth(), "+1.5 MB/day over 14 days");
+        assert_eq!(report.describe_free_space(), "300.0 MB free, full in about 200 days at this rate");
+
+        let report = DiskReport { growth_per_day: Some(-2048.0), days_left: None, ..report };
+        assert_eq!(report.describe_growth(), "-2.0 KB/day over 14 days");
+        assert_eq!(report.describe_free_space(), "300.0 MB free");
+
+        let report = DiskReport { growth_per_day: None, free_bytes: None, ..report };
+        assert!(report.describe_growth().starts_with("not known yet"));
+        assert_eq!(report.describe_free_space(), "unknown");
+    }
+
+    #[test]
+    fn daily_samples_are_kept_in_the_meta_table() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-disk-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("focusdebt.db");
+        std::fs::write(&path, vec![0u8; 1000]).unwrap();
+        std::fs::write(path.with_extension("db-wal"), vec![0u8; 24]).unwrap();
+        assert_eq!(database_size(&path), 1024);
+        assert_eq!(database_size(&dir.join("missing.db")), 0);
+
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        assert!(load_history(&db).is_empty());
+        sample(&db, &path, day(0)).unwrap();
+        std::fs::write(&path, vec![0u8; 3000]).unwrap();
+        let history = sample(&db, &path, day(2)).unwrap();
+        assert_eq!(load_history(&db), history);
+        assert_eq!(history.iter().map(|sample| sample.bytes).collect::<Vec<_>>(), [1024, 3024]);
+        assert_eq!(growth_per_day(&history), Some(1000.0));
+
+        let report = DiskReport::collect(&path, &history);
+        assert_eq!((report.database_bytes, report.history_days), (3024, 2));
+        assert!(report.free_bytes.is_some());
+
+        // A garbled value reads as no history
+        db.set_meta("size_history", "not json").unwrap();
+        assert!(load_history(&db).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
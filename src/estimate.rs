@@ -0,0 +1,320 @@
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use serde::Serialize;
+
+use crate::stats::{Stats, StatsSettings};
+use crate::storage::Database;
+use crate::utils::{self, DurationStyle, LocalDays};
+
+/// Past days of the same weekday the hourly pattern is taken from
+pub const HISTORY_WEEKS: u32 = 8;
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+/// Focus time in each hour of one day, from the hour it starts
+pub type HourlyFocus = [Duration; 24];
+
+/// Where today's focus time is heading if the rest of the day goes like
+/// the same weekday usually does
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub so_far: Duration,
+    /// From now to the end of the day
+    pub remaining: Duration,
+    /// Mean focus the past days had in the hours still ahead
+    pub expected_rest: Duration,
+    pub goal: Duration,
+    /// Past days the mean is taken over
+    pub history_days: usize,
+    /// Of those, the days whose remaining hours held enough focus to reach
+    /// the goal from where today stands
+    pub reaching_days: usize,
+}
+
+impl Projection {
+    pub fn projected(&self) -> Duration {
+        self.so_far + self.expected_rest
+    }
+
+    /// Share of the past days that would have carried today to the goal;
+    /// certain once it is reached, None without history to go on
+    pub fn chance(&self) -> Option<f64> {
+        if self.so_far >= self.goal {
+            return Some(1.0);
+        }
+        (self.history_days > 0).then(|| self.reaching_days as f64 / self.history_days as f64)
+    }
+}
+
+/// The focus `day` had after `elapsed` into it, counting the hour
+/// `elapsed` falls in pro rata
+pub fn rest_of_day(day: &HourlyFocus, elapsed: Duration) -> Duration {
+    day.iter()
+        .enumerate()
+        .map(|(hour, focus)| {
+            let start = HOUR * hour as u32;
+            let left = (start + HOUR).saturating_sub(elapsed.max(start));
+            focus.mul_f64(left.as_secs_f64() / HOUR.as_secs_f64())
+        })
+        .sum()
+}
+
+/// Today's `so_far` plus, for the hours after `elapsed`, the mean focus of
+/// the `history` days in those hours. Each past day also counts as one
+/// outcome: did its remaining hours hold the focus still missing for `goal`?
+pub fn project(so_far: Duration, elapsed: Duration, history: &[HourlyFocus], goal: Duration) -> Projection {
+    let elapsed = elapsed.min(DAY);
+    let rests: Vec<Duration> = history.iter().map(|day| rest_of_day(day, elapsed)).collect();
+    let expected_rest = match rests.len() {
+        0 => Duration::ZERO,
+        days => rests.iter().sum::<Duration>() / days as u32,
+    };
+    let missing = goal.saturating_sub(so_far);
+    Projection {
+        so_far,
+        remaining: DAY - elapsed,
+        expected_rest,
+        goal,
+        history_days: rests.len(),
+        reaching_days: rests.iter().filter(|rest| **rest >= missing).count(),
+    }
+}
+
+/// Everything `estimate` shows
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub at: DateTime<Utc>,
+    /// The local day `at` falls on
+    pub day: NaiveDate,
+    pub day_end: DateTime<Utc>,
+    pub projection: Projection,
+    /// Whether the running daemon's unsaved rows are in today's time
+    pub live: bool,
+}
+
+/// Today's focus so far (live rows included when `settings` has them)
+/// projected with the last HISTORY_WEEKS of today's weekday. Days are the
+/// local days of `days`, as in `stats`; weekdays with nothing tracked are
+/// left out.
+pub fn collect(db: &Database, settings: &StatsSettings, days: &LocalDays, now: DateTime<Utc>, goal: Duration) -> Result<Estimate, Box<dyn std::error::Error>> {
+    let day = days.day_of(now);
+    let today = Stats::calculate_daily_stats(db, settings, days, day)?;
+    let mut history = Vec::new();
+    for weeks in 1..=HISTORY_WEEKS {
+        if let Some(mut hours) = Stats::calculate_hourly_focus(db, settings, days, day - chrono::Duration::weeks(weeks as i64))? {
+            // Hours are by the clock; the day's first is day_start_hour
+            hours.rotate_left(days.start_hour as usize);
+            history.push(hours);
+        }
+    }
+    // Read off the clock, like the hours, so a DST change doesn't shift
+    // which of them are still ahead
+    let clock = now.with_timezone(&days.zone).num_seconds_from_midnight() as u64;
+    let elapsed = Duration::from_secs((clock + DAY.as_secs() - u64::from(days.start_hour) * 3600) % DAY.as_secs());
+    let (_, day_end) = days.range(day);
+    let projection = Projection {
+        remaining: (day_end - now).to_std().unwrap_or_default(),
+        ..project(today.total_focus_time, elapsed, &history, goal)
+    };
+    Ok(Estimate { at: now, day, day_end, projection, live: today.live })
+}
+
+fn weekday_name(day: NaiveDate) -> String {
+    day.format("%A").to_string()
+}
+
+pub fn render(estimate: &Estimate) -> String {
+    let projection = &estimate.projection;
+    let long = |duration: Duration| utils::format_duration(duration, DurationStyle::Long);
+    let weekdays = format!("{}s", weekday_name(estimate.day));
+    let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+    let mut report = String::new();
+    report.push_str(&format!("\n{}\n\n", top_sep));
+    report.push_str(&format!("END OF DAY ESTIMATE ({})\n\n", weekday_name(estimate.day)));
+    report.push_str(&format!("Focus So Far      : {}\n", long(projection.so_far)));
+    report.push_str(&format!("Expected Rest     : {} in the {} left of the day\n",
+        long(projection.expected_rest), long(projection.remaining)));
+    report.push_str(&format!("Projected Total   : {}\n", long(projection.projected())));
+    let against_goal = if projection.so_far >= projection.goal {
+        "reached".to_string()
+    } else if projection.projected() >= projection.goal {
+        format!("{} over at the usual pace", long(projection.projected() - projection.goal))
+    } else {
+        format!("{} short at the usual pace", long(projection.goal - projection.projected()))
+    };
+    report.push_str(&format!("Goal              : {}, {}\n", long(projection.goal), against_goal));
+    let chance = match projection.chance() {
+        Some(_) if projection.so_far >= projection.goal => "100%".to_string(),
+        Some(chance) => format!("{:.0}% ({} of the last {} tracked {} had the {} still missing after {})",
+            chance * 100.0, projection.reaching_days, projection.history_days, weekdays,
+            long(projection.goal - projection.so_far), utils::format_timestamp_local(estimate.at)),
+        None => format!("unknown, no {} tracked in the last {} weeks", weekdays, HISTORY_WEEKS),
+    };
+    report.push_str(&format!("Chance of Goal    : {}\n\n", chance));
+
+    report.push_str("ASSUMPTIONS\n\n");
+    report.push_str(&format!("  Each hour left today brings the mean focus of the last {} weeks' {}\n", HISTORY_WEEKS, weekdays));
+    report.push_str(&format!("  {} with nothing tracked are left out ({} used)\n", weekdays, projection.history_days));
+    report.push_str(&format!("  The day ends at {}, as in stats\n", utils::format_timestamp_local(estimate.day_end)));
+    if estimate.live {
+        report.push_str("  Today includes what the running daemon hasn't saved yet\n");
+    }
+    report.push_str(&format!("\n{}\n\n", top_sep));
+    report
+}
+
+#[derive(Serialize)]
+struct EstimateJson {
+    at: String,
+    weekday: String,
+    focus_so_far_seconds: u64,
+    remaining_seconds: u64,
+    expected_rest_seconds: u64,
+    projected_seconds: u64,
+    goal_seconds: u64,
+    /// 0.0–1.0, null without history
+    chance: Option<f64>,
+    history_weeks: u32,
+    history_days: usize,
+    reaching_days: usize,
+    live: bool,
+}
+
+pub fn to_json(estimate: &Estimate) -> Result<String, serde_json::Error> {
+    let projection = &estimate.projection;
+    serde_json::to_string_pretty(&EstimateJson {
+        at: estimate.at.to_rfc3339(),
+        weekday: weekday_name(estimate.day),
+        focus_so_far_seconds: projection.so_far.as_secs(),
+        remaining_seconds: projection.remaining.as_secs(),
+        expected_rest_seconds: projection.expected_rest.as_secs(),
+        projected_seconds: projection.projected().as_secs(),
+        goal_seconds: projection.goal.as_secs(),
+        chance: projection.chance(),
+        history_weeks: HISTORY_WEEKS,
+        history_days: projection.history_days,
+        reaching_days: projection.reaching_days,
+        live: estimate.live,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::{FocusSession, OpenEnd};
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    /// A day with `focus` minutes in each of `hours`
+    fn day(hours: std::ops::Range<usize>, focus: u64) -> HourlyFocus {
+        let mut day = [Duration::ZERO; 24];
+        for hour in hours {
+            day[hour] = minutes(focus);
+        }
+        day
+    }
+
+    #[test]
+    fn the_rest_of_a_day_counts_the_current_hour_pro_rata() {
+        let day = day(9..17, 40);
+        assert_eq!(rest_of_day(&day, Duration::ZERO), minutes(8 * 40));
+        assert_eq!(rest_of_day(&day, HOUR * 15), minutes(2 * 40));
+        assert_eq!(rest_of_day(&day, HOUR * 15 + minutes(30)), minutes(60));
+        assert_eq!(rest_of_day(&day, HOUR * 17), Duration::ZERO);
+        assert_eq!(rest_of_day(&day, DAY), Duration::ZERO);
+    }
+
+    #[test]
+    fn projections_add_the_mean_rest_of_past_days() {
+        // At 14:00 with 2h done and a 4h goal, so 2h still missing
+        let history = [day(9..18, 50), day(9..18, 30), day(9..12, 60), day(13..18, 20)];
+        let projection = project(minutes(120), HOUR * 14, &history, minutes(240));
+        // Rests: 200, 120, 0 and 80 minutes
+        assert_eq!(projection.expected_rest, minutes(100));
+        assert_eq!(projection.projected(), minutes(220));
+        assert_eq!(projection.remaining, HOUR * 10);
+        assert_eq!((projection.history_days, projection.reaching_days), (4, 2));
+        assert_eq!(projection.chance(), Some(0.5));
+
+        // Ahead of the goal already
+        let done = project(minutes(300), HOUR * 14, &history, minutes(240));
+        assert_eq!((done.reaching_days, done.chance()), (4, Some(1.0)));
+        assert_eq!(done.projected(), minutes(400));
+
+        // Late in the day, no past day had that much left
+        let late = project(minutes(60), HOUR * 17, &history, minutes(240));
+        assert_eq!(late.chance(), Some(0.0));
+    }
+
+    #[test]
+    fn without_history_there_is_no_chance_to_give() {
+        let projection = project(minutes(90), HOUR * 10, &[], minutes(240));
+        assert_eq!((projection.expected_rest, projection.projected()), (Duration::ZERO, minutes(90)));
+        assert_eq!(projection.chance(), None);
+        // A clock past midnight still leaves nothing to go
+        assert_eq!(project(minutes(90), DAY + HOUR, &[], minutes(60)).remaining, Duration::ZERO);
+        assert_eq!(project(minutes(90), HOUR, &[], minutes(60)).chance(), Some(1.0));
+    }
+
+    #[test]
+    fn reports_state_the_assumptions_and_json_the_numbers() {
+        let at = DateTime::parse_from_rfc3339("2024-05-21T14:00:00Z").unwrap().with_timezone(&Utc);
+        let estimate = Estimate {
+            at,
+            day: at.date_naive(),
+            day_end: at + chrono::Duration::hours(10),
+            projection: project(minutes(120), HOUR * 14, &[day(14..18, 30), day(14..16, 30)], minutes(240)),
+            live: true,
+        };
+        let report = render(&estimate);
+        assert!(report.contains("END OF DAY ESTIMATE (Tuesday)"), "{}", report);
+        assert!(report.contains("Projected Total   : 3h 30m"), "{}", report);
+        assert!(report.contains("4h 0m, 30m 0s short at the usual pace"), "{}", report);
+        assert!(report.contains("50% (1 of the last 2 tracked Tuesdays"), "{}", report);
+        assert!(report.contains("running daemon hasn't saved yet"), "{}", report);
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&estimate).unwrap()).unwrap();
+        assert_eq!(json["weekday"], "Tuesday");
+        assert_eq!(json["projected_seconds"], 210 * 60);
+        assert_eq!(json["chance"], 0.5);
+        assert_eq!(json["history_weeks"], HISTORY_WEEKS);
+    }
+
+    #[test]
+    fn estimates_follow_the_local_day() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let days = LocalDays::in_zone(utils::timezone::Zone::Named(chrono_tz::Asia::Kolkata), 0);
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        // 02:00 to 03:00 IST on Wednesday 15 May, still Tuesday in UTC
+        db.save_focus_session(&FocusSession {
+            start_time: at("2024-05-14T20:30:00Z"),
+            end_time: Some(at("2024-05-14T21:30:00Z")),
+            app_name: "fd-editor".to_string(),
+            window_title: "fd-editor window".to_string(),
+            domain: None,
+            duration: HOUR,
+            is_focus_app: true,
+            session_name: "work".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }).unwrap();
+
+        // 01:30 IST on Wednesday 22 May
+        let estimate = collect(&db, &StatsSettings::default(), &days, at("2024-05-21T20:00:00Z"), minutes(240)).unwrap();
+        assert_eq!(estimate.day, NaiveDate::from_ymd_opt(2024, 5, 22).unwrap());
+        assert_eq!(estimate.day_end, at("2024-05-22T18:30:00Z"));
+        assert_eq!(estimate.projection.history_days, 1);
+        assert_eq!(estimate.projection.expected_rest, HOUR);
+        assert_eq!(estimate.projection.remaining, HOUR * 22 + minutes(30));
+        assert!(render(&estimate).contains("END OF DAY ESTIMATE (Wednesday)"));
+    }
+}
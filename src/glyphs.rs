@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::config::DisplayGlyphs;
+use crate::utils;
+
+/// Measured widths of PROBE, by terminal (TERM_PROGRAM and TERM)
+const WIDTH_CACHE_FILE: &str = "glyph_widths.json";
+/// One of each block character bars use; a terminal that can be trusted
+/// with them draws this two columns wide
+const PROBE: &str = "■▓";
+
+/// The characters reports are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphSet {
+    Unicode,
+    Ascii,
+}
+
+/// Which bar a report draws: blocks for usage lists, shades for efficiency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarStyle {
+    /// "■■□□"
+    Blocks,
+    /// "▓▓░░"
+    Shade,
+}
+
+impl GlyphSet {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GlyphSet::Unicode => "unicode",
+            GlyphSet::Ascii => "ascii",
+        }
+    }
+
+    /// The filled and the empty cell of a `style` bar
+    pub fn bar_cells(&self, style: BarStyle) -> (char, char) {
+        match (self, style) {
+            (GlyphSet::Unicode, BarStyle::Blocks) => ('■', '□'),
+            (GlyphSet::Unicode, BarStyle::Shade) => ('▓', '░'),
+            (GlyphSet::Ascii, _) => ('=', '-'),
+        }
+    }
+
+    /// A fully filled cell, for histograms and timelines
+    pub fn solid(&self) -> char {
+        match self {
+            GlyphSet::Unicode => '█',
+            GlyphSet::Ascii => '#',
+        }
+    }
+
+    /// Heatmap cells from the lowest level to the highest, and the cell for none
+    pub fn levels(&self) -> ([char; 4], char) {
+        match self {
+            GlyphSet::Unicode => (['░', '▒', '▓', '█'], '·'),
+            GlyphSet::Ascii => (['-', '+', '*', '#'], '.'),
+        }
+    }
+}
+
+static SETTING: OnceLock<DisplayGlyphs> = OnceLock::new();
+static RESOLVED: OnceLock<GlyphSet> = OnceLock::new();
+
+/// Apply display_glyphs; auto is settled the first time a bar is drawn
+pub fn set_display_glyphs(setting: DisplayGlyphs) {
+    let _ = SETTING.set(setting);
+}
+
+/// The glyphs reports use in this run
+pub fn current() -> GlyphSet {
+    *RESOLVED.get_or_init(|| match SETTING.get().copied().unwrap_or_default() {
+        DisplayGlyphs::Unicode => GlyphSet::Unicode,
+        DisplayGlyphs::Ascii => GlyphSet::Ascii,
+        DisplayGlyphs::Auto => detect(),
+    })
+}
+
+/// What the locale and TERM settle on their own: ASCII without a UTF-8
+/// locale, on a dumb terminal or on the Linux console. None leaves it to
+/// measuring the terminal.
+pub fn from_environment(locale: Option<&str>, term: Option<&str>) -> Option<GlyphSet> {
+    let utf8 = locale.is_some_and(|locale| {
+        let locale = locale.to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    });
+    if !utf8 || matches!(term, Some("dumb" | "linux")) {
+        return Some(GlyphSet::Ascii);
+    }
+    None
+}
+
+/// Unicode when PROBE came out one column per character; a terminal that
+/// couldn't be measured (output piped, no answer) gets the benefit of the
+/// doubt, as its locale is UTF-8
+pub fn from_measured_width(width: Option<usize>) -> GlyphSet {
+    match width {
+        Some(width) if width != PROBE.chars().count() => GlyphSet::Ascii,
+        _ => GlyphSet::Unicode,
+    }
+}
+
+/// The column from a cursor position report, "ESC[<row>;<column>R"
+pub fn parse_cursor_report(response: &str) -> Option<usize> {
+    let report = &response[response.rfind("\x1b[")? + 2..];
+    let (_, column) = report.strip_suffix('R')?.split_once(';')?;
+    column.parse().ok()
+}
+
+#[cfg(windows)]
+fn detect() -> GlyphSet {
+    // Windows Terminal draws the blocks; the classic console's raster fonts may not
+    match utils::non_empty_env("WT_SESSION") {
+        Some(_) => GlyphSet::Unicode,
+        None => GlyphSet::Ascii,
+    }
+}
+
+#[cfg(not(windows))]
+fn detect() -> GlyphSet {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].into_iter().find_map(utils::non_empty_env);
+    let term = utils::non_empty_env("TERM");
+    if let Some(glyphs) = from_environment(locale.as_deref(), term.as_deref()) {
+        return glyphs;
+    }
+    let terminal = format!("{}/{}", utils::non_empty_env("TERM_PROGRAM").unwrap_or_default(), term.unwrap_or_default());
+    let mut cache = load_cache();
+    let width = match cache.get(&terminal) {
+        Some(width) => Some(*width),
+        None => {
+            let width = measure();
+            if let Some(width) = width {
+                cache.insert(terminal, width);
+                let _ = save_cache(&cache);
+            }
+            width
+        }
+    };
+    from_measured_width(width)
+}
+
+fn load_cache() -> BTreeMap<String, usize> {
+    utils::get_data_directory()
+        .and_then(|dir| fs::read_to_string(dir.join(WIDTH_CACHE_FILE)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &BTreeMap<String, usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = utils::ensure_data_directory()?;
+    fs::write(data_dir.join(WIDTH_CACHE_FILE), serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
+
+/// Print PROBE at the start of the line, ask the terminal where the cursor
+/// ended up and wipe the line again. None unless both stdin and stdout are
+/// the terminal and it answers within 300 ms.
+#[cfg(unix)]
+fn measure() -> Option<usize> {
+    use std::io::{self, IsTerminal, Read, Write};
+
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return None;
+    }
+    let original = unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(0, &mut termios) != 0 {
+            return None;
+        }
+        let mut raw = termios;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 3;
+        if libc::tcsetattr(0, libc::TCSANOW, &raw) != 0 {
+            return None;
+        }
+        termios
+    };
+    let column = (|| {
+        let mut stdout = io::stdout();
+        stdout.write_all(format!("\r{}\x1b[6n", PROBE).as_bytes()).ok()?;
+        stdout.flush().ok()?;
+        let mut stdin = io::stdin();
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while response.len() < 32 && response.last() != Some(&b'R') {
+            if stdin.read(&mut byte).ok()? == 0 {
+                return None;
+            }
+            response.push(byte[0]);
+        }
+        parse_cursor_report(&String::from_utf8_lossy(&response))
+    })();
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\r\x1b[K");
+    let _ = stdout.flush();
+    unsafe { libc::tcsetattr(0, libc::TCSANOW, &original) };
+    column.map(|column| column.saturating_sub(1))
+}
+
+#[cfg(not(unix))]
+fn measure() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_and_term_pick_ascii_or_leave_it_to_measuring() {
+        assert_eq!(from_environment(Some("en_US.UTF-8"), Some("xterm-256color")), None);
+        assert_eq!(from_environment(Some("de_DE.utf8"), None), None);
+        assert_eq!(from_environment(Some("C"), Some("xterm-256color")), Some(GlyphSet::Ascii));
+        assert_eq!(from_environment(None, Some("xterm-256color")), Some(GlyphSet::Ascii));
+        assert_eq!(from_environment(Some("en_US.UTF-8"), Some("dumb")), Some(GlyphSet::Ascii));
+        assert_eq!(from_environment(Some("en_US.UTF-8"), Some("linux")), Some(GlyphSet::Ascii));
+    }
+
+    #[test]
+    fn wide_probes_fall_back_to_ascii_and_unmeasured_ones_do_not() {
+        assert_eq!(from_measured_width(Some(2)), GlyphSet::Unicode);
+        assert_eq!(from_measured_width(Some(4)), GlyphSet::Ascii);
+        assert_eq!(from_measured_width(Some(0)), GlyphSet::Ascii);
+        assert_eq!(from_measured_width(None), GlyphSet::Unicode);
+    }
+
+    #[test]
+    fn cursor_reports_give_the_column() {
+        assert_eq!(parse_cursor_report("\x1b[12;3R"), Some(3));
+        // Anything the terminal echoed first is skipped
+        assert_eq!(parse_cursor_report("■▓\x1b[1;5R"), Some(5));
+        assert_eq!(parse_cursor_report("\x1b[12;R"), None);
+        assert_eq!(parse_cursor_report("\x1b[12;3"), None);
+        assert_eq!(parse_cursor_report("12;3R"), None);
+    }
+
+    #[test]
+    fn ascii_glyphs_are_all_one_byte() {
+        let (levels, none) = GlyphSet::Ascii.levels();
+        let mut cells: Vec<char> = levels.into_iter().chain([none, GlyphSet::Ascii.solid()]).collect();
+        for style in [BarStyle::Blocks, BarStyle::Shade] {
+            let (filled, empty) = GlyphSet::Ascii.bar_cells(style);
+            cells.extend([filled, empty]);
+            assert_ne!(filled, empty);
+            assert_ne!(GlyphSet::Unicode.bar_cells(style), (filled, empty));
+        }
+        assert!(cells.iter().all(char::is_ascii), "{:?}", cells);
+        assert_eq!(GlyphSet::Unicode.bar_cells(BarStyle::Blocks), ('■', '□'));
+        assert_eq!(GlyphSet::Unicode.bar_cells(BarStyle::Shade), ('▓', '░'));
+    }
+}
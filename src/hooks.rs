@@ -0,0 +1,201 @@
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Hooks that are still running after this long are killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run each user hook command in the background with the given extra
+/// environment. Output goes wherever the daemon's stdout/stderr go (the
+/// daemon log). Failures are logged and never propagate to the caller.
+pub fn run_hooks(kind: &str, commands: &[String], env: &[(&str, String)]) -> Vec<JoinHandle<()>> {
+    run_hooks_with_timeout(kind, commands, env, HOOK_TIMEOUT)
+}
+
+fn run_hooks_with_timeout(kind: &str, commands: &[String], env: &[(&str, String)], timeout: Duration) -> Vec<JoinHandle<()>> {
+    commands.iter()
+        .filter(|command| !command.trim().is_empty())
+        .filter_map(|command| {
+            let mut shell = shell_command(command);
+            shell.envs(env.iter().map(|(key, value)| (*key, value.as_str())))
+                .stdin(Stdio::null())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit());
+
+            match shell.spawn() {
+                Ok(child) => {
+                    println!("~=~ Running {} hook: {}", kind, command);
+                    let kind = kind.to_string();
+                    let command = command.clone();
+                    Some(thread::spawn(move || wait_with_timeout(&kind, &command, child, timeout)))
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to run {} hook '{}': {}", kind, command, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn wait_with_timeout(kind: &str, command: &str, mut child: Child, timeout: Duration) {
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return,
+            Ok(Some(status)) => {
+                eprintln!("❌ {} hook '{}' exited with {}", kind, command, status);
+                return;
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                eprintln!("❌ {} hook '{}' timed out after {}s, killing it", kind, command, timeout.as_secs());
+                let _ = child.kill();
+                let _ = child.wait();
+                return;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(100)),
+            Err(e) => {
+                eprintln!("❌ Failed to wait for {} hook '{}': {}", kind, command, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Whether the program a hook command starts can be found: a path that
+/// exists, or (on Unix) anything the shell resolves, builtins included.
+/// Leading VAR=value assignments are skipped.
+pub fn check_command(command: &str) -> Result<(), String> {
+    let Some(program) = command.split_whitespace().find(|word| !word.contains('=')) else {
+        return Err("no program to run".to_string());
+    };
+    if program.contains(['/', '\\']) {
+        return match std::path::Path::new(program).exists() {
+            true => Ok(()),
+            false => Err(format!("'{}' does not exist", program)),
+        };
+    }
+    resolve_in_shell(program)
+}
+
+#[cfg(unix)]
+fn resolve_in_shell(program: &str) -> Result<(), String> {
+    let found = Command::new("sh")
+        .args(["-c", "command -v -- \"$1\"", "sh", program])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("could not start sh: {}", e))?;
+    match found.success() {
+        true => Ok(()),
+        false => Err(format!("'{}' not found", program)),
+    }
+}
+
+#[cfg(windows)]
+fn resolve_in_shell(_program: &str) -> Result<(), String> {
+    // cmd has no cheap lookup that covers its builtins; let the hook run
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(windows)]
+pub fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("focusdebt-hooks-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        /// A hook script that writes its FOCUSDEBT_* environment to `out`
+        fn env_script(&self, out: &str) -> String {
+            let script = self.0.join("hook.sh");
+            std::fs::write(&script, format!("#!/bin/sh\nenv | grep '^FOCUSDEBT_' | sort > '{}'\n", self.0.join(out).display())).unwrap();
+            format!("sh '{}'", script.display())
+        }
+
+        fn read(&self, out: &str) -> String {
+            std::fs::read_to_string(self.0.join(out)).unwrap_or_default()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn join_all(hooks: Vec<JoinHandle<()>>) {
+        for hook in hooks {
+            hook.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn end_hooks_see_the_session_environment() {
+        let scratch = Scratch::new("env");
+        let commands = [scratch.env_script("end.env")];
+        let env = [
+            ("FOCUSDEBT_SESSION_NAME", "deep work".to_string()),
+            ("FOCUSDEBT_FOCUS_SECONDS", "1800".to_string()),
+            ("FOCUSDEBT_EFFICIENCY", "75".to_string()),
+        ];
+        join_all(run_hooks("session end", &commands, &env));
+        assert_eq!(
+            scratch.read("end.env"),
+            "FOCUSDEBT_EFFICIENCY=75\nFOCUSDEBT_FOCUS_SECONDS=1800\nFOCUSDEBT_SESSION_NAME=deep work\n",
+        );
+    }
+
+    #[test]
+    fn failing_and_blank_hooks_do_not_stop_the_others() {
+        let scratch = Scratch::new("failing");
+        let commands = [
+            "exit 3".to_string(),
+            "  ".to_string(),
+            "/nonexistent/focusdebt-hook".to_string(),
+            scratch.env_script("start.env"),
+        ];
+        let hooks = run_hooks("session start", &commands, &[("FOCUSDEBT_SESSION_NAME", "review".to_string())]);
+        assert_eq!(hooks.len(), 3);
+        join_all(hooks);
+        assert_eq!(scratch.read("start.env"), "FOCUSDEBT_SESSION_NAME=review\n");
+    }
+
+    #[test]
+    fn hooks_past_the_timeout_are_killed() {
+        let started = Instant::now();
+        join_all(run_hooks_with_timeout("session end", &["sleep 30".to_string()], &[], Duration::from_millis(300)));
+        assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn check_command_finds_paths_builtins_and_programs() {
+        assert_eq!(check_command("FOO=1 true --flag"), Ok(()));
+        assert_eq!(check_command("sh -c 'exit 0'"), Ok(()));
+        assert_eq!(check_command("/nonexistent/hook"), Err("'/nonexistent/hook' does not exist".to_string()));
+        assert_eq!(check_command("focusdebt-no-such-program"), Err("'focusdebt-no-such-program' not found".to_string()));
+        assert!(check_command("A=1 ").is_err());
+    }
+}
@@ -0,0 +1,904 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+use crate::config::{Config, SessionClass};
+use crate::desktop;
+use crate::logs;
+use crate::progress::Progress;
+use crate::storage::{Database, MANUAL_SOURCE};
+use crate::timing::{self, Phase};
+use crate::tracking::{FocusSession, OpenEnd};
+use crate::utils::{self, DurationStyle};
+
+pub const RESCUETIME_SOURCE: &str = "rescuetime";
+
+/// Rows are written in one transaction per batch
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Daily totals are laid end to end from this local hour
+const PACKED_DAY_START_HOUR: u32 = 9;
+
+/// At most this many failed rows are listed in the summary
+const MAX_REPORTED_FAILURES: usize = 5;
+
+/// One row of a RescueTime export. `bucket` is the start of the period the
+/// total covers: a whole day, or an hour in hourly exports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RescueTimeRow {
+    pub line: usize,
+    pub date: String,
+    pub bucket: NaiveDateTime,
+    pub time_spent: Duration,
+    pub activity: String,
+    pub category: Option<String>,
+    pub productivity: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub failures: Vec<String>,
+}
+
+impl ImportSummary {
+    fn fail(&mut self, count: usize, reason: String) {
+        self.failed += count;
+        if self.failures.len() < MAX_REPORTED_FAILURES {
+            self.failures.push(reason);
+        }
+    }
+}
+
+/// Split CSV text into records. Quoted fields may hold commas, doubled
+/// quotes and line breaks.
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// "2024-05-21" is a daily total; "2024-05-21T14:00:00" (or with a space)
+/// an hourly one
+fn parse_bucket(date: &str) -> Option<NaiveDateTime> {
+    let date = date.trim();
+    if let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return day.and_hms_opt(PACKED_DAY_START_HOUR, 0, 0);
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+}
+
+/// Seconds ("3600", "90.5") or "h:mm:ss"
+fn parse_time_spent(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds));
+    }
+    let parts: Vec<u64> = value.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [hours, minutes, seconds] => Some(Duration::from_secs(hours * 3600 + minutes * 60 + seconds)),
+        [minutes, seconds] => Some(Duration::from_secs(minutes * 60 + seconds)),
+        _ => None,
+    }
+}
+
+/// Rows of a RescueTime CSV export, located by header name so column order
+/// doesn't matter. Rows that can't be read come back as errors with their
+/// line number.
+pub fn parse_rescuetime_csv(content: &str) -> Result<Vec<Result<RescueTimeRow, String>>, String> {
+    let _timer = timing::scope(Phase::Parse);
+    let mut records = parse_csv(content.trim_start_matches('\u{feff}')).into_iter();
+    let header: Vec<String> = records.next()
+        .ok_or("the file is empty")?
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    let column = |matches: &dyn Fn(&str) -> bool| header.iter().position(|name| matches(name));
+
+    let date_col = column(&|name| name == "date" || name.starts_with("date "))
+        .ok_or("no Date column")?;
+    let time_col = column(&|name| name.starts_with("time spent"))
+        .ok_or("no 'Time Spent' column")?;
+    let activity_col = column(&|name| name == "activity").ok_or("no Activity column")?;
+    let productivity_col = column(&|name| name.starts_with("productivity"))
+        .ok_or("no Productivity column")?;
+    let category_col = column(&|name| name == "category");
+
+    let rows = records.enumerate().map(|(index, record)| {
+        let line = index + 2;
+        let field = |col: usize| record.get(col).map(|value| value.trim()).unwrap_or("");
+        let date = field(date_col);
+        let bucket = parse_bucket(date)
+            .ok_or_else(|| format!("line {}: unreadable date '{}'", line, date))?;
+        let time_spent = parse_time_spent(field(time_col))
+            .ok_or_else(|| format!("line {}: unreadable time spent '{}'", line, field(time_col)))?;
+        let activity = field(activity_col);
+        if activity.is_empty() {
+            return Err(format!("line {}: no activity", line));
+        }
+        let productivity = field(productivity_col).parse::<i32>()
+            .map_err(|_| format!("line {}: unreadable productivity '{}'", line, field(productivity_col)))?;
+        Ok(RescueTimeRow {
+            line,
+            date: date.to_string(),
+            bucket,
+            time_spent,
+            activity: activity.to_string(),
+            category: category_col.map(field).filter(|category| !category.is_empty()).map(str::to_string),
+            productivity,
+        })
+    }).collect();
+    Ok(rows)
+}
+
+/// RescueTime only exports totals per day (or hour), so each bucket's rows
+/// are laid end to end from the bucket's start in file order: 09:00 local
+/// for daily totals. Durations and days are exact; times of day are not.
+/// Productivity 1 and 2 count as focus. The key identifies a row across
+/// re-imports.
+pub fn pack_rescuetime_rows(rows: &[RescueTimeRow]) -> Vec<(String, FocusSession)> {
+    let mut cursors: BTreeMap<NaiveDateTime, NaiveDateTime> = BTreeMap::new();
+    rows.iter().map(|row| {
+        let spent = Duration::from_secs(row.time_spent.as_secs());
+        let cursor = cursors.entry(row.bucket).or_insert(row.bucket);
+        let local_start = *cursor;
+        *cursor += chrono::Duration::from_std(spent).unwrap_or_default();
+
+        let start_time = utils::timezone::zone().from_local_datetime(&local_start)
+            .earliest()
+            // A start in a DST gap is read as UTC rather than dropped
+            .map(|start| start.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local_start));
+        let end_time = start_time + chrono::Duration::from_std(spent).unwrap_or_default();
+        let session = FocusSession {
+            start_time,
+            end_time: Some(end_time),
+            app_name: row.activity.clone(),
+            window_title: row.category.clone().unwrap_or_default(),
+            domain: None,
+            duration: spent,
+            is_focus_app: row.productivity >= 1,
+            session_name: format!("{}-{}", RESCUETIME_SOURCE, row.bucket.date().format("%Y-%m-%d")),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        };
+        let key = format!("{}|{}|{}", row.date, row.activity, spent.as_secs());
+        (key, session)
+    }).collect()
+}
+
+/// Import a RescueTime CSV export into `db`. Rows imported by an earlier run
+/// are skipped.
+pub fn import_rescuetime(db: &Database, content: &str) -> Result<ImportSummary, String> {
+    let parsed = parse_rescuetime_csv(content)?;
+    let mut summary = ImportSummary::default();
+    let mut rows: Vec<RescueTimeRow> = Vec::new();
+    for row in parsed {
+        match row {
+            Ok(row) if row.time_spent.as_secs() == 0 => summary.skipped += 1,
+            Ok(row) => rows.push(row),
+            Err(reason) => summary.fail(1, reason),
+        }
+    }
+    let sessions = pack_rescuetime_rows(&rows);
+    let mut progress = Progress::new("Importing RescueTime rows", sessions.len());
+    for batch in sessions.chunks(IMPORT_BATCH_SIZE) {
+        match db.import_focus_sessions(RESCUETIME_SOURCE, batch) {
+            Ok(inserted) => {
+                summary.imported += inserted;
+                summary.skipped += batch.len() - inserted;
+            }
+            Err(e) => summary.fail(batch.len(), format!("batch of {} rows: {}", batch.len(), e)),
+        }
+        progress.advance(batch.len());
+    }
+    progress.finish(&format!("Processed {} rows", sessions.len()));
+    Ok(summary)
+}
+
+/// Time of day daily totals are packed from, for messages
+pub fn packed_day_start() -> NaiveTime {
+    NaiveTime::from_hms_opt(PACKED_DAY_START_HOUR, 0, 0).unwrap_or_default()
+}
+
+/// Longest stretch one manual entry may cover
+const MAX_MANUAL_ENTRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Time spent away from the computer, entered with `add-entry` or as a row
+/// of `import manual`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManualEntry {
+    pub app: String,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub duration: Duration,
+    pub focus: bool,
+    pub session: String,
+}
+
+impl ManualEntry {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + chrono::Duration::from_std(self.duration).unwrap_or_default()
+    }
+
+    /// The row it is stored as, and the key that makes entering it again a
+    /// no-op
+    pub fn to_row(&self) -> (String, FocusSession) {
+        let session = FocusSession {
+            start_time: self.start,
+            end_time: Some(self.end()),
+            app_name: self.app.clone(),
+            window_title: self.title.clone(),
+            domain: None,
+            duration: self.duration,
+            is_focus_app: self.focus,
+            session_name: self.session.clone(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: true,
+            focus_rule: None,
+            co_focus: None,
+        };
+        let key = format!("{}|{}|{}", self.start.timestamp(), self.app, self.duration.as_secs());
+        (key, session)
+    }
+
+    /// Why the entry can't be added: it isn't over yet, is empty or longer
+    /// than a day, or overlaps tracked rows (unless `allow_overlap`)
+    pub fn check(&self, db: &Database, now: DateTime<Utc>, allow_overlap: bool) -> Result<(), String> {
+        if self.duration.as_secs() == 0 {
+            return Err("the duration is under a second".to_string());
+        }
+        if self.duration > MAX_MANUAL_ENTRY {
+            return Err("an entry can cover at most 24 hours".to_string());
+        }
+        if self.end() > now {
+            return Err(format!("it would end at {}, which hasn't happened yet", utils::format_datetime_local(self.end())));
+        }
+        if allow_overlap {
+            return Ok(());
+        }
+        // The same entry made earlier is skipped on insert, not refused
+        let overlapping: Vec<FocusSession> = db.get_overlapping_sessions(self.start, self.end())
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|row| !(row.manual && row.start_time == self.start && row.app_name == self.app && row.duration == self.duration))
+            .collect();
+        match overlapping.first() {
+            None => Ok(()),
+            Some(row) => Err(format!("it overlaps {} from {} ({}{}); pass --allow-overlap to add it anyway",
+                desktop::display_name(&row.app_name),
+                utils::format_datetime_local(row.start_time),
+                utils::format_duration(row.duration, DurationStyle::Long),
+                match overlapping.len() {
+                    1 => String::new(),
+                    more => format!(", and {} more row{}", more - 1, if more == 2 { "" } else { "s" }),
+                })),
+        }
+    }
+}
+
+/// "yes", "true", "1" or "focus" count as focus; empty doesn't
+fn parse_focus_flag(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "yes" | "y" | "true" | "1" | "focus" => Some(true),
+        "no" | "n" | "false" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// Rows of a manual entries CSV with the columns app, start, duration,
+/// focus and session, plus an optional title, in any order. Starts are
+/// read like `add-entry --start` relative to `now`; session names are
+/// sanitized and cut to `max_session_name_length`.
+pub fn parse_manual_csv(content: &str, now: DateTime<Utc>, max_session_name_length: usize) -> Result<Vec<Result<ManualEntry, String>>, String> {
+    let _timer = timing::scope(Phase::Parse);
+    let mut records = parse_csv(content.trim_start_matches('\u{feff}')).into_iter();
+    let header: Vec<String> = records.next()
+        .ok_or("the file is empty")?
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let app_col = column("app").ok_or("no app column")?;
+    let start_col = column("start").ok_or("no start column")?;
+    let duration_col = column("duration").ok_or("no duration column")?;
+    let focus_col = column("focus").ok_or("no focus column")?;
+    let session_col = column("session").ok_or("no session column")?;
+    let title_col = column("title");
+
+    let rows = records.enumerate().map(|(index, record)| {
+        let line = index + 2;
+        let field = |col: usize| record.get(col).map(|value| value.trim()).unwrap_or("");
+        let app = field(app_col);
+        if app.is_empty() {
+            return Err(format!("line {}: no app", line));
+        }
+        let start = utils::parse_past_when(field(start_col), now)
+            .ok_or_else(|| format!("line {}: unreadable start '{}'", line, field(start_col)))?;
+        let duration = utils::parse_duration_arg(field(duration_col))
+            .map_err(|e| format!("line {}: {}", line, e))?;
+        let focus = parse_focus_flag(field(focus_col))
+            .ok_or_else(|| format!("line {}: unreadable focus '{}' (use yes or no)", line, field(focus_col)))?;
+        let session = utils::sanitize_session_name(field(session_col), max_session_name_length)
+            .map_err(|e| format!("line {}: {}", line, e))?;
+        Ok(ManualEntry {
+            app: app.to_string(),
+            title: title_col.map(field).unwrap_or("").to_string(),
+            start,
+            duration,
+            focus,
+            session: session.name,
+        })
+    }).collect();
+    Ok(rows)
+}
+
+/// Add manual entries to `db` in one batch. Each is checked like
+/// `add-entry`, and against the entries before it in the file; entries
+/// added by an earlier run are skipped.
+pub fn import_manual(db: &Database, parsed: Vec<Result<ManualEntry, String>>, now: DateTime<Utc>, allow_overlap: bool) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    let mut accepted: Vec<(usize, ManualEntry)> = Vec::new();
+    for (index, entry) in parsed.into_iter().enumerate() {
+        let line = index + 2;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(reason) => {
+                summary.fail(1, reason);
+                continue;
+            }
+        };
+        if let Err(reason) = entry.check(db, now, allow_overlap) {
+            summary.fail(1, format!("line {}: {}", line, reason));
+            continue;
+        }
+        let clash = accepted.iter()
+            .find(|(_, earlier)| earlier.start < entry.end() && entry.start < earlier.end());
+        if let (Some((earlier_line, _)), false) = (clash, allow_overlap) {
+            summary.fail(1, format!("line {}: it overlaps line {}; pass --allow-overlap to add both", line, earlier_line));
+            continue;
+        }
+        accepted.push((line, entry));
+    }
+    let rows: Vec<(String, FocusSession)> = accepted.iter().map(|(_, entry)| entry.to_row()).collect();
+    for batch in rows.chunks(IMPORT_BATCH_SIZE) {
+        match db.import_focus_sessions(MANUAL_SOURCE, batch) {
+            Ok(inserted) => {
+                summary.imported += inserted;
+                summary.skipped += batch.len() - inserted;
+            }
+            Err(e) => summary.fail(batch.len(), format!("batch of {} rows: {}", batch.len(), e)),
+        }
+    }
+    summary
+}
+
+pub const LEGACY_LOG_SOURCE: &str = "legacy_log";
+
+/// What a line of a daemon log written before the log moved out of /tmp
+/// says about windows. Other lines (saves, stats, the other threads'
+/// output interleaved with the tracking thread's) say nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LegacyLogEvent {
+    /// One detection ran: "RAW DETECTION: <app> - <title>"
+    Check,
+    /// "Could not get active window (consecutive failures: <n>)"; only some
+    /// failures were logged, the count says how many there were
+    Failure(u32),
+    /// "Window changed to: <app> - <title>"
+    WindowChanged { app_name: String, window_title: String },
+    /// "Tracking thread started": a daemon run began
+    Started,
+    /// "Received stop signal" or "Tracking thread exiting"
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyLogLine {
+    pub line: usize,
+    /// Only lines written once the log had timestamps have one
+    pub timestamp: Option<DateTime<Utc>>,
+    pub event: LegacyLogEvent,
+}
+
+/// Split "<app> - <title>" at the first separator; a title may hold more
+fn split_window(text: &str) -> (String, String) {
+    let (app_name, window_title) = text.split_once(" - ").unwrap_or((text, ""));
+    (app_name.trim().to_string(), window_title.trim().to_string())
+}
+
+/// The lines of an old daemon log that tell where the windows were, in file
+/// order. Lines with a timestamp (the later log format) keep it.
+pub fn parse_legacy_log(content: &str) -> Vec<LegacyLogLine> {
+    let _timer = timing::scope(Phase::Parse);
+    content.lines().enumerate().filter_map(|(index, line)| {
+        let parsed = logs::LogLine::parse(line.trim_end());
+        let message = parsed.message.trim_start_matches("~=~ ").trim_start_matches("❌ ").trim();
+        let event = if message.starts_with("RAW DETECTION:") {
+            LegacyLogEvent::Check
+        } else if let Some(window) = message.strip_prefix("Window changed to:") {
+            // The later format may end in a note about held-back lines
+            let window = match window.rfind(" (") {
+                Some(at) if window.ends_with(" more not logged)") => &window[..at],
+                _ => window,
+            };
+            let (app_name, window_title) = split_window(window);
+            if app_name.is_empty() {
+                return None;
+            }
+            LegacyLogEvent::WindowChanged { app_name, window_title }
+        } else if let Some(count) = message.strip_prefix("Could not get active window (consecutive failures: ") {
+            LegacyLogEvent::Failure(count.trim_end_matches(')').trim().parse().ok()?)
+        } else if message == "Tracking thread started" {
+            LegacyLogEvent::Started
+        } else if message == "Received stop signal" || message == "Tracking thread exiting" {
+            LegacyLogEvent::Stopped
+        } else {
+            return None;
+        };
+        Some(LegacyLogLine { line: index + 1, timestamp: parsed.timestamp, event })
+    }).collect()
+}
+
+/// How many detections each line stands for: one per check, and for a
+/// failure the ones since the previous logged failure of the same streak
+fn legacy_checks(lines: &[LegacyLogLine]) -> Vec<u32> {
+    let mut streak = 0;
+    lines.iter().map(|line| match line.event {
+        LegacyLogEvent::Check => {
+            streak = 0;
+            1
+        }
+        LegacyLogEvent::Failure(count) => {
+            let checks = if count > streak { count - streak } else { count.max(1) };
+            streak = count;
+            checks
+        }
+        LegacyLogEvent::Started => {
+            streak = 0;
+            0
+        }
+        _ => 0,
+    }).collect()
+}
+
+/// When each line was written and whether that is known rather than
+/// estimated. A line's own timestamp is exact; the bare lines before a
+/// timestamped one (or before `end`, the file's modification time, at the
+/// end of the file) are counted back one `interval` per detection.
+pub fn estimate_legacy_times(lines: &[LegacyLogLine], end: DateTime<Utc>, interval: Duration) -> Vec<(DateTime<Utc>, bool)> {
+    let checks = legacy_checks(lines);
+    let step = chrono::Duration::from_std(interval).unwrap_or_default();
+    let mut clock = end;
+    let mut times = vec![(end, false); lines.len()];
+    for (index, line) in lines.iter().enumerate().rev() {
+        match line.timestamp {
+            Some(timestamp) => {
+                clock = timestamp;
+                times[index] = (timestamp, true);
+            }
+            None => times[index] = (clock, false),
+        }
+        clock -= step * checks[index] as i32;
+    }
+    times
+}
+
+/// One window stretch rebuilt from an old log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyRow {
+    /// The "Window changed to" line it starts at
+    pub line: usize,
+    pub app_name: String,
+    pub window_title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Whether either end is an estimate
+    pub estimated: bool,
+}
+
+/// The window stretches the log shows: each window change starts one,
+/// which lasts until the next change or the run's last detection. A run
+/// that stopped or restarted closes the open stretch; empty ones are dropped.
+pub fn rebuild_legacy_rows(lines: &[LegacyLogLine], times: &[(DateTime<Utc>, bool)]) -> Vec<LegacyRow> {
+    let mut rows = Vec::new();
+    let mut open: Option<LegacyRow> = None;
+    // The last detection seen, where an open stretch ends at the latest
+    let mut last_seen: Option<(DateTime<Utc>, bool)> = None;
+    let close = |open: &mut Option<LegacyRow>, rows: &mut Vec<LegacyRow>, (end, exact): (DateTime<Utc>, bool)| {
+        if let Some(mut row) = open.take() {
+            if end > row.start {
+                row.end = end;
+                row.estimated |= !exact;
+                rows.push(row);
+            }
+        }
+    };
+    for (line, time) in lines.iter().zip(times) {
+        match &line.event {
+            LegacyLogEvent::Check | LegacyLogEvent::Failure(_) => last_seen = Some(*time),
+            LegacyLogEvent::WindowChanged { app_name, window_title } => {
+                close(&mut open, &mut rows, *time);
+                open = Some(LegacyRow {
+                    line: line.line,
+                    app_name: app_name.clone(),
+                    window_title: window_title.clone(),
+                    start: time.0,
+                    end: time.0,
+                    estimated: !time.1,
+                });
+                last_seen = Some(*time);
+            }
+            LegacyLogEvent::Started | LegacyLogEvent::Stopped => {
+                if let Some(seen) = last_seen.take() {
+                    close(&mut open, &mut rows, seen);
+                }
+                open = None;
+            }
+        }
+    }
+    if let Some(seen) = last_seen {
+        close(&mut open, &mut rows, seen);
+    }
+    rows
+}
+
+/// What `import legacy-log` recovered
+#[derive(Debug, Default)]
+pub struct LegacyLogImport {
+    pub summary: ImportSummary,
+    /// Time in the imported rows
+    pub recovered: Duration,
+    pub days: usize,
+    /// Daemon runs the log holds
+    pub runs: usize,
+    /// Imported rows with an estimated start or end
+    pub estimated: usize,
+}
+
+/// Import the windows an old daemon log recorded. `modified` is the log's
+/// modification time, when its last bare line was written, and `interval`
+/// the tracking interval the daemon checked at. Rows go into
+/// `legacy-log-YYYY-MM-DD` sessions and are classified by `config`'s focus
+/// rules as they are now; ignored ones are skipped.
+pub fn import_legacy_log(db: &Database, config: &Config, content: &str, modified: DateTime<Utc>, interval: Duration) -> LegacyLogImport {
+    let lines = parse_legacy_log(content);
+    let times = estimate_legacy_times(&lines, modified, interval);
+    let rows = rebuild_legacy_rows(&lines, &times);
+    let mut import = LegacyLogImport {
+        runs: lines.iter().filter(|line| line.event == LegacyLogEvent::Started).count(),
+        ..Default::default()
+    };
+
+    let mut sessions = Vec::new();
+    let mut days = std::collections::BTreeSet::new();
+    for row in &rows {
+        let duration = (row.end - row.start).to_std().unwrap_or_default();
+        let day = utils::timezone::to_zone(row.start).date_naive();
+        let mut session = FocusSession {
+            start_time: row.start,
+            end_time: Some(row.end),
+            app_name: row.app_name.clone(),
+            window_title: row.window_title.clone(),
+            domain: None,
+            duration,
+            is_focus_app: false,
+            session_name: format!("legacy-log-{}", day.format("%Y-%m-%d")),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        };
+        let (class, rule) = config.classify_session(&session);
+        if class == SessionClass::Ignored {
+            import.summary.skipped += 1;
+            continue;
+        }
+        session.is_focus_app = class == SessionClass::Focus;
+        session.focus_rule = Some(rule);
+        // The same file gives the same keys wherever it is and whatever its mtime
+        let key = format!("{}|{}|{}|{}", content.len(), row.line, row.app_name, row.window_title);
+        sessions.push((key, session, day, row.estimated));
+    }
+
+    let mut progress = Progress::new("Importing legacy log rows", sessions.len());
+    for batch in sessions.chunks(IMPORT_BATCH_SIZE) {
+        let rows: Vec<(String, FocusSession)> = batch.iter().map(|(key, session, _, _)| (key.clone(), session.clone())).collect();
+        match db.import_focus_sessions_each(LEGACY_LOG_SOURCE, &rows) {
+            Ok(inserted) => {
+                for ((_, session, day, estimated), new) in batch.iter().zip(inserted) {
+                    if !new {
+                        import.summary.skipped += 1;
+                        continue;
+                    }
+                    import.summary.imported += 1;
+                    import.recovered += session.duration;
+                    import.estimated += *estimated as usize;
+                    days.insert(*day);
+                }
+            }
+            Err(e) => import.summary.fail(batch.len(), format!("batch of {} rows: {}", batch.len(), e)),
+        }
+        progress.advance(batch.len());
+    }
+    progress.finish(&format!("Processed {} rows", sessions.len()));
+    import.days = days.len();
+    import
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn entry(app: &str, start: &str, minutes: u64) -> ManualEntry {
+        ManualEntry {
+            app: app.to_string(),
+            title: String::new(),
+            start: at(start),
+            duration: Duration::from_secs(minutes * 60),
+            focus: true,
+            session: "deep work".to_string(),
+        }
+    }
+
+    /// A database with fd-editor tracked from 09:00 to 10:00
+    fn tracked_morning() -> Database {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (_, mut row) = entry("fd-editor", "2024-05-21T09:00:00Z", 60).to_row();
+        row.manual = false;
+        row.session_name = "test".to_string();
+        db.save_focus_session(&row).unwrap();
+        db
+    }
+
+    #[test]
+    fn entries_must_be_over_and_not_cover_tracked_time() {
+        let db = tracked_morning();
+        let now = at("2024-05-21T18:00:00Z");
+        assert_eq!(entry("reading", "2024-05-21T14:00:00Z", 45).check(&db, now, false), Ok(()));
+        // Touching the tracked row is fine
+        assert_eq!(entry("reading", "2024-05-21T08:00:00Z", 60).check(&db, now, false), Ok(()));
+        assert_eq!(entry("reading", "2024-05-21T10:00:00Z", 30).check(&db, now, false), Ok(()));
+
+        let overlap = entry("reading", "2024-05-21T09:30:00Z", 60).check(&db, now, false).unwrap_err();
+        assert!(overlap.contains("overlaps fd-editor") && overlap.contains("--allow-overlap"), "{}", overlap);
+        assert_eq!(entry("reading", "2024-05-21T09:30:00Z", 60).check(&db, now, true), Ok(()));
+
+        assert!(entry("reading", "2024-05-21T14:00:00Z", 0).check(&db, now, true).unwrap_err().contains("under a second"));
+        assert!(entry("reading", "2024-05-19T14:00:00Z", 24 * 60 + 1).check(&db, now, true).unwrap_err().contains("24 hours"));
+        assert!(entry("reading", "2024-05-21T17:30:00Z", 45).check(&db, now, true).unwrap_err().contains("hasn't happened yet"));
+    }
+
+    #[test]
+    fn entering_the_same_entry_again_is_skipped_not_refused() {
+        let db = tracked_morning();
+        let now = at("2024-05-21T18:00:00Z");
+        let reading = entry("reading", "2024-05-21T14:00:00Z", 45);
+        let parsed = || vec![Ok(reading.clone())];
+        let first = import_manual(&db, parsed(), now, false);
+        assert_eq!((first.imported, first.skipped, first.failed), (1, 0, 0));
+        assert_eq!(reading.check(&db, now, false), Ok(()));
+        let again = import_manual(&db, parsed(), now, false);
+        assert_eq!((again.imported, again.skipped, again.failed), (0, 1, 0));
+
+        let rows = db.get_overlapping_sessions(at("2024-05-21T00:00:00Z"), now).unwrap();
+        let kinds: Vec<(&str, bool)> = rows.iter().map(|row| (row.app_name.as_str(), row.manual)).collect();
+        assert_eq!(kinds, [("fd-editor", false), ("reading", true)]);
+        // A different entry over it is refused
+        assert!(entry("whiteboard", "2024-05-21T14:30:00Z", 30).check(&db, now, false).is_err());
+    }
+
+    #[test]
+    fn manual_csv_columns_come_in_any_order() {
+        let now = at("2024-05-21T18:00:00Z");
+        let csv = "\u{feff}Session,App,Duration,Focus,Start,Title\n\
+                   deep work,reading,45m,yes,2024-05-21T14:00:00Z,a paper\n\
+                   planning,whiteboard,1h,no,2024-05-21T10:30:00Z,\n\
+                   ,reading,1h,maybe,2024-05-21T12:00:00Z,\n\
+                   x,,1h,yes,2024-05-21T12:00:00Z,\n\
+                   x,reading,soon,yes,2024-05-21T12:00:00Z,\n\
+                   x,reading,1h,yes,someday,\n";
+        let rows = parse_manual_csv(csv, now, 64).unwrap();
+        assert_eq!(rows[0], Ok(ManualEntry { title: "a paper".to_string(), ..entry("reading", "2024-05-21T14:00:00Z", 45) }));
+        assert_eq!(rows[1].as_ref().map(|entry| (entry.focus, entry.session.as_str())), Ok((false, "planning")));
+        assert!(rows[2].as_ref().unwrap_err().starts_with("line 4: unreadable focus 'maybe'"));
+        assert_eq!(rows[3], Err("line 5: no app".to_string()));
+        assert!(rows[4].as_ref().unwrap_err().starts_with("line 6:"));
+        assert_eq!(rows[5], Err("line 7: unreadable start 'someday'".to_string()));
+
+        assert_eq!(parse_manual_csv("app,start,duration,focus\n", now, 64), Err("no session column".to_string()));
+        assert_eq!(parse_manual_csv("", now, 64), Err("the file is empty".to_string()));
+    }
+
+    #[test]
+    fn imports_refuse_entries_overlapping_earlier_lines() {
+        let db = tracked_morning();
+        let now = at("2024-05-21T18:00:00Z");
+        let parsed = vec![
+            Ok(entry("reading", "2024-05-21T14:00:00Z", 60)),
+            Ok(entry("whiteboard", "2024-05-21T14:30:00Z", 60)),
+            Err("line 4: no app".to_string()),
+            Ok(entry("reading", "2024-05-21T09:15:00Z", 15)),
+            Ok(entry("walk", "2024-05-21T15:00:00Z", 20)),
+        ];
+        let summary = import_manual(&db, parsed.clone(), now, false);
+        assert_eq!((summary.imported, summary.skipped, summary.failed), (2, 0, 3));
+        assert_eq!(summary.failures[0], "line 3: it overlaps line 2; pass --allow-overlap to add both");
+        assert!(summary.failures[2].starts_with("line 5: it overlaps fd-editor"), "{:?}", summary.failures);
+
+        let db = tracked_morning();
+        let summary = import_manual(&db, parsed, now, true);
+        assert_eq!((summary.imported, summary.failed), (4, 1));
+    }
+
+    fn legacy_fixture(name: &str) -> String {
+        std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/legacy_log").join(name)).unwrap()
+    }
+
+    /// The rows rebuilt from `content` at one check a second, without their line numbers
+    fn legacy_rows(content: &str, end: DateTime<Utc>) -> Vec<LegacyRow> {
+        let lines = parse_legacy_log(content);
+        let times = estimate_legacy_times(&lines, end, Duration::from_secs(1));
+        rebuild_legacy_rows(&lines, &times).into_iter().map(|row| LegacyRow { line: 0, ..row }).collect()
+    }
+
+    fn legacy_row(app: &str, title: &str, start: DateTime<Utc>, end: DateTime<Utc>, estimated: bool) -> LegacyRow {
+        LegacyRow { line: 0, app_name: app.to_string(), window_title: title.to_string(), start, end, estimated }
+    }
+
+    #[test]
+    fn bare_legacy_lines_skip_the_other_threads_output() {
+        let lines = parse_legacy_log(&legacy_fixture("bare.log"));
+        let changed = |app: &str, title: &str| LegacyLogEvent::WindowChanged { app_name: app.to_string(), window_title: title.to_string() };
+        let events: Vec<(usize, LegacyLogEvent)> = lines.iter().map(|line| (line.line, line.event.clone())).collect();
+        assert_eq!(events, [
+            (6, LegacyLogEvent::Started),
+            (9, LegacyLogEvent::Check),
+            (10, changed("code", "main.rs - focusdebt - Visual Studio Code")),
+            (13, LegacyLogEvent::Check),
+            (15, LegacyLogEvent::Check),
+            (17, LegacyLogEvent::Check),
+            (18, changed("firefox", "std::time - Rust — Mozilla Firefox")),
+            (23, LegacyLogEvent::Check),
+            (25, LegacyLogEvent::Failure(1)),
+            (27, LegacyLogEvent::Failure(2)),
+            (29, LegacyLogEvent::Check),
+            (31, LegacyLogEvent::Stopped),
+            (32, LegacyLogEvent::Stopped),
+        ]);
+        assert!(lines.iter().all(|line| line.timestamp.is_none()));
+    }
+
+    #[test]
+    fn bare_legacy_lines_are_counted_back_from_the_modification_time() {
+        let end = at("2024-05-21T10:00:00Z");
+        let seconds = |n: i64| end - chrono::Duration::seconds(n);
+        // As the daemon's own "Completed session: code (3s)" and "Saved session: firefox (4s)" have it
+        assert_eq!(legacy_rows(&legacy_fixture("bare.log"), end), [
+            legacy_row("code", "main.rs - focusdebt - Visual Studio Code", seconds(7), seconds(4), true),
+            legacy_row("firefox", "std::time - Rust — Mozilla Firefox", seconds(4), end, true),
+        ]);
+    }
+
+    #[test]
+    fn timestamped_legacy_lines_are_placed_exactly() {
+        let content = legacy_fixture("timestamped.log");
+        assert!(parse_legacy_log(&content).iter().all(|line| line.timestamp.is_some()));
+        // The modification time plays no part
+        assert_eq!(legacy_rows(&content, at("2030-01-01T00:00:00Z")), [
+            legacy_row("kitty", "cargo test", at("2024-05-21T09:00:01Z"), at("2024-05-21T09:05:00Z"), false),
+            legacy_row("slack", "#general - Acme", at("2024-05-21T09:05:00Z"), at("2024-05-21T09:07:30Z"), false),
+        ]);
+    }
+
+    #[test]
+    fn bare_lines_before_a_timestamped_one_count_back_from_it() {
+        let content = "\
+~=~ Tracking thread started
+~=~ Window changed to: kitty - htop
+~=~ RAW DETECTION: kitty - htop
+~=~ RAW DETECTION: kitty - htop
+2024-05-21T09:00:10Z INFO ~=~ Window changed to: code - lib.rs
+2024-05-21T09:01:00Z INFO ~=~ RAW DETECTION: code - lib.rs
+";
+        assert_eq!(legacy_rows(content, at("2030-01-01T00:00:00Z")), [
+            legacy_row("kitty", "htop", at("2024-05-21T09:00:08Z"), at("2024-05-21T09:00:10Z"), true),
+            legacy_row("code", "lib.rs", at("2024-05-21T09:00:10Z"), at("2024-05-21T09:01:00Z"), false),
+        ]);
+    }
+
+    #[test]
+    fn sparse_failure_lines_stand_for_every_failed_check() {
+        // Failures were logged up to 10 and then every 50th
+        let content = "\
+~=~ Tracking thread started
+~=~ Window changed to: code - lib.rs
+❌ Could not get active window (consecutive failures: 9)
+❌ Could not get active window (consecutive failures: 10)
+❌ Could not get active window (consecutive failures: 50)
+~=~ Tracking thread exiting
+~=~ Tracking thread started
+~=~ Window changed to: kitty - htop
+~=~ RAW DETECTION: kitty - htop
+";
+        let end = at("2024-05-21T10:00:00Z");
+        let seconds = |n: i64| end - chrono::Duration::seconds(n);
+        // The new run starts afresh; the first run's window lasts until its last failure
+        assert_eq!(legacy_rows(content, end), [
+            legacy_row("code", "lib.rs", seconds(1 + 50), seconds(1), true),
+            legacy_row("kitty", "htop", seconds(1), end, true),
+        ]);
+        assert_eq!(legacy_checks(&parse_legacy_log(content)), [0, 0, 9, 1, 40, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn legacy_imports_classify_and_skip_what_was_imported() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let config = Config { focus_apps: vec!["code".to_string()], ..Config::default() };
+        let content = legacy_fixture("bare.log");
+        let end = at("2024-05-21T10:00:00Z");
+
+        let import = import_legacy_log(&db, &config, &content, end, Duration::from_secs(1));
+        assert_eq!((import.summary.imported, import.summary.skipped), (2, 0));
+        assert_eq!((import.recovered, import.days, import.runs, import.estimated), (Duration::from_secs(7), 1, 1, 2));
+
+        let rows = db.get_sessions_between(end - chrono::Duration::hours(1), end).unwrap();
+        let session = format!("legacy-log-{}", utils::timezone::to_zone(end).date_naive().format("%Y-%m-%d"));
+        let rows: Vec<(&str, bool, &str)> = rows.iter()
+            .map(|row| (row.app_name.as_str(), row.is_focus_app, row.session_name.as_str()))
+            .collect();
+        assert_eq!(rows, [("code", true, session.as_str()), ("firefox", false, session.as_str())]);
+
+        // The same file again, even with another modification time, adds nothing
+        let again = import_legacy_log(&db, &config, &content, end + chrono::Duration::days(3), Duration::from_secs(1));
+        assert_eq!((again.summary.imported, again.summary.skipped, again.recovered), (0, 2, Duration::ZERO));
+        assert_eq!(db.get_sessions_between(end - chrono::Duration::days(1), end + chrono::Duration::days(4)).unwrap().len(), 2);
+    }
+}
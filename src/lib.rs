@@ -0,0 +1,47 @@
+//! focusdebt as a library: the tracker, storage and reports behind the
+//! CLI, for embedding in another app. `api` is the only public module;
+//! the rest are the CLI's internals and stay private to the crate.
+
+pub(crate) mod activity;
+pub mod api;
+pub(crate) mod cli;
+pub(crate) mod desktop;
+pub(crate) mod tracking;
+pub(crate) mod storage;
+pub(crate) mod stats;
+pub(crate) mod utils;
+pub(crate) mod config;
+pub(crate) mod alerts;
+pub(crate) mod hooks;
+pub(crate) mod progress;
+pub(crate) mod import;
+pub(crate) mod digest;
+pub(crate) mod spill;
+pub(crate) mod logs;
+pub(crate) mod team;
+pub(crate) mod completions;
+pub(crate) mod audit;
+pub(crate) mod sounds;
+pub(crate) mod wrapped;
+pub(crate) mod browse;
+pub(crate) mod service;
+pub(crate) mod power;
+pub(crate) mod estimate;
+pub(crate) mod usage;
+pub(crate) mod reset;
+pub(crate) mod tmux;
+pub(crate) mod glyphs;
+pub(crate) mod disk;
+pub(crate) mod demo;
+pub(crate) mod timing;
+pub(crate) mod notifications;
+pub(crate) mod conflicts;
+pub(crate) mod tasks;
+pub(crate) mod operation;
+pub(crate) mod chunks;
+
+/// The focusdebt binary's entry point; not part of the embedding API
+#[doc(hidden)]
+pub fn run_cli() -> std::process::ExitCode {
+    cli::main()
+}
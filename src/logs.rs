@@ -0,0 +1,362 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Lines `focusdebt logs` shows without --tail
+pub const DEFAULT_TAIL_LINES: usize = 100;
+
+/// How often --follow looks for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the forwarders get to write out what is left when output stops
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the daemon (and `track`) write their output: focusdebt.log in the
+/// data directory, with the profile suffix like the other runtime files
+pub fn daemon_log_path() -> Option<PathBuf> {
+    crate::utils::runtime_file("log")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "info" => Some(LogLevel::Info),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    /// The level of a message printed to stdout or stderr: "❌" lines and
+    /// panics are errors, anything else on stderr (hooks, tools) a warning
+    pub fn of_output(message: &str, stderr: bool) -> Self {
+        if message.starts_with('❌') || message.contains("panicked at") {
+            LogLevel::Error
+        } else if stderr {
+            LogLevel::Warn
+        } else {
+            LogLevel::Info
+        }
+    }
+}
+
+/// One line of the daemon log: `<RFC 3339 UTC timestamp> <LEVEL> <message>`,
+/// e.g. `2024-05-21T09:14:03.120Z INFO ~=~ Started new session: code`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    /// None for lines written before the log had timestamps
+    pub timestamp: Option<DateTime<Utc>>,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn now(level: LogLevel, message: &str) -> Self {
+        LogLine { timestamp: Some(Utc::now()), level, message: message.to_string() }
+    }
+
+    /// Read a log line back. Lines in the older, bare format are kept with
+    /// no timestamp and a level guessed from the message.
+    pub fn parse(line: &str) -> Self {
+        let structured = line.split_once(' ').and_then(|(timestamp, rest)| {
+            let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?.with_timezone(&Utc);
+            let (level, message) = rest.split_once(' ').unwrap_or((rest, ""));
+            let level = LogLevel::parse(level)?;
+            Some(LogLine { timestamp: Some(timestamp), level, message: message.to_string() })
+        });
+        structured.unwrap_or_else(|| LogLine {
+            timestamp: None,
+            level: LogLevel::of_output(line, false),
+            message: line.to_string(),
+        })
+    }
+
+    /// Whether `focusdebt logs` shows this line for --since and --level
+    pub fn matches(&self, since: Option<DateTime<Utc>>, level: Option<LogLevel>) -> bool {
+        since.is_none_or(|since| self.timestamp.is_some_and(|timestamp| timestamp >= since))
+            && level.is_none_or(|level| self.level >= level)
+    }
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.timestamp {
+            Some(timestamp) => write!(f, "{} {} {}", timestamp.to_rfc3339_opts(SecondsFormat::Millis, true), self.level.as_str(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// The last `tail` lines of the log at `path` that match the filters
+pub fn read_tail(path: &Path, tail: usize, since: Option<DateTime<Utc>>, level: Option<LogLevel>) -> io::Result<Vec<LogLine>> {
+    let file = fs::File::open(path)?;
+    let mut lines: Vec<LogLine> = BufReader::new(file).lines()
+        .map_while(Result::ok)
+        .map(|line| LogLine::parse(&line))
+        .filter(|line| !line.message.is_empty() && line.matches(since, level))
+        .collect();
+    let skip = lines.len().saturating_sub(tail);
+    Ok(lines.split_off(skip))
+}
+
+/// Print lines as they are appended to the log at `path`, until the
+/// process is interrupted. Starts from the current end; a log cleared in
+/// the meantime is read again from the top.
+pub fn follow(path: &Path, since: Option<DateTime<Utc>>, level: Option<LogLevel>) -> io::Result<()> {
+    let mut position = fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+    let mut pending = String::new();
+    loop {
+        thread::sleep(FOLLOW_INTERVAL);
+        let length = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if length < position {
+            position = 0;
+            pending.clear();
+        }
+        if length == position {
+            continue;
+        }
+
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(position))?;
+        let mut chunk = Vec::new();
+        file.take(length - position).read_to_end(&mut chunk)?;
+        position = length;
+        pending.push_str(&String::from_utf8_lossy(&chunk));
+
+        // A line still being written waits for its newline
+        while let Some(end) = pending.find('\n') {
+            let line: String = pending.drain(..=end).collect();
+            let line = LogLine::parse(line.trim_end_matches('\n'));
+            if !line.message.is_empty() && line.matches(since, level) {
+                println!("{}", line);
+            }
+        }
+        io::stdout().flush()?;
+    }
+}
+
+/// Empty the log at `path` in place. The daemon keeps appending to the same
+/// file, so it isn't removed and recreated.
+pub fn clear(path: &Path) -> io::Result<()> {
+    match OpenOptions::new().write(true).open(path) {
+        Ok(file) => file.set_len(0),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// stdout (and optionally stderr) sent through a pipe into the daemon log,
+/// each line written with a timestamp and level
+#[cfg(unix)]
+pub struct Forwarding {
+    /// The original descriptors to put back; None leaves /dev/null there
+    saved: Vec<(libc::c_int, Option<libc::c_int>)>,
+    done: mpsc::Receiver<()>,
+    forwarders: usize,
+}
+
+#[cfg(unix)]
+impl Forwarding {
+    /// Point stdout, and stderr with `stderr`, at the log at `path`.
+    /// `keep_original` saves the current descriptors for `finish`; the
+    /// daemon lets go of its terminal instead.
+    pub fn start(path: &Path, stderr: bool, keep_original: bool) -> io::Result<Self> {
+        let _ = io::stdout().flush();
+        let (done_tx, done) = mpsc::channel();
+        let mut forwarding = Forwarding { saved: Vec::new(), done, forwarders: 0 };
+        let targets: &[libc::c_int] = if stderr { &[1, 2] } else { &[1] };
+        for &fd in targets {
+            let log = crate::utils::private_file_options().append(true).open(path)?;
+            let mut fds = [0; 2];
+            // Safety: plain descriptor calls on descriptors this function owns
+            unsafe {
+                if libc::pipe(fds.as_mut_ptr()) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let saved = if keep_original { Some(libc::dup(fd)).filter(|saved| *saved >= 0) } else { None };
+                libc::dup2(fds[1], fd);
+                libc::close(fds[1]);
+                forwarding.saved.push((fd, saved));
+            }
+            // Safety: the read end was just created and nothing else owns it
+            let reader = unsafe { <fs::File as std::os::fd::FromRawFd>::from_raw_fd(fds[0]) };
+            let done_tx = done_tx.clone();
+            thread::spawn(move || {
+                forward_lines(reader, log, fd == 2);
+                let _ = done_tx.send(());
+            });
+            forwarding.forwarders += 1;
+        }
+        Ok(forwarding)
+    }
+
+    /// Put the original descriptors back (or /dev/null) and wait briefly for
+    /// the forwarders to write out what is left. A hook still running keeps
+    /// its copy of the pipe open, so the wait is bounded.
+    pub fn finish(self) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        // Safety: plain descriptor calls on stdout, stderr and saved copies
+        unsafe {
+            let null = libc::open(c"/dev/null".as_ptr(), libc::O_WRONLY);
+            for (fd, saved) in &self.saved {
+                match saved {
+                    Some(saved) => {
+                        libc::dup2(*saved, *fd);
+                        libc::close(*saved);
+                    }
+                    None if null >= 0 => {
+                        libc::dup2(null, *fd);
+                    }
+                    None => {}
+                }
+            }
+            if null >= 0 {
+                libc::close(null);
+            }
+        }
+        for _ in 0..self.forwarders {
+            if self.done.recv_timeout(DRAIN_TIMEOUT).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn forward_lines(reader: fs::File, mut log: fs::File, stderr: bool) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        let line = LogLine::now(LogLevel::of_output(&line, stderr), &line);
+        // One write per line, so lines from stdout and stderr don't interleave
+        let _ = log.write_all(format!("{}\n", line).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch {
+        path: PathBuf,
+    }
+
+    impl Scratch {
+        fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("focusdebt-logs-{}-{}.log", name, std::process::id()));
+            fs::write(&path, content).unwrap();
+            Self { path }
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc)
+    }
+
+    const SAMPLE: &str = "\
+~=~ Started new session: fd-editor
+2024-05-21T09:14:03.120Z INFO ~=~ Started new session: fd-editor
+2024-05-21T09:20:00.000Z WARN hook exited with status 1
+2024-05-21T09:30:00.000Z ERROR ❌ Failed to save session: disk I/O error
+2024-05-21T09:40:00.000Z INFO
+
+2024-05-21T09:45:00.000Z INFO ~=~ Switch: fd-editor -> fd-chat
+";
+
+    #[test]
+    fn log_lines_parse_and_print_the_same() {
+        let line = LogLine::parse("2024-05-21T09:14:03.120Z INFO ~=~ Started new session: fd-editor");
+        assert_eq!(line.timestamp, Some(at("2024-05-21T09:14:03.120Z")));
+        assert_eq!(line.level, LogLevel::Info);
+        assert_eq!(line.message, "~=~ Started new session: fd-editor");
+        assert_eq!(line.to_string(), "2024-05-21T09:14:03.120Z INFO ~=~ Started new session: fd-editor");
+
+        let written = LogLine::now(LogLevel::Warn, "hook exited with status 1");
+        assert_eq!(LogLine::parse(&written.to_string()).message, written.message);
+        assert_eq!(LogLine::parse(&written.to_string()).level, LogLevel::Warn);
+
+        // Bare lines from before timestamps, and words that aren't a level
+        let bare = LogLine::parse("❌ Failed to save session");
+        assert_eq!((bare.timestamp, bare.level), (None, LogLevel::Error));
+        assert_eq!(bare.to_string(), "❌ Failed to save session");
+        let odd = LogLine::parse("2024-05-21T09:14:03Z DEBUG something");
+        assert_eq!((odd.timestamp, odd.message.as_str()), (None, "2024-05-21T09:14:03Z DEBUG something"));
+
+        assert_eq!(LogLevel::parse(" Warning "), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::parse("trace"), None);
+        assert!(LogLevel::Info < LogLevel::Warn && LogLevel::Warn < LogLevel::Error);
+        assert_eq!(LogLevel::of_output("thread 'main' panicked at src/main.rs", false), LogLevel::Error);
+        assert_eq!(LogLevel::of_output("usage: hook", true), LogLevel::Warn);
+        assert_eq!(LogLevel::of_output("~=~ Tracking", false), LogLevel::Info);
+    }
+
+    #[test]
+    fn tail_filters_by_time_and_level() {
+        let log = Scratch::new("tail", SAMPLE);
+        let messages = |lines: Vec<LogLine>| lines.into_iter().map(|line| line.message).collect::<Vec<_>>();
+
+        // Empty messages are skipped; bare lines have no time but show
+        let all = read_tail(&log.path, DEFAULT_TAIL_LINES, None, None).unwrap();
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[0].timestamp, None);
+        assert_eq!(messages(read_tail(&log.path, 2, None, None).unwrap()),
+            ["❌ Failed to save session: disk I/O error", "~=~ Switch: fd-editor -> fd-chat"]);
+
+        // --since drops lines without a time
+        let since = Some(at("2024-05-21T09:20:00Z"));
+        assert_eq!(read_tail(&log.path, DEFAULT_TAIL_LINES, since, None).unwrap().len(), 3);
+        assert_eq!(messages(read_tail(&log.path, DEFAULT_TAIL_LINES, None, Some(LogLevel::Warn)).unwrap()),
+            ["hook exited with status 1", "❌ Failed to save session: disk I/O error"]);
+        assert_eq!(messages(read_tail(&log.path, DEFAULT_TAIL_LINES, Some(at("2024-05-21T09:25:00Z")), Some(LogLevel::Warn)).unwrap()),
+            ["❌ Failed to save session: disk I/O error"]);
+    }
+
+    #[test]
+    fn clear_truncates_while_the_daemon_keeps_writing() {
+        let log = Scratch::new("clear", SAMPLE);
+        let mut daemon = OpenOptions::new().append(true).open(&log.path).unwrap();
+
+        clear(&log.path).unwrap();
+        assert_eq!(fs::metadata(&log.path).unwrap().len(), 0);
+
+        // The daemon's handle still points at the same file, now from the top
+        writeln!(daemon, "{}", LogLine { timestamp: Some(at("2024-05-21T10:00:00Z")), level: LogLevel::Info, message: "~=~ still here".to_string() }).unwrap();
+        assert_eq!(fs::read_to_string(&log.path).unwrap(), "2024-05-21T10:00:00.000Z INFO ~=~ still here\n");
+        assert_eq!(read_tail(&log.path, DEFAULT_TAIL_LINES, None, None).unwrap().len(), 1);
+
+        // A log that was never written clears without complaint
+        let missing = std::env::temp_dir().join(format!("focusdebt-logs-missing-{}.log", std::process::id()));
+        clear(&missing).unwrap();
+        assert!(!missing.exists());
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use crate::hooks;
+use crate::utils;
+
+/// What `dbus-monitor` is asked to watch: calls that show a notification
+const NOTIFY_MATCH: &str = "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+
+/// Mail clients, counted together as "email"
+const EMAIL_APPS: &[&str] = &["thunderbird", "evolution", "geary", "kmail", "mailspring", "betterbird", "mail", "outlook"];
+
+/// The source a notification from `app_name` is counted under: the app in
+/// lower case, "email" for mail clients, "other" when it gave no name.
+/// None for focusdebt's own alerts, which aren't interruptions from outside.
+pub fn source_of(app_name: &str) -> Option<String> {
+    let app = app_name.trim().to_lowercase();
+    match app.as_str() {
+        "focusdebt" => None,
+        "" => Some("other".to_string()),
+        app if EMAIL_APPS.contains(&app) => Some("email".to_string()),
+        app => Some(app.to_string()),
+    }
+}
+
+/// Picks notifications out of `dbus-monitor` output. A Notify call is a
+/// header line followed by its arguments, one per line; only the first,
+/// the sending app's name, is kept. Summary and body are never looked at.
+#[derive(Debug, Default)]
+pub struct NotifyParser {
+    in_notify: bool,
+}
+
+impl NotifyParser {
+    /// The app of the notification `line` completes, if it does
+    pub fn feed(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        if line.starts_with("method call") || line.starts_with("signal") || line.starts_with("method return") || line.starts_with("error") {
+            self.in_notify = line.starts_with("method call")
+                && line.contains("interface=org.freedesktop.Notifications")
+                && line.contains("member=Notify");
+            return None;
+        }
+        if !self.in_notify {
+            return None;
+        }
+        self.in_notify = false;
+        let app = line.strip_prefix("string \"")?.strip_suffix('"')?;
+        Some(app.to_string())
+    }
+}
+
+/// A notification_command prints one line per notification, the app it
+/// came from; a blank line is one from an unknown app
+pub fn parse_command_line(line: &str) -> String {
+    line.trim().to_string()
+}
+
+/// The command the counter reads notifications from: notification_command
+/// when set, else `dbus-monitor` on the session bus
+fn monitor_command(notification_command: Option<&str>) -> Option<Command> {
+    match notification_command {
+        Some(command) => Some(hooks::shell_command(command)),
+        None if cfg!(target_os = "linux") => {
+            let mut command = Command::new("dbus-monitor");
+            command.args(["--session", NOTIFY_MATCH]);
+            Some(command)
+        }
+        None => None,
+    }
+}
+
+/// Whether notifications can be counted here, for count_notifications
+pub fn check(notification_command: Option<&str>) -> Result<(), String> {
+    match notification_command {
+        Some(command) => hooks::check_command(command),
+        None if cfg!(target_os = "linux") => match utils::find_program("dbus-monitor") {
+            Some(_) => Ok(()),
+            None => Err("dbus-monitor not found in PATH; install dbus or set notification_command".to_string()),
+        },
+        None => Err("no notification monitor on this platform; set notification_command".to_string()),
+    }
+}
+
+/// The running monitor process and the thread reading it
+pub struct Monitor {
+    child: Child,
+    reader: JoinHandle<()>,
+}
+
+impl Monitor {
+    /// Start counting on a thread of its own: `on_notification` gets the
+    /// source of every notification until `stop`
+    pub fn start(notification_command: Option<&str>, mut on_notification: impl FnMut(String) + Send + 'static) -> Result<Self, String> {
+        let mut command = monitor_command(notification_command).ok_or("no notification monitor on this platform")?;
+        // A group of its own, so stopping also reaches what the shell started
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("couldn't start the notification monitor: {}", e))?;
+        let stdout = child.stdout.take().ok_or("the notification monitor has no output")?;
+        let from_command = notification_command.is_some();
+        let reader = thread::spawn(move || {
+            let mut parser = NotifyParser::default();
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                let app = if from_command { Some(parse_command_line(&line)) } else { parser.feed(&line) };
+                if let Some(source) = app.as_deref().and_then(source_of) {
+                    on_notification(source);
+                }
+            }
+            println!("~=~ Notification monitor exited");
+        });
+        Ok(Self { child, reader })
+    }
+
+    /// Kill the monitor process and wait for the thread to read to its end
+    pub fn stop(mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = self.reader.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn sources_group_mail_clients_and_skip_our_own() {
+        assert_eq!(source_of("Slack").as_deref(), Some("slack"));
+        assert_eq!(source_of(" Thunderbird ").as_deref(), Some("email"));
+        assert_eq!(source_of("geary").as_deref(), Some("email"));
+        assert_eq!(source_of("").as_deref(), Some("other"));
+        assert_eq!(source_of("FocusDebt"), None);
+    }
+
+    #[test]
+    fn a_captured_session_bus_counts_notify_calls_only() {
+        let transcript = std::fs::read_to_string(std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/notifications/dbus-monitor.txt")).unwrap();
+        let mut parser = NotifyParser::default();
+        let apps: Vec<String> = transcript.lines().filter_map(|line| parser.feed(line)).collect();
+        assert_eq!(apps, ["Slack", "Thunderbird", "focusdebt", "", "Slack"]);
+        let sources: Vec<String> = apps.iter().filter_map(|app| source_of(app)).collect();
+        assert_eq!(sources, ["slack", "email", "other", "slack"]);
+    }
+
+    #[test]
+    fn only_the_first_argument_of_a_notify_call_is_read() {
+        let mut parser = NotifyParser::default();
+        assert_eq!(parser.feed("method call time=1 sender=:1.5 -> destination=:1.2 serial=3 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify"), None);
+        // A call whose first line isn't the app name counts nothing
+        assert_eq!(parser.feed("   uint32 0"), None);
+        assert_eq!(parser.feed("   string \"Slack\""), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_notification_command_is_read_on_its_own_thread() {
+        let (sender, received) = mpsc::channel();
+        let monitor = Monitor::start(Some("printf 'Slack\\n\\nfocusdebt\\nThunderbird\\n'"), move |source| {
+            let _ = sender.send(source);
+        }).unwrap();
+        let sources: Vec<String> = (0..3).map(|_| received.recv_timeout(Duration::from_secs(10)).unwrap()).collect();
+        monitor.stop();
+        assert_eq!(sources, ["slack", "other", "email"]);
+        assert!(received.try_recv().is_err());
+    }
+}
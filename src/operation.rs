@@ -0,0 +1,338 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::Database;
+use crate::utils;
+
+/// meta key the running operation is recorded under, as JSON
+const META_KEY: &str = "operation_lock";
+
+/// Runtime file the lock itself is taken on
+const LOCK_EXTENSION: &str = "oplock";
+
+/// A long operation holding the database, as recorded for other commands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    /// e.g. "an import"
+    pub description: String,
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+impl Operation {
+    /// e.g. "an import started at 14:02 (PID 1234)"
+    pub fn describe(&self) -> String {
+        let started = if utils::timezone::to_zone(self.started_at).date_naive() == utils::timezone::to_zone(Utc::now()).date_naive() {
+            utils::format_timestamp_local(self.started_at)
+        } else {
+            utils::format_datetime_local(self.started_at)
+        };
+        format!("{} started at {} (PID {})", self.description, started, self.pid)
+    }
+}
+
+/// What the lock file and the recorded operation say together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockState {
+    Free,
+    /// Taken; the operation is None while its holder is still recording it
+    Held(Option<Operation>),
+    /// Recorded by an operation that died without clearing it
+    Stale(Operation),
+}
+
+/// The lock's state from whether the lock file could be locked and the
+/// operation recorded in the database. A record whose process is gone is
+/// stale even while the file is locked: that lock belongs to a new holder
+/// that hasn't replaced the record yet.
+pub fn classify(lock_free: bool, recorded: Option<Operation>, alive: impl Fn(u32) -> bool) -> LockState {
+    match (lock_free, recorded) {
+        (true, None) => LockState::Free,
+        (true, Some(operation)) => LockState::Stale(operation),
+        (false, Some(operation)) if alive(operation.pid) => LockState::Held(Some(operation)),
+        (false, _) => LockState::Held(None),
+    }
+}
+
+/// Why an operation can't start, or a command has to wait
+#[derive(Debug)]
+pub enum LockError {
+    Busy(Option<Operation>),
+    Io(String),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Busy(Some(operation)) => write!(f, "{} is in progress", operation.describe()),
+            LockError::Busy(None) => write!(f, "another long operation is in progress"),
+            LockError::Io(e) => write!(f, "couldn't take the operation lock: {}", e),
+        }
+    }
+}
+
+fn open_lock_file() -> io::Result<File> {
+    utils::ensure_data_directory()?;
+    let path = utils::runtime_file(LOCK_EXTENSION)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not find data directory"))?;
+    utils::private_file_options().read(true).write(true).open(path)
+}
+
+/// Take `file`'s lock without waiting; false when another process has it.
+/// Closing the file releases it, also when the process dies.
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    // Safety: the descriptor stays open for the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+/// Without flock only the recorded PID tells whether the holder is alive
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
+
+fn recorded(db: &Database) -> Option<Operation> {
+    db.get_meta(META_KEY).ok().flatten().and_then(|value| serde_json::from_str(&value).ok())
+}
+
+/// The copy of the record in the lock file, for when the database is too
+/// busy with the operation's writes to be read
+fn recorded_in_file(mut file: &File) -> Option<Operation> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Replace the lock file's copy of the record; None empties it
+fn record_in_file(mut file: &File, operation: Option<&Operation>) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    if let Some(operation) = operation {
+        file.write_all(serde_json::to_string(operation)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// The lock's state, trying the lock on `file` (and keeping it when free)
+/// before reading the operation `db` records. A database busy with the
+/// operation's writes can't hide that the lock is taken.
+fn current_state(file: &File, db: impl FnOnce() -> Option<Database>) -> io::Result<LockState> {
+    let lock_free = try_lock(file)?;
+    let recorded = db().as_ref().and_then(recorded).or_else(|| recorded_in_file(file));
+    #[cfg(not(unix))]
+    let lock_free = lock_free && recorded.as_ref().is_none_or(|operation| !utils::check_process_exists(operation.pid));
+    Ok(classify(lock_free, recorded, utils::check_process_exists))
+}
+
+/// Clear what an operation that died left recorded, saying so; `file`'s
+/// lock is held
+fn clear_stale(file: &File, operation: &Operation) {
+    let _ = record_in_file(file, None);
+    if Database::new().and_then(|db| db.delete_meta(META_KEY)).is_ok() {
+        println!("~=~ Cleared the lock of {}, which is no longer running", operation.describe());
+    }
+}
+
+/// Fail when a long operation is running. One that died is cleaned up.
+pub fn check() -> Result<(), LockError> {
+    // Without a lock file no operation ever ran here
+    if !utils::runtime_file(LOCK_EXTENSION).is_some_and(|path| path.exists()) {
+        return Ok(());
+    }
+    let file = open_lock_file().map_err(|e| LockError::Io(e.to_string()))?;
+    match current_state(&file, || Database::new_read_only().ok()).map_err(|e| LockError::Io(e.to_string()))? {
+        LockState::Free => Ok(()),
+        LockState::Held(operation) => Err(LockError::Busy(operation)),
+        LockState::Stale(operation) => {
+            clear_stale(&file, &operation);
+            Ok(())
+        }
+    }
+}
+
+/// Upgrade the active profile's database when it still keeps times as
+/// text, holding the lock while its tables are rebuilt so commands started
+/// meanwhile wait instead of writing into the rebuild. A current database,
+/// or one in a read-only data directory, is left alone.
+pub fn upgrade_database() -> Result<(), LockError> {
+    let path = Database::get_db_path().map_err(|e| LockError::Io(e.to_string()))?;
+    if utils::is_data_dir_read_only() || !Database::needs_epoch_upgrade(&path).map_err(|e| LockError::Io(e.to_string()))? {
+        return Ok(());
+    }
+    // Taking the lock opens the database, which upgrades it; the open
+    // after only reports a failure
+    let _lock = OperationLock::acquire("a database upgrade")?;
+    Database::new().map_err(|e| LockError::Io(e.to_string()))?;
+    Ok(())
+}
+
+/// Held by a long operation (import, dedupe, encryption, ...) for as long
+/// as it runs, so other commands that write the database wait for it.
+/// Released on drop, or by the system if the process dies.
+pub struct OperationLock {
+    file: File,
+}
+
+impl OperationLock {
+    pub fn acquire(description: &str) -> Result<Self, LockError> {
+        let file = open_lock_file().map_err(|e| LockError::Io(e.to_string()))?;
+        match current_state(&file, || Database::new_read_only().ok()).map_err(|e| LockError::Io(e.to_string()))? {
+            LockState::Held(operation) => return Err(LockError::Busy(operation)),
+            LockState::Stale(operation) => clear_stale(&file, &operation),
+            LockState::Free => {}
+        }
+        let operation = Operation { description: description.to_string(), pid: utils::get_current_pid(), started_at: Utc::now() };
+        let recorded = serde_json::to_string(&operation).map_err(|e| LockError::Io(e.to_string()))?;
+        record_in_file(&file, Some(&operation)).map_err(|e| LockError::Io(e.to_string()))?;
+        Database::new()
+            .and_then(|db| db.set_meta(META_KEY, &recorded))
+            .map_err(|e| LockError::Io(e.to_string()))?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for OperationLock {
+    fn drop(&mut self) {
+        // Opened again: an encryption migration replaces the database file.
+        // If this fails the record is cleared as stale by the next command.
+        if let Ok(db) = Database::new() {
+            let _ = db.delete_meta(META_KEY);
+        }
+        let _ = record_in_file(&self.file, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn lock_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("focusdebt-operation-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("focusdebt.oplock")
+    }
+
+    fn open(path: &Path) -> File {
+        std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).unwrap()
+    }
+
+    fn operation(pid: u32) -> Operation {
+        let started_at = DateTime::parse_from_rfc3339("2024-06-04T10:00:00Z").unwrap().with_timezone(&Utc);
+        Operation { description: "an import".to_string(), pid, started_at }
+    }
+
+    /// The PID of a process that has exited
+    fn dead_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        pid
+    }
+
+    fn database_recording(operation: Option<&Operation>) -> Database {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        if let Some(operation) = operation {
+            db.set_meta(META_KEY, &serde_json::to_string(operation).unwrap()).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn classify_covers_every_combination() {
+        let alive = |_| true;
+        let dead = |_| false;
+        assert_eq!(classify(true, None, alive), LockState::Free);
+        assert_eq!(classify(true, Some(operation(1)), alive), LockState::Stale(operation(1)));
+        assert_eq!(classify(false, Some(operation(1)), alive), LockState::Held(Some(operation(1))));
+        // A new holder that hasn't replaced a dead one's record yet
+        assert_eq!(classify(false, Some(operation(1)), dead), LockState::Held(None));
+        assert_eq!(classify(false, None, alive), LockState::Held(None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lock_is_exclusive_until_its_file_closes() {
+        let path = lock_path("exclusive");
+        let first = open(&path);
+        let second = open(&path);
+        assert!(try_lock(&first).unwrap());
+        assert!(!try_lock(&second).unwrap());
+        drop(first);
+        assert!(try_lock(&second).unwrap());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn record_in_file_round_trips_and_clears() {
+        let path = lock_path("record");
+        let file = open(&path);
+        assert_eq!(recorded_in_file(&file), None);
+        let held = operation(42);
+        record_in_file(&file, Some(&held)).unwrap();
+        assert_eq!(recorded_in_file(&open(&path)), Some(held));
+        // A shorter record replaces a longer one entirely
+        let short = Operation { description: "x".to_string(), ..operation(7) };
+        record_in_file(&file, Some(&short)).unwrap();
+        assert_eq!(recorded_in_file(&file), Some(short));
+        record_in_file(&file, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn current_state_reads_the_holder_from_the_database_or_the_file() {
+        let path = lock_path("held");
+        let holder = open(&path);
+        assert!(try_lock(&holder).unwrap());
+        let running = operation(std::process::id());
+
+        let state = current_state(&open(&path), || Some(database_recording(Some(&running)))).unwrap();
+        assert_eq!(state, LockState::Held(Some(running.clone())));
+
+        // The database too busy to read: the file's copy names the holder
+        record_in_file(&holder, Some(&running)).unwrap();
+        let state = current_state(&open(&path), || None).unwrap();
+        assert_eq!(state, LockState::Held(Some(running)));
+        // Released the way OperationLock's drop does it
+        record_in_file(&holder, None).unwrap();
+        drop(holder);
+        assert_eq!(current_state(&open(&path), || Some(database_recording(None))).unwrap(), LockState::Free);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn record_of_a_dead_pid_is_stale() {
+        let path = lock_path("stale");
+        let died = operation(dead_pid());
+
+        // The holder died and the system released its lock
+        let state = current_state(&open(&path), || Some(database_recording(Some(&died)))).unwrap();
+        assert_eq!(state, LockState::Stale(died.clone()));
+
+        // Its record is still in the file, and the lock was taken again by
+        // a holder that hasn't recorded itself yet
+        #[cfg(unix)]
+        {
+            let holder = open(&path);
+            assert!(try_lock(&holder).unwrap());
+            record_in_file(&holder, Some(&died)).unwrap();
+            assert_eq!(current_state(&open(&path), || None).unwrap(), LockState::Held(None));
+        }
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::Command;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// How often the daemon looks at the power source; a change splits the
+/// open row there
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What the machine was running on while a row was tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+impl PowerSource {
+    /// As stored in the database and JSON
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PowerSource::Ac => "ac",
+            PowerSource::Battery => "battery",
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            PowerSource::Ac => "on AC",
+            PowerSource::Battery => "on battery",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "ac" | "mains" | "plugged" => Ok(PowerSource::Ac),
+            "battery" | "bat" => Ok(PowerSource::Battery),
+            other => Err(format!("'{}' is not a power source (use ac or battery)", other)),
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn command_stdout(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The current power source, from /sys/class/power_supply: any online
+/// mains or USB supply is AC, else a present battery means battery. None
+/// without a battery (a desktop is always on AC, there is nothing to tell
+/// apart) or when it can't be read.
+#[cfg(target_os = "linux")]
+pub fn current() -> Option<PowerSource> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok().map(|value| value.trim().to_string());
+    let mut has_battery = false;
+    let mut on_mains = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        match read(path.join("type")).as_deref() {
+            // A mouse or headset battery reports scope "Device"
+            Some("Battery") if read(path.join("scope")).as_deref() != Some("Device") => {
+                has_battery |= read(path.join("present")).as_deref() != Some("0");
+            }
+            Some("Mains") | Some("USB") | Some("USB_C") => {
+                on_mains |= read(path.join("online")).as_deref() == Some("1");
+            }
+            _ => {}
+        }
+    }
+    match (has_battery, on_mains) {
+        (false, _) => None,
+        (true, true) => Some(PowerSource::Ac),
+        (true, false) => Some(PowerSource::Battery),
+    }
+}
+
+/// From `pmset -g batt`, whose first line reads "Now drawing from 'AC Power'"
+/// or "'Battery Power'"
+#[cfg(target_os = "macos")]
+pub fn current() -> Option<PowerSource> {
+    let output = command_stdout(Command::new("pmset").args(["-g", "batt"]))?;
+    if !output.contains("InternalBattery") {
+        return None;
+    }
+    let first = output.lines().next()?;
+    if first.contains("Battery Power") {
+        Some(PowerSource::Battery)
+    } else if first.contains("AC Power") {
+        Some(PowerSource::Ac)
+    } else {
+        None
+    }
+}
+
+/// From Win32_Battery's BatteryStatus, where 1 is discharging
+#[cfg(target_os = "windows")]
+pub fn current() -> Option<PowerSource> {
+    let output = command_stdout(Command::new("powershell").args([
+        "-NoProfile", "-Command",
+        "(Get-CimInstance Win32_Battery | Select-Object -First 1).BatteryStatus",
+    ]))?;
+    match output.trim().parse::<u32>().ok()? {
+        1 => Some(PowerSource::Battery),
+        _ => Some(PowerSource::Ac),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn current() -> Option<PowerSource> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_sources_parse_their_stored_names_and_aliases() {
+        for power in [PowerSource::Ac, PowerSource::Battery] {
+            assert_eq!(PowerSource::parse(power.as_str()), Ok(power));
+        }
+        assert_eq!(PowerSource::parse(" Mains "), Ok(PowerSource::Ac));
+        assert_eq!(PowerSource::parse("BAT"), Ok(PowerSource::Battery));
+        assert!(PowerSource::parse("solar").unwrap_err().contains("'solar'"));
+        assert_eq!(serde_json::to_string(&PowerSource::Battery).unwrap(), "\"battery\"");
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::utils::{self, DurationStyle};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Minimum time between two redraws of the bar
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Progress for long-running operations, drawn on stderr as
+/// `label [#####-----] 50% 500/1000 ETA 3s`. Nothing is drawn with --quiet
+/// or when stderr is not a terminal, so piped output stays clean.
+pub struct Progress {
+    label: String,
+    total: usize,
+    processed: usize,
+    started: Instant,
+    last_draw: Option<Instant>,
+    visible: bool,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: usize) -> Self {
+        Self {
+            label: label.to_string(),
+            total,
+            processed: 0,
+            started: Instant::now(),
+            last_draw: None,
+            visible: !QUIET.load(Ordering::Relaxed) && std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// A progress tracker that never draws anything
+    pub fn hidden() -> Self {
+        let mut progress = Self::new("", 0);
+        progress.visible = false;
+        progress
+    }
+
+    pub fn advance(&mut self, rows: usize) {
+        self.processed += rows;
+        if !self.visible {
+            return;
+        }
+        let due = self.last_draw.is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL);
+        if due || self.processed >= self.total {
+            self.draw();
+        }
+    }
+
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        if self.processed == 0 || self.processed >= self.total {
+            return None;
+        }
+        let per_row = self.started.elapsed().as_secs_f64() / self.processed as f64;
+        Some(Duration::from_secs_f64(per_row * (self.total - self.processed) as f64))
+    }
+
+    fn draw(&mut self) {
+        let bar_width = 30;
+        let fraction = if self.total > 0 {
+            (self.processed as f64 / self.total as f64).min(1.0)
+        } else {
+            1.0
+        };
+        let filled = (fraction * bar_width as f64) as usize;
+        let eta = self.eta()
+            .map(|eta| format!(" ETA {}", utils::format_duration(eta, DurationStyle::Compact)))
+            .unwrap_or_default();
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{} [{}{}] {:.0}% {}/{}{}\x1b[K",
+            self.label,
+            "#".repeat(filled),
+            "-".repeat(bar_width - filled),
+            fraction * 100.0,
+            self.processed,
+            self.total,
+            eta
+        );
+        let _ = stderr.flush();
+        self.last_draw = Some(Instant::now());
+    }
+
+    /// Clear the bar, e.g. before reporting an error
+    pub fn clear(&mut self) {
+        if self.visible && self.last_draw.is_some() {
+            let _ = write!(std::io::stderr(), "\r\x1b[K");
+            self.last_draw = None;
+        }
+    }
+
+    /// Clear the bar and print a one-line summary with the elapsed time
+    pub fn finish(&mut self, summary: &str) {
+        self.clear();
+        println!("~=~ {} in {}", summary, format_elapsed(self.started.elapsed()));
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{}ms", elapsed.as_millis())
+    } else if elapsed < Duration::from_secs(60) {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        utils::format_duration(elapsed, DurationStyle::Long)
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::stats;
+use crate::storage::Database;
+use crate::utils::{self, runtime_files::{self, RuntimeFile, RuntimeKind}};
+
+/// The database of a profile's data directory, SQLite's side files and
+/// what is derived from the rows: the stats cache and the clock reference
+const DATABASE_FILES: [&str; 6] = [
+    "focusdebt.db", "focusdebt.db-wal", "focusdebt.db-shm", "focusdebt.db-journal",
+    stats::DAILY_STATS_CACHE_FILE, utils::LAST_SEEN_CLOCK_FILE,
+];
+
+type Tables = BTreeMap<String, Vec<serde_json::Map<String, serde_json::Value>>>;
+
+/// What `reset --all` writes before it deletes anything
+#[derive(Serialize)]
+struct Backup<'a> {
+    created_at: String,
+    version: &'static str,
+    config: &'a Config,
+    /// Every table of each profile's database; "default" is the one
+    /// without a profile
+    databases: BTreeMap<String, Tables>,
+}
+
+/// The profiles a reset clears: the active one, or with `all` the default
+/// profile and every named one
+pub fn profiles(all: bool) -> Vec<Option<String>> {
+    if !all {
+        return vec![utils::active_profile().map(str::to_string)];
+    }
+    std::iter::once(None).chain(utils::list_profiles().into_iter().map(Some)).collect()
+}
+
+/// Profiles among `profiles`, other than the active one, whose daemon is
+/// running; a reset stops only the active profile's
+pub fn other_running_daemons(profiles: &[Option<String>]) -> Vec<String> {
+    let Some(data_dir) = utils::get_data_directory() else {
+        return Vec::new();
+    };
+    profiles.iter()
+        .filter(|profile| profile.as_deref() != utils::active_profile())
+        .filter(|profile| {
+            let stem = match profile {
+                Some(name) => format!("focusdebt-{}", name),
+                None => "focusdebt".to_string(),
+            };
+            runtime_files::pid_file_alive(&data_dir.join(format!("{}.pid", stem)))
+        })
+        .map(|profile| describe_profile(profile).to_string())
+        .collect()
+}
+
+pub fn describe_profile(profile: &Option<String>) -> &str {
+    profile.as_deref().unwrap_or("default")
+}
+
+/// e.g. focusdebt-reset-20261016-142501.json
+pub fn backup_file_name(at: DateTime<Utc>) -> String {
+    format!("focusdebt-reset-{}.json", at.format("%Y%m%d-%H%M%S"))
+}
+
+/// Write `config` and every table of each of `profiles`' databases to a
+/// timestamped JSON file in `dir`. Profiles without a database are left out.
+pub fn write_backup(dir: &Path, config: &Config, profiles: &[Option<String>], at: DateTime<Utc>) -> Result<PathBuf, String> {
+    let mut databases = BTreeMap::new();
+    for profile in profiles {
+        let path = utils::profile_data_directory(profile.as_deref())
+            .ok_or("could not find the data directory")?
+            .join(DATABASE_FILES[0]);
+        if !path.exists() {
+            continue;
+        }
+        let tables = Database::open_read_only(&path)
+            .and_then(|db| db.dump_tables())
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        databases.insert(describe_profile(profile).to_string(), tables);
+    }
+    let backup = Backup {
+        created_at: at.to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION"),
+        config,
+        databases,
+    };
+    let content = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(backup_file_name(at));
+    utils::write_private_file(&path, content).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Delete the database files and caches of `profiles`, and with
+/// `profile_dirs` the named profiles' data directories as well. Every file
+/// is tried; returns what was removed and what couldn't be.
+pub fn remove_databases(profiles: &[Option<String>], profile_dirs: bool) -> (Vec<PathBuf>, Vec<String>) {
+    let mut removed = Vec::new();
+    let mut failed = Vec::new();
+    for profile in profiles {
+        let Some(dir) = utils::profile_data_directory(profile.as_deref()) else {
+            failed.push(format!("{}: could not find the data directory", describe_profile(profile)));
+            continue;
+        };
+        if profile_dirs && profile.is_some() {
+            if dir.exists() && utils::is_safe_path(&dir) {
+                match fs::remove_dir_all(&dir) {
+                    Ok(()) => removed.push(dir),
+                    Err(e) => failed.push(format!("{}: {}", dir.display(), e)),
+                }
+            }
+            continue;
+        }
+        for name in DATABASE_FILES {
+            let path = dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => removed.push(path),
+                Err(e) => failed.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+    }
+    (removed, failed)
+}
+
+/// "focusdebt" or "focusdebt-<profile>" of a runtime file name, ignoring a
+/// staging ".tmp"
+fn runtime_stem(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let name = name.strip_suffix(".tmp").unwrap_or(name);
+    name.rsplit_once('.').map(|(stem, _)| stem)
+}
+
+/// Of the runtime files found, those a reset removes and those it leaves
+/// because their process still runs (another profile's daemon). Without
+/// `all_profiles` other profiles' files are not touched at all.
+pub fn runtime_files_to_remove(files: Vec<RuntimeFile>, all_profiles: bool) -> (Vec<RuntimeFile>, Vec<RuntimeFile>) {
+    let active = runtime_files::runtime_file("pid");
+    let active_stem = active.as_deref().and_then(runtime_stem);
+    files.into_iter()
+        .filter(|file| all_profiles
+            || matches!(file.kind, RuntimeKind::Journal | RuntimeKind::RotatedJournal | RuntimeKind::OpenedExport)
+            || runtime_stem(&file.path) == active_stem)
+        .partition(|file| !file.owner_running)
+}
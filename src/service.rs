@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use crate::completions::UserDirs;
+
+/// Minutes between watchdog runs; with the heartbeat's own staleness
+/// window, a dead daemon is reported within about ten minutes
+pub const WATCHDOG_INTERVAL_MINUTES: u32 = 5;
+
+/// A systemd user unit `service install` writes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// "focusdebt-watchdog", or "focusdebt-watchdog-work" for the work profile,
+/// so every profile's daemon gets a watchdog of its own
+pub fn watchdog_name(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("focusdebt-watchdog-{}", profile),
+        None => "focusdebt-watchdog".to_string(),
+    }
+}
+
+/// Where systemd looks for this user's units
+pub fn user_unit_directory(dirs: &UserDirs) -> PathBuf {
+    dirs.config_home().join("systemd").join("user")
+}
+
+/// The oneshot service running `heartbeat-check` with `exe`, and the timer
+/// running it every `WATCHDOG_INTERVAL_MINUTES`
+pub fn watchdog_units(dirs: &UserDirs, exe: &Path, profile: Option<&str>, restart: bool) -> Vec<Unit> {
+    let name = watchdog_name(profile);
+    let dir = user_unit_directory(dirs);
+
+    let mut command = vec![quote(&exe.display().to_string())];
+    if let Some(profile) = profile {
+        command.push("--profile".to_string());
+        command.push(profile.to_string());
+    }
+    command.push("heartbeat-check".to_string());
+    if restart {
+        command.push("--restart".to_string());
+    }
+
+    let service = format!(
+        "[Unit]\n\
+         Description=Notify when the focusdebt daemon stops tracking\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n\
+         # A restarted daemon must outlive this check\n\
+         KillMode=process\n",
+        command.join(" "),
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Check the focusdebt daemon's heartbeat every {minutes} minutes\n\
+         \n\
+         [Timer]\n\
+         OnStartupSec={minutes}min\n\
+         OnUnitActiveSec={minutes}min\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        minutes = WATCHDOG_INTERVAL_MINUTES,
+    );
+    vec![
+        Unit { path: dir.join(format!("{}.service", name)), content: service },
+        Unit { path: dir.join(format!("{}.timer", name)), content: timer },
+    ]
+}
+
+/// `value` as one word of an ExecStart line
+fn quote(value: &str) -> String {
+    if value.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn dirs(config_home: Option<&str>) -> UserDirs {
+        UserDirs {
+            home: PathBuf::from("/home/fd-user"),
+            data_home: None,
+            config_home: config_home.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn watchdog_units_run_heartbeat_check_on_a_timer() {
+        let units = watchdog_units(&dirs(None), Path::new("/usr/bin/focusdebt"), None, false);
+        assert_eq!(units.iter().map(|unit| unit.path.clone()).collect::<Vec<_>>(), [
+            PathBuf::from("/home/fd-user/.config/systemd/user/focusdebt-watchdog.service"),
+            PathBuf::from("/home/fd-user/.config/systemd/user/focusdebt-watchdog.timer"),
+        ]);
+        assert!(units[0].content.contains("\nExecStart=/usr/bin/focusdebt heartbeat-check\n"), "{}", units[0].content);
+        assert!(units[0].content.contains("\nType=oneshot\n"));
+        assert!(units[1].content.contains("\nOnUnitActiveSec=5min\n"), "{}", units[1].content);
+        assert!(units[1].content.ends_with("[Install]\nWantedBy=timers.target\n"));
+    }
+
+    #[test]
+    fn each_profile_gets_its_own_watchdog() {
+        let units = watchdog_units(&dirs(Some("/xdg/config")), Path::new("/opt/Focus Debt/focusdebt"), Some("work"), true);
+        assert_eq!(units[1].path, PathBuf::from("/xdg/config/systemd/user/focusdebt-watchdog-work.timer"));
+        assert!(units[0].content.contains("\nExecStart=\"/opt/Focus Debt/focusdebt\" --profile work heartbeat-check --restart\n"),
+            "{}", units[0].content);
+        assert_eq!(watchdog_name(None), "focusdebt-watchdog");
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+        assert_eq!(quote("plain"), "plain");
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+use crate::config::SoundsConfig;
+use crate::utils::{self, DurationStyle};
+
+const MUTE_FILE: &str = "sounds_muted_until";
+
+/// Cues waiting for the player; more than this while one plays are dropped
+const QUEUE_LENGTH: usize = 4;
+
+/// Things the daemon has a sound for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// Another `focus_minutes` of continuous focus
+    FocusMilestone,
+    /// `distraction_minutes` on a distraction
+    Distraction,
+}
+
+impl SoundEvent {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoundEvent::FocusMilestone => "focus milestone",
+            SoundEvent::Distraction => "distraction",
+        }
+    }
+
+    /// Built-in beep as (frequency in Hz, length in ms) tones: rising for
+    /// focus, one low tone for distraction
+    #[cfg(feature = "sounds")]
+    fn beep(&self) -> &'static [(f32, u64)] {
+        match self {
+            SoundEvent::FocusMilestone => &[(660.0, 150), (880.0, 250)],
+            SoundEvent::Distraction => &[(330.0, 400)],
+        }
+    }
+
+    fn sound_file<'a>(&self, config: &'a SoundsConfig) -> Option<&'a str> {
+        match self {
+            SoundEvent::FocusMilestone => config.focus_sound.as_deref(),
+            SoundEvent::Distraction => config.distraction_sound.as_deref(),
+        }
+    }
+}
+
+static PLAYER: OnceLock<SyncSender<SoundEvent>> = OnceLock::new();
+
+/// Start the player thread when sounds are enabled. Audio output is opened
+/// there, so a slow or missing device never holds up tracking.
+pub fn start_player(config: &SoundsConfig) {
+    if !config.enabled || PLAYER.get().is_some() {
+        return;
+    }
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+    let config = config.clone();
+    let spawned = std::thread::Builder::new()
+        .name("sounds".to_string())
+        .spawn(move || run_player(&config, receiver));
+    match spawned {
+        Ok(_) => {
+            let _ = PLAYER.set(sender);
+        }
+        Err(e) => eprintln!("❌ Failed to start the sound player: {}", e),
+    }
+}
+
+/// Queue `event`'s sound without waiting; dropped when the player is busy
+/// or sounds are off
+pub fn play(event: SoundEvent) {
+    let Some(player) = PLAYER.get() else { return };
+    match player.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => println!("~=~ Sound player busy, skipping the {} cue", event.label()),
+        Err(TrySendError::Disconnected(_)) => eprintln!("❌ Sound player stopped, skipping the {} cue", event.label()),
+    }
+}
+
+fn run_player(config: &SoundsConfig, receiver: Receiver<SoundEvent>) {
+    let output = output::Output::open();
+    for event in receiver {
+        if let Some(until) = muted_until() {
+            println!("~=~ Sound muted until {}, skipping the {} cue", utils::format_timestamp_local(until), event.label());
+            continue;
+        }
+        match &output {
+            Ok(output) => {
+                if let Err(e) = output.play(event, event.sound_file(config)) {
+                    eprintln!("❌ Failed to play the {} cue: {}", event.label(), e);
+                }
+            }
+            // Headless: no device (or built without the sounds feature)
+            Err(_) => println!("~=~ Sound cue: {}", event.label()),
+        }
+    }
+}
+
+#[cfg(feature = "sounds")]
+mod output {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::time::Duration;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+
+    use super::SoundEvent;
+
+    pub struct Output {
+        // Playback stops when the stream is dropped
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    impl Output {
+        pub fn open() -> Result<Self, String> {
+            let (stream, handle) = OutputStream::try_default().map_err(|e| format!("no audio device ({})", e))?;
+            Ok(Output { _stream: stream, handle })
+        }
+
+        /// Play `file`, or `event`'s beep without one, and wait for it to end
+        pub fn play(&self, event: SoundEvent, file: Option<&str>) -> Result<(), String> {
+            let sink = Sink::try_new(&self.handle).map_err(|e| e.to_string())?;
+            match file {
+                Some(path) => {
+                    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+                    sink.append(Decoder::new(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))?);
+                }
+                None => {
+                    for &(frequency, ms) in event.beep() {
+                        sink.append(rodio::source::SineWave::new(frequency)
+                            .take_duration(Duration::from_millis(ms))
+                            .amplify(0.2));
+                    }
+                }
+            }
+            sink.sleep_until_end();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "sounds"))]
+mod output {
+    use super::SoundEvent;
+
+    pub struct Output;
+
+    impl Output {
+        pub fn open() -> Result<Self, String> {
+            Err("built without the sounds feature".to_string())
+        }
+
+        pub fn play(&self, _event: SoundEvent, _file: Option<&str>) -> Result<(), String> {
+            Ok(())
+        }
+    }
+}
+
+/// End of the current mute, if sounds are muted
+pub fn muted_until() -> Option<DateTime<Utc>> {
+    let path = utils::profile_data_directory(utils::active_profile())?.join(MUTE_FILE);
+    let content = std::fs::read_to_string(path).ok()?;
+    let until = DateTime::parse_from_rfc3339(content.trim()).ok()?.with_timezone(&Utc);
+    (until > Utc::now()).then_some(until)
+}
+
+/// Mute sounds for `duration` from now
+pub fn mute(duration: Duration) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    let until = Utc::now() + chrono::Duration::from_std(duration)?;
+    let data_dir = utils::ensure_profile_data_directory()?;
+    std::fs::write(data_dir.join(MUTE_FILE), until.to_rfc3339())?;
+    Ok(until)
+}
+
+pub fn unmute() -> std::io::Result<()> {
+    let Some(dir) = utils::profile_data_directory(utils::active_profile()) else { return Ok(()) };
+    match std::fs::remove_file(dir.join(MUTE_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// "muted for 1h (until 15:30)" style description of a mute ending at `until`
+pub fn describe_mute(until: DateTime<Utc>) -> String {
+    // Rounded up, so a fresh "mute 1h" reads 1h and not 59m
+    let left = (until - Utc::now()).to_std().unwrap_or_default().as_secs().div_ceil(60) * 60;
+    let left = Duration::from_secs(left);
+    format!("muted for {} (until {})", utils::format_duration(left, DurationStyle::Long), utils::format_timestamp_local(until))
+}
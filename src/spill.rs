@@ -0,0 +1,511 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use rusqlite::Result as SqliteResult;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SessionSnapshot;
+use crate::storage::{self, Database};
+use crate::tracking::{ContextSwitch, EventKind, FocusSession, TrackerEvent};
+use crate::utils::{self, runtime_files};
+
+/// How often a daemon in degraded mode tries the database again
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// Older parts of the journal kept once it reaches max_spill_journal_mb
+/// (journal.1, journal.2); past those the oldest rows are dropped
+pub const JOURNAL_ROTATIONS: usize = 2;
+
+/// One row the database couldn't take, as a line of the spill journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpillRecord {
+    Session(FocusSession),
+    ContextSwitch(ContextSwitch),
+    Event {
+        timestamp: DateTime<Utc>,
+        kind: String,
+        payload: serde_json::Value,
+    },
+    /// A session name claimed by a rollover
+    SessionName { name: String },
+    /// A session held back by the clock check
+    Quarantined { session: FocusSession, reason: String },
+    /// The settings a session was tracked with
+    SessionMeta { name: String, recorded_at: DateTime<Utc>, snapshot: SessionSnapshot },
+}
+
+impl SpillRecord {
+    pub fn event(event: TrackerEvent) -> Self {
+        SpillRecord::Event { timestamp: event.timestamp, kind: event.kind.as_str().to_string(), payload: event.payload }
+    }
+
+    fn save(&self, db: &Database) -> SqliteResult<()> {
+        match self {
+            SpillRecord::Session(session) => db.save_focus_session(session),
+            SpillRecord::ContextSwitch(switch) => db.save_context_switch(switch),
+            SpillRecord::Event { timestamp, kind, payload } => match EventKind::parse(kind) {
+                Some(kind) => db.save_event(&TrackerEvent { timestamp: *timestamp, kind, payload: payload.clone() }),
+                // Written by a newer build; nothing here knows what it means
+                None => Ok(()),
+            },
+            SpillRecord::SessionName { name } => db.claim_session_name(name, true).map(|_| ()),
+            SpillRecord::Quarantined { session, reason } => db.quarantine_session(session, reason),
+            SpillRecord::SessionMeta { name, recorded_at, snapshot } => db.save_session_meta(name, *recorded_at, snapshot),
+        }
+    }
+}
+
+/// Where a row handed to `Spill::store` ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stored {
+    Database,
+    Journal,
+    /// Held in memory because the spill journal couldn't be written either
+    Memory,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    /// Rows still spilled, in the journal or in memory
+    pub remaining: usize,
+    pub error: Option<String>,
+}
+
+/// focusdebt-spill.jsonl in `spill_dir`, or focusdebt-<profile>-spill.jsonl
+/// for a profile, so profiles sharing a directory don't replay each other's rows
+pub fn journal_path(spill_dir: &Path) -> PathBuf {
+    match utils::active_profile() {
+        Some(profile) => spill_dir.join(format!("focusdebt-{}-spill.jsonl", profile)),
+        None => spill_dir.join("focusdebt-spill.jsonl"),
+    }
+}
+
+/// The journal's rotated parts and the journal itself, oldest first
+fn journal_parts(path: &Path) -> Vec<PathBuf> {
+    (1..=JOURNAL_ROTATIONS).rev()
+        .map(|n| runtime_files::rotated_path(path, n))
+        .chain(std::iter::once(path.to_path_buf()))
+        .collect()
+}
+
+/// Rows waiting in the journal at `path`, rotated parts included; 0 when
+/// there is none
+pub fn pending_rows(path: &Path) -> usize {
+    journal_parts(path).iter().map(|part| count_rows(part)).sum()
+}
+
+fn count_rows(path: &Path) -> usize {
+    fs::File::open(path)
+        .map(|file| BufReader::new(file).lines().map_while(Result::ok).filter(|line| !line.trim().is_empty()).count())
+        .unwrap_or(0)
+}
+
+/// Whether the journal at `path` or any rotated part of it exists
+pub fn journal_exists(path: &Path) -> bool {
+    journal_parts(path).iter().any(|part| part.exists())
+}
+
+/// Append the rows of the journal at `from` to the one at `to` and remove
+/// `from`; returns how many rows moved
+pub fn move_journal(from: &Path, to: &Path) -> std::io::Result<usize> {
+    let records = read_journal(from)?;
+    if !records.is_empty() {
+        append_journal(to, &records, 0)?;
+    }
+    remove_journal(from)?;
+    Ok(records.len())
+}
+
+/// Rows of the journal at `path`, rotated parts first
+fn read_journal(path: &Path) -> std::io::Result<Vec<SpillRecord>> {
+    let mut records = Vec::new();
+    for part in journal_parts(path) {
+        records.extend(read_journal_part(&part)?);
+    }
+    Ok(records)
+}
+
+fn read_journal_part(path: &Path) -> std::io::Result<Vec<SpillRecord>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A line cut short by a crash mid-append is the only expected damage
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => eprintln!("❌ Skipping unreadable line {} of spill journal {}: {}", number + 1, path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Append `records` to the journal, creating it readable by the owner only:
+/// it holds window titles. Past `max_bytes` (0 for no cap) the journal is
+/// rotated first; returns how many rows the oldest part took with it.
+fn append_journal(path: &Path, records: &[SpillRecord], max_bytes: u64) -> std::io::Result<usize> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut text = String::new();
+    for record in records {
+        text.push_str(&serde_json::to_string(record).map_err(std::io::Error::other)?);
+        text.push('\n');
+    }
+    let mut dropped = 0;
+    if runtime_files::needs_rotation(path, text.len() as u64, max_bytes) {
+        dropped = count_rows(&runtime_files::rotated_path(path, JOURNAL_ROTATIONS));
+        runtime_files::rotate(path, JOURNAL_ROTATIONS)?;
+    }
+    let mut file = crate::utils::private_file_options().append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_data()?;
+    Ok(dropped)
+}
+
+/// Remove the journal and its rotated parts
+fn remove_journal(path: &Path) -> std::io::Result<()> {
+    for part in journal_parts(path) {
+        match fs::remove_file(part) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The daemon's fail-safe for a full disk. Rows are saved normally until the
+/// database reports SQLITE_FULL; from then on (degraded mode) they go to the
+/// spill journal on another filesystem, or to a bounded queue in memory when
+/// that fails too, and the database is retried every `RETRY_INTERVAL`. Once
+/// the spilled rows replay, rows are saved normally again.
+#[derive(Debug)]
+pub struct Spill {
+    path: PathBuf,
+    memory: VecDeque<SpillRecord>,
+    limit: usize,
+    /// max_spill_journal_mb in bytes; 0 for no cap
+    journal_limit: u64,
+    dropped: usize,
+    degraded_since: Option<DateTime<Utc>>,
+    last_attempt: Option<Instant>,
+}
+
+impl Spill {
+    /// `limit` bounds the in-memory queue and `journal_limit` (bytes, 0 for
+    /// none) each part of the journal; the oldest rows go first past either
+    pub fn new(path: PathBuf, limit: usize, journal_limit: u64) -> Self {
+        Self {
+            path,
+            memory: VecDeque::new(),
+            limit: limit.max(1),
+            journal_limit,
+            dropped: 0,
+            degraded_since: None,
+            last_attempt: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn degraded_since(&self) -> Option<DateTime<Utc>> {
+        self.degraded_since
+    }
+
+    /// Whether a journal (maybe left by an earlier run) or queued rows wait
+    pub fn has_pending(&self) -> bool {
+        !self.memory.is_empty() || journal_exists(&self.path)
+    }
+
+    /// Save `record`, or spill it while degraded. A disk-full error switches
+    /// to degraded mode; any other error is returned and the row is lost, as
+    /// before.
+    pub fn store(&mut self, db: &Database, record: SpillRecord) -> SqliteResult<Stored> {
+        if self.degraded_since.is_none() {
+            match record.save(db) {
+                Ok(()) => return Ok(Stored::Database),
+                Err(e) if storage::is_disk_full(&e) => {
+                    eprintln!("❌ Disk full ({}): saving to {} until the database can be written again", e, self.path.display());
+                    self.degraded_since = Some(Utc::now());
+                    self.last_attempt = Some(Instant::now());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.spill(record))
+    }
+
+    fn spill(&mut self, record: SpillRecord) -> Stored {
+        // Once rows are queued in memory, later ones queue behind them to
+        // keep the order; the next retry moves them to the journal
+        if self.memory.is_empty() {
+            match append_journal(&self.path, std::slice::from_ref(&record), self.journal_limit) {
+                Ok(0) => return Stored::Journal,
+                Ok(dropped) => {
+                    eprintln!("❌ Spill journal {} reached max_spill_journal_mb; dropped its {} oldest rows", self.path.display(), dropped);
+                    return Stored::Journal;
+                }
+                Err(e) => eprintln!("❌ Failed to write spill journal {}: {}; holding rows in memory", self.path.display(), e),
+            }
+        }
+        if self.memory.len() >= self.limit {
+            self.memory.pop_front();
+            self.dropped += 1;
+        }
+        self.memory.push_back(record);
+        Stored::Memory
+    }
+
+    /// Whether degraded mode is due another try of the database
+    pub fn retry_due(&self) -> bool {
+        self.degraded_since.is_some() && self.last_attempt.is_none_or(|at| at.elapsed() >= RETRY_INTERVAL)
+    }
+
+    /// Write the journal, then the rows held in memory, to the database in
+    /// the order they were spilled. When every row fits, the journal is
+    /// removed and degraded mode ends; otherwise what's left stays spilled.
+    pub fn replay(&mut self, db: &Database) -> ReplayReport {
+        self.last_attempt = Some(Instant::now());
+        // With no journal (its directory may be unusable) only memory replays
+        let journal = if journal_exists(&self.path) { read_journal(&self.path) } else { Ok(Vec::new()) };
+        let mut pending = match journal {
+            Ok(records) => records,
+            Err(e) => {
+                return ReplayReport {
+                    remaining: self.memory.len(),
+                    error: Some(format!("could not read {}: {}", self.path.display(), e)),
+                    ..Default::default()
+                };
+            }
+        };
+        let journal_rows = pending.len();
+        pending.extend(self.memory.drain(..));
+
+        let mut report = ReplayReport::default();
+        for record in &pending {
+            if let Err(e) = record.save(db) {
+                report.error = Some(e.to_string());
+                break;
+            }
+            report.replayed += 1;
+        }
+        let rest = pending.split_off(report.replayed);
+        report.remaining = rest.len();
+
+        if rest.is_empty() {
+            if let Err(e) = remove_journal(&self.path) {
+                eprintln!("❌ Failed to remove spill journal {}: {}", self.path.display(), e);
+            }
+            if self.dropped > 0 {
+                eprintln!("❌ {} rows were dropped while the disk was full and the spill journal unwritable", self.dropped);
+                self.dropped = 0;
+            }
+            self.degraded_since = None;
+            return report;
+        }
+
+        // Rows replayed from the journal must not replay twice, so it is
+        // rewritten with only the rest; if that fails they wait in memory
+        if report.replayed > 0 || journal_rows < rest.len() {
+            let rewritten = remove_journal(&self.path).and_then(|_| append_journal(&self.path, &rest, 0));
+            if rewritten.is_err() {
+                let _ = remove_journal(&self.path);
+                self.memory = rest.into_iter().collect();
+                while self.memory.len() > self.limit {
+                    self.memory.pop_front();
+                    self.dropped += 1;
+                }
+            }
+        }
+        self.degraded_since.get_or_insert_with(Utc::now);
+        report
+    }
+
+    /// Move rows held in memory to the journal before the daemon exits.
+    /// Returns how many rows could not be kept.
+    pub fn persist(&mut self) -> usize {
+        if self.memory.is_empty() {
+            return 0;
+        }
+        let rows: Vec<SpillRecord> = self.memory.drain(..).collect();
+        match append_journal(&self.path, &rows, self.journal_limit) {
+            Ok(dropped) => dropped,
+            Err(e) => {
+                eprintln!("❌ Failed to write spill journal {}: {}", self.path.display(), e);
+                rows.len()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::OpenEnd;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("focusdebt-spill-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        fn journal(&self) -> PathBuf {
+            self.0.join("focusdebt-spill.jsonl")
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    /// Row `n`, with a title long enough to need pages of its own
+    fn row(n: i64) -> SpillRecord {
+        let start = start() + chrono::Duration::minutes(n);
+        SpillRecord::Session(FocusSession {
+            start_time: start,
+            end_time: Some(start + chrono::Duration::minutes(1)),
+            app_name: "fd-editor".to_string(),
+            window_title: format!("{} {}", n, "x".repeat(20_000)),
+            domain: None,
+            duration: Duration::from_secs(60),
+            is_focus_app: true,
+            session_name: "test".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        })
+    }
+
+    /// The numbers of the rows in the database, in start order
+    fn saved(db: &Database) -> Vec<i64> {
+        db.get_sessions_between(start() - chrono::Duration::days(1), start() + chrono::Duration::days(1)).unwrap()
+            .iter()
+            .map(|session| session.window_title.split(' ').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn a_full_disk_spills_to_the_journal_until_a_replay_fits() {
+        let scratch = Scratch::new("full");
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let mut spill = Spill::new(scratch.journal(), 10, 0);
+
+        assert_eq!(spill.store(&db, row(0)).unwrap(), Stored::Database);
+        assert!(spill.degraded_since().is_none());
+
+        db.limit_growth(Some(0)).unwrap();
+        for n in 1..4 {
+            assert_eq!(spill.store(&db, row(n)).unwrap(), Stored::Journal);
+        }
+        assert!(spill.degraded_since().is_some());
+        assert!(!spill.retry_due(), "just tried the database");
+        assert_eq!(pending_rows(&scratch.journal()), 3);
+
+        // Still full: nothing moves and nothing is lost
+        let report = spill.replay(&db);
+        assert_eq!((report.replayed, report.remaining), (0, 3));
+        assert!(report.error.is_some());
+        assert_eq!(pending_rows(&scratch.journal()), 3);
+        assert!(spill.degraded_since().is_some());
+
+        db.limit_growth(None).unwrap();
+        let report = spill.replay(&db);
+        assert_eq!((report.replayed, report.remaining, report.error), (3, 0, None));
+        assert!(spill.degraded_since().is_none());
+        assert!(!spill.has_pending());
+        assert_eq!(spill.store(&db, row(4)).unwrap(), Stored::Database);
+        assert_eq!(saved(&db), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn a_replay_cut_short_keeps_only_the_rest() {
+        let scratch = Scratch::new("partial");
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let mut spill = Spill::new(scratch.journal(), 10, 0);
+        db.limit_growth(Some(0)).unwrap();
+        for n in 0..4 {
+            spill.store(&db, row(n)).unwrap();
+        }
+
+        // Room for some rows only: those replayed leave the journal
+        db.limit_growth(Some(12)).unwrap();
+        let report = spill.replay(&db);
+        assert!(report.replayed > 0 && report.remaining > 0, "{:?}", report);
+        assert_eq!(report.replayed + report.remaining, 4);
+        assert_eq!(saved(&db).len(), report.replayed);
+        assert_eq!(pending_rows(&scratch.journal()), report.remaining);
+        assert!(spill.degraded_since().is_some());
+
+        // The rest follows once there is room, none of it twice
+        db.limit_growth(None).unwrap();
+        let rest = spill.replay(&db);
+        assert_eq!((rest.replayed, rest.remaining), (report.remaining, 0));
+        assert_eq!(saved(&db), [0, 1, 2, 3]);
+        assert!(!spill.has_pending());
+    }
+
+    #[test]
+    fn an_unwritable_journal_holds_a_bounded_queue_in_memory() {
+        let scratch = Scratch::new("memory");
+        // The journal's directory is a file, so it can't be created
+        let blocked = scratch.0.join("blocked");
+        fs::write(&blocked, "in the way").unwrap();
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let mut spill = Spill::new(blocked.join("focusdebt-spill.jsonl"), 2, 0);
+        db.limit_growth(Some(0)).unwrap();
+        for n in 0..4 {
+            assert_eq!(spill.store(&db, row(n)).unwrap(), Stored::Memory);
+        }
+        assert!(spill.has_pending());
+
+        // The oldest rows went first
+        db.limit_growth(None).unwrap();
+        let report = spill.replay(&db);
+        assert_eq!((report.replayed, report.remaining), (2, 0));
+        assert_eq!(saved(&db), [2, 3]);
+    }
+
+    #[test]
+    fn journal_moves_and_persists_keep_every_row() {
+        let scratch = Scratch::new("move");
+        let (from, to) = (scratch.0.join("a.jsonl"), scratch.0.join("b.jsonl"));
+        append_journal(&from, &[row(0), row(1)], 0).unwrap();
+        append_journal(&to, &[row(2)], 0).unwrap();
+        assert_eq!(move_journal(&from, &to).unwrap(), 2);
+        assert!(!journal_exists(&from));
+        assert_eq!(pending_rows(&to), 3);
+
+        // A line cut short by a crash is skipped, the rest still read
+        let mut file = fs::OpenOptions::new().append(true).open(&to).unwrap();
+        file.write_all(b"{\"type\":\"session\",\"start").unwrap();
+        assert_eq!(read_journal(&to).unwrap().len(), 3);
+    }
+}
@@ -1,207 +1,899 @@
-use std::collections::HashMap;
 use std::time::Duration;
-use chrono::{DateTime, Utc};
-use crate::tracking::FocusSession;
-use crate::storage::Database;
-use crate::utils;
-use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use crate::tracking::{self, ContextSwitch, EventKind, FocusSession, TrackerEvent};
+use crate::storage::{AppSighting, Database, PlanStatus, SessionIntent, SessionMeta, SessionTask};
+use crate::config::{self, AppGroups, AssistantCountsAs, Budget, BudgetPeriod, CoFocusRule, Config, RuleKind, RuleMatch, ScoreConfig, SessionClass, SessionSnapshot};
+use crate::desktop;
+use crate::glyphs::{self, BarStyle, GlyphSet};
+use crate::power::PowerSource;
+use crate::utils::{self, DurationStyle, LocalDays, ReportLayout};
+use crate::utils::fuzzy::{self, FuzzyMatch};
+use std::collections::{BTreeMap, HashSet};
+use rayon::prelude::*;
+use crate::progress::Progress;
+use crate::timing::{self, Phase};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct DailyStats {
+    /// The day, as `start_of_day` of its date; its bounds on the local
+    /// clock come from `LocalDays::range`
     pub date: DateTime<Utc>,
     pub total_focus_time: Duration,
+    /// Focus time weighted by input activity, when any was recorded
+    #[serde(default)]
+    pub engaged_focus_time: Option<Duration>,
     pub total_distraction_time: Duration,
+    /// Time in the day (up to now) not covered by any session, quiet hours excluded
+    #[serde(default)]
+    pub untracked_time: Duration,
+    /// Time tracking was paused for quiet_hours
+    #[serde(default)]
+    pub quiet_time: Duration,
+    /// Time the machine was suspended, see `SuspendGap`
+    #[serde(default)]
+    pub suspended_time: Duration,
+    /// Long low-activity stretches, counted as neither focus nor distraction
+    #[serde(default)]
+    pub passive_time: Duration,
+    /// Time in AI assistants (assistant_sites), passive stretches excluded.
+    /// Also part of focus or distraction time unless `assistant_counts_as`
+    /// is neutral.
+    #[serde(default)]
+    pub assistant_time: Duration,
+    #[serde(default)]
+    pub assistant_counts_as: AssistantCountsAs,
     pub context_switches: usize,
     pub deep_focus_sessions: usize,
     pub focus_efficiency: f64,
+    /// 0–100, see `focus_score`; None when nothing was tracked
+    #[serde(default)]
+    pub focus_score: Option<u32>,
+    /// Mean focus score of the days of the same type before, see
+    /// `focus_score_trend`, when filled in for display
+    #[serde(default)]
+    pub focus_score_7d_avg: Option<f64>,
+    #[serde(default)]
+    pub longest_focus_block: Duration,
+    /// Time from the day's first tracked activity to its first focus block
+    /// of FIRST_FOCUS_BLOCK, see `focus_bookends`; None without one
+    #[serde(default)]
+    pub time_to_first_focus: Option<Duration>,
+    /// Time from the end of the day's last such block to its last
+    /// activity; None until the day is over
+    #[serde(default)]
+    pub focus_wind_down: Option<Duration>,
+    /// Time in rows tagged by a co_focus rule, per label; already part of
+    /// the other figures, see `CoFocusTime`
+    #[serde(default)]
+    pub co_focus: Vec<CoFocusTime>,
+    /// Length of the day on the local clock, 23h or 25h across DST changes
+    #[serde(default)]
+    pub local_day_length: Option<Duration>,
+    /// Timezone changes seen by the daemon, e.g. "14:05 UTC+02:00 → UTC-04:00"
+    #[serde(default)]
+    pub timezone_changes: Vec<String>,
+    /// Upgrades during the day that changed what the numbers mean, see SEMANTIC_CHANGES
+    #[serde(default)]
+    pub version_changes: Vec<String>,
     pub most_used_apps: Vec<(String, Duration)>,
+    /// Top apps against their average over the days of the same type before
+    #[serde(default)]
+    pub app_trends: Vec<AppTrend>,
+    /// Every app's time with the configured `groups` applied, see
+    /// `group_app_usage`; empty without groups
+    #[serde(default)]
+    pub group_usage: Vec<(String, Duration, bool)>,
+    /// `app_trends` for the rows of `group_usage`
+    #[serde(default)]
+    pub group_trends: Vec<AppTrend>,
+    /// Browser time split by the site lists, per browser
+    #[serde(default)]
+    pub browser_sites: Vec<BrowserSites>,
     pub most_distracting_apps: Vec<(String, Duration)>,
+    /// App pairs bounced between most, see `round_trip_pairs`
+    #[serde(default)]
+    pub round_trips: Vec<RoundTripPair>,
+    #[serde(default)]
+    pub switching_cost: SwitchCost,
+    /// Includes rows the running daemon hadn't saved yet
+    #[serde(default)]
+    pub live: bool,
+    /// Focus time per power source, AC first; empty when no row recorded one
+    #[serde(default)]
+    pub power_focus: Vec<(PowerSource, Duration)>,
+    /// Only rows tracked on this source were counted (`stats --power`)
+    #[serde(default)]
+    pub power_filter: Option<PowerSource>,
 }
 
-pub struct Stats;
+/// Two apps bounced between: `home` → `away` → `home`, back within the
+/// round-trip window. `time_away` is the time spent in `away` on those trips.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RoundTripPair {
+    pub home: String,
+    pub away: String,
+    pub round_trips: usize,
+    pub time_away: Duration,
+}
 
-#[derive(Debug, Clone)]
-pub struct AggregatedSession {
-    pub session_name: String,
-    pub start_time: DateTime<Utc>,
-    pub end_time: Option<DateTime<Utc>>,
-    pub total_duration: Duration,
-    pub focus_efficiency: f64,
-    pub app_usage: Vec<(String, Duration, bool)>, // (app_name, duration, is_focus)
-    pub domain_usage: Vec<(String, Duration, bool)>, // (tab_name, duration, is_focus)
-    pub context_switches: usize,
+impl RoundTripPair {
+    /// "code ⇄ firefox: 34 round trips, ~26m"
+    pub(crate) fn describe(&self) -> String {
+        format!("{} ⇄ {}: {} round trip{}, ~{}",
+            self.home,
+            self.away,
+            self.round_trips,
+            if self.round_trips == 1 { "" } else { "s" },
+            utils::format_duration(self.time_away, DurationStyle::Long))
+    }
 }
 
-impl Stats {
-    pub fn calculate_daily_stats(db: &Database, date: DateTime<Utc>) -> Result<DailyStats, Box<dyn std::error::Error>> {
-        let sessions = db.get_sessions_for_date(date)?;
-        let switches = db.get_context_switches_for_date(date)?;
-        let deep_sessions = db.get_deep_focus_sessions(30 * 60, date)?; // 30 minutes
-        let most_distracting = db.get_most_distracting_apps(date, 5)?;
+/// Time a co_focus rule's label was on rows. It is counted once in the
+/// totals, under the apps it was spent in; `credited` splits it between the
+/// rule's sides at CO_FOCUS_SHARE each, so the sides add up to `time`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CoFocusTime {
+    pub label: String,
+    pub time: Duration,
+    /// (side, time) for each side of the rule; empty when the rule is no
+    /// longer configured
+    pub credited: Vec<(String, Duration)>,
+}
 
-        let mut total_focus_time = Duration::ZERO;
-        let mut total_distraction_time = Duration::ZERO;
-        let mut app_usage: HashMap<String, Duration> = HashMap::new();
+impl CoFocusTime {
+    fn new(label: String, time: Duration, rules: &[CoFocusRule]) -> Self {
+        let credited = rules.iter()
+            .find(|rule| rule.label == label)
+            .map(|rule| [0, 1].iter()
+                .map(|&side| (rule.side_name(side).to_string(), time.mul_f64(config::CO_FOCUS_SHARE)))
+                .collect())
+            .unwrap_or_default();
+        Self { label, time, credited }
+    }
 
-        // Process sessions with better validation
-        for session in &sessions {
-            // Skip sessions with invalid durations (likely from old broken tracking)
-            if session.duration > Duration::from_secs(24 * 60 * 60) {
-                // Skip sessions longer than 24 hours (likely broken data)
-                continue;
+    /// "pairing 1h 0m (30m each to meeting and coding)"
+    pub(crate) fn describe(&self) -> String {
+        let time = utils::format_duration(self.time, DurationStyle::Long);
+        match self.credited.as_slice() {
+            [(first, share), (second, _)] => format!("{} {} ({} each to {} and {})",
+                self.label, time, utils::format_duration(*share, DurationStyle::Long), first, second),
+            _ => format!("{} {}", self.label, time),
+        }
+    }
+}
+
+/// Round-trip pairs kept per day and per digest
+pub const ROUND_TRIPS_SHOWN: usize = 5;
+
+/// Find ping-pong switching in `switches`, which must be in time order: a
+/// switch A→B followed by B→A within `window` is one round trip. A trip's
+/// return switch isn't reused as the start of another, so A→B→A→B→A is two.
+/// A pair is keyed regardless of direction and named after the side that
+/// was returned to more often.
+pub fn round_trip_pairs(switches: &[ContextSwitch], window: Duration) -> Vec<RoundTripPair> {
+    // (a, b) with a < b → (trips home to a, trips home to b, time away)
+    let mut pairs: BTreeMap<(&str, &str), (usize, usize, Duration)> = BTreeMap::new();
+    let mut i = 0;
+    while i + 1 < switches.len() {
+        let (out, back) = (&switches[i], &switches[i + 1]);
+        let away = back.timestamp.signed_duration_since(out.timestamp).to_std().ok();
+        let is_trip = out.from_app != out.to_app
+            && back.from_app == out.to_app
+            && back.to_app == out.from_app
+            && away.is_some_and(|away| away <= window);
+        if !is_trip {
+            i += 1;
+            continue;
+        }
+
+        let home = out.from_app.as_str();
+        let other = out.to_app.as_str();
+        let key = if home < other { (home, other) } else { (other, home) };
+        let entry = pairs.entry(key).or_insert((0, 0, Duration::ZERO));
+        if home == key.0 {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+        entry.2 += away.unwrap_or_default();
+        i += 2;
+    }
+
+    let mut result: Vec<RoundTripPair> = pairs.into_iter()
+        .map(|((a, b), (home_a, home_b, time_away))| {
+            let (home, away) = if home_a >= home_b { (a, b) } else { (b, a) };
+            RoundTripPair { home: home.to_string(), away: away.to_string(), round_trips: home_a + home_b, time_away }
+        })
+        .collect();
+    result.sort_by(|x, y| y.round_trips.cmp(&x.round_trips)
+        .then_with(|| y.time_away.cmp(&x.time_away))
+        .then_with(|| x.home.cmp(&y.home)));
+    result
+}
+
+/// Estimated time lost to switching away from focus, see `switching_cost`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SwitchCost {
+    pub total: Duration,
+    /// Switches away whose recovery was measured
+    pub measured: usize,
+    /// Switches away counted at `default_cost`, nothing having been measured
+    pub assumed: usize,
+    /// Switches away left out: nothing measured and switch_cost_unmeasured off
+    pub skipped: usize,
+    pub default_cost: Duration,
+}
+
+impl SwitchCost {
+    /// Whether any switch away from focus was seen
+    pub fn is_empty(&self) -> bool {
+        self.measured + self.assumed + self.skipped == 0
+    }
+
+    /// "~47m lost to context switching"
+    pub(crate) fn headline(&self) -> String {
+        format!("~{} lost to context switching", utils::format_duration(self.total, DurationStyle::Long))
+    }
+
+    /// "~47m (estimate: 12 measured, 3 at 2m each)"
+    pub(crate) fn summary(&self) -> String {
+        format!("~{} ({})", utils::format_duration(self.total, DurationStyle::Long), self.basis())
+    }
+
+    /// "estimate: 12 measured, 3 at 2m each"
+    pub(crate) fn basis(&self) -> String {
+        let mut basis = format!("estimate: {} measured", self.measured);
+        if self.assumed > 0 {
+            basis.push_str(&format!(", {} at {} each", self.assumed, utils::format_duration(self.default_cost, DurationStyle::Long)));
+        }
+        if self.skipped > 0 {
+            basis.push_str(&format!(", {} unmeasured left out", self.skipped));
+        }
+        basis
+    }
+}
+
+/// Estimate the time lost to context switching. Every switch from a focus
+/// row to a non-focus row is a switch away; it costs the recovery time the
+/// daemon measured on the next switch that lands on a focus row again.
+/// When there is none, or it carries no recovery time, the switch costs
+/// `default_cost`, or is left out without `include_unmeasured`. Whether a
+/// side is focus is read from `sessions`: the row that started last before
+/// the switch, and the first one starting at or after it.
+pub fn switching_cost<'a>(
+    switches: &[ContextSwitch],
+    sessions: impl IntoIterator<Item = &'a FocusSession>,
+    default_cost: Duration,
+    include_unmeasured: bool,
+) -> SwitchCost {
+    let mut rows: Vec<&FocusSession> = sessions.into_iter().collect();
+    rows.sort_by_key(|row| row.start_time);
+    let from_focus = |switch: &ContextSwitch| {
+        let index = rows.partition_point(|row| row.start_time < switch.timestamp);
+        index > 0 && rows[index - 1].is_focus_app
+    };
+    let to_focus = |switch: &ContextSwitch| {
+        let index = rows.partition_point(|row| row.start_time < switch.timestamp);
+        rows.get(index).is_some_and(|row| row.is_focus_app)
+    };
+
+    let mut switches: Vec<&ContextSwitch> = switches.iter().collect();
+    switches.sort_by_key(|switch| switch.timestamp);
+    let mut cost = SwitchCost { default_cost, ..SwitchCost::default() };
+    for (index, switch) in switches.iter().enumerate() {
+        if !from_focus(switch) || to_focus(switch) {
+            continue;
+        }
+        let recovery = switches[index + 1..].iter()
+            .find(|later| to_focus(later))
+            .and_then(|back| back.recovery_time);
+        match recovery {
+            Some(recovery) => {
+                cost.total += recovery;
+                cost.measured += 1;
             }
-            
-            if session.duration < Duration::from_secs(1) {
-                // Skip sessions shorter than 1 second (likely noise)
-                continue;
+            None if include_unmeasured => {
+                cost.total += default_cost;
+                cost.assumed += 1;
             }
+            None => cost.skipped += 1,
+        }
+    }
+    cost
+}
 
-            if session.is_focus_app {
-                total_focus_time += session.duration;
-            } else {
-                total_distraction_time += session.duration;
-            }
+/// One top app's mean daily time over the tracked days of the same type before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AppTrend {
+    pub app: String,
+    pub average: Duration,
+}
 
-            *app_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
+/// Changes smaller than this, or than APP_TREND_RELATIVE of the average,
+/// show as "≈"
+const APP_TREND_MIN_CHANGE: Duration = Duration::from_secs(5 * 60);
+const APP_TREND_RELATIVE: f64 = 0.1;
+
+/// Today's time against the average, in seconds, and whether it counts
+/// as a change at all
+fn app_trend_delta(today: Duration, average: Duration) -> (i64, bool) {
+    let delta = today.as_secs() as i64 - average.as_secs() as i64;
+    let threshold = APP_TREND_MIN_CHANGE.as_secs_f64().max(average.as_secs_f64() * APP_TREND_RELATIVE);
+    (delta, delta.unsigned_abs() as f64 >= threshold)
+}
+
+/// "▲ +40m", "▼ −20m" or "≈"
+pub fn describe_app_trend(today: Duration, average: Duration) -> String {
+    let (delta, changed) = app_trend_delta(today, average);
+    // Whole minutes; seconds are noise here
+    let minutes = (delta.unsigned_abs() + 30) / 60;
+    let amount = if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        utils::format_duration(Duration::from_secs(minutes * 60), DurationStyle::Long)
+    };
+    match delta {
+        _ if !changed => "≈".to_string(),
+        d if d > 0 => format!("▲ +{}", amount),
+        _ => format!("▼ −{}", amount),
+    }
+}
+
+/// One browser's time on focus sites, on distraction (ignored) sites, and
+/// on tabs neither list matches or that weren't recorded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BrowserSites {
+    pub app: String,
+    pub focus: Duration,
+    pub distraction: Duration,
+    pub unclassified: Duration,
+}
+
+impl DailyStats {
+    /// `stats --by-group`: the stats with each group as one row of the top
+    /// apps, trends included. Unchanged without groups.
+    pub(crate) fn by_group(&self, settings: &StatsSettings) -> DailyStats {
+        let mut stats = self.clone();
+        if self.group_usage.is_empty() {
+            return stats;
         }
+        stats.most_used_apps = rollup_noise(
+            self.group_usage.iter().map(|(name, duration, _)| (name.clone(), *duration)).collect(),
+            5,
+            settings.min_display,
+            |(_, duration)| *duration,
+            |(name, _)| name,
+            |noise| (settings.noise_label(), noise),
+        );
+        stats.app_trends = self.group_trends.clone();
+        stats
+    }
 
-        let total_time = total_focus_time + total_distraction_time;
-        let focus_efficiency = if total_time > Duration::ZERO {
-            total_focus_time.as_secs_f64() / total_time.as_secs_f64() * 100.0
-        } else {
-            0.0
-        };
+    /// "on battery: 1h 12m, on AC: 3h 50m", battery first
+    pub(crate) fn describe_power_focus(&self) -> String {
+        self.power_focus.iter()
+            .rev()
+            .map(|(power, time)| format!("{}: {}", power.label(), utils::format_duration(*time, DurationStyle::Long)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 
-        // Sort apps by usage time and filter out minimal usage
-        let mut app_usage_vec: Vec<(String, Duration)> = app_usage.into_iter().collect();
-        app_usage_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    pub(crate) fn describe_assistant_time(&self) -> String {
+        describe_assistant_time(self.assistant_time, self.assistant_counts_as)
+    }
 
-        // Filter out apps with less than 10 seconds of usage
-        let min_usage_threshold = Duration::from_secs(10);
-        let most_used_apps = app_usage_vec.iter()
-            .filter(|(_, duration)| *duration >= min_usage_threshold)
-            .take(5)
-            .cloned()
-            .collect();
+    /// "42m after start; last focus ended 1h 10m before sign-off", or only
+    /// the first part for today; None when nothing was tracked
+    pub(crate) fn describe_focus_bookends(&self) -> Option<String> {
+        if (self.total_focus_time + self.total_distraction_time).is_zero() {
+            return None;
+        }
+        let Some(to_first) = self.time_to_first_focus else {
+            return Some(format!("no focus block of {} or more", utils::format_duration(FIRST_FOCUS_BLOCK, DurationStyle::Long)));
+        };
+        let first = match to_first.as_secs() / 60 {
+            0 => "right at the start".to_string(),
+            _ => format!("{} after start", utils::format_duration(to_first, DurationStyle::Long)),
+        };
+        match self.focus_wind_down {
+            Some(wind_down) if wind_down.as_secs() / 60 == 0 => Some(format!("{}; focus lasted until sign-off", first)),
+            Some(wind_down) => Some(format!("{}; last focus ended {} before sign-off", first, utils::format_duration(wind_down, DurationStyle::Long))),
+            None => Some(first),
+        }
+    }
 
-        // Filter distracting apps to only show meaningful usage
-        let most_distracting_filtered = most_distracting.iter()
-            .filter(|(_, duration)| *duration >= min_usage_threshold)
-            .cloned()
-            .collect();
+    pub fn app_trend(&self, app: &str) -> Option<&AppTrend> {
+        self.app_trends.iter().find(|trend| trend.app == app)
+    }
 
-        Ok(DailyStats {
-            date,
-            total_focus_time,
-            total_distraction_time,
-            context_switches: switches.len(),
-            deep_focus_sessions: deep_sessions.len(),
-            focus_efficiency,
-            most_used_apps,
-            most_distracting_apps: most_distracting_filtered,
-        })
+    pub fn browser_sites(&self, app: &str) -> Option<&BrowserSites> {
+        self.browser_sites.iter().find(|sites| sites.app == app)
     }
 
-    pub fn format_duration(duration: Duration) -> String {
-        let total_seconds = duration.as_secs();
-        let hours = total_seconds / 3600;
-        let minutes = (total_seconds % 3600) / 60;
-        let seconds = total_seconds % 60;
-        
-        if hours > 0 {
-            format!("{}h {}m", hours, minutes)
-        } else if minutes > 0 {
-            format!("{}m {}s", minutes, seconds)
-        } else {
-            format!("{}s", seconds)
+    /// Lines explaining an unusual day: a DST change, the timezone
+    /// changing while the daemon ran (e.g. travel), or an upgrade that
+    /// changed how time is counted
+    pub(crate) fn day_notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if let Some(length) = self.local_day_length.filter(|length| length.as_secs() != 24 * 60 * 60) {
+            // In hours even past 24 ("25h", not "1d 1h"); a few zones shift by half an hour
+            let minutes = length.as_secs() / 60;
+            let hours = match minutes % 60 {
+                0 => format!("{}h", minutes / 60),
+                rest => format!("{}h {}m", minutes / 60, rest),
+            };
+            notes.push(format!("DST transition: {} day", hours));
+        }
+        notes.extend(self.timezone_changes.iter().map(|change| format!("Timezone changed: {}", change)));
+        notes.extend(self.version_changes.iter().cloned());
+        if self.live {
+            notes.push("Live: includes the ongoing window and rows not saved yet".to_string());
         }
+        if let Some(power) = self.power_filter {
+            notes.push(format!("Only time tracked {}", power.label()));
+        }
+        notes
     }
+}
 
-    pub fn display_daily_stats(stats: &DailyStats) {
-        let today = Utc::now();
-        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
-        println!("\n{}\n", top_sep);
-        println!("DAILY FOCUS SUMMARY\n");
-        println!("{}\n", utils::format_datetime_local(today));
-        println!("Focus Time        : {:<30}\n", Self::format_duration(stats.total_focus_time));
-        println!("Distraction Time  : {:<30}\n", Self::format_duration(stats.total_distraction_time));
-        println!("Context Switches  : {:<30}\n", stats.context_switches);
-        println!("Focus Efficiency  : {:<30}\n", {
-            let bar_width = 30;
-            let filled = ((stats.focus_efficiency / 100.0) * bar_width as f64) as usize;
-            let empty = bar_width - filled;
-            format!("[{}{}] {:.0}%", "■".repeat(filled), "□".repeat(empty), stats.focus_efficiency)
+pub struct Stats;
+
+/// What reports are built with: the config's report settings and the
+/// running command's own options (--apply-current-rules, --power, ...).
+/// Reports take it as an argument, so nothing about them is decided once
+/// per process.
+#[derive(Debug, Clone)]
+pub struct StatsSettings {
+    /// Rows shorter than this are left out (min_record_seconds)
+    pub min_record: Duration,
+    /// Rows shorter than this are rolled into one `other` row
+    /// (min_display_seconds)
+    pub min_display: Duration,
+    /// See `merge_short_gaps` (merge_gap_seconds)
+    pub merge_gap: Duration,
+    /// See `round_trip_pairs` (round_trip_window_seconds)
+    pub round_trip_window: Duration,
+    /// What a switch away with nothing measured costs, and whether those
+    /// count at all (switch_cost_minutes, switch_cost_unmeasured)
+    pub switch_cost: Duration,
+    pub switch_cost_unmeasured: bool,
+    /// Browser tab sections in session reports (browser_tab_tracking isn't off)
+    pub tab_reports: bool,
+    /// The hourglass in share reports and session summaries
+    /// (show_ascii_art, --no-art)
+    pub ascii_art: bool,
+    /// (focus_sites, ignored_sites) browser time is split by
+    pub site_rules: Option<(Vec<String>, Vec<String>)>,
+    /// (assistant_sites, assistant_counts_as)
+    pub assistant_rules: Option<(Vec<String>, AssistantCountsAs)>,
+    pub app_groups: AppGroups,
+    pub co_focus_rules: Vec<CoFocusRule>,
+    pub score: ScoreConfig,
+    /// Rest days besides weekends, see `DayType`
+    pub holidays: HashSet<NaiveDate>,
+    /// Leave rest days out of combined averages
+    /// (exclude_weekends_from_averages)
+    pub exclude_rest_days: bool,
+    /// Aggregate on one thread (hidden --serial flag)
+    pub serial: bool,
+    /// Classify rows by this config's focus and ignore lists instead of
+    /// their stored flags (--apply-current-rules)
+    pub current_rules: Option<Config>,
+    /// Count only rows tracked on this source (--power)
+    pub power_filter: Option<PowerSource>,
+    /// Rows and switches the running daemon hasn't saved yet, its current
+    /// window among them, merged into what is read from the database
+    pub live_rows: Option<(Vec<FocusSession>, Vec<ContextSwitch>)>,
+}
+
+impl Default for StatsSettings {
+    fn default() -> Self {
+        StatsSettings {
+            min_record: Duration::from_secs(1),
+            min_display: Duration::from_secs(10),
+            merge_gap: Duration::from_secs(5),
+            round_trip_window: Duration::from_secs(60),
+            switch_cost: Duration::from_secs(2 * 60),
+            switch_cost_unmeasured: true,
+            tab_reports: true,
+            ascii_art: true,
+            site_rules: None,
+            assistant_rules: None,
+            app_groups: AppGroups::default(),
+            co_focus_rules: Vec::new(),
+            score: ScoreConfig::default(),
+            holidays: HashSet::new(),
+            exclude_rest_days: false,
+            serial: false,
+            current_rules: None,
+            power_filter: None,
+            live_rows: None,
+        }
+    }
+}
+
+impl StatsSettings {
+    /// The report settings `config` asks for. Groups and co_focus rules it
+    /// can't use are reported and left out.
+    pub fn from_config(config: &Config) -> Self {
+        let app_groups = config.app_groups().unwrap_or_else(|e| {
+            eprintln!("~=~ Ignoring app groups: {}", e);
+            AppGroups::default()
         });
-        if !stats.most_used_apps.is_empty() {
-            println!("TOP APPLICATIONS\n");
-            for (i, (app, duration)) in stats.most_used_apps.iter().take(5).enumerate() {
-                let app_display = if app.len() > 20 { format!("{}...", &app[..17]) } else { app.clone() };
-                println!("{}. {:<20} : {:<30}\n", i + 1, app_display, Self::format_duration(*duration));
-            }
+        let co_focus_rules = config.co_focus_rules().unwrap_or_else(|e| {
+            eprintln!("~=~ Ignoring co_focus rules: {}", e);
+            Vec::new()
+        });
+        StatsSettings {
+            min_record: Duration::from_secs(config.min_record_seconds),
+            min_display: Duration::from_secs(config.min_display_seconds),
+            merge_gap: Duration::from_secs(config.merge_gap_seconds),
+            round_trip_window: Duration::from_secs(config.round_trip_window_seconds),
+            switch_cost: Duration::from_secs(config.switch_cost_minutes * 60),
+            switch_cost_unmeasured: config.switch_cost_unmeasured,
+            tab_reports: config.browser_tab_tracking != tracking::BrowserTabTracking::Off,
+            ascii_art: config.show_ascii_art,
+            site_rules: Some((config.focus_sites.clone(), config.ignored_sites.clone())),
+            assistant_rules: Some((config.assistant_sites.clone(), config.assistant_counts_as)),
+            app_groups,
+            co_focus_rules,
+            score: config.score.clone(),
+            holidays: config.holiday_dates().into_iter().collect(),
+            exclude_rest_days: config.exclude_weekends_from_averages,
+            ..StatsSettings::default()
         }
-        println!("{}\n", top_sep);
     }
 
-    pub fn generate_ascii_report(stats: &DailyStats) -> String {
-        let mut report = String::new();
-        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
-        report.push_str("\n");
-        report.push_str(&format!("{}\n\n", top_sep));
-        report.push_str(&format!("~=~ FOCUSDEBT REPORT ~=~\n\n"));
-        report.push_str(&format!("{}\n\n", utils::format_datetime_local(stats.date)));
-        report.push_str(&format!("Focus Time      : {:<42}\n\n", Self::format_duration(stats.total_focus_time)));
-        report.push_str(&format!("Distraction     : {:<42}\n\n", Self::format_duration(stats.total_distraction_time)));
-        report.push_str(&format!("Context Switches: {:<42}\n\n", stats.context_switches));
-        report.push_str(&format!("Deep Sessions   : {:<42}\n\n", stats.deep_focus_sessions));
-        report.push_str(&format!("Focus Efficiency\n\n"));
-        let efficiency_bar_len = 30;
-        let efficiency_filled = ((stats.focus_efficiency / 100.0) * efficiency_bar_len as f64) as usize;
-        let efficiency_bar = format!("[{}{}] {:.0}%", 
-            "▓".repeat(efficiency_filled), 
-            "░".repeat(efficiency_bar_len - efficiency_filled),
-            stats.focus_efficiency);
-        report.push_str(&format!("   {:<58}\n\n", efficiency_bar));
-        if !stats.most_used_apps.is_empty() {
-            report.push_str("TOP APPLICATIONS\n\n");
-            let max_duration = stats.most_used_apps.first().map(|(_, d)| d.as_secs()).unwrap_or(1);
-            for (i, (app, duration)) in stats.most_used_apps.iter().take(4).enumerate() {
-                let app_display = if app.len() > 15 { format!("{}...", &app[..12]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let bar_len = 20;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let app_bar = format!("[{}{}]", "▓".repeat(filled), "░".repeat(bar_len - filled));
-                report.push_str(&format!("{}. {:<15} {} {:<12}\n\n", i + 1, app_display, app_bar, duration_str));
+    /// The configured groups, None without any
+    pub fn app_groups(&self) -> Option<&AppGroups> {
+        Some(&self.app_groups).filter(|groups| !groups.is_empty())
+    }
+
+    /// Name of the row that stands in for everything below min_display
+    pub fn noise_label(&self) -> String {
+        format!("other (<{} each)", utils::format_duration(self.min_display, DurationStyle::Compact))
+    }
+
+    fn use_parallel(&self, rows: usize) -> bool {
+        rows >= PARALLEL_ROW_THRESHOLD && !self.serial
+    }
+
+    /// `switching_cost` with the configured default and unmeasured setting
+    pub fn switching_cost<'a>(&self, switches: &[ContextSwitch], sessions: impl IntoIterator<Item = &'a FocusSession>) -> SwitchCost {
+        switching_cost(switches, sessions, self.switch_cost, self.switch_cost_unmeasured)
+    }
+
+    /// `SwitchCost` from totals the database summed up (see
+    /// `Database::get_switch_away_totals_between`), with the configured
+    /// default and unmeasured setting
+    pub fn switching_cost_from_totals(&self, switches_away: usize, measured: usize, measured_total: Duration) -> SwitchCost {
+        let unmeasured = switches_away.saturating_sub(measured);
+        let (assumed, skipped) = if self.switch_cost_unmeasured { (unmeasured, 0) } else { (0, unmeasured) };
+        SwitchCost {
+            total: measured_total + self.switch_cost * assumed as u32,
+            measured,
+            assumed,
+            skipped,
+            default_cost: self.switch_cost,
+        }
+    }
+
+    /// Browser rows on a focus site count as focus even when the browser
+    /// isn't a focus app or the row was recorded before the site was added
+    fn count_focus_sites(&self, mut session: FocusSession) -> FocusSession {
+        if site_class_with(&session, self.site_rules.as_ref()) == Some(SiteClass::Focus) {
+            session.is_focus_app = true;
+        }
+        session
+    }
+
+    /// With current rules set, drop ignored sessions and re-derive
+    /// is_focus_app
+    pub fn apply_current_rules(&self, mut session: FocusSession) -> Option<FocusSession> {
+        let Some(config) = &self.current_rules else {
+            return Some(session);
+        };
+        match config.classify_session(&session).0 {
+            SessionClass::Ignored => None,
+            class => {
+                session.is_focus_app = class == SessionClass::Focus;
+                Some(session)
             }
-        } else {
-            report.push_str("~=~ No significant application usage detected\n\n");
         }
-        report.push_str(&format!("{}\n\n", top_sep));
-        report
     }
 
-    pub fn generate_session_share_report(session: &AggregatedSession) -> String {
-        let mut report = String::new();
-        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
-        let start = utils::format_datetime_local(session.start_time);
-        let time_range = if let Some(end) = session.end_time {
-            format!("{} → {}", 
-                utils::format_timestamp_local(session.start_time), 
-                utils::format_timestamp_local(end))
+    /// `apply_current_rules` and `count_focus_sites`, as every report
+    /// counts a row
+    fn classify(&self, session: FocusSession) -> Option<FocusSession> {
+        self.apply_current_rules(session).map(|session| self.count_focus_sites(session))
+    }
+
+    /// With a power filter set, keep the rows tracked on that source and
+    /// the switches into them. Returns whether anything was filtered.
+    fn apply_power_filter(&self, records: &mut DayRecords) -> bool {
+        let Some(power) = self.power_filter else {
+            return false;
+        };
+        retain_power(records, power);
+        true
+    }
+
+    pub fn day_type(&self, day: NaiveDate) -> DayType {
+        let weekend = matches!(day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun);
+        if weekend || self.holidays.contains(&day) {
+            DayType::RestDay
         } else {
-            format!("{} → ongoing", utils::format_timestamp_local(session.start_time))
+            DayType::Workday
+        }
+    }
+
+    /// Whether days of `day_type` go into combined averages; rest days
+    /// don't with exclude_rest_days
+    pub fn counts_toward_averages(&self, day_type: DayType) -> bool {
+        day_type == DayType::Workday || !self.exclude_rest_days
+    }
+
+    /// The BASELINE_DAYS days of `day`'s type before it, oldest first
+    pub fn baseline_days(&self, day: NaiveDate) -> Vec<NaiveDate> {
+        let kind = self.day_type(day);
+        let mut days: Vec<NaiveDate> = (1..=BASELINE_LOOKBACK_DAYS as i64)
+            .map(|back| day - chrono::Duration::days(back))
+            .filter(|earlier| self.day_type(*earlier) == kind)
+            .take(BASELINE_DAYS)
+            .collect();
+        days.reverse();
+        days
+    }
+
+    /// A baseline for each day type among `days`, workdays first. Each day
+    /// is (date, focus, tracked, score); untracked days are skipped.
+    pub fn baselines(&self, days: impl IntoIterator<Item = (NaiveDate, Duration, Duration, Option<u32>)>) -> Vec<Baseline> {
+        let mut by_type: BTreeMap<bool, Vec<(Duration, Duration, Option<u32>)>> = BTreeMap::new();
+        for (date, focus, tracked, score) in days {
+            if !tracked.is_zero() {
+                by_type.entry(self.day_type(date) == DayType::RestDay).or_default().push((focus, tracked, score));
+            }
+        }
+        by_type.into_iter()
+            .map(|(rest, days)| {
+                let focus: Duration = days.iter().map(|(focus, _, _)| *focus).sum();
+                let tracked: Duration = days.iter().map(|(_, tracked, _)| *tracked).sum();
+                let scores: Vec<u32> = days.iter().filter_map(|(_, _, score)| *score).collect();
+                Baseline {
+                    day_type: if rest { DayType::RestDay } else { DayType::Workday },
+                    days: days.len(),
+                    focus_time: focus / days.len() as u32,
+                    efficiency: focus.as_secs_f64() / tracked.as_secs_f64().max(f64::EPSILON) * 100.0,
+                    focus_score: (!scores.is_empty()).then(|| scores.iter().sum::<u32>() as f64 / scores.len() as f64),
+                }
+            })
+            .collect()
+    }
+
+    /// `baselines` of daily stats
+    pub fn daily_baselines(&self, days: &[DailyStats]) -> Vec<Baseline> {
+        self.baselines(days.iter().map(|day| (
+            day.date.date_naive(),
+            day.total_focus_time,
+            day.total_focus_time + day.total_distraction_time,
+            day.focus_score,
+        )))
+    }
+
+    /// Whether live rows were merged into anything starting on `day`
+    fn has_live_rows(&self, days: &LocalDays, day: NaiveDate) -> bool {
+        self.live_rows.as_ref().is_some_and(|(sessions, _)| sessions.iter().any(|row| days.day_of(row.start_time) == day))
+    }
+
+    /// The database's rows for `day`, with live rows merged in
+    fn sessions_for_date(&self, db: &Database, days: &LocalDays, day: NaiveDate) -> rusqlite::Result<Vec<FocusSession>> {
+        let saved = db.get_sessions_for_date(days, day)?;
+        let Some((live, _)) = &self.live_rows else {
+            return Ok(saved);
         };
-        
-        // Calculate focus time from app usage
-        let focus_time: std::time::Duration = session.app_usage.iter()
-            .filter(|(_, _, is_focus)| *is_focus)
-            .map(|(_, duration, _)| *duration)
-            .sum();
-        
-        report.push_str("\n");
-        report.push_str(&format!("{}\n\n", top_sep));
-        report.push_str(&format!("~=~ SESSION REPORT ~=~\n\n"));
-                println!(
-r#"
+        let live: Vec<FocusSession> = live.iter().filter(|row| days.day_of(row.start_time) == day).cloned().collect();
+        Ok(merge_live_rows(saved, &live))
+    }
+
+    fn switches_for_date(&self, db: &Database, days: &LocalDays, day: NaiveDate) -> rusqlite::Result<Vec<ContextSwitch>> {
+        let saved = db.get_context_switches_for_date(days, day)?;
+        let Some((_, live)) = &self.live_rows else {
+            return Ok(saved);
+        };
+        let live: Vec<ContextSwitch> = live.iter().filter(|switch| days.day_of(switch.timestamp) == day).cloned().collect();
+        Ok(merge_live_switches(saved, &live))
+    }
+}
+
+/// Everything one day's stats are computed from, loaded up front so
+/// days can be aggregated away from the database connection
+#[derive(Debug, Clone, Default)]
+pub struct DayRecords {
+    pub sessions: Vec<FocusSession>,
+    pub switches: Vec<ContextSwitch>,
+    pub daemon_stops: Vec<DateTime<Utc>>,
+    pub timezone_changes: Vec<TrackerEvent>,
+    /// Pause and resume events, in time order
+    pub pauses: Vec<TrackerEvent>,
+    /// Suspends that overlap the day, in time order
+    pub suspends: Vec<SuspendGap>,
+}
+
+/// A gap between two daemon checks long enough that the machine must have
+/// been asleep, or the clock jumped ahead. Its time is no one's: it is
+/// taken out of rows spanning it and isn't untracked either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SuspendGap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl SuspendGap {
+    /// From a suspend event; None when its `until` is missing or not after it
+    pub(crate) fn from_event(event: &TrackerEvent) -> Option<Self> {
+        let until = event.payload["until"].as_str()
+            .and_then(|until| DateTime::parse_from_rfc3339(until).ok())?
+            .with_timezone(&Utc);
+        (until > event.timestamp).then_some(Self { start: event.timestamp, end: until })
+    }
+
+    /// The event the daemon records, stamped at the last check before it
+    pub(crate) fn to_event(self) -> TrackerEvent {
+        TrackerEvent {
+            timestamp: self.start,
+            kind: EventKind::Suspend,
+            payload: serde_json::json!({
+                "until": self.end.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+                "seconds": self.duration().as_secs(),
+            }),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        (self.end - self.start).to_std().unwrap_or_default()
+    }
+
+    /// "suspended 12:31–13:05, excluded 34m"
+    pub(crate) fn describe(&self) -> String {
+        format!("suspended {}–{}, excluded {}",
+            utils::format_timestamp_local(self.start),
+            utils::format_timestamp_local(self.end),
+            utils::format_duration(self.duration(), DurationStyle::Long))
+    }
+}
+
+/// The longest suspend looked for before a range, so one that began
+/// before it and ended inside is still found
+const SUSPEND_LOOKBACK: chrono::Duration = chrono::Duration::days(7);
+
+/// Suspends overlapping [start, end), in time order
+pub fn suspend_gaps(db: &Database, start: DateTime<Utc>, end: DateTime<Utc>) -> rusqlite::Result<Vec<SuspendGap>> {
+    Ok(db.get_events_between(start - SUSPEND_LOOKBACK, end, Some(EventKind::Suspend))?
+        .iter()
+        .filter_map(SuspendGap::from_event)
+        .filter(|gap| gap.start < end && gap.end > start)
+        .collect())
+}
+
+fn suspend_periods(suspends: &[SuspendGap]) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    merge_periods(suspends.iter().map(|gap| (gap.start, gap.end)).collect())
+}
+
+/// `session` without the time the machine was suspended. Rows the daemon
+/// ended at the suspend don't change; this catches ones written across
+/// it, e.g. by a daemon that didn't notice.
+fn without_suspends(session: &FocusSession, suspends: &[(DateTime<Utc>, DateTime<Utc>)]) -> FocusSession {
+    let end = session.end_time
+        .unwrap_or_else(|| session.start_time + chrono::Duration::from_std(session.duration).unwrap_or_default());
+    let suspended = overlap(session.start_time, end, suspends);
+    FocusSession {
+        duration: session.duration.saturating_sub(suspended),
+        ..session.clone()
+    }
+}
+
+/// `periods` sorted and with overlapping ones joined, so `overlap` counts
+/// no time twice
+fn merge_periods(mut periods: Vec<(DateTime<Utc>, DateTime<Utc>)>) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    periods.sort();
+    let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+    for (start, end) in periods {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Pause and resume events in [start, end), in time order
+fn pause_events(db: &Database, start: DateTime<Utc>, end: DateTime<Utc>) -> rusqlite::Result<Vec<TrackerEvent>> {
+    let mut events = db.get_events_between(start, end, Some(EventKind::Pause))?;
+    events.extend(db.get_events_between(start, end, Some(EventKind::Resume))?);
+    events.sort_by_key(|event| event.timestamp);
+    Ok(events)
+}
+
+/// How much of [start, end) the `periods` cover; they must not overlap
+fn overlap(start: DateTime<Utc>, end: DateTime<Utc>, periods: &[(DateTime<Utc>, DateTime<Utc>)]) -> Duration {
+    periods.iter()
+        .map(|&(from, to)| (to.min(end) - from.max(start)).to_std().unwrap_or_default())
+        .sum()
+}
+
+/// `reason` of the pause and resume events written for quiet_hours
+pub const QUIET_HOURS_REASON: &str = "quiet_hours";
+
+/// Stretches of [day_start, day_end) tracking was paused for quiet hours,
+/// from the day's pause and resume events. A day that starts with a resume
+/// was paused since before it began; a pause still open at a daemon stop
+/// ends there, otherwise at `day_end`.
+pub fn quiet_periods(
+    pauses: &[TrackerEvent],
+    daemon_stops: &[DateTime<Utc>],
+    day_start: DateTime<Utc>,
+    day_end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut marks: Vec<(DateTime<Utc>, Option<EventKind>)> = pauses.iter()
+        .filter(|event| event.payload["reason"].as_str() == Some(QUIET_HOURS_REASON))
+        .map(|event| (event.timestamp, Some(event.kind)))
+        .chain(daemon_stops.iter().map(|&stop| (stop, None)))
+        .collect();
+    marks.sort_by_key(|(at, _)| *at);
+
+    let mut periods = Vec::new();
+    let mut paused_since = None;
+    let mut first = true;
+    for (at, kind) in marks {
+        match kind {
+            Some(EventKind::Pause) => {
+                paused_since.get_or_insert(at);
+            }
+            // A stop ends a pause but doesn't open one
+            Some(EventKind::Resume) | None => {
+                if let Some(since) = paused_since.take().or((first && kind.is_some()).then_some(day_start)) {
+                    periods.push((since.max(day_start), at.min(day_end)));
+                }
+            }
+            Some(_) => {}
+        }
+        first = false;
+    }
+    if let Some(since) = paused_since {
+        periods.push((since.max(day_start), day_end));
+    }
+    periods.retain(|(start, end)| start < end);
+    periods
+}
+
+/// Gaps shorter than this between sessions are ordinary pauses, not lost time
+pub const UNTRACKED_GAP_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+
+/// A stretch with no recorded session, e.g. the daemon was stopped
+/// overnight or window detection failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UntrackedGap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// A daemon_stop event was logged at the start of the gap
+    pub daemon_stopped: bool,
+}
+
+impl UntrackedGap {
+    pub fn duration(&self) -> Duration {
+        self.end.signed_duration_since(self.start).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+const HOURGLASS_ART: &str = r#"
 ----------          -^-       
 ----------         / * \     
 ----------        / < > \    
@@ -222,414 +914,3772 @@ r#"
 ----------           =I=    
 ----------          --O--   
 ----------           -i-    
-----------            o
-"#
-);
-        report.push_str(&format!("Session: {}\n\n", session.session_name));
-        report.push_str(&format!("Time: {}\n\n", time_range));
-        report.push_str(&format!("Focus Time: {}\n\n", Self::format_duration(focus_time)));
-        report.push_str(&format!("Focus Efficiency: {:.0}%\n\n", session.focus_efficiency));
-        
-        // Separate browser apps from regular apps
-        let (browser_apps, regular_apps): (Vec<_>, Vec<_>) = session.app_usage.iter()
-            .partition(|(app, _, _)| Self::is_browser_app(app));
-        
-        // Show regular applications (non-browser)
-        if !regular_apps.is_empty() {
-            report.push_str("TOP APPLICATIONS\n\n");
-            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-            for (i, (app, duration, _)) in regular_apps.iter().take(5).enumerate() {
-                let app_display = if app.len() > 15 { format!("{}...", &app[..12]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let bar_len = 20;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let app_bar = format!("[{}{}]", "▓".repeat(filled), "░".repeat(bar_len - filled));
-                report.push_str(&format!("{}. {:<15} {} {:<12}\n\n", i + 1, app_display, app_bar, duration_str));
-            }
-        }
-        
-        // Show browser apps grouped together (no individual tabs)
-        if !browser_apps.is_empty() {
-            report.push_str("BROWSER APPLICATIONS\n\n");
-            let max_duration = browser_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-            for (i, (app, duration, _)) in browser_apps.iter().take(5).enumerate() {
-                let app_display = if app.len() > 15 { format!("{}...", &app[..12]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let bar_len = 20;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let app_bar = format!("[{}{}]", "▓".repeat(filled), "░".repeat(bar_len - filled));
-                report.push_str(&format!("{}. {:<15} {} {:<12}\n\n", i + 1, app_display, app_bar, duration_str));
-            }
+----------            o"#;
+
+/// (name, duration, is_group) rows from per-app times: apps in a group
+/// add up under the group's name, the rest stay as they are. Longest
+/// first; the database's per-app rows are never touched.
+pub fn group_app_usage(usage: impl IntoIterator<Item = (String, Duration)>, groups: &AppGroups) -> Vec<(String, Duration, bool)> {
+    let mut rows: BTreeMap<(String, bool), Duration> = BTreeMap::new();
+    for (app, duration) in usage {
+        let key = match groups.group_of(&app) {
+            Some(group) => (group.to_string(), true),
+            None => (app, false),
+        };
+        *rows.entry(key).or_insert(Duration::ZERO) += duration;
+    }
+    let mut rows: Vec<(String, Duration, bool)> = rows.into_iter()
+        .map(|((name, is_group), duration)| (name, duration, is_group))
+        .collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
+
+/// `group_app_usage` with apps outside any group under their display name
+fn grouped_display_usage(usage: impl IntoIterator<Item = (String, Duration)>, groups: &AppGroups) -> Vec<(String, Duration, bool)> {
+    let mut rows: Vec<(String, Duration, bool)> = Vec::new();
+    for (name, duration, is_group) in group_app_usage(usage, groups) {
+        let name = if is_group { name } else { desktop::display_name(&name) };
+        match rows.iter_mut().find(|(other, _, other_is_group)| *other == name && *other_is_group == is_group) {
+            Some(row) => row.1 += duration,
+            None => rows.push((name, duration, is_group)),
         }
-        report.push_str(&format!("{}\n\n", top_sep));
-        report
     }
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
 
-    pub fn list_sessions(db: &Database, _last: Option<usize>, _date: Option<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut sessions = Vec::new();
-        
-        // Get all sessions from last 30 days
-        let mut all_sessions = Vec::new();
-        for days_ago in 0..30 {
-            let dt = Utc::now() - chrono::Duration::days(days_ago);
-            let day_sessions = db.get_sessions_for_date(dt)?;
-            all_sessions.extend(day_sessions);
-        }
-        
-        // Group by session name and aggregate
-        let aggregated = Self::aggregate_sessions_by_name(&all_sessions);
-        let take_n = 20; // Show last 20 sessions
-        for (i, session) in aggregated.iter().take(take_n).enumerate() {
-            sessions.push(Self::format_session_summary(i + 1, session));
-        }
-        Ok(sessions)
+/// "1h 12m, counted as focus" or "40m, neutral"
+pub fn describe_assistant_time(time: Duration, counts_as: AssistantCountsAs) -> String {
+    let time = utils::format_duration(time, DurationStyle::Long);
+    match counts_as {
+        AssistantCountsAs::Neutral => format!("{}, neutral", time),
+        counts_as => format!("{}, counted as {}", time, counts_as.label()),
     }
+}
 
-    pub fn show_session_details(db: &Database, query: &str) -> Result<String, Box<dyn std::error::Error>> {
-        // Get all sessions from last 30 days
-        let mut all_sessions = Vec::new();
-        for days_ago in 0..30 {
-            let dt = Utc::now() - chrono::Duration::days(days_ago);
-            let day_sessions = db.get_sessions_for_date(dt)?;
-            all_sessions.extend(day_sessions);
-        }
-        
-        // Group by session name
-        let aggregated = Self::aggregate_sessions_by_name(&all_sessions);
-        
-        // Search by session name (case-insensitive)
-        for session in aggregated {
-            if session.session_name.eq_ignore_ascii_case(query) {
-                return Ok(Self::format_session_report(&session));
+/// Whether a row is time in an AI assistant: a browser's tab, or any other
+/// app's window title, matches assistant_sites
+fn matches_assistant_sites(session: &FocusSession, sites: &[String]) -> bool {
+    let text = session.domain.as_deref().unwrap_or(&session.window_title);
+    sites.iter().any(|site| utils::title_matches_site(text, site))
+}
+
+/// Which site list a browser row's tab matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteClass {
+    Focus,
+    Distraction,
+    Unclassified,
+}
+
+/// How the (focus_sites, ignored_sites) `rules` see a browser row; None for
+/// other apps. A tab on both lists is a focus site, as the tracker has it.
+fn site_class_with(session: &FocusSession, rules: Option<&(Vec<String>, Vec<String>)>) -> Option<SiteClass> {
+    if !Stats::is_browser_app(&session.app_name) {
+        return None;
+    }
+    let (focus_sites, ignored_sites) = rules?;
+    let matches = |sites: &[String]| session.domain.as_deref()
+        .is_some_and(|tab| sites.iter().any(|site| utils::title_matches_site(tab, site)));
+    Some(if matches(focus_sites) {
+        SiteClass::Focus
+    } else if matches(ignored_sites) {
+        SiteClass::Distraction
+    } else {
+        SiteClass::Unclassified
+    })
+}
+
+/// Join rows of the same session, app, window title, tab, workspace,
+/// focus flag and co_focus label that follow each other with less than
+/// `max_gap` in between (zero turns merging off), so a detection hiccup of
+/// a tick doesn't split one stretch of work into several. Durations are
+/// summed and the merged row spans from the first start to the last end.
+/// Rows come back in start order.
+pub fn merge_short_gaps(mut sessions: Vec<FocusSession>, max_gap: Duration) -> Vec<FocusSession> {
+    sessions.sort_by_key(|session| session.start_time);
+    if max_gap.is_zero() {
+        return sessions;
+    }
+    let max_gap = chrono::Duration::from_std(max_gap).unwrap_or(chrono::Duration::MAX);
+
+    let mut merged: Vec<FocusSession> = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        if let Some(previous) = merged.last_mut() {
+            let continues = previous.end_time.is_some_and(|end| {
+                let gap = session.start_time - end;
+                gap >= chrono::Duration::zero() && gap < max_gap
+            });
+            if continues
+                && previous.session_name == session.session_name
+                && previous.app_name == session.app_name
+                && previous.window_title == session.window_title
+                && previous.domain == session.domain
+                && previous.workspace == session.workspace
+                && previous.is_focus_app == session.is_focus_app
+                && previous.open_end == session.open_end
+                && previous.manual == session.manual
+                && previous.co_focus == session.co_focus
+            {
+                // Activity is a per-minute average, so weigh it by duration
+                previous.activity = match (previous.activity, session.activity) {
+                    (Some(a), Some(b)) => {
+                        let (da, db) = (previous.duration.as_secs_f64(), session.duration.as_secs_f64());
+                        Some(if da + db > 0.0 { (a * da + b * db) / (da + db) } else { a })
+                    }
+                    (a, b) => a.or(b),
+                };
+                previous.passive = match (previous.passive, session.passive) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+                previous.duration += session.duration;
+                // A live row without an end keeps the merged row open
+                previous.end_time = session.end_time.and(previous.end_time.max(session.end_time));
+                continue;
             }
         }
-        
-        Err(format!("❌ Session not found: {}", query).into())
+        merged.push(session);
     }
+    merged
+}
 
-    fn aggregate_sessions_by_name(sessions: &[FocusSession]) -> Vec<AggregatedSession> {
-        use std::collections::HashMap;
-        
-        let mut session_groups: HashMap<String, Vec<&FocusSession>> = HashMap::new();
-        
-        for session in sessions {
-            let session_key = if session.session_name.is_empty() {
-                // Create unique keys for each app session instead of grouping all as "unnamed"
-                format!("{}_{}", session.app_name, session.start_time.timestamp())
-            } else {
-                session.session_name.clone()
-            };
-            session_groups.entry(session_key)
-                .or_insert_with(Vec::new)
-                .push(session);
-        }
-        
-        let mut aggregated = Vec::new();
-        for (name, group_sessions) in session_groups {
-            if group_sessions.is_empty() { continue; }
-            
-            let start_time = group_sessions.iter().map(|s| s.start_time).min().unwrap();
-            let end_time = group_sessions.iter()
-                .filter_map(|s| s.end_time)
-                .max();
-            
-            // Calculate total duration from actual start and end times, not sum of individual durations
-            let total_duration = if let Some(end_time) = end_time {
-                end_time.signed_duration_since(start_time).to_std().unwrap_or(Duration::ZERO)
-            } else {
-                // If no end time, sum individual durations as fallback
-                group_sessions.iter().map(|s| s.duration).sum()
-            };
-            
-            let focus_time: Duration = group_sessions.iter()
-                .filter(|s| s.is_focus_app)
-                .map(|s| s.duration)
-                .sum();
-            
-            let focus_efficiency = if total_duration > Duration::ZERO {
-                (focus_time.as_secs_f64() / total_duration.as_secs_f64()) * 100.0
-            } else {
-                0.0
-            };
-            
-            // Collect unique apps with their total usage
-            let mut app_usage: HashMap<String, Duration> = HashMap::new();
-            let mut domain_usage: HashMap<String, Duration> = HashMap::new();
-            
+/// Session rows above which day batches and session groups are aggregated
+/// on the rayon pool. Smaller inputs aren't worth the thread handoff.
+const PARALLEL_ROW_THRESHOLD: usize = 20_000;
 
-            
-            for session in &group_sessions {
-                *app_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
-                
+/// Which baseline a day is measured against: weekends and the configured
+/// holidays are rest days, every other day a workday
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DayType {
+    Workday,
+    RestDay,
+}
 
-                
-                // Also collect domain usage if available
-                if let Some(domain) = &session.domain {
-                    *domain_usage.entry(domain.clone()).or_insert(Duration::ZERO) += session.duration;
-                }
-            }
-            
+impl DayType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DayType::Workday => "Workdays",
+            DayType::RestDay => "Rest days",
+        }
+    }
 
-            
-            let mut app_list: Vec<(String, Duration, bool)> = app_usage.into_iter()
-                .map(|(app, duration)| {
-                    let is_focus = group_sessions.iter()
-                        .any(|s| s.app_name == app && s.is_focus_app);
-                    (app, duration, is_focus)
-                })
-                .collect();
-            app_list.sort_by(|a, b| b.1.cmp(&a.1));
-            
-            let mut domain_list: Vec<(String, Duration, bool)> = domain_usage.into_iter()
-                .map(|(tab_name, duration)| {
-                    // Check if any session with this tab name was marked as focus
-                    let is_focus = group_sessions.iter()
-                        .any(|s| s.domain.as_ref() == Some(&tab_name) && s.is_focus_app);
-                    (tab_name, duration, is_focus)
-                })
-                .collect();
-            domain_list.sort_by(|a, b| b.1.cmp(&a.1));
-            
-            aggregated.push(AggregatedSession {
-                session_name: name,
-                start_time,
-                end_time,
-                total_duration,
-                focus_efficiency,
-                app_usage: app_list,
-                domain_usage: domain_list,
-                context_switches: group_sessions.len().saturating_sub(1), // Rough estimate
-            });
+    /// What a day is compared against in trends, e.g. "workday avg"
+    pub fn average_label(&self) -> &'static str {
+        match self {
+            DayType::Workday => "workday avg",
+            DayType::RestDay => "weekend avg",
         }
-        
-        // Sort by start time (newest first)
-        aggregated.sort_by(|a, b| b.start_time.cmp(&a.start_time));
-        aggregated
     }
+}
 
-    fn format_session_summary(idx: usize, s: &AggregatedSession) -> String {
-        let start = utils::format_datetime_local(s.start_time);
-        let end = s.end_time.map(|t| utils::format_timestamp_local(t)).unwrap_or("--".to_string());
-        let duration = Self::format_duration(s.total_duration);
-        let focus_percent = format!("{:.0}%", s.focus_efficiency);
-        
-        // Format with proper spacing to match example
-        let time_range = format!("{}-{}", start, end);
-        format!("{}. \"{}\"        {}  {}  Focus: {}", 
-            idx, s.session_name, time_range, duration, focus_percent)
+/// Days of the same type a day's trends are measured against
+pub const BASELINE_DAYS: usize = 7;
+/// How far back those are looked for; five weeks hold ten weekend days
+pub const BASELINE_LOOKBACK_DAYS: usize = 35;
+
+/// One day type's daily means over the tracked days of a range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Baseline {
+    pub day_type: DayType,
+    pub days: usize,
+    pub focus_time: Duration,
+    pub efficiency: f64,
+    /// None when none of the days has a score
+    pub focus_score: Option<f64>,
+}
+
+impl Baseline {
+    /// "5 days: score 72, 65% efficiency, 4h 10m focus a day"
+    pub fn describe(&self) -> String {
+        let score = self.focus_score.map(|score| format!("score {:.0}, ", score)).unwrap_or_default();
+        format!("{} day{}: {}{:.0}% efficiency, {} focus a day",
+            self.days,
+            if self.days == 1 { "" } else { "s" },
+            score,
+            self.efficiency,
+            utils::format_duration(self.focus_time, DurationStyle::Long))
     }
+}
 
-    fn format_session_report(s: &AggregatedSession) -> String {
-        let start = utils::format_datetime_local(s.start_time);
-        let end = s.end_time.map(|t| utils::format_timestamp_local(t)).unwrap_or("ongoing".to_string());
-        let duration = Self::format_duration(s.total_duration);
-        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
-        let bar_width = 30;
-        let filled = ((s.focus_efficiency / 100.0) * bar_width as f64) as usize;
-        let empty = bar_width - filled;
-        let efficiency_bar = format!("[{}{}] {:.0}%", 
-            "▓".repeat(filled), 
-            "░".repeat(empty), 
-            s.focus_efficiency);
-        let mut report = String::new();
-        report.push_str("\n");
-        report.push_str(&format!("{}\n\n", top_sep));
-        report.push_str("~=~ SESSION DETAILS ~=~\n\n");
-        report.push_str(&format!("Name:       {:<48}\n\n", if s.session_name.len() > 48 {
-            format!("{}...", &s.session_name[..45])
-        } else {
-            s.session_name.clone()
-        }));
-        let time_line = format!("Duration:  {} → {} ({})", start, end, duration);
-        report.push_str(&format!("{}\n\n", time_line));
-        report.push_str(&format!("Efficiency: {:<48}\n\n", efficiency_bar));
-        report.push_str(&format!("Switches:   {:<48}\n\n", s.context_switches));
-        // Separate browser apps from regular apps
-        let (browser_apps, regular_apps): (Vec<_>, Vec<_>) = s.app_usage.iter()
-            .partition(|(app, _, _)| Self::is_browser_app(app));
-        
-        // Show regular applications (non-browser)
-        if !regular_apps.is_empty() {
-            report.push_str("~=~ APPLICATION BREAKDOWN ~=~\n\n");
-            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-            for (i, (app, duration, is_focus)) in regular_apps.iter().take(6).enumerate() {
-                let app_display = if app.len() > 20 { format!("{}...", &app[..17]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let focus_text = if *is_focus { "Focus" } else { "Other" };
-                let bar_len = 20;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let usage_bar = format!("[{}{}]", "■".repeat(filled), "□".repeat(bar_len - filled));
-                let app_line = format!("{:<20} {} {:<10} ({:<5})", app_display, usage_bar, duration_str, focus_text);
-                report.push_str(&format!("{}\n\n", app_line));
-            }
+
+/// Keep the rows tracked on `power` and the switches into them
+fn retain_power(records: &mut DayRecords, power: PowerSource) {
+    records.sessions.retain(|session| session.power == Some(power));
+    let sessions = &records.sessions;
+    records.switches.retain(|switch| sessions.iter().any(|session| {
+        switch.timestamp >= session.start_time && session.end_time.is_none_or(|end| switch.timestamp <= end)
+    }));
+}
+
+/// `saved` plus the `live` rows it doesn't have yet. A row flushed between
+/// the snapshot and the database read is in both; rows are the same when
+/// start second (the precision the database keeps) and app match, and the
+/// saved one wins.
+pub fn merge_live_rows(mut saved: Vec<FocusSession>, live: &[FocusSession]) -> Vec<FocusSession> {
+    let keys: std::collections::HashSet<(i64, String)> = saved.iter()
+        .map(|row| (row.start_time.timestamp(), row.app_name.clone()))
+        .collect();
+    saved.extend(live.iter().filter(|row| !keys.contains(&(row.start_time.timestamp(), row.app_name.clone()))).cloned());
+    saved.sort_by_key(|row| row.start_time);
+    saved
+}
+
+/// `saved` plus the `live` switches it doesn't have yet, matched on time
+/// (to the second) and the app switched to
+pub fn merge_live_switches(mut saved: Vec<ContextSwitch>, live: &[ContextSwitch]) -> Vec<ContextSwitch> {
+    let keys: std::collections::HashSet<(i64, String)> = saved.iter()
+        .map(|switch| (switch.timestamp.timestamp(), switch.to_app.clone()))
+        .collect();
+    saved.extend(live.iter().filter(|switch| !keys.contains(&(switch.timestamp.timestamp(), switch.to_app.clone()))).cloned());
+    saved.sort_by_key(|switch| switch.timestamp);
+    saved
+}
+
+/// What a focus score is computed from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreInputs {
+    /// Focus efficiency in percent
+    pub efficiency: f64,
+    pub context_switches: usize,
+    pub tracked: Duration,
+    pub longest_block: Duration,
+}
+
+/// FocusScore, 0–100:
+///
+///   100 × (we·E + ws·S + wb·B) / (we + ws + wb)
+///
+/// - E = efficiency / 100
+/// - S = 1 / (1 + switches per hour / switch_rate_reference), so switching
+///   at the reference rate earns half and never switching earns all of it
+/// - B = min(1, longest focus block / target_block_minutes)
+///
+/// with the weights w from [score]. None when nothing was tracked or every
+/// weight is zero.
+pub fn focus_score(inputs: &ScoreInputs, config: &ScoreConfig) -> Option<u32> {
+    let weights = [config.efficiency_weight, config.switch_weight, config.block_weight].map(|w| w.max(0.0));
+    let weight_sum: f64 = weights.iter().sum();
+    if inputs.tracked.is_zero() || weight_sum <= 0.0 {
+        return None;
+    }
+
+    let efficiency = (inputs.efficiency / 100.0).clamp(0.0, 1.0);
+    let switches_per_hour = inputs.context_switches as f64 / (inputs.tracked.as_secs_f64() / 3600.0);
+    let switches = 1.0 / (1.0 + switches_per_hour / config.switch_rate_reference.max(f64::EPSILON));
+    let target_block = Duration::from_secs(config.target_block_minutes.max(1) * 60);
+    let block = (inputs.longest_block.as_secs_f64() / target_block.as_secs_f64()).min(1.0);
+
+    let weighted = weights[0] * efficiency + weights[1] * switches + weights[2] * block;
+    Some((100.0 * weighted / weight_sum).round() as u32)
+}
+
+/// A run of focus rows, see `focus_blocks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FocusBlock {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Time in the run's focus rows, without the blips between them
+    pub focus: Duration,
+}
+
+/// Where a row ends; open rows end their duration after they started
+fn row_end(session: &FocusSession) -> DateTime<Utc> {
+    session.end_time.unwrap_or_else(|| {
+        session.start_time + chrono::Duration::from_std(session.duration).unwrap_or_default()
+    })
+}
+
+/// Runs of focus rows in time order. Gaps and non-focus rows end a run,
+/// except blips shorter than `min_display`.
+pub fn focus_blocks<'a>(sessions: impl IntoIterator<Item = &'a FocusSession>, min_display: Duration) -> Vec<FocusBlock> {
+    let mut rows: Vec<&FocusSession> = sessions.into_iter().collect();
+    rows.sort_by_key(|session| session.start_time);
+
+    let tolerance = chrono::Duration::from_std(min_display).unwrap_or_default();
+    let mut blocks = Vec::new();
+    let mut current: Option<FocusBlock> = None;
+    let mut previous_end: Option<DateTime<Utc>> = None;
+    for session in rows {
+        // A corrupt open row's length is a guess, so it ends the block
+        if session.is_corrupt() {
+            blocks.extend(current.take());
+            previous_end = None;
+            continue;
         }
-        
-        // Show browser apps grouped together (no individual tabs)
-        if !browser_apps.is_empty() {
-            report.push_str("~=~ BROWSER APPLICATIONS ~=~\n");
-            let max_duration = browser_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-            for (app, duration, is_focus) in browser_apps.iter().take(6) {
-                let app_display = if app.len() > 18 { format!("{}...", &app[..15]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let focus_text = if *is_focus { "Focus" } else { "Other" };
-                let bar_len = 15;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let usage_bar = format!("[{}{}]", "■".repeat(filled), "□".repeat(bar_len - filled));
-                let app_line = format!("{:<18} {} {:<8} ({:<5})", app_display, usage_bar, duration_str, focus_text);
-                report.push_str(&format!("{}\n\n", app_line));
-            }
+        let end = row_end(session);
+        let gap_too_long = previous_end.is_some_and(|previous| session.start_time - previous > tolerance);
+        if gap_too_long {
+            blocks.extend(current.take());
         }
-        
-        // Show browser tabs individually (for session details)
-        if !s.domain_usage.is_empty() {
-            // Group browser tabs by browser name
-            let mut browser_tab_map: std::collections::BTreeMap<String, Vec<(String, std::time::Duration, bool)>> = std::collections::BTreeMap::new();
-            for (tab_name, duration, is_focus) in &s.domain_usage {
-                // Group by browser based on tab name suffix
-                let browser = if tab_name.to_lowercase().contains("chrome") {
-                    "CHROME TABS"
-                } else if tab_name.to_lowercase().contains("brave") {
-                    "BRAVE TABS"
-                } else if tab_name.to_lowercase().contains("firefox") {
-                    "FIREFOX TABS"
-                } else if tab_name.to_lowercase().contains("safari") {
-                    "SAFARI TABS"
-                } else if tab_name.to_lowercase().contains("edge") {
-                    "EDGE TABS"
-                } else if tab_name.to_lowercase().contains("opera") {
-                    "OPERA TABS"
-                } else if tab_name.to_lowercase().contains("vivaldi") {
-                    "VIVALDI TABS"
-                } else {
-                    "BROWSER TABS"
-                };
-                browser_tab_map.entry(browser.to_string()).or_default().push((tab_name.clone(), *duration, *is_focus));
-            }
-            for (browser, tabs) in browser_tab_map {
-                report.push_str(&format!("~=~ {} (TOP 5) ~=~\n\n", browser));
-                let max_duration = tabs.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-                for (tab_name, duration, is_focus) in tabs.iter().take(5) {
-                    let tab_display = if tab_name.len() > 30 { format!("{}...", &tab_name[..27]) } else { tab_name.clone() };
-                    let duration_str = Self::format_duration(*duration);
-                    let focus_text = if *is_focus { "Focus" } else { "Other" };
-                    let bar_len = 15;
-                    let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                    let usage_bar = format!("[{}{}]", "■".repeat(filled), "□".repeat(bar_len - filled));
-                    let tab_line = format!("{:<30} {} {:<8} ({:<5})", tab_display, usage_bar, duration_str, focus_text);
-                    report.push_str(&format!("{}\n\n", tab_line));
+        if session.is_focus_app {
+            match &mut current {
+                Some(block) => {
+                    block.focus += session.duration;
+                    block.end = block.end.max(end);
                 }
+                None => current = Some(FocusBlock { start: session.start_time, end, focus: session.duration }),
             }
+            previous_end = Some(end);
+        } else if session.duration >= min_display {
+            blocks.extend(current.take());
+            previous_end = None;
         }
-        report.push_str(&format!("{}\n\n", top_sep));
-        report
+        // Short distractions are skipped without ending the block
     }
+    blocks.extend(current);
+    blocks
+}
 
-    pub fn calculate_session_stats(db: &Database, session_name: &str) -> Result<AggregatedSession, Box<dyn std::error::Error>> {
-        // Get all sessions from last 30 days
-        let mut all_sessions = Vec::new();
-        for days_ago in 0..30 {
-            let dt = Utc::now() - chrono::Duration::days(days_ago);
-            let day_sessions = db.get_sessions_for_date(dt)?;
-            all_sessions.extend(day_sessions);
-        }
-        
-        // Filter sessions by the specific session name
-        let session_sessions: Vec<FocusSession> = all_sessions
-            .into_iter()
-            .filter(|s| s.session_name.eq_ignore_ascii_case(session_name))
-            .collect();
-        
+/// Longest run of focus rows, see `focus_blocks`
+pub fn longest_focus_block<'a>(sessions: impl IntoIterator<Item = &'a FocusSession>, min_display: Duration) -> Duration {
+    focus_blocks(sessions, min_display).iter().map(|block| block.focus).max().unwrap_or_default()
+}
 
-        
-        if session_sessions.is_empty() {
-            return Err(format!("❌ No sessions found with name: {}", session_name).into());
-        }
-        
-        // Aggregate the sessions
-        let aggregated = Self::aggregate_sessions_by_name(&session_sessions);
-        
-        // Return the first (and should be only) aggregated session
-        if let Some(session) = aggregated.first() {
-            Ok(session.clone())
+/// Shortest block that counts as having reached focus, for `focus_bookends`
+pub const FIRST_FOCUS_BLOCK: Duration = Duration::from_secs(15 * 60);
+
+/// How long after the first of `sessions` started the first focus block
+/// of FIRST_FOCUS_BLOCK or more began, and how long before the last one
+/// ended the last such block ended. None when no block is long enough; a
+/// day that starts (or ends) in focus gives zero. Blocks are split as
+/// `focus_blocks` splits them.
+pub fn focus_bookends<'a>(sessions: impl IntoIterator<Item = &'a FocusSession>, min_display: Duration) -> Option<(Duration, Duration)> {
+    let rows: Vec<&FocusSession> = sessions.into_iter().filter(|session| !session.is_corrupt()).collect();
+    let day_start = rows.iter().map(|session| session.start_time).min()?;
+    let day_end = rows.iter().map(|session| row_end(session)).max()?;
+    let blocks: Vec<FocusBlock> = focus_blocks(rows, min_display).into_iter().filter(|block| block.focus >= FIRST_FOCUS_BLOCK).collect();
+    let (first, last) = (blocks.first()?, blocks.last()?);
+    Some((
+        (first.start - day_start).to_std().unwrap_or_default(),
+        (day_end - last.end).to_std().unwrap_or_default(),
+    ))
+}
+
+/// "74 ▲ +6 vs workday avg", against the average of days of `day_type`
+pub fn describe_focus_score(score: u32, average: Option<f64>, day_type: DayType) -> String {
+    let Some(average) = average else {
+        return score.to_string();
+    };
+    let difference = (score as f64 - average).round() as i64;
+    match difference {
+        0 => format!("{} = {}", score, day_type.average_label()),
+        d if d > 0 => format!("{} ▲ +{} vs {}", score, d, day_type.average_label()),
+        d => format!("{} ▼ {} vs {}", score, d, day_type.average_label()),
+    }
+}
+
+/// Keep the `limit` longest entries at or above `min_display` (sorted
+/// longest first, equal durations by name) and fold the short ones into a
+/// trailing `other` row, so the time isn't silently dropped.
+fn rollup_noise<T>(
+    mut entries: Vec<T>,
+    limit: usize,
+    min_display: Duration,
+    duration: impl Fn(&T) -> Duration,
+    name: impl Fn(&T) -> &str,
+    other: impl FnOnce(Duration) -> T,
+) -> Vec<T> {
+    entries.sort_by(|a, b| duration(b).cmp(&duration(a)).then_with(|| name(a).cmp(name(b))));
+    let noise: Duration = entries.iter()
+        .map(&duration)
+        .filter(|d| *d < min_display)
+        .sum();
+    let mut kept: Vec<T> = entries.into_iter()
+        .filter(|entry| duration(entry) >= min_display)
+        .take(limit)
+        .collect();
+    if !noise.is_zero() {
+        kept.push(other(noise));
+    }
+    kept
+}
+
+/// `rollup_noise` for (name, duration, is_focus) usage lists
+fn rollup_usage(entries: Vec<(String, Duration, bool)>, settings: &StatsSettings) -> Vec<(String, Duration, bool)> {
+    rollup_noise(entries, usize::MAX, settings.min_display, |(_, duration, _)| *duration, |(name, _, _)| name, |noise| (settings.noise_label(), noise, false))
+}
+
+pub const DAILY_STATS_CACHE_FILE: &str = "daily_stats_cache.json";
+
+/// The last daily stats `stats` showed, kept for when the database can't be read
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedDailyStats {
+    pub saved_at: DateTime<Utc>,
+    pub stats: DailyStats,
+}
+
+impl CachedDailyStats {
+    /// "14:02" for today, "Jan 31, 14:02" otherwise
+    pub fn describe_saved_at(&self) -> String {
+        if self.saved_at.with_timezone(&utils::timezone::zone()).date_naive() == utils::timezone::to_zone(Utc::now()).date_naive() {
+            utils::format_timestamp_local(self.saved_at)
         } else {
-            Err(format!("❌ Failed to aggregate session: {}", session_name).into())
+            utils::format_datetime_local(self.saved_at)
         }
     }
+}
 
-    pub fn display_session_summary(session: &AggregatedSession) {
-        let start = utils::format_datetime_local(session.start_time);
-        let end = session.end_time.map(|t| utils::format_timestamp_local(t)).unwrap_or("ongoing".to_string());
-        let duration = Self::format_duration(session.total_duration);
-        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
-        let bar_width = 25;
-        let filled = ((session.focus_efficiency / 100.0) * bar_width as f64) as usize;
-        let empty = bar_width - filled;
-        let efficiency_display = format!("{:.0}% [{}{}]", 
-            session.focus_efficiency,
-            "▓".repeat(filled), 
-            "░".repeat(empty));
-        
-        println!("\n{}\n", top_sep);
-        println!("~=~ SESSION COMPLETE ~=~\n");
-        println!(
-r#"
-----------          -^-       
-----------         / * \     
-----------        / < > \    
-----------       / _-_-_ \   
-----------      /=-     -=\  
-----------     -     I     -  
-----------    -      L /    -  
-----------   -L-     o/    -I- 
-----------      =_       _=  
-----------        =__i__=    
-----------         ---|     
-----------          | |     
-----------          | |     
-----------          | |     
-----------          o |     
-----------         -O-|     
-----------          i |     
-----------           =I=    
-----------          --O--   
-----------           -i-    
-----------            o
-"#
-);
-        println!("Session: {:<48}\n", if session.session_name.len() > 48 { 
-            format!("{}...", &session.session_name[..45]) 
-        } else { 
-            session.session_name.clone() 
-        });
-        let time_line = format!("Duration: {} → {} ({})", start, end, duration);
-        println!("{}\n", time_line);
-        println!("Focus:   {:<48}\n", efficiency_display);
-        println!("Switches: {:<47}\n", session.context_switches);
-        
-        // Separate browser apps from regular apps
-        let (browser_apps, regular_apps): (Vec<_>, Vec<_>) = session.app_usage.iter()
-            .partition(|(app, _, _)| Self::is_browser_app(app));
-        
-        // Show regular applications (non-browser)
-        if !regular_apps.is_empty() {
-            println!("~=~ APPLICATIONS USED ~=~\n");
-            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-            for (i, (app, duration, is_focus)) in regular_apps.iter().take(6).enumerate() {
-                let app_display = if app.len() > 18 { format!("{}...", &app[..15]) } else { app.clone() };
-                let duration_str = Self::format_duration(*duration);
-                let focus_text = if *is_focus { "Focus" } else { "Other" };
-                let bar_len = 15;
-                let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                let usage_bar = format!("[{}{}]", "■".repeat(filled), "□".repeat(bar_len - filled));
-                let app_line = format!("{:<18} {} {:<8} ({:<5})", app_display, usage_bar, duration_str, focus_text);
-                println!("{}\n", app_line);
-            }
-        }
-        
+/// Bump whenever a field in the `sessions show --json` output is renamed,
+/// removed or changes meaning. Adding fields does not require a bump.
+pub const SESSION_JSON_SCHEMA_VERSION: u32 = 1;
 
-        
-        // Show browser tabs individually (for session summary)
-        if !session.domain_usage.is_empty() {
+#[derive(Serialize)]
+struct SessionJsonEnvelope<'a> {
+    schema_version: u32,
+    session: SessionJson<'a>,
+}
+
+#[derive(Serialize)]
+struct SessionJson<'a> {
+    session_name: &'a str,
+    start_time: String,
+    end_time: Option<String>,
+    total_duration_seconds: u64,
+    focus_efficiency: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focus_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engaged_focus_seconds: Option<u64>,
+    untracked_seconds: u64,
+    /// total_duration_seconds less the suspends
+    tracked_seconds: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suspends: Vec<SuspendJson>,
+    app_usage: Vec<UsageJson<'a>>,
+    domain_usage: Vec<UsageJson<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    workspaces: Vec<WorkspaceJson<'a>>,
+    context_switches: usize,
+    switches: Vec<SwitchJson<'a>>,
+    recovery: RecoveryJson,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Option<Vec<EventJson>>,
+    /// The task the session was started on (`start --task`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<TaskJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskJson<'a> {
+    /// "taskwarrior" or "todo_txt"
+    source: &'static str,
+    /// The taskwarrior id as given at start, or the todo.txt line
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+}
+
+impl<'a> From<&'a SessionTask> for TaskJson<'a> {
+    fn from(task: &'a SessionTask) -> Self {
+        Self {
+            source: task.source.as_str(),
+            id: &task.id,
+            uuid: task.uuid.as_deref(),
+            description: task.description.as_deref(),
+        }
+    }
+}
+
+/// `stats task --json`
+#[derive(Serialize)]
+struct TaskReportJson<'a> {
+    query: &'a str,
+    task: Option<TaskJson<'a>>,
+    days: usize,
+    tracked_seconds: u64,
+    focus_seconds: u64,
+    focus_efficiency: f64,
+    context_switches: usize,
+    sessions: Vec<TaskSessionJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskSessionJson<'a> {
+    session_name: &'a str,
+    start_time: String,
+    end_time: Option<String>,
+    tracked_seconds: u64,
+    focus_efficiency: f64,
+}
+
+#[derive(Serialize)]
+struct SuspendJson {
+    start: String,
+    end: String,
+    seconds: u64,
+}
+
+pub const DAILY_CSV_HEADER: &str = "date,focus_hours,distraction_hours,untracked_hours,context_switches,deep_sessions,efficiency,focus_score,assistant_hours\n";
+
+/// Bump whenever a field in the `stats --json` output is renamed, removed
+/// or changes meaning. Adding fields does not require a bump.
+pub const DAILY_JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct DailyStatsJson<'a> {
+    schema_version: u32,
+    date: String,
+    focus_seconds: u64,
+    distraction_seconds: u64,
+    untracked_seconds: u64,
+    quiet_seconds: u64,
+    /// Time the machine was asleep; not in any of the other figures
+    suspended_seconds: u64,
+    passive_seconds: u64,
+    /// Time in AI assistants; part of focus_seconds or distraction_seconds
+    /// unless assistant_counts_as is "neutral"
+    assistant_seconds: u64,
+    assistant_counts_as: &'static str,
+    context_switches: usize,
+    deep_focus_sessions: usize,
+    focus_efficiency: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focus_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focus_score_7d_avg: Option<f64>,
+    /// From the first tracked activity to the first focus block of 15
+    /// minutes; absent on days without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_to_first_focus_seconds: Option<u64>,
+    /// From the end of the last such block to the last tracked activity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focus_wind_down_seconds: Option<u64>,
+    /// Time on rows tagged by co_focus rules; already part of focus_seconds
+    /// and distraction_seconds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    co_focus: Vec<CoFocusJson<'a>>,
+    notes: Vec<String>,
+    top_apps: Vec<TopAppJson<'a>>,
+    /// Each of the configured `groups` with time today
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<TopAppJson<'a>>,
+    round_trips: Vec<RoundTripJson<'a>>,
+    /// An estimate, see `switching_cost`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    switching_cost: Option<SwitchCostJson>,
+    /// Focus seconds by "ac" and "battery", for rows that recorded one
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    focus_by_power: BTreeMap<&'static str, u64>,
+    /// Set with `stats --power`: only rows tracked on it were counted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power_filter: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct SwitchCostJson {
+    estimated_seconds: u64,
+    measured_switches: usize,
+    assumed_switches: usize,
+    skipped_switches: usize,
+    default_cost_seconds: u64,
+}
+
+#[derive(Serialize)]
+struct CoFocusJson<'a> {
+    label: &'a str,
+    seconds: u64,
+    /// Each side of the rule with its share; the shares add up to seconds
+    credited_seconds: BTreeMap<&'a str, u64>,
+}
+
+#[derive(Serialize)]
+struct RoundTripJson<'a> {
+    home: &'a str,
+    away: &'a str,
+    round_trips: usize,
+    time_away_seconds: u64,
+}
+
+/// `trend` is "up", "down" or "flat" (within the "≈" threshold)
+#[derive(Serialize)]
+struct TopAppJson<'a> {
+    name: &'a str,
+    duration_seconds: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_7d_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta_seconds: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trend: Option<&'static str>,
+    /// Browsers only, see `BrowserSites`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sites: Option<SitesJson>,
+}
+
+impl<'a> TopAppJson<'a> {
+    fn new(name: &'a str, duration: Duration, trend: Option<&AppTrend>) -> Self {
+        let delta = trend.map(|trend| app_trend_delta(duration, trend.average));
+        TopAppJson {
+            name,
+            duration_seconds: duration.as_secs(),
+            average_7d_seconds: trend.map(|trend| trend.average.as_secs()),
+            delta_seconds: delta.map(|(delta, _)| delta),
+            trend: delta.map(|(delta, changed)| match delta {
+                _ if !changed => "flat",
+                d if d > 0 => "up",
+                _ => "down",
+            }),
+            sites: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SitesJson {
+    focus_seconds: u64,
+    distraction_seconds: u64,
+    unclassified_seconds: u64,
+}
+
+/// `sessions export --format json`: the `sessions show --json` document
+/// plus every raw row recorded under the session name
+#[derive(Serialize)]
+struct SessionExportJson<'a> {
+    schema_version: u32,
+    session: SessionJson<'a>,
+    rows: Vec<SessionRowJson<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recorded_with: Option<RecordedWithJson<'a>>,
+}
+
+/// The settings snapshot stored when the session started
+#[derive(Serialize)]
+struct RecordedWithJson<'a> {
+    recorded_at: String,
+    snapshot_hash: &'a str,
+    settings: &'a SessionSnapshot,
+}
+
+#[derive(Serialize)]
+struct SessionRowJson<'a> {
+    start_time: String,
+    end_time: Option<String>,
+    app_name: &'a str,
+    window_title: &'a str,
+    domain: Option<&'a str>,
+    duration_seconds: u64,
+    is_focus_app: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    activity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspace: Option<&'a str>,
+    /// The row was saved without an end; end_time and duration_seconds are
+    /// where it was settled
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    open: bool,
+    /// Entered by hand (`add-entry`, `import manual`), not tracked
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    manual: bool,
+    /// The rule that classified the row, e.g. "focus_app:code"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focus_rule: Option<String>,
+    /// Label of the co_focus rule the row was part of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    co_focus: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct WorkspaceJson<'a> {
+    /// null for rows whose backend couldn't tell
+    workspace: Option<&'a str>,
+    duration_seconds: u64,
+    focus_seconds: u64,
+    focus_efficiency: f64,
+    rows: usize,
+}
+
+impl<'a> From<&'a WorkspaceUsage> for WorkspaceJson<'a> {
+    fn from(usage: &'a WorkspaceUsage) -> Self {
+        WorkspaceJson {
+            workspace: usage.workspace.as_deref(),
+            duration_seconds: usage.total.as_secs(),
+            focus_seconds: usage.focus.as_secs(),
+            focus_efficiency: (usage.efficiency() * 10.0).round() / 10.0,
+            rows: usage.rows,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WorkspaceReportJson<'a> {
+    days: u32,
+    since: String,
+    workspaces: Vec<WorkspaceJson<'a>>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    notes: &'a [String],
+}
+
+#[derive(Serialize)]
+struct UsageJson<'a> {
+    name: &'a str,
+    duration_seconds: u64,
+    is_focus: bool,
+}
+
+#[derive(Serialize)]
+struct SwitchJson<'a> {
+    timestamp: String,
+    from_app: &'a str,
+    to_app: &'a str,
+    recovery_seconds: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct EventJson {
+    timestamp: String,
+    kind: &'static str,
+    payload: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct RecoveryJson {
+    count: usize,
+    average_seconds: Option<u64>,
+    max_seconds: Option<u64>,
+}
+
+/// Weekday rows of the switch heatmap, Monday first
+const HEATMAP_WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Context switches per local weekday (rows, Monday first) and hour
+#[derive(Debug, Clone)]
+pub struct SwitchHeatmap {
+    pub weeks: u32,
+    pub since: DateTime<Utc>,
+    pub counts: [[usize; 24]; 7],
+}
+
+impl SwitchHeatmap {
+    /// Average switches per week in one cell
+    pub fn average(&self, weekday: usize, hour: usize) -> f64 {
+        self.counts[weekday][hour] as f64 / self.weeks as f64
+    }
+
+    fn max_average(&self) -> f64 {
+        (0..7).flat_map(|day| (0..24).map(move |hour| (day, hour)))
+            .map(|(day, hour)| self.average(day, hour))
+            .fold(0.0, f64::max)
+    }
+}
+
+#[derive(Serialize)]
+struct SwitchHeatmapJson<'a> {
+    weeks: u32,
+    since: String,
+    weekdays: [&'a str; 7],
+    hours: Vec<u32>,
+    /// Average switches per week, [weekday][hour]
+    average: Vec<Vec<f64>>,
+    /// Total switches over the whole range, [weekday][hour]
+    counts: Vec<Vec<usize>>,
+}
+
+/// Formats accepted by `sessions export`
+pub const SESSION_EXPORT_FORMATS: [&str; 4] = ["json", "csv", "markdown", "html"];
+
+/// Formats of `share --format`
+pub const SHARE_FORMATS: [&str; 4] = ["ascii", "oneline", "tweet", "badge"];
+
+/// Longest `share --format tweet` post
+pub const TWEET_MAX_CHARS: usize = 280;
+
+/// Where `share --format badge` writes without --output
+pub const DEFAULT_BADGE_FILE: &str = "focusdebt-badge.svg";
+
+/// Lines per page of `sessions show --raw`
+pub const RAW_PAGE_SIZE: usize = 100;
+
+/// Longer window titles are cut in `sessions show --raw`
+const RAW_TITLE_WIDTH: usize = 60;
+
+/// Everything stored for one named session, for `sessions export`
+#[derive(Debug, Clone)]
+pub struct SessionExport {
+    pub session: AggregatedSession,
+    pub rows: Vec<FocusSession>,
+    pub switches: Vec<ContextSwitch>,
+    /// What the session was tracked with; None for older sessions
+    pub meta: Option<SessionMeta>,
+    /// Notable events while it ran (pauses, alerts, daemon starts and
+    /// stops, ...), in time order; window changes are left out
+    pub events: Vec<TrackerEvent>,
+}
+
+/// Time spent today on one budgeted app or site
+#[derive(Debug, Clone)]
+pub struct BudgetUsage {
+    pub target: String,
+    /// What may be used this period, including time carried over
+    pub limit: Duration,
+    pub used: Duration,
+    pub period: BudgetPeriod,
+    pub period_start: DateTime<Utc>,
+    /// Unused time carried in from the days before
+    pub carried: Duration,
+}
+
+impl BudgetUsage {
+    pub fn percent(&self) -> f64 {
+        if self.limit.is_zero() {
+            return 100.0;
+        }
+        self.used.as_secs_f64() / self.limit.as_secs_f64() * 100.0
+    }
+}
+
+/// How many calendar days back carry-over is followed at most
+const CARRY_OVER_MAX_DAYS: usize = 31;
+
+/// Unused time a daily budget carries into the next day, after the days
+/// before used `previous_days_used` (oldest first). Each day may use its
+/// limit plus what the day before carried in; what's left carries on, up
+/// to `cap`. The first day starts with nothing carried in.
+pub fn carried_over(limit: Duration, cap: Duration, previous_days_used: &[Duration]) -> Duration {
+    previous_days_used.iter().fold(Duration::ZERO, |carried, used| {
+        (limit + carried).saturating_sub(*used).min(cap)
+    })
+}
+
+/// Tags as typed, e.g. "writing, Rust": trimmed, lowercased, sorted and
+/// without repeats
+pub fn parse_session_tags(input: &str) -> Vec<String> {
+    let tags: std::collections::BTreeSet<String> = input.split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.into_iter().collect()
+}
+
+/// Days without a sighting before `focusapp audit` flags an entry
+pub const STALE_APP_DAYS: i64 = 60;
+
+/// The list an audited app entry is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppList {
+    Focus,
+    Ignored,
+}
+
+impl AppList {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AppList::Focus => "focus",
+            AppList::Ignored => "ignored",
+        }
+    }
+}
+
+/// One focus_apps or ignored_apps entry against the rows it matches
+#[derive(Debug, Clone, Serialize)]
+pub struct AppAudit {
+    pub app: String,
+    pub list: AppList,
+    pub total_seconds: u64,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Whole days since `last_seen`; None when never seen
+    pub days_unseen: Option<i64>,
+    /// Never seen, or unseen for the stale threshold
+    pub stale: bool,
+}
+
+impl AppAudit {
+    /// "never seen", "unseen for 75 days" or "last seen Mar 02"
+    pub fn describe_seen(&self) -> String {
+        match (self.last_seen, self.days_unseen) {
+            (None, _) => "never seen".to_string(),
+            (Some(_), Some(days)) if self.stale => format!("unseen for {} days", days),
+            (Some(last), _) => format!("last seen {}", utils::format_date_local(utils::timezone::to_zone(last).date_naive())),
+        }
+    }
+}
+
+/// Audit each entry of `focus` and `ignored` (focus first, each in list
+/// order) against `sightings`, matching app names regardless of case. An
+/// entry is stale when it was never seen or not for `stale_days`.
+pub fn audit_apps(focus: &[String], ignored: &[String], sightings: &[AppSighting], now: DateTime<Utc>, stale_days: i64) -> Vec<AppAudit> {
+    let lists = focus.iter().map(|app| (app, AppList::Focus)).chain(ignored.iter().map(|app| (app, AppList::Ignored)));
+    lists
+        .map(|(app, list)| {
+            let sighting = sightings.iter().find(|sighting| sighting.app_name.eq_ignore_ascii_case(app.trim()));
+            let days_unseen = sighting.map(|sighting| (now - sighting.last_seen).num_days().max(0));
+            AppAudit {
+                app: app.clone(),
+                list,
+                total_seconds: sighting.map(|sighting| sighting.total.as_secs()).unwrap_or(0),
+                first_seen: sighting.map(|sighting| sighting.first_seen),
+                last_seen: sighting.map(|sighting| sighting.last_seen),
+                days_unseen,
+                stale: days_unseen.is_none_or(|days| days >= stale_days),
+            }
+        })
+        .collect()
+}
+
+/// Days `rules stats` covers unless told otherwise
+pub const RULE_STATS_DAYS: u32 = 30;
+
+/// The rows one recorded rule classified (`rules stats`)
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleUsage {
+    /// None for rows recorded before rules were, imported or entered by hand
+    pub rule: Option<RuleMatch>,
+    pub rows: usize,
+    pub total_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStats {
+    pub days: u32,
+    /// Most time first
+    pub rules: Vec<RuleUsage>,
+    /// focus_apps and focus_sites entries no row of the period was classified by
+    pub unused: Vec<RuleMatch>,
+}
+
+/// Total `sessions` by the rule recorded on them, and list the focus
+/// entries none of them matched
+pub fn rule_stats(sessions: &[FocusSession], focus_apps: &[String], focus_sites: &[String], days: u32) -> RuleStats {
+    let mut totals: BTreeMap<Option<RuleMatch>, (usize, u64)> = BTreeMap::new();
+    for session in sessions.iter().filter(|session| !session.is_corrupt()) {
+        let total = totals.entry(session.focus_rule.clone()).or_default();
+        total.0 += 1;
+        total.1 += session.duration.as_secs();
+    }
+    let configured = focus_apps.iter().map(|app| RuleMatch::new(RuleKind::FocusApp, app))
+        .chain(focus_sites.iter().map(|site| RuleMatch::new(RuleKind::FocusSite, site)));
+    let unused = configured.filter(|rule| !totals.contains_key(&Some(rule.clone()))).collect();
+    let mut rules: Vec<RuleUsage> = totals.into_iter()
+        .map(|(rule, (rows, total_seconds))| RuleUsage { rule, rows, total_seconds })
+        .collect();
+    rules.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds).then_with(|| a.rule.cmp(&b.rule)));
+    RuleStats { days, rules, unused }
+}
+
+/// Sessions a shared session's efficiency is ranked against
+pub const HISTORY_RANK_SESSIONS: usize = 30;
+
+/// Context for `share --with-history`
+#[derive(Debug, Clone)]
+pub struct SessionHistory {
+    pub focus_last_7_days: Duration,
+    pub focus_last_30_days: Duration,
+    pub streak_days: usize,
+    /// (sessions with lower efficiency, sessions compared)
+    pub efficiency_rank: Option<(usize, usize)>,
+}
+
+/// Days named in `sessions list`'s line about unnamed rows
+const UNNAMED_DAYS_LISTED: usize = 7;
+
+/// Number of trailing days of the same type averaged for the efficiency
+/// floor check
+pub const EFFICIENCY_TREND_DAYS: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct EfficiencyTrend {
+    pub days: Vec<(NaiveDate, f64)>,
+    pub average: f64,
+}
+
+impl EfficiencyTrend {
+    pub fn is_below(&self, floor: f64) -> bool {
+        self.average < floor
+    }
+
+    /// Compact day-by-day trend, e.g. "Mon 62% → Tue 48% → Wed 41%"
+    pub fn describe(&self) -> String {
+        self.days.iter()
+            .map(|(day, efficiency)| format!("{} {:.0}%", day.format("%a"), efficiency))
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AggregatedSession {
+    pub session_name: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub total_duration: Duration,
+    pub focus_efficiency: f64,
+    pub focus_score: Option<u32>,
+    pub engaged_focus_time: Option<Duration>,
+    pub untracked_time: Duration,
+    pub app_usage: Vec<(String, Duration, bool)>, // (app_name, duration, is_focus)
+    pub domain_usage: Vec<(String, Duration, bool)>, // (tab_name, duration, is_focus)
+    /// Empty when no row recorded a workspace
+    pub workspace_usage: Vec<WorkspaceUsage>,
+    pub context_switches: usize,
+    /// Filled in for session summaries and the share report, from the
+    /// session's recorded switches
+    pub switching_cost: Option<SwitchCost>,
+    /// Rows saved without an end, see `FocusSession::settle_open_end`
+    pub open_rows: usize,
+    /// Suspends within the span, taken out of everything but `total_duration`
+    pub suspends: Vec<SuspendGap>,
+    pub suspended_time: Duration,
+    /// Filled in for session summaries; empty unless count_notifications was on
+    pub notifications: NotificationCounts,
+    /// Filled in for session summaries and `sessions show`, when the session
+    /// was started with an intent or rated
+    pub intent: Option<SessionIntent>,
+    /// Filled in like `intent`, when the session was started with `--task`
+    pub task: Option<SessionTask>,
+}
+
+/// Notification sources named in a summary; the rest are counted as "other"
+pub const NOTIFICATION_SOURCES_SHOWN: usize = 3;
+
+/// Notifications received (with count_notifications), by the app that sent them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NotificationCounts {
+    /// Most first
+    pub sources: Vec<(String, usize)>,
+}
+
+impl NotificationCounts {
+    pub(crate) fn new(mut sources: Vec<(String, usize)>) -> Self {
+        sources.retain(|(_, count)| *count > 0);
+        sources.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self { sources }
+    }
+
+    pub fn total(&self) -> usize {
+        self.sources.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// e.g. "38 (slack 24, email 9, other 5)"
+    pub(crate) fn describe(&self) -> String {
+        let mut shown: Vec<(String, usize)> = Vec::new();
+        let mut other = 0;
+        for (source, count) in &self.sources {
+            if source == "other" || shown.len() == NOTIFICATION_SOURCES_SHOWN {
+                other += count;
+            } else {
+                shown.push((source.clone(), *count));
+            }
+        }
+        if other > 0 {
+            shown.push(("other".to_string(), other));
+        }
+        let parts: Vec<String> = shown.iter().map(|(source, count)| format!("{} {}", source, count)).collect();
+        format!("{} ({})", self.total(), parts.join(", "))
+    }
+}
+
+impl AggregatedSession {
+    /// The span without the time the machine was suspended
+    pub fn tracked_time(&self) -> Duration {
+        self.total_duration.saturating_sub(self.suspended_time)
+    }
+
+    /// The end time, marked "(open)" when it was settled from rows saved
+    /// without one, or "ongoing"
+    pub(crate) fn describe_end(&self, format: fn(DateTime<Utc>) -> String) -> String {
+        match self.end_time {
+            Some(end) if self.open_rows > 0 => format!("{} (open)", format(end)),
+            Some(end) => format(end),
+            None => "ongoing".to_string(),
+        }
+    }
+}
+
+/// Recent stretch of a running session its pace is measured over
+pub const PACE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// How far ahead a running session without a plan is projected
+pub const PROJECTION_HORIZON: Duration = Duration::from_secs(60 * 60);
+
+/// A running session for `stats --current`
+#[derive(Debug, Clone)]
+pub struct SessionProgress {
+    pub session: AggregatedSession,
+    /// Focus efficiency over the last PACE_WINDOW; None when nothing was
+    /// tracked in it
+    pub pace: Option<f64>,
+    /// End of the planned session being run, if any
+    pub planned_end: Option<DateTime<Utc>>,
+    pub now: DateTime<Utc>,
+}
+
+impl SessionProgress {
+    /// Where the projection ends: the planned end, or PROJECTION_HORIZON from now
+    pub fn projection_end(&self) -> DateTime<Utc> {
+        self.planned_end.filter(|end| *end > self.now)
+            .unwrap_or_else(|| self.now + chrono::Duration::from_std(PROJECTION_HORIZON).unwrap_or_default())
+    }
+
+    /// "last 15m at 40%: 71% by 16:30 (planned end)"
+    pub fn describe_projection(&self) -> Option<String> {
+        let pace = self.pace?;
+        let end = self.projection_end();
+        let remaining = (end - self.now).to_std().unwrap_or_default();
+        let projected = project_efficiency(self.session.focus_efficiency, self.session.total_duration, pace, remaining);
+        Some(format!("last {}m at {:.0}%: {:.0}% by {}{}",
+            PACE_WINDOW.as_secs() / 60,
+            pace,
+            projected,
+            utils::format_timestamp_local(end),
+            if self.planned_end.is_some() { " (planned end)" } else { "" }))
+    }
+}
+
+/// Efficiency at the end of a session now at `efficiency` over `elapsed`,
+/// if the `remaining` time goes at `pace` (both in percent)
+pub fn project_efficiency(efficiency: f64, elapsed: Duration, pace: f64, remaining: Duration) -> f64 {
+    let total = elapsed + remaining;
+    if total.is_zero() {
+        return efficiency;
+    }
+    (efficiency * elapsed.as_secs_f64() + pace * remaining.as_secs_f64()) / total.as_secs_f64()
+}
+
+/// Time on one workspace (virtual desktop)
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct WorkspaceUsage {
+    /// None for rows whose backend couldn't tell
+    pub workspace: Option<String>,
+    pub total: Duration,
+    pub focus: Duration,
+    pub rows: usize,
+}
+
+impl WorkspaceUsage {
+    pub(crate) fn label(&self) -> &str {
+        self.workspace.as_deref().unwrap_or("(unknown)")
+    }
+
+    pub fn efficiency(&self) -> f64 {
+        if self.total > Duration::ZERO {
+            self.focus.as_secs_f64() / self.total.as_secs_f64() * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Time per workspace, most used first. Empty when no row has a
+/// workspace, so reports can leave the section out.
+pub fn workspace_usage<'a>(sessions: impl IntoIterator<Item = &'a FocusSession>) -> Vec<WorkspaceUsage> {
+    let mut per_workspace: BTreeMap<Option<String>, WorkspaceUsage> = BTreeMap::new();
+    for session in sessions {
+        let usage = per_workspace.entry(session.workspace.clone()).or_insert_with(|| WorkspaceUsage {
+            workspace: session.workspace.clone(),
+            total: Duration::ZERO,
+            focus: Duration::ZERO,
+            rows: 0,
+        });
+        usage.total += session.duration;
+        if session.is_focus_app {
+            usage.focus += session.duration;
+        }
+        usage.rows += 1;
+    }
+    if per_workspace.keys().all(Option::is_none) {
+        return Vec::new();
+    }
+    let mut usage: Vec<WorkspaceUsage> = per_workspace.into_values().collect();
+    usage.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.workspace.cmp(&b.workspace)));
+    usage
+}
+
+/// `stats workspaces`: time per workspace over the last `days` days
+#[derive(Debug, Clone)]
+pub struct WorkspaceReport {
+    pub days: u32,
+    pub since: DateTime<Utc>,
+    pub usage: Vec<WorkspaceUsage>,
+    /// See DailyStats::version_changes
+    pub version_changes: Vec<String>,
+}
+
+/// `stats task`: the sessions linked to one task and what they add up to
+#[derive(Debug, Clone)]
+pub struct TaskReport {
+    /// The task as asked for
+    pub query: String,
+    /// Newest first
+    pub sessions: Vec<AggregatedSession>,
+    /// Local days the sessions started on
+    pub days: usize,
+    pub tracked: Duration,
+    /// Each session's tracked time at its focus efficiency
+    pub focus: Duration,
+    pub focus_efficiency: f64,
+    pub context_switches: usize,
+}
+
+/// Sum up `sessions`, all linked to the task `query` named
+pub fn task_report(query: &str, sessions: Vec<AggregatedSession>) -> TaskReport {
+    let days: std::collections::BTreeSet<NaiveDate> = sessions.iter()
+        .map(|session| utils::timezone::to_zone(session.start_time).date_naive())
+        .collect();
+    let tracked: Duration = sessions.iter().map(AggregatedSession::tracked_time).sum();
+    let focus: Duration = sessions.iter()
+        .map(|session| session.tracked_time().mul_f64(session.focus_efficiency.clamp(0.0, 100.0) / 100.0))
+        .sum();
+    TaskReport {
+        query: query.trim().to_string(),
+        days: days.len(),
+        tracked,
+        focus,
+        focus_efficiency: if tracked > Duration::ZERO { focus.as_secs_f64() / tracked.as_secs_f64() * 100.0 } else { 0.0 },
+        context_switches: sessions.iter().map(|session| session.context_switches).sum(),
+        sessions,
+    }
+}
+
+/// App and site lines `config preview` lists before summing up the rest
+pub const PREVIEW_FLIPS_SHOWN: usize = 20;
+
+/// One side of a `config preview` day, counted the way daily stats count
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PreviewTotals {
+    pub focus: Duration,
+    pub distraction: Duration,
+    pub deep_focus_sessions: usize,
+}
+
+impl PreviewTotals {
+    /// None when nothing was tracked
+    pub fn efficiency(&self) -> Option<f64> {
+        let total = self.focus + self.distraction;
+        (total > Duration::ZERO).then(|| self.focus.as_secs_f64() / total.as_secs_f64() * 100.0)
+    }
+
+    /// Ignored rows, corrupt open rows and rows under the config's
+    /// min_record_seconds are left out, passive stretches count on neither
+    /// side
+    fn add(&mut self, session: &FocusSession, class: SessionClass, config: &Config) {
+        if class == SessionClass::Ignored
+            || session.is_corrupt()
+            || session.duration > Duration::from_secs(24 * 60 * 60)
+            || session.duration < Duration::from_secs(config.min_record_seconds) {
+            return;
+        }
+        let active = session.duration - session.passive.unwrap_or_default().min(session.duration);
+        if class == SessionClass::Focus {
+            self.focus += active;
+            if session.duration >= Duration::from_secs(config.deep_focus_threshold_minutes * 60) {
+                self.deep_focus_sessions += 1;
+            }
+        } else {
+            self.distraction += active;
+        }
+    }
+
+    fn merge(mut self, other: PreviewTotals) -> PreviewTotals {
+        self.focus += other.focus;
+        self.distraction += other.distraction;
+        self.deep_focus_sessions += other.deep_focus_sessions;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewDay {
+    pub date: NaiveDate,
+    pub before: PreviewTotals,
+    pub after: PreviewTotals,
+}
+
+/// Rows of one app (and site) that the proposed config classifies differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewFlip {
+    pub app: String,
+    pub site: Option<String>,
+    pub before: SessionClass,
+    pub after: SessionClass,
+    /// The rules that gave `before` and `after`
+    pub rule_before: RuleMatch,
+    pub rule_after: RuleMatch,
+    pub rows: usize,
+    pub duration: Duration,
+}
+
+/// `config preview`: the last `days` days under the current and a proposed config
+#[derive(Debug, Clone)]
+pub struct ClassificationPreview {
+    pub days: Vec<PreviewDay>,
+    /// Largest first
+    pub flips: Vec<PreviewFlip>,
+}
+
+impl ClassificationPreview {
+    pub fn before(&self) -> PreviewTotals {
+        self.days.iter().fold(PreviewTotals::default(), |total, day| total.merge(day.before))
+    }
+
+    pub fn after(&self) -> PreviewTotals {
+        self.days.iter().fold(PreviewTotals::default(), |total, day| total.merge(day.after))
+    }
+}
+
+/// Classify `sessions` by both configs (see `Config::classify_session`) and
+/// total them per day from `first_day`. Nothing is written anywhere.
+pub fn preview_classification(sessions: &[FocusSession], current: &Config, proposed: &Config, first_day: NaiveDate, days: usize) -> ClassificationPreview {
+    let mut preview_days: Vec<PreviewDay> = (0..days)
+        .map(|index| PreviewDay {
+            date: first_day + chrono::Duration::days(index as i64),
+            before: PreviewTotals::default(),
+            after: PreviewTotals::default(),
+        })
+        .collect();
+    let mut flips: BTreeMap<(String, Option<String>, RuleMatch, RuleMatch), PreviewFlip> = BTreeMap::new();
+
+    for session in sessions {
+        let index = (session.start_time.date_naive() - first_day).num_days();
+        let Some(day) = usize::try_from(index).ok().and_then(|index| preview_days.get_mut(index)) else { continue };
+        let (before, rule_before) = current.classify_session(session);
+        let (after, rule_after) = proposed.classify_session(session);
+        day.before.add(session, before, current);
+        day.after.add(session, after, proposed);
+        if before == after {
+            continue;
+        }
+        let app = desktop::display_name(&session.app_name);
+        let key = (app.clone(), session.domain.clone(), rule_before.clone(), rule_after.clone());
+        let flip = flips.entry(key).or_insert_with(|| PreviewFlip {
+            app,
+            site: session.domain.clone(),
+            before,
+            after,
+            rule_before,
+            rule_after,
+            rows: 0,
+            duration: Duration::ZERO,
+        });
+        flip.rows += 1;
+        flip.duration += session.duration;
+    }
+
+    let mut flips: Vec<PreviewFlip> = flips.into_values().collect();
+    flips.sort_by(|a, b| b.duration.cmp(&a.duration)
+        .then_with(|| a.app.cmp(&b.app))
+        .then_with(|| a.site.cmp(&b.site)));
+    ClassificationPreview { days: preview_days, flips }
+}
+
+impl Stats {
+    /// Stats for `day`, from its start to the next day's on the local
+    /// clock `days` follows
+    pub fn calculate_daily_stats(db: &Database, settings: &StatsSettings, days: &LocalDays, day: NaiveDate) -> Result<DailyStats, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let (day_start, day_end) = days.range(day);
+        let mut records = DayRecords {
+            sessions: settings.sessions_for_date(db, days, day)?.into_iter().filter_map(|session| settings.classify(session)).collect(),
+            switches: settings.switches_for_date(db, days, day)?,
+            daemon_stops: db.get_events_between(day_start, day_end, Some(EventKind::DaemonStop))?
+                .into_iter()
+                .map(|event| event.timestamp)
+                .collect(),
+            timezone_changes: db.get_events_between(day_start, day_end, Some(EventKind::TimezoneChange))?,
+            pauses: pause_events(db, day_start, day_end)?,
+            suspends: suspend_gaps(db, day_start, day_end)?,
+        };
+        let filtered = settings.apply_power_filter(&mut records);
+        let mut stats = Self::daily_stats_from_records(settings, days, day, &records, Utc::now());
+        if filtered {
+            // Time on the other source isn't untracked, and the app
+            // baselines aren't split by source
+            stats.untracked_time = Duration::ZERO;
+            stats.power_filter = settings.power_filter;
+        } else {
+            (stats.app_trends, stats.group_trends) = Self::app_trends(db, settings, days, &stats)?;
+        }
+        stats.version_changes = Self::version_change_notes(db, day_start, day_end)?;
+        stats.live = settings.has_live_rows(days, day);
+        Ok(stats)
+    }
+
+    /// Focus time in each local hour of `day`, live rows included and rows
+    /// split at the hour boundaries they cross. The hour a DST change
+    /// repeats counts twice in its bucket, the hour it skips stays empty.
+    /// None when nothing at all was tracked that day.
+    pub fn calculate_hourly_focus(db: &Database, settings: &StatsSettings, days: &LocalDays, day: NaiveDate) -> Result<Option<[Duration; 24]>, Box<dyn std::error::Error>> {
+        let sessions: Vec<FocusSession> = settings.sessions_for_date(db, days, day)?.into_iter().filter_map(|session| settings.classify(session)).collect();
+        if sessions.is_empty() {
+            return Ok(None);
+        }
+        let (_, day_end) = days.range(day);
+        let mut hours = [Duration::ZERO; 24];
+        for session in sessions.iter().filter(|session| session.is_focus_app) {
+            let end = (session.start_time + chrono::Duration::from_std(session.duration).unwrap_or_default()).min(day_end);
+            let mut at = session.start_time;
+            while at < end {
+                // Offsets aren't always whole hours, so the next boundary
+                // is read off the local clock
+                let local = at.with_timezone(&days.zone);
+                let hour_end = at + chrono::Duration::seconds(3600 - i64::from(local.minute() * 60 + local.second()));
+                let piece_end = hour_end.min(end);
+                hours[local.hour() as usize] += (piece_end - at).to_std().unwrap_or_default();
+                at = piece_end;
+            }
+        }
+        Ok(Some(hours))
+    }
+
+    /// Each top app's mean time over the tracked days among the
+    /// BASELINE_DAYS before of the same type, so a Saturday is measured
+    /// against weekends, and the same for the rows of `group_usage`. Empty
+    /// when none of those was tracked.
+    fn app_trends(db: &Database, settings: &StatsSettings, days: &LocalDays, stats: &DailyStats) -> Result<(Vec<AppTrend>, Vec<AppTrend>), Box<dyn std::error::Error>> {
+        let baseline = settings.baseline_days(stats.date.date_naive());
+        let Some(first_day) = baseline.first() else {
+            return Ok((Vec::new(), Vec::new()));
+        };
+        let totals: Vec<(NaiveDate, String, Duration)> = db.get_app_daily_totals(days, days.start(*first_day), days.start(stats.date.date_naive()), settings.min_record)?
+            .into_iter()
+            .filter(|(day, _, _)| baseline.contains(day))
+            .collect();
+        let tracked_days = totals.iter().map(|(day, _, _)| *day).collect::<HashSet<_>>().len();
+        if tracked_days == 0 {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let group_trends = match settings.app_groups() {
+            Some(groups) => {
+                let per_row = grouped_display_usage(totals.iter().map(|(_, app, duration)| (app.clone(), *duration)), groups);
+                stats.group_usage.iter()
+                    .map(|(name, _, is_group)| AppTrend {
+                        app: name.clone(),
+                        average: per_row.iter()
+                            .find(|(other, _, other_is_group)| other == name && other_is_group == is_group)
+                            .map(|(_, duration, _)| *duration)
+                            .unwrap_or_default() / tracked_days as u32,
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let mut per_app: BTreeMap<String, Duration> = BTreeMap::new();
+        for (_, app, duration) in totals {
+            *per_app.entry(desktop::display_name(&app)).or_insert(Duration::ZERO) += duration;
+        }
+        let app_trends = stats.most_used_apps.iter()
+            .filter(|(app, _)| *app != settings.noise_label())
+            .map(|(app, _)| AppTrend {
+                app: app.clone(),
+                average: per_app.get(app).copied().unwrap_or_default() / tracked_days as u32,
+            })
+            .collect();
+        Ok((app_trends, group_trends))
+    }
+
+    /// Mean focus score of the BASELINE_DAYS before `date` of the same
+    /// type, skipping untracked days
+    pub fn focus_score_trend(db: &Database, settings: &StatsSettings, local_days: &LocalDays, day: NaiveDate) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        let baseline = settings.baseline_days(day);
+        let Some(first_day) = baseline.first() else {
+            return Ok(None);
+        };
+        let span = (day - *first_day).num_days() as usize;
+        let days = Self::calculate_daily_stats_range(db, settings, local_days, *first_day, span, &mut Progress::hidden())?;
+        let scores: Vec<u32> = days.iter()
+            .filter(|day| baseline.contains(&day.date.date_naive()))
+            .filter_map(|day| day.focus_score)
+            .collect();
+        if scores.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(scores.iter().sum::<u32>() as f64 / scores.len() as f64))
+    }
+
+    /// Stats for `days` consecutive days from `first_day`, loaded with one
+    /// query per table. Past PARALLEL_ROW_THRESHOLD rows the days are
+    /// aggregated in parallel, with the same results as the serial path.
+    pub fn calculate_daily_stats_range(
+        db: &Database,
+        settings: &StatsSettings,
+        local_days: &LocalDays,
+        first_day: NaiveDate,
+        days: usize,
+        progress: &mut Progress,
+    ) -> Result<Vec<DailyStats>, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let range_start = local_days.start(first_day);
+        let range_end = local_days.start(first_day + chrono::Duration::days(days as i64));
+        let day_index = |at: DateTime<Utc>| {
+            let index = (local_days.day_of(at) - first_day).num_days();
+            (0..days as i64).contains(&index).then_some(index as usize)
+        };
+
+        let mut batch = vec![DayRecords::default(); days];
+        let mut rows = 0;
+        for session in db.get_sessions_between(range_start, range_end - chrono::Duration::seconds(1))? {
+            let Some(session) = settings.classify(session) else { continue };
+            if let Some(index) = day_index(session.start_time) {
+                batch[index].sessions.push(session);
+                rows += 1;
+            }
+        }
+        for switch in db.get_context_switches_between(range_start, range_end - chrono::Duration::seconds(1))? {
+            if let Some(index) = day_index(switch.timestamp) {
+                batch[index].switches.push(switch);
+            }
+        }
+        for event in db.get_events_between(range_start, range_end, Some(EventKind::DaemonStop))? {
+            if let Some(index) = day_index(event.timestamp) {
+                batch[index].daemon_stops.push(event.timestamp);
+            }
+        }
+        for event in db.get_events_between(range_start, range_end, Some(EventKind::TimezoneChange))? {
+            if let Some(index) = day_index(event.timestamp) {
+                batch[index].timezone_changes.push(event);
+            }
+        }
+        for event in pause_events(db, range_start, range_end)? {
+            if let Some(index) = day_index(event.timestamp) {
+                batch[index].pauses.push(event);
+            }
+        }
+        // A suspend over midnight belongs to both days
+        for gap in suspend_gaps(db, range_start, range_end)? {
+            let first = day_index(gap.start.max(range_start)).unwrap_or(0);
+            let last = day_index(gap.end.min(range_end - chrono::Duration::seconds(1))).unwrap_or(days - 1);
+            for records in &mut batch[first..=last] {
+                records.suspends.push(gap);
+            }
+        }
+
+        for records in &mut batch {
+            settings.apply_power_filter(records);
+        }
+
+        Ok(Self::daily_stats_for_batch(settings, local_days, first_day, &batch, Utc::now(), settings.use_parallel(rows), progress))
+    }
+
+    /// Stats for each day of a loaded batch, `batch[i]` holding the records
+    /// of `first_day + i`
+    fn daily_stats_for_batch(
+        settings: &StatsSettings,
+        days: &LocalDays,
+        first_day: NaiveDate,
+        batch: &[DayRecords],
+        now: DateTime<Utc>,
+        parallel: bool,
+        progress: &mut Progress,
+    ) -> Vec<DailyStats> {
+        let day_of = |index: usize| first_day + chrono::Duration::days(index as i64);
+        if parallel {
+            let stats: Vec<DailyStats> = batch.par_iter()
+                .enumerate()
+                .map(|(index, records)| Self::daily_stats_from_records(settings, days, day_of(index), records, now))
+                .collect();
+            progress.advance(stats.len());
+            stats
+        } else {
+            batch.iter()
+                .enumerate()
+                .map(|(index, records)| {
+                    progress.advance(1);
+                    Self::daily_stats_from_records(settings, days, day_of(index), records, now)
+                })
+                .collect()
+        }
+    }
+
+    /// One day's stats from its records; `now` caps today's untracked time
+    pub fn daily_stats_from_records(settings: &StatsSettings, days: &LocalDays, day: NaiveDate, records: &DayRecords, now: DateTime<Utc>) -> DailyStats {
+        let site_rules = settings.site_rules.as_ref();
+        let assistant_rules = settings.assistant_rules.as_ref();
+        let (day_start, next_day) = days.range(day);
+        let day_end = next_day.min(now.max(day_start));
+
+        let mut total_focus_time = Duration::ZERO;
+        let mut total_distraction_time = Duration::ZERO;
+        let mut passive_time = Duration::ZERO;
+        let mut assistant_time = Duration::ZERO;
+        let counts_as = assistant_rules.map(|(_, counts_as)| *counts_as).unwrap_or_default();
+        let mut power_focus: BTreeMap<PowerSource, Duration> = BTreeMap::new();
+        let mut app_usage: BTreeMap<String, Duration> = BTreeMap::new();
+        let mut site_usage: BTreeMap<String, BrowserSites> = BTreeMap::new();
+        let mut distraction_usage: BTreeMap<String, Duration> = BTreeMap::new();
+        let mut co_focus_usage: BTreeMap<String, Duration> = BTreeMap::new();
+        let mut deep_focus_sessions = 0;
+        let mut counted = Vec::new();
+        let mut recorded = Vec::new();
+
+        // Rows written across a suspend count only the time awake
+        let suspended = suspend_periods(&records.suspends);
+        let trimmed: Vec<FocusSession>;
+        let sessions = if suspended.is_empty() {
+            &records.sessions
+        } else {
+            trimmed = records.sessions.iter().map(|session| without_suspends(session, &suspended)).collect();
+            &trimmed
+        };
+
+        for session in sessions {
+            // 30 minutes in one focus app, counted before any filtering
+            if session.is_focus_app && !session.is_corrupt() && session.duration >= Duration::from_secs(30 * 60) {
+                deep_focus_sessions += 1;
+            }
+            if !session.is_focus_app {
+                *distraction_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
+            }
+        }
+        let mut most_distracting: Vec<(String, Duration)> = distraction_usage.into_iter().collect();
+        most_distracting.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_distracting.truncate(5);
+
+        // Process sessions with better validation
+        for session in sessions {
+            // Skip sessions with invalid durations (likely from old broken tracking)
+            if session.duration > Duration::from_secs(24 * 60 * 60) {
+                // Skip sessions longer than 24 hours (likely broken data)
+                continue;
+            }
+            // Blips still count as tracked time when looking for gaps
+            recorded.push(session);
+            
+            if session.duration < settings.min_record {
+                // Skip blips recorded before min_record_seconds was raised
+                continue;
+            }
+
+            // Open for over a day: shown under its app, but its guessed
+            // length stays out of the totals efficiency is taken from
+            if session.is_corrupt() {
+                *app_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
+                continue;
+            }
+
+            // Passive stretches are neutral: they leave efficiency and the
+            // score alone but still show under the app
+            let passive = session.passive.unwrap_or_default().min(session.duration);
+            passive_time += passive;
+            // Assistant time has its own line and goes where
+            // assistant_counts_as says, whatever app it was in
+            let is_focus = if assistant_rules.is_some_and(|(sites, _)| matches_assistant_sites(session, sites)) {
+                assistant_time += session.duration - passive;
+                match counts_as {
+                    AssistantCountsAs::Focus => Some(true),
+                    AssistantCountsAs::Distraction => Some(false),
+                    AssistantCountsAs::Neutral => None,
+                }
+            } else {
+                Some(session.is_focus_app)
+            };
+            match is_focus {
+                Some(true) => {
+                    total_focus_time += session.duration - passive;
+                    if let Some(power) = session.power {
+                        *power_focus.entry(power).or_insert(Duration::ZERO) += session.duration - passive;
+                    }
+                }
+                Some(false) => total_distraction_time += session.duration - passive,
+                None => {}
+            }
+
+            *app_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
+            if let Some(class) = site_class_with(session, site_rules) {
+                let sites = site_usage.entry(session.app_name.clone()).or_default();
+                match class {
+                    SiteClass::Focus => sites.focus += session.duration,
+                    SiteClass::Distraction => sites.distraction += session.duration,
+                    SiteClass::Unclassified => sites.unclassified += session.duration,
+                }
+            }
+            // Each row is in one app, so summing rows counts the time once
+            if let Some(label) = &session.co_focus {
+                *co_focus_usage.entry(label.clone()).or_insert(Duration::ZERO) += session.duration - passive;
+            }
+            counted.push(session);
+        }
+
+        let total_time = total_focus_time + total_distraction_time;
+        let focus_efficiency = if total_time > Duration::ZERO {
+            total_focus_time.as_secs_f64() / total_time.as_secs_f64() * 100.0
+        } else {
+            0.0
+        };
+        let longest_block = longest_focus_block(counted.iter().copied(), settings.min_display);
+        let bookends = focus_bookends(counted.iter().copied(), settings.min_display);
+        let score = focus_score(&ScoreInputs {
+            efficiency: focus_efficiency,
+            context_switches: records.switches.len(),
+            tracked: total_time,
+            longest_block,
+        }, &settings.score);
+
+        let group_usage = settings.app_groups()
+            .map(|groups| grouped_display_usage(app_usage.iter().map(|(app, duration)| (app.clone(), *duration)), groups))
+            .unwrap_or_default();
+
+        // Top apps by usage time, with short ones rolled into one line
+        let app_usage_vec: Vec<(String, Duration)> = app_usage.into_iter()
+            .map(|(app, duration)| (desktop::display_name(&app), duration))
+            .collect();
+        let most_used_apps = rollup_noise(app_usage_vec, 5, settings.min_display, |(_, duration)| *duration, |(name, _)| name, |noise| (settings.noise_label(), noise));
+
+        let most_distracting_filtered = rollup_noise(
+            most_distracting.into_iter()
+                .map(|(app, duration)| (desktop::display_name(&app), duration))
+                .collect(),
+            5,
+            settings.min_display,
+            |(_, duration)| *duration,
+            |(name, _)| name,
+            |noise| (settings.noise_label(), noise),
+        );
+
+        let quiet = quiet_periods(&records.pauses, &records.daemon_stops, day_start, day_end);
+        // Neither quiet hours nor time asleep are untracked
+        let not_tracking = merge_periods(quiet.iter().chain(&suspended).copied().collect());
+        let switching_cost = settings.switching_cost(&records.switches, counted.iter().copied());
+
+        DailyStats {
+            date: start_of_day(day),
+            total_focus_time,
+            engaged_focus_time: Self::engaged_focus_time(counted),
+            total_distraction_time,
+            untracked_time: Self::untracked_gaps(recorded, day_start, day_end, &records.daemon_stops, UNTRACKED_GAP_THRESHOLD)
+                .iter()
+                .map(|gap| gap.duration().saturating_sub(overlap(gap.start, gap.end, &not_tracking)))
+                .sum(),
+            quiet_time: quiet.iter().map(|&(start, end)| (end - start).to_std().unwrap_or_default()).sum(),
+            suspended_time: overlap(day_start, day_end, &suspended),
+            passive_time,
+            assistant_time,
+            assistant_counts_as: counts_as,
+            context_switches: records.switches.len(),
+            deep_focus_sessions,
+            focus_efficiency,
+            focus_score: score,
+            focus_score_7d_avg: None,
+            longest_focus_block: longest_block,
+            time_to_first_focus: bookends.map(|(to_first, _)| to_first),
+            // Not known until the day is over
+            focus_wind_down: bookends.map(|(_, wind_down)| wind_down).filter(|_| now >= next_day),
+            co_focus: co_focus_usage.into_iter()
+                .filter(|(_, time)| !time.is_zero())
+                .map(|(label, time)| CoFocusTime::new(label, time, &settings.co_focus_rules))
+                .collect(),
+            local_day_length: (next_day - day_start).to_std().ok(),
+            timezone_changes: records.timezone_changes.iter()
+                .map(|event| format!("{} {} → {}",
+                    utils::format_timestamp_local(event.timestamp),
+                    event.payload["from"].as_str().unwrap_or("?"),
+                    event.payload["to"].as_str().unwrap_or("?")))
+                .collect(),
+            version_changes: Vec::new(),
+            most_used_apps,
+            app_trends: Vec::new(),
+            group_usage,
+            group_trends: Vec::new(),
+            // Keyed like most_used_apps; browsers sharing a display name add up
+            browser_sites: site_usage.into_iter()
+                .fold(BTreeMap::<String, BrowserSites>::new(), |mut merged, (app, sites)| {
+                    let app = desktop::display_name(&app);
+                    let entry = merged.entry(app.clone()).or_insert_with(|| BrowserSites { app, ..BrowserSites::default() });
+                    entry.focus += sites.focus;
+                    entry.distraction += sites.distraction;
+                    entry.unclassified += sites.unclassified;
+                    merged
+                })
+                .into_values()
+                .collect(),
+            most_distracting_apps: most_distracting_filtered,
+            round_trips: round_trip_pairs(&records.switches, settings.round_trip_window)
+                .into_iter()
+                .take(ROUND_TRIPS_SHOWN)
+                .map(|pair| RoundTripPair {
+                    home: desktop::display_name(&pair.home),
+                    away: desktop::display_name(&pair.away),
+                    ..pair
+                })
+                .collect(),
+            switching_cost,
+            live: false,
+            power_focus: power_focus.into_iter().collect(),
+            power_filter: None,
+        }
+    }
+
+    /// Focus time weighted by each session's input activity level, over the
+    /// focus sessions that have one. None when none of them do.
+    pub fn engaged_focus_time<'a>(sessions: impl IntoIterator<Item = &'a FocusSession>) -> Option<Duration> {
+        let mut engaged = None;
+        for session in sessions {
+            if let (true, Some(activity)) = (session.is_focus_app, session.activity) {
+                let weighted = session.duration.mul_f64(activity.clamp(0.0, 1.0));
+                *engaged.get_or_insert(Duration::ZERO) += weighted;
+            }
+        }
+        engaged
+    }
+
+    /// Stretches of at least `threshold` between `window_start` and
+    /// `window_end` that no session covers. Sessions may overlap or arrive in
+    /// any order; `daemon_stops` only labels the gaps.
+    pub fn untracked_gaps<'a>(
+        sessions: impl IntoIterator<Item = &'a FocusSession>,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+        daemon_stops: &[DateTime<Utc>],
+        threshold: Duration,
+    ) -> Vec<UntrackedGap> {
+        let mut spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = sessions.into_iter()
+            .map(|session| {
+                let end = session.end_time.unwrap_or_else(|| {
+                    session.start_time + chrono::Duration::from_std(session.duration).unwrap_or_default()
+                });
+                (session.start_time.max(window_start), end.min(window_end))
+            })
+            .filter(|(start, end)| start < end)
+            .collect();
+        spans.sort();
+
+        let mut gaps = Vec::new();
+        let mut push_gap = |start: DateTime<Utc>, end: DateTime<Utc>| {
+            if end.signed_duration_since(start).to_std().is_ok_and(|gap| gap >= threshold) {
+                // The stop event is written just after the last session ends
+                let daemon_stopped = daemon_stops.iter()
+                    .any(|&stop| stop >= start - chrono::Duration::minutes(1) && stop < end);
+                gaps.push(UntrackedGap { start, end, daemon_stopped });
+            }
+        };
+
+        let mut covered_until = window_start;
+        for (start, end) in spans {
+            if start > covered_until {
+                push_gap(covered_until, start);
+            }
+            covered_until = covered_until.max(end);
+        }
+        if window_end > covered_until {
+            push_gap(covered_until, window_end);
+        }
+        gaps
+    }
+
+    /// Context switches over the `weeks` weeks before `now`, by weekday and hour
+    pub fn calculate_switch_heatmap(db: &Database, weeks: u32, now: DateTime<Utc>) -> Result<SwitchHeatmap, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let since = now - chrono::Duration::weeks(weeks as i64);
+        let mut counts = [[0; 24]; 7];
+        for (weekday, hour, count) in db.get_switch_counts_by_weekday_hour(since)? {
+            // %w counts from Sunday; the rows start on Monday
+            let row = (weekday as usize + 6) % 7;
+            if let Some(cell) = counts.get_mut(row).and_then(|hours| hours.get_mut(hour as usize)) {
+                *cell += count;
+            }
+        }
+        Ok(SwitchHeatmap { weeks, since, counts })
+    }
+
+    /// 7×24 grid of intensity characters scaled to the busiest cell, with a legend
+    pub fn render_switch_heatmap(heatmap: &SwitchHeatmap) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let glyphs = glyphs::current();
+        let (levels, none) = glyphs.levels();
+        let max = heatmap.max_average();
+        let cell = |average: f64| {
+            if average <= 0.0 {
+                none
+            } else {
+                let level = ((average / max) * levels.len() as f64).ceil() as usize;
+                levels[level.clamp(1, levels.len()) - 1]
+            }
+        };
+
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        let mut report = String::new();
+        report.push_str(&format!("\n{}\n\n", top_sep));
+        report.push_str("~=~ CONTEXT SWITCH HEATMAP ~=~\n\n");
+        report.push_str(&format!("Average switches per week over the last {} week{} (since {})\n\n",
+            heatmap.weeks,
+            if heatmap.weeks == 1 { "" } else { "s" },
+            utils::format_datetime_local(heatmap.since)));
+
+        report.push_str("     ");
+        for hour in 0..24 {
+            report.push_str(&format!("{:<3}", format!("{:02}", hour)));
+        }
+        report.push('\n');
+        for (day, name) in HEATMAP_WEEKDAYS.iter().enumerate() {
+            report.push_str(&format!("{:<5}", name));
+            for hour in 0..24 {
+                let c = cell(heatmap.average(day, hour));
+                report.push_str(&format!("{}{} ", c, c));
+            }
+            report.push('\n');
+        }
+
+        report.push('\n');
+        if max > 0.0 {
+            let step = max / levels.len() as f64;
+            let at_most = if glyphs == GlyphSet::Ascii { "<=" } else { "≤" };
+            let legend: Vec<String> = levels.iter().enumerate()
+                .map(|(i, c)| format!("{} {}{:.1}", c, at_most, step * (i + 1) as f64))
+                .collect();
+            report.push_str(&format!("Legend: {} 0  {}\n", none, legend.join("  ")));
+        } else {
+            report.push_str("~=~ No context switches recorded in this range\n");
+        }
+        report.push_str(&format!("\n{}\n", top_sep));
+        report
+    }
+
+    /// The heatmap's matrices for external plotting
+    pub fn switch_heatmap_json(heatmap: &SwitchHeatmap) -> Result<String, Box<dyn std::error::Error>> {
+        let json = SwitchHeatmapJson {
+            weeks: heatmap.weeks,
+            since: heatmap.since.to_rfc3339(),
+            weekdays: HEATMAP_WEEKDAYS,
+            hours: (0..24).collect(),
+            average: (0..7)
+                .map(|day| (0..24).map(|hour| heatmap.average(day, hour)).collect())
+                .collect(),
+            counts: heatmap.counts.iter().map(|hours| hours.to_vec()).collect(),
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Trailing focus efficiency over the tracking days (local, starting
+    /// at day_start_hour) of `now`'s type up to it, or None when nothing
+    /// was tracked. With exclude_weekends_from_averages rest days have no
+    /// trend, so the floor only watches workdays.
+    pub fn calculate_efficiency_trend(db: &Database, settings: &StatsSettings, now: DateTime<Utc>, day_start_hour: u32) -> Result<Option<EfficiencyTrend>, Box<dyn std::error::Error>> {
+        let today = utils::tracking_day(now, day_start_hour);
+        if !settings.counts_toward_averages(settings.day_type(today)) {
+            return Ok(None);
+        }
+        let baseline = settings.baseline_days(today);
+        let mut trend_days: Vec<NaiveDate> = baseline[baseline.len().saturating_sub(EFFICIENCY_TREND_DAYS as usize - 1)..].to_vec();
+        trend_days.push(today);
+        let start = utils::tracking_day_start(trend_days[0], day_start_hour);
+        let end = utils::tracking_day_start(today + chrono::Duration::days(1), day_start_hour);
+        let days: Vec<(NaiveDate, f64)> = db.get_daily_efficiency(start, end, day_start_hour)?
+            .into_iter()
+            .filter(|(day, _)| trend_days.contains(day))
+            .collect();
+        if days.is_empty() {
+            return Ok(None);
+        }
+
+        let average = days.iter().map(|(_, efficiency)| efficiency).sum::<f64>() / days.len() as f64;
+        Ok(Some(EfficiencyTrend { days, average }))
+    }
+
+    /// Consumption of each budget in the period (day or week, starting at
+    /// day_start_hour) that `now` falls in, plus `current` if it started
+    /// in it. Daily budgets with carry_over get the unused time of the days
+    /// before added to their limit.
+    pub fn calculate_budget_usage(
+        db: &Database,
+        settings: &StatsSettings,
+        budgets: &[Budget],
+        now: DateTime<Utc>,
+        day_start_hour: u32,
+        current: Option<&FocusSession>,
+    ) -> Result<Vec<BudgetUsage>, Box<dyn std::error::Error>> {
+        let day = utils::day_start(now, day_start_hour);
+        let week = utils::week_start(now, day_start_hour);
+        let day_usage = db.get_usage_since(day)?;
+        let week_usage = if budgets.iter().any(|budget| budget.period == BudgetPeriod::Week) {
+            db.get_usage_since(week)?
+        } else {
+            Vec::new()
+        };
+
+        let mut usages = Vec::new();
+        for budget in budgets {
+            let (period_start, usage) = match budget.period {
+                BudgetPeriod::Day => (day, &day_usage),
+                BudgetPeriod::Week => (week, &week_usage),
+            };
+            let current = current.filter(|session| session.start_time >= period_start);
+            let carried = if budget.carry_over {
+                let previous = Self::previous_days_used(db, settings, budget, day, day_start_hour)?;
+                carried_over(budget.limit, budget.carry_over_cap, &previous)
+            } else {
+                Duration::ZERO
+            };
+            usages.push(BudgetUsage {
+                target: budget.target.clone(),
+                limit: budget.limit + carried,
+                used: Self::budget_used(&budget.target, usage, current),
+                period: budget.period,
+                period_start,
+                carried,
+            });
+        }
+        Ok(usages)
+    }
+
+    /// What `budget` used on each day before the one starting at `day`,
+    /// oldest first, as far back as carrying over can reach. Only days of
+    /// the same type carry over, so Monday picks up Friday's leftovers and
+    /// not Sunday's.
+    fn previous_days_used(db: &Database, settings: &StatsSettings, budget: &Budget, day: DateTime<Utc>, day_start_hour: u32) -> Result<Vec<Duration>, Box<dyn std::error::Error>> {
+        let days = if budget.limit.is_zero() {
+            1
+        } else {
+            (budget.carry_over_cap.as_secs().div_ceil(budget.limit.as_secs().max(1)) as usize + 1).min(CARRY_OVER_MAX_DAYS)
+        };
+        let kind = settings.day_type(utils::timezone::to_zone(day).date_naive());
+        let mut used = Vec::with_capacity(days);
+        let mut end = day;
+        for _ in 0..CARRY_OVER_MAX_DAYS {
+            if used.len() == days {
+                break;
+            }
+            // Stepping back a second and snapping keeps DST days right
+            let start = utils::day_start(end - chrono::Duration::seconds(1), day_start_hour);
+            if settings.day_type(utils::timezone::to_zone(start).date_naive()) == kind {
+                used.push(Self::calculate_budget_used_between(db, std::slice::from_ref(budget), start, end)?[0]);
+            }
+            end = start;
+        }
+        used.reverse();
+        Ok(used)
+    }
+
+    /// Consumption of each budget by sessions starting in [start, end)
+    pub fn calculate_budget_used_between(
+        db: &Database,
+        budgets: &[Budget],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Duration>, Box<dyn std::error::Error>> {
+        let usage = db.get_usage_between(start, end)?;
+        Ok(budgets.iter().map(|budget| Self::budget_used(&budget.target, &usage, None)).collect())
+    }
+
+    fn budget_used(target: &str, usage: &[(String, Option<String>, Duration)], current: Option<&FocusSession>) -> Duration {
+        let mut used: Duration = usage.iter()
+            .filter(|(app, domain, _)| Self::counts_toward_budget(target, app, domain.as_deref()))
+            .map(|(_, _, duration)| *duration)
+            .sum();
+        if let Some(session) = current {
+            if Self::counts_toward_budget(target, &session.app_name, session.domain.as_deref()) {
+                used += session.duration;
+            }
+        }
+        used
+    }
+
+    /// Budget targets match an app name exactly or a browser tab title by substring
+    fn counts_toward_budget(target: &str, app_name: &str, domain: Option<&str>) -> bool {
+        app_name.eq_ignore_ascii_case(target)
+            || desktop::display_name(app_name).eq_ignore_ascii_case(target)
+            || domain.is_some_and(|tab| !target.is_empty() && tab.to_lowercase().contains(&target.to_lowercase()))
+    }
+
+    pub fn display_budgets(budgets: &[BudgetUsage], now: DateTime<Utc>) {
+        println!("BUDGETS\n");
+        let layout = ReportLayout::current();
+        for budget in budgets {
+            let percent = budget.percent();
+            let marker = if percent >= 100.0 { " ⚠️ over budget" } else { "" };
+            let period = match budget.period {
+                BudgetPeriod::Day => "today".to_string(),
+                BudgetPeriod::Week => format!("this week, day {} of 7",
+                    ((now - budget.period_start).num_days() + 1).clamp(1, 7)),
+            };
+            let carried = if budget.carried > Duration::ZERO {
+                format!(", {} carried over", utils::format_duration(budget.carried, DurationStyle::Long))
+            } else {
+                String::new()
+            };
+            println!("{} {} / {} {} ({:.0}%{}){}\n",
+                bar_row(&layout, &budget.target, 20, percent / 100.0, 20, BarStyle::Blocks),
+                utils::format_duration(budget.used, DurationStyle::Long),
+                utils::format_duration(budget.limit, DurationStyle::Long),
+                period,
+                percent,
+                carried,
+                marker
+            );
+        }
+    }
+
+    pub fn display_efficiency_warning(trend: &EfficiencyTrend, floor: f64) {
+        println!("⚠️  FOCUS EFFICIENCY BELOW FLOOR\n");
+        println!("{}-day average     : {:.0}% (floor {:.0}%)\n", EFFICIENCY_TREND_DAYS, trend.average, floor);
+        println!("Trend             : {}\n", trend.describe());
+    }
+
+    pub fn save_daily_stats_cache(stats: &DailyStats) -> Result<(), Box<dyn std::error::Error>> {
+        let data_dir = utils::ensure_profile_data_directory()?;
+        let cached = CachedDailyStats { saved_at: Utc::now(), stats: stats.clone() };
+        std::fs::write(data_dir.join(DAILY_STATS_CACHE_FILE), serde_json::to_string(&cached)?)?;
+        Ok(())
+    }
+
+    pub fn load_daily_stats_cache() -> Option<CachedDailyStats> {
+        let path = utils::profile_data_directory(utils::active_profile())?.join(DAILY_STATS_CACHE_FILE);
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// One row per day with numeric hours, for spreadsheets
+    pub fn daily_summary_csv(days: &[DailyStats]) -> String {
+        format!("{}{}", DAILY_CSV_HEADER, Self::daily_summary_csv_rows(days))
+    }
+
+    /// `daily_summary_csv` without its header, so exports written in
+    /// chunks can be put together
+    pub fn daily_summary_csv_rows(days: &[DailyStats]) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let hours = |duration: Duration| duration.as_secs_f64() / 3600.0;
+        let mut csv = String::new();
+        for day in days {
+            csv.push_str(&format!(
+                "{},{:.2},{:.2},{:.2},{},{},{:.2},{},{:.2}\n",
+                day.date.format("%Y-%m-%d"),
+                hours(day.total_focus_time),
+                hours(day.total_distraction_time),
+                hours(day.untracked_time),
+                day.context_switches,
+                day.deep_focus_sessions,
+                day.focus_efficiency,
+                day.focus_score.map(|score| score.to_string()).unwrap_or_default(),
+                hours(day.assistant_time)
+            ));
+        }
+        csv
+    }
+
+    pub fn display_daily_stats(stats: &DailyStats, settings: &StatsSettings) {
+        let _timer = timing::scope(Phase::Render);
+        let today = stats.date;
+        let layout = ReportLayout::current();
+        let pad = layout.cells(30);
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        println!("\n{}\n", top_sep);
+        println!("DAILY FOCUS SUMMARY\n");
+        println!("{}\n", utils::format_datetime_local(today));
+        for note in stats.day_notes() {
+            println!("Note              : {:<pad$}\n", note);
+        }
+        if let Some(score) = stats.focus_score {
+            println!("FOCUS SCORE       : {:<pad$}\n", describe_focus_score(score, stats.focus_score_7d_avg, settings.day_type(stats.date.date_naive())));
+        }
+        println!("Focus Time        : {:<pad$}\n", utils::format_duration(stats.total_focus_time, DurationStyle::Long));
+        if let Some(engaged) = stats.engaged_focus_time {
+            println!("Engaged Focus     : {:<pad$}\n", utils::format_duration(engaged, DurationStyle::Long));
+        }
+        if !stats.power_focus.is_empty() && stats.power_filter.is_none() {
+            println!("Focus by Power    : {:<pad$}\n", stats.describe_power_focus());
+        }
+        println!("Distraction Time  : {:<pad$}\n", utils::format_duration(stats.total_distraction_time, DurationStyle::Long));
+        if stats.untracked_time > Duration::ZERO {
+            println!("Untracked         : {:<pad$}\n", utils::format_duration(stats.untracked_time, DurationStyle::Long));
+        }
+        if stats.quiet_time > Duration::ZERO {
+            println!("Quiet Hours       : {:<pad$}\n", utils::format_duration(stats.quiet_time, DurationStyle::Long));
+        }
+        if stats.suspended_time > Duration::ZERO {
+            println!("Suspended         : {:<pad$}\n", utils::format_duration(stats.suspended_time, DurationStyle::Long));
+        }
+        if stats.passive_time > Duration::ZERO {
+            println!("Passive Time      : {:<pad$}\n", utils::format_duration(stats.passive_time, DurationStyle::Long));
+        }
+        if stats.assistant_time > Duration::ZERO {
+            println!("Assistant Time    : {:<pad$}\n", stats.describe_assistant_time());
+        }
+        if let Some(bookends) = stats.describe_focus_bookends() {
+            println!("First Focus       : {:<pad$}\n", bookends);
+        }
+        for co_focus in &stats.co_focus {
+            println!("Co-focus          : {:<pad$}\n", co_focus.describe());
+        }
+        println!("Context Switches  : {:<pad$}\n", stats.context_switches);
+        if !stats.switching_cost.is_empty() {
+            println!("Switching Cost    : {:<pad$}\n", stats.switching_cost.summary());
+        }
+        println!("Focus Efficiency  : {:<pad$}\n", with_bar(
+            layout.bar(stats.focus_efficiency / 100.0, 30, BarStyle::Blocks),
+            format!("{:.0}%", stats.focus_efficiency),
+        ));
+        if !stats.most_used_apps.is_empty() {
+            println!("TOP APPLICATIONS\n");
+            for (i, (app, duration)) in stats.most_used_apps.iter().take(5).enumerate() {
+                // Compact lines have no bars to make room for, so names get more
+                let name_cells = if layout.is_compact() { 36 } else { 20 };
+                let name_width = layout.cells(name_cells);
+                let trend = stats.app_trend(app)
+                    .map(|trend| format!("  {}", describe_app_trend(*duration, trend.average)))
+                    .unwrap_or_default();
+                println!("{}. {:<name_width$} : {:<pad$}\n", i + 1, layout.fit(app, name_cells),
+                    format!("{}{}", utils::format_duration(*duration, DurationStyle::Long), trend));
+                if let Some(sites) = stats.browser_sites(app) {
+                    for (label, time) in [("focus sites", sites.focus), ("distraction sites", sites.distraction), ("other sites", sites.unclassified)] {
+                        println!("   {:<name_width$} : {:<pad$}\n", format!("↳ {}", label), utils::format_duration(time, DurationStyle::Long));
+                    }
+                }
+            }
+        }
+        if !stats.round_trips.is_empty() {
+            println!("ROUND TRIPS (back within {})\n", utils::format_duration(settings.round_trip_window, DurationStyle::Long));
+            for pair in &stats.round_trips {
+                println!("{}\n", pair.describe());
+            }
+        }
+        println!("{}\n", top_sep);
+    }
+
+    /// `stats --json`: today's summary with each top app's 7-day comparison
+    pub fn daily_stats_json(stats: &DailyStats) -> Result<String, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Render);
+        let json = DailyStatsJson {
+            schema_version: DAILY_JSON_SCHEMA_VERSION,
+            date: stats.date.format("%Y-%m-%d").to_string(),
+            focus_seconds: stats.total_focus_time.as_secs(),
+            distraction_seconds: stats.total_distraction_time.as_secs(),
+            untracked_seconds: stats.untracked_time.as_secs(),
+            quiet_seconds: stats.quiet_time.as_secs(),
+            suspended_seconds: stats.suspended_time.as_secs(),
+            passive_seconds: stats.passive_time.as_secs(),
+            assistant_seconds: stats.assistant_time.as_secs(),
+            assistant_counts_as: stats.assistant_counts_as.label(),
+            context_switches: stats.context_switches,
+            deep_focus_sessions: stats.deep_focus_sessions,
+            focus_efficiency: stats.focus_efficiency,
+            focus_score: stats.focus_score,
+            focus_score_7d_avg: stats.focus_score_7d_avg,
+            time_to_first_focus_seconds: stats.time_to_first_focus.map(|time| time.as_secs()),
+            focus_wind_down_seconds: stats.focus_wind_down.map(|time| time.as_secs()),
+            co_focus: stats.co_focus.iter()
+                .map(|co_focus| CoFocusJson {
+                    label: &co_focus.label,
+                    seconds: co_focus.time.as_secs(),
+                    credited_seconds: co_focus.credited.iter().map(|(side, time)| (side.as_str(), time.as_secs())).collect(),
+                })
+                .collect(),
+            notes: stats.day_notes(),
+            top_apps: stats.most_used_apps.iter()
+                .map(|(app, duration)| TopAppJson {
+                    sites: stats.browser_sites(app).map(|sites| SitesJson {
+                        focus_seconds: sites.focus.as_secs(),
+                        distraction_seconds: sites.distraction.as_secs(),
+                        unclassified_seconds: sites.unclassified.as_secs(),
+                    }),
+                    ..TopAppJson::new(app, *duration, stats.app_trend(app))
+                })
+                .collect(),
+            groups: stats.group_usage.iter()
+                .filter(|(_, _, is_group)| *is_group)
+                .map(|(group, duration, _)| {
+                    TopAppJson::new(group, *duration, stats.group_trends.iter().find(|trend| trend.app == *group))
+                })
+                .collect(),
+            round_trips: stats.round_trips.iter()
+                .map(|pair| RoundTripJson {
+                    home: &pair.home,
+                    away: &pair.away,
+                    round_trips: pair.round_trips,
+                    time_away_seconds: pair.time_away.as_secs(),
+                })
+                .collect(),
+            switching_cost: (!stats.switching_cost.is_empty()).then_some(SwitchCostJson {
+                estimated_seconds: stats.switching_cost.total.as_secs(),
+                measured_switches: stats.switching_cost.measured,
+                assumed_switches: stats.switching_cost.assumed,
+                skipped_switches: stats.switching_cost.skipped,
+                default_cost_seconds: stats.switching_cost.default_cost.as_secs(),
+            }),
+            focus_by_power: stats.power_focus.iter().map(|(power, time)| (power.as_str(), time.as_secs())).collect(),
+            power_filter: stats.power_filter.map(|power| power.as_str()),
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    pub fn calculate_session_history(db: &Database, days: &LocalDays, session_name: &str, now: DateTime<Utc>) -> Result<SessionHistory, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        Ok(SessionHistory {
+            focus_last_7_days: db.get_focus_time_since(now - chrono::Duration::days(7))?,
+            focus_last_30_days: db.get_focus_time_since(now - chrono::Duration::days(30))?,
+            streak_days: Self::current_streak(&db.get_focus_days(days)?, days.day_of(now)),
+            efficiency_rank: db.get_session_efficiency_rank(session_name, HISTORY_RANK_SESSIONS)?,
+        })
+    }
+
+    /// Consecutive days with focus time up to today. A day without focus
+    /// time yet doesn't break the streak until it is over.
+    fn current_streak(days_desc: &[NaiveDate], today: NaiveDate) -> usize {
+        let mut expected = match days_desc.first() {
+            Some(day) if *day == today => today,
+            Some(day) if *day == today - chrono::Duration::days(1) => *day,
+            _ => return 0,
+        };
+        let mut streak = 0;
+        for day in days_desc {
+            if *day != expected {
+                break;
+            }
+            streak += 1;
+            expected -= chrono::Duration::days(1);
+        }
+        streak
+    }
+
+    pub fn generate_history_footer(history: &SessionHistory) -> String {
+        let mut footer = String::new();
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        footer.push_str("RECENT HISTORY
+
+");
+        footer.push_str(&format!("Focus (7 days)  : {}
+
+", utils::format_duration(history.focus_last_7_days, DurationStyle::Long)));
+        footer.push_str(&format!("Focus (30 days) : {}
+
+", utils::format_duration(history.focus_last_30_days, DurationStyle::Long)));
+        footer.push_str(&format!("Current Streak  : {} day{}
+
+",
+            history.streak_days, if history.streak_days == 1 { "" } else { "s" }));
+        if let Some((beaten, compared)) = history.efficiency_rank {
+            let percent = beaten as f64 / compared as f64 * 100.0;
+            footer.push_str(&format!("Efficiency Rank : better than {:.0}% of recent sessions ({} compared)
+
+", percent, compared));
+        }
+        footer.push_str(&format!("{}
+
+", top_sep));
+        footer
+    }
+
+    /// "🎯 3h 12m focus, 82% efficiency, 14 switches — 'api refactor'"
+    pub fn share_oneline(session: &AggregatedSession) -> String {
+        format!("🎯 {} focus, {:.0}% efficiency, {} switch{} — '{}'",
+            utils::format_duration(session_focus_time(session), DurationStyle::Long),
+            session.focus_efficiency,
+            session.context_switches,
+            if session.context_switches == 1 { "" } else { "es" },
+            session.session_name)
+    }
+
+    /// A post of at most TWEET_MAX_CHARS characters; only the session name is
+    /// shortened to fit
+    pub fn share_tweet(session: &AggregatedSession) -> String {
+        let compose = |name: &str| format!("🎯 {} of focus on '{}': {:.0}% efficiency, {} context switch{}. #focusdebt",
+            utils::format_duration(session_focus_time(session), DurationStyle::Long),
+            name,
+            session.focus_efficiency,
+            session.context_switches,
+            if session.context_switches == 1 { "" } else { "es" });
+        let overhead = compose("").chars().count();
+        let room = TWEET_MAX_CHARS.saturating_sub(overhead);
+        utils::truncate_chars(&compose(&utils::truncate_chars(&session.session_name, room)), TWEET_MAX_CHARS)
+    }
+
+    /// A shields.io-style SVG badge: "focus today | 3.2h", colored by the
+    /// day's efficiency
+    pub fn share_badge_svg(stats: &DailyStats) -> String {
+        let label = "focus today";
+        let value = format!("{:.1}h", stats.total_focus_time.as_secs_f64() / 3600.0);
+        let color = efficiency_color(stats.focus_efficiency);
+        // Verdana 11px averages about 7px a character, plus 5px padding a side
+        let text_width = |text: &str| text.chars().count() * 7 + 10;
+        let (label_width, value_width) = (text_width(label), text_width(&value));
+        let width = label_width + value_width;
+        let (label, value) = (html_escape(label), html_escape(&value));
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+  <title>{label}: {value}</title>
+  <linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text>
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{value_x}" y="15" fill="#010101" fill-opacity=".3">{value}</text>
+    <text x="{value_x}" y="14">{value}</text>
+  </g>
+</svg>
+"##,
+            label_x = label_width / 2,
+            value_x = label_width + value_width / 2,
+        )
+    }
+
+    pub fn generate_session_share_report(session: &AggregatedSession, settings: &StatsSettings) -> String {
+        Self::session_share_report(session, settings.ascii_art)
+    }
+
+    /// `generate_session_share_report`, with the hourglass when `art`
+    fn session_share_report(session: &AggregatedSession, art: bool) -> String {
+        let layout = ReportLayout::current();
+        let mut report = String::new();
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        let time_range = if let Some(end) = session.end_time {
+            format!("{} → {}{}", 
+                utils::format_timestamp_local(session.start_time), 
+                utils::format_timestamp_local(end),
+                utils::utc_offset_note(end))
+        } else {
+            format!("{} → ongoing{}", utils::format_timestamp_local(session.start_time), utils::utc_offset_note(session.start_time))
+        };
+        
+        // Calculate focus time from app usage
+        let focus_time: std::time::Duration = session.app_usage.iter()
+            .filter(|(_, _, is_focus)| *is_focus)
+            .map(|(_, duration, _)| *duration)
+            .sum();
+        
+        report.push('\n');
+        report.push_str(&format!("{}\n\n", top_sep));
+        report.push_str("~=~ SESSION REPORT ~=~\n\n");
+        if art {
+            report.push_str(HOURGLASS_ART);
+            report.push_str("\n\n");
+        }
+        report.push_str(&format!("Session: {}\n\n", utils::escape_control_chars(&session.session_name)));
+        report.push_str(&format!("Time: {}\n\n", time_range));
+        report.push_str(&format!("Focus Time: {}\n\n", utils::format_duration(focus_time, DurationStyle::Long)));
+        report.push_str(&format!("Focus Efficiency: {:.0}%\n\n", session.focus_efficiency));
+        if let Some(score) = session.focus_score {
+            report.push_str(&format!("Focus Score: {}\n\n", score));
+        }
+        if let Some(cost) = session.switching_cost.filter(|cost| !cost.is_empty()) {
+            report.push_str(&format!("{} ({})\n\n", cost.headline(), cost.basis()));
+        }
+        
+        // Separate browser apps from regular apps
+        let (browser_apps, regular_apps): (Vec<_>, Vec<_>) = session.app_usage.iter()
+            .partition(|(app, _, _)| Self::is_browser_app(app));
+        
+        // Show regular applications (non-browser)
+        if !regular_apps.is_empty() {
+            report.push_str("TOP APPLICATIONS\n\n");
+            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+            for (i, (app, duration, _)) in regular_apps.iter().take(5).enumerate() {
+                let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                let fraction = duration.as_secs() as f64 / max_duration as f64;
+                report.push_str(&format!("{}. {} {:<12}\n\n", i + 1, bar_row(&layout, app, 15, fraction, 20, BarStyle::Shade), duration_str));
+            }
+        }
+        
+        // Show browser apps grouped together (no individual tabs)
+        if !browser_apps.is_empty() {
+            report.push_str("BROWSER APPLICATIONS\n\n");
+            let max_duration = browser_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+            for (i, (app, duration, _)) in browser_apps.iter().take(5).enumerate() {
+                let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                let fraction = duration.as_secs() as f64 / max_duration as f64;
+                report.push_str(&format!("{}. {} {:<12}\n\n", i + 1, bar_row(&layout, app, 15, fraction, 20, BarStyle::Shade), duration_str));
+            }
+        }
+        report.push_str(&format!("{}\n\n", top_sep));
+        report
+    }
+
+    pub fn list_sessions(db: &Database, settings: &StatsSettings, _last: Option<usize>, _date: Option<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let mut sessions = Vec::new();
+        let mut all_sessions = Self::recent_rows(db, settings)?;
+        
+        // Unnamed rows get one summary line instead of a slot per day
+        let unnamed = Self::describe_unnamed_rows(&all_sessions);
+        all_sessions.retain(|session| !session.session_name.is_empty());
+
+        // Group by session name and aggregate
+        let mut aggregated = Self::aggregate_sessions_by_name(settings, &all_sessions, &Self::recent_suspends(db)?);
+        Self::attach_intents(db, &mut aggregated)?;
+        let take_n = 20; // Show last 20 sessions
+        for (i, session) in aggregated.iter().take(take_n).enumerate() {
+            sessions.push(Self::format_session_summary(i + 1, session));
+        }
+        sessions.extend(unnamed);
+        Ok(sessions)
+    }
+
+    /// Fill in each session's intent, rating and task, where it has them
+    fn attach_intents(db: &Database, sessions: &mut [AggregatedSession]) -> Result<(), Box<dyn std::error::Error>> {
+        let intents = db.get_session_intents()?;
+        let tasks = db.get_session_tasks()?;
+        for session in sessions {
+            session.intent = intents.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&session.session_name))
+                .map(|(_, intent)| intent.clone());
+            session.task = tasks.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&session.session_name))
+                .map(|(_, task)| task.clone());
+        }
+        Ok(())
+    }
+
+    /// "(unnamed) 42 rows on 3 days: ..." for rows recorded without a
+    /// session name, or None when there are none
+    fn describe_unnamed_rows(sessions: &[FocusSession]) -> Option<String> {
+        let rows = sessions.iter().filter(|session| session.session_name.is_empty()).count();
+        if rows == 0 {
+            return None;
+        }
+        let days: std::collections::BTreeSet<NaiveDate> = sessions.iter()
+            .filter(|session| session.session_name.is_empty())
+            .map(|session| session.start_time.with_timezone(&utils::timezone::zone()).date_naive())
+            .collect();
+        let mut listed: Vec<String> = days.iter().rev().take(UNNAMED_DAYS_LISTED).map(|day| utils::format_date_local(*day)).collect();
+        if days.len() > UNNAMED_DAYS_LISTED {
+            listed.push("...".to_string());
+        }
+        Some(format!("(unnamed) {} row{} on {} day{} without a session name: {}. Name them with 'focusdebt sessions adopt <name> --date YYYY-MM-DD'",
+            rows, if rows == 1 { "" } else { "s" },
+            days.len(), if days.len() == 1 { "" } else { "s" },
+            listed.join(", ")))
+    }
+
+    /// Session list merged across several profile databases, newest first,
+    /// each line tagged with the profile it came from
+    pub fn list_sessions_across_profiles(dbs: &[(String, Database)], settings: &StatsSettings) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let mut aggregated = Vec::new();
+        let mut unnamed = Vec::new();
+        for (profile, db) in dbs {
+            let mut all_sessions = Vec::new();
+            let local_days = LocalDays::new(0);
+            let today = local_days.day_of(Utc::now());
+            for days_ago in 0..30 {
+                let day = today - chrono::Duration::days(days_ago);
+                all_sessions.extend(db.get_sessions_for_date(&local_days, day)?);
+            }
+            if let Some(line) = Self::describe_unnamed_rows(&all_sessions) {
+                unnamed.push(format!("{}  [{}]", line, profile));
+            }
+            all_sessions.retain(|session| !session.session_name.is_empty());
+            let mut sessions = Self::aggregate_sessions_by_name(settings, &all_sessions, &Self::recent_suspends(db)?);
+            Self::attach_intents(db, &mut sessions)?;
+            aggregated.extend(sessions.into_iter().map(|session| (profile.as_str(), session)));
+        }
+        aggregated.sort_by(|(a_profile, a), (b_profile, b)| b.start_time.cmp(&a.start_time)
+            .then_with(|| a.session_name.cmp(&b.session_name))
+            .then_with(|| a_profile.cmp(b_profile)));
+
+        let take_n = 20; // Show last 20 sessions
+        let mut lines: Vec<String> = aggregated.iter()
+            .take(take_n)
+            .enumerate()
+            .map(|(i, (profile, session))| format!("{}  [{}]", Self::format_session_summary(i + 1, session), profile))
+            .collect();
+        lines.extend(unnamed);
+        Ok(lines)
+    }
+
+    pub fn show_session_details(db: &Database, settings: &StatsSettings, query: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Render);
+        let mut session = Self::find_session(db, settings, query)?;
+        session.intent = db.get_session_intent(&session.session_name)?;
+        session.task = db.get_session_task(&session.session_name)?;
+        Ok(Self::format_session_report(&session))
+    }
+
+    /// `sessions show --json`: the aggregated session plus the context switches
+    /// recorded while it ran, wrapped in a versioned envelope.
+    pub fn session_details_json(db: &Database, settings: &StatsSettings, query: &str, include_events: bool) -> Result<String, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Render);
+        let mut session = Self::find_session(db, settings, query)?;
+        session.task = db.get_session_task(&session.session_name)?;
+        let end = session.end_time.unwrap_or_else(Utc::now);
+        let switches = db.get_context_switches_between(session.start_time, end)?;
+
+        let mut session_json = Self::session_json(&session, &switches);
+        if include_events {
+            let events = db.get_events_between(session.start_time, end, None)?;
+            session_json.events = Some(events.into_iter().map(Self::event_json).collect());
+        }
+
+        let envelope = SessionJsonEnvelope {
+            schema_version: SESSION_JSON_SCHEMA_VERSION,
+            session: session_json,
+        };
+        Ok(serde_json::to_string_pretty(&envelope)?)
+    }
+
+    /// Load every row, switch and the summary for one session name, across
+    /// all of history (unlike `sessions show`, which looks back 30 days)
+    pub fn load_session_export(db: &Database, settings: &StatsSettings, session_name: &str) -> Result<SessionExport, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let (mut session, rows) = Self::aggregate_named_session(db, settings, session_name)?;
+        session.task = db.get_session_task(session_name)?;
+        let end = session.end_time.unwrap_or_else(Utc::now);
+        let switches = db.get_context_switches_between(session.start_time, end)?;
+        let meta = db.get_session_meta(session_name)?;
+        let events = db.get_events_between(session.start_time, end, None)?
+            .into_iter()
+            .filter(|event| event.kind != EventKind::WindowChange)
+            .collect();
+        Ok(SessionExport { session, rows, switches, meta, events })
+    }
+
+    /// One session name's rows across all of history, and the session they
+    /// add up to with suspends taken out
+    pub fn aggregate_named_session(db: &Database, settings: &StatsSettings, session_name: &str) -> Result<(AggregatedSession, Vec<FocusSession>), Box<dyn std::error::Error>> {
+        let rows = merge_short_gaps(db.get_sessions_by_name(session_name)?, settings.merge_gap);
+        let suspends = match (rows.iter().map(|row| row.start_time).min(), rows.iter().filter_map(|row| row.end_time).max()) {
+            (Some(start), end) => suspend_gaps(db, start, end.unwrap_or_else(Utc::now))?,
+            (None, _) => Vec::new(),
+        };
+        let session = Self::aggregate_sessions_by_name(settings, &rows, &suspends)
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Session not found: {}", session_name))?;
+        Ok((session, rows))
+    }
+
+    /// `stats task`: every session linked to the task `query` names (see
+    /// `SessionTask::matches`), across all of history
+    pub fn calculate_task_stats(db: &Database, settings: &StatsSettings, query: &str, uuid: Option<&str>) -> Result<TaskReport, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let mut sessions = Vec::new();
+        for (session_name, task) in db.get_session_tasks()? {
+            if !task.matches(query, uuid) {
+                continue;
+            }
+            // A linked session whose rows were all pruned has nothing to add
+            if let Ok((mut session, _)) = Self::aggregate_named_session(db, settings, &session_name) {
+                session.task = Some(task);
+                sessions.push(session);
+            }
+        }
+        Self::attach_intents(db, &mut sessions)?;
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.start_time));
+        Ok(task_report(query, sessions))
+    }
+
+    pub fn render_task_stats(report: &TaskReport) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let Some(task) = report.sessions.first().and_then(|session| session.task.as_ref()) else {
+            return format!("No sessions are linked to task '{}'\n", report.query);
+        };
+        let mut text = format!("~=~ TASK: {} ~=~\n\n", task.describe());
+        text.push_str(&format!("Sessions:   {} on {} day{}\n", report.sessions.len(), report.days, if report.days == 1 { "" } else { "s" }));
+        text.push_str(&format!("Tracked:    {}\n", utils::format_duration(report.tracked, DurationStyle::Long)));
+        text.push_str(&format!("Focus:      {} ({:.0}%)\n", utils::format_duration(report.focus, DurationStyle::Long), report.focus_efficiency));
+        text.push_str(&format!("Switches:   {}\n\n", report.context_switches));
+        for (i, session) in report.sessions.iter().enumerate() {
+            text.push_str(&format!("{}\n", Self::format_session_summary(i + 1, session)));
+        }
+        text
+    }
+
+    pub fn task_stats_json(report: &TaskReport) -> Result<String, Box<dyn std::error::Error>> {
+        let json = TaskReportJson {
+            query: &report.query,
+            task: report.sessions.first().and_then(|session| session.task.as_ref()).map(TaskJson::from),
+            days: report.days,
+            tracked_seconds: report.tracked.as_secs(),
+            focus_seconds: report.focus.as_secs(),
+            focus_efficiency: report.focus_efficiency,
+            context_switches: report.context_switches,
+            sessions: report.sessions.iter()
+                .map(|session| TaskSessionJson {
+                    session_name: &session.session_name,
+                    start_time: session.start_time.to_rfc3339(),
+                    end_time: session.end_time.map(|t| t.to_rfc3339()),
+                    tracked_seconds: session.tracked_time().as_secs(),
+                    focus_efficiency: session.focus_efficiency,
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Render a session export in one of `SESSION_EXPORT_FORMATS`
+    pub fn render_session_export(export: &SessionExport, format: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Render);
+        match format {
+            "json" => Self::session_export_json(export),
+            "csv" => Ok(Self::session_export_csv(export)),
+            "markdown" => Ok(Self::session_export_markdown(export)),
+            "html" => Ok(Self::session_export_html(export)),
+            _ => Err(format!("Unsupported format '{}'. Supported formats: {}", format, SESSION_EXPORT_FORMATS.join(", ")).into()),
+        }
+    }
+
+    fn session_export_json(export: &SessionExport) -> Result<String, Box<dyn std::error::Error>> {
+        let mut session = Self::session_json(&export.session, &export.switches);
+        session.events = Some(export.events.iter().cloned().map(Self::event_json).collect());
+        let document = SessionExportJson {
+            schema_version: SESSION_JSON_SCHEMA_VERSION,
+            session,
+            rows: export.rows.iter()
+                .map(|row| SessionRowJson {
+                    start_time: row.start_time.to_rfc3339(),
+                    end_time: row.end_time.map(|t| t.to_rfc3339()),
+                    app_name: &row.app_name,
+                    window_title: &row.window_title,
+                    domain: row.domain.as_deref(),
+                    duration_seconds: row.duration.as_secs(),
+                    is_focus_app: row.is_focus_app,
+                    activity: row.activity,
+                    workspace: row.workspace.as_deref(),
+                    open: row.is_open_ended(),
+                    manual: row.manual,
+                    focus_rule: row.focus_rule.as_ref().map(RuleMatch::to_column),
+                    co_focus: row.co_focus.as_deref(),
+                })
+                .collect(),
+            recorded_with: export.meta.as_ref().map(|meta| RecordedWithJson {
+                recorded_at: meta.recorded_at.to_rfc3339(),
+                snapshot_hash: &meta.hash,
+                settings: &meta.snapshot,
+            }),
+        };
+        Ok(serde_json::to_string_pretty(&document)? + "\n")
+    }
+
+    /// One line per raw row; the summary and switches only fit the other formats
+    fn session_export_csv(export: &SessionExport) -> String {
+        let mut csv = String::from("start_time,end_time,app_name,window_title,domain,duration_seconds,is_focus_app,activity\n");
+        for row in &export.rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                row.start_time.to_rfc3339(),
+                row.end_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                csv_field(&row.app_name),
+                csv_field(&row.window_title),
+                csv_field(row.domain.as_deref().unwrap_or("")),
+                row.duration.as_secs(),
+                row.is_focus_app,
+                row.activity.map(|a| format!("{:.2}", a)).unwrap_or_default()
+            ));
+        }
+        csv
+    }
+
+    /// Summary lines followed by (heading, header, rows) tables, shared by
+    /// the markdown and HTML exports
+    fn session_export_sections(export: &SessionExport) -> (Vec<(String, String)>, Vec<ExportTable>) {
+        let s = &export.session;
+        let mut summary = vec![
+            ("Started".to_string(), format!("{}{}", utils::format_datetime_local(s.start_time), utils::utc_offset_note(s.start_time))),
+            ("Ended".to_string(), s.describe_end(|end| format!("{}{}", utils::format_datetime_local(end), utils::utc_offset_note(end)))),
+            ("Duration".to_string(), utils::format_duration(s.total_duration, DurationStyle::Long)),
+            ("Focus efficiency".to_string(), format!("{:.0}%", s.focus_efficiency)),
+            ("Context switches".to_string(), export.switches.len().to_string()),
+        ];
+        if let Some(score) = s.focus_score {
+            summary.push(("Focus score".to_string(), score.to_string()));
+        }
+        if let Some(engaged) = s.engaged_focus_time {
+            summary.push(("Engaged focus".to_string(), utils::format_duration(engaged, DurationStyle::Long)));
+        }
+        if s.untracked_time > Duration::ZERO {
+            summary.push(("Untracked".to_string(), utils::format_duration(s.untracked_time, DurationStyle::Long)));
+        }
+        if let Some(task) = &s.task {
+            summary.push(("Task".to_string(), task.describe()));
+        }
+        if let Some(meta) = &export.meta {
+            summary.push(("Recorded with".to_string(), recorded_with(meta)));
+        }
+
+        let usage_rows = |entries: &[(String, Duration, bool)]| entries.iter()
+            .map(|(name, duration, is_focus)| vec![
+                name.clone(),
+                utils::format_duration(*duration, DurationStyle::Long),
+                if *is_focus { "Focus" } else { "Other" }.to_string(),
+            ])
+            .collect::<Vec<_>>();
+
+        let tables = vec![
+            ExportTable {
+                heading: "Applications",
+                header: &["App", "Time", "Type"],
+                rows: usage_rows(&s.app_usage),
+            },
+            ExportTable {
+                heading: "Browser tabs",
+                header: &["Tab", "Time", "Type"],
+                rows: usage_rows(&s.domain_usage),
+            },
+            ExportTable {
+                heading: "Workspaces",
+                header: &["Workspace", "Time", "Focus"],
+                rows: s.workspace_usage.iter()
+                    .map(|workspace| vec![
+                        workspace.label().to_string(),
+                        utils::format_duration(workspace.total, DurationStyle::Long),
+                        format!("{:.0}%", workspace.efficiency()),
+                    ])
+                    .collect(),
+            },
+            ExportTable {
+                heading: "Context switches",
+                header: &["Time", "From", "To", "Recovery"],
+                rows: export.switches.iter()
+                    .map(|switch| vec![
+                        utils::format_timestamp(switch.timestamp),
+                        switch.from_app.clone(),
+                        switch.to_app.clone(),
+                        switch.recovery_time
+                            .map(|d| utils::format_duration(d, DurationStyle::Long))
+                            .unwrap_or_default(),
+                    ])
+                    .collect(),
+            },
+            ExportTable {
+                heading: "Events",
+                header: &["Time", "Event", "Details"],
+                rows: export.events.iter()
+                    .map(|event| vec![
+                        utils::format_timestamp(event.timestamp),
+                        event.kind.as_str().to_string(),
+                        event_label(event),
+                    ])
+                    .collect(),
+            },
+            ExportTable {
+                heading: "Recorded windows",
+                header: &["Start", "App", "Window", "Time", "Type"],
+                rows: export.rows.iter()
+                    .map(|row| vec![
+                        utils::format_timestamp(row.start_time),
+                        row.app_name.clone(),
+                        row.window_title.clone(),
+                        utils::format_duration(row.duration, DurationStyle::Long),
+                        if row.is_focus_app { "Focus" } else { "Other" }.to_string(),
+                    ])
+                    .collect(),
+            },
+        ];
+        (summary, tables)
+    }
+
+    fn session_export_markdown(export: &SessionExport) -> String {
+        let (summary, tables) = Self::session_export_sections(export);
+        let cell = |text: &str| text.replace('|', "\\|");
+
+        let mut markdown = format!("# {}\n\n", utils::escape_control_chars(&export.session.session_name));
+        for (label, value) in &summary {
+            markdown.push_str(&format!("- **{}:** {}\n", label, value));
+        }
+        for table in tables.iter().filter(|table| !table.rows.is_empty()) {
+            markdown.push_str(&format!("\n## {}\n\n", table.heading));
+            markdown.push_str(&format!("| {} |\n", table.header.join(" | ")));
+            markdown.push_str(&format!("|{}\n", " --- |".repeat(table.header.len())));
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|value| cell(value)).collect();
+                markdown.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        markdown
+    }
+
+    fn session_export_html(export: &SessionExport) -> String {
+        let (summary, tables) = Self::session_export_sections(export);
+        let title = html_escape(&export.session.session_name);
+
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+            title, title
+        );
+        for (label, value) in &summary {
+            html.push_str(&format!("<li><strong>{}:</strong> {}</li>\n", label, html_escape(value)));
+        }
+        html.push_str("</ul>\n");
+        for table in tables.iter().filter(|table| !table.rows.is_empty()) {
+            html.push_str(&format!("<h2>{}</h2>\n<table>\n<tr>", table.heading));
+            for column in table.header {
+                html.push_str(&format!("<th>{}</th>", column));
+            }
+            html.push_str("</tr>\n");
+            for row in &table.rows {
+                html.push_str("<tr>");
+                for value in row {
+                    html.push_str(&format!("<td>{}</td>", html_escape(value)));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// `sessions show --raw`: every row and context switch behind a session
+    /// in time order, one aligned line each, RAW_PAGE_SIZE lines per page
+    pub fn render_raw_session(export: &SessionExport, page: usize) -> Result<String, String> {
+        let _timer = timing::scope(Phase::Render);
+        let day_and_time = |at: DateTime<Utc>| format!("{} {}",
+            at.with_timezone(&utils::timezone::zone()).format("%Y-%m-%d"), utils::format_timestamp(at));
+        let mut lines: Vec<(DateTime<Utc>, [String; 7])> = export.rows.iter()
+            .map(|row| (row.start_time, [
+                day_and_time(row.start_time),
+                match row.end_time {
+                    Some(end) if row.is_open_ended() => format!("{} (open)", utils::format_timestamp(end)),
+                    Some(end) => utils::format_timestamp(end),
+                    None => "ongoing".to_string(),
+                },
+                utils::format_duration(row.duration, DurationStyle::Compact),
+                match (row.is_focus_app, row.manual) {
+                    (true, false) => "focus",
+                    (false, false) => "other",
+                    (true, true) => "focus (manual)",
+                    (false, true) => "other (manual)",
+                }.to_string(),
+                match &row.domain {
+                    Some(domain) => format!("{} ({})", row.app_name, domain),
+                    None => row.app_name.clone(),
+                },
+                row.focus_rule.as_ref().map(RuleMatch::to_column).unwrap_or_default(),
+                utils::truncate_chars(&row.window_title, RAW_TITLE_WIDTH),
+            ]))
+            .collect();
+        lines.extend(export.switches.iter().map(|switch| (switch.timestamp, [
+            day_and_time(switch.timestamp),
+            String::new(),
+            switch.recovery_time
+                .map(|recovery| utils::format_duration(recovery, DurationStyle::Compact))
+                .unwrap_or_default(),
+            "switch".to_string(),
+            format!("{} → {}", switch.from_app, switch.to_app),
+            String::new(),
+            String::new(),
+        ])));
+        // Events are markers between the rows, also when one falls inside a row
+        lines.extend(export.events.iter().map(|event| (event.timestamp, [
+            day_and_time(event.timestamp),
+            String::new(),
+            String::new(),
+            format!("▸ {}", event.kind.as_str()),
+            event_label(event),
+            String::new(),
+            String::new(),
+        ])));
+        // Stable, so a row stays ahead of the switch or event recorded at its start
+        lines.sort_by_key(|(at, _)| *at);
+
+        let pages = lines.len().div_ceil(RAW_PAGE_SIZE).max(1);
+        if page == 0 || page > pages {
+            return Err(format!("Page {} is out of range (1-{})", page, pages));
+        }
+        let first = (page - 1) * RAW_PAGE_SIZE;
+        let shown = &lines[first..(first + RAW_PAGE_SIZE).min(lines.len())];
+
+        let header = ["Start", "End", "Time", "Kind", "App", "Rule", "Title"];
+        let mut widths = header.map(|column| column.chars().count());
+        for (_, cells) in shown {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let format_line = |cells: [&str; 7]| {
+            let padded: Vec<String> = cells.iter().zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+
+        let mut output = format!("~=~ Raw rows of \"{}\": {} rows, {} context switches, {} events\n\n",
+            export.session.session_name, export.rows.len(), export.switches.len(), export.events.len());
+        output.push_str(&format_line(header));
+        output.push('\n');
+        for (_, cells) in shown {
+            output.push_str(&format_line(cells.each_ref().map(String::as_str)));
+            output.push('\n');
+        }
+        if pages > 1 {
+            output.push_str(&format!("\n~=~ Lines {}-{} of {} (page {} of {})",
+                first + 1, first + shown.len(), lines.len(), page, pages));
+            if page < pages {
+                output.push_str(&format!(", --page {} for more", page + 1));
+            }
+            output.push('\n');
+        }
+        if let Some(meta) = &export.meta {
+            output.push_str(&format!("\n~=~ Recorded with {}\n", recorded_with(meta)));
+        }
+        Ok(output)
+    }
+
+    fn session_json<'a>(session: &'a AggregatedSession, switches: &'a [ContextSwitch]) -> SessionJson<'a> {
+        let usage = |entries: &'a [(String, Duration, bool)]| {
+            entries.iter()
+                .map(|(name, duration, is_focus)| UsageJson {
+                    name,
+                    duration_seconds: duration.as_secs(),
+                    is_focus: *is_focus,
+                })
+                .collect()
+        };
+
+        let recovery_times: Vec<u64> = switches.iter()
+            .filter_map(|s| s.recovery_time.map(|d| d.as_secs()))
+            .collect();
+        let recovery = RecoveryJson {
+            count: recovery_times.len(),
+            average_seconds: if recovery_times.is_empty() {
+                None
+            } else {
+                Some(recovery_times.iter().sum::<u64>() / recovery_times.len() as u64)
+            },
+            max_seconds: recovery_times.iter().max().copied(),
+        };
+
+        SessionJson {
+            session_name: &session.session_name,
+            start_time: session.start_time.to_rfc3339(),
+            end_time: session.end_time.map(|t| t.to_rfc3339()),
+            total_duration_seconds: session.total_duration.as_secs(),
+            focus_efficiency: session.focus_efficiency,
+            focus_score: session.focus_score,
+            engaged_focus_seconds: session.engaged_focus_time.map(|d| d.as_secs()),
+            untracked_seconds: session.untracked_time.as_secs(),
+            tracked_seconds: session.tracked_time().as_secs(),
+            suspends: session.suspends.iter()
+                .map(|gap| SuspendJson {
+                    start: gap.start.to_rfc3339(),
+                    end: gap.end.to_rfc3339(),
+                    seconds: gap.duration().as_secs(),
+                })
+                .collect(),
+            app_usage: usage(&session.app_usage),
+            domain_usage: usage(&session.domain_usage),
+            workspaces: session.workspace_usage.iter().map(WorkspaceJson::from).collect(),
+            context_switches: session.context_switches,
+            switches: switches.iter()
+                .map(|s| SwitchJson {
+                    timestamp: s.timestamp.to_rfc3339(),
+                    from_app: &s.from_app,
+                    to_app: &s.to_app,
+                    recovery_seconds: s.recovery_time.map(|d| d.as_secs()),
+                })
+                .collect(),
+            recovery,
+            events: None,
+            task: session.task.as_ref().map(TaskJson::from),
+        }
+    }
+
+    fn event_json(event: TrackerEvent) -> EventJson {
+        EventJson {
+            timestamp: event.timestamp.to_rfc3339(),
+            kind: event.kind.as_str(),
+            payload: event.payload,
+        }
+    }
+
+    /// Rows of the last 30 days, which `sessions list`, `show` and
+    /// `browse` look through
+    fn recent_rows(db: &Database, settings: &StatsSettings) -> Result<Vec<FocusSession>, Box<dyn std::error::Error>> {
+        let mut all_sessions = Vec::new();
+        let local_days = LocalDays::new(0);
+        let today = local_days.day_of(Utc::now());
+        for days_ago in 0..30 {
+            let day = today - chrono::Duration::days(days_ago);
+            all_sessions.extend(settings.sessions_for_date(db, &local_days, day)?);
+        }
+        Ok(all_sessions)
+    }
+
+    /// Suspends over the stretch `recent_rows` covers
+    fn recent_suspends(db: &Database) -> Result<Vec<SuspendGap>, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        Ok(suspend_gaps(db, now - chrono::Duration::days(30), now)?)
+    }
+
+    /// Named sessions of the last 30 days, newest first
+    pub fn recent_sessions(db: &Database, settings: &StatsSettings) -> Result<Vec<AggregatedSession>, Box<dyn std::error::Error>> {
+        let mut rows = Self::recent_rows(db, settings)?;
+        rows.retain(|session| !session.session_name.is_empty());
+        Ok(Self::aggregate_sessions_by_name(settings, &rows, &Self::recent_suspends(db)?))
+    }
+
+    /// The recent session named `query`, ignoring case; near misses are
+    /// suggested in the error
+    pub fn find_session(db: &Database, settings: &StatsSettings, query: &str) -> Result<AggregatedSession, Box<dyn std::error::Error>> {
+        let aggregated = Self::aggregate_sessions_by_name(settings, &Self::recent_rows(db, settings)?, &Self::recent_suspends(db)?);
+        
+        // Search by session name (case-insensitive); a near miss is only
+        // suggested, never shown in its place
+        let names: Vec<String> = aggregated.iter().map(|session| session.session_name.clone()).collect();
+        aggregated.into_iter()
+            .find(|session| session.session_name.eq_ignore_ascii_case(query))
+            .ok_or_else(|| match fuzzy::best(query, &names, |name| vec![name.clone()]) {
+                FuzzyMatch::Unique(ranked) => format!("❌ Session not found: {}. Did you mean '{}'?", query, ranked.item).into(),
+                FuzzyMatch::Ambiguous(candidates) => format!("❌ Session not found: {}. Did you mean {}?", query,
+                    fuzzy::describe_candidates(&candidates, |name| name.clone())).into(),
+                FuzzyMatch::None => format!("❌ Session not found: {}", query).into(),
+            })
+    }
+
+    /// `suspends` are taken out of the sessions they fall in
+    fn aggregate_sessions_by_name(settings: &StatsSettings, sessions: &[FocusSession], suspends: &[SuspendGap]) -> Vec<AggregatedSession> {
+        Self::aggregate_session_groups(settings, sessions, suspends, settings.use_parallel(sessions.len()))
+    }
+
+    fn aggregate_session_groups(settings: &StatsSettings, sessions: &[FocusSession], suspends: &[SuspendGap], parallel: bool) -> Vec<AggregatedSession> {
+        let merged = merge_short_gaps(sessions.to_vec(), settings.merge_gap);
+        let mut session_groups: BTreeMap<String, Vec<&FocusSession>> = BTreeMap::new();
+        
+        for session in &merged {
+            let session_key = if session.session_name.is_empty() {
+                unnamed_session_name(session.start_time)
+            } else {
+                session.session_name.clone()
+            };
+            session_groups.entry(session_key)
+                .or_default()
+                .push(session);
+        }
+        
+        // Groups are independent, so big inputs aggregate them in parallel;
+        // the sort below merges both paths into the same order
+        let mut aggregated: Vec<AggregatedSession> = if parallel {
+            session_groups.into_par_iter()
+                .map(|(name, group_sessions)| Self::aggregate_group(settings, name, group_sessions.as_slice(), suspends))
+                .collect()
+        } else {
+            session_groups.into_iter()
+                .map(|(name, group_sessions)| Self::aggregate_group(settings, name, group_sessions.as_slice(), suspends))
+                .collect()
+        };
+        
+        // Sort by start time (newest first)
+        aggregated.sort_by(|a, b| b.start_time.cmp(&a.start_time).then_with(|| a.session_name.cmp(&b.session_name)));
+        aggregated
+    }
+
+    fn aggregate_group(settings: &StatsSettings, name: String, group_sessions: &[&FocusSession], suspends: &[SuspendGap]) -> AggregatedSession {
+        let start_time = group_sessions.iter().map(|s| s.start_time).min().unwrap();
+        // A row without an end is the daemon's current window (see
+        // `StatsSettings::live_rows`), so the session is still going
+        let ongoing = group_sessions.iter().any(|s| s.end_time.is_none());
+        let end_time = group_sessions.iter()
+            .filter_map(|s| s.end_time)
+            .max()
+            .filter(|_| !ongoing);
+        
+        // Calculate total duration from actual start and end times, not sum of individual durations
+        let total_duration = if let Some(end_time) = end_time {
+            end_time.signed_duration_since(start_time).to_std().unwrap_or(Duration::ZERO)
+        } else {
+            // Ongoing: up to where the latest row has got to
+            group_sessions.iter()
+                .map(|s| s.start_time + chrono::Duration::from_std(s.duration).unwrap_or_default())
+                .max()
+                .and_then(|latest| latest.signed_duration_since(start_time).to_std().ok())
+                .unwrap_or_default()
+        };
+
+        // The span still runs through a suspend, but nothing in it counts
+        let span_end = start_time + chrono::Duration::from_std(total_duration).unwrap_or_default();
+        let suspends: Vec<SuspendGap> = suspends.iter()
+            .filter(|gap| gap.start < span_end && gap.end > start_time)
+            .copied()
+            .collect();
+        let suspended = suspend_periods(&suspends);
+        let suspended_time = overlap(start_time, span_end, &suspended);
+        let trimmed: Vec<FocusSession>;
+        let trimmed_refs: Vec<&FocusSession>;
+        let group_sessions = if suspended.is_empty() {
+            group_sessions
+        } else {
+            trimmed = group_sessions.iter().map(|session| without_suspends(session, &suspended)).collect();
+            trimmed_refs = trimmed.iter().collect();
+            trimmed_refs.as_slice()
+        };
+        
+        let focus_time: Duration = group_sessions.iter()
+            .filter(|s| s.is_focus_app && !s.is_corrupt())
+            .map(|s| s.duration)
+            .sum();
+        // Corrupt open rows still span part of the session, but not of what
+        // efficiency is measured against
+        let corrupt_time: Duration = group_sessions.iter()
+            .filter(|s| s.is_corrupt())
+            .map(|s| s.duration)
+            .sum();
+        let measured = total_duration.saturating_sub(corrupt_time).saturating_sub(suspended_time);
+        
+        let focus_efficiency = if measured > Duration::ZERO {
+            (focus_time.as_secs_f64() / measured.as_secs_f64()) * 100.0
+        } else {
+            0.0
+        };
+        
+        // Collect unique apps with their total usage, in name order so ties
+        // sort the same way every run
+        let mut app_usage: BTreeMap<String, Duration> = BTreeMap::new();
+        let mut domain_usage: BTreeMap<String, Duration> = BTreeMap::new();
+        
+
+        
+        for session in group_sessions {
+            *app_usage.entry(session.app_name.clone()).or_insert(Duration::ZERO) += session.duration;
+            
+
+            
+            // Also collect domain usage if available
+            if let Some(domain) = session.domain.as_ref().filter(|_| settings.tab_reports) {
+                *domain_usage.entry(domain.clone()).or_insert(Duration::ZERO) += session.duration;
+            }
+        }
+        
+
+        
+        let app_list: Vec<(String, Duration, bool)> = app_usage.into_iter()
+            .map(|(app, duration)| {
+                let is_focus = group_sessions.iter()
+                    .any(|s| s.app_name == app && s.is_focus_app);
+                (desktop::display_name(&app), duration, is_focus)
+            })
+            .collect();
+        let app_list = rollup_usage(app_list, settings);
+        
+        let domain_list: Vec<(String, Duration, bool)> = domain_usage.into_iter()
+            .map(|(tab_name, duration)| {
+                // Check if any session with this tab name was marked as focus
+                let is_focus = group_sessions.iter()
+                    .any(|s| s.domain.as_ref() == Some(&tab_name) && s.is_focus_app);
+                (tab_name, duration, is_focus)
+            })
+            .collect();
+        let domain_list = rollup_usage(domain_list, settings);
+
+        let untracked_time = match end_time {
+            Some(end_time) => Self::untracked_gaps(group_sessions.iter().copied(), start_time, end_time, &[], UNTRACKED_GAP_THRESHOLD)
+                .iter()
+                .map(|gap| gap.duration().saturating_sub(overlap(gap.start, gap.end, &suspended)))
+                .sum(),
+            None => Duration::ZERO,
+        };
+        // Rough estimate; rows entered by hand are no switch
+        let context_switches = group_sessions.iter().filter(|s| !s.manual).count().saturating_sub(1);
+        let focus_score = focus_score(&ScoreInputs {
+            efficiency: focus_efficiency,
+            context_switches,
+            tracked: total_duration.saturating_sub(suspended_time),
+            longest_block: longest_focus_block(group_sessions.iter().copied(), settings.min_display),
+        }, &settings.score);
+        
+        AggregatedSession {
+            session_name: name,
+            start_time,
+            end_time,
+            total_duration,
+            focus_efficiency,
+            focus_score,
+            engaged_focus_time: Self::engaged_focus_time(group_sessions.iter().copied()),
+            untracked_time,
+            app_usage: app_list,
+            domain_usage: domain_list,
+            workspace_usage: workspace_usage(group_sessions.iter().copied()),
+            context_switches,
+            switching_cost: None,
+            open_rows: group_sessions.iter().filter(|s| s.is_open_ended()).count(),
+            suspends,
+            suspended_time,
+            notifications: NotificationCounts::default(),
+            intent: None,
+            task: None,
+        }
+    }
+
+    /// "2h 36m of the 3h 10m span"
+    fn describe_tracked(session: &AggregatedSession) -> String {
+        format!("{} of the {} span",
+            utils::format_duration(session.tracked_time(), DurationStyle::Long),
+            utils::format_duration(session.total_duration, DurationStyle::Long))
+    }
+
+    fn format_session_summary(idx: usize, s: &AggregatedSession) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let start = utils::format_datetime_local(s.start_time);
+        let end = s.describe_end(utils::format_timestamp_local);
+        let duration = utils::format_duration(s.total_duration, DurationStyle::Long);
+        let focus_percent = format!("{:.0}%", s.focus_efficiency);
+        
+        // Format with proper spacing to match example
+        let time_range = format!("{}-{}", start, end);
+        let gap = if ReportLayout::current().is_compact() { "  " } else { "        " };
+        let rated = s.intent.as_ref()
+            .and_then(SessionIntent::describe_outcome)
+            .map(|outcome| format!("  Rated: {}", outcome))
+            .unwrap_or_default();
+        let task = s.task.as_ref()
+            .map(|task| format!("  Task: {}", task.describe()))
+            .unwrap_or_default();
+        format!("{}. \"{}\"{}{}  {}  Focus: {}{}{}", 
+            idx, utils::escape_control_chars(&s.session_name), gap, time_range, duration, focus_percent, rated, task)
+    }
+
+    fn format_session_report(s: &AggregatedSession) -> String {
+        let start = utils::format_datetime_local(s.start_time);
+        let end = s.describe_end(utils::format_timestamp_local);
+        let duration = utils::format_duration(s.total_duration, DurationStyle::Long);
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        let layout = ReportLayout::current();
+        let pad = layout.cells(48);
+        let efficiency_bar = with_bar(
+            layout.bar(s.focus_efficiency / 100.0, 30, BarStyle::Shade),
+            format!("{:.0}%", s.focus_efficiency),
+        );
+        let mut report = String::new();
+        report.push('\n');
+        report.push_str(&format!("{}\n\n", top_sep));
+        report.push_str("~=~ SESSION DETAILS ~=~\n\n");
+        report.push_str(&format!("Name:       {:<pad$}\n\n", layout.fit(&s.session_name, 48)));
+        let time_line = format!("Duration:  {} → {} ({})", start, end, duration);
+        report.push_str(&format!("{}\n\n", time_line));
+        if !s.suspends.is_empty() {
+            report.push_str(&format!("Tracked:    {:<pad$}\n\n", Self::describe_tracked(s)));
+            for gap in &s.suspends {
+                report.push_str(&format!("Note:       {:<pad$}\n\n", gap.describe()));
+            }
+        }
+        report.push_str(&format!("Efficiency: {:<pad$}\n\n", efficiency_bar));
+        if let Some(score) = s.focus_score {
+            report.push_str(&format!("Score:      {:<pad$}\n\n", score));
+        }
+        if let Some(engaged) = s.engaged_focus_time {
+            report.push_str(&format!("Engaged:    {:<pad$}\n\n", utils::format_duration(engaged, DurationStyle::Long)));
+        }
+        if s.untracked_time > Duration::ZERO {
+            report.push_str(&format!("Untracked:  {:<pad$}\n\n", utils::format_duration(s.untracked_time, DurationStyle::Long)));
+        }
+        report.push_str(&format!("Switches:   {:<pad$}\n\n", s.context_switches));
+        if let Some(intent) = s.intent.as_ref().and_then(SessionIntent::describe_intent) {
+            report.push_str(&format!("Intent:     {}\n\n", intent));
+        }
+        if let Some(outcome) = s.intent.as_ref().and_then(SessionIntent::describe_outcome) {
+            report.push_str(&format!("Outcome:    {}\n\n", outcome));
+        }
+        if let Some(task) = &s.task {
+            report.push_str(&format!("Task:       {}\n\n", task.describe()));
+        }
+        // Separate browser apps from regular apps
+        let (browser_apps, regular_apps): (Vec<_>, Vec<_>) = s.app_usage.iter()
+            .partition(|(app, _, _)| Self::is_browser_app(app));
+        
+        // Show regular applications (non-browser)
+        if !regular_apps.is_empty() {
+            report.push_str("~=~ APPLICATION BREAKDOWN ~=~\n\n");
+            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+            for (app, duration, is_focus) in regular_apps.iter().take(6) {
+                let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                let focus_text = if *is_focus { "Focus" } else { "Other" };
+                let fraction = duration.as_secs() as f64 / max_duration as f64;
+                let app_line = format!("{} {:<10} ({:<5})", bar_row(&layout, app, 20, fraction, 20, BarStyle::Blocks), duration_str, focus_text);
+                report.push_str(&format!("{}\n\n", app_line));
+            }
+        }
+        
+        // Show browser apps grouped together (no individual tabs)
+        if !browser_apps.is_empty() {
+            report.push_str("~=~ BROWSER APPLICATIONS ~=~\n");
+            let max_duration = browser_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+            for (app, duration, is_focus) in browser_apps.iter().take(6) {
+                let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                let focus_text = if *is_focus { "Focus" } else { "Other" };
+                let fraction = duration.as_secs() as f64 / max_duration as f64;
+                let app_line = format!("{} {:<8} ({:<5})", bar_row(&layout, app, 18, fraction, 15, BarStyle::Blocks), duration_str, focus_text);
+                report.push_str(&format!("{}\n\n", app_line));
+            }
+        }
+        
+        // Show browser tabs individually (for session details)
+        if !s.domain_usage.is_empty() {
+            // Group browser tabs by browser name
+            let mut browser_tab_map: std::collections::BTreeMap<String, Vec<(String, std::time::Duration, bool)>> = std::collections::BTreeMap::new();
+            for (tab_name, duration, is_focus) in &s.domain_usage {
+                // Group by browser based on tab name suffix
+                let browser = if tab_name.to_lowercase().contains("chrome") {
+                    "CHROME TABS"
+                } else if tab_name.to_lowercase().contains("brave") {
+                    "BRAVE TABS"
+                } else if tab_name.to_lowercase().contains("firefox") {
+                    "FIREFOX TABS"
+                } else if tab_name.to_lowercase().contains("safari") {
+                    "SAFARI TABS"
+                } else if tab_name.to_lowercase().contains("edge") {
+                    "EDGE TABS"
+                } else if tab_name.to_lowercase().contains("opera") {
+                    "OPERA TABS"
+                } else if tab_name.to_lowercase().contains("vivaldi") {
+                    "VIVALDI TABS"
+                } else {
+                    "BROWSER TABS"
+                };
+                browser_tab_map.entry(browser.to_string()).or_default().push((tab_name.clone(), *duration, *is_focus));
+            }
+            for (browser, tabs) in browser_tab_map {
+                report.push_str(&format!("~=~ {} (TOP 5) ~=~\n\n", browser));
+                let max_duration = tabs.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+                for (tab_name, duration, is_focus) in tabs.iter().take(5) {
+                    let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                    let focus_text = if *is_focus { "Focus" } else { "Other" };
+                    let fraction = duration.as_secs() as f64 / max_duration as f64;
+                    let tab_line = format!("{} {:<8} ({:<5})", bar_row(&layout, tab_name, 30, fraction, 15, BarStyle::Blocks), duration_str, focus_text);
+                    report.push_str(&format!("{}\n\n", tab_line));
+                }
+            }
+        }
+
+        if !s.workspace_usage.is_empty() {
+            report.push_str("~=~ WORKSPACE BREAKDOWN ~=~\n\n");
+            report.push_str(&Self::workspace_lines(&layout, &s.workspace_usage));
+            report.push('\n');
+        }
+        report.push_str(&format!("{}\n\n", top_sep));
+        report
+    }
+
+    /// One bar line per workspace, scaled to the most used one
+    fn workspace_lines(layout: &ReportLayout, usage: &[WorkspaceUsage]) -> String {
+        let max_duration = usage.first().map(|workspace| workspace.total.as_secs()).unwrap_or(1).max(1);
+        usage.iter()
+            .map(|workspace| {
+                let fraction = workspace.total.as_secs() as f64 / max_duration as f64;
+                format!("{} {:<10} focus {:>3.0}%\n",
+                    bar_row(layout, workspace.label(), 20, fraction, 20, BarStyle::Blocks),
+                    utils::format_duration(workspace.total, DurationStyle::Long),
+                    workspace.efficiency())
+            })
+            .collect()
+    }
+
+    pub fn calculate_workspace_usage(db: &Database, settings: &StatsSettings, days: u32, now: DateTime<Utc>) -> Result<WorkspaceReport, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let since = now - chrono::Duration::days(days as i64);
+        let sessions: Vec<FocusSession> = db.get_sessions_between(since, now)?
+            .into_iter()
+            .filter_map(|session| settings.apply_current_rules(session))
+            .collect();
+        Ok(WorkspaceReport {
+            days,
+            since,
+            usage: workspace_usage(&sessions),
+            version_changes: Self::version_change_notes(db, since, now)?,
+        })
+    }
+
+    pub fn render_workspace_usage(report: &WorkspaceReport) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let layout = ReportLayout::current();
+        let mut text = format!("~=~ WORKSPACES (last {} days) ~=~\n\n", report.days);
+        if report.usage.is_empty() {
+            text.push_str("No workspaces recorded; the window detection backend here doesn't report them\n");
+            return text;
+        }
+        text.push_str(&Self::workspace_lines(&layout, &report.usage));
+        for note in &report.version_changes {
+            text.push_str(&format!("\nNote: {}\n", note));
+        }
+        text
+    }
+
+    pub fn workspace_usage_json(report: &WorkspaceReport) -> Result<String, Box<dyn std::error::Error>> {
+        let json = WorkspaceReportJson {
+            days: report.days,
+            since: report.since.to_rfc3339(),
+            workspaces: report.usage.iter().map(WorkspaceJson::from).collect(),
+            notes: &report.version_changes,
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// `rules stats` over the last `days` days, today included
+    pub fn calculate_rule_stats(db: &Database, config: &Config, focus_apps: &[String], days: u32, now: DateTime<Utc>) -> Result<RuleStats, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        let first_day = now.date_naive() - chrono::Duration::days(days as i64 - 1);
+        let sessions = db.get_sessions_between(start_of_day(first_day), now)?;
+        Ok(rule_stats(&sessions, focus_apps, &config.focus_sites, days))
+    }
+
+    pub fn render_rule_stats(report: &RuleStats) -> String {
+        let _timer = timing::scope(Phase::Render);
+        let mut text = format!("~=~ TIME BY RULE (last {} days) ~=~\n\n", report.days);
+        if report.rules.is_empty() {
+            text.push_str("No rows in this period\n");
+        }
+        let name = |usage: &RuleUsage| usage.rule.as_ref().map(RuleMatch::to_column).unwrap_or_else(|| "(not recorded)".to_string());
+        let width = report.rules.iter().map(|usage| name(usage).chars().count()).max().unwrap_or(0).min(48);
+        for usage in &report.rules {
+            text.push_str(&format!("  {:<width$}  {:>9}  {:>5} rows\n",
+                utils::truncate_chars(&name(usage), 48),
+                utils::format_duration(Duration::from_secs(usage.total_seconds), DurationStyle::Long),
+                usage.rows));
+        }
+        if let Some(unrecorded) = report.rules.iter().find(|usage| usage.rule.is_none()) {
+            text.push_str(&format!("\n{} rows have no rule recorded; 'focusdebt database reclassify' records the current one\n", unrecorded.rows));
+        }
+        if !report.unused.is_empty() {
+            text.push_str(&format!("\nNo time in the last {} days:\n", report.days));
+            for rule in &report.unused {
+                text.push_str(&format!("  {}\n", rule.to_column()));
+            }
+        }
+        text
+    }
+
+    /// `config preview` over the last `days` days, today included
+    pub fn calculate_classification_preview(
+        db: &Database,
+        current: &Config,
+        proposed: &Config,
+        days: u32,
+        now: DateTime<Utc>,
+    ) -> Result<ClassificationPreview, Box<dyn std::error::Error>> {
+        let first_day = now.date_naive() - chrono::Duration::days(days as i64 - 1);
+        let sessions = db.get_sessions_between(start_of_day(first_day), now)?;
+        Ok(preview_classification(&sessions, current, proposed, first_day, days as usize))
+    }
+
+    pub fn render_classification_preview(preview: &ClassificationPreview) -> String {
+        let percent = |efficiency: Option<f64>| efficiency.map(|value| format!("{:.0}%", value)).unwrap_or_else(|| "-".to_string());
+        let mut text = format!("~=~ CLASSIFICATION PREVIEW (last {} days) ~=~\n\n", preview.days.len());
+        text.push_str(&format!("{:<12} {:>7} {:>7} {:>9}\n", "Day", "Before", "After", "Change"));
+        for day in &preview.days {
+            let (before, after) = (day.before.efficiency(), day.after.efficiency());
+            if before.is_none() && after.is_none() {
+                continue;
+            }
+            let change = match (before, after) {
+                (Some(before), Some(after)) if (after - before).abs() >= 0.5 => format!("{:+.0} pts", after - before),
+                (Some(_), Some(_)) => "=".to_string(),
+                _ => "-".to_string(),
+            };
+            text.push_str(&format!("{:<12} {:>7} {:>7} {:>9}\n", utils::format_date_local(day.date), percent(before), percent(after), change));
+        }
+
+        let (before, after) = (preview.before(), preview.after());
+        let focus_change = if after.focus >= before.focus {
+            format!("+{}", utils::format_duration(after.focus - before.focus, DurationStyle::Long))
+        } else {
+            format!("-{}", utils::format_duration(before.focus - after.focus, DurationStyle::Long))
+        };
+        text.push_str(&format!("\n{:<20} {} → {} ({})\n", "Focus time:",
+            utils::format_duration(before.focus, DurationStyle::Long),
+            utils::format_duration(after.focus, DurationStyle::Long),
+            focus_change));
+        text.push_str(&format!("{:<20} {} → {}\n", "Efficiency:", percent(before.efficiency()), percent(after.efficiency())));
+        text.push_str(&format!("{:<20} {} → {}\n", "Deep focus sessions:", before.deep_focus_sessions, after.deep_focus_sessions));
+
+        if preview.flips.is_empty() {
+            text.push_str("\nNo rows change category\n");
+            return text;
+        }
+        text.push_str(&format!("\nRows that change category ({}):\n", preview.flips.iter().map(|flip| flip.rows).sum::<usize>()));
+        for flip in preview.flips.iter().take(PREVIEW_FLIPS_SHOWN) {
+            let name = match &flip.site {
+                Some(site) => format!("{} ({})", flip.app, site),
+                None => flip.app.clone(),
+            };
+            text.push_str(&format!("  {:<32} {:>11} → {:<11} {:>4} rows  {}\n",
+                utils::truncate_chars(&name, 32),
+                flip.before.label(),
+                flip.after.label(),
+                flip.rows,
+                utils::format_duration(flip.duration, DurationStyle::Long)));
+            text.push_str(&format!("    by {} → {}\n", flip.rule_before.describe(), flip.rule_after.describe()));
+        }
+        if preview.flips.len() > PREVIEW_FLIPS_SHOWN {
+            text.push_str(&format!("  ... and {} more apps and sites\n", preview.flips.len() - PREVIEW_FLIPS_SHOWN));
+        }
+        text
+    }
+
+    pub fn calculate_session_stats(db: &Database, settings: &StatsSettings, session_name: &str) -> Result<AggregatedSession, Box<dyn std::error::Error>> {
+        let _timer = timing::scope(Phase::Aggregate);
+        // Get all sessions from last 30 days
+        let mut all_sessions = Vec::new();
+        let local_days = LocalDays::new(0);
+        let today = local_days.day_of(Utc::now());
+        for days_ago in 0..30 {
+            let day = today - chrono::Duration::days(days_ago);
+            let day_sessions = settings.sessions_for_date(db, &local_days, day)?;
+            all_sessions.extend(day_sessions);
+        }
+        
+        // Filter sessions by the specific session name
+        let session_sessions: Vec<FocusSession> = all_sessions
+            .into_iter()
+            .filter(|s| s.session_name.eq_ignore_ascii_case(session_name))
+            .collect();
+        
+
+        
+        if session_sessions.is_empty() {
+            return Err(format!("❌ No sessions found with name: {}", session_name).into());
+        }
+        
+        // Aggregate the sessions
+        let aggregated = Self::aggregate_sessions_by_name(settings, &session_sessions, &Self::recent_suspends(db)?);
+        
+        // Return the first (and should be only) aggregated session
+        if let Some(session) = aggregated.first() {
+            let mut session = session.clone();
+            let end = session.end_time.unwrap_or_else(Utc::now);
+            let switches = db.get_context_switches_between(session.start_time, end)?;
+            session.switching_cost = Some(settings.switching_cost(&switches, &session_sessions));
+            session.notifications = NotificationCounts::new(db.get_session_notifications(&session.session_name)?);
+            session.intent = db.get_session_intent(&session.session_name)?;
+            session.task = db.get_session_task(&session.session_name)?;
+            Ok(session)
+        } else {
+            Err(format!("❌ Failed to aggregate session: {}", session_name).into())
+        }
+    }
+
+    /// Notes for the releases in SEMANTIC_CHANGES the daemon was upgraded
+    /// to between `start` and `end`
+    pub fn version_change_notes(db: &Database, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(version_change_notes(&db.get_daemon_versions()?, start, end))
+    }
+
+    /// The running session so far: its rows with the daemon's unsaved ones,
+    /// its pace over the last PACE_WINDOW and the end of the plan it runs
+    pub fn calculate_session_progress(db: &Database, settings: &StatsSettings, session_name: &str, now: DateTime<Utc>) -> Result<SessionProgress, Box<dyn std::error::Error>> {
+        let session = Self::calculate_session_stats(db, settings, session_name)?;
+
+        let window_start = now - chrono::Duration::from_std(PACE_WINDOW).unwrap_or_default();
+        let days = LocalDays::new(0);
+        let mut rows = settings.sessions_for_date(db, &days, days.day_of(now))?;
+        if days.day_of(window_start) != days.day_of(now) {
+            rows.extend(settings.sessions_for_date(db, &days, days.day_of(window_start))?);
+        }
+        let (mut focus, mut tracked) = (Duration::ZERO, Duration::ZERO);
+        for row in rows.iter().filter(|row| row.session_name.eq_ignore_ascii_case(session_name)) {
+            let end = row.end_time.unwrap_or_else(|| row.start_time + chrono::Duration::from_std(row.duration).unwrap_or_default());
+            let in_window = (end.min(now) - row.start_time.max(window_start)).to_std().unwrap_or_default();
+            tracked += in_window;
+            if row.is_focus_app {
+                focus += in_window;
+            }
+        }
+
+        // A database no newer build has opened for writing has no plans table yet
+        let planned_end = db.get_planned_sessions(now - chrono::Duration::days(1), None)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|plan| plan.status(now) == PlanStatus::Running && plan.name.eq_ignore_ascii_case(session_name))
+            .map(|plan| plan.end_time());
+
+        Ok(SessionProgress {
+            session,
+            pace: (!tracked.is_zero()).then(|| focus.as_secs_f64() / tracked.as_secs_f64() * 100.0),
+            planned_end,
+            now,
+        })
+    }
+
+    pub fn display_session_summary(session: &AggregatedSession, settings: &StatsSettings) {
+        let _timer = timing::scope(Phase::Render);
+        Self::print_session_summary(session, settings, None);
+    }
+
+    /// The session summary marked as in progress, with a projection of
+    /// where its efficiency ends up
+    pub fn display_session_progress(progress: &SessionProgress, settings: &StatsSettings) {
+        Self::print_session_summary(&progress.session, settings, Some(progress));
+    }
+
+    fn print_session_summary(session: &AggregatedSession, settings: &StatsSettings, progress: Option<&SessionProgress>) {
+        let start = utils::format_datetime_local(session.start_time);
+        let end = match (progress, session.end_time) {
+            (Some(_), _) => "now".to_string(),
+            (None, Some(end)) => utils::format_timestamp_local(end),
+            (None, None) => "ongoing".to_string(),
+        };
+        let duration = utils::format_duration(session.total_duration, DurationStyle::Long);
+        let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+        let layout = ReportLayout::current();
+        let pad = layout.cells(48);
+        let efficiency_display = match layout.bar(session.focus_efficiency / 100.0, 25, BarStyle::Shade) {
+            Some(bar) => format!("{:.0}% {}", session.focus_efficiency, bar),
+            None => format!("{:.0}%", session.focus_efficiency),
+        };
+        
+        println!("\n{}\n", top_sep);
+        if progress.is_some() {
+            println!("~=~ SESSION IN PROGRESS ~=~\n");
+        } else {
+            println!("~=~ SESSION COMPLETE ~=~\n");
+        }
+        if settings.ascii_art {
+            println!("{}\n", HOURGLASS_ART);
+        }
+        println!("Session: {:<pad$}\n", layout.fit(&session.session_name, 48));
+        let time_line = format!("Duration: {} → {} ({})", start, end, duration);
+        println!("{}\n", time_line);
+        if !session.suspends.is_empty() {
+            println!("Tracked: {:<pad$}\n", Self::describe_tracked(session));
+            for gap in &session.suspends {
+                println!("Note:    {:<pad$}\n", gap.describe());
+            }
+        }
+        println!("Focus:   {:<pad$}\n", efficiency_display);
+        if let Some(projection) = progress.and_then(|progress| progress.describe_projection()) {
+            println!("Pace:    {:<pad$}\n", projection);
+        }
+        if let Some(score) = session.focus_score {
+            println!("Score:   {:<pad$}\n", score);
+        }
+        if let Some(engaged) = session.engaged_focus_time {
+            println!("Engaged: {:<pad$}\n", utils::format_duration(engaged, DurationStyle::Long));
+        }
+        if session.untracked_time > Duration::ZERO {
+            println!("Untracked: {:<width$}\n", utils::format_duration(session.untracked_time, DurationStyle::Long), width = layout.cells(46));
+        }
+        println!("Switches: {:<width$}\n", session.context_switches, width = layout.cells(47));
+        if let Some(cost) = session.switching_cost.filter(|cost| !cost.is_empty()) {
+            println!("Switch cost: {:<width$}\n", cost.summary(), width = layout.cells(44));
+        }
+        if !session.notifications.is_empty() {
+            println!("Notifications received: {}\n", session.notifications.describe());
+        }
+        if let Some(intent) = session.intent.as_ref().and_then(SessionIntent::describe_intent) {
+            println!("Intent:  {}\n", intent);
+        }
+        if let Some(outcome) = session.intent.as_ref().and_then(SessionIntent::describe_outcome) {
+            println!("Outcome: {}\n", outcome);
+        }
+        if let Some(task) = &session.task {
+            println!("Task:    {}\n", task.describe());
+        }
+        
+        // Browser apps are shown per tab below, so only list regular apps here
+        let regular_apps: Vec<_> = session.app_usage.iter()
+            .filter(|(app, _, _)| !Self::is_browser_app(app))
+            .collect();
+        
+        // Show regular applications (non-browser)
+        if !regular_apps.is_empty() {
+            println!("~=~ APPLICATIONS USED ~=~\n");
+            let max_duration = regular_apps.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+            for (app, duration, is_focus) in regular_apps.iter().take(6) {
+                let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                let focus_text = if *is_focus { "Focus" } else { "Other" };
+                let fraction = duration.as_secs() as f64 / max_duration as f64;
+                let app_line = format!("{} {:<8} ({:<5})", bar_row(&layout, app, 18, fraction, 15, BarStyle::Blocks), duration_str, focus_text);
+                println!("{}\n", app_line);
+            }
+        }
+        
+
+        
+        // Show browser tabs individually (for session summary)
+        if !session.domain_usage.is_empty() {
             // Group browser tabs by browser name
             let mut browser_tab_map: BTreeMap<String, Vec<(String, std::time::Duration, bool)>> = BTreeMap::new();
             for (tab_name, duration, is_focus) in &session.domain_usage {
@@ -653,28 +4703,2202 @@ r#"
                 };
                 browser_tab_map.entry(browser.to_string()).or_default().push((tab_name.clone(), *duration, *is_focus));
             }
-            for (browser, tabs) in browser_tab_map {
-                println!("~=~ {} (TOP 5) ~=~\n", browser);
-                let max_duration = tabs.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
-                for (tab_name, duration, is_focus) in tabs.iter().take(5) {
-                    let tab_display = if tab_name.len() > 30 { format!("{}...", &tab_name[..27]) } else { tab_name.clone() };
-                    let duration_str = Self::format_duration(*duration);
-                    let focus_text = if *is_focus { "Focus" } else { "Other" };
-                    let bar_len = 15;
-                    let filled = ((duration.as_secs() as f64 / max_duration as f64) * bar_len as f64) as usize;
-                    let usage_bar = format!("[{}{}]", "■".repeat(filled), "□".repeat(bar_len - filled));
-                    let tab_line = format!("{:<30} {} {:<8} ({:<5})", tab_display, usage_bar, duration_str, focus_text);
-                    println!("{}\n", tab_line);
-                }
+            for (browser, tabs) in browser_tab_map {
+                println!("~=~ {} (TOP 5) ~=~\n", browser);
+                let max_duration = tabs.first().map(|(_, d, _)| d.as_secs()).unwrap_or(1);
+                for (tab_name, duration, is_focus) in tabs.iter().take(5) {
+                    let duration_str = utils::format_duration(*duration, DurationStyle::Long);
+                    let focus_text = if *is_focus { "Focus" } else { "Other" };
+                    let fraction = duration.as_secs() as f64 / max_duration as f64;
+                    let tab_line = format!("{} {:<8} ({:<5})", bar_row(&layout, tab_name, 30, fraction, 15, BarStyle::Blocks), duration_str, focus_text);
+                    println!("{}\n", tab_line);
+                }
+            }
+        }
+        println!("{}\n", top_sep);
+        println!("~=~ Use 'focusdebt stats' to see your recent progress\n");
+    }
+
+    // Helper function to detect browser applications
+    fn is_browser_app(app_name: &str) -> bool {
+        let browser_apps = ["chrome", "firefox", "safari", "edge", "brave", "chromium", "opera", "vivaldi"];
+        browser_apps.iter().any(|&browser| app_name.to_lowercase().contains(browser))
+    }
+} 
+
+struct ExportTable {
+    heading: &'static str,
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+/// Time in the session's focus apps
+fn session_focus_time(session: &AggregatedSession) -> Duration {
+    session.app_usage.iter()
+        .filter(|(_, _, is_focus)| *is_focus)
+        .map(|(_, duration, _)| *duration)
+        .sum()
+}
+
+/// "name [■■□□]": the name fitted and padded to `name_cells`, then a bar
+/// `bar_cells` wide (both at 80 columns). The compact layout drops the bar
+/// and gives its room to the name.
+pub fn bar_row(layout: &ReportLayout, name: &str, name_cells: usize, fraction: f64, bar_cells: usize, style: BarStyle) -> String {
+    match layout.bar(fraction, bar_cells, style) {
+        Some(bar) => {
+            let name_width = layout.cells(name_cells);
+            format!("{:<name_width$} {}", layout.fit(name, name_cells), bar)
+        }
+        None => {
+            let name_width = layout.cells(name_cells + bar_cells);
+            format!("{:<name_width$}", layout.fit(name, name_cells + bar_cells))
+        }
+    }
+}
+
+/// "[■■□□] 85%", or "85%" when the compact layout dropped the bar
+pub fn with_bar(bar: Option<String>, value: String) -> String {
+    match bar {
+        Some(bar) => format!("{} {}", bar, value),
+        None => value,
+    }
+}
+
+/// What a marker for `event` says, e.g. "paused (quiet hours 22:00-07:00)"
+/// or "budget alert: slack at 100%"
+fn event_label(event: &TrackerEvent) -> String {
+    let field = |key: &str| event.payload[key].as_str().unwrap_or("?").to_string();
+    match event.kind {
+        EventKind::Pause => match event.payload["range"].as_str() {
+            Some(range) => format!("paused ({} {})", field("reason").replace('_', " "), range),
+            None => format!("paused ({})", field("reason").replace('_', " ")),
+        },
+        EventKind::Resume => "resumed".to_string(),
+        EventKind::Alert => match event.payload["alert"].as_str() {
+            Some("budget") => format!("budget alert: {} at {}%", field("target"), event.payload["threshold"]),
+            Some("efficiency_floor") => format!("efficiency alert: {:.0}% average below the {:.0}% floor",
+                event.payload["average"].as_f64().unwrap_or(0.0), event.payload["floor"].as_f64().unwrap_or(0.0)),
+            Some(other) => format!("{} alert", other.replace('_', " ")),
+            None => "alert".to_string(),
+        },
+        EventKind::DaemonStart => "daemon started".to_string(),
+        EventKind::DaemonStop => "daemon stopped".to_string(),
+        EventKind::SessionRollover => format!("rolled over from {} to {}", field("from"), field("to")),
+        EventKind::TimezoneChange => format!("timezone {} → {}", field("from"), field("to")),
+        EventKind::WindowChange => format!("window {}", field("app")),
+        EventKind::Audit => format!("tracking audit: {} missed switches, {} failed samples",
+            event.payload["missed_switches"], event.payload["failed_samples"]),
+        EventKind::Suspend => SuspendGap::from_event(event)
+            .map(|gap| gap.describe())
+            .unwrap_or_else(|| "suspended".to_string()),
+    }
+}
+
+/// One line on the settings a session was tracked with, e.g. "focusdebt
+/// 0.1.0, xdotool, 5 focus apps, 2 focus sites, 1 ignored app, min record
+/// 5s, merge gap 5s, deep focus 25m (snapshot 3f2a9c1e)"
+fn recorded_with(meta: &SessionMeta) -> String {
+    let snapshot = &meta.snapshot;
+    let count = |items: &[String], what: &str| format!("{} {}{}", items.len(), what, if items.len() == 1 { "" } else { "s" });
+    let mut parts = vec![format!("focusdebt {}", snapshot.version)];
+    if let Some(backend) = &snapshot.backend {
+        parts.push(backend.clone());
+    }
+    parts.push(count(&snapshot.focus_apps, "focus app"));
+    parts.push(count(&snapshot.focus_sites, "focus site"));
+    if !snapshot.ignored_apps.is_empty() {
+        parts.push(count(&snapshot.ignored_apps, "ignored app"));
+    }
+    if !snapshot.ignored_sites.is_empty() {
+        parts.push(count(&snapshot.ignored_sites, "ignored site"));
+    }
+    if let Some(tabs) = snapshot.browser_tab_tracking {
+        parts.push(format!("tabs {}", tabs.label()));
+    }
+    if snapshot.tmux_integration {
+        parts.push("tmux panes".to_string());
+    }
+    parts.push(format!("min record {}s", snapshot.min_record_seconds));
+    parts.push(format!("merge gap {}s", snapshot.merge_gap_seconds));
+    parts.push(format!("deep focus {}m", snapshot.deep_focus_threshold_minutes));
+    format!("{} (snapshot {})", parts.join(", "), &meta.hash[..meta.hash.len().min(8)])
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Badge color for an efficiency percentage, green down to red
+pub fn efficiency_color(efficiency: f64) -> &'static str {
+    match efficiency {
+        e if e >= 75.0 => "#4c1",
+        e if e >= 50.0 => "#dfb317",
+        e if e >= 25.0 => "#fe7d37",
+        _ => "#e05d44",
+    }
+}
+
+pub fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Rows recorded without a session name are grouped per local day under
+/// this name, e.g. "unnamed (May 21)"
+pub fn unnamed_session_name(start_time: DateTime<Utc>) -> String {
+    format!("unnamed ({})", utils::format_date_local(start_time.with_timezone(&utils::timezone::zone()).date_naive()))
+}
+
+/// A release that changed how time is recorded or counted, so numbers
+/// from before it don't compare like for like with later ones
+pub struct SemanticChange {
+    pub version: &'static str,
+    /// What changed, e.g. "how idle time is counted"
+    pub change: &'static str,
+}
+
+/// Releases that changed what the numbers mean, oldest first. Add one with
+/// the release that makes such a change; stats warn when their range
+/// spans the upgrade.
+pub const SEMANTIC_CHANGES: &[SemanticChange] = &[];
+
+/// "1.10.2" as [1, 10, 2]; parts that aren't numbers count as 0
+fn version_parts(version: &str) -> Vec<u64> {
+    version.split('.').map(|part| part.trim().parse().unwrap_or(0)).collect()
+}
+
+/// One note per semantic change whose upgrade falls inside [start, end):
+/// the first daemon start at or past its version, after an older version
+/// had run. A database that started out past it has nothing to compare.
+pub fn version_change_notes(versions: &[(String, DateTime<Utc>)], start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<String> {
+    SEMANTIC_CHANGES.iter()
+        .filter_map(|change| {
+            let boundary = version_parts(change.version);
+            let upgraded = versions.iter()
+                .filter(|(version, _)| version_parts(version) >= boundary)
+                .map(|(_, started)| *started)
+                .min()?;
+            let ran_older = versions.iter().any(|(version, started)| version_parts(version) < boundary && *started < upgraded);
+            (ran_older && start < upgraded && upgraded < end).then(|| format!(
+                "focusdebt {} changed {} at {}; earlier numbers in this range were counted the old way",
+                change.version, change.change, utils::format_datetime_local(upgraded),
+            ))
+        })
+        .collect()
+}
+
+/// `day` as a `DailyStats::date`: midnight UTC of its date, which keeps
+/// the date whatever the zone. Not where the day starts; that is
+/// `LocalDays::start`.
+pub fn start_of_day(day: NaiveDate) -> DateTime<Utc> {
+    DateTime::<Utc>::from_naive_utc_and_offset(day.and_hms_opt(0, 0, 0).unwrap(), Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::{LiveSnapshot, OpenEnd};
+    use crate::utils::timezone::Zone;
+
+    /// xorshift64*, enough to spread synthetic rows without a rand dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len() as u64) as usize]
+        }
+    }
+
+    const APPS: [&str; 5] = ["code", "firefox", "slack", "alacritty", "obsidian"];
+    const SESSION_NAMES: [&str; 4] = ["", "deep work", "review", "planning"];
+    const DOMAINS: [&str; 3] = ["github.com", "docs.rs", "youtube.com"];
+
+    fn first_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+    }
+
+    /// Midnight-to-midnight UTC days, whatever zone the tests run in
+    fn utc_days() -> LocalDays {
+        LocalDays::in_zone(Zone::Named(chrono_tz::UTC), 0)
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    /// A closed row of `seconds` from `start`
+    fn row(start: DateTime<Utc>, app: &str, seconds: u64, is_focus_app: bool) -> FocusSession {
+        FocusSession {
+            start_time: start,
+            end_time: Some(start + chrono::Duration::seconds(seconds as i64)),
+            app_name: app.to_string(),
+            window_title: format!("{} window", app),
+            domain: None,
+            duration: Duration::from_secs(seconds),
+            is_focus_app,
+            session_name: "work".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    fn random_session(rng: &mut Rng, start: DateTime<Utc>) -> FocusSession {
+        let app = rng.pick(&APPS);
+        let seconds = 1 + rng.below(3600);
+        let open = rng.below(40) == 0;
+        FocusSession {
+            start_time: start,
+            end_time: (!open).then(|| start + chrono::Duration::seconds(seconds as i64)),
+            app_name: app.to_string(),
+            window_title: format!("{} {}", app, rng.below(5)),
+            domain: (app == "firefox").then(|| rng.pick(&DOMAINS).to_string()),
+            duration: Duration::from_secs(seconds),
+            is_focus_app: rng.below(3) > 0,
+            session_name: rng.pick(&SESSION_NAMES).to_string(),
+            activity: (rng.below(2) == 0).then(|| rng.below(101) as f64 / 100.0),
+            passive: (rng.below(6) == 0).then(|| Duration::from_secs(rng.below(seconds))),
+            workspace: (rng.below(2) == 0).then(|| format!("{}", 1 + rng.below(3))),
+            power: None,
+            open_end: if open { OpenEnd::Open } else { OpenEnd::Closed },
+            manual: rng.below(20) == 0,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    /// A batch of `days` days of rows, switches, pauses and suspends
+    fn random_batch(rng: &mut Rng, days: usize) -> Vec<DayRecords> {
+        (0..days).map(|index| {
+            let day_start = start_of_day(first_day() + chrono::Duration::days(index as i64));
+            let mut records = DayRecords::default();
+            let mut at = day_start + chrono::Duration::seconds(rng.below(4 * 3600) as i64);
+            let mut previous: Option<String> = None;
+            for _ in 0..rng.below(60) {
+                let session = random_session(rng, at);
+                if let Some(from) = previous.replace(session.app_name.clone()) {
+                    if from != session.app_name {
+                        records.switches.push(ContextSwitch {
+                            timestamp: at,
+                            from_app: from,
+                            to_app: session.app_name.clone(),
+                            recovery_time: (rng.below(2) == 0).then(|| Duration::from_secs(rng.below(900))),
+                        });
+                    }
+                }
+                at += chrono::Duration::from_std(session.duration).unwrap() + chrono::Duration::seconds(rng.below(900) as i64);
+                records.sessions.push(session);
+                if at >= day_start + chrono::Duration::days(1) {
+                    break;
+                }
+            }
+            if rng.below(3) == 0 {
+                let pause = day_start + chrono::Duration::seconds(rng.below(20 * 3600) as i64);
+                let payload = serde_json::json!({ "reason": QUIET_HOURS_REASON, "range": "22:00-07:00" });
+                records.pauses.push(TrackerEvent { timestamp: pause, kind: EventKind::Pause, payload: payload.clone() });
+                records.pauses.push(TrackerEvent {
+                    timestamp: pause + chrono::Duration::seconds(1 + rng.below(3600) as i64),
+                    kind: EventKind::Resume,
+                    payload,
+                });
+            }
+            if rng.below(3) == 0 {
+                let start = day_start + chrono::Duration::seconds(rng.below(22 * 3600) as i64);
+                records.suspends.push(SuspendGap { start, end: start + chrono::Duration::seconds(600 + rng.below(7200) as i64) });
+            }
+            if rng.below(4) == 0 {
+                records.daemon_stops.push(day_start + chrono::Duration::seconds(rng.below(24 * 3600) as i64));
+            }
+            records
+        }).collect()
+    }
+
+    #[test]
+    fn parallel_daily_stats_match_the_serial_path() {
+        let mut focus_days = 0;
+        for seed in 1..=64u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let days = 1 + rng.below(10) as usize;
+            let batch = random_batch(&mut rng, days);
+            // Some batches end mid-way through their last day
+            let now = start_of_day(first_day()) + chrono::Duration::hours(24 * days as i64 - rng.below(30) as i64);
+
+            let serial = Stats::daily_stats_for_batch(&StatsSettings::default(), &utc_days(), first_day(), &batch, now, false, &mut Progress::hidden());
+            let parallel = Stats::daily_stats_for_batch(&StatsSettings::default(), &utc_days(), first_day(), &batch, now, true, &mut Progress::hidden());
+            assert_eq!(serial.len(), days);
+            assert_eq!(format!("{:?}", serial), format!("{:?}", parallel), "seed {}", seed);
+            assert_eq!(serde_json::to_string(&serial).unwrap(), serde_json::to_string(&parallel).unwrap(), "seed {}", seed);
+            focus_days += serial.iter().filter(|day| !day.total_focus_time.is_zero()).count();
+        }
+        assert!(focus_days > 100, "only {} days with focus time", focus_days);
+    }
+
+    #[test]
+    fn parallel_session_aggregation_matches_the_serial_path() {
+        for seed in 1..=64u64 {
+            let mut rng = Rng(seed.wrapping_mul(0xD1B5_4A32_D192_ED03));
+            let days = 1 + rng.below(10) as usize;
+            let batch = random_batch(&mut rng, days);
+            let sessions: Vec<FocusSession> = batch.iter().flat_map(|records| records.sessions.clone()).collect();
+            let suspends: Vec<SuspendGap> = batch.iter().flat_map(|records| records.suspends.clone()).collect();
+
+            let serial = Stats::aggregate_session_groups(&StatsSettings::default(), &sessions, &suspends, false);
+            let parallel = Stats::aggregate_session_groups(&StatsSettings::default(), &sessions, &suspends, true);
+            assert_eq!(format!("{:?}", serial), format!("{:?}", parallel), "seed {}", seed);
+            assert_eq!(serial.is_empty(), sessions.is_empty());
+        }
+    }
+
+    fn gaps(sessions: &[FocusSession], window: (&str, &str), stops: &[&str]) -> Vec<(DateTime<Utc>, DateTime<Utc>, bool)> {
+        let stops: Vec<DateTime<Utc>> = stops.iter().map(|stop| at(stop)).collect();
+        Stats::untracked_gaps(sessions, at(window.0), at(window.1), &stops, UNTRACKED_GAP_THRESHOLD)
+            .into_iter()
+            .map(|gap| (gap.start, gap.end, gap.daemon_stopped))
+            .collect()
+    }
+
+    #[test]
+    fn a_day_without_sessions_is_one_gap() {
+        assert_eq!(gaps(&[], ("2024-05-21T00:00:00Z", "2024-05-22T00:00:00Z"), &[]),
+            [(at("2024-05-21T00:00:00Z"), at("2024-05-22T00:00:00Z"), false)]);
+    }
+
+    #[test]
+    fn gaps_below_the_threshold_are_pauses() {
+        let sessions = [
+            row(at("2024-05-21T09:00:00Z"), "code", 600, true),
+            // 4m59s later: a pause
+            row(at("2024-05-21T09:14:59Z"), "firefox", 60, false),
+            // exactly the threshold later: a gap
+            row(at("2024-05-21T09:21:00Z"), "code", 540, true),
+        ];
+        assert_eq!(gaps(&sessions, ("2024-05-21T09:00:00Z", "2024-05-21T09:30:00Z"), &[]),
+            [(at("2024-05-21T09:15:59Z"), at("2024-05-21T09:21:00Z"), false)]);
+    }
+
+    #[test]
+    fn overlapping_and_unordered_sessions_cover_their_union() {
+        let sessions = [
+            row(at("2024-05-21T11:00:00Z"), "slack", 600, false),
+            row(at("2024-05-21T09:00:00Z"), "code", 3600, true),
+            // Inside the first hour, ending before it does
+            row(at("2024-05-21T09:10:00Z"), "firefox", 300, false),
+            row(at("2024-05-21T09:50:00Z"), "alacritty", 1200, true),
+        ];
+        assert_eq!(gaps(&sessions, ("2024-05-21T09:00:00Z", "2024-05-21T12:00:00Z"), &[]), [
+            (at("2024-05-21T10:10:00Z"), at("2024-05-21T11:00:00Z"), false),
+            (at("2024-05-21T11:10:00Z"), at("2024-05-21T12:00:00Z"), false),
+        ]);
+    }
+
+    #[test]
+    fn sessions_are_clipped_to_the_window() {
+        let sessions = [
+            // Started the day before
+            row(at("2024-05-20T23:00:00Z"), "code", 7200, true),
+            // Runs into the next day
+            row(at("2024-05-21T23:30:00Z"), "code", 3600, true),
+            row(at("2024-05-19T10:00:00Z"), "code", 600, true),
+        ];
+        assert_eq!(gaps(&sessions, ("2024-05-21T00:00:00Z", "2024-05-22T00:00:00Z"), &[]),
+            [(at("2024-05-21T01:00:00Z"), at("2024-05-21T23:30:00Z"), false)]);
+    }
+
+    #[test]
+    fn an_open_row_covers_its_duration() {
+        let mut open = row(at("2024-05-21T09:00:00Z"), "code", 1800, true);
+        open.end_time = None;
+        assert_eq!(gaps(&[open], ("2024-05-21T09:00:00Z", "2024-05-21T10:00:00Z"), &[]),
+            [(at("2024-05-21T09:30:00Z"), at("2024-05-21T10:00:00Z"), false)]);
+    }
+
+    #[test]
+    fn gaps_after_a_daemon_stop_are_marked() {
+        let sessions = [
+            row(at("2024-05-21T09:00:00Z"), "code", 3600, true),
+            row(at("2024-05-21T12:00:00Z"), "code", 3600, true),
+            row(at("2024-05-21T15:00:00Z"), "code", 3600, true),
+        ];
+        // Logged a few seconds after the last row ended, and one far away
+        let stops = ["2024-05-21T10:00:05Z", "2024-05-21T17:30:00Z"];
+        assert_eq!(gaps(&sessions, ("2024-05-21T09:00:00Z", "2024-05-21T16:00:00Z"), &stops), [
+            (at("2024-05-21T10:00:00Z"), at("2024-05-21T12:00:00Z"), true),
+            (at("2024-05-21T13:00:00Z"), at("2024-05-21T15:00:00Z"), false),
+        ]);
+    }
+
+    #[test]
+    fn a_past_day_reconciles_to_24_hours() {
+        let day = at("2024-05-21T00:00:00Z");
+        let records = DayRecords {
+            sessions: vec![
+                row(day + chrono::Duration::hours(9), "code", 5400, true),
+                row(day + chrono::Duration::minutes(630), "firefox", 1800, false),
+                row(day + chrono::Duration::hours(14), "code", 7200, true),
+            ],
+            daemon_stops: vec![day + chrono::Duration::minutes(660)],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), day.date_naive(), &records, day + chrono::Duration::days(3));
+        assert_eq!(stats.total_focus_time, Duration::from_secs(5400 + 7200));
+        assert_eq!(stats.total_distraction_time, Duration::from_secs(1800));
+        assert_eq!(stats.total_focus_time + stats.total_distraction_time + stats.untracked_time,
+            Duration::from_secs(24 * 3600));
+    }
+
+    #[test]
+    fn today_counts_untracked_time_only_up_to_now() {
+        let day = at("2024-05-21T00:00:00Z");
+        let records = DayRecords {
+            sessions: vec![row(day + chrono::Duration::hours(9), "code", 3600, true)],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), day.date_naive(), &records, day + chrono::Duration::hours(12));
+        assert_eq!(stats.untracked_time, Duration::from_secs(11 * 3600));
+    }
+
+    /// Compare `actual` to tests/snapshots/`name`, or write it there with
+    /// FOCUSDEBT_UPDATE_SNAPSHOTS set
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(name);
+        if std::env::var_os("FOCUSDEBT_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(actual == expected, "{} changed; if that is deliberate, rerun with FOCUSDEBT_UPDATE_SNAPSHOTS=1\n{}", name, actual);
+    }
+
+    #[test]
+    fn session_json_matches_the_snapshot() {
+        let start = at("2024-05-21T09:00:00Z");
+        let minutes = |n: i64| start + chrono::Duration::minutes(n);
+        let mut rows = vec![
+            row(start, "editor", 1500, true),
+            row(minutes(25), "browser", 300, false),
+            row(minutes(30), "editor", 1800, true),
+            row(minutes(60), "chat", 240, false),
+            row(minutes(64), "editor", 3360, true),
+        ];
+        rows[0].activity = Some(0.8);
+        rows[1].domain = Some("docs.rs".to_string());
+        rows[1].window_title = "serde - Rust".to_string();
+        rows[2].workspace = Some("2".to_string());
+        for row in &mut rows {
+            row.session_name = "api review".to_string();
+        }
+        let suspends = [SuspendGap { start: minutes(80), end: minutes(95) }];
+        let switches = [
+            ContextSwitch { timestamp: minutes(25), from_app: "editor".into(), to_app: "browser".into(), recovery_time: Some(Duration::from_secs(300)) },
+            ContextSwitch { timestamp: minutes(30), from_app: "browser".into(), to_app: "editor".into(), recovery_time: None },
+            ContextSwitch { timestamp: minutes(60), from_app: "editor".into(), to_app: "chat".into(), recovery_time: Some(Duration::from_secs(240)) },
+            ContextSwitch { timestamp: minutes(64), from_app: "chat".into(), to_app: "editor".into(), recovery_time: None },
+        ];
+
+        let mut sessions = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &suspends, false);
+        assert_eq!(sessions.len(), 1);
+        let mut session = sessions.remove(0);
+        session.task = Some(SessionTask {
+            started_at: start,
+            source: crate::storage::TaskSource::Taskwarrior,
+            id: "42".to_string(),
+            uuid: Some("0f3c1c1e-8a43-4d8e-9a4f-3d4c5b6a7e8f".to_string()),
+            description: Some("Review the API draft".to_string()),
+        });
+
+        let mut session_json = Stats::session_json(&session, &switches);
+        session_json.events = Some(vec![Stats::event_json(TrackerEvent {
+            timestamp: minutes(80),
+            kind: EventKind::Suspend,
+            payload: serde_json::json!({ "until": minutes(95).to_rfc3339() }),
+        })]);
+        let envelope = SessionJsonEnvelope { schema_version: SESSION_JSON_SCHEMA_VERSION, session: session_json };
+        assert_snapshot("sessions_show.json", &(serde_json::to_string_pretty(&envelope).unwrap() + "\n"));
+    }
+
+    #[test]
+    fn budget_usage_resets_at_the_day_boundary() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let day_start_hour = 4;
+        let now = at("2024-05-21T15:00:00Z");
+        let boundary = utils::day_start(now, day_start_hour);
+        let mut tab = row(boundary + chrono::Duration::minutes(30), "fd-browser", 600, false);
+        tab.domain = Some("youtube.com - Video".to_string());
+        for session in [
+            // Before the day began: yesterday's budget
+            row(boundary - chrono::Duration::minutes(20), "fd-chat", 900, false),
+            row(boundary + chrono::Duration::minutes(10), "fd-chat", 300, false),
+            row(boundary + chrono::Duration::hours(2), "FD-Chat", 600, false),
+            tab,
+            row(boundary + chrono::Duration::hours(3), "fd-editor", 3600, true),
+        ] {
+            db.save_focus_session(&session).unwrap();
+        }
+        let budget = |target: &str, limit: u64| Budget {
+            target: target.to_string(),
+            limit: Duration::from_secs(limit),
+            period: BudgetPeriod::Day,
+            carry_over: false,
+            carry_over_cap: Duration::from_secs(limit),
+        };
+        let budgets = [budget("fd-chat", 1200), budget("youtube.com", 1800)];
+        let current = row(now - chrono::Duration::minutes(5), "fd-chat", 300, false);
+
+        let usage = Stats::calculate_budget_usage(&db, &StatsSettings::default(), &budgets, now, day_start_hour, Some(&current)).unwrap();
+        let used: Vec<(&str, u64, u32)> = usage.iter()
+            .map(|usage| (usage.target.as_str(), usage.used.as_secs(), usage.percent().round() as u32))
+            .collect();
+        assert_eq!(used, [("fd-chat", 1200, 100), ("youtube.com", 600, 33)]);
+        assert!(usage.iter().all(|usage| usage.period_start == boundary && usage.carried.is_zero()));
+
+        // The row just before the boundary went to the day before
+        let yesterday = utils::day_start(boundary - chrono::Duration::minutes(1), day_start_hour);
+        assert_eq!(yesterday, boundary - chrono::Duration::days(1));
+        let used = Stats::calculate_budget_used_between(&db, &budgets, yesterday, boundary).unwrap();
+        assert_eq!(used, [Duration::from_secs(900), Duration::ZERO]);
+    }
+
+    #[test]
+    fn weekly_budgets_start_over_on_monday() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let day_start_hour = 4;
+        // A Wednesday
+        let now = at("2024-05-22T15:00:00Z");
+        let monday = utils::week_start(now, day_start_hour);
+        assert_eq!(utils::tracking_day(monday, day_start_hour), NaiveDate::from_ymd_opt(2024, 5, 20).unwrap());
+        for session in [
+            // Sunday night belongs to last week
+            row(monday - chrono::Duration::minutes(30), "fd-steam", 1800, false),
+            row(monday + chrono::Duration::hours(1), "fd-steam", 3600, false),
+            row(monday + chrono::Duration::days(2), "fd-steam", 1200, false),
+        ] {
+            db.save_focus_session(&session).unwrap();
+        }
+        let budget = Budget {
+            target: "fd-steam".to_string(),
+            limit: Duration::from_secs(3 * 3600),
+            period: BudgetPeriod::Week,
+            carry_over: false,
+            carry_over_cap: Duration::from_secs(3 * 3600),
+        };
+
+        let usage = Stats::calculate_budget_usage(&db, &StatsSettings::default(), std::slice::from_ref(&budget), now, day_start_hour, None).unwrap();
+        assert_eq!((usage[0].used, usage[0].period_start), (Duration::from_secs(4800), monday));
+        // Just before the boundary it is still last week
+        let last_week = utils::week_start(monday - chrono::Duration::minutes(1), day_start_hour);
+        assert_eq!(last_week, monday - chrono::Duration::days(7));
+        assert_eq!(Stats::calculate_budget_used_between(&db, std::slice::from_ref(&budget), last_week, monday).unwrap(), [Duration::from_secs(1800)]);
+        let next = Stats::calculate_budget_usage(&db, &StatsSettings::default(), &[budget], monday + chrono::Duration::days(7), day_start_hour, None).unwrap();
+        assert_eq!((next[0].used, next[0].period_start), (Duration::ZERO, monday + chrono::Duration::days(7)));
+    }
+
+    #[test]
+    fn unused_daily_budget_carries_over_up_to_the_cap() {
+        let minutes = |n: u64| Duration::from_secs(n * 60);
+        assert_eq!(carried_over(minutes(30), minutes(60), &[]), Duration::ZERO);
+        assert_eq!(carried_over(minutes(30), minutes(60), &[Duration::ZERO; 3]), minutes(60));
+        assert_eq!(carried_over(minutes(30), minutes(60), &[Duration::ZERO, minutes(10), minutes(60)]), minutes(20));
+        assert_eq!(carried_over(minutes(30), minutes(60), &[minutes(90)]), Duration::ZERO);
+
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let day_start_hour = 4;
+        // Thursday; the three workdays before feed the carry-over
+        let now = at("2024-05-23T15:00:00Z");
+        let today = utils::day_start(now, day_start_hour);
+        let day = |back: i64| today - chrono::Duration::days(back) + chrono::Duration::hours(2);
+        for session in [
+            row(day(2), "fd-chat", 600, false),
+            row(day(1), "fd-chat", 3600, false),
+            row(day(0), "fd-chat", 900, false),
+        ] {
+            db.save_focus_session(&session).unwrap();
+        }
+        let budget = Budget {
+            target: "fd-chat".to_string(),
+            limit: minutes(30),
+            period: BudgetPeriod::Day,
+            carry_over: true,
+            carry_over_cap: minutes(60),
+        };
+
+        // Monday leaves 30m, Tuesday 50m, Wednesday's hour leaves 20m
+        let usage = Stats::calculate_budget_usage(&db, &StatsSettings::default(), std::slice::from_ref(&budget), now, day_start_hour, None).unwrap();
+        assert_eq!((usage[0].carried, usage[0].limit, usage[0].used), (minutes(20), minutes(50), minutes(15)));
+        assert_eq!(usage[0].period_start, today);
+
+        // Friday looks back to Tuesday: 20m left, none, then Thursday's 15m
+        let friday = Stats::calculate_budget_usage(&db, &StatsSettings::default(), &[budget], now + chrono::Duration::days(1), day_start_hour, None).unwrap();
+        assert_eq!((friday[0].carried, friday[0].used), (minutes(15), Duration::ZERO));
+    }
+
+    #[test]
+    fn cached_daily_stats_round_trip_and_say_when_they_were_saved() {
+        let day = first_day();
+        let start = start_of_day(day);
+        let records = DayRecords {
+            sessions: vec![row(start + chrono::Duration::hours(9), "fd-editor", 1800, true)],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        let now = Utc::now();
+        let cached = CachedDailyStats { saved_at: now, stats };
+
+        let read: CachedDailyStats = serde_json::from_str(&serde_json::to_string(&cached).unwrap()).unwrap();
+        assert_eq!(format!("{:?}", read.stats), format!("{:?}", cached.stats));
+        assert_eq!(read.describe_saved_at(), utils::format_timestamp_local(now));
+
+        let older = CachedDailyStats { saved_at: now - chrono::Duration::days(3), ..read };
+        assert_eq!(older.describe_saved_at(), utils::format_datetime_local(older.saved_at));
+    }
+
+    #[test]
+    fn engaged_focus_time_weights_focus_rows_by_activity() {
+        let start = at("2024-05-21T09:00:00Z");
+        let with_activity = |app: &str, seconds: u64, is_focus_app: bool, activity: Option<f64>| FocusSession {
+            activity,
+            ..row(start, app, seconds, is_focus_app)
+        };
+
+        assert_eq!(Stats::engaged_focus_time(&[]), None);
+        // Sampling off: no activity anywhere, so no engaged time either
+        assert_eq!(Stats::engaged_focus_time(&[with_activity("fd-editor", 3600, true, None)]), None);
+
+        let sessions = [
+            with_activity("fd-editor", 3600, true, Some(0.5)),
+            with_activity("fd-editor", 1200, true, Some(1.0)),
+            // Focused but idle: counts for nothing
+            with_activity("fd-editor", 7200, true, Some(0.0)),
+            // Rows from before sampling was on are left out, not counted as idle
+            with_activity("fd-editor", 600, true, None),
+            with_activity("fd-chat", 1800, false, Some(1.0)),
+            // Out-of-range levels are clamped
+            with_activity("fd-term", 100, true, Some(1.7)),
+            with_activity("fd-term", 100, true, Some(-0.3)),
+        ];
+        assert_eq!(Stats::engaged_focus_time(&sessions), Some(Duration::from_secs(1800 + 1200 + 100)));
+    }
+
+    fn score_inputs(efficiency: f64, context_switches: usize, tracked_minutes: u64, longest_block_minutes: u64) -> ScoreInputs {
+        ScoreInputs {
+            efficiency,
+            context_switches,
+            tracked: Duration::from_secs(tracked_minutes * 60),
+            longest_block: Duration::from_secs(longest_block_minutes * 60),
+        }
+    }
+
+    #[test]
+    fn focus_score_follows_the_formula() {
+        let config = ScoreConfig {
+            efficiency_weight: 0.5,
+            switch_weight: 0.3,
+            block_weight: 0.2,
+            switch_rate_reference: 6.0,
+            target_block_minutes: 90,
+        };
+        let score = |inputs: ScoreInputs| focus_score(&inputs, &config);
+
+        // All focus, no switches, a full block
+        assert_eq!(score(score_inputs(100.0, 0, 120, 90)), Some(100));
+        // Nothing focused, switching at the reference rate, no block
+        assert_eq!(score(score_inputs(0.0, 12, 120, 0)), Some(15));
+        // 100 × (0.5·0.8 + 0.3·0.5 + 0.2·0.5) = 65
+        assert_eq!(score(score_inputs(80.0, 6, 60, 45)), Some(65));
+        // Out-of-range efficiency and blocks past the target are capped
+        assert_eq!(score(score_inputs(130.0, 0, 60, 300)), Some(100));
+        assert_eq!(score(score_inputs(-5.0, 0, 60, 0)), Some(30));
+        // More switching never raises the score
+        let scores: Vec<u32> = (0..50).map(|switches| score(score_inputs(70.0, switches, 240, 60)).unwrap()).collect();
+        assert!(scores.windows(2).all(|pair| pair[1] <= pair[0]), "{:?}", scores);
+
+        assert_eq!(score(score_inputs(100.0, 0, 0, 0)), None);
+        let unweighted = ScoreConfig { efficiency_weight: 0.0, switch_weight: -1.0, block_weight: 0.0, ..config.clone() };
+        assert_eq!(focus_score(&score_inputs(100.0, 0, 60, 60), &unweighted), None);
+        // A single weight is that part alone
+        let efficiency_only = ScoreConfig { switch_weight: 0.0, block_weight: 0.0, ..config };
+        assert_eq!(focus_score(&score_inputs(74.0, 30, 60, 0), &efficiency_only), Some(74));
+    }
+
+    #[test]
+    fn focus_score_is_described_against_the_average() {
+        assert_eq!(describe_focus_score(74, None, DayType::Workday), "74");
+        assert_eq!(describe_focus_score(74, Some(68.0), DayType::Workday), "74 ▲ +6 vs workday avg");
+        assert_eq!(describe_focus_score(60, Some(68.4), DayType::RestDay), "60 ▼ -8 vs weekend avg");
+        assert_eq!(describe_focus_score(68, Some(68.3), DayType::Workday), "68 = workday avg");
+    }
+
+    #[test]
+    fn daily_stats_carry_the_focus_score_of_their_rows() {
+        let start = start_of_day(first_day());
+        let nine = start + chrono::Duration::hours(9);
+        let records = DayRecords {
+            sessions: vec![
+                row(nine, "fd-editor", 3600, true),
+                row(nine + chrono::Duration::hours(1), "fd-chat", 1200, false),
+            ],
+            switches: vec![ContextSwitch {
+                timestamp: nine + chrono::Duration::hours(1),
+                from_app: "fd-editor".to_string(),
+                to_app: "fd-chat".to_string(),
+                recovery_time: None,
+            }],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        let inputs = ScoreInputs {
+            efficiency: stats.focus_efficiency,
+            context_switches: 1,
+            tracked: Duration::from_secs(4800),
+            longest_block: stats.longest_focus_block,
+        };
+        assert_eq!(stats.longest_focus_block, Duration::from_secs(3600));
+        assert_eq!(stats.focus_score, focus_score(&inputs, &ScoreConfig::default()));
+        assert!(stats.focus_score.is_some());
+
+        let empty = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &DayRecords::default(), start + chrono::Duration::days(1));
+        assert_eq!(empty.focus_score, None);
+    }
+
+    #[test]
+    fn day_notes_flag_dst_days() {
+        let start = start_of_day(first_day());
+        let mut stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &DayRecords::default(), start + chrono::Duration::days(1));
+        let notes = |stats: &DailyStats, hours: u64| {
+            let stats = DailyStats { local_day_length: Some(Duration::from_secs(hours * 3600)), ..stats.clone() };
+            stats.day_notes()
+        };
+        assert!(notes(&stats, 24).is_empty());
+        assert_eq!(notes(&stats, 23), ["DST transition: 23h day"]);
+        assert_eq!(notes(&stats, 25), ["DST transition: 25h day"]);
+        // Lord Howe Island moves its clocks by half an hour
+        let lord_howe = DailyStats { local_day_length: Some(Duration::from_secs(23 * 3600 + 1800)), ..stats.clone() };
+        assert_eq!(lord_howe.day_notes(), ["DST transition: 23h 30m day"]);
+
+        stats.timezone_changes = vec!["Europe/Berlin → America/New_York".to_string()];
+        assert_eq!(notes(&stats, 24), ["Timezone changed: Europe/Berlin → America/New_York"]);
+    }
+
+    fn named(name: &str, start: DateTime<Utc>, app: &str, seconds: u64) -> FocusSession {
+        FocusSession { session_name: name.to_string(), ..row(start, app, seconds, true) }
+    }
+
+    #[test]
+    fn unnamed_rows_group_by_day_beside_named_sessions() {
+        let rows = vec![
+            named("alpha", at("2024-03-04T09:00:00Z"), "fd-editor", 600),
+            named("", at("2024-03-04T10:00:00Z"), "fd-editor", 300),
+            named("", at("2024-03-04T11:00:00Z"), "fd-chat", 300),
+            named("alpha", at("2024-03-04T12:00:00Z"), "fd-term", 600),
+            named("", at("2024-03-04T13:00:00Z"), "fd-term", 300),
+            named("", at("2024-03-05T12:00:00Z"), "fd-editor", 120),
+            named("beta", at("2024-03-05T13:00:00Z"), "fd-editor", 60),
+            named("", at("2024-03-05T14:00:00Z"), "fd-chat", 120),
+        ];
+        let sessions = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false);
+        let mut totals: Vec<(String, u64)> = sessions.iter()
+            .map(|session| (session.session_name.clone(), session.total_duration.as_secs()))
+            .collect();
+        totals.sort();
+        // A session spans its first row's start to its last row's end
+        let mut expected = vec![
+            ("alpha".to_string(), 3 * 3600 + 600),
+            ("beta".to_string(), 60),
+            (unnamed_session_name(at("2024-03-04T12:00:00Z")), 3 * 3600 + 300),
+            (unnamed_session_name(at("2024-03-05T12:00:00Z")), 2 * 3600 + 120),
+        ];
+        expected.sort();
+        assert_eq!(totals, expected);
+    }
+
+    #[test]
+    fn sessions_list_sums_up_unnamed_rows_in_one_line() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let now = Utc::now();
+        db.save_focus_session(&named("alpha", now - chrono::Duration::hours(2), "fd-editor", 600)).unwrap();
+        for days_ago in 1..4 {
+            for minute in 0..8 {
+                let start = now - chrono::Duration::days(days_ago) - chrono::Duration::minutes(minute * 5 + 60);
+                db.save_focus_session(&named("", start, "fd-editor", 60)).unwrap();
+            }
+        }
+        // Named sessions further back in the month are listed as usual
+        db.save_focus_session(&named("beta", now - chrono::Duration::days(10), "fd-term", 60)).unwrap();
+
+        let lines = Stats::list_sessions(&db, &StatsSettings::default(), None, None).unwrap();
+        assert_eq!(lines.len(), 3, "{:#?}", lines);
+        assert!(lines[0].contains("alpha"), "{}", lines[0]);
+        assert!(lines[1].contains("beta"), "{}", lines[1]);
+        assert!(lines[2].starts_with("(unnamed) 24 rows on "), "{}", lines[2]);
+        assert!(lines[2].ends_with("Name them with 'focusdebt sessions adopt <name> --date YYYY-MM-DD'"), "{}", lines[2]);
+        assert!(!lines.iter().any(|line| line.contains("unnamed (")), "{:#?}", lines);
+    }
+
+    #[test]
+    fn unnamed_rows_line_lists_the_latest_days() {
+        assert_eq!(Stats::describe_unnamed_rows(&[named("alpha", at("2024-03-04T12:00:00Z"), "fd-editor", 60)]), None);
+        let rows: Vec<FocusSession> = (0..10)
+            .map(|day| named("", at("2024-03-04T12:00:00Z") + chrono::Duration::days(day), "fd-editor", 60))
+            .collect();
+        let line = Stats::describe_unnamed_rows(&rows).unwrap();
+        assert!(line.starts_with("(unnamed) 10 rows on 10 days without a session name: "), "{}", line);
+        let newest = utils::format_date_local(NaiveDate::from_ymd_opt(2024, 3, 13).unwrap());
+        assert!(line.contains(&format!(": {}, ", newest)), "{}", line);
+        assert!(line.contains(", ...."), "{}", line);
+    }
+
+    /// Switches at the given seconds after 09:00
+    fn switch_trace(switches: &[(i64, &str, &str)]) -> Vec<ContextSwitch> {
+        switches.iter()
+            .map(|(second, from, to)| ContextSwitch {
+                timestamp: at("2024-03-04T09:00:00Z") + chrono::Duration::seconds(*second),
+                from_app: from.to_string(),
+                to_app: to.to_string(),
+                recovery_time: None,
+            })
+            .collect()
+    }
+
+    fn trips(pairs: &[RoundTripPair]) -> Vec<(&str, &str, usize, u64)> {
+        pairs.iter().map(|pair| (pair.home.as_str(), pair.away.as_str(), pair.round_trips, pair.time_away.as_secs())).collect()
+    }
+
+    #[test]
+    fn round_trips_are_returns_within_the_window() {
+        let switches = switch_trace(&[
+            (0, "fd-editor", "fd-browser"),
+            (20, "fd-browser", "fd-editor"),
+            // Too long away to be a round trip
+            (100, "fd-editor", "fd-browser"),
+            (200, "fd-browser", "fd-editor"),
+            // Exactly at the window still counts
+            (300, "fd-editor", "fd-chat"),
+            (360, "fd-chat", "fd-editor"),
+            // Moving on to a third app isn't a return
+            (400, "fd-editor", "fd-chat"),
+            (410, "fd-chat", "fd-term"),
+        ]);
+        let pairs = round_trip_pairs(&switches, Duration::from_secs(60));
+        assert_eq!(trips(&pairs), [("fd-editor", "fd-chat", 1, 60), ("fd-editor", "fd-browser", 1, 20)]);
+        assert!(round_trip_pairs(&switches, Duration::from_secs(10)).is_empty());
+        assert_eq!(trips(&round_trip_pairs(&switches, Duration::from_secs(100)))[0], ("fd-editor", "fd-browser", 2, 120));
+    }
+
+    #[test]
+    fn round_trip_returns_start_no_new_trip() {
+        // A→B→A→B→A is two trips, not four
+        let switches = switch_trace(&[
+            (0, "fd-editor", "fd-browser"),
+            (10, "fd-browser", "fd-editor"),
+            (20, "fd-editor", "fd-browser"),
+            (30, "fd-browser", "fd-editor"),
+        ]);
+        assert_eq!(trips(&round_trip_pairs(&switches, Duration::from_secs(60))), [("fd-editor", "fd-browser", 2, 20)]);
+    }
+
+    #[test]
+    fn round_trip_pairs_join_both_directions_under_the_usual_home() {
+        let switches = switch_trace(&[
+            (0, "fd-browser", "fd-editor"),
+            (5, "fd-editor", "fd-browser"),
+            (100, "fd-editor", "fd-browser"),
+            (110, "fd-browser", "fd-editor"),
+            (200, "fd-editor", "fd-browser"),
+            (215, "fd-browser", "fd-editor"),
+            (300, "fd-term", "fd-chat"),
+            (301, "fd-chat", "fd-term"),
+        ]);
+        let pairs = round_trip_pairs(&switches, Duration::from_secs(60));
+        assert_eq!(trips(&pairs), [("fd-editor", "fd-browser", 3, 30), ("fd-term", "fd-chat", 1, 1)]);
+        assert_eq!(pairs[0].describe(), "fd-editor ⇄ fd-browser: 3 round trips, ~30s");
+        assert_eq!(pairs[1].describe(), "fd-term ⇄ fd-chat: 1 round trip, ~1s");
+        assert_eq!(Config::default().round_trip_window_seconds, 60);
+    }
+
+    /// A session of 3h 12m focus, 82% efficiency and 14 switches
+    fn shared_session(name: &str) -> AggregatedSession {
+        let rows = vec![
+            named(name, at("2024-03-04T09:00:00Z"), "fd-editor", 3 * 3600 + 12 * 60),
+            FocusSession { is_focus_app: false, ..named(name, at("2024-03-04T12:30:00Z"), "fd-chat", 40 * 60) },
+        ];
+        let mut session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        session.focus_efficiency = 82.0;
+        session.context_switches = 14;
+        session
+    }
+
+    #[test]
+    fn oneline_share_has_focus_efficiency_switches_and_name() {
+        assert_eq!(Stats::share_oneline(&shared_session("api refactor")), "🎯 3h 12m focus, 82% efficiency, 14 switches — 'api refactor'");
+        let mut single = shared_session("api refactor");
+        single.context_switches = 1;
+        assert!(Stats::share_oneline(&single).contains(", 1 switch — "));
+    }
+
+    #[test]
+    fn tweet_share_shortens_only_the_name_to_fit() {
+        let tweet = Stats::share_tweet(&shared_session("api refactor"));
+        assert_eq!(tweet, "🎯 3h 12m of focus on 'api refactor': 82% efficiency, 14 context switches. #focusdebt");
+
+        let long_name = "ü".repeat(400);
+        let tweet = Stats::share_tweet(&shared_session(&long_name));
+        assert_eq!(tweet.chars().count(), TWEET_MAX_CHARS);
+        assert!(tweet.starts_with("🎯 3h 12m of focus on 'üü"), "{}", tweet);
+        assert!(tweet.ends_with("ü…': 82% efficiency, 14 context switches. #focusdebt"), "{}", tweet);
+
+        // A name that just fits is left whole
+        let overhead = Stats::share_tweet(&shared_session("x")).chars().count() - 1;
+        let exact = "x".repeat(TWEET_MAX_CHARS - overhead);
+        let tweet = Stats::share_tweet(&shared_session(&exact));
+        assert_eq!(tweet.chars().count(), TWEET_MAX_CHARS);
+        assert!(tweet.contains(&format!("'{}'", exact)));
+    }
+
+    #[test]
+    fn badge_shows_todays_focus_colored_by_efficiency() {
+        let date = start_of_day(first_day());
+        let mut day = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), date.date_naive(), &DayRecords::default(), date + chrono::Duration::days(1));
+        day.total_focus_time = Duration::from_secs(3 * 3600 + 12 * 60);
+        day.focus_efficiency = 82.0;
+        let svg = Stats::share_badge_svg(&day);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="125" height="20" role="img" aria-label="focus today: 3.2h">"#), "{}", svg);
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(r##"<rect x="87" width="38" height="20" fill="#4c1"/>"##), "{}", svg);
+        assert_eq!(svg.matches("<text ").count(), svg.matches("</text>").count());
+
+        for (efficiency, color) in [(75.0, "#4c1"), (74.9, "#dfb317"), (50.0, "#dfb317"), (25.0, "#fe7d37"), (0.0, "#e05d44")] {
+            assert_eq!(efficiency_color(efficiency), color, "{}", efficiency);
+        }
+        assert_eq!(html_escape(r#"<a href="x">Q&A</a>"#), "&lt;a href=&quot;x&quot;&gt;Q&amp;A&lt;/a&gt;");
+    }
+
+    fn quiet_event(kind: EventKind, time: &str, reason: &str) -> TrackerEvent {
+        TrackerEvent { timestamp: at(time), kind, payload: serde_json::json!({ "reason": reason, "range": "22:00-07:00" }) }
+    }
+
+    #[test]
+    fn quiet_periods_come_from_pause_and_resume_events() {
+        let (day_start, day_end) = (at("2024-03-04T00:00:00Z"), at("2024-03-05T00:00:00Z"));
+        let hours = |periods: Vec<(DateTime<Utc>, DateTime<Utc>)>| -> Vec<(String, String)> {
+            periods.into_iter().map(|(start, end)| (start.format("%H:%M").to_string(), end.format("%H:%M").to_string())).collect()
+        };
+        // Paused since the night before, and again from the evening on
+        let events = [
+            quiet_event(EventKind::Resume, "2024-03-04T07:00:00Z", QUIET_HOURS_REASON),
+            quiet_event(EventKind::Pause, "2024-03-04T12:00:00Z", "other"),
+            quiet_event(EventKind::Resume, "2024-03-04T13:00:00Z", "other"),
+            quiet_event(EventKind::Pause, "2024-03-04T22:00:00Z", QUIET_HOURS_REASON),
+        ];
+        assert_eq!(hours(quiet_periods(&events, &[], day_start, day_end)), [
+            ("00:00".to_string(), "07:00".to_string()),
+            ("22:00".to_string(), "00:00".to_string()),
+        ]);
+
+        // A daemon stop ends the pause, and doesn't start one on its own
+        let stops = [at("2024-03-04T23:00:00Z")];
+        assert_eq!(hours(quiet_periods(&events[3..], &stops, day_start, day_end)), [("22:00".to_string(), "23:00".to_string())]);
+        assert!(quiet_periods(&[], &stops, day_start, day_end).is_empty());
+    }
+
+    /// The top apps block and an efficiency bar laid out at `width`, with
+    /// bars in ASCII whatever the terminal supports
+    fn layout_sample(width: usize) -> String {
+        let layout = ReportLayout::with_width(width);
+        let apps = [
+            ("fd-editor", 9000u64),
+            ("fd-term", 4500),
+            ("a-very-long-application-name-indeed", 1200),
+        ];
+        let mut out = format!("--- {} columns{}\n", width, if layout.is_compact() { " (compact)" } else { "" });
+        for (i, (app, seconds)) in apps.iter().enumerate() {
+            let fraction = *seconds as f64 / apps[0].1 as f64;
+            let duration = utils::format_duration(Duration::from_secs(*seconds), DurationStyle::Long);
+            out.push_str(&format!("{}. {} {}\n", i + 1, bar_row(&layout, app, 15, fraction, 20, BarStyle::Shade), duration));
+        }
+        out.push_str(&format!("Efficiency: {}\n", with_bar(layout.bar(0.82, 20, BarStyle::Blocks), "82%".to_string())));
+        for style in [BarStyle::Blocks, BarStyle::Shade] {
+            let ((filled, empty), (ascii_filled, ascii_empty)) = (glyphs::current().bar_cells(style), GlyphSet::Ascii.bar_cells(style));
+            out = out.replace(filled, &ascii_filled.to_string()).replace(empty, &ascii_empty.to_string());
+        }
+        out
+    }
+
+    #[test]
+    fn report_layout_matches_the_snapshots_at_40_80_and_120_columns() {
+        let sample: String = [40, 80, 120].into_iter().map(layout_sample).collect();
+        assert_snapshot("report_layout.txt", &sample);
+    }
+
+    #[test]
+    fn report_layout_scales_up_to_a_cap_and_drops_bars_when_narrow() {
+        assert_eq!(ReportLayout::with_width(80).cells(20), 20);
+        assert_eq!(ReportLayout::with_width(40).cells(20), 10);
+        assert_eq!(ReportLayout::with_width(120).cells(20), 30);
+        // Wider terminals don't stretch bars further
+        assert_eq!(ReportLayout::with_width(300).cells(20), 30);
+        assert_eq!(ReportLayout::with_width(1).cells(20), 1);
+
+        assert!(ReportLayout::with_width(49).is_compact());
+        assert!(!ReportLayout::with_width(50).is_compact());
+        assert!(ReportLayout::with_width(49).bar(0.5, 20, BarStyle::Blocks).is_none());
+
+        // Names are cut by characters, never inside one
+        let layout = ReportLayout::with_width(40);
+        assert_eq!(layout.fit("日本語のアプリケーション名", 15), "日本語の...");
+        assert_eq!(layout.fit("a\tb", 15), utils::escape_control_chars("a\tb"));
+    }
+
+    /// (app, start second, duration seconds) of each merged row
+    fn merged(rows: Vec<FocusSession>) -> Vec<(String, i64, u64)> {
+        let start = at("2024-03-04T09:00:00Z");
+        merge_short_gaps(rows, Duration::from_secs(5)).iter()
+            .map(|row| (row.app_name.clone(), (row.start_time - start).num_seconds(), row.duration.as_secs()))
+            .collect()
+    }
+
+    #[test]
+    fn short_gaps_between_identical_rows_are_merged() {
+        let second = |n: i64| at("2024-03-04T09:00:00Z") + chrono::Duration::seconds(n);
+        // An hour in the editor split by two one-second detection hiccups,
+        // handed over out of order
+        let rows = vec![
+            row(second(1201), "fd-editor", 1200, true),
+            row(second(0), "fd-editor", 1200, true),
+            row(second(2402), "fd-editor", 1198, true),
+        ];
+        let total: u64 = rows.iter().map(|row| row.duration.as_secs()).sum();
+        let merged_rows = merged(rows);
+        assert_eq!(merged_rows, [("fd-editor".to_string(), 0, total)]);
+        let single = merge_short_gaps(vec![row(second(0), "fd-editor", 1200, true), row(second(1201), "fd-editor", 1200, true)], Duration::from_secs(5));
+        assert_eq!(single[0].end_time, Some(second(2401)));
+
+        // A gap of merge_gap_seconds or more is a real break
+        assert_eq!(merged(vec![row(second(0), "fd-editor", 60, true), row(second(65), "fd-editor", 60, true)]).len(), 2);
+        // So is anything else differing
+        let retitled = FocusSession { window_title: "other.rs".to_string(), ..row(second(61), "fd-editor", 60, true) };
+        assert_eq!(merged(vec![row(second(0), "fd-editor", 60, true), retitled]).len(), 2);
+        assert_eq!(merged(vec![row(second(0), "fd-editor", 60, true), row(second(61), "fd-editor", 60, false)]).len(), 2);
+        assert_eq!(merged(vec![row(second(0), "fd-editor", 60, true), row(second(61), "fd-term", 60, true)]).len(), 2);
+        let other_session = FocusSession { session_name: "other".to_string(), ..row(second(61), "fd-editor", 60, true) };
+        assert_eq!(merged(vec![row(second(0), "fd-editor", 60, true), other_session]).len(), 2);
+    }
+
+    #[test]
+    fn merged_rows_weigh_activity_and_stay_open_when_live() {
+        let second = |n: i64| at("2024-03-04T09:00:00Z") + chrono::Duration::seconds(n);
+        let busy = FocusSession { activity: Some(1.0), ..row(second(0), "fd-editor", 300, true) };
+        let idle = FocusSession { activity: Some(0.0), ..row(second(302), "fd-editor", 100, true) };
+        let rows = merge_short_gaps(vec![busy, idle], Duration::from_secs(5));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].activity, Some(0.75));
+
+        let live = FocusSession { end_time: None, ..row(second(61), "fd-editor", 30, true) };
+        let rows = merge_short_gaps(vec![row(second(0), "fd-editor", 60, true), live], Duration::from_secs(5));
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].end_time, rows[0].duration.as_secs()), (None, 90));
+    }
+
+    #[test]
+    fn aggregation_counts_merged_rows_once_and_keeps_the_time() {
+        let second = |n: i64| at("2024-03-04T09:00:00Z") + chrono::Duration::seconds(n);
+        let rows = vec![
+            row(second(0), "fd-editor", 1200, true),
+            row(second(1201), "fd-editor", 1200, true),
+            row(second(2402), "fd-chat", 300, false),
+            row(second(2703), "fd-chat", 300, false),
+        ];
+        let session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        let usage: Vec<(String, u64)> = session.app_usage.iter().map(|(app, time, _)| (app.clone(), time.as_secs())).collect();
+        assert_eq!(usage, [("fd-editor".to_string(), 2400), ("fd-chat".to_string(), 600)]);
+        assert_eq!(merge_short_gaps(rows, Duration::from_secs(5)).len(), 2);
+    }
+
+    #[test]
+    fn switching_cost_sums_recovery_and_assumes_the_rest() {
+        let start = at("2024-03-04T09:00:00Z");
+        let rows: Vec<FocusSession> = [
+            (0, "fd-editor", 60, true),
+            (60, "fd-chat", 30, false),
+            (90, "fd-editor", 100, true),
+            (190, "fd-browser", 20, false),
+            (210, "fd-chat", 30, false),
+            (240, "fd-editor", 60, true),
+            (300, "fd-term", 60, true),
+            (360, "fd-steam", 600, false),
+        ].into_iter()
+            .map(|(second, app, seconds, is_focus)| row(start + chrono::Duration::seconds(second), app, seconds, is_focus))
+            .collect();
+        let mut switches = switch_trace(&[
+            (60, "fd-editor", "fd-chat"),
+            (90, "fd-chat", "fd-editor"),
+            (190, "fd-editor", "fd-browser"),
+            // Between two distractions: not a switch away
+            (210, "fd-browser", "fd-chat"),
+            // Back, but nothing was measured
+            (240, "fd-chat", "fd-editor"),
+            // Focus to focus costs nothing
+            (300, "fd-editor", "fd-term"),
+            // Never came back
+            (360, "fd-term", "fd-steam"),
+        ]);
+        switches[1].recovery_time = Some(Duration::from_secs(45));
+
+        let cost = switching_cost(&switches, &rows, Duration::from_secs(120), true);
+        assert_eq!(cost, SwitchCost {
+            total: Duration::from_secs(45 + 2 * 120),
+            measured: 1,
+            assumed: 2,
+            skipped: 0,
+            default_cost: Duration::from_secs(120),
+        });
+        assert_eq!(cost.headline(), "~4m 45s lost to context switching");
+        assert_eq!(cost.summary(), "~4m 45s (estimate: 1 measured, 2 at 2m 0s each)");
+
+        let measured_only = switching_cost(&switches, &rows, Duration::from_secs(120), false);
+        assert_eq!((measured_only.total, measured_only.assumed, measured_only.skipped), (Duration::from_secs(45), 0, 2));
+        assert_eq!(measured_only.basis(), "estimate: 1 measured, 2 unmeasured left out");
+
+        // Order of the input doesn't matter
+        switches.reverse();
+        assert_eq!(switching_cost(&switches, rows.iter().rev(), Duration::from_secs(120), true), cost);
+
+        let none = switching_cost(&[], &rows, Duration::from_secs(120), true);
+        assert!(none.is_empty() && none.total.is_zero());
+    }
+
+    #[test]
+    fn live_rows_merge_without_counting_flushed_ones_twice() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let start = at("2024-05-21T09:00:00.250Z");
+        let minutes = |n: i64| start + chrono::Duration::minutes(n);
+        let mut current = row(minutes(30), "fd-editor", 120, true);
+        current.end_time = None;
+        let snapshot = LiveSnapshot {
+            pid: 1,
+            updated_at: minutes(32),
+            sessions: vec![
+                row(minutes(10), "fd-chat", 300, false),
+                row(minutes(15), "fd-editor", 900, true),
+            ],
+            switches: switch_trace(&[(600, "fd-editor", "fd-chat"), (900, "fd-chat", "fd-editor"), (1800, "fd-editor", "fd-editor")]),
+            current: Some(current),
+        };
+
+        // The daemon flushed the fd-chat row and its switch after the
+        // snapshot was taken; the database keeps whole seconds
+        db.save_focus_session(&row(minutes(0), "fd-editor", 600, true)).unwrap();
+        db.save_focus_session(&snapshot.sessions[0]).unwrap();
+        db.save_context_switch(&snapshot.switches[0]).unwrap();
+        let saved = db.get_sessions_for_date(&utc_days(), start.date_naive()).unwrap();
+        assert_eq!(saved.len(), 2);
+
+        let merged = merge_live_rows(saved, &snapshot.rows());
+        let rows: Vec<(i64, &str, bool)> = merged.iter()
+            .map(|row| ((row.start_time - at("2024-05-21T09:00:00Z")).num_minutes(), row.app_name.as_str(), row.end_time.is_none()))
+            .collect();
+        assert_eq!(rows, [(0, "fd-editor", false), (10, "fd-chat", false), (15, "fd-editor", false), (30, "fd-editor", true)]);
+        // The saved copy of a row in both wins
+        assert_eq!(merged[1].start_time.timestamp_subsec_millis(), 0);
+
+        let saved = db.get_context_switches_for_date(&utc_days(), at("2024-03-04T00:00:00Z").date_naive()).unwrap();
+        let merged = merge_live_switches(saved, &snapshot.switches);
+        let switches: Vec<(i64, &str)> = merged.iter()
+            .map(|switch| ((switch.timestamp - at("2024-03-04T09:00:00Z")).num_seconds(), switch.to_app.as_str()))
+            .collect();
+        assert_eq!(switches, [(600, "fd-chat"), (900, "fd-editor"), (1800, "fd-editor")]);
+
+        // Merging twice changes nothing
+        assert_eq!(merge_live_switches(merged.clone(), &snapshot.switches).len(), 3);
+        assert_eq!(merge_live_rows(merge_live_rows(Vec::new(), &snapshot.rows()), &snapshot.rows()).len(), 3);
+    }
+
+    #[test]
+    fn browser_time_splits_by_site_and_focus_sites_count_as_focus() {
+        let start = at("2024-05-21T09:00:00Z");
+        let rules = (vec!["fd-docs.test".to_string()], vec!["fd-video.test".to_string(), "fd-docs.test/blog".to_string()]);
+        let tab = |minute: i64, domain: Option<&str>, seconds: u64| FocusSession {
+            domain: domain.map(String::from),
+            ..row(start + chrono::Duration::minutes(minute), "fd-firefox", seconds, false)
+        };
+        let sessions = vec![
+            row(start, "fd-editor", 1800, true),
+            tab(30, Some("fd-docs.test - Iterator reference"), 1200),
+            tab(50, Some("fd-video.test - Cats"), 600),
+            // On both lists: a focus site, as the tracker has it
+            tab(60, Some("fd-docs.test/blog - Release notes"), 300),
+            tab(65, Some("fd-forum.test - Thread"), 240),
+            // Recorded without a tab
+            tab(69, None, 60),
+        ];
+        let settings = StatsSettings { site_rules: Some(rules), ..StatsSettings::default() };
+        let records = DayRecords {
+            sessions: sessions.into_iter().map(|session| settings.count_focus_sites(session)).collect(),
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&settings, &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+
+        assert_eq!(stats.total_focus_time, Duration::from_secs(1800 + 1200 + 300));
+        assert_eq!(stats.total_distraction_time, Duration::from_secs(600 + 240 + 60));
+        let browser = desktop::display_name("fd-firefox");
+        let sites = stats.browser_sites(&browser).unwrap();
+        assert_eq!((sites.focus, sites.distraction, sites.unclassified),
+            (Duration::from_secs(1500), Duration::from_secs(600), Duration::from_secs(300)));
+        // The lines add up to the browser's row
+        let browser_total = stats.most_used_apps.iter().find(|(app, _)| *app == browser).unwrap().1;
+        assert_eq!(sites.focus + sites.distraction + sites.unclassified, browser_total);
+        assert!(stats.browser_sites(&desktop::display_name("fd-editor")).is_none());
+
+        // Without site lists there is no breakdown and the rows count as recorded
+        let plain = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert!(plain.browser_sites.is_empty());
+        assert_eq!(site_class_with(&tab(0, Some("fd-docs.test"), 60), None), None);
+        assert!(!StatsSettings::default().count_focus_sites(tab(0, Some("fd-docs.test"), 60)).is_focus_app);
+    }
+
+    #[test]
+    fn projected_efficiency_weights_the_pace_by_time_left() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(project_efficiency(80.0, hour, 40.0, hour), 60.0);
+        assert_eq!(project_efficiency(80.0, hour * 3, 40.0, hour), 70.0);
+        assert_eq!(project_efficiency(80.0, hour, 40.0, Duration::ZERO), 80.0);
+        assert_eq!(project_efficiency(55.0, Duration::ZERO, 40.0, Duration::ZERO), 55.0);
+        assert_eq!(project_efficiency(0.0, Duration::ZERO, 40.0, hour), 40.0);
+    }
+
+    #[test]
+    fn session_progress_paces_the_last_fifteen_minutes() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        // Session stats look back from the real clock
+        let now = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+        let start = now - chrono::Duration::hours(1);
+        for (minute, app, seconds, is_focus) in [(0, "fd-editor", 2400, true), (40, "fd-chat", 600, false), (50, "fd-editor", 600, true)] {
+            db.save_focus_session(&row(start + chrono::Duration::minutes(minute), app, seconds, is_focus)).unwrap();
+        }
+        db.save_focus_session(&named("other", start + chrono::Duration::minutes(55), "fd-chat", 300)).unwrap();
+
+        let mut progress = Stats::calculate_session_progress(&db, &StatsSettings::default(), "work", now).unwrap();
+        assert!((progress.session.focus_efficiency - 250.0 / 3.0).abs() < 1e-9);
+        // 10 of the last 15 minutes in focus; the other session's row doesn't count
+        assert!((progress.pace.unwrap() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(progress.planned_end, None);
+
+        // An hour ahead at this pace: halfway between the two
+        let horizon = now + chrono::Duration::hours(1);
+        assert_eq!(progress.projection_end(), horizon);
+        assert_eq!(progress.describe_projection().unwrap(),
+            format!("last 15m at 67%: 75% by {}", utils::format_timestamp_local(horizon)));
+
+        let planned = now + chrono::Duration::minutes(30);
+        progress.planned_end = Some(planned);
+        assert_eq!(progress.describe_projection().unwrap(),
+            format!("last 15m at 67%: 78% by {} (planned end)", utils::format_timestamp_local(planned)));
+        // A plan already over projects the usual hour
+        progress.planned_end = Some(now - chrono::Duration::minutes(1));
+        assert_eq!(progress.projection_end(), horizon);
+
+        // Nothing in the window: no pace, no projection
+        let later = Stats::calculate_session_progress(&db, &StatsSettings::default(), "work", now + chrono::Duration::hours(2)).unwrap();
+        assert_eq!(later.pace, None);
+        assert_eq!(later.describe_projection(), None);
+    }
+
+    fn christmas_week() -> StatsSettings {
+        let holidays = [25, 26].into_iter().map(|day| NaiveDate::from_ymd_opt(2024, 12, day).unwrap()).collect();
+        StatsSettings { holidays, ..StatsSettings::default() }
+    }
+
+    fn december(days: &[u32]) -> Vec<NaiveDate> {
+        days.iter().map(|day| NaiveDate::from_ymd_opt(2024, 12, *day).unwrap()).collect()
+    }
+
+    #[test]
+    fn holidays_are_rest_days_like_weekends() {
+        let (settings, plain) = (christmas_week(), StatsSettings::default());
+        let [wednesday, friday, saturday] = december(&[25, 27, 28])[..] else { unreachable!() };
+        assert_eq!(settings.day_type(wednesday), DayType::RestDay);
+        assert_eq!(plain.day_type(wednesday), DayType::Workday);
+        assert_eq!(settings.day_type(friday), DayType::Workday);
+        assert_eq!(plain.day_type(saturday), DayType::RestDay);
+    }
+
+    #[test]
+    fn baselines_are_the_days_of_the_same_type_before() {
+        let (settings, plain) = (christmas_week(), StatsSettings::default());
+        let day = |n| NaiveDate::from_ymd_opt(2024, 12, n).unwrap();
+        // Friday after Christmas: the workdays before, skipping the holidays
+        assert_eq!(settings.baseline_days(day(27)), december(&[16, 17, 18, 19, 20, 23, 24]));
+        assert_eq!(plain.baseline_days(day(27)), december(&[18, 19, 20, 23, 24, 25, 26]));
+        // Saturday: the weekends before, holidays included
+        assert_eq!(settings.baseline_days(day(28)), december(&[8, 14, 15, 21, 22, 25, 26]));
+        assert_eq!(plain.baseline_days(day(28)), december(&[1, 7, 8, 14, 15, 21, 22]));
+        // Monday after: Friday's the day before
+        assert_eq!(settings.baseline_days(day(30)), december(&[17, 18, 19, 20, 23, 24, 27]));
+
+        // Only BASELINE_LOOKBACK_DAYS are searched
+        let all_off = (1..=BASELINE_LOOKBACK_DAYS as i64).map(|back| day(31) - chrono::Duration::days(back)).collect();
+        assert!(StatsSettings { holidays: all_off, ..plain }.baseline_days(day(31)).is_empty());
+    }
+
+    #[test]
+    fn workday_and_rest_day_means_are_kept_apart() {
+        let hours = |n: f64| Duration::from_secs_f64(n * 3600.0);
+        let day = |n| NaiveDate::from_ymd_opt(2024, 12, n).unwrap();
+        let week = [
+            (day(23), hours(4.0), hours(6.0), Some(70)),
+            (day(24), hours(3.0), hours(5.0), Some(60)),
+            (day(25), hours(1.0), hours(2.0), Some(40)),
+            (day(26), hours(2.0), hours(2.0), None),
+            (day(27), hours(5.0), hours(5.0), Some(80)),
+            // Untracked days don't count
+            (day(28), Duration::ZERO, Duration::ZERO, None),
+            (day(29), Duration::ZERO, hours(1.0), None),
+        ];
+
+        let (settings, plain) = (christmas_week(), StatsSettings::default());
+        let split = settings.baselines(week);
+        assert_eq!(split, [
+            Baseline { day_type: DayType::Workday, days: 3, focus_time: hours(4.0), efficiency: 75.0, focus_score: Some(70.0) },
+            Baseline { day_type: DayType::RestDay, days: 3, focus_time: hours(1.0), efficiency: 60.0, focus_score: Some(40.0) },
+        ]);
+        assert_eq!(split[0].describe(), format!("3 days: score 70, 75% efficiency, {} focus a day",
+            utils::format_duration(hours(4.0), DurationStyle::Long)));
+
+        // Without the holidays Christmas is a workday like any other
+        let plain_split = plain.baselines(week);
+        assert_eq!((plain_split[0].days, plain_split[0].focus_score), (5, Some(62.5)));
+        assert_eq!(plain_split[1], Baseline { day_type: DayType::RestDay, days: 1, focus_time: Duration::ZERO, efficiency: 0.0, focus_score: None });
+        assert!(plain_split[1].describe().starts_with("1 day: 0% efficiency"), "{}", plain_split[1].describe());
+
+        // A week of weekends only has no workday baseline
+        assert_eq!(plain.baselines(week[5..].to_vec()).iter().map(|baseline| baseline.day_type).collect::<Vec<_>>(), [DayType::RestDay]);
+        assert!(plain.baselines([]).is_empty());
+    }
+
+    #[test]
+    fn focus_score_trends_compare_like_with_like() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        // Three weeks: long editor mornings on workdays, chat on weekends
+        let monday = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+        let mut records = Vec::new();
+        for offset in 0..21 {
+            let day = monday + chrono::Duration::days(offset);
+            let ten = start_of_day(day) + chrono::Duration::hours(10);
+            let rows = if StatsSettings::default().day_type(day) == DayType::Workday {
+                vec![row(ten, "fd-editor", 4 * 3600, true), row(ten + chrono::Duration::hours(4), "fd-chat", 1800, false)]
+            } else {
+                vec![row(ten, "fd-chat", 2 * 3600, false), row(ten + chrono::Duration::hours(2), "fd-editor", 1200, true)]
+            };
+            for session in &rows {
+                db.save_focus_session(session).unwrap();
             }
+            records.push((day, DayRecords { sessions: rows, ..DayRecords::default() }));
         }
-        println!("{}\n", top_sep);
-        println!("~=~ Use 'focusdebt stats' to see your recent progress\n");
+        let score = |day: NaiveDate| {
+            let records = &records.iter().find(|(date, _)| *date == day).unwrap().1;
+            Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), day, records, start_of_day(day) + chrono::Duration::days(1)).focus_score.unwrap() as f64
+        };
+        let workday_score = score(monday);
+        let weekend_score = score(monday + chrono::Duration::days(5));
+        assert!(workday_score > weekend_score);
+
+        // The last Saturday against the two weekends before, a Monday against Friday and the week before it
+        let saturday = monday + chrono::Duration::days(19);
+        assert_eq!(Stats::focus_score_trend(&db, &StatsSettings::default(), &utc_days(), saturday).unwrap(), Some(weekend_score));
+        let last_monday = monday + chrono::Duration::days(14);
+        assert_eq!(Stats::focus_score_trend(&db, &StatsSettings::default(), &utc_days(), last_monday).unwrap(), Some(workday_score));
+        // Nothing of the same type tracked before the first day
+        assert_eq!(Stats::focus_score_trend(&db, &StatsSettings::default(), &utc_days(), monday).unwrap(), None);
     }
 
-    // Helper function to detect browser applications
-    fn is_browser_app(app_name: &str) -> bool {
-        let browser_apps = ["chrome", "firefox", "safari", "edge", "brave", "chromium", "opera", "vivaldi"];
-        browser_apps.iter().any(|&browser| app_name.to_lowercase().contains(browser))
+    fn settled(start: DateTime<Utc>, app: &str, seconds: u64, is_focus_app: bool, open_end: OpenEnd) -> FocusSession {
+        FocusSession { open_end, ..row(start, app, seconds, is_focus_app) }
+    }
+
+    #[test]
+    fn corrupt_open_rows_count_under_their_app_but_not_in_efficiency() {
+        let start = start_of_day(first_day());
+        let nine = start + chrono::Duration::hours(9);
+        let records = DayRecords {
+            sessions: vec![
+                row(nine, "fd-editor", 3600, true),
+                row(nine + chrono::Duration::hours(1), "fd-chat", 1200, false),
+                // A guessed twelve hours of focus
+                settled(nine + chrono::Duration::hours(2), "fd-editor", 12 * 3600, true, OpenEnd::Corrupt),
+            ],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert_eq!(stats.total_focus_time, Duration::from_secs(3600));
+        assert_eq!(stats.total_distraction_time, Duration::from_secs(1200));
+        assert_eq!(stats.focus_efficiency, 75.0);
+        assert_eq!(stats.deep_focus_sessions, 1);
+        assert_eq!(stats.longest_focus_block, Duration::from_secs(3600));
+        let editor = stats.most_used_apps.iter().find(|(app, _)| app == "fd-editor").unwrap();
+        assert_eq!(editor.1, Duration::from_secs(13 * 3600));
+
+        // A row that was only open counts like any other
+        let open = DayRecords {
+            sessions: vec![settled(nine, "fd-editor", 3600, true, OpenEnd::Open), row(nine + chrono::Duration::hours(1), "fd-chat", 1200, false)],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &open, start + chrono::Duration::days(1));
+        assert_eq!((stats.total_focus_time, stats.deep_focus_sessions), (Duration::from_secs(3600), 1));
+    }
+
+    #[test]
+    fn a_corrupt_row_breaks_the_focus_block_it_sits_in() {
+        let nine = at("2024-03-04T09:00:00Z");
+        let block = [
+            row(nine, "fd-editor", 1800, true),
+            settled(nine + chrono::Duration::minutes(30), "fd-editor", 1800, true, OpenEnd::Corrupt),
+            row(nine + chrono::Duration::hours(1), "fd-editor", 1200, true),
+        ];
+        assert_eq!(longest_focus_block(&block, Duration::from_secs(10)), Duration::from_secs(1800));
+        let trusted = [block[0].clone(), settled(nine + chrono::Duration::minutes(30), "fd-editor", 1800, true, OpenEnd::Open), block[2].clone()];
+        assert_eq!(longest_focus_block(&trusted, Duration::from_secs(10)), Duration::from_secs(80 * 60));
+    }
+
+    #[test]
+    fn sessions_with_open_rows_say_so_and_measure_around_corrupt_ones() {
+        let nine = at("2024-03-04T09:00:00Z");
+        let rows = [
+            named("fd-night", nine, "fd-editor", 3600),
+            FocusSession { is_focus_app: false, ..named("fd-night", nine + chrono::Duration::hours(1), "fd-chat", 3600) },
+            FocusSession { open_end: OpenEnd::Corrupt, ..named("fd-night", nine + chrono::Duration::hours(2), "fd-editor", 2 * 3600) },
+        ];
+        let session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        assert_eq!(session.total_duration, Duration::from_secs(4 * 3600));
+        assert_eq!(session.focus_efficiency, 50.0);
+        assert_eq!(session.open_rows, 1);
+        let format = |end: DateTime<Utc>| end.format("%H:%M").to_string();
+        assert_eq!(session.describe_end(format), "13:00 (open)");
+
+        let closed = Stats::aggregate_session_groups(&StatsSettings::default(), &rows[..2], &[], false).remove(0);
+        assert_eq!((closed.open_rows, closed.describe_end(format)), (0, "11:00".to_string()));
+        let ongoing = AggregatedSession { end_time: None, ..closed };
+        assert_eq!(ongoing.describe_end(format), "ongoing");
+    }
+
+    fn groups(toml: &str) -> AppGroups {
+        toml::from_str::<Config>(toml).unwrap().app_groups().unwrap()
+    }
+
+    #[test]
+    fn grouping_adds_up_apps_under_their_group() {
+        let groups = groups("[groups]\nrust-toolchain = [\"fd-editor\", \"fd-term\"]\n");
+        let usage = [
+            ("fd-editor".to_string(), Duration::from_secs(1800)),
+            ("fd-chat".to_string(), Duration::from_secs(2400)),
+            ("FD-TERM".to_string(), Duration::from_secs(1200)),
+            ("fd-notes".to_string(), Duration::from_secs(60)),
+        ];
+        assert_eq!(group_app_usage(usage.clone(), &groups), [
+            ("rust-toolchain".to_string(), Duration::from_secs(3000), true),
+            ("fd-chat".to_string(), Duration::from_secs(2400), false),
+            ("fd-notes".to_string(), Duration::from_secs(60), false),
+        ]);
+        // No time is lost or made up
+        let total: Duration = group_app_usage(usage.clone(), &groups).iter().map(|(_, duration, _)| *duration).sum();
+        assert_eq!(total, Duration::from_secs(5460));
+
+        // An app named like a group stays its own row
+        let usage = [("rust-toolchain".to_string(), Duration::from_secs(10)), ("fd-term".to_string(), Duration::from_secs(20))];
+        assert_eq!(group_app_usage(usage, &groups), [
+            ("rust-toolchain".to_string(), Duration::from_secs(20), true),
+            ("rust-toolchain".to_string(), Duration::from_secs(10), false),
+        ]);
+        assert!(group_app_usage(Vec::new(), &groups).is_empty());
+    }
+
+    #[test]
+    fn by_group_swaps_in_group_rows_and_trends() {
+        let start = start_of_day(first_day());
+        let records = DayRecords {
+            sessions: vec![row(start + chrono::Duration::hours(9), "fd-editor", 3600, true)],
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        // Without groups it changes nothing
+        assert_eq!(stats.by_group(&StatsSettings::default()).most_used_apps, stats.most_used_apps);
+
+        let mut stats = stats;
+        stats.group_usage = vec![
+            ("rust-toolchain".to_string(), Duration::from_secs(3000), true),
+            ("fd-chat".to_string(), Duration::from_secs(2400), false),
+        ];
+        stats.group_trends = vec![AppTrend { app: "rust-toolchain".to_string(), average: Duration::from_secs(2000) }];
+        let grouped = stats.by_group(&StatsSettings::default());
+        assert_eq!(grouped.most_used_apps, [
+            ("rust-toolchain".to_string(), Duration::from_secs(3000)),
+            ("fd-chat".to_string(), Duration::from_secs(2400)),
+        ]);
+        assert_eq!(grouped.app_trend("rust-toolchain").unwrap().average, Duration::from_secs(2000));
+        assert_eq!(grouped.total_focus_time, stats.total_focus_time);
+    }
+
+    fn on(power: Option<PowerSource>, row: FocusSession) -> FocusSession {
+        FocusSession { power, ..row }
+    }
+
+    /// A morning at the desk, an afternoon on the train, and a row from
+    /// before power was recorded
+    fn mixed_power_day() -> (DateTime<Utc>, DayRecords) {
+        let start = start_of_day(first_day());
+        let hour = |n: i64| start + chrono::Duration::hours(n);
+        let records = DayRecords {
+            sessions: vec![
+                on(Some(PowerSource::Ac), row(hour(9), "fd-editor", 2 * 3600, true)),
+                on(Some(PowerSource::Ac), row(hour(11), "fd-chat", 1800, false)),
+                on(Some(PowerSource::Battery), row(hour(14), "fd-editor", 3600, true)),
+                on(Some(PowerSource::Battery), row(hour(15), "fd-notes", 1200, true)),
+                on(None, row(hour(16), "fd-editor", 600, true)),
+            ],
+            switches: vec![
+                ContextSwitch { timestamp: hour(11), from_app: "fd-editor".to_string(), to_app: "fd-chat".to_string(), recovery_time: None },
+                ContextSwitch { timestamp: hour(15), from_app: "fd-editor".to_string(), to_app: "fd-notes".to_string(), recovery_time: None },
+                ContextSwitch { timestamp: hour(16), from_app: "fd-notes".to_string(), to_app: "fd-editor".to_string(), recovery_time: None },
+            ],
+            ..DayRecords::default()
+        };
+        (start, records)
+    }
+
+    #[test]
+    fn focus_is_broken_down_by_power_source() {
+        let (start, records) = mixed_power_day();
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert_eq!(stats.power_focus, [(PowerSource::Ac, Duration::from_secs(2 * 3600)), (PowerSource::Battery, Duration::from_secs(4800))]);
+        assert_eq!(stats.total_focus_time, Duration::from_secs(2 * 3600 + 4800 + 600));
+        assert_eq!(stats.describe_power_focus(), "on battery: 1h 20m, on AC: 2h 0m");
+        assert!(stats.day_notes().iter().all(|note| !note.contains("Only time")));
+
+        let without = DayRecords { sessions: vec![row(start, "fd-editor", 600, true)], ..DayRecords::default() };
+        assert!(Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &without, start + chrono::Duration::days(1)).power_focus.is_empty());
+    }
+
+    #[test]
+    fn the_power_filter_keeps_one_source_and_its_switches() {
+        let (start, mut records) = mixed_power_day();
+        retain_power(&mut records, PowerSource::Battery);
+        assert_eq!(records.sessions.len(), 2);
+        let kept: Vec<&str> = records.switches.iter().map(|switch| switch.to_app.as_str()).collect();
+        // Not the switches into the AC morning or the unrecorded row
+        assert_eq!(kept, ["fd-notes"]);
+
+        let mut stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert_eq!(stats.total_focus_time, Duration::from_secs(4800));
+        assert_eq!(stats.total_distraction_time, Duration::ZERO);
+        assert_eq!(stats.power_focus, [(PowerSource::Battery, Duration::from_secs(4800))]);
+        stats.power_filter = Some(PowerSource::Battery);
+        assert!(stats.day_notes().contains(&"Only time tracked on battery".to_string()));
+    }
+
+    #[test]
+    fn hourly_focus_splits_rows_at_hour_boundaries() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let day = start_of_day(first_day());
+        let at = |hours: i64, minutes: i64| day + chrono::Duration::hours(hours) + chrono::Duration::minutes(minutes);
+        assert_eq!(Stats::calculate_hourly_focus(&db, &StatsSettings::default(), &utc_days(), day.date_naive()).unwrap(), None);
+
+        db.save_focus_session(&row(at(9, 30), "fd-editor", 90 * 60, true)).unwrap();
+        db.save_focus_session(&row(at(11, 0), "fd-chat", 30 * 60, false)).unwrap();
+        db.save_focus_session(&row(at(23, 45), "fd-editor", 15 * 60, true)).unwrap();
+        let hours = Stats::calculate_hourly_focus(&db, &StatsSettings::default(), &utc_days(), day.date_naive()).unwrap().unwrap();
+        assert_eq!(hours[9], Duration::from_secs(30 * 60));
+        assert_eq!(hours[10], Duration::from_secs(60 * 60));
+        assert_eq!(hours[11], Duration::ZERO);
+        assert_eq!(hours[23], Duration::from_secs(15 * 60));
+        assert_eq!(hours.iter().sum::<Duration>(), Duration::from_secs(105 * 60));
+    }
+
+    #[test]
+    fn hourly_focus_follows_the_local_clock() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        // Half an hour off UTC: 09:15 to 10:15 IST
+        let kolkata = LocalDays::in_zone(Zone::Named(chrono_tz::Asia::Kolkata), 0);
+        let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        db.save_focus_session(&row(at("2024-03-04T03:45:00Z"), "fd-editor", 3600, true)).unwrap();
+        // 23:50 IST, cut at local midnight
+        db.save_focus_session(&row(at("2024-03-04T18:20:00Z"), "fd-editor", 1200, true)).unwrap();
+        let hours = Stats::calculate_hourly_focus(&db, &StatsSettings::default(), &kolkata, day).unwrap().unwrap();
+        assert_eq!(hours[9], Duration::from_secs(45 * 60));
+        assert_eq!(hours[10], Duration::from_secs(15 * 60));
+        assert_eq!(hours[23], Duration::from_secs(10 * 60));
+        assert_eq!(hours.iter().sum::<Duration>(), Duration::from_secs(70 * 60));
+
+        // 02:30 CEST to 02:30 CET: the repeated hour fills one bucket
+        let berlin = LocalDays::in_zone(Zone::Named(chrono_tz::Europe::Berlin), 0);
+        db.save_focus_session(&row(at("2024-10-27T00:30:00Z"), "fd-editor", 3600, true)).unwrap();
+        let hours = Stats::calculate_hourly_focus(&db, &StatsSettings::default(), &berlin, NaiveDate::from_ymd_opt(2024, 10, 27).unwrap()).unwrap().unwrap();
+        assert_eq!(hours[2], Duration::from_secs(3600));
+        assert_eq!(hours.iter().sum::<Duration>(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn daily_stats_cover_the_local_day_across_dst_changes() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let berlin = LocalDays::in_zone(Zone::Named(chrono_tz::Europe::Berlin), 0);
+        // Summer time ends on 27 October: local midnight is 22:00 UTC the
+        // day before, and the day runs 25 hours to 23:00 UTC
+        let autumn = NaiveDate::from_ymd_opt(2024, 10, 27).unwrap();
+        db.save_focus_session(&row(at("2024-10-26T21:59:00Z"), "fd-chat", 60, false)).unwrap();
+        db.save_focus_session(&row(at("2024-10-26T22:00:00Z"), "fd-editor", 600, true)).unwrap();
+        // In the hour the clock repeats
+        db.save_focus_session(&row(at("2024-10-27T01:30:00Z"), "fd-editor", 600, true)).unwrap();
+        // 23:59:59 local, the day's last second
+        db.save_focus_session(&row(at("2024-10-27T22:59:59Z"), "fd-chat", 60, false)).unwrap();
+        db.save_focus_session(&row(at("2024-10-27T23:00:00Z"), "fd-chat", 600, false)).unwrap();
+        db.save_context_switch(&ContextSwitch {
+            timestamp: at("2024-10-27T22:59:59Z"),
+            from_app: "fd-editor".to_string(),
+            to_app: "fd-chat".to_string(),
+            recovery_time: None,
+        }).unwrap();
+
+        let stats = Stats::calculate_daily_stats(&db, &StatsSettings::default(), &berlin, autumn).unwrap();
+        assert_eq!(stats.date, start_of_day(autumn));
+        assert_eq!(stats.total_focus_time, Duration::from_secs(1200));
+        assert_eq!(stats.total_distraction_time, Duration::from_secs(60));
+        assert_eq!(stats.context_switches, 1);
+        assert_eq!(stats.local_day_length, Some(Duration::from_secs(25 * 3600)));
+        assert_eq!(stats.day_notes(), ["DST transition: 25h day"]);
+        // The untracked time is counted over the same 25 hours
+        let tracked = Duration::from_secs(1200 + 1);
+        assert_eq!(stats.untracked_time, Duration::from_secs(25 * 3600) - tracked);
+
+        // The range path cuts the same day
+        let range = Stats::calculate_daily_stats_range(&db, &StatsSettings::default(), &berlin, autumn - chrono::Duration::days(1), 3, &mut Progress::hidden()).unwrap();
+        assert_eq!(range[1].total_focus_time, stats.total_focus_time);
+        assert_eq!(range[1].total_distraction_time, stats.total_distraction_time);
+        assert_eq!(range[0].total_distraction_time, Duration::from_secs(60));
+        assert_eq!(range[2].total_distraction_time, Duration::from_secs(600));
+
+        // Summer time starts on 31 March: 23 hours, 23:00 UTC to 22:00 UTC
+        let spring = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        db.save_focus_session(&row(at("2024-03-30T23:00:00Z"), "fd-editor", 600, true)).unwrap();
+        db.save_focus_session(&row(at("2024-03-31T21:59:59Z"), "fd-editor", 1, true)).unwrap();
+        db.save_focus_session(&row(at("2024-03-31T22:00:00Z"), "fd-editor", 600, true)).unwrap();
+        let stats = Stats::calculate_daily_stats(&db, &StatsSettings::default(), &berlin, spring).unwrap();
+        assert_eq!(stats.total_focus_time, Duration::from_secs(601));
+        assert_eq!(stats.local_day_length, Some(Duration::from_secs(23 * 3600)));
+        assert_eq!(stats.day_notes(), ["DST transition: 23h day"]);
+    }
+
+    #[test]
+    fn manual_rows_count_as_focus_but_never_as_switches() {
+        let nine = at("2024-03-04T09:00:00Z");
+        let manual = |row: FocusSession| FocusSession { manual: true, ..row };
+        let rows = [
+            named("fd-plan", nine, "fd-editor", 1800),
+            manual(named("fd-plan", nine + chrono::Duration::minutes(30), "whiteboard", 3600)),
+            manual(named("fd-plan", nine + chrono::Duration::minutes(90), "reading", 1800)),
+            named("fd-plan", nine + chrono::Duration::hours(2), "fd-term", 1800),
+        ];
+        let session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        assert_eq!(session.total_duration, Duration::from_secs(2 * 3600 + 1800));
+        assert_eq!(session.focus_efficiency, 100.0);
+        assert_eq!(session.context_switches, 1);
+
+        // Even next to each other, tracked and manual rows stay apart
+        let merged = merge_short_gaps(vec![rows[0].clone(), manual(named("fd-plan", nine + chrono::Duration::minutes(30), "fd-editor", 600))], Duration::from_secs(5));
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn share_reports_carry_the_hourglass_only_when_asked() {
+        let nine = at("2024-03-04T09:00:00Z");
+        let rows = [
+            named("fd-share", nine, "fd-editor", 3600),
+            FocusSession { is_focus_app: false, ..named("fd-share", nine + chrono::Duration::hours(1), "fd-chat", 1200) },
+        ];
+        let session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        let plain = Stats::session_share_report(&session, false);
+        let header = format!(
+            "\n~~+~~+*+~~+~~+*+~~+~~\n\n~=~ SESSION REPORT ~=~\n\n\
+             Session: fd-share\n\n\
+             Time: {} → {}{}\n\n\
+             Focus Time: 1h 0m\n\n\
+             Focus Efficiency: 75%\n\n",
+            utils::format_timestamp_local(nine),
+            utils::format_timestamp_local(session.end_time.unwrap()),
+            utils::utc_offset_note(session.end_time.unwrap()),
+        );
+        assert!(plain.starts_with(&header), "{}", plain);
+        assert!(plain.contains("TOP APPLICATIONS\n\n1. "), "{}", plain);
+        assert!(plain.ends_with("~~+~~+*+~~+~~+*+~~+~~\n\n"), "{}", plain);
+        assert!(plain.lines().all(|line| !line.starts_with("----------")), "{}", plain);
+
+        // The same report with the art right under the title
+        let art = Stats::session_share_report(&session, true);
+        let title = "~=~ SESSION REPORT ~=~\n\n";
+        assert_eq!(art, plain.replacen(title, &format!("{}{}\n\n", title, HOURGLASS_ART), 1));
+        assert_eq!(art.lines().filter(|line| line.starts_with("----------")).count(), 21);
+    }
+
+    /// Two hours in the editor, twenty minutes of it in its Copilot Chat
+    /// panel, and forty in ChatGPT, ten of them idle
+    fn assistant_day() -> (DateTime<Utc>, DayRecords) {
+        let start = start_of_day(first_day());
+        let hour = |n: i64| start + chrono::Duration::hours(n);
+        let chat = FocusSession {
+            domain: Some("chatgpt.com".to_string()),
+            passive: Some(Duration::from_secs(600)),
+            ..row(hour(12), "fd-firefox", 2400, false)
+        };
+        let panel = FocusSession { window_title: "Copilot Chat - main.rs".to_string(), ..row(hour(11), "fd-editor", 1200, true) };
+        let records = DayRecords {
+            sessions: vec![row(hour(9), "fd-editor", 2 * 3600 - 1200, true), panel, chat, row(hour(13), "fd-chat", 1800, false)],
+            ..DayRecords::default()
+        };
+        (start, records)
+    }
+
+    #[test]
+    fn assistant_time_rolls_up_where_configured() {
+        let (start, records) = assistant_day();
+        let sites: Vec<String> = config::DEFAULT_ASSISTANT_SITES.iter().map(|site| site.to_string()).collect();
+        let day = |counts_as: Option<AssistantCountsAs>| {
+            let settings = StatsSettings { assistant_rules: counts_as.map(|counts_as| (sites.clone(), counts_as)), ..StatsSettings::default() };
+            let stats = Stats::daily_stats_from_records(&settings, &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+            let minutes = |time: Duration| time.as_secs() / 60;
+            (minutes(stats.assistant_time), minutes(stats.total_focus_time), minutes(stats.total_distraction_time), stats.focus_efficiency.round() as u64)
+        };
+        // (assistant, focus, distraction, efficiency)
+        assert_eq!(day(None), (0, 120, 60, 67));
+        assert_eq!(day(Some(AssistantCountsAs::Neutral)), (50, 100, 30, 77));
+        assert_eq!(day(Some(AssistantCountsAs::Focus)), (50, 150, 30, 83));
+        assert_eq!(day(Some(AssistantCountsAs::Distraction)), (50, 100, 80, 56));
+    }
+
+    #[test]
+    fn assistant_time_reaches_reports_csv_and_json() {
+        let (start, records) = assistant_day();
+        let rules = (config::DEFAULT_ASSISTANT_SITES.iter().map(|site| site.to_string()).collect(), AssistantCountsAs::Focus);
+        let settings = StatsSettings { assistant_rules: Some(rules), ..StatsSettings::default() };
+        let stats = Stats::daily_stats_from_records(&settings, &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert_eq!(stats.describe_assistant_time(), "50m 0s, counted as focus");
+        assert_eq!(describe_assistant_time(Duration::from_secs(40 * 60), AssistantCountsAs::Neutral), "40m 0s, neutral");
+
+        let csv = Stats::daily_summary_csv(std::slice::from_ref(&stats));
+        assert!(csv.starts_with(DAILY_CSV_HEADER), "{}", csv);
+        assert!(csv.lines().nth(1).unwrap().ends_with(",0.83"), "{}", csv);
+
+        let json: serde_json::Value = serde_json::from_str(&Stats::daily_stats_json(&stats).unwrap()).unwrap();
+        assert_eq!(json["assistant_seconds"], 3000);
+        assert_eq!(json["assistant_counts_as"], "focus");
+    }
+
+    /// A two-hour clock jump from 10:00 to 12:00
+    fn jump(day: DateTime<Utc>) -> SuspendGap {
+        SuspendGap { start: day + chrono::Duration::hours(10), end: day + chrono::Duration::hours(12) }
+    }
+
+    #[test]
+    fn suspends_are_left_out_of_every_daily_figure() {
+        let start = start_of_day(first_day());
+        let hour = |n: i64| start + chrono::Duration::hours(n);
+        let end = start + chrono::Duration::days(1);
+        let chat = row(hour(12), "fd-chat", 1800, false);
+        // The daemon ended the editor row at the jump
+        let ended = DayRecords { sessions: vec![row(hour(9), "fd-editor", 3600, true), chat.clone()], suspends: vec![jump(start)], ..DayRecords::default() };
+        // One that didn't notice wrote a row across it
+        let across = DayRecords { sessions: vec![row(hour(9), "fd-editor", 3 * 3600, true), chat.clone()], suspends: vec![jump(start)], ..DayRecords::default() };
+
+        for records in [&ended, &across] {
+            let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), records, end);
+            assert_eq!(stats.suspended_time, Duration::from_secs(2 * 3600));
+            assert_eq!(stats.total_focus_time, Duration::from_secs(3600));
+            assert_eq!(stats.total_distraction_time, Duration::from_secs(1800));
+            assert_eq!(stats.focus_efficiency.round(), 67.0);
+        }
+
+        // The two hours aren't untracked either
+        let unaware = DayRecords { suspends: Vec::new(), ..ended.clone() };
+        let with = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &ended, end);
+        let without = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &unaware, end);
+        assert_eq!(without.suspended_time, Duration::ZERO);
+        assert_eq!(without.untracked_time - with.untracked_time, Duration::from_secs(2 * 3600));
+        let json: serde_json::Value = serde_json::from_str(&Stats::daily_stats_json(&with).unwrap()).unwrap();
+        assert_eq!(json["suspended_seconds"], 7200);
+    }
+
+    #[test]
+    fn sessions_list_their_suspends_and_the_time_actually_tracked() {
+        let day = at("2024-03-04T00:00:00Z");
+        let rows = [
+            named("fd-trip", day + chrono::Duration::hours(9), "fd-editor", 3600),
+            FocusSession { is_focus_app: false, ..named("fd-trip", day + chrono::Duration::hours(12), "fd-chat", 1800) },
+        ];
+        let elsewhere = SuspendGap { start: day + chrono::Duration::hours(20), end: day + chrono::Duration::hours(21) };
+        let session = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[jump(day), elsewhere], false).remove(0);
+        assert_eq!(session.suspends, [jump(day)]);
+        assert_eq!(session.total_duration, Duration::from_secs(3 * 3600 + 1800));
+        assert_eq!(session.tracked_time(), Duration::from_secs(5400));
+        assert_eq!(session.focus_efficiency.round(), 67.0);
+
+        let report = Stats::format_session_report(&session);
+        assert!(report.contains("1h 30m of the 3h 30m span"), "{}", report);
+        assert!(report.contains(&jump(day).describe()), "{}", report);
+        assert!(jump(day).describe().ends_with(", excluded 2h 0m"));
+
+        let unaware = Stats::aggregate_session_groups(&StatsSettings::default(), &rows, &[], false).remove(0);
+        assert!(unaware.suspends.is_empty());
+        assert_eq!(unaware.tracked_time(), unaware.total_duration);
+        assert!(!Stats::format_session_report(&unaware).contains("Tracked:"));
+    }
+
+    #[test]
+    fn suspend_events_round_trip_through_the_database() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let day = at("2024-03-04T00:00:00Z");
+        // Began the evening before and ran into the day
+        let overnight = SuspendGap { start: day - chrono::Duration::hours(2), end: day + chrono::Duration::hours(7) };
+        for gap in [overnight, jump(day), SuspendGap { start: day + chrono::Duration::days(2), end: day + chrono::Duration::days(3) }] {
+            db.save_event(&gap.to_event()).unwrap();
+        }
+        assert_eq!(suspend_gaps(&db, day, day + chrono::Duration::days(1)).unwrap(), [overnight, jump(day)]);
+        assert_eq!(suspend_gaps(&db, day + chrono::Duration::hours(8), day + chrono::Duration::hours(10)).unwrap(), []);
+
+        let event = jump(day).to_event();
+        assert_eq!(event.payload["seconds"], 7200);
+        assert_eq!(SuspendGap::from_event(&event), Some(jump(day)));
+        let backwards = TrackerEvent { payload: serde_json::json!({ "until": "2024-03-04T09:00:00Z" }), ..event.clone() };
+        assert_eq!(SuspendGap::from_event(&backwards), None);
+        assert_eq!(SuspendGap::from_event(&TrackerEvent { payload: serde_json::json!({}), ..event }), None);
+    }
+
+    /// Rows where three apps tie on time, in the given order
+    fn tied_rows(reversed: bool) -> Vec<FocusSession> {
+        let nine = start_of_day(first_day()) + chrono::Duration::hours(9);
+        let rows = [
+            ("fd-term", "2", 1800, true),
+            ("fd-notes", "1", 1800, true),
+            ("fd-editor", "2", 1800, true),
+            ("fd-chat", "1", 3600, false),
+        ];
+        let mut at = nine;
+        let mut rows: Vec<FocusSession> = rows.into_iter()
+            .map(|(app, workspace, seconds, is_focus_app)| {
+                let row = FocusSession { workspace: Some(workspace.to_string()), is_focus_app, ..named("fd-ties", at, app, seconds) };
+                at += chrono::Duration::seconds(seconds as i64);
+                row
+            })
+            .collect();
+        if reversed {
+            rows.reverse();
+        }
+        rows
+    }
+
+    #[test]
+    fn daily_reports_order_ties_by_name_whatever_the_input_order() {
+        let start = start_of_day(first_day());
+        let day = |reversed: bool| {
+            let records = DayRecords { sessions: tied_rows(reversed), ..DayRecords::default() };
+            Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1))
+        };
+        let (forward, backward) = (day(false), day(true));
+        let names: Vec<&str> = forward.most_used_apps.iter().map(|(app, _)| app.as_str()).collect();
+        assert_eq!(names, ["fd-chat", "fd-editor", "fd-notes", "fd-term"]);
+        assert_eq!(forward.most_used_apps, backward.most_used_apps);
+
+        let json = Stats::daily_stats_json(&forward).unwrap();
+        assert_eq!(json, Stats::daily_stats_json(&backward).unwrap());
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let top: Vec<(&str, u64)> = json["top_apps"].as_array().unwrap().iter()
+            .map(|app| (app["name"].as_str().unwrap(), app["duration_seconds"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(top, [("fd-chat", 3600), ("fd-editor", 1800), ("fd-notes", 1800), ("fd-term", 1800)]);
+
+        let csv = Stats::daily_summary_csv(&[forward.clone(), backward.clone()]);
+        let line = "2024-03-04,1.50,1.00,21.50,0,3,60.00,80,0.00\n";
+        assert_eq!(csv, format!("{}{}{}", DAILY_CSV_HEADER, line, line));
+
+        // fd-term and fd-notes tie between workspaces too
+        let tied: Vec<FocusSession> = tied_rows(false).into_iter().filter(|row| row.app_name == "fd-term" || row.app_name == "fd-notes").collect();
+        for rows in [tied.clone(), tied.into_iter().rev().collect()] {
+            let workspaces: Vec<(Option<String>, u64)> = workspace_usage(&rows).into_iter()
+                .map(|usage| (usage.workspace, usage.total.as_secs()))
+                .collect();
+            assert_eq!(workspaces, [(Some("1".to_string()), 1800), (Some("2".to_string()), 1800)]);
+        }
+    }
+
+    #[test]
+    fn session_exports_are_the_same_in_every_format_whatever_the_insert_order() {
+        let export = |reversed: bool| {
+            let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+            for row in tied_rows(reversed) {
+                db.save_focus_session(&row).unwrap();
+            }
+            Stats::load_session_export(&db, &StatsSettings::default(), "fd-ties").unwrap()
+        };
+        let (forward, backward) = (export(false), export(true));
+        for format in SESSION_EXPORT_FORMATS {
+            let rendered = Stats::render_session_export(&forward, format).unwrap();
+            assert_eq!(rendered, Stats::render_session_export(&backward, format).unwrap(), "{}", format);
+        }
+
+        let markdown = Stats::render_session_export(&forward, "markdown").unwrap();
+        let applications = "## Applications\n\n\
+            | App | Time | Type |\n\
+            | --- | --- | --- |\n\
+            | fd-chat | 1h 0m | Other |\n\
+            | fd-editor | 30m 0s | Focus |\n\
+            | fd-notes | 30m 0s | Focus |\n\
+            | fd-term | 30m 0s | Focus |\n";
+        assert!(markdown.contains(applications), "{}", markdown);
+        let workspaces = "| Workspace | Time | Focus |\n| --- | --- | --- |\n| 1 | 1h 30m | 33% |\n| 2 | 1h 0m | 100% |\n";
+        assert!(markdown.contains(workspaces), "{}", markdown);
+    }
+
+    #[test]
+    fn notification_counts_name_the_top_sources() {
+        let counts = NotificationCounts::new(vec![
+            ("email".to_string(), 9),
+            ("other".to_string(), 2),
+            ("slack".to_string(), 24),
+            ("fd-chat".to_string(), 0),
+            ("discord".to_string(), 2),
+            ("zoom".to_string(), 1),
+        ]);
+        assert_eq!(counts.total(), 38);
+        // Past three named sources the rest go to other, as does "other" itself
+        assert_eq!(counts.describe(), "38 (slack 24, email 9, discord 2, other 3)");
+        assert_eq!(NotificationCounts::new(vec![("other".to_string(), 5), ("slack".to_string(), 1)]).describe(), "6 (slack 1, other 5)");
+        assert!(NotificationCounts::new(vec![("slack".to_string(), 0)]).is_empty());
+    }
+
+    #[test]
+    fn session_lists_and_reports_show_the_intent_and_rating() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let now = Utc::now();
+        db.save_focus_session(&named("alpha", now - chrono::Duration::hours(3), "fd-editor", 600)).unwrap();
+        db.save_focus_session(&named("beta", now - chrono::Duration::hours(2), "fd-editor", 600)).unwrap();
+        db.set_session_intent("alpha", now - chrono::Duration::hours(3), Some("write the RFC draft"), Some(Duration::from_secs(7200))).unwrap();
+        db.set_session_outcome("ALPHA", 4, Some(true)).unwrap();
+        db.set_session_intent("beta", now - chrono::Duration::hours(2), Some("review"), None).unwrap();
+
+        let lines = Stats::list_sessions(&db, &StatsSettings::default(), None, None).unwrap();
+        let line = |name: &str| lines.iter().find(|line| line.contains(&format!("\"{}\"", name))).unwrap().clone();
+        assert!(line("alpha").ends_with("  Rated: 4/5, intent met"), "{}", line("alpha"));
+        assert!(!line("beta").contains("Rated:"), "{}", line("beta"));
+
+        let report = Stats::show_session_details(&db, &StatsSettings::default(), "alpha").unwrap();
+        assert!(report.contains("Intent:     write the RFC draft (expected 2h 0m)\n\n"), "{}", report);
+        assert!(report.contains("Outcome:    4/5, intent met\n\n"), "{}", report);
+        let report = Stats::show_session_details(&db, &StatsSettings::default(), "beta").unwrap();
+        assert!(report.contains("Intent:     review\n\n") && !report.contains("Outcome:"), "{}", report);
+    }
+
+    #[test]
+    fn task_stats_add_up_every_linked_session_across_days() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let rfc_uuid = "6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42";
+        let link = |session: &str, id: &str, uuid: Option<&str>, description: Option<&str>| {
+            db.set_session_task(session, &SessionTask {
+                started_at: at("2024-03-04T12:00:00Z"),
+                source: crate::storage::TaskSource::Taskwarrior,
+                id: id.to_string(),
+                uuid: uuid.map(str::to_string),
+                description: description.map(str::to_string),
+            }).unwrap();
+        };
+        // Two days on task 123, long before the last 30
+        db.save_focus_session(&named("rfc one", at("2024-03-04T12:00:00Z"), "fd-editor", 40 * 60)).unwrap();
+        db.save_focus_session(&FocusSession { session_name: "rfc one".to_string(), ..row(at("2024-03-04T12:40:00Z"), "fd-chat", 20 * 60, false) }).unwrap();
+        db.save_focus_session(&named("rfc two", at("2024-03-06T12:00:00Z"), "fd-editor", 30 * 60)).unwrap();
+        link("rfc one", "123", Some(rfc_uuid), Some("write the RFC draft"));
+        link("rfc two", "123", Some(rfc_uuid), Some("write the RFC draft"));
+        // An earlier task that had id 123, and a linked session with no rows left
+        db.save_focus_session(&named("old 123", at("2024-02-01T12:00:00Z"), "fd-editor", 10 * 60)).unwrap();
+        link("old 123", "123", Some("00000000-0000-0000-0000-000000000000"), Some("file taxes"));
+        link("pruned", "123", Some(rfc_uuid), Some("write the RFC draft"));
+
+        let report = Stats::calculate_task_stats(&db, &StatsSettings::default(), "123", Some(rfc_uuid)).unwrap();
+        let names: Vec<_> = report.sessions.iter().map(|session| session.session_name.as_str()).collect();
+        assert_eq!(names, ["rfc two", "rfc one"]);
+        assert_eq!((report.days, report.tracked, report.focus), (2, Duration::from_secs(90 * 60), Duration::from_secs(70 * 60)));
+        assert_eq!(report.context_switches, report.sessions.iter().map(|session| session.context_switches).sum::<usize>());
+        let text = Stats::render_task_stats(&report);
+        assert!(text.starts_with(concat!(
+            "~=~ TASK: task 123: write the RFC draft ~=~\n\n",
+            "Sessions:   2 on 2 days\n",
+            "Tracked:    1h 30m\n",
+            "Focus:      1h 10m (78%)\n",
+        )), "{}", text);
+        assert!(text.contains("1. \"rfc two\"") && text.contains("  Task: task 123: write the RFC draft"), "{}", text);
+
+        // Without taskwarrior to ask, the id takes in the earlier task too
+        assert_eq!(Stats::calculate_task_stats(&db, &StatsSettings::default(), "123", None).unwrap().sessions.len(), 3);
+        assert_eq!(Stats::calculate_task_stats(&db, &StatsSettings::default(), "taxes", None).unwrap().sessions.len(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&Stats::task_stats_json(&report).unwrap()).unwrap();
+        assert_eq!(json["task"]["uuid"], rfc_uuid);
+        assert_eq!((json["days"].as_u64(), json["tracked_seconds"].as_u64(), json["focus_seconds"].as_u64()), (Some(2), Some(5400), Some(4200)));
+        assert_eq!(json["sessions"][1]["session_name"], "rfc one");
+
+        let none = Stats::calculate_task_stats(&db, &StatsSettings::default(), " dentist ", None).unwrap();
+        assert_eq!(Stats::render_task_stats(&none), "No sessions are linked to task 'dentist'\n");
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&Stats::task_stats_json(&none).unwrap()).unwrap()["task"], serde_json::Value::Null);
+    }
+
+    /// Rows back to back from 09:00: (app, minutes, focus)
+    fn day_of_rows(rows: &[(&str, f64, bool)]) -> Vec<FocusSession> {
+        let mut start = at("2024-03-04T09:00:00Z");
+        rows.iter()
+            .map(|&(app, minutes, focus)| {
+                let seconds = (minutes * 60.0) as u64;
+                let row = row(start, app, seconds, focus);
+                start += chrono::Duration::seconds(seconds as i64);
+                row
+            })
+            .collect()
+    }
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn focus_bookends_measure_to_the_first_and_from_the_last_long_enough_block() {
+        let rows = day_of_rows(&[
+            ("fd-chat", 20.0, false),
+            // Too short to count
+            ("fd-editor", 10.0, true),
+            ("fd-chat", 12.0, false),
+            // 18 minutes with a blip in the middle is one block
+            ("fd-editor", 9.0, true),
+            ("fd-chat", 5.0 / 60.0, false),
+            ("fd-editor", 9.0, true),
+            ("fd-browser", 30.0, false),
+            ("fd-editor", 20.0, true),
+            ("fd-chat", 70.0, false),
+        ]);
+        let blocks = focus_blocks(&rows, Duration::from_secs(10));
+        assert_eq!(blocks.iter().map(|block| block.focus.as_secs() / 60).collect::<Vec<_>>(), [10, 18, 20]);
+        assert_eq!(blocks[1].start, at("2024-03-04T09:42:00Z"));
+        assert_eq!(focus_bookends(&rows, Duration::from_secs(10)), Some((minutes(42), minutes(70))));
+        // Row order doesn't matter
+        assert_eq!(focus_bookends(rows.iter().rev(), Duration::from_secs(10)), Some((minutes(42), minutes(70))));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn focus_bookends_of_days_without_focus_or_starting_and_ending_in_it() {
+        assert_eq!(focus_bookends(&[], Duration::from_secs(10)), None);
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-chat", 60.0, false), ("fd-browser", 60.0, false)]), Duration::from_secs(10)), None);
+        // Focus that never lasts 15 minutes
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-editor", 14.0, true), ("fd-chat", 30.0, false), ("fd-editor", 14.0, true)]), Duration::from_secs(10)), None);
+        // Two 8-minute stretches only join across a blip
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-editor", 8.0, true), ("fd-chat", 2.0, false), ("fd-editor", 8.0, true)]), Duration::from_secs(10)), None);
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-editor", 8.0, true), ("fd-chat", 5.0 / 60.0, false), ("fd-editor", 8.0, true)]), Duration::from_secs(10)),
+            Some((Duration::ZERO, Duration::ZERO)));
+
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-editor", 30.0, true), ("fd-chat", 30.0, false)]), Duration::from_secs(10)), Some((Duration::ZERO, minutes(30))));
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-chat", 42.0, false), ("fd-editor", 48.0, true)]), Duration::from_secs(10)), Some((minutes(42), Duration::ZERO)));
+        // One block is both the first and the last
+        assert_eq!(focus_bookends(&day_of_rows(&[("fd-chat", 5.0, false), ("fd-editor", 20.0, true), ("fd-chat", 15.0, false)]), Duration::from_secs(10)),
+            Some((minutes(5), minutes(15))));
+    }
+
+    #[test]
+    fn daily_stats_show_the_bookends_and_hold_back_wind_down_until_the_day_ends() {
+        let start = start_of_day(first_day());
+        let records = DayRecords {
+            sessions: day_of_rows(&[("fd-chat", 42.0, false), ("fd-editor", 60.0, true), ("fd-chat", 70.0, false)])
+                .into_iter()
+                .map(|row| {
+                    // The same times of day on first_day, wherever its midnight falls
+                    let shift = start - at("2024-03-04T00:00:00Z");
+                    FocusSession { start_time: row.start_time + shift, end_time: row.end_time.map(|end| end + shift), ..row }
+                })
+                .collect(),
+            ..DayRecords::default()
+        };
+        let stats = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::days(1));
+        assert_eq!((stats.time_to_first_focus, stats.focus_wind_down), (Some(minutes(42)), Some(minutes(70))));
+        assert_eq!(stats.describe_focus_bookends().as_deref(), Some("42m 0s after start; last focus ended 1h 10m before sign-off"));
+        let json: serde_json::Value = serde_json::from_str(&Stats::daily_stats_json(&stats).unwrap()).unwrap();
+        assert_eq!((json["time_to_first_focus_seconds"].as_u64(), json["focus_wind_down_seconds"].as_u64()), (Some(42 * 60), Some(70 * 60)));
+
+        // Today: the last focus may not be the last
+        let today = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &records, start + chrono::Duration::hours(14));
+        assert_eq!((today.time_to_first_focus, today.focus_wind_down), (Some(minutes(42)), None));
+        assert_eq!(today.describe_focus_bookends().as_deref(), Some("42m 0s after start"));
+        let json: serde_json::Value = serde_json::from_str(&Stats::daily_stats_json(&today).unwrap()).unwrap();
+        assert!(json.get("focus_wind_down_seconds").is_none(), "{}", json);
+
+        let mut stats = stats;
+        stats.time_to_first_focus = Some(Duration::from_secs(30));
+        stats.focus_wind_down = Some(Duration::from_secs(59));
+        assert_eq!(stats.describe_focus_bookends().as_deref(), Some("right at the start; focus lasted until sign-off"));
+        stats.time_to_first_focus = None;
+        assert_eq!(stats.describe_focus_bookends().as_deref(), Some("no focus block of 15m 0s or more"));
+        let json: serde_json::Value = serde_json::from_str(&Stats::daily_stats_json(&stats).unwrap()).unwrap();
+        assert!(json.get("time_to_first_focus_seconds").is_none(), "{}", json);
+
+        let empty = Stats::daily_stats_from_records(&StatsSettings::default(), &utc_days(), start.date_naive(), &DayRecords::default(), start + chrono::Duration::days(1));
+        assert_eq!(empty.describe_focus_bookends(), None);
+    }
+
+    #[test]
+    fn raw_session_view_interleaves_rows_switches_and_events_in_time_order() {
+        let db = Database::open(std::path::Path::new(":memory:")).unwrap();
+        let event = |time: &str, kind: EventKind, payload: serde_json::Value| TrackerEvent { timestamp: at(time), kind, payload };
+        // Saved out of order, the way the database thread may commit them
+        db.save_event(&event("2024-03-04T09:20:00Z", EventKind::DaemonStop, serde_json::json!({}))).unwrap();
+        db.save_focus_session(&named("fd-order", at("2024-03-04T09:10:00Z"), "fd-browser", 600)).unwrap();
+        db.save_event(&event("2024-03-04T09:10:00Z", EventKind::Resume, serde_json::json!({ "reason": "manual" }))).unwrap();
+        db.save_event(&event("2024-03-04T09:10:00Z", EventKind::WindowChange, serde_json::json!({ "app": "fd-browser" }))).unwrap();
+        db.save_context_switch(&ContextSwitch {
+            timestamp: at("2024-03-04T09:10:00Z"),
+            from_app: "fd-editor".to_string(),
+            to_app: "fd-browser".to_string(),
+            recovery_time: None,
+        }).unwrap();
+        db.save_event(&event("2024-03-04T09:05:00Z", EventKind::Pause, serde_json::json!({ "reason": "manual" }))).unwrap();
+        db.save_focus_session(&named("fd-order", at("2024-03-04T09:00:00Z"), "fd-editor", 600)).unwrap();
+        db.save_event(&event("2024-03-04T09:00:00Z", EventKind::DaemonStart, serde_json::json!({}))).unwrap();
+        // Outside the session
+        db.save_event(&event("2024-03-04T08:59:59Z", EventKind::Alert, serde_json::json!({ "alert": "budget" }))).unwrap();
+
+        let export = Stats::load_session_export(&db, &StatsSettings::default(), "fd-order").unwrap();
+        let kinds: Vec<&str> = export.events.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, ["daemon_start", "pause", "resume", "daemon_stop"]);
+
+        // Rows first at a shared time, then switches, then event markers;
+        // the pause falls inside the first row
+        let raw = Stats::render_raw_session(&export, 1).unwrap();
+        let order: Vec<String> = raw.lines().skip(3)
+            .map(|line| match line.split_once("▸ ") {
+                Some((_, marker)) => marker.split_whitespace().next().unwrap().to_string(),
+                None if line.contains(" → ") => "switch".to_string(),
+                None => line.split_whitespace().find(|word| word.starts_with("fd-")).unwrap().to_string(),
+            })
+            .collect();
+        assert_eq!(order, ["fd-editor", "daemon_start", "pause", "fd-browser", "switch", "resume", "daemon_stop"]);
+        assert!(raw.starts_with("~=~ Raw rows of \"fd-order\": 2 rows, 1 context switches, 4 events\n"), "{}", raw);
+    }
+}
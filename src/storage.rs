@@ -1,32 +1,138 @@
-use rusqlite::{Connection, Result as SqliteResult, OptionalExtension};
-use chrono::{DateTime, Utc};
+use rusqlite::{Connection, ErrorCode, OpenFlags, Result as SqliteResult, OptionalExtension};
+use rusqlite::types::{Value, ValueRef};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
 use std::time::Duration;
-use std::path::PathBuf;
-use dirs;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::tracking::{FocusSession, ContextSwitch};
+use crate::config::{Config, RuleMatch, SessionClass, SessionSnapshot};
+use crate::tracking::{FocusSession, ContextSwitch, EventKind, OpenEnd, TrackerEvent};
+use crate::power::PowerSource;
+use crate::progress::Progress;
+use crate::timing::{self, Phase};
+use crate::utils::{self, LocalDays};
 
 pub struct Database {
     conn: Connection,
 }
 
+/// How long a read-only connection waits on a lock before the attempt fails
+const READ_BUSY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Pauses between attempts to open a locked database (mid-VACUUM, backup)
+const READ_RETRY_BACKOFF_MS: [u64; 4] = [100, 200, 400, 800];
+
+/// Environment variable holding the passphrase of an encrypted database
+pub const DB_KEY_ENV: &str = "FOCUSDEBT_DB_KEY";
+
+/// First bytes of every plaintext SQLite file; SQLCipher files look random
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// The passphrase, once found, so a run asks for it at most once
+static DATABASE_KEY: OnceLock<String> = OnceLock::new();
+
+/// Schema this build writes. Bump it when a change would mislead older
+/// binaries (they refuse a database with a newer schema than theirs).
+pub const SCHEMA_VERSION: i64 = 2;
+
+/// First schema keeping times as epoch seconds; older ones are upgraded on open
+const EPOCH_TIMES_SCHEMA: i64 = 2;
+
+/// `source` of rows entered by hand (`add-entry`, `import manual`)
+pub const MANUAL_SOURCE: &str = "manual";
+
+/// Longest a row can run, so overlap checks only look this far back for
+/// rows that started before the range
+const MAX_ROW_SECONDS: i64 = 24 * 60 * 60;
+
 impl Database {
+    /// Opens the database of the active profile
     pub fn new() -> SqliteResult<Self> {
-        let db_path = Self::get_db_path()?;
-        let conn = Connection::open(db_path)?;
+        Self::open(&Self::get_db_path()?)
+    }
+
+    /// Opens the active profile's database for read-only commands. A database
+    /// that doesn't exist yet is created (schema included) first.
+    pub fn new_read_only() -> SqliteResult<Self> {
+        let path = Self::get_db_path()?;
+        if !path.exists() {
+            return Self::new();
+        }
+        let db = Self::open_read_only(&path)?;
+        if INCLUDE_ARCHIVES.load(Ordering::Relaxed) {
+            db.attach_archives()?;
+        }
+        Ok(db)
+    }
+
+    /// Opens a database for reading only; the schema is not touched, except
+    /// that one still keeping times as text is upgraded first (see
+    /// `migrate_epoch_times`), as reads compare epoch seconds. Transient
+    /// lock errors are retried a few times with backoff.
+    pub fn open_read_only(path: &Path) -> SqliteResult<Self> {
+        let db = Self::open_read_only_retrying(path)?;
+        if !Self::has_text_times(&db.conn)? {
+            return Ok(db);
+        }
+        if utils::is_data_dir_read_only() {
+            return Err(schema_error(format!(
+                "{} stores times as text from an older focusdebt and must be upgraded once; \
+                 run any focusdebt command with write access to it",
+                path.display(),
+            )));
+        }
+        drop(db);
+        eprintln!("~=~ Upgrading {} to epoch timestamps", path.display());
+        Self::open(path)?;
+        Self::open_read_only_retrying(path)
+    }
+
+    fn open_read_only_retrying(path: &Path) -> SqliteResult<Self> {
+        let mut attempt = 0;
+        loop {
+            match Self::try_open_read_only(path) {
+                Err(e) if is_transient_lock_error(&e) && attempt < READ_RETRY_BACKOFF_MS.len() => {
+                    std::thread::sleep(Duration::from_millis(READ_RETRY_BACKOFF_MS[attempt]));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn try_open_read_only(path: &Path) -> SqliteResult<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Self::unlock(&conn, path)?;
+        conn.busy_timeout(READ_BUSY_TIMEOUT)?;
+        // Opening is lazy; read the schema so a locked database fails here
+        // rather than halfway through a report
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+        Self::check_schema_version(&conn)?;
+        Ok(Database { conn })
+    }
+
+    pub fn open(path: &Path) -> SqliteResult<Self> {
+        let conn = Connection::open(path)?;
+        Self::unlock(&conn, path)?;
+        // Before any migration, so an older binary leaves a newer schema alone
+        Self::check_schema_version(&conn)?;
         
         // Create tables if they don't exist
         conn.execute(
             "CREATE TABLE IF NOT EXISTS focus_sessions (
                 id INTEGER PRIMARY KEY,
-                start_time TEXT NOT NULL,
-                end_time TEXT,
+                start_time INTEGER NOT NULL,
+                end_time INTEGER,
                 app_name TEXT NOT NULL,
                 window_title TEXT NOT NULL,
                 domain TEXT,
                 duration_seconds INTEGER NOT NULL,
                 is_focus_app BOOLEAN NOT NULL,
-                session_name TEXT NOT NULL DEFAULT ''
+                session_name TEXT NOT NULL DEFAULT '',
+                activity REAL,
+                passive_seconds INTEGER
             )",
             [],
         )?;
@@ -43,10 +149,109 @@ impl Database {
             [],
         );
 
+        // Add activity column if it doesn't exist (for existing databases)
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN activity REAL",
+            [],
+        );
+
+        // Add passive_seconds column if it doesn't exist (for existing databases)
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN passive_seconds INTEGER",
+            [],
+        );
+
+        // Add workspace column if it doesn't exist (for existing databases)
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN workspace TEXT",
+            [],
+        );
+
+        // Add power column if it doesn't exist (for existing databases)
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN power TEXT",
+            [],
+        );
+
+        // Imported rows carry where they came from and a key that makes
+        // re-imports skip them; tracked rows leave both NULL
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN source TEXT",
+            [],
+        );
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN source_key TEXT",
+            [],
+        );
+
+        // The rule that classified the row, e.g. "focus_site:github.com"
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN focus_rule TEXT",
+            [],
+        );
+
+        // Label of the co_focus rule the row was part of
+        let _ = conn.execute(
+            "ALTER TABLE focus_sessions ADD COLUMN co_focus TEXT",
+            [],
+        );
+
+        // Rows held back because their time looked wrong (see
+        // max_clock_skew_days) or merged away by the epoch upgrade, kept
+        // whole as JSON until restored or dropped
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS quarantined_sessions (
+                id INTEGER PRIMARY KEY,
+                quarantined_at TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                start_time TEXT NOT NULL,
+                row TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // With every column in place, move times to epoch seconds. Once
+        // the schema version says so, opens (read-only commands included)
+        // skip it and take no write lock
+        if Self::stored_schema_version(&conn)?.is_none_or(|(version, _)| version < EPOCH_TIMES_SCHEMA) {
+            Self::migrate_epoch_times(&conn)?;
+        }
+
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_focus_sessions_source_key
+             ON focus_sessions (source, source_key) WHERE source_key IS NOT NULL",
+            [],
+        )?;
+
+        // Crashes and an old double-save left exact copies of some rows.
+        // They are removed once, before the index that stops new ones. The
+        // note goes to stderr so it never mixes into exported JSON or CSV.
+        let identity_index_exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_focus_sessions_identity')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !identity_index_exists {
+            let removed = Self::dedupe_rows(&conn, false)?;
+            if !removed.is_empty() {
+                let rows: usize = removed.iter().map(|day| day.rows).sum();
+                let duration: Duration = removed.iter().map(|day| day.duration).sum();
+                eprintln!("~=~ Removed {} duplicated session rows ({}) while upgrading the database",
+                    rows, utils::format_duration(duration, utils::DurationStyle::Long));
+            }
+            // The index before it only looked at start, app and duration,
+            // and refused distinct rows that shared them
+            conn.execute_batch(&format!(
+                "CREATE UNIQUE INDEX idx_focus_sessions_identity ON focus_sessions ({});
+                 DROP INDEX IF EXISTS {};",
+                ROW_IDENTITY, LEGACY_DEDUPE_INDEX,
+            ))?;
+        }
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS context_switches (
                 id INTEGER PRIMARY KEY,
-                timestamp TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
                 from_app TEXT NOT NULL,
                 to_app TEXT NOT NULL,
                 recovery_time_seconds INTEGER
@@ -72,44 +277,742 @@ impl Database {
             [],
         )?;
 
+        // One row per named session; the unique name is what makes claiming
+        // a session name atomic across concurrent `start`s
+        let sessions_table_exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sessions')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                started_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        if !sessions_table_exists {
+            // Names used before this table existed are still taken
+            conn.execute(
+                "INSERT OR IGNORE INTO sessions (name, started_at)
+                 SELECT session_name, strftime('%Y-%m-%dT%H:%M:%S+00:00', MIN(start_time), 'unixepoch') FROM focus_sessions
+                 WHERE session_name != ''
+                 GROUP BY session_name",
+                [],
+            )?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                payload TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events (timestamp)",
+            [],
+        )?;
+
+        // The heatmap and per-day reports read switches by time range
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_context_switches_timestamp ON context_switches (timestamp)",
+            [],
+        )?;
+
+        // Year-long aggregates look up the row around each context switch
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_focus_sessions_start_time ON focus_sessions (start_time)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS planned_sessions (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                start_time TEXT NOT NULL,
+                duration_seconds INTEGER NOT NULL,
+                started_at TEXT,
+                completed_at TEXT
+            )",
+            [],
+        )?;
+
+        // Config snapshots are shared by every session recorded with the
+        // same settings; session_meta points each session at its snapshot
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS config_snapshots (
+                hash TEXT PRIMARY KEY,
+                snapshot TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_meta (
+                session_name TEXT PRIMARY KEY COLLATE NOCASE,
+                recorded_at TEXT NOT NULL,
+                snapshot_hash TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_tags (
+                session_name TEXT NOT NULL COLLATE NOCASE,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (session_name, tag)
+            )",
+            [],
+        )?;
+
+        // Notifications received, counted per session, local day and sending
+        // app; their contents are never stored
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS notification_counts (
+                session_name TEXT NOT NULL COLLATE NOCASE,
+                day TEXT NOT NULL,
+                source TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (session_name, day, source)
+            )",
+            [],
+        )?;
+
+        // What a session was started for (`start --intent`) and how it went
+        // by the rating given at `stop`
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_intents (
+                session_name TEXT PRIMARY KEY COLLATE NOCASE,
+                started_at INTEGER NOT NULL,
+                intent TEXT,
+                expected_seconds INTEGER,
+                rating INTEGER,
+                intent_met INTEGER
+            )",
+            [],
+        )?;
+
+        // The taskwarrior task or todo.txt line a session was started on (`start --task`)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_tasks (
+                session_name TEXT PRIMARY KEY COLLATE NOCASE,
+                started_at INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                task_id TEXT NOT NULL,
+                uuid TEXT,
+                description TEXT
+            )",
+            [],
+        )?;
+
+        // Schema version and the binary that wrote it
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Every version a daemon has run as, for spotting stats that span an upgrade
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS daemon_versions (
+                version TEXT PRIMARY KEY,
+                first_started TEXT NOT NULL,
+                last_started TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        if Self::stored_schema_version(&conn)?.is_none_or(|(version, _)| version < SCHEMA_VERSION) {
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1), ('schema_written_by', ?2)",
+                rusqlite::params![SCHEMA_VERSION.to_string(), env!("CARGO_PKG_VERSION")],
+            )?;
+        }
+
+        // Installed views follow the tables they read
+        let views_version: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'views_version'", [], |row| row.get(0))
+            .optional()?;
+        if views_version.is_some_and(|version| version != VIEWS_VERSION.to_string()) {
+            Self::create_views(&conn)?;
+        }
+
         Ok(Database { conn })
     }
 
-    fn get_db_path() -> SqliteResult<PathBuf> {
-        let data_dir = dirs::data_dir()
-            .ok_or_else(|| rusqlite::Error::InvalidPath("❌ Could not find data directory".into()))?;
-        
-        let focusdebt_dir = data_dir.join("focusdebt");
-        std::fs::create_dir_all(&focusdebt_dir)
+    /// Schema 2 keeps the times of focus_sessions, context_switches and
+    /// events as epoch seconds instead of RFC 3339 text, so range queries
+    /// compare integers. Tables still declaring TEXT are rebuilt, once, by
+    /// the open that finds an older schema version. Text an older daemon
+    /// writes through the upgrade is read by `timestamp_column` and
+    /// converted by `convert_text_times` when the new daemon starts.
+    fn migrate_epoch_times(conn: &Connection) -> SqliteResult<()> {
+        // Taking the write lock before looking at the schema means a second
+        // process opening the same old database waits, then finds nothing
+        // left to convert instead of converting epoch seconds again
+        let tx = rusqlite::Transaction::new_unchecked(conn, rusqlite::TransactionBehavior::Immediate)?;
+        // None for tables a new database hasn't created yet
+        let declared_type = |table: &str, column: &str| -> SqliteResult<Option<String>> {
+            tx.query_row(
+                "SELECT type FROM pragma_table_info(?1) WHERE name = ?2",
+                [table, column],
+                |row| row.get(0),
+            ).optional()
+        };
+
+        let mut existing = Vec::new();
+        let mut rebuild = Vec::new();
+        for (table, times) in EPOCH_TIME_COLUMNS {
+            match declared_type(table, times[0])? {
+                Some(declared) if declared.eq_ignore_ascii_case("TEXT") => rebuild.push((table, times)),
+                Some(_) => {}
+                None => continue,
+            }
+            existing.push((table, times));
+        }
+
+        if !rebuild.is_empty() {
+            // Renaming a table checks every view; installed ones are
+            // recreated on this open, as VIEWS_VERSION changed with them
+            for view in VIEWS.iter().rev() {
+                tx.execute_batch(&format!("DROP VIEW IF EXISTS {};", view.name))?;
+            }
+        }
+        for (table, times) in &rebuild {
+            // The copy is declared like the live table, so columns later
+            // upgrades added (focus_rule, co_focus, ...) come along
+            let columns = {
+                let mut stmt = tx.prepare(&format!("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info('{}')", table))?;
+                let columns = stmt.query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?,
+                        row.get::<_, Option<String>>(3)?, row.get::<_, i64>(4)? > 0))
+                })?.collect::<SqliteResult<Vec<_>>>()?;
+                columns
+            };
+            let definitions: Vec<String> = columns.iter()
+                .map(|(name, kind, not_null, default, primary_key)| {
+                    let kind = if times.contains(&name.as_str()) { "INTEGER" } else { kind };
+                    let mut definition = format!("{} {}", name, kind);
+                    if *primary_key {
+                        definition.push_str(" PRIMARY KEY");
+                    }
+                    if *not_null {
+                        definition.push_str(" NOT NULL");
+                    }
+                    if let Some(default) = default {
+                        definition.push_str(&format!(" DEFAULT {}", default));
+                    }
+                    definition
+                })
+                .collect();
+            let names: Vec<&str> = columns.iter().map(|(name, ..)| name.as_str()).collect();
+            let values: Vec<String> = names.iter()
+                .map(|name| if times.contains(name) { epoch_seconds(name) } else { name.to_string() })
+                .collect();
+            // Dropping the table drops its indexes; they are recreated on
+            // the copy before anything else can write to it. The legacy
+            // dedupe index is left dropped, the identity index replaces it.
+            let indexes = {
+                let mut stmt = tx.prepare("SELECT sql FROM sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL AND name != ?2")?;
+                let indexes = stmt.query_map([table, LEGACY_DEDUPE_INDEX], |row| row.get::<_, String>(0))?.collect::<SqliteResult<Vec<String>>>()?;
+                indexes
+            };
+
+            tx.execute_batch(&format!(
+                "CREATE TABLE {table}_epoch ({definitions});
+                 INSERT INTO {table}_epoch ({names}) SELECT {values} FROM {table};",
+                table = table,
+                definitions = definitions.join(", "),
+                names = names.join(", "),
+                values = values.join(", "),
+            ))?;
+            if *table == "focus_sessions" {
+                let (merged, duration) = Self::merge_truncated_starts(&tx)?;
+                if merged > 0 {
+                    eprintln!("~=~ Merged {} session rows ({}) saved twice within a second into the first copy while upgrading the database",
+                        merged, utils::format_duration(duration, utils::DurationStyle::Long));
+                    eprintln!("    The merged copies are quarantined; 'focusdebt database check' lists them");
+                }
+            }
+            tx.execute_batch(&format!(
+                "DROP TABLE {table};
+                 ALTER TABLE {table}_epoch RENAME TO {table};",
+                table = table,
+            ))?;
+            for sql in indexes {
+                tx.execute_batch(&sql)?;
+            }
+        }
+        for (table, times) in existing {
+            Self::text_times_to_epoch(&tx, table, times)?;
+        }
+        tx.commit()
+    }
+
+    /// Converts rows of focus_sessions, context_switches and events still
+    /// holding text times, left by an older daemon that kept running
+    /// through the upgrade. The daemon runs it once at start.
+    pub fn convert_text_times(&self) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut converted = 0;
+        for (table, times) in EPOCH_TIME_COLUMNS {
+            converted += Self::text_times_to_epoch(&tx, table, times)?;
+        }
+        tx.commit()?;
+        Ok(converted)
+    }
+
+    /// Rewrites `times` of `table`'s rows whose first time is text as epoch
+    /// seconds
+    fn text_times_to_epoch(conn: &Connection, table: &str, times: &[&str]) -> SqliteResult<usize> {
+        let set: Vec<String> = times.iter().map(|time| format!("{} = {}", time, epoch_seconds(time))).collect();
+        // Text sorts after every number, so this uses the time index
+        conn.execute(&format!("UPDATE {} SET {} WHERE {} >= ''", table, set.join(", "), times[0]), [])
+    }
+
+    /// Rows of focus_sessions_epoch that only collide on their identity
+    /// (`ROW_IDENTITY`) because their text start times differed below a
+    /// second: the same visit saved twice, as one row can't start within a
+    /// second of an identical one otherwise. Each is merged into the first
+    /// one saved; exact copies are left for the dedupe pass. The rows merged
+    /// away go to quarantined_sessions as they were recorded, so
+    /// `database quarantine` can still bring them back or drop them.
+    /// Returns the rows merged away and their time.
+    fn merge_truncated_starts(tx: &Connection) -> SqliteResult<(usize, Duration)> {
+        tx.execute_batch(&format!("CREATE INDEX focus_sessions_epoch_collisions ON focus_sessions_epoch ({})", ROW_IDENTITY))?;
+        let collisions = "SELECT later.id FROM focus_sessions_epoch later
+             JOIN focus_sessions_epoch earlier
+               ON earlier.start_time = later.start_time AND earlier.app_name = later.app_name
+              AND earlier.window_title = later.window_title AND earlier.session_name = later.session_name
+              AND earlier.duration_seconds = later.duration_seconds AND earlier.id < later.id
+             JOIN focus_sessions original_later ON original_later.id = later.id
+             JOIN focus_sessions original_earlier ON original_earlier.id = earlier.id
+             WHERE original_later.start_time IS NOT original_earlier.start_time";
+        let (rows, seconds): (i64, i64) = tx.query_row(
+            &format!("SELECT COUNT(*), COALESCE(SUM(duration_seconds), 0) FROM focus_sessions_epoch WHERE id IN ({})", collisions),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let merged = {
+            // Every column `session_from_row` reads exists by the upgrade
+            let mut stmt = tx.prepare(&format!(
+                "SELECT start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app,
+                        session_name, activity, passive_seconds, workspace, power, source, focus_rule, co_focus
+                 FROM focus_sessions WHERE id IN ({}) ORDER BY id",
+                collisions
+            ))?;
+            let merged = stmt.query_map([], Self::session_from_row)?.collect::<SqliteResult<Vec<_>>>()?;
+            merged
+        };
+        let now = Utc::now().to_rfc3339();
+        for session in &merged {
+            let row = serde_json::to_string(session)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            tx.execute(
+                "INSERT INTO quarantined_sessions (quarantined_at, reason, start_time, row) VALUES (?1, ?2, ?3, ?4)",
+                (&now, MERGED_AT_UPGRADE_REASON, &session.start_time.to_rfc3339(), &row),
+            )?;
+        }
+        tx.execute(&format!("DELETE FROM focus_sessions_epoch WHERE id IN ({})", collisions), [])?;
+        tx.execute_batch("DROP INDEX focus_sessions_epoch_collisions")?;
+        Ok((rows as usize, Duration::from_secs(seconds.max(0) as u64)))
+    }
+
+    /// Whether the database at `path` exists and still keeps times as text,
+    /// so opening it will rebuild its tables (see `migrate_epoch_times`)
+    pub fn needs_epoch_upgrade(path: &Path) -> SqliteResult<bool> {
+        if !path.exists() {
+            return Ok(false);
+        }
+        Self::has_text_times(&Self::open_read_only_retrying(path)?.conn)
+    }
+
+    /// Whether focus_sessions still declares its times as text
+    fn has_text_times(conn: &Connection) -> SqliteResult<bool> {
+        let declared: Option<String> = conn.query_row(
+            "SELECT type FROM pragma_table_info('focus_sessions') WHERE name = 'start_time'",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(declared.is_some_and(|declared| declared.eq_ignore_ascii_case("TEXT")))
+    }
+
+    /// Create (or recreate) the views in VIEWS and their view_docs rows
+    pub fn install_views(&self) -> SqliteResult<()> {
+        Self::create_views(&self.conn)
+    }
+
+    fn create_views(conn: &Connection) -> SqliteResult<()> {
+        let tx = conn.unchecked_transaction()?;
+        let mut sql = String::new();
+        // Dependents first, so nothing is dropped from under a view
+        for view in VIEWS.iter().rev() {
+            sql.push_str(&format!("DROP VIEW IF EXISTS {};", view.name));
+        }
+        for view in VIEWS {
+            sql.push_str(&format!("CREATE VIEW {} AS {};", view.name, view.sql));
+        }
+        sql.push_str(
+            "CREATE TABLE IF NOT EXISTS view_docs (
+                view_name TEXT NOT NULL,
+                column_name TEXT NOT NULL,
+                description TEXT NOT NULL,
+                PRIMARY KEY (view_name, column_name)
+            );
+            DELETE FROM view_docs;",
+        );
+        tx.execute_batch(&sql)?;
+
+        let mut insert = tx.prepare("INSERT INTO view_docs (view_name, column_name, description) VALUES (?1, ?2, ?3)")?;
+        for view in VIEWS {
+            // The view itself is documented under an empty column name
+            insert.execute(rusqlite::params![view.name, "", view.description])?;
+            for (column, description) in view.columns {
+                insert.execute(rusqlite::params![view.name, column, description])?;
+            }
+        }
+        drop(insert);
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('views_version', ?1)",
+            [VIEWS_VERSION.to_string()],
+        )?;
+        tx.commit()
+    }
+
+    /// A value the daemon keeps in the meta table, e.g. the size history
+    pub fn get_meta(&self, key: &str) -> SqliteResult<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+    }
+
+    pub fn set_meta(&self, key: &str, value: &str) -> SqliteResult<()> {
+        self.conn.execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)", [key, value])?;
+        Ok(())
+    }
+
+    pub fn delete_meta(&self, key: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM meta WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    /// Drop the views and view_docs; returns whether they were installed
+    pub fn remove_views(&self) -> SqliteResult<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let installed = tx.execute("DELETE FROM meta WHERE key = 'views_version'", [])? > 0;
+        let mut sql = String::new();
+        for view in VIEWS.iter().rev() {
+            sql.push_str(&format!("DROP VIEW IF EXISTS {};", view.name));
+        }
+        sql.push_str("DROP TABLE IF EXISTS view_docs;");
+        tx.execute_batch(&sql)?;
+        tx.commit()?;
+        Ok(installed)
+    }
+
+    /// Run one read-only SELECT (or WITH … SELECT) and return its column
+    /// names and rows, values as text (NULL as an empty string)
+    pub fn query_read_only(&self, sql: &str) -> SqliteResult<QueryResult> {
+        let first_word = sql.trim_start().split(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or("");
+        if !first_word.eq_ignore_ascii_case("select") && !first_word.eq_ignore_ascii_case("with") {
+            return Err(query_error("only SELECT statements can be run".to_string()));
+        }
+        if has_second_statement(sql) {
+            return Err(query_error("only one statement can be run at a time".to_string()));
+        }
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(query_error("only SELECT statements can be run".to_string()));
+        }
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query([])?;
+        let mut values = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut record = Vec::with_capacity(columns.len());
+            for index in 0..columns.len() {
+                record.push(match row.get_ref(index)? {
+                    rusqlite::types::ValueRef::Null => String::new(),
+                    rusqlite::types::ValueRef::Integer(value) => value.to_string(),
+                    rusqlite::types::ValueRef::Real(value) => value.to_string(),
+                    rusqlite::types::ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                    rusqlite::types::ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+                });
+            }
+            values.push(record);
+        }
+        Ok(QueryResult { columns, rows: values })
+    }
+
+    /// Every row of every table as JSON objects keyed by column, for the
+    /// backup `reset --all` writes. Blobs become hex strings.
+    pub fn dump_tables(&self) -> SqliteResult<BTreeMap<String, Vec<serde_json::Map<String, serde_json::Value>>>> {
+        let names: Vec<String> = self.conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?
+            .query_map([], |row| row.get(0))?
+            .collect::<SqliteResult<_>>()?;
+        let mut tables = BTreeMap::new();
+        for name in names {
+            let mut stmt = self.conn.prepare(&format!("SELECT * FROM \"{}\"", name.replace('"', "\"\"")))?;
+            let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+            let mut rows = stmt.query([])?;
+            let mut records = Vec::new();
+            while let Some(row) = rows.next()? {
+                let mut record = serde_json::Map::new();
+                for (index, column) in columns.iter().enumerate() {
+                    let value = match row.get_ref(index)? {
+                        ValueRef::Null => serde_json::Value::Null,
+                        ValueRef::Integer(value) => value.into(),
+                        ValueRef::Real(value) => value.into(),
+                        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned().into(),
+                        ValueRef::Blob(blob) => blob.iter().map(|byte| format!("{:02x}", byte)).collect::<String>().into(),
+                    };
+                    record.insert(column.clone(), value);
+                }
+                records.push(record);
+            }
+            tables.insert(name, records);
+        }
+        Ok(tables)
+    }
+
+    /// The schema version and the focusdebt version that wrote it; None for
+    /// databases from before versions were recorded
+    fn stored_schema_version(conn: &Connection) -> SqliteResult<Option<(i64, String)>> {
+        let version = conn.query_row(
+            "SELECT (SELECT value FROM meta WHERE key = 'schema_version'),
+                    (SELECT value FROM meta WHERE key = 'schema_written_by')",
+            [],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
+        );
+        match version {
+            Ok((Some(version), written_by)) => Ok(Some((version.parse().unwrap_or(0), written_by.unwrap_or_default()))),
+            Ok((None, _)) => Ok(None),
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Refuse a database whose schema is newer than this build understands,
+    /// naming the version to upgrade to instead of failing on a column later
+    fn check_schema_version(conn: &Connection) -> SqliteResult<()> {
+        match Self::stored_schema_version(conn)? {
+            Some((version, written_by)) if version > SCHEMA_VERSION => Err(schema_error(format!(
+                "database was created by focusdebt {}; please upgrade (this is focusdebt {})",
+                if written_by.is_empty() { "a newer version" } else { written_by.as_str() },
+                env!("CARGO_PKG_VERSION"),
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Note the running version at daemon start
+    pub fn record_daemon_version(&self, version: &str, at: DateTime<Utc>) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO daemon_versions (version, first_started, last_started) VALUES (?1, ?2, ?2)
+             ON CONFLICT(version) DO UPDATE SET last_started = excluded.last_started",
+            rusqlite::params![version, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Versions the daemon has run as, with when each first started, oldest first
+    pub fn get_daemon_versions(&self) -> SqliteResult<Vec<(String, DateTime<Utc>)>> {
+        let mut stmt = match self.conn.prepare("SELECT version, first_started FROM daemon_versions ORDER BY first_started") {
+            Ok(stmt) => stmt,
+            // A read-only open of a database no daemon has upgraded yet
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut versions = Vec::new();
+        for row in rows {
+            let (version, started) = row?;
+            if let Ok(started) = DateTime::parse_from_rfc3339(&started) {
+                versions.push((version, started.with_timezone(&Utc)));
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Unlock an encrypted database before anything else reads it
+    fn unlock(conn: &Connection, path: &Path) -> SqliteResult<()> {
+        if !is_encrypted(path) {
+            return Ok(());
+        }
+        if !cfg!(feature = "encryption") {
+            return Err(encryption_error(
+                "❌ The database is encrypted, but this build has no encryption support (rebuild with --features encryption)".to_string(),
+            ));
+        }
+        Self::apply_key(conn, &Self::database_key()?)
+    }
+
+    /// Set the SQLCipher key and check it on the schema, so a wrong key is
+    /// reported as such rather than as "file is not a database"
+    fn apply_key(conn: &Connection, key: &str) -> SqliteResult<()> {
+        conn.pragma_update(None, "key", key)?;
+        match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => Err(encryption_error(format!(
+                "❌ Wrong key for the encrypted database (check {} or database_key_file)", DB_KEY_ENV
+            ))),
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// The passphrase from FOCUSDEBT_DB_KEY or database_key_file, if either is set
+    pub fn configured_database_key() -> SqliteResult<Option<String>> {
+        if let Some(key) = utils::non_empty_env(DB_KEY_ENV) {
+            return Ok(Some(key));
+        }
+        let Some(path) = Config::load().ok().and_then(|config| config.database_key_file) else {
+            return Ok(None);
+        };
+        let key = std::fs::read_to_string(&path)
+            .map_err(|e| encryption_error(format!("❌ Failed to read database_key_file {}: {}", path, e)))?;
+        Ok(Some(key.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    /// The configured passphrase, or one typed at a prompt when there is none
+    pub fn database_key() -> SqliteResult<String> {
+        if let Some(key) = DATABASE_KEY.get() {
+            return Ok(key.clone());
+        }
+        let key = match Self::configured_database_key()? {
+            Some(key) => key,
+            None => utils::prompt_passphrase("Database passphrase: ").map_err(|e| encryption_error(format!(
+                "❌ The database is encrypted and {} is not set ({})", DB_KEY_ENV, e
+            )))?,
+        };
+        Ok(DATABASE_KEY.get_or_init(|| key).clone())
+    }
+
+    /// Rewrite the plaintext database at `path` encrypted with `key`
+    pub fn encrypt_file(path: &Path, key: &str) -> SqliteResult<()> {
+        Self::migrate_file(path, None, key)
+    }
+
+    /// Rewrite the encrypted database at `path` as plain SQLite
+    pub fn decrypt_file(path: &Path, key: &str) -> SqliteResult<()> {
+        Self::migrate_file(path, Some(key), "")
+    }
+
+    /// Copy the database into a sibling file with `to_key` (empty for
+    /// plaintext) using sqlcipher_export, then rename it over the original
+    fn migrate_file(path: &Path, from_key: Option<&str>, to_key: &str) -> SqliteResult<()> {
+        let migrated = path.with_extension("db.migrating");
+        let _ = std::fs::remove_file(&migrated);
+        {
+            let conn = Connection::open(path)?;
+            if let Some(key) = from_key {
+                Self::apply_key(&conn, key)?;
+            }
+            conn.execute("ATTACH DATABASE ?1 AS migrated KEY ?2", (migrated.to_string_lossy(), to_key))?;
+            conn.query_row("SELECT sqlcipher_export('migrated')", [], |_| Ok(()))?;
+            conn.execute("DETACH DATABASE migrated", [])?;
+        }
+        std::fs::rename(&migrated, path)
+            .map_err(|e| rusqlite::Error::InvalidPath(format!("❌ Failed to replace {}: {}", path.display(), e).into()))
+    }
+
+    pub fn get_db_path() -> SqliteResult<PathBuf> {
+        let focusdebt_dir = utils::ensure_profile_data_directory()
             .map_err(|e| rusqlite::Error::InvalidPath(format!("❌ Failed to create directory: {}", e).into()))?;
         
         Ok(focusdebt_dir.join("focusdebt.db"))
     }
 
+    /// A row that was already saved (same session, start, app, title and
+    /// duration) is skipped
     pub fn save_focus_session(&self, session: &FocusSession) -> SqliteResult<()> {
+        let _timer = timing::scope(Phase::Write);
+        // Only a uniqueness conflict is skipped; OR IGNORE would also drop
+        // a row failing NOT NULL without an error
         self.conn.execute(
-            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name, activity, passive_seconds, workspace, power, focus_rule, co_focus)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+             ON CONFLICT DO NOTHING",
             (
-                &session.start_time.to_rfc3339(),
-                &session.end_time.as_ref().map(|t| t.to_rfc3339()),
+                session.start_time.timestamp(),
+                session.end_time.map(|t| t.timestamp()),
                 &session.app_name,
                 &session.window_title,
                 &session.domain,
                 session.duration.as_secs() as i64,
                 session.is_focus_app,
                 &session.session_name,
+                session.activity,
+                session.passive.map(|passive| passive.as_secs() as i64),
+                &session.workspace,
+                session.power.map(|power| power.as_str()),
+                session.focus_rule.as_ref().map(RuleMatch::to_column),
+                &session.co_focus,
             ),
         )?;
         Ok(())
     }
 
+    /// Insert imported (key, session) rows tagged with `source` in one
+    /// transaction, skipping keys already imported from that source. Returns
+    /// how many rows were new.
+    pub fn import_focus_sessions(&self, source: &str, rows: &[(String, FocusSession)]) -> SqliteResult<usize> {
+        Ok(self.import_focus_sessions_each(source, rows)?.into_iter().filter(|new| *new).count())
+    }
+
+    /// `import_focus_sessions`, telling for each row whether it was new
+    pub fn import_focus_sessions_each(&self, source: &str, rows: &[(String, FocusSession)]) -> SqliteResult<Vec<bool>> {
+        let _timer = timing::scope(Phase::Write);
+        let tx = self.conn.unchecked_transaction()?;
+        let mut inserted = Vec::with_capacity(rows.len());
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO focus_sessions
+                 (start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name, activity, passive_seconds, workspace, power, source, source_key, focus_rule, co_focus)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                 ON CONFLICT DO NOTHING",
+            )?;
+            let mut claim = tx.prepare("INSERT OR IGNORE INTO sessions (name, started_at) VALUES (?1, ?2)")?;
+            for (key, session) in rows {
+                inserted.push(insert.execute((
+                    session.start_time.timestamp(),
+                    session.end_time.map(|t| t.timestamp()),
+                    &session.app_name,
+                    &session.window_title,
+                    &session.domain,
+                    session.duration.as_secs() as i64,
+                    session.is_focus_app,
+                    &session.session_name,
+                    session.activity,
+                    session.passive.map(|passive| passive.as_secs() as i64),
+                    &session.workspace,
+                    session.power.map(|power| power.as_str()),
+                    source,
+                    key,
+                    session.focus_rule.as_ref().map(RuleMatch::to_column),
+                    &session.co_focus,
+                ))? > 0);
+                if !session.session_name.is_empty() {
+                    claim.execute((&session.session_name, &session.start_time.to_rfc3339()))?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
     pub fn save_context_switch(&self, switch: &ContextSwitch) -> SqliteResult<()> {
+        let _timer = timing::scope(Phase::Write);
         self.conn.execute(
             "INSERT INTO context_switches (timestamp, from_app, to_app, recovery_time_seconds)
              VALUES (?1, ?2, ?3, ?4)",
             (
-                &switch.timestamp.to_rfc3339(),
+                switch.timestamp.timestamp(),
                 &switch.from_app,
                 &switch.to_app,
                 &switch.recovery_time.map(|d| d.as_secs() as i64),
@@ -118,14 +1021,6 @@ impl Database {
         Ok(())
     }
 
-    pub fn add_focus_app(&self, app_name: &str) -> SqliteResult<()> {
-        self.conn.execute(
-            "INSERT OR IGNORE INTO focus_apps (app_name, added_at) VALUES (?1, ?2)",
-            (app_name, &Utc::now().to_rfc3339()),
-        )?;
-        Ok(())
-    }
-
     pub fn remove_focus_app(&self, app_name: &str) -> SqliteResult<()> {
         self.conn.execute(
             "DELETE FROM focus_apps WHERE app_name = ?1",
@@ -137,7 +1032,7 @@ impl Database {
     pub fn get_focus_apps(&self) -> SqliteResult<Vec<String>> {
         let mut stmt = self.conn.prepare("SELECT app_name FROM focus_apps ORDER BY app_name")?;
         let app_iter = stmt.query_map([], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
 
         let mut apps = Vec::new();
@@ -147,26 +1042,43 @@ impl Database {
         Ok(apps)
     }
 
-    pub fn add_focus_site(&self, domain: &str) -> SqliteResult<()> {
-        self.conn.execute(
-            "INSERT OR IGNORE INTO focus_sites (domain, added_at) VALUES (?1, ?2)",
-            (domain, &Utc::now().to_rfc3339()),
+    /// Every app with rows, ignoring case: when it was first and last seen
+    /// and its total tracked time, for `focusapp audit`
+    pub fn get_app_sightings(&self) -> SqliteResult<Vec<AppSighting>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT app_name, MIN(start_time), MAX(COALESCE(end_time, start_time)), SUM(duration_seconds)
+             FROM focus_sessions GROUP BY app_name COLLATE NOCASE ORDER BY app_name COLLATE NOCASE"
         )?;
-        Ok(())
-    }
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Self::timestamp_column(row, 1).ok().flatten(),
+                Self::timestamp_column(row, 2).ok().flatten(),
+                row.get::<_, i64>(3)?,
+            ))
+        })?;
 
-    pub fn remove_focus_site(&self, domain: &str) -> SqliteResult<()> {
-        self.conn.execute(
-            "DELETE FROM focus_sites WHERE domain = ?1",
-            (domain,),
-        )?;
-        Ok(())
+        let mut sightings = Vec::new();
+        for row in rows {
+            let (app_name, first, last, seconds) = row?;
+            let (Some(first_seen), Some(last_seen)) = (first, last) else {
+                continue;
+            };
+            sightings.push(AppSighting {
+                app_name,
+                first_seen,
+                last_seen,
+                total: Duration::from_secs(seconds.max(0) as u64),
+            });
+        }
+        Ok(sightings)
     }
 
     pub fn get_focus_sites(&self) -> SqliteResult<Vec<String>> {
         let mut stmt = self.conn.prepare("SELECT domain FROM focus_sites ORDER BY domain")?;
         let site_iter = stmt.query_map([], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
 
         let mut sites = Vec::new();
@@ -176,153 +1088,196 @@ impl Database {
         Ok(sites)
     }
 
-    pub fn get_deep_focus_sessions(&self, min_duration_seconds: u64, date: DateTime<Utc>) -> SqliteResult<Vec<FocusSession>> {
-        let start_of_day = date.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let end_of_day = date.date_naive().and_hms_opt(23, 59, 59).unwrap();
-        
-        let start_str = DateTime::<Utc>::from_naive_utc_and_offset(start_of_day, Utc).to_rfc3339();
-        let end_str = DateTime::<Utc>::from_naive_utc_and_offset(end_of_day, Utc).to_rfc3339();
-        let min_duration_str = (min_duration_seconds as i64).to_string();
+    pub fn remove_focus_site(&self, domain: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM focus_sites WHERE domain = ?1",
+            (domain,),
+        )?;
+        Ok(())
+    }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name
+    /// `apps` and `sites` (the config's lists) plus the entries only the
+    /// focus_apps and focus_sites tables hold, from before config.toml
+    /// kept the lists. Classification, listings, audits and exports all
+    /// read the lists through here, so they agree on what counts as focus.
+    pub fn focus_lists(&self, apps: &[String], sites: &[String]) -> SqliteResult<(Vec<String>, Vec<String>)> {
+        let merge = |listed: &[String], stored: Vec<String>| {
+            let mut merged = listed.to_vec();
+            for entry in stored {
+                if !merged.contains(&entry) {
+                    merged.push(entry);
+                }
+            }
+            merged
+        };
+        Ok((merge(apps, self.get_focus_apps()?), merge(sites, self.get_focus_sites()?)))
+    }
+
+    /// Rows starting on `day` as `days` cuts it
+    pub fn get_sessions_for_date(&self, days: &LocalDays, day: NaiveDate) -> SqliteResult<Vec<FocusSession>> {
+        let _timer = timing::scope(Phase::Query);
+        let (start, end) = days.range(day);
+        // Times are whole seconds, so the last one before the next day closes the range
+        self.get_sessions_between(start, end - chrono::Duration::seconds(1))
+    }
+
+    pub fn get_sessions_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<FocusSession>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}
              FROM focus_sessions 
-             WHERE start_time >= ?1 AND start_time <= ?2 
-             AND is_focus_app = 1 
-             AND duration_seconds >= ?3
-             ORDER BY duration_seconds DESC"
-        )?;
-
-        let session_iter = stmt.query_map([&start_str, &end_str, &min_duration_str], |row| {
-            let start_time: String = row.get(0)?;
-            let end_time: Option<String> = row.get(1)?;
-            let app_name: String = row.get(2)?;
-            let window_title: String = row.get(3)?;
-            let domain: Option<String> = row.get(4)?;
-            let duration_seconds: i64 = row.get(5)?;
-            let is_focus_app: bool = row.get(6)?;
-            let session_name: String = row.get(7)?;
-
-            let start_time = DateTime::parse_from_rfc3339(&start_time)
-                .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid start_time".into()))?
-                .with_timezone(&Utc);
-
-            let end_time = end_time
-                .map(|t| DateTime::parse_from_rfc3339(&t)
-                    .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid end_time".into()))
-                    .map(|dt| dt.with_timezone(&Utc)))
-                .transpose()?;
+             WHERE start_time >= ?1 AND start_time <= ?2
+             ORDER BY start_time",
+            self.session_columns()?
+        ))?;
 
-            Ok(FocusSession {
-                start_time,
-                end_time,
-                app_name,
-                window_title,
-                domain,
-                duration: Duration::from_secs(duration_seconds as u64),
-                is_focus_app,
-                session_name,
-            })
-        })?;
+        let session_iter = stmt.query_map([start.timestamp(), end.timestamp()], Self::session_from_row)?;
 
         let mut sessions = Vec::new();
         for session in session_iter {
             sessions.push(session?);
         }
+        self.settle_open_rows(&mut sessions)?;
         Ok(sessions)
     }
 
-    pub fn get_most_distracting_apps(&self, date: DateTime<Utc>, limit: usize) -> SqliteResult<Vec<(String, Duration)>> {
-        let start_of_day = date.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let end_of_day = date.date_naive().and_hms_opt(23, 59, 59).unwrap();
-        
-        let start_str = DateTime::<Utc>::from_naive_utc_and_offset(start_of_day, Utc).to_rfc3339();
-        let end_str = DateTime::<Utc>::from_naive_utc_and_offset(end_of_day, Utc).to_rfc3339();
-        let limit_str = (limit as i64).to_string();
-
-        let mut stmt = self.conn.prepare(
-            "SELECT app_name, SUM(duration_seconds) as total_duration
-             FROM focus_sessions 
-             WHERE start_time >= ?1 AND start_time <= ?2 
-             AND is_focus_app = 0
-             GROUP BY app_name 
-             ORDER BY total_duration DESC 
-             LIMIT ?3"
-        )?;
+    /// Rows whose time overlaps `start`..`end` (touching ends don't count),
+    /// oldest first. Rows saved without an end count up to their duration.
+    pub fn get_overlapping_sessions(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<FocusSession>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}
+             FROM focus_sessions
+             WHERE start_time > ?1 - ?3 AND start_time < ?2
+               AND COALESCE(end_time, start_time + duration_seconds) > ?1
+             ORDER BY start_time",
+            self.session_columns()?
+        ))?;
 
-        let app_iter = stmt.query_map([&start_str, &end_str, &limit_str], |row| {
-            let app_name: String = row.get(0)?;
-            let duration_seconds: i64 = row.get(1)?;
-            Ok((app_name, Duration::from_secs(duration_seconds as u64)))
-        })?;
+        let session_iter = stmt.query_map([start.timestamp(), end.timestamp(), MAX_ROW_SECONDS], Self::session_from_row)?;
 
-        let mut apps = Vec::new();
-        for app in app_iter {
-            apps.push(app?);
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
         }
-        Ok(apps)
+        Ok(sessions)
     }
 
-    pub fn get_sessions_for_date(&self, date: DateTime<Utc>) -> SqliteResult<Vec<FocusSession>> {
-        let start_of_day = date.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let end_of_day = date.date_naive().and_hms_opt(23, 59, 59).unwrap();
-        
-        let start_str = DateTime::<Utc>::from_naive_utc_and_offset(start_of_day, Utc).to_rfc3339();
-        let end_str = DateTime::<Utc>::from_naive_utc_and_offset(end_of_day, Utc).to_rfc3339();
+    /// The columns `session_from_row` reads. A database no newer build has
+    /// opened for writing may lack passive_seconds, workspace, power, source,
+    /// focus_rule and co_focus, which then read as NULL.
+    fn session_columns(&self) -> SqliteResult<String> {
+        let has_column = |name: &str| -> SqliteResult<bool> {
+            self.conn.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('focus_sessions') WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+        };
+        let optional = |name: &'static str| -> SqliteResult<&'static str> {
+            Ok(if has_column(name)? { name } else { "NULL" })
+        };
+        Ok(format!(
+            "start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name, activity, {}, {}, {}, {}, {}, {}",
+            optional("passive_seconds")?,
+            optional("workspace")?,
+            optional("power")?,
+            optional("source")?,
+            optional("focus_rule")?,
+            optional("co_focus")?,
+        ))
+    }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name
-             FROM focus_sessions 
-             WHERE start_time >= ?1 AND start_time <= ?2
-             ORDER BY start_time"
-        )?;
+    /// Columns: start_time, end_time, app_name, window_title, domain,
+    /// duration_seconds, is_focus_app, session_name, activity, passive_seconds,
+    /// workspace, power, source, focus_rule, co_focus
+    fn session_from_row(row: &rusqlite::Row) -> SqliteResult<FocusSession> {
+        let duration_seconds: i64 = row.get(5)?;
 
-        let session_iter = stmt.query_map([&start_str, &end_str], |row| {
-            let start_time: String = row.get(0)?;
-            let end_time: Option<String> = row.get(1)?;
-            let app_name: String = row.get(2)?;
-            let window_title: String = row.get(3)?;
-            let domain: Option<String> = row.get(4)?;
-            let duration_seconds: i64 = row.get(5)?;
-            let is_focus_app: bool = row.get(6)?;
-            let session_name: String = row.get(7)?;
+        Ok(FocusSession {
+            start_time: Self::timestamp_column(row, 0)?
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid start_time".into()))?,
+            end_time: Self::timestamp_column(row, 1)?,
+            app_name: row.get(2)?,
+            window_title: row.get(3)?,
+            domain: row.get(4)?,
+            duration: Duration::from_secs(duration_seconds as u64),
+            is_focus_app: row.get(6)?,
+            session_name: row.get(7)?,
+            activity: row.get(8)?,
+            passive: row.get::<_, Option<i64>>(9)?.map(|secs| Duration::from_secs(secs.max(0) as u64)),
+            workspace: row.get(10)?,
+            // Unknown values (a newer build's) read as not recorded
+            power: row.get::<_, Option<String>>(11)?.and_then(|power| PowerSource::parse(&power).ok()),
+            open_end: OpenEnd::Closed,
+            manual: row.get::<_, Option<String>>(12)?.as_deref() == Some(MANUAL_SOURCE),
+            focus_rule: row.get::<_, Option<String>>(13)?.and_then(|rule| RuleMatch::parse(&rule).ok()),
+            co_focus: row.get(14)?,
+        })
+    }
 
-            let start_time = DateTime::parse_from_rfc3339(&start_time)
-                .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid start_time".into()))?
-                .with_timezone(&Utc);
+    /// A time column of focus_sessions, context_switches or events: epoch
+    /// seconds, or RFC 3339 text an older build wrote (see
+    /// `migrate_epoch_times`). None for NULL.
+    fn timestamp_column(row: &rusqlite::Row, index: usize) -> SqliteResult<Option<DateTime<Utc>>> {
+        let invalid = || rusqlite::Error::InvalidParameterName(format!("Invalid timestamp in column {}", index));
+        match row.get_ref(index)? {
+            ValueRef::Null => Ok(None),
+            ValueRef::Integer(secs) => DateTime::from_timestamp(secs, 0).map(Some).ok_or_else(invalid),
+            ValueRef::Text(text) => std::str::from_utf8(text).ok()
+                .and_then(|text| DateTime::parse_from_rfc3339(text).ok())
+                .map(|at| Some(at.with_timezone(&Utc)))
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
 
-            let end_time = end_time
-                .map(|t| DateTime::parse_from_rfc3339(&t)
-                    .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid end_time".into()))
-                    .map(|dt| dt.with_timezone(&Utc)))
-                .transpose()?;
+    /// Give rows saved without an end_time one, see
+    /// `FocusSession::settle_open_end`. Every read of rows for reports goes
+    /// through here, so stats, exports and the timeline agree on them.
+    fn settle_open_rows(&self, sessions: &mut [FocusSession]) -> SqliteResult<()> {
+        let now = Utc::now();
+        for session in sessions.iter_mut().filter(|session| session.end_time.is_none()) {
+            let next_start = self.conn.query_row(
+                "SELECT MIN(start_time) FROM focus_sessions WHERE start_time > ?1",
+                [session.start_time.timestamp()],
+                |row| Self::timestamp_column(row, 0),
+            )?;
+            session.settle_open_end(next_start, now);
+        }
+        Ok(())
+    }
 
-            Ok(FocusSession {
-                start_time,
-                end_time,
-                app_name,
-                window_title,
-                domain,
-                duration: Duration::from_secs(duration_seconds as u64),
-                is_focus_app,
-                session_name,
-            })
-        })?;
+    /// Every row recorded under `session_name` (case-insensitive), oldest first
+    pub fn get_sessions_by_name(&self, session_name: &str) -> SqliteResult<Vec<FocusSession>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}
+             FROM focus_sessions
+             WHERE session_name = ?1 COLLATE NOCASE
+             ORDER BY start_time",
+            self.session_columns()?
+        ))?;
+
+        let session_iter = stmt.query_map([session_name], Self::session_from_row)?;
 
         let mut sessions = Vec::new();
         for session in session_iter {
             sessions.push(session?);
         }
+        self.settle_open_rows(&mut sessions)?;
         Ok(sessions)
     }
 
-    pub fn get_context_switches_for_date(&self, date: DateTime<Utc>) -> SqliteResult<Vec<ContextSwitch>> {
-        let start_of_day = date.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let end_of_day = date.date_naive().and_hms_opt(23, 59, 59).unwrap();
-        
-        let start_str = DateTime::<Utc>::from_naive_utc_and_offset(start_of_day, Utc).to_rfc3339();
-        let end_str = DateTime::<Utc>::from_naive_utc_and_offset(end_of_day, Utc).to_rfc3339();
+    /// Switches on `day` as `days` cuts it
+    pub fn get_context_switches_for_date(&self, days: &LocalDays, day: NaiveDate) -> SqliteResult<Vec<ContextSwitch>> {
+        let _timer = timing::scope(Phase::Query);
+        let (start, end) = days.range(day);
+        self.get_context_switches_between(start, end - chrono::Duration::seconds(1))
+    }
 
+    pub fn get_context_switches_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<ContextSwitch>> {
+        let _timer = timing::scope(Phase::Query);
         let mut stmt = self.conn.prepare(
             "SELECT timestamp, from_app, to_app, recovery_time_seconds
              FROM context_switches 
@@ -330,16 +1285,13 @@ impl Database {
              ORDER BY timestamp"
         )?;
 
-        let switch_iter = stmt.query_map([&start_str, &end_str], |row| {
-            let timestamp: String = row.get(0)?;
+        let switch_iter = stmt.query_map([start.timestamp(), end.timestamp()], |row| {
+            let timestamp = Self::timestamp_column(row, 0)?
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid timestamp".into()))?;
             let from_app: String = row.get(1)?;
             let to_app: String = row.get(2)?;
             let recovery_time_seconds: Option<i64> = row.get(3)?;
 
-            let timestamp = DateTime::parse_from_rfc3339(&timestamp)
-                .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid timestamp".into()))?
-                .with_timezone(&Utc);
-
             let recovery_time = recovery_time_seconds.map(|s| Duration::from_secs(s as u64));
 
             Ok(ContextSwitch {
@@ -357,6 +1309,640 @@ impl Database {
         Ok(switches)
     }
 
+    /// Context switch counts since `since`, grouped by local weekday
+    /// (0 = Sunday, as in strftime's %w) and hour. Bucketed here rather than
+    /// with SQLite's 'localtime', which only knows the system's zone.
+    pub fn get_switch_counts_by_weekday_hour(&self, since: DateTime<Utc>) -> SqliteResult<Vec<(u32, u32, usize)>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp FROM context_switches WHERE timestamp >= ?1"
+        )?;
+
+        let rows = stmt.query_map([since.timestamp()], |row| Ok(Self::timestamp_column(row, 0).ok().flatten()))?;
+
+        let mut buckets: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+        for row in rows {
+            let Some(timestamp) = row? else { continue };
+            let local = utils::timezone::to_zone(timestamp);
+            *buckets.entry((local.weekday().num_days_from_sunday(), local.hour())).or_insert(0) += 1;
+        }
+        Ok(buckets.into_iter().map(|((weekday, hour), count)| (weekday, hour, count)).collect())
+    }
+
+    /// Focus efficiency (percent) per tracking day (`utils::tracking_day`)
+    /// of the rows starting in [start, end). Days without any tracked time
+    /// are omitted.
+    pub fn get_daily_efficiency(&self, start: DateTime<Utc>, end: DateTime<Utc>, day_start_hour: u32) -> SqliteResult<Vec<(NaiveDate, f64)>> {
+        let _timer = timing::scope(Phase::Query);
+        // Same validity window as the daily stats: ignore sub-second noise and >24h rows
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time, is_focus_app, duration_seconds
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND start_time < ?2
+             AND duration_seconds >= 1 AND duration_seconds <= 86400"
+        )?;
+        let rows = stmt.query_map([start.timestamp(), end.timestamp()], |row| {
+            Ok((Self::timestamp_column(row, 0)?, row.get::<_, bool>(1)?, row.get::<_, i64>(2)?))
+        })?.collect::<SqliteResult<Vec<_>>>()?;
+        let rows: Vec<(DateTime<Utc>, bool, i64)> = rows.into_iter()
+            .filter_map(|(start, focus, seconds)| Some((start?, focus, seconds)))
+            .collect();
+        Ok(efficiency_by_day(&rows, |at| utils::tracking_day(at, day_start_hour)))
+    }
+
+    /// Focus and total time per day, as `days` cuts them, for rows starting
+    /// in [start, end); days without tracked time are omitted
+    pub fn get_daily_totals_between(&self, days: &LocalDays, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<(NaiveDate, Duration, Duration)>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time, is_focus_app, duration_seconds
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND start_time < ?2
+             AND duration_seconds >= 1 AND duration_seconds <= 86400"
+        )?;
+
+        // Rows are folded as they are read, so a year is never held at once
+        let mut totals: BTreeMap<NaiveDate, (Duration, Duration)> = BTreeMap::new();
+        let mut rows = stmt.query([start.timestamp(), end.timestamp()])?;
+        while let Some(row) = rows.next()? {
+            let Some(start) = Self::timestamp_column(row, 0)? else {
+                continue;
+            };
+            let duration = Duration::from_secs(row.get::<_, i64>(2)?.max(0) as u64);
+            let (focus, total) = totals.entry(days.day_of(start)).or_default();
+            if row.get::<_, bool>(1)? {
+                *focus += duration;
+            }
+            *total += duration;
+        }
+        Ok(totals.into_iter().map(|(day, (focus, total))| (day, focus, total)).collect())
+    }
+
+    /// Time per (quarter 1-4 of the day as `days` cuts it, app) for rows
+    /// starting in [start, end)
+    pub fn get_app_totals_by_quarter(&self, days: &LocalDays, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<(u32, String, Duration)>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time, app_name, duration_seconds
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND start_time < ?2
+             AND duration_seconds >= 1 AND duration_seconds <= 86400"
+        )?;
+
+        let mut totals: BTreeMap<(u32, String), Duration> = BTreeMap::new();
+        let mut rows = stmt.query([start.timestamp(), end.timestamp()])?;
+        while let Some(row) = rows.next()? {
+            let Some(start) = Self::timestamp_column(row, 0)? else {
+                continue;
+            };
+            let quarter = days.day_of(start).month0() / 3 + 1;
+            *totals.entry((quarter, row.get(1)?)).or_default() += Duration::from_secs(row.get::<_, i64>(2)?.max(0) as u64);
+        }
+        Ok(totals.into_iter().map(|((quarter, app), duration)| (quarter, app, duration)).collect())
+    }
+
+    /// Longest run of focus rows starting in [start, end), as (start, length),
+    /// with the rules of `stats::longest_focus_block`: a gap of more than
+    /// `tolerance` or a distraction of at least `tolerance` ends a run,
+    /// shorter distractions are skipped
+    pub fn get_longest_focus_block_between(&self, start: DateTime<Utc>, end: DateTime<Utc>, tolerance: Duration) -> SqliteResult<Option<(DateTime<Utc>, Duration)>> {
+        let _timer = timing::scope(Phase::Query);
+        self.conn.query_row(
+            "WITH counted AS (
+                 SELECT start_time, duration_seconds, is_focus_app,
+                        COALESCE(end_time, start_time + duration_seconds) AS ended
+                 FROM focus_sessions
+                 WHERE start_time >= ?1 AND start_time < ?2
+                 AND duration_seconds >= 1 AND duration_seconds <= 86400
+                 AND (is_focus_app = 1 OR duration_seconds >= ?3)
+             ),
+             marked AS (
+                 SELECT *,
+                        CASE WHEN is_focus_app = 0 OR LAG(is_focus_app) OVER w = 0
+                                  OR start_time - LAG(ended) OVER w > ?3
+                             THEN 1 ELSE 0 END AS starts_run
+                 FROM counted
+                 WINDOW w AS (ORDER BY start_time)
+             ),
+             runs AS (
+                 SELECT *, SUM(starts_run) OVER (ORDER BY start_time ROWS UNBOUNDED PRECEDING) AS run
+                 FROM marked
+             )
+             SELECT MIN(start_time), SUM(duration_seconds) AS seconds
+             FROM runs
+             WHERE is_focus_app = 1
+             GROUP BY run
+             ORDER BY seconds DESC, MIN(start_time)
+             LIMIT 1",
+            (start.timestamp(), end.timestamp(), tolerance.as_secs() as i64),
+            |row| {
+                let started = Self::timestamp_column(row, 0)?
+                    .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid start_time".into()))?;
+                let seconds: i64 = row.get(1)?;
+                Ok((started, Duration::from_secs(seconds.max(0) as u64)))
+            },
+        ).optional()
+    }
+
+    pub fn count_context_switches_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<usize> {
+        let _timer = timing::scope(Phase::Query);
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM context_switches WHERE timestamp >= ?1 AND timestamp < ?2",
+            [start.timestamp(), end.timestamp()],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// What `stats::switching_cost` needs over [start, end), summed up in
+    /// the database: (switches away from focus, how many of them had a
+    /// recovery time measured on the next switch back, those recovery times)
+    pub fn get_switch_away_totals_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<(usize, usize, Duration)> {
+        let _timer = timing::scope(Phase::Query);
+        let (away, measured, seconds): (i64, i64, i64) = self.conn.query_row(
+            "WITH classified AS (
+                 SELECT s.timestamp, s.recovery_time_seconds AS recovery,
+                        (SELECT f.is_focus_app FROM focus_sessions f
+                         WHERE f.start_time < s.timestamp ORDER BY f.start_time DESC LIMIT 1) AS from_focus,
+                        COALESCE((SELECT f.is_focus_app FROM focus_sessions f
+                                  WHERE f.start_time >= s.timestamp ORDER BY f.start_time LIMIT 1), 0) AS to_focus
+                 FROM context_switches s
+                 WHERE s.timestamp >= ?1 AND s.timestamp < ?2
+             ),
+             numbered AS (
+                 SELECT *, SUM(to_focus) OVER (ORDER BY timestamp ROWS UNBOUNDED PRECEDING) AS returns
+                 FROM classified
+             )
+             SELECT COUNT(*), COUNT(back.recovery), COALESCE(SUM(back.recovery), 0)
+             FROM numbered away
+             LEFT JOIN numbered back ON back.to_focus = 1 AND back.returns = away.returns + 1
+             WHERE away.from_focus = 1 AND away.to_focus = 0",
+            [start.timestamp(), end.timestamp()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok((away as usize, measured as usize, Duration::from_secs(seconds.max(0) as u64)))
+    }
+
+    /// Total tracked seconds per (app, tab title) since `since`
+    pub fn get_usage_since(&self, since: DateTime<Utc>) -> SqliteResult<Vec<(String, Option<String>, Duration)>> {
+        self.query_usage(since, None)
+    }
+
+    /// Time per (app, domain) for sessions starting in [start, end)
+    pub fn get_usage_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> SqliteResult<Vec<(String, Option<String>, Duration)>> {
+        let _timer = timing::scope(Phase::Query);
+        self.query_usage(start, Some(end))
+    }
+
+    fn query_usage(&self, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> SqliteResult<Vec<(String, Option<String>, Duration)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT app_name, domain, SUM(duration_seconds)
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND (?2 IS NULL OR start_time < ?2)
+             AND duration_seconds >= 1 AND duration_seconds <= 86400
+             GROUP BY app_name, domain"
+        )?;
+
+        let usage_iter = stmt.query_map((start.timestamp(), end.map(|end| end.timestamp())), |row| {
+            let app_name: String = row.get(0)?;
+            let domain: Option<String> = row.get(1)?;
+            let duration_seconds: i64 = row.get(2)?;
+            Ok((app_name, domain, Duration::from_secs(duration_seconds as u64)))
+        })?;
+
+        let mut usage = Vec::new();
+        for entry in usage_iter {
+            usage.push(entry?);
+        }
+        Ok(usage)
+    }
+
+    pub fn get_focus_time_since(&self, since: DateTime<Utc>) -> SqliteResult<Duration> {
+        let _timer = timing::scope(Phase::Query);
+        let seconds: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(duration_seconds), 0)
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND is_focus_app = 1
+             AND duration_seconds >= 1 AND duration_seconds <= 86400",
+            [since.timestamp()],
+            |row| row.get(0),
+        )?;
+        Ok(Duration::from_secs(seconds.max(0) as u64))
+    }
+
+    /// Days, as `days` cuts them and newest first, with any focus time
+    /// recorded
+    pub fn get_focus_days(&self, days: &LocalDays) -> SqliteResult<Vec<NaiveDate>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time
+             FROM focus_sessions
+             WHERE is_focus_app = 1
+             AND duration_seconds >= 1 AND duration_seconds <= 86400"
+        )?;
+
+        let mut focus_days = std::collections::BTreeSet::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(start) = Self::timestamp_column(row, 0)? {
+                focus_days.insert(days.day_of(start));
+            }
+        }
+        Ok(focus_days.into_iter().rev().collect())
+    }
+
+    /// Time per (day as `days` cuts it, app) for rows starting from `start`
+    /// up to `end`, leaving out rows shorter than `min_duration`
+    pub fn get_app_daily_totals(
+        &self,
+        days: &LocalDays,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        min_duration: Duration,
+    ) -> SqliteResult<Vec<(NaiveDate, String, Duration)>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time, app_name, duration_seconds
+             FROM focus_sessions
+             WHERE start_time >= ?1 AND start_time < ?2
+             AND duration_seconds >= ?3 AND duration_seconds <= 86400"
+        )?;
+
+        let mut totals: BTreeMap<(NaiveDate, String), Duration> = BTreeMap::new();
+        let mut rows = stmt.query((start.timestamp(), end.timestamp(), min_duration.as_secs() as i64))?;
+        while let Some(row) = rows.next()? {
+            let Some(start) = Self::timestamp_column(row, 0)? else {
+                continue;
+            };
+            *totals.entry((days.day_of(start), row.get(1)?)).or_default() += Duration::from_secs(row.get::<_, i64>(2)?.max(0) as u64);
+        }
+        Ok(totals.into_iter().map(|((day, app), duration)| (day, app, duration)).collect())
+    }
+
+    /// How many of the `limit` most recent other sessions had a lower focus
+    /// efficiency than `session_name`, as (beaten, compared). None when the
+    /// session has no valid rows or there is nothing to compare against.
+    pub fn get_session_efficiency_rank(&self, session_name: &str, limit: usize) -> SqliteResult<Option<(usize, usize)>> {
+        let _timer = timing::scope(Phase::Query);
+        let (beaten, compared): (Option<i64>, i64) = self.conn.query_row(
+            "WITH this AS (
+                 SELECT 100.0 * SUM(CASE WHEN is_focus_app = 1 THEN duration_seconds ELSE 0 END)
+                        / SUM(duration_seconds) AS efficiency
+                 FROM focus_sessions
+                 WHERE session_name = ?1
+                 AND duration_seconds >= 1 AND duration_seconds <= 86400
+             ),
+             recent AS (
+                 SELECT 100.0 * SUM(CASE WHEN is_focus_app = 1 THEN duration_seconds ELSE 0 END)
+                        / SUM(duration_seconds) AS efficiency,
+                        MIN(start_time) AS started
+                 FROM focus_sessions
+                 WHERE session_name != '' AND session_name != ?1
+                 AND duration_seconds >= 1 AND duration_seconds <= 86400
+                 GROUP BY session_name
+                 ORDER BY started DESC
+                 LIMIT ?2
+             )
+             SELECT SUM(recent.efficiency < this.efficiency), COUNT(*)
+             FROM recent, this
+             WHERE this.efficiency IS NOT NULL",
+            (session_name, limit as i64),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        if compared == 0 {
+            return Ok(None);
+        }
+        Ok(Some((beaten.unwrap_or(0) as usize, compared as usize)))
+    }
+
+    pub fn save_event(&self, event: &TrackerEvent) -> SqliteResult<()> {
+        let _timer = timing::scope(Phase::Write);
+        self.conn.execute(
+            "INSERT INTO events (timestamp, kind, payload) VALUES (?1, ?2, ?3)",
+            (
+                event.timestamp.timestamp(),
+                event.kind.as_str(),
+                &event.payload.to_string(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Save many context switches and events in one transaction, which is
+    /// much faster than one `save_context_switch`/`save_event` call each
+    pub fn save_switches_and_events(&self, switches: &[ContextSwitch], events: &[TrackerEvent]) -> SqliteResult<()> {
+        let _timer = timing::scope(Phase::Write);
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO context_switches (timestamp, from_app, to_app, recovery_time_seconds) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for switch in switches {
+                insert.execute((
+                    switch.timestamp.timestamp(),
+                    &switch.from_app,
+                    &switch.to_app,
+                    switch.recovery_time.map(|d| d.as_secs() as i64),
+                ))?;
+            }
+            let mut insert = tx.prepare("INSERT INTO events (timestamp, kind, payload) VALUES (?1, ?2, ?3)")?;
+            for event in events {
+                insert.execute((event.timestamp.timestamp(), event.kind.as_str(), event.payload.to_string()))?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn get_events_between(&self, start: DateTime<Utc>, end: DateTime<Utc>, kind: Option<EventKind>) -> SqliteResult<Vec<TrackerEvent>> {
+        let _timer = timing::scope(Phase::Query);
+        let kind_str = kind.map(|k| k.as_str()).unwrap_or("");
+
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp, kind, payload
+             FROM events
+             WHERE timestamp >= ?1 AND timestamp <= ?2
+             AND (?3 = '' OR kind = ?3)
+             ORDER BY timestamp, id"
+        )?;
+
+        let event_iter = stmt.query_map((start.timestamp(), end.timestamp(), kind_str), |row| {
+            let timestamp = Self::timestamp_column(row, 0)?
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid timestamp".into()))?;
+            let kind: String = row.get(1)?;
+            let payload: String = row.get(2)?;
+            let kind = EventKind::parse(&kind)
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName(format!("Unknown event kind: {}", kind)))?;
+            // A payload that no longer parses is kept as a plain string rather than dropped
+            let payload = serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload));
+
+            Ok(TrackerEvent { timestamp, kind, payload })
+        })?;
+
+        let mut events = Vec::new();
+        for event in event_iter {
+            events.push(event?);
+        }
+        Ok(events)
+    }
+
+    pub fn count_events_before(&self, before: DateTime<Utc>) -> SqliteResult<usize> {
+        self.conn.query_row(
+            "SELECT COUNT(*) FROM events WHERE timestamp < ?1",
+            [before.timestamp()],
+            |row| row.get::<_, i64>(0),
+        ).map(|count| count as usize)
+    }
+
+    /// Deletes events older than `before` in batches, reporting each batch to
+    /// `progress`. Returns how many were removed.
+    pub fn prune_events(&self, before: DateTime<Utc>, progress: &mut Progress) -> SqliteResult<usize> {
+        const BATCH_SIZE: i64 = 5000;
+        let mut removed = 0;
+        loop {
+            let deleted = self.conn.execute(
+                "DELETE FROM events WHERE id IN
+                 (SELECT id FROM events WHERE timestamp < ?1 LIMIT ?2)",
+                (before.timestamp(), BATCH_SIZE),
+            )?;
+            if deleted == 0 {
+                break;
+            }
+            removed += deleted;
+            progress.advance(deleted);
+        }
+        Ok(removed)
+    }
+
+    /// Retention: deletes sessions, context switches and events older than
+    /// `before` in one transaction. Rows of `keep_session`, and anything
+    /// recorded since it started, are never touched.
+    pub fn prune_history(&self, before: DateTime<Utc>, keep_session: &str) -> SqliteResult<RetentionReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        let session_start = tx.query_row(
+            "SELECT MIN(start_time) FROM focus_sessions WHERE session_name = ?1 COLLATE NOCASE",
+            [keep_session],
+            |row| Self::timestamp_column(row, 0),
+        )?;
+        let cutoff = session_start.map_or(before, |start| start.min(before)).timestamp();
+
+        let report = RetentionReport {
+            sessions: tx.execute(
+                "DELETE FROM focus_sessions WHERE start_time < ?1 AND session_name != ?2 COLLATE NOCASE",
+                (cutoff, keep_session),
+            )?,
+            context_switches: tx.execute("DELETE FROM context_switches WHERE timestamp < ?1", [cutoff])?,
+            events: tx.execute("DELETE FROM events WHERE timestamp < ?1", [cutoff])?,
+        };
+        let cutoff_day = utils::timezone::to_zone(session_start.map_or(before, |start| start.min(before))).date_naive();
+        tx.execute(
+            "DELETE FROM notification_counts WHERE day < ?1 AND session_name != ?2 COLLATE NOCASE",
+            (cutoff_day.format("%Y-%m-%d").to_string(), keep_session),
+        )?;
+        tx.execute(
+            "DELETE FROM session_intents WHERE started_at < ?1 AND session_name != ?2 COLLATE NOCASE",
+            (cutoff, keep_session),
+        )?;
+        tx.execute(
+            "DELETE FROM session_tasks WHERE started_at < ?1 AND session_name != ?2 COLLATE NOCASE",
+            (cutoff, keep_session),
+        )?;
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Archive files noted in the meta table by `archive_history`
+    pub fn archive_paths(&self) -> SqliteResult<Vec<PathBuf>> {
+        Ok(self.get_meta(ARCHIVES_META_KEY)?
+            .and_then(|json| serde_json::from_str::<Vec<PathBuf>>(&json).ok())
+            .unwrap_or_default())
+    }
+
+    /// Rows `archive_history` would move: rows older than `before`, except
+    /// those of `keep_session` and anything recorded since it started
+    pub fn count_archivable(&self, before: DateTime<Utc>, keep_session: Option<&str>) -> SqliteResult<RetentionReport> {
+        let cutoff = self.archive_cutoff(before, keep_session)?;
+        let count = |table: &str, column: &str| -> SqliteResult<usize> {
+            let (condition, params) = archive_filter(table, column, cutoff, keep_session);
+            self.conn.query_row(
+                &format!("SELECT COUNT(*) FROM main.{} WHERE {}", table, condition),
+                rusqlite::params_from_iter(params),
+                |row| row.get::<_, i64>(0),
+            ).map(|count| count as usize)
+        };
+        Ok(RetentionReport {
+            sessions: count("focus_sessions", "start_time")?,
+            context_switches: count("context_switches", "timestamp")?,
+            events: count("events", "timestamp")?,
+        })
+    }
+
+    /// Move sessions, context switches and events older than `before` into
+    /// the archive database at `archive`, creating it (same schema) when
+    /// missing, and note it in the meta table. Both files change in one
+    /// transaction. Rows of `keep_session`, and anything recorded since it
+    /// started, stay.
+    pub fn archive_history(&self, before: DateTime<Utc>, archive: &Path, keep_session: Option<&str>) -> SqliteResult<ArchiveReport> {
+        let cutoff = self.archive_cutoff(before, keep_session)?;
+        let archive = std::path::absolute(archive)
+            .map_err(|e| rusqlite::Error::InvalidPath(format!("{}: {}", archive.display(), e).into()))?;
+        self.conn.execute("ATTACH DATABASE ?1 AS archive", [archive.to_string_lossy()])?;
+        let result = self.move_to_archive(cutoff, &archive, keep_session);
+        let detached = self.conn.execute_batch("DETACH DATABASE archive");
+        let report = result?;
+        detached?;
+        Ok(report)
+    }
+
+    fn archive_cutoff(&self, before: DateTime<Utc>, keep_session: Option<&str>) -> SqliteResult<i64> {
+        let Some(keep_session) = keep_session else {
+            return Ok(before.timestamp());
+        };
+        let session_start = self.conn.query_row(
+            "SELECT MIN(start_time) FROM main.focus_sessions WHERE session_name = ?1 COLLATE NOCASE",
+            [keep_session],
+            |row| Self::timestamp_column(row, 0),
+        )?;
+        Ok(session_start.map_or(before, |start| start.min(before)).timestamp())
+    }
+
+    fn move_to_archive(&self, cutoff: i64, archive: &Path, keep_session: Option<&str>) -> SqliteResult<ArchiveReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute_batch("CREATE TABLE IF NOT EXISTS archive.meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)")?;
+        tx.execute(
+            "INSERT OR REPLACE INTO archive.meta (key, value) VALUES ('schema_version', ?1)",
+            [SCHEMA_VERSION.to_string()],
+        )?;
+
+        let mut report = ArchiveReport::default();
+        for (table, column) in ARCHIVED_TABLES {
+            Self::copy_table_schema(&tx, table, "archive")?;
+            // The id is left to the archive so rows of several runs don't collide
+            let columns = Self::table_columns(&tx, "main", table)?
+                .into_iter()
+                .filter(|name| name != "id")
+                .collect::<Vec<_>>()
+                .join(", ");
+            let (condition, params) = archive_filter(table, column, cutoff, keep_session);
+            // Only rows the archive already has are skipped; OR IGNORE would
+            // also drop rows failing NOT NULL, which the DELETE then loses
+            let copied = tx.execute(
+                &format!("INSERT INTO archive.{table} ({columns}) SELECT {columns} FROM main.{table} WHERE {condition} ON CONFLICT DO NOTHING"),
+                rusqlite::params_from_iter(&params),
+            )?;
+            let moved = tx.execute(
+                &format!("DELETE FROM main.{} WHERE {}", table, condition),
+                rusqlite::params_from_iter(&params),
+            )?;
+            report.already_archived += moved - copied;
+            match table {
+                "focus_sessions" => report.moved.sessions = moved,
+                "context_switches" => report.moved.context_switches = moved,
+                _ => report.moved.events = moved,
+            }
+        }
+
+        let mut archives = self.archive_paths()?;
+        if !archives.iter().any(|path| path == archive) {
+            archives.push(archive.to_path_buf());
+            let json = serde_json::to_string(&archives)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            tx.execute("INSERT OR REPLACE INTO main.meta (key, value) VALUES (?1, ?2)", [ARCHIVES_META_KEY, &json])?;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Create `table` and its indexes in the attached `schema` as they are in
+    /// main, or add the columns an older archive of it lacks
+    fn copy_table_schema(conn: &Connection, table: &str, schema: &str) -> SqliteResult<()> {
+        let existing = Self::table_columns(conn, schema, table)?;
+        if existing.is_empty() {
+            let sql: String = conn.query_row(
+                "SELECT sql FROM main.sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )?;
+            conn.execute_batch(&sql.replacen("CREATE TABLE ", &format!("CREATE TABLE {}.", schema), 1))?;
+        } else {
+            let mut stmt = conn.prepare(&format!("SELECT name, type, dflt_value FROM pragma_table_info('{}', 'main')", table))?;
+            let columns = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+            })?.collect::<SqliteResult<Vec<_>>>()?;
+            for (name, kind, default) in columns.into_iter().filter(|(name, _, _)| !existing.contains(name)) {
+                let default = default.map(|value| format!(" DEFAULT {}", value)).unwrap_or_default();
+                conn.execute_batch(&format!("ALTER TABLE {}.{} ADD COLUMN {} {}{}", schema, table, name, kind, default))?;
+            }
+        }
+
+        // Indexes an upgrade dropped from main go from the archive too, or
+        // it would keep refusing rows main accepts
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name FROM {}.sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL
+               AND name NOT IN (SELECT name FROM main.sqlite_master WHERE type = 'index')",
+            schema,
+        ))?;
+        let dropped = stmt.query_map([table], |row| row.get::<_, String>(0))?.collect::<SqliteResult<Vec<_>>>()?;
+        for name in dropped {
+            conn.execute_batch(&format!("DROP INDEX {}.{}", schema, name))?;
+        }
+
+        let mut stmt = conn.prepare("SELECT sql FROM main.sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL")?;
+        let indexes = stmt.query_map([table], |row| row.get::<_, String>(0))?.collect::<SqliteResult<Vec<_>>>()?;
+        for sql in indexes {
+            let sql = sql
+                .replacen("CREATE UNIQUE INDEX IF NOT EXISTS ", "CREATE UNIQUE INDEX ", 1)
+                .replacen("CREATE INDEX IF NOT EXISTS ", "CREATE INDEX ", 1)
+                .replacen("CREATE UNIQUE INDEX ", &format!("CREATE UNIQUE INDEX IF NOT EXISTS {}.", schema), 1)
+                .replacen("CREATE INDEX ", &format!("CREATE INDEX IF NOT EXISTS {}.", schema), 1);
+            conn.execute_batch(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// Column names of `schema.table`, empty when it doesn't exist
+    fn table_columns(conn: &Connection, schema: &str, table: &str) -> SqliteResult<Vec<String>> {
+        let mut stmt = conn.prepare(&format!("SELECT name FROM pragma_table_info('{}', '{}')", table, schema))?;
+        let columns = stmt.query_map([], |row| row.get(0))?.collect();
+        columns
+    }
+
+    /// Attach the noted archives and shadow the archived tables with temp
+    /// views of the live rows and the archived ones, so every read sees
+    /// both. The connection is read-only, and so is everything attached to
+    /// it. Archives that went missing are skipped with a warning.
+    fn attach_archives(&self) -> SqliteResult<()> {
+        let mut attached = Vec::new();
+        for path in self.archive_paths()? {
+            if !path.exists() {
+                eprintln!("❌ Archive {} is missing; reporting without it", path.display());
+                continue;
+            }
+            let schema = format!("archive_{}", attached.len());
+            self.conn.execute(&format!("ATTACH DATABASE ?1 AS {}", schema), [path.to_string_lossy()])?;
+            attached.push(schema);
+        }
+
+        for (table, _) in ARCHIVED_TABLES {
+            let columns = Self::table_columns(&self.conn, "main", table)?;
+            let mut selects = vec![format!("SELECT {} FROM main.{}", columns.join(", "), table)];
+            for schema in &attached {
+                let present = Self::table_columns(&self.conn, schema, table)?;
+                if present.is_empty() {
+                    continue;
+                }
+                // Columns added after the archive was written read as NULL
+                let list = columns.iter()
+                    .map(|name| if present.contains(name) { name.clone() } else { format!("NULL AS {}", name) })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                selects.push(format!("SELECT {} FROM {}.{}", list, schema, table));
+            }
+            self.conn.execute_batch(&format!("CREATE TEMP VIEW {} AS {}", table, selects.join(" UNION ALL ")))?;
+        }
+        Ok(())
+    }
+
     pub fn get_most_recent_session_name(&self) -> SqliteResult<Option<String>> {
         let mut stmt = self.conn.prepare(
             "SELECT session_name 
@@ -377,8 +1963,8 @@ impl Database {
     pub fn session_name_exists(&self, session_name: &str) -> SqliteResult<bool> {
         let mut stmt = self.conn.prepare(
             "SELECT COUNT(*) 
-             FROM focus_sessions 
-             WHERE session_name = ?1"
+             FROM sessions 
+             WHERE name = ?1"
         )?;
 
         let count: i64 = stmt.query_row([session_name], |row| {
@@ -388,38 +1974,2621 @@ impl Database {
         Ok(count > 0)
     }
 
-    // Database cleanup and maintenance methods
-    pub fn clear_all_data(&self) -> SqliteResult<()> {
-        self.conn.execute("DELETE FROM focus_sessions", [])?;
-        self.conn.execute("DELETE FROM context_switches", [])?;
-        self.conn.execute("DELETE FROM focus_apps", [])?;
-        println!("~=~ All data cleared from database");
-        Ok(())
+    /// Give the rows recorded without a session name in [start, end) the
+    /// name `session_name`. None when the name is taken and reuse isn't
+    /// allowed (nothing is changed), otherwise the number of rows named.
+    pub fn adopt_unnamed_rows(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        session_name: &str,
+        allow_duplicate: bool,
+    ) -> SqliteResult<Option<usize>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let adopted = tx.execute(
+            "UPDATE focus_sessions SET session_name = ?1
+             WHERE session_name = '' AND start_time >= ?2 AND start_time < ?3",
+            (session_name, start.timestamp(), end.timestamp()),
+        )?;
+        if adopted == 0 {
+            return Ok(Some(0));
+        }
+        if !self.claim_session_name(session_name, allow_duplicate)? {
+            return Ok(None);
+        }
+        tx.commit()?;
+        Ok(Some(adopted))
+    }
+
+    /// Atomically reserve a session name. Returns false if it is already
+    /// taken, unless `allow_duplicate` is set, in which case reuse is fine.
+    pub fn claim_session_name(&self, session_name: &str, allow_duplicate: bool) -> SqliteResult<bool> {
+        let sql = if allow_duplicate {
+            "INSERT OR IGNORE INTO sessions (name, started_at) VALUES (?1, ?2)"
+        } else {
+            "INSERT INTO sessions (name, started_at) VALUES (?1, ?2)"
+        };
+
+        match self.conn.execute(sql, (session_name, &Utc::now().to_rfc3339())) {
+            Ok(_) => Ok(true),
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::ConstraintViolation => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
-    pub fn clear_invalid_sessions(&self) -> SqliteResult<usize> {
-        // Remove sessions with invalid durations (likely from broken tracking)
-        let invalid_sessions = self.conn.execute(
-            "DELETE FROM focus_sessions WHERE duration_seconds > 86400 OR duration_seconds < 1",
-            []
+    /// Record the settings `session_name` is tracked with. Only the first
+    /// snapshot of a session is kept, so a session resumed after a config
+    /// change still shows what it started with.
+    pub fn save_session_meta(&self, session_name: &str, recorded_at: DateTime<Utc>, snapshot: &SessionSnapshot) -> SqliteResult<()> {
+        let text = serde_json::to_string(snapshot)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let hash = snapshot_hash(&text);
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("INSERT OR IGNORE INTO config_snapshots (hash, snapshot) VALUES (?1, ?2)", (&hash, &text))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO session_meta (session_name, recorded_at, snapshot_hash) VALUES (?1, ?2, ?3)",
+            (session_name, &recorded_at.to_rfc3339(), &hash),
         )?;
-        
-        // Remove sessions without proper end times
-        let incomplete_sessions = self.conn.execute(
-            "DELETE FROM focus_sessions WHERE end_time IS NULL",
-            []
+        tx.commit()
+    }
+
+    /// The snapshot recorded for `session_name`; None for sessions tracked
+    /// before snapshots existed, or by a database that predates the tables
+    pub fn get_session_meta(&self, session_name: &str) -> SqliteResult<Option<SessionMeta>> {
+        let _timer = timing::scope(Phase::Query);
+        let row: Option<(String, String, String)> = match self.conn.query_row(
+            "SELECT m.recorded_at, m.snapshot_hash, s.snapshot
+             FROM session_meta m JOIN config_snapshots s ON s.hash = m.snapshot_hash
+             WHERE m.session_name = ?1",
+            [session_name],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ) {
+            Ok(row) => Some(row),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            // A read-only open of an older database doesn't create the tables
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => None,
+            Err(e) => return Err(e),
+        };
+        let Some((recorded_at, hash, text)) = row else {
+            return Ok(None);
+        };
+        let recorded_at = DateTime::parse_from_rfc3339(&recorded_at)
+            .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid recorded_at".into()))?
+            .with_timezone(&Utc);
+        let snapshot = serde_json::from_str(&text)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?;
+        Ok(Some(SessionMeta { recorded_at, hash, snapshot }))
+    }
+
+    /// Delete every row of `session_name` along with its name claim,
+    /// snapshot link and tags. Context switches belong to no session and
+    /// stay. Returns the number of rows deleted.
+    pub fn delete_session(&self, session_name: &str) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let rows = tx.execute("DELETE FROM focus_sessions WHERE session_name = ?1 COLLATE NOCASE", [session_name])?;
+        tx.execute("DELETE FROM sessions WHERE name = ?1", [session_name])?;
+        tx.execute("DELETE FROM session_meta WHERE session_name = ?1", [session_name])?;
+        tx.execute("DELETE FROM session_tags WHERE session_name = ?1", [session_name])?;
+        tx.execute("DELETE FROM notification_counts WHERE session_name = ?1", [session_name])?;
+        tx.execute("DELETE FROM session_intents WHERE session_name = ?1", [session_name])?;
+        tx.execute("DELETE FROM session_tasks WHERE session_name = ?1", [session_name])?;
+        tx.commit()?;
+        Ok(rows)
+    }
+
+    /// Give the rows of session `from` the name `to`, moving its name
+    /// claim, snapshot link and tags along. None when another session has
+    /// `to` already (nothing is changed), otherwise the number of rows renamed.
+    pub fn rename_session(&self, from: &str, to: &str) -> SqliteResult<Option<usize>> {
+        let tx = self.conn.unchecked_transaction()?;
+        // Names are unique regardless of case, so only a case change may reuse one
+        if !from.eq_ignore_ascii_case(to) && self.session_name_exists(to)? {
+            return Ok(None);
+        }
+        let rows = tx.execute("UPDATE focus_sessions SET session_name = ?2 WHERE session_name = ?1 COLLATE NOCASE", (from, to))?;
+        tx.execute("UPDATE sessions SET name = ?2 WHERE name = ?1", (from, to))?;
+        tx.execute("UPDATE session_meta SET session_name = ?2 WHERE session_name = ?1", (from, to))?;
+        tx.execute("UPDATE session_tags SET session_name = ?2 WHERE session_name = ?1", (from, to))?;
+        tx.execute("UPDATE notification_counts SET session_name = ?2 WHERE session_name = ?1", (from, to))?;
+        tx.execute("UPDATE session_intents SET session_name = ?2 WHERE session_name = ?1", (from, to))?;
+        tx.execute("UPDATE session_tasks SET session_name = ?2 WHERE session_name = ?1", (from, to))?;
+        tx.commit()?;
+        Ok(Some(rows))
+    }
+
+    /// Count one notification from `source` towards `session_name` on `day`
+    pub fn add_notification(&self, session_name: &str, day: NaiveDate, source: &str) -> SqliteResult<()> {
+        let _timer = timing::scope(Phase::Write);
+        self.conn.execute(
+            "INSERT INTO notification_counts (session_name, day, source, count) VALUES (?1, ?2, ?3, 1)
+             ON CONFLICT (session_name, day, source) DO UPDATE SET count = count + 1",
+            (session_name, day.format("%Y-%m-%d").to_string(), source),
         )?;
-        
-        let total_deleted = invalid_sessions + incomplete_sessions;
-        if total_deleted > 0 {
-            println!("~=~ Cleaned up {} invalid sessions", total_deleted);
+        Ok(())
+    }
+
+    /// Notifications `session_name` received, by source
+    pub fn get_session_notifications(&self, session_name: &str) -> SqliteResult<Vec<(String, usize)>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = match self.conn.prepare(
+            "SELECT source, SUM(count) FROM notification_counts WHERE session_name = ?1 COLLATE NOCASE GROUP BY source",
+        ) {
+            Ok(stmt) => stmt,
+            // A read-only open of an older database doesn't create the table
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([session_name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize)))?;
+        rows.collect()
+    }
+
+    /// Notifications received on each local day from `start` to `end`
+    /// (exclusive), by source; days without any are left out
+    pub fn get_notifications_by_day(&self, start: NaiveDate, end: NaiveDate) -> SqliteResult<BTreeMap<NaiveDate, Vec<(String, usize)>>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = match self.conn.prepare(
+            "SELECT day, source, SUM(count) FROM notification_counts WHERE day >= ?1 AND day < ?2 GROUP BY day, source",
+        ) {
+            Ok(stmt) => stmt,
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map(
+            (start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string()),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)? as usize)),
+        )?;
+        let mut days: BTreeMap<NaiveDate, Vec<(String, usize)>> = BTreeMap::new();
+        for row in rows {
+            let (day, source, count) = row?;
+            if let Ok(day) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") {
+                days.entry(day).or_default().push((source, count));
+            }
         }
-        Ok(total_deleted)
+        Ok(days)
     }
 
-    pub fn vacuum_database(&self) -> SqliteResult<()> {
-        self.conn.execute("VACUUM", [])?;
-        println!("~=~ Database vacuumed and optimized");
+    /// Record what `session_name`, started at `started_at`, is meant to
+    /// get done and how long that should take
+    pub fn set_session_intent(&self, session_name: &str, started_at: DateTime<Utc>, intent: Option<&str>, expected: Option<Duration>) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO session_intents (session_name, started_at, intent, expected_seconds) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (session_name) DO UPDATE SET intent = excluded.intent, expected_seconds = excluded.expected_seconds",
+            (session_name, started_at.timestamp(), intent, expected.map(|expected| expected.as_secs() as i64)),
+        )?;
+        Ok(())
+    }
+
+    /// Rate how `session_name` went, 1 to 5, and whether its intent was met.
+    /// A session started without an intent gets a row dated by its first row.
+    pub fn set_session_outcome(&self, session_name: &str, rating: u8, intent_met: Option<bool>) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO session_intents (session_name, started_at, rating, intent_met)
+             VALUES (?1, COALESCE((SELECT MIN(start_time) FROM focus_sessions WHERE session_name = ?1 COLLATE NOCASE), ?4), ?2, ?3)
+             ON CONFLICT (session_name) DO UPDATE SET rating = excluded.rating, intent_met = excluded.intent_met",
+            (session_name, rating, intent_met, Utc::now().timestamp()),
+        )?;
+        Ok(())
+    }
+
+    /// Intent and outcome of every session that has either, by session name
+    pub fn get_session_intents(&self) -> SqliteResult<BTreeMap<String, SessionIntent>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = match self.conn.prepare(
+            "SELECT session_name, started_at, intent, expected_seconds, rating, intent_met FROM session_intents",
+        ) {
+            Ok(stmt) => stmt,
+            // A read-only open of an older database doesn't create the table
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, SessionIntent {
+                started_at: Self::timestamp_column(row, 1)?.unwrap_or_default(),
+                intent: row.get(2)?,
+                expected: row.get::<_, Option<i64>>(3)?.map(|seconds| Duration::from_secs(seconds.max(0) as u64)),
+                rating: row.get(4)?,
+                intent_met: row.get(5)?,
+            }))
+        })?;
+        rows.collect()
+    }
+
+    /// `get_session_intents` for one session
+    pub fn get_session_intent(&self, session_name: &str) -> SqliteResult<Option<SessionIntent>> {
+        Ok(self.get_session_intents()?.into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(session_name))
+            .map(|(_, intent)| intent))
+    }
+
+    /// Link `session_name` to `task`, replacing any task it had
+    pub fn set_session_task(&self, session_name: &str, task: &SessionTask) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO session_tasks (session_name, started_at, source, task_id, uuid, description)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (session_name, task.started_at.timestamp(), task.source.as_str(), &task.id, &task.uuid, &task.description),
+        )?;
         Ok(())
     }
-} 
\ No newline at end of file
+
+    /// The task of every session started on one, by session name
+    pub fn get_session_tasks(&self) -> SqliteResult<BTreeMap<String, SessionTask>> {
+        let _timer = timing::scope(Phase::Query);
+        let mut stmt = match self.conn.prepare(
+            "SELECT session_name, started_at, source, task_id, uuid, description FROM session_tasks",
+        ) {
+            Ok(stmt) => stmt,
+            // A read-only open of an older database doesn't create the table
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, Self::timestamp_column(row, 1)?.unwrap_or_default(), row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?, row.get::<_, Option<String>>(4)?, row.get::<_, Option<String>>(5)?))
+        })?;
+        let mut tasks = BTreeMap::new();
+        for row in rows {
+            let (session_name, started_at, source, id, uuid, description) = row?;
+            // Sources a later version added are left out rather than misread
+            if let Some(source) = TaskSource::parse(&source) {
+                tasks.insert(session_name, SessionTask { started_at, source, id, uuid, description });
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// `get_session_tasks` for one session
+    pub fn get_session_task(&self, session_name: &str) -> SqliteResult<Option<SessionTask>> {
+        Ok(self.get_session_tasks()?.into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(session_name))
+            .map(|(_, task)| task))
+    }
+
+    /// Tags of every tagged session, by session name, each list sorted
+    pub fn get_session_tags(&self) -> SqliteResult<BTreeMap<String, Vec<String>>> {
+        let mut stmt = match self.conn.prepare("SELECT session_name, tag FROM session_tags ORDER BY session_name, tag") {
+            Ok(stmt) => stmt,
+            // A read-only open of an older database doesn't create the table
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for row in rows {
+            let (session_name, tag) = row?;
+            tags.entry(session_name).or_default().push(tag);
+        }
+        Ok(tags)
+    }
+
+    /// Replace the tags of `session_name`; an empty list clears them
+    pub fn set_session_tags(&self, session_name: &str, tags: &[String]) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM session_tags WHERE session_name = ?1", [session_name])?;
+        for tag in tags {
+            tx.execute("INSERT OR IGNORE INTO session_tags (session_name, tag) VALUES (?1, ?2)", (session_name, tag))?;
+        }
+        tx.commit()
+    }
+
+    /// Hold `session` back from focus_sessions until `database quarantine`
+    /// restores or drops it
+    pub fn quarantine_session(&self, session: &FocusSession, reason: &str) -> SqliteResult<()> {
+        let row = serde_json::to_string(session)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT INTO quarantined_sessions (quarantined_at, reason, start_time, row) VALUES (?1, ?2, ?3, ?4)",
+            (&Utc::now().to_rfc3339(), reason, &session.start_time.to_rfc3339(), &row),
+        )?;
+        Ok(())
+    }
+
+    /// Quarantined rows, earliest first; none for a database that predates
+    /// the table when opened read-only
+    pub fn get_quarantined_sessions(&self) -> SqliteResult<Vec<QuarantinedSession>> {
+        let mut stmt = match self.conn.prepare(
+            "SELECT reason, row FROM quarantined_sessions ORDER BY start_time, id"
+        ) {
+            Ok(stmt) => stmt,
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) if message.starts_with("no such table") => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let rows = stmt.query_map([], |row| {
+            let text: String = row.get(1)?;
+            Ok(QuarantinedSession {
+                reason: row.get(0)?,
+                session: serde_json::from_str(&text)
+                    .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Move every quarantined row into focus_sessions as recorded; returns
+    /// how many moved
+    pub fn restore_quarantined_sessions(&self) -> SqliteResult<usize> {
+        let quarantined = self.get_quarantined_sessions()?;
+        let tx = self.conn.unchecked_transaction()?;
+        for entry in &quarantined {
+            self.save_focus_session(&entry.session)?;
+        }
+        tx.execute("DELETE FROM quarantined_sessions", [])?;
+        tx.commit()?;
+        Ok(quarantined.len())
+    }
+
+    /// Delete every quarantined row; returns how many went
+    pub fn drop_quarantined_sessions(&self) -> SqliteResult<usize> {
+        self.conn.execute("DELETE FROM quarantined_sessions", [])
+    }
+
+    /// Rows in focus_sessions starting after `after`, from before the clock
+    /// check existed
+    pub fn count_sessions_after(&self, after: DateTime<Utc>) -> SqliteResult<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM focus_sessions WHERE start_time > ?1",
+            [after.timestamp()],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Result of SQLite's quick_check: "ok", or the problems it found
+    pub fn quick_check(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA quick_check")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Store a planned session; returns its id
+    pub fn add_planned_session(&self, name: &str, start_time: DateTime<Utc>, duration: Duration) -> SqliteResult<i64> {
+        self.conn.execute(
+            "INSERT INTO planned_sessions (name, start_time, duration_seconds) VALUES (?1, ?2, ?3)",
+            (name, &start_time.to_rfc3339(), duration.as_secs() as i64),
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Plans starting in [start, end), or from `start` on without an end,
+    /// earliest first
+    pub fn get_planned_sessions(&self, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> SqliteResult<Vec<PlannedSession>> {
+        let end_str = end.map(|end| end.to_rfc3339()).unwrap_or_default();
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, start_time, duration_seconds, started_at, completed_at
+             FROM planned_sessions
+             WHERE start_time >= ?1 AND (?2 = '' OR start_time < ?2)
+             ORDER BY start_time, id"
+        )?;
+        let plans = stmt.query_map([&start.to_rfc3339(), &end_str], Self::plan_from_row)?;
+        plans.collect()
+    }
+
+    /// The earliest plan not started yet whose window contains `now`
+    pub fn due_planned_session(&self, now: DateTime<Utc>) -> SqliteResult<Option<PlannedSession>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, start_time, duration_seconds, started_at, completed_at
+             FROM planned_sessions
+             WHERE started_at IS NULL AND start_time <= ?1
+             ORDER BY start_time, id"
+        )?;
+        let plans = stmt.query_map([&now.to_rfc3339()], Self::plan_from_row)?;
+        for plan in plans {
+            let plan = plan?;
+            if plan.end_time() > now {
+                return Ok(Some(plan));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn mark_plan_started(&self, id: i64, at: DateTime<Utc>) -> SqliteResult<()> {
+        self.conn.execute("UPDATE planned_sessions SET started_at = ?1 WHERE id = ?2", (&at.to_rfc3339(), id))?;
+        Ok(())
+    }
+
+    pub fn mark_plan_completed(&self, id: i64, at: DateTime<Utc>) -> SqliteResult<()> {
+        self.conn.execute("UPDATE planned_sessions SET completed_at = ?1 WHERE id = ?2", (&at.to_rfc3339(), id))?;
+        Ok(())
+    }
+
+    /// Columns: id, name, start_time, duration_seconds, started_at, completed_at
+    fn plan_from_row(row: &rusqlite::Row) -> SqliteResult<PlannedSession> {
+        let timestamp = |index: usize| -> SqliteResult<Option<DateTime<Utc>>> {
+            row.get::<_, Option<String>>(index)?
+                .map(|t| DateTime::parse_from_rfc3339(&t)
+                    .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid plan timestamp".into()))
+                    .map(|dt| dt.with_timezone(&Utc)))
+                .transpose()
+        };
+        let duration_seconds: i64 = row.get(3)?;
+        Ok(PlannedSession {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            start_time: timestamp(2)?
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid start_time".into()))?,
+            duration: Duration::from_secs(duration_seconds.max(0) as u64),
+            started_at: timestamp(4)?,
+            completed_at: timestamp(5)?,
+        })
+    }
+
+    // Database cleanup and maintenance methods
+    pub fn clear_all_data(&self) -> SqliteResult<()> {
+        // All or nothing, so a failure can't leave tags or metadata behind
+        // for sessions that are gone
+        let tx = self.conn.unchecked_transaction()?;
+        for table in CLEARED_TABLES {
+            tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        tx.commit()
+    }
+
+    /// Removes (or, with `dry_run`, only counts) invalid session rows.
+    ///
+    /// Each category is handled by its own statement and excludes the rows
+    /// matched by the categories before it, so a row is only counted once.
+    pub fn cleanup_sessions(&self, dry_run: bool) -> SqliteResult<CleanupReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut report = CleanupReport::default();
+        let mut previous: Vec<&str> = Vec::new();
+
+        for (category, condition) in CLEANUP_CATEGORIES {
+            let mut filter = condition.to_string();
+            for earlier in &previous {
+                filter.push_str(&format!(" AND NOT ({})", earlier));
+            }
+
+            let count = if dry_run {
+                tx.query_row(
+                    &format!("SELECT COUNT(*) FROM focus_sessions WHERE {}", filter),
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )? as usize
+            } else {
+                tx.execute(&format!("DELETE FROM focus_sessions WHERE {}", filter), [])?
+            };
+
+            report.set(category, count);
+            previous.push(condition);
+        }
+
+        if !dry_run {
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+
+    /// Remove (or, with `dry_run`, only count) rows that repeat another row's
+    /// identity (session, start, app, title and duration), keeping the
+    /// oldest row of each group.
+    /// Returns what was removed per UTC day.
+    pub fn dedupe_sessions(&self, dry_run: bool) -> SqliteResult<Vec<DedupeDay>> {
+        Self::dedupe_rows(&self.conn, dry_run)
+    }
+
+    fn dedupe_rows(conn: &Connection, dry_run: bool) -> SqliteResult<Vec<DedupeDay>> {
+        // Both subqueries are materialized once, so this stays linear
+        let duplicates = format!(
+            "SELECT id, start_time, duration_seconds FROM focus_sessions
+             WHERE ({identity}) IN
+                   (SELECT {identity} FROM focus_sessions GROUP BY {identity} HAVING COUNT(*) > 1)
+               AND id NOT IN
+                   (SELECT MIN(id) FROM focus_sessions GROUP BY {identity} HAVING COUNT(*) > 1)",
+            identity = ROW_IDENTITY,
+        );
+
+        let tx = conn.unchecked_transaction()?;
+        let mut days: Vec<DedupeDay> = Vec::new();
+        {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT date(start_time, 'unixepoch'), COUNT(*), SUM(duration_seconds) FROM ({}) GROUP BY 1 ORDER BY 1",
+                duplicates
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+            })?;
+            for row in rows {
+                let (day, count, seconds) = row?;
+                let Ok(day) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") else { continue };
+                days.push(DedupeDay { day, rows: count as usize, duration: Duration::from_secs(seconds.max(0) as u64) });
+            }
+        }
+
+        if !dry_run && !days.is_empty() {
+            tx.execute(&format!("DELETE FROM focus_sessions WHERE id IN (SELECT id FROM ({}))", duplicates), [])?;
+            tx.commit()?;
+        }
+        Ok(days)
+    }
+
+    /// Delete sessions recorded for terminals showing focusdebt's own CLI,
+    /// from before `self_ignore` kept them out. `rule_terminals` are the
+    /// apps terminal title rules are for. Returns how many rows matched.
+    pub fn cleanup_self_sessions(&self, rule_terminals: &[String], dry_run: bool) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let ids: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id, app_name, window_title FROM focus_sessions")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+            let mut ids = Vec::new();
+            for row in rows {
+                let (id, app_name, window_title) = row?;
+                if utils::is_self_window(&app_name, &window_title, rule_terminals) {
+                    ids.push(id);
+                }
+            }
+            ids
+        };
+
+        if !dry_run {
+            let mut stmt = tx.prepare("DELETE FROM focus_sessions WHERE id = ?1")?;
+            for id in &ids {
+                stmt.execute([id])?;
+            }
+            drop(stmt);
+            tx.commit()?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Rewrite every session's is_focus_app and focus_rule from `classify`;
+    /// ignored sessions are stored as distractions. With `dry_run` only the
+    /// counts are returned.
+    pub fn reclassify_sessions(
+        &self,
+        classify: impl Fn(&FocusSession) -> (SessionClass, RuleMatch),
+        dry_run: bool,
+    ) -> SqliteResult<ReclassifyReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut report = ReclassifyReport::default();
+        let mut changes: Vec<(i64, bool, String)> = Vec::new();
+        {
+            let mut stmt = tx.prepare(
+                "SELECT start_time, end_time, app_name, window_title, domain, duration_seconds, is_focus_app, session_name, activity, passive_seconds, workspace, power, source, focus_rule, co_focus, id
+                 FROM focus_sessions"
+            )?;
+            let rows = stmt.query_map([], |row| Ok((Self::session_from_row(row)?, row.get::<_, i64>(15)?)))?;
+            for row in rows {
+                let (session, id) = row?;
+                let (class, rule) = classify(&session);
+                if class == SessionClass::Ignored {
+                    report.ignored += 1;
+                }
+                let is_focus = class == SessionClass::Focus;
+                match (session.is_focus_app, is_focus) {
+                    (false, true) => report.to_focus += 1,
+                    (true, false) => report.to_distraction += 1,
+                    _ if session.focus_rule.as_ref() == Some(&rule) => continue,
+                    _ => report.rules_recorded += 1,
+                }
+                changes.push((id, is_focus, rule.to_column()));
+            }
+        }
+
+        if !dry_run {
+            let mut stmt = tx.prepare("UPDATE focus_sessions SET is_focus_app = ?1, focus_rule = ?2 WHERE id = ?3")?;
+            for (id, is_focus, rule) in &changes {
+                stmt.execute((is_focus, rule, id))?;
+            }
+            drop(stmt);
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+
+    /// Cut stored window titles longer than `max_title_length` characters,
+    /// in session rows and window change events, the way the tracker cuts
+    /// new ones. With `dry_run` only the counts are returned.
+    pub fn trim_titles(&self, max_title_length: usize, dry_run: bool) -> SqliteResult<TrimTitlesReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut report = TrimTitlesReport::default();
+
+        // length() counts characters, so short titles never leave SQLite
+        let sessions: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare("SELECT id, window_title FROM focus_sessions WHERE length(window_title) > ?1")?;
+            let rows = stmt.query_map([max_title_length as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+        let mut trimmed_sessions = Vec::new();
+        for (id, title) in sessions {
+            let cut = utils::cap_title(title.clone(), max_title_length);
+            report.bytes_cut += title.len().saturating_sub(cut.len()) as u64;
+            trimmed_sessions.push((id, cut));
+        }
+        report.sessions = trimmed_sessions.len();
+
+        let events: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare("SELECT id, payload FROM events WHERE kind = ?1 AND length(payload) > ?2")?;
+            let rows = stmt.query_map((EventKind::WindowChange.as_str(), max_title_length as i64), |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+        let mut trimmed_events = Vec::new();
+        for (id, payload) in events {
+            let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&payload) else { continue };
+            let Some(title) = value.get("title").and_then(|title| title.as_str()) else { continue };
+            if title.chars().count() <= max_title_length {
+                continue;
+            }
+            value["title"] = serde_json::Value::String(utils::cap_title(title.to_string(), max_title_length));
+            let rewritten = value.to_string();
+            report.bytes_cut += payload.len().saturating_sub(rewritten.len()) as u64;
+            trimmed_events.push((id, rewritten));
+        }
+        report.events = trimmed_events.len();
+
+        if !dry_run {
+            {
+                let mut stmt = tx.prepare("UPDATE focus_sessions SET window_title = ?1 WHERE id = ?2")?;
+                for (id, title) in &trimmed_sessions {
+                    stmt.execute((title, id))?;
+                }
+                let mut stmt = tx.prepare("UPDATE events SET payload = ?1 WHERE id = ?2")?;
+                for (id, payload) in &trimmed_events {
+                    stmt.execute((payload, id))?;
+                }
+            }
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+
+    pub fn vacuum_database(&self) -> SqliteResult<OptimizeReport> {
+        let size_before = self.file_size();
+        self.conn.execute("VACUUM", [])?;
+        let size_after = self.file_size();
+        let analyzed = match self.conn.execute_batch("ANALYZE") {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("❌ ANALYZE failed: {}", e);
+                false
+            }
+        };
+
+        Ok(OptimizeReport {
+            size_before,
+            size_after,
+            analyzed,
+        })
+    }
+
+    fn file_size(&self) -> Option<u64> {
+        self.conn.path()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+    }
+
+    /// Let the database grow by only `pages` more pages (None for no cap),
+    /// so writes past them fail with SQLITE_FULL as on a full disk
+    #[cfg(test)]
+    pub fn limit_growth(&self, pages: Option<u32>) -> SqliteResult<()> {
+        let in_use: i64 = self.conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let cap = pages.map_or(1_073_741_823, |pages| in_use + pages as i64);
+        self.conn.query_row(&format!("PRAGMA max_page_count = {}", cap), [], |_| Ok(()))
+    }
+}
+
+fn is_transient_lock_error(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::SqliteFailure(e, _) if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// SQLITE_FULL: the disk (or the filesystem quota) has no room for the write
+pub fn is_disk_full(error: &rusqlite::Error) -> bool {
+    error.sqlite_error_code() == Some(ErrorCode::DiskFull)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupCategory {
+    ZeroDuration,
+    OverOneDay,
+    EndBeforeStart,
+    Duplicate,
+}
+
+impl CleanupCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CleanupCategory::ZeroDuration => "Zero-duration rows",
+            CleanupCategory::OverOneDay => "Rows longer than 24h",
+            CleanupCategory::EndBeforeStart => "Rows ending before they start",
+            CleanupCategory::Duplicate => "Duplicate rows",
+        }
+    }
+}
+
+/// The time columns kept as epoch seconds, per table, the indexed one first
+const EPOCH_TIME_COLUMNS: [(&str, &[&str]); 3] = [
+    ("focus_sessions", &["start_time", "end_time"]),
+    ("context_switches", &["timestamp"]),
+    ("events", &["timestamp"]),
+];
+
+/// SQL turning the RFC 3339 text in `column` into epoch seconds. A value
+/// that doesn't parse is kept, and fails to read as before.
+fn epoch_seconds(column: &str) -> String {
+    format!("COALESCE(CAST(strftime('%s', {0}) AS INTEGER), {0})", column)
+}
+
+/// What makes a focus_sessions row itself: saving one that repeats another
+/// row's identity is skipped, and `dedupe_sessions` keeps one row of each
+const ROW_IDENTITY: &str = "start_time, app_name, window_title, session_name, duration_seconds";
+
+/// The unique index on (start_time, app_name, duration_seconds) older
+/// versions created; upgrades drop it for idx_focus_sessions_identity
+const LEGACY_DEDUPE_INDEX: &str = "idx_focus_sessions_dedupe";
+
+/// Reason recorded for the rows `merge_truncated_starts` quarantines
+const MERGED_AT_UPGRADE_REASON: &str = "saved twice within a second; merged into the first copy while upgrading the database";
+
+/// Tables `clear_all_data` empties: everything recorded, leaving only the
+/// schema's own bookkeeping (meta, view_docs)
+const CLEARED_TABLES: [&str; 15] = [
+    "focus_sessions",
+    "context_switches",
+    "focus_apps",
+    "focus_sites",
+    "events",
+    "sessions",
+    "planned_sessions",
+    "quarantined_sessions",
+    "session_meta",
+    "session_tags",
+    "config_snapshots",
+    "notification_counts",
+    "session_intents",
+    "session_tasks",
+    "daemon_versions",
+];
+
+// Order matters: a row matching several conditions is attributed to the first one.
+const CLEANUP_CATEGORIES: [(CleanupCategory, &str); 4] = [
+    (CleanupCategory::ZeroDuration, "duration_seconds < 1"),
+    (CleanupCategory::OverOneDay, "duration_seconds > 86400"),
+    (CleanupCategory::EndBeforeStart, "end_time IS NOT NULL AND end_time < start_time"),
+    (
+        CleanupCategory::Duplicate,
+        "id NOT IN (SELECT MIN(id) FROM focus_sessions
+                    GROUP BY start_time, end_time, app_name, window_title, domain,
+                             duration_seconds, is_focus_app, session_name)",
+    ),
+];
+
+/// Focus efficiency (percent) per day of (start, focus, seconds) rows,
+/// the day of a row being `day_of` its start; days in order
+fn efficiency_by_day(rows: &[(DateTime<Utc>, bool, i64)], day_of: impl Fn(DateTime<Utc>) -> NaiveDate) -> Vec<(NaiveDate, f64)> {
+    let mut days: BTreeMap<NaiveDate, (i64, i64)> = BTreeMap::new();
+    for &(start, focus, seconds) in rows {
+        let (focus_seconds, total_seconds) = days.entry(day_of(start)).or_default();
+        if focus {
+            *focus_seconds += seconds;
+        }
+        *total_seconds += seconds;
+    }
+    days.into_iter()
+        .filter(|(_, (_, total))| *total > 0)
+        .map(|(day, (focus, total))| (day, focus as f64 / total as f64 * 100.0))
+        .collect()
+}
+
+/// Rows removed by one retention run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RetentionReport {
+    pub sessions: usize,
+    pub context_switches: usize,
+    pub events: usize,
+}
+
+/// What one `DailyRetention::run` did
+#[derive(Debug)]
+pub enum RetentionRun {
+    /// Already ran this tracking day
+    NotDue,
+    /// Skipped so the export sees every row; the next call tries again
+    ExportRunning,
+    Pruned(RetentionReport),
+    /// Not retried until the next day
+    Failed(rusqlite::Error),
+}
+
+/// The daemon's retention_days: prunes history on the first call after each
+/// day boundary (day_start_hour, local time)
+#[derive(Debug)]
+pub struct DailyRetention {
+    days: u32,
+    day_start_hour: u32,
+    ran_for: Option<DateTime<Utc>>,
+}
+
+impl DailyRetention {
+    pub fn new(days: u32, day_start_hour: u32) -> Self {
+        Self { days, day_start_hour, ran_for: None }
+    }
+
+    pub fn days(&self) -> u32 {
+        self.days
+    }
+
+    /// Prune rows older than `days` before `at` if this tracking day hasn't
+    /// had its run yet; `keep_session` (the running one) is never touched
+    pub fn run(&mut self, db: &Database, keep_session: &str, at: DateTime<Utc>, export_running: bool) -> RetentionRun {
+        if !utils::daily_run_due(self.ran_for, at, self.day_start_hour) {
+            return RetentionRun::NotDue;
+        }
+        if export_running {
+            return RetentionRun::ExportRunning;
+        }
+        self.ran_for = Some(utils::day_start(at, self.day_start_hour));
+        match db.prune_history(at - chrono::Duration::days(self.days as i64), keep_session) {
+            Ok(report) => RetentionRun::Pruned(report),
+            Err(e) => RetentionRun::Failed(e),
+        }
+    }
+}
+
+/// Tables `archive_history` moves rows of, with the time column it goes by
+const ARCHIVED_TABLES: [(&str, &str); 3] = [
+    ("focus_sessions", "start_time"),
+    ("context_switches", "timestamp"),
+    ("events", "timestamp"),
+];
+
+/// Meta key holding the archive files as a JSON list of absolute paths
+const ARCHIVES_META_KEY: &str = "archives";
+
+/// Archive `database archive` writes to without `--to`, in the data directory
+pub const DEFAULT_ARCHIVE_FILE: &str = "focusdebt-archive.db";
+
+/// Whether read-only opens also read the archives (`--include-archives`)
+static INCLUDE_ARCHIVES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_include_archives(include: bool) {
+    INCLUDE_ARCHIVES.store(include, Ordering::Relaxed);
+}
+
+/// The WHERE clause picking the rows of `table` to archive, and its parameters
+fn archive_filter(table: &str, column: &str, cutoff: i64, keep_session: Option<&str>) -> (String, Vec<Value>) {
+    match keep_session {
+        Some(session) if table == "focus_sessions" => (
+            format!("{} < ?1 AND session_name != ?2 COLLATE NOCASE", column),
+            vec![Value::from(cutoff), Value::from(session.to_string())],
+        ),
+        _ => (format!("{} < ?1", column), vec![Value::from(cutoff)]),
+    }
+}
+
+/// Rows moved by one `database archive` run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ArchiveReport {
+    pub moved: RetentionReport,
+    /// Moved rows the archive already had (from an earlier, interrupted run)
+    pub already_archived: usize,
+}
+
+/// Whether `path` holds something other than a plaintext SQLite database,
+/// i.e. an encrypted one. Missing and empty files are new plaintext ones.
+pub fn is_encrypted(path: &Path) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header != SQLITE_HEADER)
+}
+
+/// An error carrying `message`, with SQLite's "not a database" code
+fn encryption_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTADB), Some(message))
+}
+
+/// Whether anything but whitespace and comments follows the first `;`
+/// outside quotes and comments; prepare would silently ignore it
+fn has_second_statement(sql: &str) -> bool {
+    let mut chars = sql.chars().peekable();
+    let mut ended = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                if ended {
+                    return true;
+                }
+                let close = if c == '[' { ']' } else { c };
+                for c in chars.by_ref() {
+                    if c == close {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            ';' => ended = true,
+            c if ended && !c.is_whitespace() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn query_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_READONLY), Some(message))
+}
+
+/// Column names and text values of a `query_read_only` result
+#[derive(Debug, Clone, Default)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Bump when a view in VIEWS changes (or a table it reads does); databases
+/// with the views installed recreate them on the next open
+pub const VIEWS_VERSION: i64 = 4;
+
+/// A view `database views install` creates for hand-written SQL
+pub struct ViewDef {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The SELECT behind the view
+    pub sql: &'static str,
+    /// Each column and what it holds, in order
+    pub columns: &'static [(&'static str, &'static str)],
+}
+
+/// The installable views, each after those it reads. Local dates and times
+/// are in the system timezone of whoever runs the query; SQLite can't see
+/// the timezone setting.
+pub const VIEWS: &[ViewDef] = &[
+    ViewDef {
+        name: "v_sessions",
+        description: "One row per recorded window, with the settings its session was tracked with",
+        sql: "SELECT s.id, NULLIF(s.session_name, '') AS session_name,
+                  strftime('%Y-%m-%dT%H:%M:%S+00:00', s.start_time, 'unixepoch') AS start_time,
+                  strftime('%Y-%m-%dT%H:%M:%S+00:00', s.end_time, 'unixepoch') AS end_time,
+                  date(s.start_time, 'unixepoch', 'localtime') AS local_date,
+                  time(s.start_time, 'unixepoch', 'localtime') AS local_time,
+                  s.app_name, s.window_title, s.domain, s.workspace, s.power, s.duration_seconds,
+                  MIN(COALESCE(s.passive_seconds, 0), s.duration_seconds) AS passive_seconds,
+                  s.is_focus_app AS is_focus, s.activity, s.source, s.co_focus,
+                  m.recorded_at AS meta_recorded_at, m.snapshot_hash AS settings_hash,
+                  json_extract(c.snapshot, '$.version') AS recorded_version
+              FROM focus_sessions s
+              LEFT JOIN session_meta m ON m.session_name = s.session_name
+              LEFT JOIN config_snapshots c ON c.hash = m.snapshot_hash",
+        columns: &[
+            ("id", "Row id in focus_sessions"),
+            ("session_name", "Session the row belongs to; NULL for rows recorded without one"),
+            ("start_time", "Start, RFC 3339 in UTC"),
+            ("end_time", "End, RFC 3339 in UTC; NULL for rows saved before they ended"),
+            ("local_date", "Local date of the start (YYYY-MM-DD)"),
+            ("local_time", "Local time of the start (HH:MM:SS)"),
+            ("app_name", "Application, as recorded"),
+            ("window_title", "Window title, as recorded (cut to max_title_length)"),
+            ("domain", "Site of a browser tab, when recorded"),
+            ("workspace", "Workspace (virtual desktop), when recorded"),
+            ("power", "'ac' or 'battery', when the machine has a battery"),
+            ("duration_seconds", "Length of the row"),
+            ("passive_seconds", "Part of duration_seconds in long low-activity stretches"),
+            ("is_focus", "1 for focus time, 0 for distraction, as classified when recorded"),
+            ("activity", "Share of the row with input activity (0-1), when sampled"),
+            ("source", "Where imported rows came from, e.g. rescuetime; NULL when tracked"),
+            ("co_focus", "Label of the co_focus rule the row was part of, when any"),
+            ("meta_recorded_at", "When the session's settings were recorded"),
+            ("settings_hash", "Snapshot of the settings the session was tracked with"),
+            ("recorded_version", "focusdebt version the session was tracked with"),
+        ],
+    },
+    ViewDef {
+        name: "v_switches",
+        description: "One row per context switch between applications",
+        sql: "SELECT id, strftime('%Y-%m-%dT%H:%M:%S+00:00', timestamp, 'unixepoch') AS timestamp,
+                  date(timestamp, 'unixepoch', 'localtime') AS local_date,
+                  time(timestamp, 'unixepoch', 'localtime') AS local_time,
+                  CAST(strftime('%H', timestamp, 'unixepoch', 'localtime') AS INTEGER) AS local_hour,
+                  from_app, to_app, recovery_time_seconds
+              FROM context_switches",
+        columns: &[
+            ("id", "Row id in context_switches"),
+            ("timestamp", "When the switch happened, RFC 3339 in UTC"),
+            ("local_date", "Local date of the switch (YYYY-MM-DD)"),
+            ("local_time", "Local time of the switch (HH:MM:SS)"),
+            ("local_hour", "Local hour of the switch (0-23)"),
+            ("from_app", "Application switched away from"),
+            ("to_app", "Application switched to"),
+            ("recovery_time_seconds", "Time until focus came back, when it did"),
+        ],
+    },
+    ViewDef {
+        name: "v_daily",
+        description: "Totals per local day, passive time counted on neither side",
+        sql: "SELECT d.local_date, d.total_seconds, d.focus_seconds, d.distraction_seconds, d.passive_seconds,
+                  ROUND(100.0 * d.focus_seconds / NULLIF(d.focus_seconds + d.distraction_seconds, 0), 1) AS focus_efficiency,
+                  d.sessions, d.apps, COALESCE(w.context_switches, 0) AS context_switches
+              FROM (
+                  SELECT local_date, SUM(duration_seconds) AS total_seconds,
+                      SUM(CASE WHEN is_focus THEN duration_seconds - passive_seconds ELSE 0 END) AS focus_seconds,
+                      SUM(CASE WHEN is_focus THEN 0 ELSE duration_seconds - passive_seconds END) AS distraction_seconds,
+                      SUM(passive_seconds) AS passive_seconds,
+                      COUNT(DISTINCT session_name) AS sessions, COUNT(DISTINCT app_name) AS apps
+                  FROM v_sessions GROUP BY local_date
+              ) d
+              LEFT JOIN (SELECT local_date, COUNT(*) AS context_switches FROM v_switches GROUP BY local_date) w
+                  ON w.local_date = d.local_date",
+        columns: &[
+            ("local_date", "Local date (YYYY-MM-DD)"),
+            ("total_seconds", "Everything recorded that day"),
+            ("focus_seconds", "Focus time, passive stretches left out"),
+            ("distraction_seconds", "Distraction time, passive stretches left out"),
+            ("passive_seconds", "Time in long low-activity stretches"),
+            ("focus_efficiency", "Focus share of focus plus distraction time, in percent"),
+            ("sessions", "Named sessions with rows that day"),
+            ("apps", "Distinct applications used"),
+            ("context_switches", "Context switches that day"),
+        ],
+    },
+    ViewDef {
+        name: "v_app_daily",
+        description: "Time per application and local day",
+        sql: "SELECT local_date, app_name, SUM(duration_seconds) AS total_seconds,
+                  SUM(CASE WHEN is_focus THEN duration_seconds - passive_seconds ELSE 0 END) AS focus_seconds,
+                  SUM(passive_seconds) AS passive_seconds, COUNT(*) AS rows
+              FROM v_sessions GROUP BY local_date, app_name",
+        columns: &[
+            ("local_date", "Local date (YYYY-MM-DD)"),
+            ("app_name", "Application, as recorded"),
+            ("total_seconds", "Time in the application that day"),
+            ("focus_seconds", "Part of it classified as focus, passive stretches left out"),
+            ("passive_seconds", "Part of it in long low-activity stretches"),
+            ("rows", "Recorded windows"),
+        ],
+    },
+];
+
+fn schema_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN), Some(message))
+}
+
+/// Rows whose stored focus flag disagrees with the current rules
+#[derive(Debug, Default)]
+pub struct ReclassifyReport {
+    pub to_focus: usize,
+    pub to_distraction: usize,
+    /// Rows matching ignored_apps/ignored_sites, whatever their flag
+    pub ignored: usize,
+    /// Rows that keep their flag but had no rule or another one recorded
+    pub rules_recorded: usize,
+}
+
+/// Duplicated rows of one UTC day found by `dedupe_sessions`
+#[derive(Debug, Clone, Copy)]
+pub struct DedupeDay {
+    pub day: NaiveDate,
+    pub rows: usize,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub zero_duration: usize,
+    pub over_one_day: usize,
+    pub end_before_start: usize,
+    pub duplicates: usize,
+}
+
+impl CleanupReport {
+    fn set(&mut self, category: CleanupCategory, count: usize) {
+        match category {
+            CleanupCategory::ZeroDuration => self.zero_duration = count,
+            CleanupCategory::OverOneDay => self.over_one_day = count,
+            CleanupCategory::EndBeforeStart => self.end_before_start = count,
+            CleanupCategory::Duplicate => self.duplicates = count,
+        }
+    }
+
+    pub fn entries(&self) -> Vec<(CleanupCategory, usize)> {
+        vec![
+            (CleanupCategory::ZeroDuration, self.zero_duration),
+            (CleanupCategory::OverOneDay, self.over_one_day),
+            (CleanupCategory::EndBeforeStart, self.end_before_start),
+            (CleanupCategory::Duplicate, self.duplicates),
+        ]
+    }
+
+    pub fn total(&self) -> usize {
+        self.entries().iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// What `trim_titles` cut
+#[derive(Debug, Default)]
+pub struct TrimTitlesReport {
+    pub sessions: usize,
+    pub events: usize,
+    /// Title bytes removed, before VACUUM gives the space back
+    pub bytes_cut: u64,
+}
+
+#[derive(Debug)]
+pub struct OptimizeReport {
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+    pub analyzed: bool,
+}
+
+/// One app's rows at a glance, from `get_app_sightings`
+#[derive(Debug, Clone)]
+pub struct AppSighting {
+    pub app_name: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub total: Duration,
+}
+
+/// A session row held back by the clock check
+#[derive(Debug, Clone)]
+pub struct QuarantinedSession {
+    pub reason: String,
+    pub session: FocusSession,
+}
+
+/// What `get_session_meta` found for a session
+#[derive(Debug, Clone)]
+pub struct SessionMeta {
+    pub recorded_at: DateTime<Utc>,
+    /// Key of the snapshot in config_snapshots
+    pub hash: String,
+    pub snapshot: SessionSnapshot,
+}
+
+/// What a session was started for and how it went; every part is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SessionIntent {
+    pub started_at: DateTime<Utc>,
+    /// e.g. "write the RFC draft"
+    pub intent: Option<String>,
+    pub expected: Option<Duration>,
+    /// 1 (poor) to 5 (great)
+    pub rating: Option<u8>,
+    pub intent_met: Option<bool>,
+}
+
+impl SessionIntent {
+    /// e.g. "write the RFC draft (expected 2h 0m)"
+    pub(crate) fn describe_intent(&self) -> Option<String> {
+        let intent = self.intent.as_deref()?;
+        Some(match self.expected {
+            Some(expected) => format!("{} (expected {})", utils::escape_control_chars(intent),
+                utils::format_duration(expected, utils::DurationStyle::Long)),
+            None => utils::escape_control_chars(intent).into_owned(),
+        })
+    }
+
+    /// e.g. "4/5, intent met"; None until rated
+    pub(crate) fn describe_outcome(&self) -> Option<String> {
+        let rating = self.rating?;
+        Some(match self.intent_met {
+            Some(true) => format!("{}/5, intent met", rating),
+            Some(false) => format!("{}/5, intent not met", rating),
+            None => format!("{}/5", rating),
+        })
+    }
+}
+
+/// Where a session's task comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TaskSource {
+    Taskwarrior,
+    TodoTxt,
+}
+
+impl TaskSource {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            TaskSource::Taskwarrior => "taskwarrior",
+            TaskSource::TodoTxt => "todo_txt",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "taskwarrior" => Some(TaskSource::Taskwarrior),
+            "todo_txt" => Some(TaskSource::TodoTxt),
+            _ => None,
+        }
+    }
+}
+
+/// The task a session was started on
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SessionTask {
+    pub started_at: DateTime<Utc>,
+    pub source: TaskSource,
+    /// The taskwarrior id as given at start, or the todo.txt line
+    pub id: String,
+    /// Taskwarrior's uuid, which outlives the id; None when taskwarrior
+    /// couldn't be asked
+    pub uuid: Option<String>,
+    pub description: Option<String>,
+}
+
+impl SessionTask {
+    /// e.g. "task 123: write the RFC draft" or "todo.txt: (A) Call mom +family"
+    pub(crate) fn describe(&self) -> String {
+        let text = match (self.source, &self.description) {
+            (TaskSource::Taskwarrior, Some(description)) => format!("task {}: {}", self.id, description),
+            (TaskSource::Taskwarrior, None) => format!("task {}", self.id),
+            (TaskSource::TodoTxt, _) => format!("todo.txt: {}", self.id),
+        };
+        utils::escape_control_chars(&text).into_owned()
+    }
+
+    /// Whether `query` names this task: its uuid as resolved from the
+    /// query, the id it was started with, or text of its description or
+    /// todo.txt line, all ignoring case
+    pub(crate) fn matches(&self, query: &str, uuid: Option<&str>) -> bool {
+        // Taskwarrior reuses ids once tasks are done; the uuid tells them apart
+        if let (Some(uuid), Some(own)) = (uuid, &self.uuid) {
+            return uuid.eq_ignore_ascii_case(own);
+        }
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return false;
+        }
+        if self.id.to_lowercase() == query {
+            return true;
+        }
+        // A number is a taskwarrior id, not text to look for
+        if query.parse::<u64>().is_ok() {
+            return false;
+        }
+        [Some(&self.id), self.uuid.as_ref(), self.description.as_ref()].into_iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&query))
+    }
+}
+
+/// Hash of a snapshot's JSON, see `utils::stable_hash`. Stored in the
+/// database, so it must not change between builds.
+fn snapshot_hash(text: &str) -> String {
+    utils::stable_hash(text.as_bytes())
+}
+
+/// A session scheduled ahead of time with `plan add`
+#[derive(Debug, Clone)]
+pub struct PlannedSession {
+    pub id: i64,
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    pub duration: Duration,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanStatus {
+    Planned,
+    /// Its time has come but no idle daemon has picked it up yet
+    Due,
+    Running,
+    Completed,
+    /// Started, but the daemon stopped before the planned end
+    CutShort,
+    Missed,
+}
+
+impl PlanStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlanStatus::Planned => "planned",
+            PlanStatus::Due => "due",
+            PlanStatus::Running => "running",
+            PlanStatus::Completed => "completed",
+            PlanStatus::CutShort => "cut short",
+            PlanStatus::Missed => "missed",
+        }
+    }
+}
+
+impl PlannedSession {
+    pub fn end_time(&self) -> DateTime<Utc> {
+        self.start_time + chrono::Duration::from_std(self.duration).unwrap_or_default()
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> PlanStatus {
+        match (self.started_at, self.completed_at) {
+            (_, Some(_)) => PlanStatus::Completed,
+            (Some(_), None) if now < self.end_time() => PlanStatus::Running,
+            (Some(_), None) => PlanStatus::CutShort,
+            (None, None) if now < self.start_time => PlanStatus::Planned,
+            (None, None) if now < self.end_time() => PlanStatus::Due,
+            (None, None) => PlanStatus::Missed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleKind;
+    use crate::tracking::WindowInfo;
+
+    fn memory_db() -> Database {
+        Database::open(Path::new(":memory:")).unwrap()
+    }
+
+    /// Insert a row as stored, bypassing every check on the way in
+    fn insert_row(db: &Database, start: i64, end: Option<i64>, app: &str, title: &str, seconds: i64) {
+        db.conn.execute(
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app, session_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, 'test')",
+            rusqlite::params![start, end, app, title, seconds],
+        ).unwrap();
+    }
+
+    /// A fresh database file path under the temp dir, unique per test
+    fn temp_db_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("focusdebt-storage-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("focusdebt.db")
+    }
+
+    /// A database file as the first release created it: text times and
+    /// none of the columns added since
+    fn baseline_db(name: &str) -> PathBuf {
+        let path = temp_db_path(name);
+        Connection::open(&path).unwrap().execute_batch(
+            "CREATE TABLE focus_sessions (
+                 id INTEGER PRIMARY KEY,
+                 start_time TEXT NOT NULL,
+                 end_time TEXT,
+                 app_name TEXT NOT NULL,
+                 window_title TEXT NOT NULL,
+                 domain TEXT,
+                 duration_seconds INTEGER NOT NULL,
+                 is_focus_app BOOLEAN NOT NULL,
+                 session_name TEXT NOT NULL DEFAULT ''
+             );
+             CREATE TABLE context_switches (
+                 id INTEGER PRIMARY KEY,
+                 timestamp TEXT NOT NULL,
+                 from_app TEXT NOT NULL,
+                 to_app TEXT NOT NULL,
+                 recovery_time_seconds INTEGER
+             );
+             CREATE TABLE focus_apps (id INTEGER PRIMARY KEY, app_name TEXT UNIQUE NOT NULL, added_at TEXT NOT NULL);
+             CREATE TABLE focus_sites (id INTEGER PRIMARY KEY, domain TEXT UNIQUE NOT NULL, added_at TEXT NOT NULL);",
+        ).unwrap();
+        path
+    }
+
+    fn session(start: DateTime<Utc>, app: &str, seconds: u64) -> FocusSession {
+        FocusSession {
+            start_time: start,
+            end_time: Some(start + chrono::Duration::seconds(seconds as i64)),
+            app_name: app.to_string(),
+            window_title: format!("{} window", app),
+            domain: None,
+            duration: Duration::from_secs(seconds),
+            is_focus_app: true,
+            session_name: "test".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::default(),
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    fn index_exists(db: &Database, name: &str) -> bool {
+        db.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?1)",
+            [name],
+            |row| row.get(0),
+        ).unwrap()
+    }
+
+    fn row_count(db: &Database, table: &str) -> i64 {
+        db.conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn cleanup_counts_and_removes_each_kind_of_bad_row() {
+        let db = memory_db();
+        // Duplicates predate the index that refuses them
+        db.conn.execute_batch("DROP INDEX idx_focus_sessions_identity").unwrap();
+        let t = 1_700_000_000;
+        insert_row(&db, t, Some(t + 60), "code", "good", 60);
+        insert_row(&db, t + 100, Some(t + 100), "code", "zero", 0);
+        insert_row(&db, t + 200, Some(t + 200 + 90_000), "code", "long", 90_000);
+        insert_row(&db, t + 300, Some(t + 250), "code", "backwards", 50);
+        insert_row(&db, t + 400, None, "code", "open", 30);
+        insert_row(&db, t + 500, Some(t + 520), "slack", "twice", 20);
+        insert_row(&db, t + 500, Some(t + 520), "slack", "twice", 20);
+
+        let preview = db.cleanup_sessions(true).unwrap();
+        assert_eq!(
+            (preview.zero_duration, preview.over_one_day, preview.end_before_start, preview.duplicates),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(row_count(&db, "focus_sessions"), 7, "a dry run removes nothing");
+
+        let report = db.cleanup_sessions(false).unwrap();
+        assert_eq!(report.total(), 4);
+        let titles: Vec<String> = db.conn
+            .prepare("SELECT window_title FROM focus_sessions ORDER BY start_time").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        // A row without an end time is left for crash recovery to settle
+        assert_eq!(titles, ["good", "open", "twice"]);
+    }
+
+    #[test]
+    fn cleanup_counts_a_row_under_its_first_category_only() {
+        let db = memory_db();
+        let t = 1_700_000_000;
+        // Zero duration and ending before it starts: reported as zero duration
+        insert_row(&db, t, Some(t - 10), "code", "both", 0);
+        let report = db.cleanup_sessions(true).unwrap();
+        assert_eq!((report.zero_duration, report.end_before_start, report.total()), (1, 0, 1));
+    }
+
+    #[test]
+    fn efficiency_days_follow_the_local_tracking_day() {
+        let tz = chrono_tz::America::Los_Angeles;
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let rows = [
+            // 20:00 PDT on June 3rd is already June 4th in UTC
+            (at("2024-06-04T03:00:00Z"), true, 3600),
+            (at("2024-06-04T04:00:00Z"), false, 3600),
+            // 02:00 PDT on June 4th, before a 4am day start
+            (at("2024-06-04T09:00:00Z"), true, 1800),
+            (at("2024-06-04T18:00:00Z"), true, 600),
+        ];
+        let days = efficiency_by_day(&rows, |start| utils::tracking_day_in(&tz, start, 4));
+        let june = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, june(3));
+        assert!((days[0].1 - 60.0).abs() < 1e-9);
+        assert_eq!(days[1], (june(4), 100.0));
+    }
+
+    #[test]
+    fn daily_efficiency_reads_the_range_by_tracking_day() {
+        let db = memory_db();
+        let t = DateTime::parse_from_rfc3339("2024-06-04T12:00:00Z").unwrap().with_timezone(&Utc);
+        insert_row(&db, t.timestamp(), Some(t.timestamp() + 600), "code", "work", 600);
+        insert_row(&db, t.timestamp() + 86_400 * 3, Some(t.timestamp() + 86_400 * 3 + 600), "code", "later", 600);
+        let days = db.get_daily_efficiency(t - chrono::Duration::hours(1), t + chrono::Duration::days(1), 0).unwrap();
+        assert_eq!(days, vec![(utils::tracking_day(t, 0), 100.0)]);
+    }
+
+    #[test]
+    fn focus_lists_add_what_only_the_database_holds() {
+        let db = memory_db();
+        db.conn.execute_batch(
+            "INSERT INTO focus_apps (app_name, added_at) VALUES ('code', '2024-01-01T00:00:00Z'), ('zed', '2024-01-01T00:00:00Z');
+             INSERT INTO focus_sites (domain, added_at) VALUES ('docs.rs', '2024-01-01T00:00:00Z');",
+        ).unwrap();
+        let strings = |entries: &[&str]| entries.iter().map(|entry| entry.to_string()).collect::<Vec<_>>();
+
+        let (apps, sites) = db.focus_lists(&strings(&["nvim", "code"]), &[]).unwrap();
+        assert_eq!(apps, ["nvim", "code", "zed"]);
+        assert_eq!(sites, ["docs.rs"]);
+
+        db.remove_focus_app("zed").unwrap();
+        db.remove_focus_site("docs.rs").unwrap();
+        assert_eq!(db.focus_lists(&strings(&["nvim"]), &strings(&["github.com"])).unwrap(), (strings(&["nvim", "code"]), strings(&["github.com"])));
+    }
+
+    #[test]
+    fn daily_and_quarter_totals_follow_the_local_day() {
+        let db = memory_db();
+        let days = LocalDays::in_zone(utils::timezone::Zone::Named(chrono_tz::Asia::Kolkata), 0);
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().timestamp();
+        // 23:45 IST on 31 March, then 00:15 IST on 1 April: both still
+        // 31 March in UTC
+        insert_row(&db, at("2024-03-31T18:15:00Z"), Some(at("2024-03-31T18:25:00Z")), "code", "late", 600);
+        insert_row(&db, at("2024-03-31T18:45:00Z"), Some(at("2024-03-31T18:55:00Z")), "code", "early", 600);
+        let start = days.start(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let end = days.start(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+        let ymd = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        let ten = Duration::from_secs(600);
+        assert_eq!(db.get_daily_totals_between(&days, start, end).unwrap(), [(ymd(3, 31), ten, ten), (ymd(4, 1), ten, ten)]);
+        assert_eq!(db.get_app_totals_by_quarter(&days, start, end).unwrap(), [(1, "code".to_string(), ten), (2, "code".to_string(), ten)]);
+        assert_eq!(db.get_app_daily_totals(&days, start, end, Duration::ZERO).unwrap(), [(ymd(3, 31), "code".to_string(), ten), (ymd(4, 1), "code".to_string(), ten)]);
+    }
+
+    #[test]
+    fn self_cleanup_removes_only_terminals_running_the_cli() {
+        let db = memory_db();
+        let t = 1_700_000_000;
+        insert_row(&db, t, Some(t + 60), "kitty", "focusdebt stats", 60);
+        insert_row(&db, t + 100, Some(t + 160), "my-term", "focusdebt wrapped", 60);
+        insert_row(&db, t + 200, Some(t + 260), "firefox", "focusdebt stats - Mozilla Firefox", 60);
+        insert_row(&db, t + 300, Some(t + 360), "kitty", "~/src/focusdebt", 60);
+        let rule_terminals = vec!["my-term".to_string()];
+
+        assert_eq!(db.cleanup_self_sessions(&rule_terminals, true).unwrap(), 2);
+        assert_eq!(row_count(&db, "focus_sessions"), 4);
+        assert_eq!(db.cleanup_self_sessions(&rule_terminals, false).unwrap(), 2);
+        let apps: Vec<String> = db.conn.prepare("SELECT app_name FROM focus_sessions ORDER BY start_time").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        assert_eq!(apps, ["firefox", "kitty"]);
+    }
+
+    #[test]
+    fn opening_removes_duplicates_left_from_before_the_dedupe_index() {
+        let path = temp_db_path("dedupe-on-open");
+        {
+            let db = Database::open(&path).unwrap();
+            db.conn.execute_batch("DROP INDEX idx_focus_sessions_identity").unwrap();
+            let t = 1_700_000_000;
+            insert_row(&db, t, Some(t + 60), "code", "copy", 60);
+            insert_row(&db, t, Some(t + 60), "code", "copy", 60);
+            insert_row(&db, t + 100, Some(t + 160), "code", "single", 60);
+        }
+        let db = Database::open(&path).unwrap();
+        assert_eq!(row_count(&db, "focus_sessions"), 2);
+        assert!(index_exists(&db, "idx_focus_sessions_identity"));
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn epoch_upgrade_keeps_indexes_and_merges_rows_saved_twice_within_a_second() {
+        let path = baseline_db("epoch-indexes");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "ALTER TABLE focus_sessions ADD COLUMN source TEXT;
+                 ALTER TABLE focus_sessions ADD COLUMN source_key TEXT;
+                 CREATE UNIQUE INDEX idx_focus_sessions_dedupe ON focus_sessions (start_time, app_name, duration_seconds);
+                 INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+                 VALUES ('2024-06-04T10:00:00.200+00:00', '2024-06-04T10:01:00.200+00:00', 'code', 'edit', 60, 1),
+                        ('2024-06-04T10:00:00.700+00:00', '2024-06-04T10:01:00.700+00:00', 'code', 'edit', 60, 1),
+                        ('2024-06-04T10:00:01+00:00', '2024-06-04T10:01:01+00:00', 'code', 'edit', 60, 1),
+                        ('2024-06-04T10:00:00.500+00:00', '2024-06-04T10:00:30.500+00:00', 'code', 'edit', 30, 1),
+                        ('2024-06-04T10:00:00.900+00:00', '2024-06-04T10:01:00.900+00:00', 'code', 'review', 60, 1);",
+
+            ).unwrap();
+        }
+
+        let db = Database::open(&path).unwrap();
+        assert!(!index_exists(&db, "idx_focus_sessions_dedupe"));
+        assert!(index_exists(&db, "idx_focus_sessions_identity"));
+        assert!(index_exists(&db, "idx_focus_sessions_source_key"));
+        let t = DateTime::parse_from_rfc3339("2024-06-04T10:00:00Z").unwrap().timestamp();
+        let rows: Vec<(String, i64, i64)> = db.conn
+            .prepare("SELECT window_title, start_time, end_time FROM focus_sessions ORDER BY id").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        // The second row truncates onto the first and is merged into it;
+        // the third starts a second later, the fourth differs in duration
+        // and the last in title, so they stay
+        assert_eq!(rows, vec![
+            ("edit".to_string(), t, t + 60),
+            ("edit".to_string(), t + 1, t + 61),
+            ("edit".to_string(), t, t + 30),
+            ("review".to_string(), t, t + 60),
+        ]);
+        // The merged copy is kept in quarantine with its own start
+        let quarantined = db.get_quarantined_sessions().unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].reason, MERGED_AT_UPGRADE_REASON);
+        assert_eq!(quarantined[0].session.start_time, DateTime::parse_from_rfc3339("2024-06-04T10:00:00.700Z").unwrap());
+        drop(db);
+
+        // Reopening finds nothing left to upgrade or dedupe
+        let db = Database::open(&path).unwrap();
+        assert_eq!(row_count(&db, "focus_sessions"), 4);
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn epoch_upgrade_leaves_exact_copies_to_the_dedupe_pass() {
+        let path = baseline_db("epoch-copies");
+        Connection::open(&path).unwrap().execute_batch(
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+             VALUES ('2024-06-04T10:00:00.200+00:00', NULL, 'code', 'a', 60, 1),
+                    ('2024-06-04T10:00:00.200+00:00', NULL, 'code', 'a', 60, 1),
+                    ('2024-06-04T10:00:00.700+00:00', NULL, 'code', 'b', 60, 1);",
+        ).unwrap();
+
+        let db = Database::open(&path).unwrap();
+        let titles: Vec<String> = db.conn.prepare("SELECT window_title FROM focus_sessions ORDER BY start_time").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        assert_eq!(titles, ["a", "b"]);
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    /// Range query over seeded rows as the daily views run it: times
+    /// compared and ordered in SQL, then read into DateTimes. Text columns
+    /// other than the times cost both layouts the same and are left out.
+    fn timed_range_query(conn: &Connection, start: &Value, end: &Value) -> (usize, Duration) {
+        let began = std::time::Instant::now();
+        let mut stmt = conn.prepare(
+            "SELECT start_time, end_time, duration_seconds FROM focus_sessions
+             WHERE start_time >= ?1 AND start_time < ?2 ORDER BY start_time",
+        ).unwrap();
+        let rows = stmt.query_map([start, end], |row| {
+            Ok((Database::timestamp_column(row, 0)?, Database::timestamp_column(row, 1)?, row.get::<_, i64>(2)?))
+        }).unwrap();
+        let count = rows.map(|row| row.unwrap()).filter(|row| row.0.is_some()).count();
+        (count, began.elapsed())
+    }
+
+    #[test]
+    #[ignore = "benchmark: seeds 500k rows; run with cargo test --release -- --ignored"]
+    fn epoch_times_make_range_queries_at_least_twice_as_fast() {
+        const ROWS: i64 = 500_000;
+        let first = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().timestamp();
+        // An old database with text times, as a release before the upgrade left it
+        let text_path = baseline_db("epoch-bench");
+        {
+            let conn = Connection::open(&text_path).unwrap();
+            conn.execute_batch("CREATE INDEX idx_focus_sessions_start_time ON focus_sessions (start_time);").unwrap();
+            let tx = conn.unchecked_transaction().unwrap();
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+                     VALUES (?1, ?2, ?3, 'window', ?4, 1)",
+                ).unwrap();
+                // A row every 90 seconds, with a fixed pseudo-random app and length
+                let mut state = 0x2545_f491_u64;
+                for i in 0..ROWS {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    let start = first + i * 90;
+                    let seconds = (state % 90) as i64;
+                    let time = |secs: i64| DateTime::from_timestamp(secs, 0).unwrap().to_rfc3339();
+                    insert.execute(rusqlite::params![time(start), time(start + seconds), format!("app{}", state % 20), seconds]).unwrap();
+                }
+            }
+            tx.commit().unwrap();
+        }
+        // A copy upgraded by the real migration, next to the original
+        let epoch_path = text_path.with_file_name("epoch.db");
+        std::fs::copy(&text_path, &epoch_path).unwrap();
+        let epoch_db = Database::open(&epoch_path).unwrap();
+        assert!(!Database::has_text_times(&epoch_db.conn).unwrap());
+        let (text_conn, epoch_conn) = (Connection::open(&text_path).unwrap(), &epoch_db.conn);
+        // A month in the middle of the ~520 days seeded
+        let (start, end) = (first + 200 * 86_400, first + 230 * 86_400);
+        let text_bound = |secs: i64| Value::Text(DateTime::from_timestamp(secs, 0).unwrap().to_rfc3339());
+        // Best of several runs, taken in turns so a busy machine slows both
+        let (mut text_time, mut epoch_time) = (Duration::MAX, Duration::MAX);
+        for _ in 0..7 {
+            let (text_rows, text) = timed_range_query(&text_conn, &text_bound(start), &text_bound(end));
+            let (epoch_rows, epoch) = timed_range_query(epoch_conn, &Value::Integer(start), &Value::Integer(end));
+            assert_eq!(text_rows, 28_800);
+            assert_eq!(epoch_rows, text_rows);
+            text_time = text_time.min(text);
+            epoch_time = epoch_time.min(epoch);
+        }
+        assert!(epoch_time * 2 <= text_time, "text {:?}, epoch {:?}", text_time, epoch_time);
+        drop(text_conn);
+        drop(epoch_db);
+        let _ = std::fs::remove_dir_all(text_path.parent().unwrap());
+    }
+
+    #[test]
+    fn range_queries_on_epoch_times_search_the_time_index() {
+        let db = memory_db();
+        for (table, times) in EPOCH_TIME_COLUMNS {
+            let time = times[0];
+            let plan: Vec<String> = db.conn
+                .prepare(&format!("EXPLAIN QUERY PLAN SELECT * FROM {table} WHERE {time} >= ?1 AND {time} < ?2 ORDER BY {time}")).unwrap()
+                .query_map([1_700_000_000i64, 1_700_086_400], |row| row.get::<_, String>(3)).unwrap()
+                .collect::<SqliteResult<_>>().unwrap();
+            let searched = format!("SEARCH {} USING INDEX", table);
+            assert!(plan.iter().any(|step| step.starts_with(&searched) && step.contains(&format!("({0}>? AND {0}<?)", time))), "{}: {:?}", table, plan);
+            // The index gives the order too
+            assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")), "{}: {:?}", table, plan);
+        }
+    }
+
+    #[test]
+    fn baseline_database_upgrades_and_keeps_focus_rule_and_co_focus() {
+        let path = baseline_db("upgrade-added-columns");
+        Connection::open(&path).unwrap().execute(
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+             VALUES ('2024-06-04T09:00:00+00:00', '2024-06-04T09:10:00+00:00', 'code', 'old', 600, 1)",
+            [],
+        ).unwrap();
+
+        let db = Database::open(&path).unwrap();
+        let start = DateTime::parse_from_rfc3339("2024-06-04T10:00:00Z").unwrap().with_timezone(&Utc);
+        let mut new = session(start, "code", 300);
+        new.focus_rule = Some(RuleMatch::new(crate::config::RuleKind::FocusApp, "code"));
+        new.co_focus = Some("pairing".to_string());
+        db.save_focus_session(&new).unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        let sessions = db.get_sessions_between(start - chrono::Duration::hours(2), start + chrono::Duration::hours(1)).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].window_title, "old");
+        assert_eq!((&sessions[0].focus_rule, &sessions[0].co_focus), (&None, &None));
+        assert_eq!(sessions[1].focus_rule, new.focus_rule);
+        assert_eq!(sessions[1].co_focus.as_deref(), Some("pairing"));
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn epoch_upgrade_is_detected_and_runs_once() {
+        let path = baseline_db("upgrade-once");
+        Connection::open(&path).unwrap().execute(
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+             VALUES ('2024-06-04T09:00:00+00:00', NULL, 'code', 'old', 600, 1)",
+            [],
+        ).unwrap();
+        assert!(Database::needs_epoch_upgrade(&path).unwrap());
+        assert!(!Database::needs_epoch_upgrade(&path.with_file_name("missing.db")).unwrap());
+
+        let db = Database::open(&path).unwrap();
+        assert!(!Database::needs_epoch_upgrade(&path).unwrap());
+        // Running it again converts nothing
+        Database::migrate_epoch_times(&db.conn).unwrap();
+        let start: i64 = db.conn.query_row("SELECT start_time FROM focus_sessions", [], |row| row.get(0)).unwrap();
+        assert_eq!(start, DateTime::parse_from_rfc3339("2024-06-04T09:00:00Z").unwrap().timestamp());
+        drop(db);
+
+        // Later opens leave it alone: text an older daemon writes through
+        // the upgrade waits for the new daemon to convert it
+        Connection::open(&path).unwrap().execute(
+            "INSERT INTO focus_sessions (start_time, end_time, app_name, window_title, duration_seconds, is_focus_app)
+             VALUES ('2024-06-04T10:00:00+00:00', '2024-06-04T10:05:00+00:00', 'code', 'late', 300, 1)",
+            [],
+        ).unwrap();
+        let db = Database::open(&path).unwrap();
+        let late = || db.conn.query_row("SELECT typeof(start_time), typeof(end_time) FROM focus_sessions WHERE window_title = 'late'", [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).unwrap();
+        assert_eq!(late(), ("text".to_string(), "text".to_string()));
+        assert_eq!(db.convert_text_times().unwrap(), 1);
+        assert_eq!(late(), ("integer".to_string(), "integer".to_string()));
+        assert_eq!(db.convert_text_times().unwrap(), 0);
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn retention_prunes_once_per_tracking_day_as_the_clock_moves() {
+        let db = memory_db();
+        let day_start_hour = 4;
+        let hours = chrono::Duration::hours;
+        let days = chrono::Duration::days;
+        let base = utils::day_start(DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z").unwrap().with_timezone(&Utc), day_start_hour);
+        let saved = |start: DateTime<Utc>, name: &str| {
+            let mut row = session(start, "code", 600);
+            row.session_name = name.to_string();
+            db.save_focus_session(&row).unwrap();
+        };
+        for n in 0..60 {
+            saved(base - days(n) + hours(1), &format!("day {}", n));
+        }
+        // Running since 45 days ago: it and everything after it stay
+        saved(base - days(45) + hours(2), "running");
+        saved(base, "running");
+        let running_rows = || db.conn.query_row(
+            "SELECT COUNT(*) FROM focus_sessions WHERE session_name = 'running'", [], |row| row.get::<_, i64>(0)).unwrap();
+
+        let mut retention = DailyRetention::new(30, day_start_hour);
+        let mut clock = base + hours(1);
+        match retention.run(&db, "running", clock, false) {
+            RetentionRun::Pruned(report) => assert_eq!(report.sessions, 15),
+            other => panic!("expected a run, got {:?}", other),
+        }
+        assert_eq!(row_count(&db, "focus_sessions"), 47);
+        assert_eq!(running_rows(), 2);
+
+        // Later flushes the same tracking day, up to just before day_start_hour
+        for later in [hours(5), hours(19), days(1) - hours(1)] {
+            clock = base + later;
+            assert!(matches!(retention.run(&db, "running", clock, false), RetentionRun::NotDue), "at {}", clock);
+        }
+
+        // Across the boundary, an export holds the run off until it ends
+        clock = base + days(1) + hours(1);
+        assert!(matches!(retention.run(&db, "today", clock, true), RetentionRun::ExportRunning));
+        assert_eq!(row_count(&db, "focus_sessions"), 47);
+        match retention.run(&db, "today", clock, false) {
+            RetentionRun::Pruned(report) => assert_eq!(report.sessions, 16),
+            other => panic!("expected a run, got {:?}", other),
+        }
+        assert_eq!(running_rows(), 1);
+        assert!(matches!(retention.run(&db, "today", clock + hours(2), false), RetentionRun::NotDue));
+    }
+
+    /// Another connection holding an exclusive transaction on `path`, like a
+    /// VACUUM or backup in progress
+    fn lock_exclusively(path: &Path) -> Connection {
+        let locker = Connection::open(path).unwrap();
+        locker.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        locker
+    }
+
+    #[test]
+    fn read_only_open_gives_up_on_a_held_lock_as_transient() {
+        let path = temp_db_path("locked");
+        drop(Database::open(&path).unwrap());
+        let locker = lock_exclusively(&path);
+
+        let started = std::time::Instant::now();
+        let error = Database::open_read_only(&path).err().expect("the lock is held throughout");
+        assert!(is_transient_lock_error(&error), "{}", error);
+        // Every backoff step was waited out before giving up
+        let backoff: u64 = READ_RETRY_BACKOFF_MS.iter().sum();
+        assert!(started.elapsed() >= Duration::from_millis(backoff), "{:?}", started.elapsed());
+
+        drop(locker);
+        let db = Database::open_read_only(&path).unwrap();
+        assert!(db.get_sessions_between(Utc::now() - chrono::Duration::days(1), Utc::now()).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn read_only_open_succeeds_once_the_lock_is_released() {
+        let path = temp_db_path("released");
+        let db = Database::open(&path).unwrap();
+        insert_row(&db, 1_716_282_000, Some(1_716_282_600), "fd-editor", "main.rs", 600);
+        drop(db);
+        let locker = lock_exclusively(&path);
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            locker.execute_batch("COMMIT").unwrap();
+        });
+
+        let db = Database::open_read_only(&path).unwrap();
+        release.join().unwrap();
+        let start = DateTime::from_timestamp(1_716_281_000, 0).unwrap();
+        let rows = db.get_sessions_between(start, start + chrono::Duration::hours(1)).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].app_name, "fd-editor");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn only_busy_and_locked_count_as_transient() {
+        let failure = |code: i32| rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), None);
+        assert!(is_transient_lock_error(&failure(rusqlite::ffi::SQLITE_BUSY)));
+        assert!(is_transient_lock_error(&failure(rusqlite::ffi::SQLITE_LOCKED)));
+        assert!(!is_transient_lock_error(&failure(rusqlite::ffi::SQLITE_CORRUPT)));
+        assert!(!is_transient_lock_error(&rusqlite::Error::QueryReturnedNoRows));
+    }
+
+    #[test]
+    fn reclassify_reports_then_rewrites_the_flags() {
+        let db = memory_db();
+        let start = Utc::now() - chrono::Duration::days(3);
+        for (minute, (app, is_focus_app)) in [("fd-editor", false), ("fd-editor", true), ("fd-chat", true), ("fd-steam", true), ("fd-term", false)].into_iter().enumerate() {
+            let session = FocusSession { is_focus_app, ..session(start + chrono::Duration::minutes(minute as i64 * 10), app, 300) };
+            db.save_focus_session(&session).unwrap();
+        }
+        let classify = |session: &FocusSession| match session.app_name.as_str() {
+            "fd-editor" => (SessionClass::Focus, RuleMatch::new(RuleKind::FocusApp, "fd-editor")),
+            "fd-steam" => (SessionClass::Ignored, RuleMatch::new(RuleKind::IgnoredApp, "fd-steam")),
+            _ => (SessionClass::Distraction, RuleMatch::default_rule()),
+        };
+        let flags = |db: &Database| -> Vec<(String, bool, Option<String>)> {
+            let mut stmt = db.conn.prepare("SELECT app_name, is_focus_app, focus_rule FROM focus_sessions ORDER BY start_time").unwrap();
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+            rows.collect::<SqliteResult<_>>().unwrap()
+        };
+        let before = flags(&db);
+
+        let report = db.reclassify_sessions(classify, true).unwrap();
+        assert_eq!((report.to_focus, report.to_distraction, report.ignored, report.rules_recorded), (1, 2, 1, 2));
+        assert_eq!(flags(&db), before);
+
+        let report = db.reclassify_sessions(classify, false).unwrap();
+        assert_eq!((report.to_focus, report.to_distraction, report.ignored, report.rules_recorded), (1, 2, 1, 2));
+        assert_eq!(flags(&db), [
+            ("fd-editor".to_string(), true, Some("focus_app:fd-editor".to_string())),
+            ("fd-editor".to_string(), true, Some("focus_app:fd-editor".to_string())),
+            ("fd-chat".to_string(), false, Some("default".to_string())),
+            // Ignored rows are stored as distractions
+            ("fd-steam".to_string(), false, Some("ignored_app:fd-steam".to_string())),
+            ("fd-term".to_string(), false, Some("default".to_string())),
+        ]);
+
+        // A second run has nothing left to change
+        let report = db.reclassify_sessions(classify, false).unwrap();
+        assert_eq!((report.to_focus, report.to_distraction, report.rules_recorded), (0, 0, 0));
+        assert_eq!(report.ignored, 1);
+    }
+
+    #[test]
+    fn adopting_names_only_that_days_unnamed_rows() {
+        let db = memory_db();
+        let day = DateTime::parse_from_rfc3339("2024-05-21T00:00:00Z").unwrap().with_timezone(&Utc);
+        for (hours, name) in [(-1, ""), (9, ""), (10, "alpha"), (11, ""), (25, "")] {
+            db.save_focus_session(&FocusSession { session_name: name.to_string(), ..session(day + chrono::Duration::hours(hours), "fd-editor", 60) }).unwrap();
+        }
+        let names = |db: &Database| -> Vec<String> {
+            let mut stmt = db.conn.prepare("SELECT session_name FROM focus_sessions ORDER BY start_time").unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<SqliteResult<_>>().unwrap()
+        };
+        let end = day + chrono::Duration::days(1);
+
+        assert!(db.claim_session_name("alpha", false).unwrap());
+        // A taken name changes nothing unless reuse is allowed
+        assert_eq!(db.adopt_unnamed_rows(day, end, "alpha", false).unwrap(), None);
+        assert_eq!(names(&db), ["", "", "alpha", "", ""]);
+
+        assert_eq!(db.adopt_unnamed_rows(day, end, "review", false).unwrap(), Some(2));
+        assert_eq!(names(&db), ["", "review", "alpha", "review", ""]);
+        assert_eq!(db.adopt_unnamed_rows(day, end, "other", false).unwrap(), Some(0));
+        assert!(db.claim_session_name("other", false).unwrap(), "an empty adopt claims nothing");
+        assert_eq!(db.adopt_unnamed_rows(end, end + chrono::Duration::days(1), "alpha", true).unwrap(), Some(1));
+        assert_eq!(names(&db), ["", "review", "alpha", "review", "alpha"]);
+    }
+
+    #[test]
+    fn plan_status_follows_the_clock() {
+        let db = memory_db();
+        let nine = DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc);
+        let hour = Duration::from_secs(3600);
+        db.add_planned_session("missed", nine - chrono::Duration::hours(3), hour).unwrap();
+        let done = db.add_planned_session("done", nine, hour).unwrap();
+        let cut = db.add_planned_session("cut", nine + chrono::Duration::hours(2), hour).unwrap();
+        db.add_planned_session("later", nine + chrono::Duration::days(8), hour).unwrap();
+
+        db.mark_plan_started(done, nine).unwrap();
+        db.mark_plan_completed(done, nine + chrono::Duration::hours(1)).unwrap();
+        db.mark_plan_started(cut, nine + chrono::Duration::hours(2)).unwrap();
+
+        let statuses = |now: DateTime<Utc>| -> Vec<(String, PlanStatus)> {
+            db.get_planned_sessions(nine - chrono::Duration::days(1), Some(nine + chrono::Duration::days(7))).unwrap()
+                .into_iter().map(|plan| (plan.name.clone(), plan.status(now))).collect()
+        };
+        let at = |hours: i64| nine + chrono::Duration::hours(hours);
+        assert_eq!(statuses(at(2) + chrono::Duration::minutes(30)), [
+            ("missed".to_string(), PlanStatus::Missed),
+            ("done".to_string(), PlanStatus::Completed),
+            ("cut".to_string(), PlanStatus::Running),
+        ]);
+        // The daemon stopped before the end
+        assert_eq!(statuses(at(4))[2].1, PlanStatus::CutShort);
+
+        // Due plans are the unstarted ones still inside their window
+        let later = db.get_planned_sessions(nine + chrono::Duration::days(1), None).unwrap();
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].status(nine), PlanStatus::Planned);
+        assert_eq!(later[0].status(later[0].start_time), PlanStatus::Due);
+        assert!(db.due_planned_session(at(-3) + chrono::Duration::minutes(30)).unwrap().is_some_and(|plan| plan.name == "missed"));
+        assert!(db.due_planned_session(at(1)).unwrap().is_none());
+        assert!(db.due_planned_session(later[0].start_time).unwrap().is_some_and(|plan| plan.name == "later"));
+
+        assert_eq!(crate::digest::describe_plans(&db.get_planned_sessions(at(-4), Some(at(4))).unwrap(), at(4)), "1 of 3 planned sessions completed");
+    }
+
+    #[test]
+    fn open_rows_are_settled_on_every_read() {
+        let db = memory_db();
+        let now = Utc::now().timestamp();
+        let hours = |n: i64| n * 3600;
+        // Crashed three hours ago, tracking resumed an hour later
+        insert_row(&db, now - hours(3), None, "fd-editor", "main.rs", 0);
+        insert_row(&db, now - hours(2), Some(now - hours(2) + 600), "fd-chat", "#team", 600);
+        // Still open, the latest row
+        insert_row(&db, now - 1800, None, "fd-term", "cargo", 60);
+
+        let from = DateTime::from_timestamp(now - hours(4), 0).unwrap();
+        let to = DateTime::from_timestamp(now + 60, 0).unwrap();
+        let rows = db.get_sessions_between(from, to).unwrap();
+        let summary: Vec<(&str, OpenEnd)> = rows.iter().map(|row| (row.app_name.as_str(), row.open_end)).collect();
+        assert_eq!(summary, [("fd-editor", OpenEnd::Open), ("fd-chat", OpenEnd::Closed), ("fd-term", OpenEnd::Open)]);
+        assert_eq!(rows[0].end_time, DateTime::from_timestamp(now - hours(2), 0));
+        assert_eq!(rows[0].duration, Duration::from_secs(3600));
+        assert!(rows[2].duration >= Duration::from_secs(1800), "{:?}", rows[2].duration);
+        assert!(rows[2].end_time.unwrap() >= DateTime::from_timestamp(now, 0).unwrap());
+
+        // The same by name, and a row open since last week is corrupt
+        insert_row(&db, now - hours(24 * 7), None, "fd-editor", "old.rs", 120);
+        let rows = db.get_sessions_by_name("TEST").unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].open_end, OpenEnd::Corrupt);
+        assert_eq!(rows[0].duration, Duration::from_secs(12 * 3600));
+        assert_eq!(rows.iter().filter(|row| row.is_open_ended()).count(), 3);
+    }
+
+    #[test]
+    fn power_sources_round_trip_and_unknown_ones_read_as_unrecorded() {
+        let db = memory_db();
+        let start = DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc);
+        for (minute, power) in [(0, Some(PowerSource::Ac)), (10, Some(PowerSource::Battery)), (20, None)] {
+            let row = FocusSession { power, ..session(start + chrono::Duration::minutes(minute), "fd-editor", 600) };
+            db.save_focus_session(&row).unwrap();
+        }
+        // Written by a newer build
+        db.conn.execute("UPDATE focus_sessions SET power = 'solar' WHERE power IS NULL", []).unwrap();
+        db.save_focus_session(&FocusSession { power: None, ..session(start + chrono::Duration::minutes(30), "fd-editor", 600) }).unwrap();
+
+        let rows = db.get_sessions_between(start, start + chrono::Duration::hours(1)).unwrap();
+        let powers: Vec<Option<PowerSource>> = rows.iter().map(|row| row.power).collect();
+        assert_eq!(powers, [Some(PowerSource::Ac), Some(PowerSource::Battery), None, None]);
+    }
+
+    #[test]
+    fn overlapping_rows_are_found_by_time_not_by_start() {
+        let db = memory_db();
+        let at = |hours: i64, minutes: i64| DateTime::parse_from_rfc3339("2024-05-21T00:00:00Z").unwrap().with_timezone(&Utc)
+            + chrono::Duration::hours(hours) + chrono::Duration::minutes(minutes);
+        db.save_focus_session(&session(at(9, 0), "fd-editor", 3600)).unwrap();
+        db.save_focus_session(&session(at(10, 0), "fd-chat", 1800)).unwrap();
+        // Started the day before and ran past midnight
+        db.save_focus_session(&session(at(-1, 0), "fd-term", 2 * 3600)).unwrap();
+        // Saved without an end, it counts up to its duration
+        insert_row(&db, at(12, 0).timestamp(), None, "fd-notes", "todo", 600);
+
+        let apps = |start: DateTime<Utc>, end: DateTime<Utc>| -> Vec<String> {
+            db.get_overlapping_sessions(start, end).unwrap().into_iter().map(|row| row.app_name).collect()
+        };
+        assert_eq!(apps(at(9, 30), at(10, 15)), ["fd-editor", "fd-chat"]);
+        assert_eq!(apps(at(0, 30), at(1, 0)), ["fd-term"]);
+        assert_eq!(apps(at(12, 5), at(12, 6)), ["fd-notes"]);
+        // Touching ends don't overlap
+        assert!(apps(at(8, 0), at(9, 0)).is_empty());
+        assert!(apps(at(10, 30), at(11, 0)).is_empty());
+        assert!(apps(at(12, 10), at(13, 0)).is_empty());
+    }
+
+    #[test]
+    fn notification_counts_follow_their_session() {
+        let db = memory_db();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        for (session, d, source) in [("Morning", 4, "slack"), ("morning", 4, "slack"), ("morning", 5, "email"), ("evening", 5, "slack")] {
+            db.add_notification(session, day(d), source).unwrap();
+        }
+        let mut counts = db.get_session_notifications("MORNING").unwrap();
+        counts.sort();
+        assert_eq!(counts, [("email".to_string(), 1), ("slack".to_string(), 2)]);
+
+        let by_day = db.get_notifications_by_day(day(4), day(6)).unwrap();
+        assert_eq!(by_day[&day(4)], [("slack".to_string(), 2)]);
+        let mut fifth = by_day[&day(5)].clone();
+        fifth.sort();
+        assert_eq!(fifth, [("email".to_string(), 1), ("slack".to_string(), 1)]);
+        assert!(db.get_notifications_by_day(day(6), day(7)).unwrap().is_empty());
+
+        db.rename_session("morning", "deep work").unwrap();
+        assert!(db.get_session_notifications("morning").unwrap().is_empty());
+        assert_eq!(db.get_session_notifications("deep work").unwrap().len(), 2);
+        db.delete_session("deep work").unwrap();
+        assert!(db.get_session_notifications("deep work").unwrap().is_empty());
+        assert_eq!(db.get_session_notifications("evening").unwrap(), [("slack".to_string(), 1)]);
+        db.clear_all_data().unwrap();
+        assert!(db.get_notifications_by_day(day(1), day(31)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn intents_and_ratings_follow_their_session() {
+        let db = memory_db();
+        let started = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z").unwrap().with_timezone(&Utc);
+        db.set_session_intent("Morning", started, Some("write the RFC draft"), Some(Duration::from_secs(2 * 3600))).unwrap();
+        let intent = db.get_session_intent("morning").unwrap().unwrap();
+        assert_eq!(intent, SessionIntent {
+            started_at: started,
+            intent: Some("write the RFC draft".to_string()),
+            expected: Some(Duration::from_secs(2 * 3600)),
+            rating: None,
+            intent_met: None,
+        });
+
+        // Rating keeps the intent and when the session started
+        db.set_session_outcome("MORNING", 4, Some(true)).unwrap();
+        let intent = db.get_session_intent("Morning").unwrap().unwrap();
+        assert_eq!((intent.started_at, intent.rating, intent.intent_met), (started, Some(4), Some(true)));
+        assert_eq!(intent.intent.as_deref(), Some("write the RFC draft"));
+        // And stating the intent again keeps the rating
+        db.set_session_intent("Morning", started, Some("finish the RFC draft"), None).unwrap();
+        let intent = db.get_session_intent("Morning").unwrap().unwrap();
+        assert_eq!((intent.intent.as_deref(), intent.expected, intent.rating), (Some("finish the RFC draft"), None, Some(4)));
+
+        // A session rated without an intent is dated by its first row
+        let evening = started + chrono::Duration::hours(9);
+        for minutes in [30, 0, 60] {
+            let row = session(evening + chrono::Duration::minutes(minutes), "fd-editor", 60);
+            db.save_focus_session(&FocusSession { session_name: "evening".to_string(), ..row }).unwrap();
+        }
+        db.set_session_outcome("Evening", 2, None).unwrap();
+        let intent = db.get_session_intent("evening").unwrap().unwrap();
+        assert_eq!((intent.started_at, intent.intent, intent.rating, intent.intent_met), (evening, None, Some(2), None));
+        assert_eq!(db.get_session_intents().unwrap().len(), 2);
+
+        db.rename_session("Morning", "deep work").unwrap();
+        assert_eq!(db.get_session_intent("morning").unwrap(), None);
+        assert_eq!(db.get_session_intent("deep work").unwrap().unwrap().rating, Some(4));
+        db.delete_session("deep work").unwrap();
+        assert_eq!(db.get_session_intent("deep work").unwrap(), None);
+        assert!(db.get_session_intent("evening").unwrap().is_some());
+        db.clear_all_data().unwrap();
+        assert!(db.get_session_intents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn intents_and_outcomes_describe_what_they_have() {
+        let intent = SessionIntent {
+            intent: Some("write the\x1b[2J RFC draft".to_string()),
+            expected: Some(Duration::from_secs(90 * 60)),
+            ..SessionIntent::default()
+        };
+        assert_eq!(intent.describe_intent().as_deref(), Some("write the\\u{1b}[2J RFC draft (expected 1h 30m)"));
+        assert_eq!(intent.describe_outcome(), None);
+        let rated = |rating, intent_met| SessionIntent { rating: Some(rating), intent_met, ..SessionIntent::default() };
+        assert_eq!(rated(4, Some(true)).describe_outcome().as_deref(), Some("4/5, intent met"));
+        assert_eq!(rated(2, Some(false)).describe_outcome().as_deref(), Some("2/5, intent not met"));
+        assert_eq!(rated(3, None).describe_outcome().as_deref(), Some("3/5"));
+        assert_eq!(rated(3, None).describe_intent(), None);
+    }
+
+    #[test]
+    fn app_sightings_span_each_apps_rows_ignoring_case() {
+        let db = memory_db();
+        assert!(db.get_app_sightings().unwrap().is_empty());
+        let start = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z").unwrap().with_timezone(&Utc);
+        let days = |n| chrono::Duration::days(n);
+        for (offset, app, seconds) in [(days(3), "fd-term", 600), (days(0), "fd-editor", 60), (days(9), "FD-Editor", 120), (days(1), "fd-editor", 30)] {
+            db.save_focus_session(&session(start + offset, app, seconds)).unwrap();
+        }
+        let sightings: Vec<_> = db.get_app_sightings().unwrap().into_iter()
+            .map(|sighting| (sighting.app_name.to_lowercase(), sighting.first_seen, sighting.last_seen, sighting.total.as_secs()))
+            .collect();
+        assert_eq!(sightings, [
+            ("fd-editor".to_string(), start, start + days(9) + chrono::Duration::seconds(120), 210),
+            ("fd-term".to_string(), start + days(3), start + days(3) + chrono::Duration::seconds(600), 600),
+        ]);
+    }
+
+    fn task(source: TaskSource, id: &str, uuid: Option<&str>, description: Option<&str>) -> SessionTask {
+        SessionTask {
+            started_at: DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z").unwrap().with_timezone(&Utc),
+            source,
+            id: id.to_string(),
+            uuid: uuid.map(str::to_string),
+            description: description.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn session_tasks_follow_their_session() {
+        let db = memory_db();
+        let rfc = task(TaskSource::Taskwarrior, "123", Some("6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42"), Some("write the RFC draft"));
+        db.set_session_task("Morning", &rfc).unwrap();
+        assert_eq!(db.get_session_task("MORNING").unwrap(), Some(rfc.clone()));
+        // Linking again replaces the task
+        let plumber = task(TaskSource::TodoTxt, "Call the plumber", None, None);
+        db.set_session_task("morning", &plumber).unwrap();
+        assert_eq!(db.get_session_task("Morning").unwrap(), Some(plumber));
+        db.set_session_task("evening", &rfc).unwrap();
+
+        // A source this version doesn't know is left out
+        db.conn.execute(
+            "INSERT INTO session_tasks (session_name, started_at, source, task_id) VALUES ('later', 0, 'jira', 'FD-1')",
+            [],
+        ).unwrap();
+        assert_eq!(db.get_session_task("later").unwrap(), None);
+        assert_eq!(db.get_session_tasks().unwrap().keys().collect::<Vec<_>>(), ["evening", "morning"]);
+
+        db.rename_session("morning", "deep work").unwrap();
+        assert_eq!(db.get_session_task("morning").unwrap(), None);
+        assert_eq!(db.get_session_task("deep work").unwrap().unwrap().source, TaskSource::TodoTxt);
+        db.delete_session("deep work").unwrap();
+        assert_eq!(db.get_session_task("deep work").unwrap(), None);
+        assert_eq!(db.get_session_task("evening").unwrap(), Some(rfc));
+        db.clear_all_data().unwrap();
+        assert!(db.get_session_tasks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn session_tasks_describe_and_match_queries() {
+        let rfc = task(TaskSource::Taskwarrior, "123", Some("6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42"), Some("write the RFC draft"));
+        assert_eq!(rfc.describe(), "task 123: write the RFC draft");
+        assert_eq!(task(TaskSource::Taskwarrior, "7", None, None).describe(), "task 7");
+        let line = task(TaskSource::TodoTxt, "(A) Call mom\x07 +family", None, None);
+        assert_eq!(line.describe(), "todo.txt: (A) Call mom\\u{7} +family");
+        assert_eq!(TaskSource::parse(TaskSource::TodoTxt.as_str()), Some(TaskSource::TodoTxt));
+        assert_eq!(TaskSource::parse(TaskSource::Taskwarrior.as_str()), Some(TaskSource::Taskwarrior));
+
+        // The uuid the query resolves to settles it
+        assert!(rfc.matches("123", Some("6F1C3A2E-94B1-4C55-8D0E-3B7A9E1F0C42")));
+        assert!(!rfc.matches("123", Some("00000000-0000-0000-0000-000000000000")));
+        // Without one: the id, or text of the description or uuid
+        assert!(rfc.matches(" 123 ", None));
+        assert!(!rfc.matches("12", None));
+        assert!(rfc.matches("rfc DRAFT", None));
+        assert!(rfc.matches("6f1c3a2e", None));
+        assert!(!rfc.matches("  ", None));
+        assert!(line.matches("call mom", None));
+        // A uuid for a task linked without one doesn't rule it out
+        assert!(line.matches("+family", Some("6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42")));
+    }
+
+    /// Every session, switch and event `db` reads, in a form that compares
+    /// as a multiset once sorted
+    fn every_row(db: &Database) -> Vec<String> {
+        let (start, end) = (DateTime::<Utc>::UNIX_EPOCH, Utc::now() + chrono::Duration::days(365));
+        let sessions = db.get_sessions_between(start, end).unwrap().into_iter()
+            .map(|row| format!("session {} {} {} {}s {}", row.start_time, row.session_name, row.app_name, row.duration.as_secs(), row.is_focus_app));
+        let switches = db.get_context_switches_between(start, end).unwrap().into_iter()
+            .map(|switch| format!("switch {} {} {}", switch.timestamp, switch.from_app, switch.to_app));
+        let events = db.get_events_between(start, end, None).unwrap().into_iter()
+            .map(|event| format!("event {} {} {}", event.timestamp, event.kind.as_str(), event.payload));
+        let mut rows: Vec<String> = sessions.chain(switches).chain(events).collect();
+        rows.sort();
+        rows
+    }
+
+    /// Rows in `table` of the database file at `path`
+    fn count_rows(path: &Path, table: &str) -> usize {
+        Connection::open(path).unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0))
+            .unwrap() as usize
+    }
+
+    /// A day a session, a switch and an event, from 2024-01-01 for `days`
+    /// days; the last few days belong to the running session "current"
+    fn fill_history(db: &Database, days: i64) -> DateTime<Utc> {
+        let first = DateTime::parse_from_rfc3339("2024-01-01T09:00:00Z").unwrap().with_timezone(&Utc);
+        // One commit for the lot keeps file-backed tests quick
+        let tx = db.conn.unchecked_transaction().unwrap();
+        for day in 0..days {
+            let start = first + chrono::Duration::days(day);
+            let name = if day >= days - 3 { "current" } else { "history" };
+            db.save_focus_session(&FocusSession { session_name: name.to_string(), is_focus_app: day % 2 == 0, ..session(start, "fd-editor", 600 + day as u64) }).unwrap();
+            db.save_context_switch(&ContextSwitch { timestamp: start, from_app: "fd-editor".to_string(), to_app: "fd-chat".to_string(), recovery_time: None }).unwrap();
+            db.save_event(&TrackerEvent { timestamp: start, kind: EventKind::DaemonStart, payload: serde_json::json!({ "day": day }) }).unwrap();
+        }
+        tx.commit().unwrap();
+        first
+    }
+
+    #[test]
+    fn archiving_moves_old_rows_without_losing_or_duplicating_any() {
+        let path = temp_db_path("archive-move");
+        let archive = path.with_file_name("archive.db");
+        let db = Database::open(&path).unwrap();
+        let first = fill_history(&db, 40);
+        let before = every_row(&db);
+        assert_eq!(before.len(), 120);
+
+        // "current" started on day 37, so with it kept nothing from then on moves
+        let cutoff = first + chrono::Duration::days(38);
+        let planned = db.count_archivable(cutoff, Some("Current")).unwrap();
+        let report = db.archive_history(cutoff, &archive, Some("Current")).unwrap();
+        assert_eq!((report.moved.sessions, report.moved.context_switches, report.moved.events), (37, 37, 37));
+        assert_eq!((planned.sessions, planned.context_switches, planned.events), (37, 37, 37));
+        assert_eq!(report.already_archived, 0);
+        assert_eq!(db.archive_paths().unwrap(), [std::path::absolute(&archive).unwrap()]);
+
+        // Each row is in exactly one of the two files
+        let live = every_row(&db);
+        assert_eq!(live.len(), 9);
+        assert!(live.iter().all(|row| !row.contains("history")), "{:#?}", live);
+        for table in ["focus_sessions", "context_switches", "events"] {
+            assert_eq!(count_rows(&archive, table), 37, "{}", table);
+        }
+        let archived = every_row(&Database::open_read_only(&archive).unwrap());
+        let mut both: Vec<String> = live.iter().chain(&archived).cloned().collect();
+        both.sort();
+        assert_eq!(both, before);
+
+        // Read with the archives attached, everything is there once
+        let reader = Database::open_read_only(&path).unwrap();
+        reader.attach_archives().unwrap();
+        assert_eq!(every_row(&reader), before);
+        drop(reader);
+
+        // Archiving again moves nothing more and notes the archive once
+        let again = db.archive_history(cutoff, &archive, Some("current")).unwrap();
+        assert_eq!((again.moved.sessions, again.moved.context_switches, again.moved.events, again.already_archived), (0, 0, 0, 0));
+        assert_eq!(db.archive_paths().unwrap().len(), 1);
+        // Without a running session the rest goes too, into the same file
+        let rest = db.archive_history(first + chrono::Duration::days(365), &archive, None).unwrap();
+        assert_eq!((rest.moved.sessions, rest.moved.context_switches, rest.moved.events), (3, 3, 3));
+        assert!(every_row(&db).is_empty());
+        let reader = Database::open_read_only(&path).unwrap();
+        reader.attach_archives().unwrap();
+        assert_eq!(every_row(&reader), before);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn archived_rows_are_read_from_every_archive_and_missing_ones_are_skipped() {
+        let path = temp_db_path("archive-several");
+        let (older, newer) = (path.with_file_name("2023.db"), path.with_file_name("2024.db"));
+        let db = Database::open(&path).unwrap();
+        let first = fill_history(&db, 20);
+        let before = every_row(&db);
+        db.archive_history(first + chrono::Duration::days(5), &older, None).unwrap();
+        db.archive_history(first + chrono::Duration::days(12), &newer, None).unwrap();
+        assert_eq!((count_rows(&older, "focus_sessions"), count_rows(&newer, "focus_sessions")), (5, 7));
+
+        let reader = Database::open_read_only(&path).unwrap();
+        reader.attach_archives().unwrap();
+        assert_eq!(every_row(&reader), before);
+        drop(reader);
+
+        std::fs::remove_file(&older).unwrap();
+        let reader = Database::open_read_only(&path).unwrap();
+        reader.attach_archives().unwrap();
+        assert_eq!(every_row(&reader).len(), before.len() - 15);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_session_row_the_archive_already_has_is_not_archived_twice() {
+        let path = temp_db_path("archive-again");
+        let archive = path.with_file_name("archive.db");
+        let db = Database::open(&path).unwrap();
+        let first = fill_history(&db, 10);
+        db.archive_history(first + chrono::Duration::days(5), &archive, None).unwrap();
+        // The live file restored from a backup brings an archived row back
+        let restored = FocusSession { session_name: "history".to_string(), is_focus_app: true, ..session(first, "fd-editor", 600) };
+        db.save_focus_session(&restored).unwrap();
+
+        let report = db.archive_history(first + chrono::Duration::days(5), &archive, None).unwrap();
+        assert_eq!((report.moved.sessions, report.already_archived), (1, 1));
+        assert_eq!(count_rows(&archive, "focus_sessions"), 5);
+        assert_eq!(count_rows(&path, "focus_sessions"), 5);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_failed_archive_run_leaves_both_files_as_they_were() {
+        let path = temp_db_path("archive-fail");
+        let archive = path.with_file_name("archive.db");
+        let db = Database::open(&path).unwrap();
+        let first = fill_history(&db, 10);
+        let before = every_row(&db);
+        // An archive whose events table takes no rows from this version;
+        // sessions and switches are moved before events are reached
+        Connection::open(&archive).unwrap().execute_batch(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY, timestamp INTEGER NOT NULL, kind TEXT NOT NULL, payload TEXT NOT NULL, origin TEXT NOT NULL)",
+        ).unwrap();
+
+        assert!(db.archive_history(first + chrono::Duration::days(5), &archive, None).is_err());
+        assert_eq!(every_row(&db), before);
+        assert!(db.archive_paths().unwrap().is_empty());
+        for table in ["focus_sessions", "context_switches", "events"] {
+            let exists: bool = Connection::open(&archive).unwrap().query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)", [table], |row| row.get(0),
+            ).unwrap();
+            assert!(!exists || count_rows(&archive, table) == 0, "{}", table);
+        }
+        // The archive was detached, so a good file still works
+        let good = path.with_file_name("good.db");
+        let report = db.archive_history(first + chrono::Duration::days(5), &good, None).unwrap();
+        assert_eq!(report.moved.sessions, 5);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+    #[test]
+    fn clearing_empties_every_recorded_table_at_once() {
+        let db = memory_db();
+        let at = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z").unwrap().with_timezone(&Utc);
+        fill_history(&db, 3);
+        db.conn.execute("INSERT INTO focus_sites (domain, added_at) VALUES ('docs.rs', ?1)", [at.to_rfc3339()]).unwrap();
+        db.add_planned_session("tomorrow", at + chrono::Duration::days(1), Duration::from_secs(3600)).unwrap();
+        db.set_session_tags("history", &["writing".to_string()]).unwrap();
+        db.record_daemon_version("0.1.0", at).unwrap();
+
+        // Every table but the schema's bookkeeping is cleared
+        let tables: Vec<String> = db.conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        let mut recorded: Vec<&str> = tables.iter().map(String::as_str).filter(|table| !["meta", "view_docs"].contains(table)).collect();
+        let mut cleared = CLEARED_TABLES.to_vec();
+        recorded.sort();
+        cleared.sort();
+        assert_eq!(recorded, cleared);
+
+        let rows = |table: &str| db.conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0)).unwrap();
+        for table in ["focus_sessions", "focus_sites", "planned_sessions", "session_tags", "daemon_versions"] {
+            assert!(rows(table) > 0, "{} has no rows to clear", table);
+        }
+        db.clear_all_data().unwrap();
+        for table in CLEARED_TABLES {
+            assert_eq!(rows(table), 0, "{} kept rows", table);
+        }
+        assert_eq!(Database::stored_schema_version(&db.conn).unwrap().map(|(version, _)| version), Some(SCHEMA_VERSION));
+
+        // A failing delete rolls the whole clear back
+        fill_history(&db, 1);
+        db.conn.execute_batch("CREATE TRIGGER keep_versions BEFORE DELETE ON daemon_versions BEGIN SELECT RAISE(ABORT, 'kept'); END").unwrap();
+        db.record_daemon_version("0.1.0", at).unwrap();
+        assert!(db.clear_all_data().is_err());
+        assert_eq!(rows("focus_sessions"), 1);
+    }
+
+    #[test]
+    fn rows_are_only_skipped_as_copies_of_the_same_session_title_and_duration() {
+        let db = memory_db();
+        let start = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z").unwrap().with_timezone(&Utc);
+        let row = FocusSession { session_name: "morning".to_string(), window_title: "main.rs".to_string(), ..session(start, "code", 60) };
+        db.save_focus_session(&row).unwrap();
+        // Saved twice, as the old double-save did: skipped
+        db.save_focus_session(&row).unwrap();
+        assert_eq!(row_count(&db, "focus_sessions"), 1);
+
+        // Same second, app and length, but another window or session: kept
+        db.save_focus_session(&FocusSession { window_title: "lib.rs".to_string(), ..row.clone() }).unwrap();
+        db.save_focus_session(&FocusSession { session_name: "pairing".to_string(), ..row.clone() }).unwrap();
+        assert_eq!(row_count(&db, "focus_sessions"), 3);
+        assert!(db.dedupe_sessions(true).unwrap().is_empty());
+
+        // Imports skip the same copies, and count only what they add
+        let imported = [("a".to_string(), row.clone()), ("b".to_string(), FocusSession { window_title: "tests.rs".to_string(), ..row.clone() })];
+        assert_eq!(db.import_focus_sessions_each("csv", &imported).unwrap(), [false, true]);
+        assert_eq!(row_count(&db, "focus_sessions"), 4);
+    }
+
+    #[test]
+    fn opening_replaces_the_legacy_dedupe_index_in_the_database_and_its_archives() {
+        let path = temp_db_path("legacy-dedupe-index");
+        let archive = path.with_file_name("archive.db");
+        let first = {
+            let db = Database::open(&path).unwrap();
+            let first = fill_history(&db, 5);
+            db.archive_history(first + chrono::Duration::days(2), &archive, None).unwrap();
+            // As older versions left both files
+            for schema in ["main", "archive"] {
+                let conn = if schema == "main" { Connection::open(&path).unwrap() } else { Connection::open(&archive).unwrap() };
+                conn.execute_batch(
+                    "DROP INDEX idx_focus_sessions_identity;
+                     CREATE UNIQUE INDEX idx_focus_sessions_dedupe ON focus_sessions (start_time, app_name, duration_seconds);",
+                ).unwrap();
+            }
+            first
+        };
+
+        let db = Database::open(&path).unwrap();
+        assert!(!index_exists(&db, "idx_focus_sessions_dedupe"));
+        assert!(index_exists(&db, "idx_focus_sessions_identity"));
+        assert_eq!(row_count(&db, "focus_sessions"), 3);
+
+        // A distinct row the old index refused is kept, here and once archived
+        let day = first + chrono::Duration::days(2);
+        let twin = FocusSession { session_name: "history".to_string(), window_title: "other".to_string(), is_focus_app: true, ..session(day, "fd-editor", 602) };
+        db.save_focus_session(&twin).unwrap();
+        assert_eq!(row_count(&db, "focus_sessions"), 4);
+        let report = db.archive_history(first + chrono::Duration::days(3), &archive, None).unwrap();
+        assert_eq!(report.moved.sessions, 2);
+        assert_eq!(count_rows(&archive, "focus_sessions"), 4);
+        let archived_indexes: Vec<String> = Connection::open(&archive).unwrap()
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'focus_sessions' AND sql IS NOT NULL ORDER BY name").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<SqliteResult<_>>().unwrap();
+        assert!(archived_indexes.contains(&"idx_focus_sessions_identity".to_string()), "{:?}", archived_indexes);
+        assert!(!archived_indexes.contains(&"idx_focus_sessions_dedupe".to_string()), "{:?}", archived_indexes);
+        drop(db);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn events_are_stored_filtered_by_kind_and_pruned() {
+        let db = memory_db();
+        let at = |secs: i64| DateTime::from_timestamp(1_704_067_200 + secs, 0).unwrap();
+        let config = Config { private_title_patterns: vec!["bank".to_string()], ..Config::default() };
+        let window = |app: &str, title: &str| WindowInfo {
+            app_name: app.to_string(),
+            title: title.to_string(),
+            window_id: Some("0x1".to_string()),
+            workspace: None,
+        };
+
+        db.save_event(&TrackerEvent { timestamp: at(0), kind: EventKind::DaemonStart, payload: serde_json::json!({ "version": "1" }) }).unwrap();
+        db.save_switches_and_events(&[], &[
+            TrackerEvent::window_change(&config, &window("code", "main.rs"), at(60)),
+            TrackerEvent::window_change(&config, &window("firefox", "My Bank - Login"), at(120)),
+        ]).unwrap();
+        db.save_event(&TrackerEvent { timestamp: at(3600), kind: EventKind::DaemonStop, payload: serde_json::json!({}) }).unwrap();
+
+        let all = db.get_events_between(at(0), at(3600), None).unwrap();
+        let kinds: Vec<&str> = all.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, ["daemon_start", "window_change", "window_change", "daemon_stop"]);
+
+        // `events --kind window_change`, with private titles never written
+        let changes = db.get_events_between(at(0), at(3600), EventKind::parse("window_change")).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].payload, serde_json::json!({ "app": "code", "title": "main.rs", "window": "0x1" }));
+        assert_eq!(changes[1].payload["title"], "[private]");
+        let stored: Vec<String> = db.conn.prepare("SELECT payload FROM events").unwrap()
+            .query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        assert!(stored.iter().all(|payload| !payload.contains("Bank")), "{:?}", stored);
+        assert!(db.get_events_between(at(0), at(3600), Some(EventKind::Alert)).unwrap().is_empty());
+
+        // Pruning takes what is older than the cutoff and nothing else
+        assert_eq!(db.count_events_before(at(3600)).unwrap(), 3);
+        assert_eq!(db.prune_events(at(3600), &mut Progress::hidden()).unwrap(), 3);
+        let left = db.get_events_between(at(0), at(3600), None).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].kind, EventKind::DaemonStop);
+        assert_eq!(db.prune_events(at(3600), &mut Progress::hidden()).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_only_queries_reject_anything_that_writes() {
+        let db = memory_db();
+        insert_row(&db, 1_704_067_200, Some(1_704_067_260), "code", "main.rs", 60);
+        insert_row(&db, 1_704_067_260, Some(1_704_067_320), "firefox", "docs; notes", 60);
+
+        let rejected = |sql: &str| match db.query_read_only(sql) {
+            Err(rusqlite::Error::SqliteFailure(_, Some(message))) => message,
+            other => panic!("{} was not rejected: {:?}", sql, other),
+        };
+        assert_eq!(rejected("DELETE FROM focus_sessions"), "only SELECT statements can be run");
+        assert_eq!(rejected("SELECT 1; DROP TABLE focus_sessions"), "only one statement can be run at a time");
+        assert_eq!(rejected("select 1;\n-- done\ndrop table focus_sessions;"), "only one statement can be run at a time");
+        assert_eq!(
+            rejected("WITH old AS (SELECT id FROM focus_sessions) DELETE FROM focus_sessions WHERE id IN (SELECT id FROM old)"),
+            "only SELECT statements can be run",
+        );
+        assert_eq!(row_count(&db, "focus_sessions"), 2);
+
+        let result = db.query_read_only("SELECT app_name, duration_seconds, session_name IS NULL FROM focus_sessions ORDER BY start_time").unwrap();
+        assert_eq!(result.columns, ["app_name", "duration_seconds", "session_name IS NULL"]);
+        assert_eq!(result.rows, [["code", "60", "0"], ["firefox", "60", "0"]]);
+
+        // A `;` in a string, or one ending the statement, is no second statement
+        let result = db.query_read_only("SELECT window_title FROM focus_sessions WHERE window_title LIKE '%;%'; -- titles with a semicolon").unwrap();
+        assert_eq!(result.rows, [["docs; notes"]]);
+    }
+
+    #[test]
+    fn trim_titles_cuts_multi_byte_titles_between_characters() {
+        let db = memory_db();
+        insert_row(&db, 1_704_067_200, Some(1_704_067_260), "code", "Straße – Überblick über Änderungen", 60);
+        insert_row(&db, 1_704_067_260, Some(1_704_067_320), "code", "日本語日本語日本語日本語日本語日本語", 60);
+        insert_row(&db, 1_704_067_320, Some(1_704_067_380), "code", "ÄÖÜ short", 60);
+        db.save_event(&TrackerEvent {
+            timestamp: DateTime::from_timestamp(1_704_067_200, 0).unwrap(),
+            kind: EventKind::WindowChange,
+            payload: serde_json::json!({ "app": "code", "title": "🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉" }),
+        }).unwrap();
+
+        let planned = db.trim_titles(16, true).unwrap();
+        assert_eq!((planned.sessions, planned.events), (2, 1));
+        let report = db.trim_titles(16, false).unwrap();
+        assert_eq!((report.sessions, report.events), (2, 1));
+        assert_eq!(report.bytes_cut, planned.bytes_cut);
+
+        let titles: Vec<String> = db.conn.prepare("SELECT window_title FROM focus_sessions ORDER BY start_time").unwrap()
+            .query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        assert_eq!(titles, ["Straße – Überbl…", "日本語日本語日本語日本語日本語…", "ÄÖÜ short"]);
+        let events = db.get_events_between(DateTime::from_timestamp(0, 0).unwrap(), Utc::now(), None).unwrap();
+        assert_eq!(events[0].payload["title"], "🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉…");
+        assert_eq!(db.trim_titles(16, false).unwrap().sessions, 0);
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::PathBuf;
+use std::process::Command;
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::storage::{SessionTask, TaskSource};
+
+/// Lines of a todo.txt match listed when the text is ambiguous
+const AMBIGUOUS_LINES_SHOWN: usize = 3;
+
+/// How focusdebt talks to taskwarrior, so it can be swapped for a fake
+pub trait Taskwarrior {
+    /// `task <args>`'s output, or why it couldn't be run or failed
+    fn run(&self, args: &[&str]) -> Result<String, String>;
+}
+
+/// The `task` program in PATH
+pub struct TaskCommand;
+
+impl Taskwarrior for TaskCommand {
+    fn run(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new("task")
+            // Never stop for a question; the daemon has no terminal to answer it
+            .args(["rc.confirmation=off", "rc.verbose=nothing"])
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => "taskwarrior ('task') not found in PATH".to_string(),
+                _ => format!("couldn't run task: {}", e),
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(match stderr.trim() {
+                "" => format!("task {} failed ({})", args.join(" "), output.status),
+                message => message.to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Whether `task` is a taskwarrior id or uuid rather than todo.txt text
+pub fn is_taskwarrior_id(task: &str) -> bool {
+    let task = task.trim();
+    let hex = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_hexdigit());
+    let uuid = task.split('-').map(str::len).collect::<Vec<_>>() == [8, 4, 4, 4, 12] && task.split('-').all(hex);
+    task.parse::<u64>().is_ok_and(|id| id > 0) || uuid
+}
+
+/// The uuid and description taskwarrior has for `id`; Err when it has no
+/// such task or couldn't be asked
+pub fn lookup(taskwarrior: &impl Taskwarrior, id: &str) -> Result<(String, String), String> {
+    let uuid = taskwarrior.run(&["_get", &format!("{}.uuid", id)])?.trim().to_string();
+    if uuid.is_empty() {
+        return Err(format!("taskwarrior has no task {}", id));
+    }
+    let description = taskwarrior.run(&["_get", &format!("{}.description", id)])?.trim().to_string();
+    Ok((uuid, description))
+}
+
+/// `task <uuid> start`, or with `start` false `task <uuid> stop`
+pub fn set_active(taskwarrior: &impl Taskwarrior, task: &SessionTask, start: bool) -> Result<(), String> {
+    let filter = task.uuid.as_deref().unwrap_or(&task.id);
+    taskwarrior.run(&[filter, if start { "start" } else { "stop" }]).map(|_| ())
+}
+
+/// The todo.txt file tasks are looked up in: todo_txt_file, $TODO_FILE or
+/// ~/todo.txt, whichever is set first; None when that file doesn't exist
+pub fn todo_txt_path(config: &Config) -> Option<PathBuf> {
+    let path = match config.todo_txt_file.as_deref().map(str::to_string).or_else(|| std::env::var("TODO_FILE").ok()) {
+        Some(path) => match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()?.join(rest),
+            None => PathBuf::from(path),
+        },
+        None => dirs::home_dir()?.join("todo.txt"),
+    };
+    path.is_file().then_some(path)
+}
+
+/// The open line of `contents` holding `text`, ignoring case. Done lines
+/// ("x ...") are skipped; more than one match is an error listing them.
+pub fn match_todo_line(contents: &str, text: &str) -> Result<String, String> {
+    let query = text.trim().to_lowercase();
+    let matches: Vec<&str> = contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("x "))
+        .filter(|line| line.to_lowercase().contains(&query))
+        .collect();
+    match matches.as_slice() {
+        [] => Err(format!("no open todo.txt line contains '{}'", text.trim())),
+        [line] => Ok(line.to_string()),
+        lines => {
+            let mut shown: Vec<String> = lines.iter().take(AMBIGUOUS_LINES_SHOWN).map(|line| format!("'{}'", line)).collect();
+            if lines.len() > AMBIGUOUS_LINES_SHOWN {
+                shown.push(format!("{} more", lines.len() - AMBIGUOUS_LINES_SHOWN));
+            }
+            Err(format!("{} todo.txt lines contain '{}': {}; use more of the line", lines.len(), text.trim(), shown.join(", ")))
+        }
+    }
+}
+
+/// The task `start --task` was given: a taskwarrior id or uuid, looked up
+/// so the link survives taskwarrior renumbering, else the todo.txt line
+/// holding the text. With taskwarrior_integration off, a taskwarrior that
+/// can't be asked only costs the description.
+pub fn resolve(taskwarrior: &impl Taskwarrior, config: &Config, task: &str) -> Result<SessionTask, String> {
+    let task = task.trim();
+    if task.is_empty() {
+        return Err("the task is empty".to_string());
+    }
+    if is_taskwarrior_id(task) {
+        let (uuid, description) = match lookup(taskwarrior, task) {
+            Ok((uuid, description)) => (Some(uuid), Some(description).filter(|description| !description.is_empty())),
+            Err(e) if config.taskwarrior_integration => return Err(e),
+            Err(_) => (None, None),
+        };
+        return Ok(SessionTask { started_at: Utc::now(), source: TaskSource::Taskwarrior, id: task.to_string(), uuid, description });
+    }
+    let path = todo_txt_path(config)
+        .ok_or("no todo.txt found; set todo_txt_file or TODO_FILE, or give a taskwarrior id")?;
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    let line = match_todo_line(&contents, task)?;
+    Ok(SessionTask { started_at: Utc::now(), source: TaskSource::TodoTxt, id: line, uuid: None, description: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Taskwarrior answering `replies` by the arguments it's run with, and
+    /// with nothing otherwise; every run is kept in `calls`
+    struct FakeTaskwarrior {
+        replies: Vec<(&'static str, Result<&'static str, &'static str>)>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl FakeTaskwarrior {
+        fn new(replies: Vec<(&'static str, Result<&'static str, &'static str>)>) -> Self {
+            Self { replies, calls: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl Taskwarrior for FakeTaskwarrior {
+        fn run(&self, args: &[&str]) -> Result<String, String> {
+            let call = args.join(" ");
+            self.calls.borrow_mut().push(call.clone());
+            self.replies.iter()
+                .find(|(args, _)| *args == call)
+                .map_or(Ok(String::new()), |(_, reply)| reply.map(str::to_string).map_err(str::to_string))
+        }
+    }
+
+    const UUID: &str = "6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42";
+
+    fn task_123() -> FakeTaskwarrior {
+        FakeTaskwarrior::new(vec![
+            ("_get 123.uuid", Ok("6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c42\n")),
+            ("_get 123.description", Ok("write the RFC draft\n")),
+        ])
+    }
+
+    /// A config reading todo.txt from a file holding `contents`
+    fn with_todo_txt(name: &str, contents: &str) -> (Config, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("focusdebt-tasks-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todo.txt");
+        std::fs::write(&path, contents).unwrap();
+        (Config { todo_txt_file: Some(path.display().to_string()), ..Config::default() }, dir)
+    }
+
+    #[test]
+    fn taskwarrior_ids_are_positive_numbers_or_uuids() {
+        for task in ["1", " 123 ", UUID, "6F1C3A2E-94B1-4C55-8D0E-3B7A9E1F0C42"] {
+            assert!(is_taskwarrior_id(task), "{}", task);
+        }
+        for task in ["0", "-3", "12a", "call mom", "6f1c3a2e-94b1-4c55-8d0e", "6f1c3a2e-94b1-4c55-8d0e-3b7a9e1f0c4z", ""] {
+            assert!(!is_taskwarrior_id(task), "{}", task);
+        }
+    }
+
+    #[test]
+    fn lookup_asks_for_the_uuid_then_the_description() {
+        let taskwarrior = task_123();
+        assert_eq!(lookup(&taskwarrior, "123"), Ok((UUID.to_string(), "write the RFC draft".to_string())));
+        assert_eq!(*taskwarrior.calls.borrow(), ["_get 123.uuid", "_get 123.description"]);
+
+        // No such task: nothing after the uuid is asked
+        let taskwarrior = FakeTaskwarrior::new(Vec::new());
+        assert_eq!(lookup(&taskwarrior, "7"), Err("taskwarrior has no task 7".to_string()));
+        assert_eq!(taskwarrior.calls.borrow().len(), 1);
+        let missing = FakeTaskwarrior::new(vec![("_get 7.uuid", Err("taskwarrior ('task') not found in PATH"))]);
+        assert_eq!(lookup(&missing, "7"), Err("taskwarrior ('task') not found in PATH".to_string()));
+    }
+
+    #[test]
+    fn tasks_are_started_and_stopped_by_uuid_when_known() {
+        let taskwarrior = FakeTaskwarrior::new(vec![("9 stop", Err("Task 9 not started."))]);
+        let mut task = SessionTask { started_at: Utc::now(), source: TaskSource::Taskwarrior, id: "9".to_string(), uuid: Some(UUID.to_string()), description: None };
+        set_active(&taskwarrior, &task, true).unwrap();
+        set_active(&taskwarrior, &task, false).unwrap();
+        task.uuid = None;
+        assert_eq!(set_active(&taskwarrior, &task, false), Err("Task 9 not started.".to_string()));
+        assert_eq!(*taskwarrior.calls.borrow(), [format!("{} start", UUID), format!("{} stop", UUID), "9 stop".to_string()]);
+    }
+
+    #[test]
+    fn todo_lines_match_open_lines_by_text() {
+        let todo = "(A) Write the RFC draft +work\nx 2024-03-01 Call mom\n\n  Call the plumber  \nReview PR 42 +work\nReview PR 43 +work\nReview PR 44 +work\nReview PR 45 +work\n";
+        assert_eq!(match_todo_line(todo, " rfc DRAFT "), Ok("(A) Write the RFC draft +work".to_string()));
+        // Done lines don't count, so this one is unambiguous
+        assert_eq!(match_todo_line(todo, "call"), Ok("Call the plumber".to_string()));
+        assert_eq!(match_todo_line(todo, "call mom"), Err("no open todo.txt line contains 'call mom'".to_string()));
+        assert_eq!(match_todo_line(todo, "+work"), Err(
+            "5 todo.txt lines contain '+work': '(A) Write the RFC draft +work', 'Review PR 42 +work', 'Review PR 43 +work', 2 more; use more of the line".to_string()));
+        assert_eq!(match_todo_line(todo, "pr 44"), Ok("Review PR 44 +work".to_string()));
+    }
+
+    #[test]
+    fn resolve_links_a_taskwarrior_task_by_uuid() {
+        let task = resolve(&task_123(), &Config::default(), " 123 ").unwrap();
+        assert_eq!((task.source, task.id.as_str(), task.uuid.as_deref(), task.description.as_deref()),
+            (TaskSource::Taskwarrior, "123", Some(UUID), Some("write the RFC draft")));
+
+        // Taskwarrior that can't answer only matters with the integration on
+        let missing = FakeTaskwarrior::new(vec![("_get 123.uuid", Err("taskwarrior ('task') not found in PATH"))]);
+        let task = resolve(&missing, &Config::default(), "123").unwrap();
+        assert_eq!((task.id.as_str(), task.uuid, task.description), ("123", None, None));
+        let integrated = Config { taskwarrior_integration: true, ..Config::default() };
+        assert_eq!(resolve(&missing, &integrated, "123"), Err("taskwarrior ('task') not found in PATH".to_string()));
+        assert_eq!(resolve(&FakeTaskwarrior::new(Vec::new()), &integrated, "123"), Err("taskwarrior has no task 123".to_string()));
+        assert_eq!(resolve(&missing, &integrated, "  "), Err("the task is empty".to_string()));
+    }
+
+    #[test]
+    fn resolve_links_a_todo_txt_line_without_asking_taskwarrior() {
+        let (config, dir) = with_todo_txt("resolve", "(A) Write the RFC draft +work\nCall the plumber\n");
+        let taskwarrior = FakeTaskwarrior::new(Vec::new());
+        assert_eq!(todo_txt_path(&config), Some(dir.join("todo.txt")));
+        let task = resolve(&taskwarrior, &config, "plumber").unwrap();
+        assert_eq!((task.source, task.id.as_str(), task.uuid, task.description), (TaskSource::TodoTxt, "Call the plumber", None, None));
+        assert!(resolve(&taskwarrior, &config, "dentist").unwrap_err().starts_with("no open todo.txt line"));
+        assert!(taskwarrior.calls.borrow().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(todo_txt_path(&config), None);
+        assert_eq!(resolve(&taskwarrior, &config, "plumber").unwrap_err(),
+            "no todo.txt found; set todo_txt_file or TODO_FILE, or give a taskwarrior id");
+    }
+}
@@ -0,0 +1,330 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::glyphs;
+use crate::stats::DailyStats;
+
+/// Version of the `export --anonymize` layout
+pub const ANONYMIZED_SCHEMA_VERSION: u32 = 1;
+
+/// Fewer people than this and the aggregate would describe individuals
+pub const MIN_TEAM_SIZE: usize = 3;
+
+/// Keys that only appear in exports carrying raw data (titles, apps, sites,
+/// session names); a file with any of them is refused outright
+const RAW_KEYS: [&str; 9] = [
+    "window_title", "title", "app_name", "app", "domain", "session_name",
+    "most_used_apps", "most_distracting_apps", "rows",
+];
+
+/// Upper bounds (exclusive) of the efficiency buckets, in percent
+const EFFICIENCY_BUCKETS: [f64; 5] = [20.0, 40.0, 60.0, 80.0, f64::INFINITY];
+
+/// Days with this many deep focus sessions or more share the last bucket
+const DEEP_FOCUS_BUCKETS: usize = 4;
+
+/// `export --anonymize`: one person's days as plain numbers, without dates,
+/// apps, sites, titles or session names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnonymizedExport {
+    pub schema_version: u32,
+    /// Always true; a file without it wasn't written by the anonymizer
+    pub anonymized: bool,
+    pub days: Vec<AnonymizedDay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnonymizedDay {
+    pub focus_seconds: u64,
+    pub distraction_seconds: u64,
+    pub focus_efficiency: f64,
+    pub context_switches: usize,
+    pub deep_focus_sessions: usize,
+}
+
+impl AnonymizedDay {
+    fn tracked_seconds(&self) -> u64 {
+        self.focus_seconds + self.distraction_seconds
+    }
+}
+
+/// Anonymize daily stats; days with nothing tracked are left out
+pub fn anonymize(days: &[DailyStats]) -> AnonymizedExport {
+    AnonymizedExport {
+        schema_version: ANONYMIZED_SCHEMA_VERSION,
+        anonymized: true,
+        days: days.iter()
+            .filter(|day| day.total_focus_time + day.total_distraction_time > std::time::Duration::ZERO)
+            .map(|day| AnonymizedDay {
+                focus_seconds: day.total_focus_time.as_secs(),
+                distraction_seconds: day.total_distraction_time.as_secs(),
+                focus_efficiency: (day.focus_efficiency * 10.0).round() / 10.0,
+                context_switches: day.context_switches,
+                deep_focus_sessions: day.deep_focus_sessions,
+            })
+            .collect(),
+    }
+}
+
+/// Read one anonymized export, refusing anything that could carry raw
+/// titles or names: files with such keys, without the anonymized marker,
+/// or with fields this version doesn't know
+pub fn parse_export(text: &str) -> Result<AnonymizedExport, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("not JSON: {}", e))?;
+    if let Some(key) = find_raw_key(&value) {
+        return Err(format!("contains raw data (\"{}\"); only files from 'focusdebt export --anonymize' are accepted", key));
+    }
+    if value.get("anonymized") != Some(&serde_json::Value::Bool(true)) {
+        return Err("is not marked as anonymized; create it with 'focusdebt export --anonymize'".to_string());
+    }
+    let export: AnonymizedExport = serde_json::from_value(value).map_err(|e| format!("is not an anonymized export: {}", e))?;
+    if export.schema_version != ANONYMIZED_SCHEMA_VERSION {
+        return Err(format!("has schema version {}, expected {}", export.schema_version, ANONYMIZED_SCHEMA_VERSION));
+    }
+    Ok(export)
+}
+
+fn find_raw_key(value: &serde_json::Value) -> Option<&str> {
+    match value {
+        serde_json::Value::Object(map) => map.iter().find_map(|(key, value)| {
+            if RAW_KEYS.contains(&key.as_str()) {
+                Some(key.as_str())
+            } else {
+                find_raw_key(value)
+            }
+        }),
+        serde_json::Value::Array(items) => items.iter().find_map(find_raw_key),
+        _ => None,
+    }
+}
+
+/// Every *.json file in `dir`, parsed, in file name order. The first file
+/// that fails is named in the error.
+pub fn read_exports(dir: &Path) -> Result<Vec<AnonymizedExport>, String> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+    paths.iter()
+        .map(|path| {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            parse_export(&text).map_err(|e| format!("{} {}", path.display(), e))
+        })
+        .collect()
+}
+
+/// Combined numbers over everyone's days; nothing in it is per person
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamAggregate {
+    pub people: usize,
+    pub days: usize,
+    /// 25th, 50th and 75th percentile of daily focus efficiency
+    pub efficiency_quartiles: [f64; 3],
+    /// Days per efficiency bucket (0-20%, 20-40%, ..., 80-100%)
+    pub efficiency_histogram: [usize; 5],
+    pub median_switches_per_hour: f64,
+    /// Days with 0, 1, 2, 3 and 4+ deep focus sessions
+    pub deep_focus_histogram: [usize; DEEP_FOCUS_BUCKETS + 1],
+}
+
+/// Pool the days of all exports. Needs `MIN_TEAM_SIZE` people with at
+/// least one tracked day each.
+pub fn aggregate(exports: &[AnonymizedExport]) -> Result<TeamAggregate, String> {
+    let people = exports.iter().filter(|export| !export.days.is_empty()).count();
+    if people < MIN_TEAM_SIZE {
+        return Err(format!(
+            "Need exports with tracked days from at least {} people, found {}; fewer would describe individuals",
+            MIN_TEAM_SIZE, people
+        ));
+    }
+    let days: Vec<&AnonymizedDay> = exports.iter().flat_map(|export| &export.days).collect();
+
+    let mut efficiencies: Vec<f64> = days.iter().map(|day| day.focus_efficiency.clamp(0.0, 100.0)).collect();
+    efficiencies.sort_by(f64::total_cmp);
+    let mut efficiency_histogram = [0; 5];
+    for efficiency in &efficiencies {
+        let bucket = EFFICIENCY_BUCKETS.iter().position(|bound| efficiency < bound).unwrap_or(EFFICIENCY_BUCKETS.len() - 1);
+        efficiency_histogram[bucket] += 1;
+    }
+
+    let mut switch_rates: Vec<f64> = days.iter()
+        .filter(|day| day.tracked_seconds() > 0)
+        .map(|day| day.context_switches as f64 / (day.tracked_seconds() as f64 / 3600.0))
+        .collect();
+    switch_rates.sort_by(f64::total_cmp);
+
+    let mut deep_focus_histogram = [0; DEEP_FOCUS_BUCKETS + 1];
+    for day in &days {
+        deep_focus_histogram[day.deep_focus_sessions.min(DEEP_FOCUS_BUCKETS)] += 1;
+    }
+
+    Ok(TeamAggregate {
+        people,
+        days: days.len(),
+        efficiency_quartiles: [
+            percentile(&efficiencies, 0.25),
+            percentile(&efficiencies, 0.5),
+            percentile(&efficiencies, 0.75),
+        ],
+        efficiency_histogram,
+        median_switches_per_hour: percentile(&switch_rates, 0.5),
+        deep_focus_histogram,
+    })
+}
+
+/// Linear interpolation between the closest ranks of sorted `values`
+fn percentile(values: &[f64], fraction: f64) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        len => {
+            let rank = fraction * (len - 1) as f64;
+            let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+            values[low] + (values[high] - values[low]) * (rank - low as f64)
+        }
+    }
+}
+
+pub fn render(aggregate: &TeamAggregate) -> String {
+    let bar = |count: usize| {
+        let width = (count * 30).div_ceil(aggregate.days.max(1));
+        glyphs::current().solid().to_string().repeat(width)
+    };
+    let mut text = format!("~=~ Team aggregate: {} people, {} tracked days\n\n", aggregate.people, aggregate.days);
+    let [q1, median, q3] = aggregate.efficiency_quartiles;
+    text.push_str(&format!("Focus efficiency: median {:.0}% (middle half {:.0}%-{:.0}%)\n", median, q1, q3));
+    let labels = ["0-20%", "20-40%", "40-60%", "60-80%", "80-100%"];
+    for (label, count) in labels.iter().zip(aggregate.efficiency_histogram) {
+        text.push_str(&format!("  {:<8} {:>4} days  {}\n", label, count, bar(count)));
+    }
+    text.push_str(&format!("\nContext switches: median {:.1} per tracked hour\n", aggregate.median_switches_per_hour));
+    text.push_str("\nDeep focus sessions per day:\n");
+    for (sessions, count) in aggregate.deep_focus_histogram.iter().enumerate() {
+        let label = if sessions == DEEP_FOCUS_BUCKETS { format!("{}+", sessions) } else { sessions.to_string() };
+        text.push_str(&format!("  {:<8} {:>4} days  {}\n", label, count, bar(*count)));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use chrono::{DateTime, Utc};
+    use crate::stats::{DayRecords, Stats, StatsSettings};
+    use crate::utils::LocalDays;
+    use crate::utils::timezone::Zone;
+
+    fn tracked(focus: u64, distraction: u64, efficiency: f64, switches: usize, deep: usize) -> AnonymizedDay {
+        AnonymizedDay {
+            focus_seconds: focus,
+            distraction_seconds: distraction,
+            focus_efficiency: efficiency,
+            context_switches: switches,
+            deep_focus_sessions: deep,
+        }
+    }
+
+    fn person(days: Vec<AnonymizedDay>) -> AnonymizedExport {
+        AnonymizedExport { schema_version: ANONYMIZED_SCHEMA_VERSION, anonymized: true, days }
+    }
+
+    fn team() -> Vec<AnonymizedExport> {
+        vec![
+            person(vec![tracked(3600, 0, 90.0, 4, 2), tracked(1800, 1800, 50.0, 6, 1)]),
+            person(vec![tracked(0, 3600, 0.0, 12, 0)]),
+            person(Vec::new()),
+            person(vec![tracked(7200, 0, 100.0, 2, 5)]),
+        ]
+    }
+
+    #[test]
+    fn anonymize_keeps_only_numbers() {
+        let date = DateTime::parse_from_rfc3339("2024-03-04T00:00:00Z").unwrap().with_timezone(&Utc);
+        let mut day = Stats::daily_stats_from_records(&StatsSettings::default(), &LocalDays::in_zone(Zone::Named(chrono_tz::UTC), 0), date.date_naive(), &DayRecords::default(), date + chrono::Duration::days(1));
+        let empty = day.clone();
+        day.total_focus_time = Duration::from_secs(2 * 3600);
+        day.total_distraction_time = Duration::from_secs(3600);
+        day.focus_efficiency = 200.0 / 3.0;
+        day.context_switches = 9;
+        day.deep_focus_sessions = 1;
+
+        let export = anonymize(&[empty, day]);
+        assert_eq!(export.days.len(), 1);
+        assert_eq!(export.days[0].focus_seconds, 7200);
+        assert_eq!(export.days[0].focus_efficiency, 66.7);
+
+        // What the exporter writes, the aggregator accepts
+        let text = serde_json::to_string_pretty(&export).unwrap();
+        assert!(!text.contains("2024"), "{}", text);
+        assert_eq!(parse_export(&text).unwrap().days[0].context_switches, 9);
+    }
+
+    #[test]
+    fn exports_with_raw_data_are_refused() {
+        let day = r#"{"focus_seconds": 60, "distraction_seconds": 0, "focus_efficiency": 100.0, "context_switches": 0, "deep_focus_sessions": 0}"#;
+        let export = |extra: &str, days: &str| format!(r#"{{"schema_version": 1, "anonymized": true{}, "days": [{}]}}"#, extra, days);
+        assert!(parse_export(&export("", day)).is_ok());
+
+        let nested = export("", &day.replace("{", r#"{"window_title": "Payroll - fd-browser", "#));
+        assert!(parse_export(&nested).unwrap_err().contains("raw data (\"window_title\")"));
+        let apps = export(r#", "most_used_apps": []"#, day);
+        assert!(parse_export(&apps).unwrap_err().contains("\"most_used_apps\""));
+        assert!(parse_export(&export(r#", "note": "mine""#, day)).unwrap_err().starts_with("is not an anonymized export"));
+        assert!(parse_export(&export("", day).replace("true", "false")).unwrap_err().starts_with("is not marked as anonymized"));
+        assert_eq!(parse_export(&export("", day).replace("\"schema_version\": 1", "\"schema_version\": 2")).unwrap_err(),
+            "has schema version 2, expected 1");
+        assert!(parse_export("{").unwrap_err().starts_with("not JSON"));
+    }
+
+    #[test]
+    fn aggregate_pools_days_without_naming_anyone() {
+        let aggregate = aggregate(&team()).unwrap();
+        assert_eq!(aggregate, TeamAggregate {
+            people: 3,
+            days: 4,
+            efficiency_quartiles: [37.5, 70.0, 92.5],
+            efficiency_histogram: [1, 0, 1, 0, 2],
+            // 4, 6, 12 and 1 per tracked hour
+            median_switches_per_hour: 5.0,
+            deep_focus_histogram: [1, 1, 1, 0, 1],
+        });
+
+        let text = render(&aggregate);
+        assert!(text.starts_with("~=~ Team aggregate: 3 people, 4 tracked days\n"), "{}", text);
+        assert!(text.contains("Context switches: median 5.0 per tracked hour"), "{}", text);
+        assert!(text.contains("  4+          1 days  "), "{}", text);
+
+        // Too few people with tracked days would describe individuals
+        let error = super::aggregate(&team()[1..3]).unwrap_err();
+        assert!(error.starts_with("Need exports with tracked days from at least 3 people, found 1"), "{}", error);
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_ranks() {
+        assert_eq!(percentile(&[], 0.5), 0.0);
+        assert_eq!(percentile(&[7.0], 0.25), 7.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0], 0.5), 2.0);
+        assert_eq!(percentile(&[0.0, 10.0], 0.75), 7.5);
+    }
+
+    #[test]
+    fn read_exports_names_the_first_bad_file() {
+        let dir = std::env::temp_dir().join(format!("focusdebt-team-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (index, export) in team().iter().enumerate() {
+            std::fs::write(dir.join(format!("person-{}.json", index)), serde_json::to_string(export).unwrap()).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not an export").unwrap();
+        assert_eq!(read_exports(&dir).unwrap().len(), 4);
+
+        std::fs::write(dir.join("raw.json"), r#"{"focus_sessions": [{"app_name": "fd-editor"}]}"#).unwrap();
+        let error = read_exports(&dir).unwrap_err();
+        assert!(error.contains("raw.json contains raw data (\"app_name\")"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Environment variable that turns timing on, like --profile-self
+pub const ENV_VAR: &str = "FOCUSDEBT_PROFILE";
+
+/// What a timed stretch of a command went to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Reading from SQLite
+    Query,
+    /// Turning rows into stats
+    Aggregate,
+    /// Formatting output
+    Render,
+    /// Reading import files
+    Parse,
+    /// Writing to SQLite
+    Write,
+}
+
+impl Phase {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Phase::Query => "query",
+            Phase::Aggregate => "aggregate",
+            Phase::Render => "render",
+            Phase::Parse => "parse",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// A phase's own time (without the scopes nested in it) and how many scopes it had
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTotal {
+    pub time: Duration,
+    pub calls: usize,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDER: Recorder = Recorder::new();
+
+thread_local! {
+    /// Time of the scopes nested in each open scope of this thread, innermost last
+    static OPEN: RefCell<Vec<Duration>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether FOCUSDEBT_PROFILE asks for timing
+pub fn requested_by_environment() -> bool {
+    std::env::var(ENV_VAR).is_ok_and(|value| !matches!(value.as_str(), "" | "0"))
+}
+
+/// Where scopes add up their time; the command's is RECORDER
+struct Recorder {
+    totals: Mutex<BTreeMap<Phase, PhaseTotal>>,
+}
+
+impl Recorder {
+    const fn new() -> Self {
+        Self { totals: Mutex::new(BTreeMap::new()) }
+    }
+
+    fn scope(&self, phase: Phase) -> Scope<'_> {
+        OPEN.with(|open| open.borrow_mut().push(Duration::ZERO));
+        Scope { phase, started: Some(Instant::now()), recorder: self }
+    }
+
+    fn totals(&self) -> Vec<(Phase, PhaseTotal)> {
+        self.totals.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(phase, total)| (*phase, *total)).collect()
+    }
+}
+
+/// Counts the time until it is dropped towards `phase`. Scopes opened
+/// while it runs count their time to their own phase, not this one, so
+/// the phases add up to the time spent. Disabled, it is a flag check.
+#[must_use = "the time is counted until the scope is dropped"]
+pub struct Scope<'a> {
+    phase: Phase,
+    started: Option<Instant>,
+    recorder: &'a Recorder,
+}
+
+pub fn scope(phase: Phase) -> Scope<'static> {
+    if !enabled() {
+        return Scope { phase, started: None, recorder: &RECORDER };
+    }
+    RECORDER.scope(phase)
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        let Some(started) = self.started else { return };
+        let elapsed = started.elapsed();
+        let nested = OPEN.with(|open| {
+            let mut open = open.borrow_mut();
+            let nested = open.pop().unwrap_or_default();
+            if let Some(parent) = open.last_mut() {
+                *parent += elapsed;
+            }
+            nested
+        });
+        let mut totals = self.recorder.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let total = totals.entry(self.phase).or_default();
+        total.time += elapsed.saturating_sub(nested);
+        total.calls += 1;
+    }
+}
+
+/// Each phase's totals so far, in Phase order
+pub fn totals() -> Vec<(Phase, PhaseTotal)> {
+    RECORDER.totals()
+}
+
+/// e.g. "0.4ms", "420ms" or "1.2s"
+fn format_time(time: Duration) -> String {
+    if time < Duration::from_millis(10) {
+        format!("{:.1}ms", time.as_secs_f64() * 1000.0)
+    } else if time < Duration::from_secs(1) {
+        format!("{}ms", time.as_millis())
+    } else {
+        format!("{:.1}s", time.as_secs_f64())
+    }
+}
+
+/// The breakdown printed after the command: each phase, the rest of the
+/// `wall` time as "other", and a one-line summary
+pub fn render_report(totals: &[(Phase, PhaseTotal)], wall: Duration) -> String {
+    let mut text = String::from("~=~ Time by phase:\n");
+    for (phase, total) in totals {
+        text.push_str(&format!("  {:<10} {:>8}  {:>5} {}\n",
+            phase.label(), format_time(total.time), total.calls, if total.calls == 1 { "call" } else { "calls" }));
+    }
+    let timed: Duration = totals.iter().map(|(_, total)| total.time).sum();
+    text.push_str(&format!("  {:<10} {:>8}\n", "other", format_time(wall.saturating_sub(timed))));
+    text.push_str(&format!("  {:<10} {:>8}\n", "total", format_time(wall)));
+    if timed > wall {
+        // Stats of several days are worked out on several threads at once
+        text.push_str("  (phases on parallel threads overlap, so they add up to more than the total)\n");
+    }
+    let summary: Vec<String> = totals.iter()
+        .map(|(phase, total)| format!("{}: {}", phase.label(), format_time(total.time)))
+        .collect();
+    if !summary.is_empty() {
+        text.push_str(&format!("~=~ {}\n", summary.join(", ")));
+    }
+    text
+}
+
+/// Prints the report to stderr when dropped at the end of the command, if
+/// timing is on
+pub struct Report {
+    started: Instant,
+}
+
+impl Report {
+    pub fn start() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Drop for Report {
+    fn drop(&mut self) {
+        if enabled() {
+            eprint!("{}", render_report(&totals(), self.started.elapsed()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    fn total(totals: &[(Phase, PhaseTotal)], phase: Phase) -> PhaseTotal {
+        totals.iter().find(|(each, _)| *each == phase).map(|(_, total)| *total).unwrap_or_default()
+    }
+
+    #[test]
+    fn nested_scopes_count_towards_their_own_phase_only() {
+        let recorder = Recorder::new();
+        let started = Instant::now();
+        {
+            let _render = recorder.scope(Phase::Render);
+            sleep(Duration::from_millis(10));
+            {
+                let _query = recorder.scope(Phase::Query);
+                sleep(Duration::from_millis(10));
+                let _write = recorder.scope(Phase::Write);
+                sleep(Duration::from_millis(30));
+            }
+            let _query = recorder.scope(Phase::Query);
+            sleep(Duration::from_millis(5));
+        }
+        let wall = started.elapsed();
+
+        let totals = recorder.totals();
+        assert_eq!(totals.iter().map(|(phase, _)| *phase).collect::<Vec<_>>(), [Phase::Query, Phase::Render, Phase::Write]);
+        let (render, query, write) = (total(&totals, Phase::Render), total(&totals, Phase::Query), total(&totals, Phase::Write));
+        assert_eq!((render.calls, query.calls, write.calls), (1, 2, 1));
+        // Each phase keeps its own time, and nothing is counted twice
+        assert!(render.time >= Duration::from_millis(10), "{:?}", render);
+        assert!(query.time >= Duration::from_millis(15), "{:?}", query);
+        assert!(write.time >= Duration::from_millis(30), "{:?}", write);
+        assert!(render.time + query.time + write.time <= wall, "{:?}", totals);
+        OPEN.with(|open| assert!(open.borrow().is_empty()));
+    }
+
+    #[test]
+    fn disabled_scopes_record_nothing() {
+        assert!(!enabled());
+        let scope = scope(Phase::Query);
+        assert!(scope.started.is_none());
+        drop(scope);
+        OPEN.with(|open| assert!(open.borrow().is_empty()));
+    }
+
+    #[test]
+    fn the_report_lists_each_phase_the_rest_and_a_summary() {
+        let totals = [
+            (Phase::Query, PhaseTotal { time: Duration::from_millis(420), calls: 12 }),
+            (Phase::Aggregate, PhaseTotal { time: Duration::from_millis(1200), calls: 1 }),
+            (Phase::Render, PhaseTotal { time: Duration::from_micros(1500), calls: 3 }),
+        ];
+        assert_eq!(render_report(&totals, Duration::from_secs(2)), "\
+~=~ Time by phase:
+  query         420ms     12 calls
+  aggregate      1.2s      1 call
+  render        1.5ms      3 calls
+  other         378ms
+  total          2.0s
+~=~ query: 420ms, aggregate: 1.2s, render: 1.5ms
+");
+        // Parallel threads add up to more than the wall time
+        let report = render_report(&totals, Duration::from_secs(1));
+        assert!(report.contains("  other         0.0ms\n"), "{}", report);
+        assert!(report.contains("phases on parallel threads overlap"), "{}", report);
+        assert_eq!(render_report(&[], Duration::from_millis(50)), "~=~ Time by phase:\n  other          50ms\n  total          50ms\n");
+    }
+}
@@ -0,0 +1,137 @@
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::utils;
+
+/// How long an answer from tmux is reused before it is asked again
+pub const QUERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Fields of the pane `display-message` reports, tab-separated since paths
+/// may hold spaces
+const PANE_FORMAT: &str = "#{pane_current_command}\t#{pane_current_path}";
+
+/// Terminal emulators tmux may run in, besides those named in
+/// terminal_title_rules
+const TERMINALS: &[&str] = &[
+    "alacritty", "foot", "ghostty", "gnome-terminal", "gnome-terminal-server", "iterm2", "kitty", "konsole",
+    "ptyxis", "rxvt", "st", "terminal", "terminator", "tilix", "urxvt", "wezterm", "wezterm-gui",
+    "windowsterminal", "xfce4-terminal", "xterm",
+];
+
+/// What runs in the active pane of the most recently used tmux client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    pub command: String,
+    pub path: Option<String>,
+}
+
+/// Whether `app_name` is a terminal emulator: a known one, or one a
+/// terminal title rule is for
+pub fn is_terminal(app_name: &str, rule_terminals: &[String]) -> bool {
+    let name = app_name.to_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    TERMINALS.contains(&name) || rule_terminals.iter().any(|terminal| terminal.eq_ignore_ascii_case(app_name))
+}
+
+/// The pane from `display-message -p` output in PANE_FORMAT. None when
+/// there is no command, e.g. the format wasn't expanded.
+pub fn parse_display_output(output: &str) -> Option<Pane> {
+    let line = output.lines().next()?;
+    let (command, path) = line.split_once('\t').unwrap_or((line, ""));
+    let command = command.trim();
+    if command.is_empty() || command.contains("#{") {
+        return None;
+    }
+    let path = path.trim();
+    Some(Pane {
+        command: command.to_string(),
+        path: (!path.is_empty()).then(|| path.to_string()),
+    })
+}
+
+/// Whether the tmux integration can run here, for tmux_integration
+pub fn check() -> Result<(), String> {
+    match utils::find_program("tmux") {
+        Some(_) => Ok(()),
+        None => Err("tmux not found in PATH".to_string()),
+    }
+}
+
+/// Asks tmux for the active pane at most once per QUERY_INTERVAL. Run from
+/// outside tmux, `display-message` answers for the client used last, which
+/// is the one in the focused terminal while it's being typed in.
+#[derive(Debug, Default)]
+pub struct Probe {
+    last: Option<(Instant, Option<Pane>)>,
+    /// Set once running tmux failed; the probe stays quiet from then on
+    unavailable: bool,
+}
+
+impl Probe {
+    /// The active pane, None without a tmux server or when tmux can't run.
+    /// The first failure to start tmux at all is returned as the error.
+    pub fn pane(&mut self) -> Result<Option<Pane>, String> {
+        if self.unavailable {
+            return Ok(None);
+        }
+        if let Some((at, pane)) = &self.last {
+            if at.elapsed() < QUERY_INTERVAL {
+                return Ok(pane.clone());
+            }
+        }
+        let output = match Command::new("tmux").args(["display-message", "-p", PANE_FORMAT]).output() {
+            Ok(output) => output,
+            Err(e) => {
+                self.unavailable = true;
+                return Err(format!("couldn't run tmux ({}); the tmux integration is off until the daemon restarts", e));
+            }
+        };
+        // A non-zero exit is "no server running" or no client attached
+        let pane = output.status.success()
+            .then(|| parse_display_output(&String::from_utf8_lossy(&output.stdout)))
+            .flatten();
+        self.last = Some((Instant::now(), pane.clone()));
+        Ok(pane)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_output_gives_the_command_and_path() {
+        let pane = |command: &str, path: Option<&str>| Some(Pane { command: command.to_string(), path: path.map(String::from) });
+        assert_eq!(parse_display_output("nvim\t/home/me/src/focus debt\n"), pane("nvim", Some("/home/me/src/focus debt")));
+        assert_eq!(parse_display_output(" cargo \t\n"), pane("cargo", None));
+        assert_eq!(parse_display_output("zsh"), pane("zsh", None));
+        // Only the first line counts
+        assert_eq!(parse_display_output("htop\t/tmp\nvim\t/home\n"), pane("htop", Some("/tmp")));
+        // Unexpanded formats, empty output and a blank command are no pane
+        assert_eq!(parse_display_output("#{pane_current_command}\t#{pane_current_path}\n"), None);
+        assert_eq!(parse_display_output(""), None);
+        assert_eq!(parse_display_output("\t/home/me\n"), None);
+    }
+
+    #[test]
+    fn terminals_are_known_emulators_or_title_rule_ones() {
+        let rules = ["fd-term".to_string()];
+        assert!(is_terminal("Alacritty", &[]));
+        assert!(is_terminal("WindowsTerminal.exe", &[]));
+        assert!(is_terminal("FD-TERM", &rules));
+        assert!(!is_terminal("fd-term", &[]));
+        assert!(!is_terminal("fd-editor", &rules));
+    }
+
+    #[test]
+    fn probes_reuse_answers_and_stay_quiet_once_tmux_is_gone() {
+        let cached = Pane { command: "nvim".to_string(), path: None };
+        let mut probe = Probe { last: Some((Instant::now(), Some(cached.clone()))), unavailable: false };
+        assert_eq!(probe.pane(), Ok(Some(cached)));
+        let mut probe = Probe { last: Some((Instant::now(), None)), unavailable: false };
+        assert_eq!(probe.pane(), Ok(None));
+
+        let mut gone = Probe { last: None, unavailable: true };
+        assert_eq!(gone.pane(), Ok(None));
+    }
+}
@@ -1,8 +1,20 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::activity::ActivityMeter;
+use crate::config::{self, RuleMatch, SessionClass};
+use crate::desktop;
+use crate::power::PowerSource;
+use crate::utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct FocusSession {
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
@@ -12,9 +24,101 @@ pub struct FocusSession {
     pub duration: Duration,
     pub is_focus_app: bool,
     pub session_name: String,
+    /// Mean input activity level (0.0–1.0), only with track_input_activity
+    #[serde(default)]
+    pub activity: Option<f64>,
+    /// Time in long low-activity stretches (watching, not working), only
+    /// with track_input_activity
+    #[serde(default)]
+    pub passive: Option<Duration>,
+    /// Virtual desktop / workspace the session started on, when the backend
+    /// reports one
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// AC or battery for the whole row, when the machine has a battery
+    #[serde(default)]
+    pub power: Option<PowerSource>,
+    /// Whether the row was saved without an end_time and had one filled
+    /// in by `settle_open_end`
+    #[serde(default)]
+    pub open_end: OpenEnd,
+    /// Entered by hand (`add-entry`, `import manual`) rather than tracked:
+    /// counts as time, but not towards switch metrics
+    #[serde(default)]
+    pub manual: bool,
+    /// The focus_apps or focus_sites entry that made the row focus, or the
+    /// default, when it was tracked or last reclassified. None on rows
+    /// recorded before rules were, imported and manual ones.
+    #[serde(default)]
+    pub focus_rule: Option<RuleMatch>,
+    /// Label of the co_focus rule the row was part of, see `CoFocusDetector`
+    #[serde(default)]
+    pub co_focus: Option<String>,
+}
+
+/// A row saved without an end_time (the daemon crashed, or an old
+/// checkpoint of the window then open) gets an end when it is read back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum OpenEnd {
+    #[default]
+    Closed,
+    /// Ended at the next row, now, or max_session_hours after its start
+    Open,
+    /// Still open more than `OPEN_ROW_TRUSTED_FOR` after its start, so its
+    /// length is a guess: counted in totals, left out of efficiency and
+    /// deep focus
+    Corrupt,
+}
+
+/// How long after its start an open row may still be a window that is
+/// genuinely open
+pub const OPEN_ROW_TRUSTED_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+static MAX_SESSION_HOURS: AtomicU64 = AtomicU64::new(12);
+
+/// Apply the configured max_session_hours, the most an open row counts for
+pub fn set_max_session_hours(hours: u64) {
+    MAX_SESSION_HOURS.store(hours.max(1), Ordering::Relaxed);
+}
+
+impl FocusSession {
+    /// End an open row at `next_start` (the next row recorded on this
+    /// machine), `now` or max_session_hours after its start, whichever comes
+    /// first, and set its duration to match. Rows with an end are left alone.
+    pub(crate) fn settle_open_end(&mut self, next_start: Option<DateTime<Utc>>, now: DateTime<Utc>) {
+        self.settle_open_end_within(next_start, now, MAX_SESSION_HOURS.load(Ordering::Relaxed));
+    }
+
+    fn settle_open_end_within(&mut self, next_start: Option<DateTime<Utc>>, now: DateTime<Utc>, max_session_hours: u64) {
+        if self.end_time.is_some() {
+            return;
+        }
+        let max = chrono::Duration::hours(max_session_hours as i64);
+        let end = [Some(now), Some(self.start_time + max), next_start]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(now)
+            .max(self.start_time);
+        self.end_time = Some(end);
+        self.duration = (end - self.start_time).to_std().unwrap_or_default();
+        let trusted = chrono::Duration::from_std(OPEN_ROW_TRUSTED_FOR).unwrap_or_default();
+        self.open_end = if now - self.start_time > trusted { OpenEnd::Corrupt } else { OpenEnd::Open };
+    }
+
+    pub fn is_open_ended(&self) -> bool {
+        self.open_end != OpenEnd::Closed
+    }
+
+    /// Left out of efficiency and deep focus, see `OpenEnd::Corrupt`
+    pub fn is_corrupt(&self) -> bool {
+        self.open_end == OpenEnd::Corrupt
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ContextSwitch {
     pub timestamp: DateTime<Utc>,
     pub from_app: String,
@@ -22,26 +126,308 @@ pub struct ContextSwitch {
     pub recovery_time: Option<Duration>,
 }
 
+/// Raw things the daemon observed, kept in the append-only `events` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    WindowChange,
+    DaemonStart,
+    DaemonStop,
+    SessionRollover,
+    TimezoneChange,
+    Alert,
+    /// Tracking paused, e.g. for quiet hours; `reason` in the payload
+    Pause,
+    Resume,
+    /// Result of `focusdebt audit --save`
+    Audit,
+    /// The machine slept (or the clock jumped ahead) between two checks;
+    /// stamped with the check before, `until` in the payload is the one after
+    Suspend,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 10] = [
+        EventKind::WindowChange,
+        EventKind::DaemonStart,
+        EventKind::DaemonStop,
+        EventKind::SessionRollover,
+        EventKind::TimezoneChange,
+        EventKind::Alert,
+        EventKind::Pause,
+        EventKind::Resume,
+        EventKind::Audit,
+        EventKind::Suspend,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::WindowChange => "window_change",
+            EventKind::DaemonStart => "daemon_start",
+            EventKind::DaemonStop => "daemon_stop",
+            EventKind::SessionRollover => "session_rollover",
+            EventKind::TimezoneChange => "timezone_change",
+            EventKind::Alert => "alert",
+            EventKind::Pause => "pause",
+            EventKind::Resume => "resume",
+            EventKind::Audit => "audit",
+            EventKind::Suspend => "suspend",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackerEvent {
+    pub timestamp: DateTime<Utc>,
+    pub kind: EventKind,
+    pub payload: serde_json::Value,
+}
+
+impl TrackerEvent {
+    pub fn now(kind: EventKind, payload: serde_json::Value) -> Self {
+        Self { timestamp: Utc::now(), kind, payload }
+    }
+
+    /// A window_change at `since`, with the title replaced by `[private]`
+    /// when it matches private_title_patterns
+    pub fn window_change(config: &config::Config, window: &WindowInfo, since: DateTime<Utc>) -> Self {
+        let title = if config.is_private_title(&window.title) {
+            "[private]"
+        } else {
+            window.title.as_str()
+        };
+        Self {
+            timestamp: since,
+            kind: EventKind::WindowChange,
+            payload: serde_json::json!({ "app": window.app_name, "title": title, "window": window.window_id }),
+        }
+    }
+}
+
+/// Shortest max_title_length accepted; shorter cuts leave titles unreadable
+pub const MIN_TITLE_LENGTH: usize = 16;
+
+static MAX_TITLE_LENGTH: AtomicUsize = AtomicUsize::new(512);
+
+/// Apply the configured max_title_length (0 keeps titles whole)
+pub fn set_max_title_length(max_title_length: usize) {
+    MAX_TITLE_LENGTH.store(max_title_length, Ordering::Relaxed);
+}
+
+/// Name of the backend that guesses the window from running processes
+pub const PROCESS_SCAN_BACKEND: &str = "process_scan";
+
+static PROCESS_SCAN_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+/// Apply the configured allow_process_scan_fallback
+pub fn set_process_scan_fallback(allowed: bool) {
+    PROCESS_SCAN_FALLBACK.store(allowed, Ordering::Relaxed);
+}
+
+/// Whether the process_scan backend may be used. It reports a running GUI
+/// app, not the focused window, so it is off unless asked for.
+pub fn process_scan_allowed() -> bool {
+    PROCESS_SCAN_FALLBACK.load(Ordering::Relaxed)
+}
+
+static GNOME_EVAL_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether GNOME Shell refused an Eval call in this process, see
+/// `is_gnome_eval_disabled`
+pub fn gnome_eval_disabled() -> bool {
+    GNOME_EVAL_DISABLED.load(Ordering::Relaxed)
+}
+
+/// What to do when GNOME Shell's Eval is disabled and nothing else can read
+/// the focused window
+pub const GNOME_EVAL_GUIDANCE: &str = "GNOME Shell's Eval interface is disabled (the default since GNOME 41), \
+    so the focused window can't be read on GNOME Wayland; install and enable the Window Calls extension \
+    (window-calls on extensions.gnome.org), which the gnome_window_calls backend reads";
+
+/// GNOME_EVAL_GUIDANCE when GNOME Shell refused Eval and the window came
+/// from no backend, or only from the process scan's guess
+pub fn detection_advice(chosen: Option<&str>) -> Option<&'static str> {
+    (gnome_eval_disabled() && chosen.is_none_or(|backend| backend == PROCESS_SCAN_BACKEND)).then_some(GNOME_EVAL_GUIDANCE)
+}
+
+/// The focused window as a detection backend reports it. `window_id` is the
+/// platform's handle (X11 window id, Hyprland address, HWND, ...) and
+/// `workspace` the current virtual desktop, when the backend exposes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub app_name: String,
+    pub title: String,
+    pub window_id: Option<String>,
+    pub workspace: Option<String>,
+}
+
+impl WindowInfo {
+    /// The title is cut to max_title_length here, before anything logs or
+    /// stores it; some apps put whole documents in their title
+    pub fn new(app_name: impl Into<String>, title: impl Into<String>) -> Self {
+        let title = utils::cap_title(title.into(), MAX_TITLE_LENGTH.load(Ordering::Relaxed));
+        Self { app_name: app_name.into(), title, window_id: None, workspace: None }
+    }
+
+    pub fn with_workspace(mut self, workspace: Option<String>) -> Self {
+        self.workspace = workspace.filter(|workspace| !workspace.trim().is_empty());
+        self
+    }
+
+    pub fn with_id(mut self, window_id: impl Into<String>) -> Self {
+        let window_id = window_id.into();
+        self.window_id = (!window_id.trim().is_empty()).then_some(window_id);
+        self
+    }
+}
+
+/// How finely browser time is split (`browser_tab_tracking`): a session per
+/// tab, per site, or one for the whole browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrowserTabTracking {
+    #[default]
+    Full,
+    Domain,
+    Off,
+}
+
+impl BrowserTabTracking {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "full" => Some(BrowserTabTracking::Full),
+            "domain" => Some(BrowserTabTracking::Domain),
+            "off" => Some(BrowserTabTracking::Off),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BrowserTabTracking::Full => "full",
+            BrowserTabTracking::Domain => "domain",
+            BrowserTabTracking::Off => "off",
+        }
+    }
+}
+
+/// Tags rows with the co_focus rule they take part in: a row of one side
+/// of a rule is co-focused when the other side was in front no longer than
+/// the window before the row started. Rows are looked at in the order they
+/// end, so a stretch only counts once both sides have shown up.
+#[derive(Debug, Default)]
+pub struct CoFocusDetector {
+    rules: Vec<config::CoFocusRule>,
+    window: chrono::Duration,
+    /// When each side of each rule (by index) was last in front
+    last_seen: HashMap<(usize, usize), DateTime<Utc>>,
+}
+
+impl CoFocusDetector {
+    pub fn new(rules: Vec<config::CoFocusRule>, window: Duration) -> Self {
+        Self { rules, window: chrono::Duration::from_std(window).unwrap_or_default(), last_seen: HashMap::new() }
+    }
+
+    /// The label of the first rule `app` is on a side of whose other side
+    /// was in front within the window before `start`
+    pub fn label_for(&self, app: &str, start: DateTime<Utc>) -> Option<String> {
+        self.rules.iter().enumerate().find_map(|(index, rule)| {
+            let side = rule.side_of(app)?;
+            let other_seen = self.last_seen.get(&(index, 1 - side))?;
+            (start - *other_seen <= self.window).then(|| rule.label.clone())
+        })
+    }
+
+    /// Tag the completed `row`, then count its app as in front until its
+    /// end. Rows shorter than `min_duration` aren't saved, so they don't
+    /// count as the app being used.
+    pub fn complete(&mut self, row: &mut FocusSession, min_duration: Duration) {
+        if self.rules.is_empty() {
+            return;
+        }
+        row.co_focus = self.label_for(&row.app_name, row.start_time);
+        if row.duration < min_duration {
+            return;
+        }
+        let end = row.end_time.unwrap_or(row.start_time);
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Some(side) = rule.side_of(&row.app_name) {
+                self.last_seen.insert((index, side), end);
+            }
+        }
+    }
+}
+
+/// Titles browsers give their picture-in-picture windows
+pub const DEFAULT_PIP_TITLES: [&str; 2] = ["Picture-in-Picture", "Picture in picture"];
+
 pub struct FocusTracker {
     current_session: Option<FocusSession>,
+    /// Platform handle of the window the current session is in
+    current_window_id: Option<String>,
+    /// Browser and tab title of the last window that wasn't picture-in-picture
+    last_browser_tab: Option<(String, String)>,
+    /// Tab each PiP window was popped out of, by window id, so refocusing it
+    /// later still counts as that tab
+    pip_origins: HashMap<String, String>,
+    pip_titles: Vec<String>,
+    browser_tab_tracking: BrowserTabTracking,
     completed_sessions: Vec<FocusSession>,
     context_switches: Vec<ContextSwitch>,
     focus_apps: Vec<String>,
     focus_sites: Vec<String>,
+    /// Non-browser apps focus_sites are matched against the title of;
+    /// empty unless apply_sites_to_apps is on
+    site_apps: Vec<String>,
+    terminal_rules: Vec<(String, Regex)>,
+    /// title_noise_markers, stripped before titles are compared or parsed
+    title_noise: Option<Regex>,
+    self_ignore: bool,
+    track_input_activity: bool,
+    activity_meter: ActivityMeter,
+    min_record_duration: Duration,
+    buffer_limit: usize,
+    /// Power source new rows start with; a change splits the open row
+    power: Option<PowerSource>,
+    co_focus: CoFocusDetector,
     last_switch_time: Option<Instant>,
     is_tracking: bool,
     debug_mode: bool,
     session_name: String,
 }
 
+impl Default for FocusTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FocusTracker {
     pub fn new() -> Self {
         Self {
             current_session: None,
+            current_window_id: None,
+            last_browser_tab: None,
+            pip_origins: HashMap::new(),
+            pip_titles: DEFAULT_PIP_TITLES.iter().map(|title| title.to_string()).collect(),
+            browser_tab_tracking: BrowserTabTracking::default(),
             completed_sessions: Vec::new(),
             context_switches: Vec::new(),
             focus_apps: Vec::new(),
             focus_sites: Vec::new(),
+            site_apps: Vec::new(),
+            terminal_rules: Vec::new(),
+            title_noise: utils::title_noise_regex(&utils::DEFAULT_TITLE_NOISE_MARKERS.map(String::from)),
+            self_ignore: true,
+            track_input_activity: false,
+            activity_meter: ActivityMeter::default(),
+            min_record_duration: Duration::from_secs(1),
+            buffer_limit: usize::MAX,
+            power: None,
+            co_focus: CoFocusDetector::default(),
             last_switch_time: None,
             is_tracking: false,
             debug_mode: true, // Enable debug mode by default
@@ -49,6 +435,46 @@ impl FocusTracker {
         }
     }
 
+    /// Follow `config`'s rules, with `focus_apps` (see
+    /// `Config::tracked_focus_apps`) as the focus apps and rows recorded
+    /// under `session_name`. Tracking starts with `start_tracking`.
+    pub fn apply_config(&mut self, config: &config::Config, focus_apps: Vec<String>, session_name: &str) {
+        let tracker = self;
+        for app in focus_apps {
+            tracker.add_focus_app(app);
+        }
+        for site in config.focus_sites.clone() {
+            tracker.add_focus_site(site);
+        }
+        for rule in &config.terminal_title_rules {
+            if let Err(e) = tracker.add_terminal_rule(rule.terminal.clone(), &rule.pattern) {
+                eprintln!("❌ Invalid terminal title rule for {}: {}", rule.terminal, e);
+            }
+        }
+        tracker.set_self_ignore(config.self_ignore);
+        tracker.set_pip_titles(config.pip_titles.clone());
+        tracker.set_title_noise_markers(&config.title_noise_markers);
+        tracker.set_browser_tab_tracking(config.browser_tab_tracking);
+        if config.apply_sites_to_apps {
+            tracker.set_site_apps(config.site_apps.clone());
+        }
+        tracker.set_track_input_activity(config.track_input_activity);
+        tracker.set_min_record_duration(Duration::from_secs(config.min_record_seconds));
+        tracker.set_buffer_limit(config.max_buffered_rows);
+        tracker.set_session_name(session_name.to_string());
+        tracker.set_power_source(crate::power::current());
+        // Already reported when the config was loaded
+        if let Ok(rules) = config.co_focus_rules() {
+            tracker.set_co_focus(rules, Duration::from_secs(config.co_focus_window_minutes * 60));
+        }
+    }
+
+    /// Print what the tracker does (on by default), as the daemon's log
+    /// shows it
+    pub fn set_debug_mode(&mut self, debug_mode: bool) {
+        self.debug_mode = debug_mode;
+    }
+
     pub fn is_browser_app(app_name: &str) -> bool {
         let browser_apps = ["chrome", "firefox", "safari", "edge", "brave", "chromium", "opera", "vivaldi"];
         browser_apps.iter().any(|&browser| app_name.to_lowercase().contains(browser))
@@ -59,101 +485,263 @@ impl FocusTracker {
         println!("~=~ Focus tracking started (debug mode: {})", self.debug_mode);
     }
 
-    pub fn stop_tracking(&mut self) {
-        self.is_tracking = false;
-        if let Some(session) = &mut self.current_session {
-            let now = Utc::now();
-            session.end_time = Some(now);
-            session.duration = now.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+    pub fn add_focus_app(&mut self, app_name: String) {
+        if !self.focus_apps.contains(&app_name) {
+            self.focus_apps.push(app_name.clone());
             if self.debug_mode {
-                println!("~=~ Ending session: {} ({}s)", session.app_name, session.duration.as_secs());
+                println!("~=~ Added focus app: {}", app_name);
             }
         }
-        println!("~=~ Focus tracking stopped");
     }
 
-    pub fn is_tracking(&self) -> bool {
-        self.is_tracking
-    }
-
-    pub fn add_focus_app(&mut self, app_name: String) {
-        if !self.focus_apps.contains(&app_name) {
-            self.focus_apps.push(app_name.clone());
+    pub fn add_focus_site(&mut self, domain: String) {
+        if !self.focus_sites.contains(&domain) {
+            self.focus_sites.push(domain.clone());
             if self.debug_mode {
-                println!("~=~ Added focus app: {}", app_name);
+                println!("~=~ Added focus site: {}", domain);
             }
         }
     }
 
-    pub fn remove_focus_app(&mut self, app_name: &str) {
-        self.focus_apps.retain(|app| app != app_name);
+    /// Replace both focus lists, e.g. after `focusapp add` while the daemon
+    /// runs. The current session keeps its classification; the lists apply
+    /// from the next window change. Returns the changes ("+app code").
+    pub fn sync_focus_lists(&mut self, apps: &[String], sites: &[String]) -> Vec<String> {
+        let diff = |kind: &str, old: &[String], new: &[String]| {
+            let added = new.iter().filter(|entry| !old.contains(entry)).map(|entry| format!("+{} {}", kind, entry));
+            let removed = old.iter().filter(|entry| !new.contains(entry)).map(|entry| format!("-{} {}", kind, entry));
+            added.chain(removed).collect::<Vec<_>>()
+        };
+        let mut changes = diff("app", &self.focus_apps, apps);
+        changes.extend(diff("site", &self.focus_sites, sites));
+        self.focus_apps = apps.to_vec();
+        self.focus_sites = sites.to_vec();
+        changes
+    }
+
+    /// Apps the terminal title rules are for
+    fn rule_terminals(&self) -> Vec<String> {
+        self.terminal_rules.iter().map(|(terminal, _)| terminal.clone()).collect()
+    }
+
+    pub fn add_terminal_rule(&mut self, terminal: String, pattern: &str) -> Result<(), regex::Error> {
+        let regex = Regex::new(pattern)?;
         if self.debug_mode {
-            println!("~=~ Removed focus app: {}", app_name);
+            println!("~=~ Added terminal title rule for {}: {}", terminal, pattern);
         }
+        self.terminal_rules.push((terminal, regex));
+        Ok(())
     }
 
-    pub fn list_focus_apps(&self) -> &[String] {
-        &self.focus_apps
+    /// Whether windows showing focusdebt's own CLI are left out of tracking
+    pub fn set_self_ignore(&mut self, self_ignore: bool) {
+        self.self_ignore = self_ignore;
     }
 
-    pub fn get_focus_apps(&self) -> &[String] {
-        &self.focus_apps
+    /// Completed sessions shorter than this are dropped instead of saved
+    pub fn set_min_record_duration(&mut self, min_record_duration: Duration) {
+        self.min_record_duration = min_record_duration;
     }
 
-    pub fn add_focus_site(&mut self, domain: String) {
-        if !self.focus_sites.contains(&domain) {
-            self.focus_sites.push(domain.clone());
-            if self.debug_mode {
-                println!("~=~ Added focus site: {}", domain);
-            }
+    /// Rows held between saves before `needs_flush` asks for an early one
+    pub fn set_buffer_limit(&mut self, buffer_limit: usize) {
+        self.buffer_limit = buffer_limit.max(1);
+    }
+
+    /// Tag rows with the configured co_focus rules from now on
+    pub fn set_co_focus(&mut self, rules: Vec<config::CoFocusRule>, window: Duration) {
+        self.co_focus = CoFocusDetector::new(rules, window);
+    }
+
+    /// Completed sessions and context switches waiting to be taken
+    pub fn buffered_rows(&self) -> usize {
+        self.completed_sessions.len() + self.context_switches.len()
+    }
+
+    pub fn needs_flush(&self) -> bool {
+        self.buffered_rows() >= self.buffer_limit
+    }
+
+    pub fn set_track_input_activity(&mut self, track_input_activity: bool) {
+        self.track_input_activity = track_input_activity;
+    }
+
+    /// Record one tick of input activity for the current session
+    pub fn record_activity(&mut self, active: bool) {
+        if self.track_input_activity && self.current_session.is_some() {
+            self.activity_meter.record(Utc::now(), active);
         }
     }
 
-    pub fn remove_focus_site(&mut self, domain: &str) {
-        self.focus_sites.retain(|s| s != domain);
+    /// Record the power source sampled now. When it changed, the open row
+    /// is split like at a session rollover, so each row was tracked on one
+    /// source. Not a context switch.
+    pub fn set_power_source(&mut self, power: Option<PowerSource>) {
+        self.set_power_source_at(power, Utc::now());
+    }
+
+    /// `set_power_source` for a source sampled at `now`
+    pub fn set_power_source_at(&mut self, power: Option<PowerSource>, now: DateTime<Utc>) {
+        if power == self.power {
+            return;
+        }
+        self.power = power;
+        if let Some(session) = &mut self.current_session {
+            if session.end_time.is_none() {
+                let now = now.max(session.start_time);
+                let mut continued = session.clone();
+                session.end_time = Some(now);
+                session.duration = now.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+                (session.activity, session.passive) = self.activity_meter.take();
+                let mut completed = session.clone();
+                self.co_focus.complete(&mut completed, self.min_record_duration);
+                self.completed_sessions.push(completed);
+
+                continued.start_time = now;
+                continued.power = power;
+                self.current_session = Some(continued);
+            }
+        }
         if self.debug_mode {
-            println!("~=~ Removed focus site: {}", domain);
+            println!("~=~ Power source: {}", power.map_or("unknown", |power| power.label()));
+        }
+    }
+
+    /// Markers that change a title without changing what it shows, e.g.
+    /// an editor's unsaved dot
+    pub fn set_title_noise_markers(&mut self, markers: &[String]) {
+        self.title_noise = utils::title_noise_regex(markers);
+    }
+
+    /// `title` as compared for changes, see `utils::normalize_title`
+    fn title_key(&self, title: &str) -> String {
+        utils::normalize_title(title, self.title_noise.as_ref())
+    }
+
+    /// For terminals with a title rule, the program parsed from the title
+    /// (noise markers stripped) stands in for the terminal itself;
+    /// everything else is unchanged.
+    pub fn effective_app_name(&self, app_name: &str, window_title: &str) -> String {
+        let title = self.title_key(window_title);
+        for (terminal, regex) in &self.terminal_rules {
+            if !terminal.eq_ignore_ascii_case(app_name) {
+                continue;
+            }
+            if let Some(program) = utils::parse_terminal_title(regex, &title).and_then(|t| t.program) {
+                return program;
+            }
         }
+        app_name.to_string()
+    }
+
+    /// Titles that mark a browser's picture-in-picture window
+    pub fn set_pip_titles(&mut self, pip_titles: Vec<String>) {
+        self.pip_titles = pip_titles;
+    }
+
+    pub fn is_pip_title(&self, title: &str) -> bool {
+        self.pip_titles.iter().any(|pip| pip.trim().eq_ignore_ascii_case(title.trim()))
     }
 
-    pub fn list_focus_sites(&self) -> &[String] {
-        &self.focus_sites
+    /// Split browser time per tab, per site, or not at all
+    pub fn set_browser_tab_tracking(&mut self, mode: BrowserTabTracking) {
+        self.browser_tab_tracking = mode;
     }
 
-    pub fn get_focus_sites(&self) -> &[String] {
-        &self.focus_sites
+    /// Apps whose window titles focus_sites apply to, like a browser's tabs.
+    /// A title change in one of them starts a new session.
+    pub fn set_site_apps(&mut self, site_apps: Vec<String>) {
+        self.site_apps = site_apps;
     }
 
-    pub fn update_active_window(&mut self, app_name: String, window_title: String) {
+    /// Track `window`, which became active at `now` (for a debounced change,
+    /// its first sighting)
+    pub fn update_active_window_at(&mut self, window: WindowInfo, now: DateTime<Utc>) {
+        let WindowInfo { app_name, title: window_title, window_id, workspace } = window;
         if !self.is_tracking {
             return;
         }
 
-        let now = Utc::now();
-        
-        // For browsers, store the window title (tab name); for non-browsers, no domain tracking
-        let domain = if Self::is_browser_app(&app_name) {
-            Some(window_title.clone())  // Store the full tab name for browsers
+        // Checking on focusdebt itself isn't a context switch: the previous
+        // session simply keeps running
+        if self.self_ignore && utils::is_self_window(&app_name, &window_title, &self.rule_terminals()) {
+            if self.debug_mode {
+                println!("~=~ Ignoring focusdebt's own window: {} - {}", app_name, window_title);
+            }
+            return;
+        }
+
+        let raw_app_name = app_name;
+        let mut app_name = self.effective_app_name(&raw_app_name, &window_title);
+
+        // Flatpak, AppImage and distro packages of one app share the
+        // desktop file ID as their key
+        let identity = if app_name == raw_app_name { desktop::resolve(&app_name) } else { None };
+        if let Some(identity) = &identity {
+            app_name = identity.key.clone();
+        }
+
+        // A picture-in-picture window carries on the tab it was popped out
+        // of, so it inherits that tab's classification. Without a known tab
+        // it is tracked under its own title.
+        let is_browser = Self::is_browser_app(&app_name);
+        let mut is_pip = false;
+        let window_title = if is_browser && self.is_pip_title(&window_title) {
+            let origin = window_id.as_ref()
+                .and_then(|id| self.pip_origins.get(id).cloned())
+                .or_else(|| self.last_browser_tab.as_ref()
+                    .filter(|(browser, _)| *browser == app_name)
+                    .map(|(_, tab)| tab.clone()));
+            match origin {
+                Some(tab) => {
+                    if let Some(id) = &window_id {
+                        self.pip_origins.insert(id.clone(), tab.clone());
+                    }
+                    is_pip = true;
+                    tab
+                }
+                None => window_title,
+            }
         } else {
-            None  // No domain tracking for non-browsers
+            window_title
         };
+        if is_browser && !is_pip && !self.is_pip_title(&window_title) {
+            self.last_browser_tab = Some((app_name.clone(), window_title.clone()));
+        }
         
-        // Determine if this is a focus session based on app and/or tab name
-        let mut is_focus_app = self.focus_apps.contains(&app_name);
+        // Titles are compared without noise markers, so saving a file or a
+        // page finishing loading isn't a new tab. A title that is only noise
+        // ("Loading…") is a placeholder for whatever comes next.
+        let title_key = self.title_key(&window_title);
+        let is_placeholder = title_key.is_empty();
+        let tab_title = if is_placeholder { &window_title } else { &title_key };
+
+        // For browsers, store the tab name, or the tab's site in domain
+        // mode; with tab tracking off the browser is one app like any other
+        let tracks_tabs = is_browser && self.browser_tab_tracking != BrowserTabTracking::Off;
+        let domain = match self.browser_tab_tracking {
+            _ if !tracks_tabs => None,
+            BrowserTabTracking::Domain => Some(utils::extract_tab_site(tab_title)),
+            _ => Some(tab_title.clone()),
+        };
         
-        // If we have a tab name and it's in focus sites, mark as focus (case-insensitive)
-        if let Some(ref tab_name) = domain {
-            if self.focus_sites.iter().any(|site| tab_name.to_lowercase().contains(&site.to_lowercase())) {
-                is_focus_app = true;
-            }
-        }
+        // Apps in site_apps have their window title matched like a tab's
+        let tracks_titles = !is_browser && config::is_site_app(&self.site_apps, &app_name);
+
+        // Determine if this is a focus session based on app and/or tab name
+        // Focus apps may be listed by key, by the detected name or by display name
+        let listed_app = self.focus_apps.iter().find(|app| {
+            **app == app_name
+                || **app == raw_app_name
+                || identity.as_ref().is_some_and(|identity| identity.name.eq_ignore_ascii_case(app))
+        });
+        let site_text = (tracks_tabs || tracks_titles).then_some(window_title.as_str());
+        let (class, focus_rule) = config::classify_window(None, listed_app.map(String::as_str), site_text, &self.focus_sites, &[]);
+        let is_focus_app = class == SessionClass::Focus;
 
         if self.debug_mode {
-            let is_browser = Self::is_browser_app(&app_name);
             let debug_msg = format!("~=~ BROWSER CHECK: {} - is_browser: {}, tab_name: {:?}", app_name, is_browser, domain);
             println!("{}", debug_msg);
-            // Also write to debug file for visibility
-            let _ = std::fs::write("/tmp/focusdebt_debug.log", format!("{}\n", debug_msg));
         }
 
         if self.debug_mode {
@@ -164,22 +752,44 @@ impl FocusTracker {
                 format!("~=~ Window update: {} - {} (focus: {})", app_name, window_title, is_focus_app)
             };
             println!("{}", debug_msg);
-            // Also write to debug file for visibility
-            let _ = std::fs::write("/tmp/focusdebt_debug.log", format!("{}\n", debug_msg));
         }
 
+        // Moving between two windows of one browser is a window switch, even
+        // when both show the same tab title. A PiP window stays with its tab.
+        let is_window_switch = tracks_tabs && !is_pip
+            && window_id.is_some()
+            && self.current_window_id.is_some()
+            && window_id != self.current_window_id;
+        if !is_pip {
+            self.current_window_id = window_id;
+        }
+
+        let current_key = self.current_session.as_ref().map(|session| self.title_key(&session.window_title));
         if let Some(current_session) = &mut self.current_session {
-            // Check if we're switching to a different app OR different browser tab/domain
-            let is_browser = Self::is_browser_app(&app_name);
-            let is_browser_tab_change = is_browser && (
-                current_session.window_title != window_title ||
-                current_session.domain != domain
+            // A placeholder on either side is no change: the placeholder
+            // session takes the real title below
+            let current_key = current_key.unwrap_or_default();
+            let comparable = !is_placeholder && !current_key.is_empty();
+            let titles_differ = comparable && current_key != title_key;
+
+            // Check if we're switching to a different app OR different browser tab/domain.
+            // In domain mode only the site counts, so the title is just updated below.
+            let is_browser_tab_change = tracks_tabs && (
+                (self.browser_tab_tracking == BrowserTabTracking::Full && titles_differ) ||
+                (comparable && current_session.domain != domain)
             );
+            let is_title_change = tracks_titles && titles_differ;
             
-            if current_session.app_name != app_name || is_browser_tab_change {
+            if current_session.app_name != app_name || is_window_switch || is_browser_tab_change || is_title_change {
                 if self.debug_mode {
                     if current_session.app_name != app_name {
                         println!("~=~ App switch detected: {} → {}", current_session.app_name, app_name);
+                    } else if is_title_change {
+                        println!("~=~ Title switch detected: {} → {}",
+                            current_session.window_title, window_title);
+                    } else if is_window_switch {
+                        println!("~=~ Browser window switch detected: {} → {}",
+                            current_session.window_title, window_title);
                     } else {
                         println!("~=~ Browser tab switch detected: {} → {}", 
                             current_session.window_title, window_title);
@@ -187,10 +797,9 @@ impl FocusTracker {
                 }
 
                 // Calculate recovery time if switching to a focus app
-                let recovery_time = if is_focus_app && self.last_switch_time.is_some() {
-                    Some(Instant::now().duration_since(self.last_switch_time.unwrap()))
-                } else {
-                    None
+                let recovery_time = match self.last_switch_time {
+                    Some(last_switch) if is_focus_app => Some(Instant::now().duration_since(last_switch)),
+                    _ => None,
                 };
 
                 // Create context switch record
@@ -211,8 +820,10 @@ impl FocusTracker {
                 // End current session and add to completed sessions
                 current_session.end_time = Some(now);
                 current_session.duration = now.signed_duration_since(current_session.start_time).to_std().unwrap_or(Duration::ZERO);
+                (current_session.activity, current_session.passive) = self.activity_meter.take();
                 
-                let completed_session = current_session.clone();
+                let mut completed_session = current_session.clone();
+                self.co_focus.complete(&mut completed_session, self.min_record_duration);
                 self.completed_sessions.push(completed_session);
 
                 if self.debug_mode {
@@ -232,6 +843,14 @@ impl FocusTracker {
                     duration: Duration::ZERO,
                     is_focus_app,
                     session_name: self.session_name.clone(),
+                    activity: None,
+                    passive: None,
+                    workspace,
+                    power: self.power,
+                    open_end: OpenEnd::Closed,
+                    manual: false,
+                    focus_rule: Some(focus_rule),
+                    co_focus: None,
                 });
 
                 // Update last switch time
@@ -241,8 +860,18 @@ impl FocusTracker {
                     println!("~=~ Started new session: {}", app_name);
                 }
             } else {
-                // Same app and same browser tab, just update window title if it changed
-                if current_session.window_title != window_title {
+                // Same app and same browser tab, just update the window title
+                // if more than its noise changed. A placeholder session takes
+                // the first real title, with its tab and classification.
+                if current_key.is_empty() && !is_placeholder {
+                    if self.debug_mode {
+                        println!("~=~ Placeholder title resolved: {} → {}", current_session.window_title, window_title);
+                    }
+                    current_session.window_title = window_title;
+                    current_session.domain = domain;
+                    current_session.is_focus_app = is_focus_app;
+                    current_session.focus_rule = Some(focus_rule);
+                } else if titles_differ {
                     if self.debug_mode {
                         println!("~=~ Window title update: {} → {}", current_session.window_title, window_title);
                     }
@@ -260,6 +889,14 @@ impl FocusTracker {
                 duration: Duration::ZERO,
                 is_focus_app,
                 session_name: self.session_name.clone(),
+                activity: None,
+                passive: None,
+                workspace,
+                power: self.power,
+                open_end: OpenEnd::Closed,
+                manual: false,
+                focus_rule: Some(focus_rule.clone()),
+                co_focus: None,
             });
 
             if self.debug_mode {
@@ -268,14 +905,46 @@ impl FocusTracker {
         }
     }
 
+    /// Stop tracking until the next window update: the open session ends
+    /// now, and the first window after the pause starts a fresh one
+    /// without counting a context switch
+    pub fn pause(&mut self) {
+        self.end_current_session();
+        self.current_session = None;
+        self.current_window_id = None;
+        self.last_switch_time = None;
+    }
+
+    /// End the current session at `at`, the last check before the machine
+    /// slept, so the time asleep isn't counted. The window found after
+    /// waking starts a new session.
+    pub fn suspend(&mut self, at: DateTime<Utc>) {
+        if let Some(session) = &mut self.current_session {
+            if session.end_time.is_none() {
+                let end = at.max(session.start_time);
+                session.end_time = Some(end);
+                session.duration = end.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+                (session.activity, session.passive) = self.activity_meter.take();
+                let mut completed = session.clone();
+                self.co_focus.complete(&mut completed, self.min_record_duration);
+                self.completed_sessions.push(completed);
+            }
+        }
+        self.current_session = None;
+        self.current_window_id = None;
+        self.last_switch_time = None;
+    }
+
     pub fn end_current_session(&mut self) {
         if let Some(session) = &mut self.current_session {
             if session.end_time.is_none() {
                 let now = Utc::now();
                 session.end_time = Some(now);
                 session.duration = now.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+                (session.activity, session.passive) = self.activity_meter.take();
                 
-                let completed_session = session.clone();
+                let mut completed_session = session.clone();
+                self.co_focus.complete(&mut completed_session, self.min_record_duration);
                 self.completed_sessions.push(completed_session);
 
                 if self.debug_mode {
@@ -291,10 +960,13 @@ impl FocusTracker {
     pub fn get_current_session(&self) -> Option<FocusSession> {
         self.current_session.as_ref().map(|session| {
             let mut updated_session = session.clone();
+            updated_session.co_focus = self.co_focus.label_for(&session.app_name, session.start_time);
             // Only update duration if session hasn't ended yet
             if updated_session.end_time.is_none() {
                 let now = Utc::now();
                 updated_session.duration = now.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+                updated_session.activity = self.activity_meter.average();
+                updated_session.passive = self.activity_meter.passive();
             }
             updated_session
         })
@@ -305,9 +977,11 @@ impl FocusTracker {
     }
 
     pub fn take_completed_sessions(&mut self) -> Vec<FocusSession> {
-        let sessions = self.completed_sessions.clone();
-        self.completed_sessions.clear();
-        sessions
+        let min_record_duration = self.min_record_duration;
+        std::mem::take(&mut self.completed_sessions)
+            .into_iter()
+            .filter(|session| session.duration >= min_record_duration)
+            .collect()
     }
 
     pub fn get_context_switches(&self) -> &[ContextSwitch] {
@@ -315,15 +989,7 @@ impl FocusTracker {
     }
 
     pub fn take_context_switches(&mut self) -> Vec<ContextSwitch> {
-        let switches = self.context_switches.clone();
-        self.context_switches.clear();
-        switches
-    }
-
-    pub fn get_deep_focus_sessions(&self, _min_duration: Duration) -> Vec<&FocusSession> {
-        // This would need to be implemented with database queries
-        // For now, return empty vector
-        Vec::new()
+        std::mem::take(&mut self.context_switches)
     }
 
     pub fn get_stats(&self) -> TrackerStats {
@@ -336,7 +1002,6 @@ impl FocusTracker {
                     now.signed_duration_since(s.start_time).to_std().unwrap_or(Duration::ZERO)
                 })
                 .unwrap_or(Duration::ZERO),
-            focus_apps_count: self.focus_apps.len(),
         }
     }
 
@@ -350,6 +1015,32 @@ impl FocusTracker {
     pub fn get_session_name(&self) -> &str {
         &self.session_name
     }
+
+    /// Switch to session `name` at `at`: the open window is split there, so
+    /// the part before belongs to the old session and the rest continues
+    /// under the new one. Not a context switch.
+    pub fn roll_over_session(&mut self, name: String, at: DateTime<Utc>) {
+        if let Some(session) = &mut self.current_session {
+            if session.end_time.is_none() {
+                let split = at.max(session.start_time);
+                let mut continued = session.clone();
+                session.end_time = Some(split);
+                session.duration = split.signed_duration_since(session.start_time).to_std().unwrap_or(Duration::ZERO);
+                (session.activity, session.passive) = self.activity_meter.take();
+                let mut completed = session.clone();
+                self.co_focus.complete(&mut completed, self.min_record_duration);
+                self.completed_sessions.push(completed);
+
+                continued.start_time = split;
+                continued.session_name = name.clone();
+                self.current_session = Some(continued);
+            }
+        }
+        if self.debug_mode {
+            println!("~=~ Session rolled over: {} → {}", self.session_name, name);
+        }
+        self.session_name = name;
+    }
 }
 
 #[derive(Debug)]
@@ -357,136 +1048,828 @@ pub struct TrackerStats {
     pub total_sessions: usize,
     pub total_context_switches: usize,
     pub current_session_duration: Duration,
-    pub focus_apps_count: usize,
+}
+
+/// A live snapshot older than this is from a daemon that stopped writing
+/// them; reports then read the database only
+pub const LIVE_SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(10);
+
+/// Rows the daemon holds in memory, written next to its debug state so
+/// `stats` and `sessions` can show what isn't saved yet
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiveSnapshot {
+    pub pid: u32,
+    pub updated_at: DateTime<Utc>,
+    /// Completed rows not yet handed to the database thread
+    pub sessions: Vec<FocusSession>,
+    pub switches: Vec<ContextSwitch>,
+    /// The window being tracked: no end time, duration up to `updated_at`
+    pub current: Option<FocusSession>,
+}
+
+impl LiveSnapshot {
+    pub fn capture(tracker: &FocusTracker) -> Self {
+        LiveSnapshot {
+            pid: utils::get_current_pid(),
+            updated_at: Utc::now(),
+            sessions: tracker.get_completed_sessions().to_vec(),
+            switches: tracker.get_context_switches().to_vec(),
+            current: tracker.get_current_session(),
+        }
+    }
+
+    /// The completed rows followed by the current one
+    pub fn rows(&self) -> Vec<FocusSession> {
+        self.sessions.iter().chain(&self.current).cloned().collect()
+    }
+}
+
+pub fn write_live_snapshot(snapshot: &LiveSnapshot) -> std::io::Result<()> {
+    let path = utils::runtime_file("live")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    let content = serde_json::to_string(snapshot).map_err(std::io::Error::other)?;
+    utils::write_private_file(&path, content)
+}
+
+/// The running daemon's latest snapshot; None without a daemon or when the
+/// snapshot is older than LIVE_SNAPSHOT_MAX_AGE
+pub fn read_live_snapshot() -> Option<LiveSnapshot> {
+    if !utils::is_daemon_running() {
+        return None;
+    }
+    let content = std::fs::read_to_string(utils::runtime_file("live")?).ok()?;
+    let snapshot: LiveSnapshot = serde_json::from_str(&content).ok()?;
+    let age = (Utc::now() - snapshot.updated_at).to_std().unwrap_or_default();
+    (age <= LIVE_SNAPSHOT_MAX_AGE).then_some(snapshot)
+}
+
+pub fn remove_live_snapshot() -> std::io::Result<()> {
+    match utils::runtime_file("live").map(std::fs::remove_file) {
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
 }
 
 // Platform-specific window tracking
-#[cfg(target_os = "linux")]
-pub mod platform {
-    use std::process::Command;
-    use std::env;
+thread_local! {
+    /// Commands spawned on this thread while a backend probe is recording
+    static SPAWNED_COMMANDS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
 
-    pub fn get_active_window() -> Option<(String, String)> {
-        let debug = true;
-        
-        if debug {
-            println!("~=~ Detecting Linux window manager and attempting window detection...");
+/// Run a detection command, noting it down if a probe is recording
+fn run_command(command: &mut Command) -> std::io::Result<Output> {
+    SPAWNED_COMMANDS.with(|spawned| {
+        if let Some(spawned) = spawned.borrow_mut().as_mut() {
+            let args: Vec<String> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+            spawned.push(format!("{} {}", command.get_program().to_string_lossy(), args.join(" ")).trim_end().to_string());
         }
+    });
+    command.output()
+}
 
-        // Detect the current window manager/compositor environment
-        let session_type = env::var("XDG_SESSION_TYPE").unwrap_or_default();
-        let current_desktop = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
-        let wayland_display = env::var("WAYLAND_DISPLAY").unwrap_or_default();
-        
-        if debug {
-            println!("   Session type: {}", session_type);
-            println!("   Current desktop: {}", current_desktop);
-            println!("   Wayland display: {}", wayland_display);
+/// One run of a single detection backend, as reported by `focusdebt debug`
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendProbe {
+    pub backend: &'static str,
+    /// Whether the daemon would try this backend in the current environment
+    pub applicable: bool,
+    pub detected: bool,
+    pub app_name: Option<String>,
+    pub window_title: Option<String>,
+    pub window_id: Option<String>,
+    pub elapsed_ms: f64,
+    pub commands: Vec<String>,
+}
+
+/// Everything `focusdebt debug` knows about window detection on this machine
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectionReport {
+    pub platform: &'static str,
+    pub environment: BTreeMap<String, Option<String>>,
+    pub backends: Vec<BackendProbe>,
+    /// First applicable backend that detected a window, which is what the daemon would use
+    pub chosen: Option<&'static str>,
+    /// What to set up so a backend can read the focused window, see `detection_advice`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advice: Option<&'static str>,
+}
+
+impl DetectionReport {
+    /// Run every backend once, whether or not it applies, and time it
+    pub fn collect() -> Self {
+        let environment = platform::DETECTION_ENV_VARS.iter()
+            .map(|var| (var.to_string(), std::env::var(var).ok()))
+            .collect();
+
+        let backends: Vec<BackendProbe> = platform::Backend::ALL.iter()
+            .map(|backend| {
+                SPAWNED_COMMANDS.with(|spawned| *spawned.borrow_mut() = Some(Vec::new()));
+                let started = Instant::now();
+                let window = backend.detect(false);
+                let elapsed = started.elapsed();
+                let commands = SPAWNED_COMMANDS.with(|spawned| spawned.borrow_mut().take()).unwrap_or_default();
+
+                BackendProbe {
+                    backend: backend.name(),
+                    applicable: backend.is_applicable(),
+                    detected: window.is_some(),
+                    app_name: window.as_ref().map(|window| window.app_name.clone()),
+                    window_title: window.as_ref().map(|window| window.title.clone()),
+                    window_id: window.and_then(|window| window.window_id),
+                    elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+                    commands,
+                }
+            })
+            .collect();
+
+        let chosen = backends.iter()
+            .find(|probe| probe.applicable && probe.detected)
+            .map(|probe| probe.backend);
+
+        Self {
+            platform: std::env::consts::OS,
+            environment,
+            backends,
+            chosen,
+            advice: detection_advice(chosen),
+        }
+    }
+}
+
+/// How many recent detections a backend's success rate is taken over
+pub const BACKEND_HEALTH_SAMPLES: usize = 30;
+
+/// The active backend counts as failing below this success rate
+pub const FAILOVER_SUCCESS_RATE: f64 = 0.5;
+
+/// How long the active backend may keep failing before the daemon looks for another
+pub const FAILOVER_AFTER: Duration = Duration::from_secs(60);
+
+/// Recent detection results of one backend
+#[derive(Debug, Clone, Default)]
+pub struct BackendHealth {
+    recent: VecDeque<bool>,
+}
+
+impl BackendHealth {
+    pub fn record(&mut self, detected: bool) {
+        if self.recent.len() == BACKEND_HEALTH_SAMPLES {
+            self.recent.pop_front();
         }
+        self.recent.push_back(detected);
+    }
+
+    /// Share of the recent detections that found a window; None before the first
+    pub fn success_rate(&self) -> Option<f64> {
+        (!self.recent.is_empty()).then(|| self.recent.iter().filter(|detected| **detected).count() as f64 / self.recent.len() as f64)
+    }
+}
+
+/// The daemon giving up on one backend for another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failover {
+    pub from: &'static str,
+    pub to: &'static str,
+    /// `from`'s success rate when it was given up
+    pub success_rate: f64,
+}
 
-        // Method 1: Hyprland (Wayland compositor)
-        if current_desktop.to_lowercase().contains("hyprland") || 
-           env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
-            if debug {
-                println!("~=~ Detected Hyprland, trying hyprctl...");
+/// Which backend the daemon asks first, and when it gives up on it. The
+/// active backend (the first to find a window, or the pinned one) is tried
+/// first on every poll, then the other applicable ones in the usual order.
+/// Once its success rate has stayed below FAILOVER_SUCCESS_RATE for
+/// FAILOVER_AFTER, every applicable backend is probed again and the
+/// healthiest one that found a window takes over. A pinned backend is the
+/// only one tried, and only fails over with `fallback`.
+#[derive(Debug, Clone, Default)]
+pub struct BackendSelector {
+    active: Option<&'static str>,
+    pinned: bool,
+    fallback: bool,
+    health: BTreeMap<&'static str, BackendHealth>,
+    failing_since: Option<DateTime<Utc>>,
+    failovers: u32,
+    quiet: bool,
+}
+
+impl BackendSelector {
+    pub fn new(pinned: Option<&'static str>, fallback: bool) -> Self {
+        BackendSelector {
+            active: pinned,
+            pinned: pinned.is_some(),
+            fallback,
+            ..Default::default()
+        }
+    }
+
+    /// The selector `config` asks for: its `backend` pinned (an unknown
+    /// name is reported and then picked automatically), falling back as
+    /// detection_fallback says
+    pub fn for_config(config: &config::Config) -> Self {
+        let pinned = config.backend.as_deref().and_then(|name| {
+            let backend = platform::Backend::ALL.into_iter().find(|backend| backend.name() == name);
+            if backend.is_none() {
+                eprintln!("❌ Unknown backend '{}' in config, picking one automatically", name);
             }
-            
-            if let Some(result) = try_hyprland_detection(debug) {
-                return Some(result);
+            backend.map(|backend| backend.name())
+        });
+        Self::new(pinned, config.detection_fallback)
+    }
+
+    /// Detect without printing each attempt; failures and failovers are
+    /// still reported on stderr
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    pub fn active(&self) -> Option<&'static str> {
+        self.active
+    }
+
+    pub fn failovers(&self) -> u32 {
+        self.failovers
+    }
+
+    pub fn success_rate(&self, backend: &str) -> Option<f64> {
+        self.health.get(backend).and_then(BackendHealth::success_rate)
+    }
+
+    /// The backends to ask this poll, out of the applicable ones in their
+    /// usual order. The process scan only guesses, so every other backend
+    /// is asked before it even while it is active.
+    pub fn order(&self, applicable: &[&'static str]) -> Vec<&'static str> {
+        match self.active {
+            Some(active) if self.pinned => vec![active],
+            Some(PROCESS_SCAN_BACKEND) => applicable.to_vec(),
+            Some(active) => std::iter::once(active)
+                .chain(applicable.iter().copied().filter(|backend| *backend != active))
+                .collect(),
+            None => applicable.to_vec(),
+        }
+    }
+
+    /// Note whether `backend` found a window. Without an active backend,
+    /// or with only the process scan, the first one that does becomes active.
+    pub fn record(&mut self, backend: &'static str, detected: bool, now: DateTime<Utc>) {
+        self.health.entry(backend).or_default().record(detected);
+        let replaceable = self.active.is_none() || (self.active == Some(PROCESS_SCAN_BACKEND) && !self.pinned);
+        if replaceable && detected {
+            self.active = Some(backend);
+        }
+        if self.active == Some(backend) {
+            let failing = self.success_rate(backend).is_some_and(|rate| rate < FAILOVER_SUCCESS_RATE);
+            self.failing_since = if failing { self.failing_since.or(Some(now)) } else { None };
+        }
+    }
+
+    /// Whether the active backend has been failing long enough to look for another
+    pub fn failover_due(&self, now: DateTime<Utc>) -> bool {
+        let allowed = !self.pinned || self.fallback;
+        let failing_for = |since: DateTime<Utc>| (now - since).to_std().unwrap_or_default();
+        allowed && self.failing_since.is_some_and(|since| failing_for(since) >= FAILOVER_AFTER)
+    }
+
+    /// Switch to the healthiest backend that found a window in `probe`, a
+    /// fresh run of every applicable backend in their usual order (the
+    /// first wins a tie). When none but the active one did, it stays and
+    /// gets another FAILOVER_AFTER.
+    pub fn fail_over(&mut self, probe: &[(&'static str, bool)], now: DateTime<Utc>) -> Option<Failover> {
+        for (backend, detected) in probe {
+            self.health.entry(backend).or_default().record(*detected);
+        }
+        let from = self.active?;
+        let mut best: Option<(&'static str, f64)> = None;
+        for (backend, _) in probe.iter().filter(|(backend, detected)| *detected && *backend != from) {
+            let rate = self.success_rate(backend).unwrap_or(0.0);
+            if best.is_none_or(|(_, best_rate)| rate > best_rate) {
+                best = Some((backend, rate));
             }
         }
+        let Some((to, _)) = best else {
+            self.failing_since = Some(now);
+            return None;
+        };
 
-        // Method 2: Sway (Wayland compositor)
-        if current_desktop.to_lowercase().contains("sway") || 
-           env::var("SWAYSOCK").is_ok() {
-            if debug {
-                println!("~=~ Detected Sway, trying swaymsg...");
+        let failover = Failover { from, to, success_rate: self.success_rate(from).unwrap_or(0.0) };
+        self.active = Some(to);
+        // The pin was given up with the backend
+        self.pinned = false;
+        self.failing_since = None;
+        self.failovers += 1;
+        Some(failover)
+    }
+}
+
+/// Shortest tracking_interval_ms for backends that run a command on every
+/// check; below it the process spawns cost more than the extra resolution
+pub const MIN_SPAWNING_INTERVAL_MS: u64 = 1000;
+
+/// Whether the daemon may check every `interval_ms` with the backends it
+/// would use (`pinned`, or every applicable one). A sub-second interval
+/// needs one that answers without spawning a process.
+pub fn check_interval(interval_ms: u64, pinned: Option<&str>) -> Result<(), String> {
+    if interval_ms >= MIN_SPAWNING_INTERVAL_MS {
+        return Ok(());
+    }
+    let candidates: Vec<platform::Backend> = platform::Backend::ALL.into_iter()
+        .filter(|backend| match pinned {
+            Some(name) => backend.name() == name,
+            None => backend.is_applicable(),
+        })
+        .collect();
+    if candidates.iter().any(platform::Backend::is_native) {
+        return Ok(());
+    }
+    let names: Vec<&str> = candidates.iter().map(|backend| backend.name()).collect();
+    Err(format!(
+        "tracking_interval_ms {} is below {}, which needs a native or event-driven window backend; {} run{} a command on every check here",
+        interval_ms, MIN_SPAWNING_INTERVAL_MS,
+        if names.is_empty() { "the backends".to_string() } else { names.join(", ") },
+        if names.len() == 1 { "s" } else { "" },
+    ))
+}
+
+/// Whether the daemon can see windows at all: the backend it would use
+/// (`pinned`, or without `fallback` only that one; else every applicable
+/// one) finds a window. The name of the first one that does.
+pub fn check_detection(pinned: Option<&str>, fallback: bool) -> Result<&'static str, String> {
+    let candidates: Vec<platform::Backend> = platform::Backend::ALL.into_iter()
+        .filter(|backend| match pinned {
+            Some(name) if !fallback => backend.name() == name,
+            Some(name) => backend.name() == name || backend.is_applicable(),
+            None => backend.is_applicable(),
+        })
+        .collect();
+    if let Some(backend) = candidates.iter().find(|backend| backend.detect(false).is_some()) {
+        return Ok(backend.name());
+    }
+    let names: Vec<&str> = candidates.iter().map(|backend| backend.name()).collect();
+    Err(format!("no window detection backend found a window (tried: {}); run 'focusdebt debug' to see why",
+        if names.is_empty() { "none apply here".to_string() } else { names.join(", ") }))
+}
+
+/// How long a new window has to stay before a sub-second daemon counts it,
+/// so the switcher an alt-tab passes through isn't a row of its own
+pub const WINDOW_SETTLE: Duration = Duration::from_millis(500);
+
+/// Holds a window change back until the new window has stayed for the
+/// settle time; the change is then dated to when it was first seen, so a
+/// row boundary lands within one tick of the real switch
+#[derive(Debug, Clone, Default)]
+pub struct WindowDebouncer {
+    settle: Duration,
+    pending: Option<(WindowInfo, DateTime<Utc>)>,
+}
+
+impl WindowDebouncer {
+    /// WINDOW_SETTLE for sub-second intervals; at a second or more every
+    /// change counts at once, as a window seen on one tick has been there
+    /// longer than any switcher
+    pub fn for_interval(interval_ms: u64) -> Self {
+        let settle = if interval_ms < MIN_SPAWNING_INTERVAL_MS { WINDOW_SETTLE } else { Duration::ZERO };
+        WindowDebouncer { settle, pending: None }
+    }
+
+    /// `window`, seen at `at` while `current` is active: the window to
+    /// switch to and when it became active, once it has settled
+    pub fn observe(&mut self, current: Option<&WindowInfo>, window: WindowInfo, at: DateTime<Utc>) -> Option<(WindowInfo, DateTime<Utc>)> {
+        if current == Some(&window) {
+            self.pending = None;
+            return None;
+        }
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == window => *since,
+            _ => at,
+        };
+        if (at - since).to_std().unwrap_or_default() >= self.settle {
+            self.pending = None;
+            return Some((window, since));
+        }
+        self.pending = Some((window, since));
+        None
+    }
+}
+
+/// How often the tracking loop logs each kind of message at most
+pub const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Keeps the tracking loop's log volume independent of its tick: each kind
+/// of message is logged at most once per interval, and the next one says
+/// how many were held back
+#[derive(Debug, Clone, Default)]
+pub struct LogLimiter {
+    kinds: HashMap<&'static str, (Instant, u32)>,
+}
+
+impl LogLimiter {
+    /// Whether a `kind` message may be logged now; if so, how many were
+    /// held back since the last one
+    pub fn allow(&mut self, kind: &'static str, every: Duration) -> Option<u32> {
+        let now = Instant::now();
+        match self.kinds.get_mut(kind) {
+            Some((last, held)) if now.duration_since(*last) < every => {
+                *held += 1;
+                None
             }
-            
-            if let Some(result) = try_sway_detection(debug) {
-                return Some(result);
+            Some((last, held)) => {
+                *last = now;
+                Some(std::mem::take(held))
+            }
+            None => {
+                self.kinds.insert(kind, (now, 0));
+                Some(0)
             }
         }
+    }
+}
 
-        // Method 3: GNOME on Wayland
-        if session_type == "wayland" && current_desktop.to_lowercase().contains("gnome") {
-            if debug {
-                println!("~=~ Detected GNOME on Wayland, trying gdbus...");
-            }
-            
-            if let Some(result) = try_gnome_wayland_detection(debug) {
-                return Some(result);
+/// " (3 more not logged)" after a rate-limited message, or nothing
+pub fn held_back_note(held: u32) -> String {
+    match held {
+        0 => String::new(),
+        held => format!(" ({} more not logged)", held),
+    }
+}
+
+/// The daemon's window detection: the backends in `selector`'s order,
+/// after failing over first when that is due
+pub fn detect_with(selector: &mut BackendSelector) -> Option<(&'static str, WindowInfo)> {
+    let now = Utc::now();
+    let applicable: Vec<platform::Backend> = platform::Backend::ALL.into_iter()
+        .filter(|backend| backend.is_applicable())
+        .collect();
+
+    if selector.failover_due(now) {
+        let failing = selector.active().unwrap_or_default();
+        if !selector.quiet {
+            println!("~=~ Backend {} has been failing for {}s, detecting the environment again", failing, FAILOVER_AFTER.as_secs());
+        }
+        let probe: Vec<(&'static str, Option<WindowInfo>)> = applicable.iter()
+            .map(|backend| (backend.name(), backend.detect(false)))
+            .collect();
+        let results: Vec<(&'static str, bool)> = probe.iter().map(|(backend, window)| (*backend, window.is_some())).collect();
+        match selector.fail_over(&results, now) {
+            Some(failover) => {
+                let note = format!("~=~ Failing over from {} ({:.0}% of recent detections worked) to {}",
+                    failover.from, failover.success_rate * 100.0, failover.to);
+                if selector.quiet {
+                    eprintln!("{}", note);
+                } else {
+                    println!("{}", note);
+                }
+                let window = probe.into_iter().find(|(backend, _)| *backend == failover.to).and_then(|(_, window)| window);
+                if let Some(window) = window {
+                    return Some((failover.to, window));
+                }
             }
+            None => eprintln!("❌ No other backend found a window, staying with {}", failing),
+        }
+    }
+
+    let names: Vec<&'static str> = applicable.iter().map(|backend| backend.name()).collect();
+    for name in selector.order(&names) {
+        let Some(backend) = platform::Backend::ALL.into_iter().find(|backend| backend.name() == name) else {
+            continue;
+        };
+        if !selector.quiet {
+            println!("~=~ Trying {} detection...", name);
+        }
+        let window = backend.detect(!selector.quiet);
+        selector.record(name, window.is_some(), now);
+        if let Some(window) = window {
+            return Some((name, window));
         }
+    }
 
-        // Method 4: KDE on Wayland
-        if session_type == "wayland" && current_desktop.to_lowercase().contains("kde") {
-            if debug {
-                println!("~=~ Detected KDE on Wayland, trying kwin...");
+    eprintln!("❌ All window detection methods failed");
+    None
+}
+
+/// Split backend output printed one field per line into `count` fields. The
+/// last one takes the rest, so a window title may hold any character; line
+/// breaks inside it become spaces.
+#[cfg(any(test, target_os = "macos", target_os = "windows"))]
+fn split_output_lines(out: &str, count: usize) -> Option<Vec<String>> {
+    let out = out.trim_end_matches(['\r', '\n']);
+    if out.trim().is_empty() {
+        return None;
+    }
+    let mut fields: Vec<String> = out.splitn(count, '\n')
+        .map(|field| field.trim_end_matches('\r').to_string())
+        .collect();
+    if let Some(last) = fields.last_mut() {
+        *last = last.lines().map(str::trim_end).collect::<Vec<_>>().join(" ");
+    }
+    fields.resize(count, String::new());
+    Some(fields)
+}
+
+/// Parse the macOS AppleScript output: the app name on the first line, the
+/// window title after it
+#[cfg(any(test, target_os = "macos"))]
+pub fn parse_osascript_output(out: &str) -> Option<WindowInfo> {
+    let fields = split_output_lines(out, 2)?;
+    let app_name = fields[0].trim();
+    if app_name.is_empty() {
+        return None;
+    }
+    Some(WindowInfo::new(app_name, fields[1].trim()))
+}
+
+/// Parse the Windows PowerShell output: the window handle, the process name
+/// and the title, one per line
+#[cfg(any(test, target_os = "windows"))]
+pub fn parse_powershell_output(out: &str) -> Option<WindowInfo> {
+    let fields = split_output_lines(out, 3)?;
+    let app_name = fields[1].trim();
+    if app_name.is_empty() || app_name == "UnknownApp" {
+        return None;
+    }
+    Some(WindowInfo::new(app_name, fields[2].trim()).with_id(fields[0].trim()))
+}
+
+/// The window title printed by `xdotool getwindowname`: only the trailing
+/// line break goes, so leading spaces, tabs and quotes stay; a line break
+/// inside the title becomes a space. None for a blank title.
+pub fn parse_xdotool_title(out: &str) -> Option<String> {
+    let title = out.strip_suffix('\n').unwrap_or(out);
+    let title = title.strip_suffix('\r').unwrap_or(title).replace(['\r', '\n'], " ");
+    (!title.trim().is_empty()).then_some(title)
+}
+
+/// The values of an xprop property as strings: `"code", "Code"` gives both
+/// names, a bare value like `1` or `0x3a00004` comes back as is. Inside
+/// quotes xprop escapes `"` and `\` with a backslash and other bytes as
+/// \n, \t or octal \ooo; those bytes are decoded as UTF-8, which covers
+/// UTF8_STRING values printed without a UTF-8 locale.
+pub fn parse_xprop_strings(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        let Some(first) = chars.next() else { break };
+        if first != '"' {
+            let mut bare = String::from(first);
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                bare.push(c);
             }
-            
-            if let Some(result) = try_kde_wayland_detection(debug) {
-                return Some(result);
+            values.push(bare.trim_end().to_string());
+            continue;
+        }
+        let mut bytes = Vec::new();
+        let mut buffer = [0; 4];
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => bytes.push(b'\n'),
+                    Some('t') => bytes.push(b'\t'),
+                    Some('r') => bytes.push(b'\r'),
+                    Some(digit @ '0'..='7') => {
+                        let mut byte = digit.to_digit(8).unwrap_or_default();
+                        for _ in 0..2 {
+                            match chars.next_if(|c| ('0'..='7').contains(c)) {
+                                Some(digit) => byte = byte * 8 + digit.to_digit(8).unwrap_or_default(),
+                                None => break,
+                            }
+                        }
+                        bytes.push(byte as u8);
+                    }
+                    Some(other) => bytes.extend_from_slice(other.encode_utf8(&mut buffer).as_bytes()),
+                    None => break,
+                },
+                c => bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes()),
             }
         }
+        values.push(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    values
+}
+
+/// The values of `property` in xprop output, from a line like
+/// `WM_CLASS(STRING) = "code", "Code"` or
+/// `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00004`. None when the
+/// property is missing (`WM_NAME:  not found.`).
+pub fn xprop_property(output: &str, property: &str) -> Option<Vec<String>> {
+    output.lines().find_map(|line| {
+        let rest = line.strip_prefix(property)?.strip_prefix('(')?;
+        let (_, rest) = rest.split_once(')')?;
+        let value = rest.strip_prefix(" = ").or_else(|| rest.strip_prefix(": "))?;
+        Some(parse_xprop_strings(value))
+    })
+}
+
+/// The window in `xprop -root _NET_ACTIVE_WINDOW` output; None when no
+/// window has focus (id 0x0)
+pub fn parse_xprop_active_window(output: &str) -> Option<u64> {
+    let value = xprop_property(output, "_NET_ACTIVE_WINDOW")?.into_iter().next()?;
+    let id = value.rsplit("# ").next()?.trim();
+    u64::from_str_radix(id.strip_prefix("0x")?, 16).ok().filter(|id| *id != 0)
+}
+
+/// The app name in a WM_CLASS "instance.class" pair as wmctrl -lx prints
+/// it. Instances may hold dots themselves (org.gnome.Nautilus), so a split
+/// with both halves equal but for case wins; otherwise the first dot splits.
+fn wm_class_instance(class: &str) -> &str {
+    let dots: Vec<usize> = class.match_indices('.').map(|(index, _)| index).collect();
+    let split = dots.iter()
+        .find(|&&dot| class[..dot].eq_ignore_ascii_case(&class[dot + 1..]))
+        .or(dots.first());
+    match split {
+        Some(&dot) => &class[..dot],
+        None => class,
+    }
+}
+
+/// Window `id` in `wmctrl -lx` output. Each line is the id, the desktop,
+/// "instance.class" padded to 20 columns, the client machine padded on the
+/// left and, after a single space, the title, which is kept exactly.
+pub fn parse_wmctrl_lx(output: &str, id: u64) -> Option<WindowInfo> {
+    output.lines().find_map(|line| {
+        let mut rest = line;
+        let mut fields = Vec::with_capacity(4);
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let line_id = u64::from_str_radix(fields[0].strip_prefix("0x")?, 16).ok()?;
+        if line_id != id || fields[2].is_empty() || fields[2] == "N/A" {
+            return None;
+        }
+        let title = rest.strip_prefix(' ').unwrap_or(rest);
+        Some(WindowInfo::new(wm_class_instance(fields[2]), title).with_id(id.to_string()))
+    })
+}
+
+/// Whether a `gdbus call` to org.gnome.Shell.Eval was refused: GNOME 41
+/// and later answer `(false, '')` outside unsafe mode, and some setups deny
+/// the call outright
+pub fn is_gnome_eval_disabled(output: &Output) -> bool {
+    if output.status.success() {
+        return String::from_utf8_lossy(&output.stdout).trim_start().starts_with("(false,");
+    }
+    String::from_utf8_lossy(&output.stderr).contains("AccessDenied")
+}
+
+/// The string `gdbus call` prints as the only value of a reply, e.g.
+/// `('text',)`, with GVariant's escapes undone. Strings holding a single
+/// quote are printed in double quotes.
+pub fn parse_gvariant_string(out: &str) -> Option<String> {
+    let inner = out.trim().strip_prefix('(')?.strip_suffix(",)")?;
+    let quote = inner.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let mut chars = inner[1..].chars();
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => text.push('\n'),
+                't' => text.push('\t'),
+                'r' => text.push('\r'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    text.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                other => text.push(other),
+            },
+            c if c == quote => return chars.as_str().is_empty().then_some(text),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+/// The focused window in the JSON array the Window Calls extension's List
+/// returns, and its id for GetTitle. Older versions of the extension put
+/// the title in the list, newer ones only answer GetTitle.
+pub fn parse_window_calls_list(json: &str) -> Option<(WindowInfo, Option<u64>)> {
+    let windows: Vec<serde_json::Value> = serde_json::from_str(json).ok()?;
+    let focused = windows.iter().find(|window| window["focus"].as_bool() == Some(true))?;
+    let app = [&focused["wm_class_instance"], &focused["wm_class"]].into_iter()
+        .filter_map(|class| class.as_str())
+        .find(|class| !class.trim().is_empty())?;
+    let id = focused["id"].as_u64();
+    let mut window = WindowInfo::new(app, focused["title"].as_str().unwrap_or_default());
+    if let Some(id) = id {
+        window = window.with_id(id.to_string());
+    }
+    Some((window, id))
+}
+
+#[cfg(target_os = "linux")]
+pub mod platform {
+    use std::process::Command;
+    use std::sync::atomic::Ordering;
+    use super::{
+        is_gnome_eval_disabled, parse_gvariant_string, parse_window_calls_list, parse_wmctrl_lx, parse_xdotool_title,
+        parse_xprop_active_window, parse_xprop_strings, process_scan_allowed, run_command, xprop_property, WindowInfo,
+        GNOME_EVAL_DISABLED,
+    };
+    use std::env;
 
-        // Method 5: Generic Wayland fallback
-        if session_type == "wayland" || !wayland_display.is_empty() {
-            if debug {
-                println!("~=~ Generic Wayland detected, trying wlrctl/wlr-randr...");
-            }
-            
-            if let Some(result) = try_generic_wayland_detection(debug) {
-                return Some(result);
-            }
-        }
+    /// Environment variables that decide which backends apply
+    pub const DETECTION_ENV_VARS: &[&str] = &[
+        "XDG_SESSION_TYPE",
+        "XDG_CURRENT_DESKTOP",
+        "WAYLAND_DISPLAY",
+        "DISPLAY",
+        "HYPRLAND_INSTANCE_SIGNATURE",
+        "SWAYSOCK",
+    ];
+
+    /// Window detection backends, in the order the daemon tries them
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Backend {
+        Hyprland,
+        Sway,
+        GnomeWindowCalls,
+        GnomeWayland,
+        KdeWayland,
+        GenericWayland,
+        Xdotool,
+        Wmctrl,
+        Xprop,
+        ProcessScan,
+    }
 
-        // Method 6: X11 with xdotool (traditional method)
-        if debug {
-            println!("~=~ Trying X11 detection with xdotool...");
-        }
-        
-        if let Some(result) = try_x11_xdotool_detection(debug) {
-            return Some(result);
-        }
+    impl Backend {
+        pub const ALL: [Backend; 10] = [
+            Backend::Hyprland,
+            Backend::Sway,
+            Backend::GnomeWindowCalls,
+            Backend::GnomeWayland,
+            Backend::KdeWayland,
+            Backend::GenericWayland,
+            Backend::Xdotool,
+            Backend::Wmctrl,
+            Backend::Xprop,
+            Backend::ProcessScan,
+        ];
 
-        // Method 7: X11 with wmctrl fallback
-        if debug {
-            println!("~=~Trying X11 detection with wmctrl...");
-        }
-        
-        if let Some(result) = try_x11_wmctrl_detection(debug) {
-            return Some(result);
+        pub fn name(&self) -> &'static str {
+            match self {
+                Backend::Hyprland => "hyprland",
+                Backend::Sway => "sway",
+                Backend::GnomeWindowCalls => "gnome_window_calls",
+                Backend::GnomeWayland => "gnome_wayland",
+                Backend::KdeWayland => "kde_wayland",
+                Backend::GenericWayland => "generic_wayland",
+                Backend::Xdotool => "xdotool",
+                Backend::Wmctrl => "wmctrl",
+                Backend::Xprop => "xprop",
+                Backend::ProcessScan => super::PROCESS_SCAN_BACKEND,
+            }
         }
 
-        // Method 8: X11 with xprop fallback
-        if debug {
-            println!("~=~ Trying X11 detection with xprop...");
-        }
-        
-        if let Some(result) = try_x11_xprop_detection(debug) {
-            return Some(result);
+        /// Whether the backend answers without spawning a process per check
+        /// (a native API or an event stream), fast enough for sub-second
+        /// intervals. Every backend here runs a command.
+        pub fn is_native(&self) -> bool {
+            false
         }
 
-        // Method 9: Fallback to process scanning
-        if debug {
-            println!("❌ All methods failed, trying process scanning fallback...");
-        }
-        
-        if let Some(result) = try_process_scanning_fallback(debug) {
-            return Some(result);
+        /// Whether the session environment says this backend is worth trying
+        pub fn is_applicable(&self) -> bool {
+            let session_type = env::var("XDG_SESSION_TYPE").unwrap_or_default();
+            let current_desktop = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+            let wayland_display = env::var("WAYLAND_DISPLAY").unwrap_or_default();
+
+            match self {
+                Backend::Hyprland => current_desktop.contains("hyprland")
+                    || env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok(),
+                Backend::Sway => current_desktop.contains("sway") || env::var("SWAYSOCK").is_ok(),
+                Backend::GnomeWindowCalls | Backend::GnomeWayland => session_type == "wayland" && current_desktop.contains("gnome"),
+                Backend::KdeWayland => session_type == "wayland" && current_desktop.contains("kde"),
+                Backend::GenericWayland => session_type == "wayland" || !wayland_display.is_empty(),
+                Backend::Xdotool | Backend::Wmctrl | Backend::Xprop => true,
+                Backend::ProcessScan => process_scan_allowed(),
+            }
         }
 
-        if debug {
-            eprintln!("❌ All window detection methods failed");
+        pub fn detect(&self, debug: bool) -> Option<WindowInfo> {
+            match self {
+                Backend::Hyprland => try_hyprland_detection(debug),
+                Backend::Sway => try_sway_detection(debug),
+                Backend::GnomeWindowCalls => try_gnome_window_calls_detection(debug),
+                Backend::GnomeWayland => try_gnome_wayland_detection(debug),
+                Backend::KdeWayland => try_kde_wayland_detection(debug),
+                Backend::GenericWayland => try_generic_wayland_detection(debug),
+                Backend::Xdotool => try_x11_xdotool_detection(debug),
+                Backend::Wmctrl => try_x11_wmctrl_detection(debug),
+                Backend::Xprop => try_x11_xprop_detection(debug),
+                Backend::ProcessScan if process_scan_allowed() => try_process_scanning_fallback(debug),
+                Backend::ProcessScan => None,
+            }
         }
-        None
     }
 
-    fn try_hyprland_detection(debug: bool) -> Option<(String, String)> {
-        if let Ok(output) = Command::new("hyprctl")
-            .args(&["activewindow", "-j"])
-            .output() {
+    fn try_hyprland_detection(debug: bool) -> Option<WindowInfo> {
+        if let Ok(output) = run_command(Command::new("hyprctl")
+            .args(["activewindow", "-j"])) {
             
             if output.status.success() {
                 let json_str = String::from_utf8_lossy(&output.stdout);
@@ -500,10 +1883,18 @@ pub mod platform {
                             if let Some(title_end) = json_str[title_start + 9..].find("\"") {
                                 let title = &json_str[title_start + 9..title_start + 9 + title_end];
                                 
+                                let address = json_str.find("\"address\":\"")
+                                    .and_then(|start| json_str[start + 11..].split('"').next())
+                                    .unwrap_or("");
+                                let workspace = serde_json::from_str::<serde_json::Value>(&json_str).ok()
+                                    .and_then(|window| window["workspace"]["name"].as_str().map(str::to_string));
+                                let window = WindowInfo::new(class_name.to_string(), title.to_string())
+                                    .with_id(address)
+                                    .with_workspace(workspace);
                                 if debug {
-                                    println!("~=~ Hyprland detected: {} - {}", class_name, title);
+                                    println!("~=~ Hyprland detected: {} - {}", window.app_name, window.title);
                                 }
-                                return Some((class_name.to_string(), title.to_string()));
+                                return Some(window);
                             }
                         }
                     }
@@ -512,28 +1903,40 @@ pub mod platform {
         }
         
         // Fallback to non-JSON hyprctl
-        if let Ok(output) = Command::new("hyprctl")
-            .args(&["activewindow"])
-            .output() {
+        if let Ok(output) = run_command(Command::new("hyprctl")
+            .args(["activewindow"])) {
             
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
                 let mut class_name = String::new();
                 let mut title = String::new();
+                let mut workspace = None;
+                // First line: "Window 55d1c0a8 -> title:"
+                let address = output_str.lines().next()
+                    .and_then(|line| line.strip_prefix("Window "))
+                    .and_then(|line| line.split_whitespace().next())
+                    .map(|address| format!("0x{}", address))
+                    .unwrap_or_default();
                 
                 for line in output_str.lines() {
                     if line.trim().starts_with("class:") {
                         class_name = line.trim().strip_prefix("class:").unwrap_or("").trim().to_string();
                     } else if line.trim().starts_with("title:") {
                         title = line.trim().strip_prefix("title:").unwrap_or("").trim().to_string();
+                    } else if let Some(value) = line.trim().strip_prefix("workspace:") {
+                        // "workspace: 2 (code)": the id, then the name
+                        workspace = value.split_once('(')
+                            .and_then(|(_, name)| name.strip_suffix(')'))
+                            .map(str::to_string);
                     }
                 }
                 
                 if !class_name.is_empty() && !title.is_empty() {
+                    let window = WindowInfo::new(class_name, title).with_id(address).with_workspace(workspace);
                     if debug {
-                        println!("~=~Hyprland detected: {} - {}", class_name, title);
+                        println!("~=~Hyprland detected: {} - {}", window.app_name, window.title);
                     }
-                    return Some((class_name, title));
+                    return Some(window);
                 }
             }
         }
@@ -541,10 +1944,9 @@ pub mod platform {
         None
     }
 
-    fn try_sway_detection(debug: bool) -> Option<(String, String)> {
-        if let Ok(output) = Command::new("swaymsg")
-            .args(&["-t", "get_tree"])
-            .output() {
+    fn try_sway_detection(debug: bool) -> Option<WindowInfo> {
+        if let Ok(output) = run_command(Command::new("swaymsg")
+            .args(["-t", "get_tree"])) {
             
             if output.status.success() {
                 let json_str = String::from_utf8_lossy(&output.stdout);
@@ -560,10 +1962,12 @@ pub mod platform {
                                 if let Some(name_end) = json_str[name_start + 8..].find("\"") {
                                     let name = &json_str[name_start + 8..name_start + 8 + name_end];
                                     
+                                    let window = WindowInfo::new(app_id.to_string(), name.to_string())
+                                        .with_workspace(sway_workspace());
                                     if debug {
-                                        println!("~=~ Sway detected: {} - {}", app_id, name);
+                                        println!("~=~ Sway detected: {} - {}", window.app_name, window.title);
                                     }
-                                    return Some((app_id.to_string(), name.to_string()));
+                                    return Some(window);
                                 }
                             }
                         }
@@ -575,15 +1979,49 @@ pub mod platform {
         None
     }
 
-    fn try_gnome_wayland_detection(debug: bool) -> Option<(String, String)> {
+    /// The Window Calls GNOME Shell extension's D-Bus interface, which
+    /// lists windows without Eval
+    const WINDOW_CALLS_PATH: &str = "/org/gnome/Shell/Extensions/Windows";
+    const WINDOW_CALLS_INTERFACE: &str = "org.gnome.Shell.Extensions.Windows";
+
+    fn window_calls(method: &str, args: &[&str]) -> Option<String> {
+        let method = format!("{}.{}", WINDOW_CALLS_INTERFACE, method);
+        let output = run_command(Command::new("gdbus")
+            .args(["call", "--session", "--dest", "org.gnome.Shell", "--object-path", WINDOW_CALLS_PATH, "--method", &method])
+            .args(args)).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_gvariant_string(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn try_gnome_window_calls_detection(debug: bool) -> Option<WindowInfo> {
+        let (mut window, id) = parse_window_calls_list(&window_calls("List", &[])?)?;
+        if window.title.is_empty() {
+            if let Some(title) = id.and_then(|id| window_calls("GetTitle", &[&id.to_string()])) {
+                window = WindowInfo { window_id: window.window_id, ..WindowInfo::new(window.app_name, title) };
+            }
+        }
+        if debug {
+            println!("~=~ GNOME Window Calls detected: {} - {}", window.app_name, window.title);
+        }
+        Some(window)
+    }
+
+    fn try_gnome_wayland_detection(debug: bool) -> Option<WindowInfo> {
         // Try to get focused window via GNOME Shell's D-Bus interface
-        if let Ok(output) = Command::new("gdbus")
-            .args(&["call", "--session", "--dest", "org.gnome.Shell", 
+        if let Ok(output) = run_command(Command::new("gdbus")
+            .args(["call", "--session", "--dest", "org.gnome.Shell", 
                    "--object-path", "/org/gnome/Shell", 
                    "--method", "org.gnome.Shell.Eval", 
-                   "global.display.get_focus_window().get_wm_class()"])
-            .output() {
+                   "global.display.get_focus_window().get_wm_class()"])) {
             
+            // Refused since GNOME 41; a reply of (false, '') holds no window.
+            // The daemon logs GNOME_EVAL_GUIDANCE once rather than every poll.
+            if is_gnome_eval_disabled(&output) {
+                GNOME_EVAL_DISABLED.store(true, Ordering::Relaxed);
+                return None;
+            }
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
                 if let Some(class_start) = output_str.find("'") {
@@ -591,12 +2029,11 @@ pub mod platform {
                         let class_name = &output_str[class_start + 1..class_start + 1 + class_end];
                         
                         // Get window title
-                        if let Ok(title_output) = Command::new("gdbus")
-                            .args(&["call", "--session", "--dest", "org.gnome.Shell", 
+                        if let Ok(title_output) = run_command(Command::new("gdbus")
+                            .args(["call", "--session", "--dest", "org.gnome.Shell", 
                                    "--object-path", "/org/gnome/Shell", 
                                    "--method", "org.gnome.Shell.Eval", 
-                                   "global.display.get_focus_window().get_title()"])
-                            .output() {
+                                   "global.display.get_focus_window().get_title()"])) {
                             
                             if title_output.status.success() {
                                 let title_str = String::from_utf8_lossy(&title_output.stdout);
@@ -604,10 +2041,11 @@ pub mod platform {
                                     if let Some(title_end) = title_str[title_start + 1..].find("'") {
                                         let title = &title_str[title_start + 1..title_start + 1 + title_end];
                                         
+                                        let window = WindowInfo::new(class_name.to_string(), title.to_string());
                                         if debug {
-                                            println!("~=~ GNOME Wayland detected: {} - {}", class_name, title);
+                                            println!("~=~ GNOME Wayland detected: {} - {}", window.app_name, window.title);
                                         }
-                                        return Some((class_name.to_string(), title.to_string()));
+                                        return Some(window);
                                     }
                                 }
                             }
@@ -620,38 +2058,36 @@ pub mod platform {
         None
     }
 
-    fn try_kde_wayland_detection(debug: bool) -> Option<(String, String)> {
+    fn try_kde_wayland_detection(debug: bool) -> Option<WindowInfo> {
         // Try KDE's kwin D-Bus interface
-        if let Ok(output) = Command::new("qdbus")
-            .args(&["org.kde.KWin", "/KWin", "org.kde.KWin.activeWindow"])
-            .output() {
+        if let Ok(output) = run_command(Command::new("qdbus")
+            .args(["org.kde.KWin", "/KWin", "org.kde.KWin.activeWindow"])) {
             
             if output.status.success() {
                 let window_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
                 
                 if !window_id.is_empty() {
                     // Get window class
-                    if let Ok(class_output) = Command::new("qdbus")
-                        .args(&["org.kde.KWin", &format!("/KWin/Window_{}", window_id), 
-                               "org.kde.KWin.Window.resourceClass"])
-                        .output() {
+                    if let Ok(class_output) = run_command(Command::new("qdbus")
+                        .args(["org.kde.KWin", &format!("/KWin/Window_{}", window_id), 
+                               "org.kde.KWin.Window.resourceClass"])) {
                         
                         if class_output.status.success() {
                             let class_name = String::from_utf8_lossy(&class_output.stdout).trim().to_string();
                             
                             // Get window title
-                            if let Ok(title_output) = Command::new("qdbus")
-                                .args(&["org.kde.KWin", &format!("/KWin/Window_{}", window_id), 
-                                       "org.kde.KWin.Window.caption"])
-                                .output() {
+                            if let Ok(title_output) = run_command(Command::new("qdbus")
+                                .args(["org.kde.KWin", &format!("/KWin/Window_{}", window_id), 
+                                       "org.kde.KWin.Window.caption"])) {
                                 
                                 if title_output.status.success() {
                                     let title = String::from_utf8_lossy(&title_output.stdout).trim().to_string();
                                     
+                                    let window = WindowInfo::new(class_name, title).with_id(window_id);
                                     if debug {
-                                        println!("✅ KDE Wayland detected: {} - {}", class_name, title);
+                                        println!("✅ KDE Wayland detected: {} - {}", window.app_name, window.title);
                                     }
-                                    return Some((class_name, title));
+                                    return Some(window);
                                 }
                             }
                         }
@@ -663,11 +2099,10 @@ pub mod platform {
         None
     }
 
-    fn try_generic_wayland_detection(debug: bool) -> Option<(String, String)> {
+    fn try_generic_wayland_detection(debug: bool) -> Option<WindowInfo> {
         // Try wlr-randr for wlroots-based compositors
-        if let Ok(output) = Command::new("wlrctl")
-            .args(&["window", "get"])
-            .output() {
+        if let Ok(output) = run_command(Command::new("wlrctl")
+            .args(["window", "get"])) {
             
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
@@ -678,10 +2113,11 @@ pub mod platform {
                         let title = title_line.split(':').nth(1).unwrap_or("").trim().to_string();
                         
                         if !app_id.is_empty() && !title.is_empty() {
+                            let window = WindowInfo::new(app_id, title);
                             if debug {
-                                println!("~=~ wlrctl detected: {} - {}", app_id, title);
+                                println!("~=~ wlrctl detected: {} - {}", window.app_name, window.title);
                             }
-                            return Some((app_id, title));
+                            return Some(window);
                         }
                     }
                 }
@@ -691,10 +2127,48 @@ pub mod platform {
         None
     }
 
-    fn try_x11_xdotool_detection(debug: bool) -> Option<(String, String)> {
-        if let Ok(window_id_output) = Command::new("xdotool")
-            .args(&["getactivewindow"])
-            .output() {
+    /// Name of the focused Sway workspace
+    fn sway_workspace() -> Option<String> {
+        let output = run_command(Command::new("swaymsg").args(["-t", "get_workspaces"])).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let workspaces: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+        workspaces.as_array()?.iter()
+            .find(|workspace| workspace["focused"].as_bool() == Some(true))
+            .and_then(|workspace| workspace["name"].as_str().map(str::to_string))
+    }
+
+    /// The current EWMH desktop (_NET_CURRENT_DESKTOP), by its name when
+    /// the window manager sets _NET_DESKTOP_NAMES, else numbered from 1
+    fn x11_current_desktop() -> Option<String> {
+        let output = run_command(Command::new("xprop").args(["-root", "_NET_CURRENT_DESKTOP", "_NET_DESKTOP_NAMES"])).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_x11_desktop(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Reads `xprop -root` lines like `_NET_CURRENT_DESKTOP(CARDINAL) = 1`
+    /// and `_NET_DESKTOP_NAMES(UTF8_STRING) = "1", "code", "mail"`
+    fn parse_x11_desktop(output: &str) -> Option<String> {
+        let mut index = None;
+        let mut names = Vec::new();
+        for line in output.lines() {
+            let Some((property, value)) = line.split_once(" = ") else { continue };
+            if property.starts_with("_NET_CURRENT_DESKTOP") {
+                index = value.trim().parse::<usize>().ok();
+            } else if property.starts_with("_NET_DESKTOP_NAMES") {
+                names = parse_xprop_strings(value);
+            }
+        }
+        let index = index?;
+        Some(names.get(index).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| (index + 1).to_string()))
+    }
+
+    fn try_x11_xdotool_detection(debug: bool) -> Option<WindowInfo> {
+        if let Ok(window_id_output) = run_command(Command::new("xdotool")
+            .args(["getactivewindow"])) {
             
             if window_id_output.status.success() {
                 let window_id = String::from_utf8_lossy(&window_id_output.stdout).trim().to_string();
@@ -702,27 +2176,29 @@ pub mod platform {
                     
                     // Get window title and PID
                     if let (Ok(title_output), Ok(pid_output)) = (
-                        Command::new("xdotool").args(&["getwindowname", &window_id]).output(),
-                        Command::new("xdotool").args(&["getwindowpid", &window_id]).output()
+                        run_command(Command::new("xdotool").args(["getwindowname", &window_id])),
+                        run_command(Command::new("xdotool").args(["getwindowpid", &window_id]))
                     ) {
                         
                         if title_output.status.success() && pid_output.status.success() {
-                            let window_title = String::from_utf8_lossy(&title_output.stdout).trim().to_string();
+                            let window_title = parse_xdotool_title(&String::from_utf8_lossy(&title_output.stdout));
                             let pid = String::from_utf8_lossy(&pid_output.stdout).trim().to_string();
                             
-                            if !window_title.is_empty() && !pid.is_empty() {
+                            if let Some(window_title) = window_title.filter(|_| pid.parse::<u32>().is_ok()) {
                                 // Get process name from PID
-                                if let Ok(ps_output) = Command::new("ps")
-                                    .args(&["-p", &pid, "-o", "comm=", "--no-headers"])
-                                    .output() {
+                                if let Ok(ps_output) = run_command(Command::new("ps")
+                                    .args(["-p", &pid, "-o", "comm=", "--no-headers"])) {
                                     
                                     if ps_output.status.success() {
                                         let app_name = String::from_utf8_lossy(&ps_output.stdout).trim().to_string();
                                         if !app_name.is_empty() {
+                                            let window = WindowInfo::new(app_name, window_title)
+                                                .with_id(window_id)
+                                                .with_workspace(x11_current_desktop());
                                             if debug {
-                                                println!("~=~ xdotool detected: {} - {}", app_name, window_title);
+                                                println!("~=~ xdotool detected: {} - {}", window.app_name, window.title);
                                             }
-                                            return Some((app_name, window_title));
+                                            return Some(window);
                                         }
                                     }
                                 }
@@ -736,90 +2212,61 @@ pub mod platform {
         None
     }
 
-    fn try_x11_wmctrl_detection(debug: bool) -> Option<(String, String)> {
-        if let Ok(output) = Command::new("wmctrl")
-            .args(&["-a", "-l"])
-            .output() {
-            
-            if output.status.success() {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                // Find the active window (marked with *)
-                for line in output_str.lines() {
-                    if line.contains("*") {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() >= 4 {
-                            let window_title = parts[3..].join(" ");
-                            
-                            // Get window class using xprop
-                            if let Some(window_id) = parts.get(0) {
-                                if let Ok(xprop_output) = Command::new("xprop")
-                                    .args(&["-id", window_id, "WM_CLASS"])
-                                    .output() {
-                                    
-                                    if xprop_output.status.success() {
-                                        let xprop_str = String::from_utf8_lossy(&xprop_output.stdout);
-                                        if let Some(class_start) = xprop_str.find("\"") {
-                                            if let Some(class_end) = xprop_str[class_start + 1..].find("\"") {
-                                                let app_name = &xprop_str[class_start + 1..class_start + 1 + class_end];
-                                                
-                                                if debug {
-                                                    println!("~=~ wmctrl detected: {} - {}", app_name, window_title);
-                                                }
-                                                return Some((app_name.to_string(), window_title));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// The active window's id, from _NET_ACTIVE_WINDOW on the root window
+    fn x11_active_window_id() -> Option<u64> {
+        let output = run_command(Command::new("xprop").args(["-root", "_NET_ACTIVE_WINDOW"])).ok()?;
+        if !output.status.success() {
+            return None;
         }
-        
-        None
+        parse_xprop_active_window(&String::from_utf8_lossy(&output.stdout))
     }
 
-    fn try_x11_xprop_detection(debug: bool) -> Option<(String, String)> {
-        if let Ok(xprop_output) = Command::new("bash")
-            .args(&["-c", "xprop -id $(xdotool getactivewindow 2>/dev/null) WM_CLASS _NET_WM_NAME 2>/dev/null"])
-            .output() {
-            
-            if xprop_output.status.success() {
-                let output_str = String::from_utf8_lossy(&xprop_output.stdout);
-                let mut app_name = String::new();
-                let mut window_title = String::new();
-                
-                for line in output_str.lines() {
-                    if line.contains("WM_CLASS") {
-                        let parts: Vec<&str> = line.split('"').collect();
-                        if parts.len() >= 3 {
-                            app_name = parts[1].to_string();
-                        }
-                    } else if line.contains("_NET_WM_NAME") {
-                        if let Some(start) = line.find('"') {
-                            if let Some(end) = line.rfind('"') {
-                                if end > start + 1 {
-                                    window_title = line[start+1..end].to_string();
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                if !app_name.is_empty() && !window_title.is_empty() {
-                    if debug {
-                        println!("~=~ xprop detected: {} - {}", app_name, window_title);
-                    }
-                    return Some((app_name, window_title));
-                }
-            }
+    /// wmctrl doesn't mark the active window, so it is looked up by the id
+    /// the window manager publishes
+    fn try_x11_wmctrl_detection(debug: bool) -> Option<WindowInfo> {
+        let id = x11_active_window_id()?;
+        let output = run_command(Command::new("wmctrl").arg("-lx")).ok()?;
+        if !output.status.success() {
+            return None;
         }
-        
-        None
+        let window = parse_wmctrl_lx(&String::from_utf8_lossy(&output.stdout), id)?
+            .with_workspace(x11_current_desktop());
+        if window.title.trim().is_empty() {
+            return None;
+        }
+        if debug {
+            println!("~=~ wmctrl detected: {} - {}", window.app_name, window.title);
+        }
+        Some(window)
+    }
+
+    fn try_x11_xprop_detection(debug: bool) -> Option<WindowInfo> {
+        let id = x11_active_window_id()?;
+        let output = run_command(Command::new("xprop")
+            .args(["-id", &format!("0x{:x}", id), "WM_CLASS", "_NET_WM_NAME", "WM_NAME"])).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        let app_name = xprop_property(&output, "WM_CLASS")?.into_iter().next()?;
+        // _NET_WM_NAME is UTF-8; WM_NAME is the legacy fallback
+        let title = xprop_property(&output, "_NET_WM_NAME")
+            .or_else(|| xprop_property(&output, "WM_NAME"))?
+            .into_iter()
+            .next()?;
+        if app_name.is_empty() || title.trim().is_empty() {
+            return None;
+        }
+        let window = WindowInfo::new(app_name, title)
+            .with_id(id.to_string())
+            .with_workspace(x11_current_desktop());
+        if debug {
+            println!("~=~ xprop detected: {} - {}", window.app_name, window.title);
+        }
+        Some(window)
     }
 
-    fn try_process_scanning_fallback(debug: bool) -> Option<(String, String)> {
+    fn try_process_scanning_fallback(debug: bool) -> Option<WindowInfo> {
         // Last resort: scan for common GUI processes
         let gui_processes = vec![
             "firefox", "chrome", "chromium", "code", "cursor", "vim", "nvim",
@@ -828,9 +2275,8 @@ pub mod platform {
         ];
         
         for process in gui_processes {
-            if let Ok(output) = Command::new("pgrep")
-                .args(&["-f", process])
-                .output() {
+            if let Ok(output) = run_command(Command::new("pgrep")
+                .args(["-f", process])) {
                 
                 if output.status.success() && !output.stdout.is_empty() {
                     let pids = String::from_utf8_lossy(&output.stdout);
@@ -838,7 +2284,7 @@ pub mod platform {
                         if debug {
                             println!("~=~ Process fallback detected: {} (PID: {})", process, pid);
                         }
-                        return Some((process.to_string(), format!("{} window", process)));
+                        return Some(WindowInfo::new(process.to_string(), format!("{} window", process)));
                     }
                 }
             }
@@ -851,8 +2297,37 @@ pub mod platform {
 #[cfg(target_os = "macos")]
 pub mod platform {
     use std::process::Command;
+    use super::{parse_osascript_output, run_command, WindowInfo};
+
+    pub const DETECTION_ENV_VARS: &[&str] = &[];
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Backend {
+        Osascript,
+    }
+
+    impl Backend {
+        pub const ALL: [Backend; 1] = [Backend::Osascript];
+
+        pub fn name(&self) -> &'static str {
+            "osascript"
+        }
+
+        pub fn is_applicable(&self) -> bool {
+            true
+        }
+
+        /// The backend runs a command per check, too slow for sub-second intervals
+        pub fn is_native(&self) -> bool {
+            false
+        }
+
+        pub fn detect(&self, _debug: bool) -> Option<WindowInfo> {
+            get_active_window()
+        }
+    }
 
-    pub fn get_active_window() -> Option<(String, String)> {
+    pub fn get_active_window() -> Option<WindowInfo> {
         // More robust AppleScript that handles errors gracefully
         let script = r#"
         try
@@ -869,7 +2344,7 @@ pub mod platform {
                     set window_name to "Unknown Window"
                 end try
                 
-                return frontApp & "|" & window_name
+                return frontApp & linefeed & window_name
             else
                 return ""
             end if
@@ -878,41 +2353,53 @@ pub mod platform {
         end try
         "#;
 
-        let output = Command::new("osascript")
+        let output = run_command(Command::new("osascript")
             .arg("-e")
-            .arg(script)
-            .output()
+            .arg(script))
             .ok()?;
 
         if !output.status.success() {
             return None;
         }
 
-        let out = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if out.is_empty() {
-            return None;
-        }
-
-        // Parse the output safely
-        let parts: Vec<&str> = out.split('|').collect();
-        if parts.len() >= 2 {
-            let app_name = parts[0].trim().to_string();
-            let window_title = parts[1].trim().to_string();
-            
-            if !app_name.is_empty() {
-                return Some((app_name, window_title));
-            }
-        }
-
-        None
+        parse_osascript_output(&String::from_utf8_lossy(&output.stdout))
     }
 }
 
 #[cfg(target_os = "windows")]
 pub mod platform {
     use std::process::Command;
+    use super::{parse_powershell_output, run_command, WindowInfo};
+
+    pub const DETECTION_ENV_VARS: &[&str] = &[];
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Backend {
+        PowerShell,
+    }
+
+    impl Backend {
+        pub const ALL: [Backend; 1] = [Backend::PowerShell];
+
+        pub fn name(&self) -> &'static str {
+            "powershell"
+        }
 
-    pub fn get_active_window() -> Option<(String, String)> {
+        pub fn is_applicable(&self) -> bool {
+            true
+        }
+
+        /// The backend runs a command per check, too slow for sub-second intervals
+        pub fn is_native(&self) -> bool {
+            false
+        }
+
+        pub fn detect(&self, _debug: bool) -> Option<WindowInfo> {
+            get_active_window()
+        }
+    }
+
+    pub fn get_active_window() -> Option<WindowInfo> {
         // PowerShell script to get both window title and process name
         let script = r#"
         Add-Type @"
@@ -949,42 +2436,837 @@ pub mod platform {
                 $process = Get-Process -Id $processId -ErrorAction SilentlyContinue
                 if ($process) {
                     $appName = $process.ProcessName
-                    return "$appName|$windowTitle"
+                    return "$h`n$appName`n$windowTitle"
                 }
             }
             
-            return "UnknownApp|$windowTitle"
+            return "$h`nUnknownApp`n$windowTitle"
         }
         catch {
             return ""
         }
         "#;
 
-        let output = Command::new("powershell")
-            .args(&["-Command", script])
-            .output()
+        let output = run_command(Command::new("powershell")
+            .args(&["-Command", script]))
             .ok()?;
 
         if !output.status.success() {
             return None;
         }
 
-        let out = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if out.is_empty() {
-            return None;
+        parse_powershell_output(&String::from_utf8_lossy(&output.stdout))
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_output_lines_keeps_the_rest_in_the_last_field() {
+        assert_eq!(split_output_lines("a\nb\nc\n", 3).unwrap(), ["a", "b", "c"]);
+        assert_eq!(split_output_lines("a\r\nb\r\nc\r\n", 3).unwrap(), ["a", "b", "c"]);
+        // Line breaks in the last field become spaces
+        assert_eq!(split_output_lines("a\nline one\r\nline two\n", 2).unwrap(), ["a", "line one line two"]);
+        // Missing fields are empty rather than shifting the others
+        assert_eq!(split_output_lines("a\n", 3).unwrap(), ["a", "", ""]);
+        assert_eq!(split_output_lines("", 2), None);
+        assert_eq!(split_output_lines(" \r\n\n", 2), None);
+    }
+
+    #[test]
+    fn osascript_output_keeps_pipes_quotes_and_spacing() {
+        let parse = |out: &str| parse_osascript_output(out).map(|window| (window.app_name, window.title));
+        let window = |app: &str, title: &str| Some((app.to_string(), title.to_string()));
+        assert_eq!(parse("Safari\nFoo | Bar — Docs\n"), window("Safari", "Foo | Bar — Docs"));
+        assert_eq!(parse("Safari\nHe said \"hi\" | 'there'\n"), window("Safari", "He said \"hi\" | 'there'"));
+        assert_eq!(parse("Notes\nfirst line\nsecond line\n"), window("Notes", "first line second line"));
+        assert_eq!(parse("Safari\r\nCRLF | title\r\n"), window("Safari", "CRLF | title"));
+        assert_eq!(parse("Finder\n"), window("Finder", ""));
+        assert_eq!(parse("Google Chrome\n   \n"), window("Google Chrome", ""));
+        assert_eq!(parse("\nTitle without an app\n"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn powershell_output_keeps_pipes_quotes_and_the_handle() {
+        let window = parse_powershell_output("131844\r\nchrome\r\nFoo | Bar \"quoted\" — Docs\r\n").unwrap();
+        assert_eq!(window.app_name, "chrome");
+        assert_eq!(window.title, "Foo | Bar \"quoted\" — Docs");
+        assert_eq!(window.window_id.as_deref(), Some("131844"));
+
+        let window = parse_powershell_output("7\r\nnotepad\r\nline one\r\nline two\r\n").unwrap();
+        assert_eq!(window.title, "line one line two");
+        let window = parse_powershell_output("\r\nexplorer\r\n\r\n").unwrap();
+        assert_eq!((window.app_name.as_str(), window.title.as_str(), window.window_id), ("explorer", "", None));
+        assert!(parse_powershell_output("1\r\nUnknownApp\r\ntitle\r\n").is_none());
+        assert!(parse_powershell_output("1\r\n\r\ntitle\r\n").is_none());
+        assert!(parse_powershell_output("\r\n").is_none());
+    }
+
+    fn minute(n: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-21T09:00:00Z").unwrap().with_timezone(&Utc) + chrono::Duration::minutes(n)
+    }
+
+    fn started(tracker: FocusTracker) -> FocusTracker {
+        let mut tracker = tracker;
+        tracker.set_session_name("test".to_string());
+        tracker.start_tracking();
+        tracker
+    }
+
+    /// Play `windows` (window, minute it became active) and close the last
+    /// one at `end`; returns the rows and the switches
+    fn play(tracker: &mut FocusTracker, windows: Vec<(WindowInfo, i64)>, end: i64) -> (Vec<FocusSession>, Vec<ContextSwitch>) {
+        for (window, at) in windows {
+            tracker.update_active_window_at(window, minute(at));
         }
+        tracker.suspend(minute(end));
+        (tracker.take_completed_sessions(), tracker.take_context_switches())
+    }
 
-        // Parse the output safely
-        let parts: Vec<&str> = out.split('|').collect();
-        if parts.len() >= 2 {
-            let app_name = parts[0].trim().to_string();
-            let window_title = parts[1].trim().to_string();
-            
-            if !app_name.is_empty() && app_name != "UnknownApp" {
-                return Some((app_name, window_title));
+    /// (app, minutes, focus) of each row
+    fn summary(rows: &[FocusSession]) -> Vec<(String, u64, bool)> {
+        rows.iter().map(|row| (row.app_name.clone(), row.duration.as_secs() / 60, row.is_focus_app)).collect()
+    }
+
+    fn switches(switches: &[ContextSwitch]) -> Vec<(String, String)> {
+        switches.iter().map(|switch| (switch.from_app.clone(), switch.to_app.clone())).collect()
+    }
+
+    #[test]
+    fn terminal_title_rules_track_the_program_in_the_title() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_terminal_rule("fd-term".to_string(), r"^(?P<program>[\w.-]+)(?: [^—]*)?(?: — (?P<path>.+))?$").unwrap();
+        tracker.add_focus_app("nvim".to_string());
+        let mut tracker = started(tracker);
+        let (rows, switched) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-term", "nvim src/main.rs — ~/proj/api"), 0),
+            // Another file in the same program is the same row
+            (WindowInfo::new("fd-term", "nvim src/lib.rs — ~/proj/api"), 5),
+            (WindowInfo::new("fd-term", "cargo test — ~/proj/api"), 10),
+            (WindowInfo::new("fd-term", "nvim src/lib.rs — ~/proj/api"), 15),
+            // A title the rule doesn't match stays the terminal
+            (WindowInfo::new("fd-term", "~/proj/api"), 30),
+            // A terminal without a rule is left alone
+            (WindowInfo::new("fd-other-term", "nvim notes.md — ~"), 35),
+        ], 40);
+
+        assert_eq!(summary(&rows), [
+            ("nvim".to_string(), 10, true),
+            ("cargo".to_string(), 5, false),
+            ("nvim".to_string(), 15, true),
+            ("fd-term".to_string(), 5, false),
+            ("fd-other-term".to_string(), 5, false),
+        ]);
+        assert_eq!(rows[0].window_title, "nvim src/lib.rs — ~/proj/api");
+        assert_eq!(switches(&switched), [
+            ("nvim".to_string(), "cargo".to_string()),
+            ("cargo".to_string(), "nvim".to_string()),
+            ("nvim".to_string(), "fd-term".to_string()),
+            ("fd-term".to_string(), "fd-other-term".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn invalid_terminal_title_rules_are_refused() {
+        let mut tracker = FocusTracker::new();
+        assert!(tracker.add_terminal_rule("fd-term".to_string(), "(?P<program>").is_err());
+        assert_eq!(tracker.effective_app_name("fd-term", "nvim — ~"), "fd-term");
+    }
+
+    #[test]
+    fn buffers_stay_bounded_through_100k_sessions() {
+        const ROWS: usize = 100_000;
+        const LIMIT: usize = 500;
+        let mut tracker = FocusTracker::new();
+        tracker.set_debug_mode(false);
+        tracker.set_buffer_limit(LIMIT);
+        let mut tracker = started(tracker);
+
+        let apps = ["fd-editor", "fd-browser", "fd-term"];
+        let (mut sessions, mut switches, mut flushes, mut peak) = (0, 0, 0, 0);
+        let start = minute(0);
+        for index in 0..=ROWS {
+            let window = WindowInfo::new(apps[index % apps.len()], "window");
+            tracker.update_active_window_at(window, start + chrono::Duration::seconds(index as i64 * 30));
+            peak = peak.max(tracker.buffered_rows());
+            // What the save thread does when the tracker asks for an early flush
+            if tracker.needs_flush() {
+                sessions += tracker.take_completed_sessions().len();
+                switches += tracker.take_context_switches().len();
+                flushes += 1;
+                // Taken, not cloned: nothing is left allocated behind
+                assert_eq!((tracker.completed_sessions.capacity(), tracker.context_switches.capacity()), (0, 0));
             }
         }
+        sessions += tracker.take_completed_sessions().len();
+        switches += tracker.take_context_switches().len();
 
-        None
+        assert_eq!((sessions, switches), (ROWS, ROWS));
+        // One window change adds a row and a switch, so at most one over the limit
+        assert!(peak <= LIMIT + 1, "peak {}", peak);
+        assert_eq!(flushes, 2 * ROWS / LIMIT);
+    }
+
+    /// (title, minutes, focus) of each row
+    fn titles(rows: &[FocusSession]) -> Vec<(String, u64, bool)> {
+        rows.iter().map(|row| (row.window_title.clone(), row.duration.as_secs() / 60, row.is_focus_app)).collect()
+    }
+
+    fn browser(title: &str, id: &str) -> WindowInfo {
+        WindowInfo::new("fd-firefox", title).with_id(id)
+    }
+
+    #[test]
+    fn browser_windows_switch_without_phantom_tab_changes() {
+        let mut tracker = started(FocusTracker::new());
+        let (rows, switched) = play(&mut tracker, vec![
+            (browser("Issue #12 · GitHub", "w1"), 0),
+            // The same window seen again is no change
+            (browser("Issue #12 · GitHub", "w1"), 1),
+            (browser("New Tab", "w2"), 5),
+            // Two windows on the same title are still two windows
+            (browser("Issue #12 · GitHub", "w2"), 6),
+            (browser("Issue #12 · GitHub", "w1"), 10),
+            // A tab change inside one window
+            (browser("serde - Rust", "w1"), 12),
+        ], 20);
+
+        assert_eq!(titles(&rows), [
+            ("Issue #12 · GitHub".to_string(), 5, false),
+            ("New Tab".to_string(), 1, false),
+            ("Issue #12 · GitHub".to_string(), 4, false),
+            ("Issue #12 · GitHub".to_string(), 2, false),
+            ("serde - Rust".to_string(), 8, false),
+        ]);
+        assert_eq!(switched.len(), 4);
+        assert!(switched.iter().all(|switch| switch.from_app == "fd-firefox" && switch.to_app == "fd-firefox"));
+    }
+
+    #[test]
+    fn browsers_without_window_ids_only_split_on_titles() {
+        let mut tracker = started(FocusTracker::new());
+        let (rows, switched) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-firefox", "Issue #12 · GitHub"), 0),
+            (WindowInfo::new("fd-firefox", "Issue #12 · GitHub"), 5),
+            (WindowInfo::new("fd-firefox", "serde - Rust"), 10),
+        ], 15);
+        assert_eq!(titles(&rows), [("Issue #12 · GitHub".to_string(), 10, false), ("serde - Rust".to_string(), 5, false)]);
+        assert_eq!(switched.len(), 1);
+    }
+
+    #[test]
+    fn picture_in_picture_inherits_the_tab_it_came_from() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_focus_site("domain:youtube.com".to_string());
+        let mut tracker = started(tracker);
+        let (rows, switched) = play(&mut tracker, vec![
+            (browser("Rust lecture - YouTube", "w1"), 0),
+            // Popping the video out carries on the same tab
+            (browser("Picture-in-Picture", "pip"), 10),
+            (browser("Rust lecture - YouTube", "w1"), 20),
+            (browser("Inbox - Mail", "w1"), 25),
+            // Back to the PiP window: its tab is remembered by window id
+            (browser("Picture-in-Picture", "pip"), 30),
+        ], 40);
+
+        assert_eq!(titles(&rows), [
+            ("Rust lecture - YouTube".to_string(), 25, true),
+            ("Inbox - Mail".to_string(), 5, false),
+            ("Rust lecture - YouTube".to_string(), 10, true),
+        ]);
+        assert_eq!(switched.len(), 2);
+    }
+
+    #[test]
+    fn picture_in_picture_without_a_known_tab_keeps_its_title() {
+        let mut tracker = FocusTracker::new();
+        tracker.set_pip_titles(vec!["Bild im Bild".to_string()]);
+        let mut tracker = started(tracker);
+        assert!(tracker.is_pip_title(" bild im bild "));
+        assert!(!tracker.is_pip_title("Picture-in-Picture"));
+        let (rows, _) = play(&mut tracker, vec![
+            (browser("Bild im Bild", "pip"), 0),
+            // Only browsers have PiP windows
+            (WindowInfo::new("fd-player", "Bild im Bild").with_id("v1"), 5),
+        ], 10);
+        assert_eq!(titles(&rows), [("Bild im Bild".to_string(), 5, false), ("Bild im Bild".to_string(), 5, false)]);
+        assert_eq!(rows[1].app_name, "fd-player");
+    }
+
+    /// One browsing trace: two GitHub tabs, docs, back to GitHub, the editor
+    fn tab_trace(mode: BrowserTabTracking) -> (Vec<FocusSession>, Vec<ContextSwitch>) {
+        let mut tracker = FocusTracker::new();
+        tracker.set_browser_tab_tracking(mode);
+        let mut tracker = started(tracker);
+        play(&mut tracker, vec![
+            (WindowInfo::new("fd-firefox", "Issue #1 · GitHub"), 0),
+            (WindowInfo::new("fd-firefox", "Pull request #2 · GitHub"), 2),
+            (WindowInfo::new("fd-firefox", "serde - Rust - docs.rs"), 5),
+            (WindowInfo::new("fd-firefox", "Issue #1 · GitHub"), 9),
+            (WindowInfo::new("fd-editor", "main.rs"), 12),
+        ], 15)
+    }
+
+    #[test]
+    fn full_tab_tracking_splits_every_tab() {
+        let (rows, switched) = tab_trace(BrowserTabTracking::Full);
+        assert_eq!(titles(&rows).iter().map(|row| row.1).collect::<Vec<_>>(), [2, 3, 4, 3, 3]);
+        assert_eq!(rows[1].domain.as_deref(), Some("Pull request #2 · GitHub"));
+        assert_eq!(switched.len(), 4);
+    }
+
+    #[test]
+    fn domain_tab_tracking_joins_tabs_of_one_site() {
+        let (rows, switched) = tab_trace(BrowserTabTracking::Domain);
+        let domains: Vec<(Option<&str>, u64)> = rows.iter().map(|row| (row.domain.as_deref(), row.duration.as_secs() / 60)).collect();
+        assert_eq!(domains, [(Some("github"), 5), (Some("docs.rs"), 4), (Some("github"), 3), (None, 3)]);
+        // The row carries the latest title of its site
+        assert_eq!(rows[0].window_title, "Pull request #2 · GitHub");
+        assert_eq!(switched.len(), 3);
+    }
+
+    #[test]
+    fn tab_tracking_off_keeps_the_browser_one_app() {
+        let (rows, switched) = tab_trace(BrowserTabTracking::Off);
+        assert_eq!(summary(&rows), [("fd-firefox".to_string(), 12, false), ("fd-editor".to_string(), 3, false)]);
+        assert!(rows.iter().all(|row| row.domain.is_none()));
+        assert_eq!(switches(&switched), [("fd-firefox".to_string(), "fd-editor".to_string())]);
+    }
+
+    #[test]
+    fn tab_tracking_modes_parse_by_name() {
+        for mode in [BrowserTabTracking::Full, BrowserTabTracking::Domain, BrowserTabTracking::Off] {
+            assert_eq!(BrowserTabTracking::parse(mode.label()), Some(mode));
+        }
+        assert_eq!(BrowserTabTracking::parse(" Domain "), Some(BrowserTabTracking::Domain));
+        assert_eq!(BrowserTabTracking::parse("tabs"), None);
+        assert_eq!(BrowserTabTracking::default(), BrowserTabTracking::Full);
+    }
+
+    #[test]
+    fn focus_lists_synced_mid_trace_apply_from_the_next_window() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_focus_app("fd-editor".to_string());
+        let mut tracker = started(tracker);
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), minute(0));
+        tracker.update_active_window_at(WindowInfo::new("fd-chat", "#team"), minute(5));
+
+        // `focusapp add fd-chat` lands while fd-chat has focus
+        let apps = vec!["fd-editor".to_string(), "fd-chat".to_string()];
+        let sites = vec!["domain:github.com".to_string()];
+        assert_eq!(tracker.sync_focus_lists(&apps, &sites), ["+app fd-chat", "+site domain:github.com"]);
+        assert!(tracker.sync_focus_lists(&apps, &sites).is_empty());
+
+        let (rows, _) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-editor", "main.rs"), 10),
+            (WindowInfo::new("fd-chat", "#team"), 15),
+            (WindowInfo::new("fd-firefox", "Issue #3 · GitHub"), 20),
+        ], 25);
+        // The open row keeps the class it started with
+        assert_eq!(summary(&rows), [
+            ("fd-editor".to_string(), 5, true),
+            ("fd-chat".to_string(), 5, false),
+            ("fd-editor".to_string(), 5, true),
+            ("fd-chat".to_string(), 5, true),
+            ("fd-firefox".to_string(), 5, true),
+        ]);
+
+        // Removals apply the same way
+        assert_eq!(tracker.sync_focus_lists(&["fd-chat".to_string()], &[]), ["-app fd-editor", "-site domain:github.com"]);
+        let (rows, _) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-editor", "main.rs"), 30),
+            (WindowInfo::new("fd-firefox", "Issue #3 · GitHub"), 35),
+            (WindowInfo::new("fd-chat", "#team"), 40),
+        ], 45);
+        assert_eq!(summary(&rows).iter().map(|row| row.2).collect::<Vec<_>>(), [false, false, true]);
+    }
+
+    #[test]
+    fn site_apps_split_and_classify_by_window_title() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_focus_site("title:engineering wiki".to_string());
+        tracker.set_site_apps(vec!["fd-notes".to_string()]);
+        let mut tracker = started(tracker);
+        let (rows, switched) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-notes", "Notion — Engineering Wiki"), 0),
+            (WindowInfo::new("fd-notes", "Notion — Meal planning"), 10),
+            (WindowInfo::new("fd-notes", "Notion — Engineering Wiki"), 15),
+            // Other apps' titles are neither matched nor split on
+            (WindowInfo::new("fd-chat", "Engineering Wiki"), 20),
+            (WindowInfo::new("fd-chat", "#random"), 25),
+        ], 30);
+        assert_eq!(titles(&rows), [
+            ("Notion — Engineering Wiki".to_string(), 10, true),
+            ("Notion — Meal planning".to_string(), 5, false),
+            ("Notion — Engineering Wiki".to_string(), 5, true),
+            ("#random".to_string(), 10, false),
+        ]);
+        assert_eq!(switched.len(), 3);
+        assert!(rows.iter().all(|row| row.domain.is_none()));
+    }
+
+    /// A title as xprop prints it: quoted, with `"` and `\` escaped, and in
+    /// `octal` mode every non-ASCII byte as \ooo like a C locale does
+    fn xprop_quoted(title: &str, octal: bool) -> String {
+        let mut quoted = String::from("\"");
+        for c in title.chars() {
+            match c {
+                '"' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '\t' => quoted.push_str("\\t"),
+                c if octal && !c.is_ascii() => {
+                    let mut buffer = [0; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        quoted.push_str(&format!("\\{:03o}", byte));
+                    }
+                }
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
     }
-} 
\ No newline at end of file
+
+    /// Titles built from the characters that broke the old parsers
+    fn awkward_titles(count: usize) -> Vec<String> {
+        const PIECES: [&str; 16] = ["a", "Z", "0", "42 ", " ", "\t", "\"", "\\", "*", "#", "é", "日本", "🦀", ", ", " = ", "0x1f"];
+        let mut rng = crate::demo::Rng::new(2501);
+        (0..count)
+            .map(|_| (0..rng.range(0, 12)).map(|_| PIECES[rng.range(0, PIECES.len() as u64 - 1) as usize]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn xprop_strings_read_back_any_title() {
+        for title in awkward_titles(300) {
+            for octal in [false, true] {
+                let quoted = xprop_quoted(&title, octal);
+                assert_eq!(parse_xprop_strings(&quoted), std::slice::from_ref(&title), "{}", quoted);
+                let line = format!("WM_CLASS(STRING) = \"fd-editor\", {}\n_NET_WM_NAME(UTF8_STRING) = {}\n", xprop_quoted("Fd-Editor", false), quoted);
+                assert_eq!(xprop_property(&line, "_NET_WM_NAME"), Some(vec![title.clone()]), "{}", line);
+                assert_eq!(xprop_property(&line, "WM_CLASS"), Some(vec!["fd-editor".to_string(), "Fd-Editor".to_string()]));
+            }
+        }
+    }
+
+    #[test]
+    fn xprop_fixtures_parse() {
+        assert_eq!(parse_xprop_strings(r#""say \"hi\"", "C:\\dir""#), ["say \"hi\"", "C:\\dir"]);
+        assert_eq!(parse_xprop_strings(r#""caf\303\251 \342\200\224 menu""#), ["café — menu"]);
+        assert_eq!(parse_xprop_strings("1, 0x3a00004"), ["1", "0x3a00004"]);
+        assert_eq!(parse_xprop_strings(r#""""#), [""]);
+        assert_eq!(xprop_property("WM_NAME:  not found.\n", "WM_NAME"), None);
+        assert_eq!(xprop_property("_NET_WM_NAME(UTF8_STRING) = \"a\"\n", "WM_NAME"), None);
+
+        assert_eq!(parse_xprop_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00004\n"), Some(0x3a00004));
+        assert_eq!(parse_xprop_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"), None);
+        assert_eq!(parse_xprop_active_window("_NET_ACTIVE_WINDOW:  no such atom on any window.\n"), None);
+    }
+
+    #[test]
+    fn wmctrl_lines_keep_titles_exactly() {
+        for (index, title) in awkward_titles(300).into_iter().enumerate() {
+            let id = 0x3a00000 + index as u64;
+            let output = format!(
+                "0x02000003 -1 desktop_window.Nautilus  fd-host Desktop\n\
+                 0x{:08x}  0 {:<20} fd-host {}\n\
+                 0x04400007  1 N/A                   N/A \n",
+                id, "fd-term.Fd-term", title,
+            );
+            let window = parse_wmctrl_lx(&output, id).unwrap_or_else(|| panic!("{:?}", output));
+            assert_eq!((window.app_name.as_str(), window.title.as_str()), ("fd-term", title.as_str()), "{:?}", output);
+            assert_eq!(window.window_id, Some(id.to_string()));
+        }
+    }
+
+    #[test]
+    fn wmctrl_fixtures_parse() {
+        let output = "\
+0x02000003 -1 desktop_window.Nautilus  fd-host Desktop
+0x03a00004  0 org.gnome.Nautilus.Org.gnome.Nautilus  fd-host Home
+0x04400007  1 N/A                   N/A 
+0x05000001  0 fd-chat.Fd-chat       fd-host 3 new messages * \"team\"
+";
+        assert_eq!(parse_wmctrl_lx(output, 0x03a00004).unwrap().app_name, "org.gnome.Nautilus");
+        assert_eq!(parse_wmctrl_lx(output, 0x05000001).unwrap().title, "3 new messages * \"team\"");
+        assert_eq!(parse_wmctrl_lx(output, 0x04400007), None);
+        assert_eq!(parse_wmctrl_lx(output, 0x01), None);
+        assert_eq!(parse_wmctrl_lx("", 0x03a00004), None);
+    }
+
+    #[test]
+    fn xdotool_titles_keep_everything_but_the_line_end() {
+        for title in awkward_titles(300) {
+            let expected = (!title.trim().is_empty()).then(|| title.clone());
+            assert_eq!(parse_xdotool_title(&format!("{}\n", title)), expected);
+            assert_eq!(parse_xdotool_title(&format!("{}\r\n", title)), expected);
+        }
+        assert_eq!(parse_xdotool_title("line one\nline two\n").as_deref(), Some("line one line two"));
+        assert_eq!(parse_xdotool_title("\n"), None);
+    }
+
+    /// Each second from `start`, `backend` answers as `results` says
+    fn run(selector: &mut BackendSelector, backend: &'static str, results: &[bool], start: DateTime<Utc>) -> DateTime<Utc> {
+        let mut now = start;
+        for detected in results {
+            selector.record(backend, *detected, now);
+            now += chrono::Duration::seconds(1);
+        }
+        now
+    }
+
+    #[test]
+    fn backend_health_is_a_rolling_success_rate() {
+        let mut health = BackendHealth::default();
+        assert_eq!(health.success_rate(), None);
+        for _ in 0..BACKEND_HEALTH_SAMPLES {
+            health.record(false);
+        }
+        assert_eq!(health.success_rate(), Some(0.0));
+        for _ in 0..BACKEND_HEALTH_SAMPLES / 2 {
+            health.record(true);
+        }
+        // The oldest failures dropped out
+        assert_eq!(health.success_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn failing_backend_fails_over_to_the_healthiest_after_a_minute() {
+        let start = minute(0);
+        let mut selector = BackendSelector::new(None, false).quiet();
+        let now = run(&mut selector, "fake-a", &[true; 10], start);
+        assert_eq!(selector.active(), Some("fake-a"));
+        // Other backends only answer when asked after the active one failed
+        selector.record("fake-b", true, now);
+        selector.record("fake-c", false, now);
+        assert_eq!(selector.order(&["fake-c", "fake-b", "fake-a"]), ["fake-a", "fake-c", "fake-b"]);
+
+        // Eleven failures take fake-a below half at 10 of 21
+        let failing_from = run(&mut selector, "fake-a", &[false; 10], now);
+        let now = run(&mut selector, "fake-a", &[false], failing_from);
+        assert!(selector.success_rate("fake-a").unwrap() < FAILOVER_SUCCESS_RATE);
+        assert!(!selector.failover_due(now));
+        let due_at = failing_from + chrono::Duration::from_std(FAILOVER_AFTER).unwrap();
+        assert!(!selector.failover_due(due_at - chrono::Duration::seconds(1)));
+        assert!(selector.failover_due(due_at));
+
+        // fake-c found a window this time, but fake-b has the better record
+        let failover = selector.fail_over(&[("fake-a", false), ("fake-b", true), ("fake-c", true)], due_at).unwrap();
+        assert_eq!((failover.from, failover.to), ("fake-a", "fake-b"));
+        assert!((failover.success_rate - 10.0 / 22.0).abs() < 1e-9);
+        assert_eq!((selector.active(), selector.failovers()), (Some("fake-b"), 1));
+        assert!(!selector.failover_due(due_at + chrono::Duration::hours(1)));
+    }
+
+    #[test]
+    fn recovering_backend_is_kept() {
+        let mut selector = BackendSelector::new(None, false).quiet();
+        let now = run(&mut selector, "fake-a", &[true, false, false], minute(0));
+        // Below half, then back above it before the minute is up
+        let now = run(&mut selector, "fake-a", &[true, true], now);
+        assert!(!selector.failover_due(now + chrono::Duration::minutes(5)));
+
+        // Without another backend that works it stays, for another minute
+        let now = run(&mut selector, "fake-a", &[false; 6], now);
+        let due_at = now + chrono::Duration::minutes(1);
+        assert!(selector.failover_due(due_at));
+        assert_eq!(selector.fail_over(&[("fake-a", false), ("fake-b", false)], due_at), None);
+        assert_eq!(selector.active(), Some("fake-a"));
+        assert!(!selector.failover_due(due_at + chrono::Duration::seconds(59)));
+        assert!(selector.failover_due(due_at + chrono::Duration::seconds(60)));
+    }
+
+    #[test]
+    fn pinned_backends_fail_over_only_with_fallback() {
+        let mut pinned = BackendSelector::new(Some("fake-a"), false).quiet();
+        let now = run(&mut pinned, "fake-a", &[false; 5], minute(0));
+        assert_eq!(pinned.order(&["fake-b", "fake-a"]), ["fake-a"]);
+        assert!(!pinned.failover_due(now + chrono::Duration::hours(1)));
+
+        let mut fallback = BackendSelector::new(Some("fake-a"), true).quiet();
+        let now = run(&mut fallback, "fake-a", &[false; 5], minute(0));
+        let due_at = now + chrono::Duration::minutes(1);
+        assert!(fallback.failover_due(due_at));
+        assert_eq!(fallback.fail_over(&[("fake-b", true)], due_at).map(|failover| failover.to), Some("fake-b"));
+        // The pin went with the backend
+        assert_eq!(fallback.order(&["fake-a", "fake-b"]), ["fake-b", "fake-a"]);
+    }
+
+    #[test]
+    fn process_scan_gives_way_to_real_backends() {
+        let mut selector = BackendSelector::new(None, false).quiet();
+        selector.record(PROCESS_SCAN_BACKEND, true, minute(0));
+        assert_eq!(selector.active(), Some(PROCESS_SCAN_BACKEND));
+        assert_eq!(selector.order(&["fake-a", PROCESS_SCAN_BACKEND]), ["fake-a", PROCESS_SCAN_BACKEND]);
+        selector.record("fake-a", true, minute(1));
+        assert_eq!(selector.active(), Some("fake-a"));
+        assert_eq!(selector.failovers(), 0);
+    }
+
+    #[test]
+    fn saving_in_an_editor_is_no_title_change() {
+        let mut tracker = FocusTracker::new();
+        tracker.set_site_apps(vec!["fd-editor".to_string()]);
+        let mut tracker = started(tracker);
+        let (rows, switched) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-editor", "main.rs - fd-editor"), 0),
+            (WindowInfo::new("fd-editor", "● main.rs - fd-editor"), 2),
+            (WindowInfo::new("fd-editor", "main.rs - fd-editor"), 4),
+            (WindowInfo::new("fd-editor", "*main.rs - fd-editor"), 6),
+            (WindowInfo::new("fd-editor", "lib.rs - fd-editor"), 10),
+            (WindowInfo::new("fd-editor", "● lib.rs - fd-editor"), 12),
+        ], 15);
+        // The stored title isn't rewritten by the marks either
+        assert_eq!(titles(&rows), [
+            ("main.rs - fd-editor".to_string(), 10, false),
+            ("lib.rs - fd-editor".to_string(), 5, false),
+        ]);
+        assert_eq!(switched.len(), 1);
+
+        // With the markers turned off every mark is an edit again
+        let mut tracker = FocusTracker::new();
+        tracker.set_site_apps(vec!["fd-editor".to_string()]);
+        tracker.set_title_noise_markers(&[]);
+        let mut tracker = started(tracker);
+        let (rows, _) = play(&mut tracker, vec![
+            (WindowInfo::new("fd-editor", "main.rs - fd-editor"), 0),
+            (WindowInfo::new("fd-editor", "● main.rs - fd-editor"), 2),
+        ], 4);
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn loading_pages_and_notification_counts_are_no_tab_change() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_focus_site("title:fd docs".to_string());
+        let mut tracker = started(tracker);
+        let (rows, switched) = play(&mut tracker, vec![
+            // The placeholder becomes the page it was loading, focus and all
+            (browser("Loading… — Mozilla Firefox", "w1"), 0),
+            (browser("FD Docs — Mozilla Firefox", "w1"), 1),
+            (browser("Loading… — Mozilla Firefox", "w1"), 5),
+            (browser("FD Docs — Mozilla Firefox", "w1"), 6),
+            (browser("(3) Inbox — Mozilla Firefox", "w1"), 10),
+            (browser("(4) Inbox — Mozilla Firefox", "w1"), 12),
+            (browser("Inbox — Mozilla Firefox", "w1"), 14),
+        ], 20);
+        assert_eq!(titles(&rows), [
+            ("FD Docs — Mozilla Firefox".to_string(), 10, true),
+            ("(3) Inbox — Mozilla Firefox".to_string(), 10, false),
+        ]);
+        assert_eq!(rows[0].domain.as_deref(), Some("FD Docs — Mozilla Firefox"));
+        assert_eq!(switches(&switched), [("fd-firefox".to_string(), "fd-firefox".to_string())]);
+    }
+
+    #[test]
+    fn terminal_titles_are_parsed_without_noise() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_terminal_rule("fd-term".to_string(), r"^(?P<program>[\w.-]+)(?: [^—]*)?(?: — (?P<path>.+))?$").unwrap();
+        assert_eq!(tracker.effective_app_name("fd-term", "● nvim main.rs — ~/proj"), "nvim");
+        assert_eq!(tracker.effective_app_name("fd-term", "(2) cargo build — ~/proj"), "cargo");
+        tracker.set_title_noise_markers(&[]);
+        assert_eq!(tracker.effective_app_name("fd-term", "● nvim main.rs — ~/proj"), "fd-term");
+    }
+
+    fn open_row(start: DateTime<Utc>, stored_seconds: u64) -> FocusSession {
+        FocusSession {
+            start_time: start,
+            end_time: None,
+            app_name: "fd-editor".to_string(),
+            window_title: "main.rs".to_string(),
+            domain: None,
+            duration: Duration::from_secs(stored_seconds),
+            is_focus_app: true,
+            session_name: "test".to_string(),
+            activity: None,
+            passive: None,
+            workspace: None,
+            power: None,
+            open_end: OpenEnd::Closed,
+            manual: false,
+            focus_rule: None,
+            co_focus: None,
+        }
+    }
+
+    /// (end, minutes, open_end) once settled
+    fn settled(mut row: FocusSession, next_start: Option<DateTime<Utc>>, now: DateTime<Utc>) -> (DateTime<Utc>, u64, OpenEnd) {
+        row.settle_open_end_within(next_start, now, 12);
+        (row.end_time.unwrap(), row.duration.as_secs() / 60, row.open_end)
+    }
+
+    #[test]
+    fn open_rows_end_at_the_first_of_next_row_now_and_the_cap() {
+        let start = minute(0);
+        // The last row of a crashed daemon runs to now, whatever it last saved
+        assert_eq!(settled(open_row(start, 0), None, minute(40)), (minute(40), 40, OpenEnd::Open));
+        // The next row on this machine ends it
+        assert_eq!(settled(open_row(start, 0), Some(minute(25)), minute(40)), (minute(25), 25, OpenEnd::Open));
+        assert_eq!(settled(open_row(start, 0), Some(minute(90)), minute(40)), (minute(40), 40, OpenEnd::Open));
+        // Never past max_session_hours
+        assert_eq!(settled(open_row(start, 0), None, minute(20 * 60)), (minute(12 * 60), 12 * 60, OpenEnd::Open));
+        // Nor before its own start
+        assert_eq!(settled(open_row(start, 300), Some(minute(-5)), minute(40)), (start, 0, OpenEnd::Open));
+
+        // Open for over a day, its length is a guess
+        let (end, minutes, open_end) = settled(open_row(start, 0), Some(minute(30)), minute(24 * 60 + 1));
+        assert_eq!((end, minutes, open_end), (minute(30), 30, OpenEnd::Corrupt));
+        assert_eq!(settled(open_row(start, 0), None, minute(24 * 60)).2, OpenEnd::Open);
+
+        // Rows with an end are left alone
+        let mut closed = open_row(start, 600);
+        closed.end_time = Some(minute(10));
+        closed.settle_open_end_within(None, minute(40), 12);
+        assert_eq!((closed.duration.as_secs(), closed.open_end), (600, OpenEnd::Closed));
+        assert!(!closed.is_open_ended());
+
+        let mut corrupt = open_row(start, 0);
+        corrupt.settle_open_end_within(None, minute(48 * 60), 12);
+        assert!(corrupt.is_open_ended() && corrupt.is_corrupt());
+    }
+
+    #[test]
+    fn a_power_change_splits_the_open_row_without_a_switch() {
+        let mut tracker = started(FocusTracker::new());
+        tracker.set_power_source_at(Some(PowerSource::Ac), minute(0));
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), minute(0));
+        // Sampling the same source again changes nothing
+        tracker.set_power_source_at(Some(PowerSource::Ac), minute(10));
+        tracker.set_power_source_at(Some(PowerSource::Battery), minute(20));
+        let (rows, switched) = play(&mut tracker, vec![(WindowInfo::new("fd-chat", "#team"), 30)], 45);
+
+        let powers: Vec<(String, u64, Option<PowerSource>)> = rows.iter()
+            .map(|row| (row.app_name.clone(), row.duration.as_secs() / 60, row.power))
+            .collect();
+        assert_eq!(powers, [
+            ("fd-editor".to_string(), 20, Some(PowerSource::Ac)),
+            ("fd-editor".to_string(), 10, Some(PowerSource::Battery)),
+            ("fd-chat".to_string(), 15, Some(PowerSource::Battery)),
+        ]);
+        assert_eq!(switches(&switched), [("fd-editor".to_string(), "fd-chat".to_string())]);
+    }
+
+    #[test]
+    fn a_suspend_ends_the_row_and_the_window_after_waking_starts_anew() {
+        let mut tracker = started(FocusTracker::new());
+        tracker.update_active_window_at(WindowInfo::new("fd-editor", "main.rs"), minute(0));
+        tracker.update_active_window_at(WindowInfo::new("fd-chat", "#team"), minute(10));
+        // The last check before a two-hour clock jump
+        tracker.suspend(minute(20));
+        let (rows, switched) = play(&mut tracker, vec![(WindowInfo::new("fd-chat", "#team"), 140)], 150);
+        let spans: Vec<(String, DateTime<Utc>, u64)> = rows.iter().map(|row| (row.app_name.clone(), row.start_time, row.duration.as_secs() / 60)).collect();
+        assert_eq!(spans, [
+            ("fd-editor".to_string(), minute(0), 10),
+            ("fd-chat".to_string(), minute(10), 10),
+            ("fd-chat".to_string(), minute(140), 10),
+        ]);
+        assert_eq!(rows.iter().map(|row| row.duration).sum::<Duration>(), Duration::from_secs(30 * 60));
+        assert_eq!(switches(&switched), [("fd-editor".to_string(), "fd-chat".to_string())]);
+    }
+
+    #[test]
+    fn sub_second_intervals_need_a_native_backend() {
+        let spawning = platform::Backend::ALL[0].name();
+        assert!(check_interval(MIN_SPAWNING_INTERVAL_MS, Some(spawning)).is_ok());
+        assert!(check_interval(5000, None).is_ok());
+
+        let error = check_interval(250, Some(spawning)).unwrap_err();
+        assert!(error.starts_with("tracking_interval_ms 250 is below 1000"), "{}", error);
+        assert!(error.ends_with(&format!("; {} runs a command on every check here", spawning)), "{}", error);
+        let error = check_interval(250, Some("no-such-backend")).unwrap_err();
+        assert!(error.ends_with("; the backends run a command on every check here"), "{}", error);
+        // None of the backends in this tree is native yet
+        assert!(check_interval(999, None).is_err());
+    }
+
+    /// The daemon's loop at `interval_ms` over `script` (window, from tick),
+    /// one check every 100 ms, until `end_tick`; returns the rows
+    fn tick(interval_ms: u64, script: &[(&str, u32)], end_tick: u32) -> Vec<FocusSession> {
+        let at = |tick: u32| minute(0) + chrono::Duration::milliseconds(tick as i64 * 100);
+        let mut tracker = started(FocusTracker::new());
+        let mut debouncer = WindowDebouncer::for_interval(interval_ms);
+        let mut last_window: Option<WindowInfo> = None;
+        for tick in 0..end_tick {
+            let app = script.iter().rev().find(|(_, from)| *from <= tick).unwrap().0;
+            if let Some((window, since)) = debouncer.observe(last_window.as_ref(), WindowInfo::new(app, app), at(tick)) {
+                tracker.update_active_window_at(window.clone(), since);
+                last_window = Some(window);
+            }
+        }
+        tracker.suspend(at(end_tick));
+        tracker.take_completed_sessions()
+    }
+
+    #[test]
+    fn an_alt_tab_at_100ms_ticks_is_one_switch_dated_to_the_change() {
+        // The switcher is up for two ticks between the editor and the terminal
+        let script = [("fd-editor", 0), ("fd-switcher", 50), ("fd-term", 52)];
+        let rows = tick(100, &script, 100);
+        let at = |tick: i64| minute(0) + chrono::Duration::milliseconds(tick * 100);
+        let rows: Vec<_> = rows.iter()
+            .map(|row| (row.app_name.as_str(), row.start_time, row.end_time))
+            .collect();
+        assert_eq!(rows, [
+            ("fd-editor", at(0), Some(at(52))),
+            ("fd-term", at(52), Some(at(100))),
+        ]);
+
+        // A second or more: whatever a tick sees counts at once
+        let mut debouncer = WindowDebouncer::for_interval(1000);
+        let switcher = WindowInfo::new("fd-switcher", "");
+        assert_eq!(debouncer.observe(None, switcher.clone(), at(50)), Some((switcher, at(50))));
+    }
+
+    #[test]
+    fn a_window_that_comes_straight_back_is_no_change() {
+        let mut debouncer = WindowDebouncer::for_interval(100);
+        let editor = WindowInfo::new("fd-editor", "main.rs");
+        let at = |ms: i64| minute(0) + chrono::Duration::milliseconds(ms);
+        assert_eq!(debouncer.observe(Some(&editor), WindowInfo::new("fd-switcher", ""), at(0)), None);
+        assert_eq!(debouncer.observe(Some(&editor), editor.clone(), at(100)), None);
+        // The switcher starts settling afresh
+        assert_eq!(debouncer.observe(Some(&editor), WindowInfo::new("fd-switcher", ""), at(200)), None);
+        assert_eq!(debouncer.observe(Some(&editor), WindowInfo::new("fd-switcher", ""), at(600)), None);
+        let (window, since) = debouncer.observe(Some(&editor), WindowInfo::new("fd-switcher", ""), at(700)).unwrap();
+        assert_eq!((window.app_name.as_str(), since), ("fd-switcher", at(200)));
+        // A different title is a different window
+        assert!(debouncer.observe(Some(&editor), WindowInfo::new("fd-editor", "lib.rs"), at(800)).is_none());
+    }
+
+    #[test]
+    fn log_limiter_counts_what_it_held_back() {
+        let mut limiter = LogLimiter::default();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(limiter.allow("window_change", hour), Some(0));
+        assert_eq!(limiter.allow("window_change", hour), None);
+        assert_eq!(limiter.allow("window_change", hour), None);
+        // Kinds are limited separately
+        assert_eq!(limiter.allow("detection", hour), Some(0));
+        assert_eq!(limiter.allow("window_change", Duration::ZERO), Some(2));
+        assert_eq!(limiter.allow("window_change", Duration::ZERO), Some(0));
+
+        assert_eq!(held_back_note(0), "");
+        assert_eq!(held_back_note(3), " (3 more not logged)");
+    }
+
+    #[test]
+    fn rows_record_the_rule_that_settled_them_in_precedence_order() {
+        let mut tracker = FocusTracker::new();
+        tracker.add_focus_app("firefox".to_string());
+        tracker.add_focus_site("title:engineering wiki".to_string());
+        let mut tracker = started(tracker);
+        let (rows, _) = play(&mut tracker, vec![
+            // A focus browser showing a focus site: the app comes first
+            (WindowInfo::new("firefox", "Engineering Wiki — Mozilla Firefox"), 0),
+            (WindowInfo::new("chromium", "Engineering Wiki — Chromium"), 5),
+            (WindowInfo::new("chromium", "Inbox — Chromium"), 10),
+        ], 15);
+        let kinds: Vec<_> = rows.iter().map(|row| row.focus_rule.as_ref().map(|rule| rule.kind)).collect();
+        assert_eq!(kinds, [Some(config::RuleKind::FocusApp), Some(config::RuleKind::FocusSite), Some(config::RuleKind::Default)]);
+        assert_eq!(rows.iter().map(|row| row.is_focus_app).collect::<Vec<_>>(), [true, true, false]);
+    }
+}
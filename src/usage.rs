@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+/// How often the daemon samples its own CPU time and memory
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// Samples kept for the mean: the last hour
+pub const WINDOW: usize = 60;
+/// Samples in a row above cpu_warning_percent before the daemon warns
+pub const WARN_AFTER: usize = 5;
+
+/// The daemon's CPU use over one sample interval and its memory at the end
+/// of it. CPU is a share of one core, so it can pass 100% on several.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+}
+
+/// What `status --verbose` and `debug` show of the daemon's own footprint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub current: Option<Sample>,
+    /// Mean CPU over the samples in the window
+    pub mean_cpu_percent: f32,
+    pub samples: usize,
+    /// Highest values since the daemon started
+    pub peak_cpu_percent: f32,
+    pub peak_rss_bytes: u64,
+}
+
+/// Samples this process through sysinfo, which reads /proc/self on Linux,
+/// task_info on macOS and GetProcessTimes on Windows
+pub struct Monitor {
+    system: sysinfo::System,
+    pid: sysinfo::Pid,
+    samples: VecDeque<Sample>,
+    peak_cpu_percent: f32,
+    peak_rss_bytes: u64,
+    /// cpu_warning_percent; 0 never warns
+    warn_percent: u64,
+    warned: bool,
+}
+
+impl Monitor {
+    /// Takes the baseline the first sample's CPU use is measured from
+    pub fn new(warn_percent: u64) -> Self {
+        let pid = sysinfo::Pid::from_u32(utils::get_current_pid());
+        let mut system = sysinfo::System::new();
+        system.refresh_process(pid);
+        Self {
+            system,
+            pid,
+            samples: VecDeque::with_capacity(WINDOW),
+            peak_cpu_percent: 0.0,
+            peak_rss_bytes: 0,
+            warn_percent,
+            warned: false,
+        }
+    }
+
+    /// Take a sample. Returns a warning the first time WARN_AFTER samples in
+    /// a row are above cpu_warning_percent; the next one comes only after
+    /// the CPU use has dropped below it again.
+    pub fn sample(&mut self, at: DateTime<Utc>) -> Option<String> {
+        self.system.refresh_process(self.pid);
+        let process = self.system.process(self.pid)?;
+        let sample = Sample { at, cpu_percent: process.cpu_usage(), rss_bytes: process.memory() };
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        self.peak_cpu_percent = self.peak_cpu_percent.max(sample.cpu_percent);
+        self.peak_rss_bytes = self.peak_rss_bytes.max(sample.rss_bytes);
+
+        if self.warn_percent == 0 {
+            return None;
+        }
+        let over = self.samples.iter().rev().take_while(|sample| sample.cpu_percent > self.warn_percent as f32).count();
+        if over == 0 {
+            self.warned = false;
+        }
+        if over < WARN_AFTER || self.warned {
+            return None;
+        }
+        self.warned = true;
+        let minutes = (SAMPLE_INTERVAL * WARN_AFTER as u32).as_secs() / 60;
+        Some(format!("The daemon has used more than {}% CPU for {} minutes (now {:.1}%, {} resident)",
+            self.warn_percent, minutes, sample.cpu_percent, utils::format_bytes(sample.rss_bytes)))
+    }
+
+    pub fn usage(&self) -> ResourceUsage {
+        let mean_cpu_percent = match self.samples.len() {
+            0 => 0.0,
+            count => self.samples.iter().map(|sample| sample.cpu_percent).sum::<f32>() / count as f32,
+        };
+        ResourceUsage {
+            current: self.samples.back().copied(),
+            mean_cpu_percent,
+            samples: self.samples.len(),
+            peak_cpu_percent: self.peak_cpu_percent,
+            peak_rss_bytes: self.peak_rss_bytes,
+        }
+    }
+}
+
+impl ResourceUsage {
+    /// e.g. "0.4% now, 0.3% mean over 60 min, 1.2% peak"
+    pub fn describe_cpu(&self) -> String {
+        match self.current {
+            Some(current) => format!("{:.1}% now, {:.1}% mean over {} min, {:.1}% peak",
+                current.cpu_percent, self.mean_cpu_percent,
+                (SAMPLE_INTERVAL * self.samples as u32).as_secs() / 60, self.peak_cpu_percent),
+            None => format!("no sample yet (taken every {} s)", SAMPLE_INTERVAL.as_secs()),
+        }
+    }
+
+    /// e.g. "14.2 MB now, 15.0 MB peak"
+    pub fn describe_memory(&self) -> Option<String> {
+        self.current.map(|current| format!("{} now, {} peak",
+            utils::format_bytes(current.rss_bytes), utils::format_bytes(self.peak_rss_bytes)))
+    }
+}
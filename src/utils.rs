@@ -1,10 +1,25 @@
 use std::process::Command;
+use std::fmt;
 use std::time::Duration;
-use chrono::{DateTime, Utc, Local};
-use std::path::PathBuf;
-use regex::Regex;
-use url::Url;
+use chrono::{DateTime, Datelike, Utc, NaiveDate, TimeZone, FixedOffset, Offset};
+use std::path::{Component, Path, PathBuf, Prefix};
+use regex::{Regex, RegexBuilder};
 use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::glyphs::{self, BarStyle, GlyphSet};
+
+// Spelled out so examples that load this file by #[path] find it too
+#[path = "utils/fuzzy.rs"]
+pub mod fuzzy;
+#[path = "utils/runtime_files.rs"]
+pub mod runtime_files;
+#[path = "utils/timezone.rs"]
+pub mod timezone;
+
+pub use runtime_files::{private_file_options, runtime_directory, runtime_file, write_private_file};
+use timezone::zone;
 
 pub fn check_dependencies() -> bool {
     // Check if xdotool is available on Linux
@@ -23,217 +38,1583 @@ pub fn check_dependencies() -> bool {
     }
 }
 
+/// The first file called `program` in PATH (with the usual extensions on
+/// Windows)
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &["", ".exe", ".cmd", ".bat"] } else { &[""] };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |extension| dir.join(format!("{}{}", program, extension))))
+        .find(|path| path.is_file())
+}
+
+/// 12- or 24-hour clock for displayed times (`time_format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeFormat {
+    #[serde(rename = "12h")]
+    H12,
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+}
+
+impl TimeFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "12h" | "12" => Some(TimeFormat::H12),
+            "24h" | "24" => Some(TimeFormat::H24),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimeFormat::H12 => "12h",
+            TimeFormat::H24 => "24h",
+        }
+    }
+
+    fn minutes(&self) -> &'static str {
+        match self {
+            TimeFormat::H12 => "%-I:%M %p",
+            TimeFormat::H24 => "%H:%M",
+        }
+    }
+
+    fn seconds(&self) -> &'static str {
+        match self {
+            TimeFormat::H12 => "%-I:%M:%S %p",
+            TimeFormat::H24 => "%H:%M:%S",
+        }
+    }
+}
+
+pub const DEFAULT_DATE_FORMAT: &str = "%b %d";
+
+struct DisplayFormats {
+    time: TimeFormat,
+    date: String,
+    utc_offset: bool,
+}
+
+static DISPLAY_FORMATS: OnceLock<DisplayFormats> = OnceLock::new();
+
+/// Apply time_format, date_format and show_utc_offset. A date format that
+/// doesn't parse falls back to the default.
+pub fn set_display_formats(time: TimeFormat, date: &str, utc_offset: bool) {
+    let date = match validate_strftime(date) {
+        Ok(()) => date.to_string(),
+        Err(e) => {
+            eprintln!("❌ Ignoring date_format: {}", e);
+            DEFAULT_DATE_FORMAT.to_string()
+        }
+    };
+    let _ = DISPLAY_FORMATS.set(DisplayFormats { time, date, utc_offset });
+}
+
+fn display_formats() -> &'static DisplayFormats {
+    DISPLAY_FORMATS.get_or_init(|| DisplayFormats {
+        time: TimeFormat::default(),
+        date: DEFAULT_DATE_FORMAT.to_string(),
+        utc_offset: false,
+    })
+}
+
+/// Check a strftime string such as date_format, so typos fail when set
+/// rather than garbling every report
+pub fn validate_strftime(format: &str) -> Result<(), String> {
+    if format.trim().is_empty() {
+        return Err("the format is empty".to_string());
+    }
+    if chrono::format::StrftimeItems::new(format).any(|item| matches!(item, chrono::format::Item::Error)) {
+        return Err(format!("'{}' is not a valid strftime format", format));
+    }
+    Ok(())
+}
+
 pub fn format_timestamp(timestamp: DateTime<Utc>) -> String {
-    timestamp.with_timezone(&Local).format("%H:%M:%S").to_string()
+    timestamp.with_timezone(&zone()).format(display_formats().time.seconds()).to_string()
+}
+
+pub fn format_timestamp_local(timestamp: DateTime<Utc>) -> String {
+    timestamp.with_timezone(&zone()).format(display_formats().time.minutes()).to_string()
+}
+
+pub fn format_date_local(date: NaiveDate) -> String {
+    date.format(&display_formats().date).to_string()
+}
+
+pub fn format_datetime_local(timestamp: DateTime<Utc>) -> String {
+    let local = timestamp.with_timezone(&zone());
+    format!("{}, {}", local.format(&display_formats().date), local.format(display_formats().time.minutes()))
+}
+
+/// " (UTC+02:00)" for `timestamp` with show_utc_offset, else nothing. Share
+/// reports and exports leave the machine, so the offset is worth noting.
+pub fn utc_offset_note(timestamp: DateTime<Utc>) -> String {
+    if !display_formats().utc_offset {
+        return String::new();
+    }
+    format!(" (UTC{})", timestamp.with_timezone(&zone()).format("%:z"))
+}
+
+/// Columns reports are laid out for when the width is neither pinned nor
+/// measurable (output piped to a file)
+pub const DEFAULT_REPORT_WIDTH: usize = 80;
+/// Narrower than this, reports switch to the compact layout: numbers, no bars
+pub const COMPACT_REPORT_WIDTH: usize = 50;
+/// Bars and columns stop growing past this width
+const MAX_SCALED_REPORT_WIDTH: usize = 120;
+
+static REPORT_WIDTH: OnceLock<Option<usize>> = OnceLock::new();
+
+/// Apply report_width; None measures the terminal each time
+pub fn set_report_width(width: Option<usize>) {
+    let _ = REPORT_WIDTH.set(width);
+}
+
+/// The width a report is laid out for. Report code writes bar and column
+/// widths as they look at 80 columns; the layout scales them from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportLayout {
+    pub width: usize,
+}
+
+impl ReportLayout {
+    /// report_width if pinned, else the terminal's width, else 80 columns
+    pub fn current() -> Self {
+        let width = REPORT_WIDTH.get().copied().flatten()
+            .or_else(|| terminal_size::terminal_size().map(|(width, _)| width.0 as usize))
+            .unwrap_or(DEFAULT_REPORT_WIDTH);
+        Self::with_width(width)
+    }
+
+    pub fn with_width(width: usize) -> Self {
+        Self { width }
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.width < COMPACT_REPORT_WIDTH
+    }
+
+    /// `cells` as written for 80 columns, scaled to this width
+    pub fn cells(&self, cells: usize) -> usize {
+        (cells * self.width.min(MAX_SCALED_REPORT_WIDTH) / DEFAULT_REPORT_WIDTH).max(1)
+    }
+
+    /// "[■■■□□]" filled to `fraction` (0.0–1.0) and `cells` wide at 80
+    /// columns, "[===--]" with ASCII glyphs. None in the compact layout.
+    pub fn bar(&self, fraction: f64, cells: usize, style: BarStyle) -> Option<String> {
+        if self.is_compact() {
+            return None;
+        }
+        Some(render_bar(fraction, self.cells(cells), glyphs::current(), style))
+    }
+
+    /// `text` cut to a column `cells` wide at 80 columns, ending in "..."
+    /// when cut, with control characters escaped
+    pub fn fit(&self, text: &str, cells: usize) -> String {
+        let text = escape_control_chars(text);
+        let max = self.cells(cells).max(6);
+        if text.chars().count() <= max {
+            return text.to_string();
+        }
+        format!("{}...", text.chars().take(max - 3).collect::<String>())
+    }
+}
+
+/// "[■■■□□]" exactly `cells` filled or empty cells wide between the brackets
+pub fn render_bar(fraction: f64, cells: usize, glyphs: GlyphSet, style: BarStyle) -> String {
+    let (filled, empty) = glyphs.bar_cells(style);
+    let full = ((fraction.clamp(0.0, 1.0) * cells as f64) as usize).min(cells);
+    format!("[{}{}]", filled.to_string().repeat(full), empty.to_string().repeat(cells - full))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationStyle {
+    /// "2d 3h", "1h 3m", "3m 20s", "45s"
+    Long,
+    /// "2d3h", "1h3m", "3m20s", "45s"
+    Compact,
+    /// "1:03:20", or "2d 1:03:20" past a day
+    Clock,
+}
+
+/// Format a duration, showing the two most significant units for
+/// `Long`/`Compact` and every field for `Clock`.
+pub fn format_duration(duration: Duration, style: DurationStyle) -> String {
+    let total_seconds = duration.as_secs();
+    let days = total_seconds / 86_400;
+    let hours = (total_seconds % 86_400) / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+
+    if style == DurationStyle::Clock {
+        let clock = format!("{}:{:02}:{:02}", hours, minutes, seconds);
+        return if days > 0 { format!("{}d {}", days, clock) } else { clock };
+    }
+
+    let separator = if style == DurationStyle::Long { " " } else { "" };
+    let (major, minor) = if days > 0 {
+        ((days, 'd'), Some((hours, 'h')))
+    } else if hours > 0 {
+        ((hours, 'h'), Some((minutes, 'm')))
+    } else if minutes > 0 {
+        ((minutes, 'm'), Some((seconds, 's')))
+    } else {
+        ((seconds, 's'), None)
+    };
+    match minor {
+        Some((value, unit)) => format!("{}{}{}{}{}", major.0, major.1, separator, value, unit),
+        None => format!("{}{}", major.0, major.1),
+    }
+}
+
+/// Parse durations like "30m", "1h", "1h30m", "1h 30m", "2d" or "45s".
+/// A bare number is minutes. None when it doesn't fit in a u64 of seconds.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return None;
+    }
+    if let Ok(minutes) = input.parse::<u64>() {
+        return minutes.checked_mul(60).map(Duration::from_secs);
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in input.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().ok()?;
+        let unit = match c {
+            'd' => 86_400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(value.checked_mul(unit)?)?;
+        number.clear();
+    }
+
+    // Trailing digits without a unit ("1h30") are ambiguous
+    if !number.is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// `parse_duration` for clap flags
+pub fn parse_duration_arg(input: &str) -> Result<Duration, String> {
+    parse_duration(input).ok_or_else(|| format!("invalid duration '{}' (try 90m, 1h30m, 2d or 45s)", input))
+}
+
+/// Items picked from a numbered list of `count`, as typed: "1,3", "2-4 6"
+/// or "all"; empty picks none. Returns 0-based indices, sorted and without
+/// repeats.
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+    let number = |part: &str| match part.trim().parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Ok(n - 1),
+        _ => Err(format!("'{}' is not a number from 1 to {}", part.trim(), count)),
+    };
+    let mut picked = std::collections::BTreeSet::new();
+    for part in input.split([',', ' ']).filter(|part| !part.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (number(from)?, number(to)?);
+                picked.extend(from.min(to)..=from.max(to));
+            }
+            None => {
+                picked.insert(number(part)?);
+            }
+        }
+    }
+    Ok(picked.into_iter().collect())
+}
+
+/// Daily housekeeping (retention, the size sample) runs on the daemon's
+/// first chance and then on the first one after each day boundary
+/// (day_start_hour, local time)
+pub fn daily_run_due(last_run_day: Option<DateTime<Utc>>, now: DateTime<Utc>, day_start_hour: u32) -> bool {
+    last_run_day.is_none_or(|day| day_start(now, day_start_hour) > day)
+}
+
+/// Start of the tracking "day" containing `now`, where days roll over at
+/// `day_start_hour` local time instead of midnight.
+pub fn day_start(now: DateTime<Utc>, day_start_hour: u32) -> DateTime<Utc> {
+    let local_now = now.with_timezone(&zone());
+    let hour = day_start_hour.min(23);
+    let mut date = local_now.date_naive();
+    if local_now.time() < chrono::NaiveTime::from_hms_opt(hour, 0, 0).unwrap() {
+        date = date.pred_opt().unwrap_or(date);
+    }
+    local_boundary(date, hour).unwrap_or(now)
+}
+
+/// Start of the week `now` falls in: Monday at day_start_hour, local time,
+/// the same weeks the digest uses
+pub fn week_start(now: DateTime<Utc>, day_start_hour: u32) -> DateTime<Utc> {
+    let day = day_start(now, day_start_hour);
+    let date = day.with_timezone(&zone()).date_naive();
+    let monday = date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64);
+    local_boundary(monday, day_start_hour.min(23)).unwrap_or(day)
+}
+
+/// The tracking day `at` belongs to: its local date, or the one before
+/// when it is earlier than day_start_hour
+pub fn tracking_day(at: DateTime<Utc>, day_start_hour: u32) -> NaiveDate {
+    tracking_day_in(&zone(), at, day_start_hour)
+}
+
+/// `tracking_day` in `tz` rather than the configured zone
+pub fn tracking_day_in<Tz: TimeZone>(tz: &Tz, at: DateTime<Utc>, day_start_hour: u32) -> NaiveDate {
+    (at.with_timezone(tz).naive_local() - chrono::Duration::hours(day_start_hour.min(23) as i64)).date()
+}
+
+/// Start of the tracking day `day`: day_start_hour local time on it
+pub fn tracking_day_start(day: NaiveDate, day_start_hour: u32) -> DateTime<Utc> {
+    LocalDays::new(day_start_hour).start(day)
+}
+
+/// `hour`:00 local time on `date`, the earlier one when DST repeats it
+fn local_boundary(date: NaiveDate, hour: u32) -> Option<DateTime<Utc>> {
+    local_boundary_in(&zone(), date, hour)
+}
+
+/// `local_boundary` in `tz`. When DST skips the hour, the day starts when
+/// the clock comes back to it, an hour (or half an hour) later.
+fn local_boundary_in<Tz: TimeZone>(tz: &Tz, date: NaiveDate, hour: u32) -> Option<DateTime<Utc>> {
+    let boundary = date.and_hms_opt(hour, 0, 0)?;
+    [0, 30, 60].iter()
+        .find_map(|minutes| tz.from_local_datetime(&(boundary + chrono::Duration::minutes(*minutes))).earliest())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// How days are cut on the local clock of `zone`: each runs from
+/// `start_hour` on its date to `start_hour` on the next, so it lasts 23 or
+/// 25 hours across a DST change. The same days as `tracking_day`, with the
+/// zone passed in rather than read from `zone()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalDays {
+    pub zone: timezone::Zone,
+    pub start_hour: u32,
+}
+
+impl LocalDays {
+    /// Days starting at `day_start_hour` in the resolved zone
+    pub fn new(day_start_hour: u32) -> Self {
+        Self::in_zone(zone(), day_start_hour)
+    }
+
+    pub fn in_zone(zone: timezone::Zone, day_start_hour: u32) -> Self {
+        LocalDays { zone, start_hour: day_start_hour.min(23) }
+    }
+
+    /// The day `at` belongs to
+    pub fn day_of(&self, at: DateTime<Utc>) -> NaiveDate {
+        tracking_day_in(&self.zone, at, self.start_hour)
+    }
+
+    /// When `day` starts. A date the zone skipped (Samoa's 30 December
+    /// 2011) starts, and ends, with the next one.
+    pub fn start(&self, day: NaiveDate) -> DateTime<Utc> {
+        local_boundary_in(&self.zone, day, self.start_hour)
+            .or_else(|| local_boundary_in(&self.zone, day.succ_opt()?, self.start_hour))
+            .unwrap_or_else(|| DateTime::<Utc>::from_naive_utc_and_offset(day.and_time(chrono::NaiveTime::MIN), Utc))
+    }
+
+    /// `day` as the half-open range [its start, the next day's start)
+    pub fn range(&self, day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+        (self.start(day), self.start(day.succ_opt().unwrap_or(day)))
+    }
+}
+
+/// Name of the automatic session for the day `now` falls in, e.g.
+/// "2024-05-21". Before day_start_hour it is still the previous day's.
+pub fn daily_session_name(now: DateTime<Utc>, day_start_hour: u32) -> String {
+    daily_session_name_in(&zone(), now, day_start_hour)
+}
+
+/// `daily_session_name` in `tz` rather than the configured zone
+pub fn daily_session_name_in<Tz: TimeZone>(tz: &Tz, now: DateTime<Utc>, day_start_hour: u32) -> String {
+    tracking_day_in(tz, now, day_start_hour).format("%Y-%m-%d").to_string()
+}
+
+/// What `sanitize_session_name` made of a typed or pasted name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionName {
+    pub name: String,
+    /// Control characters or escape sequences were removed
+    pub stripped: bool,
+    /// Cut to the maximum length
+    pub cut: bool,
+}
+
+/// Bidirectional overrides and isolates, which can make a name display as
+/// something else
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Skip the rest of an escape sequence whose introducer (ESC, or the C1
+/// CSI/OSC characters) was just read
+fn skip_escape(introducer: char, chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    let kind = match introducer {
+        '\u{1b}' => match chars.next() {
+            Some('[') => '[',
+            Some(']' | 'P' | 'X' | '^' | '_') => ']',
+            // Two- or three-character sequences such as ESC ( B
+            Some(c) if ('\u{20}'..='\u{2f}').contains(&c) => {
+                chars.next();
+                return;
+            }
+            _ => return,
+        },
+        '\u{9b}' => '[',
+        _ => ']',
+    };
+    if kind == '[' {
+        // Parameters and intermediates up to a final byte in @..~
+        for c in chars.by_ref() {
+            if ('\u{40}'..='\u{7e}').contains(&c) {
+                return;
+            }
+        }
+    } else {
+        // Strings run to BEL or ST (ESC \ or U+009C)
+        while let Some(c) = chars.next() {
+            if c == '\u{7}' || c == '\u{9c}' {
+                return;
+            }
+            if c == '\u{1b}' {
+                if chars.peek() == Some(&'\\') {
+                    chars.next();
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// A session name with ANSI escape sequences, control characters and
+/// bidirectional overrides removed, line breaks and runs of whitespace
+/// folded into single spaces, and cut to `max_length` characters (0 keeps
+/// it whole). Emoji and other text are kept as typed. Err when nothing is
+/// left.
+pub fn sanitize_session_name(raw: &str, max_length: usize) -> Result<SessionName, String> {
+    let mut cleaned = String::with_capacity(raw.len());
+    let mut stripped = false;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' | '\u{9b}' | '\u{9d}' | '\u{90}' => {
+                skip_escape(c, &mut chars);
+                stripped = true;
+            }
+            c if c.is_whitespace() => cleaned.push(' '),
+            c if c.is_control() || is_bidi_control(c) => stripped = true,
+            c => cleaned.push(c),
+        }
+    }
+    let mut name = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err(if stripped {
+            "Session name is empty once control characters are removed".to_string()
+        } else {
+            "Session name cannot be empty".to_string()
+        });
+    }
+    let cut = max_length > 0 && name.chars().count() > max_length;
+    if cut {
+        name = name.chars().take(max_length).collect::<String>().trim_end().to_string();
+    }
+    Ok(SessionName { name, stripped, cut })
+}
+
+/// `text` with control characters and bidirectional overrides written out
+/// as escapes (ESC shows as `\u{1b}`), so names stored before they were
+/// sanitized can't drive the terminal
+pub fn escape_control_chars(text: &str) -> std::borrow::Cow<'_, str> {
+    if !text.chars().any(|c| c.is_control() || is_bidi_control(c)) {
+        return std::borrow::Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if c.is_control() || is_bidi_control(c) {
+            escaped.extend(c.escape_unicode());
+        } else {
+            escaped.push(c);
+        }
+    }
+    std::borrow::Cow::Owned(escaped)
+}
+
+/// Offset of the local timezone at `at`
+pub fn local_offset(at: DateTime<Utc>) -> FixedOffset {
+    zone().offset_from_utc_datetime(&at.naive_utc()).fix()
+}
+
+/// "UTC+02:00"
+pub fn describe_utc_offset(offset: FixedOffset) -> String {
+    format!("UTC{}", offset)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// `value` cut to `max` characters, ending in "…" when cut
+pub fn truncate_chars(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        return value.to_string();
+    }
+    let mut cut: String = value.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// A window title cut to `max_title_length` characters; 0 keeps it whole
+pub fn cap_title(title: String, max_title_length: usize) -> String {
+    if max_title_length == 0 || title.len() <= max_title_length {
+        // Bytes never undercount characters, so this skips the count
+        return title;
+    }
+    truncate_chars(&title, max_title_length)
+}
+
+/// Parse a `--from`/`--to` style argument: either RFC3339 or a local
+/// YYYY-MM-DD date, which maps to the start (or end) of that day.
+pub fn parse_date_arg(input: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)?
+    } else {
+        date.and_hms_opt(0, 0, 0)?
+    };
+    zone().from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// Parse a point in time the way people type it, relative to `now` on the
+/// local clock: "tomorrow 09:00", "today 14:30", "fri 10:00" (the next
+/// Friday, today included if the time is still ahead), "2024-05-21 09:00",
+/// RFC3339, or a bare "09:00" (today, or tomorrow once it has passed).
+pub fn parse_when(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(local) = chrono::NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return zone().from_local_datetime(&local).earliest().map(|t| t.with_timezone(&Utc));
+    }
+
+    let local_now = now.with_timezone(&zone());
+    let today = local_now.date_naive();
+    let lowered = input.to_lowercase();
+    let (day, time) = match lowered.split_once(char::is_whitespace) {
+        Some((day, time)) => (Some(day), time.trim()),
+        None => (None, lowered.as_str()),
+    };
+    let time = chrono::NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+
+    let date = match day {
+        None if time > local_now.time() => today,
+        None => today.succ_opt()?,
+        Some("today") => today,
+        Some("tomorrow") => today.succ_opt()?,
+        Some(day) => {
+            let weekday: chrono::Weekday = day.parse().ok()?;
+            let mut ahead = (weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64).rem_euclid(7);
+            if ahead == 0 && time <= local_now.time() {
+                ahead = 7;
+            }
+            today + chrono::Duration::days(ahead)
+        }
+    };
+    zone().from_local_datetime(&date.and_time(time)).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// Like `parse_when`, but for a time already past: a bare "14:00" is today,
+/// or yesterday while it is still ahead; "yesterday 14:00"; "fri 10:00" is
+/// the latest Friday, today included once the time has passed.
+pub fn parse_past_when(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(local) = chrono::NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return zone().from_local_datetime(&local).earliest().map(|t| t.with_timezone(&Utc));
+    }
+
+    let local_now = now.with_timezone(&zone());
+    let today = local_now.date_naive();
+    let lowered = input.to_lowercase();
+    let (day, time) = match lowered.split_once(char::is_whitespace) {
+        Some((day, time)) => (Some(day), time.trim()),
+        None => (None, lowered.as_str()),
+    };
+    let time = chrono::NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+
+    let date = match day {
+        None if time <= local_now.time() => today,
+        None => today.pred_opt()?,
+        Some("today") => today,
+        Some("yesterday") => today.pred_opt()?,
+        Some(day) => {
+            let weekday: chrono::Weekday = day.parse().ok()?;
+            let mut back = (today.weekday().num_days_from_monday() as i64 - weekday.num_days_from_monday() as i64).rem_euclid(7);
+            if back == 0 && time > local_now.time() {
+                back = 7;
+            }
+            today - chrono::Duration::days(back)
+        }
+    };
+    zone().from_local_datetime(&date.and_time(time)).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// Open a file with the desktop's default application for its type
+pub fn open_in_default_app(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg(path);
+        command
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    };
+
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("opener exited with {}", status)))
+    }
+}
+
+static DATA_DIR_OVERRIDE: OnceLock<Option<PathBuf>> = OnceLock::new();
+static DATA_DIR_READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Use this data directory instead of FOCUSDEBT_DATA_DIR or the platform
+/// default (--data-dir). Same rules as `set_active_profile`.
+pub fn set_data_dir_override(dir: Option<PathBuf>) {
+    let _ = DATA_DIR_OVERRIDE.set(dir);
+}
+
+/// The data directory is unwritable and only an existing database is read
+pub fn set_data_dir_read_only(read_only: bool) {
+    DATA_DIR_READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn is_data_dir_read_only() -> bool {
+    DATA_DIR_READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// --data-dir, then FOCUSDEBT_DATA_DIR, then the platform data directory
+pub fn get_data_directory() -> Option<PathBuf> {
+    let dir = DATA_DIR_OVERRIDE.get().cloned().flatten()
+        .or_else(|| non_empty_env("FOCUSDEBT_DATA_DIR").map(PathBuf::from))
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("focusdebt")))?;
+    Some(normalize_path(&dir))
+}
+
+/// Resolve "." and ".." components without touching the filesystem, so
+/// /home/me/../me/.local/share is accepted as /home/me/.local/share
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Create `dir` if needed and prove it's writable by creating a scratch file
+pub fn check_writable_directory(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".focusdebt-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+static ACTIVE_PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Select the profile for this process. Must be called once, before any
+/// database or runtime file is touched; later calls are ignored.
+pub fn set_active_profile(profile: Option<String>) {
+    let _ = ACTIVE_PROFILE.set(profile);
+}
+
+pub fn active_profile() -> Option<&'static str> {
+    ACTIVE_PROFILE.get().and_then(|p| p.as_deref())
+}
+
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Where a profile keeps its database and state: data_dir/profiles/<name>,
+/// or the data directory itself for the default profile.
+pub fn profile_data_directory(profile: Option<&str>) -> Option<PathBuf> {
+    let data_dir = get_data_directory()?;
+    Some(match profile {
+        Some(name) => data_dir.join("profiles").join(name),
+        None => data_dir,
+    })
+}
+
+pub fn ensure_profile_data_directory() -> std::io::Result<PathBuf> {
+    let dir = profile_data_directory(active_profile())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    if !is_safe_path(&dir) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "❌ Invalid data directory path"
+        ));
+    }
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Names of all profiles that have a data directory
+pub fn list_profiles() -> Vec<String> {
+    let Some(profiles_dir) = get_data_directory().map(|dir| dir.join("profiles")) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<String> = std::fs::read_dir(profiles_dir)
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| is_valid_profile_name(name))
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles
+}
+
+/// File in the profile's data directory with the latest wall-clock time a
+/// saved session reached, the reference for `clock_skew`
+pub const LAST_SEEN_CLOCK_FILE: &str = "last_seen_clock";
+
+pub fn last_seen_clock() -> Option<DateTime<Utc>> {
+    let path = profile_data_directory(active_profile())?.join(LAST_SEEN_CLOCK_FILE);
+    let content = std::fs::read_to_string(path).ok()?;
+    DateTime::parse_from_rfc3339(content.trim()).ok().map(|at| at.with_timezone(&Utc))
+}
+
+/// Move the last seen time forward to `at`; an earlier time is ignored
+pub fn record_seen_clock(at: DateTime<Utc>) -> std::io::Result<()> {
+    if last_seen_clock().is_some_and(|seen| seen >= at) {
+        return Ok(());
+    }
+    reset_seen_clock(at)
+}
+
+/// Set the last seen time to `at`, even backwards, once the clock is known
+/// to be right again
+pub fn reset_seen_clock(at: DateTime<Utc>) -> std::io::Result<()> {
+    let path = ensure_profile_data_directory()?.join(LAST_SEEN_CLOCK_FILE);
+    write_private_file(&path, at.to_rfc3339())
+}
+
+/// How far `at` is from `last_seen` when that is more than `max_skew_days`
+/// either way, e.g. a session dated 2036 by a VM that booted with a wrong
+/// clock. None when it is within range, nothing was seen yet, or the check
+/// is off (0 days).
+pub fn clock_skew(at: DateTime<Utc>, last_seen: Option<DateTime<Utc>>, max_skew_days: u32) -> Option<chrono::Duration> {
+    let offset = at - last_seen?;
+    (max_skew_days > 0 && offset.num_seconds().abs() > max_skew_days as i64 * 86_400).then_some(offset)
+}
+
+pub fn ensure_data_directory() -> std::io::Result<PathBuf> {
+    let data_dir = get_data_directory()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    
+    // Validate path to prevent path traversal
+    if !is_safe_path(&data_dir) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "❌ Invalid data directory path"
+        ));
+    }
+    
+    std::fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Data paths must be absolute, stay inside the root once ".." components
+/// are resolved and, on Windows, be on a local drive rather than a UNC
+/// share or device path
+pub fn is_safe_path(path: &Path) -> bool {
+    path.is_absolute() && normalize_path(path).components().all(|component| match component {
+        // Left over only when it climbs past the root
+        Component::ParentDir => false,
+        Component::Prefix(prefix) => is_local_prefix(prefix.kind()),
+        _ => true,
+    })
+}
+
+/// Drive letters only; UNC shares, `\\?\UNC` and device paths aren't local
+fn is_local_prefix(prefix: Prefix) -> bool {
+    matches!(prefix, Prefix::Disk(_) | Prefix::VerbatimDisk(_))
+}
+
+pub fn is_daemon_running() -> bool {
+    is_runtime_pid_running("pid")
+}
+
+/// Whether the PID file names `pid`, so that process is the one tracking
+pub fn pid_file_holds(pid: u32) -> bool {
+    runtime_file("pid")
+        .and_then(|pid_file| std::fs::read_to_string(pid_file).ok())
+        .is_some_and(|content| content.trim() == pid.to_string())
+}
+
+/// Whether the process whose PID is in the runtime file `extension` is alive
+fn is_runtime_pid_running(extension: &str) -> bool {
+    runtime_file(extension).is_some_and(|pid_file| runtime_files::pid_file_alive(&pid_file))
+}
+
+pub fn check_process_exists(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        // Use /proc filesystem instead of kill command
+        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    }
+    
+    #[cfg(target_os = "macos")]
+    {
+        // Use ps command with safe arguments
+        let output = Command::new("ps")
+            .args(&["-p", &pid.to_string(), "-o", "pid="])
+            .output();
+        match output {
+            Ok(output) => output.status.success() && !output.stdout.is_empty(),
+            Err(_) => false,
+        }
+    }
+    
+    #[cfg(target_os = "windows")]
+    {
+        // Use tasklist with safe arguments
+        let output = Command::new("tasklist")
+            .args(&["/FI", &format!("PID eq {}", pid), "/FO", "CSV"])
+            .output();
+        match output {
+            Ok(output) => {
+                let output_str = String::from_utf8_lossy(&output.stdout);
+                output_str.contains(&pid.to_string())
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn write_pid_file(pid: u32) -> std::io::Result<()> {
+    if let Some(pid_file) = runtime_file("pid") {
+        // Validate path before writing
+        if !is_safe_path(&pid_file) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "❌ Invalid PID file path"
+            ));
+        }
+        write_private_file(&pid_file, pid.to_string())?;
+    }
+    Ok(())
+}
+
+/// Held while an export reads the database, so the daemon's retention run
+/// doesn't delete rows from under it. Removed on drop.
+pub struct ExportMarker(PathBuf);
+
+impl ExportMarker {
+    pub fn acquire() -> std::io::Result<Self> {
+        ensure_data_directory()?;
+        let path = runtime_file("export")
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+        write_private_file(&path, get_current_pid().to_string())?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for ExportMarker {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// An export holds an `ExportMarker` and is still running
+pub fn is_export_running() -> bool {
+    is_runtime_pid_running("export")
+}
+
+pub fn remove_pid_file() -> std::io::Result<()> {
+    if let Some(pid_file) = runtime_file("pid") {
+        if pid_file.exists() && is_safe_path(&pid_file) {
+            std::fs::remove_file(pid_file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Written by the daemon once its final flush has been committed. Holds the
+/// timestamp of the last row written, or nothing if there was nothing to write.
+pub fn write_flush_marker(last_committed: Option<DateTime<Utc>>) -> std::io::Result<()> {
+    ensure_data_directory()?;
+    let marker = runtime_file("flushed")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    let content = last_committed.map(|t| t.to_rfc3339()).unwrap_or_default();
+    // Written then renamed so `stop` never reads a half-written marker
+    write_private_file(&marker, content)
+}
+
+/// None while the daemon has not confirmed its flush yet
+pub fn read_flush_marker() -> Option<Option<DateTime<Utc>>> {
+    let marker = runtime_file("flushed")?;
+    let content = std::fs::read_to_string(marker).ok()?;
+    Some(DateTime::parse_from_rfc3339(content.trim()).ok().map(|t| t.with_timezone(&Utc)))
+}
+
+pub fn remove_flush_marker() -> std::io::Result<()> {
+    if let Some(marker) = runtime_file("flushed") {
+        if marker.exists() && is_safe_path(&marker) {
+            std::fs::remove_file(marker)?;
+        }
+    }
+    Ok(())
+}
+
+/// What the running daemon is tracking and which display it is bound to.
+/// Written by the daemon when it starts and removed when it exits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub pid: u32,
+    pub session_name: String,
+    pub started_at: DateTime<Utc>,
+    pub display: Option<String>,
+    pub wayland_display: Option<String>,
+    /// When the daemon noticed config.toml changed under it
+    #[serde(default)]
+    pub config_changed_at: Option<DateTime<Utc>>,
+    /// The quiet_hours range tracking is paused for, if any
+    #[serde(default)]
+    pub quiet_hours: Option<String>,
+    /// Since when the disk has been full and rows go to the spill journal
+    #[serde(default)]
+    pub degraded_since: Option<DateTime<Utc>>,
+    /// Optional integrations checked at startup; failed ones are off for the run
+    #[serde(default)]
+    pub integrations: Vec<IntegrationStatus>,
+    /// Why window detection can't work here and what to do about it, see
+    /// `tracking::detection_advice`
+    #[serde(default)]
+    pub detection_problem: Option<String>,
+}
+
+/// One optional integration (hooks, notifications, ...) after the startup
+/// check, shown as "notifications ✓" or "hooks ✗ (reason)"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrationStatus {
+    pub name: String,
+    /// Why it was turned off; None when it works
+    #[serde(default)]
+    pub problem: Option<String>,
+}
+
+impl fmt::Display for IntegrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            None => write!(f, "{} ✓", self.name),
+            Some(problem) => write!(f, "{} ✗ ({})", self.name, problem),
+        }
+    }
+}
+
+/// e.g. "hooks ✗ (on_session_end: 'sync-notes' not found), notifications ✓"
+pub fn describe_integrations(integrations: &[IntegrationStatus]) -> String {
+    integrations.iter().map(|integration| integration.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl DaemonState {
+    /// The displays of the current environment, for the daemon about to start
+    pub fn current(session_name: &str) -> Self {
+        Self {
+            pid: get_current_pid(),
+            session_name: session_name.to_string(),
+            started_at: Utc::now(),
+            display: non_empty_env("DISPLAY"),
+            wayland_display: non_empty_env("WAYLAND_DISPLAY"),
+            config_changed_at: None,
+            quiet_hours: None,
+            degraded_since: None,
+            integrations: Vec::new(),
+            detection_problem: None,
+        }
+    }
+
+    /// e.g. "Wayland wayland-1", "X11 :0" or "none"
+    pub fn describe_display(&self) -> String {
+        describe_display(self.display.as_deref(), self.wayland_display.as_deref())
+    }
+
+    /// Whether this shell is on the display the daemon is bound to
+    pub fn matches_current_display(&self) -> bool {
+        self.display == non_empty_env("DISPLAY") && self.wayland_display == non_empty_env("WAYLAND_DISPLAY")
+    }
+
+    /// Whether the display the daemon is bound to still exists. Only local
+    /// displays can be checked; remote ones are assumed to be there.
+    pub fn display_available(&self) -> bool {
+        if let Some(wayland_display) = &self.wayland_display {
+            let socket = Path::new(wayland_display);
+            if socket.is_absolute() {
+                return socket.exists();
+            }
+            return match std::env::var_os("XDG_RUNTIME_DIR") {
+                Some(runtime_dir) => Path::new(&runtime_dir).join(socket).exists(),
+                None => true,
+            };
+        }
+
+        if let Some(display) = &self.display {
+            // ":1" or ":1.0" is local and has a socket; "host:0" is remote
+            if let Some(number) = display.strip_prefix(':') {
+                let number = number.split('.').next().unwrap_or(number);
+                return Path::new("/tmp/.X11-unix").join(format!("X{}", number)).exists();
+            }
+        }
+
+        true
+    }
+}
+
+pub fn describe_display(display: Option<&str>, wayland_display: Option<&str>) -> String {
+    match (wayland_display, display) {
+        (Some(wayland_display), _) => format!("Wayland {}", wayland_display),
+        (None, Some(display)) => format!("X11 {}", display),
+        (None, None) => "none".to_string(),
+    }
+}
+
+/// The display this process is on, described like `DaemonState::describe_display`
+pub fn describe_current_display() -> String {
+    describe_display(non_empty_env("DISPLAY").as_deref(), non_empty_env("WAYLAND_DISPLAY").as_deref())
+}
+
+pub fn non_empty_env(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|value| !value.is_empty())
+}
+
+/// Read a passphrase from the terminal without echoing it (echo stays on
+/// off Unix). Fails when stdin isn't a terminal.
+pub fn prompt_passphrase(prompt: &str) -> std::io::Result<String> {
+    use std::io::{IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no terminal to ask for the passphrase"));
+    }
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+
+    #[cfg(unix)]
+    let saved = unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        (libc::tcgetattr(0, &mut termios) == 0).then(|| {
+            let mut silent = termios;
+            silent.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(0, libc::TCSANOW, &silent);
+            termios
+        })
+    };
+    let mut passphrase = String::new();
+    let read = std::io::stdin().read_line(&mut passphrase);
+    #[cfg(unix)]
+    if let Some(termios) = saved {
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &termios) };
+    }
+    eprintln!();
+    read?;
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub fn write_daemon_state(state: &DaemonState) -> std::io::Result<()> {
+    ensure_data_directory()?;
+    let path = runtime_file("state")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    let content = serde_json::to_string(state).map_err(std::io::Error::other)?;
+    write_private_file(&path, content)
+}
+
+pub fn read_daemon_state() -> Option<DaemonState> {
+    let content = std::fs::read_to_string(runtime_file("state")?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn remove_daemon_state() -> std::io::Result<()> {
+    if let Some(path) = runtime_file("state") {
+        if path.exists() && is_safe_path(&path) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves a heartbeat may miss before it counts as stale
+const HEARTBEAT_MISSED_SAVES: u32 = 3;
+/// A heartbeat is never stale sooner than this, however short the save
+/// interval
+const HEARTBEAT_MIN_STALE: Duration = Duration::from_secs(2 * 60);
+
+/// Proof of life the daemon writes at every save, for `heartbeat-check`.
+/// Removed when the daemon stops, so one that is left behind and has gone
+/// stale means the daemon died or hung.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub pid: u32,
+    pub session_name: String,
+    pub at: DateTime<Utc>,
+    /// The daemon's save interval, which sets how soon the heartbeat is stale
+    pub save_interval_ms: u64,
+    pub display: Option<String>,
+    pub wayland_display: Option<String>,
+    /// When `heartbeat-check` reported this heartbeat stale, so each death
+    /// is reported once
+    #[serde(default)]
+    pub reported_at: Option<DateTime<Utc>>,
+}
+
+impl Heartbeat {
+    pub fn now(session_name: &str, save_interval_ms: u64) -> Self {
+        Self {
+            pid: get_current_pid(),
+            session_name: session_name.to_string(),
+            at: Utc::now(),
+            save_interval_ms,
+            display: non_empty_env("DISPLAY"),
+            wayland_display: non_empty_env("WAYLAND_DISPLAY"),
+            reported_at: None,
+        }
+    }
+
+    /// How long the daemon may go without a beat before it is stale
+    pub fn stale_after(&self) -> Duration {
+        (Duration::from_millis(self.save_interval_ms) * HEARTBEAT_MISSED_SAVES).max(HEARTBEAT_MIN_STALE)
+    }
+
+    /// How long ago the last beat was, if that is past `stale_after` as of
+    /// `now`; None while it is fresh
+    pub fn silent_for(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let age = (now - self.at).to_std().unwrap_or_default();
+        (age > self.stale_after()).then_some(age)
+    }
+}
+
+pub fn write_heartbeat(heartbeat: &Heartbeat) -> std::io::Result<()> {
+    let path = runtime_file("heartbeat")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    let content = serde_json::to_string(heartbeat).map_err(std::io::Error::other)?;
+    write_private_file(&path, content)
+}
+
+pub fn read_heartbeat() -> Option<Heartbeat> {
+    let content = std::fs::read_to_string(runtime_file("heartbeat")?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn remove_heartbeat() -> std::io::Result<()> {
+    if let Some(path) = runtime_file("heartbeat") {
+        if path.exists() && is_safe_path(&path) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The daemon's in-memory view, written every few seconds for
+/// `focusdebt debug state`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebugState {
+    pub pid: u32,
+    pub updated_at: DateTime<Utc>,
+    pub session_name: String,
+    pub current_app: Option<String>,
+    pub current_title: Option<String>,
+    pub current_started_at: Option<DateTime<Utc>>,
+    pub current_is_focus: Option<bool>,
+    /// Completed window sessions not yet handed to the database thread
+    pub buffered_sessions: usize,
+    /// Context switches not yet handed to the database thread
+    pub buffered_switches: usize,
+    /// max_buffered_rows: buffering this many rows triggers an early save
+    #[serde(default)]
+    pub buffer_limit: usize,
+    /// Saves triggered by the buffer limit rather than the save interval
+    #[serde(default)]
+    pub early_saves: usize,
+    /// Commands sent to the database thread but not yet written
+    pub db_queue_depth: usize,
+    pub last_detection_at: Option<DateTime<Utc>>,
+    pub backend: Option<String>,
+    pub consecutive_failures: u32,
+    /// Times the daemon gave up on a failing backend for another
+    #[serde(default)]
+    pub backend_failovers: u32,
+    pub memory_bytes: Option<u64>,
+    /// The daemon's own CPU and memory, sampled once a minute
+    #[serde(default)]
+    pub resources: Option<crate::usage::ResourceUsage>,
+}
+
+pub fn write_debug_state(state: &DebugState) -> std::io::Result<()> {
+    let path = runtime_file("debug")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+    let content = serde_json::to_string(state).map_err(std::io::Error::other)?;
+    write_private_file(&path, content)
+}
+
+pub fn read_debug_state() -> Option<DebugState> {
+    let content = std::fs::read_to_string(runtime_file("debug")?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn remove_debug_state() -> std::io::Result<()> {
+    if let Some(path) = runtime_file("debug") {
+        if path.exists() && is_safe_path(&path) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
 }
 
-pub fn format_timestamp_local(timestamp: DateTime<Utc>) -> String {
-    timestamp.with_timezone(&Local).format("%H:%M").to_string()
+/// Resident memory of this process
+pub fn resident_memory() -> Option<u64> {
+    let pid = sysinfo::Pid::from_u32(get_current_pid());
+    let mut system = sysinfo::System::new();
+    system.refresh_process(pid);
+    system.process(pid).map(|process| process.memory())
 }
 
-pub fn format_datetime_local(timestamp: DateTime<Utc>) -> String {
-    timestamp.with_timezone(&Local).format("%b %d, %H:%M").to_string()
+/// 64-bit FNV-1a of `bytes`, in hex. Unlike std's hasher it never changes
+/// between builds, so it can be stored and compared later.
+pub fn stable_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
 }
 
-pub fn format_duration_short(duration: Duration) -> String {
-    let hours = duration.as_secs() / 3600;
-    let minutes = (duration.as_secs() % 3600) / 60;
-    
-    if hours > 0 {
-        format!("{}h{}m", hours, minutes)
-    } else {
-        format!("{}m", minutes)
-    }
+pub fn get_current_pid() -> u32 {
+    std::process::id()
 }
 
-pub fn get_data_directory() -> Option<PathBuf> {
-    dirs::data_dir().map(|dir| dir.join("focusdebt"))
+pub fn sleep_ms(milliseconds: u64) {
+    std::thread::sleep(Duration::from_millis(milliseconds));
 }
 
-pub fn ensure_data_directory() -> std::io::Result<PathBuf> {
-    let data_dir = get_data_directory()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "❌ Could not find data directory"))?;
+fn extract_domain_from_text(text: &str) -> Option<String> {
+    // Look for URL patterns
+    let url_pattern = Regex::new(r"https?://([^/\s]+)").ok()?;
+    if let Some(captures) = url_pattern.captures(text) {
+        return Some(captures.get(1).unwrap().as_str().to_lowercase());
+    }
     
-    // Validate path to prevent path traversal
-    if !is_safe_path(&data_dir) {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "❌ Invalid data directory path"
-        ));
+    // Look for domain patterns
+    let domain_pattern = Regex::new(r"([a-zA-Z0-9]([a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]{2,}").ok()?;
+    if let Some(captures) = domain_pattern.captures(text) {
+        return Some(captures.get(1).unwrap().as_str().to_lowercase());
     }
     
-    std::fs::create_dir_all(&data_dir)?;
-    Ok(data_dir)
+    None
 }
 
-fn is_safe_path(path: &PathBuf) -> bool {
-    // Check for path traversal attempts
-    let path_str = path.to_string_lossy();
-    !path_str.contains("..") && 
-    !path_str.contains("//") && 
-    (path_str.starts_with('/') || path_str.starts_with("C:\\") || path.is_absolute())
+/// How a focus/ignored site entry is matched against a browser tab title
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteMode {
+    Domain,
+    Title,
+    Regex,
 }
 
-pub fn is_daemon_running() -> bool {
-    // Check if there's a PID file or process running
-    if let Some(data_dir) = get_data_directory() {
-        let pid_file = data_dir.join("focusdebt.pid");
-        if pid_file.exists() {
-            if let Ok(pid_content) = std::fs::read_to_string(&pid_file) {
-                // Validate PID content - should only contain digits
-                let pid_content = pid_content.trim();
-                if pid_content.chars().all(|c| c.is_ascii_digit()) {
-                    if let Ok(pid) = pid_content.parse::<u32>() {
-                        // Validate PID range (1-999999 is reasonable)
-                        if pid > 0 && pid < 1000000 {
-                            // Check if process is still running using safe method
-                            return check_process_exists(pid);
-                        }
-                    }
-                }
-            }
+impl SiteMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SiteMode::Domain => "domain",
+            SiteMode::Title => "title",
+            SiteMode::Regex => "regex",
         }
     }
-    false
 }
 
-fn check_process_exists(pid: u32) -> bool {
-    #[cfg(target_os = "linux")]
-    {
-        // Use /proc filesystem instead of kill command
-        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+/// A focus/ignored site entry: "domain:github.com", "title:jira" or
+/// "re:/\bRFC\b/". Entries without a prefix are domains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteRule {
+    pub mode: SiteMode,
+    pub value: String,
+}
+
+impl SiteRule {
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim();
+        let (mode, value) = if let Some(domain) = entry.strip_prefix("domain:") {
+            (SiteMode::Domain, domain.trim().to_lowercase().trim_start_matches("www.").to_string())
+        } else if let Some(text) = entry.strip_prefix("title:") {
+            (SiteMode::Title, text.trim().to_string())
+        } else if let Some(pattern) = entry.strip_prefix("re:") {
+            let pattern = pattern.trim();
+            let pattern = pattern.strip_prefix('/')
+                .and_then(|inner| inner.strip_suffix('/'))
+                .unwrap_or(pattern);
+            RegexBuilder::new(pattern).case_insensitive(true).build()
+                .map_err(|e| format!("invalid regex '{}': {}", pattern, e))?;
+            (SiteMode::Regex, pattern.to_string())
+        } else {
+            (SiteMode::Domain, entry.to_lowercase().trim_start_matches("www.").to_string())
+        };
+        if value.is_empty() {
+            return Err(format!("'{}' has nothing to match", entry));
+        }
+        Ok(Self { mode, value })
     }
-    
-    #[cfg(target_os = "macos")]
-    {
-        // Use ps command with safe arguments
-        let output = Command::new("ps")
-            .args(&["-p", &pid.to_string(), "-o", "pid="])
-            .output();
-        match output {
-            Ok(output) => output.status.success() && !output.stdout.is_empty(),
-            Err(_) => false,
+
+    /// The prefixed form that is stored in config.toml
+    pub fn to_entry(&self) -> String {
+        match self.mode {
+            SiteMode::Domain => format!("domain:{}", self.value),
+            SiteMode::Title => format!("title:{}", self.value),
+            SiteMode::Regex => format!("re:/{}/", self.value),
         }
     }
-    
-    #[cfg(target_os = "windows")]
-    {
-        // Use tasklist with safe arguments
-        let output = Command::new("tasklist")
-            .args(&["/FI", &format!("PID eq {}", pid), "/FO", "CSV"])
-            .output();
-        match output {
-            Ok(output) => {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                output_str.contains(&pid.to_string())
-            },
-            Err(_) => false,
+
+    /// Case-insensitive in every mode
+    pub fn matches(&self, title: &str) -> bool {
+        match self.mode {
+            SiteMode::Title => title.to_lowercase().contains(&self.value.to_lowercase()),
+            SiteMode::Regex => RegexBuilder::new(&self.value).case_insensitive(true).build()
+                .is_ok_and(|regex| regex.is_match(title)),
+            SiteMode::Domain => title_matches_domain(title, &self.value),
         }
     }
 }
 
-pub fn write_pid_file(pid: u32) -> std::io::Result<()> {
-    if let Some(data_dir) = get_data_directory() {
-        let pid_file = data_dir.join("focusdebt.pid");
-        // Validate path before writing
-        if !is_safe_path(&pid_file) {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "❌ Invalid PID file path"
-            ));
-        }
-        std::fs::write(pid_file, pid.to_string())?;
+/// Separators browsers and sites put between title segments ("Issue · GitHub",
+/// "Page - Site — Mozilla Firefox")
+static TITLE_SEPARATOR_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Tab titles don't carry the URL, so a domain matches when the title
+/// mentions it ("github.com/foo") or when one of the title's segments is the
+/// site's name ("… · GitHub" for github.com, "… - Stack Overflow" for
+/// stackoverflow.com). "docs.rs" therefore doesn't match "Google Docs".
+fn title_matches_domain(title: &str, domain: &str) -> bool {
+    let title = title.to_lowercase();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '-' || c == '.';
+    let mentioned = title.match_indices(domain).any(|(start, _)| {
+        let before = title[..start].chars().next_back();
+        let after = title[start + domain.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(|c| c.is_alphanumeric() || c == '-')
+    });
+    if mentioned {
+        return true;
     }
-    Ok(())
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let site_name = labels.len().checked_sub(2).map(|i| labels[i]).unwrap_or(domain);
+    TITLE_SEPARATOR_REGEX
+        .get_or_init(|| Regex::new(r"\s+[-–—|·•:]\s+").expect("valid title separator pattern"))
+        .split(&title)
+        .any(|segment| segment.chars().filter(|c| !c.is_whitespace()).eq(site_name.chars()))
 }
 
-pub fn remove_pid_file() -> std::io::Result<()> {
-    if let Some(data_dir) = get_data_directory() {
-        let pid_file = data_dir.join("focusdebt.pid");
-        if pid_file.exists() && is_safe_path(&pid_file) {
-            std::fs::remove_file(pid_file)?;
-        }
+/// Browser names that trail tab titles ("… — Mozilla Firefox")
+const BROWSER_TITLE_SUFFIXES: [&str; 10] = [
+    "mozilla firefox", "firefox", "google chrome", "chromium", "brave", "microsoft edge",
+    "edge", "safari", "opera", "vivaldi",
+];
+
+/// The site a tab title belongs to, for grouping tabs by site: the last
+/// title segment once the browser's name is dropped ("Issue #12 · GitHub —
+/// Mozilla Firefox" → "github"), or the host of a URL in a one-segment
+/// title. Falls back to the whole title, lowercased.
+pub fn extract_tab_site(title: &str) -> String {
+    let title = title.trim().to_lowercase();
+    let mut segments: Vec<&str> = TITLE_SEPARATOR_REGEX
+        .get_or_init(|| Regex::new(r"\s+[-–—|·•:]\s+").expect("valid title separator pattern"))
+        .split(&title)
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    while segments.len() > 1 && segments.last().is_some_and(|last| BROWSER_TITLE_SUFFIXES.contains(last)) {
+        segments.pop();
     }
-    Ok(())
+    if segments.len() > 1 {
+        return segments[segments.len() - 1].to_string();
+    }
+    let single = segments.first().copied().unwrap_or(&title);
+    extract_domain_from_text(single)
+        .filter(|_| single.contains("://"))
+        .map(|host| host.trim_start_matches("www.").to_string())
+        .unwrap_or_else(|| single.to_string())
 }
 
-pub fn get_current_pid() -> u32 {
-    std::process::id()
+/// Rewrite an entry from before match modes existed. Things that look like
+/// domains become domain entries and the rest title substrings; the flag is
+/// set for single words, which could have been meant either way.
+pub fn migrate_bare_site(entry: &str) -> (String, bool) {
+    let entry = entry.trim();
+    let looks_like_domain = entry.contains('.')
+        && !entry.contains(char::is_whitespace)
+        && entry.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '-'));
+    if looks_like_domain {
+        (format!("domain:{}", entry.to_lowercase()), false)
+    } else {
+        (format!("title:{}", entry), !entry.contains(char::is_whitespace))
+    }
 }
 
-pub fn sleep_ms(milliseconds: u64) {
-    std::thread::sleep(Duration::from_millis(milliseconds));
+/// Whether an entry already declares its match mode
+pub fn has_site_prefix(entry: &str) -> bool {
+    let entry = entry.trim();
+    ["domain:", "title:", "re:"].iter().any(|prefix| entry.starts_with(prefix))
 }
 
-pub fn extract_domain_from_title(window_title: &str, app_name: &str) -> Option<String> {
-    // Common browser process names
-    let browser_apps = [
-        "chrome", "firefox", "safari", "edge", "brave", "chromium", "opera", "vivaldi"
-    ];
-    
-    // Check if this is a browser
-    let is_browser = browser_apps.iter().any(|&browser| {
-        app_name.to_lowercase().contains(browser)
-    });
-    
-    if !is_browser {
-        return None;
-    }
-    
-    // Try to extract domain from various title patterns
-    let patterns = [
-        // "Page Title - Browser Name" -> extract from title
-        r"^(.+?)\s*[-–—]\s*(?:Google Chrome|Firefox|Safari|Edge|Brave|Chromium|Opera|Vivaldi)$",
-        // "Page Title | Domain" -> extract domain
-        r"^(.+?)\s*\|\s*([^|]+)$",
-        // "Domain - Page Title" -> extract domain
-        r"^([^-\s]+)\s*[-–—]\s*(.+)$",
-        // Just try to find a domain-like pattern
-        r"([a-zA-Z0-9]([a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]{2,}",
-    ];
-    
-    for pattern in &patterns {
-        if let Ok(regex) = Regex::new(pattern) {
-            if let Some(captures) = regex.captures(window_title) {
-                if pattern == &patterns[0] {
-                    // First pattern: extract from title part
-                    let title_part = captures.get(1).unwrap().as_str();
-                    return extract_domain_from_text(title_part);
-                } else if pattern == &patterns[1] {
-                    // Second pattern: extract from domain part
-                    let domain_part = captures.get(2).unwrap().as_str();
-                    return Some(domain_part.trim().to_lowercase());
-                } else if pattern == &patterns[2] {
-                    // Third pattern: extract from domain part
-                    let domain_part = captures.get(1).unwrap().as_str();
-                    return Some(domain_part.trim().to_lowercase());
-                } else {
-                    // Fourth pattern: direct domain match
-                    let domain = captures.get(1).unwrap().as_str();
-                    return Some(domain.to_lowercase());
-                }
-            }
+/// Match a focus/ignored site entry against a tab title. Invalid entries
+/// match nothing.
+pub fn title_matches_site(title: &str, site: &str) -> bool {
+    SiteRule::parse(site).is_ok_and(|rule| rule.matches(title))
+}
+
+/// Terminal titles showing a focusdebt CLI invocation, like "focusdebt stats"
+/// or "~/src/app: target/debug/focusdebt wrapped --year". A checkout such
+/// as "0x45o/focusdebt" or a file like "focusdebt README.md" doesn't match.
+const SELF_TITLE_PATTERN: &str = r"(^|[\s:$>])(\S*/)?(?i:focusdebt)\s+[a-z][a-z-]*(\s|$)";
+
+static SELF_TITLE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Whether the window is a terminal (a known one, or one a terminal title
+/// rule is for) showing focusdebt's own CLI
+pub fn is_self_window(app_name: &str, title: &str, rule_terminals: &[String]) -> bool {
+    crate::tmux::is_terminal(app_name, rule_terminals)
+        && SELF_TITLE_REGEX
+            .get_or_init(|| Regex::new(SELF_TITLE_PATTERN).expect("valid self title pattern"))
+            .is_match(title)
+}
+
+/// Case-insensitive regex match against any of the patterns. Patterns that
+/// fail to compile are treated as plain substrings so a typo never leaks a title.
+pub fn matches_any_pattern(text: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        match RegexBuilder::new(pattern).case_insensitive(true).build() {
+            Ok(regex) => regex.is_match(text),
+            Err(_) => text.to_lowercase().contains(&pattern.to_lowercase()),
         }
-    }
-    
-    // Fallback: try to extract domain from the entire title
-    extract_domain_from_text(window_title)
+    })
 }
 
-fn extract_domain_from_text(text: &str) -> Option<String> {
-    // Look for URL patterns
-    let url_pattern = Regex::new(r"https?://([^/\s]+)").ok()?;
-    if let Some(captures) = url_pattern.captures(text) {
-        return Some(captures.get(1).unwrap().as_str().to_lowercase());
+/// Default title_noise_markers: an editor's unsaved marks, a page still
+/// loading, and notification counts like "(3) Inbox"
+pub const DEFAULT_TITLE_NOISE_MARKERS: [&str; 5] = ["●", "*", "Loading…", "Loading...", "(#)"];
+
+/// One case-insensitive regex for the title_noise_markers. Markers are
+/// literal text except `#`, which stands for a number. None without any.
+pub fn title_noise_regex(markers: &[String]) -> Option<Regex> {
+    let alternatives: Vec<String> = markers.iter()
+        .map(|marker| marker.trim())
+        .filter(|marker| !marker.is_empty())
+        .map(|marker| marker.split('#').map(regex::escape).collect::<Vec<_>>().join(r"\d+"))
+        .collect();
+    if alternatives.is_empty() {
+        return None;
     }
-    
-    // Look for domain patterns
-    let domain_pattern = Regex::new(r"([a-zA-Z0-9]([a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]{2,}").ok()?;
-    if let Some(captures) = domain_pattern.captures(text) {
-        return Some(captures.get(1).unwrap().as_str().to_lowercase());
+    RegexBuilder::new(&alternatives.join("|")).case_insensitive(true).build().ok()
+}
+
+/// A window title as compared for changes: noise markers removed,
+/// whitespace collapsed and separators at either end dropped, so
+/// "● main.rs - Code" and "main.rs - Code" are the same. Empty when nothing
+/// but noise and a browser's name is left ("Loading… — Mozilla Firefox").
+pub fn normalize_title(title: &str, noise: Option<&Regex>) -> String {
+    let stripped = match noise {
+        Some(noise) => noise.replace_all(title, " "),
+        None => title.into(),
+    };
+    let collapsed = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | '—' | '|' | '·' | '•' | ':'));
+    if BROWSER_TITLE_SUFFIXES.contains(&trimmed.to_lowercase().as_str()) {
+        return String::new();
     }
-    
-    None
+    trimmed.to_string()
+}
+
+/// Fields pulled out of a terminal window title by a `terminal_title_rules` regex
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TerminalTitle {
+    pub program: Option<String>,
+    pub path: Option<String>,
+    pub host: Option<String>,
+}
+
+/// Apply a terminal title rule. Returns None if the regex does not match;
+/// missing or empty named groups (program, path, host) are left as None.
+pub fn parse_terminal_title(regex: &Regex, title: &str) -> Option<TerminalTitle> {
+    let captures = regex.captures(title)?;
+    let group = |name: &str| {
+        captures.name(name)
+            .map(|m| m.as_str().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Some(TerminalTitle {
+        program: group("program"),
+        path: group("path"),
+        host: group("host"),
+    })
+}
+
+/// Whether `name` could be a process name as the tracker records it:
+/// lower case, with no spaces
+pub fn looks_like_process_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || c.is_uppercase())
 }
 
 pub fn get_running_apps() -> Vec<(String, String)> {
@@ -244,7 +1625,7 @@ pub fn get_running_apps() -> Vec<(String, String)> {
     {
         // Try to get running GUI applications using ps
         if let Ok(output) = Command::new("ps")
-            .args(&["-eo", "comm,pid"])
+            .args(["-eo", "comm,pid"])
             .output() {
             let output_str = String::from_utf8_lossy(&output.stdout);
             
@@ -266,7 +1647,9 @@ pub fn get_running_apps() -> Vec<(String, String)> {
                     let comm = parts[0];
                     // Filter for common GUI applications and skip system/crashpad
                     if gui_apps.iter().any(|&app| comm.contains(app)) && !skip_patterns.iter().any(|&skip| comm.contains(skip)) {
-                        let friendly_name = get_friendly_app_name(comm);
+                        let friendly_name = crate::desktop::resolve(comm)
+                            .map(|identity| identity.name)
+                            .unwrap_or_else(|| get_friendly_app_name(comm));
                         // Deduplicate by friendly name
                         if seen.insert(friendly_name.clone()) {
                             apps.push((friendly_name, comm.to_string()));
@@ -374,14 +1757,14 @@ pub fn get_open_browser_tabs() -> Vec<String> {
     {
         // Try to get all browser windows using xdotool
         if let Ok(output) = Command::new("xdotool")
-            .args(&["search", "--name", ".*"])
+            .args(["search", "--name", ".*"])
             .output() {
             
             let window_ids = String::from_utf8_lossy(&output.stdout);
             
             for window_id in window_ids.lines() {
                 if let Ok(title_output) = Command::new("xdotool")
-                    .args(&["getwindowname", window_id])
+                    .args(["getwindowname", window_id])
                     .output() {
                     
                     if title_output.status.success() {
@@ -502,4 +1885,604 @@ pub fn get_open_browser_tabs() -> Vec<String> {
     tabs.sort();
     tabs.dedup();
     tabs
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn tracking_day_is_local_and_starts_at_day_start_hour() {
+        let tz = chrono_tz::Europe::Berlin;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        // 00:30 CET on the 5th is still the 4th in UTC
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-04T23:30:00Z"), 0), day(5));
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-04T23:30:00Z"), 4), day(4));
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-05T02:59:00Z"), 4), day(4));
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-05T03:00:00Z"), 4), day(5));
+        // Hours past 23 are clamped, not wrapped
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-05T21:00:00Z"), 30), day(4));
+        assert_eq!(tracking_day_in(&tz, utc("2024-03-05T23:00:00Z"), 30), day(5));
+    }
+
+    #[test]
+    fn daily_session_names_change_at_day_start_hour() {
+        let tz = chrono_tz::Europe::Berlin;
+        let name = |at: &str, hour| daily_session_name_in(&tz, utc(at), hour);
+        // Midnight local time, an hour before midnight UTC
+        assert_eq!(name("2024-05-21T21:59:59Z", 0), "2024-05-21");
+        assert_eq!(name("2024-05-21T22:00:00Z", 0), "2024-05-22");
+        // With day_start_hour = 4 the night still belongs to the day before
+        assert_eq!(name("2024-05-22T01:59:59Z", 4), "2024-05-21");
+        assert_eq!(name("2024-05-22T02:00:00Z", 4), "2024-05-22");
+        // Across the switch to summer time (02:00 CET → 03:00 CEST)
+        assert_eq!(name("2024-03-31T01:59:59Z", 4), "2024-03-30");
+        assert_eq!(name("2024-03-31T02:00:00Z", 4), "2024-03-31");
+        // Across the end of the year
+        assert_eq!(name("2024-12-31T23:30:00Z", 0), "2025-01-01");
+        assert_eq!(name("2024-12-31T23:30:00Z", 4), "2024-12-31");
+    }
+
+    #[test]
+    fn local_days_last_23_or_25_hours_across_dst_changes() {
+        let length = |days: &LocalDays, y, m, d| {
+            let (start, end) = days.range(NaiveDate::from_ymd_opt(y, m, d).unwrap());
+            (end - start).num_minutes()
+        };
+        let hours = |tz, y, m, d| length(&LocalDays::in_zone(timezone::Zone::Named(tz), 0), y, m, d) / 60;
+        let berlin = chrono_tz::Europe::Berlin;
+        assert_eq!(hours(berlin, 2024, 3, 30), 24);
+        assert_eq!(hours(berlin, 2024, 3, 31), 23);
+        assert_eq!(hours(berlin, 2024, 4, 1), 24);
+        assert_eq!(hours(berlin, 2024, 10, 27), 25);
+        assert_eq!(hours(berlin, 2024, 10, 28), 24);
+
+        let new_york = chrono_tz::America::New_York;
+        assert_eq!(hours(new_york, 2024, 3, 10), 23);
+        assert_eq!(hours(new_york, 2024, 11, 3), 25);
+        assert_eq!(hours(new_york, 2024, 3, 31), 24);
+
+        // Lord Howe Island moves its clocks by half an hour
+        let lord_howe = LocalDays::in_zone(timezone::Zone::Named(chrono_tz::Australia::Lord_Howe), 0);
+        assert_eq!(length(&lord_howe, 2024, 4, 7), 24 * 60 + 30);
+
+        // Zones without DST never have odd days
+        assert_eq!(hours(chrono_tz::Asia::Tokyo, 2024, 3, 31), 24);
+        // Samoa skipped 30 December 2011 entirely; the day before ran into the 31st
+        assert_eq!(hours(chrono_tz::Pacific::Apia, 2011, 12, 29), 24);
+        assert_eq!(hours(chrono_tz::Pacific::Apia, 2011, 12, 30), 0);
+
+        // 02:00 doesn't exist on 31 March, so with days starting then that
+        // day starts at 03:00, and the hour lost comes out of it
+        let berlin_at_2 = LocalDays::in_zone(timezone::Zone::Named(berlin), 2);
+        assert_eq!(length(&berlin_at_2, 2024, 3, 30) / 60, 24);
+        assert_eq!(length(&berlin_at_2, 2024, 3, 31) / 60, 23);
+        assert_eq!(berlin_at_2.start(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()), utc("2024-03-31T01:00:00Z"));
+
+        // Days are half-open: the last second before the next start is still today
+        let berlin_days = LocalDays::in_zone(timezone::Zone::Named(berlin), 0);
+        let (start, end) = berlin_days.range(NaiveDate::from_ymd_opt(2024, 10, 27).unwrap());
+        assert_eq!((start, end), (utc("2024-10-26T22:00:00Z"), utc("2024-10-27T23:00:00Z")));
+        assert_eq!(berlin_days.day_of(end - chrono::Duration::seconds(1)), NaiveDate::from_ymd_opt(2024, 10, 27).unwrap());
+        assert_eq!(berlin_days.day_of(end), NaiveDate::from_ymd_opt(2024, 10, 28).unwrap());
+    }
+
+    #[test]
+    fn self_window_is_a_terminal_running_the_cli() {
+        let none: &[String] = &[];
+        for title in [
+            "focusdebt stats",
+            "alacritty — focusdebt stats --today",
+            "user@host: ~/src$ focusdebt wrapped",
+            "~/src/app: target/debug/focusdebt export --format csv",
+            "FocusDebt status",
+        ] {
+            assert!(is_self_window("kitty", title, none), "{}", title);
+        }
+        assert!(is_self_window("WindowsTerminal.exe", "focusdebt stats", none));
+        assert!(is_self_window("my-term", "focusdebt stats", &["my-term".to_string()]));
+    }
+
+    #[test]
+    fn self_window_ignores_other_apps_and_mentions() {
+        let none: &[String] = &[];
+        // Not a terminal, whatever the title says
+        assert!(!is_self_window("firefox", "focusdebt stats - Mozilla Firefox", none));
+        assert!(!is_self_window("code", "main.rs - focusdebt - Visual Studio Code", none));
+        assert!(!is_self_window("my-term", "focusdebt stats", none));
+        for title in [
+            "focusdebt",
+            "alacritty — focusdebt",
+            "~/src/0x45o/focusdebt",
+            "vim focusdebt/README.md",
+            "vim ~/focusdebt README.md",
+            "cd focusdebt && make",
+            "focusdebtd stats",
+            "myfocusdebt stats",
+        ] {
+            assert!(!is_self_window("kitty", title, none), "{}", title);
+        }
+    }
+
+    #[test]
+    fn format_duration_matrix() {
+        let cases: &[(u64, &str, &str, &str)] = &[
+            (0, "0s", "0s", "0:00:00"),
+            (45, "45s", "45s", "0:00:45"),
+            (60, "1m 0s", "1m0s", "0:01:00"),
+            (200, "3m 20s", "3m20s", "0:03:20"),
+            (3599, "59m 59s", "59m59s", "0:59:59"),
+            (3600, "1h 0m", "1h0m", "1:00:00"),
+            (3800, "1h 3m", "1h3m", "1:03:20"),
+            (86_399, "23h 59m", "23h59m", "23:59:59"),
+            (86_400, "1d 0h", "1d0h", "1d 0:00:00"),
+            (183_800, "2d 3h", "2d3h", "2d 3:03:20"),
+            (u64::MAX, "213503982334601d 7h", "213503982334601d7h", "213503982334601d 7:00:15"),
+        ];
+        for &(seconds, long, compact, clock) in cases {
+            let duration = Duration::from_secs(seconds);
+            assert_eq!(format_duration(duration, DurationStyle::Long), long);
+            assert_eq!(format_duration(duration, DurationStyle::Compact), compact);
+            assert_eq!(format_duration(duration, DurationStyle::Clock), clock);
+        }
+        // Sub-second parts are dropped
+        assert_eq!(format_duration(Duration::from_millis(59_999), DurationStyle::Compact), "59s");
+    }
+
+    #[test]
+    fn parse_duration_matrix() {
+        let cases: &[(&str, u64)] = &[
+            ("0", 0),
+            ("25", 1500),
+            (" 25 ", 1500),
+            ("45s", 45),
+            ("30m", 1800),
+            ("1h", 3600),
+            ("1H30M", 5400),
+            ("1h30m", 5400),
+            ("1h 30m", 5400),
+            ("2d", 172_800),
+            ("1d2h3m4s", 93_784),
+            ("90m", 5400),
+            ("1m1m", 120),
+            ("0h", 0),
+        ];
+        for &(input, seconds) in cases {
+            assert_eq!(parse_duration(input), Some(Duration::from_secs(seconds)), "{}", input);
+        }
+        for input in ["", "  ", "h", "1x", "1h30", "-5", "1.5h", "m30", "1h-30m"] {
+            assert_eq!(parse_duration(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        let max = u64::MAX.to_string();
+        assert_eq!(parse_duration(&max), None);
+        assert_eq!(parse_duration(&format!("{}d", u64::MAX / 86_400 + 1)), None);
+        assert_eq!(parse_duration(&format!("{}h", u64::MAX / 3600 + 1)), None);
+        assert_eq!(parse_duration(&format!("{}m", u64::MAX / 60 + 1)), None);
+        assert_eq!(parse_duration(&format!("{}s", max)), Some(Duration::from_secs(u64::MAX)));
+        assert_eq!(parse_duration(&format!("{}s1s", max)), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+        let max_days = u64::MAX / 86_400;
+        assert_eq!(parse_duration(&format!("{}d", max_days)), Some(Duration::from_secs(max_days * 86_400)));
+        assert!(parse_duration_arg(&format!("{}d", max_days + 1)).is_err());
+    }
+
+    #[test]
+    fn formatted_durations_parse_back() {
+        for seconds in [0, 45, 60, 200, 3599, 3600, 3780, 86_340, 86_400, 183_600] {
+            let duration = Duration::from_secs(seconds);
+            for style in [DurationStyle::Long, DurationStyle::Compact] {
+                let text = format_duration(duration, style);
+                assert_eq!(parse_duration(&text), Some(duration), "{}", text);
+            }
+        }
+        for text in ["45s", "3m20s", "1h3m", "2d3h"] {
+            assert_eq!(format_duration(parse_duration(text).unwrap(), DurationStyle::Compact), text);
+        }
+    }
+
+    /// The zsh/fish and tmux examples from the README
+    const ZSH_TITLE: &str = r"^(?P<program>[\w.-]+)(?: [^—]*)?(?: — (?P<path>.+))?$";
+    const TMUX_TITLE: &str = r"^(?P<host>[\w.-]+):\d+:(?P<program>[\w.-]+)";
+
+    fn title(pattern: &str, text: &str) -> Option<TerminalTitle> {
+        parse_terminal_title(&Regex::new(pattern).unwrap(), text)
+    }
+
+    fn fields(program: Option<&str>, path: Option<&str>, host: Option<&str>) -> Option<TerminalTitle> {
+        Some(TerminalTitle {
+            program: program.map(String::from),
+            path: path.map(String::from),
+            host: host.map(String::from),
+        })
+    }
+
+    #[test]
+    fn zsh_titles_give_the_program_and_path() {
+        assert_eq!(title(ZSH_TITLE, "nvim src/main.rs — ~/proj/api"), fields(Some("nvim"), Some("~/proj/api"), None));
+        assert_eq!(title(ZSH_TITLE, "cargo test --workspace — ~/proj/api"), fields(Some("cargo"), Some("~/proj/api"), None));
+        assert_eq!(title(ZSH_TITLE, "htop"), fields(Some("htop"), None, None));
+        assert_eq!(title(ZSH_TITLE, "python3.12 — /tmp"), fields(Some("python3.12"), Some("/tmp"), None));
+        assert_eq!(title(ZSH_TITLE, "~/proj/api"), None);
+        assert_eq!(title(ZSH_TITLE, ""), None);
+    }
+
+    #[test]
+    fn tmux_titles_give_the_host_and_program() {
+        assert_eq!(title(TMUX_TITLE, "devbox:1:nvim"), fields(Some("nvim"), None, Some("devbox")));
+        assert_eq!(title(TMUX_TITLE, "build-01.example.com:12:cargo"), fields(Some("cargo"), None, Some("build-01.example.com")));
+        assert_eq!(title(TMUX_TITLE, "devbox:nvim"), None);
+    }
+
+    #[test]
+    fn shell_prompt_titles_give_the_host_and_path_only() {
+        // bash's and oh-my-zsh's default "user@host: dir"
+        let prompt = r"^[\w.-]+@(?P<host>[\w.-]+): (?P<path>.+)$";
+        assert_eq!(title(prompt, "me@laptop: ~/proj/api"), fields(None, Some("~/proj/api"), Some("laptop")));
+        // tmux's "#S:#W - #T" with an empty window name
+        let session = r"^(?P<host>\w*):(?P<program>\S*) - (?P<path>.*)$";
+        assert_eq!(title(session, ": - ~/proj"), fields(None, Some("~/proj"), None));
+    }
+
+    #[test]
+    fn site_entries_parse_their_mode() {
+        let rule = |mode, value: &str| Ok(SiteRule { mode, value: value.to_string() });
+        assert_eq!(SiteRule::parse("domain:WWW.GitHub.com"), rule(SiteMode::Domain, "github.com"));
+        assert_eq!(SiteRule::parse(" github.com "), rule(SiteMode::Domain, "github.com"));
+        assert_eq!(SiteRule::parse("title: Jira "), rule(SiteMode::Title, "Jira"));
+        assert_eq!(SiteRule::parse(r"re:/^.*\bRFC\b.*$/"), rule(SiteMode::Regex, r"^.*\bRFC\b.*$"));
+        assert_eq!(SiteRule::parse(r"re:\bRFC\b"), rule(SiteMode::Regex, r"\bRFC\b"));
+
+        assert!(SiteRule::parse("re:/(unclosed/").unwrap_err().starts_with("invalid regex '(unclosed'"));
+        for empty in ["", "title:", "domain:  ", "re://"] {
+            assert!(SiteRule::parse(empty).is_err(), "{:?}", empty);
+        }
+
+        for entry in ["domain:github.com", "title:Jira", r"re:/\bRFC\b/"] {
+            assert_eq!(SiteRule::parse(entry).unwrap().to_entry(), entry);
+        }
+    }
+
+    #[test]
+    fn site_entries_match_titles_by_mode() {
+        let cases: &[(&str, &str, bool)] = &[
+            // A domain matches a mention or the site's own title segment
+            ("domain:github.com", "Issue #12 · GitHub — Mozilla Firefox", true),
+            ("domain:github.com", "see github.com/rust-lang/rust", true),
+            ("domain:github.com", "notgithub.com login", false),
+            ("domain:github.com", "GitHub Copilot pricing - Some Blog", false),
+            ("domain:docs.rs", "serde - Rust - Docs.rs", true),
+            ("domain:docs.rs", "Funny memes - Google Docs", false),
+            ("domain:stackoverflow.com", "How to sort - Stack Overflow", true),
+            // Title text anywhere, ignoring case
+            ("title:jira", "PROJ-12 Fix the build - JIRA", true),
+            ("title:jira", "Confluence", false),
+            ("title:docs", "Funny memes - Google Docs", true),
+            (r"re:/\bRFC\b/", "rfc 9110: HTTP Semantics", true),
+            (r"re:/\bRFC\b/", "RFCs explained", false),
+            // Broken entries match nothing
+            ("re:/(/", "(", false),
+        ];
+        for &(site, title, expected) in cases {
+            assert_eq!(title_matches_site(title, site), expected, "{} on {:?}", site, title);
+        }
+    }
+
+    #[test]
+    fn bare_site_entries_migrate_to_the_likely_mode() {
+        assert_eq!(migrate_bare_site("GitHub.com"), ("domain:github.com".to_string(), false));
+        assert_eq!(migrate_bare_site("docs.rust-lang.org"), ("domain:docs.rust-lang.org".to_string(), false));
+        // One word could have been a site name or a title word
+        assert_eq!(migrate_bare_site("jira"), ("title:jira".to_string(), true));
+        assert_eq!(migrate_bare_site(" pull request "), ("title:pull request".to_string(), false));
+        assert_eq!(migrate_bare_site("v1.2 notes"), ("title:v1.2 notes".to_string(), false));
+
+        assert!(has_site_prefix(" re:/x/"));
+        assert!(has_site_prefix("title:x") && has_site_prefix("domain:x"));
+        assert!(!has_site_prefix("github.com") && !has_site_prefix("regex:x"));
+    }
+
+    #[test]
+    fn integrations_describe_themselves_and_default_to_none() {
+        let integrations = [
+            IntegrationStatus { name: "hooks".to_string(), problem: Some("on_session_end: 'sync-notes' not found".to_string()) },
+            IntegrationStatus { name: "notifications".to_string(), problem: None },
+        ];
+        assert_eq!(describe_integrations(&integrations), "hooks ✗ (on_session_end: 'sync-notes' not found), notifications ✓");
+        assert_eq!(describe_integrations(&[]), "");
+
+        // State files from before the check read with no integrations
+        let mut state = serde_json::to_value(DaemonState::current("work")).unwrap();
+        state.as_object_mut().unwrap().remove("integrations");
+        let state: DaemonState = serde_json::from_value(state).unwrap();
+        assert!(state.integrations.is_empty());
+        let read: IntegrationStatus = serde_json::from_str(r#"{"name": "tmux"}"#).unwrap();
+        assert_eq!(read, IntegrationStatus { name: "tmux".to_string(), problem: None });
+    }
+
+    fn default_noise() -> Regex {
+        title_noise_regex(&DEFAULT_TITLE_NOISE_MARKERS.map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn editor_titles_lose_their_unsaved_marks() {
+        let noise = default_noise();
+        let normalize = |title: &str| normalize_title(title, Some(&noise));
+        for title in ["main.rs - Code", "● main.rs - Code", "main.rs ● - Code", "*main.rs - gedit", "main.rs* - gedit"] {
+            let expected = if title.contains("gedit") { "main.rs - gedit" } else { "main.rs - Code" };
+            assert_eq!(normalize(title), expected, "{:?}", title);
+        }
+        // Separators left dangling at either end go, the ones inside stay
+        assert_eq!(normalize("● — main.rs — Code"), "main.rs — Code");
+        assert_eq!(normalize("main.rs   ●   Code"), "main.rs Code");
+        // Without markers only whitespace and separators are tidied
+        assert_eq!(normalize_title("  ● main.rs -  Code ", None), "● main.rs - Code");
+    }
+
+    #[test]
+    fn browser_titles_lose_loading_and_notification_counts() {
+        let noise = default_noise();
+        let normalize = |title: &str| normalize_title(title, Some(&noise));
+        assert_eq!(normalize("(3) Inbox — Mozilla Firefox"), "Inbox — Mozilla Firefox");
+        assert_eq!(normalize("(12) Inbox — Mozilla Firefox"), "Inbox — Mozilla Firefox");
+        assert_eq!(normalize("Inbox (120) — Mozilla Firefox"), "Inbox — Mozilla Firefox");
+        assert_eq!(normalize("LOADING... Docs - Google Chrome"), "Docs - Google Chrome");
+        // Numbers that aren't a bracketed count stay
+        assert_eq!(normalize("Issue #12 · GitHub"), "Issue #12 · GitHub");
+        assert_eq!(normalize("(draft) Notes"), "(draft) Notes");
+        // Nothing but noise and the browser's name is a placeholder
+        for title in ["Loading…", "Loading… — Mozilla Firefox", "(2) - Google Chrome", "", " ● "] {
+            assert_eq!(normalize(title), "", "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn noise_markers_are_literal_but_for_numbers() {
+        let markers = |markers: &[&str]| title_noise_regex(&markers.iter().map(|marker| marker.to_string()).collect::<Vec<_>>());
+        assert!(markers(&[]).is_none());
+        assert!(markers(&["", "  "]).is_none());
+
+        let noise = markers(&["[modified]", "[#/#]", " + "]).unwrap();
+        let normalize = |title: &str| normalize_title(title, Some(&noise));
+        assert_eq!(normalize("notes.txt [Modified] - fd-notes"), "notes.txt - fd-notes");
+        assert_eq!(normalize("Build [3/10] - fd-term"), "Build - fd-term");
+        assert_eq!(normalize("Build [a/b] - fd-term"), "Build [a/b] - fd-term");
+        // Markers are trimmed, and the defaults no longer apply
+        assert_eq!(normalize("a+b ● *"), "a b ● *");
+    }
+
+    #[test]
+    fn heartbeats_go_stale_after_three_missed_saves() {
+        let beat = utc("2024-05-21T09:00:00Z");
+        let heartbeat = |save_interval_ms| Heartbeat {
+            pid: 4242,
+            session_name: "fd-writing".to_string(),
+            at: beat,
+            save_interval_ms,
+            display: None,
+            wayland_display: None,
+            reported_at: None,
+        };
+        let seconds = |n| chrono::Duration::seconds(n);
+
+        // Three saves of a minute
+        let slow = heartbeat(60_000);
+        assert_eq!(slow.stale_after(), Duration::from_secs(180));
+        assert_eq!(slow.silent_for(beat + seconds(180)), None);
+        assert_eq!(slow.silent_for(beat + seconds(181)), Some(Duration::from_secs(181)));
+
+        // Never sooner than two minutes, however often it saves
+        for save_interval_ms in [0, 1000, 30_000, 40_000] {
+            let fast = heartbeat(save_interval_ms);
+            assert_eq!(fast.stale_after(), Duration::from_secs(120), "{}", save_interval_ms);
+            assert_eq!(fast.silent_for(beat + seconds(120)), None);
+            assert_eq!(fast.silent_for(beat + seconds(121)), Some(Duration::from_secs(121)));
+        }
+
+        // A clock that went backwards isn't a dead daemon
+        assert_eq!(slow.silent_for(beat - seconds(3600)), None);
+
+        // Heartbeats written before reports were tracked still read
+        let old = r#"{"pid":1,"session_name":"s","at":"2024-05-21T09:00:00Z","save_interval_ms":30000,"display":null,"wayland_display":null}"#;
+        let read: Heartbeat = serde_json::from_str(old).unwrap();
+        assert_eq!((read.at, read.reported_at), (beat, None));
+    }
+
+    #[test]
+    fn past_times_read_as_the_latest_one() {
+        let local = |day: u32, hour: u32, minute: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(hour, minute, 0).unwrap();
+        // Wednesday noon on the local clock
+        let now = zone().from_local_datetime(&local(22, 12, 0)).earliest().unwrap().with_timezone(&Utc);
+        let past = |input: &str| parse_past_when(input, now).map(|time| time.with_timezone(&zone()).naive_local());
+        assert_eq!(past("10:00"), Some(local(22, 10, 0)));
+        assert_eq!(past("12:00"), Some(local(22, 12, 0)));
+        assert_eq!(past("14:00"), Some(local(21, 14, 0)));
+        assert_eq!(past("today 14:00"), Some(local(22, 14, 0)));
+        assert_eq!(past("Yesterday 09:30"), Some(local(21, 9, 30)));
+        assert_eq!(past("wed 11:00"), Some(local(22, 11, 0)));
+        assert_eq!(past("wed 13:00"), Some(local(15, 13, 0)));
+        assert_eq!(past("fri 10:00"), Some(local(17, 10, 0)));
+        assert_eq!(past("2024-05-01 08:15"), Some(local(1, 8, 15)));
+        assert_eq!(parse_past_when("2024-05-21T14:00:00Z", now), Some(DateTime::parse_from_rfc3339("2024-05-21T14:00:00Z").unwrap().with_timezone(&Utc)));
+        assert_eq!(past("someday 10:00"), None);
+        assert_eq!(past("25:00"), None);
+    }
+
+    #[test]
+    fn bars_are_exactly_as_wide_as_asked_in_either_glyph_set() {
+        assert_eq!(render_bar(0.5, 10, GlyphSet::Unicode, BarStyle::Blocks), "[■■■■■□□□□□]");
+        assert_eq!(render_bar(0.5, 10, GlyphSet::Unicode, BarStyle::Shade), "[▓▓▓▓▓░░░░░]");
+        assert_eq!(render_bar(0.5, 10, GlyphSet::Ascii, BarStyle::Blocks), "[=====-----]");
+        assert_eq!(render_bar(0.5, 10, GlyphSet::Ascii, BarStyle::Shade), "[=====-----]");
+        // Partial cells round down, and out of range fractions are clamped
+        assert_eq!(render_bar(0.99, 4, GlyphSet::Ascii, BarStyle::Blocks), "[===-]");
+        assert_eq!(render_bar(1.5, 4, GlyphSet::Ascii, BarStyle::Blocks), "[====]");
+        assert_eq!(render_bar(-1.0, 4, GlyphSet::Ascii, BarStyle::Blocks), "[----]");
+        assert_eq!(render_bar(f64::NAN, 4, GlyphSet::Ascii, BarStyle::Blocks), "[----]");
+        for glyphs in [GlyphSet::Unicode, GlyphSet::Ascii] {
+            for cells in [1, 7, 20, 30] {
+                for fraction in [0.0, 0.33, 0.5, 0.82, 1.0] {
+                    let bar = render_bar(fraction, cells, glyphs, BarStyle::Blocks);
+                    assert_eq!(bar.chars().count(), cells + 2, "{}", bar);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn process_names_are_lower_case_without_spaces() {
+        for name in ["code", "fd-editor", "org.gnome.nautilus", "python3.12", "élan"] {
+            assert!(looks_like_process_name(name), "{}", name);
+        }
+        for name in ["", "Code", "visual studio code", "fd\teditor", "ÉLAN"] {
+            assert!(!looks_like_process_name(name), "{:?}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn safe_paths_may_climb_back_but_not_out_and_must_be_absolute() {
+        assert!(is_safe_path(Path::new("/home/u/../u/.local/share")));
+        assert_eq!(normalize_path(Path::new("/home/u/../u/./.local/share")), PathBuf::from("/home/u/.local/share"));
+        assert!(is_safe_path(Path::new("/home/u/.local/share/focusdebt")));
+        assert!(!is_safe_path(Path::new("/../etc")));
+        assert!(!is_safe_path(Path::new("/home/../../etc")));
+        for relative in [".local/share", "../share", "./data", ""] {
+            assert!(!is_safe_path(Path::new(relative)), "{:?}", relative);
+        }
+        // Backslashes are plain name characters here, so this is relative
+        assert!(!is_safe_path(Path::new(r"\\server\share\focusdebt")));
+    }
+
+    #[test]
+    fn only_drive_letter_prefixes_are_local() {
+        use std::ffi::OsStr;
+        assert!(is_local_prefix(Prefix::Disk(b'C')));
+        assert!(is_local_prefix(Prefix::VerbatimDisk(b'D')));
+        assert!(!is_local_prefix(Prefix::UNC(OsStr::new("server"), OsStr::new("share"))));
+        assert!(!is_local_prefix(Prefix::VerbatimUNC(OsStr::new("server"), OsStr::new("share"))));
+        assert!(!is_local_prefix(Prefix::Verbatim(OsStr::new("GLOBALROOT"))));
+        assert!(!is_local_prefix(Prefix::DeviceNS(OsStr::new("PhysicalDrive0"))));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_data_paths_must_be_on_a_local_drive() {
+        assert!(is_safe_path(Path::new(r"C:\Users\u\..\u\AppData\Roaming")));
+        assert!(is_safe_path(Path::new(r"\\?\C:\Users\u\AppData")));
+        assert!(!is_safe_path(Path::new(r"\\server\share\focusdebt")));
+        assert!(!is_safe_path(Path::new(r"\\?\UNC\server\share\focusdebt")));
+        assert!(!is_safe_path(Path::new(r"\\.\PhysicalDrive0")));
+        assert!(!is_safe_path(Path::new(r"C:focusdebt")));
+    }
+
+    fn sanitized(raw: &str) -> String {
+        sanitize_session_name(raw, 100).unwrap().name
+    }
+
+    #[test]
+    fn session_names_lose_escape_sequences() {
+        // CSI colours and cursor movement, 7-bit and C1
+        assert_eq!(sanitized("\x1b[1;31mdeep\x1b[0m work\x1b[2J"), "deep work");
+        assert_eq!(sanitized("\u{9b}31mdeep work"), "deep work");
+        // OSC window titles ended by BEL or ST, and OSC 8 hyperlinks
+        assert_eq!(sanitized("\x1b]0;pwned\x07review"), "review");
+        assert_eq!(sanitized("\x1b]8;;https://example.com\x1b\\review\x1b]8;;\x1b\\ notes"), "review notes");
+        assert_eq!(sanitized("\u{9d}0;pwned\u{9c}review"), "review");
+        // Charset switches, bare control bytes and bidi overrides
+        assert_eq!(sanitized("\x1b(Bplan\x07ning\x00"), "planning");
+        assert_eq!(sanitized("invoice \u{202e}fdp.exe"), "invoice fdp.exe");
+        let name = sanitize_session_name("\x1b[31mdeep work", 100).unwrap();
+        assert!(name.stripped && !name.cut);
+        assert!(!sanitize_session_name("deep work", 100).unwrap().stripped);
+    }
+
+    #[test]
+    fn session_names_fold_line_breaks_and_keep_emoji() {
+        assert_eq!(sanitized("deep\nwork\r\n"), "deep work");
+        assert_eq!(sanitized("  deep\r\n\r\n\twork  "), "deep work");
+        for name in ["🚀 launch prep", "日本語のレビュー", "café résumé", "👩‍💻 pairing", "🇳🇱 sync"] {
+            assert_eq!(sanitized(name), name);
+        }
+        assert_eq!(sanitized("\x1b[32m🚀\x1b[0m launch"), "🚀 launch");
+    }
+
+    #[test]
+    fn session_names_are_cut_on_a_char_boundary() {
+        let name = sanitize_session_name("日本語のレビュー会議", 5).unwrap();
+        assert_eq!((name.name.as_str(), name.cut), ("日本語のレ", true));
+        assert_eq!(sanitize_session_name("🚀🚀🚀🚀", 3).unwrap().name, "🚀🚀🚀");
+        // A cut that lands after a space doesn't keep it
+        assert_eq!(sanitize_session_name("deep work", 5).unwrap().name, "deep");
+        // Escapes removed before counting
+        assert_eq!(sanitize_session_name("\x1b[1;31mabc", 3).unwrap(), SessionName { name: "abc".to_string(), stripped: true, cut: false });
+        let long = "x".repeat(5000);
+        assert_eq!(sanitize_session_name(&long, 100).unwrap().name.len(), 100);
+        // 0 is no limit
+        assert_eq!(sanitize_session_name(&long, 0).unwrap().name.len(), 5000);
+    }
+
+    #[test]
+    fn session_names_empty_once_sanitized_are_refused() {
+        assert_eq!(sanitize_session_name("\x1b[2J\x1b]0;x\x07\x07\r\n", 100),
+            Err("Session name is empty once control characters are removed".to_string()));
+        assert_eq!(sanitize_session_name(" \n\t ", 100), Err("Session name cannot be empty".to_string()));
+        assert_eq!(sanitize_session_name("", 100), Err("Session name cannot be empty".to_string()));
+    }
+
+    #[test]
+    fn stored_control_characters_are_shown_as_escapes() {
+        assert!(matches!(escape_control_chars("deep work 🚀"), std::borrow::Cow::Borrowed("deep work 🚀")));
+        assert_eq!(escape_control_chars("\x1b[2Jdeep\nwork\r\x07"), "\\u{1b}[2Jdeep\\u{a}work\\u{d}\\u{7}");
+        assert_eq!(escape_control_chars("\u{9b}31m"), "\\u{9b}31m");
+        assert_eq!(escape_control_chars("invoice \u{202e}fdp.exe"), "invoice \\u{202e}fdp.exe");
+        // Nothing left that a terminal acts on
+        assert!(!escape_control_chars("\x1b]0;pwned\x07").chars().any(|c| c.is_control()));
+    }
+
+    #[test]
+    fn clock_skew_is_checked_both_ways_past_the_limit() {
+        let seen = Some(utc("2026-03-01T12:00:00Z"));
+        assert_eq!(clock_skew(utc("2026-03-31T12:00:00Z"), seen, 30), None);
+        assert_eq!(clock_skew(utc("2026-01-30T12:00:00Z"), seen, 30), None);
+        assert_eq!(clock_skew(utc("2026-03-31T12:00:01Z"), seen, 30), Some(chrono::Duration::seconds(30 * 86_400 + 1)));
+        assert_eq!(clock_skew(utc("2026-01-30T11:59:59Z"), seen, 30), Some(chrono::Duration::seconds(-(30 * 86_400 + 1))));
+        // Off, or nothing seen yet
+        assert_eq!(clock_skew(utc("2036-03-01T12:00:00Z"), seen, 0), None);
+        assert_eq!(clock_skew(utc("2036-03-01T12:00:00Z"), None, 30), None);
+    }
+
+    #[test]
+    fn strftime_formats_are_validated_when_set() {
+        for format in [DEFAULT_DATE_FORMAT, "%d.%m.", "%m/%d", "%Y-%m-%d", "%a %-d %B", "Week %V", "100%%"] {
+            assert_eq!(validate_strftime(format), Ok(()), "{}", format);
+        }
+        // Unknown specifiers, a dangling %, a bad modifier, nothing at all
+        for format in ["%Q", "%d.%m.%", "%d %!", "%-", ""] {
+            assert!(validate_strftime(format).is_err(), "{} was accepted", format);
+        }
+        assert_eq!(validate_strftime("%Q"), Err("'%Q' is not a valid strftime format".to_string()));
+        assert_eq!(validate_strftime("  "), Err("the format is empty".to_string()));
+    }
+
+    #[test]
+    fn titles_are_capped_on_character_boundaries() {
+        // "日" is bytes 18-20, so slicing 19 bytes would split it
+        let title = "Meeting notes — 日本語の議事録.md".to_string();
+        assert!(!title.is_char_boundary(19));
+        let capped = cap_title(title.clone(), 19);
+        assert_eq!(capped, "Meeting notes — 日本…");
+        assert_eq!(capped.chars().count(), 19);
+        // A four-byte emoji right at the cut
+        assert_eq!(cap_title("Release 🎉🎉 party".to_string(), 10), "Release 🎉…");
+        // Longer in bytes than the cap, but not in characters: kept whole
+        assert_eq!(cap_title("Ünïcödé ärgér".to_string(), 13), "Ünïcödé ärgér");
+        assert_eq!(cap_title(title.clone(), 0), title);
+    }
+}
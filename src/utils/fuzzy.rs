@@ -0,0 +1,225 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+/// Default fuzzy_min_score: below this a candidate doesn't match at all.
+/// Skim scores grow with the query, about 16 a matched character plus
+/// bonuses, so 40 asks for a few characters in a sensible place.
+pub const DEFAULT_MIN_SCORE: i64 = 40;
+
+/// Default fuzzy_ambiguity_margin: a runner-up this close to the best
+/// candidate makes the match ambiguous
+pub const DEFAULT_AMBIGUITY_MARGIN: i64 = 5;
+
+static MIN_SCORE: AtomicI64 = AtomicI64::new(DEFAULT_MIN_SCORE);
+static AMBIGUITY_MARGIN: AtomicI64 = AtomicI64::new(DEFAULT_AMBIGUITY_MARGIN);
+
+/// Apply the configured fuzzy_min_score and fuzzy_ambiguity_margin
+pub fn set_thresholds(min_score: i64, ambiguity_margin: i64) {
+    MIN_SCORE.store(min_score, Ordering::Relaxed);
+    AMBIGUITY_MARGIN.store(ambiguity_margin.max(0), Ordering::Relaxed);
+}
+
+/// A candidate and its best score over its names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranked<T> {
+    pub item: T,
+    pub score: i64,
+}
+
+/// What a query picks out of the candidates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FuzzyMatch<T> {
+    /// Nothing scored fuzzy_min_score
+    None,
+    /// One candidate, exactly named or ahead of the rest by more than the margin
+    Unique(Ranked<T>),
+    /// Candidates within the margin of the best, best first
+    Ambiguous(Vec<Ranked<T>>),
+}
+
+/// Candidates scoring at least fuzzy_min_score against `query`, best first
+/// (ties in candidate order). `names` gives each candidate's names, e.g. an
+/// app's display name and process; its best one counts. Equal items are
+/// ranked once.
+pub fn rank<T: Clone + PartialEq, N: AsRef<str>>(
+    query: &str,
+    candidates: &[T],
+    names: impl Fn(&T) -> Vec<N>,
+) -> Vec<Ranked<T>> {
+    rank_with(query, candidates, names, MIN_SCORE.load(Ordering::Relaxed))
+}
+
+fn rank_with<T: Clone + PartialEq, N: AsRef<str>>(
+    query: &str,
+    candidates: &[T],
+    names: impl Fn(&T) -> Vec<N>,
+    min_score: i64,
+) -> Vec<Ranked<T>> {
+    let matcher = SkimMatcherV2::default();
+    let mut ranked: Vec<Ranked<T>> = Vec::new();
+    for candidate in candidates {
+        let Some(score) = names(candidate).iter().filter_map(|name| matcher.fuzzy_match(name.as_ref(), query)).max() else { continue };
+        if score < min_score {
+            continue;
+        }
+        match ranked.iter_mut().find(|ranked| ranked.item == *candidate) {
+            Some(existing) => existing.score = existing.score.max(score),
+            None => ranked.push(Ranked { item: candidate.clone(), score }),
+        }
+    }
+    // Stable, so equal scores keep candidate order
+    ranked.sort_by_key(|ranked| std::cmp::Reverse(ranked.score));
+    ranked
+}
+
+/// The candidate `query` means. A name equal to the query (ignoring case)
+/// wins outright; otherwise the best fuzzy match, unless the runner-up is
+/// within fuzzy_ambiguity_margin.
+pub fn best<T: Clone + PartialEq, N: AsRef<str>>(
+    query: &str,
+    candidates: &[T],
+    names: impl Fn(&T) -> Vec<N>,
+) -> FuzzyMatch<T> {
+    best_with(query, candidates, names, MIN_SCORE.load(Ordering::Relaxed), AMBIGUITY_MARGIN.load(Ordering::Relaxed))
+}
+
+fn best_with<T: Clone + PartialEq, N: AsRef<str>>(
+    query: &str,
+    candidates: &[T],
+    names: impl Fn(&T) -> Vec<N>,
+    min_score: i64,
+    margin: i64,
+) -> FuzzyMatch<T> {
+    let query = query.trim();
+    if let Some(exact) = candidates.iter().find(|candidate| names(candidate).iter().any(|name| name.as_ref().eq_ignore_ascii_case(query))) {
+        return FuzzyMatch::Unique(Ranked { item: exact.clone(), score: i64::MAX });
+    }
+    let ranked = rank_with(query, candidates, names, min_score);
+    let Some(top) = ranked.first() else {
+        return FuzzyMatch::None;
+    };
+    let close: Vec<Ranked<T>> = ranked.iter()
+        .take_while(|ranked| top.score - ranked.score <= margin)
+        .cloned()
+        .collect();
+    if close.len() > 1 {
+        FuzzyMatch::Ambiguous(close)
+    } else {
+        FuzzyMatch::Unique(top.clone())
+    }
+}
+
+/// "'a', 'b' or 'c'", for "did you mean" hints
+pub fn describe_candidates<T>(candidates: &[Ranked<T>], name: impl Fn(&T) -> String) -> String {
+    let names: Vec<String> = candidates.iter().map(|ranked| format!("'{}'", name(&ranked.item))).collect();
+    match names.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APPS: [&str; 8] = ["fd-editor", "fd-chat", "fd-term", "firefox", "firefox-esr", "fd-firefox", "Code", "code-insiders"];
+
+    fn names(app: &&str) -> Vec<String> {
+        vec![app.to_string()]
+    }
+
+    fn items<'a>(ranked: &[Ranked<&'a str>]) -> Vec<&'a str> {
+        ranked.iter().map(|ranked| ranked.item).collect()
+    }
+
+    #[test]
+    fn ranking_is_best_first_and_drops_weak_matches() {
+        // firefox and firefox-esr tie and keep candidate order
+        let ranked = rank_with("fire", &APPS, names, DEFAULT_MIN_SCORE);
+        assert_eq!(items(&ranked), ["firefox", "firefox-esr", "fd-firefox"]);
+        assert_eq!(ranked[0].score, ranked[1].score);
+        assert!(ranked[1].score > ranked[2].score);
+
+        // Two letters scattered through a name score too little
+        assert_eq!(items(&rank_with("ed", &APPS, names, DEFAULT_MIN_SCORE)), ["fd-editor"]);
+        assert_eq!(items(&rank_with("ed", &APPS, names, 0)), ["fd-editor", "code-insiders"]);
+        assert!(rank_with("ed", &APPS, names, 100).is_empty());
+        assert!(rank_with("xyz", &APPS, names, i64::MIN).is_empty());
+    }
+
+    #[test]
+    fn a_candidates_best_name_counts_and_repeats_rank_once() {
+        let processes = ["fd-editor", "fd-chat", "fd-editor"];
+        let display = |process: &&str| match *process {
+            "fd-editor" => vec!["fd-editor".to_string(), "Writer".to_string()],
+            other => vec![other.to_string()],
+        };
+        let ranked = rank_with("writer", &processes, display, DEFAULT_MIN_SCORE);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].item, "fd-editor");
+        assert_eq!(rank_with("edit", &processes, display, DEFAULT_MIN_SCORE).len(), 1);
+    }
+
+    #[test]
+    fn an_exact_name_wins_outright() {
+        // code-insiders scores higher for "code", but Code is what was typed
+        assert!(rank_with("code", &APPS, names, DEFAULT_MIN_SCORE)[0].item == "code-insiders");
+        for query in ["code", " CODE ", "Code"] {
+            assert_eq!(best_with(query, &APPS, names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN),
+                FuzzyMatch::Unique(Ranked { item: "Code", score: i64::MAX }), "{:?}", query);
+        }
+        // Even when the runner-ups would tie
+        assert_eq!(best_with("firefox", &APPS, names, DEFAULT_MIN_SCORE, 100),
+            FuzzyMatch::Unique(Ranked { item: "firefox", score: i64::MAX }));
+    }
+
+    #[test]
+    fn runner_ups_within_the_margin_make_it_ambiguous() {
+        let Ranked { score: chat, .. } = rank_with("chat", &APPS, names, DEFAULT_MIN_SCORE)[0].clone();
+        assert_eq!(best_with("chat", &APPS, names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN),
+            FuzzyMatch::Unique(Ranked { item: "fd-chat", score: chat }));
+
+        // "cod" puts code-insiders two points ahead of Code
+        let FuzzyMatch::Ambiguous(close) = best_with("cod", &APPS, names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN) else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(items(&close), ["code-insiders", "Code"]);
+        let FuzzyMatch::Unique(top) = best_with("cod", &APPS, names, DEFAULT_MIN_SCORE, 1) else {
+            panic!("expected a unique match");
+        };
+        assert_eq!(top.item, "code-insiders");
+
+        // Only candidates within the margin are offered
+        let FuzzyMatch::Ambiguous(close) = best_with("fire", &APPS, names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN) else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(items(&close), ["firefox", "firefox-esr"]);
+        let FuzzyMatch::Ambiguous(close) = best_with("fire", &APPS, names, DEFAULT_MIN_SCORE, 20) else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(items(&close), ["firefox", "firefox-esr", "fd-firefox"]);
+        // Ties stay ambiguous with no margin at all
+        assert!(matches!(best_with("fire", &APPS, names, DEFAULT_MIN_SCORE, 0), FuzzyMatch::Ambiguous(close) if close.len() == 2));
+    }
+
+    #[test]
+    fn nothing_above_the_minimum_is_no_match() {
+        assert_eq!(best_with("xyz", &APPS, names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN), FuzzyMatch::None);
+        assert_eq!(best_with("chat", &APPS, names, 200, DEFAULT_AMBIGUITY_MARGIN), FuzzyMatch::None);
+        assert_eq!(best_with("chat", &[] as &[&str], names, DEFAULT_MIN_SCORE, DEFAULT_AMBIGUITY_MARGIN), FuzzyMatch::None);
+    }
+
+    #[test]
+    fn candidates_read_as_a_list() {
+        let ranked = |items: &[&'static str]| -> Vec<Ranked<&'static str>> {
+            items.iter().map(|item| Ranked { item: *item, score: 50 }).collect()
+        };
+        let describe = |items: &[&'static str]| describe_candidates(&ranked(items), |item| item.to_string());
+        assert_eq!(describe(&[]), "");
+        assert_eq!(describe(&["firefox"]), "'firefox'");
+        assert_eq!(describe(&["firefox", "Code"]), "'firefox' or 'Code'");
+        assert_eq!(describe(&["firefox", "Code", "fd-chat"]), "'firefox', 'Code' or 'fd-chat'");
+    }
+}
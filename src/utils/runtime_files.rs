@@ -0,0 +1,564 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::{active_profile, check_process_exists, get_data_directory, is_safe_path};
+
+/// Runtime files younger than this are never stale: a daemon that just
+/// exited may still have `stop` reading its flush marker
+pub const STALE_GRACE: Duration = Duration::from_secs(60);
+
+/// Rotated spill journals nothing replayed for this long are given up on
+pub const STALE_JOURNAL_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Files `export --open` wrote for the viewer are done with after a day
+pub const STALE_OPENED_EXPORT_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A dead daemon's heartbeat stays for `heartbeat-check` to report; after
+/// a week nothing is going to
+pub const STALE_HEARTBEAT_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Runtime files live in the shared data directory and carry the profile as
+/// a suffix (focusdebt-work.pid), so daemons for different profiles can run
+/// side by side.
+pub fn runtime_file(extension: &str) -> Option<PathBuf> {
+    let name = match active_profile() {
+        Some(profile) => format!("focusdebt-{}.{}", profile, extension),
+        None => format!("focusdebt.{}", extension),
+    };
+    get_data_directory().map(|dir| dir.join(name))
+}
+
+/// $XDG_RUNTIME_DIR/focusdebt when the session has a runtime directory,
+/// otherwise the data directory. Either way it belongs to this user alone;
+/// nothing is ever put in the shared temp directory.
+pub fn runtime_directory() -> Option<PathBuf> {
+    runtime_directory_in(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from)).or_else(get_data_directory)
+}
+
+/// `runtime_dir`/focusdebt, created private, when `runtime_dir` is a usable
+/// runtime directory
+fn runtime_directory_in(runtime_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = runtime_dir
+        .filter(|dir| is_safe_path(dir) && dir.is_dir())?
+        .join("focusdebt");
+    create_private_dir(&dir).ok().map(|()| dir)
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// Files older versions kept in the shared temp directory: the daemon log,
+/// the debug log and the spill journal. Only files this user owns are
+/// listed; another user's are theirs to clean up.
+pub fn legacy_temp_files() -> Vec<PathBuf> {
+    legacy_files_in(&std::env::temp_dir())
+}
+
+fn legacy_files_in(tmp: &Path) -> Vec<PathBuf> {
+    [tmp.join("focusdebt_daemon.log"), tmp.join("focusdebt_debug.log"), crate::spill::journal_path(tmp)]
+        .into_iter()
+        .filter(|path| is_own_file(path))
+        .collect()
+}
+
+fn is_own_file(path: &Path) -> bool {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Safety: getuid has no preconditions and can't fail
+        metadata.is_file() && metadata.uid() == unsafe { libc::getuid() }
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+/// Options for opening a file only its owner can read (0600 on unix),
+/// with create set; add append or truncate as needed
+pub fn private_file_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+/// Write `content` to `path` as a private file, via a temporary file and a
+/// rename so readers never see half of it. The data is synced before the
+/// rename, so a crash leaves the old content or the new, never an empty file.
+pub fn write_private_file(path: &Path, content: impl AsRef<[u8]>) -> std::io::Result<()> {
+    use std::io::Write;
+    let tmp = partial_path(path);
+    // The mode only applies on creation, so never reuse a leftover
+    let _ = fs::remove_file(&tmp);
+    let mut file = private_file_options().write(true).truncate(true).open(&tmp)?;
+    file.write_all(content.as_ref())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(tmp, path)?;
+    sync_directory(path);
+    Ok(())
+}
+
+/// Where `write_private_file` stages `path`
+fn partial_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Make a rename in `path`'s directory durable. Best effort: some
+/// filesystems can't sync a directory, and the rename happened either way.
+fn sync_directory(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().and_then(|dir| fs::File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Whether the process whose PID is in the file at `path` is alive
+pub fn pid_file_alive(path: &Path) -> bool {
+    let Ok(content) = fs::read_to_string(path) else {
+        return false;
+    };
+    // Only digits, in a sane range; anything else is no process of ours
+    let content = content.trim();
+    if content.is_empty() || !content.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    match content.parse::<u32>() {
+        Ok(pid) if pid > 0 && pid < 1000000 => check_process_exists(pid),
+        _ => false,
+    }
+}
+
+/// `path.<n>`: the n-th older copy of a capped file, higher is older
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", n));
+    PathBuf::from(rotated)
+}
+
+/// Whether appending `incoming` bytes would take the file at `path` past
+/// `max_bytes` (0 for no cap). An empty or missing file never needs it, so
+/// one write larger than the cap still goes in.
+pub fn needs_rotation(path: &Path, incoming: u64, max_bytes: u64) -> bool {
+    let size = fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+    max_bytes > 0 && size > 0 && size + incoming > max_bytes
+}
+
+/// Shift `path` to `path.1`, `path.1` to `path.2` and so on up to
+/// `path.<keep>`, whose old content is overwritten. With `keep` 0 the file
+/// is simply removed.
+pub fn rotate(path: &Path, keep: usize) -> std::io::Result<()> {
+    if keep == 0 {
+        return fs::remove_file(path);
+    }
+    for n in (1..keep).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            fs::rename(from, rotated_path(path, n + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))?;
+    sync_directory(path);
+    Ok(())
+}
+
+/// What a runtime file is for, which decides when it goes stale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeKind {
+    /// The daemon's PID file
+    PidFile,
+    /// Held by a running export (`ExportMarker`)
+    ExportMarker,
+    /// State, live snapshot, flush marker or debug snapshot of a daemon
+    DaemonFile,
+    /// The daemon's heartbeat, kept after a crash for `heartbeat-check`
+    Heartbeat,
+    /// The daemon log, trimmed by `logs clear` rather than here
+    Log,
+    /// A `write_private_file` staging file a crash left behind
+    Partial,
+    /// The spill journal rows wait in
+    Journal,
+    /// An older part of the spill journal, replayed before it
+    RotatedJournal,
+    /// A file `export --open` wrote for the viewer
+    OpenedExport,
+}
+
+impl RuntimeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuntimeKind::PidFile => "daemon PID",
+            RuntimeKind::ExportMarker => "export lock",
+            RuntimeKind::DaemonFile => "daemon state",
+            RuntimeKind::Heartbeat => "daemon heartbeat",
+            RuntimeKind::Log => "daemon log",
+            RuntimeKind::Partial => "partial write",
+            RuntimeKind::Journal => "spill journal",
+            RuntimeKind::RotatedJournal => "rotated spill journal",
+            RuntimeKind::OpenedExport => "opened export",
+        }
+    }
+
+    /// Kind of a runtime file by its extension; None for files that aren't one
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "pid" => Some(RuntimeKind::PidFile),
+            "export" => Some(RuntimeKind::ExportMarker),
+            "state" | "live" | "flushed" | "debug" => Some(RuntimeKind::DaemonFile),
+            "heartbeat" => Some(RuntimeKind::Heartbeat),
+            "log" => Some(RuntimeKind::Log),
+            _ => None,
+        }
+    }
+}
+
+/// Why a file of `kind` is stale, or None while it may still be in use.
+/// `owner_running` is whether the process it belongs to is alive and `age`
+/// how long since it was last written.
+pub fn staleness(kind: RuntimeKind, owner_running: bool, age: Duration) -> Option<&'static str> {
+    if age < STALE_GRACE {
+        return None;
+    }
+    match kind {
+        RuntimeKind::PidFile => (!owner_running).then_some("its process is gone"),
+        RuntimeKind::ExportMarker => (!owner_running).then_some("the export holding it is gone"),
+        RuntimeKind::DaemonFile => (!owner_running).then_some("no daemon is running"),
+        RuntimeKind::Heartbeat => (!owner_running && age >= STALE_HEARTBEAT_AGE).then_some("its daemon died over a week ago"),
+        RuntimeKind::Partial => (!owner_running).then_some("left half-written"),
+        RuntimeKind::RotatedJournal => (!owner_running && age >= STALE_JOURNAL_AGE).then_some("not replayed in 30 days"),
+        RuntimeKind::OpenedExport => (age >= STALE_OPENED_EXPORT_AGE).then_some("opened over a day ago"),
+        RuntimeKind::Log | RuntimeKind::Journal => None,
+    }
+}
+
+/// A file focusdebt keeps beside the database while it runs
+#[derive(Debug, Clone)]
+pub struct RuntimeFile {
+    pub path: PathBuf,
+    pub kind: RuntimeKind,
+    pub size: u64,
+    /// Time since it was last written
+    pub age: Duration,
+    /// Whether the process it belongs to is running
+    pub owner_running: bool,
+}
+
+impl RuntimeFile {
+    fn new(path: PathBuf, kind: RuntimeKind, owner_running: bool, now: SystemTime) -> Option<Self> {
+        let metadata = fs::symlink_metadata(&path).ok().filter(|metadata| metadata.is_file())?;
+        let age = metadata.modified().ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        Some(RuntimeFile { path, kind, size: metadata.len(), age, owner_running })
+    }
+
+    pub fn stale_reason(&self) -> Option<&'static str> {
+        staleness(self.kind, self.owner_running, self.age)
+    }
+}
+
+/// Runtime files of every profile in the data directory, the spill journal
+/// at `journal` with its rotated parts (`journal_rotations` of them), and
+/// files `export --open` left in the runtime directory; sorted by path
+pub fn scan(journal: &Path, journal_rotations: usize) -> Vec<RuntimeFile> {
+    let now = SystemTime::now();
+    let daemon_running = super::is_daemon_running();
+    let mut files = Vec::new();
+
+    if let Some(data_dir) = get_data_directory() {
+        for path in file_names(&data_dir) {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else { continue };
+            let (name, partial) = match name.strip_suffix(".tmp") {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let Some((stem, extension)) = name.rsplit_once('.') else { continue };
+            let Some(kind) = RuntimeKind::from_extension(extension) else { continue };
+            if !is_runtime_stem(stem) {
+                continue;
+            }
+            // The daemon of this file's profile, or the process in the file
+            let owner_running = match kind {
+                RuntimeKind::PidFile | RuntimeKind::ExportMarker if !partial => pid_file_alive(&path),
+                _ => pid_file_alive(&data_dir.join(format!("{}.pid", stem))),
+            };
+            let kind = if partial { RuntimeKind::Partial } else { kind };
+            files.extend(RuntimeFile::new(path, kind, owner_running, now));
+        }
+    }
+
+    files.extend(RuntimeFile::new(journal.to_path_buf(), RuntimeKind::Journal, daemon_running, now));
+    for n in 1..=journal_rotations {
+        files.extend(RuntimeFile::new(rotated_path(journal, n), RuntimeKind::RotatedJournal, daemon_running, now));
+    }
+
+    if let Some(runtime_dir) = runtime_directory() {
+        for path in file_names(&runtime_dir) {
+            let opened_export = path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("focusdebt-daily-"));
+            if opened_export {
+                files.extend(RuntimeFile::new(path, RuntimeKind::OpenedExport, false, now));
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files.dedup_by(|a, b| a.path == b.path);
+    files
+}
+
+/// "focusdebt" or "focusdebt-<profile>"
+fn is_runtime_stem(stem: &str) -> bool {
+    match stem.strip_prefix("focusdebt") {
+        Some("") => true,
+        Some(rest) => rest.strip_prefix('-').is_some_and(super::is_valid_profile_name),
+        None => false,
+    }
+}
+
+fn file_names(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.filter_map(Result::ok).map(|entry| entry.path()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("focusdebt-runtime-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn runtime_directory_needs_a_usable_runtime_dir() {
+        let scratch = Scratch::new("xdg");
+        let dir = runtime_directory_in(Some(scratch.0.clone())).unwrap();
+        assert_eq!(dir, scratch.0.join("focusdebt"));
+        assert!(dir.is_dir());
+        #[cfg(unix)]
+        assert_eq!(mode(&dir), 0o700);
+        // Already there is fine
+        assert_eq!(runtime_directory_in(Some(scratch.0.clone())), Some(dir.clone()));
+
+        assert_eq!(runtime_directory_in(None), None);
+        assert_eq!(runtime_directory_in(Some(PathBuf::from("relative/run"))), None);
+        assert_eq!(runtime_directory_in(Some(scratch.0.join("missing"))), None);
+        // ".." that stays inside the root is fine; climbing past it isn't
+        let climbed = runtime_directory_in(Some(scratch.0.join("focusdebt").join(".."))).unwrap();
+        assert_eq!(crate::utils::normalize_path(&climbed), dir);
+        assert_eq!(runtime_directory_in(Some(PathBuf::from("/.."))), None);
+    }
+
+    #[test]
+    fn legacy_files_are_only_this_users_regular_files() {
+        let scratch = Scratch::new("legacy");
+        assert!(legacy_files_in(&scratch.0).is_empty());
+
+        fs::write(scratch.0.join("focusdebt_daemon.log"), "old log\n").unwrap();
+        fs::create_dir(scratch.0.join("focusdebt_debug.log")).unwrap();
+        assert_eq!(legacy_files_in(&scratch.0), [scratch.0.join("focusdebt_daemon.log")]);
+
+        // A link planted in the shared directory is not followed
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(scratch.0.join("focusdebt_daemon.log"), crate::spill::journal_path(&scratch.0)).unwrap();
+            assert_eq!(legacy_files_in(&scratch.0).len(), 1);
+        }
+    }
+
+    #[test]
+    fn private_files_are_owner_only_and_replaced_whole() {
+        let scratch = Scratch::new("private");
+        let path = scratch.0.join("focusdebt.state");
+        write_private_file(&path, "first").unwrap();
+        write_private_file(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert!(!partial_path(&path).exists());
+
+        let log = scratch.0.join("focusdebt.log");
+        drop(private_file_options().append(true).open(&log).unwrap());
+        #[cfg(unix)]
+        {
+            assert_eq!(mode(&path), 0o600);
+            assert_eq!(mode(&log), 0o600);
+        }
+    }
+
+    #[test]
+    fn runtime_files_are_known_by_stem_and_extension() {
+        assert!(is_runtime_stem("focusdebt"));
+        assert!(is_runtime_stem("focusdebt-work"));
+        assert!(!is_runtime_stem("focusdebt-"));
+        assert!(!is_runtime_stem("focusdebt_daemon"));
+        assert!(!is_runtime_stem("other"));
+        assert_eq!(RuntimeKind::from_extension("live"), Some(RuntimeKind::DaemonFile));
+        assert_eq!(RuntimeKind::from_extension("log"), Some(RuntimeKind::Log));
+        assert_eq!(RuntimeKind::from_extension("db"), None);
+    }
+
+    #[test]
+    fn rotation_waits_for_the_cap_and_takes_any_single_write() {
+        let scratch = Scratch::new("cap");
+        let journal = scratch.0.join("focusdebt.spill");
+        assert_eq!(rotated_path(&journal, 2), scratch.0.join("focusdebt.spill.2"));
+
+        // Missing or empty: the write goes in however large
+        assert!(!needs_rotation(&journal, 500, 100));
+        fs::write(&journal, "").unwrap();
+        assert!(!needs_rotation(&journal, 500, 100));
+
+        fs::write(&journal, [b'x'; 60]).unwrap();
+        assert!(!needs_rotation(&journal, 40, 100));
+        assert!(needs_rotation(&journal, 41, 100));
+        // No cap
+        assert!(!needs_rotation(&journal, 1 << 40, 0));
+    }
+
+    #[test]
+    fn rotation_shifts_copies_and_drops_the_oldest() {
+        let scratch = Scratch::new("rotate");
+        let journal = scratch.0.join("focusdebt.spill");
+        let read = |n: usize| fs::read_to_string(rotated_path(&journal, n)).ok();
+
+        for content in ["first", "second", "third"] {
+            fs::write(&journal, content).unwrap();
+            rotate(&journal, 2).unwrap();
+            assert!(!journal.exists());
+        }
+        assert_eq!(read(1).as_deref(), Some("third"));
+        assert_eq!(read(2).as_deref(), Some("second"));
+        assert_eq!(read(3), None);
+
+        // A gap in the copies is fine
+        fs::remove_file(rotated_path(&journal, 1)).unwrap();
+        fs::write(&journal, "fourth").unwrap();
+        rotate(&journal, 2).unwrap();
+        assert_eq!(read(1).as_deref(), Some("fourth"));
+        assert_eq!(read(2).as_deref(), Some("second"));
+
+        // Keeping none removes the file and leaves the copies alone
+        fs::write(&journal, "fifth").unwrap();
+        rotate(&journal, 0).unwrap();
+        assert!(!journal.exists());
+        assert_eq!(read(1).as_deref(), Some("fourth"));
+        assert!(rotate(&journal, 0).is_err());
+    }
+
+    #[test]
+    fn files_go_stale_by_kind_owner_and_age() {
+        let minute = STALE_GRACE;
+        let day = Duration::from_secs(24 * 60 * 60);
+        let just_now = Duration::from_secs(5);
+
+        // Nothing is stale in its first minute, whoever owns it
+        for kind in [RuntimeKind::PidFile, RuntimeKind::Partial, RuntimeKind::OpenedExport, RuntimeKind::RotatedJournal] {
+            assert_eq!(staleness(kind, false, just_now), None, "{:?}", kind);
+        }
+
+        // Owned files last as long as their process
+        for kind in [RuntimeKind::PidFile, RuntimeKind::ExportMarker, RuntimeKind::DaemonFile, RuntimeKind::Partial] {
+            assert_eq!(staleness(kind, true, 365 * day), None, "{:?}", kind);
+            assert!(staleness(kind, false, minute).is_some(), "{:?}", kind);
+        }
+        assert_eq!(staleness(RuntimeKind::PidFile, false, minute), Some("its process is gone"));
+        assert_eq!(staleness(RuntimeKind::Partial, false, minute), Some("left half-written"));
+
+        // A dead daemon's heartbeat is kept a week for heartbeat-check
+        assert_eq!(staleness(RuntimeKind::Heartbeat, false, 6 * day), None);
+        assert_eq!(staleness(RuntimeKind::Heartbeat, false, STALE_HEARTBEAT_AGE), Some("its daemon died over a week ago"));
+        assert_eq!(staleness(RuntimeKind::Heartbeat, true, 30 * day), None);
+
+        // Rotated journal parts wait 30 days to be replayed
+        assert_eq!(staleness(RuntimeKind::RotatedJournal, false, 29 * day), None);
+        assert_eq!(staleness(RuntimeKind::RotatedJournal, false, STALE_JOURNAL_AGE), Some("not replayed in 30 days"));
+        assert_eq!(staleness(RuntimeKind::RotatedJournal, true, 60 * day), None);
+
+        // Opened exports go after a day, with no owner to ask
+        assert_eq!(staleness(RuntimeKind::OpenedExport, false, day - minute), None);
+        assert_eq!(staleness(RuntimeKind::OpenedExport, true, STALE_OPENED_EXPORT_AGE), Some("opened over a day ago"));
+
+        // The live journal and log are never cleaned here
+        for kind in [RuntimeKind::Journal, RuntimeKind::Log] {
+            assert_eq!(staleness(kind, false, 365 * day), None, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn runtime_files_take_their_age_from_the_last_write() {
+        let scratch = Scratch::new("age");
+        let path = scratch.0.join("focusdebt-work.pid");
+        fs::write(&path, "999999").unwrap();
+        assert!(!pid_file_alive(&path));
+
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let file = RuntimeFile::new(path.clone(), RuntimeKind::PidFile, false, modified + Duration::from_secs(90)).unwrap();
+        assert_eq!(file.age, Duration::from_secs(90));
+        assert_eq!(file.size, 6);
+        assert_eq!(file.stale_reason(), Some("its process is gone"));
+
+        // A clock behind the file reads as brand new
+        let file = RuntimeFile::new(path, RuntimeKind::PidFile, false, modified - Duration::from_secs(90)).unwrap();
+        assert_eq!(file.age, Duration::ZERO);
+        assert_eq!(file.stale_reason(), None);
+
+        assert!(RuntimeFile::new(scratch.0.join("missing.pid"), RuntimeKind::PidFile, false, modified).is_none());
+        assert!(RuntimeFile::new(scratch.0.clone(), RuntimeKind::PidFile, false, modified).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pid_files_only_name_a_live_process_in_plain_digits() {
+        let scratch = Scratch::new("pid");
+        let path = scratch.0.join("focusdebt.pid");
+        assert!(!pid_file_alive(&path));
+        fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+        assert!(pid_file_alive(&path));
+        for content in ["", "0", "-1", "12 34", "abc", "1000000", "99999999999"] {
+            fs::write(&path, content).unwrap();
+            assert!(!pid_file_alive(&path), "{:?}", content);
+        }
+    }
+}
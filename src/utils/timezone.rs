@@ -0,0 +1,235 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{DateTime, FixedOffset, Local, MappedLocalTime, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use super::fuzzy;
+
+/// The timezone times are shown in and days are cut by: the `timezone`
+/// config key when set, else the system's as chrono's `Local` sees it.
+/// Display and day helpers go through `zone()` rather than `Local`, so the
+/// setting reaches all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    System,
+    Named(Tz),
+}
+
+impl Zone {
+    fn wrap(&self, offset: impl Offset) -> ZoneOffset {
+        ZoneOffset { zone: *self, fixed: offset.fix() }
+    }
+}
+
+/// A `Zone`'s offset at some instant, remembering the zone so date
+/// arithmetic stays in it
+#[derive(Debug, Clone, Copy)]
+pub struct ZoneOffset {
+    zone: Zone,
+    fixed: FixedOffset,
+}
+
+impl Offset for ZoneOffset {
+    fn fix(&self) -> FixedOffset {
+        self.fixed
+    }
+}
+
+impl fmt::Display for ZoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fixed.fmt(f)
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = ZoneOffset;
+
+    fn from_offset(offset: &ZoneOffset) -> Self {
+        offset.zone
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> MappedLocalTime<ZoneOffset> {
+        self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> MappedLocalTime<ZoneOffset> {
+        match self {
+            Zone::System => Local.offset_from_local_datetime(local).map(|offset| self.wrap(offset)),
+            Zone::Named(tz) => tz.offset_from_local_datetime(local).map(|offset| self.wrap(offset)),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> ZoneOffset {
+        self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        match self {
+            Zone::System => self.wrap(Local.offset_from_utc_datetime(utc)),
+            Zone::Named(tz) => self.wrap(tz.offset_from_utc_datetime(utc)),
+        }
+    }
+}
+
+static ZONE: OnceLock<Zone> = OnceLock::new();
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The resolved zone; the system's until `resolve` runs
+pub fn zone() -> Zone {
+    *ZONE.get_or_init(|| Zone::System)
+}
+
+/// Settle the zone for this run from the `timezone` config key, warning
+/// (once) when the system's zone can't be determined, the key names no
+/// zone, or the key and the system clock disagree
+pub fn resolve(configured: Option<&str>) -> Zone {
+    let named = match configured.map(parse) {
+        None => None,
+        Some(Ok(tz)) => Some(tz),
+        Some(Err(e)) => {
+            warn_once(&format!("Ignoring timezone: {}", e));
+            None
+        }
+    };
+    let zone = match named {
+        Some(tz) => {
+            let now = Utc::now();
+            let system = Local.offset_from_utc_datetime(&now.naive_utc()).fix();
+            let configured = tz.offset_from_utc_datetime(&now.naive_utc()).fix();
+            if system_zone_problem().is_none() && system != configured {
+                warn_once(&format!("Showing times in {} (UTC{}, the timezone setting); this system's clock is on UTC{}",
+                    tz.name(), configured, system));
+            }
+            Zone::Named(tz)
+        }
+        None => {
+            if let Some(problem) = system_zone_problem() {
+                warn_once(&format!("Couldn't determine the local timezone ({}), so times are in UTC. \
+                    Set one with 'focusdebt config set timezone Europe/Berlin'.", problem));
+            }
+            Zone::System
+        }
+    };
+    let _ = ZONE.set(zone);
+    zone
+}
+
+fn warn_once(message: &str) {
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!("~=~ {}", message);
+    }
+}
+
+/// Why chrono's `Local` would silently fall back to UTC, if it would: a TZ
+/// it can't read, or no TZ and no /etc/localtime
+pub fn system_zone_problem() -> Option<String> {
+    zone_problem(std::env::var("TZ").ok().as_deref(), !cfg!(unix) || Path::new("/etc/localtime").exists())
+}
+
+fn zone_problem(tz: Option<&str>, has_localtime: bool) -> Option<String> {
+    if let Some(tz) = tz {
+        // An empty TZ means UTC on purpose
+        if tz.is_empty() {
+            return None;
+        }
+        let name = tz.strip_prefix(':').unwrap_or(tz);
+        // POSIX rules such as "CET-1CEST" carry their offset in digits
+        let known = name.parse::<Tz>().is_ok()
+            || name.chars().any(|c| c.is_ascii_digit())
+            || Path::new(name).is_file()
+            || Path::new("/usr/share/zoneinfo").join(name).is_file();
+        return (!known).then(|| format!("TZ={} isn't a timezone this system knows", tz));
+    }
+    if !has_localtime {
+        return Some("TZ is unset and /etc/localtime is missing".to_string());
+    }
+    None
+}
+
+/// An IANA timezone name such as "Europe/Berlin", any case. Typos list the
+/// closest names.
+pub fn parse(name: &str) -> Result<Tz, String> {
+    let name = name.trim();
+    if let Some(tz) = chrono_tz::TZ_VARIANTS.iter().find(|tz| tz.name().eq_ignore_ascii_case(name)) {
+        return Ok(*tz);
+    }
+    let names: Vec<&str> = chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
+    let close: Vec<&str> = fuzzy::rank(name, &names, |name| vec![*name])
+        .into_iter()
+        .take(5)
+        .map(|ranked| ranked.item)
+        .collect();
+    if close.is_empty() {
+        Err(format!("'{}' is not a timezone name (use IANA names such as Europe/Berlin or America/New_York)", name))
+    } else {
+        Err(format!("'{}' is not a timezone name. Close matches: {}", name, close.join(", ")))
+    }
+}
+
+/// `at` in the resolved zone
+pub fn to_zone(at: DateTime<Utc>) -> DateTime<Zone> {
+    at.with_timezone(&zone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn names_parse_in_any_case_and_typos_list_close_matches() {
+        assert_eq!(parse("Europe/Berlin"), Ok(chrono_tz::Europe::Berlin));
+        assert_eq!(parse(" europe/berlin "), Ok(chrono_tz::Europe::Berlin));
+        assert_eq!(parse("AMERICA/NEW_YORK"), Ok(chrono_tz::America::New_York));
+        assert_eq!(parse("utc"), Ok(chrono_tz::UTC));
+
+        let typo = parse("Europe/Berln").unwrap_err();
+        assert!(typo.starts_with("'Europe/Berln' is not a timezone name. Close matches: "), "{}", typo);
+        assert!(typo.contains("Europe/Berlin"), "{}", typo);
+        assert!(typo.split(": ").nth(1).unwrap().split(", ").count() <= 5, "{}", typo);
+
+        let nonsense = parse("qqqqzzzz").unwrap_err();
+        assert_eq!(nonsense, "'qqqqzzzz' is not a timezone name (use IANA names such as Europe/Berlin or America/New_York)");
+    }
+
+    #[test]
+    fn a_named_zone_follows_its_own_rules() {
+        let berlin = Zone::Named(chrono_tz::Europe::Berlin);
+        assert_eq!(utc("2024-01-15T12:00:00Z").with_timezone(&berlin).to_rfc3339(), "2024-01-15T13:00:00+01:00");
+        assert_eq!(utc("2024-07-15T12:00:00Z").with_timezone(&berlin).to_rfc3339(), "2024-07-15T14:00:00+02:00");
+        assert_eq!(utc("2024-07-15T23:30:00Z").with_timezone(&berlin).date_naive(), NaiveDate::from_ymd_opt(2024, 7, 16).unwrap());
+
+        let local = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        // The hour skipped in spring doesn't exist; the one repeated in autumn has two offsets
+        assert!(berlin.from_local_datetime(&local("2024-03-31 02:30")).earliest().is_none());
+        let repeated = berlin.from_local_datetime(&local("2024-10-27 02:30"));
+        assert_eq!(repeated.earliest().unwrap().with_timezone(&Utc), utc("2024-10-27T00:30:00Z"));
+        assert_eq!(repeated.latest().unwrap().with_timezone(&Utc), utc("2024-10-27T01:30:00Z"));
+
+        // Date arithmetic stays in the zone across the change
+        let evening = utc("2024-10-26T18:00:00Z").with_timezone(&berlin);
+        let next = evening + chrono::Duration::days(1);
+        assert_eq!(next.to_rfc3339(), "2024-10-27T19:00:00+01:00");
+        assert_eq!(next.timezone(), berlin);
+
+        let tokyo = Zone::Named(chrono_tz::Asia::Tokyo);
+        assert_eq!(tokyo.from_utc_datetime(&utc("2024-07-15T20:00:00Z").naive_utc()).format("%F %R %:z").to_string(),
+            "2024-07-16 05:00 +09:00");
+    }
+
+    #[test]
+    fn an_unreadable_system_zone_is_a_problem() {
+        // TZ names and POSIX rules are fine, as is an empty TZ meaning UTC
+        for tz in ["Europe/Berlin", ":Europe/Berlin", "CET-1CEST", "", "UTC"] {
+            assert_eq!(zone_problem(Some(tz), false), None, "{:?}", tz);
+        }
+        assert_eq!(zone_problem(Some("Mars/Olympus"), true).as_deref(), Some("TZ=Mars/Olympus isn't a timezone this system knows"));
+        assert_eq!(zone_problem(None, true), None);
+        assert_eq!(zone_problem(None, false).as_deref(), Some("TZ is unset and /etc/localtime is missing"));
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use crate::desktop;
+use crate::glyphs::BarStyle;
+use crate::stats::{self, StatsSettings, SwitchCost};
+use crate::storage::Database;
+use crate::utils::{self, DurationStyle, LocalDays, ReportLayout};
+
+/// Formats of `wrapped --format`
+pub const WRAPPED_FORMATS: [&str; 3] = ["ascii", "markdown", "svg"];
+
+/// Tracked time of one local day
+#[derive(Debug, Clone, Copy)]
+pub struct DayTotal {
+    pub date: NaiveDate,
+    pub focus: Duration,
+    pub total: Duration,
+}
+
+/// Everything the year in review shows. Each figure comes from an aggregate
+/// query, so a year of rows is never loaded at once.
+#[derive(Debug, Clone)]
+pub struct YearInReview {
+    pub year: i32,
+    /// Days with tracked time, in order
+    pub days: Vec<DayTotal>,
+    /// Start and length of the longest focus block
+    pub longest_block: Option<(DateTime<Utc>, Duration)>,
+    pub top_app: Option<(String, Duration)>,
+    /// Most used app of each quarter
+    pub quarter_apps: [Option<(String, Duration)>; 4],
+    pub context_switches: usize,
+    pub switching_cost: SwitchCost,
+    /// Daily means of workdays and of rest days (weekends, holidays), apart
+    pub baselines: Vec<stats::Baseline>,
+}
+
+/// focusdebt-wrapped-2024.svg
+pub fn file_name(year: i32) -> String {
+    format!("focusdebt-wrapped-{}.svg", year)
+}
+
+/// The year `year` as `days` cuts it, from the start of 1 January to the
+/// start of the next one
+pub fn collect(db: &Database, settings: &StatsSettings, days: &LocalDays, year: i32) -> Result<YearInReview, Box<dyn std::error::Error>> {
+    let first_day = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| format!("year {} is out of range", year))?;
+    let next_year = NaiveDate::from_ymd_opt(year + 1, 1, 1).ok_or_else(|| format!("year {} is out of range", year))?;
+    let (start, end) = (days.start(first_day), days.start(next_year));
+
+    let totals: Vec<DayTotal> = db.get_daily_totals_between(days, start, end)?
+        .into_iter()
+        .map(|(date, focus, total)| DayTotal { date, focus, total })
+        .collect();
+    let baselines = settings.baselines(totals.iter().map(|day| (day.date, day.focus, day.total, None)));
+
+    // Stored keys fold into display names, which several keys can share
+    let mut per_quarter: [BTreeMap<String, Duration>; 4] = Default::default();
+    for (quarter, app, duration) in db.get_app_totals_by_quarter(days, start, end)? {
+        let index = (quarter.clamp(1, 4) - 1) as usize;
+        *per_quarter[index].entry(desktop::display_name(&app)).or_default() += duration;
+    }
+    let mut overall: BTreeMap<String, Duration> = BTreeMap::new();
+    for quarter in &per_quarter {
+        for (app, duration) in quarter {
+            *overall.entry(app.clone()).or_default() += *duration;
+        }
+    }
+    let quarter_apps = per_quarter.map(|quarter| top_entry(&quarter));
+
+    let (switches_away, measured, measured_total) = db.get_switch_away_totals_between(start, end)?;
+    Ok(YearInReview {
+        year,
+        days: totals,
+        longest_block: db.get_longest_focus_block_between(start, end, settings.min_display)?,
+        top_app: top_entry(&overall),
+        quarter_apps,
+        context_switches: db.count_context_switches_between(start, end)?,
+        switching_cost: settings.switching_cost_from_totals(switches_away, measured, measured_total),
+        baselines,
+    })
+}
+
+/// Largest entry, ties going to the name that sorts first
+fn top_entry(totals: &BTreeMap<String, Duration>) -> Option<(String, Duration)> {
+    totals.iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(app, duration)| (app.clone(), *duration))
+}
+
+impl YearInReview {
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.days.iter().map(|day| day.total).sum()
+    }
+
+    pub fn focus(&self) -> Duration {
+        self.days.iter().map(|day| day.focus).sum()
+    }
+
+    pub fn efficiency(&self) -> f64 {
+        efficiency(self.focus(), self.total())
+    }
+
+    /// (focus, total) per month, January first
+    pub fn months(&self) -> [(Duration, Duration); 12] {
+        let mut months = [(Duration::ZERO, Duration::ZERO); 12];
+        for day in &self.days {
+            let month = &mut months[day.date.month0() as usize];
+            month.0 += day.focus;
+            month.1 += day.total;
+        }
+        months
+    }
+
+    /// Month (1-12) with the most tracked time
+    pub fn busiest_month(&self) -> Option<(u32, Duration)> {
+        self.months().iter()
+            .enumerate()
+            .filter(|(_, (_, total))| !total.is_zero())
+            .max_by_key(|(index, (_, total))| (*total, std::cmp::Reverse(*index)))
+            .map(|(index, (_, total))| (index as u32 + 1, *total))
+    }
+
+    /// Monday of the ISO week with the most tracked time; only this year's
+    /// days of a week that crosses New Year count
+    pub fn busiest_week(&self) -> Option<(NaiveDate, Duration)> {
+        let mut weeks: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+        for day in &self.days {
+            let monday = day.date - chrono::Duration::days(day.date.weekday().num_days_from_monday() as i64);
+            *weeks.entry(monday).or_default() += day.total;
+        }
+        weeks.into_iter().max_by_key(|(monday, total)| (*total, std::cmp::Reverse(*monday)))
+    }
+
+    pub fn busiest_day(&self) -> Option<DayTotal> {
+        self.days.iter().copied().max_by_key(|day| (day.total, std::cmp::Reverse(day.date)))
+    }
+
+    /// First day and length of the longest run of consecutive days with focus time
+    pub fn longest_streak(&self) -> Option<(NaiveDate, usize)> {
+        let mut longest: Option<(NaiveDate, usize)> = None;
+        let mut current: Option<(NaiveDate, usize)> = None;
+        let mut previous: Option<NaiveDate> = None;
+        for day in self.days.iter().filter(|day| !day.focus.is_zero()) {
+            current = match (current, previous) {
+                (Some((first, length)), Some(previous)) if day.date - previous == chrono::Duration::days(1) => Some((first, length + 1)),
+                _ => Some((day.date, 1)),
+            };
+            if current.map(|(_, length)| length) > longest.map(|(_, length)| length) {
+                longest = current;
+            }
+            previous = Some(day.date);
+        }
+        longest
+    }
+
+    /// Days with focus time
+    pub fn focus_days(&self) -> usize {
+        self.days.iter().filter(|day| !day.focus.is_zero()).count()
+    }
+}
+
+fn efficiency(focus: Duration, total: Duration) -> f64 {
+    if total.is_zero() { 0.0 } else { focus.as_secs_f64() / total.as_secs_f64() * 100.0 }
+}
+
+fn month_name(month: u32) -> String {
+    NaiveDate::from_ymd_opt(2000, month, 1).map(|date| date.format("%b").to_string()).unwrap_or_default()
+}
+
+fn hours(duration: Duration) -> String {
+    format!("{:.0}h", duration.as_secs_f64() / 3600.0)
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+/// (label, value) lines shared by the text formats
+fn highlights(review: &YearInReview) -> Vec<(&'static str, String)> {
+    let long = |duration: Duration| utils::format_duration(duration, DurationStyle::Long);
+    let mut lines = vec![
+        ("Tracked Time", format!("{} over {}", long(review.total()), plural(review.days.len(), "day", "days"))),
+        ("Focus Time", format!("{} ({:.0}% efficiency)", long(review.focus()), review.efficiency())),
+    ];
+    for baseline in &review.baselines {
+        lines.push((baseline.day_type.label(), baseline.describe()));
+    }
+    if let Some((month, total)) = review.busiest_month() {
+        lines.push(("Busiest Month", format!("{} ({})", month_name(month), long(total))));
+    }
+    if let Some((monday, total)) = review.busiest_week() {
+        let week = monday.iso_week();
+        lines.push(("Busiest Week", format!("W{:02}, from {} ({})", week.week(), utils::format_date_local(monday), long(total))));
+    }
+    if let Some(day) = review.busiest_day() {
+        lines.push(("Busiest Day", format!("{} ({})", utils::format_date_local(day.date), long(day.total))));
+    }
+    if let Some((started, length)) = review.longest_block {
+        lines.push(("Longest Focus", format!("{} on {}", long(length), utils::format_datetime_local(started))));
+    }
+    if let Some((first, length)) = review.longest_streak() {
+        lines.push(("Longest Streak", format!("{} from {}", plural(length, "day", "days"), utils::format_date_local(first))));
+    }
+    lines.push(("Focus Days", review.focus_days().to_string()));
+    if let Some((app, duration)) = &review.top_app {
+        lines.push(("Top App", format!("{} ({})", app, long(*duration))));
+    }
+    lines.push(("Context Switches", review.context_switches.to_string()));
+    if !review.switching_cost.is_empty() {
+        lines.push(("Switching Cost", review.switching_cost.summary()));
+    }
+    lines
+}
+
+/// The year in the share report's style, with a bar per month
+pub fn render_ascii(review: &YearInReview) -> String {
+    let layout = ReportLayout::current();
+    let pad = layout.cells(42);
+    let top_sep = "~~+~~+*+~~+~~+*+~~+~~";
+    let mut report = String::new();
+    report.push('\n');
+    report.push_str(&format!("{}\n\n", top_sep));
+    report.push_str(&format!("~=~ FOCUSDEBT WRAPPED {} ~=~\n\n", review.year));
+    if review.is_empty() {
+        report.push_str(&format!("~=~ Nothing was tracked in {}\n\n", review.year));
+        report.push_str(&format!("{}\n\n", top_sep));
+        return report;
+    }
+    for (label, value) in highlights(review) {
+        report.push_str(&format!("{:<16}: {:<pad$}\n\n", label, value));
+    }
+
+    report.push_str("TOP APP BY QUARTER\n\n");
+    for (index, top) in review.quarter_apps.iter().enumerate() {
+        let value = match top {
+            Some((app, duration)) => format!("{} ({})", app, utils::format_duration(*duration, DurationStyle::Long)),
+            None => "-".to_string(),
+        };
+        report.push_str(&format!("Q{}  {}\n", index + 1, value));
+    }
+    report.push('\n');
+
+    report.push_str("EFFICIENCY BY MONTH\n\n");
+    for (index, (focus, total)) in review.months().iter().enumerate() {
+        let value = if total.is_zero() {
+            "-".to_string()
+        } else {
+            format!("{:.0}% of {}", efficiency(*focus, *total), hours(*total))
+        };
+        let name = month_name(index as u32 + 1);
+        report.push_str(&format!("{} {}\n", stats::bar_row(&layout, &name, 4, efficiency(*focus, *total) / 100.0, 40, BarStyle::Shade), value));
+    }
+    report.push('\n');
+    report.push_str(&format!("{}\n\n", top_sep));
+    report
+}
+
+pub fn render_markdown(review: &YearInReview) -> String {
+    // A file isn't read in this terminal, so bars are laid out for 80 columns
+    let layout = ReportLayout::with_width(utils::DEFAULT_REPORT_WIDTH);
+    let mut markdown = format!("# FocusDebt Wrapped {}\n\n", review.year);
+    if review.is_empty() {
+        markdown.push_str(&format!("Nothing was tracked in {}.\n", review.year));
+        return markdown;
+    }
+    for (label, value) in highlights(review) {
+        markdown.push_str(&format!("- **{}:** {}\n", label, value));
+    }
+
+    markdown.push_str("\n## Top App by Quarter\n\n| Quarter | App | Time |\n| --- | --- | --- |\n");
+    for (index, top) in review.quarter_apps.iter().enumerate() {
+        match top {
+            Some((app, duration)) => markdown.push_str(&format!("| Q{} | {} | {} |\n",
+                index + 1, app.replace('|', "\\|"), utils::format_duration(*duration, DurationStyle::Long))),
+            None => markdown.push_str(&format!("| Q{} | - | - |\n", index + 1)),
+        }
+    }
+
+    markdown.push_str("\n## Efficiency by Month\n\n| Month | Efficiency | Tracked |\n| --- | --- | --- |\n");
+    for (index, (focus, total)) in review.months().iter().enumerate() {
+        let name = month_name(index as u32 + 1);
+        if total.is_zero() {
+            markdown.push_str(&format!("| {} | - | - |\n", name));
+        } else {
+            markdown.push_str(&format!("| {} | `{}` {:.0}% | {} |\n",
+                name,
+                layout.bar(efficiency(*focus, *total) / 100.0, 20, BarStyle::Shade).unwrap_or_default(),
+                efficiency(*focus, *total),
+                hours(*total)));
+        }
+    }
+    markdown
+}
+
+/// A card with the headline numbers and a bar per month, colored like the
+/// share badge
+pub fn render_svg(review: &YearInReview) -> String {
+    const WIDTH: usize = 480;
+    const BAR_X: usize = 60;
+    const BAR_WIDTH: usize = 300;
+    const ROW: usize = 24;
+    const TOP: usize = 110;
+    let height = TOP + 12 * ROW + 20;
+
+    let headline = if review.is_empty() {
+        "Nothing tracked".to_string()
+    } else {
+        format!("{} focus · {:.0}% efficiency", utils::format_duration(review.focus(), DurationStyle::Long), review.efficiency())
+    };
+    let detail = match (&review.top_app, review.longest_block) {
+        (Some((app, _)), Some((_, length))) => format!("Top app {} · longest focus {}", app, utils::format_duration(length, DurationStyle::Long)),
+        (Some((app, _)), None) => format!("Top app {}", app),
+        _ => String::new(),
+    };
+
+    let mut rows = String::new();
+    for (index, (focus, total)) in review.months().iter().enumerate() {
+        let y = TOP + index * ROW;
+        let percent = efficiency(*focus, *total);
+        let value = if total.is_zero() { "-".to_string() } else { format!("{:.0}%", percent) };
+        rows.push_str(&format!(
+            r##"  <text x="20" y="{text_y}" fill="#ccc">{name}</text>
+  <rect x="{BAR_X}" y="{y}" width="{BAR_WIDTH}" height="16" rx="3" fill="#333"/>
+  <rect x="{BAR_X}" y="{y}" width="{filled}" height="16" rx="3" fill="{color}"/>
+  <text x="{value_x}" y="{text_y}" fill="#ccc">{value}</text>
+"##,
+            text_y = y + 13,
+            name = month_name(index as u32 + 1),
+            filled = (percent / 100.0 * BAR_WIDTH as f64).round() as usize,
+            color = stats::efficiency_color(percent),
+            value_x = BAR_X + BAR_WIDTH + 10,
+        ));
+    }
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" role="img" aria-label="FocusDebt Wrapped {year}: {headline}">
+  <title>FocusDebt Wrapped {year}: {headline}</title>
+  <rect width="{WIDTH}" height="{height}" rx="8" fill="#1e1e1e"/>
+  <g font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="12">
+  <text x="20" y="36" fill="#fff" font-size="20">FocusDebt Wrapped {year}</text>
+  <text x="20" y="62" fill="#fff" font-size="14">{headline}</text>
+  <text x="20" y="84" fill="#aaa">{detail}</text>
+{rows}  </g>
+</svg>
+"##,
+        year = review.year,
+        headline = stats::html_escape(&headline),
+        detail = stats::html_escape(&detail),
+    )
+}
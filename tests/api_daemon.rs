@@ -0,0 +1,82 @@
+// The embedded tracker against the CLI's daemon commands: it holds the
+// daemon's PID file, so `start` leaves it alone and `stop` stops it.
+// One test per binary, as it points this process at a sandbox.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::time::Duration;
+use focusdebt::api::{FocusTrackerHandle, TrackerOptions};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-api-daemon-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        // Detection finds no window
+        let xdotool = root.join("bin/xdotool");
+        fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn data(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let home = self.root.join("home");
+        Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.data())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[test]
+fn the_cli_sees_the_embedded_tracker_as_its_daemon() {
+    let sandbox = Sandbox::new("pid");
+    std::env::set_var("FOCUSDEBT_DATA_DIR", sandbox.data());
+    std::env::set_var("PATH", format!("{}:/usr/bin:/bin", sandbox.root.join("bin").display()));
+    let options = TrackerOptions::default().tracking_interval(Duration::from_millis(50));
+    let pid_file = sandbox.data().join("focusdebt.pid");
+
+    let handle = FocusTrackerHandle::start(options.clone()).unwrap();
+    assert_eq!(fs::read_to_string(&pid_file).unwrap().trim(), std::process::id().to_string());
+    assert!(FocusTrackerHandle::start(options.clone()).is_err(), "a second handle tracked alongside");
+    let start = sandbox.run(&["start"]);
+    assert!(String::from_utf8_lossy(&start.stdout).contains("daemon is already running"),
+        "{}", String::from_utf8_lossy(&start.stdout));
+    // Stopping removes the file again
+    handle.stop().unwrap();
+    assert!(!pid_file.exists());
+
+    // `focusdebt stop` ends the thread, which confirms the flush
+    let handle = FocusTrackerHandle::start(options).unwrap();
+    let stop = sandbox.run(&["stop"]);
+    assert!(String::from_utf8_lossy(&stop.stdout).contains("Daemon flushed all data"),
+        "{}\n--- stderr\n{}", String::from_utf8_lossy(&stop.stdout), String::from_utf8_lossy(&stop.stderr));
+    let summary = handle.stop().unwrap();
+    assert_eq!(summary.sessions, 0);
+    assert!(!pid_file.exists());
+}
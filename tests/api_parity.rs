@@ -0,0 +1,106 @@
+// The embedding API's reports against the CLI's, on one demo database
+// read with the same config
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::time::Duration;
+use chrono::Utc;
+use focusdebt::api::{DailyStats, StatsReader, TrackerOptions};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-parity-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        // Window detection is never used, but commands check for it
+        let xdotool = root.join("bin/xdotool");
+        fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let home = self.root.join("home");
+        Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.root.join("data"))
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn run_ok(&self, args: &[&str]) {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed:\n{}\n--- stderr\n{}", args,
+            String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A row of `export --format daily-csv`
+fn csv_row(day: &DailyStats) -> String {
+    let hours = |duration: Duration| duration.as_secs_f64() / 3600.0;
+    format!("{},{:.2},{:.2},{:.2},{},{},{:.2},{},{:.2}",
+        day.date.format("%Y-%m-%d"),
+        hours(day.total_focus_time),
+        hours(day.total_distraction_time),
+        hours(day.untracked_time),
+        day.context_switches,
+        day.deep_focus_sessions,
+        day.focus_efficiency,
+        day.focus_score.map(|score| score.to_string()).unwrap_or_default(),
+        hours(day.assistant_time))
+}
+
+#[test]
+fn stats_reader_cuts_days_as_the_cli_does() {
+    let sandbox = Sandbox::new("days");
+    sandbox.run_ok(&["demo", "generate", "--days", "7", "--seed", "7"]);
+    // Days cut unlike the defaults, so a reader ignoring the config shows
+    sandbox.run_ok(&["config", "set", "day_start_hour", "4"]);
+    sandbox.run_ok(&["config", "set", "timezone", "Asia/Tokyo"]);
+
+    let first_day = Utc::now().date_naive() - chrono::Duration::days(7);
+    let last_day = first_day + chrono::Duration::days(6);
+    let csv = sandbox.root.join("days.csv");
+    sandbox.run_ok(&["export", "--format", "daily-csv",
+        "--start-date", &first_day.to_string(), "--end-date", &last_day.to_string(),
+        "--output", csv.to_str().unwrap()]);
+    let cli: Vec<String> = fs::read_to_string(&csv).unwrap().lines().skip(1).map(String::from).collect();
+
+    let db = sandbox.root.join("data/focusdebt.db");
+    let options = TrackerOptions::default().day_start_hour(4).timezone("Asia/Tokyo").unwrap();
+    let api: Vec<String> = StatsReader::open_with(&db, &options).unwrap()
+        .daily_stats_range(first_day, 7).unwrap()
+        .iter()
+        .map(csv_row)
+        .collect();
+    assert_eq!(api, cli);
+    assert!(api.iter().any(|row| !row.contains(",0.00,0.00,24.00,")), "no tracked days: {:?}", api);
+
+    // Read with the defaults the days come out differently
+    let plain: Vec<String> = StatsReader::open_with(&db, &TrackerOptions::default()).unwrap()
+        .daily_stats_range(first_day, 7).unwrap()
+        .iter()
+        .map(csv_row)
+        .collect();
+    assert_ne!(plain, cli);
+}
@@ -0,0 +1,76 @@
+// `demo generate` run as a user would, against a throwaway data directory
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-demo-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        // Window detection is never used, but commands check for it
+        let xdotool = root.join("bin/xdotool");
+        fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let home = self.root.join("home");
+        Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.root.join("data"))
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed:\n{}", args, describe(&output));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn describe(output: &Output) -> String {
+    format!("{}\n--- stdout\n{}--- stderr\n{}", output.status,
+        String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+}
+
+#[test]
+fn generate_refuses_a_database_with_rows_without_force() {
+    let sandbox = Sandbox::new("generate");
+    let stdout = sandbox.stdout(&["demo", "generate", "--days", "7", "--seed", "7"]);
+    assert!(stdout.contains("~=~ Wrote "), "{}", stdout);
+
+    // Tracked rows are there now, so a second generate needs --force
+    let output = sandbox.run(&["demo", "generate", "--days", "7", "--seed", "8"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("demo data would mix with them"), "{}", describe(&output));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("~=~ Wrote "), "{}", describe(&output));
+
+    let stdout = sandbox.stdout(&["demo", "generate", "--days", "7", "--seed", "8", "--force"]);
+    assert!(stdout.contains("~=~ Wrote "), "{}", stdout);
+
+    sandbox.stdout(&["stats"]);
+    sandbox.stdout(&["sessions", "list"]);
+}
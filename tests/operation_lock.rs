@@ -0,0 +1,118 @@
+// The operation lock as commands see it, run against a throwaway home and
+// data directory: a long operation that fails still releases it, and
+// commands that write the database refuse to run while it is held.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-oplock-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        let sandbox = Self { root };
+        sandbox.set_xdotool(true);
+        sandbox
+    }
+
+    /// Window detection is never used, but commands check xdotool is there
+    fn set_xdotool(&self, installed: bool) {
+        let xdotool = self.root.join("bin/xdotool");
+        if installed {
+            fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+            fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        } else {
+            fs::remove_file(&xdotool).unwrap();
+        }
+    }
+
+    fn data(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let home = self.root.join("home");
+        Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.data())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed:\n{}", args, describe(&output));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// The operation recorded in the database, if any
+    fn recorded_operation(&self) -> Option<String> {
+        let conn = rusqlite::Connection::open(self.data().join("focusdebt.db")).unwrap();
+        conn.query_row("SELECT value FROM meta WHERE key = 'operation_lock'", [], |row| row.get(0)).ok()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn describe(output: &Output) -> String {
+    format!("{}\n--- stdout\n{}--- stderr\n{}", output.status,
+        String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+}
+
+#[test]
+fn a_long_operation_that_fails_releases_the_lock() {
+    let sandbox = Sandbox::new("failed");
+    sandbox.run_ok(&["demo", "generate", "--days", "2", "--seed", "1"]);
+
+    // The dedupe takes the lock, then stops at the dependency check
+    sandbox.set_xdotool(false);
+    let output = sandbox.run(&["database", "dedupe"]);
+    assert_eq!(output.status.code(), Some(1), "{}", describe(&output));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Required dependencies not found"), "{}", describe(&output));
+    assert_eq!(sandbox.recorded_operation(), None);
+
+    // The next command finds nothing to clean up after it
+    sandbox.set_xdotool(true);
+    let stdout = sandbox.run_ok(&["database", "dedupe"]);
+    assert!(!stdout.contains("Cleared the lock"), "{}", stdout);
+    assert_eq!(sandbox.recorded_operation(), None);
+}
+
+#[test]
+fn focus_list_changes_wait_for_a_long_operation() {
+    let sandbox = Sandbox::new("focuslists");
+    sandbox.run_ok(&["demo", "generate", "--days", "2", "--seed", "1"]);
+
+    // Another process holds the lock file, as a running import would
+    let lock_file = sandbox.data().join("focusdebt.oplock");
+    let mut holder = Command::new("flock").arg(&lock_file).args(["sleep", "30"]).spawn().unwrap();
+    while Command::new("flock").arg("-n").arg(&lock_file).arg("true").status().unwrap().success() {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    for args in [["focusapp", "remove", "code"], ["focussite", "remove", "github.com"]] {
+        let output = sandbox.run(&args);
+        assert_eq!(output.status.code(), Some(1), "{}", describe(&output));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("try again once it has finished"), "{}", describe(&output));
+    }
+    holder.kill().unwrap();
+    holder.wait().unwrap();
+}
@@ -0,0 +1,281 @@
+// Guards focusdebt::api, the surface embedders depend on. A change that
+// fails here is a breaking one (or an addition) and should be deliberate:
+// rerun with FOCUSDEBT_UPDATE_SNAPSHOTS=1 to accept it, and bump the
+// version to match.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use focusdebt::api::{
+    AggregatedSession, AppTrend, AssistantCountsAs, BrowserSites, CoFocusTime, ContextSwitch, DailyStats, Error,
+    FocusSession, FocusTrackerHandle, NotificationCounts, OpenEnd, PowerSource, RoundTripPair, RuleKind, RuleMatch,
+    SessionIntent, SessionSummary, SessionTask, StatsReader, SuspendGap, SwitchCost, TaskSource, TrackerOptions,
+    TrackerStatus, TrackerUpdate, WorkspaceUsage,
+};
+
+const SNAPSHOT_ENV: &str = "FOCUSDEBT_UPDATE_SNAPSHOTS";
+
+type RangeQuery<T> = fn(&StatsReader, DateTime<Utc>, DateTime<Utc>) -> Result<Vec<T>, Error>;
+
+fn manifest_path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+/// The public items of a module's source, one declaration per line: `pub`
+/// items with their derives and `#[non_exhaustive]`, the public fields and
+/// variants of public types, and `impl` blocks with their public methods.
+/// Comments, bodies and the tests module are left out.
+fn public_surface(source: &str) -> String {
+    let source = source.split("\n#[cfg(test)]").next().unwrap_or(source);
+    let mut surface = Vec::new();
+    let mut depth = 0usize;
+    // Derive and #[non_exhaustive] lines waiting for their item
+    let mut attributes: Vec<&str> = Vec::new();
+    // Inside a public type's body, and whether only `pub` fields count
+    let mut body: Option<bool> = None;
+    let mut impl_header: Option<&str> = None;
+    for line in source.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let opens = line.matches('{').count();
+        let closes = line.matches('}').count();
+        if depth == 0 {
+            if line.starts_with("#[derive") || line == "#[non_exhaustive]" {
+                attributes.push(line);
+            } else if line.starts_with("pub ") {
+                surface.extend(attributes.drain(..).map(String::from));
+                surface.push(line.to_string());
+                if opens > closes {
+                    body = Some(line.starts_with("pub struct"));
+                }
+            } else if line.starts_with("impl") {
+                attributes.clear();
+                impl_header = Some(line.trim_end_matches('{').trim_end());
+                if line.contains(" for ") {
+                    surface.push(impl_header.take().unwrap().to_string());
+                }
+            } else if !line.starts_with("#[") {
+                attributes.clear();
+            }
+        } else if depth == 1 {
+            if let Some(pub_fields_only) = body {
+                if line == "}" {
+                    surface.push("}".to_string());
+                } else if !pub_fields_only || line.starts_with("pub ") {
+                    surface.push(format!("    {}", line));
+                }
+            } else if line.starts_with("pub fn") {
+                if let Some(header) = impl_header.take() {
+                    surface.push(header.to_string());
+                }
+                surface.push(format!("    {}", line.trim_end_matches('{').trim_end()));
+            }
+        }
+        depth = (depth + opens).saturating_sub(closes);
+        if depth == 0 {
+            body = None;
+        }
+    }
+    surface.join("\n") + "\n"
+}
+
+/// The items `api` re-exports from the crate's internals, as (module,
+/// names) pairs read from its `pub use crate::...` lines
+fn reexports(source: &str) -> Vec<(String, Vec<String>)> {
+    source.lines()
+        .filter_map(|line| line.trim().strip_prefix("pub use crate::")?.strip_suffix(';'))
+        .filter_map(|path| {
+            let (module, names) = path.split_once("::")?;
+            let names = names.trim_start_matches('{').trim_end_matches('}');
+            Some((module.to_string(), names.split(',').map(|name| name.trim().to_string()).collect()))
+        })
+        .collect()
+}
+
+/// The name a surface entry's first line declares or implements
+fn declared_name(header: &str) -> &str {
+    let rest = header.rsplit_once(" for ").map(|(_, ty)| ty)
+        .or_else(|| header.strip_prefix("impl "))
+        .or_else(|| ["pub struct ", "pub enum ", "pub type "].iter().find_map(|kind| header.strip_prefix(kind)))
+        .unwrap_or("");
+    rest.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("")
+}
+
+/// `public_surface` cut down to the declarations and impls of `names`
+fn surface_of(source: &str, names: &[String]) -> String {
+    // (declared name, lines), attributes kept with the item they precede
+    let mut entries: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut attributes = Vec::new();
+    let surface = public_surface(source);
+    for line in surface.lines() {
+        if line.starts_with("#[") {
+            attributes.push(line);
+        } else if line.starts_with(' ') || line == "}" {
+            if let Some((_, lines)) = entries.last_mut() {
+                lines.push(line);
+            }
+        } else {
+            entries.push((declared_name(line), attributes.drain(..).chain([line]).collect()));
+        }
+    }
+    entries.into_iter()
+        .filter(|(name, _)| names.iter().any(|wanted| wanted == name))
+        .flat_map(|(_, lines)| lines)
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// The API's own declarations followed by those of the types it
+/// re-exports, whose public fields and methods embedders reach as well
+fn api_surface() -> String {
+    let source = std::fs::read_to_string(manifest_path("src/api.rs")).unwrap();
+    let mut surface = public_surface(&source);
+    for (module, names) in reexports(&source) {
+        let module_source = std::fs::read_to_string(manifest_path(&format!("src/{}.rs", module))).unwrap();
+        surface.push_str(&format!("// crate::{}\n", module));
+        surface.push_str(&surface_of(&module_source, &names));
+    }
+    surface
+}
+
+#[test]
+fn public_api_matches_the_snapshot() {
+    let surface = api_surface();
+    let snapshot_path = manifest_path("tests/public_api.txt");
+    if std::env::var_os(SNAPSHOT_ENV).is_some() {
+        std::fs::write(&snapshot_path, &surface).unwrap();
+        return;
+    }
+    let snapshot = std::fs::read_to_string(&snapshot_path).unwrap_or_default();
+    assert!(
+        surface == snapshot,
+        "focusdebt::api changed; if that is deliberate, rerun with {}=1\n--- snapshot\n{}--- now\n{}",
+        SNAPSHOT_ENV, snapshot, surface,
+    );
+}
+
+/// Fails to compile when a signature embedders call changes
+#[test]
+fn public_api_signatures() {
+    let _: fn() -> Result<TrackerOptions, Error> = TrackerOptions::load;
+    let _: fn(TrackerOptions, Duration) -> TrackerOptions = TrackerOptions::tracking_interval;
+    let _: fn(TrackerOptions, Vec<String>) -> TrackerOptions = TrackerOptions::focus_apps;
+    let _: fn(TrackerOptions, Vec<String>) -> TrackerOptions = TrackerOptions::ignored_apps;
+    let _: fn(TrackerOptions, u32) -> TrackerOptions = TrackerOptions::day_start_hour;
+    let _: fn(TrackerOptions, &str) -> Result<TrackerOptions, Error> = TrackerOptions::timezone;
+    let _: fn(TrackerOptions) -> Result<FocusTrackerHandle, Error> = FocusTrackerHandle::start;
+    let _: fn(&FocusTrackerHandle) -> mpsc::Receiver<TrackerUpdate> = FocusTrackerHandle::subscribe;
+    let _: fn(&FocusTrackerHandle) -> TrackerStatus = FocusTrackerHandle::current_status;
+    let _: fn(FocusTrackerHandle) -> Result<SessionSummary, Error> = FocusTrackerHandle::stop;
+    let _: fn(&SessionSummary) -> f64 = SessionSummary::efficiency;
+
+    let _: fn() -> Result<StatsReader, Error> = StatsReader::open;
+    let _: fn(&Path) -> Result<StatsReader, Error> = StatsReader::open_path;
+    let _: fn(&Path, &TrackerOptions) -> Result<StatsReader, Error> = StatsReader::open_with;
+    let _: fn(&StatsReader, DateTime<Utc>) -> Result<DailyStats, Error> = StatsReader::daily_stats;
+    let _: fn(&StatsReader, NaiveDate, usize) -> Result<Vec<DailyStats>, Error> = StatsReader::daily_stats_range;
+    let _: fn(&StatsReader, &str) -> Result<AggregatedSession, Error> = StatsReader::session_stats;
+    let _: RangeQuery<FocusSession> = StatsReader::sessions_between;
+    let _: RangeQuery<ContextSwitch> = StatsReader::context_switches_between;
+    let _: fn(&DailyStats, &AggregatedSession, &FocusSession) = name_field_types;
+
+    let now = Utc::now();
+    let summary = SessionSummary {
+        session_name: String::new(),
+        started_at: now,
+        ended_at: now,
+        sessions: 0,
+        context_switches: 0,
+        total_time: Duration::ZERO,
+        focus_time: Duration::ZERO,
+    };
+    let TrackerStatus { session_name: _, current: _, sessions: _, context_switches: _ } = TrackerStatus::default();
+    let describe = |update: TrackerUpdate| match update {
+        TrackerUpdate::SessionStarted { app_name, window_title: _, at: _ } => app_name,
+        TrackerUpdate::SessionEnded(session) => session.app_name,
+        TrackerUpdate::Switch(switch) => switch.to_app,
+    };
+    let _ = describe;
+    assert_eq!(summary.clone(), summary);
+}
+
+/// Fails to compile when a public field's type can't be named from outside
+fn name_field_types(day: &DailyStats, session: &AggregatedSession, row: &FocusSession) {
+    let _: AssistantCountsAs = day.assistant_counts_as;
+    let _: &[CoFocusTime] = &day.co_focus;
+    let _: &[AppTrend] = &day.app_trends;
+    let _: &[BrowserSites] = &day.browser_sites;
+    let _: &[RoundTripPair] = &day.round_trips;
+    let _: SwitchCost = day.switching_cost;
+    let _: Option<PowerSource> = day.power_filter;
+    let _: &[WorkspaceUsage] = &session.workspace_usage;
+    let _: &[SuspendGap] = &session.suspends;
+    let _: &NotificationCounts = &session.notifications;
+    let _: Option<&SessionIntent> = session.intent.as_ref();
+    let _: Option<TaskSource> = session.task.as_ref().map(|task: &SessionTask| task.source);
+    let _: OpenEnd = row.open_end;
+    let _: Option<RuleKind> = row.focus_rule.as_ref().map(|rule: &RuleMatch| rule.kind);
+}
+
+#[test]
+fn public_surface_reads_declarations_only() {
+    let source = "\
+//! Module docs
+use std::fmt;
+
+/// Docs
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Open {
+    /// Field docs
+    pub name: String,
+    hidden: u32,
+}
+
+#[derive(Debug)]
+struct Private {
+    pub name: String,
+}
+
+pub enum Kind {
+    A { at: u32 },
+    B(String),
+}
+
+impl Open {
+    pub fn name(&self) -> &str {
+        if true { &self.name } else { \"\" }
+    }
+
+    fn hidden(&self) -> u32 {
+        self.hidden
+    }
+}
+
+impl Drop for Open {
+    fn drop(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    pub fn helper() {}
+}
+";
+    assert_eq!(public_surface(source), "\
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Open {
+    pub name: String,
+}
+pub enum Kind {
+    A { at: u32 },
+    B(String),
+}
+impl Open
+    pub fn name(&self) -> &str
+impl Drop for Open
+");
+}
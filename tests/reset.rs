@@ -0,0 +1,151 @@
+// `reset --all` run as a user would, against a throwaway home and data
+// directory, with steps made to fail part way through.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-reset-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        // Window detection is never used, but commands check for it
+        let xdotool = root.join("bin/xdotool");
+        fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn data(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    fn config_file(&self) -> PathBuf {
+        self.root.join("home/.config/focusdebt/config.toml")
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let home = self.root.join("home");
+        Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.data())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed:\n{}", args, describe(&output));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Three days of demo data and a changed config
+    fn seeded(name: &str) -> Self {
+        let sandbox = Self::new(name);
+        sandbox.run_ok(&["demo", "generate", "--days", "3", "--seed", "1"]);
+        sandbox.run_ok(&["config", "set", "retention_days", "90"]);
+        assert!(sandbox.data().join("focusdebt.db").exists());
+        sandbox
+    }
+
+    fn backups(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(self.data().join("exports")) else { return Vec::new() };
+        entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("focusdebt-reset-"))
+            .collect()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn describe(output: &Output) -> String {
+    format!("{}\n--- stdout\n{}--- stderr\n{}", output.status,
+        String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+}
+
+fn has_retention(config_file: &Path) -> bool {
+    fs::read_to_string(config_file).unwrap_or_default().contains("retention_days = 90")
+}
+
+#[test]
+fn reset_backs_up_then_clears_everything() {
+    let sandbox = Sandbox::seeded("clean");
+    let stdout = sandbox.run_ok(&["reset", "--all", "--yes"]);
+    assert!(stdout.contains("~=~ Reset complete"), "{}", stdout);
+
+    assert!(!sandbox.data().join("focusdebt.db").exists());
+    assert!(!has_retention(&sandbox.config_file()));
+    let backups = sandbox.backups();
+    assert_eq!(backups.len(), 1);
+    let backup: serde_json::Value = serde_json::from_str(&fs::read_to_string(&backups[0]).unwrap()).unwrap();
+    assert_eq!(backup["config"]["retention_days"], 90);
+    assert!(!backup["databases"]["default"]["focus_sessions"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn failed_backup_deletes_nothing() {
+    let sandbox = Sandbox::seeded("backup");
+    // The export directory can't be created
+    fs::write(sandbox.data().join("exports"), "in the way").unwrap();
+
+    let output = sandbox.run(&["reset", "--all", "--yes"]);
+    assert!(!output.status.success(), "{}", describe(&output));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Nothing was deleted"), "{}", describe(&output));
+    assert!(sandbox.data().join("focusdebt.db").exists());
+    assert!(has_retention(&sandbox.config_file()));
+    sandbox.run_ok(&["stats"]);
+}
+
+#[test]
+fn failed_delete_step_leaves_a_backup_and_a_rerun_finishes() {
+    let sandbox = Sandbox::seeded("midway");
+    // The stats cache can't be removed, so step 3 fails
+    let cache = sandbox.data().join("daily_stats_cache.json");
+    let _ = fs::remove_file(&cache);
+    fs::create_dir_all(cache.join("in-the-way")).unwrap();
+
+    let output = sandbox.run(&["reset", "--all", "--yes"]);
+    assert!(!output.status.success(), "{}", describe(&output));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 step failed"), "{}", describe(&output));
+    assert!(stderr.contains("reset --all --no-backup"), "{}", describe(&output));
+
+    // The later steps still ran, and the backup has everything
+    assert!(!sandbox.data().join("focusdebt.db").exists());
+    assert!(!has_retention(&sandbox.config_file()));
+    let backups = sandbox.backups();
+    assert_eq!(backups.len(), 1);
+    let backup: serde_json::Value = serde_json::from_str(&fs::read_to_string(&backups[0]).unwrap()).unwrap();
+    assert_eq!(backup["config"]["retention_days"], 90);
+    assert!(!backup["databases"]["default"]["focus_sessions"].as_array().unwrap().is_empty());
+
+    // Rerunning before the problem is fixed fails the same way, harmlessly
+    let output = sandbox.run(&["reset", "--all", "--no-backup", "--yes"]);
+    assert!(!output.status.success(), "{}", describe(&output));
+
+    fs::remove_dir_all(&cache).unwrap();
+    let stdout = sandbox.run_ok(&["reset", "--all", "--no-backup", "--yes"]);
+    assert!(stdout.contains("~=~ Reset complete"), "{}", stdout);
+    assert!(!cache.exists());
+    assert_eq!(sandbox.backups(), backups);
+    sandbox.run_ok(&["stats"]);
+}
@@ -0,0 +1,87 @@
+// `start` run as a user would, with a daemon that can't come up: the
+// failure has to reach the terminal start was run from.
+#![cfg(unix)]
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("focusdebt-start-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        fs::create_dir_all(root.join("data")).unwrap();
+        // Detection finds no window
+        let xdotool = root.join("bin/xdotool");
+        fs::write(&xdotool, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&xdotool, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn data(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    /// `start`, naming the session at the prompt
+    fn start(&self) -> Output {
+        let home = self.root.join("home");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_focusdebt"))
+            .env_clear()
+            .env("PATH", format!("{}:/usr/bin:/bin", self.root.join("bin").display()))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .arg("--data-dir")
+            .arg(self.data())
+            .arg("start")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"fd-startup\n").unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    /// That start failed with `reason` from the daemon and left nothing running
+    fn assert_failed(&self, output: &Output, reason: &str) {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let context = format!("{}\n--- stdout\n{}--- stderr\n{}", output.status, stdout, stderr);
+        assert_eq!(output.status.code(), Some(1), "{}", context);
+        assert!(stderr.contains(&format!("❌ Daemon failed to start: {}", reason)), "{}", context);
+        assert!(stderr.contains(&format!("(daemon log: {})", self.data().join("focusdebt.log").display())), "{}", context);
+        assert!(!stdout.contains("started successfully"), "{}", context);
+        assert!(!self.data().join("focusdebt.pid").exists(), "{}", context);
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[test]
+fn a_daemon_that_sees_no_windows_fails_start() {
+    let sandbox = Sandbox::new("detection");
+    let output = sandbox.start();
+    sandbox.assert_failed(&output, "no window detection backend found a window");
+}
+
+#[test]
+fn a_database_the_daemon_cant_open_fails_start() {
+    let sandbox = Sandbox::new("database");
+    // Something that isn't a database where the database goes
+    fs::create_dir_all(sandbox.data().join("focusdebt.db/in-the-way")).unwrap();
+    let output = sandbox.start();
+    sandbox.assert_failed(&output, "failed to claim the session name: unable to open database file");
+}